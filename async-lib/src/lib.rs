@@ -2,3 +2,4 @@ pub(crate) mod arc;
 pub(crate) mod shared_awake_token;
 pub mod awake_token;
 pub mod once_watch;
+pub mod poll_budget;
@@ -0,0 +1,106 @@
+use std::{sync::atomic::{AtomicU64, Ordering}, task::Context};
+
+/// The number of times a poll loop may go around in a single call to `poll()` before it yields.
+/// This matches the budget tokio gives each task before its own resources start returning
+/// `Pending`.
+pub const DEFAULT_POLL_BUDGET: u32 = 128;
+
+/// Counts how hard a hand-written poll loop is working. One of these is kept (usually as a
+/// `static`) for each loop so that a loop that keeps spinning without making progress shows up
+/// in the yield count instead of quietly starving the other tasks on its worker thread.
+#[derive(Debug)]
+pub struct PollLoopStats {
+    name: &'static str,
+    polls: AtomicU64,
+    iterations: AtomicU64,
+    yields: AtomicU64,
+    max_iterations: AtomicU64,
+}
+
+/// A point-in-time view of a `PollLoopStats`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct PollLoopSnapshot {
+    pub name: &'static str,
+    /// The number of calls to `poll()`.
+    pub polls: u64,
+    /// The number of times the loop went around, across every poll.
+    pub iterations: u64,
+    /// The number of polls that used up their budget and yielded. A loop that yields often is
+    /// either doing a lot of work per poll or spinning on a state that does not make progress.
+    pub yields: u64,
+    /// The most times the loop went around in a single poll.
+    pub max_iterations: u64,
+}
+
+impl PollLoopStats {
+    #[inline]
+    pub const fn new(name: &'static str) -> Self {
+        Self {
+            name,
+            polls: AtomicU64::new(0),
+            iterations: AtomicU64::new(0),
+            yields: AtomicU64::new(0),
+            max_iterations: AtomicU64::new(0),
+        }
+    }
+
+    #[inline]
+    pub fn name(&self) -> &'static str { self.name }
+
+    #[inline]
+    pub fn snapshot(&self) -> PollLoopSnapshot {
+        PollLoopSnapshot {
+            name: self.name,
+            polls: self.polls.load(Ordering::Relaxed),
+            iterations: self.iterations.load(Ordering::Relaxed),
+            yields: self.yields.load(Ordering::Relaxed),
+            max_iterations: self.max_iterations.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// Limits the number of times a poll loop can go around before it gives the executor a chance to
+/// run something else. Create one at the start of `poll()` and call `exhausted()` at the top of
+/// each iteration, where the state machine is in a consistent state. If it returns true, return
+/// `Poll::Pending`. The task has already been woken so it will be polled again right away.
+#[derive(Debug)]
+pub struct PollBudget<'a> {
+    stats: &'a PollLoopStats,
+    iterations: u32,
+    budget: u32,
+}
+
+impl<'a> PollBudget<'a> {
+    #[inline]
+    pub fn new(stats: &'a PollLoopStats) -> Self {
+        Self::with_budget(stats, DEFAULT_POLL_BUDGET)
+    }
+
+    #[inline]
+    pub fn with_budget(stats: &'a PollLoopStats, budget: u32) -> Self {
+        stats.polls.fetch_add(1, Ordering::Relaxed);
+        Self { stats, iterations: 0, budget }
+    }
+
+    /// Counts an iteration of the loop. Returns true, after waking the task, if the loop has used
+    /// up its budget and should return `Poll::Pending`.
+    #[inline]
+    pub fn exhausted(&mut self, cx: &mut Context<'_>) -> bool {
+        if self.iterations >= self.budget {
+            self.stats.yields.fetch_add(1, Ordering::Relaxed);
+            cx.waker().wake_by_ref();
+            return true;
+        }
+        self.iterations += 1;
+        false
+    }
+}
+
+impl<'a> Drop for PollBudget<'a> {
+    #[inline]
+    fn drop(&mut self) {
+        let iterations = u64::from(self.iterations);
+        self.stats.iterations.fetch_add(iterations, Ordering::Relaxed);
+        self.stats.max_iterations.fetch_max(iterations, Ordering::Relaxed);
+    }
+}
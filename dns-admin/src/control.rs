@@ -0,0 +1,230 @@
+use std::{error::Error, fmt::Display, path::PathBuf, sync::Arc, time::Duration};
+
+use dns_client::{health::DEFAULT_LIVENESS_WINDOW, policy::{PolicyMiddleware, PolicyRule, PolicyRules}, DNSAsyncClient};
+use dns_lib::{interface::cache::main_cache::AsyncMainCache, query::question::Question, resource_record::{rclass::RClass, rtype::RType}, types::c_domain_name::CDomainName};
+use log::info;
+
+use crate::json::JsonObject;
+
+/// An operation that can be performed on a running client. Commands are independent of how they
+/// are delivered, so every transport (such as the HTTP server in `http`) shares the same
+/// `ControlHandler`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ControlCommand {
+    /// Report the client's counters and resource usage.
+    Stats,
+    /// Report the client's readiness and liveness.
+    Health,
+    /// Remove records from the client's cache so that the next query for them goes to the
+    /// network. An `rtype` of ANY removes all of the name's records. If `subtree` is set, every
+    /// record at or below the name is removed, whatever its type.
+    Invalidate { name: CDomainName, rclass: RClass, rtype: RType, subtree: bool },
+    /// Reload the policy rules from their control name, bypassing the cached copy.
+    ReloadPolicy,
+    /// Replace the policy rules with these, one rule per line. Blank lines and lines starting with
+    /// `#` are skipped. If any line is not a valid rule, none of them are applied. A running
+    /// `PolicyWatcher` replaces these the next time it refreshes the rules.
+    SetPolicyRules(String),
+    /// Reload the configuration that the client reads from files. This is the hosts file, which
+    /// replaces the hosts file entries the client answers locally.
+    ReloadConfig,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum ControlError {
+    /// The command could not be understood.
+    BadRequest(String),
+    /// The command needs something that this client does not have, such as a policy middleware or
+    /// a cache that supports removing records.
+    Unsupported(&'static str),
+    /// The command was understood but could not be completed.
+    Failed(String),
+}
+impl Error for ControlError {}
+impl Display for ControlError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::BadRequest(reason) => write!(f, "Bad request: {reason}"),
+            Self::Unsupported(reason) => write!(f, "Not supported: {reason}"),
+            Self::Failed(reason) => write!(f, "Failed: {reason}"),
+        }
+    }
+}
+
+/// Carries out control commands against a client. Every command's result is a JSON object.
+pub struct ControlHandler {
+    client: Arc<DNSAsyncClient>,
+    policy: Option<(PolicyMiddleware, RClass)>,
+    hosts_file: Option<PathBuf>,
+    liveness_window: Duration,
+}
+
+impl ControlHandler {
+    #[inline]
+    pub fn new(client: Arc<DNSAsyncClient>) -> Self {
+        Self { client, policy: None, hosts_file: None, liveness_window: DEFAULT_LIVENESS_WINDOW }
+    }
+
+    /// Allows the policy commands to change the rules of this middleware. It should be a clone of
+    /// the one in the client's chain. Its rules are reloaded from its control name in `rclass`.
+    #[inline]
+    pub fn with_policy(mut self, policy: PolicyMiddleware, rclass: RClass) -> Self {
+        self.policy = Some((policy, rclass));
+        self
+    }
+
+    /// Allows the config reload command to reload the client's hosts file entries from this file.
+    #[inline]
+    pub fn with_hosts_file(mut self, hosts_file: impl Into<PathBuf>) -> Self {
+        self.hosts_file = Some(hosts_file.into());
+        self
+    }
+
+    /// The window that the health command uses to decide whether the client is live.
+    #[inline]
+    pub fn with_liveness_window(mut self, liveness_window: Duration) -> Self {
+        self.liveness_window = liveness_window;
+        self
+    }
+
+    pub async fn execute(&self, command: ControlCommand) -> Result<String, ControlError> {
+        match command {
+            ControlCommand::Stats => Ok(self.stats().await),
+            ControlCommand::Health => Ok(self.health()),
+            ControlCommand::Invalidate { name, rclass, rtype, subtree } => self.invalidate(name, rclass, rtype, subtree).await,
+            ControlCommand::ReloadPolicy => self.reload_policy().await,
+            ControlCommand::SetPolicyRules(text) => self.set_policy_rules(&text),
+            ControlCommand::ReloadConfig => self.reload_config().await,
+        }
+    }
+
+    async fn stats(&self) -> String {
+        let client_stats = self.client.stats().await;
+        let sockets = client_stats.sockets;
+        let cache = match client_stats.cache {
+            Some(cache) => JsonObject::new()
+                .value("nodes", cache.nodes)
+                .value("record_sets", cache.record_sets)
+                .value("records", cache.records)
+                .value("expired_records", cache.expired_records)
+                .value("estimated_bytes", cache.estimated_bytes)
+                .value("evicted_records", cache.evicted_records),
+            None => JsonObject::new(),
+        };
+        let validation = self.client.validation_stats();
+        let consistency = self.client.consistency_stats();
+        let hedging = self.client.hedging_stats();
+        let poisoning = self.client.poisoning_stats();
+        JsonObject::new()
+            .object("sockets", JsonObject::new()
+                .value("sockets", sockets.sockets)
+                .value("in_flight_queries", sockets.in_flight_queries)
+                .value("running_query_tasks", sockets.running_query_tasks)
+                .value("tls_connections", sockets.tls_connections)
+                .value("remembered_peers", sockets.remembered_peers))
+            .object("cache", cache)
+            .object("validation", JsonObject::new()
+                .value("not_a_response", validation.not_a_response)
+                .value("opcode_mismatch", validation.opcode_mismatch)
+                .value("question_mismatch", validation.question_mismatch)
+                .value("incoherent_flags", validation.incoherent_flags)
+                .value("unrelated_answers", validation.unrelated_answers)
+                .value("compact_denials", validation.compact_denials)
+                .value("compact_nxdomains", validation.compact_nxdomains)
+                .value("too_many_records", validation.too_many_records)
+                .value("too_large", validation.too_large)
+                .value("unauthenticated", validation.unauthenticated))
+            .object("consistency", JsonObject::new()
+                .value("duplicate_records", consistency.duplicate_records)
+                .value("conflicting_cnames", consistency.conflicting_cnames)
+                .value("conflicting_dnames", consistency.conflicting_dnames)
+                .value("inconsistent_synthesized_cnames", consistency.inconsistent_synthesized_cnames))
+            .object("hedging", JsonObject::new()
+                .value("hedged", hedging.hedged)
+                .value("hedge_answered_first", hedging.hedge_answered_first)
+                .value("primary_answered_first", hedging.primary_answered_first))
+            .object("poisoning", JsonObject::new()
+                .value("conflicts", poisoning.conflicts)
+                .value("confirmed", poisoning.confirmed)
+                .value("rejected", poisoning.rejected)
+                .value("quarantined", poisoning.quarantined))
+            .finish()
+    }
+
+    fn health(&self) -> String {
+        let report = self.client.health(self.liveness_window);
+        JsonObject::new()
+            .value("ready", report.ready())
+            .value("live", report.live)
+            .value("primed", report.primed)
+            .value("cache_loaded", report.cache_loaded)
+            .optional("seconds_since_upstream_success", report.since_upstream_success.map(|since| since.as_secs()))
+            .finish()
+    }
+
+    async fn invalidate(&self, name: CDomainName, rclass: RClass, rtype: RType, subtree: bool) -> Result<String, ControlError> {
+        if !name.is_fully_qualified() {
+            return Err(ControlError::BadRequest(format!("the name '{name}' must be fully qualified")));
+        }
+        let cache = self.client.cache();
+        let removed = if subtree {
+            cache.remove_subtree(&name, rclass).await
+        } else {
+            cache.remove(&Question::new(name.clone(), rtype, rclass)).await
+        };
+        let Some(removed) = removed else {
+            return Err(ControlError::Unsupported("the cache does not support removing records"));
+        };
+        info!("Invalidated {removed} cached records for '{name}'");
+        Ok(JsonObject::new().value("removed", removed).finish())
+    }
+
+    async fn reload_policy(&self) -> Result<String, ControlError> {
+        let Some((policy, rclass)) = &self.policy else {
+            return Err(ControlError::Unsupported("no policy middleware is configured"));
+        };
+        // The rules are read through the cache, which would otherwise answer with the copy it
+        // already has until the TTL runs out.
+        let _ = self.client.cache().remove(&Question::new(policy.control_name().clone(), RType::TXT, *rclass)).await;
+        match policy.refresh(self.client.clone(), *rclass).await {
+            Ok(rule_count) => {
+                info!("Reloaded {rule_count} policy rules from '{}'", policy.control_name());
+                Ok(JsonObject::new().value("rules", rule_count).finish())
+            },
+            Err(error) => Err(ControlError::Failed(format!("reloading the policy rules from '{}' failed: {error}", policy.control_name()))),
+        }
+    }
+
+    fn set_policy_rules(&self, text: &str) -> Result<String, ControlError> {
+        let Some((policy, _)) = &self.policy else {
+            return Err(ControlError::Unsupported("no policy middleware is configured"));
+        };
+        let mut rules = PolicyRules::new();
+        let lines = text.lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'));
+        for line in lines {
+            match PolicyRule::from_text(line) {
+                Ok(rule) => rules.insert(rule),
+                Err(error) => return Err(ControlError::BadRequest(format!("invalid rule '{line}': {error}"))),
+            }
+        }
+        let rule_count = rules.len();
+        policy.set_rules(rules);
+        info!("Replaced the policy rules with {rule_count} rules");
+        Ok(JsonObject::new().value("rules", rule_count).finish())
+    }
+
+    async fn reload_config(&self) -> Result<String, ControlError> {
+        let Some(hosts_file) = &self.hosts_file else {
+            return Err(ControlError::Unsupported("no hosts file is configured"));
+        };
+        match self.client.local_data().load_hosts_file(hosts_file).await {
+            Ok(record_count) => {
+                info!("Reloaded {record_count} records from the hosts file '{}'", hosts_file.display());
+                Ok(JsonObject::new().value("records", record_count).finish())
+            },
+            Err(error) => Err(ControlError::Failed(format!("reloading the hosts file '{}' failed: {error}", hosts_file.display()))),
+        }
+    }
+}
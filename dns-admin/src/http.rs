@@ -0,0 +1,407 @@
+use std::{io, net::SocketAddr, sync::Arc, time::Duration};
+
+use dns_lib::{resource_record::{rclass::RClass, rtype::RType}, types::c_domain_name::CDomainName};
+use log::{debug, warn};
+use tokio::{io::{AsyncReadExt, AsyncWriteExt}, net::{TcpListener, TcpStream}, sync::Semaphore, task::JoinHandle, time::{timeout_at, Instant}};
+
+use crate::{control::{ControlCommand, ControlError, ControlHandler}, json::JsonObject};
+
+/// Requests with a larger header than this are dropped.
+const MAX_HEADER_SIZE: usize = 8 * 1024;
+/// Requests with a larger body than this are rejected. Large enough for a long list of policy
+/// rules.
+const MAX_BODY_SIZE: usize = 4 * 1024 * 1024;
+/// Requests that take longer than this to arrive are dropped.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+/// The number of connections served at once by default. The API is meant for a handful of
+/// management tools, not for heavy traffic.
+const DEFAULT_MAX_CONNECTIONS: usize = 16;
+
+/// Where the admin API listens and the token that every request must present.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct AdminConfig {
+    pub address: SocketAddr,
+    /// Requests must have an `Authorization: Bearer <token>` header with this token.
+    pub token: String,
+    /// Connections accepted while this many are already being served are closed right away.
+    pub max_connections: usize,
+}
+
+impl AdminConfig {
+    #[inline]
+    pub fn new(address: SocketAddr, token: String) -> Self {
+        Self { address, token, max_connections: DEFAULT_MAX_CONNECTIONS }
+    }
+}
+
+/// A running admin API. The API stops when this is dropped.
+///
+/// Every request must be authorized with the configured token. The endpoints are:
+///
+/// - `GET /v1/stats` reports the client's counters.
+/// - `GET /v1/health` reports its readiness and liveness.
+/// - `POST /v1/cache/invalidate?name=<name>[&type=<type>][&class=<class>][&subtree=true]` removes
+///   cached records.
+/// - `POST /v1/policy/reload` reloads the policy rules from their control name.
+/// - `PUT /v1/policy/rules` replaces the policy rules with the ones in the body, one per line.
+/// - `POST /v1/config/reload` reloads the configuration that the client reads from files.
+///
+/// Responses are JSON objects. Errors have an `error` field. The token is checked as soon as the
+/// request's header has arrived, so the body of an unauthorized request is never read.
+pub struct AdminServer {
+    local_address: SocketAddr,
+    task: JoinHandle<()>,
+}
+
+impl AdminServer {
+    /// Starts serving the API. Fails if the token is empty, since that would let anyone in.
+    pub async fn bind(config: AdminConfig, handler: ControlHandler) -> io::Result<Self> {
+        if config.token.is_empty() {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "the admin API token must not be empty"));
+        }
+        let listener = TcpListener::bind(config.address).await?;
+        let local_address = listener.local_addr()?;
+        let handler = Arc::new(handler);
+        let token = Arc::new(config.token);
+        let connections = Arc::new(Semaphore::new(config.max_connections));
+        let task = tokio::spawn(async move {
+            loop {
+                match listener.accept().await {
+                    Ok((stream, peer)) => {
+                        let Ok(permit) = connections.clone().try_acquire_owned() else {
+                            debug!("Admin API on {local_address} is serving too many connections, closing the connection from {peer}");
+                            drop(stream);
+                            continue;
+                        };
+                        let handler = handler.clone();
+                        let token = token.clone();
+                        tokio::spawn(async move {
+                            if let Err(error) = respond(stream, &handler, &token).await {
+                                debug!("Admin request from {peer} failed: {error}");
+                            }
+                            drop(permit);
+                        });
+                    },
+                    Err(error) => warn!("Admin API on {local_address} failed to accept a connection: {error}"),
+                }
+            }
+        });
+        Ok(Self { local_address, task })
+    }
+
+    /// The address that the API is listening on. Useful if it was bound to port 0.
+    #[inline]
+    pub fn local_address(&self) -> SocketAddr { self.local_address }
+}
+
+impl Drop for AdminServer {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+/// The parts of an HTTP request that the API uses.
+#[derive(Debug)]
+struct Request {
+    method: String,
+    path: String,
+    query: String,
+    authorization: Option<String>,
+    body: Vec<u8>,
+}
+
+/// Reads the request up to the end of its header. The body is left for `read_body()`, except for
+/// any part of it that arrived with the header, which is returned in the request's `body`. Returns
+/// the length of the body given by the header.
+async fn read_head(stream: &mut TcpStream) -> io::Result<(Request, usize)> {
+    let mut request = Vec::with_capacity(1024);
+    let mut buffer = [0; 4096];
+    let header_end = loop {
+        if let Some(position) = request.windows(4).position(|window| window == b"\r\n\r\n") {
+            break position + 4;
+        }
+        if request.len() > MAX_HEADER_SIZE {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "request header too large"));
+        }
+        let read = stream.read(&mut buffer).await?;
+        if read == 0 {
+            return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "connection closed before the request was complete"));
+        }
+        request.extend_from_slice(&buffer[..read]);
+    };
+
+    let header = String::from_utf8_lossy(&request[..header_end]).into_owned();
+    let mut lines = header.split("\r\n");
+    let mut request_line = lines.next().unwrap_or_default().split(' ');
+    let method = request_line.next().unwrap_or_default().to_string();
+    let target = request_line.next().unwrap_or_default();
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+
+    let mut authorization = None;
+    let mut content_length = 0;
+    for line in lines {
+        let Some((name, value)) = line.split_once(':') else { continue };
+        let value = value.trim();
+        if name.eq_ignore_ascii_case("authorization") {
+            authorization = Some(value.to_string());
+        } else if name.eq_ignore_ascii_case("content-length") {
+            content_length = value.parse().map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "invalid content length"))?;
+        }
+    }
+    if content_length > MAX_BODY_SIZE {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "request body too large"));
+    }
+
+    let body = request.split_off(header_end);
+    Ok((Request { method, path: path.to_string(), query: query.to_string(), authorization, body }, content_length))
+}
+
+/// Reads the rest of the request's body, which is `content_length` bytes long.
+async fn read_body(stream: &mut TcpStream, request: &mut Request, content_length: usize) -> io::Result<()> {
+    if request.body.len() < content_length {
+        let already_read = request.body.len();
+        request.body.resize(content_length, 0);
+        stream.read_exact(&mut request.body[already_read..]).await?;
+    }
+    request.body.truncate(content_length);
+    Ok(())
+}
+
+async fn respond(mut stream: TcpStream, handler: &ControlHandler, token: &str) -> io::Result<()> {
+    let deadline = Instant::now() + REQUEST_TIMEOUT;
+    let (mut request, content_length) = match timeout_at(deadline, read_head(&mut stream)).await {
+        Ok(request) => request?,
+        Err(_) => return Err(io::Error::new(io::ErrorKind::TimedOut, "request timed out")),
+    };
+
+    let (status, body) = if !is_authorized(request.authorization.as_deref(), token) {
+        ("401 Unauthorized", error_body("missing or incorrect token"))
+    } else {
+        match timeout_at(deadline, read_body(&mut stream, &mut request, content_length)).await {
+            Ok(result) => result?,
+            Err(_) => return Err(io::Error::new(io::ErrorKind::TimedOut, "request timed out")),
+        }
+        match route(&request) {
+            Ok(command) => match handler.execute(command).await {
+                Ok(body) => ("200 OK", body),
+                Err(error) => (error_status(&error), error_body(&error.to_string())),
+            },
+            Err((status, reason)) => (status, error_body(&reason)),
+        }
+    };
+    let response = format!(
+        "HTTP/1.1 {status}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nCache-Control: no-store\r\nConnection: close\r\n\r\n{body}",
+        body.len()
+    );
+    match timeout_at(Instant::now() + REQUEST_TIMEOUT, stream.write_all(response.as_bytes())).await {
+        Ok(result) => result?,
+        Err(_) => return Err(io::Error::new(io::ErrorKind::TimedOut, "response timed out")),
+    }
+    stream.shutdown().await
+}
+
+/// Compares the whole token, whatever the position of the first difference, so that the time
+/// taken does not reveal how much of a guess was correct.
+fn is_authorized(authorization: Option<&str>, token: &str) -> bool {
+    let Some(presented) = authorization.and_then(|authorization| authorization.strip_prefix("Bearer ")) else {
+        return false;
+    };
+    let (presented, token) = (presented.trim().as_bytes(), token.as_bytes());
+    let difference = presented.iter()
+        .zip(token)
+        .fold(0, |difference, (presented, token)| difference | (presented ^ token));
+    (difference == 0) && (presented.len() == token.len())
+}
+
+fn route(request: &Request) -> Result<ControlCommand, (&'static str, String)> {
+    match (request.method.as_str(), request.path.as_str()) {
+        ("GET", "/v1/stats") => Ok(ControlCommand::Stats),
+        ("GET", "/v1/health") => Ok(ControlCommand::Health),
+        ("POST", "/v1/cache/invalidate") => invalidate_command(&request.query).map_err(|reason| ("400 Bad Request", reason)),
+        ("POST", "/v1/policy/reload") => Ok(ControlCommand::ReloadPolicy),
+        ("PUT", "/v1/policy/rules") => match String::from_utf8(request.body.clone()) {
+            Ok(text) => Ok(ControlCommand::SetPolicyRules(text)),
+            Err(_) => Err(("400 Bad Request", "the rules must be UTF-8 text".to_string())),
+        },
+        ("POST", "/v1/config/reload") => Ok(ControlCommand::ReloadConfig),
+        (_, "/v1/stats" | "/v1/health" | "/v1/cache/invalidate" | "/v1/policy/reload" | "/v1/policy/rules" | "/v1/config/reload") => Err(("405 Method Not Allowed", format!("'{}' is not allowed on '{}'", request.method, request.path))),
+        _ => Err(("404 Not Found", format!("'{}' does not exist", request.path))),
+    }
+}
+
+fn invalidate_command(query: &str) -> Result<ControlCommand, String> {
+    let mut name = None;
+    let mut rtype = RType::ANY;
+    let mut rclass = RClass::Internet;
+    let mut subtree = false;
+    for parameter in query.split('&').filter(|parameter| !parameter.is_empty()) {
+        let (key, value) = parameter.split_once('=').unwrap_or((parameter, ""));
+        match key {
+            "name" => name = Some(CDomainName::from_utf8(value).map_err(|error| format!("invalid name '{value}': {error}"))?),
+            "type" => rtype = RType::from_str(value).map_err(|error| format!("invalid type '{value}': {error}"))?,
+            "class" => rclass = RClass::from_str(value).map_err(|error| format!("invalid class '{value}': {error}"))?,
+            "subtree" => subtree = match value {
+                "true" => true,
+                "false" => false,
+                _ => return Err(format!("invalid subtree '{value}': expected 'true' or 'false'")),
+            },
+            _ => return Err(format!("unknown parameter '{key}'")),
+        }
+    }
+    let Some(name) = name else {
+        return Err("the name parameter is required".to_string());
+    };
+    Ok(ControlCommand::Invalidate { name, rclass, rtype, subtree })
+}
+
+#[inline]
+fn error_status(error: &ControlError) -> &'static str {
+    match error {
+        ControlError::BadRequest(_) => "400 Bad Request",
+        ControlError::Unsupported(_) => "501 Not Implemented",
+        ControlError::Failed(_) => "502 Bad Gateway",
+    }
+}
+
+#[inline]
+fn error_body(reason: &str) -> String {
+    JsonObject::new().string("error", reason).finish()
+}
+
+#[cfg(test)]
+mod http_tests {
+    use std::{net::{Ipv4Addr, SocketAddr}, sync::Arc, time::{Duration, Instant}};
+
+    use dns_cache::asynchronous::async_main_cache::AsyncMainTreeCache;
+    use dns_client::{policy::PolicyMiddleware, DNSAsyncClient};
+    use dns_lib::{interface::cache::{main_cache::AsyncMainCache, CacheMeta, CacheQuery, CacheRecord, CacheResponse, MetaAuth}, query::question::Question, resource_record::{rclass::RClass, resource_record::{RecordData, ResourceRecord}, rtype::RType, time::Time, types::a::A}, types::c_domain_name::CDomainName};
+    use tokio::{io::{AsyncReadExt, AsyncWriteExt}, net::TcpStream};
+
+    use crate::control::ControlHandler;
+
+    use super::{AdminConfig, AdminServer};
+
+    const TOKEN: &str = "secret";
+
+    async fn request(server: &AdminServer, method: &str, target: &str, token: &str, body: &str) -> (String, String) {
+        let mut stream = TcpStream::connect(server.local_address()).await.unwrap();
+        let request = format!("{method} {target} HTTP/1.1\r\nHost: localhost\r\nAuthorization: Bearer {token}\r\nContent-Length: {}\r\n\r\n{body}", body.len());
+        stream.write_all(request.as_bytes()).await.unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+        let (head, body) = response.split_once("\r\n\r\n").unwrap();
+        let status = head.lines().next().unwrap().split_once(' ').unwrap().1.to_string();
+        (status, body.to_string())
+    }
+
+    async fn server() -> (AdminServer, Arc<AsyncMainTreeCache>, PolicyMiddleware) {
+        let cache = Arc::new(AsyncMainTreeCache::new());
+        let client = Arc::new(DNSAsyncClient::new(cache.clone()).await);
+        let policy = PolicyMiddleware::new(CDomainName::from_utf8("policy.internal.").unwrap());
+        let handler = ControlHandler::new(client).with_policy(policy.clone(), RClass::Internet);
+        let server = AdminServer::bind(AdminConfig::new(SocketAddr::new(Ipv4Addr::LOCALHOST.into(), 0), TOKEN.to_string()), handler).await.unwrap();
+        (server, cache, policy)
+    }
+
+    #[tokio::test]
+    async fn requests_need_the_token() {
+        let (server, _, _) = server().await;
+        let (status, body) = request(&server, "GET", "/v1/stats", "wrong", "").await;
+        assert_eq!(status, "401 Unauthorized");
+        assert!(body.contains("\"error\""));
+
+        let (status, body) = request(&server, "GET", "/v1/stats", TOKEN, "").await;
+        assert_eq!(status, "200 OK");
+        assert!(body.starts_with("{\"sockets\":"));
+    }
+
+    #[tokio::test]
+    async fn invalidate_removes_cached_records() {
+        let (server, cache, _) = server().await;
+        let name = CDomainName::from_utf8("www.example.com.").unwrap();
+        let record = ResourceRecord::new(name.clone(), RClass::Internet, Time::from_secs(300), RecordData::A(A::new(Ipv4Addr::new(192, 0, 2, 1))));
+        cache.insert_record(CacheRecord { meta: CacheMeta { auth: MetaAuth::NotAuthoritative, insertion_time: Instant::now(), original_ttl: None }, record }).await;
+
+        let (status, body) = request(&server, "POST", "/v1/cache/invalidate?name=example.com.&subtree=true", TOKEN, "").await;
+        assert_eq!(status, "200 OK");
+        assert_eq!(body, "{\"removed\":1}");
+        let question = Question::new(name, RType::A, RClass::Internet);
+        assert!(matches!(cache.get(&CacheQuery { authoritative: false, question: &question }).await, CacheResponse::Records(records) if records.is_empty()));
+
+        let (status, _) = request(&server, "POST", "/v1/cache/invalidate?type=A", TOKEN, "").await;
+        assert_eq!(status, "400 Bad Request");
+    }
+
+    #[tokio::test]
+    async fn policy_rules_are_replaced_atomically() {
+        let (server, _, policy) = server().await;
+        let (status, body) = request(&server, "PUT", "/v1/policy/rules", TOKEN, "# comment\nblock ads.example.\n\nallow *.example.org.\n").await;
+        assert_eq!(status, "200 OK", "{body}");
+        assert_eq!(body, "{\"rules\":2}");
+        assert_eq!(policy.rules().len(), 2);
+
+        let (status, _) = request(&server, "PUT", "/v1/policy/rules", TOKEN, "block other.example.\nnonsense\n").await;
+        assert_eq!(status, "400 Bad Request");
+        assert_eq!(policy.rules().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn unauthorized_bodies_are_not_read() {
+        let (server, _, _) = server().await;
+        let mut stream = TcpStream::connect(server.local_address()).await.unwrap();
+        let request = format!("PUT /v1/policy/rules HTTP/1.1\r\nHost: localhost\r\nAuthorization: Bearer wrong\r\nContent-Length: {}\r\n\r\n", 1024 * 1024);
+        stream.write_all(request.as_bytes()).await.unwrap();
+        let mut response = String::new();
+        tokio::time::timeout(Duration::from_secs(1), stream.read_to_string(&mut response)).await.unwrap().unwrap();
+        assert!(response.starts_with("HTTP/1.1 401 Unauthorized\r\n"), "{response}");
+    }
+
+    #[tokio::test]
+    async fn connections_are_capped() {
+        let cache = Arc::new(AsyncMainTreeCache::new());
+        let client = Arc::new(DNSAsyncClient::new(cache).await);
+        let mut config = AdminConfig::new(SocketAddr::new(Ipv4Addr::LOCALHOST.into(), 0), TOKEN.to_string());
+        config.max_connections = 1;
+        let server = AdminServer::bind(config, ControlHandler::new(client)).await.unwrap();
+
+        // Holds the only connection open without finishing the request.
+        let mut held = TcpStream::connect(server.local_address()).await.unwrap();
+        held.write_all(b"GET /v1/health HTTP/1.1\r\n").await.unwrap();
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        let mut rejected = TcpStream::connect(server.local_address()).await.unwrap();
+        let mut response = Vec::new();
+        let read = tokio::time::timeout(Duration::from_secs(1), rejected.read_to_end(&mut response)).await.unwrap();
+        assert!(read.is_err() || response.is_empty());
+
+        held.write_all(format!("Authorization: Bearer {TOKEN}\r\n\r\n").as_bytes()).await.unwrap();
+        let mut response = String::new();
+        held.read_to_string(&mut response).await.unwrap();
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"), "{response}");
+
+        let (status, _) = request(&server, "GET", "/v1/health", TOKEN, "").await;
+        assert_eq!(status, "200 OK");
+    }
+
+    #[tokio::test]
+    async fn config_reload_reads_the_hosts_file() {
+        let hosts_file = std::env::temp_dir().join(format!("dns-admin-hosts-{}", std::process::id()));
+        std::fs::write(&hosts_file, "192.0.2.1 one.example\n192.0.2.2 two.example\n").unwrap();
+        let cache = Arc::new(AsyncMainTreeCache::new());
+        let client = Arc::new(DNSAsyncClient::new(cache).await);
+        let handler = ControlHandler::new(client).with_hosts_file(&hosts_file);
+        let reloading = AdminServer::bind(AdminConfig::new(SocketAddr::new(Ipv4Addr::LOCALHOST.into(), 0), TOKEN.to_string()), handler).await.unwrap();
+
+        let (status, body) = request(&reloading, "POST", "/v1/config/reload", TOKEN, "").await;
+        std::fs::remove_file(&hosts_file).unwrap();
+        assert_eq!(status, "200 OK", "{body}");
+        assert_eq!(body, "{\"records\":4}");
+
+        let (status, _) = request(&reloading, "GET", "/v1/config/reload", TOKEN, "").await;
+        assert_eq!(status, "405 Method Not Allowed");
+
+        let (without_hosts, _, _) = server().await;
+        let (status, _) = request(&without_hosts, "POST", "/v1/config/reload", TOKEN, "").await;
+        assert_eq!(status, "501 Not Implemented");
+    }
+}
@@ -0,0 +1,100 @@
+use std::fmt::{Display, Write};
+
+/// Builds a JSON object one field at a time. The admin API only returns flat records of counters,
+/// so this is all that is needed to write them.
+#[derive(Debug, Default)]
+pub(crate) struct JsonObject {
+    text: String,
+}
+
+impl JsonObject {
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn key(&mut self, key: &str) {
+        self.text.push(if self.text.is_empty() { '{' } else { ',' });
+        write_string(&mut self.text, key);
+        self.text.push(':');
+    }
+
+    /// Adds a number or boolean, which are written the same way in JSON as by `Display`.
+    #[inline]
+    pub fn value(mut self, key: &str, value: impl Display) -> Self {
+        self.key(key);
+        let _ = write!(self.text, "{value}");
+        self
+    }
+
+    #[inline]
+    pub fn string(mut self, key: &str, value: &str) -> Self {
+        self.key(key);
+        write_string(&mut self.text, value);
+        self
+    }
+
+    /// Adds the value, or `null` if there is none.
+    #[inline]
+    pub fn optional(mut self, key: &str, value: Option<impl Display>) -> Self {
+        match value {
+            Some(value) => self.value(key, value),
+            None => {
+                self.key(key);
+                self.text.push_str("null");
+                self
+            },
+        }
+    }
+
+    #[inline]
+    pub fn object(mut self, key: &str, value: JsonObject) -> Self {
+        self.key(key);
+        self.text.push_str(&value.finish());
+        self
+    }
+
+    pub fn finish(mut self) -> String {
+        if self.text.is_empty() {
+            self.text.push('{');
+        }
+        self.text.push('}');
+        self.text
+    }
+}
+
+/// Writes the string as a quoted JSON string, escaping the characters that must be.
+fn write_string(out: &mut String, value: &str) {
+    out.push('"');
+    for character in value.chars() {
+        match character {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            character if character.is_control() => {
+                let _ = write!(out, "\\u{:04x}", u32::from(character));
+            },
+            character => out.push(character),
+        }
+    }
+    out.push('"');
+}
+
+#[cfg(test)]
+mod json_tests {
+    use super::JsonObject;
+
+    #[test]
+    fn objects_are_written_in_order() {
+        let json = JsonObject::new()
+            .value("count", 3)
+            .string("name", "say \"hi\"\n")
+            .optional("missing", None::<u64>)
+            .object("nested", JsonObject::new().value("ok", true))
+            .object("empty", JsonObject::new())
+            .finish();
+        assert_eq!(json, r#"{"count":3,"name":"say \"hi\"\n","missing":null,"nested":{"ok":true},"empty":{}}"#);
+    }
+}
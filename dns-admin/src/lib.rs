@@ -0,0 +1,6 @@
+pub mod control;
+pub mod http;
+mod json;
+
+pub use control::{ControlCommand, ControlError, ControlHandler};
+pub use http::{AdminConfig, AdminServer};
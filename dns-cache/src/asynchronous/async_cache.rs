@@ -1,19 +1,17 @@
-use std::sync::Arc;
-
 use async_trait::async_trait;
-use dns_lib::interface::cache::{cache::AsyncCache, main_cache::AsyncMainCache, transaction_cache::AsyncTransactionCache, CacheQuery, CacheRecord, CacheResponse};
+use dns_lib::interface::cache::{cache::AsyncCache, main_cache::{AsyncMainCache, SharedAsyncMainCache}, transaction_cache::AsyncTransactionCache, CacheQuery, CacheRecord, CacheResponse};
 use tokio::join;
 
-use super::{async_main_cache::AsyncMainTreeCache, async_transaction_cache::AsyncTransactionTreeCache};
+use super::async_transaction_cache::AsyncTransactionTreeCache;
 
 pub struct AsyncTreeCache {
-    main_cache: Arc<AsyncMainTreeCache>,
+    main_cache: SharedAsyncMainCache,
     transaction_cache: AsyncTransactionTreeCache
 }
 
 impl AsyncTreeCache {
     #[inline]
-    pub fn new(main_cache: Arc<AsyncMainTreeCache>) -> Self {
+    pub fn new(main_cache: SharedAsyncMainCache) -> Self {
         Self {
             main_cache,
             transaction_cache: AsyncTransactionTreeCache::new(),
@@ -1,18 +1,123 @@
-use std::{collections::{hash_map::Entry, HashSet}, time::Instant};
+use std::{collections::{hash_map::Entry, HashSet}, fmt, io, mem::size_of, path::{Path, PathBuf}, sync::{atomic::{AtomicUsize, Ordering}, Arc}, time::{Duration, Instant, SystemTime}};
 
 use async_trait::async_trait;
-use dns_lib::{interface::cache::{main_cache::AsyncMainCache, CacheQuery, CacheRecord, CacheResponse}, query::question::Question, resource_record::{rcode::RCode, rtype::RType}, types::c_domain_name::CDomainName};
+use dns_lib::{interface::cache::{main_cache::AsyncMainCache, CacheQuery, CacheRecord, CacheResponse}, query::question::Question, resource_record::{rclass::RClass, rcode::RCode, rtype::RType, time::Time}, serde::{presentation::zone_file_writer::{CommentedRecord, ZoneFileWriter}, wire::to_wire::ToWire}, types::{c_domain_name::CDomainName, label::Label}};
+use tokio::{io::AsyncWriteExt, sync::Mutex, task::JoinHandle};
+
+/// The `$TTL` written at the top of zone dumps. Every dumped record has an explicit TTL so this
+/// only matters if records are added to the file by hand.
+const DEFAULT_ZONE_DUMP_TTL: Time = Time::new(3600);
+
+use crate::record_codec::{decode_records, encode_records};
 
 use super::async_tree_cache::{AsyncTreeCache, AsyncTreeCacheError};
 
+/// Written at the start of every snapshot so that some other file is not mistaken for one.
+const SNAPSHOT_MAGIC: &[u8; 4] = b"DNSS";
+
+pub use dns_lib::interface::cache::CacheStats;
+
+/// The estimated memory used by a cached record, as counted by `CacheStats::estimated_bytes`.
+#[inline]
+fn record_size(record: &CacheRecord) -> usize {
+    size_of::<CacheRecord>() + usize::from(record.record.serial_length())
+}
+
+/// Bounds on the size of a cache. Once an insertion takes the cache over either limit, records are
+/// evicted until it is 10% below both of them, so that the cache is not scanned again on every
+/// insertion that follows.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct CacheLimits {
+    /// The most records that the cache holds, or `None` for no limit.
+    pub max_records: Option<usize>,
+    /// The most memory that the records may use, as estimated by `CacheStats::estimated_bytes`, or
+    /// `None` for no limit.
+    pub max_bytes: Option<usize>,
+}
+
+impl CacheLimits {
+    /// No limits. The cache grows until records expire and are cleaned out.
+    #[inline]
+    pub const fn unlimited() -> Self {
+        Self { max_records: None, max_bytes: None }
+    }
+
+    #[inline]
+    fn is_exceeded(&self, records: usize, bytes: usize) -> bool {
+        self.max_records.is_some_and(|max_records| records > max_records)
+            || self.max_bytes.is_some_and(|max_bytes| bytes > max_bytes)
+    }
+
+    /// The limits that eviction brings the cache down to.
+    #[inline]
+    fn low_watermark(&self) -> Self {
+        Self {
+            max_records: self.max_records.map(|max_records| max_records - (max_records / 10)),
+            max_bytes: self.max_bytes.map(|max_bytes| max_bytes - (max_bytes / 10)),
+        }
+    }
+}
+
 pub struct AsyncMainTreeCache {
-    cache: AsyncTreeCache<Vec<CacheRecord>>
+    cache: AsyncTreeCache<Vec<CacheRecord>>,
+    limits: CacheLimits,
+    /// The number of records in the cache, including expired ones that have not been removed yet.
+    records: AtomicUsize,
+    /// The estimated memory used by those records.
+    bytes: AtomicUsize,
+    evicted: AtomicUsize,
+    /// Held while records are being evicted so that concurrent insertions do not all start
+    /// scanning the cache at once.
+    pruning: Mutex<()>,
 }
 
 impl AsyncMainTreeCache {
     #[inline]
     pub fn new() -> Self {
-        Self { cache: AsyncTreeCache::new() }
+        Self::with_limits(CacheLimits::unlimited())
+    }
+
+    /// A cache that evicts records to stay within the limits.
+    #[inline]
+    pub fn with_limits(limits: CacheLimits) -> Self {
+        Self {
+            cache: AsyncTreeCache::new(),
+            limits,
+            records: AtomicUsize::new(0),
+            bytes: AtomicUsize::new(0),
+            evicted: AtomicUsize::new(0),
+            pruning: Mutex::new(()),
+        }
+    }
+
+    #[inline]
+    pub fn limits(&self) -> CacheLimits { self.limits }
+
+    /// The number of records in the cache, including expired ones that have not been removed yet.
+    /// Unlike `stats()`, this does not scan the cache.
+    #[inline]
+    pub fn len(&self) -> usize { self.records.load(Ordering::Relaxed) }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool { self.len() == 0 }
+
+    /// The estimated memory used by the records in the cache. Unlike `stats()`, this does not scan
+    /// the cache.
+    #[inline]
+    pub fn estimated_bytes(&self) -> usize { self.bytes.load(Ordering::Relaxed) }
+
+    #[inline]
+    fn count_added(&self, record: &CacheRecord) {
+        self.records.fetch_add(1, Ordering::Relaxed);
+        self.bytes.fetch_add(record_size(record), Ordering::Relaxed);
+    }
+
+    #[inline]
+    fn count_removed<'a>(&self, records: impl IntoIterator<Item = &'a CacheRecord>) -> usize {
+        let (count, bytes) = records.into_iter().fold((0, 0), |(count, bytes), record| (count + 1, bytes + record_size(record)));
+        let _ = self.records.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |records| Some(records.saturating_sub(count)));
+        let _ = self.bytes.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |total| Some(total.saturating_sub(bytes)));
+        count
     }
 
     #[inline]
@@ -116,16 +221,19 @@ impl AsyncMainTreeCache {
                 //         However, use a reversed order so that the later indexes are not screwed up by removing
                 //         something near the beginning.
                 for index in indexes_to_remove.iter().rev() {
-                    cached_records.remove(*index);
+                    let removed = cached_records.remove(*index);
+                    self.count_removed([&removed]);
                 }
 
                 // Step 3: If no matches were found, we can now add the newest record to the cache.
                 //         Note: This must be done AFTER the expired records are removed to make sure the indexes are accurate.
                 if !record_matched {
+                    self.count_added(&record);
                     cached_records.push(record);
                 }
             },
             Entry::Vacant(entry) => {
+                self.count_added(&record);
                 entry.insert(vec![record]);
             },
         }
@@ -133,14 +241,272 @@ impl AsyncMainTreeCache {
         Ok(())
     }
 
+    /// Counts the nodes and records in the cache and estimates how much memory they use.
+    pub async fn stats(&self) -> CacheStats {
+        let mut stats = CacheStats::default();
+        for node in self.cache.get_all_nodes().await {
+            stats.nodes += 1;
+            let read_records = node.records.read().await;
+            for records in read_records.values() {
+                stats.record_sets += 1;
+                for record in records {
+                    stats.records += 1;
+                    if record.is_expired() {
+                        stats.expired_records += 1;
+                    }
+                    stats.estimated_bytes += record_size(record);
+                }
+            }
+            drop(read_records);
+        }
+        stats.evicted_records = self.evicted.load(Ordering::Relaxed);
+        stats
+    }
+
+    /// Removes the expired records. Then, if the cache is over its limits, evicts record sets until
+    /// it is 10% below them. The record sets closest to expiring are evicted first, since they are
+    /// the least useful. Record sets with bootstrap records (the root hints) are never evicted
+    /// because the resolver cannot find the root name servers without them. Returns the number of
+    /// records that were removed.
+    ///
+    /// Insertions call this automatically when they take the cache over its limits.
+    pub async fn prune(&self) -> usize {
+        let guard = self.pruning.lock().await;
+        let mut removed = self.remove_expired().await;
+        if self.limits.is_exceeded(self.len(), self.estimated_bytes()) {
+            removed += self.evict(self.limits.low_watermark()).await;
+        }
+        drop(guard);
+        removed
+    }
+
+    /// Removes the expired records and returns how many there were.
+    async fn remove_expired(&self) -> usize {
+        let mut removed = 0;
+        for node in self.cache.get_all_nodes().await {
+            let mut write_records = node.records.write().await;
+            write_records.retain(|_, records| {
+                records.retain(|record| if record.is_expired() {
+                    removed += self.count_removed([record]);
+                    false
+                } else {
+                    true
+                });
+                !records.is_empty()
+            });
+            drop(write_records);
+        }
+        removed
+    }
+
+    /// Evicts record sets, closest to expiring first, until the cache is within the limits.
+    async fn evict(&self, limits: CacheLimits) -> usize {
+        let mut candidates = Vec::new();
+        for node in self.cache.get_all_nodes().await {
+            let read_records = node.records.read().await;
+            for (rtype, records) in read_records.iter() {
+                if records.iter().any(CacheRecord::is_bootstrap) {
+                    continue;
+                }
+                if let Some(remaining_ttl) = records.iter().map(CacheRecord::remaining_ttl).min() {
+                    candidates.push((remaining_ttl, node.clone(), *rtype));
+                }
+            }
+            drop(read_records);
+        }
+        candidates.sort_unstable_by_key(|(remaining_ttl, _, _)| *remaining_ttl);
+
+        let mut removed = 0;
+        for (_, node, rtype) in candidates {
+            if !limits.is_exceeded(self.len(), self.estimated_bytes()) {
+                break;
+            }
+            let mut write_records = node.records.write().await;
+            // The record set may have been refreshed with bootstrap records since it was scanned.
+            if write_records.get(&rtype).is_some_and(|records| !records.iter().any(CacheRecord::is_bootstrap)) {
+                if let Some(records) = write_records.remove(&rtype) {
+                    removed += self.count_removed(&records);
+                }
+            }
+            drop(write_records);
+        }
+        self.evicted.fetch_add(removed, Ordering::Relaxed);
+        removed
+    }
+
+    /// Evicts records if the cache is over its limits. Nothing is done if records are already
+    /// being evicted, since that will make room for this insertion as well.
+    async fn enforce_limits(&self) {
+        if !self.limits.is_exceeded(self.len(), self.estimated_bytes()) {
+            return;
+        }
+        let Ok(guard) = self.pruning.try_lock() else {
+            return;
+        };
+        self.remove_expired().await;
+        if self.limits.is_exceeded(self.len(), self.estimated_bytes()) {
+            self.evict(self.limits.low_watermark()).await;
+        }
+        drop(guard);
+    }
+
     pub async fn get_domains(&self) -> HashSet<CDomainName> { self.cache.get_domains().await }
+
+    /// Gets all of the unexpired records at or below `apex`, sorted in canonical order (RFC 4034
+    /// section 6.1) by owner name. The TTL of each record is the time it has left in the cache.
+    pub async fn get_subtree_records(&self, apex: &CDomainName, qclass: RClass) -> Vec<CacheRecord> {
+        let nodes = match self.cache.get_subtree(apex, qclass).await {
+            Ok(nodes) => nodes,
+            Err(_) => return vec![],
+        };
+
+        let mut records = Vec::new();
+        for node in nodes {
+            let read_records = node.records.read().await;
+            records.extend(read_records.values()
+                .flatten()
+                .filter(|record| !record.is_expired())
+                .cloned()
+            );
+            drop(read_records);
+        }
+
+        for record in records.iter_mut() {
+            record.decay_ttl();
+        }
+        records.sort_by_cached_key(|record| (
+            record.get_name().case_insensitive_labels()
+                .rev()
+                .map(|label| label.octets().to_ascii_lowercase())
+                .collect::<Vec<_>>(),
+            record.get_rtype().code(),
+        ));
+        records
+    }
+
+    /// Every unexpired record in the cache, in no particular order. Unlike
+    /// `get_subtree_records()`, the records keep their original TTLs and insertion times.
+    pub async fn get_all_records(&self) -> Vec<CacheRecord> {
+        let mut records = Vec::new();
+        for node in self.cache.get_all_nodes().await {
+            let read_records = node.records.read().await;
+            records.extend(read_records.values()
+                .flatten()
+                .filter(|record| !record.is_expired())
+                .cloned()
+            );
+            drop(read_records);
+        }
+        records
+    }
+
+    /// Writes every unexpired record to a snapshot at the path, replacing any snapshot that is
+    /// already there, and returns the number of records written. Each record keeps its authority
+    /// and the wall clock time at which it expires, so `load_snapshot()` can warm up a cache in
+    /// another process with the time the records have left.
+    ///
+    /// The snapshot is written to a separate file and then renamed over the old one, so a crash
+    /// part way through leaves the previous snapshot in place.
+    pub async fn save_snapshot(&self, path: impl AsRef<Path>) -> io::Result<usize> {
+        let path = path.as_ref();
+        let records = self.get_all_records().await;
+        let payload = encode_records(&records, SystemTime::now()).map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))?;
+        let mut contents = Vec::with_capacity(SNAPSHOT_MAGIC.len() + payload.len());
+        contents.extend_from_slice(SNAPSHOT_MAGIC);
+        contents.extend_from_slice(&payload);
+
+        let temporary_path = path.with_extension("saving");
+        let mut file = tokio::fs::File::create(&temporary_path).await?;
+        file.write_all(&contents).await?;
+        file.sync_all().await?;
+        drop(file);
+        tokio::fs::rename(&temporary_path, path).await?;
+        Ok(records.len())
+    }
+
+    /// Inserts the records from a snapshot written by `save_snapshot()` and returns the number of
+    /// records that were loaded. Their TTLs are set to the time they have left and records that
+    /// expired since the snapshot was taken are left out. A snapshot that does not exist is
+    /// treated as an empty one, so a deployment can call this unconditionally when it starts.
+    pub async fn load_snapshot(&self, path: impl AsRef<Path>) -> io::Result<usize> {
+        let contents = match tokio::fs::read(path).await {
+            Ok(contents) => contents,
+            Err(error) if error.kind() == io::ErrorKind::NotFound => return Ok(0),
+            Err(error) => return Err(error),
+        };
+        let Some(payload) = contents.strip_prefix(SNAPSHOT_MAGIC) else {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "not a cache snapshot"));
+        };
+        let records = decode_records(payload, SystemTime::now()).map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))?;
+        let count = records.len();
+        self.insert_iter(records.into_iter()).await;
+        Ok(count)
+    }
+
+    /// Starts saving a snapshot to the path in the background every `interval`. The first
+    /// snapshot is taken one interval from now.
+    pub fn save_snapshots(self: &Arc<Self>, path: impl Into<PathBuf>, interval: Duration) -> SnapshotWriter {
+        let cache = self.clone();
+        let path = path.into();
+        let task = tokio::spawn(async move {
+            let mut interval = tokio::time::interval(interval);
+            interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+            // The first tick completes immediately.
+            interval.tick().await;
+            loop {
+                interval.tick().await;
+                if let Err(error) = cache.save_snapshot(&path).await {
+                    println!("Failed to save a cache snapshot to '{}': {error}", path.display());
+                }
+            }
+        });
+        SnapshotWriter { task }
+    }
+
+    /// Writes all of the unexpired records at or below `apex` as a zone file which can be loaded
+    /// back in using `load_from_file()`. Each record is preceded by a comment noting when it
+    /// expires and how it was learned.
+    pub async fn dump_zone(&self, apex: &CDomainName, qclass: RClass, out: &mut (impl fmt::Write + Send)) -> fmt::Result {
+        let records = self.get_subtree_records(apex, qclass).await;
+
+        let mut writer = ZoneFileWriter::new(out, apex, DEFAULT_ZONE_DUMP_TTL)?;
+        writer.write_comment(format_args!("{} cached records at or below {apex} ({qclass})", records.len()))?;
+        writer.write_zone(records.into_iter().map(|record| {
+            let source = if record.is_authoritative() {
+                "authoritative"
+            } else if record.is_bootstrap() {
+                "bootstrap"
+            } else {
+                "not authoritative"
+            };
+            let comment = format!("expires in {}s, {source}", record.get_ttl().as_secs());
+            CommentedRecord::new(record.record, vec![comment])
+        }))
+    }
+}
+
+/// Saves snapshots of a cache in the background. Snapshots stop being saved when this is dropped.
+/// The last snapshot that was saved stays in place.
+#[derive(Debug)]
+pub struct SnapshotWriter {
+    task: JoinHandle<()>,
+}
+
+impl Drop for SnapshotWriter {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
 }
 
 #[async_trait]
 impl AsyncMainCache for AsyncMainTreeCache {
+    /// The records are given the time they have left in the cache as their TTL.
     async fn get(&self, query: &CacheQuery) -> CacheResponse {
         match self.get_records(&query).await {
-            Ok(records) => CacheResponse::Records(records),
+            Ok(mut records) => {
+                records.iter_mut().for_each(CacheRecord::decay_ttl);
+                CacheResponse::Records(records)
+            },
             Err(_) => CacheResponse::Err(RCode::ServFail),
         }
     }
@@ -149,10 +515,146 @@ impl AsyncMainCache for AsyncMainTreeCache {
         if record.get_ttl().as_secs() != 0 {
             let received_time = Instant::now();
             let _ = self.insert_record(record, received_time).await;
+            self.enforce_limits().await;
         }
     }
 
+    #[inline]
+    async fn stats(&self) -> Option<CacheStats> {
+        Some(AsyncMainTreeCache::stats(self).await)
+    }
+
+    async fn remove(&self, question: &Question) -> Option<usize> {
+        let Ok(Some(node)) = self.cache.get_node(question).await else {
+            return Some(0);
+        };
+        let mut write_records = node.records.write().await;
+        let removed = match question.qtype() {
+            RType::ANY => write_records.drain().map(|(_, records)| self.count_removed(&records)).sum(),
+            qtype => write_records.remove(&qtype).map_or(0, |records| self.count_removed(&records)),
+        };
+        drop(write_records);
+        Some(removed)
+    }
+
+    async fn remove_subtree(&self, apex: &CDomainName, qclass: RClass) -> Option<usize> {
+        let Ok(nodes) = self.cache.get_subtree(apex, qclass).await else {
+            return Some(0);
+        };
+        let mut removed = 0;
+        for node in nodes {
+            let mut write_records = node.records.write().await;
+            removed += write_records.drain().map(|(_, records)| self.count_removed(&records)).sum::<usize>();
+            drop(write_records);
+        }
+        Some(removed)
+    }
+
     async fn clean(&self) {
-        todo!()
+        self.remove_expired().await;
+    }
+}
+
+#[cfg(test)]
+mod async_main_cache_tests {
+    use std::{net::Ipv4Addr, path::PathBuf, time::{Duration, Instant, SystemTime}};
+
+    use dns_lib::{interface::cache::{main_cache::AsyncMainCache, CacheMeta, CacheQuery, CacheRecord, CacheResponse, MetaAuth}, query::question::Question, resource_record::{rclass::RClass, resource_record::{RecordData, ResourceRecord}, rtype::RType, time::Time, types::a::A}, types::c_domain_name::CDomainName};
+    use tokio::fs;
+
+    use crate::record_codec::encode_records;
+
+    use super::{AsyncMainTreeCache, CacheLimits, SNAPSHOT_MAGIC};
+
+    /// A snapshot path that no other test uses. Anything left over from an earlier run is removed.
+    async fn snapshot_path(test: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("dns-cache-snapshot-{test}-{}", std::process::id()));
+        let _ = fs::remove_file(&path).await;
+        path
+    }
+
+    fn record(name: &str, ttl: u32) -> CacheRecord {
+        CacheRecord {
+            meta: CacheMeta { auth: MetaAuth::NotAuthoritative, insertion_time: Instant::now(), original_ttl: None },
+            record: ResourceRecord::new(CDomainName::from_utf8(name).unwrap(), RClass::Internet, Time::from_secs(ttl), RecordData::A(A::new(Ipv4Addr::new(192, 0, 2, 1)))),
+        }
+    }
+
+    /// The TTLs of the records cached for the name, which are the time they have left.
+    async fn cached_ttls(cache: &AsyncMainTreeCache, name: &str) -> Vec<u32> {
+        let question = Question::new(CDomainName::from_utf8(name).unwrap(), RType::A, RClass::Internet);
+        match AsyncMainCache::get(cache, &CacheQuery { authoritative: false, question: &question }).await {
+            CacheResponse::Records(records) => records.iter().map(|record| record.get_ttl().as_secs()).collect(),
+            CacheResponse::Err(rcode) => panic!("The cache responded with {rcode}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn snapshots_keep_the_time_records_have_left() {
+        let path = snapshot_path("round-trip").await;
+        let cache = AsyncMainTreeCache::new();
+        AsyncMainCache::insert_record(&cache, record("fresh.example.", 300)).await;
+        // Cached with a TTL of 300 seconds and has 200 of them left.
+        let mut aged = record("aged.example.", 200);
+        aged.meta.original_ttl = Some(Time::from_secs(300));
+        AsyncMainCache::insert_record(&cache, aged).await;
+        assert_eq!(cache.save_snapshot(&path).await.unwrap(), 2);
+
+        let loaded = AsyncMainTreeCache::new();
+        assert_eq!(loaded.load_snapshot(&path).await.unwrap(), 2);
+        let fresh = cached_ttls(&loaded, "fresh.example.").await;
+        assert!(fresh.len() == 1 && (299..=300).contains(&fresh[0]), "{fresh:?}");
+        let aged = cached_ttls(&loaded, "aged.example.").await;
+        assert!(aged.len() == 1 && (199..=200).contains(&aged[0]), "{aged:?}");
+        fs::remove_file(&path).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn records_that_expired_since_the_snapshot_are_dropped() {
+        let path = snapshot_path("expired").await;
+        let earlier = SystemTime::now() - Duration::from_secs(250);
+        let mut contents = SNAPSHOT_MAGIC.to_vec();
+        contents.extend_from_slice(&encode_records(&[record("expired.example.", 100), record("current.example.", 300)], earlier).unwrap());
+        fs::write(&path, &contents).await.unwrap();
+
+        let cache = AsyncMainTreeCache::new();
+        assert_eq!(cache.load_snapshot(&path).await.unwrap(), 1);
+        assert!(cached_ttls(&cache, "expired.example.").await.is_empty());
+        let current = cached_ttls(&cache, "current.example.").await;
+        assert!(current.len() == 1 && (49..=50).contains(&current[0]), "{current:?}");
+        assert_eq!(cache.len(), 1);
+        fs::remove_file(&path).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn missing_snapshots_are_empty() {
+        let path = snapshot_path("missing").await;
+        let cache = AsyncMainTreeCache::new();
+        assert_eq!(cache.load_snapshot(&path).await.unwrap(), 0);
+        assert!(cache.is_empty());
+    }
+
+    #[tokio::test]
+    async fn records_closest_to_expiring_are_evicted_first() {
+        let cache = AsyncMainTreeCache::with_limits(CacheLimits { max_records: Some(10), max_bytes: None });
+        // The root hints expire the soonest but are never evicted.
+        let mut hint = record("hint.example.", 10);
+        hint.meta.auth = MetaAuth::NotAuthoritativeBootstrap;
+        AsyncMainCache::insert_record(&cache, hint).await;
+        // Inserted longest lived first so that the ones evicted are also the newest.
+        for index in (1..=10).rev() {
+            AsyncMainCache::insert_record(&cache, record(&format!("r{index}.example."), index * 100)).await;
+        }
+
+        // The 11th record took the cache over its limit, so it was brought down to 9 records.
+        assert_eq!(cache.len(), 9);
+        assert_eq!(cache.stats().await.evicted_records, 2);
+        assert_eq!(cached_ttls(&cache, "hint.example.").await.len(), 1);
+        for index in 1..=2 {
+            assert!(cached_ttls(&cache, &format!("r{index}.example.")).await.is_empty(), "r{index} was not evicted");
+        }
+        for index in 3..=10 {
+            assert_eq!(cached_ttls(&cache, &format!("r{index}.example.")).await.len(), 1, "r{index} was evicted");
+        }
     }
 }
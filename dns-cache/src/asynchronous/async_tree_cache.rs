@@ -210,6 +210,41 @@ impl<Records> AsyncTreeCache<Records> where Records: Send + Sync {
         return Ok(result);
     }
 
+    /// Gets the node for `qname` and every node below it. If the domain is not in the cache, the
+    /// result is empty.
+    pub async fn get_subtree(&self, qname: &CDomainName, qclass: RClass) -> Result<Vec<Arc<TreeNode<Records>>>, AsyncTreeCacheError> {
+        let apex_node = match self.get_node(&Question::new(qname.clone(), RType::ANY, qclass)).await? {
+            Some(apex_node) => apex_node,
+            None => return Ok(vec![]),
+        };
+
+        let mut subtree = Vec::new();
+        let mut unvisited = vec![apex_node];
+        while let Some(node) = unvisited.pop() {
+            let read_node_children = node.children.read().await;
+            unvisited.extend(read_node_children.values().cloned());
+            drop(read_node_children);
+            subtree.push(node);
+        }
+        return Ok(subtree);
+    }
+
+    /// Gets every node in the cache, across all classes.
+    pub async fn get_all_nodes(&self) -> Vec<Arc<TreeNode<Records>>> {
+        let read_root_node = self.root_nodes.read().await;
+        let mut unvisited: Vec<_> = read_root_node.values().cloned().collect();
+        drop(read_root_node);
+
+        let mut nodes = Vec::new();
+        while let Some(node) = unvisited.pop() {
+            let read_node_children = node.children.read().await;
+            unvisited.extend(read_node_children.values().cloned());
+            drop(read_node_children);
+            nodes.push(node);
+        }
+        return nodes;
+    }
+
     async fn get_subdomains(node: Arc<TreeNode<Records>>) -> HashSet<Vec<CaseInsensitiveOwnedLabel>> {
         let read_node_children = node.children.read().await;
         let node_children = read_node_children.clone();
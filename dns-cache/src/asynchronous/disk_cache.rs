@@ -0,0 +1,407 @@
+use std::{io, path::{Path, PathBuf}, time::SystemTime};
+
+use async_trait::async_trait;
+use dns_lib::interface::cache::{main_cache::AsyncMainCache, CacheQuery, CacheRecord, CacheResponse, CacheStats};
+use tokio::{fs::{self, File, OpenOptions}, io::AsyncWriteExt, sync::Mutex};
+
+use crate::record_codec::{decode_records, encode_records};
+
+use super::async_main_cache::AsyncMainTreeCache;
+
+/// Written at the start of every log so that some other file is not mistaken for one.
+const LOG_MAGIC: &[u8; 4] = b"DNSC";
+
+/// The bytes in front of each entry: the length of the entry and its checksum.
+const ENTRY_HEADER_LENGTH: usize = 4 + 4;
+
+/// The most records written in a single entry when the log is compacted.
+const RECORDS_PER_COMPACTED_ENTRY: usize = 512;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct DiskCacheConfig {
+    /// Wait for every write to reach the disk before returning. Without this, the records written
+    /// just before a power loss may be lost, but the rest of the log is still intact.
+    pub sync_writes: bool,
+    /// The log is compacted once it is at least this long and more than twice as long as it was
+    /// after it was last compacted.
+    pub min_compaction_bytes: u64,
+}
+
+impl Default for DiskCacheConfig {
+    #[inline]
+    fn default() -> Self {
+        Self { sync_writes: false, min_compaction_bytes: 1 << 20 }
+    }
+}
+
+#[derive(Debug)]
+struct DiskLog {
+    file: File,
+    length: u64,
+    compacted_length: u64,
+}
+
+/// A cache that keeps its records on disk so that it is still warm after a restart.
+///
+/// The records are kept in memory to answer queries and every insertion is appended to a log.
+/// When the cache is opened, the log is read back in and any records that expired while it was
+/// closed are dropped. Each entry in the log is checksummed, so if the process stopped part way
+/// through a write, the partial entry is discarded and the log is truncated to the last complete
+/// one.
+///
+/// The log grows with every insertion, so it is periodically rewritten with only the unexpired
+/// records. The new log is written to a separate file and then renamed over the old one so that a
+/// crash during compaction leaves the old log in place.
+pub struct DiskCache {
+    path: PathBuf,
+    memory: AsyncMainTreeCache,
+    log: Mutex<DiskLog>,
+    config: DiskCacheConfig,
+}
+
+impl DiskCache {
+    #[inline]
+    pub async fn open(path: impl Into<PathBuf>) -> io::Result<Self> {
+        Self::open_with_config(path, DiskCacheConfig::default()).await
+    }
+
+    /// Opens the log at the path, creating it if it does not exist, and loads the records in it.
+    pub async fn open_with_config(path: impl Into<PathBuf>, config: DiskCacheConfig) -> io::Result<Self> {
+        let path = path.into();
+        let memory = AsyncMainTreeCache::new();
+        let contents = match fs::read(&path).await {
+            Ok(contents) => contents,
+            Err(error) if error.kind() == io::ErrorKind::NotFound => Vec::new(),
+            Err(error) => return Err(error),
+        };
+
+        let (records, valid_length) = scan_log(&contents)?;
+        memory.insert_iter(records.into_iter()).await;
+
+        let mut file = OpenOptions::new().create(true).read(true).write(true).open(&path).await?;
+        if valid_length == 0 {
+            file.set_len(0).await?;
+            file.write_all(LOG_MAGIC).await?;
+            file.sync_all().await?;
+        } else if valid_length < contents.len() {
+            println!("Discarding {} bytes from the end of the disk cache '{}'", contents.len() - valid_length, path.display());
+            file.set_len(valid_length as u64).await?;
+            file.sync_all().await?;
+        }
+        // Writes always go to the end of the log, even after it has been truncated.
+        drop(file);
+        let file = OpenOptions::new().append(true).open(&path).await?;
+        let length = valid_length.max(LOG_MAGIC.len()) as u64;
+
+        let cache = Self {
+            path,
+            memory,
+            log: Mutex::new(DiskLog { file, length, compacted_length: 0 }),
+            config,
+        };
+        // The records that expired while the cache was closed are still in the log.
+        cache.compact().await?;
+        Ok(cache)
+    }
+
+    #[inline]
+    pub fn path(&self) -> &Path { &self.path }
+
+    #[inline]
+    pub fn config(&self) -> &DiskCacheConfig { &self.config }
+
+    /// The records held in memory.
+    #[inline]
+    pub fn memory(&self) -> &AsyncMainTreeCache { &self.memory }
+
+    /// The current length of the log in bytes.
+    #[inline]
+    pub async fn log_length(&self) -> u64 {
+        let r_log = self.log.lock().await;
+        let length = r_log.length;
+        drop(r_log);
+        length
+    }
+
+    /// Rewrites the log with only the records that have not expired.
+    pub async fn compact(&self) -> io::Result<()> {
+        let mut w_log = self.log.lock().await;
+        let records = self.memory.get_all_records().await;
+        let now = SystemTime::now();
+
+        let mut contents = LOG_MAGIC.to_vec();
+        for records in records.chunks(RECORDS_PER_COMPACTED_ENTRY) {
+            let payload = encode_records(records, now).map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))?;
+            push_entry(&mut contents, &payload)?;
+        }
+
+        let compacted_path = self.path.with_extension("compacting");
+        let mut compacted = File::create(&compacted_path).await?;
+        compacted.write_all(&contents).await?;
+        compacted.sync_all().await?;
+        drop(compacted);
+        fs::rename(&compacted_path, &self.path).await?;
+        // Make the rename itself durable.
+        if let Some(directory) = self.path.parent().filter(|directory| !directory.as_os_str().is_empty()) {
+            File::open(directory).await?.sync_all().await?;
+        }
+
+        w_log.file = OpenOptions::new().append(true).open(&self.path).await?;
+        w_log.length = contents.len() as u64;
+        w_log.compacted_length = w_log.length;
+        drop(w_log);
+        Ok(())
+    }
+
+    /// Adds the records to memory and appends them to the log. The records go into memory while
+    /// the log is locked, so a compaction, which rebuilds the log from memory, either includes them
+    /// or runs before they are appended.
+    async fn insert_and_append(&self, records: Vec<CacheRecord>) -> io::Result<()> {
+        let entry = encode_records(&records, SystemTime::now())
+            .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))
+            .and_then(|payload| {
+                let mut entry = Vec::with_capacity(ENTRY_HEADER_LENGTH + payload.len());
+                push_entry(&mut entry, &payload)?;
+                Ok(entry)
+            });
+
+        let mut w_log = self.log.lock().await;
+        AsyncMainCache::insert_records(&self.memory, records).await;
+        let entry = match entry {
+            Ok(entry) => entry,
+            Err(error) => {
+                drop(w_log);
+                return Err(error);
+            },
+        };
+        let result = async {
+            w_log.file.write_all(&entry).await?;
+            if self.config.sync_writes {
+                w_log.file.sync_data().await?;
+            }
+            Ok(())
+        }.await;
+        match result {
+            Ok(()) => w_log.length += entry.len() as u64,
+            Err(error) => {
+                // Remove whatever part of the entry was written so that later entries are not
+                // hidden behind it when the log is scanned.
+                let _ = w_log.file.set_len(w_log.length).await;
+                drop(w_log);
+                return Err(error);
+            },
+        }
+        let should_compact = (w_log.length >= self.config.min_compaction_bytes) && (w_log.length > w_log.compacted_length.saturating_mul(2));
+        drop(w_log);
+
+        if should_compact {
+            self.compact().await?;
+        }
+        Ok(())
+    }
+}
+
+/// FNV-1a. This only needs to catch torn and partially written entries, not deliberate tampering.
+#[inline]
+fn checksum(bytes: &[u8]) -> u32 {
+    bytes.iter().fold(0x811c9dc5_u32, |hash, byte| (hash ^ u32::from(*byte)).wrapping_mul(0x01000193))
+}
+
+#[inline]
+fn push_entry(out: &mut Vec<u8>, payload: &[u8]) -> io::Result<()> {
+    let Ok(length) = u32::try_from(payload.len()) else {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "disk cache entry is too long"));
+    };
+    out.extend_from_slice(&length.to_be_bytes());
+    out.extend_from_slice(&checksum(payload).to_be_bytes());
+    out.extend_from_slice(payload);
+    Ok(())
+}
+
+/// Reads the unexpired records out of a log. Also returns the length of the log up to the end of
+/// the last complete entry. Anything after that was not fully written and should be discarded.
+fn scan_log(contents: &[u8]) -> io::Result<(Vec<CacheRecord>, usize)> {
+    if contents.is_empty() {
+        return Ok((Vec::new(), 0));
+    }
+    if !contents.starts_with(LOG_MAGIC) {
+        if LOG_MAGIC.starts_with(contents) {
+            // The log was created but the magic was not fully written.
+            return Ok((Vec::new(), 0));
+        }
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "not a disk cache log"));
+    }
+
+    let now = SystemTime::now();
+    let mut records = Vec::new();
+    let mut offset = LOG_MAGIC.len();
+    while let Some(header) = contents.get(offset..(offset + ENTRY_HEADER_LENGTH)) {
+        let length = u32::from_be_bytes([header[0], header[1], header[2], header[3]]) as usize;
+        let expected_checksum = u32::from_be_bytes([header[4], header[5], header[6], header[7]]);
+        let payload_start = offset + ENTRY_HEADER_LENGTH;
+        let Some(payload) = contents.get(payload_start..(payload_start + length)) else {
+            break;
+        };
+        if checksum(payload) != expected_checksum {
+            break;
+        }
+        match decode_records(payload, now) {
+            Ok(entry_records) => records.extend(entry_records),
+            Err(error) => {
+                println!("{error} in disk cache entry at offset {offset}");
+                break;
+            },
+        }
+        offset = payload_start + length;
+    }
+    Ok((records, offset))
+}
+
+#[async_trait]
+impl AsyncMainCache for DiskCache {
+    #[inline]
+    async fn get(&self, query: &CacheQuery) -> CacheResponse {
+        AsyncMainCache::get(&self.memory, query).await
+    }
+
+    #[inline]
+    async fn insert_record(&self, record: CacheRecord) {
+        self.insert_records(vec![record]).await;
+    }
+
+    async fn insert_records(&self, records: Vec<CacheRecord>) {
+        let records: Vec<CacheRecord> = records.into_iter().filter(|record| !record.get_ttl().is_zero()).collect();
+        if records.is_empty() {
+            return;
+        }
+        if let Err(error) = self.insert_and_append(records).await {
+            println!("{error} when writing to the disk cache '{}'", self.path.display());
+        }
+    }
+
+    async fn clean(&self) {
+        AsyncMainCache::clean(&self.memory).await;
+        if let Err(error) = self.compact().await {
+            println!("{error} when compacting the disk cache '{}'", self.path.display());
+        }
+    }
+
+    #[inline]
+    async fn stats(&self) -> Option<CacheStats> {
+        Some(self.memory.stats().await)
+    }
+}
+
+#[cfg(test)]
+mod disk_cache_tests {
+    use std::{net::Ipv4Addr, path::PathBuf, time::{Duration, Instant, SystemTime}};
+
+    use dns_lib::{interface::cache::{main_cache::AsyncMainCache, CacheMeta, CacheQuery, CacheRecord, CacheResponse, MetaAuth}, query::question::Question, resource_record::{rclass::RClass, resource_record::{RecordData, ResourceRecord}, rtype::RType, time::Time, types::a::A}, types::c_domain_name::CDomainName};
+    use tokio::fs;
+
+    use crate::record_codec::encode_records;
+
+    use super::{push_entry, DiskCache, DiskCacheConfig, ENTRY_HEADER_LENGTH, LOG_MAGIC};
+
+    /// A log path that no other test uses. Anything left over from an earlier run is removed.
+    async fn log_path(test: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("dns-cache-{test}-{}", std::process::id()));
+        let _ = fs::remove_file(&path).await;
+        path
+    }
+
+    fn record(name: &str, ttl: u32) -> CacheRecord {
+        CacheRecord {
+            meta: CacheMeta { auth: MetaAuth::NotAuthoritative, insertion_time: Instant::now(), original_ttl: None },
+            record: ResourceRecord::new(CDomainName::from_utf8(name).unwrap(), RClass::Internet, Time::from_secs(ttl), RecordData::A(A::new(Ipv4Addr::new(192, 0, 2, 1)))),
+        }
+    }
+
+    async fn is_cached(cache: &DiskCache, name: &str) -> bool {
+        let question = Question::new(CDomainName::from_utf8(name).unwrap(), RType::A, RClass::Internet);
+        matches!(cache.get(&CacheQuery { authoritative: false, question: &question }).await, CacheResponse::Records(records) if !records.is_empty())
+    }
+
+    /// A log with one entry for each group of records, written as if it were `now`.
+    fn log(entries: &[Vec<CacheRecord>], now: SystemTime) -> Vec<u8> {
+        let mut contents = LOG_MAGIC.to_vec();
+        for records in entries {
+            push_entry(&mut contents, &encode_records(records, now).unwrap()).unwrap();
+        }
+        contents
+    }
+
+    #[tokio::test]
+    async fn torn_entries_at_the_end_are_discarded() {
+        let path = log_path("torn").await;
+        let mut contents = log(&[vec![record("a.example.", 300)]], SystemTime::now());
+        // The header says that the entry is longer than what was written before the crash.
+        contents.extend_from_slice(&100_u32.to_be_bytes());
+        contents.extend_from_slice(&[0; 4 + 10]);
+        fs::write(&path, &contents).await.unwrap();
+
+        let cache = DiskCache::open(&path).await.unwrap();
+        assert!(is_cached(&cache, "a.example.").await);
+        // Records appended after reopening are not hidden behind the torn entry.
+        cache.insert_record(record("b.example.", 300)).await;
+        drop(cache);
+
+        let cache = DiskCache::open(&path).await.unwrap();
+        assert!(is_cached(&cache, "a.example.").await);
+        assert!(is_cached(&cache, "b.example.").await);
+        fs::remove_file(&path).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn entries_after_a_bad_checksum_are_discarded() {
+        let path = log_path("checksum").await;
+        let mut contents = log(&[vec![record("a.example.", 300)], vec![record("b.example.", 300)], vec![record("c.example.", 300)]], SystemTime::now());
+        // Change the last octet of the address in the second entry.
+        let first_entry_length = ENTRY_HEADER_LENGTH + u32::from_be_bytes(contents[LOG_MAGIC.len()..LOG_MAGIC.len() + 4].try_into().unwrap()) as usize;
+        let second_entry_end = LOG_MAGIC.len() + (2 * first_entry_length);
+        contents[second_entry_end - 1] ^= 0xFF;
+        fs::write(&path, &contents).await.unwrap();
+
+        // The log cannot be trusted past the damaged entry, so it is cut off there.
+        let cache = DiskCache::open(&path).await.unwrap();
+        assert!(is_cached(&cache, "a.example.").await);
+        assert!(!is_cached(&cache, "b.example.").await);
+        assert!(!is_cached(&cache, "c.example.").await);
+        drop(cache);
+        fs::remove_file(&path).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn records_that_expired_while_closed_are_dropped() {
+        let path = log_path("expired").await;
+        let an_hour_ago = SystemTime::now() - Duration::from_secs(3600);
+        let contents = log(&[vec![record("expired.example.", 60), record("current.example.", 7200)]], an_hour_ago);
+        fs::write(&path, &contents).await.unwrap();
+
+        let cache = DiskCache::open(&path).await.unwrap();
+        assert!(!is_cached(&cache, "expired.example.").await);
+        assert!(is_cached(&cache, "current.example.").await);
+        assert_eq!(cache.memory().stats().await.records, 1);
+        // The log is compacted when it is opened, so the expired record is gone from it as well.
+        assert_eq!(cache.log_length().await as usize, log(&[vec![record("current.example.", 3600)]], SystemTime::now()).len());
+        drop(cache);
+        fs::remove_file(&path).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn compaction_keeps_the_newest_insert() {
+        let path = log_path("compaction").await;
+        // Every insertion compacts the log.
+        let config = DiskCacheConfig { min_compaction_bytes: 0, ..DiskCacheConfig::default() };
+        let cache = DiskCache::open_with_config(&path, config).await.unwrap();
+        cache.insert_record(record("a.example.", 300)).await;
+        cache.insert_record(record("b.example.", 300)).await;
+        drop(cache);
+
+        let cache = DiskCache::open_with_config(&path, config).await.unwrap();
+        assert!(is_cached(&cache, "a.example.").await);
+        assert!(is_cached(&cache, "b.example.").await);
+        drop(cache);
+        fs::remove_file(&path).await.unwrap();
+    }
+}
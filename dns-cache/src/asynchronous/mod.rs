@@ -2,3 +2,6 @@ mod async_tree_cache;
 pub mod async_cache;
 pub mod async_main_cache;
 pub mod async_transaction_cache;
+pub mod disk_cache;
+pub mod remote_cache;
+pub mod tiered_cache;
@@ -0,0 +1,390 @@
+use std::{collections::HashMap, error::Error, fmt::Display, sync::Arc, time::{Duration, SystemTime}};
+
+use async_trait::async_trait;
+use dns_lib::{interface::cache::{main_cache::AsyncMainCache, CacheQuery, CacheRecord, CacheResponse}, resource_record::{rclass::RClass, rtype::RType, time::Time}, serde::wire::{to_wire::ToWire, write_wire::WriteWire}, types::c_domain_name::CDomainName};
+use futures::future::join_all;
+use log::warn;
+
+use crate::record_codec::{decode_records, encode_records, remaining_ttl, RecordCodecError};
+
+use super::async_main_cache::AsyncMainTreeCache;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum RemoteStoreError {
+    /// The store could not be reached or rejected the request.
+    Unavailable(String),
+    Codec(RecordCodecError),
+}
+impl Error for RemoteStoreError {}
+impl Display for RemoteStoreError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Unavailable(message) => write!(f, "Remote cache store unavailable: {message}"),
+            Self::Codec(error) => write!(f, "{error}"),
+        }
+    }
+}
+impl From<RecordCodecError> for RemoteStoreError {
+    fn from(error: RecordCodecError) -> Self {
+        Self::Codec(error)
+    }
+}
+
+/// A store shared by several resolvers, such as Redis. Each key holds a set of fields (a hash in
+/// Redis) so that resolvers can add to what is stored under a key without reading it first, and
+/// each key is set with a TTL after which the store should discard it.
+#[async_trait]
+pub trait RemoteStore: Send + Sync {
+    /// The values of every field stored under the key. Empty if there is nothing stored under it.
+    async fn get_fields(&self, key: &str) -> Result<Vec<Vec<u8>>, RemoteStoreError>;
+    /// Sets the fields under the key in a single atomic step, leaving its other fields as they
+    /// are. The key is kept for at least `ttl`. A later expiration that it already has is kept.
+    async fn set_fields(&self, key: &str, fields: Vec<(Vec<u8>, Vec<u8>)>, ttl: Duration) -> Result<(), RemoteStoreError>;
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct RemoteCacheConfig {
+    /// Prepended to every key so that several caches (or other data) can share one store.
+    pub key_prefix: String,
+    /// The longest that a record is kept in the local cache before it is fetched from the store
+    /// again. This bounds how long a resolver can miss a record set that another resolver updated.
+    pub local_ttl_limit: Time,
+}
+
+impl Default for RemoteCacheConfig {
+    #[inline]
+    fn default() -> Self {
+        Self { key_prefix: "dns".to_string(), local_ttl_limit: Time::from_secs(30) }
+    }
+}
+
+/// A cache that shares its records with other resolvers through a remote store and keeps a local
+/// copy of the records it has seen.
+///
+/// Each record set (records with the same name, type, and class) is stored under its own key, with
+/// one field for each record holding the time at which it expires. Records with the same data
+/// replace each other and records that have expired are left out when the set is read. Since every
+/// record is its own field, resolvers that add records to the same set at the same time do not
+/// overwrite each other. Queries are answered from the local copy when it has the records and from
+/// the store otherwise. Records are written to both.
+///
+/// If the store is unavailable, the cache behaves like a local cache.
+pub struct RemoteCache {
+    local: AsyncMainTreeCache,
+    remote: Arc<dyn RemoteStore>,
+    config: RemoteCacheConfig,
+}
+
+impl RemoteCache {
+    #[inline]
+    pub fn new(remote: Arc<dyn RemoteStore>) -> Self {
+        Self::with_config(remote, RemoteCacheConfig::default())
+    }
+
+    #[inline]
+    pub fn with_config(remote: Arc<dyn RemoteStore>, config: RemoteCacheConfig) -> Self {
+        Self { local: AsyncMainTreeCache::new(), remote, config }
+    }
+
+    #[inline]
+    pub fn config(&self) -> &RemoteCacheConfig { &self.config }
+
+    /// The records that this instance holds locally.
+    #[inline]
+    pub fn local(&self) -> &AsyncMainTreeCache { &self.local }
+
+    #[inline]
+    fn key(&self, qname: &CDomainName, rtype: RType, rclass: RClass) -> String {
+        format!("{}:{rclass}:{rtype}:{}", self.config.key_prefix, qname.as_lowercase())
+    }
+
+    /// Inserts the records into the local cache, limiting how long they are kept there.
+    async fn insert_local(&self, mut records: Vec<CacheRecord>) {
+        for record in records.iter_mut() {
+            if *record.get_ttl() > self.config.local_ttl_limit {
+                record.set_ttl(self.config.local_ttl_limit);
+            }
+        }
+        AsyncMainCache::insert_records(&self.local, records).await;
+    }
+
+    async fn get_remote(&self, key: &str) -> Result<Vec<CacheRecord>, RemoteStoreError> {
+        let now = SystemTime::now();
+        let mut records = Vec::new();
+        for value in self.remote.get_fields(key).await? {
+            records.extend(decode_records(&value, now)?);
+        }
+        Ok(records)
+    }
+
+    /// Adds the records to the record set stored under the key. Records that are already stored
+    /// with the same data are replaced.
+    async fn merge_remote(&self, key: &str, records: Vec<CacheRecord>) -> Result<(), RemoteStoreError> {
+        let now = SystemTime::now();
+        let records: Vec<CacheRecord> = records.into_iter().filter(|record| remaining_ttl(record) > 0).collect();
+        let Some(ttl) = records.iter().map(remaining_ttl).max() else {
+            return Ok(());
+        };
+        let fields = records.iter()
+            .map(|record| Ok((field(record)?, encode_records([record], now)?)))
+            .collect::<Result<Vec<_>, RecordCodecError>>()?;
+        self.remote.set_fields(key, fields, Duration::from_secs(u64::from(ttl))).await
+    }
+}
+
+/// The field that a record is stored under in its record set: its data, as it is written on the
+/// wire.
+fn field(record: &CacheRecord) -> Result<Vec<u8>, RecordCodecError> {
+    let rdata = record.get_rdata();
+    let mut buffer = vec![0; usize::from(rdata.serial_length())];
+    let mut wire = WriteWire::from_bytes(&mut buffer);
+    rdata.to_wire_format(&mut wire, &mut None)?;
+    let length = wire.current_len();
+    buffer.truncate(length);
+    Ok(buffer)
+}
+
+#[async_trait]
+impl AsyncMainCache for RemoteCache {
+    async fn get(&self, query: &CacheQuery) -> CacheResponse {
+        let local_response = AsyncMainCache::get(&self.local, query).await;
+        // Record sets are stored separately so there is no way to ask the store for all of them at
+        // once.
+        if query.qtype() == RType::ANY {
+            return local_response;
+        }
+        match &local_response {
+            CacheResponse::Records(records) if records.is_empty() => (),
+            _ => return local_response,
+        }
+
+        let key = self.key(query.qname(), query.qtype(), query.qclass());
+        let records = match self.get_remote(&key).await {
+            Ok(records) => records,
+            Err(error) => {
+                warn!("{error} when getting '{key}'");
+                return local_response;
+            },
+        };
+        self.insert_local(records.clone()).await;
+        if query.authoritative {
+            CacheResponse::Records(records.into_iter().filter(|record| record.is_authoritative()).collect())
+        } else {
+            CacheResponse::Records(records)
+        }
+    }
+
+    #[inline]
+    async fn insert_record(&self, record: CacheRecord) {
+        self.insert_records(vec![record]).await;
+    }
+
+    async fn insert_records(&self, records: Vec<CacheRecord>) {
+        let records: Vec<CacheRecord> = records.into_iter().filter(|record| !record.get_ttl().is_zero()).collect();
+        self.insert_local(records.clone()).await;
+
+        // Records in the same set are written together, in one request to the store.
+        let mut record_sets: HashMap<String, Vec<CacheRecord>> = HashMap::new();
+        for record in records {
+            let key = self.key(record.get_name(), record.get_rtype(), record.get_rclass());
+            record_sets.entry(key).or_default().push(record);
+        }
+        join_all(record_sets.into_iter().map(|(key, records)| async move {
+            if let Err(error) = self.merge_remote(&key, records).await {
+                warn!("{error} when setting '{key}'");
+            }
+        })).await;
+    }
+
+    /// Only the local copy is cleaned. The store discards record sets once every record in them
+    /// has expired.
+    #[inline]
+    async fn clean(&self) {
+        AsyncMainCache::clean(&self.local).await;
+    }
+}
+
+#[cfg(feature = "redis")]
+mod redis_store {
+    use std::time::Duration;
+
+    use async_trait::async_trait;
+    use redis::{aio::ConnectionManager, AsyncCommands, Client, RedisError};
+
+    use super::{RemoteStore, RemoteStoreError};
+
+    impl From<RedisError> for RemoteStoreError {
+        fn from(error: RedisError) -> Self {
+            Self::Unavailable(error.to_string())
+        }
+    }
+
+    /// A remote store backed by Redis. The connection is re-established automatically if it is
+    /// lost.
+    #[derive(Clone)]
+    pub struct RedisStore {
+        connection: ConnectionManager,
+    }
+
+    impl RedisStore {
+        /// Connects to the Redis server at the URL, for example "redis://127.0.0.1:6379/".
+        pub async fn connect(url: &str) -> Result<Self, RemoteStoreError> {
+            let client = Client::open(url)?;
+            let connection = ConnectionManager::new(client).await?;
+            Ok(Self { connection })
+        }
+    }
+
+    #[async_trait]
+    impl RemoteStore for RedisStore {
+        async fn get_fields(&self, key: &str) -> Result<Vec<Vec<u8>>, RemoteStoreError> {
+            let mut connection = self.connection.clone();
+            Ok(connection.hvals(key).await?)
+        }
+
+        /// The fields are set with HSET. The expiration is set with NX if the key does not have
+        /// one yet and raised with GT otherwise, which needs Redis 7.0 or later.
+        async fn set_fields(&self, key: &str, fields: Vec<(Vec<u8>, Vec<u8>)>, ttl: Duration) -> Result<(), RemoteStoreError> {
+            let mut connection = self.connection.clone();
+            // Redis rejects an expiration of 0.
+            let ttl = ttl.as_secs().max(1);
+            redis::pipe()
+                .atomic()
+                .hset_multiple(key, &fields).ignore()
+                .cmd("EXPIRE").arg(key).arg(ttl).arg("NX").ignore()
+                .cmd("EXPIRE").arg(key).arg(ttl).arg("GT").ignore()
+                .exec_async(&mut connection).await?;
+            Ok(())
+        }
+    }
+}
+
+#[cfg(feature = "redis")]
+pub use redis_store::RedisStore;
+
+#[cfg(test)]
+mod remote_cache_tests {
+    use std::{collections::HashMap, net::Ipv4Addr, sync::{atomic::{AtomicBool, AtomicUsize, Ordering}, Arc, Mutex}, time::{Duration, Instant}};
+
+    use async_trait::async_trait;
+    use dns_lib::{interface::cache::{main_cache::AsyncMainCache, CacheMeta, CacheQuery, CacheRecord, CacheResponse, MetaAuth}, query::question::Question, resource_record::{rclass::RClass, resource_record::{RecordData, ResourceRecord}, rtype::RType, time::Time, types::a::A}, types::c_domain_name::CDomainName};
+
+    use super::{RemoteCache, RemoteStore, RemoteStoreError};
+
+    /// A store that keeps everything in memory and never expires anything.
+    #[derive(Default)]
+    struct MemoryStore {
+        keys: Mutex<HashMap<String, HashMap<Vec<u8>, Vec<u8>>>>,
+        unavailable: AtomicBool,
+        gets: AtomicUsize,
+    }
+
+    impl MemoryStore {
+        fn check_available(&self) -> Result<(), RemoteStoreError> {
+            match self.unavailable.load(Ordering::Acquire) {
+                true => Err(RemoteStoreError::Unavailable("the store is down".to_string())),
+                false => Ok(()),
+            }
+        }
+    }
+
+    #[async_trait]
+    impl RemoteStore for MemoryStore {
+        async fn get_fields(&self, key: &str) -> Result<Vec<Vec<u8>>, RemoteStoreError> {
+            self.check_available()?;
+            self.gets.fetch_add(1, Ordering::AcqRel);
+            let keys = self.keys.lock().unwrap();
+            Ok(keys.get(key).map(|fields| fields.values().cloned().collect()).unwrap_or_default())
+        }
+
+        async fn set_fields(&self, key: &str, fields: Vec<(Vec<u8>, Vec<u8>)>, _ttl: Duration) -> Result<(), RemoteStoreError> {
+            self.check_available()?;
+            let mut keys = self.keys.lock().unwrap();
+            keys.entry(key.to_string()).or_default().extend(fields);
+            Ok(())
+        }
+    }
+
+    fn record(address: Ipv4Addr, ttl: u32) -> CacheRecord {
+        CacheRecord {
+            meta: CacheMeta { auth: MetaAuth::NotAuthoritative, insertion_time: Instant::now(), original_ttl: None },
+            record: ResourceRecord::new(CDomainName::from_utf8("www.example.").unwrap(), RClass::Internet, Time::from_secs(ttl), RecordData::A(A::new(address))),
+        }
+    }
+
+    async fn get(cache: &impl AsyncMainCache) -> Vec<CacheRecord> {
+        let question = Question::new(CDomainName::from_utf8("www.example.").unwrap(), RType::A, RClass::Internet);
+        match cache.get(&CacheQuery { authoritative: false, question: &question }).await {
+            CacheResponse::Records(records) => records,
+            CacheResponse::Err(rcode) => panic!("The cache responded with {rcode}"),
+        }
+    }
+
+    const FIRST: Ipv4Addr = Ipv4Addr::new(192, 0, 2, 1);
+    const SECOND: Ipv4Addr = Ipv4Addr::new(192, 0, 2, 2);
+
+    #[tokio::test]
+    async fn local_hits_do_not_reach_the_store() {
+        let store = Arc::new(MemoryStore::default());
+        let cache = RemoteCache::new(store.clone());
+        cache.insert_record(record(FIRST, 300)).await;
+
+        assert_eq!(get(&cache).await.len(), 1);
+        assert_eq!(store.gets.load(Ordering::Acquire), 0);
+    }
+
+    #[tokio::test]
+    async fn misses_are_filled_from_the_store() {
+        let store = Arc::new(MemoryStore::default());
+        let writer = RemoteCache::new(store.clone());
+        let reader = RemoteCache::new(store.clone());
+        writer.insert_record(record(FIRST, 300)).await;
+
+        let records = get(&reader).await;
+        assert_eq!(records.len(), 1);
+        assert_eq!(store.gets.load(Ordering::Acquire), 1);
+        // The records are kept locally, but only for as long as the local limit allows.
+        let local_records = get(reader.local()).await;
+        assert_eq!(local_records.len(), 1);
+        assert_eq!(*local_records[0].get_ttl(), reader.config().local_ttl_limit);
+        assert_eq!(get(&reader).await.len(), 1);
+        assert_eq!(store.gets.load(Ordering::Acquire), 1);
+    }
+
+    #[tokio::test]
+    async fn records_from_different_resolvers_are_merged() {
+        let store = Arc::new(MemoryStore::default());
+        let first = RemoteCache::new(store.clone());
+        let second = RemoteCache::new(store.clone());
+        let reader = RemoteCache::new(store.clone());
+
+        // Neither resolver reads the set before writing to it, so neither write is lost.
+        first.insert_record(record(FIRST, 300)).await;
+        second.insert_record(record(SECOND, 300)).await;
+        // A record with the same data replaces the one that is stored.
+        second.insert_record(record(FIRST, 600)).await;
+
+        let records = get(&reader).await;
+        let ttl_of = |address: Ipv4Addr| records.iter()
+            .find(|record| record.get_rdata() == &RecordData::A(A::new(address)))
+            .map(|record| record.get_ttl().as_secs());
+        assert_eq!(records.len(), 2);
+        assert!(ttl_of(FIRST).is_some_and(|ttl| ttl > 300));
+        assert!(ttl_of(SECOND).is_some_and(|ttl| ttl <= 300));
+    }
+
+    #[tokio::test]
+    async fn store_errors_fall_back_to_the_local_cache() {
+        let store = Arc::new(MemoryStore::default());
+        store.unavailable.store(true, Ordering::Release);
+        let cache = RemoteCache::new(store.clone());
+
+        assert!(get(&cache).await.is_empty());
+        cache.insert_record(record(FIRST, 300)).await;
+        assert_eq!(get(&cache).await.len(), 1);
+
+        // Nothing was written to the store while it was down.
+        store.unavailable.store(false, Ordering::Release);
+        assert!(get(&RemoteCache::new(store.clone())).await.is_empty());
+    }
+}
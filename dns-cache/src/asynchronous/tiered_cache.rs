@@ -0,0 +1,234 @@
+use std::mem;
+
+use async_trait::async_trait;
+use dns_lib::{interface::cache::{main_cache::{AsyncMainCache, SharedAsyncMainCache}, CacheQuery, CacheRecord, CacheResponse, CacheStats}, query::question::Question, resource_record::rclass::RClass, types::c_domain_name::CDomainName};
+use futures::future::join_all;
+use tokio::sync::Mutex;
+
+/// When the records inserted into a `TieredCache` reach the tiers after the first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum WritePolicy {
+    /// Records are written to every tier as they are inserted.
+    WriteThrough,
+    /// Records are written to the first tier as they are inserted. The other tiers are written to
+    /// once `max_pending` records are waiting or when the cache is flushed or cleaned. Records
+    /// that are waiting are lost if the cache is dropped before then.
+    WriteBack { max_pending: usize },
+}
+
+/// Composes several caches into one. Queries are answered by the first tier that has records for
+/// them, so faster (and usually smaller) caches should come first. For example, an in-memory cache
+/// followed by a disk or remote cache.
+pub struct TieredCache {
+    tiers: Vec<SharedAsyncMainCache>,
+    write_policy: WritePolicy,
+    /// Copy the records found in a later tier into the earlier tiers so that the next query for
+    /// them is answered sooner.
+    promote_on_hit: bool,
+    /// The records waiting to be written to the later tiers under `WritePolicy::WriteBack`.
+    pending: Mutex<Vec<CacheRecord>>,
+}
+
+impl TieredCache {
+    /// A write-through cache that promotes records on hit.
+    #[inline]
+    pub fn new(tiers: Vec<SharedAsyncMainCache>) -> Self {
+        Self::with_policy(tiers, WritePolicy::WriteThrough, true)
+    }
+
+    #[inline]
+    pub fn with_policy(tiers: Vec<SharedAsyncMainCache>, write_policy: WritePolicy, promote_on_hit: bool) -> Self {
+        Self { tiers, write_policy, promote_on_hit, pending: Mutex::new(Vec::new()) }
+    }
+
+    #[inline]
+    pub fn tiers(&self) -> &[SharedAsyncMainCache] { &self.tiers }
+
+    #[inline]
+    pub fn write_policy(&self) -> WritePolicy { self.write_policy }
+
+    #[inline]
+    pub fn promote_on_hit(&self) -> bool { self.promote_on_hit }
+
+    /// The number of records waiting to be written to the later tiers.
+    #[inline]
+    pub async fn pending(&self) -> usize {
+        let r_pending = self.pending.lock().await;
+        let pending = r_pending.len();
+        drop(r_pending);
+        pending
+    }
+
+    /// Writes the records waiting under `WritePolicy::WriteBack` to the later tiers.
+    pub async fn flush(&self) {
+        let mut w_pending = self.pending.lock().await;
+        let pending = mem::take(&mut *w_pending);
+        drop(w_pending);
+        if !pending.is_empty() {
+            insert_into(self.tiers.iter().skip(1), pending).await;
+        }
+    }
+}
+
+/// Copies the records with their TTLs set to the time they have left so that they do not live
+/// longer in the tier they are promoted to than in the one they came from.
+fn with_remaining_ttls(records: &[CacheRecord]) -> Vec<CacheRecord> {
+    records.iter()
+        .cloned()
+        .map(|mut record| {
+            record.decay_ttl();
+            record
+        })
+        .collect()
+}
+
+async fn insert_into(tiers: impl Iterator<Item = &SharedAsyncMainCache>, records: Vec<CacheRecord>) {
+    join_all(tiers.map(|tier| tier.insert_records(records.clone()))).await;
+}
+
+#[async_trait]
+impl AsyncMainCache for TieredCache {
+    async fn get(&self, query: &CacheQuery) -> CacheResponse {
+        let mut first_response = None;
+        for (index, tier) in self.tiers.iter().enumerate() {
+            match tier.get(query).await {
+                CacheResponse::Records(records) if !records.is_empty() => {
+                    if self.promote_on_hit && (index > 0) {
+                        insert_into(self.tiers[..index].iter(), with_remaining_ttls(&records)).await;
+                    }
+                    return CacheResponse::Records(records);
+                },
+                response => {
+                    first_response.get_or_insert(response);
+                },
+            }
+        }
+        first_response.unwrap_or(CacheResponse::Records(Vec::new()))
+    }
+
+    #[inline]
+    async fn insert_record(&self, record: CacheRecord) {
+        self.insert_records(vec![record]).await;
+    }
+
+    async fn insert_records(&self, records: Vec<CacheRecord>) {
+        match self.write_policy {
+            WritePolicy::WriteThrough => insert_into(self.tiers.iter(), records).await,
+            WritePolicy::WriteBack { max_pending } => {
+                let Some(first_tier) = self.tiers.first() else {
+                    return;
+                };
+                let mut w_pending = self.pending.lock().await;
+                w_pending.extend(records.iter().cloned());
+                let should_flush = w_pending.len() >= max_pending;
+                drop(w_pending);
+
+                first_tier.insert_records(records).await;
+                if should_flush {
+                    self.flush().await;
+                }
+            },
+        }
+    }
+
+    async fn clean(&self) {
+        self.flush().await;
+        join_all(self.tiers.iter().map(|tier| tier.clean())).await;
+    }
+
+    /// The counts for the first tier, which has the records that are answered the soonest.
+    #[inline]
+    async fn stats(&self) -> Option<CacheStats> {
+        self.tiers.first()?.stats().await
+    }
+
+    /// Removes the records from every tier. Records waiting to be written back are written first
+    /// so that they cannot reappear afterwards. The count is the total across the tiers that
+    /// support removal.
+    async fn remove(&self, question: &Question) -> Option<usize> {
+        self.flush().await;
+        sum_removed(join_all(self.tiers.iter().map(|tier| tier.remove(question))).await)
+    }
+
+    async fn remove_subtree(&self, apex: &CDomainName, qclass: RClass) -> Option<usize> {
+        self.flush().await;
+        sum_removed(join_all(self.tiers.iter().map(|tier| tier.remove_subtree(apex, qclass))).await)
+    }
+}
+
+/// `None` only if none of the tiers support removal.
+fn sum_removed(removed: Vec<Option<usize>>) -> Option<usize> {
+    removed.into_iter().flatten().reduce(|total, removed| total + removed)
+}
+
+#[cfg(test)]
+mod tiered_cache_tests {
+    use std::{net::Ipv4Addr, sync::Arc, time::Instant};
+
+    use dns_lib::{interface::cache::{main_cache::AsyncMainCache, CacheMeta, CacheQuery, CacheRecord, CacheResponse, MetaAuth}, query::question::Question, resource_record::{rclass::RClass, resource_record::{RecordData, ResourceRecord}, rtype::RType, time::Time, types::a::A}, types::c_domain_name::CDomainName};
+
+    use crate::asynchronous::async_main_cache::AsyncMainTreeCache;
+
+    use super::{TieredCache, WritePolicy};
+
+    /// A record that was cached with a TTL of 300 seconds and has 200 of them left.
+    fn partly_aged_record() -> CacheRecord {
+        CacheRecord {
+            meta: CacheMeta { auth: MetaAuth::NotAuthoritative, insertion_time: Instant::now(), original_ttl: Some(Time::from_secs(300)) },
+            record: ResourceRecord::new(CDomainName::from_utf8("www.example.").unwrap(), RClass::Internet, Time::from_secs(200), RecordData::A(A::new(Ipv4Addr::new(192, 0, 2, 1)))),
+        }
+    }
+
+    async fn get(cache: &impl AsyncMainCache) -> Vec<CacheRecord> {
+        let question = Question::new(CDomainName::from_utf8("www.example.").unwrap(), RType::A, RClass::Internet);
+        match cache.get(&CacheQuery { authoritative: false, question: &question }).await {
+            CacheResponse::Records(records) => records,
+            CacheResponse::Err(rcode) => panic!("The cache responded with {rcode}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn hits_in_later_tiers_are_promoted() {
+        let (first, second) = (Arc::new(AsyncMainTreeCache::new()), Arc::new(AsyncMainTreeCache::new()));
+        let cache = TieredCache::new(vec![first.clone(), second.clone()]);
+        AsyncMainCache::insert_record(&*second, partly_aged_record()).await;
+
+        assert_eq!(get(&cache).await.len(), 1);
+        assert_eq!(get(&*first).await.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn hits_are_not_promoted_unless_asked() {
+        let (first, second) = (Arc::new(AsyncMainTreeCache::new()), Arc::new(AsyncMainTreeCache::new()));
+        let cache = TieredCache::with_policy(vec![first.clone(), second.clone()], WritePolicy::WriteThrough, false);
+        AsyncMainCache::insert_record(&*second, partly_aged_record()).await;
+
+        assert_eq!(get(&cache).await.len(), 1);
+        assert!(get(&*first).await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn ttls_are_preserved_across_tiers() {
+        let (first, second) = (Arc::new(AsyncMainTreeCache::new()), Arc::new(AsyncMainTreeCache::new()));
+        let cache = TieredCache::new(vec![first.clone(), second.clone()]);
+
+        // A promoted record has the time it had left in the tier it came from, not its original
+        // TTL, so it does not outlive the record it was copied from.
+        AsyncMainCache::insert_record(&*second, partly_aged_record()).await;
+        get(&cache).await;
+        let promoted = get(&*first).await;
+        assert!(promoted[0].get_ttl().as_secs() <= 200);
+        assert_eq!(promoted[0].original_ttl(), Time::from_secs(300));
+
+        // Records written through reach every tier with the same TTL.
+        let record = CacheRecord {
+            meta: CacheMeta { auth: MetaAuth::NotAuthoritative, insertion_time: Instant::now(), original_ttl: None },
+            record: ResourceRecord::new(CDomainName::from_utf8("www.example.").unwrap(), RClass::Internet, Time::from_secs(600), RecordData::A(A::new(Ipv4Addr::new(192, 0, 2, 2)))),
+        };
+        cache.insert_record(record.clone()).await;
+        for tier in [&first, &second] {
+            let written = get(&**tier).await.into_iter().find(|cached| cached.get_rdata() == record.get_rdata()).unwrap();
+            assert!((599..=600).contains(&written.get_ttl().as_secs()));
+        }
+    }
+}
@@ -1,2 +1,3 @@
 pub mod synchronous;
 pub mod asynchronous;
+pub mod record_codec;
@@ -0,0 +1,135 @@
+use std::{error::Error, fmt::Display, time::{Instant, SystemTime, UNIX_EPOCH}};
+
+use dns_lib::{interface::cache::{CacheMeta, CacheRecord, MetaAuth}, resource_record::{resource_record::{RecordData, ResourceRecord}, time::Time}, serde::wire::{from_wire::FromWire, read_wire::{ReadWire, ReadWireError}, to_wire::ToWire, write_wire::{WriteWire, WriteWireError}}};
+
+/// Changes whenever the layout of the encoded records changes so that records written by an older
+/// (or newer) version are not misread.
+const CODEC_VERSION: u8 = 1;
+
+/// The bytes in front of each record: the authority, the expiration time, and the record length.
+const RECORD_HEADER_LENGTH: usize = 1 + 8 + 2;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum RecordCodecError {
+    UnknownVersion(u8),
+    UnknownAuth(u8),
+    Truncated,
+    Read(ReadWireError),
+    Write(WriteWireError),
+}
+impl Error for RecordCodecError {}
+impl Display for RecordCodecError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::UnknownVersion(version) => write!(f, "Cached records were encoded with unknown version {version}"),
+            Self::UnknownAuth(auth) => write!(f, "Cached record has unknown authority {auth}"),
+            Self::Truncated => write!(f, "Cached records were truncated"),
+            Self::Read(error) => write!(f, "{error}"),
+            Self::Write(error) => write!(f, "{error}"),
+        }
+    }
+}
+impl From<ReadWireError> for RecordCodecError {
+    fn from(error: ReadWireError) -> Self {
+        Self::Read(error)
+    }
+}
+impl From<WriteWireError> for RecordCodecError {
+    fn from(error: WriteWireError) -> Self {
+        Self::Write(error)
+    }
+}
+
+#[inline]
+const fn auth_to_byte(auth: MetaAuth) -> u8 {
+    match auth {
+        MetaAuth::Authoritative => 0,
+        MetaAuth::NotAuthoritative => 1,
+        MetaAuth::NotAuthoritativeBootstrap => 2,
+    }
+}
+
+#[inline]
+const fn auth_from_byte(byte: u8) -> Result<MetaAuth, RecordCodecError> {
+    match byte {
+        0 => Ok(MetaAuth::Authoritative),
+        1 => Ok(MetaAuth::NotAuthoritative),
+        2 => Ok(MetaAuth::NotAuthoritativeBootstrap),
+        _ => Err(RecordCodecError::UnknownAuth(byte)),
+    }
+}
+
+/// The number of seconds the record has left before it expires.
+#[inline]
+pub fn remaining_ttl(record: &CacheRecord) -> u32 {
+    record.remaining_ttl().as_secs()
+}
+
+/// Encodes the records so that they can be stored outside of this process.
+///
+/// Records only know when they were inserted relative to this process's clock, so each one is
+/// stored with the wall clock time at which it expires. When they are decoded (possibly by a
+/// different process) their TTLs are set to the time that is left. Records that have already
+/// expired are left out.
+pub fn encode_records<'a>(records: impl IntoIterator<Item = &'a CacheRecord>, now: SystemTime) -> Result<Vec<u8>, RecordCodecError> {
+    let now = now.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+    let mut bytes = vec![CODEC_VERSION];
+    for record in records {
+        let remaining_ttl = remaining_ttl(record);
+        if remaining_ttl == 0 {
+            continue;
+        }
+        let mut buffer = vec![0; usize::from(record.record.serial_length())];
+        let mut wire = WriteWire::from_bytes(&mut buffer);
+        record.record.to_wire_format(&mut wire, &mut None)?;
+        let record_length = wire.current_len();
+        let Ok(wire_record_length) = u16::try_from(record_length) else {
+            return Err(RecordCodecError::Write(WriteWireError::OverflowError(format!("record is {record_length} bytes long"))));
+        };
+
+        bytes.reserve(RECORD_HEADER_LENGTH + record_length);
+        bytes.push(auth_to_byte(record.meta.auth));
+        bytes.extend_from_slice(&(now + u64::from(remaining_ttl)).to_be_bytes());
+        bytes.extend_from_slice(&wire_record_length.to_be_bytes());
+        bytes.extend_from_slice(&buffer[..record_length]);
+    }
+    Ok(bytes)
+}
+
+/// Decodes records that were encoded by `encode_records()`. Each record's TTL is set to the time
+/// it has left as of `now` and records that have since expired are left out.
+pub fn decode_records(bytes: &[u8], now: SystemTime) -> Result<Vec<CacheRecord>, RecordCodecError> {
+    let now = now.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+    let insertion_time = Instant::now();
+    let Some((version, mut bytes)) = bytes.split_first() else {
+        return Ok(Vec::new());
+    };
+    if *version != CODEC_VERSION {
+        return Err(RecordCodecError::UnknownVersion(*version));
+    }
+
+    let mut records = Vec::new();
+    while !bytes.is_empty() {
+        if bytes.len() < RECORD_HEADER_LENGTH {
+            return Err(RecordCodecError::Truncated);
+        }
+        let (header, rest) = bytes.split_at(RECORD_HEADER_LENGTH);
+        let auth = auth_from_byte(header[0])?;
+        let expires_at = u64::from_be_bytes(header[1..9].try_into().unwrap_or_default());
+        let record_length = usize::from(u16::from_be_bytes([header[9], header[10]]));
+        if rest.len() < record_length {
+            return Err(RecordCodecError::Truncated);
+        }
+        let (record_bytes, rest) = rest.split_at(record_length);
+        bytes = rest;
+
+        let remaining_ttl = expires_at.saturating_sub(now);
+        if remaining_ttl == 0 {
+            continue;
+        }
+        let mut record = ResourceRecord::<RecordData>::from_wire_format(&mut ReadWire::from_bytes(record_bytes))?;
+        record.set_ttl(Time::new(remaining_ttl.min(u64::from(u32::MAX)) as u32));
+        records.push(CacheRecord { meta: CacheMeta { auth, insertion_time, original_ttl: None }, record });
+    }
+    Ok(records)
+}
@@ -118,9 +118,13 @@ impl MainTreeCache {
 }
 
 impl MainCache for MainTreeCache {
+    /// The records are given the time they have left in the cache as their TTL.
     fn get(&self, query: &CacheQuery) -> CacheResponse {
         match self.get_records(&query) {
-            Ok(records) => CacheResponse::Records(records),
+            Ok(mut records) => {
+                records.iter_mut().for_each(CacheRecord::decay_ttl);
+                CacheResponse::Records(records)
+            },
             Err(_) => CacheResponse::Err(RCode::ServFail),
         }
     }
@@ -134,6 +138,9 @@ impl MainCache for MainTreeCache {
     }
 
     fn clean(&mut self) {
-        todo!()
+        self.cache.for_each_node_mut(|node| node.records.retain(|_, records| {
+            records.retain(|record| !record.is_expired());
+            !records.is_empty()
+        }));
     }
 }
@@ -147,6 +147,15 @@ impl<Records> TreeCache<Records> {
         return Ok(parent_node.children.remove(&last_label));
     }
 
+    /// Calls `f` on every node in the cache, across all classes.
+    pub fn for_each_node_mut(&mut self, mut f: impl FnMut(&mut TreeNode<Records>)) {
+        let mut unvisited: Vec<_> = self.root_nodes.values_mut().collect();
+        while let Some(node) = unvisited.pop() {
+            f(node);
+            unvisited.extend(node.children.values_mut());
+        }
+    }
+
     #[inline]
     pub fn iter(&self) -> impl Iterator<Item = &TreeNode<Records>> {
         TreeRootIterator::new(&self)
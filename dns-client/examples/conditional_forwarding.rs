@@ -0,0 +1,106 @@
+//! Forwards the queries for one domain, such as an internal zone, to a different upstream than
+//! everything else. A middleware hands the queries under the domain to a second client before they
+//! reach the default client's resolver. Each client keeps its own cache, so answers from the
+//! internal upstream are never given out for public names.
+//!
+//! ```text
+//! cargo run --example conditional_forwarding -- <domain> <domain-upstream> <default-upstream> <name> [<name>...]
+//! cargo run --example conditional_forwarding -- corp.example. 10.0.0.53:53 9.9.9.9:53 intranet.corp.example. example.com.
+//! ```
+
+use std::{env, fmt::Debug, net::SocketAddr, process::ExitCode, sync::Arc};
+
+use async_trait::async_trait;
+use dns_client::prelude::*;
+
+/// Sends the queries for names under `domain` to `client` instead of the rest of the chain.
+struct ConditionalForwarder {
+    domain: CDomainName,
+    client: Arc<DNSAsyncClient>,
+}
+
+impl Debug for ConditionalForwarder {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ConditionalForwarder")
+            .field("domain", &self.domain)
+            .field("forwarders", &self.client.config().forwarders)
+            .finish()
+    }
+}
+
+#[async_trait]
+impl Middleware for ConditionalForwarder {
+    async fn handle(&self, context: Context, next: Next<'_>) -> Response {
+        if self.domain.is_parent_domain_of(context.query().qname()) {
+            self.client.clone().query(context).await
+        } else {
+            next.run(context).await
+        }
+    }
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    let args = env::args().skip(1).collect::<Vec<_>>();
+    let [domain, domain_upstream, default_upstream, names @ ..] = args.as_slice() else {
+        eprintln!("usage: conditional_forwarding <domain> <domain-upstream> <default-upstream> <name> [<name>...]");
+        return ExitCode::FAILURE;
+    };
+    if names.is_empty() {
+        eprintln!("usage: conditional_forwarding <domain> <domain-upstream> <default-upstream> <name> [<name>...]");
+        return ExitCode::FAILURE;
+    }
+    let domain = match CDomainName::from_utf8(domain) {
+        Ok(domain) => domain,
+        Err(error) => {
+            eprintln!("{error}");
+            return ExitCode::FAILURE;
+        },
+    };
+    let (domain_upstream, default_upstream) = match (domain_upstream.parse::<SocketAddr>(), default_upstream.parse::<SocketAddr>()) {
+        (Ok(domain_upstream), Ok(default_upstream)) => (domain_upstream, default_upstream),
+        (Err(error), _) | (_, Err(error)) => {
+            eprintln!("invalid upstream address: {error}");
+            return ExitCode::FAILURE;
+        },
+    };
+
+    let domain_client = Arc::new(DNSAsyncClient::with_config(
+        Arc::new(AsyncMainTreeCache::new()),
+        ClientConfig::forwarding(vec![ForwardUpstream::Plain(domain_upstream)]),
+    ).await);
+    let mut client = DNSAsyncClient::with_config(
+        Arc::new(AsyncMainTreeCache::new()),
+        ClientConfig::forwarding(vec![ForwardUpstream::Plain(default_upstream)]),
+    ).await;
+    // The forwarder goes after the default query logger so that every query is logged once.
+    let mut middleware = MiddlewareChain::default();
+    middleware.push(ConditionalForwarder { domain, client: domain_client.clone() });
+    client.set_middleware(middleware);
+    let client = Arc::new(client);
+
+    let mut status = ExitCode::SUCCESS;
+    for name in names {
+        let qname = match CDomainName::from_utf8(name) {
+            Ok(qname) => qname,
+            Err(error) => {
+                eprintln!("{error}");
+                status = ExitCode::FAILURE;
+                continue;
+            },
+        };
+        let question = Question::new(qname, RType::A, RClass::Internet);
+        match client.clone().query(Context::new(question, QNameMinimization::None)).await {
+            Response::Answer(answer) if answer.answer.is_empty() => println!("{name} has no A records"),
+            Response::Answer(answer) => println!("{answer}"),
+            Response::Error(error) => {
+                eprintln!("{name}: {error}");
+                status = ExitCode::FAILURE;
+            },
+        }
+    }
+
+    client.close().await;
+    domain_client.close().await;
+    status
+}
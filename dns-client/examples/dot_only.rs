@@ -0,0 +1,108 @@
+//! Resolves names only over DNS over TLS. Every query is forwarded to the upstream over an
+//! authenticated TLS connection that stays open between queries, and nothing is ever sent in
+//! cleartext. The upstream's certificate is checked against the platform's trust store, or against
+//! SPKI pins if any are given.
+//!
+//! ```text
+//! cargo run --example dot_only -- [--pin <base64-spki-sha256>]... <address:port> <server-name> <name> [<name>...]
+//! cargo run --example dot_only -- 9.9.9.9:853 dns.quad9.net example.com.
+//! ```
+
+use std::{env, net::SocketAddr, process::ExitCode, sync::Arc};
+
+use dns_client::prelude::*;
+use network::tls_config::TlsVerification;
+
+const USAGE: &str = "usage: dot_only [--pin <base64-spki-sha256>]... <address:port> <server-name> <name> [<name>...]";
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    let mut pins = Vec::new();
+    let mut positional = Vec::new();
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--pin" => match args.next() {
+                Some(pin) => pins.push(pin),
+                None => {
+                    eprintln!("missing pin after '--pin'\n\n{USAGE}");
+                    return ExitCode::FAILURE;
+                },
+            },
+            _ => positional.push(arg),
+        }
+    }
+    let [address, server_name, names @ ..] = positional.as_slice() else {
+        eprintln!("{USAGE}");
+        return ExitCode::FAILURE;
+    };
+    if names.is_empty() {
+        eprintln!("{USAGE}");
+        return ExitCode::FAILURE;
+    }
+    let address = match address.parse::<SocketAddr>() {
+        Ok(address) => address,
+        Err(error) => {
+            eprintln!("invalid upstream address: {error}");
+            return ExitCode::FAILURE;
+        },
+    };
+    let server_name = match CDomainName::from_utf8(server_name) {
+        Ok(server_name) => server_name,
+        Err(error) => {
+            eprintln!("{error}");
+            return ExitCode::FAILURE;
+        },
+    };
+    let verification = if pins.is_empty() {
+        TlsVerification::Platform
+    } else {
+        match TlsVerification::spki_pins_from_base64(pins.iter().map(String::as_str)) {
+            Ok(verification) => verification,
+            Err(error) => {
+                eprintln!("invalid pin: {error}");
+                return ExitCode::FAILURE;
+            },
+        }
+    };
+    // Opportunistic and insecure verification would allow the queries to be read or answered by
+    // anyone on the path, which defeats the purpose of this example.
+    assert!(verification.is_authenticated());
+
+    let upstream = EncryptedUpstream {
+        address,
+        protocol: QueryOpt::Tls,
+        server_name,
+        doh_path: None,
+        priority: 0,
+        verification,
+    };
+    let config = ClientConfig::forwarding(vec![ForwardUpstream::Encrypted(upstream)]);
+    let client = Arc::new(DNSAsyncClient::with_config(Arc::new(AsyncMainTreeCache::new()), config).await);
+
+    let mut status = ExitCode::SUCCESS;
+    for name in names {
+        let qname = match CDomainName::from_utf8(name) {
+            Ok(qname) => qname,
+            Err(error) => {
+                eprintln!("{error}");
+                status = ExitCode::FAILURE;
+                continue;
+            },
+        };
+        for rtype in [RType::A, RType::AAAA] {
+            let question = Question::new(qname.clone(), rtype, RClass::Internet);
+            match client.clone().query(Context::new(question, QNameMinimization::None)).await {
+                Response::Answer(answer) if answer.answer.is_empty() => println!("{name} has no {rtype} records"),
+                Response::Answer(answer) => println!("{answer}"),
+                Response::Error(error) => {
+                    eprintln!("{name} {rtype}: {error}");
+                    status = ExitCode::FAILURE;
+                },
+            }
+        }
+    }
+
+    client.close().await;
+    status
+}
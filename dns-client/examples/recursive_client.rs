@@ -0,0 +1,95 @@
+//! Embeds a recursive resolver in an application. The client is primed from the root hints and
+//! then resolves each name on the command line from the root down, caching what it learns along
+//! the way. With `--trace`, the queries that were sent to name servers for each answer are printed
+//! as well, similar to `dig +trace`.
+//!
+//! ```text
+//! cargo run --example recursive_client -- [--hints <root-hints-file>] [--trace] <domain> [<domain>...]
+//! ```
+
+use std::{env, process::ExitCode, sync::Arc};
+
+use dns_client::{prelude::*, RootHintsConfig};
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    let mut hints = RootHintsConfig::default();
+    let mut trace = false;
+    let mut domains = Vec::new();
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--hints" => match args.next() {
+                Some(path) => hints.hints_path = path.into(),
+                None => {
+                    eprintln!("missing file name after '--hints'");
+                    return ExitCode::FAILURE;
+                },
+            },
+            "--trace" => trace = true,
+            _ => domains.push(arg),
+        }
+    }
+    if domains.is_empty() {
+        eprintln!("usage: recursive_client [--hints <root-hints-file>] [--trace] <domain> [<domain>...]");
+        return ExitCode::FAILURE;
+    }
+
+    // One cache can be shared by any number of clients. It is kept for as long as the
+    // application runs so that later lookups are answered from it.
+    let cache: SharedAsyncMainCache = Arc::new(AsyncMainTreeCache::new());
+    let client = Arc::new(DNSAsyncClient::new(cache).await);
+    match client.bootstrap_root_hints(&hints).await {
+        Ok(report) => println!("primed from {} ({} root name servers)", report.primed_from, report.name_servers.len()),
+        Err(error) => {
+            eprintln!("failed to prime the root hints: {error}");
+            client.close().await;
+            return ExitCode::FAILURE;
+        },
+    }
+
+    let mut status = ExitCode::SUCCESS;
+    for domain in domains {
+        let qname = match CDomainName::from_utf8(&domain) {
+            Ok(qname) if qname.is_fully_qualified() => qname,
+            Ok(_) => {
+                eprintln!("the domain '{domain}' must be fully qualified");
+                status = ExitCode::FAILURE;
+                continue;
+            },
+            Err(error) => {
+                eprintln!("{error}");
+                status = ExitCode::FAILURE;
+                continue;
+            },
+        };
+        for rtype in [RType::A, RType::AAAA] {
+            let question = Question::new(qname.clone(), rtype, RClass::Internet);
+            let mut context = Context::new(question, QNameMinimization::None);
+            if trace {
+                context = context.with_trace(Arc::new(QueryTrace::new()));
+            }
+            match client.clone().query(context).await {
+                Response::Answer(answer) => {
+                    if let Some(trace) = &answer.trace {
+                        print!("{}", trace.to_text());
+                    }
+                    if answer.answer.is_empty() {
+                        println!("{domain} has no {rtype} records");
+                    } else {
+                        println!("{answer}");
+                    }
+                },
+                Response::Error(error) => {
+                    eprintln!("{domain} {rtype}: {error}");
+                    status = ExitCode::FAILURE;
+                },
+            }
+        }
+    }
+
+    let stats = client.stats().await;
+    println!("{stats:?}");
+    client.close().await;
+    status
+}
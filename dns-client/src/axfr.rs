@@ -0,0 +1,545 @@
+use std::{collections::VecDeque, error::Error, fmt::Display, io, mem, net::SocketAddr, sync::Arc, time::Duration};
+
+use dns_lib::{query::{message::Message, qr::QR, question::Question}, resource_record::{rclass::RClass, rcode::RCode, resource_record::{RecordData, ResourceRecord}, rtype::RType, time::Time, types::soa::SOA}, serde::wire::{from_wire::FromWire, read_wire::{ReadWire, ReadWireError}, to_wire::ToWire, write_wire::{WriteWire, WriteWireError}}, tsig::{MacCalculator, TsigError, TsigExchange, TsigKey}, types::c_domain_name::{CDomainName, CmpDomainName, CompressionMap}};
+use futures::Stream;
+use log::warn;
+use tokio::{io::{AsyncReadExt, AsyncWriteExt}, net::TcpStream, time::timeout};
+
+/// How long to wait to connect to the primary and for each message of the transfer, by default.
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+
+#[derive(Debug)]
+pub enum TransferError {
+    Io(io::Error),
+    /// The primary did not connect or send the next message in time.
+    Timeout,
+    Encode(WriteWireError),
+    Decode(ReadWireError),
+    Tsig(TsigError),
+    /// The primary answered with an error instead of the zone.
+    Rejected(RCode),
+    /// A message is not an answer to the transfer query.
+    NotAnAnswer,
+    /// The transfer does not start with the zone's SOA record.
+    MissingSoa,
+    /// The SOA record that ends the transfer is not the one that it started with.
+    SoaMismatch { expected: u32, received: u32 },
+    /// The primary closed the connection before the SOA record that ends the transfer.
+    UnexpectedEnd,
+}
+impl Error for TransferError {}
+impl Display for TransferError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Io(error) => write!(f, "{error} during zone transfer"),
+            Self::Timeout => write!(f, "timeout during zone transfer"),
+            Self::Encode(error) => write!(f, "{error} while writing the transfer query"),
+            Self::Decode(error) => write!(f, "{error} while reading a transfer message"),
+            Self::Tsig(error) => write!(f, "{error}"),
+            Self::Rejected(rcode) => write!(f, "the primary refused the transfer with {rcode}"),
+            Self::NotAnAnswer => write!(f, "a transfer message is not an answer to the transfer query"),
+            Self::MissingSoa => write!(f, "the transfer does not start with the zone's SOA record"),
+            Self::SoaMismatch { expected, received } => write!(f, "the transfer started with serial {expected} but ended with serial {received}"),
+            Self::UnexpectedEnd => write!(f, "the connection closed before the end of the transfer"),
+        }
+    }
+}
+impl From<io::Error> for TransferError {
+    fn from(error: io::Error) -> Self {
+        match error.kind() {
+            io::ErrorKind::UnexpectedEof => Self::UnexpectedEnd,
+            _ => Self::Io(error),
+        }
+    }
+}
+impl From<WriteWireError> for TransferError {
+    fn from(error: WriteWireError) -> Self {
+        Self::Encode(error)
+    }
+}
+impl From<ReadWireError> for TransferError {
+    fn from(error: ReadWireError) -> Self {
+        Self::Decode(error)
+    }
+}
+impl From<TsigError> for TransferError {
+    fn from(error: TsigError) -> Self {
+        Self::Tsig(error)
+    }
+}
+
+/// What the primary sent in answer to the transfer query.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TransferKind {
+    /// Every record of the zone (RFC 5936). An IXFR query can be answered this way too.
+    Full,
+    /// The differences between the client's version of the zone and the current one (RFC 1995).
+    Incremental,
+    /// The client's version of the zone is current. Nothing is transferred.
+    UpToDate,
+}
+
+/// A record from a transfer. Full transfers only have `Record`s. Incremental transfers are made of
+/// `Deleted` records followed by `Added` ones for each version of the zone in turn, starting with
+/// the old SOA record and the new one respectively.
+#[derive(Debug, Clone, PartialEq, Hash)]
+pub enum TransferRecord {
+    Record(ResourceRecord),
+    Deleted(ResourceRecord),
+    Added(ResourceRecord),
+}
+
+/// Transfers a zone from its primary name server over TCP.
+///
+/// ```no_run
+/// # async fn transfer() -> Result<(), dns_client::axfr::TransferError> {
+/// use dns_client::axfr::ZoneTransfer;
+/// use dns_lib::types::c_domain_name::CDomainName;
+///
+/// let zone = ZoneTransfer::new("192.0.2.53:53".parse().unwrap(), CDomainName::from_utf8("example.com.").unwrap());
+/// let mut transfer = zone.axfr().await?;
+/// while let Some(record) = transfer.next_record().await {
+///     println!("{:?}", record?);
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub struct ZoneTransfer {
+    primary: SocketAddr,
+    zone: CDomainName,
+    timeout: Duration,
+    tsig: Option<(TsigKey, Arc<dyn MacCalculator>)>,
+}
+
+impl ZoneTransfer {
+    #[inline]
+    pub fn new(primary: SocketAddr, zone: CDomainName) -> Self {
+        Self { primary, zone, timeout: DEFAULT_TIMEOUT, tsig: None }
+    }
+
+    /// How long to wait to connect to the primary and for each message of the transfer.
+    #[inline]
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Signs the query with the key and requires every message of the transfer to be signed with
+    /// it as well, except for the ones that RFC 8945 allows to be left unsigned.
+    #[inline]
+    pub fn with_tsig(mut self, key: TsigKey, calculator: Arc<dyn MacCalculator>) -> Self {
+        self.tsig = Some((key, calculator));
+        self
+    }
+
+    /// Starts a full transfer of the zone.
+    pub async fn axfr(&self) -> Result<Transfer<'_>, TransferError> {
+        let query = Message::from(Question::new(self.zone.clone(), RType::AXFR, RClass::Internet));
+        self.start(query, None).await
+    }
+
+    /// Starts an incremental transfer of the changes made to the zone since the version with this
+    /// serial. The primary may send the whole zone instead, in which case the transfer's kind is
+    /// `TransferKind::Full`. If the primary does not implement IXFR, a full transfer is started
+    /// instead.
+    pub async fn ixfr(&self, serial: u32) -> Result<Transfer<'_>, TransferError> {
+        let mut query = Message::from(Question::new(self.zone.clone(), RType::IXFR, RClass::Internet));
+        // Only the serial of the SOA record is used by the primary.
+        // https://datatracker.ietf.org/doc/html/rfc1995#section-3
+        let soa = SOA::new(CDomainName::new_root(), CDomainName::new_root(), serial, Time::from_secs(0), Time::from_secs(0), Time::from_secs(0), 0);
+        query.authority.push(ResourceRecord::new(self.zone.clone(), RClass::Internet, Time::from_secs(0), RecordData::SOA(soa)));
+        match self.start(query, Some(serial)).await {
+            Err(TransferError::Rejected(RCode::NotImp | RCode::FormErr)) => {
+                warn!("Primary '{}' does not implement IXFR for '{}'. Falling back to AXFR", self.primary, self.zone);
+                self.axfr().await
+            },
+            result => result,
+        }
+    }
+
+    async fn start(&self, mut query: Message, requested_serial: Option<u32>) -> Result<Transfer<'_>, TransferError> {
+        query.id = rand::random();
+        let mut tsig = match &self.tsig {
+            Some((key, calculator)) => Some(TsigExchange::new(key, calculator.as_ref())?),
+            None => None,
+        };
+        if let Some(tsig) = &mut tsig {
+            tsig.sign(&mut query)?;
+        }
+
+        let mut buffer = vec![0_u8; u16::MAX as usize + 2];
+        let mut wire = WriteWire::from_bytes(&mut buffer[2..]);
+        query.to_wire_format(&mut wire, &mut Some(CompressionMap::new()))?;
+        let length = wire.current_len();
+        buffer[..2].copy_from_slice(&(length as u16).to_be_bytes());
+
+        let mut stream = timeout(self.timeout, TcpStream::connect(self.primary)).await.map_err(|_| TransferError::Timeout)??;
+        stream.write_all(&buffer[..length + 2]).await?;
+
+        let mut transfer = Transfer {
+            stream,
+            query,
+            zone: &self.zone,
+            timeout: self.timeout,
+            tsig,
+            requested_serial,
+            kind: None,
+            state: State::Start,
+            records: VecDeque::new(),
+            pending: VecDeque::new(),
+            messages: 0,
+        };
+        // The first message is read now so that a primary that refuses the transfer is reported
+        // here, rather than by the first record.
+        transfer.read_message().await?;
+        Ok(transfer)
+    }
+}
+
+#[derive(Debug)]
+enum State {
+    /// Waiting for the SOA record that starts the transfer.
+    Start,
+    /// Waiting for the record after the first SOA record, which tells the kind of transfer.
+    Second { first: ResourceRecord },
+    Full { serial: u32 },
+    Deleting { serial: u32 },
+    Adding { serial: u32 },
+    Done,
+}
+
+/// A zone transfer in progress. Records are read from the primary as they are needed, so the
+/// zone is never held in memory all at once. The transfer ends at the SOA record that matches the
+/// one it started with.
+pub struct Transfer<'a> {
+    stream: TcpStream,
+    query: Message,
+    zone: &'a CDomainName,
+    timeout: Duration,
+    tsig: Option<TsigExchange<'a>>,
+    requested_serial: Option<u32>,
+    kind: Option<TransferKind>,
+    state: State,
+    /// The records of the last message that have not been looked at yet.
+    records: VecDeque<ResourceRecord>,
+    /// The records that are ready to be returned.
+    pending: VecDeque<TransferRecord>,
+    messages: usize,
+}
+
+impl<'a> Transfer<'a> {
+    /// The kind of transfer. `None` until the first two records have been read.
+    #[inline]
+    pub fn kind(&self) -> Option<TransferKind> { self.kind }
+
+    /// The number of messages that have been read so far.
+    #[inline]
+    pub fn messages(&self) -> usize { self.messages }
+
+    /// The next record of the transfer, or `None` once it has ended. Once an error is returned,
+    /// the transfer has ended.
+    pub async fn next_record(&mut self) -> Option<Result<TransferRecord, TransferError>> {
+        loop {
+            if let Some(record) = self.pending.pop_front() {
+                return Some(Ok(record));
+            }
+            if let State::Done = self.state {
+                return None;
+            }
+            let result = match self.records.pop_front() {
+                Some(record) => self.process(record),
+                None => self.end_of_message().await,
+            };
+            if let Err(error) = result {
+                self.state = State::Done;
+                self.pending.clear();
+                return Some(Err(error));
+            }
+        }
+    }
+
+    /// The records of the transfer as a stream.
+    pub fn into_stream(self) -> impl Stream<Item = Result<TransferRecord, TransferError>> + 'a {
+        futures::stream::unfold(self, |mut transfer| async move {
+            transfer.next_record().await.map(|record| (record, transfer))
+        })
+    }
+
+    /// Reads the rest of the transfer into memory.
+    pub async fn collect(mut self) -> Result<(TransferKind, Vec<TransferRecord>), TransferError> {
+        let mut records = Vec::new();
+        while let Some(record) = self.next_record().await {
+            records.push(record?);
+        }
+        Ok((self.kind.unwrap_or(TransferKind::Full), records))
+    }
+
+    async fn end_of_message(&mut self) -> Result<(), TransferError> {
+        match &self.state {
+            // An answer to an IXFR query with only the SOA record means that the client's version is
+            // current. If the SOA record is newer, the rest of the transfer is in the next messages.
+            // https://datatracker.ietf.org/doc/html/rfc1995#section-4
+            State::Second { first } if self.requested_serial.is_some() => {
+                let serial = soa_serial(first).expect("the first record of a transfer is an SOA record");
+                if self.requested_serial.is_some_and(|requested| is_newer_serial(serial, requested)) {
+                    return self.read_message().await;
+                }
+                self.kind = Some(TransferKind::UpToDate);
+                self.finish()
+            },
+            State::Start => Err(TransferError::MissingSoa),
+            _ => self.read_message().await,
+        }
+    }
+
+    async fn read_message(&mut self) -> Result<(), TransferError> {
+        let bytes = timeout(self.timeout, read_frame(&mut self.stream)).await.map_err(|_| TransferError::Timeout)??;
+        if let Some(tsig) = &mut self.tsig {
+            tsig.verify(&bytes)?;
+        }
+        let mut message = Message::from_wire_format(&mut ReadWire::from_bytes(&bytes))?;
+        if (message.id != self.query.id) || (message.qr != QR::Response) {
+            return Err(TransferError::NotAnAnswer);
+        }
+        // Only the first message has to repeat the question.
+        // https://datatracker.ietf.org/doc/html/rfc5936#section-2.2.1
+        let question_matches = match (message.question.first(), self.query.question.first()) {
+            (Some(answered), Some(asked)) => (message.question.len() == 1) && answered.qname().matches(asked.qname()) && (answered.qtype() == asked.qtype()),
+            (None, _) => self.messages > 0,
+            (Some(_), None) => false,
+        };
+        if !question_matches {
+            return Err(TransferError::NotAnAnswer);
+        }
+        if message.rcode != RCode::NoError {
+            return Err(TransferError::Rejected(message.rcode));
+        }
+        self.messages += 1;
+        self.records.extend(mem::take(&mut message.answer));
+        Ok(())
+    }
+
+    fn process(&mut self, record: ResourceRecord) -> Result<(), TransferError> {
+        // Records outside of the zone are ignored.
+        // https://datatracker.ietf.org/doc/html/rfc5936#section-3.5
+        if !self.zone.is_parent_domain_of(record.get_name()) {
+            return Ok(());
+        }
+        let record_serial = soa_serial(&record);
+        match mem::replace(&mut self.state, State::Done) {
+            // The transfer starts with the SOA record at the zone's apex.
+            // https://datatracker.ietf.org/doc/html/rfc5936#section-2.2
+            State::Start => match record_serial {
+                Some(_) if record.get_name().matches(self.zone) => self.state = State::Second { first: record },
+                _ => return Err(TransferError::MissingSoa),
+            },
+            State::Second { first } => {
+                let serial = soa_serial(&first).expect("the first record of a transfer is an SOA record");
+                match (record_serial, self.requested_serial) {
+                    // An incremental transfer starts with the client's SOA record.
+                    (Some(received), Some(_)) if received != serial => {
+                        self.kind = Some(TransferKind::Incremental);
+                        self.pending.push_back(TransferRecord::Deleted(record));
+                        self.state = State::Deleting { serial };
+                    },
+                    // A zone that only has its SOA record.
+                    (Some(received), _) if received == serial => {
+                        self.kind = Some(TransferKind::Full);
+                        self.pending.push_back(TransferRecord::Record(first));
+                        self.finish()?;
+                    },
+                    (Some(received), _) => return Err(TransferError::SoaMismatch { expected: serial, received }),
+                    (None, _) => {
+                        self.kind = Some(TransferKind::Full);
+                        self.pending.push_back(TransferRecord::Record(first));
+                        self.pending.push_back(TransferRecord::Record(record));
+                        self.state = State::Full { serial };
+                    },
+                }
+            },
+            State::Full { serial } => match record_serial {
+                Some(received) if received == serial => self.finish()?,
+                Some(received) => return Err(TransferError::SoaMismatch { expected: serial, received }),
+                None => {
+                    self.pending.push_back(TransferRecord::Record(record));
+                    self.state = State::Full { serial };
+                },
+            },
+            // Each SOA record in a difference sequence switches between the records deleted from
+            // one version and the records added in the next.
+            // https://datatracker.ietf.org/doc/html/rfc1995#section-4
+            State::Deleting { serial } => match record_serial {
+                Some(_) => {
+                    self.pending.push_back(TransferRecord::Added(record));
+                    self.state = State::Adding { serial };
+                },
+                None => {
+                    self.pending.push_back(TransferRecord::Deleted(record));
+                    self.state = State::Deleting { serial };
+                },
+            },
+            State::Adding { serial } => match record_serial {
+                Some(received) if received == serial => self.finish()?,
+                Some(_) => {
+                    self.pending.push_back(TransferRecord::Deleted(record));
+                    self.state = State::Deleting { serial };
+                },
+                None => {
+                    self.pending.push_back(TransferRecord::Added(record));
+                    self.state = State::Adding { serial };
+                },
+            },
+            State::Done => (),
+        }
+        Ok(())
+    }
+
+    /// Ends the transfer. The last message must have been signed if the transfer is.
+    fn finish(&mut self) -> Result<(), TransferError> {
+        self.state = State::Done;
+        self.records.clear();
+        if let Some(tsig) = &self.tsig {
+            tsig.finish()?;
+        }
+        Ok(())
+    }
+}
+
+#[inline]
+fn soa_serial(record: &ResourceRecord) -> Option<u32> {
+    match record.get_rdata() {
+        RecordData::SOA(soa) => Some(*soa.serial()),
+        _ => None,
+    }
+}
+
+/// Whether `serial` is newer than `other`, using serial number arithmetic.
+/// https://datatracker.ietf.org/doc/html/rfc1982#section-3.2
+#[inline]
+fn is_newer_serial(serial: u32, other: u32) -> bool {
+    (serial != other) && ((serial.wrapping_sub(other) as i32) > 0)
+}
+
+/// Reads one length-prefixed message from the stream.
+async fn read_frame(stream: &mut TcpStream) -> Result<Vec<u8>, TransferError> {
+    let length = stream.read_u16().await?;
+    let mut bytes = vec![0; usize::from(length)];
+    stream.read_exact(&mut bytes).await?;
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod axfr_tests {
+    use std::net::SocketAddr;
+
+    use dns_lib::{query::{message::Message, qr::QR}, resource_record::{rcode::RCode, resource_record::ResourceRecord}, serde::wire::{from_wire::FromWire, read_wire::ReadWire, to_wire::ToWire, write_wire::WriteWire}, types::c_domain_name::CompressionMap};
+    use futures::StreamExt;
+    use tokio::{io::{AsyncReadExt, AsyncWriteExt}, net::TcpListener};
+
+    use crate::test_support::{a, name, soa};
+
+    use super::{TransferError, TransferKind, TransferRecord, ZoneTransfer};
+
+    /// A primary that answers one query with these messages, each with these answer records.
+    async fn primary(messages: Vec<Vec<ResourceRecord>>, rcode: RCode) -> SocketAddr {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let length = stream.read_u16().await.unwrap();
+            let mut bytes = vec![0; usize::from(length)];
+            stream.read_exact(&mut bytes).await.unwrap();
+            let query = Message::from_wire_format(&mut ReadWire::from_bytes(&bytes)).unwrap();
+            for (index, answer) in messages.into_iter().enumerate() {
+                let mut response = query.clone();
+                response.qr = QR::Response;
+                response.rcode = rcode;
+                response.authority.clear();
+                response.answer = answer;
+                if index > 0 {
+                    response.question.clear();
+                }
+                let mut buffer = vec![0_u8; u16::MAX as usize];
+                let mut wire = WriteWire::from_bytes(&mut buffer);
+                response.to_wire_format(&mut wire, &mut Some(CompressionMap::new())).unwrap();
+                let length = wire.current_len();
+                stream.write_u16(length as u16).await.unwrap();
+                stream.write_all(&buffer[..length]).await.unwrap();
+            }
+        });
+        address
+    }
+
+    #[tokio::test]
+    async fn full_transfers_stream_every_record_across_messages() {
+        let address = primary(vec![vec![soa("example.com.", 5), a("www.example.com.", "192.0.2.1")], vec![a("mail.example.com.", "192.0.2.2"), a("other.test.", "192.0.2.9")], vec![soa("example.com.", 5)]], RCode::NoError).await;
+        let zone = ZoneTransfer::new(address, name("example.com."));
+        let transfer = zone.axfr().await.unwrap();
+        let records = transfer.into_stream().collect::<Vec<_>>().await;
+        let records = records.into_iter().collect::<Result<Vec<_>, _>>().unwrap();
+        assert_eq!(records, vec![
+            TransferRecord::Record(soa("example.com.", 5)),
+            TransferRecord::Record(a("www.example.com.", "192.0.2.1")),
+            TransferRecord::Record(a("mail.example.com.", "192.0.2.2")),
+        ]);
+    }
+
+    #[tokio::test]
+    async fn incremental_transfers_are_split_into_deletions_and_additions() {
+        let address = primary(vec![vec![soa("example.com.", 7), soa("example.com.", 5), a("www.example.com.", "192.0.2.1"), soa("example.com.", 7), a("www.example.com.", "192.0.2.3"), soa("example.com.", 7)]], RCode::NoError).await;
+        let zone = ZoneTransfer::new(address, name("example.com."));
+        let (kind, records) = zone.ixfr(5).await.unwrap().collect().await.unwrap();
+        assert_eq!(kind, TransferKind::Incremental);
+        assert_eq!(records, vec![
+            TransferRecord::Deleted(soa("example.com.", 5)),
+            TransferRecord::Deleted(a("www.example.com.", "192.0.2.1")),
+            TransferRecord::Added(soa("example.com.", 7)),
+            TransferRecord::Added(a("www.example.com.", "192.0.2.3")),
+        ]);
+
+        let address = primary(vec![vec![soa("example.com.", 7), a("www.example.com.", "192.0.2.3"), soa("example.com.", 7)]], RCode::NoError).await;
+        let zone = ZoneTransfer::new(address, name("example.com."));
+        let (kind, records) = zone.ixfr(5).await.unwrap().collect().await.unwrap();
+        assert_eq!(kind, TransferKind::Full);
+        assert_eq!(records.len(), 2);
+
+        let address = primary(vec![vec![soa("example.com.", 7)]], RCode::NoError).await;
+        let zone = ZoneTransfer::new(address, name("example.com."));
+        let (kind, records) = zone.ixfr(7).await.unwrap().collect().await.unwrap();
+        assert_eq!(kind, TransferKind::UpToDate);
+        assert!(records.is_empty());
+
+        // A newer SOA record alone in the first message is not an up to date answer.
+        let address = primary(vec![vec![soa("example.com.", 7)], vec![soa("example.com.", 5), soa("example.com.", 7), a("www.example.com.", "192.0.2.3"), soa("example.com.", 7)]], RCode::NoError).await;
+        let zone = ZoneTransfer::new(address, name("example.com."));
+        let (kind, records) = zone.ixfr(5).await.unwrap().collect().await.unwrap();
+        assert_eq!(kind, TransferKind::Incremental);
+        assert_eq!(records, vec![
+            TransferRecord::Deleted(soa("example.com.", 5)),
+            TransferRecord::Added(soa("example.com.", 7)),
+            TransferRecord::Added(a("www.example.com.", "192.0.2.3")),
+        ]);
+    }
+
+    #[tokio::test]
+    async fn broken_transfers_are_reported() {
+        let address = primary(vec![vec![soa("example.com.", 5), a("www.example.com.", "192.0.2.1")]], RCode::NoError).await;
+        let zone = ZoneTransfer::new(address, name("example.com."));
+        let result = zone.axfr().await.unwrap().collect().await;
+        assert!(matches!(result, Err(TransferError::UnexpectedEnd)));
+
+        let address = primary(vec![vec![soa("example.com.", 5), a("www.example.com.", "192.0.2.1"), soa("example.com.", 6)]], RCode::NoError).await;
+        let zone = ZoneTransfer::new(address, name("example.com."));
+        let result = zone.axfr().await.unwrap().collect().await;
+        assert!(matches!(result, Err(TransferError::SoaMismatch { expected: 5, received: 6 })));
+
+        let address = primary(vec![vec![soa("www.example.com.", 5), a("www.example.com.", "192.0.2.1"), soa("www.example.com.", 5)]], RCode::NoError).await;
+        let zone = ZoneTransfer::new(address, name("example.com."));
+        let result = zone.axfr().await.unwrap().collect().await;
+        assert!(matches!(result, Err(TransferError::MissingSoa)));
+
+        let address = primary(vec![vec![]], RCode::Refused).await;
+        let zone = ZoneTransfer::new(address, name("example.com."));
+        assert!(matches!(zone.axfr().await, Err(TransferError::Rejected(RCode::Refused))));
+    }
+}
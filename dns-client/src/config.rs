@@ -0,0 +1,123 @@
+use dns_lib::{interface::dnr::{DnrError, DnrInstance}, query::edns::DEFAULT_EDNS_BUFFER_SIZE};
+
+use crate::{happy_eyeballs::HappyEyeballsConfig, hedging::HedgingConfig, prefetch::PrefetchConfig, upstream::{EncryptedUpstream, ForwardUpstream}};
+
+/// The standard port for DNS over UDP and TCP.
+pub const UPSTREAM_PORT: u16 = 53;
+
+/// The default for `max_response_records`. Far more than any legitimate response has.
+pub const DEFAULT_MAX_RESPONSE_RECORDS: usize = 4096;
+
+/// The default for `max_response_size`. The largest message that can be sent over TCP.
+pub const DEFAULT_MAX_RESPONSE_SIZE: usize = u16::MAX as usize;
+
+/// Options that control the behaviour of a `DNSAsyncClient`. These are fixed once the client has
+/// been created.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ClientConfig {
+    /// When a response contains an RRset that conflicts with a cached RRset of the same
+    /// credibility, query an independent name server for the zone before caching the new data.
+    /// If no independent name server can be found, the new data is quarantined until a later
+    /// response confirms it.
+    pub revalidate_conflicts: bool,
+    /// Reject responses whose QR bit, opcode, question, or flags do not match the query and drop
+    /// answer records that are unrelated to the question before they are cached.
+    pub validate_responses: bool,
+    /// Encrypted resolvers to use as upstreams, ordered by priority. Queries to the address of a
+    /// DNS over HTTPS or DNS over QUIC upstream are sent over that transport instead of UDP and
+    /// TCP.
+    pub encrypted_upstreams: Vec<EncryptedUpstream>,
+    /// Ask each name server to identify itself using the NSID option and record the identifier
+    /// it returns. This helps to diagnose problems that only affect one instance of an anycast
+    /// service.
+    pub request_nsid: bool,
+    /// The EDNS UDP payload size advertised in queries to upstream servers, or `None` to send
+    /// queries without EDNS. The socket manager may advertise less on paths where large responses
+    /// go missing.
+    pub udp_payload_size: Option<u16>,
+    /// Recognize compact denial of existence responses, where a signed zone answers a query for a
+    /// name that does not exist with NODATA and an NSEC record whose next name is synthesized just
+    /// past the qname. When the NSEC record has the NXNAME type in its bitmap, the response is
+    /// treated as NXDOMAIN, which is what it stands for. Only applies when `validate_responses` is
+    /// set.
+    ///
+    /// https://datatracker.ietf.org/doc/draft-ietf-dnsop-compact-denial-of-existence/
+    pub compact_denial: bool,
+    /// Send the same query to a zone's second best name server when its best name server is
+    /// slower than usual to answer, and use whichever answers first. `None` disables hedging and
+    /// queries up to three name servers at fixed 200ms intervals instead (or as configured by
+    /// `happy_eyeballs`).
+    pub hedging: Option<HedgingConfig>,
+    /// Alternate between the IPv6 and IPv4 addresses of a zone's name servers and start a query
+    /// to the next one after a short delay, cancelling the rest once one answers. When hedging is
+    /// enabled as well, hedging decides when the second query is sent and this only decides
+    /// which address family each query goes to. `None` chooses name servers by their response times
+    /// alone.
+    pub happy_eyeballs: Option<HappyEyeballsConfig>,
+    /// The port that queries over UDP and TCP are sent to. This is only changed to test against
+    /// name servers that are not listening on the standard port.
+    pub upstream_port: u16,
+    /// Responses from upstream servers with more records than this (across the answer, authority,
+    /// and additional sections) are rejected as abusive.
+    pub max_response_records: usize,
+    /// Responses from upstream servers larger than this many bytes are rejected as abusive.
+    pub max_response_size: usize,
+    /// Recursive resolvers to forward every query to, in order of preference. When there are
+    /// any, the client is a stub resolver: it sends its queries with the RD bit set to the first
+    /// upstream that answers them and never resolves names from the root itself. The next
+    /// upstream is tried if one fails to answer or answers with SERVFAIL or REFUSED.
+    pub forwarders: Vec<ForwardUpstream>,
+    /// Query names that are answered from the cache often again shortly before their records
+    /// expire, so that they are refreshed in the background. `None` lets every name expire.
+    pub prefetch: Option<PrefetchConfig>,
+}
+
+impl Default for ClientConfig {
+    fn default() -> Self {
+        Self {
+            revalidate_conflicts: false,
+            validate_responses: true,
+            encrypted_upstreams: Vec::new(),
+            request_nsid: false,
+            udp_payload_size: Some(DEFAULT_EDNS_BUFFER_SIZE),
+            compact_denial: false,
+            hedging: None,
+            happy_eyeballs: None,
+            upstream_port: UPSTREAM_PORT,
+            max_response_records: DEFAULT_MAX_RESPONSE_RECORDS,
+            max_response_size: DEFAULT_MAX_RESPONSE_SIZE,
+            forwarders: Vec::new(),
+            prefetch: None,
+        }
+    }
+}
+
+impl ClientConfig {
+    /// The default config for a client that forwards its queries to these resolvers.
+    #[inline]
+    pub fn forwarding(forwarders: Vec<ForwardUpstream>) -> Self {
+        Self { forwarders, ..Self::default() }
+    }
+
+    /// Whether queries are forwarded to upstream resolvers instead of being resolved from the
+    /// root.
+    #[inline]
+    pub fn is_forwarding(&self) -> bool {
+        !self.forwarders.is_empty()
+    }
+
+    /// Adds the encrypted upstreams described by Discovery of Network-designated Resolvers
+    /// instances (e.g. from a DHCP lease or Router Advertisement). Instances that fail to convert
+    /// are returned as errors without affecting the others.
+    pub fn add_dnr_upstreams<'a>(&mut self, instances: impl IntoIterator<Item = &'a DnrInstance>) -> Vec<DnrError> {
+        let mut errors = Vec::new();
+        for instance in instances {
+            match EncryptedUpstream::from_dnr(instance) {
+                Ok(upstreams) => self.encrypted_upstreams.extend(upstreams),
+                Err(error) => errors.push(error),
+            }
+        }
+        self.encrypted_upstreams.sort_by_key(|upstream| upstream.priority);
+        errors
+    }
+}
@@ -0,0 +1,285 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use dns_lib::{query::question::Question, resource_record::{resource_record::{RecordData, ResourceRecord}, types::ns::NS}, types::c_domain_name::{CDomainName, CmpDomainName}};
+use log::warn;
+
+use crate::result::QOk;
+
+/// A point-in-time copy of the answer consistency counters.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ConsistencyStats {
+    /// The number of records removed because the same record was already in the answer.
+    pub duplicate_records: u64,
+    /// The number of CNAME records dropped because their owner already had a CNAME with another
+    /// target.
+    pub conflicting_cnames: u64,
+    /// The number of DNAME records dropped because their owner already had a DNAME with another
+    /// target.
+    pub conflicting_dnames: u64,
+    /// The number of CNAME records dropped because they contradict the DNAME that they should
+    /// have been synthesized from.
+    pub inconsistent_synthesized_cnames: u64,
+}
+
+/// Cleans up the records gathered while resolving a query before they are given to the
+/// application. The records come from several responses (one for each step of a CNAME or DNAME
+/// chain) and from the cache, so the same record may show up more than once and an owner may
+/// have been given different targets by different name servers. The first record seen for an
+/// owner is kept since it is the one that the rest of the chain was resolved from.
+#[derive(Debug, Default)]
+pub(crate) struct AnswerConsistency {
+    duplicate_records: AtomicU64,
+    conflicting_cnames: AtomicU64,
+    conflicting_dnames: AtomicU64,
+    inconsistent_synthesized_cnames: AtomicU64,
+}
+
+impl AnswerConsistency {
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[inline]
+    pub fn stats(&self) -> ConsistencyStats {
+        ConsistencyStats {
+            duplicate_records: self.duplicate_records.load(Ordering::Relaxed),
+            conflicting_cnames: self.conflicting_cnames.load(Ordering::Relaxed),
+            conflicting_dnames: self.conflicting_dnames.load(Ordering::Relaxed),
+            inconsistent_synthesized_cnames: self.inconsistent_synthesized_cnames.load(Ordering::Relaxed),
+        }
+    }
+
+    pub fn make_consistent(&self, question: &Question, answer: &mut QOk) {
+        let duplicates = remove_duplicates(answer);
+        if duplicates > 0 {
+            self.duplicate_records.fetch_add(duplicates as u64, Ordering::Relaxed);
+            warn!(question:?; "Removed {duplicates} duplicate records from the answer");
+        }
+
+        let conflicting_dnames = remove_conflicting_targets(&mut answer.answer, dname_target);
+        if conflicting_dnames > 0 {
+            self.conflicting_dnames.fetch_add(conflicting_dnames as u64, Ordering::Relaxed);
+            warn!(question:?; "Dropped {conflicting_dnames} DNAME records whose owner already had a different target");
+        }
+
+        let inconsistent = remove_inconsistent_synthesized_cnames(&mut answer.answer);
+        if inconsistent > 0 {
+            self.inconsistent_synthesized_cnames.fetch_add(inconsistent as u64, Ordering::Relaxed);
+            warn!(question:?; "Dropped {inconsistent} CNAME records that contradict the DNAME they are below");
+        }
+
+        let conflicting_cnames = remove_conflicting_targets(&mut answer.answer, cname_target);
+        if conflicting_cnames > 0 {
+            self.conflicting_cnames.fetch_add(conflicting_cnames as u64, Ordering::Relaxed);
+            warn!(question:?; "Dropped {conflicting_cnames} CNAME records whose owner already had a different target");
+        }
+    }
+}
+
+/// Records are compared the same way as `ResourceRecord::eq()`, except that the owner names are
+/// compared without regard to case.
+#[inline]
+fn same_record(record: &ResourceRecord, other: &ResourceRecord) -> bool {
+    record.get_name().matches(other.get_name())
+        && (record.get_rclass() == other.get_rclass())
+        && (record.get_rdata() == other.get_rdata())
+}
+
+/// Removes repeated records within each section, records in the additional section that are
+/// already in the answer, and name servers that are repeated in the additional section. Returns
+/// the number of records that were removed.
+fn remove_duplicates(answer: &mut QOk) -> usize {
+    let count = answer.answer.len() + answer.name_servers.len() + answer.additional.len();
+
+    let mut seen: Vec<ResourceRecord> = Vec::with_capacity(count);
+    answer.answer.retain(|record| keep_first(&mut seen, record));
+
+    let mut seen_name_servers: Vec<ResourceRecord<NS>> = Vec::with_capacity(answer.name_servers.len());
+    answer.name_servers.retain(|record| {
+        let is_duplicate = seen_name_servers.iter().any(|seen| seen.get_name().matches(record.get_name()) && (seen.get_rclass() == record.get_rclass()) && (seen.get_rdata() == record.get_rdata()));
+        if !is_duplicate {
+            seen_name_servers.push(record.clone());
+        }
+        !is_duplicate
+    });
+    seen.extend(answer.name_servers.iter().cloned().map(ResourceRecord::from));
+    answer.additional.retain(|record| keep_first(&mut seen, record));
+
+    count - (answer.answer.len() + answer.name_servers.len() + answer.additional.len())
+}
+
+#[inline]
+fn keep_first(seen: &mut Vec<ResourceRecord>, record: &ResourceRecord) -> bool {
+    if seen.iter().any(|seen| same_record(seen, record)) {
+        return false;
+    }
+    seen.push(record.clone());
+    true
+}
+
+#[inline]
+fn cname_target(record: &ResourceRecord) -> Option<CDomainName> {
+    match record.get_rdata() {
+        RecordData::CNAME(cname) => Some(cname.primary_name().clone()),
+        _ => None,
+    }
+}
+
+#[inline]
+fn dname_target(record: &ResourceRecord) -> Option<CDomainName> {
+    match record.get_rdata() {
+        RecordData::DNAME(dname) => CDomainName::from_ref_labels(dname.target_name().case_sensitive_labels().collect()).ok(),
+        _ => None,
+    }
+}
+
+/// An owner can only have one CNAME (or DNAME) record. Any record of the type whose owner has
+/// already been given a different target is removed. Returns the number of records that were
+/// removed.
+///
+/// https://datatracker.ietf.org/doc/html/rfc2181#section-10.1
+/// https://datatracker.ietf.org/doc/html/rfc6672#section-2.4
+fn remove_conflicting_targets(records: &mut Vec<ResourceRecord>, target: impl Fn(&ResourceRecord) -> Option<CDomainName>) -> usize {
+    let mut targets: Vec<(CDomainName, CDomainName)> = Vec::new();
+    let count = records.len();
+    records.retain(|record| {
+        let Some(record_target) = target(record) else { return true };
+        match targets.iter().find(|(owner, _)| owner.matches(record.get_name())) {
+            Some((_, first_target)) => first_target.matches(&record_target),
+            None => {
+                targets.push((record.get_name().clone(), record_target));
+                true
+            },
+        }
+    });
+    count - records.len()
+}
+
+/// A CNAME below the owner of a DNAME must point to the name synthesized from the DNAME: the CNAME's
+/// owner with the DNAME's owner replaced by its target. Any other target contradicts the DNAME.
+/// Returns the number of records that were removed.
+///
+/// https://datatracker.ietf.org/doc/html/rfc6672#section-2.2
+fn remove_inconsistent_synthesized_cnames(records: &mut Vec<ResourceRecord>) -> usize {
+    let dnames = records.iter()
+        .filter_map(|record| dname_target(record).map(|target| (record.get_name().clone(), target)))
+        .collect::<Vec<_>>();
+    if dnames.is_empty() {
+        return 0;
+    }
+
+    let count = records.len();
+    records.retain(|record| {
+        let Some(target) = cname_target(record) else { return true };
+        let owner = record.get_name();
+        dnames.iter()
+            .filter(|(dname_owner, _)| dname_owner.is_parent_domain_of(owner) && !dname_owner.matches(owner))
+            .all(|(dname_owner, dname_target)| synthesize(owner, dname_owner, dname_target).is_some_and(|synthesized| synthesized.matches(&target)))
+    });
+    count - records.len()
+}
+
+/// Replaces the `dname_owner` suffix of `owner` with `dname_target`.
+fn synthesize(owner: &CDomainName, dname_owner: &CDomainName, dname_target: &CDomainName) -> Option<CDomainName> {
+    let prefix_length = owner.label_count().checked_sub(dname_owner.label_count())?;
+    CDomainName::from_ref_labels(
+        owner.case_sensitive_labels()
+            .take(prefix_length)
+            .chain(dname_target.case_sensitive_labels())
+            .collect()
+    ).ok()
+}
+
+#[cfg(test)]
+mod consistency_tests {
+    use std::net::Ipv4Addr;
+
+    use dns_lib::{query::question::Question, resource_record::{rclass::RClass, resource_record::{RecordData, ResourceRecord}, rtype::RType, time::Time, types::{a::A, cname::CNAME, dname::DNAME, ns::NS}}, types::{c_domain_name::CDomainName, domain_name::DomainName}};
+
+    use crate::result::QOk;
+
+    use super::AnswerConsistency;
+
+    fn name(name: &str) -> CDomainName {
+        CDomainName::from_utf8(name).unwrap()
+    }
+
+    fn record(owner: &str, rdata: RecordData) -> ResourceRecord {
+        ResourceRecord::new(name(owner), RClass::Internet, Time::from_secs(300), rdata)
+    }
+
+    fn cname(owner: &str, target: &str) -> ResourceRecord {
+        record(owner, RecordData::CNAME(CNAME::new(name(target))))
+    }
+
+    fn dname(owner: &str, target: &str) -> ResourceRecord {
+        record(owner, RecordData::DNAME(DNAME::new(DomainName::from_utf8(target).unwrap())))
+    }
+
+    fn a(owner: &str, octet: u8) -> ResourceRecord {
+        record(owner, RecordData::A(A::new(Ipv4Addr::new(192, 0, 2, octet))))
+    }
+
+    fn make_consistent(answer: Vec<ResourceRecord>, additional: Vec<ResourceRecord>) -> (QOk, AnswerConsistency) {
+        let consistency = AnswerConsistency::new();
+        let question = Question::new(name("www.example.org."), RType::A, RClass::Internet);
+        let mut answer = QOk { answer, name_servers: Vec::new(), additional };
+        consistency.make_consistent(&question, &mut answer);
+        (answer, consistency)
+    }
+
+    #[test]
+    fn removes_duplicates_across_sections() {
+        let (answer, consistency) = make_consistent(
+            vec![cname("www.example.org.", "web.example.org."), a("web.example.org.", 1), a("WEB.example.org.", 1)],
+            vec![a("web.example.org.", 1), a("ns.example.org.", 2)],
+        );
+        assert_eq!(answer.answer, vec![cname("www.example.org.", "web.example.org."), a("web.example.org.", 1)]);
+        assert_eq!(answer.additional, vec![a("ns.example.org.", 2)]);
+        assert_eq!(consistency.stats().duplicate_records, 2);
+    }
+
+    #[test]
+    fn removes_name_servers_repeated_in_additional() {
+        let ns = ResourceRecord::new(name("example.org."), RClass::Internet, Time::from_secs(300), NS::new(name("ns.example.org.")));
+        let consistency = AnswerConsistency::new();
+        let question = Question::new(name("www.example.org."), RType::A, RClass::Internet);
+        let mut answer = QOk { answer: Vec::new(), name_servers: vec![ns.clone(), ns.clone()], additional: vec![ns.clone().into()] };
+        consistency.make_consistent(&question, &mut answer);
+        assert_eq!(answer.name_servers, vec![ns]);
+        assert!(answer.additional.is_empty());
+        assert_eq!(consistency.stats().duplicate_records, 2);
+    }
+
+    #[test]
+    fn keeps_first_cname_target() {
+        let (answer, consistency) = make_consistent(
+            vec![cname("www.example.org.", "web.example.org."), a("web.example.org.", 1), cname("www.example.org.", "evil.example.net.")],
+            Vec::new(),
+        );
+        assert_eq!(answer.answer, vec![cname("www.example.org.", "web.example.org."), a("web.example.org.", 1)]);
+        assert_eq!(consistency.stats().conflicting_cnames, 1);
+    }
+
+    #[test]
+    fn keeps_first_dname_target() {
+        let (answer, consistency) = make_consistent(
+            vec![dname("example.org.", "example.com."), dname("EXAMPLE.org.", "example.net.")],
+            Vec::new(),
+        );
+        assert_eq!(answer.answer, vec![dname("example.org.", "example.com.")]);
+        assert_eq!(consistency.stats().conflicting_dnames, 1);
+    }
+
+    #[test]
+    fn drops_cname_contradicting_dname() {
+        let (answer, consistency) = make_consistent(
+            vec![dname("example.org.", "example.com."), cname("www.example.org.", "www.example.net."), cname("www.example.org.", "www.example.com.")],
+            Vec::new(),
+        );
+        assert_eq!(answer.answer, vec![dname("example.org.", "example.com."), cname("www.example.org.", "www.example.com.")]);
+        assert_eq!(consistency.stats().inconsistent_synthesized_cnames, 1);
+        assert_eq!(consistency.stats().conflicting_cnames, 0);
+    }
+}
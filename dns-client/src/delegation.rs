@@ -0,0 +1,104 @@
+use std::{net::IpAddr, time::Instant};
+
+use async_trait::async_trait;
+use dns_cache::asynchronous::{async_cache::AsyncTreeCache, async_transaction_cache::AsyncTransactionTreeCache};
+use dns_lib::{interface::cache::{cache::AsyncCache, main_cache::SharedAsyncMainCache, transaction_cache::AsyncTransactionCache, CacheMeta, CacheQuery, CacheRecord, CacheResponse, MetaAuth}, resource_record::{rclass::RClass, resource_record::{RecordData, ResourceRecord}, time::Time, types::{a::A, aaaa::AAAA, ns::NS}}, types::c_domain_name::{CDomainName, CmpDomainName}};
+
+/// The TTL given to the records of a supplied delegation. They only live as long as the query
+/// that uses them, so the value does not matter as long as they do not expire during it.
+const DELEGATION_TTL: Time = Time::from_secs(86400);
+
+/// A zone cut supplied by the caller: the zone, its name servers, and (optionally) their
+/// addresses. Name servers without addresses are looked up during the resolution.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Delegation {
+    zone: CDomainName,
+    name_servers: Vec<(CDomainName, Vec<IpAddr>)>,
+}
+
+impl Delegation {
+    #[inline]
+    pub fn new(zone: CDomainName) -> Self {
+        Self { zone, name_servers: Vec::new() }
+    }
+
+    /// Adds a name server for the zone along with any addresses it is known to have.
+    #[inline]
+    pub fn with_name_server(mut self, name_server: CDomainName, addresses: Vec<IpAddr>) -> Self {
+        self.name_servers.push((name_server, addresses));
+        self
+    }
+
+    #[inline]
+    pub fn zone(&self) -> &CDomainName { &self.zone }
+
+    #[inline]
+    pub fn name_servers(&self) -> &[(CDomainName, Vec<IpAddr>)] { &self.name_servers }
+
+    fn records(&self) -> impl Iterator<Item = ResourceRecord> + '_ {
+        let ns_records = self.name_servers.iter().map(|(name_server, _)| ResourceRecord::new(
+            self.zone.clone(),
+            RClass::Internet,
+            DELEGATION_TTL,
+            RecordData::NS(NS::new(name_server.clone())),
+        ));
+        let address_records = self.name_servers.iter().flat_map(|(name_server, addresses)| addresses.iter().map(|address| ResourceRecord::new(
+            name_server.clone(),
+            RClass::Internet,
+            DELEGATION_TTL,
+            match address {
+                IpAddr::V4(address) => RecordData::A(A::new(*address)),
+                IpAddr::V6(address) => RecordData::AAAA(AAAA::new(*address)),
+            },
+        )));
+        ns_records.chain(address_records)
+    }
+}
+
+/// The cache used by a query that starts from a supplied delegation. Names at or below the zone
+/// only see the delegation and whatever its name servers return, so the main cache can neither
+/// answer for them nor be filled with them. Names outside of the zone (such as the names of
+/// out-of-zone name servers) are resolved normally.
+pub(crate) struct DelegatedCache {
+    zone: CDomainName,
+    delegated: AsyncTransactionTreeCache,
+    outside: AsyncTreeCache,
+}
+
+impl DelegatedCache {
+    pub async fn new(main_cache: SharedAsyncMainCache, delegation: &Delegation) -> Self {
+        let cache = Self {
+            zone: delegation.zone.clone(),
+            delegated: AsyncTransactionTreeCache::new(),
+            outside: AsyncTreeCache::new(main_cache),
+        };
+        let insertion_time = Instant::now();
+        AsyncCache::insert_iter(&cache, delegation.records().map(|record| CacheRecord {
+            meta: CacheMeta { auth: MetaAuth::NotAuthoritative, insertion_time, original_ttl: None },
+            record,
+        })).await;
+        cache
+    }
+}
+
+#[async_trait]
+impl AsyncCache for DelegatedCache {
+    async fn get(&self, query: &CacheQuery<'_>) -> CacheResponse {
+        if self.zone.is_parent_domain_of(query.question.qname()) {
+            self.delegated.get(query).await
+        } else {
+            self.outside.get(query).await
+        }
+    }
+
+    async fn insert_record(&self, record: CacheRecord) {
+        if self.zone.is_parent_domain_of(record.record.get_name()) {
+            self.delegated.insert_record(record).await
+        } else {
+            self.outside.insert_record(record).await
+        }
+    }
+
+    #[inline]
+    fn is_isolated(&self) -> bool { true }
+}
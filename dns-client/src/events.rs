@@ -0,0 +1,160 @@
+use std::{fmt::Display, net::IpAddr, time::Duration};
+
+use dns_lib::{query::{message::Message, question::Question}, resource_record::rcode::RCode};
+use tokio::sync::broadcast;
+
+/// How many events are buffered for each subscriber. A subscriber that falls further behind than
+/// this skips the oldest events and is told how many it missed.
+const EVENT_CAPACITY: usize = 256;
+
+/// A significant change in how the client is able to resolve queries. These are published as they
+/// happen so that embedding applications can alert or adapt without polling the stats.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ClientEvent {
+    /// A name server answered in a way that shows it is not serving the zone that it was asked
+    /// about. Only published the first time the server is marked lame, not for every query that
+    /// it answers while it is.
+    UpstreamLame { address: IpAddr, reason: LameReason },
+    /// A name server failed too many queries in a row and is skipped until it is probed again,
+    /// `retry_in` from now. Also published each time a probe fails.
+    UpstreamDown { address: IpAddr, retry_in: Duration },
+    /// A name server that was down answered a probe, so queries are sent to it again.
+    UpstreamRecovered { address: IpAddr },
+    /// Queries to a name server are being sent with fewer features than the client is configured
+    /// to use.
+    TransportDowngraded { address: IpAddr, downgrade: Downgrade },
+    /// An expired answer was returned because the name servers for it could not be reached.
+    ServeStale { question: Question },
+    /// Records were evicted from the cache before they expired to stay within its limits.
+    CacheEvictionPressure { evicted: usize },
+    /// The answer to the question failed DNSSEC validation.
+    DnssecBogus { question: Question, reason: String },
+}
+
+impl Display for ClientEvent {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::UpstreamLame { address, reason } => write!(f, "name server '{address}' is lame: {reason}"),
+            Self::UpstreamDown { address, retry_in } => write!(f, "name server '{address}' is down, retrying in {}ms", retry_in.as_millis()),
+            Self::UpstreamRecovered { address } => write!(f, "name server '{address}' is back up"),
+            Self::TransportDowngraded { address, downgrade } => write!(f, "name server '{address}' downgraded: {downgrade}"),
+            Self::ServeStale { question } => write!(f, "serving stale answer for '{question}'"),
+            Self::CacheEvictionPressure { evicted } => write!(f, "evicted {evicted} unexpired records from the cache"),
+            Self::DnssecBogus { question, reason } => write!(f, "bogus DNSSEC answer for '{question}': {reason}"),
+        }
+    }
+}
+
+/// What a lame name server did.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum LameReason {
+    /// The server refused to answer.
+    Refused,
+    /// The server answered without authority and without an answer or a referral.
+    NotAuthoritative,
+}
+
+impl LameReason {
+    /// Checks whether the response shows that the name server that sent it is lame.
+    pub(crate) fn from_response(response: &Message) -> Option<Self> {
+        match response.rcode {
+            RCode::Refused => Some(Self::Refused),
+            RCode::NoError if !response.authoritative_answer && response.answer.is_empty() && response.authority.is_empty() => Some(Self::NotAuthoritative),
+            _ => None,
+        }
+    }
+}
+
+impl Display for LameReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Refused => write!(f, "refused"),
+            Self::NotAuthoritative => write!(f, "not authoritative"),
+        }
+    }
+}
+
+/// The feature that queries are now sent without.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Downgrade {
+    /// The server rejected a query with an OPT record, so queries to it are sent without EDNS.
+    NoEdns,
+}
+
+impl Display for Downgrade {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::NoEdns => write!(f, "EDNS disabled"),
+        }
+    }
+}
+
+/// Distributes client events to every subscriber. Publishing is cheap when there are none.
+#[derive(Debug)]
+pub(crate) struct EventBus {
+    sender: broadcast::Sender<ClientEvent>,
+}
+
+impl EventBus {
+    #[inline]
+    pub fn new() -> Self {
+        let (sender, _) = broadcast::channel(EVENT_CAPACITY);
+        Self { sender }
+    }
+
+    #[inline]
+    pub fn subscribe(&self) -> broadcast::Receiver<ClientEvent> {
+        self.sender.subscribe()
+    }
+
+    #[inline]
+    pub fn publish(&self, event: ClientEvent) {
+        // Sending only fails if nobody is subscribed, in which case the event is not needed.
+        let _ = self.sender.send(event);
+    }
+}
+
+#[cfg(test)]
+mod events_tests {
+    use dns_lib::{query::{message::Message, qr::QR, question::Question}, resource_record::{rclass::RClass, rcode::RCode, rtype::RType}, types::c_domain_name::CDomainName};
+    use tokio::sync::broadcast::error::TryRecvError;
+
+    use super::{ClientEvent, Downgrade, EventBus, LameReason};
+
+    fn response(rcode: RCode, authoritative_answer: bool) -> Message {
+        let question = Question::new(CDomainName::from_utf8("example.com.").unwrap(), RType::A, RClass::Internet);
+        let mut response = Message::from(&question);
+        response.qr = QR::Response;
+        response.rcode = rcode;
+        response.authoritative_answer = authoritative_answer;
+        response
+    }
+
+    #[test]
+    fn refused_is_lame() {
+        assert_eq!(LameReason::from_response(&response(RCode::Refused, false)), Some(LameReason::Refused));
+    }
+
+    #[test]
+    fn empty_non_authoritative_answer_is_lame() {
+        assert_eq!(LameReason::from_response(&response(RCode::NoError, false)), Some(LameReason::NotAuthoritative));
+        assert_eq!(LameReason::from_response(&response(RCode::NoError, true)), None);
+        assert_eq!(LameReason::from_response(&response(RCode::NXDomain, false)), None);
+    }
+
+    #[test]
+    fn every_subscriber_receives_events() {
+        let bus = EventBus::new();
+        // Publishing without subscribers must not fail.
+        bus.publish(ClientEvent::CacheEvictionPressure { evicted: 1 });
+
+        let mut first = bus.subscribe();
+        let mut second = bus.subscribe();
+        let event = ClientEvent::TransportDowngraded { address: [192, 0, 2, 1].into(), downgrade: Downgrade::NoEdns };
+        bus.publish(event.clone());
+
+        assert_eq!(first.try_recv(), Ok(event.clone()));
+        assert_eq!(second.try_recv(), Ok(event));
+        assert_eq!(first.try_recv(), Err(TryRecvError::Empty));
+    }
+}
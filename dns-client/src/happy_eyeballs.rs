@@ -0,0 +1,89 @@
+use std::time::Duration;
+
+use dns_lib::resource_record::rtype::RType;
+
+/// Controls how the addresses of a zone's name servers are raced, in the style of Happy Eyeballs.
+/// Queries to IPv6 and IPv4 addresses are started alternately, each one `attempt_delay` after the
+/// last, until one of them answers. The queries that are still running at that point are
+/// cancelled. On a network where IPv6 is broken, the IPv6 query fails or stalls and the IPv4
+/// query that was started shortly after answers instead, so the broken family costs at most one
+/// attempt delay.
+///
+/// https://datatracker.ietf.org/doc/html/rfc8305#section-5
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct HappyEyeballsConfig {
+    /// Start with an IPv6 address. If this is false, an IPv4 address is queried first.
+    pub prefer_ipv6: bool,
+    /// How long to wait for a query to answer before starting the next one. RFC 8305 recommends
+    /// 250ms.
+    pub attempt_delay: Duration,
+    /// The most queries that are allowed to run at once for a single question.
+    pub max_concurrency: usize,
+}
+
+impl Default for HappyEyeballsConfig {
+    fn default() -> Self {
+        Self {
+            prefer_ipv6: true,
+            attempt_delay: Duration::from_millis(250),
+            max_concurrency: 4,
+        }
+    }
+}
+
+/// Keeps track of which address family should be queried next.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct FamilyAlternator {
+    prefer_ipv6: bool,
+    /// The address type of the last query that was started.
+    last: Option<RType>,
+}
+
+impl FamilyAlternator {
+    #[inline]
+    pub fn new(config: &HappyEyeballsConfig) -> Self {
+        Self { prefer_ipv6: config.prefer_ipv6, last: None }
+    }
+
+    /// The address type (A or AAAA) that the next query should be sent to, if there is a name
+    /// server with that type of address left.
+    #[inline]
+    pub fn next(&self) -> RType {
+        match self.last {
+            Some(RType::AAAA) => RType::A,
+            Some(_) => RType::AAAA,
+            None if self.prefer_ipv6 => RType::AAAA,
+            None => RType::A,
+        }
+    }
+
+    #[inline]
+    pub fn started(&mut self, rtype: RType) {
+        self.last = Some(rtype);
+    }
+}
+
+#[cfg(test)]
+mod happy_eyeballs_tests {
+    use dns_lib::resource_record::rtype::RType;
+
+    use super::{FamilyAlternator, HappyEyeballsConfig};
+
+    #[test]
+    fn families_alternate_starting_with_the_preferred_one() {
+        let mut alternator = FamilyAlternator::new(&HappyEyeballsConfig::default());
+        assert_eq!(alternator.next(), RType::AAAA);
+        alternator.started(RType::AAAA);
+        assert_eq!(alternator.next(), RType::A);
+        alternator.started(RType::A);
+        assert_eq!(alternator.next(), RType::AAAA);
+
+        // If there are no IPv6 addresses left, IPv4 addresses are queried back to back. IPv6 is
+        // still tried next in case there are more.
+        alternator.started(RType::A);
+        assert_eq!(alternator.next(), RType::AAAA);
+
+        let alternator = FamilyAlternator::new(&HappyEyeballsConfig { prefer_ipv6: false, ..HappyEyeballsConfig::default() });
+        assert_eq!(alternator.next(), RType::A);
+    }
+}
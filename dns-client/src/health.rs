@@ -0,0 +1,349 @@
+use std::{fmt::Display, io, net::SocketAddr, sync::{atomic::{AtomicBool, Ordering}, Mutex}, time::{Duration, Instant}};
+
+use log::{debug, warn};
+use tokio::{io::{AsyncReadExt, AsyncWriteExt}, net::{TcpListener, TcpStream}, task::JoinHandle, time::timeout};
+
+use crate::DNSAsyncClient;
+
+/// How long a successful upstream query keeps the client live if no later query succeeds.
+pub const DEFAULT_LIVENESS_WINDOW: Duration = Duration::from_secs(60);
+
+/// Requests that are larger than this or that take longer than `REQUEST_TIMEOUT` to arrive are
+/// dropped. Health checks are a single request line and a few headers.
+const MAX_REQUEST_SIZE: usize = 2048;
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Where the health endpoint listens and what counts as live.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct HealthConfig {
+    pub address: SocketAddr,
+    /// The client is live if an upstream query succeeded within this window, or if no upstream
+    /// query failed within it.
+    pub liveness_window: Duration,
+}
+
+impl HealthConfig {
+    #[inline]
+    pub fn new(address: SocketAddr) -> Self {
+        Self { address, liveness_window: DEFAULT_LIVENESS_WINDOW }
+    }
+}
+
+/// The conditions that the health endpoint reports on. Updated by the client as it runs.
+#[derive(Debug)]
+pub(crate) struct HealthState {
+    primed: AtomicBool,
+    cache_loaded: AtomicBool,
+    last_upstream_success: Mutex<Option<Instant>>,
+    last_upstream_failure: Mutex<Option<Instant>>,
+}
+
+impl HealthState {
+    #[inline]
+    pub fn new() -> Self {
+        Self {
+            primed: AtomicBool::new(false),
+            cache_loaded: AtomicBool::new(false),
+            last_upstream_success: Mutex::new(None),
+            last_upstream_failure: Mutex::new(None),
+        }
+    }
+
+    #[inline]
+    pub fn set_primed(&self) {
+        self.primed.store(true, Ordering::Release);
+    }
+
+    #[inline]
+    pub fn set_cache_loaded(&self) {
+        self.cache_loaded.store(true, Ordering::Release);
+    }
+
+    #[inline]
+    pub fn record_upstream_success(&self) {
+        let mut w_last_upstream_success = self.last_upstream_success.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        *w_last_upstream_success = Some(Instant::now());
+        drop(w_last_upstream_success);
+    }
+
+    #[inline]
+    pub fn record_upstream_failure(&self) {
+        let mut w_last_upstream_failure = self.last_upstream_failure.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        *w_last_upstream_failure = Some(Instant::now());
+        drop(w_last_upstream_failure);
+    }
+
+    pub fn report(&self, liveness_window: Duration) -> HealthReport {
+        let r_last_upstream_success = self.last_upstream_success.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let since_upstream_success = r_last_upstream_success.map(|last_success| last_success.elapsed());
+        drop(r_last_upstream_success);
+        let r_last_upstream_failure = self.last_upstream_failure.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let since_upstream_failure = r_last_upstream_failure.map(|last_failure| last_failure.elapsed());
+        drop(r_last_upstream_failure);
+
+        let live = match (since_upstream_success, since_upstream_failure) {
+            (Some(since_success), _) if since_success <= liveness_window => true,
+            // Queries have been failing and none have succeeded recently.
+            (_, Some(since_failure)) if since_failure <= liveness_window => false,
+            // Nothing has been sent recently, so there is no sign of a problem.
+            _ => true,
+        };
+        HealthReport {
+            primed: self.primed.load(Ordering::Acquire),
+            cache_loaded: self.cache_loaded.load(Ordering::Acquire),
+            live,
+            since_upstream_success,
+        }
+    }
+}
+
+/// A point-in-time view of the client's health.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct HealthReport {
+    /// The root hints have been primed.
+    pub primed: bool,
+    /// The cache has been loaded, either with the root hints or from a saved copy.
+    pub cache_loaded: bool,
+    /// An upstream query succeeded recently, or none have failed recently.
+    pub live: bool,
+    /// The time since an upstream query last succeeded.
+    pub since_upstream_success: Option<Duration>,
+}
+
+impl HealthReport {
+    /// The client can answer queries.
+    #[inline]
+    pub fn ready(&self) -> bool {
+        self.primed && self.cache_loaded
+    }
+
+    fn to_json(&self) -> String {
+        let since_upstream_success = match self.since_upstream_success {
+            Some(since_upstream_success) => since_upstream_success.as_secs().to_string(),
+            None => "null".to_string(),
+        };
+        format!(
+            "{{\"ready\":{},\"live\":{},\"primed\":{},\"cache_loaded\":{},\"seconds_since_upstream_success\":{}}}\n",
+            self.ready(), self.live, self.primed, self.cache_loaded, since_upstream_success
+        )
+    }
+}
+
+impl Display for HealthReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "ready: {}, live: {}, primed: {}, cache loaded: {}", self.ready(), self.live, self.primed, self.cache_loaded)?;
+        if let Some(since_upstream_success) = self.since_upstream_success {
+            write!(f, ", last upstream success {}s ago", since_upstream_success.as_secs())?;
+        }
+        Ok(())
+    }
+}
+
+/// A running health endpoint. The endpoint stops when this is dropped.
+///
+/// It answers `GET /livez` and `GET /readyz` with `200 OK` or `503 Service Unavailable` and
+/// `GET /healthz` with the same status as `/readyz`. Every response has the full `HealthReport` as
+/// a JSON body.
+pub struct HealthServer {
+    local_address: SocketAddr,
+    task: JoinHandle<()>,
+}
+
+impl HealthServer {
+    /// The address that the endpoint is listening on. Useful if it was bound to port 0.
+    #[inline]
+    pub fn local_address(&self) -> SocketAddr { self.local_address }
+}
+
+impl Drop for HealthServer {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+impl DNSAsyncClient {
+    /// The client's current readiness and liveness.
+    #[inline]
+    pub fn health(&self, liveness_window: Duration) -> HealthReport {
+        self.health.report(liveness_window)
+    }
+
+    /// Records that the cache has been filled from a saved copy. Loading the root hints through
+    /// `bootstrap_root_hints()` records this automatically.
+    #[inline]
+    pub fn set_cache_loaded(&self) {
+        self.health.set_cache_loaded();
+    }
+
+    /// Starts an HTTP endpoint that reports the client's readiness and liveness so that it can be
+    /// managed by an orchestration system.
+    pub async fn serve_health(&self, config: HealthConfig) -> io::Result<HealthServer> {
+        let listener = TcpListener::bind(config.address).await?;
+        let local_address = listener.local_addr()?;
+        let health = self.health.clone();
+        let task = tokio::spawn(async move {
+            loop {
+                match listener.accept().await {
+                    Ok((stream, peer)) => {
+                        let health = health.clone();
+                        tokio::spawn(async move {
+                            if let Err(error) = respond(stream, &health, config.liveness_window).await {
+                                debug!("Health check from {peer} failed: {error}");
+                            }
+                        });
+                    },
+                    Err(error) => warn!("Health endpoint on {local_address} failed to accept a connection: {error}"),
+                }
+            }
+        });
+        Ok(HealthServer { local_address, task })
+    }
+}
+
+async fn respond(mut stream: TcpStream, health: &HealthState, liveness_window: Duration) -> io::Result<()> {
+    let mut request = Vec::with_capacity(256);
+    let read_request = async {
+        let mut buffer = [0; 256];
+        while !request.windows(4).any(|window| window == b"\r\n\r\n") {
+            let read = stream.read(&mut buffer).await?;
+            if read == 0 {
+                break;
+            }
+            request.extend_from_slice(&buffer[..read]);
+            if request.len() > MAX_REQUEST_SIZE {
+                return Err(io::Error::new(io::ErrorKind::InvalidData, "request too large"));
+            }
+        }
+        Ok(())
+    };
+    match timeout(REQUEST_TIMEOUT, read_request).await {
+        Ok(result) => result?,
+        Err(_) => return Err(io::Error::new(io::ErrorKind::TimedOut, "request timed out")),
+    }
+
+    let request_line = request.split(|byte| *byte == b'\n').next().unwrap_or_default();
+    let mut parts = request_line.split(|byte| *byte == b' ');
+    let method = parts.next().unwrap_or_default();
+    let path = parts.next().unwrap_or_default();
+
+    let report = health.report(liveness_window);
+    let (status, body) = match (method, path) {
+        (b"GET", b"/livez") => (healthy_status(report.live), report.to_json()),
+        (b"GET", b"/readyz" | b"/healthz") => (healthy_status(report.ready()), report.to_json()),
+        (b"GET", _) => ("404 Not Found", String::new()),
+        _ => ("405 Method Not Allowed", String::new()),
+    };
+    let response = format!(
+        "HTTP/1.1 {status}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nCache-Control: no-store\r\nConnection: close\r\n\r\n{body}",
+        body.len()
+    );
+    stream.write_all(response.as_bytes()).await?;
+    stream.shutdown().await
+}
+
+#[inline]
+fn healthy_status(healthy: bool) -> &'static str {
+    if healthy {
+        "200 OK"
+    } else {
+        "503 Service Unavailable"
+    }
+}
+
+#[cfg(test)]
+mod health_tests {
+    use std::{net::SocketAddr, sync::Arc, time::Duration};
+
+    use dns_cache::asynchronous::async_main_cache::AsyncMainTreeCache;
+    use tokio::{io::{AsyncReadExt, AsyncWriteExt}, net::TcpStream};
+
+    use crate::DNSAsyncClient;
+
+    use super::{HealthConfig, HealthState};
+
+    const WINDOW: Duration = Duration::from_secs(60);
+    /// A window short enough that `AGE` takes an event out of it.
+    const SHORT_WINDOW: Duration = Duration::from_millis(20);
+    const AGE: Duration = Duration::from_millis(40);
+
+    /// Sends a request to the endpoint and returns the status line of the response.
+    async fn status(address: SocketAddr, method: &str, path: &str) -> String {
+        let mut stream = TcpStream::connect(address).await.unwrap();
+        stream.write_all(format!("{method} {path} HTTP/1.1\r\nHost: localhost\r\n\r\n").as_bytes()).await.unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+        response.lines().next().unwrap().to_string()
+    }
+
+    #[test]
+    fn liveness_follows_upstream_queries() {
+        let health = HealthState::new();
+        // Nothing has been sent, so there is no sign of a problem.
+        let report = health.report(WINDOW);
+        assert!(report.live);
+        assert_eq!(report.since_upstream_success, None);
+
+        health.record_upstream_failure();
+        assert!(!health.report(WINDOW).live);
+
+        health.record_upstream_success();
+        let report = health.report(WINDOW);
+        assert!(report.live);
+        assert!(report.since_upstream_success.is_some());
+
+        // A failure does not outweigh a success that is still within the window.
+        health.record_upstream_failure();
+        assert!(health.report(WINDOW).live);
+    }
+
+    #[tokio::test]
+    async fn liveness_recovers_once_failures_leave_the_window() {
+        let health = HealthState::new();
+        health.record_upstream_success();
+        tokio::time::sleep(AGE).await;
+        // The success is too old to count, so a new failure makes the client unhealthy.
+        health.record_upstream_failure();
+        assert!(!health.report(SHORT_WINDOW).live);
+        // The same failure is within a longer window, but so is the success.
+        assert!(health.report(WINDOW).live);
+
+        tokio::time::sleep(AGE).await;
+        assert!(health.report(SHORT_WINDOW).live);
+    }
+
+    #[test]
+    fn ready_once_primed_and_loaded() {
+        let health = HealthState::new();
+        assert!(!health.report(WINDOW).ready());
+        health.set_primed();
+        assert!(!health.report(WINDOW).ready());
+        health.set_cache_loaded();
+        assert!(health.report(WINDOW).ready());
+    }
+
+    #[tokio::test]
+    async fn endpoint_reports_each_state() {
+        let client = DNSAsyncClient::new(Arc::new(AsyncMainTreeCache::new())).await;
+        let server = client.serve_health(HealthConfig::new("127.0.0.1:0".parse().unwrap())).await.unwrap();
+        let address = server.local_address();
+
+        assert_eq!(status(address, "GET", "/readyz").await, "HTTP/1.1 503 Service Unavailable");
+        assert_eq!(status(address, "GET", "/healthz").await, "HTTP/1.1 503 Service Unavailable");
+        assert_eq!(status(address, "GET", "/livez").await, "HTTP/1.1 200 OK");
+
+        client.health.set_primed();
+        client.set_cache_loaded();
+        client.health.record_upstream_failure();
+        assert_eq!(status(address, "GET", "/readyz").await, "HTTP/1.1 200 OK");
+        assert_eq!(status(address, "GET", "/healthz").await, "HTTP/1.1 200 OK");
+        assert_eq!(status(address, "GET", "/livez").await, "HTTP/1.1 503 Service Unavailable");
+
+        client.health.record_upstream_success();
+        assert_eq!(status(address, "GET", "/livez").await, "HTTP/1.1 200 OK");
+
+        assert_eq!(status(address, "GET", "/metrics").await, "HTTP/1.1 404 Not Found");
+        assert_eq!(status(address, "POST", "/livez").await, "HTTP/1.1 405 Method Not Allowed");
+        drop(server);
+        client.close().await;
+    }
+}
@@ -0,0 +1,97 @@
+use std::{sync::atomic::{AtomicU64, Ordering}, time::Duration};
+
+const PERCENT: u64 = 100;
+
+/// Controls hedged queries. When a name server has not answered within roughly the 95th
+/// percentile of its usual response time, the same query is sent to the next best name server for
+/// the zone. Whichever answers first is used and the other query is cancelled. This trades a few
+/// extra queries for a shorter tail on lossy paths, where waiting for a retransmission is what
+/// makes the slow queries slow.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct HedgingConfig {
+    /// How long to wait before sending the hedge, as a percentage of the average response time of
+    /// the name server that was queried first. Response times have a long tail, so the default of
+    /// 300% is close to their 95th percentile.
+    pub delay_percent: u32,
+    /// The shortest time to wait before hedging, so that fast name servers are not hedged because
+    /// of ordinary jitter.
+    pub min_delay: Duration,
+    /// The longest time to wait before hedging.
+    pub max_delay: Duration,
+    /// The time to wait before hedging when the name server has not answered enough queries yet
+    /// for its response time to be known.
+    pub default_delay: Duration,
+}
+
+impl Default for HedgingConfig {
+    fn default() -> Self {
+        Self {
+            delay_percent: 300,
+            min_delay: Duration::from_millis(10),
+            max_delay: Duration::from_secs(1),
+            default_delay: Duration::from_millis(200),
+        }
+    }
+}
+
+impl HedgingConfig {
+    /// How long to wait for a name server with this average response time (in milliseconds)
+    /// before hedging.
+    pub fn delay(&self, average_response_time: Option<u32>) -> Duration {
+        let Some(average_response_time) = average_response_time else {
+            return self.default_delay;
+        };
+        let delay = Duration::from_millis((u64::from(average_response_time) * u64::from(self.delay_percent)) / PERCENT);
+        delay.clamp(self.min_delay, self.max_delay.max(self.min_delay))
+    }
+}
+
+/// A point-in-time copy of the hedging counters.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct HedgingStats {
+    /// The number of hedges that were sent.
+    pub hedged: u64,
+    /// The number of hedged queries where the hedge answered first. These are the queries that
+    /// hedging made faster.
+    pub hedge_answered_first: u64,
+    /// The number of hedged queries where the name server that was queried first still answered
+    /// first. The hedge was wasted.
+    pub primary_answered_first: u64,
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct HedgingRecorder {
+    hedged: AtomicU64,
+    hedge_answered_first: AtomicU64,
+    primary_answered_first: AtomicU64,
+}
+
+impl HedgingRecorder {
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[inline]
+    pub fn record_hedge(&self) {
+        self.hedged.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Records which query answered first once a hedge has been sent.
+    #[inline]
+    pub fn record_answer(&self, from_hedge: bool) {
+        match from_hedge {
+            true => self.hedge_answered_first.fetch_add(1, Ordering::Relaxed),
+            false => self.primary_answered_first.fetch_add(1, Ordering::Relaxed),
+        };
+    }
+
+    #[inline]
+    pub fn stats(&self) -> HedgingStats {
+        HedgingStats {
+            hedged: self.hedged.load(Ordering::Relaxed),
+            hedge_answered_first: self.hedge_answered_first.load(Ordering::Relaxed),
+            primary_answered_first: self.primary_answered_first.load(Ordering::Relaxed),
+        }
+    }
+}
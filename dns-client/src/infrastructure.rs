@@ -0,0 +1,107 @@
+use std::{collections::HashMap, net::IpAddr, time::{Duration, Instant}};
+
+use dns_lib::query::nsid::Nsid;
+use log::debug;
+use tokio::sync::RwLock;
+
+/// What has been learned about a single upstream name server.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ServerIdentity {
+    /// The NSID returned in the most recent response that contained one.
+    pub nsid: Nsid,
+    /// When the NSID was last received.
+    pub last_seen: Instant,
+}
+
+/// How long a name server that rejected a query with EDNS is sent queries without it. Afterwards,
+/// EDNS is tried again in case the server was upgraded or the rejection came from a middlebox on
+/// a path that has since changed.
+const NO_EDNS_DURATION: Duration = Duration::from_secs(60 * 60);
+
+/// How long a name server stays marked as lame after it last answered like one.
+const LAME_DURATION: Duration = Duration::from_secs(10 * 60);
+
+/// Information about the name servers that the client has queried, keyed by address. Because
+/// an anycast address is served by many instances, the identity recorded for an address may
+/// change from one response to the next.
+#[derive(Debug, Default)]
+pub(crate) struct InfrastructureCache {
+    identities: RwLock<HashMap<IpAddr, ServerIdentity>>,
+    /// When each name server last answered a query with EDNS with FORMERR.
+    no_edns: RwLock<HashMap<IpAddr, Instant>>,
+    /// When each name server last gave a lame response.
+    lame: RwLock<HashMap<IpAddr, Instant>>,
+}
+
+impl InfrastructureCache {
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub async fn identity(&self, address: &IpAddr) -> Option<ServerIdentity> {
+        let r_identities = self.identities.read().await;
+        let identity = r_identities.get(address).cloned();
+        drop(r_identities);
+        identity
+    }
+
+    pub async fn identities(&self) -> HashMap<IpAddr, ServerIdentity> {
+        let r_identities = self.identities.read().await;
+        let identities = r_identities.clone();
+        drop(r_identities);
+        identities
+    }
+
+    pub async fn record_nsid(&self, address: IpAddr, nsid: Nsid) {
+        let mut w_identities = self.identities.write().await;
+        match w_identities.get_mut(&address) {
+            Some(identity) if identity.nsid == nsid => identity.last_seen = Instant::now(),
+            Some(identity) => {
+                debug!("Name server '{address}' changed identity from '{}' to '{nsid}'", identity.nsid);
+                identity.nsid = nsid;
+                identity.last_seen = Instant::now();
+            },
+            None => {
+                debug!("Name server '{address}' identified itself as '{nsid}'");
+                w_identities.insert(address, ServerIdentity { nsid, last_seen: Instant::now() });
+            },
+        }
+        drop(w_identities);
+    }
+
+    /// Whether queries to the name server should include an OPT record. This is only false for a
+    /// while after the server rejected one.
+    pub async fn supports_edns(&self, address: &IpAddr) -> bool {
+        let r_no_edns = self.no_edns.read().await;
+        let rejected = r_no_edns.get(address).copied();
+        drop(r_no_edns);
+        rejected.is_none_or(|rejected| rejected.elapsed() >= NO_EDNS_DURATION)
+    }
+
+    pub async fn record_no_edns(&self, address: IpAddr) {
+        debug!("Name server '{address}' rejected a query with EDNS");
+        let mut w_no_edns = self.no_edns.write().await;
+        w_no_edns.insert(address, Instant::now());
+        drop(w_no_edns);
+    }
+
+    pub async fn is_lame(&self, address: &IpAddr) -> bool {
+        let r_lame = self.lame.read().await;
+        let marked = r_lame.get(address).copied();
+        drop(r_lame);
+        marked.is_some_and(|marked| marked.elapsed() < LAME_DURATION)
+    }
+
+    /// Marks the name server as lame. Returns true if it was not already marked.
+    pub async fn record_lame(&self, address: IpAddr) -> bool {
+        let mut w_lame = self.lame.write().await;
+        let previous = w_lame.insert(address, Instant::now());
+        drop(w_lame);
+        let newly_lame = previous.is_none_or(|marked| marked.elapsed() >= LAME_DURATION);
+        if newly_lame {
+            debug!("Name server '{address}' is lame");
+        }
+        newly_lame
+    }
+}
@@ -1,39 +1,361 @@
-use std::{collections::HashMap, sync::Arc};
+use std::{collections::HashMap, net::{IpAddr, SocketAddr}, sync::Arc};
 
-use async_lib::once_watch;
+use async_lib::{once_watch, poll_budget::PollLoopSnapshot};
 use async_trait::async_trait;
-use dns_cache::asynchronous::{async_cache::AsyncTreeCache, async_main_cache::AsyncMainTreeCache};
-use dns_lib::{interface::client::{Answer, AsyncClient, Context, Response}, query::question::Question, resource_record::rcode::RCode};
-use log::info;
-use network::socket_manager::SocketManager;
+use consistency::AnswerConsistency;
+use delegation::{DelegatedCache, Delegation};
+use events::EventBus;
+use health::HealthState;
+use hedging::HedgingRecorder;
+use dns_lib::{interface::{cache::{cache::SharedAsyncCache, main_cache::SharedAsyncMainCache, CacheStats}, client::{AsyncClient, Context, ErrorResponse, Response}}, query::{chaos::ChaosQuery, message::Message, question::QuestionKey}, resource_record::rcode::RCode, types::c_domain_name::{CDomainName, CmpDomainName}};
+use infrastructure::InfrastructureCache;
+use local_data::LocalData;
+use middleware::{into_response, MiddlewareChain, Next};
+use network::{async_query::QueryOpt, errors::QueryError, socket_manager::{SocketManager, SocketManagerStats}};
+#[cfg(feature = "tls")]
+use network::dot::DotClient;
+use poisoning::{PoisoningGuard, PoisoningStats};
+use prefetch::Prefetcher;
 use query::recursive_query::recursive_query;
-use result::{QOk, QResult};
-use tokio::sync::RwLock;
+use result::QResult;
+use tokio::sync::{broadcast, RwLock};
+use validation::ResponseValidator;
+use zone_stats::ZoneStatsRecorder;
 
+pub mod axfr;
+pub mod config;
+mod consistency;
+pub mod delegation;
+pub mod events;
+mod happy_eyeballs;
+pub mod health;
+mod hedging;
+mod infrastructure;
+pub mod local_data;
+pub mod lookup;
+pub mod middleware;
+mod negative;
+pub mod policy;
+mod prefetch;
+pub mod prelude;
+pub mod propagation;
+mod poisoning;
 mod qname_minimizer;
 mod query;
+pub mod query_log;
 mod result;
+pub mod root_hints;
+#[cfg(test)]
+mod test_support;
+mod tsig;
+pub mod upstream;
+mod validation;
+pub mod zone_stats;
 
+pub use config::ClientConfig;
+pub use consistency::ConsistencyStats;
+pub use events::ClientEvent;
+pub use happy_eyeballs::HappyEyeballsConfig;
+pub use health::{HealthConfig, HealthReport};
+pub use hedging::{HedgingConfig, HedgingStats};
+pub use infrastructure::ServerIdentity;
+pub use prefetch::{PrefetchConfig, PrefetchStats};
+pub use query_log::QueryLog;
+pub use root_hints::{HintsWarning, RootHintsConfig, RootHintsError};
+pub use tsig::TsigKeys;
+pub use validation::ValidationStats;
+pub use zone_stats::ZoneStats;
+
+
+/// The sizes of the resources held by a client, for dashboards and capacity planning.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ClientStats {
+    pub sockets: SocketManagerStats,
+    /// `None` if the cache cannot count its records cheaply.
+    pub cache: Option<CacheStats>,
+}
+
+/// Identifies a query that is being resolved. Queries for the same question in different views are
+/// resolved separately so that their answers stay in their own caches.
+type ActiveQueryKey = (Option<Arc<str>>, QuestionKey);
 
 pub struct DNSAsyncClient {
-    cache: Arc<AsyncMainTreeCache>,
+    cache: SharedAsyncMainCache,
+    views: HashMap<Arc<str>, SharedAsyncMainCache>,
     socket_manager: SocketManager,
-    active_queries: RwLock<HashMap<Question, once_watch::Sender<QResult>>>,
+    active_queries: RwLock<HashMap<ActiveQueryKey, once_watch::Sender<QResult>>>,
+    config: ClientConfig,
+    poisoning: PoisoningGuard,
+    validator: ResponseValidator,
+    consistency: AnswerConsistency,
+    middleware: MiddlewareChain,
+    tsig_keys: TsigKeys,
+    infrastructure: InfrastructureCache,
+    zone_stats: ZoneStatsRecorder,
+    health: Arc<HealthState>,
+    hedging: Arc<HedgingRecorder>,
+    prefetcher: Prefetcher,
+    events: EventBus,
+    local_data: LocalData,
+    #[cfg(feature = "tls")]
+    dot_clients: HashMap<SocketAddr, Arc<DotClient>>,
 }
 
 impl DNSAsyncClient {
     #[inline]
-    pub async fn new(cache: Arc<AsyncMainTreeCache>) -> Self {
+    pub async fn new(cache: SharedAsyncMainCache) -> Self {
+        Self::with_config(cache, ClientConfig::default()).await
+    }
+
+    #[inline]
+    pub async fn with_config(cache: SharedAsyncMainCache, config: ClientConfig) -> Self {
+        Self::with_socket_manager(cache, config, SocketManager::new().await)
+    }
+
+    /// Creates a client that sends its queries through an existing socket manager. Any number of
+    /// clients, each with their own cache and config, can share one manager (and the sockets and
+    /// per-upstream statistics it has learned) by passing in clones of it.
+    #[inline]
+    pub fn with_socket_manager(cache: SharedAsyncMainCache, config: ClientConfig, socket_manager: SocketManager) -> Self {
+        #[cfg(feature = "tls")]
+        let dot_clients = upstream::dot_clients(&config.forwarders);
         Self {
             cache,
-            socket_manager: SocketManager::new().await,
+            views: HashMap::new(),
+            socket_manager,
             active_queries: RwLock::new(HashMap::new()),
+            validator: ResponseValidator::new(config.compact_denial, config.max_response_records, config.max_response_size),
+            config,
+            poisoning: PoisoningGuard::new(),
+            consistency: AnswerConsistency::new(),
+            middleware: MiddlewareChain::default(),
+            tsig_keys: TsigKeys::default(),
+            infrastructure: InfrastructureCache::new(),
+            zone_stats: ZoneStatsRecorder::new(),
+            health: Arc::new(HealthState::new()),
+            hedging: Arc::new(HedgingRecorder::new()),
+            prefetcher: Prefetcher::new(),
+            events: EventBus::new(),
+            local_data: LocalData::new(),
+            #[cfg(feature = "tls")]
+            dot_clients,
+        }
+    }
+
+    #[inline]
+    pub fn config(&self) -> &ClientConfig { &self.config }
+
+    #[inline]
+    pub fn poisoning_stats(&self) -> PoisoningStats { self.poisoning.stats() }
+
+    #[inline]
+    pub fn validation_stats(&self) -> ValidationStats { self.validator.stats() }
+
+    /// How many duplicate and contradictory records were removed from answers before they were
+    /// returned.
+    #[inline]
+    pub fn consistency_stats(&self) -> ConsistencyStats { self.consistency.stats() }
+
+    /// How often hedged queries were sent and how often they answered before the name server
+    /// that was queried first. Only counted when `hedging` is enabled in the config.
+    #[inline]
+    pub fn hedging_stats(&self) -> HedgingStats { self.hedging.stats() }
+
+    /// How many names were prefetched before their records expired. Only counted when `prefetch`
+    /// is enabled in the config.
+    #[inline]
+    pub fn prefetch_stats(&self) -> PrefetchStats { self.prefetcher.stats() }
+
+    /// The outcomes of the queries sent to the zone's name servers. The same counts are reported
+    /// through the `metrics` facade as `zone_stats::ZONE_OUTCOMES_METRIC`.
+    #[inline]
+    pub async fn zone_stats(&self, zone: &CDomainName) -> Option<ZoneStats> {
+        self.zone_stats.stats(zone).await
+    }
+
+    /// The outcomes of the queries sent to every zone's name servers, keyed by the lowercase zone
+    /// name.
+    #[inline]
+    pub async fn all_zone_stats(&self) -> HashMap<CDomainName, ZoneStats> {
+        self.zone_stats.all_stats().await
+    }
+
+    /// How hard the poll loops of the query state machines have been working. These are shared by
+    /// every client in the process. A loop with a high yield count relative to its polls is doing
+    /// a lot of work per poll or spinning without making progress.
+    #[inline]
+    pub fn poll_loop_stats(&self) -> Vec<PollLoopSnapshot> {
+        let mut stats = network::mixed_tcp_udp::poll_loop_stats().to_vec();
+        stats.push(query::round_robin_query::NS_ROUND_ROBIN_POLL_LOOP.snapshot());
+        stats
+    }
+
+    /// The identity that the name server at this address most recently reported. This is only
+    /// recorded if `request_nsid` is enabled in the config.
+    #[inline]
+    pub async fn server_identity(&self, address: &IpAddr) -> Option<ServerIdentity> {
+        self.infrastructure.identity(address).await
+    }
+
+    /// The identities reported by every name server that has been queried.
+    #[inline]
+    pub async fn server_identities(&self) -> HashMap<IpAddr, ServerIdentity> {
+        self.infrastructure.identities().await
+    }
+
+    /// Whether queries to the name server at this address are sent with an OPT record. Name
+    /// servers that answer a query with EDNS with FORMERR are sent queries without EDNS for a
+    /// while afterwards.
+    #[inline]
+    pub async fn edns_supported(&self, address: &IpAddr) -> bool {
+        self.infrastructure.supports_edns(address).await
+    }
+
+    /// Whether the name server at this address recently answered a query in a way that shows it
+    /// is not serving the zone it was asked about.
+    #[inline]
+    pub async fn is_lame(&self, address: &IpAddr) -> bool {
+        self.infrastructure.is_lame(address).await
+    }
+
+    /// Subscribes to the significant events that the client publishes, such as name servers being
+    /// marked lame or downgraded. Only events published after subscribing are received. A
+    /// subscriber that falls too far behind misses the oldest events and is told how many with
+    /// `RecvError::Lagged`.
+    #[inline]
+    pub fn subscribe_events(&self) -> broadcast::Receiver<ClientEvent> {
+        self.events.subscribe()
+    }
+
+    /// Publishes an event to every subscriber. This lets caches and middleware report events that
+    /// the client cannot see itself, such as serving stale answers or failing DNSSEC validation.
+    #[inline]
+    pub fn publish_event(&self, event: ClientEvent) {
+        self.events.publish(event);
+    }
+
+    /// Sends a CHAOS introspection query (such as `id.server.`) directly to the name server at
+    /// this address and returns the text it answers with. This identifies which instance of an
+    /// anycast service the address currently reaches.
+    #[inline]
+    pub async fn query_chaos(&self, query: ChaosQuery, address: &IpAddr) -> Result<Vec<String>, QueryError> {
+        query::network_query::query_chaos(self, query, address).await
+    }
+
+    /// Sends the message to the server at the address over the transport and returns its
+    /// response as it was received. The middleware, the caches, and recursion are bypassed and the
+    /// message is sent as is: it is not signed, given EDNS options, or retried over TCP if the
+    /// response is truncated. It does go through the client's sockets, so it reuses their
+    /// connections and adaptive timeouts. This is meant for diagnostics and tests.
+    ///
+    /// DNS over TLS, QUIC, and HTTPS can only be used with a server that an upstream (or a
+    /// forwarder) is configured for with that transport, since the certificate is verified
+    /// against the upstream's server name.
+    #[inline]
+    pub async fn send_message(&self, address: &SocketAddr, transport: QueryOpt, message: Message) -> Result<Message, QueryError> {
+        query::network_query::send_message(self, address, transport, message).await
+    }
+
+    #[inline]
+    pub fn middleware(&self) -> &MiddlewareChain { &self.middleware }
+
+    /// Replaces the middleware that queries pass through before they are resolved. This must be
+    /// done before the client is shared.
+    #[inline]
+    pub fn set_middleware(&mut self, middleware: MiddlewareChain) {
+        self.middleware = middleware;
+    }
+
+    #[inline]
+    pub fn tsig_keys(&self) -> &TsigKeys { &self.tsig_keys }
+
+    /// Replaces the TSIG keys that queries to particular name servers are signed with. This must be
+    /// done before the client is shared.
+    #[inline]
+    pub fn set_tsig_keys(&mut self, tsig_keys: TsigKeys) {
+        self.tsig_keys = tsig_keys;
+    }
+
+    /// The hosts file entries and static records that are answered before the cache is checked.
+    /// They can be replaced while the client is running, through this or a clone of it.
+    #[inline]
+    pub fn local_data(&self) -> &LocalData { &self.local_data }
+
+    #[inline]
+    pub fn cache(&self) -> SharedAsyncMainCache { self.cache.clone() }
+
+    /// Adds a cache that queries can be resolved with instead of the main cache by giving its name
+    /// to `Context::with_view()`. Nothing that is learned in one view is seen by the others, but
+    /// they all share the client's sockets and what it knows about the name servers. This must be
+    /// done before the client is shared.
+    ///
+    /// The cache needs its own root hints. `bootstrap_root_hints()` only loads them into the main
+    /// cache.
+    #[inline]
+    pub fn add_view(&mut self, name: impl Into<Arc<str>>, cache: SharedAsyncMainCache) -> Option<SharedAsyncMainCache> {
+        self.views.insert(name.into(), cache)
+    }
+
+    /// The cache of the view with this name.
+    #[inline]
+    pub fn view(&self, name: &str) -> Option<SharedAsyncMainCache> {
+        self.views.get(name).cloned()
+    }
+
+    /// The names of the views that have been added.
+    #[inline]
+    pub fn view_names(&self) -> impl Iterator<Item = &str> {
+        self.views.keys().map(|name| name.as_ref())
+    }
+
+    /// The cache that the context is resolved with. `None` if it asks for a view that the client
+    /// does not have.
+    #[inline]
+    fn cache_for(&self, context: &Context) -> Option<SharedAsyncMainCache> {
+        match context.view() {
+            Some(view) => self.views.get(view).cloned(),
+            None => Some(self.cache.clone()),
+        }
+    }
+
+    /// Resolves the query starting from the supplied delegation instead of the closest delegation
+    /// in the cache. Everything at or below the delegated zone is learned from the delegation's
+    /// name servers alone and none of it is added to the client's cache. This gives the view of
+    /// the zone held by a particular set of name servers, such as a DNS provider's.
+    ///
+    /// The middleware is not applied. Queries for names outside of the zone, or for a view that the
+    /// client does not have, are refused.
+    pub async fn query_with_delegation(client: Arc<Self>, context: Context, delegation: &Delegation) -> Response {
+        if !delegation.zone().is_parent_domain_of(context.qname()) {
+            return Response::Error(ErrorResponse::new(RCode::Refused));
         }
+        let Some(cache) = client.cache_for(&context) else {
+            return Response::Error(ErrorResponse::new(RCode::Refused));
+        };
+        let delegated_cache: SharedAsyncCache = Arc::new(DelegatedCache::new(cache, delegation).await);
+        let question = context.query().clone();
+        let trace = context.trace().cloned();
+        let result = recursive_query(client.clone(), delegated_cache.clone(), context).await;
+        into_response(&client, result, &delegated_cache, &question, trace).await
     }
 
+    /// The socket manager that this client sends its queries through. Cloning it shares the same
+    /// sockets.
     #[inline]
-    pub fn cache(&self) -> Arc<AsyncMainTreeCache> { self.cache.clone() }
+    pub fn socket_manager(&self) -> &SocketManager { &self.socket_manager }
 
+    /// Counts the sockets, running query tasks, and cached records that the client holds. If the
+    /// socket manager is shared, its counts include the sockets used by the other clients.
+    #[inline]
+    pub async fn stats(&self) -> ClientStats {
+        ClientStats {
+            sockets: self.socket_manager.stats().await,
+            cache: self.cache.stats().await,
+        }
+    }
+
+    /// Closes every socket in the socket manager. If the manager is shared, this closes the
+    /// sockets for all of the clients that share it.
     #[inline]
     pub async fn close(&self) {
         self.socket_manager.drop_all_sockets().await;
@@ -42,13 +364,7 @@ impl DNSAsyncClient {
 
 #[async_trait]
 impl AsyncClient for DNSAsyncClient {
-    async fn query(client: Arc<Self>, context: Context) -> Response {
-        info!("Start query '{}'", context.query());
-        let joined_cache = Arc::new(AsyncTreeCache::new(client.cache.clone()));
-        match recursive_query(client, joined_cache, context).await {
-            QResult::Err(_) => Response::Error(RCode::ServFail),
-            QResult::Fail(rcode) => Response::Error(rcode),
-            QResult::Ok(QOk { answer, name_servers, additional }) => Response::Answer(Answer { answer, name_servers, additional, authoritative: false }),
-        }
+    async fn query(self: Arc<Self>, context: Context) -> Response {
+        Next::new(&self, &self.middleware).run(context).await
     }
 }
@@ -0,0 +1,343 @@
+use std::{collections::HashMap, error::Error, fmt::Display, io, net::IpAddr, path::{Path, PathBuf}, sync::{Arc, RwLock}, time::{Duration, SystemTime}};
+
+use dns_lib::{query::question::Question, resource_record::{rclass::RClass, resource_record::{RecordData, ResourceRecord}, rtype::RType, time::Time, types::{a::A, aaaa::AAAA, ptr::PTR}}, types::c_domain_name::{CDomainName, CDomainNameError}};
+use log::{info, warn};
+use tokio::task::JoinHandle;
+
+/// The TTL given to the records read from a hosts file. It is kept short so that changes to the
+/// file reach downstream caches soon after it is reloaded.
+pub const HOSTS_TTL: Time = Time::from_secs(60);
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum HostsError {
+    BadAddress(String),
+    MissingName,
+    BadName(String, CDomainNameError),
+}
+impl Error for HostsError {}
+impl Display for HostsError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::BadAddress(address) => write!(f, "'{address}' is not an IPv4 or IPv6 address"),
+            Self::MissingName => write!(f, "The address is not given a name"),
+            Self::BadName(name, error) => write!(f, "'{name}' is not a valid domain name: {error}"),
+        }
+    }
+}
+
+/// The name that reverse lookups for the address are made for.
+///
+/// https://datatracker.ietf.org/doc/html/rfc1035#section-3.5
+/// https://datatracker.ietf.org/doc/html/rfc3596#section-2.5
+fn reverse_name(address: &IpAddr) -> CDomainName {
+    let name = match address {
+        IpAddr::V4(address) => {
+            let [a, b, c, d] = address.octets();
+            format!("{d}.{c}.{b}.{a}.in-addr.arpa.")
+        },
+        IpAddr::V6(address) => {
+            let mut name = String::with_capacity(73);
+            for octet in address.octets().iter().rev() {
+                name.push_str(&format!("{:x}.{:x}.", octet & 0x0F, octet >> 4));
+            }
+            name.push_str("ip6.arpa.");
+            name
+        },
+    };
+    CDomainName::from_utf8(&name).expect("reverse names are always valid")
+}
+
+/// Records that are answered locally instead of being resolved, indexed by owner name.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct LocalRecords {
+    names: HashMap<CDomainName, Vec<ResourceRecord>>,
+    record_count: usize,
+}
+
+impl LocalRecords {
+    #[inline]
+    pub fn new() -> Self {
+        Self { names: HashMap::new(), record_count: 0 }
+    }
+
+    pub fn insert(&mut self, record: ResourceRecord) {
+        let records = self.names.entry(record.get_name().as_lowercase()).or_default();
+        if !records.contains(&record) {
+            records.push(record);
+            self.record_count += 1;
+        }
+    }
+
+    /// Reads the entries of a file in the format of `/etc/hosts`. Each line holds an address
+    /// followed by its canonical name and any aliases, separated by whitespace. Everything after a
+    /// `#` is a comment. Every name is given an A or AAAA record for the address, and the address
+    /// is given a PTR record for the canonical name so that reverse lookups work too. Names that
+    /// are not fully qualified are treated as if they were.
+    ///
+    /// Lines that cannot be read are returned, by line number (starting from 1), alongside the
+    /// records that were.
+    pub fn from_hosts(text: &str) -> (Self, Vec<(usize, HostsError)>) {
+        let mut records = Self::new();
+        let mut invalid = Vec::new();
+        for (index, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or_default();
+            let mut fields = line.split_whitespace();
+            let Some(address) = fields.next() else {
+                continue;
+            };
+            if let Err(error) = records.insert_host(address, fields) {
+                invalid.push((index + 1, error));
+            }
+        }
+        (records, invalid)
+    }
+
+    fn insert_host<'a>(&mut self, address: &str, names: impl Iterator<Item = &'a str>) -> Result<(), HostsError> {
+        let address = address.parse::<IpAddr>().map_err(|_| HostsError::BadAddress(address.to_string()))?;
+        let names = names
+            .map(|name| CDomainName::from_utf8(name)
+                .and_then(|domain| domain.as_fully_qualified())
+                .map_err(|error| HostsError::BadName(name.to_string(), error)))
+            .collect::<Result<Vec<_>, _>>()?;
+        let Some(canonical_name) = names.first() else {
+            return Err(HostsError::MissingName);
+        };
+        self.insert(ResourceRecord::new(reverse_name(&address), RClass::Internet, HOSTS_TTL, RecordData::PTR(PTR::new(canonical_name.clone()))));
+        for name in names {
+            let rdata = match address {
+                IpAddr::V4(address) => RecordData::A(A::new(address)),
+                IpAddr::V6(address) => RecordData::AAAA(AAAA::new(address)),
+            };
+            self.insert(ResourceRecord::new(name, RClass::Internet, HOSTS_TTL, rdata));
+        }
+        Ok(())
+    }
+
+    /// The records that answer the question, if there are any. Questions for a type that the name
+    /// has no records of are not answered, so that only the types that were given are
+    /// overridden.
+    pub fn lookup(&self, question: &Question) -> Option<Vec<ResourceRecord>> {
+        let records = self.names.get(&question.qname().as_lowercase())?;
+        let answer = records.iter()
+            .filter(|record| record.get_rclass() == question.qclass())
+            .filter(|record| (record.get_rtype() == question.qtype()) || (question.qtype() == RType::ANY))
+            .cloned()
+            .collect::<Vec<_>>();
+        (!answer.is_empty()).then_some(answer)
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize { self.record_count }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool { self.record_count == 0 }
+}
+
+#[derive(Debug, Default)]
+struct Tables {
+    overrides: Arc<LocalRecords>,
+    hosts: Arc<LocalRecords>,
+}
+
+/// The records that the client answers before it looks in its cache: the entries of a hosts file
+/// and any static records added by the application, such as split-horizon overrides. When both
+/// have records for a question, the static records are used.
+///
+/// Clones share the same records, so they can be replaced while the client is running.
+#[derive(Debug, Default, Clone)]
+pub struct LocalData {
+    tables: Arc<RwLock<Tables>>,
+}
+
+impl LocalData {
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The static records currently being answered.
+    #[inline]
+    pub fn overrides(&self) -> Arc<LocalRecords> {
+        let r_tables = self.tables.read().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let overrides = r_tables.overrides.clone();
+        drop(r_tables);
+        overrides
+    }
+
+    /// Replaces the static records. Queries that have already been answered are not affected.
+    #[inline]
+    pub fn set_overrides(&self, overrides: LocalRecords) {
+        let mut w_tables = self.tables.write().unwrap_or_else(|poisoned| poisoned.into_inner());
+        w_tables.overrides = Arc::new(overrides);
+        drop(w_tables);
+    }
+
+    /// The hosts file entries currently being answered.
+    #[inline]
+    pub fn hosts(&self) -> Arc<LocalRecords> {
+        let r_tables = self.tables.read().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let hosts = r_tables.hosts.clone();
+        drop(r_tables);
+        hosts
+    }
+
+    /// Replaces the hosts file entries. Queries that have already been answered are not affected.
+    #[inline]
+    pub fn set_hosts(&self, hosts: LocalRecords) {
+        let mut w_tables = self.tables.write().unwrap_or_else(|poisoned| poisoned.into_inner());
+        w_tables.hosts = Arc::new(hosts);
+        drop(w_tables);
+    }
+
+    /// Reads the hosts file and replaces the hosts file entries with the ones it holds. Lines that
+    /// cannot be read are skipped. If the file cannot be read, the current entries are kept and
+    /// the error is returned.
+    pub async fn load_hosts_file(&self, path: impl AsRef<Path>) -> io::Result<usize> {
+        let path = path.as_ref();
+        let text = tokio::fs::read_to_string(path).await?;
+        let (hosts, invalid) = LocalRecords::from_hosts(&text);
+        for (line, error) in invalid {
+            warn!("Ignoring line {line} of the hosts file '{}': {error}", path.display());
+        }
+        let record_count = hosts.len();
+        self.set_hosts(hosts);
+        Ok(record_count)
+    }
+
+    /// Starts reloading the hosts file in the background whenever it is modified. The file is
+    /// checked for changes at the interval. It is loaded once right away.
+    pub fn watch_hosts_file(&self, path: impl Into<PathBuf>, check_interval: Duration) -> HostsWatcher {
+        let local_data = self.clone();
+        let path = path.into();
+        let task = tokio::spawn(async move {
+            let mut interval = tokio::time::interval(check_interval);
+            interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+            let mut loaded_modified: Option<SystemTime> = None;
+            loop {
+                interval.tick().await;
+                let modified = match tokio::fs::metadata(&path).await.and_then(|metadata| metadata.modified()) {
+                    Ok(modified) => modified,
+                    Err(error) => {
+                        warn!("Failed to check the hosts file '{}' for changes, keeping the current entries: {error}", path.display());
+                        continue;
+                    },
+                };
+                if loaded_modified == Some(modified) {
+                    continue;
+                }
+                match local_data.load_hosts_file(&path).await {
+                    Ok(record_count) => {
+                        info!("Loaded {record_count} records from the hosts file '{}'", path.display());
+                        loaded_modified = Some(modified);
+                    },
+                    Err(error) => warn!("Failed to reload the hosts file '{}', keeping the current entries: {error}", path.display()),
+                }
+            }
+        });
+        HostsWatcher { task }
+    }
+
+    /// The records that answer the question, if there are any.
+    pub(crate) fn lookup(&self, question: &Question) -> Option<Vec<ResourceRecord>> {
+        let r_tables = self.tables.read().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let answer = r_tables.overrides.lookup(question).or_else(|| r_tables.hosts.lookup(question));
+        drop(r_tables);
+        answer
+    }
+}
+
+/// Keeps a `LocalData`'s hosts file entries in sync with the file. The file stops being watched
+/// when this is dropped. The entries that were last loaded stay in place.
+#[derive(Debug)]
+pub struct HostsWatcher {
+    task: JoinHandle<()>,
+}
+
+impl Drop for HostsWatcher {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+#[cfg(test)]
+mod local_data_tests {
+    use std::net::{Ipv4Addr, Ipv6Addr};
+
+    use dns_lib::{query::question::Question, resource_record::{rclass::RClass, resource_record::{RecordData, ResourceRecord}, rtype::RType, time::Time, types::{a::A, aaaa::AAAA, ptr::PTR}}, types::c_domain_name::CDomainName};
+
+    use super::{HostsError, LocalData, LocalRecords};
+
+    const HOSTS: &str = "\
+# The usual entries.
+127.0.0.1   localhost
+::1         localhost ip6-localhost
+
+192.0.2.10  printer.lan printer  # The office printer.
+not-an-ip   broken.lan
+192.0.2.11
+";
+
+    fn question(name: &str, rtype: RType) -> Question {
+        Question::new(CDomainName::from_utf8(name).unwrap(), rtype, RClass::Internet)
+    }
+
+    #[test]
+    fn hosts_entries_answer_forward_and_reverse_lookups() {
+        let (hosts, invalid) = LocalRecords::from_hosts(HOSTS);
+        assert_eq!(invalid, vec![(6, HostsError::BadAddress("not-an-ip".to_string())), (7, HostsError::MissingName)]);
+
+        let answer = hosts.lookup(&question("LocalHost.", RType::A)).unwrap();
+        assert_eq!(answer.len(), 1);
+        assert_eq!(answer[0].get_rdata(), &RecordData::A(A::new(Ipv4Addr::LOCALHOST)));
+        let answer = hosts.lookup(&question("localhost.", RType::AAAA)).unwrap();
+        assert_eq!(answer[0].get_rdata(), &RecordData::AAAA(AAAA::new(Ipv6Addr::LOCALHOST)));
+        assert_eq!(hosts.lookup(&question("ip6-localhost.", RType::ANY)).unwrap().len(), 1);
+
+        // Aliases are answered, but reverse lookups give the canonical name.
+        assert!(hosts.lookup(&question("printer.", RType::A)).is_some());
+        let answer = hosts.lookup(&question("10.2.0.192.in-addr.arpa.", RType::PTR)).unwrap();
+        assert_eq!(answer[0].get_rdata(), &RecordData::PTR(PTR::new(CDomainName::from_utf8("printer.lan.").unwrap())));
+        assert!(hosts.lookup(&question("1.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.ip6.arpa.", RType::PTR)).is_some());
+
+        // Other types and names are resolved normally.
+        assert!(hosts.lookup(&question("printer.lan.", RType::MX)).is_none());
+        assert!(hosts.lookup(&question("www.example.com.", RType::A)).is_none());
+    }
+
+    #[test]
+    fn overrides_take_precedence_over_hosts_entries() {
+        let local_data = LocalData::new();
+        local_data.set_hosts(LocalRecords::from_hosts("192.0.2.10 printer.lan").0);
+
+        let mut overrides = LocalRecords::new();
+        let name = CDomainName::from_utf8("printer.lan.").unwrap();
+        overrides.insert(ResourceRecord::new(name.clone(), RClass::Internet, Time::from_secs(300), RecordData::A(A::new(Ipv4Addr::new(198, 51, 100, 10)))));
+        local_data.set_overrides(overrides);
+
+        let answer = local_data.lookup(&question("printer.lan.", RType::A)).unwrap();
+        assert_eq!(answer, vec![ResourceRecord::new(name, RClass::Internet, Time::from_secs(300), RecordData::A(A::new(Ipv4Addr::new(198, 51, 100, 10))))]);
+        assert!(local_data.lookup(&question("10.2.0.192.in-addr.arpa.", RType::PTR)).is_some());
+
+        local_data.set_overrides(LocalRecords::new());
+        let answer = local_data.lookup(&question("printer.lan.", RType::A)).unwrap();
+        assert_eq!(answer[0].get_rdata(), &RecordData::A(A::new(Ipv4Addr::new(192, 0, 2, 10))));
+    }
+
+    #[tokio::test]
+    async fn hosts_files_can_be_reloaded() {
+        let path = std::env::temp_dir().join(format!("dns-client-hosts-{}", std::process::id()));
+        let local_data = LocalData::new();
+        tokio::fs::write(&path, "192.0.2.10 printer.lan\n").await.unwrap();
+        assert_eq!(local_data.load_hosts_file(&path).await.unwrap(), 2);
+
+        tokio::fs::write(&path, "192.0.2.20 scanner.lan\n").await.unwrap();
+        assert_eq!(local_data.load_hosts_file(&path).await.unwrap(), 2);
+        assert!(local_data.lookup(&question("printer.lan.", RType::A)).is_none());
+        assert!(local_data.lookup(&question("scanner.lan.", RType::A)).is_some());
+
+        // The entries are kept if the file goes missing.
+        tokio::fs::remove_file(&path).await.unwrap();
+        assert!(local_data.load_hosts_file(&path).await.is_err());
+        assert!(local_data.lookup(&question("scanner.lan.", RType::A)).is_some());
+    }
+}
@@ -0,0 +1,236 @@
+use std::{cmp::Ordering, collections::HashSet, net::{IpAddr, Ipv6Addr, SocketAddr, UdpSocket}, sync::Arc};
+
+use dns_lib::{interface::client::{AsyncClient, Context, ErrorResponse, QNameMinimization, Response}, query::question::Question, resource_record::{rclass::RClass, rcode::RCode, resource_record::RecordData, rtype::RType}, types::c_domain_name::CDomainName};
+
+use crate::DNSAsyncClient;
+
+/// A row of the default policy table.
+///
+/// https://datatracker.ietf.org/doc/html/rfc6724#section-2.1
+struct Policy {
+    prefix: Ipv6Addr,
+    prefix_length: u32,
+    precedence: u8,
+    label: u8,
+}
+
+/// The default policy table, longest prefixes first so that the first match is the most specific.
+const POLICY_TABLE: [Policy; 9] = [
+    // Loopback
+    Policy { prefix: Ipv6Addr::LOCALHOST, prefix_length: 128, precedence: 50, label: 0 },
+    // IPv4-mapped
+    Policy { prefix: Ipv6Addr::new(0, 0, 0, 0, 0, 0xFFFF, 0, 0), prefix_length: 96, precedence: 35, label: 4 },
+    // IPv4-compatible (deprecated)
+    Policy { prefix: Ipv6Addr::UNSPECIFIED, prefix_length: 96, precedence: 1, label: 3 },
+    // Teredo
+    Policy { prefix: Ipv6Addr::new(0x2001, 0, 0, 0, 0, 0, 0, 0), prefix_length: 32, precedence: 5, label: 5 },
+    // 6to4
+    Policy { prefix: Ipv6Addr::new(0x2002, 0, 0, 0, 0, 0, 0, 0), prefix_length: 16, precedence: 30, label: 2 },
+    // 6bone (returned)
+    Policy { prefix: Ipv6Addr::new(0x3FFE, 0, 0, 0, 0, 0, 0, 0), prefix_length: 16, precedence: 1, label: 12 },
+    // Site-local (deprecated)
+    Policy { prefix: Ipv6Addr::new(0xFEC0, 0, 0, 0, 0, 0, 0, 0), prefix_length: 10, precedence: 1, label: 11 },
+    // Unique local
+    Policy { prefix: Ipv6Addr::new(0xFC00, 0, 0, 0, 0, 0, 0, 0), prefix_length: 7, precedence: 3, label: 13 },
+    Policy { prefix: Ipv6Addr::UNSPECIFIED, prefix_length: 0, precedence: 40, label: 1 },
+];
+
+/// IPv4 addresses are looked up in the policy table as IPv4-mapped IPv6 addresses.
+#[inline]
+fn as_ipv6(address: &IpAddr) -> Ipv6Addr {
+    match address {
+        IpAddr::V4(address) => address.to_ipv6_mapped(),
+        IpAddr::V6(address) => *address,
+    }
+}
+
+#[inline]
+fn common_prefix_length(a: &Ipv6Addr, b: &Ipv6Addr) -> u32 {
+    (u128::from(*a) ^ u128::from(*b)).leading_zeros()
+}
+
+#[inline]
+fn policy(address: &IpAddr) -> &'static Policy {
+    let address = as_ipv6(address);
+    POLICY_TABLE.iter()
+        .find(|policy| common_prefix_length(&address, &policy.prefix) >= policy.prefix_length)
+        .expect("the policy table ends with ::/0")
+}
+
+/// The scope of the address. Larger values are wider scopes.
+///
+/// https://datatracker.ietf.org/doc/html/rfc6724#section-3.1
+fn scope(address: &IpAddr) -> u8 {
+    const LINK_LOCAL: u8 = 0x2;
+    const SITE_LOCAL: u8 = 0x5;
+    const GLOBAL: u8 = 0xE;
+    match address {
+        IpAddr::V4(address) if address.is_loopback() || address.is_link_local() => LINK_LOCAL,
+        IpAddr::V4(_) => GLOBAL,
+        IpAddr::V6(address) if address.is_multicast() => address.octets()[1] & 0x0F,
+        IpAddr::V6(address) if address.is_loopback() || address.is_unicast_link_local() => LINK_LOCAL,
+        IpAddr::V6(address) if (address.segments()[0] & 0xFFC0) == 0xFEC0 => SITE_LOCAL,
+        IpAddr::V6(_) => GLOBAL,
+    }
+}
+
+/// Compares two destinations, each with the source address that would be used to reach it (or
+/// `None` if it cannot be reached). Preferred destinations are ordered first. The rules about
+/// deprecated, home, and temporary addresses and native transport are not applied since the
+/// properties of the source addresses are not known.
+///
+/// https://datatracker.ietf.org/doc/html/rfc6724#section-6
+fn compare_destinations((destination_a, source_a): &(IpAddr, Option<IpAddr>), (destination_b, source_b): &(IpAddr, Option<IpAddr>)) -> Ordering {
+    let (source_a, source_b) = match (source_a, source_b) {
+        (Some(source_a), Some(source_b)) => (source_a, source_b),
+        // Rule 1: Avoid unusable destinations.
+        (Some(_), None) => return Ordering::Less,
+        (None, Some(_)) => return Ordering::Greater,
+        (None, None) => return Ordering::Equal,
+    };
+    // Rule 2: Prefer matching scope.
+    let scope_matches_a = scope(destination_a) == scope(source_a);
+    let scope_matches_b = scope(destination_b) == scope(source_b);
+    // Rule 5: Prefer matching label.
+    let label_matches_a = policy(destination_a).label == policy(source_a).label;
+    let label_matches_b = policy(destination_b).label == policy(source_b).label;
+    scope_matches_b.cmp(&scope_matches_a)
+        .then(label_matches_b.cmp(&label_matches_a))
+        // Rule 6: Prefer higher precedence.
+        .then(policy(destination_b).precedence.cmp(&policy(destination_a).precedence))
+        // Rule 8: Prefer smaller scope.
+        .then(scope(destination_a).cmp(&scope(destination_b)))
+        // Rule 9: Use longest matching prefix. Only addresses of the same family are compared.
+        .then_with(|| match (destination_a, destination_b) {
+            (IpAddr::V4(_), IpAddr::V4(_)) | (IpAddr::V6(_), IpAddr::V6(_)) => {
+                let prefix_a = common_prefix_length(&as_ipv6(destination_a), &as_ipv6(source_a));
+                let prefix_b = common_prefix_length(&as_ipv6(destination_b), &as_ipv6(source_b));
+                prefix_b.cmp(&prefix_a)
+            },
+            _ => Ordering::Equal,
+        })
+    // Rule 10: Otherwise, leave the order unchanged.
+}
+
+/// The source address that the operating system would use to send to the destination, or `None`
+/// if there is no route to it. Connecting a UDP socket only looks up the route, so nothing is
+/// sent.
+fn source_address(destination: &IpAddr) -> Option<IpAddr> {
+    let unspecified: IpAddr = match destination {
+        IpAddr::V4(_) => [0, 0, 0, 0].into(),
+        IpAddr::V6(_) => Ipv6Addr::UNSPECIFIED.into(),
+    };
+    let socket = UdpSocket::bind(SocketAddr::new(unspecified, 0)).ok()?;
+    socket.connect(SocketAddr::new(*destination, 9)).ok()?;
+    socket.local_addr().ok().map(|address| address.ip())
+}
+
+/// Orders the addresses by RFC 6724 destination address selection, most preferred first, using
+/// the routes of this host.
+pub fn sort_addresses(addresses: &mut Vec<IpAddr>) {
+    let mut destinations = addresses.drain(..)
+        .map(|address| (address, source_address(&address)))
+        .collect::<Vec<_>>();
+    destinations.sort_by(compare_destinations);
+    addresses.extend(destinations.into_iter().map(|(address, _)| address));
+}
+
+impl DNSAsyncClient {
+    /// Looks up the addresses of the host. The A and AAAA queries are made at the same time, go
+    /// through the middleware and the cache like any other query, and follow CNAME records. The
+    /// addresses are ordered by RFC 6724 destination address selection, so the first one is the
+    /// one to try first.
+    ///
+    /// Only fails if neither query is answered. If one family's query fails and the other's does
+    /// not, only the addresses of the other family are returned. A host with no addresses gives an
+    /// empty list.
+    pub async fn lookup_ip(self: Arc<Self>, host: &CDomainName) -> Result<Vec<IpAddr>, ErrorResponse> {
+        let context = |rtype| Context::new(Question::new(host.clone(), rtype, RClass::Internet), QNameMinimization::None);
+        let (a_response, aaaa_response) = tokio::join!(self.clone().query(context(RType::A)), self.query(context(RType::AAAA)));
+
+        let mut addresses = Vec::new();
+        let mut seen = HashSet::new();
+        let mut answered = false;
+        let mut error: Option<ErrorResponse> = None;
+        for response in [a_response, aaaa_response] {
+            match response {
+                Response::Answer(answer) => {
+                    answered = true;
+                    addresses.extend(answer.answer.iter()
+                        .filter_map(|record| match record.get_rdata() {
+                            RecordData::A(rdata) => Some(IpAddr::V4(*rdata.ipv4_addr())),
+                            RecordData::AAAA(rdata) => Some(IpAddr::V6(*rdata.ipv6_addr())),
+                            _ => None,
+                        })
+                        .filter(|address| seen.insert(*address))
+                    );
+                },
+                // NXDOMAIN is the more useful error to report since it applies to both families.
+                Response::Error(response) => if error.as_ref().is_none_or(|error| error.rcode != RCode::NXDomain) {
+                    error = Some(response);
+                },
+            }
+        }
+        match error {
+            Some(error) if !answered => Err(error),
+            _ => {
+                sort_addresses(&mut addresses);
+                Ok(addresses)
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod lookup_tests {
+    use std::{net::{IpAddr, Ipv4Addr}, sync::Arc};
+
+    use dns_cache::asynchronous::async_main_cache::AsyncMainTreeCache;
+    use dns_lib::{resource_record::rcode::RCode, types::c_domain_name::CDomainName};
+
+    use crate::{local_data::LocalRecords, upstream::ForwardUpstream, ClientConfig, DNSAsyncClient};
+
+    use super::compare_destinations;
+
+    fn sorted(mut destinations: Vec<(&str, Option<&str>)>) -> Vec<String> {
+        let mut destinations = destinations.drain(..)
+            .map(|(destination, source)| (destination.parse::<IpAddr>().unwrap(), source.map(|source| source.parse::<IpAddr>().unwrap())))
+            .collect::<Vec<_>>();
+        destinations.sort_by(compare_destinations);
+        destinations.into_iter().map(|(destination, _)| destination.to_string()).collect()
+    }
+
+    #[test]
+    fn destinations_are_ordered_by_rfc_6724() {
+        // The examples from RFC 6724 section 10.2.
+        assert_eq!(sorted(vec![("198.51.100.121", Some("198.51.100.117")), ("2001:db8:1::1", Some("2001:db8:1::2"))]), ["2001:db8:1::1", "198.51.100.121"]);
+        assert_eq!(sorted(vec![("2001:db8:1::1", Some("fe80::2")), ("198.51.100.121", Some("198.51.100.117"))]), ["198.51.100.121", "2001:db8:1::1"]);
+        assert_eq!(sorted(vec![("2001:db8:1::1", Some("2001:db8:1::2")), ("10.1.2.3", Some("10.1.2.4"))]), ["2001:db8:1::1", "10.1.2.3"]);
+        assert_eq!(sorted(vec![("2001:db8:1::1", Some("2001:db8:1::2")), ("fe80::1", Some("fe80::2"))]), ["fe80::1", "2001:db8:1::1"]);
+        assert_eq!(sorted(vec![("2001:db8:1::1", Some("2001:db8:1::2")), ("2002:c633:6401::1", Some("2002:c633:6401::2"))]), ["2001:db8:1::1", "2002:c633:6401::1"]);
+        // Destinations that cannot be reached go last.
+        assert_eq!(sorted(vec![("2001:db8:1::1", None), ("198.51.100.121", Some("198.51.100.117"))]), ["198.51.100.121", "2001:db8:1::1"]);
+        // The longest matching prefix wins within a family.
+        assert_eq!(sorted(vec![("2001:db8:2::1", Some("2001:db8:1::2")), ("2001:db8:1::1", Some("2001:db8:1::2"))]), ["2001:db8:1::1", "2001:db8:2::1"]);
+    }
+
+    #[tokio::test]
+    async fn both_families_are_looked_up() {
+        // There is no upstream, so only the local data can answer.
+        let config = ClientConfig::forwarding(vec![ForwardUpstream::Plain("127.0.0.1:9".parse().unwrap())]);
+        let client = Arc::new(DNSAsyncClient::with_config(Arc::new(AsyncMainTreeCache::new()), config).await);
+        client.local_data().set_hosts(LocalRecords::from_hosts("192.0.2.10 host.lan\n2001:db8::10 host.lan\n192.0.2.11 v4.lan").0);
+
+        let mut addresses = client.clone().lookup_ip(&CDomainName::from_utf8("host.lan.").unwrap()).await.unwrap();
+        addresses.sort();
+        assert_eq!(addresses, ["192.0.2.10".parse::<IpAddr>().unwrap(), "2001:db8::10".parse().unwrap()]);
+
+        // The AAAA query fails, but the A record is still returned.
+        let addresses = client.clone().lookup_ip(&CDomainName::from_utf8("v4.lan.").unwrap()).await.unwrap();
+        assert_eq!(addresses, [IpAddr::V4(Ipv4Addr::new(192, 0, 2, 11))]);
+
+        let error = client.clone().lookup_ip(&CDomainName::from_utf8("missing.lan.").unwrap()).await.unwrap_err();
+        assert_eq!(error.rcode, RCode::ServFail);
+        client.close().await;
+    }
+}
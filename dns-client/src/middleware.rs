@@ -0,0 +1,139 @@
+use std::{fmt::Debug, sync::Arc, time::Instant};
+
+use async_trait::async_trait;
+use dns_cache::asynchronous::async_cache::AsyncTreeCache;
+use dns_lib::{interface::{cache::cache::SharedAsyncCache, client::{Answer, Context, ErrorResponse, Response}, trace::QueryTrace}, query::question::Question, resource_record::rcode::RCode};
+use log::info;
+
+use crate::{negative::negative_details, query::{forward_query::forward_query, recursive_query::recursive_query}, result::{QOk, QResult}, DNSAsyncClient};
+
+/// An interceptor in the query pipeline. Each middleware receives the context before it is
+/// resolved and decides how to continue: it may change the context, call `next` any number of
+/// times (or not at all), and change the response before returning it.
+#[async_trait]
+pub trait Middleware: Debug + Send + Sync {
+    async fn handle(&self, context: Context, next: Next<'_>) -> Response;
+}
+
+/// The remainder of the middleware chain, ending in the resolver itself.
+#[derive(Clone, Copy)]
+pub struct Next<'a> {
+    client: &'a Arc<DNSAsyncClient>,
+    layers: &'a [Arc<dyn Middleware>],
+}
+
+impl<'a> Next<'a> {
+    #[inline]
+    pub(crate) fn new(client: &'a Arc<DNSAsyncClient>, chain: &'a MiddlewareChain) -> Self {
+        Self { client, layers: &chain.layers }
+    }
+
+    #[inline]
+    pub fn client(&self) -> &Arc<DNSAsyncClient> { self.client }
+
+    /// Passes the context to the next middleware in the chain. If this is the end of the chain,
+    /// the context is resolved.
+    pub async fn run(self, context: Context) -> Response {
+        match self.layers.split_first() {
+            Some((middleware, layers)) => middleware.handle(context, Next { client: self.client, layers }).await,
+            None => resolve(self.client.clone(), context).await,
+        }
+    }
+}
+
+async fn resolve(client: Arc<DNSAsyncClient>, context: Context) -> Response {
+    // Queries for a view that the client does not have are refused rather than being resolved
+    // with another view's cache.
+    let Some(cache) = client.cache_for(&context) else {
+        return Response::Error(ErrorResponse::new(RCode::Refused));
+    };
+    let joined_cache: SharedAsyncCache = Arc::new(AsyncTreeCache::new(cache));
+    let question = context.query().clone();
+    let trace = context.trace().cloned();
+    let result = if client.config.is_forwarding() {
+        // The forwarding future holds the state of every transport, so it is kept off the stack.
+        Box::pin(forward_query(client.clone(), joined_cache.clone(), context)).await
+    } else {
+        recursive_query(client.clone(), joined_cache.clone(), context).await
+    };
+    into_response(&client, result, &joined_cache, &question, trace).await
+}
+
+/// Converts the result of a query into the response given to the application. Duplicate and
+/// contradictory records are removed from answers. Negative answers are given the details of the
+/// zone that they came from. Answers carry the query's trace, if it had one.
+pub(crate) async fn into_response(client: &DNSAsyncClient, result: QResult, joined_cache: &SharedAsyncCache, question: &Question, trace: Option<Arc<QueryTrace>>) -> Response {
+    match result {
+        QResult::Err(_) => Response::Error(ErrorResponse::new(RCode::ServFail)),
+        QResult::Fail(RCode::NXDomain) => Response::Error(ErrorResponse {
+            rcode: RCode::NXDomain,
+            negative: Some(negative_details(joined_cache, question).await),
+        }),
+        QResult::Fail(rcode) => Response::Error(ErrorResponse::new(rcode)),
+        QResult::Ok(mut answer) => {
+            client.consistency.make_consistent(question, &mut answer);
+            let QOk { answer, name_servers, additional } = answer;
+            let negative = if answer.is_empty() { Some(negative_details(joined_cache, question).await) } else { None };
+            Response::Answer(Answer { answer, name_servers, additional, authoritative: false, negative, trace })
+        },
+    }
+}
+
+/// The ordered list of middleware that every query passes through. The first middleware sees the
+/// context first and the response last.
+#[derive(Debug, Clone)]
+pub struct MiddlewareChain {
+    layers: Vec<Arc<dyn Middleware>>,
+}
+
+impl Default for MiddlewareChain {
+    /// A chain that only logs queries.
+    fn default() -> Self {
+        Self { layers: vec![Arc::new(QueryLogger)] }
+    }
+}
+
+impl MiddlewareChain {
+    /// A chain with no middleware. Queries go straight to the resolver.
+    #[inline]
+    pub fn new() -> Self {
+        Self { layers: Vec::new() }
+    }
+
+    /// Adds the middleware to the end of the chain, closest to the resolver.
+    #[inline]
+    pub fn push(&mut self, middleware: impl Middleware + 'static) {
+        self.layers.push(Arc::new(middleware));
+    }
+
+    /// Adds the middleware at the position in the chain. Panics if `index > len`.
+    #[inline]
+    pub fn insert(&mut self, index: usize, middleware: impl Middleware + 'static) {
+        self.layers.insert(index, Arc::new(middleware));
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize { self.layers.len() }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool { self.layers.is_empty() }
+}
+
+/// Logs each query as it starts and the outcome once it completes.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct QueryLogger;
+
+#[async_trait]
+impl Middleware for QueryLogger {
+    async fn handle(&self, context: Context, next: Next<'_>) -> Response {
+        let query = context.query().clone();
+        info!("Start query '{query}'");
+        let start = Instant::now();
+        let response = next.run(context).await;
+        match &response {
+            Response::Answer(answer) => info!("Finished query '{query}' with {} answer records in {:?}", answer.answer.len(), start.elapsed()),
+            Response::Error(error) => info!("Finished query '{query}' with error '{error}' in {:?}", start.elapsed()),
+        }
+        response
+    }
+}
@@ -0,0 +1,35 @@
+use dns_lib::{interface::{cache::{cache::SharedAsyncCache, CacheQuery, CacheResponse}, client::NegativeDetails}, query::question::Question, resource_record::{resource_record::ResourceRecord, rtype::RType, types::soa::SOA}};
+
+/// Looks up what the cache knows about the zone that a negative answer (NXDOMAIN or NODATA) came
+/// from. The name servers include the zone's SOA record in the authority section of negative
+/// answers and the whole response is cached, so the SOA record is found by searching up from the
+/// qname for the closest one.
+///
+/// https://datatracker.ietf.org/doc/html/rfc2308#section-3
+pub(crate) async fn negative_details(joined_cache: &SharedAsyncCache, question: &Question) -> NegativeDetails {
+    let mut soa = None;
+    for search_name in question.qname().search_domains() {
+        let records = match joined_cache.get(&CacheQuery { authoritative: false, question: &question.with_new_qname_qtype(search_name, RType::SOA) }).await {
+            CacheResponse::Records(records) => records,
+            CacheResponse::Err(_) => break,
+        };
+        if let Some(record) = records.into_iter().find_map(|record| ResourceRecord::<SOA>::try_from(record.record).ok()) {
+            soa = Some(record);
+            break;
+        }
+    }
+
+    // The zones that were delegated through are the ones above the SOA's zone that have name
+    // servers.
+    let zone = soa.as_ref().map_or(question.qname(), |soa| soa.get_name());
+    let mut authority_chain = Vec::new();
+    for search_name in zone.search_domains().rev() {
+        match joined_cache.get(&CacheQuery { authoritative: false, question: &question.with_new_qname_qtype(search_name.clone(), RType::NS) }).await {
+            CacheResponse::Records(records) if !records.is_empty() => authority_chain.push(search_name),
+            CacheResponse::Records(_) => (),
+            CacheResponse::Err(_) => break,
+        }
+    }
+
+    NegativeDetails { soa, authority_chain }
+}
@@ -0,0 +1,367 @@
+use std::{collections::HashMap, net::IpAddr, sync::atomic::{AtomicU64, Ordering}};
+
+use dns_lib::{interface::cache::{cache::{AsyncCache, SharedAsyncCache}, CacheQuery, CacheResponse}, query::{message::Message, question::Question}, resource_record::{resource_record::{RecordData, ResourceRecord}, rtype::RType}, types::c_domain_name::CmpDomainName};
+use log::{trace, warn};
+use tokio::sync::RwLock;
+
+use crate::{query::network_query::query_network_uncached, DNSAsyncClient};
+
+/// An RRset that was received from a name server but conflicted with the cached RRset of the same
+/// credibility. It is held here instead of the cache until another name server confirms it.
+#[derive(Clone, PartialEq, Hash, Debug)]
+struct Quarantined {
+    records: Vec<ResourceRecord>,
+    source: IpAddr,
+}
+
+/// A point-in-time copy of the poisoning counters.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct PoisoningStats {
+    /// The number of RRsets that conflicted with cached data.
+    pub conflicts: u64,
+    /// The number of conflicting RRsets that were confirmed by an independent name server.
+    pub confirmed: u64,
+    /// The number of conflicting RRsets that an independent name server disagreed with.
+    pub rejected: u64,
+    /// The number of conflicting RRsets that could not be re-validated and were quarantined.
+    pub quarantined: u64,
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct PoisoningGuard {
+    quarantine: RwLock<HashMap<Question, Quarantined>>,
+    conflicts: AtomicU64,
+    confirmed: AtomicU64,
+    rejected: AtomicU64,
+    quarantined: AtomicU64,
+}
+
+impl PoisoningGuard {
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[inline]
+    pub fn stats(&self) -> PoisoningStats {
+        PoisoningStats {
+            conflicts: self.conflicts.load(Ordering::Relaxed),
+            confirmed: self.confirmed.load(Ordering::Relaxed),
+            rejected: self.rejected.load(Ordering::Relaxed),
+            quarantined: self.quarantined.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Checks whether a previously quarantined RRset is confirmed by a response from a different
+    /// name server. Confirmed RRsets are removed from the quarantine.
+    async fn confirm_quarantined(&self, rrset_question: &Question, records: &[ResourceRecord], source: &IpAddr) -> bool {
+        let mut w_quarantine = self.quarantine.write().await;
+        let confirmed = match w_quarantine.get(rrset_question) {
+            Some(quarantined) => (quarantined.source != *source) && same_rrset(&quarantined.records, records),
+            None => false,
+        };
+        if confirmed {
+            w_quarantine.remove(rrset_question);
+        }
+        drop(w_quarantine);
+        confirmed
+    }
+
+    async fn quarantine(&self, rrset_question: Question, records: Vec<ResourceRecord>, source: IpAddr) {
+        let mut w_quarantine = self.quarantine.write().await;
+        w_quarantine.insert(rrset_question, Quarantined { records, source });
+        drop(w_quarantine);
+    }
+}
+
+/// An RRset in a response that disagrees with what is already cached.
+#[derive(Clone, PartialEq, Hash, Debug)]
+struct Conflict {
+    rrset_question: Question,
+    received: Vec<ResourceRecord>,
+    cached: Vec<ResourceRecord>,
+}
+
+#[inline]
+fn same_rrset(left: &[ResourceRecord], right: &[ResourceRecord]) -> bool {
+    left.iter().all(|record| right.contains(record))
+    && right.iter().all(|record| left.contains(record))
+}
+
+/// Groups the answer section into RRsets and compares each one against the cache. Only cached
+/// records with the same credibility as the received records are considered.
+async fn find_conflicts(cache: &SharedAsyncCache, message: &Message) -> Vec<Conflict> {
+    let qname = match message.question.first() {
+        Some(question) => question.qname(),
+        None => return vec![],
+    };
+
+    let mut rrsets: HashMap<Question, Vec<ResourceRecord>> = HashMap::new();
+    for record in &message.answer {
+        let rrset_question = Question::new(record.get_name().clone(), record.get_rtype(), record.get_rclass());
+        rrsets.entry(rrset_question).or_default().push(record.clone());
+    }
+
+    let mut conflicts = Vec::new();
+    for (rrset_question, received) in rrsets {
+        let authoritative = message.authoritative_answer && rrset_question.qname().matches(qname);
+        let cached = match cache.get(&CacheQuery { authoritative: false, question: &rrset_question }).await {
+            CacheResponse::Records(records) => records.into_iter()
+                .filter(|record| !record.is_bootstrap())
+                .filter(|record| record.is_authoritative() == authoritative)
+                .filter(|record| record.get_rtype() == rrset_question.qtype())
+                .map(|record| record.record)
+                .collect::<Vec<_>>(),
+            CacheResponse::Err(_) => continue,
+        };
+        if !cached.is_empty() && received.iter().any(|record| !cached.contains(record)) {
+            conflicts.push(Conflict { rrset_question, received, cached });
+        }
+    }
+    conflicts
+}
+
+/// Looks through the cache for the addresses of the name servers for the closest enclosing zone,
+/// excluding the server that sent the conflicting response.
+async fn independent_name_server(cache: &SharedAsyncCache, question: &Question, exclude: &IpAddr) -> Option<IpAddr> {
+    for search_name in question.qname().search_domains() {
+        let ns_question = question.with_new_qname_qtype(search_name.clone(), RType::NS);
+        let name_servers = match cache.get(&CacheQuery { authoritative: false, question: &ns_question }).await {
+            CacheResponse::Records(records) if records.is_empty() => continue,
+            CacheResponse::Records(records) => records,
+            CacheResponse::Err(_) => return None,
+        };
+        for name_server in name_servers {
+            let ns_domain = match name_server.record.into_rdata() {
+                RecordData::NS(ns) => ns.into_name_server_domain_name(),
+                _ => continue,
+            };
+            for address_rtype in [RType::A, RType::AAAA] {
+                let address_question = question.with_new_qname_qtype(ns_domain.clone(), address_rtype);
+                if let CacheResponse::Records(records) = cache.get(&CacheQuery { authoritative: false, question: &address_question }).await {
+                    let address = records.into_iter()
+                        .filter_map(|record| match record.record.into_rdata() {
+                            RecordData::A(rdata) => Some(IpAddr::from(rdata.into_ipv4_addr())),
+                            RecordData::AAAA(rdata) => Some(IpAddr::from(rdata.into_ipv6_addr())),
+                            _ => None,
+                        })
+                        .find(|address| address != exclude);
+                    if address.is_some() {
+                        return address;
+                    }
+                }
+            }
+        }
+        // Only the closest zone's name servers are authoritative for the data.
+        return None;
+    }
+    None
+}
+
+/// Removes the records of the RRset from every section of the message.
+fn strip_rrset(message: &mut Message, rrset_question: &Question) {
+    let in_rrset = |record: &ResourceRecord| (record.get_rtype() == rrset_question.qtype())
+        && (record.get_rclass() == rrset_question.qclass())
+        && (record.get_name() == rrset_question.qname());
+    message.answer.retain(|record| !in_rrset(record));
+    message.authority.retain(|record| !in_rrset(record));
+    message.additional.retain(|record| !in_rrset(record));
+}
+
+/// Inserts the message into the cache. If conflict re-validation is enabled, any RRset that
+/// conflicts with the cache is first checked against an independent name server. The returned
+/// message is the one that should be used to answer the query. Conflicting data that was not
+/// confirmed is replaced with the cached RRset.
+pub(crate) async fn insert_checked(client: &DNSAsyncClient, cache: &SharedAsyncCache, question: &Question, mut message: Message, source: &IpAddr) -> Message {
+    if !client.config.revalidate_conflicts {
+        cache.insert_message(&message).await;
+        return message;
+    }
+
+    let guard = &client.poisoning;
+    let mut substituted = Vec::new();
+    for conflict in find_conflicts(cache, &message).await {
+        let rrset_question = &conflict.rrset_question;
+        guard.conflicts.fetch_add(1, Ordering::Relaxed);
+        warn!(question:?; "Received RRset '{rrset_question:?}' from '{source}' that conflicts with the cache");
+
+        if guard.confirm_quarantined(rrset_question, &conflict.received, source).await {
+            guard.confirmed.fetch_add(1, Ordering::Relaxed);
+            trace!(question:?; "Conflicting RRset '{rrset_question:?}' confirmed by quarantined response");
+            continue;
+        }
+
+        match independent_name_server(cache, rrset_question, source).await {
+            Some(independent_address) => match query_network_uncached(client, question, &independent_address).await {
+                Ok(revalidation) => {
+                    let revalidated = revalidation.answer.iter()
+                        .filter(|record| (record.get_rtype() == rrset_question.qtype()) && (record.get_name() == rrset_question.qname()))
+                        .cloned()
+                        .collect::<Vec<_>>();
+                    if same_rrset(&revalidated, &conflict.received) {
+                        guard.confirmed.fetch_add(1, Ordering::Relaxed);
+                        trace!(question:?; "Conflicting RRset '{rrset_question:?}' confirmed by '{independent_address}'");
+                        continue;
+                    }
+                    guard.rejected.fetch_add(1, Ordering::Relaxed);
+                    warn!(question:?; "Conflicting RRset '{rrset_question:?}' from '{source}' rejected by '{independent_address}'");
+                },
+                Err(error) => {
+                    guard.quarantined.fetch_add(1, Ordering::Relaxed);
+                    warn!(question:?; "Conflicting RRset '{rrset_question:?}' quarantined, re-validation with '{independent_address}' failed: {error}");
+                    guard.quarantine(rrset_question.clone(), conflict.received.clone(), *source).await;
+                },
+            },
+            None => {
+                guard.quarantined.fetch_add(1, Ordering::Relaxed);
+                warn!(question:?; "Conflicting RRset '{rrset_question:?}' quarantined, no independent name server found");
+                guard.quarantine(rrset_question.clone(), conflict.received.clone(), *source).await;
+            },
+        }
+
+        strip_rrset(&mut message, rrset_question);
+        substituted.extend(conflict.cached);
+    }
+
+    cache.insert_message(&message).await;
+    // The cached records are added after the message is cached so that their TTLs are not
+    // refreshed by a response that disagreed with them.
+    message.answer.extend(substituted);
+    message
+}
+
+#[cfg(test)]
+mod poisoning_tests {
+    use std::{net::{IpAddr, Ipv4Addr, SocketAddr}, sync::Arc, time::Instant};
+
+    use dns_cache::asynchronous::{async_cache::AsyncTreeCache, async_main_cache::AsyncMainTreeCache};
+    use dns_lib::{interface::cache::{cache::SharedAsyncCache, main_cache::AsyncMainCache, CacheMeta, CacheQuery, CacheRecord, CacheResponse, MetaAuth}, query::{message::Message, qr::QR, question::Question}, resource_record::{rclass::RClass, resource_record::{RecordData, ResourceRecord}, rtype::RType, time::Time, types::ns::NS}, serde::wire::{from_wire::FromWire, read_wire::ReadWire}, types::c_domain_name::CompressionMap};
+    use tokio::net::UdpSocket;
+
+    use crate::{config::ClientConfig, test_support::{a, name}, DNSAsyncClient};
+
+    use super::{insert_checked, PoisoningStats};
+
+    /// The address that the conflicting responses come from.
+    const SOURCE: IpAddr = IpAddr::V4(Ipv4Addr::new(192, 0, 2, 53));
+    const CACHED: &str = "192.0.2.1";
+    const RECEIVED: &str = "192.0.2.66";
+
+    /// A name server that answers every query with A records for these addresses.
+    async fn name_server(addresses: &'static [&'static str]) -> SocketAddr {
+        let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let address = socket.local_addr().unwrap();
+        tokio::spawn(async move {
+            let mut buffer = vec![0_u8; u16::MAX as usize];
+            loop {
+                let (length, peer) = socket.recv_from(&mut buffer).await.unwrap();
+                let mut response = Message::from_wire_format(&mut ReadWire::from_bytes(&buffer[..length])).unwrap();
+                response.qr = QR::Response;
+                response.additional.clear();
+                let qname = response.question[0].qname().to_string();
+                response.answer = addresses.iter().map(|address| a(&qname, address)).collect();
+                let wire = response.to_wire_vec(&mut Some(CompressionMap::new())).unwrap();
+                socket.send_to(&wire, peer).await.unwrap();
+            }
+        });
+        address
+    }
+
+    fn cached(record: ResourceRecord) -> CacheRecord {
+        CacheRecord { meta: CacheMeta { auth: MetaAuth::NotAuthoritative, insertion_time: Instant::now(), original_ttl: None }, record }
+    }
+
+    /// A client that re-validates conflicts with the name server at `upstream`, and a joined cache
+    /// like the one queries insert into. The main cache already has `www.example.` and, if there
+    /// is an `upstream`, the name server for `example.`.
+    async fn client_and_cache(upstream: Option<SocketAddr>) -> (DNSAsyncClient, SharedAsyncCache) {
+        let config = ClientConfig { revalidate_conflicts: true, upstream_port: upstream.map_or(0, |upstream| upstream.port()), ..ClientConfig::default() };
+        let main_cache = Arc::new(AsyncMainTreeCache::new());
+        AsyncMainCache::insert_record(&main_cache, cached(a("www.example.", CACHED))).await;
+        if let Some(upstream) = upstream {
+            AsyncMainCache::insert_record(&main_cache, cached(ResourceRecord::new(name("example."), RClass::Internet, Time::from_secs(3600), RecordData::NS(NS::new(name("ns.example.")))))).await;
+            AsyncMainCache::insert_record(&main_cache, cached(a("ns.example.", &upstream.ip().to_string()))).await;
+        }
+        let client = DNSAsyncClient::with_config(main_cache.clone(), config).await;
+        (client, Arc::new(AsyncTreeCache::new(main_cache)))
+    }
+
+    fn question() -> Question {
+        Question::new(name("www.example."), RType::A, RClass::Internet)
+    }
+
+    /// A non-authoritative response for `www.example.` that disagrees with the cache.
+    fn conflicting_response() -> Message {
+        let mut response = Message::from(question());
+        response.qr = QR::Response;
+        response.answer = vec![a("www.example.", RECEIVED)];
+        response
+    }
+
+    fn addresses(records: &[ResourceRecord]) -> Vec<String> {
+        let mut addresses = records.iter()
+            .filter_map(|record| match record.get_rdata() {
+                RecordData::A(rdata) => Some(rdata.ipv4_addr().to_string()),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        addresses.sort();
+        addresses
+    }
+
+    /// The addresses in the client's main cache. The joined cache would also return the copies in
+    /// its transaction cache.
+    async fn cached_addresses(client: &DNSAsyncClient) -> Vec<String> {
+        match client.cache.get(&CacheQuery { authoritative: false, question: &question() }).await {
+            CacheResponse::Records(records) => addresses(&records.into_iter().map(|record| record.record).collect::<Vec<_>>()),
+            CacheResponse::Err(rcode) => panic!("The cache responded with {rcode}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn conflicts_confirmed_by_another_name_server_are_cached() {
+        let upstream = name_server(&[RECEIVED]).await;
+        let (client, cache) = client_and_cache(Some(upstream)).await;
+
+        let answer = insert_checked(&client, &cache, &question(), conflicting_response(), &SOURCE).await;
+        assert_eq!(addresses(&answer.answer), [RECEIVED]);
+        assert!(cached_addresses(&client).await.contains(&RECEIVED.to_string()));
+        assert_eq!(client.poisoning_stats(), PoisoningStats { conflicts: 1, confirmed: 1, rejected: 0, quarantined: 0 });
+        client.close().await;
+    }
+
+    #[tokio::test]
+    async fn conflicts_rejected_by_another_name_server_are_replaced_with_the_cached_records() {
+        let upstream = name_server(&[CACHED]).await;
+        let (client, cache) = client_and_cache(Some(upstream)).await;
+
+        let answer = insert_checked(&client, &cache, &question(), conflicting_response(), &SOURCE).await;
+        assert_eq!(addresses(&answer.answer), [CACHED]);
+        assert_eq!(cached_addresses(&client).await, [CACHED]);
+        assert_eq!(client.poisoning_stats(), PoisoningStats { conflicts: 1, confirmed: 0, rejected: 1, quarantined: 0 });
+        client.close().await;
+    }
+
+    #[tokio::test]
+    async fn quarantined_conflicts_are_confirmed_by_a_second_source() {
+        // With no name server for `example.` in the cache, the conflict cannot be re-validated.
+        let (client, cache) = client_and_cache(None).await;
+
+        let answer = insert_checked(&client, &cache, &question(), conflicting_response(), &SOURCE).await;
+        assert_eq!(addresses(&answer.answer), [CACHED]);
+        assert_eq!(cached_addresses(&client).await, [CACHED]);
+        assert_eq!(client.poisoning_stats(), PoisoningStats { conflicts: 1, confirmed: 0, rejected: 0, quarantined: 1 });
+
+        // The same source repeating itself does not count as confirmation.
+        let answer = insert_checked(&client, &cache, &question(), conflicting_response(), &SOURCE).await;
+        assert_eq!(addresses(&answer.answer), [CACHED]);
+        assert_eq!(client.poisoning_stats(), PoisoningStats { conflicts: 2, confirmed: 0, rejected: 0, quarantined: 2 });
+
+        let second_source = IpAddr::V4(Ipv4Addr::new(198, 51, 100, 53));
+        let answer = insert_checked(&client, &cache, &question(), conflicting_response(), &second_source).await;
+        assert_eq!(addresses(&answer.answer), [RECEIVED]);
+        assert!(cached_addresses(&client).await.contains(&RECEIVED.to_string()));
+        assert_eq!(client.poisoning_stats(), PoisoningStats { conflicts: 3, confirmed: 1, rejected: 0, quarantined: 2 });
+        client.close().await;
+    }
+}
@@ -0,0 +1,492 @@
+use std::{collections::HashMap, error::Error, fmt::Display, net::IpAddr, sync::{Arc, RwLock}, time::Duration};
+
+use async_trait::async_trait;
+use dns_lib::{interface::client::{Answer, AsyncClient, Context, ErrorResponse, QNameMinimization, Response}, query::question::Question, resource_record::{rclass::RClass, rcode::RCode, resource_record::{RecordData, ResourceRecord}, rtype::RType, time::Time, types::{a::A, aaaa::AAAA}}, types::c_domain_name::{CDomainName, CDomainNameError, CmpDomainName}};
+use log::{info, warn};
+use tokio::task::JoinHandle;
+
+use crate::{middleware::{Middleware, Next}, DNSAsyncClient};
+
+/// The TTL given to the address records synthesized for a redirect.
+const REDIRECT_TTL: Time = Time::from_secs(60);
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum PolicyRuleError {
+    Empty,
+    UnknownAction(String),
+    MissingName,
+    BadName(String, CDomainNameError),
+    MissingRedirectAddress,
+    BadRedirectAddress(String),
+    UnexpectedArgument(String),
+}
+impl Error for PolicyRuleError {}
+impl Display for PolicyRuleError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Empty => write!(f, "The rule is empty"),
+            Self::UnknownAction(action) => write!(f, "Unknown policy action '{action}'. Expected 'block', 'allow', or 'redirect'"),
+            Self::MissingName => write!(f, "The rule does not name a domain"),
+            Self::BadName(name, error) => write!(f, "'{name}' is not a valid domain name: {error}"),
+            Self::MissingRedirectAddress => write!(f, "A redirect needs at least one address to redirect to"),
+            Self::BadRedirectAddress(address) => write!(f, "'{address}' is not an IPv4 or IPv6 address"),
+            Self::UnexpectedArgument(argument) => write!(f, "Unexpected argument '{argument}'"),
+        }
+    }
+}
+
+/// What happens to a query that a rule matches.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum PolicyAction {
+    /// Answer NXDOMAIN without resolving the name.
+    Block,
+    /// Resolve the name normally, even if a rule for one of its parent domains blocks or redirects
+    /// it.
+    Allow,
+    /// Answer A and AAAA queries with these addresses instead of resolving the name. Queries for
+    /// other types get an empty answer.
+    Redirect(Vec<IpAddr>),
+}
+
+impl PolicyAction {
+    /// When several rules apply to the same name, the one with the highest precedence is used.
+    /// Allowing always wins so that an exception cannot be overridden by accident, and blocking
+    /// wins over redirecting since it gives away less.
+    #[inline]
+    fn precedence(&self) -> u8 {
+        match self {
+            Self::Allow => 2,
+            Self::Block => 1,
+            Self::Redirect(_) => 0,
+        }
+    }
+}
+
+impl Display for PolicyAction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Block => write!(f, "block"),
+            Self::Allow => write!(f, "allow"),
+            Self::Redirect(addresses) => {
+                write!(f, "redirect")?;
+                for address in addresses {
+                    write!(f, " {address}")?;
+                }
+                Ok(())
+            },
+        }
+    }
+}
+
+/// A single filtering rule. It is written as the text of a TXT record, with the action, the
+/// domain, and (for redirects) the addresses separated by whitespace:
+///
+/// ```text
+/// block ads.example.
+/// allow *.cdn.example.
+/// redirect tracker.example. 192.0.2.1 2001:db8::1
+/// ```
+///
+/// A domain that starts with `*.` applies to that domain and everything below it. Otherwise, the
+/// rule only applies to that exact name. Domains that are not fully qualified are treated as if
+/// they were.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct PolicyRule {
+    pub domain: CDomainName,
+    pub include_subdomains: bool,
+    pub action: PolicyAction,
+}
+
+impl PolicyRule {
+    pub fn from_text(text: &str) -> Result<Self, PolicyRuleError> {
+        let mut arguments = text.split_whitespace();
+        let action = arguments.next().ok_or(PolicyRuleError::Empty)?;
+        let domain = arguments.next().ok_or(PolicyRuleError::MissingName)?;
+        let (domain, include_subdomains) = match domain.strip_prefix("*.") {
+            Some(domain) => (domain, true),
+            None => (domain, false),
+        };
+        let domain = CDomainName::from_utf8(domain)
+            .and_then(|name| name.as_fully_qualified())
+            .map_err(|error| PolicyRuleError::BadName(domain.to_string(), error))?;
+        let action = match action.to_ascii_lowercase().as_str() {
+            "block" => PolicyAction::Block,
+            "allow" => PolicyAction::Allow,
+            "redirect" => {
+                let addresses = arguments.by_ref()
+                    .map(|address| address.parse::<IpAddr>().map_err(|_| PolicyRuleError::BadRedirectAddress(address.to_string())))
+                    .collect::<Result<Vec<_>, _>>()?;
+                if addresses.is_empty() {
+                    return Err(PolicyRuleError::MissingRedirectAddress);
+                }
+                PolicyAction::Redirect(addresses)
+            },
+            _ => return Err(PolicyRuleError::UnknownAction(action.to_string())),
+        };
+        if let Some(argument) = arguments.next() {
+            return Err(PolicyRuleError::UnexpectedArgument(argument.to_string()));
+        }
+        Ok(Self { domain, include_subdomains, action })
+    }
+}
+
+impl Display for PolicyRule {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.action {
+            PolicyAction::Redirect(addresses) => {
+                write!(f, "redirect ")?;
+                if self.include_subdomains {
+                    write!(f, "*.")?;
+                }
+                write!(f, "{}", self.domain)?;
+                for address in addresses {
+                    write!(f, " {address}")?;
+                }
+                Ok(())
+            },
+            action => {
+                write!(f, "{action} ")?;
+                if self.include_subdomains {
+                    write!(f, "*.")?;
+                }
+                write!(f, "{}", self.domain)
+            },
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+struct DomainRules {
+    exact: Option<PolicyAction>,
+    subdomains: Option<PolicyAction>,
+}
+
+/// Combines a new action with the one already set for a name, keeping the one with the higher
+/// precedence. The addresses of redirects for the same name are merged.
+fn merge_action(existing: &mut Option<PolicyAction>, action: PolicyAction) {
+    match (existing.as_mut(), action) {
+        (None, action) => *existing = Some(action),
+        (Some(PolicyAction::Redirect(addresses)), PolicyAction::Redirect(new_addresses)) => {
+            for address in new_addresses {
+                if !addresses.contains(&address) {
+                    addresses.push(address);
+                }
+            }
+        },
+        (Some(current), action) => if action.precedence() > current.precedence() {
+            *current = action;
+        },
+    }
+}
+
+/// A set of filtering rules, indexed by domain.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct PolicyRules {
+    domains: HashMap<CDomainName, DomainRules>,
+    rule_count: usize,
+}
+
+impl PolicyRules {
+    #[inline]
+    pub fn new() -> Self {
+        Self { domains: HashMap::new(), rule_count: 0 }
+    }
+
+    pub fn insert(&mut self, rule: PolicyRule) {
+        let rules = self.domains.entry(rule.domain.as_lowercase()).or_default();
+        if rule.include_subdomains {
+            merge_action(&mut rules.subdomains, rule.action);
+        } else {
+            merge_action(&mut rules.exact, rule.action);
+        }
+        self.rule_count += 1;
+    }
+
+    /// Reads the rules out of the text of TXT records. Each record holds one rule. The
+    /// character-strings of a record are joined before it is parsed, so long rules can be split
+    /// across several of them. Records that are not valid rules are returned alongside the rules
+    /// that were read.
+    pub fn from_txt_records<'a>(records: impl IntoIterator<Item = &'a ResourceRecord>) -> (Self, Vec<(String, PolicyRuleError)>) {
+        let mut rules = Self::new();
+        let mut invalid = Vec::new();
+        for record in records {
+            let RecordData::TXT(txt) = record.get_rdata() else {
+                continue;
+            };
+            let text = txt.concatenated().to_string();
+            match PolicyRule::from_text(&text) {
+                Ok(rule) => rules.insert(rule),
+                Err(error) => invalid.push((text, error)),
+            }
+        }
+        (rules, invalid)
+    }
+
+    /// The number of rules that were added, including any that were merged with another rule for
+    /// the same name.
+    #[inline]
+    pub fn len(&self) -> usize { self.rule_count }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool { self.rule_count == 0 }
+
+    /// Finds the action for the name. The most specific matching rule decides, so a rule for
+    /// `www.example.` overrides one for `*.example.`. Returns `None` if no rule matches.
+    pub fn action(&self, name: &CDomainName) -> Option<&PolicyAction> {
+        let name = name.as_lowercase();
+        let mut domains = name.search_domains();
+        let exact_rules = domains.next().and_then(|domain| self.domains.get(&domain));
+        if let Some(DomainRules { exact, subdomains }) = exact_rules {
+            match (exact, subdomains) {
+                (Some(exact), Some(subdomains)) if subdomains.precedence() > exact.precedence() => return Some(subdomains),
+                (Some(action), _) | (None, Some(action)) => return Some(action),
+                (None, None) => (),
+            }
+        }
+        domains.find_map(|domain| self.domains.get(&domain).and_then(|rules| rules.subdomains.as_ref()))
+    }
+}
+
+/// Filters queries using rules kept in an internal zone. Each TXT record at the control name is a
+/// `PolicyRule`. Operators change the rules through ordinary DNS updates to that zone rather than
+/// by reloading files. Start a `PolicyWatcher` with `watch()` to keep the rules up to date.
+///
+/// Queries for the control name and the names below it are never filtered, so that a bad rule
+/// cannot stop the rules from being fixed.
+///
+/// Clones share the same rules, so the middleware can be added to the client's chain while a
+/// clone is kept to start the watcher once the client has been created.
+#[derive(Debug, Clone)]
+pub struct PolicyMiddleware {
+    control_name: CDomainName,
+    rules: Arc<RwLock<Arc<PolicyRules>>>,
+}
+
+impl PolicyMiddleware {
+    #[inline]
+    pub fn new(control_name: CDomainName) -> Self {
+        Self { control_name, rules: Arc::new(RwLock::new(Arc::new(PolicyRules::new()))) }
+    }
+
+    #[inline]
+    pub fn control_name(&self) -> &CDomainName { &self.control_name }
+
+    /// The rules currently being applied.
+    #[inline]
+    pub fn rules(&self) -> Arc<PolicyRules> {
+        let r_rules = self.rules.read().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let rules = r_rules.clone();
+        drop(r_rules);
+        rules
+    }
+
+    /// Replaces the rules. Queries that have already been checked are not affected.
+    #[inline]
+    pub fn set_rules(&self, rules: PolicyRules) {
+        let mut w_rules = self.rules.write().unwrap_or_else(|poisoned| poisoned.into_inner());
+        *w_rules = Arc::new(rules);
+        drop(w_rules);
+    }
+
+    /// Queries the control name for its TXT records and replaces the rules with the ones they
+    /// hold. If the name does not exist or has no TXT records, all of the rules are removed. If
+    /// the query fails, the current rules are kept and the error is returned.
+    pub async fn refresh(&self, client: Arc<DNSAsyncClient>, rclass: RClass) -> Result<usize, ErrorResponse> {
+        let question = Question::new(self.control_name.clone(), RType::TXT, rclass);
+        let records = match client.query(Context::new(question, QNameMinimization::None)).await {
+            Response::Answer(answer) => answer.answer,
+            Response::Error(ErrorResponse { rcode: RCode::NXDomain, negative: _ }) => Vec::new(),
+            Response::Error(error) => return Err(error),
+        };
+        let (rules, invalid) = PolicyRules::from_txt_records(records.iter().filter(|record| record.get_name().matches(&self.control_name)));
+        for (text, error) in invalid {
+            warn!("Ignoring policy rule '{text}' from '{}': {error}", self.control_name);
+        }
+        let rule_count = rules.len();
+        self.set_rules(rules);
+        Ok(rule_count)
+    }
+
+    /// Starts refreshing the rules from the control name in the background. The records are
+    /// cached like any other, so the rules change no sooner than the TTL of the TXT records
+    /// allows, even if the refresh interval is shorter.
+    pub fn watch(&self, client: Arc<DNSAsyncClient>, config: PolicyWatchConfig) -> PolicyWatcher {
+        let policy = self.clone();
+        let task = tokio::spawn(async move {
+            let mut interval = tokio::time::interval(config.refresh_interval);
+            interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+            loop {
+                interval.tick().await;
+                match policy.refresh(client.clone(), config.rclass).await {
+                    Ok(rule_count) => info!("Loaded {rule_count} policy rules from '{}'", policy.control_name),
+                    Err(error) => warn!("Failed to refresh the policy rules from '{}', keeping the current rules: {error}", policy.control_name),
+                }
+            }
+        });
+        PolicyWatcher { task }
+    }
+
+    fn synthesize_redirect(context: &Context, addresses: &[IpAddr]) -> Response {
+        let question = context.query();
+        let answer = addresses.iter()
+            .filter_map(|address| match (address, question.qtype()) {
+                (IpAddr::V4(address), RType::A | RType::ANY) => Some(RecordData::A(A::new(*address))),
+                (IpAddr::V6(address), RType::AAAA | RType::ANY) => Some(RecordData::AAAA(AAAA::new(*address))),
+                _ => None,
+            })
+            .map(|rdata| ResourceRecord::new(question.qname().clone(), question.qclass(), REDIRECT_TTL, rdata))
+            .collect();
+        Response::Answer(Answer { answer, name_servers: Vec::new(), additional: Vec::new(), authoritative: false, negative: None, trace: context.trace().cloned() })
+    }
+}
+
+#[async_trait]
+impl Middleware for PolicyMiddleware {
+    async fn handle(&self, context: Context, next: Next<'_>) -> Response {
+        if self.control_name.is_parent_domain_of(context.qname()) {
+            return next.run(context).await;
+        }
+        let rules = self.rules();
+        match rules.action(context.qname()) {
+            None | Some(PolicyAction::Allow) => next.run(context).await,
+            Some(PolicyAction::Block) => Response::Error(ErrorResponse::new(RCode::NXDomain)),
+            Some(PolicyAction::Redirect(addresses)) => Self::synthesize_redirect(&context, addresses),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct PolicyWatchConfig {
+    pub rclass: RClass,
+    pub refresh_interval: Duration,
+}
+
+impl Default for PolicyWatchConfig {
+    fn default() -> Self {
+        Self { rclass: RClass::Internet, refresh_interval: Duration::from_secs(60) }
+    }
+}
+
+/// Keeps a `PolicyMiddleware`'s rules in sync with its control zone. The rules stop being
+/// refreshed when this is dropped. The rules that were last loaded stay in place.
+#[derive(Debug)]
+pub struct PolicyWatcher {
+    task: JoinHandle<()>,
+}
+
+impl Drop for PolicyWatcher {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+#[cfg(test)]
+mod policy_tests {
+    use std::net::IpAddr;
+
+    use dns_lib::{resource_record::{rclass::RClass, resource_record::{RecordData, ResourceRecord}, time::Time, types::txt::TXT}, types::character_string::CharacterString};
+
+    use crate::test_support::{a, name};
+
+    use super::{PolicyAction, PolicyRule, PolicyRuleError, PolicyRules};
+
+    fn rules(texts: &[&str]) -> PolicyRules {
+        let mut rules = PolicyRules::new();
+        for text in texts {
+            rules.insert(PolicyRule::from_text(text).unwrap());
+        }
+        rules
+    }
+
+    fn action(rules: &PolicyRules, domain: &str) -> Option<PolicyAction> {
+        rules.action(&name(domain)).cloned()
+    }
+
+    fn redirect(addresses: &[&str]) -> PolicyAction {
+        PolicyAction::Redirect(addresses.iter().map(|address| address.parse::<IpAddr>().unwrap()).collect())
+    }
+
+    fn txt(strings: &[&str]) -> ResourceRecord {
+        let strings = strings.iter().map(|string| CharacterString::from_utf8(string).unwrap()).collect();
+        ResourceRecord::new(name("policy.example."), RClass::Internet, Time::from_secs(300), RecordData::TXT(TXT::new(strings)))
+    }
+
+    #[test]
+    fn rules_for_the_same_name_are_decided_by_precedence() {
+        // The order that the rules are added in does not matter.
+        for texts in [["redirect a.example. 192.0.2.1", "block a.example.", "allow a.example."], ["allow a.example.", "block a.example.", "redirect a.example. 192.0.2.1"]] {
+            assert_eq!(action(&rules(&texts), "a.example."), Some(PolicyAction::Allow));
+        }
+        for texts in [["redirect a.example. 192.0.2.1", "block a.example."], ["block a.example.", "redirect a.example. 192.0.2.1"]] {
+            assert_eq!(action(&rules(&texts), "a.example."), Some(PolicyAction::Block));
+        }
+
+        // Redirects for the same name are merged instead.
+        let merged = rules(&["redirect a.example. 192.0.2.1", "redirect a.example. 2001:db8::1 192.0.2.1"]);
+        assert_eq!(action(&merged, "a.example."), Some(redirect(&["192.0.2.1", "2001:db8::1"])));
+        assert_eq!(merged.len(), 2);
+    }
+
+    #[test]
+    fn the_most_specific_rule_decides() {
+        let nested = rules(&["block *.example.", "redirect www.example. 192.0.2.1", "allow *.cdn.example.", "block ads.cdn.example."]);
+        assert_eq!(action(&nested, "mail.example."), Some(PolicyAction::Block));
+        // An exact rule overrides a wildcard for a parent domain, even with a lower precedence.
+        assert_eq!(action(&nested, "www.example."), Some(redirect(&["192.0.2.1"])));
+        // The closest wildcard wins over ones further up.
+        assert_eq!(action(&nested, "img.cdn.example."), Some(PolicyAction::Allow));
+        assert_eq!(action(&nested, "ads.cdn.example."), Some(PolicyAction::Block));
+        // Exact rules do not apply to the names below them.
+        assert_eq!(action(&nested, "a.www.example."), Some(PolicyAction::Block));
+        assert_eq!(action(&nested, "x.ads.cdn.example."), Some(PolicyAction::Allow));
+
+        // At the name of a wildcard, the exact rule and the wildcard are decided by precedence.
+        assert_eq!(action(&rules(&["block *.example.", "redirect example. 192.0.2.1"]), "example."), Some(PolicyAction::Block));
+        assert_eq!(action(&rules(&["block *.example.", "allow example."]), "example."), Some(PolicyAction::Allow));
+    }
+
+    #[test]
+    fn wildcards_match_whole_labels() {
+        let blocked = rules(&["block *.example.", "block ads.test."]);
+        for domain in ["example.", "www.example.", "a.b.example.", "WWW.Example.", "ads.test."] {
+            assert_eq!(action(&blocked, domain), Some(PolicyAction::Block), "{domain}");
+        }
+        for domain in ["badexample.", "example.com.", "com.", "x.ads.test.", "bads.test."] {
+            assert_eq!(action(&blocked, domain), None, "{domain}");
+        }
+        // Names in rules are case-insensitive and fully qualified even if they are not written that
+        // way.
+        assert_eq!(action(&rules(&["BLOCK Ads.Example"]), "ads.example."), Some(PolicyAction::Block));
+    }
+
+    #[test]
+    fn malformed_txt_rules_are_returned_with_their_errors() {
+        let records = [
+            txt(&["block ok.example."]),
+            // Long rules can be split across character-strings.
+            txt(&["redirect split.example. ", "192.0.2.1"]),
+            txt(&[""]),
+            txt(&["deny a.example."]),
+            txt(&["block"]),
+            txt(&["block a..example."]),
+            txt(&["redirect a.example."]),
+            txt(&["redirect a.example. 192.0.2.1 not-an-address"]),
+            txt(&["block a.example. b.example."]),
+            // Only TXT records hold rules.
+            a("policy.example.", "192.0.2.1"),
+        ];
+        let (rules, invalid) = PolicyRules::from_txt_records(&records);
+        assert_eq!(rules.len(), 2);
+        assert_eq!(action(&rules, "ok.example."), Some(PolicyAction::Block));
+        assert_eq!(action(&rules, "split.example."), Some(redirect(&["192.0.2.1"])));
+
+        assert_eq!(invalid.len(), 7);
+        assert_eq!(invalid[0], (String::new(), PolicyRuleError::Empty));
+        assert_eq!(invalid[1], ("deny a.example.".to_string(), PolicyRuleError::UnknownAction("deny".to_string())));
+        assert_eq!(invalid[2], ("block".to_string(), PolicyRuleError::MissingName));
+        assert!(matches!(&invalid[3], (_, PolicyRuleError::BadName(name, _)) if name == "a..example."));
+        assert_eq!(invalid[4].1, PolicyRuleError::MissingRedirectAddress);
+        assert_eq!(invalid[5].1, PolicyRuleError::BadRedirectAddress("not-an-address".to_string()));
+        assert_eq!(invalid[6].1, PolicyRuleError::UnexpectedArgument("b.example.".to_string()));
+    }
+}
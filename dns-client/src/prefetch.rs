@@ -0,0 +1,242 @@
+use std::{collections::{hash_map::Entry, HashMap}, sync::{atomic::{AtomicU64, Ordering}, Arc, Mutex}, time::{Duration, Instant}};
+
+use async_trait::async_trait;
+use dns_cache::asynchronous::async_cache::AsyncTreeCache;
+use dns_lib::{interface::{cache::{cache::{AsyncCache, SharedAsyncCache}, CacheQuery, CacheRecord, CacheResponse}, client::{Context, QNameMinimization}}, query::question::QuestionKey, resource_record::time::Time};
+use log::debug;
+
+use crate::{query::{forward_query::forward_query, recursive_query::recursive_query}, result::QResult, ActiveQueryKey, DNSAsyncClient};
+
+const PERCENT: u64 = 100;
+
+/// Controls prefetching. Names that keep being answered from the cache are queried again in the
+/// background shortly before their records expire, so the cache is refreshed before anybody has
+/// to wait for it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct PrefetchConfig {
+    /// The number of times a name has to be answered from the cache before it is prefetched. The
+    /// count starts over whenever the records are refreshed.
+    pub min_hits: u32,
+    /// Prefetch once the records have less than this percentage of their original TTL left.
+    pub threshold_percent: u32,
+    /// Records with a shorter original TTL than this are never prefetched. Refreshing them would
+    /// cost a query every few seconds.
+    pub min_ttl: Time,
+    /// The most names that hits are counted for at once. Names that have expired are forgotten
+    /// to make room for new ones. Once it is full of names that have not, new names are not
+    /// prefetched.
+    pub max_tracked: usize,
+}
+
+impl Default for PrefetchConfig {
+    fn default() -> Self {
+        Self {
+            min_hits: 2,
+            threshold_percent: 10,
+            min_ttl: Time::from_secs(10),
+            max_tracked: 10_000,
+        }
+    }
+}
+
+/// A point-in-time copy of the prefetch counters.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct PrefetchStats {
+    /// The number of names that were queried again before their records expired.
+    pub prefetched: u64,
+    /// The number of those queries that did not get an answer. The records are left to expire.
+    pub failed: u64,
+    /// The number of names that hits are being counted for.
+    pub tracked: usize,
+}
+
+#[derive(Debug)]
+struct Hits {
+    count: u32,
+    /// When the records that the hits were counted against expire. Hits on records that expire at
+    /// another time are hits on a new set of records.
+    expires: Instant,
+    prefetching: bool,
+}
+
+/// Counts the cache hits for each name and decides when they should be prefetched.
+#[derive(Debug, Default)]
+pub(crate) struct Prefetcher {
+    hits: Mutex<HashMap<ActiveQueryKey, Hits>>,
+    prefetched: AtomicU64,
+    failed: AtomicU64,
+}
+
+impl Prefetcher {
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Counts a hit on the cached records for the context's question. Returns true if the name
+    /// should be prefetched now. It is not prefetched again until the records are refreshed or
+    /// expire.
+    pub fn record_hit(&self, config: &PrefetchConfig, context: &Context, records: &[CacheRecord]) -> bool {
+        // The records have already been given the time they have left as their TTL. The name has
+        // to be refreshed before the first of them expires.
+        let Some(first_to_expire) = records.iter().min_by_key(|record| record.get_ttl()) else {
+            return false;
+        };
+        let original_ttl = first_to_expire.original_ttl();
+        if original_ttl < config.min_ttl {
+            return false;
+        }
+        let remaining_ttl = first_to_expire.get_ttl().as_secs();
+        let now = Instant::now();
+        let expires = now + Duration::from_secs(u64::from(remaining_ttl));
+
+        let mut hits = self.hits.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        if hits.len() >= config.max_tracked {
+            hits.retain(|_, hits| hits.expires > now);
+        }
+        let tracked = hits.len();
+        let hits = match hits.entry((context.view().cloned(), QuestionKey::from(context.query()))) {
+            Entry::Occupied(entry) => {
+                let hits = entry.into_mut();
+                // The records were refreshed since the last hit.
+                if hits.expires.max(expires) - hits.expires.min(expires) > Duration::from_secs(1) {
+                    *hits = Hits { count: 0, expires, prefetching: false };
+                }
+                hits
+            },
+            Entry::Vacant(_) if tracked >= config.max_tracked => return false,
+            Entry::Vacant(entry) => entry.insert(Hits { count: 0, expires, prefetching: false }),
+        };
+        hits.count = hits.count.saturating_add(1);
+
+        let below_threshold = u64::from(remaining_ttl) * PERCENT <= u64::from(original_ttl.as_secs()) * u64::from(config.threshold_percent);
+        if below_threshold && (hits.count >= config.min_hits) && !hits.prefetching {
+            hits.prefetching = true;
+            return true;
+        }
+        false
+    }
+
+    #[inline]
+    pub fn stats(&self) -> PrefetchStats {
+        PrefetchStats {
+            prefetched: self.prefetched.load(Ordering::Relaxed),
+            failed: self.failed.load(Ordering::Relaxed),
+            tracked: self.hits.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).len(),
+        }
+    }
+}
+
+/// Counts a hit on the cached records for the context's question and, if it is time, queries the
+/// name again in the background.
+pub(crate) fn record_cache_hit(client: &Arc<DNSAsyncClient>, context: &Context, records: &[CacheRecord]) {
+    let Some(config) = &client.config.prefetch else {
+        return;
+    };
+    if !client.prefetcher.record_hit(config, context, records) {
+        return;
+    }
+    let mut prefetch_context = Context::new(context.query().clone(), QNameMinimization::None);
+    if let Some(view) = context.view() {
+        prefetch_context = prefetch_context.with_view(view.clone());
+    }
+    tokio::spawn(prefetch(client.clone(), prefetch_context));
+}
+
+async fn prefetch(client: Arc<DNSAsyncClient>, context: Context) {
+    let Some(cache) = client.cache_for(&context) else {
+        return;
+    };
+    debug!(context:?; "Prefetching '{}'", context.query());
+    client.prefetcher.prefetched.fetch_add(1, Ordering::Relaxed);
+    let prefetch_cache: SharedAsyncCache = Arc::new(PrefetchCache {
+        question: QuestionKey::from(context.query()),
+        cache: AsyncTreeCache::new(cache),
+    });
+    // The answer is cached by the query. The result only needs to be counted.
+    let result = if client.config.is_forwarding() {
+        Box::pin(forward_query(client.clone(), prefetch_cache, context)).await
+    } else {
+        recursive_query(client.clone(), prefetch_cache, context).await
+    };
+    if !matches!(result, QResult::Ok(_)) {
+        client.prefetcher.failed.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// The client's cache, except that it never has the records for the question being prefetched.
+/// This makes the query for the question go to the network even though the records that it is
+/// refreshing are still cached.
+struct PrefetchCache {
+    question: QuestionKey,
+    cache: AsyncTreeCache,
+}
+
+#[async_trait]
+impl AsyncCache for PrefetchCache {
+    async fn get(&self, query: &CacheQuery<'_>) -> CacheResponse {
+        if QuestionKey::from(query.question) == self.question {
+            return CacheResponse::Records(Vec::new());
+        }
+        self.cache.get(query).await
+    }
+
+    #[inline]
+    async fn insert_record(&self, record: CacheRecord) {
+        self.cache.insert_record(record).await
+    }
+
+    #[inline]
+    async fn insert_records(&self, records: Vec<CacheRecord>) {
+        self.cache.insert_records(records).await
+    }
+
+    /// A prefetch must not be answered by another query for the same question, since that query
+    /// may be answered from the cache.
+    #[inline]
+    fn is_isolated(&self) -> bool { true }
+}
+
+#[cfg(test)]
+mod prefetch_tests {
+    use std::{net::Ipv4Addr, time::{Duration, Instant}};
+
+    use dns_lib::{interface::{cache::{CacheMeta, CacheRecord, MetaAuth}, client::{Context, QNameMinimization}}, query::question::Question, resource_record::{rclass::RClass, resource_record::{RecordData, ResourceRecord}, rtype::RType, time::Time, types::a::A}, types::c_domain_name::CDomainName};
+
+    use super::{PrefetchConfig, Prefetcher};
+
+    /// The record as the cache returns it, when it was cached with this TTL this many seconds ago.
+    fn cached(ttl: u32, age: u64) -> CacheRecord {
+        let record = ResourceRecord::new(CDomainName::from_utf8("www.example.com.").unwrap(), RClass::Internet, Time::new(ttl), RecordData::A(A::new(Ipv4Addr::new(192, 0, 2, 1))));
+        let insertion_time = Instant::now().checked_sub(Duration::from_secs(age)).unwrap();
+        let mut record = CacheRecord { meta: CacheMeta { auth: MetaAuth::NotAuthoritative, insertion_time, original_ttl: None }, record };
+        record.decay_ttl();
+        record
+    }
+
+    #[test]
+    fn popular_names_are_prefetched_once_shortly_before_they_expire() {
+        let config = PrefetchConfig::default();
+        let prefetcher = Prefetcher::new();
+        let context = Context::new(Question::new(CDomainName::from_utf8("www.example.com.").unwrap(), RType::A, RClass::Internet), QNameMinimization::None);
+
+        // Plenty of time left.
+        assert!(!prefetcher.record_hit(&config, &context, &[cached(300, 10)]));
+        assert!(!prefetcher.record_hit(&config, &context, &[cached(300, 10)]));
+
+        // Within the last 10% of the TTL. These records were cached at another time, so their hits
+        // are counted from the start.
+        let refreshed = [cached(300, 280)];
+        assert!(!prefetcher.record_hit(&config, &context, &refreshed));
+        assert!(prefetcher.record_hit(&config, &context, &refreshed));
+        // The prefetch has already started.
+        assert!(!prefetcher.record_hit(&config, &context, &refreshed));
+        assert_eq!(prefetcher.stats().tracked, 1);
+
+        // Short TTLs are left to expire.
+        let context = Context::new(Question::new(CDomainName::from_utf8("short.example.com.").unwrap(), RType::A, RClass::Internet), QNameMinimization::None);
+        for _ in 0..5 {
+            assert!(!prefetcher.record_hit(&config, &context, &[cached(5, 4)]));
+        }
+    }
+}
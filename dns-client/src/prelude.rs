@@ -0,0 +1,36 @@
+//! The types and traits needed to create a client, configure it, and query it, along with
+//! everything in `dns_lib::prelude`:
+//!
+//! ```
+//! use std::sync::Arc;
+//!
+//! use dns_client::prelude::*;
+//!
+//! # async fn resolve() {
+//! let cache: SharedAsyncMainCache = Arc::new(AsyncMainTreeCache::new());
+//! let client = Arc::new(DNSAsyncClient::with_config(cache, ClientConfig::default()).await);
+//! let question = Question::new(CDomainName::from_utf8("example.com.").unwrap(), RType::A, RClass::Internet);
+//! if let Response::Answer(answer) = client.query(Context::new(question, QNameMinimization::None)).await {
+//!     for record in answer.answer {
+//!         println!("{record}");
+//!     }
+//! }
+//! # }
+//! ```
+//!
+//! Everything here is part of the crate's stable surface. Items are only added once their API has
+//! settled, and removing or renaming one is a breaking change.
+
+pub use dns_cache::asynchronous::async_main_cache::AsyncMainTreeCache;
+pub use dns_lib::{interface::cache::main_cache::SharedAsyncMainCache, prelude::*};
+pub use network::async_query::QueryOpt;
+
+pub use crate::{
+    local_data::{LocalData, LocalRecords},
+    middleware::{Middleware, MiddlewareChain, Next},
+    upstream::{EncryptedUpstream, ForwardUpstream},
+    ClientConfig,
+    ClientEvent,
+    ClientStats,
+    DNSAsyncClient,
+};
@@ -0,0 +1,351 @@
+use std::{collections::BTreeMap, error::Error, fmt::Display, net::IpAddr, sync::Arc};
+
+use dns_lib::{interface::client::{AsyncClient, Context, QNameMinimization, Response}, query::{message::Message, question::Question}, resource_record::{rclass::RClass, rcode::RCode, resource_record::{RecordData, ResourceRecord}, rtype::RType}, types::c_domain_name::{CDomainName, CmpDomainName}};
+use futures::future::join_all;
+use log::debug;
+use network::errors::QueryError;
+
+use crate::{query::network_query::query_network_uncached, DNSAsyncClient};
+
+/// Why a name server's answer could not be compared with the others.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum ServerFailure {
+    /// The name server could not be queried.
+    Query(QueryError),
+    /// The name server answered with an RCode other than NOERROR or NXDOMAIN.
+    RCode(RCode),
+    /// The name server answered without the AA bit, so it does not serve the zone.
+    NotAuthoritative,
+    /// The name server's answer for the zone's SOA record did not include one.
+    MissingSoa,
+}
+
+impl Display for ServerFailure {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Query(error) => write!(f, "{error}"),
+            Self::RCode(rcode) => write!(f, "answered with {rcode}"),
+            Self::NotAuthoritative => write!(f, "answered without the AA bit"),
+            Self::MissingSoa => write!(f, "did not answer with the zone's SOA record"),
+        }
+    }
+}
+
+/// The records a name server answered the question with. Records are sorted by their
+/// presentation format so that the same RRset compares as equal no matter what order each name
+/// server sent it in.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ServerAnswer {
+    pub rcode: RCode,
+    pub records: Vec<ResourceRecord>,
+}
+
+impl Display for ServerAnswer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.records.is_empty() {
+            return match self.rcode {
+                RCode::NoError => write!(f, "no records"),
+                rcode => write!(f, "{rcode}"),
+            };
+        }
+        let mut records = self.records.iter();
+        if let Some(record) = records.next() {
+            write!(f, "{record}")?;
+        }
+        for record in records {
+            write!(f, "\n{record}")?;
+        }
+        Ok(())
+    }
+}
+
+/// What one of the zone's name servers answered.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ServerReport {
+    /// The name of the name server, if it was found through the zone's NS records.
+    pub name_server: Option<CDomainName>,
+    pub address: IpAddr,
+    /// The serial of the zone's SOA record.
+    pub serial: Result<u32, ServerFailure>,
+    pub answer: Result<ServerAnswer, ServerFailure>,
+}
+
+/// The answers that each of a zone's name servers gave for the same question, queried directly
+/// so that no cache hides a difference between them. A change has propagated once every name
+/// server has the same SOA serial and the same answer.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PropagationReport {
+    pub zone: CDomainName,
+    pub question: Question,
+    pub servers: Vec<ServerReport>,
+}
+
+impl PropagationReport {
+    /// Whether every name server answered and they all agree on both the SOA serial and the
+    /// answer.
+    pub fn is_propagated(&self) -> bool {
+        self.failures().is_empty() && (self.serials().len() <= 1) && (self.answers().len() <= 1)
+    }
+
+    /// The addresses of the name servers that have each SOA serial.
+    pub fn serials(&self) -> BTreeMap<u32, Vec<IpAddr>> {
+        let mut serials = BTreeMap::<u32, Vec<IpAddr>>::new();
+        for server in &self.servers {
+            if let Ok(serial) = server.serial {
+                serials.entry(serial).or_default().push(server.address);
+            }
+        }
+        serials
+    }
+
+    /// Each distinct answer along with the addresses of the name servers that gave it.
+    pub fn answers(&self) -> Vec<(&ServerAnswer, Vec<IpAddr>)> {
+        let mut answers = Vec::<(&ServerAnswer, Vec<IpAddr>)>::new();
+        for server in &self.servers {
+            let Ok(answer) = &server.answer else {
+                continue;
+            };
+            match answers.iter_mut().find(|(known, _)| *known == answer) {
+                Some((_, addresses)) => addresses.push(server.address),
+                None => answers.push((answer, vec![server.address])),
+            }
+        }
+        answers
+    }
+
+    /// The name servers whose SOA serial or answer could not be compared with the others.
+    pub fn failures(&self) -> Vec<(IpAddr, &ServerFailure)> {
+        self.servers.iter()
+            .flat_map(|server| [server.serial.as_ref().err(), server.answer.as_ref().err()]
+                .into_iter()
+                .flatten()
+                .map(|failure| (server.address, failure)))
+            .collect()
+    }
+}
+
+impl Display for PropagationReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let question = &self.question;
+        match self.is_propagated() {
+            true => writeln!(f, "all {} name servers for '{}' agree on {} {} {}", self.servers.len(), self.zone, question.qname(), question.qclass(), question.qtype())?,
+            false => writeln!(f, "the name servers for '{}' disagree on {} {} {}", self.zone, question.qname(), question.qclass(), question.qtype())?,
+        }
+        let serials = self.serials();
+        if serials.len() > 1 {
+            for (serial, addresses) in serials {
+                writeln!(f, "serial {serial}: {}", join(&addresses))?;
+            }
+        }
+        let answers = self.answers();
+        if answers.len() > 1 {
+            for (answer, addresses) in answers {
+                writeln!(f, "answered by {}:", join(&addresses))?;
+                writeln!(f, "{answer}")?;
+            }
+        }
+        for (address, failure) in self.failures() {
+            writeln!(f, "{address} failed: {failure}")?;
+        }
+        Ok(())
+    }
+}
+
+fn join(addresses: &[IpAddr]) -> String {
+    addresses.iter().map(IpAddr::to_string).collect::<Vec<_>>().join(", ")
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum PropagationError {
+    /// The question is not for a name in the zone.
+    OutOfZone,
+    /// The zone's NS records could not be resolved.
+    NameServers(RCode),
+    /// The zone has no NS records.
+    NoNameServers,
+    /// None of the zone's name servers has an address.
+    NoAddresses,
+}
+
+impl Display for PropagationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::OutOfZone => write!(f, "the question is not for a name in the zone"),
+            Self::NameServers(rcode) => write!(f, "resolving the zone's name servers failed with {rcode}"),
+            Self::NoNameServers => write!(f, "the zone has no name servers"),
+            Self::NoAddresses => write!(f, "none of the zone's name servers has an address"),
+        }
+    }
+}
+
+impl Error for PropagationError {}
+
+impl DNSAsyncClient {
+    /// Sends the question to every address of every name server for the zone and compares their
+    /// answers and SOA serials. The name servers and their addresses are resolved as usual, but
+    /// the question itself is sent straight to each name server and the answers are not cached.
+    pub async fn check_propagation(client: Arc<Self>, zone: &CDomainName, question: &Question) -> Result<PropagationReport, PropagationError> {
+        if !zone.is_parent_domain_of(question.qname()) {
+            return Err(PropagationError::OutOfZone);
+        }
+        let ns_question = Question::new(zone.clone(), RType::NS, question.qclass());
+        let name_servers = match client.clone().query(Context::new(ns_question, QNameMinimization::None)).await {
+            Response::Answer(answer) => {
+                let mut name_servers = answer.answer.iter()
+                    .filter_map(|record| match record.get_rdata() {
+                        RecordData::NS(ns) if record.get_name().matches(zone) => Some(ns.name_server_domain_name().as_lowercase()),
+                        _ => None,
+                    })
+                    .collect::<Vec<_>>();
+                name_servers.sort_by_key(|name| name.to_string());
+                name_servers.dedup();
+                name_servers
+            },
+            Response::Error(error) => return Err(PropagationError::NameServers(error.rcode)),
+        };
+        if name_servers.is_empty() {
+            return Err(PropagationError::NoNameServers);
+        }
+
+        let mut servers = Vec::new();
+        for name_server in name_servers {
+            for rtype in [RType::A, RType::AAAA] {
+                let address_question = Question::new(name_server.clone(), rtype, RClass::Internet);
+                if let Response::Answer(answer) = client.clone().query(Context::new(address_question, QNameMinimization::None)).await {
+                    servers.extend(answer.answer.iter()
+                        .filter_map(|record| match record.get_rdata() {
+                            RecordData::A(a) => Some(IpAddr::from(*a.ipv4_addr())),
+                            RecordData::AAAA(aaaa) => Some(IpAddr::from(*aaaa.ipv6_addr())),
+                            _ => None,
+                        })
+                        .map(|address| (Some(name_server.clone()), address)));
+                }
+            }
+        }
+        if servers.is_empty() {
+            return Err(PropagationError::NoAddresses);
+        }
+        Ok(client.compare_servers(zone, question, servers).await)
+    }
+
+    /// Sends the question to each of these addresses, which should be name servers for the zone,
+    /// and compares their answers and SOA serials. This is useful for name servers that are not
+    /// listed in the zone's NS records, such as a hidden primary. The answers are not cached.
+    pub async fn compare_name_servers(&self, zone: &CDomainName, question: &Question, addresses: &[IpAddr]) -> PropagationReport {
+        self.compare_servers(zone, question, addresses.iter().map(|address| (None, *address)).collect()).await
+    }
+
+    async fn compare_servers(&self, zone: &CDomainName, question: &Question, servers: Vec<(Option<CDomainName>, IpAddr)>) -> PropagationReport {
+        let soa_question = Question::new(zone.clone(), RType::SOA, question.qclass());
+        let servers = join_all(servers.into_iter().map(|(name_server, address)| {
+            let soa_question = &soa_question;
+            async move {
+                let (serial, answer) = futures::join!(
+                    query_serial(self, soa_question, &address),
+                    query_answer(self, question, &address),
+                );
+                ServerReport { name_server, address, serial, answer }
+            }
+        })).await;
+        PropagationReport { zone: zone.clone(), question: question.clone(), servers }
+    }
+}
+
+async fn query_authoritative(client: &DNSAsyncClient, question: &Question, address: &IpAddr) -> Result<Message, ServerFailure> {
+    let response = query_network_uncached(client, question, address).await.map_err(|error| {
+        debug!(question:?; "Propagation check query to {address} failed: {error}");
+        ServerFailure::Query(error)
+    })?;
+    match response.rcode {
+        RCode::NoError | RCode::NXDomain if response.authoritative_answer => Ok(response),
+        RCode::NoError | RCode::NXDomain => Err(ServerFailure::NotAuthoritative),
+        rcode => Err(ServerFailure::RCode(rcode)),
+    }
+}
+
+async fn query_serial(client: &DNSAsyncClient, soa_question: &Question, address: &IpAddr) -> Result<u32, ServerFailure> {
+    let response = query_authoritative(client, soa_question, address).await?;
+    response.answer.iter()
+        .find_map(|record| match record.get_rdata() {
+            RecordData::SOA(soa) if record.get_name().matches(soa_question.qname()) => Some(*soa.serial()),
+            _ => None,
+        })
+        .ok_or(ServerFailure::MissingSoa)
+}
+
+async fn query_answer(client: &DNSAsyncClient, question: &Question, address: &IpAddr) -> Result<ServerAnswer, ServerFailure> {
+    let response = query_authoritative(client, question, address).await?;
+    let mut records = response.answer;
+    records.sort_by_cached_key(|record| record.to_string());
+    Ok(ServerAnswer { rcode: response.rcode, records })
+}
+
+#[cfg(test)]
+mod propagation_tests {
+    use std::{net::{IpAddr, Ipv4Addr}, sync::Arc};
+
+    use dns_cache::asynchronous::async_main_cache::AsyncMainTreeCache;
+    use dns_lib::{query::{message::Message, qr::QR, question::Question}, resource_record::{rclass::RClass, rcode::RCode, resource_record::{RecordData, ResourceRecord}, rtype::RType, time::Time, types::{a::A, soa::SOA}}, serde::wire::{from_wire::FromWire, read_wire::ReadWire, to_wire::ToWire, write_wire::WriteWire}, types::c_domain_name::{CDomainName, CompressionMap}};
+    use tokio::net::UdpSocket;
+
+    use crate::{ClientConfig, DNSAsyncClient};
+
+    fn name(name: &str) -> CDomainName {
+        CDomainName::from_utf8(name).unwrap()
+    }
+
+    /// An authoritative name server for example.com. that has the zone at this serial, with
+    /// www.example.com. pointing at this address.
+    fn serve(socket: UdpSocket, serial: u32, www: Ipv4Addr) {
+        tokio::spawn(async move {
+            let mut buffer = vec![0_u8; u16::MAX as usize];
+            loop {
+                let (length, peer) = socket.recv_from(&mut buffer).await.unwrap();
+                let query = Message::from_wire_format(&mut ReadWire::from_bytes(&buffer[..length])).unwrap();
+                let mut response = query.clone();
+                response.qr = QR::Response;
+                response.authoritative_answer = true;
+                response.additional.clear();
+                let question = &query.question[0];
+                let rdata = match question.qtype() {
+                    RType::SOA => RecordData::SOA(SOA::new(name("ns1.example.com."), name("admin.example.com."), serial, Time::from_secs(7200), Time::from_secs(3600), Time::from_secs(1209600), 300)),
+                    _ => RecordData::A(A::new(www)),
+                };
+                response.answer.push(ResourceRecord::new(question.qname().clone(), RClass::Internet, Time::from_secs(300), rdata));
+                let mut out = vec![0_u8; u16::MAX as usize];
+                let mut wire = WriteWire::from_bytes(&mut out);
+                response.to_wire_format(&mut wire, &mut Some(CompressionMap::new())).unwrap();
+                let length = wire.current_len();
+                socket.send_to(&out[..length], peer).await.unwrap();
+            }
+        });
+    }
+
+    #[tokio::test]
+    async fn name_servers_that_lag_behind_are_reported() {
+        let first = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let port = first.local_addr().unwrap().port();
+        let second = UdpSocket::bind((Ipv4Addr::new(127, 0, 0, 2), port)).await.unwrap();
+        serve(first, 2, Ipv4Addr::new(192, 0, 2, 2));
+        serve(second, 1, Ipv4Addr::new(192, 0, 2, 1));
+
+        let config = ClientConfig { upstream_port: port, ..ClientConfig::default() };
+        let client = Arc::new(DNSAsyncClient::with_config(Arc::new(AsyncMainTreeCache::new()), config).await);
+        let zone = name("example.com.");
+        let question = Question::new(name("www.example.com."), RType::A, RClass::Internet);
+        let addresses = [IpAddr::from(Ipv4Addr::new(127, 0, 0, 1)), IpAddr::from(Ipv4Addr::new(127, 0, 0, 2))];
+
+        let report = client.compare_name_servers(&zone, &question, &addresses).await;
+        assert!(!report.is_propagated());
+        assert!(report.failures().is_empty());
+        assert_eq!(report.serials().into_iter().collect::<Vec<_>>(), vec![(1, vec![addresses[1]]), (2, vec![addresses[0]])]);
+        let answers = report.answers();
+        assert_eq!(answers.len(), 2);
+        assert_eq!(answers[0].1, vec![addresses[0]]);
+        assert_eq!(answers[0].0.rcode, RCode::NoError);
+
+        let report = client.compare_name_servers(&zone, &question, &addresses[..1]).await;
+        assert!(report.is_propagated());
+        client.close().await;
+    }
+}
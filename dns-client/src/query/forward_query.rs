@@ -0,0 +1,174 @@
+use std::sync::Arc;
+
+use dns_lib::{interface::{cache::{cache::{AsyncCache, SharedAsyncCache}, CacheQuery, CacheResponse}, client::Context}, resource_record::{rcode::RCode, rtype::RType}};
+use log::{debug, trace};
+
+use crate::{prefetch::record_cache_hit, query::network_query::query_forwarder, result::{QError, QOk, QResult}, DNSAsyncClient};
+
+/// Answers the query from the cache, or else from the first forwarding upstream that answers it
+/// with NOERROR or NXDOMAIN. The upstream follows CNAME chains itself, so a cached CNAME without
+/// its target is not used as the answer.
+pub(crate) async fn forward_query(client: Arc<DNSAsyncClient>, joined_cache: SharedAsyncCache, context: Context) -> QResult {
+    debug!(context:?; "Start forwarded search");
+    if let Some(answer) = client.local_data.lookup(context.query()) {
+        trace!(context:?; "Forwarded search local data response: '{answer:?}'");
+        return QResult::Ok(QOk { answer, name_servers: Vec::new(), additional: Vec::new() });
+    }
+    match joined_cache.get(&CacheQuery { authoritative: false, question: context.query() }).await {
+        CacheResponse::Records(records) if records.iter().any(|record| (record.get_rtype() == context.qtype()) || (context.qtype() == RType::ANY)) => {
+            trace!(context:?; "Forwarded search cache response: '{records:?}'");
+            record_cache_hit(&client, &context, &records);
+            return QResult::Ok(QOk {
+                answer: records.into_iter().map(|record| record.record).collect(),
+                name_servers: Vec::new(),
+                additional: Vec::new(),
+            });
+        },
+        CacheResponse::Records(_) => (),
+        CacheResponse::Err(rcode) => return QError::CacheFailure(rcode).into(),
+    }
+
+    let mut result = QResult::Fail(RCode::ServFail);
+    for upstream in &client.config.forwarders {
+        let exchange = Box::pin(query_forwarder(&client, &context, upstream));
+        let response = match context.cancellation() {
+            Some(token) => tokio::select! {
+                response = exchange => response,
+                () = token.awoken() => {
+                    debug!(context:?; "Forwarded search to '{}' was cancelled", upstream.address());
+                    return QError::Cancelled.into();
+                },
+            },
+            None => exchange.await,
+        };
+        let response = match response {
+            Ok(response) => response,
+            Err(error) => {
+                debug!(context:?; "Forwarded search to '{}' failed: {error}", upstream.address());
+                result = QResult::Err(error.into());
+                continue;
+            },
+        };
+        match response.rcode {
+            RCode::NoError | RCode::NXDomain => {
+                trace!(context:?; "Forwarded search to '{}' got response '{response:?}'", upstream.address());
+                joined_cache.insert_message(&response).await;
+                if response.rcode == RCode::NXDomain {
+                    return QResult::Fail(RCode::NXDomain);
+                }
+                return QResult::Ok(QOk { answer: response.answer, name_servers: Vec::new(), additional: response.additional });
+            },
+            rcode => {
+                debug!(context:?; "Forwarded search to '{}' answered with {rcode}", upstream.address());
+                result = QResult::Fail(rcode);
+            },
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod forward_query_tests {
+    use std::{net::{Ipv4Addr, SocketAddr}, sync::Arc};
+
+    use dns_cache::asynchronous::async_main_cache::AsyncMainTreeCache;
+    use dns_lib::{interface::{client::{AsyncClient, Context, QNameMinimization, Response}, trace::{QueryOutcome, QueryTrace}}, query::{message::Message, qr::QR, question::Question}, resource_record::{rclass::RClass, rcode::RCode, resource_record::{RecordData, ResourceRecord}, rtype::RType, time::Time, types::a::A}, serde::wire::{from_wire::FromWire, read_wire::ReadWire, to_wire::ToWire, write_wire::WriteWire}, types::c_domain_name::{CDomainName, CompressionMap}};
+    use tokio::net::UdpSocket;
+
+    use crate::{local_data::LocalRecords, upstream::ForwardUpstream, ClientConfig, DNSAsyncClient};
+
+    /// A resolver that answers every query with this rcode, and with an A record if it is
+    /// NOERROR. It only answers queries that have the RD bit set.
+    async fn upstream(rcode: RCode) -> SocketAddr {
+        let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let address = socket.local_addr().unwrap();
+        tokio::spawn(async move {
+            let mut buffer = vec![0_u8; u16::MAX as usize];
+            loop {
+                let (length, peer) = socket.recv_from(&mut buffer).await.unwrap();
+                let query = Message::from_wire_format(&mut ReadWire::from_bytes(&buffer[..length])).unwrap();
+                if !query.recursion_desired {
+                    continue;
+                }
+                let mut response = query.clone();
+                response.qr = QR::Response;
+                response.recursion_available = true;
+                response.rcode = rcode;
+                response.additional.clear();
+                if rcode == RCode::NoError {
+                    let question = &query.question[0];
+                    response.answer.push(ResourceRecord::new(question.qname().clone(), RClass::Internet, Time::from_secs(300), RecordData::A(A::new(Ipv4Addr::new(192, 0, 2, 1)))));
+                }
+                let mut out = vec![0_u8; u16::MAX as usize];
+                let mut wire = WriteWire::from_bytes(&mut out);
+                response.to_wire_format(&mut wire, &mut Some(CompressionMap::new())).unwrap();
+                let length = wire.current_len();
+                socket.send_to(&out[..length], peer).await.unwrap();
+            }
+        });
+        address
+    }
+
+    #[tokio::test]
+    async fn queries_go_to_the_next_upstream_when_one_fails() {
+        let failing = upstream(RCode::ServFail).await;
+        let working = upstream(RCode::NoError).await;
+        let config = ClientConfig::forwarding(vec![ForwardUpstream::Plain(failing), ForwardUpstream::Plain(working)]);
+        let client = Arc::new(DNSAsyncClient::with_config(Arc::new(AsyncMainTreeCache::new()), config).await);
+
+        let question = Question::new(CDomainName::from_utf8("www.example.com.").unwrap(), RType::A, RClass::Internet);
+        let Response::Answer(answer) = client.clone().query(Context::new(question.clone(), QNameMinimization::None)).await else {
+            panic!("the query was not answered");
+        };
+        assert_eq!(answer.answer.len(), 1);
+
+        // The answer is cached, so it is still answered once the upstreams are gone.
+        client.close().await;
+        let Response::Answer(answer) = client.query(Context::new(question, QNameMinimization::None)).await else {
+            panic!("the query was not answered from the cache");
+        };
+        assert_eq!(answer.answer.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn answers_carry_the_trace() {
+        let failing = upstream(RCode::ServFail).await;
+        let working = upstream(RCode::NoError).await;
+        let config = ClientConfig::forwarding(vec![ForwardUpstream::Plain(failing), ForwardUpstream::Plain(working)]);
+        let client = Arc::new(DNSAsyncClient::with_config(Arc::new(AsyncMainTreeCache::new()), config).await);
+
+        let question = Question::new(CDomainName::from_utf8("www.example.com.").unwrap(), RType::A, RClass::Internet);
+        let trace = Arc::new(QueryTrace::new());
+        let Response::Answer(answer) = client.clone().query(Context::new(question, QNameMinimization::None).with_trace(trace.clone())).await else {
+            panic!("the query was not answered");
+        };
+        assert!(Arc::ptr_eq(answer.trace.as_ref().unwrap(), &trace));
+        let queries = trace.queries();
+        assert_eq!(queries.len(), 2);
+        assert_eq!(queries[0].server, failing.ip());
+        assert!(matches!(queries[0].outcome, QueryOutcome::Answer { rcode: RCode::ServFail, .. }));
+        assert_eq!(queries[1].server, working.ip());
+        assert!(matches!(queries[1].outcome, QueryOutcome::Answer { rcode: RCode::NoError, answers: 1, .. }));
+        assert!(!queries[1].attempts.is_empty());
+        client.close().await;
+    }
+
+    #[tokio::test]
+    async fn local_data_is_answered_before_the_upstreams() {
+        let failing = upstream(RCode::ServFail).await;
+        let client = Arc::new(DNSAsyncClient::with_config(Arc::new(AsyncMainTreeCache::new()), ClientConfig::forwarding(vec![ForwardUpstream::Plain(failing)])).await);
+        client.local_data().set_hosts(LocalRecords::from_hosts("192.0.2.10 printer.lan").0);
+
+        let question = Question::new(CDomainName::from_utf8("printer.lan.").unwrap(), RType::A, RClass::Internet);
+        let Response::Answer(answer) = client.clone().query(Context::new(question, QNameMinimization::None)).await else {
+            panic!("the query was not answered");
+        };
+        assert_eq!(answer.answer[0].get_rdata(), &RecordData::A(A::new(Ipv4Addr::new(192, 0, 2, 10))));
+
+        // Once the entry is gone, the name goes to the upstream.
+        client.local_data().set_hosts(LocalRecords::new());
+        let question = Question::new(CDomainName::from_utf8("printer.lan.").unwrap(), RType::A, RClass::Internet);
+        assert!(matches!(client.clone().query(Context::new(question, QNameMinimization::None)).await, Response::Error(_)));
+        client.close().await;
+    }
+}
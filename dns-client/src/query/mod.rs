@@ -1,3 +1,4 @@
+pub mod forward_query;
 pub mod network_query;
 pub mod recursive_query;
 pub mod round_robin_query;
@@ -1,34 +1,556 @@
-use std::{net::{IpAddr, SocketAddr}, sync::Arc};
+use std::{future::Future, net::{IpAddr, SocketAddr}, sync::Arc, time::Instant};
 
-use dns_lib::{interface::cache::cache::AsyncCache, query::{message::Message, question::Question}};
+use async_lib::awake_token::AwakeToken;
+use dns_lib::{interface::{cache::cache::SharedAsyncCache, client::Context, trace::{QueryTrace, TraceTransport, TransportAttempt}}, query::{chaos::{chaos_txt, ChaosQuery}, edns::set_udp_payload_size, message::Message, nsid::{request_nsid, response_nsid}, question::Question}, resource_record::rcode::RCode, tsig::TsigError};
 use log::trace;
-use network::{async_query::QueryOpt, errors::QueryError, mixed_tcp_udp::MixedSocket};
+use network::{async_query::QueryOpt, errors::{QueryError, UdpSendError}, mixed_tcp_udp::{MixedSocket, MixedTransport}};
+use tokio::pin;
 
-use crate::DNSAsyncClient;
+use crate::{events::{ClientEvent, Downgrade, LameReason}, poisoning::insert_checked, upstream::ForwardUpstream, DNSAsyncClient};
+#[cfg(feature = "tls")]
+use std::time::Duration;
+#[cfg(feature = "https")]
+use network::doh::{post_path, DEFAULT_DOH_PATH};
+#[cfg(any(feature = "https", feature = "quic", feature = "tls"))]
+use crate::upstream::EncryptedUpstream;
 
-const UPSTREAM_PORT: u16 = 53;
+pub async fn query_network(client: &DNSAsyncClient, cache: SharedAsyncCache, context: &Context, name_server_address: &IpAddr, cancellation: Option<&AwakeToken>) -> Result<Message, QueryError> {
+    let question = context.query();
+    let deadline = context.deadline();
+    let message = match context.trace() {
+        Some(trace) => {
+            let sent = Instant::now();
+            let mut attempts = Vec::new();
+            let result = query_network_attempts(client, client_query(client, question), name_server_address, deadline, cancellation, Some((trace, &mut attempts))).await;
+            trace.record_attempts(question, *name_server_address, sent, result.as_ref(), attempts);
+            result?
+        },
+        None => query_network_attempts(client, client_query(client, question), name_server_address, deadline, cancellation, None).await?,
+    };
+    return Ok(insert_checked(client, &cache, question, message, name_server_address).await);
+}
+
+/// Queries the name server without adding the response to any cache.
+pub(crate) async fn query_network_uncached(client: &DNSAsyncClient, question: &Question, name_server_address: &IpAddr) -> Result<Message, QueryError> {
+    query_network_message(client, client_query(client, question), name_server_address).await
+}
+
+/// Builds the query that the client sends for this question, with the EDNS options from its
+/// config.
+pub(crate) fn client_query(client: &DNSAsyncClient, question: &Question) -> Message {
+    let mut message_question = Message::from(question);
+    if let Some(udp_payload_size) = client.config.udp_payload_size {
+        set_udp_payload_size(&mut message_question, udp_payload_size);
+    }
+    if client.config.request_nsid {
+        request_nsid(&mut message_question);
+    }
+    message_question
+}
+
+/// Sends an already built query to the name server without adding the response to any cache.
+pub(crate) async fn query_network_message(client: &DNSAsyncClient, message_question: Message, name_server_address: &IpAddr) -> Result<Message, QueryError> {
+    query_network_attempts(client, message_question, name_server_address, None, None, None).await
+}
+
+/// Sends the context's query to a forwarding upstream, with the RD bit set, over the upstream's
+/// transport. Nothing is cached.
+pub(crate) async fn query_forwarder(client: &DNSAsyncClient, context: &Context, upstream: &ForwardUpstream) -> Result<Message, QueryError> {
+    let question = context.query();
+    let mut message_question = client_query(client, question);
+    message_question.recursion_desired = true;
+    let deadline = context.deadline().map(tokio::time::Instant::from_std);
+    let result = match context.trace() {
+        Some(trace) => {
+            let sent = Instant::now();
+            let mut attempts = Vec::new();
+            let result = send_forwarded_query(client, message_question, upstream, deadline, context.cancellation(), Some((trace, &mut attempts))).await;
+            trace.record_attempts(question, upstream.address().ip(), sent, result.as_ref(), attempts);
+            result
+        },
+        None => send_forwarded_query(client, message_question, upstream, deadline, context.cancellation(), None).await,
+    };
+    record_result(client, &upstream.address(), &result);
+    result
+}
+
+async fn send_forwarded_query(client: &DNSAsyncClient, message_question: Message, upstream: &ForwardUpstream, deadline: Option<tokio::time::Instant>, cancellation: Option<&AwakeToken>, trace: Option<(&QueryTrace, &mut Vec<TransportAttempt>)>) -> Result<Message, QueryError> {
+    let upstream = match upstream {
+        ForwardUpstream::Plain(address) => return send_udp_tcp_query(client, message_question, *address, deadline, cancellation, trace).await,
+        ForwardUpstream::Encrypted(upstream) => upstream,
+    };
+    match upstream.protocol {
+        #[cfg(feature = "tls")]
+        QueryOpt::Tls | QueryOpt::TlsInsecure => send_dot_query(client, message_question, upstream, deadline, trace).await,
+        #[cfg(feature = "https")]
+        QueryOpt::Https => send_doh_query(client, message_question, upstream, deadline, cancellation, trace).await,
+        #[cfg(feature = "quic")]
+        QueryOpt::Quic => send_doq_query(client, message_question, upstream, deadline, cancellation, trace).await,
+        protocol => Err(QueryError::UnsupportedTransport(protocol)),
+    }
+}
+
+/// Sends the query to the name server, retrying over TCP if the response is truncated. If a trace
+/// is given, each attempt is added to `attempts`. No attempt is allowed to run past the deadline
+/// or to keep going once the query is cancelled.
+async fn query_network_attempts(client: &DNSAsyncClient, message_question: Message, name_server_address: &IpAddr, deadline: Option<Instant>, cancellation: Option<&AwakeToken>, trace: Option<(&QueryTrace, &mut Vec<TransportAttempt>)>) -> Result<Message, QueryError> {
+    let result = send_query(client, message_question, name_server_address, deadline.map(tokio::time::Instant::from_std), cancellation, trace).await;
+    record_result(client, &name_server_socket_address(client, name_server_address), &result);
+    result
+}
+
+/// Counts the result towards the client's health and the upstream's retry budget. A response
+/// that was rejected still shows that the upstream is reachable, so it is not held against it.
+/// Cancelled queries say nothing about the upstream.
+fn record_result(client: &DNSAsyncClient, address: &SocketAddr, result: &Result<Message, QueryError>) {
+    match result {
+        Ok(_) => client.health.record_upstream_success(),
+        Err(QueryError::Cancelled) => (),
+        Err(_) => client.health.record_upstream_failure(),
+    }
+    match result {
+        Ok(_) | Err(QueryError::InvalidResponse(_)) => if client.socket_manager.record_query_success(address) {
+            client.events.publish(ClientEvent::UpstreamRecovered { address: address.ip() });
+        },
+        Err(QueryError::UnsupportedTransport(_) | QueryError::Cancelled) => (),
+        Err(_) => if let Some(retry_in) = client.socket_manager.record_query_failure(address) {
+            client.events.publish(ClientEvent::UpstreamDown { address: address.ip(), retry_in });
+        },
+    }
+}
+
+async fn send_query(client: &DNSAsyncClient, message_question: Message, name_server_address: &IpAddr, deadline: Option<tokio::time::Instant>, cancellation: Option<&AwakeToken>, trace: Option<(&QueryTrace, &mut Vec<TransportAttempt>)>) -> Result<Message, QueryError> {
+    #[cfg(any(feature = "https", feature = "quic"))]
+    if let Some(upstream) = encrypted_upstream(client, name_server_address) {
+        match upstream.protocol {
+            #[cfg(feature = "https")]
+            QueryOpt::Https => return send_doh_query(client, message_question, upstream, deadline, cancellation, trace).await,
+            #[cfg(feature = "quic")]
+            QueryOpt::Quic => return send_doq_query(client, message_question, upstream, deadline, cancellation, trace).await,
+            _ => (),
+        }
+    }
 
-pub async fn query_network<CCache>(client: &DNSAsyncClient, cache: Arc<CCache>, question: &Question, name_server_address: &IpAddr) -> Result<Message, QueryError> where CCache: AsyncCache + Sync {
     let upstream_dns_address = SocketAddr::new(
         *name_server_address,
-        UPSTREAM_PORT,
+        client.config.upstream_port,
     );
-    let mut message_question = Message::from(question);
+    send_udp_tcp_query(client, message_question, upstream_dns_address, deadline, cancellation, trace).await
+}
+
+/// Sends the query over UDP, retrying over TCP if the response is truncated and without EDNS if
+/// the server does not implement it.
+async fn send_udp_tcp_query(client: &DNSAsyncClient, mut message_question: Message, upstream_dns_address: SocketAddr, deadline: Option<tokio::time::Instant>, cancellation: Option<&AwakeToken>, mut trace: Option<(&QueryTrace, &mut Vec<TransportAttempt>)>) -> Result<Message, QueryError> {
+    let name_server_address = &upstream_dns_address.ip();
+    let question = message_question.question().first().cloned();
     trace!(question:?; "Querying network '{upstream_dns_address}' (UDP/TCP) with query '{message_question:?}'");
 
     let socket = client.socket_manager.get(&upstream_dns_address).await;
-    let message = MixedSocket::query(&socket, &mut message_question, QueryOpt::UdpTcp).await?;
+    if message_question.edns().is_some() && !client.infrastructure.supports_edns(name_server_address).await {
+        message_question.remove_edns();
+    }
+    let mut message = exchange(client, &socket, &upstream_dns_address, &mut message_question, deadline, cancellation, &mut trace).await?;
+
+    // A name server that does not implement EDNS answers queries that have an OPT record with
+    // FORMERR (and no OPT record of its own). The query is retried without one.
+    // https://datatracker.ietf.org/doc/html/rfc6891#section-7
+    if (message.rcode == RCode::FormErr) && message_question.edns().is_some() && message.edns().is_none() {
+        trace!(question:?; "Querying network '{upstream_dns_address}', got FORMERR to a query with EDNS. Retrying without EDNS");
+        client.infrastructure.record_no_edns(*name_server_address).await;
+        client.events.publish(ClientEvent::TransportDowngraded { address: *name_server_address, downgrade: Downgrade::NoEdns });
+        message_question.remove_edns();
+        message = exchange(client, &socket, &upstream_dns_address, &mut message_question, deadline, cancellation, &mut trace).await?;
+    }
+    if let Some(reason) = LameReason::from_response(&message) {
+        if client.infrastructure.record_lame(*name_server_address).await {
+            client.events.publish(ClientEvent::UpstreamLame { address: *name_server_address, reason });
+        }
+    }
+    record_nsid(client, name_server_address, &message).await;
+    validate(client, &message_question, message)
+}
+
+/// Sends the query over UDP, retrying over TCP if the response is truncated.
+async fn exchange(client: &DNSAsyncClient, socket: &Arc<MixedSocket>, upstream_dns_address: &SocketAddr, message_question: &mut Message, deadline: Option<tokio::time::Instant>, cancellation: Option<&AwakeToken>, trace: &mut Option<(&QueryTrace, &mut Vec<TransportAttempt>)>) -> Result<Message, QueryError> {
+    let question = message_question.question().first().cloned();
+    let message = signed_attempt(client, socket, &upstream_dns_address.ip(), message_question, QueryOpt::UdpTcp, deadline, cancellation, trace.as_mut()).await?;
 
     // If the truncation flag is set, we need to try again with TCP
     if !message.truncation_flag() {
         trace!(question:?; "Querying network '{upstream_dns_address}', got response '{message:?}'");
-        cache.insert_message(&message).await;
         return Ok(message);
     }
     trace!(question:?; "Querying network '{upstream_dns_address}', got truncation flag in response '{message:?}'");
 
-    let message = MixedSocket::query(&socket, &mut message_question, QueryOpt::Tcp).await?;
+    let message = signed_attempt(client, socket, &upstream_dns_address.ip(), message_question, QueryOpt::Tcp, deadline, cancellation, trace.as_mut()).await?;
     trace!(question:?; "Querying network '{upstream_dns_address}' (TCP Only), got response '{message:?}'");
-    cache.insert_message(&message).await;
-    return Ok(message);
+    Ok(message)
+}
+
+/// If the client has a TSIG key for the name server, the query is signed and the response must be
+/// signed with the same key. The response's TSIG record is removed once it has been verified.
+/// Truncated responses are not verified since the query is sent again over TCP.
+async fn signed_attempt(client: &DNSAsyncClient, socket: &Arc<MixedSocket>, name_server_address: &IpAddr, query: &mut Message, options: QueryOpt, deadline: Option<tokio::time::Instant>, cancellation: Option<&AwakeToken>, trace: Option<&mut (&QueryTrace, &mut Vec<TransportAttempt>)>) -> Result<Message, QueryError> {
+    let Some(tsig) = client.tsig_keys.exchange(name_server_address) else {
+        return attempt(socket, query, options, deadline, cancellation, trace).await;
+    };
+    let mut tsig = tsig.and_then(|mut tsig| tsig.sign(query).map(|()| tsig)).map_err(|error| match error {
+        TsigError::Encode(error) => QueryError::UdpSend(UdpSendError::Serialization(error)),
+        error => QueryError::InvalidResponse(client.validator.reject_unauthenticated(query, &error)),
+    })?;
+    let (mut message, wire) = attempt_with_wire(socket, query, options, deadline, cancellation, trace).await?;
+    if message.truncation_flag() {
+        return Ok(message);
+    }
+    if let Err(error) = wire.ok_or(TsigError::Unsigned).and_then(|wire| tsig.verify(&wire)) {
+        return Err(QueryError::InvalidResponse(client.validator.reject_unauthenticated(&message, &error)));
+    }
+    message.additional.pop();
+    Ok(message)
+}
+
+async fn attempt(socket: &Arc<MixedSocket>, query: &mut Message, options: QueryOpt, deadline: Option<tokio::time::Instant>, cancellation: Option<&AwakeToken>, trace: Option<&mut (&QueryTrace, &mut Vec<TransportAttempt>)>) -> Result<Message, QueryError> {
+    attempt_with_wire(socket, query, options, deadline, cancellation, trace).await.map(|(message, _)| message)
+}
+
+/// Same as `attempt()`, but also returns the bytes the response was received as if the query was
+/// signed. They are `None` for queries that are not signed.
+async fn attempt_with_wire(socket: &Arc<MixedSocket>, query: &mut Message, options: QueryOpt, deadline: Option<tokio::time::Instant>, cancellation: Option<&AwakeToken>, trace: Option<&mut (&QueryTrace, &mut Vec<TransportAttempt>)>) -> Result<(Message, Option<Vec<u8>>), QueryError> {
+    if deadline.is_some_and(|deadline| deadline <= tokio::time::Instant::now()) {
+        return Err(QueryError::Timeout);
+    }
+    let started = Instant::now();
+    let query_task = socket.query_with_deadline(query, options, deadline).with_cancellation(cancellation);
+    pin!(query_task);
+    let result = until_deadline(deadline, query_task.as_mut(), || Err(QueryError::Timeout)).await;
+    let Some((trace, attempts)) = trace else {
+        return result.map(|message| (message, query_task.take_response_wire()));
+    };
+    if let Some(details) = query_task.details() {
+        attempts.push(TransportAttempt {
+            transport: match details.transport {
+                MixedTransport::Udp => TraceTransport::Udp,
+                MixedTransport::Tcp => TraceTransport::Tcp,
+                #[cfg(feature = "quic")]
+                MixedTransport::Quic => TraceTransport::Quic,
+                #[cfg(feature = "https")]
+                MixedTransport::Https => TraceTransport::Https,
+            },
+            sent: started.saturating_duration_since(trace.start()),
+            elapsed: started.elapsed(),
+            query_size: details.query_size,
+            response_size: result.as_ref().ok().map(|response| response.estimated_wire_size(true)),
+            udp_payload_size: details.udp_payload_size,
+            truncated: result.as_ref().is_ok_and(|response| response.truncation_flag()),
+            retransmissions: details.retransmissions,
+            retransmission_timeout: details.retransmission_timeout,
+            timeout: details.timeout,
+        });
+    }
+    result.map(|message| (message, query_task.take_response_wire()))
+}
+
+/// The address of the socket that queries to the name server are sent on. This is the DoQ or DoH
+/// upstream's address if queries to the name server go over QUIC or HTTPS. Otherwise, it is the
+/// name server's address with the upstream port.
+pub(crate) fn name_server_socket_address(client: &DNSAsyncClient, name_server_address: &IpAddr) -> SocketAddr {
+    #[cfg(any(feature = "https", feature = "quic"))]
+    if let Some(upstream) = encrypted_upstream(client, name_server_address) {
+        return upstream.address;
+    }
+    SocketAddr::new(*name_server_address, client.config.upstream_port)
+}
+
+/// The DNS over HTTPS or DNS over QUIC upstream configured for this address, if there is one.
+/// Queries to the address are sent to it instead of being sent over UDP and TCP.
+#[cfg(any(feature = "https", feature = "quic"))]
+fn encrypted_upstream<'a>(client: &'a DNSAsyncClient, name_server_address: &IpAddr) -> Option<&'a EncryptedUpstream> {
+    client.config.encrypted_upstreams.iter()
+        .filter(|upstream| upstream.ip() == *name_server_address)
+        .filter(|upstream| match upstream.protocol {
+            #[cfg(feature = "https")]
+            QueryOpt::Https => true,
+            #[cfg(feature = "quic")]
+            QueryOpt::Quic => true,
+            _ => false,
+        })
+        .min_by_key(|upstream| upstream.priority)
+}
+
+/// The name that the upstream's certificate is verified against. Certificates are issued for the
+/// name without the trailing dot.
+#[cfg(any(feature = "https", feature = "quic"))]
+#[inline]
+fn certificate_name(upstream: &EncryptedUpstream) -> String {
+    let server_name = upstream.server_name.to_string();
+    server_name.trim_end_matches('.').to_string()
+}
+
+/// Sends the query to the upstream over DNS over TLS, reusing the connection from earlier queries
+/// if it is still open. DoT responses are never truncated.
+#[cfg(feature = "tls")]
+async fn send_dot_query(client: &DNSAsyncClient, mut message_question: Message, upstream: &EncryptedUpstream, deadline: Option<tokio::time::Instant>, trace: Option<(&QueryTrace, &mut Vec<TransportAttempt>)>) -> Result<Message, QueryError> {
+    let question = message_question.question().first().cloned();
+    trace!(question:?; "Querying network '{}' (TLS) with query '{message_question:?}'", upstream.address);
+
+    if deadline.is_some_and(|deadline| deadline <= tokio::time::Instant::now()) {
+        return Err(QueryError::Timeout);
+    }
+    let Some(dot_client) = client.dot_clients.get(&upstream.address) else {
+        return Err(QueryError::UnsupportedTransport(upstream.protocol));
+    };
+
+    let started = Instant::now();
+    let result = until_deadline(deadline, dot_client.clone().query(&mut message_question), || Err(QueryError::Timeout)).await;
+    if let Some((trace, attempts)) = trace {
+        attempts.push(TransportAttempt {
+            transport: TraceTransport::Tls,
+            sent: started.saturating_duration_since(trace.start()),
+            elapsed: started.elapsed(),
+            query_size: message_question.estimated_wire_size(true),
+            response_size: result.as_ref().ok().map(|response| response.estimated_wire_size(true)),
+            udp_payload_size: None,
+            truncated: false,
+            retransmissions: 0,
+            retransmission_timeout: None,
+            timeout: deadline.map_or(Duration::ZERO, |deadline| deadline.into_std().saturating_duration_since(started)),
+        });
+    }
+    let message = result?;
+    trace!(question:?; "Querying network '{}' (TLS), got response '{message:?}'", upstream.address);
+    record_nsid(client, &upstream.ip(), &message).await;
+    validate(client, &message_question, message)
+}
+
+/// Sends the query to the upstream over DNS over QUIC. The socket keeps its QUIC connection open
+/// for later queries and sends each query on a new stream. DoQ responses are never truncated.
+#[cfg(feature = "quic")]
+async fn send_doq_query(client: &DNSAsyncClient, mut message_question: Message, upstream: &EncryptedUpstream, deadline: Option<tokio::time::Instant>, cancellation: Option<&AwakeToken>, mut trace: Option<(&QueryTrace, &mut Vec<TransportAttempt>)>) -> Result<Message, QueryError> {
+    let question = message_question.question().first().cloned();
+    trace!(question:?; "Querying network '{}' (QUIC) with query '{message_question:?}'", upstream.address);
+
+    let socket = client.socket_manager.get_quic(&upstream.address, &certificate_name(upstream)).await;
+    let message = attempt(&socket, &mut message_question, QueryOpt::Quic, deadline, cancellation, trace.as_mut()).await?;
+    trace!(question:?; "Querying network '{}' (QUIC), got response '{message:?}'", upstream.address);
+    record_nsid(client, &upstream.ip(), &message).await;
+    validate(client, &message_question, message)
+}
+
+/// Sends the query to the upstream as an HTTP POST request. The socket keeps its connection open
+/// for later queries and sends each query on a new stream. DoH responses are never truncated.
+#[cfg(feature = "https")]
+async fn send_doh_query(client: &DNSAsyncClient, mut message_question: Message, upstream: &EncryptedUpstream, deadline: Option<tokio::time::Instant>, cancellation: Option<&AwakeToken>, mut trace: Option<(&QueryTrace, &mut Vec<TransportAttempt>)>) -> Result<Message, QueryError> {
+    let question = message_question.question().first().cloned();
+    trace!(question:?; "Querying network '{}' (HTTPS) with query '{message_question:?}'", upstream.address);
+
+    let path = post_path(upstream.doh_path.as_deref().unwrap_or(DEFAULT_DOH_PATH));
+    let socket = client.socket_manager.get_https(&upstream.address, &certificate_name(upstream), path).await;
+    let message = attempt(&socket, &mut message_question, QueryOpt::Https, deadline, cancellation, trace.as_mut()).await?;
+    trace!(question:?; "Querying network '{}' (HTTPS), got response '{message:?}'", upstream.address);
+    record_nsid(client, &upstream.ip(), &message).await;
+    validate(client, &message_question, message)
+}
+
+/// Waits for the query until the deadline. The socket stops retransmitting at the deadline, but an
+/// identical query from a caller with a later deadline can keep it running, so the wait is cut
+/// short here as well.
+#[inline]
+async fn until_deadline<T>(deadline: Option<tokio::time::Instant>, query: impl Future<Output = T>, timed_out: impl FnOnce() -> T) -> T {
+    match deadline {
+        Some(deadline) => tokio::time::timeout_at(deadline, query).await.unwrap_or_else(|_| timed_out()),
+        None => query.await,
+    }
+}
+
+/// Asks the name server to identify itself (or its version) using a CH TXT query. The text of each
+/// TXT record in the answer is returned. Nothing is cached.
+pub(crate) async fn query_chaos(client: &DNSAsyncClient, query: ChaosQuery, name_server_address: &IpAddr) -> Result<Vec<String>, QueryError> {
+    let response = query_network_uncached(client, &query.question(), name_server_address).await?;
+    Ok(chaos_txt(&response))
+}
+
+/// Sends the message to the server over the transport and returns its response as it was
+/// received. The encrypted transports reuse the connection of the upstream configured for the
+/// address with that transport, since the server's certificate is verified against the upstream's
+/// server name.
+#[cfg_attr(not(any(feature = "tls", feature = "quic", feature = "https")), allow(unused_mut))]
+pub(crate) async fn send_message(client: &DNSAsyncClient, address: &SocketAddr, transport: QueryOpt, mut message: Message) -> Result<Message, QueryError> {
+    match transport {
+        QueryOpt::UdpTcp | QueryOpt::Tcp => client.socket_manager.send_message(address, transport, message).await,
+        #[cfg(feature = "tls")]
+        QueryOpt::Tls | QueryOpt::TlsInsecure => match client.dot_clients.get(address) {
+            Some(dot_client) => dot_client.clone().query(&mut message).await,
+            None => Err(QueryError::UnsupportedTransport(transport)),
+        },
+        #[cfg(feature = "quic")]
+        QueryOpt::Quic => match configured_upstream(client, address, transport) {
+            Some(upstream) => {
+                let socket = client.socket_manager.get_quic(address, &certificate_name(upstream)).await;
+                socket.query(&mut message, transport).await
+            },
+            None => Err(QueryError::UnsupportedTransport(transport)),
+        },
+        #[cfg(feature = "https")]
+        QueryOpt::Https => match configured_upstream(client, address, transport) {
+            Some(upstream) => {
+                let path = post_path(upstream.doh_path.as_deref().unwrap_or(DEFAULT_DOH_PATH));
+                let socket = client.socket_manager.get_https(address, &certificate_name(upstream), path).await;
+                socket.query(&mut message, transport).await
+            },
+            None => Err(QueryError::UnsupportedTransport(transport)),
+        },
+        #[allow(unreachable_patterns)]
+        transport => Err(QueryError::UnsupportedTransport(transport)),
+    }
+}
+
+/// The encrypted upstream, either a forwarder or one used for a name server, that is configured
+/// for the address with the transport.
+#[cfg(any(feature = "https", feature = "quic"))]
+fn configured_upstream<'a>(client: &'a DNSAsyncClient, address: &SocketAddr, transport: QueryOpt) -> Option<&'a EncryptedUpstream> {
+    let forwarders = client.config.forwarders.iter().filter_map(|forwarder| match forwarder {
+        ForwardUpstream::Encrypted(upstream) => Some(upstream),
+        ForwardUpstream::Plain(_) => None,
+    });
+    forwarders.chain(&client.config.encrypted_upstreams)
+        .find(|upstream| (upstream.address == *address) && (upstream.protocol == transport))
+}
+
+#[inline]
+async fn record_nsid(client: &DNSAsyncClient, name_server_address: &IpAddr, response: &Message) {
+    if !client.config.request_nsid {
+        return;
+    }
+    if let Some(nsid) = response_nsid(response) {
+        client.infrastructure.record_nsid(*name_server_address, nsid).await;
+    }
+}
+
+#[inline]
+fn validate(client: &DNSAsyncClient, query: &Message, response: Message) -> Result<Message, QueryError> {
+    client.validator.check_limits(&response)?;
+    if !client.config.validate_responses {
+        return Ok(response);
+    }
+    Ok(client.validator.validate(query, response)?)
+}
+
+#[cfg(test)]
+mod network_query_tests {
+    use std::{net::{Ipv4Addr, SocketAddr}, sync::Arc};
+
+    use dns_cache::asynchronous::async_main_cache::AsyncMainTreeCache;
+    use dns_lib::{query::{message::Message, qr::QR, question::Question}, resource_record::{rclass::RClass, rtype::RType}, serde::wire::{from_wire::FromWire, read_wire::ReadWire, to_wire::ToWire, write_wire::WriteWire}, tsig::{MacCalculator, TsigAlgorithm, TsigExchange, TsigKey}, types::c_domain_name::{CDomainName, CompressionMap}};
+    use network::{async_query::QueryOpt, errors::QueryError};
+    use tokio::net::UdpSocket;
+
+    use crate::{config::ClientConfig, query::network_query::query_network_uncached, DNSAsyncClient, TsigKeys};
+
+    /// Not a real MAC, but it depends on every octet of the key and the data.
+    struct XorCalculator;
+
+    impl MacCalculator for XorCalculator {
+        fn supports(&self, _algorithm: TsigAlgorithm) -> bool { true }
+
+        fn mac(&self, algorithm: TsigAlgorithm, secret: &[u8], data: &[u8]) -> Option<Vec<u8>> {
+            let mut mac = vec![0_u8; algorithm.output_length()];
+            for (index, octet) in secret.iter().chain(data).enumerate() {
+                let position = index % mac.len();
+                mac[position] = mac[position].rotate_left(3) ^ octet ^ (index as u8);
+            }
+            Some(mac)
+        }
+    }
+
+    /// A server that answers every query with an empty, truncated response.
+    async fn truncating_server() -> SocketAddr {
+        let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let address = socket.local_addr().unwrap();
+        tokio::spawn(async move {
+            let mut buffer = vec![0_u8; u16::MAX as usize];
+            loop {
+                let (length, peer) = socket.recv_from(&mut buffer).await.unwrap();
+                let mut response = Message::from_wire_format(&mut ReadWire::from_bytes(&buffer[..length])).unwrap();
+                response.qr = QR::Response;
+                response.truncation = true;
+                let mut out = vec![0_u8; u16::MAX as usize];
+                let mut wire = WriteWire::from_bytes(&mut out);
+                response.to_wire_format(&mut wire, &mut Some(CompressionMap::new())).unwrap();
+                let length = wire.current_len();
+                socket.send_to(&out[..length], peer).await.unwrap();
+            }
+        });
+        address
+    }
+
+    /// A server that verifies each signed query and answers it with two A records. Their owner
+    /// names are compressed to point at the question, which this library does not do, and the
+    /// response is signed over the bytes as they are sent.
+    async fn signing_server(key: TsigKey) -> SocketAddr {
+        let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let address = socket.local_addr().unwrap();
+        tokio::spawn(async move {
+            let mut buffer = vec![0_u8; u16::MAX as usize];
+            loop {
+                let (length, peer) = socket.recv_from(&mut buffer).await.unwrap();
+                let mut exchange = TsigExchange::new(&key, &XorCalculator).unwrap();
+                exchange.verify(&buffer[..length]).unwrap();
+                let mut response = Message::from_wire_format(&mut ReadWire::from_bytes(&buffer[..length])).unwrap();
+                response.qr = QR::Response;
+                response.additional.clear();
+                let mut wire = response.to_wire_vec(&mut Some(CompressionMap::new())).unwrap();
+                wire[7] = 2;
+                for address in [Ipv4Addr::new(192, 0, 2, 1), Ipv4Addr::new(192, 0, 2, 2)] {
+                    wire.extend_from_slice(&[0xC0, 0x0C, 0, 1, 0, 1, 0, 0, 1, 44, 0, 4]);
+                    wire.extend_from_slice(&address.octets());
+                }
+                exchange.sign_wire(&mut wire).unwrap();
+                socket.send_to(&wire, peer).await.unwrap();
+            }
+        });
+        address
+    }
+
+    #[tokio::test]
+    async fn signed_responses_are_verified_as_they_were_received() {
+        let key = TsigKey::new(CDomainName::from_utf8("key.example.").unwrap(), TsigAlgorithm::HmacSha256, b"secret".to_vec());
+        let address = signing_server(key.clone()).await;
+        let config = ClientConfig { upstream_port: address.port(), ..ClientConfig::default() };
+        let mut client = DNSAsyncClient::with_config(Arc::new(AsyncMainTreeCache::new()), config).await;
+        let mut tsig_keys = TsigKeys::new(Arc::new(XorCalculator));
+        tsig_keys.insert(address.ip(), key).unwrap();
+        client.set_tsig_keys(tsig_keys);
+
+        // Writing the response out again would not compress the answers, so its MAC would only
+        // check out over the bytes that were received.
+        let question = Question::new(CDomainName::from_utf8("www.example.com.").unwrap(), RType::A, RClass::Internet);
+        let response = query_network_uncached(&client, &question, &address.ip()).await.unwrap();
+        assert_eq!(response.answer.len(), 2);
+        // The TSIG record is removed once it has been verified.
+        assert!(response.additional.is_empty());
+
+        client.close().await;
+    }
+
+    #[tokio::test]
+    async fn messages_are_exchanged_as_they_are() {
+        let address = truncating_server().await;
+        let client = Arc::new(DNSAsyncClient::new(Arc::new(AsyncMainTreeCache::new())).await);
+
+        // The query is sent without the RD bit or an OPT record, just as it was given.
+        let query = Message::from(Question::new(CDomainName::from_utf8("www.example.com.").unwrap(), RType::A, RClass::Internet));
+        let response = client.send_message(&address, QueryOpt::UdpTcp, query.clone()).await.unwrap();
+        assert!(!response.recursion_desired);
+        assert!(response.edns().is_none());
+        // The truncated response is not retried over TCP, where nothing is listening.
+        assert!(response.truncation);
+        assert_eq!(response.question, query.question);
+        assert!(client.socket_manager().try_get(&address).await.is_some());
+
+        // There is no upstream to verify the certificate against.
+        #[cfg(feature = "tls")]
+        assert!(matches!(client.send_message(&address, QueryOpt::Tls, query).await, Err(QueryError::UnsupportedTransport(QueryOpt::Tls))));
+
+        client.close().await;
+    }
 }
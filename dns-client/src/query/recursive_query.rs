@@ -1,35 +1,43 @@
 use std::sync::Arc;
 
 use async_recursion::async_recursion;
-use dns_lib::{interface::{cache::{cache::AsyncCache, CacheQuery, CacheResponse}, client::Context}, query::question::Question, resource_record::{resource_record::{RecordData, ResourceRecord}, rtype::RType, types::ns::NS}, types::c_domain_name::{CDomainName, CmpDomainName}};
+use dns_lib::{interface::{cache::{cache::{AsyncCache, SharedAsyncCache}, CacheQuery, CacheResponse}, client::Context}, query::question::Question, resource_record::{resource_record::{RecordData, ResourceRecord}, rtype::RType, types::ns::NS}, types::c_domain_name::{CDomainName, CmpDomainName}};
 use log::{debug, trace};
 use rand::{thread_rng, seq::SliceRandom};
 
-use crate::{qname_minimizer::QNameMinimizer, query::round_robin_query::query_name_servers, result::{QError, QOk, QResult}, DNSAsyncClient};
+use crate::{prefetch::record_cache_hit, qname_minimizer::QNameMinimizer, query::round_robin_query::query_name_servers, result::{QError, QOk, QResult}, zone_stats::ZoneOutcome, DNSAsyncClient};
 
 
 #[async_recursion]
-pub(crate) async fn recursive_query<CCache>(client: Arc<DNSAsyncClient>, joined_cache: Arc<CCache>, context: Context) -> QResult where CCache: AsyncCache + Send + Sync + 'static {
+pub(crate) async fn recursive_query(client: Arc<DNSAsyncClient>, joined_cache: SharedAsyncCache, context: Context) -> QResult {
     debug!(context:?; "Start recursive search");
+    if let Some(answer) = client.local_data.lookup(context.query()) {
+        trace!(context:?; "Recursive search local data response: '{answer:?}'");
+        return QResult::Ok(QOk { answer, name_servers: Vec::new(), additional: Vec::new() });
+    }
     let cache_response = joined_cache.get(&CacheQuery { authoritative: false, question: context.query() }).await;
     // Initial Cache Check: Check to see if the records we're looking for are already cached.
     trace!(context:?; "Recursive search initial cache response: '{cache_response:?}'");
     match cache_response {
         CacheResponse::Records(records) if (records.len() == 0) => (),
-        CacheResponse::Records(records) => return QResult::Ok(QOk {
-            answer: records.into_iter().map(|record| record.record).collect(),
-            name_servers: Vec::new(),
-            additional: Vec::new(),
-        }),
+        CacheResponse::Records(records) => {
+            record_cache_hit(&client, &context, &records);
+            return QResult::Ok(QOk {
+                answer: records.into_iter().map(|record| record.record).collect(),
+                name_servers: Vec::new(),
+                additional: Vec::new(),
+            });
+        },
         CacheResponse::Err(rcode) => return QError::CacheFailure(rcode).into(),
     };
 
     // Discovery Stage: See if we have name servers that handle one of the parent domains of the
     // qname.
-    let (search_names_max_index, mut name_servers) = match get_closest_name_server(&client, &joined_cache, context.query()).await {
+    let (search_names_max_index, mut zone, mut name_servers) = match get_closest_name_server(&client, &joined_cache, context.query()).await {
         NSResponse::Error(error) => return error.into(),
         NSResponse::Records(search_names_max_index, name_servers) => (
             search_names_max_index,
+            name_servers.first().map_or_else(CDomainName::new_root, |record| record.get_name().clone()),
             name_servers.into_iter().map(|record| record.into_rdata().into_name_server_domain_name()).collect::<Vec<_>>()
         ),
     };
@@ -61,7 +69,7 @@ pub(crate) async fn recursive_query<CCache>(client: Arc<DNSAsyncClient>, joined_
         };
         trace!(context:?; "Recursive search querying name servers '{name_servers:?}' with search context '{search_context:?}'");
 
-        match query_name_servers(&client, &joined_cache, search_context, &name_servers).await {
+        match query_zone_name_servers(&client, &joined_cache, search_context, &zone, &name_servers).await {
             QResult::Err(error) => {
                 trace!(context:?; "Recursive search querying name servers '{name_servers:?}' for '{}' with search context response: error {error}", context.query());
                 return error.into();
@@ -79,7 +87,8 @@ pub(crate) async fn recursive_query<CCache>(client: Arc<DNSAsyncClient>, joined_
                     }
                 }
 
-                if !found_name_servers.is_empty() {
+                if let Some(found_zone) = found_name_servers.first() {
+                    zone = found_zone.get_name().clone();
                     name_servers.clear();
                     name_servers.extend(found_name_servers.into_iter().map(|record| record.into_rdata().into_name_server_domain_name()));
                 }
@@ -116,7 +125,7 @@ pub(crate) async fn recursive_query<CCache>(client: Arc<DNSAsyncClient>, joined_
 
     // Query name servers for answers.
     trace!(context:?; "Recursive search: querying name servers '{name_servers:?}' with full context");
-    match query_name_servers(&client, &joined_cache, context.clone(), &name_servers).await {
+    match query_zone_name_servers(&client, &joined_cache, context.clone(), &zone, &name_servers).await {
         QResult::Err(error) => {
             trace!(context:?; "Recursive search name server response: error '{error}'");
             return error.into();
@@ -150,13 +159,23 @@ pub(crate) async fn recursive_query<CCache>(client: Arc<DNSAsyncClient>, joined_
         });
 }
 
+/// Queries the name servers for the zone and records the outcome against the zone. Cancelled
+/// queries say nothing about the zone, so they are not recorded.
+async fn query_zone_name_servers(client: &Arc<DNSAsyncClient>, joined_cache: &SharedAsyncCache, context: Arc<Context>, zone: &CDomainName, name_servers: &[CDomainName]) -> QResult {
+    let result = query_name_servers(client, joined_cache, context, name_servers).await;
+    if !matches!(result, QResult::Err(QError::Cancelled)) {
+        client.zone_stats.record(zone, ZoneOutcome::from_result(&result)).await;
+    }
+    result
+}
+
 #[derive(Clone, PartialEq, Hash, Debug)]
 enum NSResponse {
     Records(usize, Vec<ResourceRecord<NS>>),
     Error(QError),
 }
 
-async fn get_closest_name_server<CCache>(_client: &Arc<DNSAsyncClient>, joined_cache: &Arc<CCache>, question: &Question) -> NSResponse where CCache: AsyncCache {
+async fn get_closest_name_server(_client: &Arc<DNSAsyncClient>, joined_cache: &SharedAsyncCache, question: &Question) -> NSResponse {
     for (index, search_name) in question.qname().search_domains().enumerate() {
         match joined_cache.get(&CacheQuery { authoritative: false, question: &question.with_new_qname_qtype(search_name.clone(), RType::NS) }).await {
             CacheResponse::Err(rcode) => return NSResponse::Error(QError::CacheFailure(rcode)),
@@ -172,7 +191,7 @@ async fn get_closest_name_server<CCache>(_client: &Arc<DNSAsyncClient>, joined_c
     return NSResponse::Error(QError::NoClosestNameServerFound(question.qname().clone()));
 }
 
-async fn handle_cname<CCache>(client: Arc<DNSAsyncClient>, joined_cache: Arc<CCache>, context: Arc<Context>, mut answer: Vec<ResourceRecord>, name_servers: Vec<ResourceRecord<NS>>, mut additional: Vec<ResourceRecord>) -> QResult where CCache: AsyncCache + Send + Sync + 'static {
+async fn handle_cname(client: Arc<DNSAsyncClient>, joined_cache: SharedAsyncCache, context: Arc<Context>, mut answer: Vec<ResourceRecord>, name_servers: Vec<ResourceRecord<NS>>, mut additional: Vec<ResourceRecord>) -> QResult {
     debug!(context:?; "Recursive search redirected by cname");
     for record in &answer {
         if let RecordData::CNAME(cname_rdata) = record.get_rdata() {
@@ -203,7 +222,7 @@ async fn handle_cname<CCache>(client: Arc<DNSAsyncClient>, joined_cache: Arc<CCa
     return QError::MissingRecord(RType::CNAME).into();
 }
 
-async fn handle_dname<CCache>(client: Arc<DNSAsyncClient>, joined_cache: Arc<CCache>, context: Arc<Context>, mut answer: Vec<ResourceRecord>, name_servers: Vec<ResourceRecord<NS>>, mut additional: Vec<ResourceRecord>) -> QResult where CCache: AsyncCache + Send + Sync + 'static {
+async fn handle_dname(client: Arc<DNSAsyncClient>, joined_cache: SharedAsyncCache, context: Arc<Context>, mut answer: Vec<ResourceRecord>, name_servers: Vec<ResourceRecord<NS>>, mut additional: Vec<ResourceRecord>) -> QResult {
     debug!(context:?; "Recursive search redirected by dname");
     for record in &answer {
         if let RecordData::DNAME(dname_rdata) = record.get_rdata() {
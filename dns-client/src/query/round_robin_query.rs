@@ -1,15 +1,15 @@
 use std::{borrow::BorrowMut, cmp::Reverse, collections::HashMap, future::Future, net::{IpAddr, SocketAddr}, pin::Pin, sync::Arc, task::Poll, time::Duration};
 
-use async_lib::once_watch::{self, OnceWatchSend, OnceWatchSubscribe};
-use dns_lib::{interface::{cache::{cache::AsyncCache, CacheQuery, CacheResponse}, client::Context}, query::{message::Message, qr::QR, question::Question}, resource_record::{rcode::RCode, resource_record::{RecordData, ResourceRecord}, rtype::RType}, types::c_domain_name::CDomainName};
+use async_lib::{awake_token::{AwakeToken, AwokenToken}, once_watch::{self, OnceWatchSend, OnceWatchSubscribe}, poll_budget::{PollBudget, PollLoopStats}};
+use dns_lib::{interface::{cache::{cache::{AsyncCache, SharedAsyncCache}, CacheQuery, CacheResponse}, client::Context}, query::{message::Message, qr::QR, question::QuestionKey}, resource_record::{rcode::RCode, resource_record::{RecordData, ResourceRecord}, rtype::RType}, types::c_domain_name::CDomainName};
 use futures::{future::BoxFuture, FutureExt};
 use log::{debug, info, trace};
-use network::{errors::QueryError, mixed_tcp_udp::MixedSocket};
+use network::{errors::QueryError, mixed_tcp_udp::MixedSocket, retry_budget::ServerStatus};
 use pin_project::{pin_project, pinned_drop};
 use rand::{seq::IteratorRandom, thread_rng};
 use tokio::sync::{RwLockReadGuard, RwLockWriteGuard};
 
-use crate::{query::{network_query::query_network, recursive_query::recursive_query}, result::{QError, QOk, QResult}, DNSAsyncClient};
+use crate::{happy_eyeballs::{FamilyAlternator, HappyEyeballsConfig}, hedging::{HedgingConfig, HedgingRecorder}, query::{network_query::{name_server_socket_address, query_network}, recursive_query::recursive_query}, result::{QError, QOk, QResult}, ActiveQueryKey, DNSAsyncClient};
 
 fn rr_to_ip(record: ResourceRecord) -> Option<IpAddr> {
     match record.into_rdata() {
@@ -19,7 +19,7 @@ fn rr_to_ip(record: ResourceRecord) -> Option<IpAddr> {
     }
 }
 
-async fn query_cache_for_ns_addresses<'a, 'b, 'c, CCache>(ns_domain: CDomainName, address_rtype: RType, context: Arc<Context>, client: Arc<DNSAsyncClient>, joined_cache: Arc<CCache>) -> NSQuery<'a, 'b, 'c, CCache> where CCache: AsyncCache + Send + Sync {
+async fn query_cache_for_ns_addresses<'a, 'b, 'c>(ns_domain: CDomainName, address_rtype: RType, context: Arc<Context>, client: Arc<DNSAsyncClient>, joined_cache: SharedAsyncCache) -> NSQuery<'a, 'b, 'c> {
     let ns_question = context.query().with_new_qname_qtype(ns_domain.clone(), address_rtype.clone());
 
     let ns_addresses;
@@ -48,6 +48,8 @@ async fn query_cache_for_ns_addresses<'a, 'b, 'c, CCache>(ns_domain: CDomainName
         ns_addresses,
         sockets: HashMap::new(),
         state: InnerNSQuery::Fresh(cache_response),
+        hedge: false,
+        cancellation: AwakeToken::new(),
     }
 }
 
@@ -57,18 +59,30 @@ enum NSQueryResult {
     Result(QResult<Message, QError>),
 }
 
-#[pin_project]
-struct NSQuery<'a, 'b, 'c, CCache> where CCache: AsyncCache + Send + Sync {
+#[pin_project(PinnedDrop)]
+struct NSQuery<'a, 'b, 'c> {
     ns_domain: CDomainName,
     ns_address_rtype: RType,
     context: Arc<Context>,
 
     client: Arc<DNSAsyncClient>,
-    joined_cache: Arc<CCache>,
+    joined_cache: SharedAsyncCache,
 
     ns_addresses: Vec<IpAddr>,
     sockets: HashMap<IpAddr, Arc<MixedSocket>>,
     state: InnerNSQuery<'a, 'b, 'c>,
+    /// Whether this query was started as a hedge against a slow name server.
+    hedge: bool,
+    /// Awoken when the query is dropped, so that a network query it loses a race with another
+    /// name server is cancelled instead of running until it times out.
+    cancellation: AwakeToken,
+}
+
+#[pinned_drop]
+impl<'a, 'b, 'c> PinnedDrop for NSQuery<'a, 'b, 'c> {
+    fn drop(self: Pin<&mut Self>) {
+        self.cancellation.awake();
+    }
 }
 
 enum InnerNSQuery<'a, 'b, 'c> {
@@ -87,16 +101,16 @@ enum NSQueryCacheResponse {
     Miss,
 }
 
-impl<'a, 'b, 'c, CCache> NSQuery<'a, 'b, 'c, CCache> where CCache: AsyncCache + Send + Sync {
+impl<'a, 'b, 'c> NSQuery<'a, 'b, 'c> {
     pub fn best_address_stats(&self) -> Option<(u32, u32)> {
         self.ns_addresses.iter().map(|address| self.sockets.get(address)
-                .map(|socket| (socket.average_dropped_udp_packets(), socket.average_udp_response_time()))
-                .filter(|(average_dropped_udp_packets, average_udp_response_time)| (average_dropped_udp_packets.is_finite() && average_udp_response_time.is_finite()))
-                // If more than 80% of UDP packets are being dropped, we'd rather explore new
+                .map(|socket| socket.selection_stats())
+                .filter(|(average_dropped_packets, average_response_time)| average_dropped_packets.is_finite() && average_response_time.is_finite())
+                // If more than 80% of packets are being dropped, we'd rather explore new
                 // addresses. Otherwise, this address would still be technically better than one
                 // which had not yet been explored.
-                .filter(|(average_dropped_udp_packets, _)| *average_dropped_udp_packets < 0.80)
-                .map(|(average_dropped_udp_packets, average_udp_response_time)| Reverse(((average_dropped_udp_packets * 100.0).ceil() as u32, average_udp_response_time.ceil() as u32))))
+                .filter(|(average_dropped_packets, _)| *average_dropped_packets < 0.80)
+                .map(|(average_dropped_packets, average_response_time)| Reverse(((average_dropped_packets * 100.0).ceil() as u32, average_response_time.ceil() as u32))))
             .max()
             .flatten()
             .map(|val| val.0)
@@ -108,36 +122,49 @@ fn take_random<T>(vec: &mut Vec<T>) -> Option<T> {
     Some(vec.swap_remove(i))
 }
 
-fn take_best_address<'a, 'b, 'c, CCache>(ns_addresses: &mut Vec<IpAddr>, sockets: &HashMap<IpAddr, Arc<MixedSocket>>) -> Option<IpAddr> where CCache: AsyncCache + Send + Sync {
+/// Takes the best address that the retry budget allows a query to. Addresses of name servers that
+/// are down are dropped, so that the query does not wait on them to time out.
+fn take_best_address(client: &DNSAsyncClient, ns_addresses: &mut Vec<IpAddr>, sockets: &HashMap<IpAddr, Arc<MixedSocket>>) -> Option<IpAddr> {
+    loop {
+        ns_addresses.retain(|address| !matches!(client.socket_manager.server_status(&name_server_socket_address(client, address)), ServerStatus::Down { .. }));
+        let address = take_best_available_address(ns_addresses, sockets)?;
+        // Another query may have taken the probe since the status was checked.
+        if client.socket_manager.try_acquire_query(&name_server_socket_address(client, &address)) {
+            return Some(address);
+        }
+    }
+}
+
+fn take_best_available_address(ns_addresses: &mut Vec<IpAddr>, sockets: &HashMap<IpAddr, Arc<MixedSocket>>) -> Option<IpAddr> {
     match ns_addresses.iter()
         .enumerate()
         .max_by_key(|(_, address)| sockets.get(address)
-            .map(|socket| (socket.average_dropped_udp_packets(), socket.average_udp_response_time()))
-            .filter(|(average_dropped_udp_packets, average_udp_response_time)| (average_dropped_udp_packets.is_finite() && average_udp_response_time.is_finite()))
-            // If more than 80% of UDP packets are being dropped, we'd rather explore new
+            .map(|socket| socket.selection_stats())
+            .filter(|(average_dropped_packets, average_response_time)| average_dropped_packets.is_finite() && average_response_time.is_finite())
+            // If more than 80% of packets are being dropped, we'd rather explore new
             // addresses. Otherwise, this address would still be technically better than one
             // which had not yet been explored.
-            .filter(|(average_dropped_udp_packets, _)| *average_dropped_udp_packets < 0.80)
-            .map(|(average_dropped_udp_packets, average_udp_response_time)| Reverse(((average_dropped_udp_packets * 100.0).ceil() as u32, average_udp_response_time.ceil() as u32))))
+            .filter(|(average_dropped_packets, _)| *average_dropped_packets < 0.80)
+            .map(|(average_dropped_packets, average_response_time)| Reverse(((average_dropped_packets * 100.0).ceil() as u32, average_response_time.ceil() as u32))))
     {
         Some((index, _)) => Some(ns_addresses.swap_remove(index)),
         None => take_random(ns_addresses),
     }
 }
 
-impl<'a, 'b, 'c, CCache> Future for NSQuery<'a, 'b, 'c, CCache> where CCache: AsyncCache + Send + Sync + 'static {
+impl<'a, 'b, 'c> Future for NSQuery<'a, 'b, 'c> {
     type Output = NSQueryResult;
 
     fn poll(mut self: Pin<&mut Self>, cx: &mut std::task::Context<'_>) -> std::task::Poll<Self::Output> {
-        async fn recursive_query_owned_args<CCache>(client: Arc<DNSAsyncClient>, joined_cache: Arc<CCache>, context: Context) -> QResult where CCache: AsyncCache + Send + Sync + 'static {
+        async fn recursive_query_owned_args(client: Arc<DNSAsyncClient>, joined_cache: SharedAsyncCache, context: Context) -> QResult {
             recursive_query(client, joined_cache, context).await
         }
 
-        async fn query_network_owned_args<CCache>(client: Arc<DNSAsyncClient>, joined_cache: Arc<CCache>, context: Arc<Context>, name_server_address: IpAddr) -> Result<Message, QueryError> where CCache: AsyncCache + Send + Sync {
-            query_network(&client, joined_cache, context.query(), &name_server_address).await
+        async fn query_network_owned_args(client: Arc<DNSAsyncClient>, joined_cache: SharedAsyncCache, context: Arc<Context>, name_server_address: IpAddr, cancellation: AwakeToken) -> Result<Message, QueryError> {
+            query_network(&client, joined_cache, &context, &name_server_address, Some(&cancellation)).await
         }
 
-        async fn query_for_sockets<CCache>(client: Arc<DNSAsyncClient>, sockets: Vec<SocketAddr>) -> Vec<Arc<MixedSocket>> where CCache: AsyncCache + Send {
+        async fn query_for_sockets(client: Arc<DNSAsyncClient>, sockets: Vec<SocketAddr>) -> Vec<Arc<MixedSocket>> {
             client.socket_manager.try_get_all(sockets.iter()).await
         }
 
@@ -146,13 +173,13 @@ impl<'a, 'b, 'c, CCache> Future for NSQuery<'a, 'b, 'c, CCache> where CCache: As
             match this.state {
                 InnerNSQuery::Fresh(NSQueryCacheResponse::Hit) => {
                     let sockets_addresses = this.ns_addresses.iter()
-                        .map(|address| SocketAddr::new(*address, 53))
+                        .map(|address| name_server_socket_address(this.client, address))
                         .collect::<Vec<_>>();
                     let client = this.client.clone();
                     let context = &self.context;
                     trace!(context:?; "NSQuery::Fresh(Hit) -> NSQuery::GettingSocketStats for {:#?}", self.ns_addresses);
 
-                    self.state = InnerNSQuery::GettingSocketStats(query_for_sockets::<CCache>(client, sockets_addresses).boxed());
+                    self.state = InnerNSQuery::GettingSocketStats(query_for_sockets(client, sockets_addresses).boxed());
 
                     // TODO
                     continue;
@@ -205,13 +232,13 @@ impl<'a, 'b, 'c, CCache> Future for NSQuery<'a, 'b, 'c, CCache> where CCache: As
                                 return Poll::Ready(NSQueryResult::OutOfAddresses);
                             } else {
                                 let sockets_addresses = this.ns_addresses.iter()
-                                    .map(|address| SocketAddr::new(*address, 53))
+                                    .map(|address| name_server_socket_address(this.client, address))
                                     .collect::<Vec<_>>();
                                 let client = this.client.clone();
                                 let context = &self.context;
                                 trace!(context:?; "NSQuery::QueryingNetworkNSAddresses -> NSQuery::GettingSocketStats");
 
-                                self.state = InnerNSQuery::GettingSocketStats(query_for_sockets::<CCache>(client, sockets_addresses).boxed());
+                                self.state = InnerNSQuery::GettingSocketStats(query_for_sockets(client, sockets_addresses).boxed());
 
                                 // TODO
                                 continue;
@@ -270,7 +297,7 @@ impl<'a, 'b, 'c, CCache> Future for NSQuery<'a, 'b, 'c, CCache> where CCache: As
                     }
                 },
                 InnerNSQuery::NetworkQueryStart => {
-                    match take_best_address::<CCache>(this.ns_addresses, &this.sockets) {
+                    match take_best_address(this.client, this.ns_addresses, this.sockets) {
                         Some(next_ns_address) => {
                             let context = this.context.as_ref();
                             trace!(context:?; "NSQuery::NetworkQueryStart -> NSQuery::QueryingNetwork: setting up query to next ns {next_ns_address}");
@@ -278,7 +305,8 @@ impl<'a, 'b, 'c, CCache> Future for NSQuery<'a, 'b, 'c, CCache> where CCache: As
                             let client = this.client.clone();
                             let cache = this.joined_cache.clone();
                             let context = this.context.clone();
-                            let query = query_network_owned_args(client, cache, context, next_ns_address).boxed();
+                            let cancellation = this.cancellation.clone();
+                            let query = query_network_owned_args(client, cache, context, next_ns_address, cancellation).boxed();
 
                             self.state = InnerNSQuery::QueryingNetwork(query);
 
@@ -331,24 +359,72 @@ impl<'a, 'b, 'c, CCache> Future for NSQuery<'a, 'b, 'c, CCache> where CCache: As
 }
 
 #[pin_project]
-struct NSSelectQuery<'a, 'b, 'c, CCache> where CCache: AsyncCache + Send + Sync {
+struct NSSelectQuery<'a, 'b, 'c> {
     // Note: the queries are read in reverse order (like a stack).
-    ns_queries: Vec<Pin<Box<NSQuery<'a, 'b, 'c, CCache>>>>,
-    running: Vec<Pin<Box<NSQuery<'a, 'b, 'c, CCache>>>>,
+    ns_queries: Vec<Pin<Box<NSQuery<'a, 'b, 'c>>>>,
+    running: Vec<Pin<Box<NSQuery<'a, 'b, 'c>>>>,
     max_concurrency: usize,
     add_query_timeout: Duration,
     #[pin]
     add_query_timer: Option<tokio::time::Sleep>,
+    hedging: Option<Hedging>,
+    /// Alternates the address family of the queries as they are started, if Happy Eyeballs is
+    /// enabled.
+    families: Option<FamilyAlternator>,
+}
+
+struct Hedging {
+    config: HedgingConfig,
+    recorder: Arc<HedgingRecorder>,
+    /// A hedge has been sent but neither query has answered yet.
+    pending: bool,
 }
 
-impl<'a, 'b, 'c, CCache> NSSelectQuery<'a, 'b, 'c, CCache> where CCache: AsyncCache + Send + Sync {
-    pub fn new(ns_queries: Vec<Pin<Box<NSQuery<'a, 'b, 'c, CCache>>>>, max_concurrency: usize, add_query_timeout: Duration) -> Self {
+impl<'a, 'b, 'c> NSSelectQuery<'a, 'b, 'c> {
+    pub fn new(ns_queries: Vec<Pin<Box<NSQuery<'a, 'b, 'c>>>>, max_concurrency: usize, add_query_timeout: Duration) -> Self {
         Self {
             ns_queries,
             running: Vec::new(),
             max_concurrency,
             add_query_timeout,
             add_query_timer: None,
+            hedging: None,
+            families: None,
+        }
+    }
+
+    /// Races the name servers' addresses, alternating between IPv6 and IPv4 addresses and
+    /// starting the next query every `attempt_delay` until one of them answers.
+    pub fn happy_eyeballs(ns_queries: Vec<Pin<Box<NSQuery<'a, 'b, 'c>>>>, config: HappyEyeballsConfig) -> Self {
+        Self {
+            ns_queries,
+            running: Vec::new(),
+            max_concurrency: config.max_concurrency.max(1),
+            add_query_timeout: config.attempt_delay,
+            add_query_timer: None,
+            hedging: None,
+            families: Some(FamilyAlternator::new(&config)),
+        }
+    }
+
+    /// Alternates the address family of the hedge as well, if Happy Eyeballs is enabled.
+    pub fn with_families(mut self, config: Option<HappyEyeballsConfig>) -> Self {
+        self.families = config.as_ref().map(FamilyAlternator::new);
+        self
+    }
+
+    /// Queries the best name server and, if it is slower than usual to answer, the second best
+    /// name server as well. The timeout is chosen on the first poll, once the best name server is
+    /// known.
+    pub fn hedged(ns_queries: Vec<Pin<Box<NSQuery<'a, 'b, 'c>>>>, config: HedgingConfig, recorder: Arc<HedgingRecorder>) -> Self {
+        Self {
+            ns_queries,
+            running: Vec::new(),
+            max_concurrency: 2,
+            add_query_timeout: config.default_delay,
+            add_query_timer: None,
+            hedging: Some(Hedging { config, recorder, pending: false }),
+            families: None,
         }
     }
 
@@ -359,7 +435,7 @@ impl<'a, 'b, 'c, CCache> NSSelectQuery<'a, 'b, 'c, CCache> where CCache: AsyncCa
     }
 }
 
-fn take_best_ns_query<'a, 'b, 'c, CCache>(ns_queries: &mut Vec<Pin<Box<NSQuery<'a, 'b, 'c, CCache>>>>) -> Option<Pin<Box<NSQuery<'a, 'b, 'c, CCache>>>> where CCache: AsyncCache + Send + Sync {
+fn take_best_ns_query<'a, 'b, 'c>(ns_queries: &mut Vec<Pin<Box<NSQuery<'a, 'b, 'c>>>>) -> Option<Pin<Box<NSQuery<'a, 'b, 'c>>>> {
     match ns_queries.iter()
         .enumerate()
         .max_by_key(|(_, ns_query)| ns_query.best_address_stats().map(|stats| Reverse(stats)))
@@ -369,15 +445,40 @@ fn take_best_ns_query<'a, 'b, 'c, CCache>(ns_queries: &mut Vec<Pin<Box<NSQuery<'
     }
 }
 
-impl<'a, 'b, 'c, CCache> Future for NSSelectQuery<'a, 'b, 'c, CCache> where CCache: AsyncCache + Send + Sync + 'static {
+/// Takes the best query for the address family that is due next. If there are no queries left
+/// for that family, the best query for the other family is taken instead.
+fn take_next_ns_query<'a, 'b, 'c>(ns_queries: &mut Vec<Pin<Box<NSQuery<'a, 'b, 'c>>>>, families: &mut Option<FamilyAlternator>) -> Option<Pin<Box<NSQuery<'a, 'b, 'c>>>> {
+    let Some(families) = families else {
+        return take_best_ns_query(ns_queries);
+    };
+    let family = families.next();
+    let ns_query = match ns_queries.iter()
+        .enumerate()
+        .filter(|(_, ns_query)| ns_query.ns_address_rtype == family)
+        .max_by_key(|(_, ns_query)| ns_query.best_address_stats().map(Reverse))
+    {
+        Some((index, _)) => ns_queries.swap_remove(index),
+        None => take_best_ns_query(ns_queries)?,
+    };
+    families.started(ns_query.ns_address_rtype);
+    Some(ns_query)
+}
+
+impl<'a, 'b, 'c> Future for NSSelectQuery<'a, 'b, 'c> {
     type Output = Option<NSQueryResult>;
 
     fn poll(mut self: Pin<&mut Self>, cx: &mut std::task::Context<'_>) -> Poll<Self::Output> {
         if self.is_first_poll() {
             let mut this = self.as_mut().project();
             // Initialize the `running` queue with its first query.
-            match take_best_ns_query(this.ns_queries) {
-                Some(ns_query) => this.running.push(ns_query),
+            match take_next_ns_query(this.ns_queries, this.families) {
+                Some(ns_query) => {
+                    // Hedge once the name server is slower than it usually is to answer.
+                    if let Some(hedging) = this.hedging.as_mut() {
+                        *this.add_query_timeout = hedging.config.delay(ns_query.best_address_stats().map(|(_, average_response_time)| average_response_time));
+                    }
+                    this.running.push(ns_query);
+                },
                 None => {
                     // Don't want to be erroneously woken up if we are done.
                     // Although on the first poll, it is probably already None.
@@ -408,8 +509,15 @@ impl<'a, 'b, 'c, CCache> Future for NSSelectQuery<'a, 'b, 'c, CCache> where CCac
         let mut this = self.as_mut().project();
         if let Some(mut timer) = this.add_query_timer.as_mut().as_pin_mut() {
             if let Poll::Ready(()) = timer.as_mut().poll(cx) {
-                match take_best_ns_query(this.ns_queries) {
-                    Some(ns_query) => {
+                match take_next_ns_query(this.ns_queries, this.families) {
+                    Some(mut ns_query) => {
+                        if let Some(hedging) = this.hedging.as_mut() {
+                            if this.running.len() == 1 {
+                                *ns_query.as_mut().project().hedge = true;
+                                hedging.pending = true;
+                                hedging.recorder.record_hedge();
+                            }
+                        }
                         this.running.push(ns_query);
                         // Keep setting the timer until the maximum number of allowed concurrent
                         // queries have been started for this group. Then, we will maintain that
@@ -440,7 +548,12 @@ impl<'a, 'b, 'c, CCache> Future for NSSelectQuery<'a, 'b, 'c, CCache> where CCac
 
         for (index, ns_query) in this.running.iter_mut().enumerate() {
             if let Poll::Ready(result) = ns_query.as_mut().poll(cx) {
-                match (take_best_ns_query(this.ns_queries), &result) {
+                // The first answer after a hedge was sent tells us whether the hedge was worth it.
+                if let (Some(hedging @ Hedging { pending: true, .. }), NSQueryResult::Result(QResult::Ok(_))) = (this.hedging.as_mut(), &result) {
+                    hedging.pending = false;
+                    hedging.recorder.record_answer(ns_query.hedge);
+                }
+                match (take_next_ns_query(this.ns_queries, this.families), &result) {
                     // We can re-use the spot in the `running` list for the new query since we don't
                     // care about the order of this list. They should all get polled eventually (as
                     // long as no result is found). Re-using the spot means the vector does not need
@@ -486,23 +599,26 @@ impl<'a, 'b, 'c, CCache> Future for NSSelectQuery<'a, 'b, 'c, CCache> where CCac
     }
 }
 
+pub(crate) static NS_ROUND_ROBIN_POLL_LOOP: PollLoopStats = PollLoopStats::new("NSRoundRobin");
+
 #[pin_project(PinnedDrop)]
-struct NSRoundRobin<'a, 'b, 'c, 'd, 'e, 'f, 'g, 'h, CCache>
+struct NSRoundRobin<'a, 'b, 'c, 'd, 'e, 'f, 'g, 'h>
 where
-    CCache: AsyncCache + Send + Sync + 'static,
     'f: 'e,
     'g: 'e,
     'h: 'e,
 {
     client: &'a Arc<DNSAsyncClient>,
-    joined_cache: &'b Arc<CCache>,
+    joined_cache: &'b SharedAsyncCache,
     context: &'c Arc<Context>,
-    inner: InnerNSRoundRobin<'d, 'e, 'f, 'g, 'h, CCache>,
+    /// Ready once the caller cancels the query.
+    #[pin]
+    cancelled: Option<AwokenToken>,
+    inner: InnerNSRoundRobin<'d, 'e, 'f, 'g, 'h>,
 }
 
-enum InnerNSRoundRobin<'a, 'b, 'c, 'd, 'e, CCache>
+enum InnerNSRoundRobin<'a, 'b, 'c, 'd, 'e>
 where
-    CCache: AsyncCache + Send + Sync + 'static,
     'c: 'b,
     'd: 'b,
 {
@@ -510,27 +626,43 @@ where
         name_servers: &'a [CDomainName],
     },
     GetCachedNSAddresses {
-        name_server_address_queries: Vec<BoxFuture<'b, NSQuery<'c, 'd, 'e, CCache>>>,
-        name_server_non_cached_queries: Vec<Pin<Box<NSQuery<'c, 'd, 'e, CCache>>>>,
-        name_server_cached_queries: Vec<Pin<Box<NSQuery<'c, 'd, 'e, CCache>>>>,
+        name_server_address_queries: Vec<BoxFuture<'b, NSQuery<'c, 'd, 'e>>>,
+        name_server_non_cached_queries: Vec<Pin<Box<NSQuery<'c, 'd, 'e>>>>,
+        name_server_cached_queries: Vec<Pin<Box<NSQuery<'c, 'd, 'e>>>>,
     },
     QueryNameServers {
-        ns_query_select: Pin<Box<NSSelectQuery<'c, 'd, 'e, CCache>>>,
+        ns_query_select: Pin<Box<NSSelectQuery<'c, 'd, 'e>>>,
     },
     Complete,
 }
 
-impl<'a, 'b, 'c, 'd, 'e, 'f, 'g, 'h, CCache> NSRoundRobin<'a, 'b, 'c, 'd, 'e, 'f, 'g, 'h, CCache> where CCache: AsyncCache + Send + Sync + 'static {
-    fn new(client: &'a Arc<DNSAsyncClient>, joined_cache: &'b Arc<CCache>, question: &'c Arc<Context>, name_servers: &'d [CDomainName]) -> Self {
-        Self { client, joined_cache, context: question, inner: InnerNSRoundRobin::Fresh { name_servers } }
+impl<'a, 'b, 'c, 'd, 'e, 'f, 'g, 'h> NSRoundRobin<'a, 'b, 'c, 'd, 'e, 'f, 'g, 'h> {
+    fn new(client: &'a Arc<DNSAsyncClient>, joined_cache: &'b SharedAsyncCache, question: &'c Arc<Context>, name_servers: &'d [CDomainName]) -> Self {
+        let cancelled = question.cancellation().map(|token| token.awoken());
+        Self { client, joined_cache, context: question, cancelled, inner: InnerNSRoundRobin::Fresh { name_servers } }
     }
 }
 
-impl<'a, 'b, 'c, 'd, 'e, 'f, 'g, 'h, CCache> Future for NSRoundRobin<'a, 'b, 'c, 'd, 'e, 'f, 'g, 'h, CCache> where CCache: AsyncCache + Send + Sync + 'static {
+impl<'a, 'b, 'c, 'd, 'e, 'f, 'g, 'h> Future for NSRoundRobin<'a, 'b, 'c, 'd, 'e, 'f, 'g, 'h> {
     type Output = QResult;
 
     fn poll(mut self: Pin<&mut Self>, cx: &mut std::task::Context<'_>) -> Poll<Self::Output> {
+        let mut this = self.as_mut().project();
+        if let Some(Poll::Ready(())) = this.cancelled.as_mut().as_pin_mut().map(|cancelled| cancelled.poll(cx)) {
+            let context = this.context.as_ref();
+            debug!(context:?; "NSRoundRobin -> NSRoundRobin::Complete: Query for '{}' was cancelled", this.context.query());
+
+            *this.inner = InnerNSRoundRobin::Complete;
+
+            return Poll::Ready(QResult::Err(QError::Cancelled));
+        }
+
+        let mut budget = PollBudget::new(&NS_ROUND_ROBIN_POLL_LOOP);
         loop {
+            if budget.exhausted(cx) {
+                return Poll::Pending;
+            }
+
             let this = self.as_mut().project();
             match this.inner.borrow_mut() {
                 InnerNSRoundRobin::Fresh { name_servers } => {
@@ -553,7 +685,7 @@ impl<'a, 'b, 'c, 'd, 'e, 'f, 'g, 'h, CCache> Future for NSRoundRobin<'a, 'b, 'c,
                 InnerNSRoundRobin::GetCachedNSAddresses { name_server_address_queries, name_server_non_cached_queries, name_server_cached_queries } => {
                     name_server_address_queries.retain_mut(|ns_address_query| {
                         match ns_address_query.as_mut().poll(cx) {
-                            Poll::Ready(ns_query @ NSQuery { ns_domain: _, ns_address_rtype: _, context: _, client: _, joined_cache: _, ns_addresses: _, sockets: _, state: InnerNSQuery::Fresh(NSQueryCacheResponse::Hit) }) => {
+                            Poll::Ready(ns_query @ NSQuery { ns_domain: _, ns_address_rtype: _, context: _, client: _, joined_cache: _, ns_addresses: _, sockets: _, state: InnerNSQuery::Fresh(NSQueryCacheResponse::Hit), hedge: _, cancellation: _ }) => {
                                 name_server_cached_queries.push(Box::pin(ns_query));
                                 false
                             },
@@ -574,7 +706,11 @@ impl<'a, 'b, 'c, 'd, 'e, 'f, 'g, 'h, CCache> Future for NSRoundRobin<'a, 'b, 'c,
                         let mut ns_queries = Vec::with_capacity(name_server_non_cached_queries.len() + name_server_cached_queries.len());
                         ns_queries.extend(name_server_non_cached_queries.drain(..));
                         ns_queries.extend(name_server_cached_queries.drain(..));
-                        let ns_query_select = Box::pin(NSSelectQuery::new(ns_queries, 3, Duration::from_millis(200)));
+                        let ns_query_select = match (this.client.config().hedging, this.client.config().happy_eyeballs) {
+                            (Some(config), happy_eyeballs) => Box::pin(NSSelectQuery::hedged(ns_queries, config, this.client.hedging.clone()).with_families(happy_eyeballs)),
+                            (None, Some(config)) => Box::pin(NSSelectQuery::happy_eyeballs(ns_queries, config)),
+                            (None, None) => Box::pin(NSSelectQuery::new(ns_queries, 3, Duration::from_millis(200))),
+                        };
 
                         *this.inner = InnerNSRoundRobin::QueryNameServers { ns_query_select };
 
@@ -670,7 +806,7 @@ impl<'a, 'b, 'c, 'd, 'e, 'f, 'g, 'h, CCache> Future for NSRoundRobin<'a, 'b, 'c,
 }
 
 #[pinned_drop]
-impl<'a, 'b, 'c, 'd, 'e, 'f, 'g, 'h, CCache> PinnedDrop for NSRoundRobin<'a, 'b, 'c, 'd, 'e, 'f, 'g, 'h, CCache> where CCache: AsyncCache + Send + Sync + 'static {
+impl<'a, 'b, 'c, 'd, 'e, 'f, 'g, 'h> PinnedDrop for NSRoundRobin<'a, 'b, 'c, 'd, 'e, 'f, 'g, 'h> {
     fn drop(mut self: Pin<&mut Self>) {
         let this = self.project();
         match this.inner {
@@ -705,30 +841,57 @@ fn query_response(answer: Message) -> QResult {
 }
 
 #[pin_project(PinnedDrop)]
-struct ActiveQuery<'a, 'b, 'c, 'd, 'e, 'f, 'g, 'h, 'i, 'j, CCache>
+struct ActiveQuery<'a, 'b, 'c, 'd, 'e, 'f, 'g, 'h, 'i, 'j>
 where
-    CCache: AsyncCache + Send + Sync + 'static,
     'a: 'j,
     'j: 'i,
 {
     #[pin]
-    round_robin: NSRoundRobin<'a, 'b, 'c, 'd, 'e, 'f, 'g, 'h, CCache>,
+    round_robin: NSRoundRobin<'a, 'b, 'c, 'd, 'e, 'f, 'g, 'h>,
     #[pin]
     inner: InnerActiveQuery<'i, 'j>,
 }
 
+#[inline]
+fn active_query_key(context: &Context) -> ActiveQueryKey {
+    (context.view().cloned(), QuestionKey::from(context.query()))
+}
+
+/// A cancelled query's result is not shared with the queries that are following it, since they
+/// were not cancelled. They keep waiting on their own queries instead.
+#[inline]
+fn is_cancelled(result: &QResult) -> bool {
+    matches!(result, QResult::Err(QError::Cancelled))
+}
+
+/// Removes the query from the active queries once it has a result. A cancelled query is only
+/// removed if nobody else is waiting on it.
+fn remove_active_query(active_queries: &mut HashMap<ActiveQueryKey, once_watch::Sender<QResult>>, context: &Context, result: Option<&QResult>) {
+    let key = active_query_key(context);
+    if let (Some(result_sender), Some(true)) = (active_queries.get(&key), result.map(is_cancelled)) {
+        if result_sender.receiver_count() > 0 {
+            return;
+        }
+    }
+    if let Some(result_sender) = active_queries.remove(&key) {
+        // Always make sure the channel is closed. This should never have an
+        // effect but will ensure that it is never left open.
+        result_sender.close();
+    }
+}
+
 #[pin_project(project = InnerActiveQueryProj)]
 enum InnerActiveQuery<'i, 'j> {
     Fresh,
-    ReadActiveQueries(BoxFuture<'i, RwLockReadGuard<'j, HashMap<Question, once_watch::Sender<QResult>>>>),
-    WriteActiveQueries(BoxFuture<'i, RwLockWriteGuard<'j, HashMap<Question, once_watch::Sender<QResult>>>>),
+    ReadActiveQueries(BoxFuture<'i, RwLockReadGuard<'j, HashMap<ActiveQueryKey, once_watch::Sender<QResult>>>>),
+    WriteActiveQueries(BoxFuture<'i, RwLockWriteGuard<'j, HashMap<ActiveQueryKey, once_watch::Sender<QResult>>>>),
     Following(#[pin] once_watch::Receiver<QResult>),
-    Cleanup(BoxFuture<'i, RwLockWriteGuard<'j, HashMap<Question, once_watch::Sender<QResult>>>>, Option<QResult>),
+    Cleanup(BoxFuture<'i, RwLockWriteGuard<'j, HashMap<ActiveQueryKey, once_watch::Sender<QResult>>>>, Option<QResult>),
     Complete,
 }
 
-impl<'a, 'b, 'c, 'd, 'e, 'f, 'g, 'h, 'i, 'j, CCache> ActiveQuery<'a, 'b, 'c, 'd, 'e, 'f, 'g, 'h, 'i, 'j, CCache> where CCache: AsyncCache + Send + Sync + 'static {
-    fn new(client: &'a Arc<DNSAsyncClient>, joined_cache: &'b Arc<CCache>, question: &'c Arc<Context>, name_servers: &'d [CDomainName]) -> Self {
+impl<'a, 'b, 'c, 'd, 'e, 'f, 'g, 'h, 'i, 'j> ActiveQuery<'a, 'b, 'c, 'd, 'e, 'f, 'g, 'h, 'i, 'j> {
+    fn new(client: &'a Arc<DNSAsyncClient>, joined_cache: &'b SharedAsyncCache, question: &'c Arc<Context>, name_servers: &'d [CDomainName]) -> Self {
         Self {
             round_robin: NSRoundRobin::new(client, joined_cache, question, name_servers),
             inner: InnerActiveQuery::Fresh,
@@ -764,9 +927,8 @@ impl<'a, 'i, 'j> InnerActiveQuery<'i, 'j> where 'a: 'j, 'j: 'i {
     }
 }
 
-impl<'a, 'b, 'c, 'd, 'e, 'f, 'g, 'h, 'i, 'j, CCache> Future for ActiveQuery<'a, 'b, 'c, 'd, 'e, 'f, 'g, 'h, 'i, 'j, CCache>
+impl<'a, 'b, 'c, 'd, 'e, 'f, 'g, 'h, 'i, 'j> Future for ActiveQuery<'a, 'b, 'c, 'd, 'e, 'f, 'g, 'h, 'i, 'j>
 where
-    CCache: AsyncCache + Send + Sync + 'static,
     'a: 'j,
     'j: 'i,
 {
@@ -784,7 +946,7 @@ where
                         // If there is already an active query and the lock can be gotten right now,
                         // then we can send out this answer. Otherwise, move on.
                         if let Ok(r_active_queries) = this.round_robin.client.active_queries.try_read() {
-                            if let Some(result_sender) = r_active_queries.get(this.round_robin.context.query()) {
+                            if let (Some(result_sender), false) = (r_active_queries.get(&active_query_key(&this.round_robin.context)), is_cancelled(&result)) {
                                 let _ = result_sender.send(result.clone());
                             }
                             drop(r_active_queries);
@@ -802,7 +964,7 @@ where
                 },
                 InnerActiveQueryProj::ReadActiveQueries(r_active_queries) => {
                     if let Poll::Ready(r_active_queries) = r_active_queries.as_mut().poll(cx) {
-                        match r_active_queries.get(&this.round_robin.context.query()) {
+                        match r_active_queries.get(&active_query_key(&this.round_robin.context)) {
                             Some(result_sender) => {
                                 let result_receiver = result_sender.subscribe();
                                 drop(r_active_queries);
@@ -827,7 +989,7 @@ where
                         // If there is already an active query and the lock can be gotten right now,
                         // then we can send out this answer. Otherwise, move on.
                         if let Ok(r_active_queries) = this.round_robin.client.active_queries.try_read() {
-                            if let Some(result_sender) = r_active_queries.get(this.round_robin.context.query()) {
+                            if let (Some(result_sender), false) = (r_active_queries.get(&active_query_key(&this.round_robin.context)), is_cancelled(&result)) {
                                 let _ = result_sender.send(result.clone());
                             }
                             drop(r_active_queries);
@@ -842,7 +1004,7 @@ where
                 },
                 InnerActiveQueryProj::WriteActiveQueries(w_active_queries) => {
                     if let Poll::Ready(mut w_active_queries) = w_active_queries.as_mut().poll(cx) {
-                        match w_active_queries.get(&this.round_robin.context.query()) {
+                        match w_active_queries.get(&active_query_key(&this.round_robin.context)) {
                             Some(result_sender) => {
                                 let result_receiver = result_sender.subscribe();
                                 drop(w_active_queries);
@@ -854,7 +1016,7 @@ where
                             },
                             None => {
                                 let (send_response, result_receiver) = once_watch::channel();
-                                w_active_queries.insert(this.round_robin.context.query().clone(), send_response);
+                                w_active_queries.insert(active_query_key(&this.round_robin.context), send_response);
                                 drop(w_active_queries);
 
                                 this.inner.set_following(result_receiver);
@@ -870,7 +1032,7 @@ where
                         // If there is already an active query and the lock can be gotten right now,
                         // then we can send out this answer. Otherwise, move on.
                         if let Ok(r_active_queries) = this.round_robin.client.active_queries.try_read() {
-                            if let Some(result_sender) = r_active_queries.get(this.round_robin.context.query()) {
+                            if let (Some(result_sender), false) = (r_active_queries.get(&active_query_key(&this.round_robin.context)), is_cancelled(&result)) {
                                 let _ = result_sender.send(result.clone());
                             }
                             drop(r_active_queries);
@@ -905,7 +1067,9 @@ where
                     }
 
                     if let Poll::Ready(result) = this.round_robin.as_mut().poll(cx) {
-                        let _ = result_receiver.get_sender().send(result.clone());
+                        if !is_cancelled(&result) {
+                            let _ = result_receiver.get_sender().send(result.clone());
+                        }
 
                         this.inner.set_cleanup(result, this.round_robin.client);
 
@@ -917,11 +1081,7 @@ where
                 InnerActiveQueryProj::Cleanup(w_active_queries, result) => {
                     match w_active_queries.as_mut().poll(cx) {
                         Poll::Ready(mut w_active_queries) => {
-                            if let Some(result_sender) = w_active_queries.remove(&this.round_robin.context.query()) {
-                                // Always make sure the channel is closed. This should never have an
-                                // effect but will ensure that it is never left open.
-                                result_sender.close();
-                            }
+                            remove_active_query(&mut w_active_queries, &this.round_robin.context, result.as_ref());
                             drop(w_active_queries);
 
                             match result.take() {
@@ -959,7 +1119,9 @@ where
                         // TODO: add comment
                     },
                     InnerActiveQueryProj::Following(result_receiver) => {
-                        let _ = result_receiver.get_sender().send(result.clone());
+                        if !is_cancelled(&result) {
+                            let _ = result_receiver.get_sender().send(result.clone());
+                        }
 
                         this.inner.set_cleanup(result, this.round_robin.client);
                     },
@@ -1014,11 +1176,7 @@ where
             InnerActiveQueryProj::Cleanup(w_active_queries, result) => {
                 match w_active_queries.as_mut().poll(cx) {
                     Poll::Ready(mut w_active_queries) => {
-                        if let Some(result_sender) = w_active_queries.remove(&this.round_robin.context.query()) {
-                            // Always make sure the channel is closed. This should never have an
-                            // effect but will ensure that it is never left open.
-                            result_sender.close();
-                        }
+                        remove_active_query(&mut w_active_queries, &this.round_robin.context, result.as_ref());
                         drop(w_active_queries);
 
                         match result.take() {
@@ -1048,18 +1206,17 @@ where
 }
 
 #[pinned_drop]
-impl<'a, 'b, 'c, 'd, 'e, 'f, 'g, 'h, 'i, 'j, CCache> PinnedDrop for ActiveQuery<'a, 'b, 'c, 'd, 'e, 'f, 'g, 'h, 'i, 'j, CCache>
+impl<'a, 'b, 'c, 'd, 'e, 'f, 'g, 'h, 'i, 'j> PinnedDrop for ActiveQuery<'a, 'b, 'c, 'd, 'e, 'f, 'g, 'h, 'i, 'j>
 where
-    CCache: AsyncCache + Send + Sync + 'static,
     'a: 'j,
     'j: 'i,
 {
     fn drop(mut self: Pin<&mut Self>) {
         async fn cleanup(client: Arc<DNSAsyncClient>, query: Arc<Context>) {
             let mut w_active_queries = client.active_queries.write().await;
-            if let Some(sender) = w_active_queries.get(query.query()) {
+            if let Some(sender) = w_active_queries.get(&active_query_key(&query)) {
                 if (sender.sender_count() <= 1) && (sender.receiver_count() == 0) {
-                    let _ = w_active_queries.remove(query.query());
+                    let _ = w_active_queries.remove(&active_query_key(&query));
                 }
             }
             drop(w_active_queries);
@@ -1087,7 +1244,145 @@ where
 }
 
 #[inline]
-pub async fn query_name_servers<CCache>(client: &Arc<DNSAsyncClient>, joined_cache: &Arc<CCache>, context: Arc<Context>, name_servers: &[CDomainName]) -> QResult where CCache: AsyncCache + Send + Sync + 'static {
+pub async fn query_name_servers(client: &Arc<DNSAsyncClient>, joined_cache: &SharedAsyncCache, context: Arc<Context>, name_servers: &[CDomainName]) -> QResult {
     info!(context:?; "Querying Name Servers for '{}'", context.query());
+    // A query that starts from a supplied delegation sees a different view of the zone than an
+    // ordinary query for the same question, so the two must not share results.
+    if joined_cache.is_isolated() {
+        return NSRoundRobin::new(client, joined_cache, &context, name_servers).await;
+    }
     ActiveQuery::new(client, joined_cache, &context, name_servers).await
 }
+
+#[cfg(test)]
+mod round_robin_query_tests {
+    use std::{net::{IpAddr, Ipv4Addr, SocketAddr}, sync::{Arc, Mutex}, time::{Duration, Instant}};
+
+    use async_lib::awake_token::AwakeToken;
+    use dns_cache::asynchronous::async_main_cache::AsyncMainTreeCache;
+    use dns_lib::{interface::{cache::{main_cache::AsyncMainCache, MetaAuth}, client::{AsyncClient, Context, QNameMinimization, Response}}, query::{message::Message, qr::QR, question::Question}, resource_record::{rclass::RClass, rcode::RCode, resource_record::{RecordData, ResourceRecord}, rtype::RType, time::Time, types::a::A}, serde::wire::{from_wire::FromWire, read_wire::ReadWire}, types::c_domain_name::{CDomainName, CompressionMap}};
+    use tokio::net::UdpSocket;
+
+    use crate::{ClientConfig, DNSAsyncClient, HedgingConfig, HedgingStats};
+
+    /// The addresses of the name servers, in the order that they first received a query.
+    type Received = Arc<Mutex<Vec<IpAddr>>>;
+
+    /// Name servers at each of the addresses, all on the same port. Whichever receives a query first
+    /// never answers. The others answer every query with an A record.
+    async fn name_servers(addresses: [Ipv4Addr; 2]) -> (u16, Received) {
+        let received = Received::default();
+        let first = UdpSocket::bind(SocketAddr::from((addresses[0], 0))).await.unwrap();
+        let port = first.local_addr().unwrap().port();
+        let second = UdpSocket::bind(SocketAddr::from((addresses[1], port))).await.unwrap();
+        for socket in [first, second] {
+            let received = received.clone();
+            tokio::spawn(async move {
+                let address = socket.local_addr().unwrap().ip();
+                let mut buffer = vec![0_u8; u16::MAX as usize];
+                loop {
+                    let (length, peer) = socket.recv_from(&mut buffer).await.unwrap();
+                    let silent = {
+                        let mut w_received = received.lock().unwrap();
+                        if !w_received.contains(&address) {
+                            w_received.push(address);
+                        }
+                        w_received[0] == address
+                    };
+                    if silent {
+                        continue;
+                    }
+                    let mut response = Message::from_wire_format(&mut ReadWire::from_bytes(&buffer[..length])).unwrap();
+                    response.qr = QR::Response;
+                    response.authoritative_answer = true;
+                    response.additional.clear();
+                    response.answer = vec![ResourceRecord::new(response.question[0].qname().clone(), RClass::Internet, Time::from_secs(300), RecordData::A(A::new(Ipv4Addr::new(192, 0, 2, 1))))];
+                    let wire = response.to_wire_vec(&mut Some(CompressionMap::new())).unwrap();
+                    socket.send_to(&wire, peer).await.unwrap();
+                }
+            });
+        }
+        (port, received)
+    }
+
+    #[tokio::test]
+    async fn cancelled_queries_stop_and_are_removed_from_the_active_queries() {
+        // The root name server never answers, so the query only ends when it is cancelled.
+        let root = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let cache = Arc::new(AsyncMainTreeCache::new());
+        cache.load_from_string(". 3600 IN NS ns.test.\nns.test. 3600 IN A 127.0.0.1\n", MetaAuth::NotAuthoritativeBootstrap).await;
+        let config = ClientConfig { upstream_port: root.local_addr().unwrap().port(), ..ClientConfig::default() };
+        let client = Arc::new(DNSAsyncClient::with_config(cache, config).await);
+
+        let token = AwakeToken::new();
+        let cancel = token.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            cancel.awake();
+        });
+
+        let question = Question::new(CDomainName::from_utf8("www.example.com.").unwrap(), RType::A, RClass::Internet);
+        let start = Instant::now();
+        let response = client.clone().query(Context::new(question, QNameMinimization::None).with_cancellation(token)).await;
+        assert!(matches!(response, Response::Error(error) if error.rcode == RCode::ServFail));
+        assert!(start.elapsed() < Duration::from_secs(1));
+        assert!(client.active_queries.read().await.is_empty());
+
+        // The query to the root name server is stopped and taken out of its socket well before
+        // it would have been retransmitted.
+        let socket = client.socket_manager().try_get(&root.local_addr().unwrap()).await.unwrap();
+        let stopped = tokio::time::timeout(Duration::from_millis(250), async {
+            loop {
+                let stats = socket.stats().await;
+                if (stats.in_flight_queries == 0) && (stats.running_query_tasks == 0) {
+                    break;
+                }
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        }).await;
+        assert!(stopped.is_ok());
+        client.close().await;
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn hedges_are_sent_after_the_delay_and_the_slower_query_is_cancelled() {
+        const HEDGE_DELAY: Duration = Duration::from_millis(300);
+        let addresses = [Ipv4Addr::new(127, 0, 0, 1), Ipv4Addr::new(127, 0, 0, 2)];
+        let (port, received) = name_servers(addresses).await;
+        let cache = Arc::new(AsyncMainTreeCache::new());
+        cache.load_from_string(&format!("example. 3600 IN NS ns1.example.\nexample. 3600 IN NS ns2.example.\nns1.example. 3600 IN A {}\nns2.example. 3600 IN A {}\n", addresses[0], addresses[1]), MetaAuth::NotAuthoritative).await;
+        // Neither name server has answered before, so the hedge is sent after the default delay.
+        let hedging = HedgingConfig { default_delay: HEDGE_DELAY, ..HedgingConfig::default() };
+        let config = ClientConfig { upstream_port: port, hedging: Some(hedging), ..ClientConfig::default() };
+        let client = Arc::new(DNSAsyncClient::with_config(cache, config).await);
+
+        let question = Question::new(CDomainName::from_utf8("www.example.").unwrap(), RType::A, RClass::Internet);
+        let query = tokio::spawn(client.clone().query(Context::new(question, QNameMinimization::None)));
+        // While time is paused, sockets are only read when the clock moves on to the next timer, so
+        // the name servers cannot tell when the hedge was sent. The client counts it as it is sent.
+        tokio::time::sleep(HEDGE_DELAY - Duration::from_millis(1)).await;
+        assert_eq!(client.hedging_stats().hedged, 0);
+        tokio::time::sleep(Duration::from_millis(2)).await;
+        assert_eq!(client.hedging_stats().hedged, 1);
+
+        let response = query.await.unwrap();
+        assert!(matches!(response, Response::Answer(answer) if answer.answer.len() == 1));
+        assert_eq!(client.hedging_stats(), HedgingStats { hedged: 1, hedge_answered_first: 1, primary_answered_first: 0 });
+
+        // The query to the name server that never answered is stopped well before it would have
+        // timed out.
+        let slow = received.lock().unwrap()[0];
+        let socket = client.socket_manager().try_get(&SocketAddr::new(slow, port)).await.unwrap();
+        let stopped = tokio::time::timeout(Duration::from_millis(100), async {
+            loop {
+                let stats = socket.stats().await;
+                if (stats.in_flight_queries == 0) && (stats.running_query_tasks == 0) {
+                    break;
+                }
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        }).await;
+        assert!(stopped.is_ok());
+        client.close().await;
+    }
+}
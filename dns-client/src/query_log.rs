@@ -0,0 +1,141 @@
+use std::{collections::VecDeque, fmt::Display, num::NonZeroU32, sync::{atomic::{AtomicU64, Ordering}, Arc, Mutex}, time::{Duration, Instant, SystemTime, UNIX_EPOCH}};
+
+use async_trait::async_trait;
+use dns_lib::{interface::client::{Context, Response}, query::question::Question, resource_record::rcode::RCode};
+
+use crate::middleware::{Middleware, Next};
+
+/// A summary of one query that passed through the client and how it was answered.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct QueryLogEntry {
+    /// When the query started.
+    pub time: SystemTime,
+    pub question: Question,
+    pub elapsed: Duration,
+    pub rcode: RCode,
+    pub answers: usize,
+}
+
+impl Display for QueryLogEntry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let time = self.time.duration_since(UNIX_EPOCH).unwrap_or_default();
+        write!(f, "{}.{:03} {} {} ({} answers) {}ms", time.as_secs(), time.subsec_millis(), self.question, self.rcode, self.answers, self.elapsed.as_millis())
+    }
+}
+
+/// Counts of the queries that the log has seen.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct QueryLogStats {
+    /// Every query that passed through the log.
+    pub seen: u64,
+    /// The queries that were sampled, including those that have since been pushed out.
+    pub recorded: u64,
+}
+
+#[derive(Debug)]
+struct QueryLogInner {
+    capacity: usize,
+    sample_one_in: NonZeroU32,
+    entries: Mutex<VecDeque<QueryLogEntry>>,
+    seen: AtomicU64,
+    recorded: AtomicU64,
+}
+
+/// Keeps summaries of the most recent queries in memory so that recent traffic can be inspected
+/// without turning on verbose logging. Once the log is full, the oldest summary is dropped to make
+/// room for each new one.
+///
+/// The log is a middleware. Clones share the same buffer, so one clone can be added to the
+/// client's middleware chain while another is kept to read the summaries.
+#[derive(Debug, Clone)]
+pub struct QueryLog {
+    inner: Arc<QueryLogInner>,
+}
+
+impl QueryLog {
+    /// A log that keeps the last `capacity` queries.
+    #[inline]
+    pub fn new(capacity: usize) -> Self {
+        Self::sampled(capacity, NonZeroU32::MIN)
+    }
+
+    /// A log that keeps one in every `sample_one_in` queries, up to the last `capacity` of them.
+    #[inline]
+    pub fn sampled(capacity: usize, sample_one_in: NonZeroU32) -> Self {
+        Self {
+            inner: Arc::new(QueryLogInner {
+                capacity,
+                sample_one_in,
+                entries: Mutex::new(VecDeque::with_capacity(capacity)),
+                seen: AtomicU64::new(0),
+                recorded: AtomicU64::new(0),
+            }),
+        }
+    }
+
+    #[inline]
+    pub fn capacity(&self) -> usize { self.inner.capacity }
+
+    #[inline]
+    pub fn sample_one_in(&self) -> NonZeroU32 { self.inner.sample_one_in }
+
+    /// The summaries currently in the log, oldest first.
+    pub fn entries(&self) -> Vec<QueryLogEntry> {
+        let r_entries = self.inner.entries.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let entries = r_entries.iter().cloned().collect();
+        drop(r_entries);
+        entries
+    }
+
+    /// Removes and returns the summaries currently in the log, oldest first.
+    pub fn drain(&self) -> Vec<QueryLogEntry> {
+        let mut w_entries = self.inner.entries.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let entries = w_entries.drain(..).collect();
+        drop(w_entries);
+        entries
+    }
+
+    #[inline]
+    pub fn stats(&self) -> QueryLogStats {
+        QueryLogStats {
+            seen: self.inner.seen.load(Ordering::Relaxed),
+            recorded: self.inner.recorded.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Whether the next query should be recorded.
+    #[inline]
+    fn sample(&self) -> bool {
+        let seen = self.inner.seen.fetch_add(1, Ordering::Relaxed);
+        (self.inner.capacity != 0) && (seen % u64::from(self.inner.sample_one_in.get()) == 0)
+    }
+
+    fn push(&self, entry: QueryLogEntry) {
+        self.inner.recorded.fetch_add(1, Ordering::Relaxed);
+        let mut w_entries = self.inner.entries.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        if w_entries.len() >= self.inner.capacity {
+            w_entries.pop_front();
+        }
+        w_entries.push_back(entry);
+        drop(w_entries);
+    }
+}
+
+#[async_trait]
+impl Middleware for QueryLog {
+    async fn handle(&self, context: Context, next: Next<'_>) -> Response {
+        if !self.sample() {
+            return next.run(context).await;
+        }
+        let question = context.query().clone();
+        let time = SystemTime::now();
+        let start = Instant::now();
+        let response = next.run(context).await;
+        let (rcode, answers) = match &response {
+            Response::Answer(answer) => (RCode::NoError, answer.answer.len()),
+            Response::Error(error) => (error.rcode, 0),
+        };
+        self.push(QueryLogEntry { time, question, elapsed: start.elapsed(), rcode, answers });
+        response
+    }
+}
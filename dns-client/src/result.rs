@@ -33,6 +33,8 @@ pub(crate) enum QError {
         dname: CDomainName,
         qname: CDomainName,
     },
+    /// The caller cancelled the query with the context's cancellation token.
+    Cancelled,
 }
 
 impl Display for QError {
@@ -45,6 +47,7 @@ impl Display for QError {
             QError::NoClosestNameServerFound(domain) => write!(f, "could not find a closest name server for '{domain}'"),
             QError::MissingRecord(rtype) => write!(f, "could not find a {rtype} record in the set but one was expected"),
             QError::QNameIsNotChildOfDName { dname, qname } => write!(f, "the qname '{qname}' is not a child of the dname's owner '{dname}'"),
+            QError::Cancelled => write!(f, "the query was cancelled"),
         }
     }
 }
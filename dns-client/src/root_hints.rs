@@ -0,0 +1,553 @@
+use std::{collections::HashSet, error::Error, fmt::Display, io, net::IpAddr, path::PathBuf, time::{Duration, Instant, SystemTime, UNIX_EPOCH}};
+#[cfg(feature = "https")]
+use std::net::{Ipv4Addr, SocketAddr};
+
+use dns_lib::{interface::cache::{main_cache::AsyncMainCache, CacheMeta, CacheQuery, CacheRecord, CacheResponse, MetaAuth}, query::{edns::set_dnssec_ok, message::Message, question::Question}, resource_record::{rclass::RClass, rcode::RCode, resource_record::{RecordData, ResourceRecord}, rtype::RType}, serde::presentation::zone_file_reader::{ZoneFileReader, ZoneToken}, types::c_domain_name::CDomainName};
+#[cfg(feature = "https")]
+use dns_lib::types::c_domain_name::CmpDomainName;
+#[cfg(feature = "https")]
+use futures::future::join_all;
+#[cfg(feature = "https")]
+use log::info;
+use log::warn;
+#[cfg(feature = "https")]
+use network::doh::{DohClient, DohVersionPolicy, DEFAULT_DOH_PATH};
+use network::errors::QueryError;
+use rand::seq::SliceRandom;
+
+use crate::{query::network_query::{client_query, query_network_message}, DNSAsyncClient};
+
+/// The file that root hints are read from if no other path is configured.
+pub const DEFAULT_ROOT_HINTS_PATH: &str = "root.hints";
+
+/// The default for `max_hints_age`. IANA publishes a new hints file whenever a root server's
+/// address changes, which has happened every year or two.
+pub const DEFAULT_MAX_HINTS_AGE: Duration = Duration::from_secs(2 * 365 * 24 * 60 * 60);
+
+const SECONDS_PER_DAY: u64 = 24 * 60 * 60;
+
+const MONTHS: [&str; 12] = ["january", "february", "march", "april", "may", "june", "july", "august", "september", "october", "november", "december"];
+
+#[cfg(feature = "https")]
+const DOH_PORT: u16 = 443;
+
+/// The AD bit within the header's Z field.
+///
+/// https://datatracker.ietf.org/doc/html/rfc4035#section-3.2.3
+#[cfg(feature = "https")]
+const AUTHENTIC_DATA_FLAG: u8 = 0b010;
+
+/// A well-known DNS over HTTPS resolver that the addresses of the root name servers are fetched
+/// from when there is no root hints file. Requires the `https` feature.
+#[cfg(feature = "https")]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct DohBootstrap {
+    pub address: SocketAddr,
+    /// The name used to authenticate the resolver's certificate.
+    pub server_name: String,
+    pub path: String,
+}
+
+#[cfg(feature = "https")]
+impl Default for DohBootstrap {
+    fn default() -> Self {
+        Self {
+            address: SocketAddr::new(IpAddr::V4(Ipv4Addr::new(1, 1, 1, 1)), DOH_PORT),
+            server_name: "cloudflare-dns.com".to_string(),
+            path: DEFAULT_DOH_PATH.to_string(),
+        }
+    }
+}
+
+/// Where the root hints come from and how strictly the priming response is checked.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct RootHintsConfig {
+    /// The root hints file. If it exists, it is always used instead of the bootstrap resolver.
+    pub hints_path: PathBuf,
+    /// The resolver to ask for the root name servers when the hints file does not exist, or
+    /// `None` to require the file.
+    #[cfg(feature = "https")]
+    pub doh_bootstrap: Option<DohBootstrap>,
+    /// Require the bootstrap resolver to have authenticated its answers (the AD bit) and the
+    /// priming response to carry a current signature by the root zone over its NS RRset.
+    pub require_dnssec: bool,
+    /// Warn about a hints file that is older than this, or `None` to accept hints of any age.
+    /// The age is taken from the date in the file's header comments if it has one, and from the
+    /// time the file was last modified otherwise.
+    pub max_hints_age: Option<Duration>,
+}
+
+impl Default for RootHintsConfig {
+    fn default() -> Self {
+        Self {
+            hints_path: PathBuf::from(DEFAULT_ROOT_HINTS_PATH),
+            #[cfg(feature = "https")]
+            doh_bootstrap: Some(DohBootstrap::default()),
+            require_dnssec: true,
+            max_hints_age: Some(DEFAULT_MAX_HINTS_AGE),
+        }
+    }
+}
+
+/// Where the hints that the client was primed from came from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RootHintsSource {
+    File,
+    DohBootstrap,
+}
+
+/// The outcome of a successful bootstrap.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PrimingReport {
+    pub source: RootHintsSource,
+    /// The root name server that answered the priming query.
+    pub primed_from: IpAddr,
+    /// The root name servers listed in the priming response.
+    pub name_servers: Vec<CDomainName>,
+    /// The number of root name server addresses in the priming response.
+    pub addresses: usize,
+    /// Whether the priming response included a current signature over the root NS RRset.
+    pub signed: bool,
+    /// The problems found in the hints file. Priming can succeed in spite of them, since it only
+    /// needs one root name server to answer, but they should be fixed before it cannot.
+    pub warnings: Vec<HintsWarning>,
+}
+
+/// A problem with a root hints file that does not stop it from being loaded.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum HintsWarning {
+    /// An entry in the file could not be read and was skipped.
+    Invalid(String),
+    /// The file does not list any root name servers.
+    NoNameServers,
+    /// A root name server does not have an address of this type (A or AAAA), so it cannot be
+    /// reached over that address family.
+    MissingAddress { name_server: CDomainName, rtype: RType },
+    /// The file is older than `max_hints_age`.
+    Stale { age: Duration },
+}
+impl Display for HintsWarning {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Invalid(diagnostic) => write!(f, "skipped an invalid entry: {diagnostic}"),
+            Self::NoNameServers => write!(f, "the root hints do not list any root name servers"),
+            Self::MissingAddress { name_server, rtype } => write!(f, "the root name server '{name_server}' does not have an {rtype} record"),
+            Self::Stale { age } => write!(f, "the root hints are {} days old", age.as_secs() / SECONDS_PER_DAY),
+        }
+    }
+}
+
+/// Checks a root hints file for problems that would not stop it from being loaded. Every root
+/// name server should have both an A and an AAAA record, and the file should not be older than
+/// `max_age`. The age is taken from the date in the file's header comments (the "last update" or
+/// "related version of root zone" lines that IANA's file has) and otherwise from `modified`.
+pub fn check_root_hints(hints: &str, modified: Option<SystemTime>, max_age: Option<Duration>, now: SystemTime) -> Vec<HintsWarning> {
+    let (tokens, diagnostics) = ZoneFileReader::new(hints).read_all();
+    let mut warnings = diagnostics.iter()
+        .map(|diagnostic| HintsWarning::Invalid(diagnostic.to_string()))
+        .collect::<Vec<_>>();
+    let records = tokens.into_iter()
+        .filter_map(|token| match token {
+            ZoneToken::ResourceRecord(record) => Some(record),
+            ZoneToken::Include { .. } => None,
+        })
+        .collect::<Vec<_>>();
+
+    let name_servers = name_servers(&records);
+    if name_servers.is_empty() {
+        warnings.push(HintsWarning::NoNameServers);
+    }
+    for name_server in name_servers {
+        for rtype in [RType::A, RType::AAAA] {
+            if !records.iter().any(|record| (record.get_rtype() == rtype) && (record.get_name().as_lowercase() == name_server)) {
+                warnings.push(HintsWarning::MissingAddress { name_server: name_server.clone(), rtype });
+            }
+        }
+    }
+
+    if let Some(max_age) = max_age {
+        let age = hints_date(hints).or(modified).and_then(|date| now.duration_since(date).ok());
+        if let Some(age) = age.filter(|age| *age > max_age) {
+            warnings.push(HintsWarning::Stale { age });
+        }
+    }
+    warnings
+}
+
+/// The date in the header comments of IANA's root hints file. It has a line like
+/// `;       last update:     July 03, 2023` and one like
+/// `;       related version of root zone:     2023070301`.
+fn hints_date(hints: &str) -> Option<SystemTime> {
+    let comments = hints.lines()
+        .map(str::trim_start)
+        .filter_map(|line| line.strip_prefix(';'))
+        .map(|comment| comment.trim().to_ascii_lowercase());
+    for comment in comments {
+        if let Some(date) = comment.strip_prefix("last update:") {
+            // July 03, 2023
+            let mut parts = date.split(|character: char| character.is_whitespace() || (character == ',')).filter(|part| !part.is_empty());
+            let (Some(month), Some(day), Some(year)) = (parts.next(), parts.next(), parts.next()) else {
+                continue;
+            };
+            let Some(month) = MONTHS.iter().position(|name| *name == month) else {
+                continue;
+            };
+            if let (Ok(day), Ok(year)) = (day.parse(), year.parse()) {
+                return civil_date(year, month as u32 + 1, day);
+            }
+        } else if let Some(version) = comment.strip_prefix("related version of root zone:") {
+            // YYYYMMDDnn
+            let version = version.trim();
+            if let (Some(Ok(year)), Some(Ok(month)), Some(Ok(day))) = (version.get(0..4).map(str::parse), version.get(4..6).map(str::parse), version.get(6..8).map(str::parse)) {
+                return civil_date(year, month, day);
+            }
+        }
+    }
+    None
+}
+
+/// Midnight UTC at the start of the day.
+fn civil_date(year: i64, month: u32, day: u32) -> Option<SystemTime> {
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+        return None;
+    }
+    // Days since 1970-01-01 in the proleptic Gregorian calendar.
+    // https://howardhinnant.github.io/date_algorithms.html#days_from_civil
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year.rem_euclid(400);
+    let day_of_year = (153 * ((i64::from(month) + 9) % 12) + 2) / 5 + i64::from(day) - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    let days = era * 146097 + day_of_era - 719468;
+    UNIX_EPOCH.checked_add(Duration::from_secs(u64::try_from(days).ok()? * SECONDS_PER_DAY))
+}
+
+#[derive(Debug)]
+pub enum RootHintsError {
+    /// The hints file exists but could not be read.
+    HintsFile(io::Error),
+    /// There is no hints file and no bootstrap resolver is configured.
+    NoHints,
+    /// The bootstrap resolver could not be queried.
+    Bootstrap(io::Error),
+    /// The bootstrap resolver answered with this RCode.
+    BootstrapRCode(RCode),
+    /// The bootstrap resolver did not set the AD bit on its answer for the root name servers.
+    BootstrapUnauthenticated,
+    /// Neither the hints nor the bootstrap resolver provided the address of any root name server.
+    NoRootServers,
+    /// Every root name server failed to answer the priming query. The last error is kept.
+    Priming(QueryError),
+    /// A root name server answered the priming query, but the answer was not usable.
+    InvalidPrimingResponse(&'static str),
+    /// The root name servers in the priming response differ from the ones the bootstrap resolver
+    /// authenticated.
+    MismatchedNameServers,
+}
+impl Display for RootHintsError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self {
+            Self::HintsFile(error) => write!(f, "failed to read the root hints file: {error}"),
+            Self::NoHints => write!(f, "there is no root hints file and no bootstrap resolver is configured"),
+            Self::Bootstrap(error) => write!(f, "failed to query the bootstrap resolver: {error}"),
+            Self::BootstrapRCode(rcode) => write!(f, "the bootstrap resolver responded with {rcode}"),
+            Self::BootstrapUnauthenticated => write!(f, "the bootstrap resolver did not authenticate the root name servers"),
+            Self::NoRootServers => write!(f, "no root name server addresses are known"),
+            Self::Priming(error) => write!(f, "no root name server answered the priming query: {error}"),
+            Self::InvalidPrimingResponse(reason) => write!(f, "invalid priming response: {reason}"),
+            Self::MismatchedNameServers => write!(f, "the priming response lists different root name servers than the bootstrap resolver"),
+        }
+    }
+}
+impl Error for RootHintsError {}
+impl From<QueryError> for RootHintsError {
+    fn from(error: QueryError) -> Self {
+        Self::Priming(error)
+    }
+}
+
+#[inline]
+fn root_ns_question() -> Question {
+    Question::new(CDomainName::new_root(), RType::NS, RClass::Internet)
+}
+
+#[inline]
+fn cache_records(records: impl IntoIterator<Item = ResourceRecord>, auth: MetaAuth) -> Vec<CacheRecord> {
+    let meta = CacheMeta { auth, insertion_time: Instant::now(), original_ttl: None };
+    records.into_iter().map(move |record| CacheRecord { meta: meta.clone(), record }).collect()
+}
+
+#[inline]
+fn name_servers<'a>(records: impl IntoIterator<Item = &'a ResourceRecord>) -> Vec<CDomainName> {
+    let mut name_servers = records.into_iter()
+        .filter(|record| record.get_name().is_root())
+        .filter_map(|record| match record.get_rdata() {
+            RecordData::NS(ns) => Some(ns.name_server_domain_name().as_lowercase()),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    name_servers.sort_by_key(|name| name.to_string());
+    name_servers.dedup();
+    name_servers
+}
+
+#[inline]
+fn addresses<'a>(records: impl IntoIterator<Item = &'a ResourceRecord>) -> Vec<IpAddr> {
+    records.into_iter()
+        .filter_map(|record| match record.get_rdata() {
+            RecordData::A(a) => Some(IpAddr::from(*a.ipv4_addr())),
+            RecordData::AAAA(aaaa) => Some(IpAddr::from(*aaaa.ipv6_addr())),
+            _ => None,
+        })
+        .collect()
+}
+
+impl DNSAsyncClient {
+    /// Loads the root hints into the client's cache and then primes them (RFC 8109).
+    ///
+    /// The hints are read from the configured file if it exists. Otherwise, the root name servers
+    /// and their addresses are fetched from the bootstrap DNS over HTTPS resolver. Either way, a
+    /// randomly chosen root name server is then asked for the root NS RRset and its answer is
+    /// cached alongside the hints.
+    ///
+    /// With `require_dnssec`, the bootstrap resolver must have validated its answer and the
+    /// priming response must include a signature from the root zone over the NS RRset that is
+    /// valid right now. The signature itself is not verified against the root zone's keys.
+    pub async fn bootstrap_root_hints(&self, config: &RootHintsConfig) -> Result<PrimingReport, RootHintsError> {
+        let mut warnings = Vec::new();
+        let (source, bootstrap_name_servers) = match tokio::fs::read_to_string(&config.hints_path).await {
+            Ok(hints) => {
+                let modified = tokio::fs::metadata(&config.hints_path).await.and_then(|metadata| metadata.modified()).ok();
+                warnings = check_root_hints(&hints, modified, config.max_hints_age, SystemTime::now());
+                for warning in &warnings {
+                    warn!("Root hints '{}': {warning}", config.hints_path.display());
+                }
+                self.cache.load_from_string(&hints, MetaAuth::NotAuthoritativeBootstrap).await;
+                (RootHintsSource::File, None)
+            },
+            Err(error) if error.kind() == io::ErrorKind::NotFound => self.bootstrap_without_hints_file(config).await?,
+            Err(error) => return Err(RootHintsError::HintsFile(error)),
+        };
+
+        self.health.set_cache_loaded();
+
+        let mut report = self.prime(config.require_dnssec).await?;
+        report.source = source;
+        report.warnings = warnings;
+        if let Some(bootstrap_name_servers) = bootstrap_name_servers {
+            if bootstrap_name_servers != report.name_servers {
+                return Err(RootHintsError::MismatchedNameServers);
+            }
+        }
+        self.health.set_primed();
+        Ok(report)
+    }
+
+    /// Fetches the hints from the bootstrap resolver, if one is configured.
+    #[cfg(feature = "https")]
+    async fn bootstrap_without_hints_file(&self, config: &RootHintsConfig) -> Result<(RootHintsSource, Option<Vec<CDomainName>>), RootHintsError> {
+        match &config.doh_bootstrap {
+            Some(bootstrap) => {
+                info!("No root hints at '{}', fetching them from {}", config.hints_path.display(), bootstrap.server_name);
+                let name_servers = self.bootstrap_over_doh(bootstrap, config.require_dnssec).await?;
+                Ok((RootHintsSource::DohBootstrap, Some(name_servers)))
+            },
+            None => Err(RootHintsError::NoHints),
+        }
+    }
+
+    /// Without DNS over HTTPS, there is nowhere else to get the hints from.
+    #[cfg(not(feature = "https"))]
+    async fn bootstrap_without_hints_file(&self, _config: &RootHintsConfig) -> Result<(RootHintsSource, Option<Vec<CDomainName>>), RootHintsError> {
+        Err(RootHintsError::NoHints)
+    }
+
+    /// Asks the bootstrap resolver for the root NS RRset and the addresses of each name server in
+    /// it, then caches them as hints. The name servers are returned so that the priming response
+    /// can be compared against them.
+    #[cfg(feature = "https")]
+    async fn bootstrap_over_doh(&self, bootstrap: &DohBootstrap, require_dnssec: bool) -> Result<Vec<CDomainName>, RootHintsError> {
+        let doh_client = DohClient::new(bootstrap.address, bootstrap.server_name.clone(), bootstrap.path.clone(), DohVersionPolicy::default());
+        let query = |question: Question| {
+            let doh_client = doh_client.clone();
+            async move {
+                let mut message = Message::from(question);
+                message.recursion_desired = true;
+                set_dnssec_ok(&mut message, true);
+                let response = doh_client.query(message).await.map_err(RootHintsError::Bootstrap)?;
+                match response.rcode_flag() {
+                    RCode::NoError => Ok(response),
+                    rcode => Err(RootHintsError::BootstrapRCode(*rcode)),
+                }
+            }
+        };
+
+        let ns_response = query(root_ns_question()).await?;
+        if require_dnssec && (u8::from(ns_response.z_flag()) & AUTHENTIC_DATA_FLAG) == 0 {
+            return Err(RootHintsError::BootstrapUnauthenticated);
+        }
+        let name_servers = name_servers(ns_response.answer());
+        if name_servers.is_empty() {
+            return Err(RootHintsError::NoRootServers);
+        }
+
+        let address_questions = name_servers.iter()
+            .flat_map(|name_server| [RType::A, RType::AAAA].map(|rtype| Question::new(name_server.clone(), rtype, RClass::Internet)));
+        let mut hints = ns_response.answer.into_iter()
+            .filter(|record| record.get_rtype() == RType::NS)
+            .collect::<Vec<_>>();
+        for response in join_all(address_questions.map(query)).await {
+            // A root name server that is missing an address is not fatal as long as some others
+            // can be reached.
+            match response {
+                Ok(response) => hints.extend(response.answer.into_iter().filter(|record| name_servers.iter().any(|name_server| name_server.matches(record.get_name())))),
+                Err(error) => warn!("Failed to fetch a root name server address: {error}"),
+            }
+        }
+        self.cache.insert_records(cache_records(hints, MetaAuth::NotAuthoritativeBootstrap)).await;
+        Ok(name_servers)
+    }
+
+    /// The addresses of the root name servers currently held in the cache.
+    async fn root_server_addresses(&self) -> Vec<IpAddr> {
+        let root_ns_question = root_ns_question();
+        let name_servers = match self.cache.get(&CacheQuery { authoritative: false, question: &root_ns_question }).await {
+            CacheResponse::Records(records) => name_servers(records.iter().map(|record| &record.record)),
+            CacheResponse::Err(_) => return Vec::new(),
+        };
+        let mut root_server_addresses = Vec::new();
+        for name_server in name_servers {
+            for rtype in [RType::A, RType::AAAA] {
+                let question = Question::new(name_server.clone(), rtype, RClass::Internet);
+                if let CacheResponse::Records(records) = self.cache.get(&CacheQuery { authoritative: false, question: &question }).await {
+                    root_server_addresses.extend(addresses(records.iter().map(|record| &record.record)));
+                }
+            }
+        }
+        root_server_addresses
+    }
+
+    /// Sends the priming query to the root name servers in a random order until one of them
+    /// gives a usable answer, which is then cached.
+    ///
+    /// https://datatracker.ietf.org/doc/html/rfc8109#section-3
+    async fn prime(&self, require_dnssec: bool) -> Result<PrimingReport, RootHintsError> {
+        let mut root_server_addresses = self.root_server_addresses().await;
+        if root_server_addresses.is_empty() {
+            return Err(RootHintsError::NoRootServers);
+        }
+        root_server_addresses.shuffle(&mut rand::thread_rng());
+
+        let mut last_error = RootHintsError::NoRootServers;
+        for address in root_server_addresses {
+            let mut query = client_query(self, &root_ns_question());
+            set_dnssec_ok(&mut query, true);
+            let response = match query_network_message(self, query, &address).await {
+                Ok(response) => response,
+                Err(error) => {
+                    warn!("Priming query to root name server {address} failed: {error}");
+                    last_error = RootHintsError::Priming(error);
+                    continue;
+                },
+            };
+            match self.cache_priming_response(address, response, require_dnssec).await {
+                Ok(report) => return Ok(report),
+                Err(error) => {
+                    warn!("Root name server {address} sent an unusable priming response: {error}");
+                    last_error = error;
+                },
+            }
+        }
+        Err(last_error)
+    }
+
+    async fn cache_priming_response(&self, address: IpAddr, response: Message, require_dnssec: bool) -> Result<PrimingReport, RootHintsError> {
+        if response.rcode_flag() != &RCode::NoError {
+            return Err(RootHintsError::InvalidPrimingResponse("the RCode is not NOERROR"));
+        }
+        if !response.authoritative_answer_flag() {
+            return Err(RootHintsError::InvalidPrimingResponse("the AA bit is not set"));
+        }
+        let name_servers = name_servers(response.answer());
+        if name_servers.is_empty() {
+            return Err(RootHintsError::InvalidPrimingResponse("the answer has no root NS records"));
+        }
+
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |duration| duration.as_secs() as u32);
+        let signed = response.answer().iter().any(|record| match record.get_rdata() {
+            RecordData::RRSIG(rrsig) => record.get_name().is_root()
+                && rrsig.type_covered() == RType::NS
+                && rrsig.signers_name().is_root()
+                && rrsig.is_current(now),
+            _ => false,
+        });
+        if require_dnssec && !signed {
+            return Err(RootHintsError::InvalidPrimingResponse("the root NS RRset does not have a current signature"));
+        }
+
+        // Only the root NS RRset, its signatures, and the addresses of the name servers in it are
+        // taken from the response.
+        let name_server_set = name_servers.iter().cloned().collect::<HashSet<_>>();
+        let Message { answer, additional, .. } = response;
+        let primed_addresses = additional.into_iter()
+            .filter(|record| matches!(record.get_rtype(), RType::A | RType::AAAA))
+            .filter(|record| name_server_set.contains(&record.get_name().as_lowercase()))
+            .collect::<Vec<_>>();
+        let report = PrimingReport {
+            source: RootHintsSource::File,
+            primed_from: address,
+            name_servers,
+            addresses: primed_addresses.len(),
+            signed,
+            warnings: Vec::new(),
+        };
+        let primed_records = answer.into_iter()
+            .filter(|record| record.get_name().is_root())
+            .filter(|record| matches!(record.get_rtype(), RType::NS | RType::RRSIG))
+            .chain(primed_addresses);
+        self.cache.insert_records(cache_records(primed_records, MetaAuth::NotAuthoritative)).await;
+        Ok(report)
+    }
+}
+
+#[cfg(test)]
+mod root_hints_tests {
+    use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+    use dns_lib::{resource_record::rtype::RType, types::c_domain_name::CDomainName};
+
+    use super::{check_root_hints, HintsWarning, DEFAULT_MAX_HINTS_AGE};
+
+    const HINTS: &str = ";       last update:     July 03, 2023
+;       related version of root zone:     2023070301
+.                        3600000      NS    A.ROOT-SERVERS.NET.
+A.ROOT-SERVERS.NET.      3600000      A     198.41.0.4
+A.ROOT-SERVERS.NET.      3600000      AAAA  2001:503:ba3e::2:30
+.                        3600000      NS    B.ROOT-SERVERS.NET.
+B.ROOT-SERVERS.NET.      3600000      A     170.247.170.2
+";
+
+    /// 2023-07-03 00:00:00 UTC
+    const RELEASED: Duration = Duration::from_secs(1_688_342_400);
+
+    #[test]
+    fn missing_addresses_and_stale_hints_are_reported() {
+        let released = UNIX_EPOCH + RELEASED;
+        let warnings = check_root_hints(HINTS, None, Some(DEFAULT_MAX_HINTS_AGE), released + Duration::from_secs(24 * 60 * 60));
+        assert_eq!(warnings, vec![HintsWarning::MissingAddress { name_server: CDomainName::from_utf8("b.root-servers.net.").unwrap(), rtype: RType::AAAA }]);
+
+        // The date in the header is used in preference to the file's modification time.
+        let now = released + DEFAULT_MAX_HINTS_AGE + Duration::from_secs(1);
+        let warnings = check_root_hints(HINTS, Some(now), Some(DEFAULT_MAX_HINTS_AGE), now);
+        assert!(warnings.contains(&HintsWarning::Stale { age: DEFAULT_MAX_HINTS_AGE + Duration::from_secs(1) }));
+
+        // Without a date in the header, the modification time is used.
+        let undated = HINTS.lines().skip(2).collect::<Vec<_>>().join("\n");
+        let warnings = check_root_hints(&undated, Some(released), Some(DEFAULT_MAX_HINTS_AGE), now);
+        assert!(warnings.contains(&HintsWarning::Stale { age: DEFAULT_MAX_HINTS_AGE + Duration::from_secs(1) }));
+        assert!(check_root_hints(&undated, Some(now), Some(DEFAULT_MAX_HINTS_AGE), now).iter().all(|warning| !matches!(warning, HintsWarning::Stale { .. })));
+        assert!(check_root_hints(&undated, None, None, SystemTime::now()).iter().all(|warning| !matches!(warning, HintsWarning::Stale { .. })));
+
+        assert!(check_root_hints("", None, None, now).contains(&HintsWarning::NoNameServers));
+    }
+}
@@ -0,0 +1,15 @@
+use dns_lib::{resource_record::{rclass::RClass, resource_record::{RecordData, ResourceRecord}, time::Time, types::{a::A, soa::SOA}}, types::c_domain_name::CDomainName};
+
+pub(crate) fn name(name: &str) -> CDomainName {
+    CDomainName::from_utf8(name).unwrap()
+}
+
+/// The SOA record of `zone` with this serial.
+pub(crate) fn soa(zone: &str, serial: u32) -> ResourceRecord {
+    let soa = SOA::new(name(&format!("ns.{zone}")), name(&format!("admin.{zone}")), serial, Time::from_secs(3600), Time::from_secs(600), Time::from_secs(86400), 300);
+    ResourceRecord::new(name(zone), RClass::Internet, Time::from_secs(3600), RecordData::SOA(soa))
+}
+
+pub(crate) fn a(owner: &str, address: &str) -> ResourceRecord {
+    ResourceRecord::new(name(owner), RClass::Internet, Time::from_secs(300), RecordData::A(A::new(address.parse().unwrap())))
+}
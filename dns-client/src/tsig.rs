@@ -0,0 +1,108 @@
+use std::{collections::HashMap, fmt::Debug, net::IpAddr, sync::Arc};
+
+use dns_lib::tsig::{MacCalculator, TsigAlgorithm, TsigError, TsigExchange, TsigKey};
+
+/// The TSIG keys that the client signs its queries with, by the address of the name server that
+/// shares the key. Responses from these name servers must be signed with the same key or they are
+/// rejected as `ResponseRejection::Unauthenticated`.
+///
+/// This is meant for the name servers that the client sends zone transfers and dynamic updates to.
+/// Only queries sent over UDP and TCP are signed. Queries to an address with an encrypted upstream
+/// are sent without a signature.
+///
+/// Responses are verified over the bytes they were received as. Signed queries are never joined
+/// with an identical query that is already in flight, since a signed response only answers the
+/// query it was signed for.
+#[derive(Clone, Default)]
+pub struct TsigKeys {
+    keys: HashMap<IpAddr, TsigKey>,
+    calculator: Option<Arc<dyn MacCalculator>>,
+}
+
+impl TsigKeys {
+    #[inline]
+    pub fn new(calculator: Arc<dyn MacCalculator>) -> Self {
+        Self { keys: HashMap::new(), calculator: Some(calculator) }
+    }
+
+    /// Signs every query sent to this address with the key, replacing any key that it had. The
+    /// key is rejected if the calculator does not support its algorithm.
+    pub fn insert(&mut self, address: IpAddr, key: TsigKey) -> Result<Option<TsigKey>, TsigError> {
+        if !self.supports(key.algorithm()) {
+            return Err(TsigError::UnsupportedAlgorithm(key.algorithm()));
+        }
+        Ok(self.keys.insert(address, key))
+    }
+
+    #[inline]
+    pub fn remove(&mut self, address: &IpAddr) -> Option<TsigKey> {
+        self.keys.remove(address)
+    }
+
+    #[inline]
+    pub fn get(&self, address: &IpAddr) -> Option<&TsigKey> {
+        self.keys.get(address)
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+
+    #[inline]
+    fn supports(&self, algorithm: TsigAlgorithm) -> bool {
+        self.calculator.as_ref().is_some_and(|calculator| calculator.supports(algorithm))
+    }
+
+    /// Starts a signed exchange with the name server, if the client has a key for it.
+    pub(crate) fn exchange(&self, address: &IpAddr) -> Option<Result<TsigExchange<'_>, TsigError>> {
+        let key = self.keys.get(address)?;
+        let calculator = self.calculator.as_deref()?;
+        Some(TsigExchange::new(key, calculator))
+    }
+}
+
+impl Debug for TsigKeys {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TsigKeys")
+            .field("keys", &self.keys)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tsig_tests {
+    use std::sync::Arc;
+
+    use dns_lib::{tsig::{MacCalculator, TsigAlgorithm, TsigError, TsigKey}, types::c_domain_name::CDomainName};
+
+    use super::TsigKeys;
+
+    struct Sha256Only;
+
+    impl MacCalculator for Sha256Only {
+        fn supports(&self, algorithm: TsigAlgorithm) -> bool {
+            algorithm == TsigAlgorithm::HmacSha256
+        }
+
+        fn mac(&self, algorithm: TsigAlgorithm, _secret: &[u8], _data: &[u8]) -> Option<Vec<u8>> {
+            self.supports(algorithm).then(|| vec![0; algorithm.output_length()])
+        }
+    }
+
+    fn key(algorithm: TsigAlgorithm) -> TsigKey {
+        TsigKey::new(CDomainName::from_utf8("transfer.example.").unwrap(), algorithm, b"secret".to_vec())
+    }
+
+    #[test]
+    fn keys_need_a_calculator_that_supports_them() {
+        let address = "192.0.2.1".parse().unwrap();
+        assert_eq!(TsigKeys::default().insert(address, key(TsigAlgorithm::HmacSha256)), Err(TsigError::UnsupportedAlgorithm(TsigAlgorithm::HmacSha256)));
+
+        let mut keys = TsigKeys::new(Arc::new(Sha256Only));
+        assert_eq!(keys.insert(address, key(TsigAlgorithm::HmacSha1)), Err(TsigError::UnsupportedAlgorithm(TsigAlgorithm::HmacSha1)));
+        assert_eq!(keys.insert(address, key(TsigAlgorithm::HmacSha256)), Ok(None));
+        assert!(keys.exchange(&address).is_some_and(|exchange| exchange.is_ok()));
+        assert!(keys.exchange(&"192.0.2.2".parse().unwrap()).is_none());
+    }
+}
@@ -0,0 +1,125 @@
+use std::net::{IpAddr, SocketAddr};
+
+use dns_lib::{interface::dnr::{DnrError, DnrInstance}, types::c_domain_name::CDomainName};
+use network::async_query::QueryOpt;
+#[cfg(feature = "tls")]
+use std::{collections::HashMap, sync::Arc};
+#[cfg(feature = "tls")]
+use network::{dot::DotClient, tls_config::TlsVerification};
+
+#[cfg(feature = "tls")]
+const DOT_PORT: u16 = 853;
+#[cfg(feature = "quic")]
+const DOQ_PORT: u16 = 853;
+#[cfg(feature = "https")]
+const DOH_PORT: u16 = 443;
+#[cfg(feature = "https")]
+const DEFAULT_DOH_PATH: &str = "/dns-query{?dns}";
+
+/// A resolver that queries can be forwarded to over an encrypted transport.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct EncryptedUpstream {
+    pub address: SocketAddr,
+    pub protocol: QueryOpt,
+    /// The name used to authenticate the server's certificate.
+    pub server_name: CDomainName,
+    /// The URI template, only used for DNS over HTTPS.
+    pub doh_path: Option<String>,
+    /// Lower values are preferred.
+    pub priority: u16,
+    /// How the server's certificate is verified. Upstreams learned from the network are verified
+    /// against the platform's trust store.
+    #[cfg(feature = "tls")]
+    pub verification: TlsVerification,
+}
+
+/// The transport and default port for an ALPN identifier, if it is supported.
+#[inline]
+fn alpn_protocol(alpn_id: &str) -> Option<(QueryOpt, u16)> {
+    match alpn_id {
+        #[cfg(feature = "tls")]
+        "dot" => Some((QueryOpt::Tls, DOT_PORT)),
+        #[cfg(feature = "quic")]
+        "doq" => Some((QueryOpt::Quic, DOQ_PORT)),
+        #[cfg(feature = "https")]
+        "h2" | "h3" => Some((QueryOpt::Https, DOH_PORT)),
+        _ => None,
+    }
+}
+
+impl EncryptedUpstream {
+    /// Converts a network-designated resolver into the list of upstreams that it describes (one
+    /// per address and supported protocol). ADN-only instances cannot be used until their
+    /// addresses are resolved, so they produce no upstreams. ALPN identifiers for protocols that
+    /// are not supported, or whose cargo feature is disabled, are skipped.
+    pub fn from_dnr(instance: &DnrInstance) -> Result<Vec<Self>, DnrError> {
+        let port = instance.port()?;
+        #[cfg_attr(not(feature = "https"), allow(unused_variables))]
+        let doh_path = instance.doh_path()?;
+        let mut upstreams = Vec::new();
+        for alpn_id in instance.alpn()? {
+            let Some((protocol, default_port)) = alpn_protocol(&alpn_id) else { continue };
+            let doh_path = match protocol {
+                #[cfg(feature = "https")]
+                QueryOpt::Https => Some(doh_path.clone().unwrap_or_else(|| DEFAULT_DOH_PATH.to_string())),
+                _ => None,
+            };
+            for address in instance.addresses() {
+                let upstream = Self {
+                    address: SocketAddr::new(*address, port.unwrap_or(default_port)),
+                    protocol,
+                    server_name: instance.authentication_domain_name().clone(),
+                    doh_path: doh_path.clone(),
+                    priority: instance.service_priority(),
+                    #[cfg(feature = "tls")]
+                    verification: TlsVerification::Platform,
+                };
+                // "h2" and "h3" describe the same DoH endpoint.
+                if !upstreams.contains(&upstream) {
+                    upstreams.push(upstream);
+                }
+            }
+        }
+        Ok(upstreams)
+    }
+
+    #[inline]
+    pub fn ip(&self) -> IpAddr { self.address.ip() }
+}
+
+/// A recursive resolver that a forwarding client sends its queries to.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum ForwardUpstream {
+    /// Queries are sent over UDP and retried over TCP when the response is truncated.
+    Plain(SocketAddr),
+    /// Queries are only sent over the upstream's encrypted transport: DNS over TLS, QUIC, or
+    /// HTTPS.
+    Encrypted(EncryptedUpstream),
+}
+
+impl ForwardUpstream {
+    #[inline]
+    pub fn address(&self) -> SocketAddr {
+        match self {
+            Self::Plain(address) => *address,
+            Self::Encrypted(upstream) => upstream.address,
+        }
+    }
+}
+
+/// The DNS over TLS clients for the forwarders that use DoT, by address. Each keeps its connection
+/// open between queries.
+#[cfg(feature = "tls")]
+pub(crate) fn dot_clients(forwarders: &[ForwardUpstream]) -> HashMap<SocketAddr, Arc<DotClient>> {
+    forwarders.iter()
+        .filter_map(|forwarder| match forwarder {
+            ForwardUpstream::Encrypted(upstream) if matches!(upstream.protocol, QueryOpt::Tls | QueryOpt::TlsInsecure) => Some(upstream),
+            _ => None,
+        })
+        .map(|upstream| {
+            // Certificates are issued for the name without the trailing dot.
+            let server_name = upstream.server_name.to_string().trim_end_matches('.').to_string();
+            (upstream.address, DotClient::new(upstream.address, server_name, upstream.verification.clone()))
+        })
+        .collect()
+}
@@ -0,0 +1,312 @@
+use std::{collections::HashSet, sync::atomic::{AtomicU64, Ordering}};
+
+use dns_lib::{query::{message::Message, qr::QR}, resource_record::{rcode::RCode, resource_record::RecordData, rtype::RType, types::nsec::NSEC}, tsig::TsigError, types::{c_domain_name::{CDomainName, CmpDomainName}, domain_name::DomainName, label::Label}};
+use log::warn;
+use network::errors::ResponseRejection;
+
+/// A point-in-time copy of the response validation counters.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ValidationStats {
+    /// The number of responses rejected because the QR bit was not set.
+    pub not_a_response: u64,
+    /// The number of responses rejected because the opcode did not match the query.
+    pub opcode_mismatch: u64,
+    /// The number of responses rejected because the question section did not match the query.
+    pub question_mismatch: u64,
+    /// The number of responses rejected because the flags did not make sense for an
+    /// authoritative server.
+    pub incoherent_flags: u64,
+    /// The number of answer records dropped because they were not related to the question.
+    pub unrelated_answers: u64,
+    /// The number of compact denial of existence responses that were recognized, whether they
+    /// denied the name or only the type.
+    pub compact_denials: u64,
+    /// The number of compact denial responses that denied the name and were turned into NXDOMAIN.
+    pub compact_nxdomains: u64,
+    /// The number of responses rejected because they had more records than
+    /// `max_response_records`.
+    pub too_many_records: u64,
+    /// The number of responses rejected because they were larger than `max_response_size`.
+    pub too_large: u64,
+    /// The number of responses to queries signed with TSIG that were rejected because their
+    /// signature was missing or not valid.
+    pub unauthenticated: u64,
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct ResponseValidator {
+    compact_denial: bool,
+    max_records: usize,
+    max_size: usize,
+    not_a_response: AtomicU64,
+    opcode_mismatch: AtomicU64,
+    question_mismatch: AtomicU64,
+    incoherent_flags: AtomicU64,
+    unrelated_answers: AtomicU64,
+    compact_denials: AtomicU64,
+    compact_nxdomains: AtomicU64,
+    too_many_records: AtomicU64,
+    too_large: AtomicU64,
+    unauthenticated: AtomicU64,
+}
+
+impl ResponseValidator {
+    #[inline]
+    pub fn new(compact_denial: bool, max_records: usize, max_size: usize) -> Self {
+        Self { compact_denial, max_records, max_size, ..Self::default() }
+    }
+
+    #[inline]
+    pub fn stats(&self) -> ValidationStats {
+        ValidationStats {
+            not_a_response: self.not_a_response.load(Ordering::Relaxed),
+            opcode_mismatch: self.opcode_mismatch.load(Ordering::Relaxed),
+            question_mismatch: self.question_mismatch.load(Ordering::Relaxed),
+            incoherent_flags: self.incoherent_flags.load(Ordering::Relaxed),
+            unrelated_answers: self.unrelated_answers.load(Ordering::Relaxed),
+            compact_denials: self.compact_denials.load(Ordering::Relaxed),
+            compact_nxdomains: self.compact_nxdomains.load(Ordering::Relaxed),
+            too_many_records: self.too_many_records.load(Ordering::Relaxed),
+            too_large: self.too_large.load(Ordering::Relaxed),
+            unauthenticated: self.unauthenticated.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Rejects responses with more records or more bytes than the client accepts, so that a
+    /// hostile name server cannot make the client hold onto (and cache) huge answers. These limits
+    /// are checked even when the rest of the validation is disabled.
+    ///
+    /// The size is estimated from the parsed message and is never smaller than its actual size.
+    pub fn check_limits(&self, response: &Message) -> Result<(), ResponseRejection> {
+        let records = response.answer.len() + response.authority.len() + response.additional.len();
+        let rejection = if records > self.max_records {
+            warn!("Rejected response {}: it has {records} records but at most {} are allowed", response.id, self.max_records);
+            ResponseRejection::TooManyRecords
+        } else if response.estimated_wire_size(true) > self.max_size {
+            warn!("Rejected response {}: it is larger than {} bytes", response.id, self.max_size);
+            ResponseRejection::TooLarge
+        } else {
+            return Ok(());
+        };
+        self.count(rejection);
+        Err(rejection)
+    }
+
+    /// Counts a response whose TSIG signature could not be verified.
+    pub fn reject_unauthenticated(&self, response: &Message, error: &TsigError) -> ResponseRejection {
+        warn!("Rejected response {}: {error}", response.id);
+        self.count(ResponseRejection::Unauthenticated);
+        ResponseRejection::Unauthenticated
+    }
+
+    #[inline]
+    fn count(&self, rejection: ResponseRejection) {
+        let counter = match rejection {
+            ResponseRejection::NotAResponse => &self.not_a_response,
+            ResponseRejection::OpcodeMismatch => &self.opcode_mismatch,
+            ResponseRejection::QuestionMismatch => &self.question_mismatch,
+            ResponseRejection::IncoherentFlags => &self.incoherent_flags,
+            ResponseRejection::TooManyRecords => &self.too_many_records,
+            ResponseRejection::TooLarge => &self.too_large,
+            ResponseRejection::Unauthenticated => &self.unauthenticated,
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Checks that the response from an authoritative name server is an answer to the query. If
+    /// it is, any answer records that are not part of the answer to the question (or the CNAME
+    /// and DNAME chain leading to it) are removed. Otherwise, the reason it was rejected is
+    /// counted and returned.
+    pub fn validate(&self, query: &Message, mut response: Message) -> Result<Message, ResponseRejection> {
+        if let Err(rejection) = check_header(query, &response) {
+            self.count(rejection);
+            warn!("Rejected response {}: {rejection}", response.id);
+            return Err(rejection);
+        }
+
+        let dropped = drop_unrelated_answers(query, &mut response);
+        if dropped > 0 {
+            self.unrelated_answers.fetch_add(dropped as u64, Ordering::Relaxed);
+            warn!("Dropped {dropped} unrelated answer records from response {}", response.id);
+        }
+
+        if self.compact_denial {
+            match compact_denial(query, &response) {
+                Some(CompactDenial::NxName) => {
+                    self.compact_denials.fetch_add(1, Ordering::Relaxed);
+                    self.compact_nxdomains.fetch_add(1, Ordering::Relaxed);
+                    response.rcode = RCode::NXDomain;
+                },
+                Some(CompactDenial::NoData) => {
+                    self.compact_denials.fetch_add(1, Ordering::Relaxed);
+                },
+                None => (),
+            }
+        }
+        Ok(response)
+    }
+}
+
+/// What a compact denial of existence response says about the qname.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum CompactDenial {
+    /// The name exists but has no records of the queried type. Empty non-terminals look like this
+    /// too, since their bitmap only has NSEC and RRSIG.
+    NoData,
+    /// The name does not exist. The NSEC record's bitmap has the NXNAME pseudo-type.
+    NxName,
+}
+
+/// Compact denial answers every negative query with NODATA. The NSEC record is owned by the qname
+/// and its next name is `\000.<qname>`, the name immediately after the qname in canonical order,
+/// so it covers nothing except the qname itself.
+///
+/// https://datatracker.ietf.org/doc/draft-ietf-dnsop-compact-denial-of-existence/
+fn compact_denial(query: &Message, response: &Message) -> Option<CompactDenial> {
+    let question = query.question.first()?;
+    if (response.rcode != RCode::NoError) || !response.answer.is_empty() {
+        return None;
+    }
+    response.authority.iter()
+        .filter(|record| record.get_name().matches(question.qname()))
+        .find_map(|record| match record.get_rdata() {
+            RecordData::NSEC(nsec) if is_immediate_successor(question.qname(), nsec.next_domain_name()) => Some(nsec_denial(nsec)),
+            _ => None,
+        })
+}
+
+#[inline]
+fn nsec_denial(nsec: &NSEC) -> CompactDenial {
+    if nsec.type_bit_map().has_rtype(&RType::NXNAME) {
+        CompactDenial::NxName
+    } else {
+        CompactDenial::NoData
+    }
+}
+
+/// Whether the name is the qname with a single zero octet label added to the front.
+fn is_immediate_successor(qname: &CDomainName, next_name: &DomainName) -> bool {
+    let mut labels = next_name.case_sensitive_labels();
+    match labels.next() {
+        Some(label) if label.octets() == [0] => (),
+        _ => return false,
+    }
+    next_name.search_domains().nth(1).is_some_and(|parent| parent.matches(qname))
+}
+
+fn check_header(query: &Message, response: &Message) -> Result<(), ResponseRejection> {
+    if response.qr != QR::Response {
+        return Err(ResponseRejection::NotAResponse);
+    }
+    if response.opcode != query.opcode {
+        return Err(ResponseRejection::OpcodeMismatch);
+    }
+
+    match (query.question.as_slice(), response.question.as_slice()) {
+        ([query_question], [response_question]) => {
+            if !query_question.matches(response_question) {
+                return Err(ResponseRejection::QuestionMismatch);
+            }
+        },
+        // Some servers do not echo the question when they refuse to or cannot answer it.
+        (_, []) if response.rcode != RCode::NoError => (),
+        _ => return Err(ResponseRejection::QuestionMismatch),
+    }
+
+    // The RD bit is copied from the query into the response.
+    // https://datatracker.ietf.org/doc/html/rfc1035#section-4.1.1
+    if response.recursion_desired != query.recursion_desired {
+        return Err(ResponseRejection::IncoherentFlags);
+    }
+    // A referral is not an authoritative answer. An authoritative server that is delegating the
+    // name does not have authority over it.
+    let is_referral = (response.rcode == RCode::NoError)
+        && response.answer.is_empty()
+        && response.authority.iter().any(|record| record.get_rtype() == RType::NS)
+        && !response.authority.iter().any(|record| record.get_rtype() == RType::SOA);
+    if response.authoritative_answer && is_referral {
+        return Err(ResponseRejection::IncoherentFlags);
+    }
+    Ok(())
+}
+
+/// Removes the answer records that are not owned by the qname or by a name in the CNAME chain
+/// that starts at the qname. Returns the number of records that were removed.
+fn drop_unrelated_answers(query: &Message, response: &mut Message) -> usize {
+    let question = match query.question.first() {
+        Some(question) => question,
+        None => return 0,
+    };
+
+    // Follow the CNAME chain. Records are not required to be in order so this repeats until no
+    // new names are found.
+    let mut chain: HashSet<CDomainName> = HashSet::from([question.qname().as_lowercase()]);
+    loop {
+        let next_names = response.answer.iter()
+            .filter(|record| chain.contains(&record.get_name().as_lowercase()))
+            .filter_map(|record| match record.get_rdata() {
+                RecordData::CNAME(cname) => Some(cname.primary_name().as_lowercase()),
+                _ => None,
+            })
+            .filter(|name| !chain.contains(name))
+            .collect::<Vec<_>>();
+        if next_names.is_empty() {
+            break;
+        }
+        chain.extend(next_names);
+    }
+
+    let qtype = question.qtype();
+    let answer_count = response.answer.len();
+    response.answer.retain(|record| match record.get_rtype() {
+        // A DNAME applies to every name below its owner.
+        RType::DNAME => chain.iter().any(|name| record.get_name().is_parent_domain_of(name)),
+        RType::CNAME | RType::RRSIG => chain.contains(&record.get_name().as_lowercase()),
+        rtype => ((rtype == qtype) || (qtype == RType::ANY))
+            && chain.contains(&record.get_name().as_lowercase()),
+    });
+    answer_count - response.answer.len()
+}
+
+#[cfg(test)]
+mod validation_tests {
+    use std::net::Ipv4Addr;
+
+    use dns_lib::{query::{message::Message, qr::QR, question::Question}, resource_record::{rclass::RClass, resource_record::{RecordData, ResourceRecord}, rtype::RType, time::Time, types::a::A}, types::c_domain_name::CDomainName};
+    use network::errors::ResponseRejection;
+
+    use super::ResponseValidator;
+
+    fn response(records: u8) -> Message {
+        let qname = CDomainName::from_utf8("www.example.org.").unwrap();
+        let mut response = Message::from(&Question::new(qname.clone(), RType::A, RClass::Internet));
+        response.qr = QR::Response;
+        response.answer = (0..records)
+            .map(|octet| ResourceRecord::new(qname.clone(), RClass::Internet, Time::from_secs(300), RecordData::A(A::new(Ipv4Addr::new(192, 0, 2, octet)))))
+            .collect();
+        response
+    }
+
+    #[test]
+    fn responses_within_limits_are_accepted() {
+        let validator = ResponseValidator::new(false, 4, 512);
+        assert_eq!(validator.check_limits(&response(4)), Ok(()));
+        assert_eq!(validator.stats().too_many_records, 0);
+        assert_eq!(validator.stats().too_large, 0);
+    }
+
+    #[test]
+    fn responses_with_too_many_records_are_rejected() {
+        let validator = ResponseValidator::new(false, 4, 512);
+        assert_eq!(validator.check_limits(&response(5)), Err(ResponseRejection::TooManyRecords));
+        assert_eq!(validator.stats().too_many_records, 1);
+    }
+
+    #[test]
+    fn responses_that_are_too_large_are_rejected() {
+        let validator = ResponseValidator::new(false, 100, 128);
+        assert_eq!(validator.check_limits(&response(10)), Err(ResponseRejection::TooLarge));
+        assert_eq!(validator.stats().too_large, 1);
+        assert_eq!(validator.stats().too_many_records, 0);
+    }
+}
@@ -0,0 +1,141 @@
+use std::{collections::HashMap, fmt::Display};
+
+use dns_lib::{resource_record::rcode::RCode, types::c_domain_name::CDomainName};
+use metrics::counter;
+use network::errors::QueryError;
+use tokio::sync::RwLock;
+
+use crate::result::{QError, QResult};
+
+/// The name of the counter that every zone outcome is reported to. It has the labels `zone` and
+/// `outcome`.
+pub const ZONE_OUTCOMES_METRIC: &str = "dns_client_zone_outcomes_total";
+
+/// Zones beyond this many are still reported to the metrics recorder but are not kept in the
+/// client's own table, which would otherwise grow with every zone ever visited.
+const MAX_TRACKED_ZONES: usize = 10_000;
+
+/// The outcome of asking a zone's name servers a question.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ZoneOutcome {
+    NoError,
+    NXDomain,
+    ServFail,
+    /// Any other rcode, such as Refused or FormErr.
+    OtherRCode,
+    /// None of the zone's name servers answered in time.
+    Timeout,
+    /// The query failed for any other reason.
+    Error,
+}
+
+impl ZoneOutcome {
+    #[inline]
+    pub const fn as_str(&self) -> &'static str {
+        match self {
+            Self::NoError    => "noerror",
+            Self::NXDomain   => "nxdomain",
+            Self::ServFail   => "servfail",
+            Self::OtherRCode => "other_rcode",
+            Self::Timeout    => "timeout",
+            Self::Error      => "error",
+        }
+    }
+
+    pub(crate) fn from_result(result: &QResult) -> Self {
+        match result {
+            QResult::Ok(_) => Self::NoError,
+            QResult::Fail(RCode::NXDomain) => Self::NXDomain,
+            QResult::Fail(RCode::ServFail) => Self::ServFail,
+            QResult::Fail(_) => Self::OtherRCode,
+            QResult::Err(QError::NetworkQueryErr(QueryError::Timeout)) => Self::Timeout,
+            QResult::Err(_) => Self::Error,
+        }
+    }
+}
+
+impl Display for ZoneOutcome {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+/// A point-in-time copy of the outcome counters for a single zone.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ZoneStats {
+    pub no_error: u64,
+    pub nx_domain: u64,
+    pub serv_fail: u64,
+    pub other_rcode: u64,
+    pub timeout: u64,
+    pub error: u64,
+}
+
+impl ZoneStats {
+    /// The total number of outcomes recorded for the zone.
+    #[inline]
+    pub fn total(&self) -> u64 {
+        self.no_error + self.nx_domain + self.serv_fail + self.other_rcode + self.timeout + self.error
+    }
+
+    /// The number of outcomes that indicate the zone is not being served correctly.
+    #[inline]
+    pub fn failures(&self) -> u64 {
+        self.serv_fail + self.timeout + self.error
+    }
+
+    #[inline]
+    fn add(&mut self, outcome: ZoneOutcome) {
+        let counter = match outcome {
+            ZoneOutcome::NoError    => &mut self.no_error,
+            ZoneOutcome::NXDomain   => &mut self.nx_domain,
+            ZoneOutcome::ServFail   => &mut self.serv_fail,
+            ZoneOutcome::OtherRCode => &mut self.other_rcode,
+            ZoneOutcome::Timeout    => &mut self.timeout,
+            ZoneOutcome::Error      => &mut self.error,
+        };
+        *counter += 1;
+    }
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct ZoneStatsRecorder {
+    zones: RwLock<HashMap<CDomainName, ZoneStats>>,
+}
+
+impl ZoneStatsRecorder {
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Records the outcome of querying the zone's name servers, both in the client's table and
+    /// through the `metrics` facade.
+    pub async fn record(&self, zone: &CDomainName, outcome: ZoneOutcome) {
+        let zone = zone.as_lowercase();
+        counter!(ZONE_OUTCOMES_METRIC, "zone" => zone.to_string(), "outcome" => outcome.as_str()).increment(1);
+
+        let mut w_zones = self.zones.write().await;
+        let is_full = w_zones.len() >= MAX_TRACKED_ZONES;
+        match w_zones.get_mut(&zone) {
+            Some(stats) => stats.add(outcome),
+            None if is_full => (),
+            None => w_zones.entry(zone).or_default().add(outcome),
+        }
+        drop(w_zones);
+    }
+
+    pub async fn stats(&self, zone: &CDomainName) -> Option<ZoneStats> {
+        let r_zones = self.zones.read().await;
+        let stats = r_zones.get(&zone.as_lowercase()).copied();
+        drop(r_zones);
+        stats
+    }
+
+    pub async fn all_stats(&self) -> HashMap<CDomainName, ZoneStats> {
+        let r_zones = self.zones.read().await;
+        let stats = r_zones.clone();
+        drop(r_zones);
+        stats
+    }
+}
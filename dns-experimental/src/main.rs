@@ -0,0 +1,286 @@
+use std::{env, net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr}, process::ExitCode, sync::Arc, time::{Duration, Instant, SystemTime}};
+
+use dns_cache::asynchronous::async_main_cache::AsyncMainTreeCache;
+use dns_client::{root_hints::RootHintsConfig, DNSAsyncClient};
+use dns_lib::{interface::cache::{main_cache::AsyncMainCache, MetaAuth}, query::{dig::{Dig, DigMetadata}, edns::DEFAULT_EDNS_BUFFER_SIZE, message::Message, question::Question}, resource_record::{rclass::RClass, rtype::RType}, serde::wire::{from_wire::FromWire, read_wire::ReadWire, to_wire::ToWire, write_wire::WriteWire}, types::c_domain_name::{CDomainName, CompressionMap}};
+use dns_test_support::self_test::run_self_test;
+use tokio::{io::AsyncReadExt, net::{TcpStream, UdpSocket}};
+
+/// How long the `query` command waits for a response before giving up.
+const QUERY_TIMEOUT: Duration = Duration::from_secs(5);
+
+const USAGE: &str = "\
+usage: dns-experimental <command> [<args>]
+
+commands:
+    dump-zone <domain> [--out <file>] <zone-file>...
+        Loads the zone files into a cache and then writes every cached record at or below
+        <domain> as a zone file, to <file> if given or to stdout otherwise.
+
+    prime-root [--hints <file>] [--no-dnssec]
+        Loads the root hints (from <file>, or 'root.hints' by default) and primes them. If the
+        file does not exist, the root name servers are fetched over DNS over HTTPS instead.
+
+    query [--server <address:port>] [--tcp] <domain> [<type>]
+        Sends a recursive query for the domain (type A by default) to the server (127.0.0.1:53
+        by default) and prints the response the way dig does. The query is retried over TCP if
+        the response is truncated.
+
+    self-test
+        Runs the resolver against a mock name server on the loopback address and reports which
+        of its behaviours (truncation, 0x20, minimization, negative caching, CNAME loops,
+        EDNS fallback) pass.";
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    let args = env::args().skip(1).collect::<Vec<_>>();
+    match args.split_first() {
+        Some((command, args)) if command == "dump-zone" => dump_zone(args).await,
+        Some((command, args)) if command == "prime-root" => prime_root(args).await,
+        Some((command, args)) if command == "query" => query(args).await,
+        Some((command, [])) if command == "self-test" => self_test().await,
+        _ => {
+            eprintln!("{USAGE}");
+            ExitCode::FAILURE
+        },
+    }
+}
+
+async fn dump_zone(args: &[String]) -> ExitCode {
+    let mut domain = None;
+    let mut out_path = None;
+    let mut zone_paths = Vec::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--out" => match args.next() {
+                Some(path) => out_path = Some(path),
+                None => {
+                    eprintln!("missing file name after '--out'\n\n{USAGE}");
+                    return ExitCode::FAILURE;
+                },
+            },
+            _ if domain.is_none() => domain = Some(arg),
+            _ => zone_paths.push(arg),
+        }
+    }
+    let domain = match domain.map(|domain| CDomainName::from_utf8(domain)) {
+        Some(Ok(domain)) if domain.is_fully_qualified() => domain,
+        Some(Ok(domain)) => {
+            eprintln!("the domain '{domain}' must be fully qualified");
+            return ExitCode::FAILURE;
+        },
+        Some(Err(error)) => {
+            eprintln!("{error}");
+            return ExitCode::FAILURE;
+        },
+        None => {
+            eprintln!("{USAGE}");
+            return ExitCode::FAILURE;
+        },
+    };
+
+    let cache = AsyncMainTreeCache::new();
+    for zone_path in zone_paths {
+        let result = match tokio::fs::File::open(zone_path).await {
+            Ok(mut file) => cache.load_from_file(&mut file, MetaAuth::NotAuthoritative).await,
+            Err(error) => Err(error),
+        };
+        if let Err(error) = result {
+            eprintln!("failed to load '{zone_path}': {error}");
+            return ExitCode::FAILURE;
+        }
+    }
+
+    let mut zone = String::new();
+    if let Err(error) = cache.dump_zone(&domain, RClass::Internet, &mut zone).await {
+        eprintln!("failed to write the zone for '{domain}': {error}");
+        return ExitCode::FAILURE;
+    }
+    match out_path {
+        Some(out_path) => if let Err(error) = tokio::fs::write(out_path, zone).await {
+            eprintln!("failed to write '{out_path}': {error}");
+            return ExitCode::FAILURE;
+        },
+        None => print!("{zone}"),
+    }
+    ExitCode::SUCCESS
+}
+
+async fn prime_root(args: &[String]) -> ExitCode {
+    let mut config = RootHintsConfig::default();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--hints" => match args.next() {
+                Some(path) => config.hints_path = path.into(),
+                None => {
+                    eprintln!("missing file name after '--hints'\n\n{USAGE}");
+                    return ExitCode::FAILURE;
+                },
+            },
+            "--no-dnssec" => config.require_dnssec = false,
+            _ => {
+                eprintln!("{USAGE}");
+                return ExitCode::FAILURE;
+            },
+        }
+    }
+
+    let client = DNSAsyncClient::new(Arc::new(AsyncMainTreeCache::new())).await;
+    let result = client.bootstrap_root_hints(&config).await;
+    client.close().await;
+    match result {
+        Ok(report) => {
+            println!("primed from {} using hints from {:?} ({})", report.primed_from, report.source, if report.signed { "signed" } else { "unsigned" });
+            for warning in &report.warnings {
+                eprintln!("warning: {warning}");
+            }
+            for name_server in report.name_servers {
+                println!("{name_server}");
+            }
+            ExitCode::SUCCESS
+        },
+        Err(error) => {
+            eprintln!("{error}");
+            ExitCode::FAILURE
+        },
+    }
+}
+
+async fn query(args: &[String]) -> ExitCode {
+    let mut server = SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 53);
+    let mut use_tcp = false;
+    let mut positional = Vec::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--server" => match args.next().map(|address| address.parse()) {
+                Some(Ok(address)) => server = address,
+                Some(Err(error)) => {
+                    eprintln!("invalid server address: {error}");
+                    return ExitCode::FAILURE;
+                },
+                None => {
+                    eprintln!("missing address after '--server'\n\n{USAGE}");
+                    return ExitCode::FAILURE;
+                },
+            },
+            "--tcp" => use_tcp = true,
+            _ => positional.push(arg),
+        }
+    }
+    let (domain, rtype) = match positional.as_slice() {
+        [domain] => (domain, RType::A),
+        [domain, rtype] => match RType::from_str(&rtype.to_uppercase()) {
+            Ok(rtype) => (domain, rtype),
+            Err(error) => {
+                eprintln!("{error}");
+                return ExitCode::FAILURE;
+            },
+        },
+        _ => {
+            eprintln!("{USAGE}");
+            return ExitCode::FAILURE;
+        },
+    };
+    let domain = match CDomainName::from_utf8(domain) {
+        Ok(domain) if domain.is_fully_qualified() => domain,
+        Ok(domain) => {
+            eprintln!("the domain '{domain}' must be fully qualified");
+            return ExitCode::FAILURE;
+        },
+        Err(error) => {
+            eprintln!("{error}");
+            return ExitCode::FAILURE;
+        },
+    };
+
+    let mut query = Message::from(Question::new(domain, rtype, RClass::Internet));
+    query.id = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).map_or(0, |now| now.subsec_nanos() as u16);
+    query.recursion_desired = true;
+    query.edns_mut().set_udp_payload_size(DEFAULT_EDNS_BUFFER_SIZE);
+
+    let started = Instant::now();
+    let mut exchange = None;
+    if !use_tcp {
+        exchange = Some(("UDP", tokio::time::timeout(QUERY_TIMEOUT, exchange_udp(&query, server)).await));
+    }
+    let truncated = matches!(&exchange, Some((_, Ok(Ok((response, _))))) if response.truncation);
+    if use_tcp || truncated {
+        exchange = Some(("TCP", tokio::time::timeout(QUERY_TIMEOUT, exchange_tcp(&query, server)).await));
+    }
+    let query_time = started.elapsed();
+    match exchange {
+        Some((transport, Ok(Ok((response, message_size))))) => {
+            let metadata = DigMetadata::new()
+                .with_server(server)
+                .with_transport(transport)
+                .with_query_time(query_time)
+                .with_message_size(message_size);
+            print!("{}", Dig::new(&response, &metadata));
+            ExitCode::SUCCESS
+        },
+        Some((transport, Ok(Err(error)))) => {
+            eprintln!("the query to {server} over {transport} failed: {error}");
+            ExitCode::FAILURE
+        },
+        Some((transport, Err(_))) => {
+            eprintln!("the query to {server} over {transport} timed out");
+            ExitCode::FAILURE
+        },
+        None => unreachable!("the query is sent over UDP, TCP, or both"),
+    }
+}
+
+/// Sends the query over UDP and returns the response along with its size on the wire.
+async fn exchange_udp(query: &Message, server: SocketAddr) -> Result<(Message, usize), String> {
+    let local = match server {
+        SocketAddr::V4(_) => SocketAddr::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED), 0),
+        SocketAddr::V6(_) => SocketAddr::new(IpAddr::V6(Ipv6Addr::UNSPECIFIED), 0),
+    };
+    let socket = UdpSocket::bind(local).await.map_err(|error| error.to_string())?;
+    socket.connect(server).await.map_err(|error| error.to_string())?;
+
+    let mut buffer = vec![0_u8; u16::MAX as usize];
+    let mut wire = WriteWire::from_bytes(&mut buffer);
+    query.to_wire_format(&mut wire, &mut Some(CompressionMap::new())).map_err(|error| error.to_string())?;
+    let length = wire.current_len();
+    socket.send(&buffer[..length]).await.map_err(|error| error.to_string())?;
+
+    loop {
+        let length = socket.recv(&mut buffer).await.map_err(|error| error.to_string())?;
+        let response = Message::from_wire_format(&mut ReadWire::from_bytes(&buffer[..length])).map_err(|error| error.to_string())?;
+        // Anything that is not the response to this query is ignored, the same as dig does.
+        if response.id == query.id {
+            return Ok((response, length));
+        }
+    }
+}
+
+/// Sends the query over TCP and returns the response along with its size on the wire (without
+/// the two octet length).
+async fn exchange_tcp(query: &Message, server: SocketAddr) -> Result<(Message, usize), String> {
+    let mut stream = TcpStream::connect(server).await.map_err(|error| error.to_string())?;
+
+    query.write_with_two_octet_length(&mut stream, &mut Some(CompressionMap::new())).await.map_err(|error| error.to_string())?;
+
+    let mut buffer = vec![0_u8; u16::MAX as usize];
+    let length = stream.read_u16().await.map_err(|error| error.to_string())? as usize;
+    stream.read_exact(&mut buffer[..length]).await.map_err(|error| error.to_string())?;
+    let response = Message::from_wire_format(&mut ReadWire::from_bytes(&buffer[..length])).map_err(|error| error.to_string())?;
+    Ok((response, length))
+}
+
+async fn self_test() -> ExitCode {
+    match run_self_test().await {
+        Ok(report) => {
+            println!("{report}");
+            if report.passed() { ExitCode::SUCCESS } else { ExitCode::FAILURE }
+        },
+        Err(error) => {
+            eprintln!("failed to start the mock name server: {error}");
+            ExitCode::FAILURE
+        },
+    }
+}
@@ -0,0 +1,690 @@
+#[cfg(feature = "ring")]
+pub mod ring_verifier;
+
+use std::{collections::HashSet, error::Error, fmt::Display};
+
+use crate::{resource_record::{digest_alg::DigestAlgorithm, dnssec_alg::DnsSecAlgorithm, rclass::RClass, resource_record::{RecordData, ResourceRecord}, types::{afsdb::AFSDB, cname::CNAME, dname::DNAME, dnskey::DNSKEY, ds::DS, mb::MB, md::MD, mf::MF, mg::MG, minfo::MINFO, mr::MR, mx::MX, naptr::NAPTR, ns::NS, nsap_ptr::NSAP_PTR, ptr::PTR, rp::RP, rrsig::RRSIG, rt::RT, soa::SOA, srv::SRV}}, serde::wire::{to_wire::ToWire, write_wire::WriteWire}, types::{base_conversions::BaseConversions, c_domain_name::{CDomainName, CmpDomainName}, domain_name::DomainName}};
+
+/// The DNSKEY protocol field must always be 3.
+///
+/// https://datatracker.ietf.org/doc/html/rfc4034#section-2.1.2
+const DNSKEY_PROTOCOL: u8 = 3;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum DnssecError {
+    /// There is no RRSIG for the RRset, or no DS record for the zone.
+    NoSignatures,
+    /// None of the RRSIGs were made by, and none of the DS records refer to, a key in the DNSKEY
+    /// RRset.
+    NoMatchingKey,
+    /// Every RRSIG that matched a key has expired or is not valid yet.
+    SignatureNotCurrent,
+    /// Every RRSIG that matched a key used an algorithm that the policy does not allow.
+    RejectedAlgorithm(DnsSecAlgorithm),
+    /// Every RRSIG that matched a key used an algorithm that the verifier does not support.
+    UnsupportedAlgorithm(DnsSecAlgorithm),
+    /// The strongest digest type among the DS records is not allowed by the policy.
+    RejectedDigest(DigestAlgorithm),
+    /// The strongest digest type among the DS records is not supported by the digester.
+    UnsupportedDigest(DigestAlgorithm),
+    /// Signatures (or DS digests) were checked and none of them were valid.
+    BadSignature,
+    /// The data covered by the signature could not be written out.
+    Encode(String),
+}
+impl Error for DnssecError {}
+impl Display for DnssecError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::NoSignatures => write!(f, "The RRset is not signed"),
+            Self::NoMatchingKey => write!(f, "None of the RRSIGs or DS records match a key in the DNSKEY RRset"),
+            Self::SignatureNotCurrent => write!(f, "None of the RRSIGs are within their validity period"),
+            Self::RejectedAlgorithm(algorithm) => write!(f, "The RRSIGs use the algorithm {algorithm}, which is not allowed"),
+            Self::UnsupportedAlgorithm(algorithm) => write!(f, "The RRSIGs use the unsupported algorithm {algorithm}"),
+            Self::RejectedDigest(digest_type) => write!(f, "The DS records use the digest {digest_type}, which is not allowed"),
+            Self::UnsupportedDigest(digest_type) => write!(f, "The DS records use the unsupported digest {digest_type}"),
+            Self::BadSignature => write!(f, "None of the RRSIGs or DS records are valid for the RRset"),
+            Self::Encode(error) => write!(f, "Could not write the signed data: {error}"),
+        }
+    }
+}
+
+impl DnssecError {
+    /// How far the validation got before it failed. When every signature fails, the error that got
+    /// the furthest is the one reported since it says the most about what went wrong.
+    #[inline]
+    fn progress(&self) -> u8 {
+        match self {
+            Self::NoSignatures => 0,
+            Self::NoMatchingKey => 1,
+            Self::SignatureNotCurrent => 2,
+            Self::RejectedAlgorithm(_) => 3,
+            Self::UnsupportedAlgorithm(_) => 4,
+            Self::RejectedDigest(_) => 3,
+            Self::UnsupportedDigest(_) => 4,
+            Self::Encode(_) => 5,
+            Self::BadSignature => 6,
+        }
+    }
+}
+
+/// Checks the cryptographic signatures. This keeps the algorithms (and whichever library
+/// implements them) out of the rest of the validation.
+pub trait SignatureVerifier {
+    fn supports(&self, algorithm: DnsSecAlgorithm) -> bool;
+
+    /// Whether `signature` is a valid signature of `signed_data` by the public key, in the format
+    /// it has in the DNSKEY record.
+    fn verify(&self, algorithm: DnsSecAlgorithm, public_key: &[u8], signed_data: &[u8], signature: &[u8]) -> bool;
+}
+
+/// Calculates the digests used by DS records.
+pub trait Digester {
+    fn supports(&self, digest_type: DigestAlgorithm) -> bool;
+
+    /// The digest of the data, or `None` if the digest type is not supported.
+    fn digest(&self, digest_type: DigestAlgorithm, data: &[u8]) -> Option<Vec<u8>>;
+}
+
+/// Which algorithms a validator is willing to trust. Signatures and DS records that use any other
+/// algorithm are treated as if they were not there. By default, the algorithms and digests that
+/// are no longer considered secure are rejected.
+///
+/// https://datatracker.ietf.org/doc/html/rfc8624
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AlgorithmPolicy {
+    rejected_algorithms: HashSet<DnsSecAlgorithm>,
+    rejected_digests: HashSet<DigestAlgorithm>,
+}
+
+impl Default for AlgorithmPolicy {
+    fn default() -> Self {
+        let rejected_algorithms = (DnsSecAlgorithm::MIN..=DnsSecAlgorithm::MAX)
+            .map(DnsSecAlgorithm::from_code)
+            .filter(DnsSecAlgorithm::is_weak)
+            .collect();
+        let rejected_digests = (DigestAlgorithm::MIN..=DigestAlgorithm::MAX)
+            .map(DigestAlgorithm::from_code)
+            .filter(DigestAlgorithm::is_weak)
+            .collect();
+        Self { rejected_algorithms, rejected_digests }
+    }
+}
+
+impl AlgorithmPolicy {
+    /// A policy that allows every algorithm and digest.
+    #[inline]
+    pub fn permissive() -> Self {
+        Self { rejected_algorithms: HashSet::new(), rejected_digests: HashSet::new() }
+    }
+
+    #[inline]
+    pub fn allow_algorithm(&mut self, algorithm: DnsSecAlgorithm) -> &mut Self {
+        self.rejected_algorithms.remove(&algorithm);
+        self
+    }
+
+    #[inline]
+    pub fn reject_algorithm(&mut self, algorithm: DnsSecAlgorithm) -> &mut Self {
+        self.rejected_algorithms.insert(algorithm);
+        self
+    }
+
+    #[inline]
+    pub fn allow_digest(&mut self, digest_type: DigestAlgorithm) -> &mut Self {
+        self.rejected_digests.remove(&digest_type);
+        self
+    }
+
+    #[inline]
+    pub fn reject_digest(&mut self, digest_type: DigestAlgorithm) -> &mut Self {
+        self.rejected_digests.insert(digest_type);
+        self
+    }
+
+    #[inline]
+    pub fn allows_algorithm(&self, algorithm: DnsSecAlgorithm) -> bool {
+        algorithm.is_zone_signing() && !self.rejected_algorithms.contains(&algorithm)
+    }
+
+    #[inline]
+    pub fn allows_digest(&self, digest_type: DigestAlgorithm) -> bool {
+        !self.rejected_digests.contains(&digest_type)
+    }
+}
+
+/// The key and signature that an RRset was verified with.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct VerifiedBy {
+    pub signer: CDomainName,
+    pub key_tag: u16,
+    pub algorithm: DnsSecAlgorithm,
+}
+
+/// Calculates the key tag that RRSIG and DS records use to refer to the key.
+///
+/// https://datatracker.ietf.org/doc/html/rfc4034#appendix-B
+pub fn key_tag(dnskey: &DNSKEY) -> u16 {
+    let key = dnskey.key().to_bytes();
+    // RSA/MD5 keys use the last 16 bits of the modulus instead.
+    // https://datatracker.ietf.org/doc/html/rfc4034#appendix-B.1
+    if dnskey.algorithm() == DnsSecAlgorithm::RsaMd5 {
+        return match key {
+            [.., high, low] if key.len() >= 3 => u16::from_be_bytes([*high, *low]),
+            _ => 0,
+        };
+    }
+
+    let [flags_high, flags_low] = dnskey.flags().to_be_bytes();
+    let header = [flags_high, flags_low, dnskey.protocol(), dnskey.algorithm().code()];
+    let mut accumulator: u32 = 0;
+    for (index, byte) in header.iter().chain(key.iter()).enumerate() {
+        accumulator += if (index & 1) == 0 { u32::from(*byte) << 8 } else { u32::from(*byte) };
+    }
+    accumulator += (accumulator >> 16) & 0xFFFF;
+    (accumulator & 0xFFFF) as u16
+}
+
+/// Lowercases the domain names embedded in the RDATA of the types listed by RFC 4034 (as amended
+/// by RFC 6840, which removed NSEC). Other types are returned unchanged.
+///
+/// https://datatracker.ietf.org/doc/html/rfc4034#section-6.2
+/// https://datatracker.ietf.org/doc/html/rfc6840#section-5.1
+pub fn canonical_rdata(rdata: &RecordData) -> RecordData {
+    match rdata {
+        RecordData::NS(ns) => RecordData::NS(NS::new(ns.name_server_domain_name().as_lowercase())),
+        RecordData::MD(md) => RecordData::MD(MD::new(md.mail_agent_domain_name().as_lowercase())),
+        RecordData::MF(mf) => RecordData::MF(MF::new(mf.mail_forwarding_agent_domain_name().as_lowercase())),
+        RecordData::CNAME(cname) => RecordData::CNAME(CNAME::new(cname.primary_name().as_lowercase())),
+        RecordData::SOA(soa) => RecordData::SOA(SOA::new(
+            soa.main_domain_name().as_lowercase(),
+            soa.responsible_mailbox_domain_name().as_lowercase(),
+            *soa.serial(),
+            *soa.refresh(),
+            *soa.retry(),
+            *soa.expire(),
+            *soa.minimum(),
+        )),
+        RecordData::MB(mb) => RecordData::MB(MB::new(mb.mailbox_domain_name().as_lowercase())),
+        RecordData::MG(mg) => RecordData::MG(MG::new(mg.mailbox_group_domain_name().as_lowercase())),
+        RecordData::MR(mr) => RecordData::MR(MR::new(mr.mailbox_rename_domain_name().as_lowercase())),
+        RecordData::PTR(ptr) => RecordData::PTR(PTR::new(ptr.ptr_domain_name().as_lowercase())),
+        RecordData::MINFO(minfo) => RecordData::MINFO(MINFO::new(minfo.responsible_mailbox().as_lowercase(), minfo.error_mailbox().as_lowercase())),
+        RecordData::MX(mx) => RecordData::MX(MX::new(mx.preference(), mx.exchange().as_lowercase())),
+        RecordData::RP(rp) => RecordData::RP(RP::new(rp.mailbox().as_lowercase(), rp.txt_domain().map(DomainName::as_lowercase).unwrap_or_else(DomainName::new_root))),
+        RecordData::AFSDB(afsdb) => RecordData::AFSDB(AFSDB::new(*afsdb.subtype(), afsdb.hostname().as_lowercase())),
+        RecordData::RT(rt) => RecordData::RT(RT::new(rt.preference(), rt.intermediate_host().as_lowercase())),
+        RecordData::NSAP_PTR(nsap_ptr) => RecordData::NSAP_PTR(NSAP_PTR::new(nsap_ptr.owner().as_lowercase())),
+        RecordData::NAPTR(naptr) => RecordData::NAPTR(NAPTR::new(
+            naptr.order(),
+            naptr.preference(),
+            naptr.flags().clone(),
+            naptr.service().clone(),
+            naptr.regexp().clone(),
+            naptr.replacement().as_lowercase(),
+        )),
+        RecordData::SRV(srv) => RecordData::SRV(SRV::new(srv.priority(), srv.weight(), srv.port(), srv.target().as_lowercase())),
+        RecordData::DNAME(dname) => RecordData::DNAME(DNAME::new(dname.target_name().as_lowercase())),
+        rdata => rdata.clone(),
+    }
+}
+
+/// The owner name that the signature covers. If the RRSIG has fewer labels than the owner, the
+/// RRset was synthesized from a wildcard and the signature covers the wildcard name instead.
+///
+/// https://datatracker.ietf.org/doc/html/rfc4035#section-5.3.2
+fn signed_owner(owner: &CDomainName, rrsig: &RRSIG) -> Result<CDomainName, DnssecError> {
+    let owner = owner.as_lowercase();
+    let mut owner_labels = owner.label_count().saturating_sub(1);
+    if owner.case_sensitive_labels().next().is_some_and(|label| label.to_string() == "*") {
+        owner_labels -= 1;
+    }
+    let signed_labels = usize::from(rrsig.labels());
+    if signed_labels >= owner_labels {
+        return Ok(owner);
+    }
+    let suffix = owner.search_domains()
+        .nth(owner.label_count() - 1 - signed_labels)
+        .ok_or(DnssecError::BadSignature)?;
+    let wildcard = if suffix.is_root() { String::from("*.") } else { format!("*.{suffix}") };
+    CDomainName::from_utf8(&wildcard).map_err(|error| DnssecError::Encode(error.to_string()))
+}
+
+/// Builds the data that the RRSIG signs: its own RDATA without the signature, followed by each
+/// record of the RRset in canonical form and order.
+///
+/// https://datatracker.ietf.org/doc/html/rfc4034#section-3.1.8.1
+pub fn signed_data(rrsig: &RRSIG, rrset: &[ResourceRecord]) -> Result<Vec<u8>, DnssecError> {
+    let encode_error = |error: crate::serde::wire::write_wire::WriteWireError| DnssecError::Encode(error.to_string());
+    let first = rrset.first().ok_or(DnssecError::NoSignatures)?;
+    let owner = signed_owner(first.get_name(), rrsig)?;
+
+    let mut buffer = vec![0; u16::MAX as usize];
+    let mut records = rrset.iter()
+        .map(|record| {
+            let canonical = ResourceRecord::new(owner.clone(), record.get_rclass(), *rrsig.original_ttl(), canonical_rdata(record.get_rdata()));
+            let mut wire = WriteWire::from_bytes(&mut buffer);
+            canonical.to_wire_format(&mut wire, &mut None).map_err(encode_error)?;
+            let header_length = wire.current_len() - usize::from(canonical.get_rdata().serial_length());
+            Ok((header_length, wire.current().to_vec()))
+        })
+        .collect::<Result<Vec<_>, DnssecError>>()?;
+    // Records are sorted by their RDATA, treated as a left-justified unsigned octet sequence, and
+    // duplicates are removed.
+    records.sort_by(|(header_1, record_1), (header_2, record_2)| record_1[*header_1..].cmp(&record_2[*header_2..]));
+    records.dedup_by(|(header_1, record_1), (header_2, record_2)| record_1[*header_1..] == record_2[*header_2..]);
+
+    let unsigned = rrsig.without_signature();
+    let mut wire = WriteWire::from_bytes(&mut buffer);
+    unsigned.to_wire_format(&mut wire, &mut None).map_err(encode_error)?;
+    let mut data = wire.current().to_vec();
+    for (_, record) in records {
+        data.extend(record);
+    }
+    Ok(data)
+}
+
+/// Verifies the RRset against the zone's DNSKEY RRset. The RRset is valid if any one of its
+/// RRSIGs is a valid signature by any one of the keys.
+///
+/// This tolerates zones that are signed by more than one provider (RFC 8901). Each provider signs
+/// with its own keys and the DNSKEY RRset holds the keys of all of them, but a response only needs
+/// to carry the RRSIGs from one provider. Signatures whose key is not in the DNSKEY RRset, or that
+/// fail to verify, are skipped rather than failing the RRset. Since key tags are not unique, every
+/// key with the signature's tag, algorithm, and signer is tried. Nor is a signature required for
+/// every algorithm in the DNSKEY RRset.
+///
+/// https://datatracker.ietf.org/doc/html/rfc8901#section-5
+/// https://datatracker.ietf.org/doc/html/rfc6840#section-5.11
+#[inline]
+pub fn verify_rrset(rrset: &[ResourceRecord], rrsigs: &[ResourceRecord<RRSIG>], dnskeys: &[ResourceRecord<DNSKEY>], now: u32, verifier: &impl SignatureVerifier) -> Result<VerifiedBy, DnssecError> {
+    verify_rrset_with_policy(rrset, rrsigs, dnskeys, now, verifier, &AlgorithmPolicy::default())
+}
+
+/// Same as `verify_rrset()` but only trusts the signatures whose algorithm the policy allows.
+pub fn verify_rrset_with_policy(rrset: &[ResourceRecord], rrsigs: &[ResourceRecord<RRSIG>], dnskeys: &[ResourceRecord<DNSKEY>], now: u32, verifier: &impl SignatureVerifier, policy: &AlgorithmPolicy) -> Result<VerifiedBy, DnssecError> {
+    let Some(first) = rrset.first() else {
+        return Err(DnssecError::NoSignatures);
+    };
+    let owner = first.get_name();
+    let rtype = first.get_rtype();
+    let rclass: RClass = first.get_rclass();
+
+    let mut error = DnssecError::NoSignatures;
+    let mut record_error = |new_error: DnssecError| if new_error.progress() > error.progress() {
+        error = new_error;
+    };
+    let covering = rrsigs.iter().filter(|rrsig| rrsig.get_name().matches(owner)
+        && (rrsig.get_rclass() == rclass)
+        && (rrsig.get_rdata().type_covered() == rtype)
+    );
+    for rrsig_record in covering {
+        let rrsig = rrsig_record.get_rdata();
+        let signer = CDomainName::from(rrsig.signers_name());
+        if !signer.is_parent_domain_of(owner) {
+            record_error(DnssecError::NoMatchingKey);
+            continue;
+        }
+        let candidates = dnskeys.iter()
+            .filter(|key| key.get_name().matches(&signer)
+                && key.get_rdata().dns_zone_key()
+                && (key.get_rdata().protocol() == DNSKEY_PROTOCOL)
+                && (key.get_rdata().algorithm() == rrsig.algorithm())
+                && (key_tag(key.get_rdata()) == rrsig.key_tag())
+            )
+            .collect::<Vec<_>>();
+        if candidates.is_empty() {
+            record_error(DnssecError::NoMatchingKey);
+            continue;
+        }
+        if !rrsig.is_current(now) {
+            record_error(DnssecError::SignatureNotCurrent);
+            continue;
+        }
+        if !policy.allows_algorithm(rrsig.algorithm()) {
+            record_error(DnssecError::RejectedAlgorithm(rrsig.algorithm()));
+            continue;
+        }
+        if !verifier.supports(rrsig.algorithm()) {
+            record_error(DnssecError::UnsupportedAlgorithm(rrsig.algorithm()));
+            continue;
+        }
+        let data = match signed_data(rrsig, rrset) {
+            Ok(data) => data,
+            Err(new_error) => {
+                record_error(new_error);
+                continue;
+            },
+        };
+        let verified = candidates.iter()
+            .any(|key| verifier.verify(rrsig.algorithm(), key.get_rdata().key().to_bytes(), &data, rrsig.signature().to_bytes()));
+        if verified {
+            return Ok(VerifiedBy { signer, key_tag: rrsig.key_tag(), algorithm: rrsig.algorithm() });
+        }
+        record_error(DnssecError::BadSignature);
+    }
+    Err(error)
+}
+
+/// The data that a DS record's digest covers: the key's owner name in canonical form followed by
+/// the DNSKEY RDATA.
+///
+/// https://datatracker.ietf.org/doc/html/rfc4034#section-5.1.4
+pub fn ds_digest_data(dnskey: &ResourceRecord<DNSKEY>) -> Result<Vec<u8>, DnssecError> {
+    let encode_error = |error: crate::serde::wire::write_wire::WriteWireError| DnssecError::Encode(error.to_string());
+    let mut buffer = vec![0; u16::MAX as usize];
+    let mut wire = WriteWire::from_bytes(&mut buffer);
+    dnskey.get_name().as_lowercase().to_wire_format(&mut wire, &mut None).map_err(encode_error)?;
+    dnskey.get_rdata().to_wire_format(&mut wire, &mut None).map_err(encode_error)?;
+    Ok(wire.current().to_vec())
+}
+
+/// Finds the keys in the DNSKEY RRset that the parent zone's DS RRset vouches for. Only the DS
+/// records with the strongest digest type that the policy allows are used, so that removing
+/// them cannot downgrade the validation to a weaker digest. If the strongest type is one that the
+/// digester does not support, the DS RRset cannot be used at all.
+///
+/// Like `verify_rrset()`, every key with a matching tag and algorithm is tried and the DS records
+/// that do not match any key are skipped, so multi-signer zones with several KSKs work.
+///
+/// https://datatracker.ietf.org/doc/html/rfc4509#section-3
+/// https://datatracker.ietf.org/doc/html/rfc4035#section-5.2
+pub fn verify_ds<'a>(ds_rrset: &[ResourceRecord<DS>], dnskeys: &'a [ResourceRecord<DNSKEY>], digester: &impl Digester, policy: &AlgorithmPolicy) -> Result<Vec<&'a ResourceRecord<DNSKEY>>, DnssecError> {
+    let usable_ds = ds_rrset.iter()
+        .filter(|ds| ds.get_rdata().algorithm().is_zone_signing())
+        .collect::<Vec<_>>();
+    if usable_ds.is_empty() {
+        return Err(DnssecError::NoSignatures);
+    }
+    let Some(digest_type) = usable_ds.iter()
+        .map(|ds| ds.get_rdata().digest_type())
+        .filter(|digest_type| policy.allows_digest(*digest_type))
+        .max_by_key(DigestAlgorithm::strength)
+    else {
+        return Err(DnssecError::RejectedDigest(usable_ds[0].get_rdata().digest_type()));
+    };
+    if !digester.supports(digest_type) {
+        return Err(DnssecError::UnsupportedDigest(digest_type));
+    }
+
+    let mut error = DnssecError::NoMatchingKey;
+    let mut matched = Vec::new();
+    for dnskey in dnskeys {
+        let key = dnskey.get_rdata();
+        if !key.dns_zone_key() || (key.protocol() != DNSKEY_PROTOCOL) {
+            continue;
+        }
+        let tag = key_tag(key);
+        let candidates = usable_ds.iter()
+            .map(|ds| ds.get_rdata())
+            .filter(|ds| (ds.digest_type() == digest_type) && (ds.algorithm() == key.algorithm()) && (ds.key_tag() == tag))
+            .collect::<Vec<_>>();
+        if candidates.is_empty() {
+            continue;
+        }
+        if !policy.allows_algorithm(key.algorithm()) {
+            error = DnssecError::RejectedAlgorithm(key.algorithm());
+            continue;
+        }
+        let Some(digest) = digester.digest(digest_type, &ds_digest_data(dnskey)?) else {
+            return Err(DnssecError::UnsupportedDigest(digest_type));
+        };
+        if candidates.iter().any(|ds| ds.digest().to_bytes() == digest.as_slice()) {
+            matched.push(dnskey);
+        } else if error.progress() < DnssecError::BadSignature.progress() {
+            error = DnssecError::BadSignature;
+        }
+    }
+    match matched.is_empty() {
+        true => Err(error),
+        false => Ok(matched),
+    }
+}
+
+#[cfg(test)]
+mod dnssec_tests {
+    use crate::{resource_record::{digest_alg::DigestAlgorithm, dnssec_alg::DnsSecAlgorithm, rclass::RClass, resource_record::{RecordData, ResourceRecord}, rtype::RType, time::Time, types::{a::A, dnskey::DNSKEY, ds::DS, rrsig::RRSIG}}, types::{base16::Base16, base64::Base64, base_conversions::BaseConversions, c_domain_name::CDomainName, domain_name::DomainName}};
+
+    use super::{key_tag, signed_data, verify_ds, verify_rrset, verify_rrset_with_policy, AlgorithmPolicy, Digester, DnssecError, SignatureVerifier};
+
+    const ZONE_KEY: u16 = 256;
+    const NOW: u32 = 1_000_000;
+
+    /// Stands in for real cryptography. A "signature" is a checksum of the key and the data, so
+    /// a signature only verifies with the key that made it and only for the same data.
+    struct ChecksumVerifier;
+
+    fn checksum_signature(public_key: &[u8], signed_data: &[u8]) -> Vec<u8> {
+        let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+        for byte in public_key.iter().chain(signed_data.iter()) {
+            hash = (hash ^ u64::from(*byte)).wrapping_mul(0x0100_0000_01b3);
+        }
+        hash.to_be_bytes().to_vec()
+    }
+
+    impl SignatureVerifier for ChecksumVerifier {
+        fn supports(&self, algorithm: DnsSecAlgorithm) -> bool {
+            matches!(algorithm, DnsSecAlgorithm::EcdsaP256Sha256 | DnsSecAlgorithm::Ed25519)
+        }
+
+        fn verify(&self, _algorithm: DnsSecAlgorithm, public_key: &[u8], signed_data: &[u8], signature: &[u8]) -> bool {
+            checksum_signature(public_key, signed_data) == signature
+        }
+    }
+
+    /// A "digest" is the checksum of the data, repeated to the length of the real digest.
+    struct ChecksumDigester;
+
+    impl Digester for ChecksumDigester {
+        fn supports(&self, digest_type: DigestAlgorithm) -> bool {
+            matches!(digest_type, DigestAlgorithm::Sha1 | DigestAlgorithm::Sha256 | DigestAlgorithm::Sha384)
+        }
+
+        fn digest(&self, digest_type: DigestAlgorithm, data: &[u8]) -> Option<Vec<u8>> {
+            let length = match digest_type {
+                DigestAlgorithm::Sha1 => 20,
+                DigestAlgorithm::Sha256 => 32,
+                DigestAlgorithm::Sha384 => 48,
+                _ => return None,
+            };
+            Some(checksum_signature(&[], data).into_iter().cycle().take(length).collect())
+        }
+    }
+
+    fn name(name: &str) -> CDomainName {
+        CDomainName::from_utf8(name).unwrap()
+    }
+
+    fn dnskey(algorithm: DnsSecAlgorithm, key: &[u8]) -> ResourceRecord<DNSKEY> {
+        ResourceRecord::new(name("example.com."), RClass::Internet, Time::from_secs(3600), DNSKEY::new(ZONE_KEY, algorithm, Base64::from_bytes(key)))
+    }
+
+    fn rrset() -> Vec<ResourceRecord> {
+        [[192, 0, 2, 2], [192, 0, 2, 1]].into_iter()
+            .map(|address| ResourceRecord::new(name("www.Example.com."), RClass::Internet, Time::from_secs(300), RecordData::A(A::new(address.into()))))
+            .collect()
+    }
+
+    fn sign(rrset: &[ResourceRecord], key: &ResourceRecord<DNSKEY>) -> ResourceRecord<RRSIG> {
+        let unsigned = RRSIG::new(
+            RType::A,
+            key.get_rdata().algorithm(),
+            2,
+            Time::from_secs(300),
+            NOW + 3600,
+            NOW - 3600,
+            key_tag(key.get_rdata()),
+            DomainName::from_utf8("example.com.").unwrap(),
+            Base64::from_bytes(&[]),
+        );
+        let data = signed_data(&unsigned, rrset).unwrap();
+        let signature = checksum_signature(key.get_rdata().key().to_bytes(), &data);
+        let rrsig = RRSIG::new(
+            unsigned.type_covered(),
+            unsigned.algorithm(),
+            unsigned.labels(),
+            *unsigned.original_ttl(),
+            unsigned.signature_expiration(),
+            unsigned.signature_inception(),
+            unsigned.key_tag(),
+            unsigned.signers_name().clone(),
+            Base64::from_bytes(&signature),
+        );
+        ResourceRecord::new(rrset[0].get_name().clone(), RClass::Internet, Time::from_secs(300), rrsig)
+    }
+
+    #[test]
+    fn rfc_4034_key_tag() {
+        // https://datatracker.ietf.org/doc/html/rfc4034#section-5.4
+        let dnskey = DNSKEY::new(
+            256,
+            DnsSecAlgorithm::RsaSha1,
+            Base64::from_utf8("AQOeiiR0GOMYkDshWoSKz9XzfwJr1AYtsmx3TGkJaNXVbfi/2pHm822aJ5iI9BMzNXxeYCmZDRD99WYwYqUSdjMmmAphXdvxegXd/M5+X7OrzKBaMbCVdFLUUh6DhweJBjEVv5f2wwjM9XzcnOf+EPbtG9DMBmADjFDc2w/rljwvFw==").unwrap(),
+        );
+        assert_eq!(key_tag(&dnskey), 60485);
+    }
+
+    #[test]
+    fn single_signer() {
+        let key = dnskey(DnsSecAlgorithm::EcdsaP256Sha256, b"provider-a-zsk");
+        let rrset = rrset();
+        let rrsig = sign(&rrset, &key);
+        let verified = verify_rrset(&rrset, &[rrsig], &[key.clone()], NOW, &ChecksumVerifier).unwrap();
+        assert_eq!(verified.key_tag, key_tag(key.get_rdata()));
+    }
+
+    #[test]
+    fn signed_data_ignores_record_order_and_case() {
+        let key = dnskey(DnsSecAlgorithm::EcdsaP256Sha256, b"provider-a-zsk");
+        let rrset = rrset();
+        let rrsig = sign(&rrset, &key);
+        let reordered = rrset.iter().rev()
+            .map(|record| ResourceRecord::new(name("WWW.example.COM."), record.get_rclass(), Time::from_secs(12), record.get_rdata().clone()))
+            .collect::<Vec<_>>();
+        assert_eq!(signed_data(rrsig.get_rdata(), &reordered).unwrap(), signed_data(rrsig.get_rdata(), &rrset).unwrap());
+        assert!(verify_rrset(&reordered, &[rrsig], &[key], NOW, &ChecksumVerifier).is_ok());
+    }
+
+    /// Model 2 from RFC 8901: each provider signs the zone with its own ZSK. The DNSKEY RRset
+    /// holds the keys of both providers, but each provider only serves its own RRSIGs.
+    #[test]
+    fn multi_signer_partial_coverage() {
+        let provider_a = dnskey(DnsSecAlgorithm::EcdsaP256Sha256, b"provider-a-zsk");
+        let provider_b = dnskey(DnsSecAlgorithm::EcdsaP256Sha256, b"provider-b-zsk");
+        let dnskeys = [provider_a.clone(), provider_b.clone()];
+        let rrset = rrset();
+
+        let from_a = sign(&rrset, &provider_a);
+        let from_b = sign(&rrset, &provider_b);
+        assert!(verify_rrset(&rrset, &[from_a.clone()], &dnskeys, NOW, &ChecksumVerifier).is_ok());
+        assert_eq!(verify_rrset(&rrset, &[from_b.clone()], &dnskeys, NOW, &ChecksumVerifier).unwrap().key_tag, key_tag(provider_b.get_rdata()));
+        assert!(verify_rrset(&rrset, &[from_a, from_b], &dnskeys, NOW, &ChecksumVerifier).is_ok());
+    }
+
+    /// While a provider is being added, a resolver may see RRSIGs from a provider whose key it
+    /// does not have yet, or signatures that do not verify. As long as one signature verifies
+    /// with a key in the DNSKEY RRset, the RRset is valid.
+    #[test]
+    fn multi_signer_skips_unknown_and_bad_signatures() {
+        let provider_a = dnskey(DnsSecAlgorithm::EcdsaP256Sha256, b"provider-a-zsk");
+        let provider_b = dnskey(DnsSecAlgorithm::EcdsaP256Sha256, b"provider-b-zsk");
+        let rrset = rrset();
+
+        let unknown = sign(&rrset, &provider_b);
+        let mut tampered_rrset = rrset.clone();
+        tampered_rrset.pop();
+        let bad = sign(&tampered_rrset, &provider_a);
+        let good = sign(&rrset, &provider_a);
+
+        let dnskeys = [provider_a];
+        assert_eq!(verify_rrset(&rrset, &[unknown.clone()], &dnskeys, NOW, &ChecksumVerifier), Err(DnssecError::NoMatchingKey));
+        assert_eq!(verify_rrset(&rrset, &[unknown.clone(), bad.clone()], &dnskeys, NOW, &ChecksumVerifier), Err(DnssecError::BadSignature));
+        assert!(verify_rrset(&rrset, &[unknown, bad, good], &dnskeys, NOW, &ChecksumVerifier).is_ok());
+    }
+
+    /// The providers may use different algorithms. A signature is not needed for every algorithm
+    /// in the DNSKEY RRset.
+    #[test]
+    fn multi_signer_mixed_algorithms() {
+        let provider_a = dnskey(DnsSecAlgorithm::EcdsaP256Sha256, b"provider-a-zsk");
+        let provider_b = dnskey(DnsSecAlgorithm::Ed25519, b"provider-b-zsk");
+        let dnskeys = [provider_a, provider_b.clone()];
+        let rrset = rrset();
+        let verified = verify_rrset(&rrset, &[sign(&rrset, &provider_b)], &dnskeys, NOW, &ChecksumVerifier).unwrap();
+        assert_eq!(verified.algorithm, DnsSecAlgorithm::Ed25519);
+    }
+
+    /// Key tags are only 16 bits, so two providers' keys can share one. Both keys are tried.
+    #[test]
+    fn multi_signer_key_tag_collision() {
+        let provider_a = dnskey(DnsSecAlgorithm::EcdsaP256Sha256, b"provider-a-zsk");
+        let target_tag = key_tag(provider_a.get_rdata());
+        let provider_b = (0_u32..)
+            .map(|counter| dnskey(DnsSecAlgorithm::EcdsaP256Sha256, &[b"provider-b-zsk".as_slice(), &counter.to_be_bytes()].concat()))
+            .find(|key| key_tag(key.get_rdata()) == target_tag)
+            .unwrap();
+        assert_ne!(provider_a, provider_b);
+
+        let rrset = rrset();
+        let rrsig = sign(&rrset, &provider_b);
+        assert!(verify_rrset(&rrset, &[rrsig.clone()], &[provider_a.clone(), provider_b.clone()], NOW, &ChecksumVerifier).is_ok());
+        assert!(verify_rrset(&rrset, &[rrsig], &[provider_b, provider_a], NOW, &ChecksumVerifier).is_ok());
+    }
+
+    #[test]
+    fn expired_and_unsupported_signatures() {
+        let current = dnskey(DnsSecAlgorithm::EcdsaP256Sha256, b"provider-a-zsk");
+        let unsupported = dnskey(DnsSecAlgorithm::RsaSha1, b"provider-b-zsk");
+        let rrset = rrset();
+        let dnskeys = [current.clone(), unsupported.clone()];
+        assert_eq!(verify_rrset(&rrset, &[sign(&rrset, &current)], &dnskeys, NOW + 7200, &ChecksumVerifier), Err(DnssecError::SignatureNotCurrent));
+        assert_eq!(
+            verify_rrset_with_policy(&rrset, &[sign(&rrset, &unsupported)], &dnskeys, NOW, &ChecksumVerifier, &AlgorithmPolicy::permissive()),
+            Err(DnssecError::UnsupportedAlgorithm(DnsSecAlgorithm::RsaSha1))
+        );
+        assert_eq!(verify_rrset(&rrset, &[], &dnskeys, NOW, &ChecksumVerifier), Err(DnssecError::NoSignatures));
+    }
+
+    #[test]
+    fn weak_algorithms_are_rejected_by_default() {
+        let weak = dnskey(DnsSecAlgorithm::RsaSha1, b"provider-a-zsk");
+        let strong = dnskey(DnsSecAlgorithm::Ed25519, b"provider-b-zsk");
+        let rrset = rrset();
+        let dnskeys = [weak.clone(), strong.clone()];
+        let weak_rrsig = sign(&rrset, &weak);
+        assert_eq!(verify_rrset(&rrset, &[weak_rrsig.clone()], &dnskeys, NOW, &ChecksumVerifier), Err(DnssecError::RejectedAlgorithm(DnsSecAlgorithm::RsaSha1)));
+        assert!(verify_rrset(&rrset, &[weak_rrsig.clone(), sign(&rrset, &strong)], &dnskeys, NOW, &ChecksumVerifier).is_ok());
+
+        let mut policy = AlgorithmPolicy::default();
+        policy.reject_algorithm(DnsSecAlgorithm::Ed25519);
+        assert_eq!(verify_rrset_with_policy(&rrset, &[sign(&rrset, &strong)], &dnskeys, NOW, &ChecksumVerifier, &policy), Err(DnssecError::RejectedAlgorithm(DnsSecAlgorithm::Ed25519)));
+        policy.allow_algorithm(DnsSecAlgorithm::Ed25519);
+        assert!(verify_rrset_with_policy(&rrset, &[sign(&rrset, &strong)], &dnskeys, NOW, &ChecksumVerifier, &policy).is_ok());
+    }
+
+    fn ds(key: &ResourceRecord<DNSKEY>, digest_type: DigestAlgorithm) -> ResourceRecord<DS> {
+        let digest = ChecksumDigester.digest(digest_type, &super::ds_digest_data(key).unwrap()).unwrap();
+        let ds = DS::new(key_tag(key.get_rdata()), key.get_rdata().algorithm(), digest_type, Base16::from_bytes(&digest));
+        ResourceRecord::new(key.get_name().clone(), RClass::Internet, Time::from_secs(3600), ds)
+    }
+
+    #[test]
+    fn ds_uses_strongest_digest() {
+        let ksk = dnskey(DnsSecAlgorithm::EcdsaP384Sha384, b"provider-a-ksk");
+        let other = dnskey(DnsSecAlgorithm::Ed448, b"provider-b-ksk");
+        let dnskeys = [ksk.clone(), other.clone()];
+        let policy = AlgorithmPolicy::default();
+
+        let matched = verify_ds(&[ds(&ksk, DigestAlgorithm::Sha256), ds(&other, DigestAlgorithm::Sha384)], &dnskeys, &ChecksumDigester, &policy).unwrap();
+        assert_eq!(matched, vec![&other]);
+
+        // A SHA-1 DS is ignored once there is a stronger one, even if it is the only one that
+        // matches.
+        let mut bad_sha256 = ds(&ksk, DigestAlgorithm::Sha256);
+        bad_sha256 = ResourceRecord::new(bad_sha256.get_name().clone(), RClass::Internet, Time::from_secs(3600), DS::new(bad_sha256.get_rdata().key_tag(), bad_sha256.get_rdata().algorithm(), DigestAlgorithm::Sha256, Base16::from_bytes(&[0; 32])));
+        assert_eq!(verify_ds(&[ds(&ksk, DigestAlgorithm::Sha1), bad_sha256], &dnskeys, &ChecksumDigester, &AlgorithmPolicy::permissive()), Err(DnssecError::BadSignature));
+
+        assert_eq!(verify_ds(&[ds(&ksk, DigestAlgorithm::Sha1)], &dnskeys, &ChecksumDigester, &policy), Err(DnssecError::RejectedDigest(DigestAlgorithm::Sha1)));
+        assert_eq!(verify_ds(&[ds(&ksk, DigestAlgorithm::Sha1)], &dnskeys, &ChecksumDigester, &AlgorithmPolicy::permissive()).unwrap(), vec![&ksk]);
+    }
+}
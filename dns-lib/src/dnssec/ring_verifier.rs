@@ -0,0 +1,189 @@
+use ring::{digest, signature::{self, RsaPublicKeyComponents, UnparsedPublicKey}};
+
+use crate::resource_record::{digest_alg::DigestAlgorithm, dnssec_alg::DnsSecAlgorithm};
+
+use super::{Digester, SignatureVerifier};
+
+/// Verifies signatures and calculates DS digests with `ring`.
+///
+/// Supports RSA/SHA-1, RSA/SHA-256 and RSA/SHA-512 (with keys of at least 1024 bits), ECDSA P-256
+/// and P-384, and Ed25519. `ring` does not implement Ed448, so signatures made with it are
+/// reported as unsupported. Whether the SHA-1 algorithms are trusted at all is up to the
+/// `AlgorithmPolicy`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct RingVerifier;
+
+/// Splits an RSA key into its exponent and modulus.
+///
+/// https://datatracker.ietf.org/doc/html/rfc3110#section-2
+fn rsa_components(public_key: &[u8]) -> Option<RsaPublicKeyComponents<&[u8]>> {
+    let (exponent_length, rest) = match public_key {
+        [0, high, low, rest @ ..] => (usize::from(u16::from_be_bytes([*high, *low])), rest),
+        [length, rest @ ..] => (usize::from(*length), rest),
+        [] => return None,
+    };
+    if (exponent_length == 0) || (rest.len() <= exponent_length) {
+        return None;
+    }
+    let (e, n) = rest.split_at(exponent_length);
+    Some(RsaPublicKeyComponents { n, e })
+}
+
+/// ECDSA keys are stored as the X and Y coordinates but `ring` expects the uncompressed point
+/// encoding, which starts with 0x04.
+///
+/// https://datatracker.ietf.org/doc/html/rfc6605#section-4
+#[inline]
+fn ecdsa_point(public_key: &[u8], coordinate_length: usize) -> Option<Vec<u8>> {
+    if public_key.len() != coordinate_length * 2 {
+        return None;
+    }
+    let mut point = Vec::with_capacity(public_key.len() + 1);
+    point.push(0x04);
+    point.extend_from_slice(public_key);
+    Some(point)
+}
+
+impl SignatureVerifier for RingVerifier {
+    fn supports(&self, algorithm: DnsSecAlgorithm) -> bool {
+        matches!(algorithm,
+            DnsSecAlgorithm::RsaSha1
+            | DnsSecAlgorithm::RsaSha1Nsec3Sha1
+            | DnsSecAlgorithm::RsaSha256
+            | DnsSecAlgorithm::RsaSha512
+            | DnsSecAlgorithm::EcdsaP256Sha256
+            | DnsSecAlgorithm::EcdsaP384Sha384
+            | DnsSecAlgorithm::Ed25519
+        )
+    }
+
+    fn verify(&self, algorithm: DnsSecAlgorithm, public_key: &[u8], signed_data: &[u8], signature: &[u8]) -> bool {
+        let rsa_parameters = match algorithm {
+            DnsSecAlgorithm::RsaSha1
+            | DnsSecAlgorithm::RsaSha1Nsec3Sha1 => Some(&signature::RSA_PKCS1_1024_8192_SHA1_FOR_LEGACY_USE_ONLY),
+            DnsSecAlgorithm::RsaSha256 => Some(&signature::RSA_PKCS1_1024_8192_SHA256_FOR_LEGACY_USE_ONLY),
+            DnsSecAlgorithm::RsaSha512 => Some(&signature::RSA_PKCS1_1024_8192_SHA512_FOR_LEGACY_USE_ONLY),
+            _ => None,
+        };
+        if let Some(parameters) = rsa_parameters {
+            return rsa_components(public_key)
+                .is_some_and(|components| components.verify(parameters, signed_data, signature).is_ok());
+        }
+        match algorithm {
+            DnsSecAlgorithm::EcdsaP256Sha256 => ecdsa_point(public_key, 32)
+                .is_some_and(|point| UnparsedPublicKey::new(&signature::ECDSA_P256_SHA256_FIXED, point).verify(signed_data, signature).is_ok()),
+            DnsSecAlgorithm::EcdsaP384Sha384 => ecdsa_point(public_key, 48)
+                .is_some_and(|point| UnparsedPublicKey::new(&signature::ECDSA_P384_SHA384_FIXED, point).verify(signed_data, signature).is_ok()),
+            DnsSecAlgorithm::Ed25519 => UnparsedPublicKey::new(&signature::ED25519, public_key).verify(signed_data, signature).is_ok(),
+            _ => false,
+        }
+    }
+}
+
+impl Digester for RingVerifier {
+    fn supports(&self, digest_type: DigestAlgorithm) -> bool {
+        matches!(digest_type, DigestAlgorithm::Sha1 | DigestAlgorithm::Sha256 | DigestAlgorithm::Sha384)
+    }
+
+    fn digest(&self, digest_type: DigestAlgorithm, data: &[u8]) -> Option<Vec<u8>> {
+        let algorithm = match digest_type {
+            DigestAlgorithm::Sha1 => &digest::SHA1_FOR_LEGACY_USE_ONLY,
+            DigestAlgorithm::Sha256 => &digest::SHA256,
+            DigestAlgorithm::Sha384 => &digest::SHA384,
+            _ => return None,
+        };
+        Some(digest::digest(algorithm, data).as_ref().to_vec())
+    }
+}
+
+#[cfg(test)]
+mod ring_verifier_tests {
+    use ring::{rand::SystemRandom, signature::{EcdsaKeyPair, Ed25519KeyPair, KeyPair, ECDSA_P256_SHA256_FIXED_SIGNING, ECDSA_P384_SHA384_FIXED_SIGNING}};
+
+    use crate::{dnssec::{key_tag, signed_data, verify_ds, verify_rrset, AlgorithmPolicy, Digester, DnssecError, SignatureVerifier}, resource_record::{digest_alg::DigestAlgorithm, dnssec_alg::DnsSecAlgorithm, rclass::RClass, resource_record::{RecordData, ResourceRecord}, rtype::RType, time::Time, types::{aaaa::AAAA, dnskey::DNSKEY, ds::DS, rrsig::RRSIG}}, types::{base16::Base16, base64::Base64, base_conversions::BaseConversions, c_domain_name::CDomainName, domain_name::DomainName}};
+
+    use super::RingVerifier;
+
+    const NOW: u32 = 1_000_000;
+
+    fn rrset() -> Vec<ResourceRecord> {
+        vec![ResourceRecord::new(CDomainName::from_utf8("www.example.com.").unwrap(), RClass::Internet, Time::from_secs(300), RecordData::AAAA(AAAA::new("2001:db8::1".parse().unwrap())))]
+    }
+
+    fn sign(rrset: &[ResourceRecord], key: &DNSKEY, sign: impl Fn(&[u8]) -> Vec<u8>) -> ResourceRecord<RRSIG> {
+        let unsigned = RRSIG::new(RType::AAAA, key.algorithm(), 3, Time::from_secs(300), NOW + 3600, NOW - 3600, key_tag(key), DomainName::from_utf8("example.com.").unwrap(), Base64::from_bytes(&[]));
+        let signature = sign(&signed_data(&unsigned, rrset).unwrap());
+        let rrsig = RRSIG::new(RType::AAAA, key.algorithm(), 3, Time::from_secs(300), NOW + 3600, NOW - 3600, key_tag(key), DomainName::from_utf8("example.com.").unwrap(), Base64::from_bytes(&signature));
+        ResourceRecord::new(rrset[0].get_name().clone(), RClass::Internet, Time::from_secs(300), rrsig)
+    }
+
+    fn zone_key(key: DNSKEY) -> ResourceRecord<DNSKEY> {
+        ResourceRecord::new(CDomainName::from_utf8("example.com.").unwrap(), RClass::Internet, Time::from_secs(3600), key)
+    }
+
+    #[test]
+    fn ed25519() {
+        let random = SystemRandom::new();
+        let key_pair = Ed25519KeyPair::from_pkcs8(Ed25519KeyPair::generate_pkcs8(&random).unwrap().as_ref()).unwrap();
+        let dnskey = zone_key(DNSKEY::new(256, DnsSecAlgorithm::Ed25519, Base64::from_bytes(key_pair.public_key().as_ref())));
+        let rrset = rrset();
+        let rrsig = sign(&rrset, dnskey.get_rdata(), |data| key_pair.sign(data).as_ref().to_vec());
+        assert!(verify_rrset(&rrset, &[rrsig], &[dnskey], NOW, &RingVerifier).is_ok());
+    }
+
+    #[test]
+    fn ecdsa_p256_and_p384() {
+        let random = SystemRandom::new();
+        for (algorithm, signing) in [(DnsSecAlgorithm::EcdsaP256Sha256, &ECDSA_P256_SHA256_FIXED_SIGNING), (DnsSecAlgorithm::EcdsaP384Sha384, &ECDSA_P384_SHA384_FIXED_SIGNING)] {
+            let pkcs8 = EcdsaKeyPair::generate_pkcs8(signing, &random).unwrap();
+            let key_pair = EcdsaKeyPair::from_pkcs8(signing, pkcs8.as_ref(), &random).unwrap();
+            // Drop the 0x04 that marks an uncompressed point.
+            let dnskey = zone_key(DNSKEY::new(256, algorithm, Base64::from_bytes(&key_pair.public_key().as_ref()[1..])));
+            let rrset = rrset();
+            let rrsig = sign(&rrset, dnskey.get_rdata(), |data| key_pair.sign(&random, data).unwrap().as_ref().to_vec());
+            assert_eq!(verify_rrset(&rrset, &[rrsig.clone()], &[dnskey.clone()], NOW, &RingVerifier).unwrap().algorithm, algorithm);
+
+            let mut tampered = rrset.clone();
+            tampered[0] = ResourceRecord::new(tampered[0].get_name().clone(), RClass::Internet, Time::from_secs(300), RecordData::AAAA(AAAA::new("2001:db8::2".parse().unwrap())));
+            assert_eq!(verify_rrset(&tampered, &[rrsig], &[dnskey], NOW, &RingVerifier), Err(DnssecError::BadSignature));
+        }
+    }
+
+    #[test]
+    fn ed448_is_unsupported() {
+        assert!(!SignatureVerifier::supports(&RingVerifier, DnsSecAlgorithm::Ed448));
+        let dnskey = zone_key(DNSKEY::new(256, DnsSecAlgorithm::Ed448, Base64::from_bytes(&[7; 57])));
+        let rrset = rrset();
+        let rrsig = sign(&rrset, dnskey.get_rdata(), |_| vec![0; 114]);
+        assert_eq!(verify_rrset(&rrset, &[rrsig], &[dnskey], NOW, &RingVerifier), Err(DnssecError::UnsupportedAlgorithm(DnsSecAlgorithm::Ed448)));
+    }
+
+    /// https://datatracker.ietf.org/doc/html/rfc4034#section-5.4
+    /// https://datatracker.ietf.org/doc/html/rfc4509#section-2.3
+    #[test]
+    fn rfc_ds_digests() {
+        let dnskey = ResourceRecord::new(
+            CDomainName::from_utf8("dskey.example.com.").unwrap(),
+            RClass::Internet,
+            Time::from_secs(86400),
+            DNSKEY::new(
+                256,
+                DnsSecAlgorithm::RsaSha1,
+                Base64::from_utf8("AQOeiiR0GOMYkDshWoSKz9XzfwJr1AYtsmx3TGkJaNXVbfi/2pHm822aJ5iI9BMzNXxeYCmZDRD99WYwYqUSdjMmmAphXdvxegXd/M5+X7OrzKBaMbCVdFLUUh6DhweJBjEVv5f2wwjM9XzcnOf+EPbtG9DMBmADjFDc2w/rljwvFw==").unwrap(),
+            ),
+        );
+        let data = crate::dnssec::ds_digest_data(&dnskey).unwrap();
+        assert_eq!(RingVerifier.digest(DigestAlgorithm::Sha1, &data).unwrap(), Base16::from_utf8("2BB183AF5F22588179A53B0A98631FAD1A292118").unwrap().to_bytes());
+        assert_eq!(RingVerifier.digest(DigestAlgorithm::Sha256, &data).unwrap(), Base16::from_utf8("D4B7D520E7BB5F0F67674A0CCEB1E3E0614B93C4F9E99B8383F6A1E4469DA50A").unwrap().to_bytes());
+
+        let ds = |digest_type, digest: &str| ResourceRecord::new(dnskey.get_name().clone(), RClass::Internet, Time::from_secs(86400), DS::new(60485, DnsSecAlgorithm::RsaSha1, digest_type, Base16::from_utf8(digest).unwrap()));
+        let ds_rrset = [
+            ds(DigestAlgorithm::Sha1, "2BB183AF5F22588179A53B0A98631FAD1A292118"),
+            ds(DigestAlgorithm::Sha256, "D4B7D520E7BB5F0F67674A0CCEB1E3E0614B93C4F9E99B8383F6A1E4469DA50A"),
+        ];
+        let mut policy = AlgorithmPolicy::default();
+        assert_eq!(verify_ds(&ds_rrset, std::slice::from_ref(&dnskey), &RingVerifier, &policy), Err(DnssecError::RejectedAlgorithm(DnsSecAlgorithm::RsaSha1)));
+        policy.allow_algorithm(DnsSecAlgorithm::RsaSha1);
+        assert_eq!(verify_ds(&ds_rrset, std::slice::from_ref(&dnskey), &RingVerifier, &policy).unwrap(), vec![&dnskey]);
+    }
+}
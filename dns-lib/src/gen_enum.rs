@@ -116,6 +116,15 @@ macro_rules! impl_enum_mnemonic {
                     $(Self::$item_name => $item_mnemonic.to_string(),)+
                 }
             }
+
+            /// Writes the mnemonic without allocating a `String`.
+            #[inline]
+            pub fn write_mnemonic<W: std::fmt::Write + ?Sized>(&self, out: &mut W) -> std::fmt::Result {
+                match self {
+                    Self::Unknown(code) => write!(out, "{code}"),
+                    $(Self::$item_name => out.write_str($item_mnemonic),)+
+                }
+            }
         }
     };
     ($enum_name:ident, $int_ty:ty, ($(($item_name:ident, $item_mnemonic:literal)),+$(,)?), $wildcard:literal) => {
@@ -127,6 +136,15 @@ macro_rules! impl_enum_mnemonic {
                     $(Self::$item_name => $item_mnemonic.to_string(),)+
                 }
             }
+
+            /// Writes the mnemonic without allocating a `String`.
+            #[inline]
+            pub fn write_mnemonic<W: std::fmt::Write + ?Sized>(&self, out: &mut W) -> std::fmt::Result {
+                match self {
+                    Self::Unknown(code) => write!(out, "{}{code}", $wildcard),
+                    $(Self::$item_name => out.write_str($item_mnemonic),)+
+                }
+            }
         }
     };
 }
@@ -136,7 +154,7 @@ macro_rules! impl_enum_display {
         impl std::fmt::Display for $enum_name {
             #[inline]
             fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-                write!(f, "{}", self.mnemonic())
+                self.write_mnemonic(f)
             }
         }
     };
@@ -242,6 +260,11 @@ macro_rules! impl_enum_to_presentation {
             fn to_presentation_format(&self, out_buffer: &mut std::vec::Vec<std::string::String>) {
                 out_buffer.push(self.code().to_string())
             }
+
+            #[inline]
+            fn write_presentation_tokens<W: std::fmt::Write + ?Sized>(&self, out: &mut $crate::serde::presentation::to_presentation::PresentationWriter<'_, W>) -> std::fmt::Result {
+                out.write_display(&self.code())
+            }
         }
     };
     ($enum_name:ident, $int_ty:ty, mnemonic_presentation) => {
@@ -250,6 +273,11 @@ macro_rules! impl_enum_to_presentation {
             fn to_presentation_format(&self, out_buffer: &mut std::vec::Vec<std::string::String>) {
                 out_buffer.push(self.mnemonic())
             }
+
+            #[inline]
+            fn write_presentation_tokens<W: std::fmt::Write + ?Sized>(&self, out: &mut $crate::serde::presentation::to_presentation::PresentationWriter<'_, W>) -> std::fmt::Result {
+                self.write_mnemonic(out.start_token()?)
+            }
         }
     };
 }
@@ -1,10 +1,10 @@
-use std::time::Instant;
+use std::{sync::Arc, time::Instant};
 
 use async_trait::async_trait;
 use futures::{Stream, StreamExt};
 use tokio::join;
 
-use crate::{query::message::Message, types::c_domain_name::CmpDomainName};
+use crate::{query::message::Message, resource_record::resource_record::RecordData, types::c_domain_name::CmpDomainName};
 
 use super::{CacheMeta, CacheQuery, CacheRecord, CacheResponse, MetaAuth};
 
@@ -16,17 +16,32 @@ pub trait Cache {
     }
 }
 
+/// A cache that can be shared between tasks and swapped out at runtime.
+pub type SharedAsyncCache = Arc<dyn AsyncCache + Send + Sync>;
+
+/// The trait is object safe so that it can be used as a `dyn AsyncCache + Send + Sync` (see
+/// `SharedAsyncCache`). The methods that take generic arguments are only available on concrete
+/// types. Their object safe counterpart is `insert_records()`.
 #[async_trait]
 pub trait AsyncCache {
     async fn get(&self, query: &CacheQuery<'_>) -> CacheResponse;
     async fn insert_record(&self, record: CacheRecord);
-    async fn insert_stream(&self, records: impl Stream<Item = CacheRecord> + Send) {
+    async fn insert_records(&self, records: Vec<CacheRecord>) {
+        futures::stream::iter(records).for_each_concurrent(None, |record| self.insert_record(record)).await;
+    }
+    async fn insert_stream(&self, records: impl Stream<Item = CacheRecord> + Send) where Self: Sized {
         records.for_each_concurrent(None, |record| self.insert_record(record)).await;
     }
-    async fn insert_iter(&self, records: impl Iterator<Item = CacheRecord> + Send) {
+    async fn insert_iter(&self, records: impl Iterator<Item = CacheRecord> + Send) where Self: Sized {
         self.insert_stream(futures::stream::iter(records)).await;
     }
 
+    /// Whether the records in this cache are a different view of the DNS than the one shared by
+    /// the rest of the client, such as one that was seeded with a particular delegation. Queries
+    /// resolved with an isolated cache must not share their results with other queries.
+    #[inline]
+    fn is_isolated(&self) -> bool { false }
+
     async fn insert_message(&self, message: &Message) {
         let insertion_time = Instant::now();
         match message.question.get(0) {
@@ -35,29 +50,63 @@ pub trait AsyncCache {
                 let qname = question.qname();
                 // TODO: Verify and validate authority.
                 join!(
-                    self.insert_iter(message.answer.iter().map(|answer| CacheRecord {
+                    self.insert_records(message.answer.iter().map(|answer| CacheRecord {
                         meta: CacheMeta {
                             auth: if message.authoritative_answer && answer.get_name().matches(qname) { MetaAuth::Authoritative } else { MetaAuth::NotAuthoritative },
                             insertion_time,
+                            original_ttl: None,
                         },
                         record: answer.clone(),
-                    })),
-                    self.insert_iter(message.authority.iter().map(|authority| CacheRecord {
+                    }).collect()),
+                    self.insert_records(message.authority.iter().map(|authority| CacheRecord {
                         meta: CacheMeta {
                             auth: MetaAuth::NotAuthoritative,
-                            insertion_time
+                            insertion_time,
+                            original_ttl: None,
                         },
                         record: authority.clone()
-                    })),
-                    self.insert_iter(message.additional.iter().map(|additional| CacheRecord {
+                    }).collect()),
+                    // The OPT pseudo-record describes the transaction, not the domain, so it must
+                    // never be cached.
+                    // https://datatracker.ietf.org/doc/html/rfc6891#section-6.1.1
+                    self.insert_records(message.additional.iter().filter(|additional| !matches!(additional.get_rdata(), RecordData::OPT(_))).map(|additional| CacheRecord {
                         meta: CacheMeta {
                             auth: MetaAuth::NotAuthoritative,
-                            insertion_time
+                            insertion_time,
+                            original_ttl: None,
                         },
                         record: additional.clone()
-                    })),
+                    }).collect()),
                 );
             },
         }
     }
 }
+
+#[async_trait]
+impl<C> AsyncCache for Arc<C> where C: AsyncCache + Send + Sync + ?Sized {
+    #[inline]
+    async fn get(&self, query: &CacheQuery<'_>) -> CacheResponse {
+        self.as_ref().get(query).await
+    }
+
+    #[inline]
+    async fn insert_record(&self, record: CacheRecord) {
+        self.as_ref().insert_record(record).await
+    }
+
+    #[inline]
+    async fn insert_records(&self, records: Vec<CacheRecord>) {
+        self.as_ref().insert_records(records).await
+    }
+
+    #[inline]
+    fn is_isolated(&self) -> bool {
+        self.as_ref().is_isolated()
+    }
+
+    #[inline]
+    async fn insert_message(&self, message: &Message) {
+        self.as_ref().insert_message(message).await
+    }
+}
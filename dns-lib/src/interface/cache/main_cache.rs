@@ -1,12 +1,12 @@
-use std::{fs::File, io::{self, Read}, time::Instant};
+use std::{fs::File, io::{self, Read}, sync::Arc, time::Instant};
 
 use async_trait::async_trait;
 use futures::{Stream, StreamExt};
 use tokio::io::AsyncReadExt;
 
-use crate::serde::presentation::zone_file_reader::{ZoneFileReader, ZoneToken};
+use crate::{query::question::Question, resource_record::rclass::RClass, serde::presentation::zone_file_reader::{ZoneFileReader, ZoneToken}, types::c_domain_name::CDomainName};
 
-use super::{CacheMeta, CacheQuery, CacheRecord, CacheResponse, MetaAuth};
+use super::{CacheMeta, CacheQuery, CacheRecord, CacheResponse, CacheStats, MetaAuth};
 
 pub trait MainCache {
     fn get(&self, query: &CacheQuery) -> CacheResponse;
@@ -19,8 +19,8 @@ pub trait MainCache {
     #[inline]
     fn load_from_tokenizer(&mut self, tokenizer: ZoneFileReader, authoritative: MetaAuth) {
         let insertion_time = Instant::now();
-        let meta = CacheMeta { auth: authoritative, insertion_time };
-        for token in tokenizer {
+        let meta = CacheMeta { auth: authoritative, insertion_time, original_ttl: None };
+        for token in tokenizer.diagnosed() {
             match token {
                 Ok(ZoneToken::ResourceRecord(record)) => self.insert_record(CacheRecord { meta: meta.clone(), record }),
                 Ok(ZoneToken::Include { file_path, domain_name }) => {
@@ -50,7 +50,7 @@ pub trait MainCache {
                         None => self.load_from_tokenizer(sub_tokenizer, authoritative),
                     }
                 },
-                Err(error) => println!("{error}"),
+                Err(diagnostic) => println!("{diagnostic}"),
             }
         }
     }
@@ -69,23 +69,48 @@ pub trait MainCache {
     }
 }
 
+/// A main cache that can be shared between tasks and swapped out at runtime.
+pub type SharedAsyncMainCache = Arc<dyn AsyncMainCache + Send + Sync>;
+
+/// The trait is object safe so that it can be used as a `dyn AsyncMainCache + Send + Sync` (see
+/// `SharedAsyncMainCache`). The methods that take generic arguments are only available on concrete
+/// types. Their object safe counterpart is `insert_records()`.
 #[async_trait]
 pub trait AsyncMainCache {
     async fn get(&self, query: &CacheQuery) -> CacheResponse;
     async fn insert_record(&self, record: CacheRecord);
-    async fn insert_stream(&self, records: impl Stream<Item = CacheRecord> + Send) {
+    async fn insert_records(&self, records: Vec<CacheRecord>) {
+        futures::stream::iter(records).for_each_concurrent(None, |record| self.insert_record(record)).await;
+    }
+    async fn insert_stream(&self, records: impl Stream<Item = CacheRecord> + Send) where Self: Sized {
         records.for_each_concurrent(None, |record| self.insert_record(record)).await;
     }
-    async fn insert_iter(&self, records: impl Iterator<Item = CacheRecord> + Send) {
+    async fn insert_iter(&self, records: impl Iterator<Item = CacheRecord> + Send) where Self: Sized {
         self.insert_stream(futures::stream::iter(records)).await;
     }
     async fn clean(&self);
 
+    /// Counts the records in the cache. Returns `None` if the cache cannot count them cheaply,
+    /// such as one that is stored remotely.
+    #[inline]
+    async fn stats(&self) -> Option<CacheStats> { None }
+
+    /// Removes the records owned by the qname with the qtype, or all of the qname's records if the
+    /// qtype is ANY. Returns the number of records removed, or `None` if the cache does not
+    /// support removing records.
+    #[inline]
+    async fn remove(&self, _question: &Question) -> Option<usize> { None }
+
+    /// Removes every record at or below the apex. Returns the number of records removed, or `None`
+    /// if the cache does not support removing records.
+    #[inline]
+    async fn remove_subtree(&self, _apex: &CDomainName, _qclass: RClass) -> Option<usize> { None }
+
     #[inline]
     async fn load_from_tokenizer<'a>(&self, tokenizer: ZoneFileReader<'a>, authoritative: MetaAuth) {
         let insertion_time = Instant::now();
-        let meta = CacheMeta { auth: authoritative, insertion_time };
-        futures::stream::iter(tokenizer).for_each_concurrent(None, |token| {
+        let meta = CacheMeta { auth: authoritative, insertion_time, original_ttl: None };
+        futures::stream::iter(tokenizer.diagnosed()).for_each_concurrent(None, |token| {
             let meta = meta.clone();
             async move {
                 match token {
@@ -117,7 +142,7 @@ pub trait AsyncMainCache {
                             None => self.load_from_tokenizer(sub_tokenizer, authoritative).await,
                         }
                     },
-                    Err(error) => println!("{error}"),
+                    Err(diagnostic) => println!("{diagnostic}"),
                 }
             }
         }).await;
@@ -136,3 +161,41 @@ pub trait AsyncMainCache {
         Ok(())
     }
 }
+
+#[async_trait]
+impl<C> AsyncMainCache for Arc<C> where C: AsyncMainCache + Send + Sync + ?Sized {
+    #[inline]
+    async fn get(&self, query: &CacheQuery) -> CacheResponse {
+        self.as_ref().get(query).await
+    }
+
+    #[inline]
+    async fn insert_record(&self, record: CacheRecord) {
+        self.as_ref().insert_record(record).await
+    }
+
+    #[inline]
+    async fn insert_records(&self, records: Vec<CacheRecord>) {
+        self.as_ref().insert_records(records).await
+    }
+
+    #[inline]
+    async fn clean(&self) {
+        self.as_ref().clean().await
+    }
+
+    #[inline]
+    async fn stats(&self) -> Option<CacheStats> {
+        self.as_ref().stats().await
+    }
+
+    #[inline]
+    async fn remove(&self, question: &Question) -> Option<usize> {
+        self.as_ref().remove(question).await
+    }
+
+    #[inline]
+    async fn remove_subtree(&self, apex: &CDomainName, qclass: RClass) -> Option<usize> {
+        self.as_ref().remove_subtree(apex, qclass).await
+    }
+}
@@ -1,6 +1,6 @@
-use std::{ops::{Deref, DerefMut}, time::Instant};
+use std::{ops::{Deref, DerefMut}, time::{Duration, Instant}};
 
-use crate::{query::question::Question, resource_record::{rclass::RClass, rcode::RCode, resource_record::ResourceRecord, rtype::RType}, types::c_domain_name::CDomainName};
+use crate::{query::question::Question, resource_record::{rclass::RClass, rcode::RCode, resource_record::ResourceRecord, rtype::RType, time::Time}, types::c_domain_name::CDomainName};
 
 pub mod cache;
 
@@ -43,6 +43,9 @@ pub enum MetaAuth {
 pub struct CacheMeta {
     pub auth: MetaAuth,
     pub insertion_time: Instant,
+    /// The TTL that the record had when it was cached, once its TTL has been lowered to the time
+    /// it has left. `None` while the record still has its original TTL.
+    pub original_ttl: Option<Time>,
 }
 
 #[derive(Clone, PartialEq, Hash, Debug)]
@@ -54,7 +57,50 @@ pub struct CacheRecord {
 impl CacheRecord {
     #[inline]
     pub fn is_expired(&self) -> bool {
-        self.meta.insertion_time.elapsed().as_secs() >= self.record.get_ttl().as_secs() as u64
+        self.is_expired_at(Instant::now())
+    }
+
+    /// Whether the record will have expired at `now`.
+    #[inline]
+    pub fn is_expired_at(&self, now: Instant) -> bool {
+        now.saturating_duration_since(self.meta.insertion_time).as_secs() >= self.record.get_ttl().as_secs() as u64
+    }
+
+    /// The time the record has left before it expires, in whole seconds. Zero once it has
+    /// expired.
+    #[inline]
+    pub fn remaining_ttl(&self) -> Time {
+        self.remaining_ttl_at(Instant::now())
+    }
+
+    /// The time the record will have left at `now`, in whole seconds.
+    #[inline]
+    pub fn remaining_ttl_at(&self, now: Instant) -> Time {
+        let elapsed = now.saturating_duration_since(self.meta.insertion_time).as_secs();
+        Time::new(u64::from(self.record.get_ttl().as_secs()).saturating_sub(elapsed) as u32)
+    }
+
+    /// The TTL the record had when it was cached, before any time was taken off of it.
+    #[inline]
+    pub fn original_ttl(&self) -> Time {
+        self.meta.original_ttl.unwrap_or(*self.record.get_ttl())
+    }
+
+    /// Lowers the record's TTL to the time it has left. The insertion time is moved forward by the
+    /// same number of seconds, so the record still expires at the same moment.
+    #[inline]
+    pub fn decay_ttl(&mut self) {
+        self.decay_ttl_at(Instant::now())
+    }
+
+    /// Same as `decay_ttl` but takes the time it has left at `now`.
+    pub fn decay_ttl_at(&mut self, now: Instant) {
+        let original_ttl = self.original_ttl();
+        let remaining_ttl = self.remaining_ttl_at(now);
+        let elapsed = self.record.get_ttl().as_secs() - remaining_ttl.as_secs();
+        self.meta.original_ttl = Some(original_ttl);
+        self.meta.insertion_time += Duration::from_secs(u64::from(elapsed));
+        self.record.set_ttl(remaining_ttl);
     }
 
     #[inline]
@@ -91,3 +137,82 @@ impl DerefMut for CacheRecord {
         &mut self.record
     }
 }
+
+/// A point-in-time summary of the contents of the cache.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct CacheStats {
+    /// The number of domain names in the cache tree, including those with no records.
+    pub nodes: usize,
+    /// The number of record sets (records with the same name, type, and class).
+    pub record_sets: usize,
+    pub records: usize,
+    /// Records that have expired but not yet been cleaned out.
+    pub expired_records: usize,
+    /// A rough estimate of the memory used by the records. It counts the size of each record and
+    /// the wire length of its data but not the overhead of the tree.
+    pub estimated_bytes: usize,
+    /// The records that were evicted to keep the cache within its size limits since it was
+    /// created.
+    pub evicted_records: usize,
+}
+
+#[cfg(test)]
+mod cache_record_tests {
+    use std::{net::Ipv4Addr, time::{Duration, Instant}};
+
+    use crate::{resource_record::{rclass::RClass, resource_record::{RecordData, ResourceRecord}, time::Time, types::a::A}, types::c_domain_name::CDomainName};
+
+    use super::{CacheMeta, CacheRecord, MetaAuth};
+
+    /// A record with this TTL that was cached at `cached_at`.
+    fn cached(ttl: u32, cached_at: Instant) -> CacheRecord {
+        let record = ResourceRecord::new(CDomainName::from_utf8("www.example.com.").unwrap(), RClass::Internet, Time::new(ttl), RecordData::A(A::new(Ipv4Addr::new(192, 0, 2, 1))));
+        CacheRecord { meta: CacheMeta { auth: MetaAuth::NotAuthoritative, insertion_time: cached_at, original_ttl: None }, record }
+    }
+
+    /// The moment `age` seconds after `cached_at`.
+    fn after(cached_at: Instant, age: u64) -> Instant {
+        cached_at + Duration::from_secs(age)
+    }
+
+    #[test]
+    fn ttl_decays_with_time_in_the_cache() {
+        let cached_at = Instant::now();
+        let mut record = cached(300, cached_at);
+        record.decay_ttl_at(cached_at);
+        assert_eq!(record.get_ttl(), &Time::new(300));
+        assert_eq!(record.original_ttl(), Time::new(300));
+
+        let mut record = cached(300, cached_at);
+        record.decay_ttl_at(after(cached_at, 100));
+        assert_eq!(record.get_ttl(), &Time::new(200));
+        assert_eq!(record.original_ttl(), Time::new(300));
+        assert!(!record.is_expired_at(after(cached_at, 100)));
+
+        // Decaying again does not take the same time off twice or lose the original TTL.
+        record.decay_ttl_at(after(cached_at, 100));
+        assert_eq!(record.get_ttl(), &Time::new(200));
+        assert_eq!(record.original_ttl(), Time::new(300));
+    }
+
+    #[test]
+    fn ttl_decays_to_one_second_before_expiry() {
+        let cached_at = Instant::now();
+        let mut record = cached(300, cached_at);
+        assert!(!record.is_expired_at(after(cached_at, 299)));
+        record.decay_ttl_at(after(cached_at, 299));
+        assert_eq!(record.get_ttl(), &Time::new(1));
+        assert!(!record.is_expired_at(after(cached_at, 299)));
+
+        let mut record = cached(300, cached_at);
+        assert!(record.is_expired_at(after(cached_at, 300)));
+        assert_eq!(record.remaining_ttl_at(after(cached_at, 300)), Time::new(0));
+        record.decay_ttl_at(after(cached_at, 300));
+        assert_eq!(record.get_ttl(), &Time::new(0));
+        assert!(record.is_expired_at(after(cached_at, 300)));
+
+        let record = cached(300, cached_at);
+        assert!(record.is_expired_at(after(cached_at, 1000)));
+        assert_eq!(record.remaining_ttl_at(after(cached_at, 1000)), Time::new(0));
+    }
+}
@@ -15,10 +15,13 @@ pub trait TransactionCache {
 pub trait AsyncTransactionCache {
     async fn get(&self, query: &CacheQuery) -> CacheResponse;
     async fn insert_record(&self, record: CacheRecord);
-    async fn insert_stream(&self, records: impl Stream<Item = CacheRecord> + Send) {
+    async fn insert_records(&self, records: Vec<CacheRecord>) {
+        futures::stream::iter(records).for_each_concurrent(None, |record| self.insert_record(record)).await;
+    }
+    async fn insert_stream(&self, records: impl Stream<Item = CacheRecord> + Send) where Self: Sized {
         records.for_each_concurrent(None, |record| self.insert_record(record)).await;
     }
-    async fn insert_iter(&self, records: impl Iterator<Item = CacheRecord> + Send) {
+    async fn insert_iter(&self, records: impl Iterator<Item = CacheRecord> + Send) where Self: Sized {
         self.insert_stream(futures::stream::iter(records)).await;
     }
 }
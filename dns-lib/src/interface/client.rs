@@ -1,20 +1,105 @@
-use std::{error::Error, fmt::Display, sync::Arc};
+use std::{error::Error, fmt::Display, sync::Arc, time::{Duration, Instant}};
 
+use async_lib::awake_token::AwakeToken;
 use async_trait::async_trait;
 
-use crate::{query::{message::Message, question::Question}, resource_record::{rclass::RClass, rcode::RCode, resource_record::ResourceRecord, rtype::RType, types::ns::NS}, types::c_domain_name::{CDomainName, CmpDomainName}};
+use crate::{interface::trace::QueryTrace, query::{message::Message, question::Question}, resource_record::{rclass::RClass, rcode::RCode, resource_record::ResourceRecord, rtype::RType, time::Time, types::{ns::NS, soa::SOA}}, types::c_domain_name::{CDomainName, CmpDomainName}};
 
 #[derive(Debug)]
 pub enum Response {
     Answer(Answer),
-    Error(RCode),
+    Error(ErrorResponse),
+}
+
+impl Response {
+    /// The response code. Answers always have the code `NoError`.
+    #[inline]
+    pub fn rcode(&self) -> RCode {
+        match self {
+            Response::Answer(_) => RCode::NoError,
+            Response::Error(error) => error.rcode,
+        }
+    }
 }
 
 impl Display for Response {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             Response::Answer(answer) => write!(f, "Answer:\n{answer}"),
-            Response::Error(rcode) => write!(f, "Error: {rcode}"),
+            Response::Error(error) => write!(f, "Error: {error}"),
+        }
+    }
+}
+
+/// What the name servers said about a name or type that does not exist (NXDOMAIN or NODATA), so
+/// that applications can explain the failure instead of only reporting the response code.
+#[derive(Debug, Clone, PartialEq, Hash, Default)]
+pub struct NegativeDetails {
+    /// The SOA record of the zone that the name would be in, if it is known.
+    pub soa: Option<ResourceRecord<SOA>>,
+    /// The zones that were delegated through on the way to that zone, starting with the root.
+    pub authority_chain: Vec<CDomainName>,
+}
+
+impl NegativeDetails {
+    /// The zone that said the name or type does not exist.
+    #[inline]
+    pub fn zone(&self) -> Option<&CDomainName> {
+        self.soa.as_ref().map(|soa| soa.get_name())
+    }
+
+    /// How long the negative answer may be cached: the lesser of the SOA record's TTL and its
+    /// minimum field.
+    ///
+    /// https://datatracker.ietf.org/doc/html/rfc2308#section-5
+    #[inline]
+    pub fn negative_ttl(&self) -> Option<Time> {
+        self.soa.as_ref().map(|soa| Time::new((*soa.get_rdata().minimum()).min(soa.get_ttl().as_secs())))
+    }
+}
+
+impl Display for NegativeDetails {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match (self.zone(), self.negative_ttl()) {
+            (Some(zone), Some(negative_ttl)) => write!(f, "zone '{zone}' (negative TTL {}s)", negative_ttl.as_secs())?,
+            _ => write!(f, "unknown zone")?,
+        }
+        if !self.authority_chain.is_empty() {
+            write!(f, " via")?;
+            for zone in &self.authority_chain {
+                write!(f, " '{zone}'")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Hash)]
+pub struct ErrorResponse {
+    pub rcode: RCode,
+    /// Only set for NXDOMAIN.
+    pub negative: Option<NegativeDetails>,
+}
+
+impl ErrorResponse {
+    #[inline]
+    pub fn new(rcode: RCode) -> Self {
+        Self { rcode, negative: None }
+    }
+}
+
+impl From<RCode> for ErrorResponse {
+    #[inline]
+    fn from(rcode: RCode) -> Self {
+        Self::new(rcode)
+    }
+}
+
+impl Display for ErrorResponse {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.negative {
+            Some(negative) => write!(f, "{} from {negative}", self.rcode),
+            None => write!(f, "{}", self.rcode),
         }
     }
 }
@@ -25,6 +110,11 @@ pub struct Answer {
     pub name_servers: Vec<ResourceRecord<NS>>,
     pub additional: Vec<ResourceRecord>,
     pub authoritative: bool,
+    /// Set when there are no records of the requested type (NODATA).
+    pub negative: Option<NegativeDetails>,
+    /// The queries that were made to name servers to get the answer, if the query was traced. It
+    /// is the same trace that was given to the context.
+    pub trace: Option<Arc<QueryTrace>>,
 }
 
 impl Display for Answer {
@@ -45,9 +135,10 @@ pub trait Client {
     fn query(&mut self, question: &Question) -> Message;
 }
 
+/// The trait is object safe so that clients can be used as an `Arc<dyn AsyncClient>`.
 #[async_trait]
 pub trait AsyncClient: Sync + Send {
-    async fn query(client: Arc<Self>, question: Context) -> Response;
+    async fn query(self: Arc<Self>, question: Context) -> Response;
 }
 
 
@@ -115,6 +206,14 @@ pub enum Context {
     Root {
         query: Question,
         minimization: QNameMinimization,
+        trace: Option<Arc<QueryTrace>>,
+        /// When the caller stops being interested in the answer.
+        deadline: Option<Instant>,
+        /// The name of the cache that the query is resolved with, for clients that keep more than
+        /// one. `None` for the client's main cache.
+        view: Option<Arc<str>>,
+        /// Awoken when the caller cancels the query.
+        cancellation: Option<AwakeToken>,
     },
     RootSearch {
         query: Question,
@@ -159,14 +258,82 @@ impl Context {
     pub const fn new(query: Question, minimization: QNameMinimization) -> Self {
         Self::Root {
             query,
-            minimization
+            minimization,
+            trace: None,
+            deadline: None,
+            view: None,
+            cancellation: None,
+        }
+    }
+
+    /// A root context whose queries to name servers are recorded in the trace.
+    #[inline]
+    pub const fn new_traced(query: Question, minimization: QNameMinimization, trace: Arc<QueryTrace>) -> Self {
+        Self::Root {
+            query,
+            minimization,
+            trace: Some(trace),
+            deadline: None,
+            view: None,
+            cancellation: None,
+        }
+    }
+
+    /// Sets when the caller stops being interested in the answer. Every query made on behalf of
+    /// this context, including the ones for CNAME targets and name server addresses, has to finish
+    /// by then. Only root contexts have their own deadline. Any other context is returned as is.
+    #[inline]
+    pub fn with_deadline(mut self, new_deadline: Instant) -> Self {
+        if let Context::Root { query: _, minimization: _, trace: _, deadline, view: _, cancellation: _ } = &mut self {
+            *deadline = Some(new_deadline);
+        }
+        self
+    }
+
+    /// Records the queries made to name servers on behalf of this context, including the ones for
+    /// CNAME targets and name server addresses, in the trace. Only root contexts have their own
+    /// trace. Any other context is returned as is.
+    #[inline]
+    pub fn with_trace(mut self, new_trace: Arc<QueryTrace>) -> Self {
+        if let Context::Root { query: _, minimization: _, trace, deadline: _, view: _, cancellation: _ } = &mut self {
+            *trace = Some(new_trace);
+        }
+        self
+    }
+
+    /// Resolves the query with the client's cache of this name instead of its main cache. Only root
+    /// contexts have their own view. Any other context is returned as is.
+    #[inline]
+    pub fn with_view(mut self, new_view: impl Into<Arc<str>>) -> Self {
+        if let Context::Root { query: _, minimization: _, trace: _, deadline: _, view, cancellation: _ } = &mut self {
+            *view = Some(new_view.into());
+        }
+        self
+    }
+
+    /// Cancels the query when the token is awoken. The queries that are still running for this
+    /// context, including the ones for CNAME targets and name server addresses, are stopped and the
+    /// query is answered with SERVFAIL. Only root contexts have their own token. Any other context
+    /// is returned as is.
+    #[inline]
+    pub fn with_cancellation(mut self, token: AwakeToken) -> Self {
+        if let Context::Root { query: _, minimization: _, trace: _, deadline: _, view: _, cancellation } = &mut self {
+            *cancellation = Some(token);
         }
+        self
+    }
+
+    /// Same as `with_deadline()`, with the deadline this long from now.
+    #[inline]
+    pub fn with_timeout(self, timeout: Duration) -> Self {
+        let now = Instant::now();
+        self.with_deadline(now.checked_add(timeout).unwrap_or(now))
     }
 
     #[inline]
     pub fn new_search_name(self: Arc<Self>, query: Question) -> Result<Context, ContextErr> {
         match self.as_ref() {
-            Context::Root { query: _, minimization: _ } => Ok(Self::RootSearch { query, parent: self }),
+            Context::Root { query: _, minimization: _, trace: _, deadline: _, view: _, cancellation: _ } => Ok(Self::RootSearch { query, parent: self }),
             Context::CName { query: _, parent: _ } => Ok(Self::CNameSearch { query, parent: self }),
             Context::DName { query: _, parent: _ } => Ok(Self::DNameSearch { query, parent: self }),
             Context::NSAddress { query: _, parent: _ } => Ok(Self::NSAddressSearch { query, parent: self }),
@@ -186,7 +353,7 @@ impl Context {
         let query = Question::new(qname, self.qtype(), self.qclass());
         match (self.is_cname_allowed(&query), self.as_ref()) {
             (Err(error), _) => Err(error),
-            (Ok(()), Context::Root { query: _, minimization: _ })
+            (Ok(()), Context::Root { query: _, minimization: _, trace: _, deadline: _, view: _, cancellation: _ })
           | (Ok(()), Context::CName { query: _, parent: _ })
           | (Ok(()), Context::DName { query: _, parent: _ }) => {
                 Ok(Self::CName { query, parent: self })
@@ -208,7 +375,7 @@ impl Context {
         let query = Question::new(qname, self.qtype(), self.qclass());
         match (self.is_dname_allowed(&query), self.as_ref()) {
             (Err(error), _) => Err(error),
-            (Ok(()), Context::Root { query: _, minimization: _ })
+            (Ok(()), Context::Root { query: _, minimization: _, trace: _, deadline: _, view: _, cancellation: _ })
           | (Ok(()), Context::CName { query: _, parent: _ })
           | (Ok(()), Context::DName { query: _, parent: _ }) => {
                 Ok(Self::DName { query, parent: self })
@@ -229,7 +396,7 @@ impl Context {
     pub fn new_ns_address(self: Arc<Self>, query: Question) -> Result<Context, ContextErr> {
         match (self.is_ns_allowed(&query), self.as_ref()) {
             (Err(error), _) => Err(error),
-            (Ok(()), Context::Root { query: _, minimization: _ })
+            (Ok(()), Context::Root { query: _, minimization: _, trace: _, deadline: _, view: _, cancellation: _ })
           | (Ok(()), Context::RootSearch { query: _, parent: _ })
           | (Ok(()), Context::CName { query: _, parent: _ })
           | (Ok(()), Context::CNameSearch { query: _, parent: _ })
@@ -249,7 +416,7 @@ impl Context {
     #[inline]
     pub const fn query(&self) -> &Question {
         match self {
-            Context::Root { query, minimization: _ } => query,
+            Context::Root { query, minimization: _, trace: _, deadline: _, view: _, cancellation: _ } => query,
             Context::RootSearch { query, parent: _ } => query,
             Context::CName { query, parent: _ } => query,
             Context::CNameSearch { query, parent: _ } => query,
@@ -280,7 +447,7 @@ impl Context {
     #[inline]
     pub fn qname_minimization(&self) -> &QNameMinimization {
         match self {
-            Context::Root { query: _, minimization } => minimization,
+            Context::Root { query: _, minimization, trace: _, deadline: _, view: _, cancellation: _ } => minimization,
             Context::RootSearch { query: _, parent } => parent.qname_minimization(),
             Context::CName { query: _, parent } => parent.qname_minimization(),
             Context::CNameSearch { query: _, parent } => parent.qname_minimization(),
@@ -297,9 +464,9 @@ impl Context {
     pub fn qname_minimization_limit(&self) -> Option<usize> {
         let minimization = self.qname_minimization();
         match (self, minimization) {
-            (Context::Root { query: _, minimization: _ }, QNameMinimization::All { primary_minimization_limit, ns_minimization_limit: _, sub_ns_minimization_limit: _ })
-          | (Context::Root { query: _, minimization: _ }, QNameMinimization::PrimaryQueryAndNS { primary_minimization_limit, ns_minimization_limit: _ })
-          | (Context::Root { query: _, minimization: _ }, QNameMinimization::PrimaryQuery { primary_minimization_limit })
+            (Context::Root { query: _, minimization: _, trace: _, deadline: _, view: _, cancellation: _ }, QNameMinimization::All { primary_minimization_limit, ns_minimization_limit: _, sub_ns_minimization_limit: _ })
+          | (Context::Root { query: _, minimization: _, trace: _, deadline: _, view: _, cancellation: _ }, QNameMinimization::PrimaryQueryAndNS { primary_minimization_limit, ns_minimization_limit: _ })
+          | (Context::Root { query: _, minimization: _, trace: _, deadline: _, view: _, cancellation: _ }, QNameMinimization::PrimaryQuery { primary_minimization_limit })
           | (Context::CName { query: _, parent: _ }, QNameMinimization::All { primary_minimization_limit, ns_minimization_limit: _, sub_ns_minimization_limit: _ })
           | (Context::CName { query: _, parent: _ }, QNameMinimization::PrimaryQueryAndNS { primary_minimization_limit, ns_minimization_limit: _ })
           | (Context::CName { query: _, parent: _ }, QNameMinimization::PrimaryQuery { primary_minimization_limit })
@@ -308,7 +475,7 @@ impl Context {
           | (Context::DName { query: _, parent: _ }, QNameMinimization::PrimaryQuery { primary_minimization_limit }) => {
                 Some(*primary_minimization_limit)
             },
-            (Context::Root { query: _, minimization: _ }, QNameMinimization::None)
+            (Context::Root { query: _, minimization: _, trace: _, deadline: _, view: _, cancellation: _ }, QNameMinimization::None)
           | (Context::CName { query: _, parent: _ }, QNameMinimization::None)
           | (Context::DName { query: _, parent: _ }, QNameMinimization::None) => {
                 None
@@ -343,7 +510,7 @@ impl Context {
     #[inline]
     pub const fn parent(&self) -> Option<&Arc<Context>> {
         match self {
-            Context::Root { query: _, minimization: _ } => None,
+            Context::Root { query: _, minimization: _, trace: _, deadline: _, view: _, cancellation: _ } => None,
             Context::RootSearch { query: _, parent } => Some(parent),
             Context::CName { query: _, parent } => Some(parent),
             Context::CNameSearch { query: _, parent } => Some(parent),
@@ -356,10 +523,86 @@ impl Context {
         }
     }
 
+    /// The trace shared by every context descended from the same root, if tracing was enabled.
+    #[inline]
+    pub fn trace(&self) -> Option<&Arc<QueryTrace>> {
+        match self {
+            Context::Root { query: _, minimization: _, trace, deadline: _, view: _, cancellation: _ } => trace.as_ref(),
+            Context::RootSearch { query: _, parent } => parent.trace(),
+            Context::CName { query: _, parent } => parent.trace(),
+            Context::CNameSearch { query: _, parent } => parent.trace(),
+            Context::DName { query: _, parent } => parent.trace(),
+            Context::DNameSearch { query: _, parent } => parent.trace(),
+            Context::NSAddress { query: _, parent } => parent.trace(),
+            Context::NSAddressSearch { query: _, parent } => parent.trace(),
+            Context::SubNSAddress { query: _, parent } => parent.trace(),
+            Context::SubNSAddressSearch { query: _, parent } => parent.trace(),
+        }
+    }
+
+    /// The deadline shared by every context descended from the same root, if one was set.
+    #[inline]
+    pub fn deadline(&self) -> Option<Instant> {
+        match self {
+            Context::Root { query: _, minimization: _, trace: _, deadline, view: _, cancellation: _ } => *deadline,
+            Context::RootSearch { query: _, parent } => parent.deadline(),
+            Context::CName { query: _, parent } => parent.deadline(),
+            Context::CNameSearch { query: _, parent } => parent.deadline(),
+            Context::DName { query: _, parent } => parent.deadline(),
+            Context::DNameSearch { query: _, parent } => parent.deadline(),
+            Context::NSAddress { query: _, parent } => parent.deadline(),
+            Context::NSAddressSearch { query: _, parent } => parent.deadline(),
+            Context::SubNSAddress { query: _, parent } => parent.deadline(),
+            Context::SubNSAddressSearch { query: _, parent } => parent.deadline(),
+        }
+    }
+
+    /// The view shared by every context descended from the same root, if one was chosen.
+    #[inline]
+    pub fn view(&self) -> Option<&Arc<str>> {
+        match self {
+            Context::Root { query: _, minimization: _, trace: _, deadline: _, view, cancellation: _ } => view.as_ref(),
+            Context::RootSearch { query: _, parent } => parent.view(),
+            Context::CName { query: _, parent } => parent.view(),
+            Context::CNameSearch { query: _, parent } => parent.view(),
+            Context::DName { query: _, parent } => parent.view(),
+            Context::DNameSearch { query: _, parent } => parent.view(),
+            Context::NSAddress { query: _, parent } => parent.view(),
+            Context::NSAddressSearch { query: _, parent } => parent.view(),
+            Context::SubNSAddress { query: _, parent } => parent.view(),
+            Context::SubNSAddressSearch { query: _, parent } => parent.view(),
+        }
+    }
+
+    /// The cancellation token shared by every context descended from the same root, if one was
+    /// given.
+    #[inline]
+    pub fn cancellation(&self) -> Option<&AwakeToken> {
+        match self {
+            Context::Root { query: _, minimization: _, trace: _, deadline: _, view: _, cancellation } => cancellation.as_ref(),
+            Context::RootSearch { query: _, parent } => parent.cancellation(),
+            Context::CName { query: _, parent } => parent.cancellation(),
+            Context::CNameSearch { query: _, parent } => parent.cancellation(),
+            Context::DName { query: _, parent } => parent.cancellation(),
+            Context::DNameSearch { query: _, parent } => parent.cancellation(),
+            Context::NSAddress { query: _, parent } => parent.cancellation(),
+            Context::NSAddressSearch { query: _, parent } => parent.cancellation(),
+            Context::SubNSAddress { query: _, parent } => parent.cancellation(),
+            Context::SubNSAddressSearch { query: _, parent } => parent.cancellation(),
+        }
+    }
+
+    /// How much of the deadline is left. `None` if there is no deadline and `Some(Duration::ZERO)`
+    /// once it has passed.
+    #[inline]
+    pub fn remaining(&self) -> Option<Duration> {
+        self.deadline().map(|deadline| deadline.saturating_duration_since(Instant::now()))
+    }
+
     #[inline]
     pub fn root(self: &Arc<Self>) -> &Arc<Context> {
         match self.as_ref() {
-            Context::Root { query: _, minimization: _ } => self,
+            Context::Root { query: _, minimization: _, trace: _, deadline: _, view: _, cancellation: _ } => self,
             Context::RootSearch { query: _, parent } => parent.root(),
             Context::CName { query: _, parent } => parent.root(),
             Context::CNameSearch { query: _, parent } => parent.root(),
@@ -375,7 +618,7 @@ impl Context {
     #[inline]
     pub fn is_cname_allowed(&self, child: &Question) -> Result<(), ContextErr> {
         match &self {
-            Context::Root { query, minimization: _ } => {
+            Context::Root { query, minimization: _, trace: _, deadline: _, view: _, cancellation: _ } => {
                 if query.qname().is_parent_domain_of(child.qname()) {
                     Err(ContextErr::CNameWillLoop { parent: self.short_name(), child: child.clone() })
                 } else {
@@ -405,7 +648,7 @@ impl Context {
     #[inline]
     pub fn is_dname_allowed(&self, child: &Question) -> Result<(), ContextErr> {
         match &self {
-            Context::Root { query, minimization: _ } => {
+            Context::Root { query, minimization: _, trace: _, deadline: _, view: _, cancellation: _ } => {
                 if query.qname().is_parent_domain_of(child.qname()) {
                     Err(ContextErr::DNameWillLoop { parent: self.short_name(), child: child.clone() })
                 } else {
@@ -435,7 +678,7 @@ impl Context {
     #[inline]
     pub fn is_ns_allowed(&self, child: &Question) -> Result<(), ContextErr> {
         match &self {
-            Context::Root { query, minimization: _ } => {
+            Context::Root { query, minimization: _, trace: _, deadline: _, view: _, cancellation: _ } => {
                 if query.eq(child) {
                     Err(ContextErr::NSWillLoop { parent: self.short_name(), child: child.clone() })
                 } else {
@@ -465,7 +708,7 @@ impl Context {
     #[inline]
     fn short_name(&self) -> String {
         match &self {
-            Context::Root { query, minimization: _ } =>         format!("Context::Root {{ qname: {}, qtype: {}, qclass: {} }}",                query.qname(), query.qtype(), query.qclass()),
+            Context::Root { query, minimization: _, trace: _, deadline: _, view: _, cancellation: _ } =>         format!("Context::Root {{ qname: {}, qtype: {}, qclass: {} }}",                query.qname(), query.qtype(), query.qclass()),
             Context::RootSearch { query, parent: _ } =>         format!("Context::RootSearch {{ qname: {}, qtype: {}, qclass: {} }}",          query.qname(), query.qtype(), query.qclass()),
             Context::CName { query, parent: _ } =>              format!("Context::CName {{ qname: {}, qtype: {}, qclass: {} }}",               query.qname(), query.qtype(), query.qclass()),
             Context::CNameSearch { query, parent: _ } =>        format!("Context::CNameSearch {{ qname: {}, qtype: {}, qclass: {} }}",         query.qname(), query.qtype(), query.qclass()),
@@ -0,0 +1,355 @@
+use std::{error::Error, fmt::Display, net::{IpAddr, Ipv4Addr, Ipv6Addr}};
+
+use crate::{resource_record::svc_param_key::SvcParamKey, serde::wire::{from_wire::FromWire, read_wire::{ReadWire, ReadWireError}}, types::c_domain_name::CDomainName};
+
+/// The type of the IPv6 Router Advertisement DNR option.
+///
+/// https://datatracker.ietf.org/doc/html/rfc9463#section-6.1
+const RA_OPTION_TYPE: u8 = 144;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum DnrError {
+    Wire(ReadWireError),
+    UnexpectedRaOptionType(u8),
+    InvalidAddressLength {
+        address_length: usize,
+        address_size: usize,
+    },
+    ZeroServicePriority,
+    InvalidServiceParameter(SvcParamKey),
+    MissingAlpn,
+}
+impl Error for DnrError {}
+impl Display for DnrError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Wire(error) => write!(f, "{error} in DNR data"),
+            Self::UnexpectedRaOptionType(option_type) => write!(f, "expected a router advertisement option of type {RA_OPTION_TYPE} but found {option_type}"),
+            Self::InvalidAddressLength { address_length, address_size } => write!(f, "DNR address length {address_length} is not a multiple of {address_size}"),
+            Self::ZeroServicePriority => write!(f, "DNR service priority must not be zero"),
+            Self::InvalidServiceParameter(key) => write!(f, "DNR service parameter '{key}' is malformed"),
+            Self::MissingAlpn => write!(f, "DNR instance with addresses is missing the mandatory 'alpn' service parameter"),
+        }
+    }
+}
+impl From<ReadWireError> for DnrError {
+    fn from(error: ReadWireError) -> Self {
+        Self::Wire(error)
+    }
+}
+
+/// A single Discovery of Network-designated Resolvers instance. An instance describes one
+/// encrypted resolver that the network recommends, as delivered by DHCPv4, DHCPv6, or an IPv6
+/// Router Advertisement.
+///
+/// https://datatracker.ietf.org/doc/html/rfc9463#section-3.1
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct DnrInstance {
+    service_priority: u16,
+    lifetime: Option<u32>,
+    authentication_domain_name: CDomainName,
+    addresses: Vec<IpAddr>,
+    service_parameters: Vec<(SvcParamKey, Vec<u8>)>,
+}
+
+impl DnrInstance {
+    /// Lower values are preferred.
+    #[inline]
+    pub fn service_priority(&self) -> u16 { self.service_priority }
+
+    /// The number of seconds the instance is valid for. Only Router Advertisements carry a
+    /// lifetime. DHCP instances are valid for the lifetime of the lease.
+    #[inline]
+    pub fn lifetime(&self) -> Option<u32> { self.lifetime }
+
+    /// The name used to authenticate the resolver (i.e. the TLS server name).
+    #[inline]
+    pub fn authentication_domain_name(&self) -> &CDomainName { &self.authentication_domain_name }
+
+    #[inline]
+    pub fn addresses(&self) -> &[IpAddr] { &self.addresses }
+
+    #[inline]
+    pub fn service_parameters(&self) -> &[(SvcParamKey, Vec<u8>)] { &self.service_parameters }
+
+    /// In ADN-only mode, the addresses of the resolver must be discovered by resolving the
+    /// authentication domain name.
+    #[inline]
+    pub fn is_adn_only(&self) -> bool { self.addresses.is_empty() }
+
+    #[inline]
+    fn service_parameter(&self, key: SvcParamKey) -> Option<&[u8]> {
+        self.service_parameters.iter()
+            .find(|(param_key, _)| *param_key == key)
+            .map(|(_, value)| value.as_slice())
+    }
+
+    /// The ALPN protocol identifiers supported by the resolver (e.g. "dot", "doq", "h2", "h3").
+    pub fn alpn(&self) -> Result<Vec<String>, DnrError> {
+        let mut wire = match self.service_parameter(SvcParamKey::Alpn) {
+            Some(value) => ReadWire::from_bytes(value),
+            None => return Ok(vec![]),
+        };
+        let mut alpn_ids = Vec::new();
+        while !wire.is_end_reached() {
+            let length = wire.take_byte()?;
+            let alpn_id = wire.take(length as usize)?;
+            match String::from_utf8(alpn_id.to_vec()) {
+                Ok(alpn_id) => alpn_ids.push(alpn_id),
+                Err(_) => return Err(DnrError::InvalidServiceParameter(SvcParamKey::Alpn)),
+            }
+        }
+        Ok(alpn_ids)
+    }
+
+    /// The port to use instead of the protocol's default port.
+    pub fn port(&self) -> Result<Option<u16>, DnrError> {
+        match self.service_parameter(SvcParamKey::Port) {
+            Some(&[high, low]) => Ok(Some(u16::from_be_bytes([high, low]))),
+            Some(_) => Err(DnrError::InvalidServiceParameter(SvcParamKey::Port)),
+            None => Ok(None),
+        }
+    }
+
+    /// The URI template for DNS over HTTPS.
+    ///
+    /// https://datatracker.ietf.org/doc/html/rfc9461#section-5
+    pub fn doh_path(&self) -> Result<Option<String>, DnrError> {
+        match self.service_parameter(SvcParamKey::DohPath) {
+            Some(value) => match String::from_utf8(value.to_vec()) {
+                Ok(doh_path) => Ok(Some(doh_path)),
+                Err(_) => Err(DnrError::InvalidServiceParameter(SvcParamKey::DohPath)),
+            },
+            None => Ok(None),
+        }
+    }
+
+    /// Parses the value of the DHCPv6 OPTION_V6_DNR option. The option code and option length
+    /// must already have been removed.
+    ///
+    /// https://datatracker.ietf.org/doc/html/rfc9463#section-4.1
+    pub fn from_dhcpv6_option(option: &[u8]) -> Result<Self, DnrError> {
+        let mut wire = ReadWire::from_bytes(option);
+        let service_priority = read_service_priority(&mut wire)?;
+        let adn_length = u16::from_wire_format(&mut wire)?;
+        let authentication_domain_name = read_adn(&mut wire, adn_length as usize)?;
+        if wire.is_end_reached() {
+            return Ok(Self::new_adn_only(service_priority, None, authentication_domain_name));
+        }
+        let address_length = u16::from_wire_format(&mut wire)?;
+        let addresses = read_ipv6_addresses(&mut wire, address_length as usize)?;
+        let service_parameters = read_service_parameters(&mut wire)?;
+        Self::new_validated(service_priority, None, authentication_domain_name, addresses, service_parameters)
+    }
+
+    /// Parses the value of the DHCPv4 OPTION_V4_DNR option, which may contain several instances.
+    /// The option code and option length must already have been removed. If the option was split
+    /// across multiple DHCPv4 options, they must be concatenated first.
+    ///
+    /// https://datatracker.ietf.org/doc/html/rfc9463#section-5.1
+    pub fn from_dhcpv4_option(option: &[u8]) -> Result<Vec<Self>, DnrError> {
+        let mut wire = ReadWire::from_bytes(option);
+        let mut instances = Vec::new();
+        while !wire.is_end_reached() {
+            let instance_length = u16::from_wire_format(&mut wire)?;
+            let mut instance_wire = wire.take_as_read_wire(instance_length as usize)?;
+            let service_priority = read_service_priority(&mut instance_wire)?;
+            let adn_length = instance_wire.take_byte()?;
+            let authentication_domain_name = read_adn(&mut instance_wire, adn_length as usize)?;
+            if instance_wire.is_end_reached() {
+                instances.push(Self::new_adn_only(service_priority, None, authentication_domain_name));
+                continue;
+            }
+            let address_length = instance_wire.take_byte()?;
+            let addresses = read_ipv4_addresses(&mut instance_wire, address_length as usize)?;
+            let service_parameters = read_service_parameters(&mut instance_wire)?;
+            instances.push(Self::new_validated(service_priority, None, authentication_domain_name, addresses, service_parameters)?);
+        }
+        Ok(instances)
+    }
+
+    /// Parses an IPv6 Router Advertisement DNR option, including its type and length octets.
+    ///
+    /// https://datatracker.ietf.org/doc/html/rfc9463#section-6.1
+    pub fn from_ra_option(option: &[u8]) -> Result<Self, DnrError> {
+        let mut wire = ReadWire::from_bytes(option);
+        let option_type = wire.take_byte()?;
+        if option_type != RA_OPTION_TYPE {
+            return Err(DnrError::UnexpectedRaOptionType(option_type));
+        }
+        // The length is in units of 8 octets and includes the type and length fields.
+        let option_length = (wire.take_byte()? as usize) * 8;
+        let mut wire = wire.take_as_read_wire(option_length.saturating_sub(2))?;
+        let service_priority = read_service_priority(&mut wire)?;
+        let lifetime = u32::from_wire_format(&mut wire)?;
+        let adn_length = u16::from_wire_format(&mut wire)?;
+        let authentication_domain_name = read_adn(&mut wire, adn_length as usize)?;
+        // Anything left in ADN-only mode is padding.
+        let address_length = match wire.current_len() {
+            0..=1 => 0,
+            _ => u16::from_wire_format(&mut wire)?,
+        };
+        if address_length == 0 {
+            return Ok(Self::new_adn_only(service_priority, Some(lifetime), authentication_domain_name));
+        }
+        let addresses = read_ipv6_addresses(&mut wire, address_length as usize)?;
+        let service_parameters_length = u16::from_wire_format(&mut wire)?;
+        let mut service_parameters_wire = wire.take_as_read_wire(service_parameters_length as usize)?;
+        let service_parameters = read_service_parameters(&mut service_parameters_wire)?;
+        Self::new_validated(service_priority, Some(lifetime), authentication_domain_name, addresses, service_parameters)
+    }
+
+    #[inline]
+    fn new_adn_only(service_priority: u16, lifetime: Option<u32>, authentication_domain_name: CDomainName) -> Self {
+        Self { service_priority, lifetime, authentication_domain_name, addresses: vec![], service_parameters: vec![] }
+    }
+
+    fn new_validated(service_priority: u16, lifetime: Option<u32>, authentication_domain_name: CDomainName, addresses: Vec<IpAddr>, service_parameters: Vec<(SvcParamKey, Vec<u8>)>) -> Result<Self, DnrError> {
+        let instance = Self { service_priority, lifetime, authentication_domain_name, addresses, service_parameters };
+        // The "alpn" parameter is mandatory whenever addresses are provided.
+        // https://datatracker.ietf.org/doc/html/rfc9463#section-3.1.5
+        if !instance.is_adn_only() && instance.alpn()?.is_empty() {
+            return Err(DnrError::MissingAlpn);
+        }
+        Ok(instance)
+    }
+}
+
+#[inline]
+fn read_service_priority(wire: &mut ReadWire) -> Result<u16, DnrError> {
+    match u16::from_wire_format(wire)? {
+        0 => Err(DnrError::ZeroServicePriority),
+        service_priority => Ok(service_priority),
+    }
+}
+
+#[inline]
+fn read_adn(wire: &mut ReadWire, adn_length: usize) -> Result<CDomainName, DnrError> {
+    let mut adn_wire = wire.take_as_read_wire(adn_length)?;
+    Ok(CDomainName::from_wire_format(&mut adn_wire)?)
+}
+
+fn read_ipv4_addresses(wire: &mut ReadWire, address_length: usize) -> Result<Vec<IpAddr>, DnrError> {
+    if (address_length % 4) != 0 {
+        return Err(DnrError::InvalidAddressLength { address_length, address_size: 4 });
+    }
+    let mut address_wire = wire.take_as_read_wire(address_length)?;
+    let mut addresses = Vec::with_capacity(address_length / 4);
+    while !address_wire.is_end_reached() {
+        addresses.push(IpAddr::V4(Ipv4Addr::from_wire_format(&mut address_wire)?));
+    }
+    Ok(addresses)
+}
+
+fn read_ipv6_addresses(wire: &mut ReadWire, address_length: usize) -> Result<Vec<IpAddr>, DnrError> {
+    if (address_length % 16) != 0 {
+        return Err(DnrError::InvalidAddressLength { address_length, address_size: 16 });
+    }
+    let mut address_wire = wire.take_as_read_wire(address_length)?;
+    let mut addresses = Vec::with_capacity(address_length / 16);
+    while !address_wire.is_end_reached() {
+        addresses.push(IpAddr::V6(Ipv6Addr::from_wire_format(&mut address_wire)?));
+    }
+    Ok(addresses)
+}
+
+/// Consumes the rest of the wire as a list of SvcParams.
+///
+/// https://datatracker.ietf.org/doc/html/rfc9460#section-2.2
+fn read_service_parameters(wire: &mut ReadWire) -> Result<Vec<(SvcParamKey, Vec<u8>)>, DnrError> {
+    let mut service_parameters = Vec::new();
+    while !wire.is_end_reached() {
+        let key = SvcParamKey::from_wire_format(wire)?;
+        let length = u16::from_wire_format(wire)?;
+        let value = wire.take(length as usize)?.to_vec();
+        service_parameters.push((key, value));
+    }
+    Ok(service_parameters)
+}
+
+#[cfg(test)]
+mod dnr_tests {
+    use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+    use crate::types::c_domain_name::CDomainName;
+
+    use super::{DnrError, DnrInstance};
+
+    const ADN: &[u8] = b"\x03dns\x07example\x03net\x00";
+    // alpn = "dot", port = 853
+    const SVC_PARAMS: &[u8] = &[0, 1, 0, 4, 3, b'd', b'o', b't', 0, 3, 0, 2, 0x03, 0x55];
+
+    fn adn() -> CDomainName {
+        CDomainName::from_utf8("dns.example.net.").unwrap()
+    }
+
+    #[test]
+    fn dhcpv6_instance() {
+        let mut option = vec![0, 10, 0, ADN.len() as u8];
+        option.extend_from_slice(ADN);
+        option.extend_from_slice(&[0, 16]);
+        option.extend_from_slice(&Ipv6Addr::LOCALHOST.octets());
+        option.extend_from_slice(SVC_PARAMS);
+
+        let instance = DnrInstance::from_dhcpv6_option(&option).unwrap();
+        assert_eq!(instance.service_priority(), 10);
+        assert_eq!(instance.authentication_domain_name(), &adn());
+        assert_eq!(instance.addresses(), &[IpAddr::V6(Ipv6Addr::LOCALHOST)]);
+        assert_eq!(instance.alpn().unwrap(), vec!["dot".to_string()]);
+        assert_eq!(instance.port().unwrap(), Some(853));
+    }
+
+    #[test]
+    fn dhcpv4_adn_only_and_full_instances() {
+        let mut option = Vec::new();
+        // ADN-only instance.
+        option.extend_from_slice(&[0, (3 + ADN.len()) as u8, 0, 1, ADN.len() as u8]);
+        option.extend_from_slice(ADN);
+        // Full instance.
+        option.extend_from_slice(&[0, (3 + ADN.len() + 1 + 4 + SVC_PARAMS.len()) as u8, 0, 2, ADN.len() as u8]);
+        option.extend_from_slice(ADN);
+        option.push(4);
+        option.extend_from_slice(&[192, 0, 2, 1]);
+        option.extend_from_slice(SVC_PARAMS);
+
+        let instances = DnrInstance::from_dhcpv4_option(&option).unwrap();
+        assert_eq!(instances.len(), 2);
+        assert!(instances[0].is_adn_only());
+        assert_eq!(instances[1].addresses(), &[IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1))]);
+    }
+
+    #[test]
+    fn ra_instance() {
+        let mut option = vec![144, 0, 0, 5, 0, 0, 0x0e, 0x10, 0, ADN.len() as u8];
+        option.extend_from_slice(ADN);
+        option.extend_from_slice(&[0, 16]);
+        option.extend_from_slice(&Ipv6Addr::LOCALHOST.octets());
+        option.extend_from_slice(&[0, SVC_PARAMS.len() as u8]);
+        option.extend_from_slice(SVC_PARAMS);
+        while (option.len() % 8) != 0 {
+            option.push(0);
+        }
+        option[1] = (option.len() / 8) as u8;
+
+        let instance = DnrInstance::from_ra_option(&option).unwrap();
+        assert_eq!(instance.lifetime(), Some(3600));
+        assert_eq!(instance.service_priority(), 5);
+        assert_eq!(instance.alpn().unwrap(), vec!["dot".to_string()]);
+    }
+
+    #[test]
+    fn missing_alpn() {
+        let mut option = vec![0, 10, 0, ADN.len() as u8];
+        option.extend_from_slice(ADN);
+        option.extend_from_slice(&[0, 16]);
+        option.extend_from_slice(&Ipv6Addr::LOCALHOST.octets());
+        assert_eq!(DnrInstance::from_dhcpv6_option(&option), Err(DnrError::MissingAlpn));
+    }
+
+    #[test]
+    fn zero_priority() {
+        let mut option = vec![0, 0, 0, ADN.len() as u8];
+        option.extend_from_slice(ADN);
+        assert_eq!(DnrInstance::from_dhcpv6_option(&option), Err(DnrError::ZeroServicePriority));
+    }
+}
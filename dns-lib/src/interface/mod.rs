@@ -1,4 +1,6 @@
 pub mod client;
 pub mod server;
+pub mod trace;
 
 pub mod cache;
+pub mod dnr;
@@ -0,0 +1,76 @@
+use std::{fmt::{Debug, Display}, net::SocketAddr, sync::Arc};
+
+use async_trait::async_trait;
+
+use crate::query::message::Message;
+
+/// The transport that a request was received over.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RequestTransport {
+    Udp,
+    Tcp,
+    Tls,
+    Quic,
+    Https,
+}
+
+impl RequestTransport {
+    /// Whether the requestor's address was verified by the transport's handshake. Responses sent
+    /// over UDP can be reflected at a spoofed address, so they are the ones worth rate limiting.
+    #[inline]
+    pub const fn is_connection_oriented(&self) -> bool {
+        match self {
+            Self::Udp => false,
+            Self::Tcp | Self::Tls | Self::Quic | Self::Https => true,
+        }
+    }
+}
+
+impl Display for RequestTransport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Udp => write!(f, "udp"),
+            Self::Tcp => write!(f, "tcp"),
+            Self::Tls => write!(f, "tls"),
+            Self::Quic => write!(f, "quic"),
+            Self::Https => write!(f, "https"),
+        }
+    }
+}
+
+/// Who sent a request and how it was received.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct RequestInfo {
+    pub peer: SocketAddr,
+    /// The address that the request was received on.
+    pub local: SocketAddr,
+    pub transport: RequestTransport,
+}
+
+/// A query received by a server, along with who sent it.
+#[derive(Debug, Clone)]
+pub struct Request {
+    pub message: Message,
+    pub info: RequestInfo,
+}
+
+/// Turns requests into responses. Handlers are meant to be layered: a handler can wrap another one
+/// and decide whether to pass the request on, change the response that comes back, or answer by
+/// itself. A server is given the outermost handler.
+///
+/// Returning `None` sends no response at all, such as when a request is dropped by a rate limit.
+#[async_trait]
+pub trait QueryHandler: Debug + Send + Sync {
+    async fn handle(&self, request: &Request) -> Option<Message>;
+}
+
+/// A handler that can be shared between the tasks of a server.
+pub type SharedQueryHandler = Arc<dyn QueryHandler>;
+
+#[async_trait]
+impl<H> QueryHandler for Arc<H> where H: QueryHandler + ?Sized {
+    #[inline]
+    async fn handle(&self, request: &Request) -> Option<Message> {
+        self.as_ref().handle(request).await
+    }
+}
@@ -0,0 +1,550 @@
+use std::{collections::HashMap, fmt::{self, Write}, net::IpAddr, sync::Mutex, time::{Duration, Instant}};
+
+use crate::{query::{message::Message, nsid::{response_nsid, Nsid}, question::Question}, resource_record::{rcode::RCode, rtype::RType}, types::c_domain_name::{CDomainName, CmpDomainName}};
+
+/// What came back from a single query sent to a name server.
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub enum QueryOutcome {
+    /// The server answered the question or returned an error rcode.
+    Answer {
+        rcode: RCode,
+        answers: usize,
+        authoritative: bool,
+    },
+    /// The server delegated the question to the name servers of a child zone.
+    Referral {
+        zone: CDomainName,
+        name_servers: usize,
+    },
+    /// No usable response was received.
+    Error(String),
+}
+
+impl QueryOutcome {
+    /// Classifies a response as either an answer or a referral. A response is a referral if it has
+    /// no answers and its authority section contains NS records but no SOA record.
+    pub fn from_response(response: &Message) -> Self {
+        let is_referral = (response.rcode == RCode::NoError)
+            && response.answer.is_empty()
+            && !response.authority.iter().any(|record| record.get_rtype() == RType::SOA);
+        let delegation = response.authority.iter().find(|record| record.get_rtype() == RType::NS);
+        match delegation {
+            Some(ns_record) if is_referral => Self::Referral {
+                zone: ns_record.get_name().as_lowercase(),
+                name_servers: response.authority.iter().filter(|record| record.get_rtype() == RType::NS).count(),
+            },
+            _ => Self::Answer {
+                rcode: response.rcode,
+                answers: response.answer.len(),
+                authoritative: response.authoritative_answer,
+            },
+        }
+    }
+}
+
+impl fmt::Display for QueryOutcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Answer { rcode, answers, authoritative: true } => write!(f, "{rcode} ({answers} answers, authoritative)"),
+            Self::Answer { rcode, answers, authoritative: false } => write!(f, "{rcode} ({answers} answers)"),
+            Self::Referral { zone, name_servers } => write!(f, "referral to {zone} ({name_servers} name servers)"),
+            Self::Error(error) => write!(f, "error: {error}"),
+        }
+    }
+}
+
+/// The transport that carried an attempt.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum TraceTransport {
+    Udp,
+    Tcp,
+    Tls,
+    Quic,
+    Https,
+}
+
+impl fmt::Display for TraceTransport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Udp => write!(f, "udp"),
+            Self::Tcp => write!(f, "tcp"),
+            Self::Tls => write!(f, "tls"),
+            Self::Quic => write!(f, "quic"),
+            Self::Https => write!(f, "https"),
+        }
+    }
+}
+
+/// One attempt at getting a response from a name server. A query is attempted again if, for
+/// example, the UDP response was truncated and the query has to be repeated over TCP.
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub struct TransportAttempt {
+    pub transport: TraceTransport,
+    /// When the attempt was started, relative to the start of the trace.
+    pub sent: Duration,
+    pub elapsed: Duration,
+    /// The length of the query in octets, excluding any two octet length prefix.
+    pub query_size: usize,
+    /// The estimated length of the response in octets (with name compression), if one was
+    /// received.
+    pub response_size: Option<usize>,
+    /// The UDP payload size advertised in the query's OPT record, if it had one.
+    pub udp_payload_size: Option<u16>,
+    /// Whether the response had the TC bit set.
+    pub truncated: bool,
+    /// The number of times the query was sent again within this attempt after the first
+    /// transmission went unanswered.
+    pub retransmissions: u8,
+    /// The retransmission timeout in effect, if the transport retransmits.
+    pub retransmission_timeout: Option<Duration>,
+    /// The timeout in effect.
+    pub timeout: Duration,
+}
+
+impl fmt::Display for TransportAttempt {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}B", self.transport, self.query_size)?;
+        if let Some(response_size) = self.response_size {
+            write!(f, "/{response_size}B")?;
+        }
+        if let Some(udp_payload_size) = self.udp_payload_size {
+            write!(f, " bufsize {udp_payload_size}")?;
+        }
+        write!(f, " {}ms", self.elapsed.as_millis())?;
+        if self.truncated {
+            write!(f, " truncated")?;
+        }
+        if self.retransmissions != 0 {
+            write!(f, " {} retransmissions", self.retransmissions)?;
+        }
+        match self.retransmission_timeout {
+            Some(retransmission_timeout) => write!(f, " (rto {}ms, timeout {}ms)", retransmission_timeout.as_millis(), self.timeout.as_millis()),
+            None => write!(f, " (timeout {}ms)", self.timeout.as_millis()),
+        }
+    }
+}
+
+/// A single query sent to a single name server.
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub struct ServerQuery {
+    pub question: Question,
+    pub server: IpAddr,
+    /// When the query was sent, relative to the start of the trace.
+    pub sent: Duration,
+    pub elapsed: Duration,
+    pub outcome: QueryOutcome,
+    /// The identifier the server returned in the NSID option, if any.
+    pub nsid: Option<Nsid>,
+    /// The attempts made to get the response, in the order they were made. This is empty if the
+    /// transport details were not recorded.
+    pub attempts: Vec<TransportAttempt>,
+}
+
+/// A record of the queries made to name servers while resolving a single question. The trace can
+/// be shared by all of the concurrent lookups (CNAME targets, name server addresses, etc.) that
+/// make up the resolution.
+#[derive(Debug)]
+pub struct QueryTrace {
+    start: Instant,
+    queries: Mutex<Vec<ServerQuery>>,
+}
+
+impl Default for QueryTrace {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl QueryTrace {
+    #[inline]
+    pub fn new() -> Self {
+        Self { start: Instant::now(), queries: Mutex::new(Vec::new()) }
+    }
+
+    #[inline]
+    pub fn start(&self) -> Instant { self.start }
+
+    /// The time since the trace was started.
+    #[inline]
+    pub fn elapsed(&self) -> Duration { self.start.elapsed() }
+
+    /// Records a query that was sent at `sent` and finished with the response (or error).
+    #[inline]
+    pub fn record<E: fmt::Display>(&self, question: &Question, server: IpAddr, sent: Instant, response: Result<&Message, E>) {
+        self.record_attempts(question, server, sent, response, Vec::new())
+    }
+
+    /// Records a query that was sent at `sent` and finished with the response (or error), along
+    /// with the attempts that were made to get it.
+    pub fn record_attempts<E: fmt::Display>(&self, question: &Question, server: IpAddr, sent: Instant, response: Result<&Message, E>, attempts: Vec<TransportAttempt>) {
+        let (outcome, nsid) = match response {
+            Ok(response) => (QueryOutcome::from_response(response), response_nsid(response)),
+            Err(error) => (QueryOutcome::Error(error.to_string()), None),
+        };
+        self.push(ServerQuery {
+            question: question.clone(),
+            server,
+            sent: sent.saturating_duration_since(self.start),
+            elapsed: sent.elapsed(),
+            outcome,
+            nsid,
+            attempts,
+        });
+    }
+
+    #[inline]
+    pub fn push(&self, query: ServerQuery) {
+        self.queries.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).push(query);
+    }
+
+    /// The queries in the order that they completed.
+    #[inline]
+    pub fn queries(&self) -> Vec<ServerQuery> {
+        self.queries.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).clone()
+    }
+
+    /// Writes the trace as a Graphviz DOT digraph. Zones form the delegation tree, with an edge
+    /// from each zone to the servers that were queried for it. Each query is a separate edge so
+    /// that retries are visible.
+    pub fn write_dot(&self, out: &mut impl Write) -> fmt::Result {
+        let queries = self.queries();
+        let graph = TraceGraph::new(&queries);
+        writeln!(out, "digraph query_trace {{")?;
+        writeln!(out, "    rankdir=LR;")?;
+        for zone in &graph.zones {
+            writeln!(out, "    {} [label={}, shape=box];", dot_id("zone", &zone.to_string()), dot_string(&zone.to_string()))?;
+        }
+        for (parent, child) in &graph.delegations {
+            writeln!(out, "    {} -> {} [style=dashed];", dot_id("zone", &parent.to_string()), dot_id("zone", &child.to_string()))?;
+        }
+        for (server, nsid) in &graph.servers {
+            let label = match nsid {
+                Some(nsid) => format!("{server}\\n{nsid}"),
+                None => server.to_string(),
+            };
+            writeln!(out, "    {} [label={}, shape=ellipse];", dot_id("server", &server.to_string()), dot_string(&label))?;
+        }
+        for edge in &graph.edges {
+            let query = edge.query;
+            let mut label = format!(
+                "#{} {} {} attempt {}\\n+{}ms, {}ms\\n{}",
+                edge.index, query.question.qname(), query.question.qtype(), edge.attempt,
+                query.sent.as_millis(), query.elapsed.as_millis(), query.outcome,
+            );
+            for attempt in &query.attempts {
+                write!(label, "\\n{attempt}")?;
+            }
+            let color = match &query.outcome {
+                QueryOutcome::Error(_) => "red",
+                QueryOutcome::Answer { rcode, .. } if *rcode != RCode::NoError => "orange",
+                QueryOutcome::Answer { .. } | QueryOutcome::Referral { .. } => "black",
+            };
+            writeln!(out, "    {} -> {} [label={}, color={color}];", dot_id("zone", &edge.zone.to_string()), dot_id("server", &query.server.to_string()), dot_string(&label))?;
+        }
+        writeln!(out, "}}")
+    }
+
+    #[inline]
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::new();
+        self.write_dot(&mut dot).expect("writing to a String does not fail");
+        dot
+    }
+
+    /// Writes the trace as text, one line per query in the order they completed, similar to
+    /// `dig +trace`. Each line names the zone the server was queried for, followed by the attempts
+    /// made to get the response.
+    pub fn write_text(&self, out: &mut impl Write) -> fmt::Result {
+        let queries = self.queries();
+        let graph = TraceGraph::new(&queries);
+        for edge in &graph.edges {
+            let query = edge.query;
+            write!(
+                out,
+                "{}. {} @{} {} {}: {} (+{}ms, {}ms)",
+                edge.index, edge.zone, query.server, query.question.qname(), query.question.qtype(),
+                query.outcome, query.sent.as_millis(), query.elapsed.as_millis(),
+            )?;
+            if let Some(nsid) = &query.nsid {
+                write!(out, " nsid {nsid}")?;
+            }
+            writeln!(out)?;
+            for attempt in &query.attempts {
+                writeln!(out, "    {attempt}")?;
+            }
+        }
+        Ok(())
+    }
+
+    #[inline]
+    pub fn to_text(&self) -> String {
+        let mut text = String::new();
+        self.write_text(&mut text).expect("writing to a String does not fail");
+        text
+    }
+
+    /// Writes the trace as a JSON object containing the delegations between zones and the list of
+    /// queries, in the order they completed.
+    pub fn write_json(&self, out: &mut impl Write) -> fmt::Result {
+        let queries = self.queries();
+        let graph = TraceGraph::new(&queries);
+        write!(out, "{{\"delegations\":[")?;
+        for (index, (parent, child)) in graph.delegations.iter().enumerate() {
+            if index != 0 {
+                write!(out, ",")?;
+            }
+            write!(out, "{{\"parent\":{},\"child\":{}}}", json_string(&parent.to_string()), json_string(&child.to_string()))?;
+        }
+        write!(out, "],\"queries\":[")?;
+        for (index, edge) in graph.edges.iter().enumerate() {
+            let query = edge.query;
+            if index != 0 {
+                write!(out, ",")?;
+            }
+            write!(
+                out,
+                "{{\"zone\":{},\"qname\":{},\"qtype\":{},\"qclass\":{},\"server\":{},\"attempt\":{},\"sent_us\":{},\"elapsed_us\":{},",
+                json_string(&edge.zone.to_string()),
+                json_string(&query.question.qname().to_string()),
+                json_string(&query.question.qtype().to_string()),
+                json_string(&query.question.qclass().to_string()),
+                json_string(&query.server.to_string()),
+                edge.attempt,
+                query.sent.as_micros(),
+                query.elapsed.as_micros(),
+            )?;
+            match &query.outcome {
+                QueryOutcome::Answer { rcode, answers, authoritative } => write!(out, "\"outcome\":\"answer\",\"rcode\":{},\"answers\":{answers},\"authoritative\":{authoritative}", json_string(&rcode.to_string()))?,
+                QueryOutcome::Referral { zone, name_servers } => write!(out, "\"outcome\":\"referral\",\"referral\":{},\"name_servers\":{name_servers}", json_string(&zone.to_string()))?,
+                QueryOutcome::Error(error) => write!(out, "\"outcome\":\"error\",\"error\":{}", json_string(error))?,
+            }
+            if let Some(nsid) = &query.nsid {
+                write!(out, ",\"nsid\":{}", json_string(&nsid.to_string()))?;
+            }
+            if !query.attempts.is_empty() {
+                write!(out, ",\"attempts\":[")?;
+                for (index, attempt) in query.attempts.iter().enumerate() {
+                    if index != 0 {
+                        write!(out, ",")?;
+                    }
+                    write_json_attempt(out, attempt)?;
+                }
+                write!(out, "]")?;
+            }
+            write!(out, "}}")?;
+        }
+        write!(out, "]}}")
+    }
+
+    #[inline]
+    pub fn to_json(&self) -> String {
+        let mut json = String::new();
+        self.write_json(&mut json).expect("writing to a String does not fail");
+        json
+    }
+}
+
+struct TraceEdge<'a> {
+    index: usize,
+    zone: CDomainName,
+    attempt: usize,
+    query: &'a ServerQuery,
+}
+
+/// The delegation tree reconstructed from the referrals in a trace.
+struct TraceGraph<'a> {
+    zones: Vec<CDomainName>,
+    delegations: Vec<(CDomainName, CDomainName)>,
+    servers: Vec<(IpAddr, Option<Nsid>)>,
+    edges: Vec<TraceEdge<'a>>,
+}
+
+impl<'a> TraceGraph<'a> {
+    fn new(queries: &'a [ServerQuery]) -> Self {
+        let root = CDomainName::new_root();
+        let mut zones = vec![root.clone()];
+        let mut delegations = Vec::new();
+        let mut servers: Vec<(IpAddr, Option<Nsid>)> = Vec::new();
+        let mut attempts: HashMap<(&Question, IpAddr), usize> = HashMap::new();
+        let mut edges = Vec::with_capacity(queries.len());
+
+        for (index, query) in queries.iter().enumerate() {
+            // The query was sent to a server for the deepest zone known so far that contains the
+            // qname.
+            let qname = query.question.qname();
+            let zone = zones.iter()
+                .filter(|zone| zone.is_parent_domain_of(qname))
+                .max_by_key(|zone| zone.label_count())
+                .cloned()
+                .unwrap_or_else(|| root.clone());
+
+            if let QueryOutcome::Referral { zone: child, name_servers: _ } = &query.outcome {
+                if !zones.contains(child) {
+                    zones.push(child.clone());
+                    delegations.push((zone.clone(), child.clone()));
+                }
+            }
+
+            match servers.iter_mut().find(|(server, _)| *server == query.server) {
+                Some((_, nsid)) => if query.nsid.is_some() { *nsid = query.nsid.clone() },
+                None => servers.push((query.server, query.nsid.clone())),
+            }
+
+            let attempt = attempts.entry((&query.question, query.server)).or_insert(0);
+            *attempt += 1;
+            edges.push(TraceEdge { index: index + 1, zone, attempt: *attempt, query });
+        }
+        Self { zones, delegations, servers, edges }
+    }
+}
+
+fn write_json_attempt(out: &mut impl Write, attempt: &TransportAttempt) -> fmt::Result {
+    write!(
+        out,
+        "{{\"transport\":{},\"sent_us\":{},\"elapsed_us\":{},\"query_size\":{},\"truncated\":{},\"retransmissions\":{},\"timeout_us\":{}",
+        json_string(&attempt.transport.to_string()),
+        attempt.sent.as_micros(),
+        attempt.elapsed.as_micros(),
+        attempt.query_size,
+        attempt.truncated,
+        attempt.retransmissions,
+        attempt.timeout.as_micros(),
+    )?;
+    if let Some(response_size) = attempt.response_size {
+        write!(out, ",\"response_size\":{response_size}")?;
+    }
+    if let Some(udp_payload_size) = attempt.udp_payload_size {
+        write!(out, ",\"udp_payload_size\":{udp_payload_size}")?;
+    }
+    if let Some(retransmission_timeout) = attempt.retransmission_timeout {
+        write!(out, ",\"retransmission_timeout_us\":{}", retransmission_timeout.as_micros())?;
+    }
+    write!(out, "}}")
+}
+
+fn dot_id(kind: &str, name: &str) -> String {
+    dot_string(&format!("{kind}:{name}"))
+}
+
+fn dot_string(string: &str) -> String {
+    // Backslashes are kept as-is so that the "\n" line breaks in labels are interpreted by
+    // Graphviz. None of the names written here can contain a backslash of their own since domain
+    // names are written with decimal escapes.
+    format!("\"{}\"", string.replace('"', "\\\""))
+}
+
+fn json_string(string: &str) -> String {
+    let mut escaped = String::with_capacity(string.len() + 2);
+    escaped.push('"');
+    for character in string.chars() {
+        match character {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            character if character.is_control() => escaped.push_str(&format!("\\u{:04x}", character as u32)),
+            character => escaped.push(character),
+        }
+    }
+    escaped.push('"');
+    escaped
+}
+
+#[cfg(test)]
+mod trace_tests {
+    use std::{net::{IpAddr, Ipv4Addr}, time::Duration};
+
+    use crate::{query::{nsid::Nsid, question::Question}, resource_record::{rclass::RClass, rcode::RCode, rtype::RType}, types::c_domain_name::CDomainName};
+
+    use super::{QueryOutcome, QueryTrace, ServerQuery, TraceTransport, TransportAttempt};
+
+    fn server_query(qname: &str, server: u8, sent_ms: u64, outcome: QueryOutcome) -> ServerQuery {
+        ServerQuery {
+            question: Question::new(CDomainName::from_utf8(qname).unwrap(), RType::A, RClass::Internet),
+            server: IpAddr::V4(Ipv4Addr::new(192, 0, 2, server)),
+            sent: Duration::from_millis(sent_ms),
+            elapsed: Duration::from_millis(10),
+            outcome,
+            nsid: None,
+            attempts: Vec::new(),
+        }
+    }
+
+    fn trace() -> QueryTrace {
+        let trace = QueryTrace::new();
+        trace.push(server_query("com.", 1, 0, QueryOutcome::Error("timed out".to_string())));
+        trace.push(server_query("com.", 1, 20, QueryOutcome::Referral { zone: CDomainName::from_utf8("com.").unwrap(), name_servers: 13 }));
+        trace.push(server_query("example.com.", 2, 40, QueryOutcome::Referral { zone: CDomainName::from_utf8("example.com.").unwrap(), name_servers: 2 }));
+        let mut answer = server_query("www.example.com.", 3, 60, QueryOutcome::Answer { rcode: RCode::NoError, answers: 1, authoritative: true });
+        answer.nsid = Some(Nsid::from_utf8("ns1.lax"));
+        answer.attempts = vec![
+            TransportAttempt {
+                transport: TraceTransport::Udp,
+                sent: Duration::from_millis(60),
+                elapsed: Duration::from_millis(4),
+                query_size: 45,
+                response_size: Some(1200),
+                udp_payload_size: Some(1232),
+                truncated: true,
+                retransmissions: 1,
+                retransmission_timeout: Some(Duration::from_millis(300)),
+                timeout: Duration::from_millis(1000),
+            },
+            TransportAttempt {
+                transport: TraceTransport::Tcp,
+                sent: Duration::from_millis(64),
+                elapsed: Duration::from_millis(6),
+                query_size: 45,
+                response_size: Some(3100),
+                udp_payload_size: Some(1232),
+                truncated: false,
+                retransmissions: 0,
+                retransmission_timeout: None,
+                timeout: Duration::from_millis(2000),
+            },
+        ];
+        trace.push(answer);
+        trace
+    }
+
+    #[test]
+    fn dot_contains_delegation_tree() {
+        let dot = trace().to_dot();
+        assert!(dot.starts_with("digraph query_trace {\n"));
+        assert!(dot.ends_with("}\n"));
+        assert!(dot.contains("\"zone:.\" -> \"zone:com.\" [style=dashed];"));
+        assert!(dot.contains("\"zone:com.\" -> \"zone:example.com.\" [style=dashed];"));
+        // The retry is a second edge to the same server.
+        assert!(dot.contains("attempt 1"));
+        assert!(dot.contains("attempt 2"));
+        assert!(dot.contains("\"zone:example.com.\" -> \"server:192.0.2.3\""));
+        assert!(dot.contains("192.0.2.3\\nns1.lax"));
+        assert!(dot.contains("color=red"));
+        assert!(dot.contains("\\nudp 45B/1200B bufsize 1232 4ms truncated 1 retransmissions (rto 300ms, timeout 1000ms)\\ntcp 45B/3100B"));
+    }
+
+    #[test]
+    fn text() {
+        let text = trace().to_text();
+        let lines = text.lines().collect::<Vec<_>>();
+        assert_eq!(lines.len(), 6);
+        assert_eq!(lines[0], "1. . @192.0.2.1 com. A: error: timed out (+0ms, 10ms)");
+        assert_eq!(lines[1], "2. . @192.0.2.1 com. A: referral to com. (13 name servers) (+20ms, 10ms)");
+        assert_eq!(lines[2], "3. com. @192.0.2.2 example.com. A: referral to example.com. (2 name servers) (+40ms, 10ms)");
+        assert_eq!(lines[3], "4. example.com. @192.0.2.3 www.example.com. A: NoError (1 answers, authoritative) (+60ms, 10ms) nsid ns1.lax");
+        assert_eq!(lines[4], "    udp 45B/1200B bufsize 1232 4ms truncated 1 retransmissions (rto 300ms, timeout 1000ms)");
+        assert!(lines[5].starts_with("    tcp 45B/3100B"));
+    }
+
+    #[test]
+    fn json() {
+        let json = trace().to_json();
+        assert!(json.starts_with("{\"delegations\":[{\"parent\":\".\",\"child\":\"com.\"},{\"parent\":\"com.\",\"child\":\"example.com.\"}],\"queries\":["));
+        assert!(json.contains("\"outcome\":\"error\",\"error\":\"timed out\""));
+        assert!(json.contains("\"zone\":\"example.com.\",\"qname\":\"www.example.com.\""));
+        assert!(json.contains("\"nsid\":\"ns1.lax\""));
+        assert!(json.contains("\"attempts\":[{\"transport\":\"udp\",\"sent_us\":60000,\"elapsed_us\":4000,\"query_size\":45,\"truncated\":true,\"retransmissions\":1,\"timeout_us\":1000000,\"response_size\":1200,\"udp_payload_size\":1232,\"retransmission_timeout_us\":300000},{\"transport\":\"tcp\""));
+        assert!(json.ends_with("}]}"));
+    }
+}
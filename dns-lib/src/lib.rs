@@ -5,5 +5,9 @@ pub mod serde;
 
 pub mod resource_record;
 pub mod query;
+pub mod dnssec;
+pub mod tsig;
 
 pub mod interface;
+
+pub mod prelude;
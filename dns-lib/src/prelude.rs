@@ -0,0 +1,34 @@
+//! The types and traits needed for typical uses of the crate, so that they can be imported
+//! together instead of from the modules that define them:
+//!
+//! ```
+//! use dns_lib::prelude::*;
+//!
+//! let question = Question::new(CDomainName::from_utf8("example.com.").unwrap(), RType::AAAA, RClass::Internet);
+//! let message = Message::from(question);
+//! let bytes = message.to_wire_vec(&mut Some(CompressionMap::new())).unwrap();
+//! let parsed = Message::from_wire_format(&mut ReadWire::from_bytes(&bytes)).unwrap();
+//! assert_eq!(parsed.question[0].qtype(), RType::AAAA);
+//! ```
+//!
+//! Everything here is part of the crate's stable surface. Items are only added once their API has
+//! settled, and removing or renaming one is a breaking change.
+
+pub use crate::{
+    interface::{client::{Answer, AsyncClient, Context, ErrorResponse, QNameMinimization, Response}, trace::QueryTrace},
+    query::{edns::Edns, message::Message, qr::QR, question::Question},
+    resource_record::{
+        opcode::OpCode,
+        rclass::RClass,
+        rcode::RCode,
+        resource_record::{RecordData, ResourceRecord},
+        rtype::RType,
+        time::Time,
+        types::{a::A, aaaa::AAAA, caa::CAA, cname::CNAME, dnskey::DNSKEY, ds::DS, https::HTTPS, mx::MX, ns::NS, ptr::PTR, rrsig::RRSIG, soa::SOA, srv::SRV, svcb::SVCB, txt::TXT},
+    },
+    serde::{
+        presentation::{from_presentation::FromPresentation, to_presentation::{PresentationOptions, ToPresentation}},
+        wire::{from_wire::FromWire, read_wire::ReadWire, to_wire::ToWire, write_wire::WriteWire},
+    },
+    types::{c_domain_name::{CDomainName, CmpDomainName, CompressionMap}, domain_name::DomainName},
+};
@@ -0,0 +1,180 @@
+use crate::{resource_record::{rclass::RClass, rcode::RCode, resource_record::{RecordData, ResourceRecord}, rtype::RType, time::Time, types::txt::TXT}, types::{ascii::AsciiString, c_domain_name::CDomainName}};
+
+use super::{message::Message, qr::QR, question::Question};
+
+/// The well-known CHAOS class names that a name server can be asked in order to identify itself.
+/// These are answered with TXT records in the CH class.
+///
+/// https://datatracker.ietf.org/doc/html/rfc4892#section-2
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum ChaosQuery {
+    /// `id.server.`, the standardized name for the server's identity.
+    IdServer,
+    /// `hostname.bind.`, the name originally used by BIND for the server's identity.
+    HostnameBind,
+    /// `version.server.`, the standardized name for the server's software version.
+    VersionServer,
+    /// `version.bind.`, the name originally used by BIND for the server's software version.
+    VersionBind,
+}
+
+impl ChaosQuery {
+    pub const ALL: [Self; 4] = [Self::IdServer, Self::HostnameBind, Self::VersionServer, Self::VersionBind];
+
+    #[inline]
+    pub const fn name(&self) -> &'static str {
+        match self {
+            Self::IdServer      => "id.server.",
+            Self::HostnameBind  => "hostname.bind.",
+            Self::VersionServer => "version.server.",
+            Self::VersionBind   => "version.bind.",
+        }
+    }
+
+    /// Whether the query asks for the identity of the server, as opposed to its version.
+    #[inline]
+    pub const fn is_identity(&self) -> bool {
+        matches!(self, Self::IdServer | Self::HostnameBind)
+    }
+
+    /// The CH TXT question for this name.
+    #[inline]
+    pub fn question(&self) -> Question {
+        Question::new(
+            CDomainName::from_utf8(self.name()).expect("CHAOS query names are valid domain names"),
+            RType::TXT,
+            RClass::Chaos,
+        )
+    }
+
+    /// Identifies which of the well-known names is being asked for. Names are compared
+    /// case-insensitively. Questions that are not in the CH class never match.
+    pub fn from_question(question: &Question) -> Option<Self> {
+        if question.qclass() != RClass::Chaos {
+            return None;
+        }
+        let qname = question.qname().as_lowercase();
+        Self::ALL.into_iter().find(|query| query.question().qname() == &qname)
+    }
+}
+
+/// The values a server answers CHAOS introspection queries with. A value that is not configured
+/// is not disclosed.
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Default)]
+pub struct ChaosResponder {
+    pub identity: Option<String>,
+    pub version: Option<String>,
+}
+
+impl ChaosResponder {
+    #[inline]
+    pub fn new(identity: Option<String>, version: Option<String>) -> Self {
+        Self { identity, version }
+    }
+
+    /// Answers the query if it is a CHAOS introspection query. Queries for values that are not
+    /// configured, or for other names in the CH class, are refused. `None` is returned for queries
+    /// that should be handled normally.
+    pub fn answer(&self, query: &Message) -> Option<Message> {
+        let question = match query.question() {
+            [question] if question.qclass() == RClass::Chaos => question,
+            _ => return None,
+        };
+
+        let mut response = query.clone();
+        response.qr = QR::Response;
+        response.authoritative_answer = true;
+        response.recursion_available = false;
+        response.answer.clear();
+        response.authority.clear();
+        response.additional.clear();
+
+        let value = ChaosQuery::from_question(question)
+            .and_then(|chaos_query| match chaos_query.is_identity() {
+                true => self.identity.as_ref(),
+                false => self.version.as_ref(),
+            });
+        let txt = value.and_then(|value| AsciiString::from_utf8(value).ok());
+        match (txt, question.qtype()) {
+            (Some(txt), RType::TXT | RType::ANY) => {
+                response.rcode = RCode::NoError;
+                response.answer.push(ResourceRecord::new(
+                    question.qname().clone(),
+                    RClass::Chaos,
+                    Time::from_secs(0),
+                    RecordData::TXT(TXT::from_octets(txt.as_slice())),
+                ));
+            },
+            // The name exists but has no data of the requested type.
+            (Some(_), _) => response.rcode = RCode::NoError,
+            (None, _) => response.rcode = RCode::Refused,
+        }
+        Some(response)
+    }
+}
+
+/// The text of the CH TXT records that answer the question in the response. Each record's
+/// character-strings are joined together.
+pub fn chaos_txt(response: &Message) -> Vec<String> {
+    let qname = match response.question() {
+        [question] => question.qname().as_lowercase(),
+        _ => return Vec::new(),
+    };
+    response.answer().iter()
+        .filter(|record| record.get_rclass() == RClass::Chaos)
+        .filter(|record| record.get_name().as_lowercase() == qname)
+        .filter_map(|record| match record.get_rdata() {
+            RecordData::TXT(txt) => Some(txt.concatenated().to_string()),
+            _ => None,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod chaos_tests {
+    use crate::{query::{message::Message, qr::QR, question::Question}, resource_record::{rclass::RClass, rcode::RCode, rtype::RType}, serde::wire::circular_test::circular_serde, types::c_domain_name::CDomainName};
+
+    use super::{chaos_txt, ChaosQuery, ChaosResponder};
+
+    #[test]
+    fn from_question() {
+        for query in ChaosQuery::ALL {
+            assert_eq!(ChaosQuery::from_question(&query.question()), Some(query));
+        }
+        let upper = Question::new(CDomainName::from_utf8("ID.Server.").unwrap(), RType::TXT, RClass::Chaos);
+        assert_eq!(ChaosQuery::from_question(&upper), Some(ChaosQuery::IdServer));
+        let internet = Question::new(CDomainName::from_utf8("id.server.").unwrap(), RType::TXT, RClass::Internet);
+        assert_eq!(ChaosQuery::from_question(&internet), None);
+    }
+
+    #[test]
+    fn answers_configured_values() {
+        let responder = ChaosResponder::new(Some("ns1.lax.example".to_string()), Some("1.0.0".to_string()));
+
+        let response = circular_serde(&responder.answer(&Message::from(ChaosQuery::HostnameBind.question())).unwrap());
+        assert_eq!(response.qr, QR::Response);
+        assert_eq!(response.rcode, RCode::NoError);
+        assert_eq!(chaos_txt(&response), vec!["ns1.lax.example".to_string()]);
+
+        let response = circular_serde(&responder.answer(&Message::from(ChaosQuery::VersionBind.question())).unwrap());
+        assert_eq!(chaos_txt(&response), vec!["1.0.0".to_string()]);
+    }
+
+    #[test]
+    fn refuses_unconfigured_values() {
+        let responder = ChaosResponder::new(Some("ns1.lax.example".to_string()), None);
+        let response = responder.answer(&Message::from(ChaosQuery::VersionServer.question())).unwrap();
+        assert_eq!(response.rcode, RCode::Refused);
+        assert!(response.answer.is_empty());
+
+        let other = Question::new(CDomainName::from_utf8("authors.bind.").unwrap(), RType::TXT, RClass::Chaos);
+        assert_eq!(responder.answer(&Message::from(other)).unwrap().rcode, RCode::Refused);
+    }
+
+    #[test]
+    fn ignores_other_classes() {
+        let responder = ChaosResponder::new(Some("ns1.lax.example".to_string()), None);
+        let question = Question::new(CDomainName::from_utf8("id.server.").unwrap(), RType::TXT, RClass::Internet);
+        assert!(responder.answer(&Message::from(question)).is_none());
+    }
+}
@@ -0,0 +1,345 @@
+use std::{fmt::{self, Display, Write}, net::SocketAddr, time::Duration};
+
+use crate::{resource_record::{edns_option_code::EDNSOptionCode, opcode::OpCode, resource_record::{RecordData, ResourceRecord}}, serde::presentation::to_presentation::{PresentationOptions, ToPresentation}};
+
+use super::{message::Message, nsid::Nsid};
+
+/// The flags in the header's Z field, from the most significant bit.
+///
+/// https://datatracker.ietf.org/doc/html/rfc4035#section-3.2
+const Z_FLAGS: [(u8, &str); 3] = [(0b100, "z"), (0b010, "ad"), (0b001, "cd")];
+
+/// Details about the exchange that are not part of the message itself. They are written in the
+/// footer of the dig-style output, and any that are not known are left out.
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Default)]
+pub struct DigMetadata {
+    server: Option<SocketAddr>,
+    transport: Option<String>,
+    query_time: Option<Duration>,
+    message_size: Option<usize>,
+}
+
+impl DigMetadata {
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The address the message was received from.
+    #[inline]
+    pub fn with_server(mut self, server: SocketAddr) -> Self {
+        self.server = Some(server);
+        self
+    }
+
+    /// The transport the message was received over, such as "UDP" or "TLS".
+    #[inline]
+    pub fn with_transport(mut self, transport: impl Into<String>) -> Self {
+        self.transport = Some(transport.into());
+        self
+    }
+
+    /// The time between sending the query and receiving the message.
+    #[inline]
+    pub fn with_query_time(mut self, query_time: Duration) -> Self {
+        self.query_time = Some(query_time);
+        self
+    }
+
+    /// The number of octets the message took up on the wire.
+    #[inline]
+    pub fn with_message_size(mut self, message_size: usize) -> Self {
+        self.message_size = Some(message_size);
+        self
+    }
+}
+
+/// Writes a message the same way `dig` does: a header line and flags line, the OPT pseudosection,
+/// each non-empty section with its records in aligned columns, and a footer with the metadata.
+///
+/// ```
+/// use dns_lib::{query::{dig::{Dig, DigMetadata}, message::Message, question::Question}, resource_record::{rclass::RClass, rtype::RType}, types::c_domain_name::CDomainName};
+///
+/// let question = Question::new(CDomainName::from_utf8("example.com.").unwrap(), RType::A, RClass::Internet);
+/// let message = Message::from(question);
+/// let output = Dig::new(&message, &DigMetadata::new().with_message_size(29)).to_string();
+/// assert!(output.starts_with(";; ->>HEADER<<- opcode: QUERY, status: NOERROR, id: 0\n"));
+/// assert!(output.ends_with(";; MSG SIZE  rcvd: 29\n"));
+/// ```
+#[derive(Clone, Copy, Debug)]
+pub struct Dig<'a> {
+    message: &'a Message,
+    metadata: &'a DigMetadata,
+    options: PresentationOptions,
+}
+
+impl<'a> Dig<'a> {
+    #[inline]
+    pub fn new(message: &'a Message, metadata: &'a DigMetadata) -> Self {
+        Self { message, metadata, options: PresentationOptions::default() }
+    }
+
+    /// Annotated records are followed by a comment, like `dig +rrcomments` writes them.
+    #[inline]
+    pub fn with_options(mut self, options: PresentationOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    fn write_header(&self, out: &mut impl Write) -> fmt::Result {
+        let message = self.message;
+        writeln!(out, ";; ->>HEADER<<- opcode: {}, status: {}, id: {}", opcode_mnemonic(message.opcode), message.rcode.mnemonic().to_uppercase(), message.id)?;
+
+        out.write_str(";; flags:")?;
+        let header_flags = [
+            (message.qr.is_response(), "qr"),
+            (message.authoritative_answer, "aa"),
+            (message.truncation, "tc"),
+            (message.recursion_desired, "rd"),
+            (message.recursion_available, "ra"),
+        ];
+        let z = u8::from(message.z);
+        let z_flags = Z_FLAGS.map(|(bit, flag)| ((z & bit) != 0, flag));
+        for (_, flag) in header_flags.iter().chain(&z_flags).filter(|(set, _)| *set) {
+            write!(out, " {flag}")?;
+        }
+        writeln!(out, "; QUERY: {}, ANSWER: {}, AUTHORITY: {}, ADDITIONAL: {}", message.question.len(), message.answer.len(), message.authority.len(), message.additional.len())
+    }
+
+    fn write_opt(&self, out: &mut impl Write) -> fmt::Result {
+        let Some(edns) = self.message.edns() else {
+            return Ok(());
+        };
+        writeln!(out, "\n;; OPT PSEUDOSECTION:")?;
+        write!(out, "; EDNS: version: {}, flags:", edns.version())?;
+        if edns.dnssec_ok() {
+            out.write_str(" do")?;
+        }
+        writeln!(out, "; udp: {}", edns.udp_payload_size())?;
+        for option in edns.options() {
+            match option.code() {
+                EDNSOptionCode::NSID => writeln!(out, "; NSID: {}", Nsid::new(option.data().to_vec()))?,
+                EDNSOptionCode::Padding => writeln!(out, "; PAD: ({} bytes)", option.data().len())?,
+                code => {
+                    write!(out, "; {}: ", code.mnemonic().to_uppercase())?;
+                    write_hex(out, option.data())?;
+                    writeln!(out)?;
+                },
+            }
+        }
+        Ok(())
+    }
+
+    fn write_question(&self, out: &mut impl Write) -> fmt::Result {
+        if self.message.question.is_empty() {
+            return Ok(());
+        }
+        writeln!(out, "\n;; QUESTION SECTION:")?;
+        let rows = self.message.question.iter()
+            .map(|question| {
+                let mut tokens = Vec::new();
+                question.qname().to_presentation_format(&mut tokens);
+                question.qclass().to_presentation_format(&mut tokens);
+                question.qtype().to_presentation_format(&mut tokens);
+                tokens[0].insert(0, ';');
+                tokens
+            })
+            .collect::<Vec<_>>();
+        write_aligned(out, &rows)
+    }
+
+    fn write_section(&self, out: &mut impl Write, name: &str, records: &[ResourceRecord]) -> fmt::Result {
+        // The OPT record is written in its own pseudosection instead.
+        let records = records.iter().filter(|record| !matches!(record.get_rdata(), RecordData::OPT(_)));
+        let (presentable, comments): (Vec<_>, Vec<_>) = records.partition(|record| record.get_rdata().presentation_allowed());
+        if presentable.is_empty() && comments.is_empty() {
+            return Ok(());
+        }
+        writeln!(out, "\n;; {name} SECTION:")?;
+        let rows = presentable.iter()
+            .map(|record| {
+                let mut tokens = Vec::new();
+                record.to_presentation_format(&mut tokens);
+                if let Some(comment) = self.options.annotate.then(|| record.presentation_comment()).flatten() {
+                    tokens.push(format!("; {comment}"));
+                }
+                tokens
+            })
+            .collect::<Vec<_>>();
+        write_aligned(out, &rows)?;
+        for record in comments {
+            writeln!(out, "; {} {} record", record.get_name(), record.get_rtype())?;
+        }
+        Ok(())
+    }
+
+    fn write_footer(&self, out: &mut impl Write) -> fmt::Result {
+        let metadata = self.metadata;
+        if metadata.query_time.is_none() && metadata.server.is_none() && metadata.message_size.is_none() {
+            return Ok(());
+        }
+        writeln!(out)?;
+        if let Some(query_time) = metadata.query_time {
+            writeln!(out, ";; Query time: {} msec", query_time.as_millis())?;
+        }
+        if let Some(server) = metadata.server {
+            write!(out, ";; SERVER: {}#{}({})", server.ip(), server.port(), server.ip())?;
+            if let Some(transport) = &metadata.transport {
+                write!(out, " ({transport})")?;
+            }
+            writeln!(out)?;
+        }
+        if let Some(message_size) = metadata.message_size {
+            writeln!(out, ";; MSG SIZE  rcvd: {message_size}")?;
+        }
+        Ok(())
+    }
+}
+
+impl Display for Dig<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.write_header(f)?;
+        self.write_opt(f)?;
+        self.write_question(f)?;
+        self.write_section(f, "ANSWER", &self.message.answer)?;
+        self.write_section(f, "AUTHORITY", &self.message.authority)?;
+        self.write_section(f, "ADDITIONAL", &self.message.additional)?;
+        self.write_footer(f)
+    }
+}
+
+/// The opcode the way dig writes it, which is not the same as its mnemonic.
+fn opcode_mnemonic(opcode: OpCode) -> String {
+    match opcode {
+        OpCode::Unknown(code) => format!("RESERVED{code}"),
+        OpCode::Query => String::from("QUERY"),
+        OpCode::IQuery => String::from("IQUERY"),
+        OpCode::Status => String::from("STATUS"),
+        OpCode::Notify => String::from("NOTIFY"),
+        OpCode::Update => String::from("UPDATE"),
+        OpCode::DNSStatefulOperations => String::from("DSO"),
+    }
+}
+
+/// Writes one row per line. Every column except the last is padded to the width of the widest
+/// token in that column, and the tokens past the last padded column (the RDATA) are separated by
+/// single spaces.
+fn write_aligned(out: &mut impl Write, rows: &[Vec<String>]) -> fmt::Result {
+    const PADDED_COLUMNS: usize = 4;
+    let mut widths = [0; PADDED_COLUMNS];
+    for row in rows {
+        for (width, token) in widths.iter_mut().zip(row) {
+            *width = (*width).max(token.len());
+        }
+    }
+    for row in rows {
+        let padded = row.len().min(PADDED_COLUMNS);
+        for (index, token) in row.iter().enumerate() {
+            if index > 0 {
+                out.write_char(' ')?;
+            }
+            // The last token on a line is not padded, so that lines do not end with spaces.
+            match (index < padded) && (index + 1 < row.len()) {
+                true => write!(out, "{token:<width$}", width = widths[index])?,
+                false => out.write_str(token)?,
+            }
+        }
+        writeln!(out)?;
+    }
+    Ok(())
+}
+
+fn write_hex(out: &mut impl Write, data: &[u8]) -> fmt::Result {
+    for octet in data {
+        write!(out, "{octet:02x}")?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod dig_tests {
+    use std::{net::{Ipv4Addr, SocketAddr}, time::Duration};
+
+    use ux::u3;
+
+    use crate::{query::{message::Message, nsid::{answer_nsid, request_nsid, Nsid}, qr::QR, question::Question}, resource_record::{digest_alg::DigestAlgorithm, dnssec_alg::DnsSecAlgorithm, rclass::RClass, rcode::RCode, resource_record::{RecordData, ResourceRecord}, rtype::RType, time::Time, types::{a::A, ds::DS, ns::NS}}, serde::presentation::to_presentation::PresentationOptions, types::{base16::Base16, c_domain_name::CDomainName}};
+
+    use super::{Dig, DigMetadata};
+
+    fn name(name: &str) -> CDomainName {
+        CDomainName::from_utf8(name).unwrap()
+    }
+
+    #[test]
+    fn responses_are_written_like_dig() {
+        let mut query = Message::from(Question::new(name("www.example.com."), RType::A, RClass::Internet));
+        query.id = 4660;
+        query.recursion_desired = true;
+        request_nsid(&mut query);
+
+        let mut response = query.clone();
+        response.qr = QR::Response;
+        response.recursion_available = true;
+        response.z = u3::new(0b010);
+        response.answer.push(ResourceRecord::new(name("www.example.com."), RClass::Internet, Time::from_secs(300), RecordData::A(A::new(Ipv4Addr::new(192, 0, 2, 1)))));
+        response.authority.push(ResourceRecord::new(name("example.com."), RClass::Internet, Time::from_secs(86400), RecordData::NS(NS::new(name("ns1.example.com.")))));
+        answer_nsid(&query, &mut response, &Nsid::from_utf8("ns1"));
+
+        let metadata = DigMetadata::new()
+            .with_server(SocketAddr::new(Ipv4Addr::new(192, 0, 2, 53).into(), 53))
+            .with_transport("UDP")
+            .with_query_time(Duration::from_millis(12))
+            .with_message_size(95);
+        assert_eq!(Dig::new(&response, &metadata).to_string(), "\
+;; ->>HEADER<<- opcode: QUERY, status: NOERROR, id: 4660
+;; flags: qr rd ra ad; QUERY: 1, ANSWER: 1, AUTHORITY: 1, ADDITIONAL: 1
+
+;; OPT PSEUDOSECTION:
+; EDNS: version: 0, flags:; udp: 1232
+; NSID: ns1
+
+;; QUESTION SECTION:
+;www.example.com. IN A
+
+;; ANSWER SECTION:
+www.example.com. 300 IN A 192.0.2.1
+
+;; AUTHORITY SECTION:
+example.com. 86400 IN NS ns1.example.com.
+
+;; Query time: 12 msec
+;; SERVER: 192.0.2.53#53(192.0.2.53) (UDP)
+;; MSG SIZE  rcvd: 95
+");
+    }
+
+    #[test]
+    fn records_in_a_section_are_aligned() {
+        let mut response = Message::from(Question::new(name("example.com."), RType::NS, RClass::Internet));
+        response.qr = QR::Response;
+        response.rcode = RCode::NXDomain;
+        response.answer.push(ResourceRecord::new(name("example.com."), RClass::Internet, Time::from_secs(300), RecordData::NS(NS::new(name("a.example.com.")))));
+        response.answer.push(ResourceRecord::new(name("b.example.com."), RClass::Internet, Time::from_secs(30), RecordData::A(A::new(Ipv4Addr::new(192, 0, 2, 2)))));
+
+        let output = Dig::new(&response, &DigMetadata::new()).to_string();
+        assert!(output.starts_with(";; ->>HEADER<<- opcode: QUERY, status: NXDOMAIN, id: 0\n;; flags: qr; "));
+        assert!(output.ends_with("\
+;; ANSWER SECTION:
+example.com.   300 IN NS a.example.com.
+b.example.com. 30  IN A  192.0.2.2
+"));
+    }
+
+    #[test]
+    fn annotated_records_are_followed_by_a_comment() {
+        let mut response = Message::from(Question::new(name("example.com."), RType::DS, RClass::Internet));
+        response.qr = QR::Response;
+        let digest = Base16::from_case_insensitive_utf8("2BB183AF5F22588179A53B0A98631FAD1A292118").unwrap();
+        response.answer.push(ResourceRecord::new(name("example.com."), RClass::Internet, Time::from_secs(3600), RecordData::DS(DS::new(60485, DnsSecAlgorithm::RsaSha1, DigestAlgorithm::Sha1, digest))));
+
+        let metadata = DigMetadata::new();
+        assert!(Dig::new(&response, &metadata).to_string().ends_with("IN DS 60485 RSASHA1 1 2BB183AF5F22588179A53B0A98631FAD1A292118\n"));
+        assert!(Dig::new(&response, &metadata).with_options(PresentationOptions::annotated()).to_string()
+            .ends_with("IN DS 60485 RSASHA1 1 2BB183AF5F22588179A53B0A98631FAD1A292118 ; key id = 60485; alg = RSASHA1; digest = SHA-1\n"));
+    }
+}
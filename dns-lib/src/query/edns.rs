@@ -0,0 +1,376 @@
+use crate::{resource_record::{edns_option_code::EDNSOptionCode, rclass::RClass, rcode::RCode, resource_record::{RecordData, ResourceRecord}, time::Time, types::opt::{EDNSOption, OPT}}, types::c_domain_name::CDomainName};
+
+use super::{message::Message, padding::opt_rdata_mut};
+
+/// The UDP payload size to advertise when nothing is known about the path. Responses this large
+/// fit in a single packet on almost every path, so they never rely on IP fragmentation.
+///
+/// https://www.dnsflagday.net/2020/
+pub const DEFAULT_EDNS_BUFFER_SIZE: u16 = 1232;
+
+/// Requestors must be able to receive messages of at least this size, and an advertised size
+/// below it is treated as this size.
+///
+/// https://datatracker.ietf.org/doc/html/rfc6891#section-6.2.5
+pub const MINIMUM_EDNS_BUFFER_SIZE: u16 = 512;
+
+/// The DO bit within the TTL field of the OPT record.
+///
+/// https://datatracker.ietf.org/doc/html/rfc3225#section-3
+const DNSSEC_OK_FLAG: u32 = 0x0000_8000;
+
+/// The position of the upper 8 bits of the extended RCODE and of the EDNS version within the TTL
+/// field of the OPT record.
+///
+/// https://datatracker.ietf.org/doc/html/rfc6891#section-6.1.3
+const EXTENDED_RCODE_SHIFT: u32 = 24;
+const VERSION_SHIFT: u32 = 16;
+const VERSION_MASK: u32 = 0x00FF_0000;
+
+/// The number of RCODE bits that fit in the message header. The rest are carried in the OPT
+/// record.
+pub(crate) const HEADER_RCODE_BITS: u16 = 4;
+
+/// An OPT record with no options that advertises `DEFAULT_EDNS_BUFFER_SIZE`.
+pub(crate) fn new_opt_record() -> ResourceRecord {
+    ResourceRecord::new(
+        CDomainName::new_root(),
+        RClass::from_code(DEFAULT_EDNS_BUFFER_SIZE),
+        Time::from_secs(0),
+        RecordData::OPT(OPT::new(vec![])),
+    )
+}
+
+/// The upper 8 bits of the rcode, which are carried in the OPT record's TTL field.
+#[inline]
+pub(crate) fn extended_rcode(rcode: RCode) -> u8 {
+    (rcode.code() >> HEADER_RCODE_BITS) as u8
+}
+
+/// The OPT record's TTL field with its extended RCODE replaced.
+#[inline]
+pub(crate) fn with_extended_rcode(ttl: &Time, extended_rcode: u8) -> Time {
+    Time::from_secs((ttl.as_secs() & !(0xFF << EXTENDED_RCODE_SHIFT)) | (u32::from(extended_rcode) << EXTENDED_RCODE_SHIFT))
+}
+
+/// The extended RCODE that the OPT record's TTL field carries.
+#[inline]
+pub(crate) fn ttl_extended_rcode(ttl: &Time) -> u8 {
+    (ttl.as_secs() >> EXTENDED_RCODE_SHIFT) as u8
+}
+
+/// The EDNS fields of a message, read from its OPT record.
+///
+/// The extended RCODE is not stored separately. It is the upper 8 bits of the message's `rcode`
+/// and is written into the OPT record when the message is serialized, so the two can never
+/// disagree.
+///
+/// https://datatracker.ietf.org/doc/html/rfc6891#section-6.1
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub struct Edns {
+    udp_payload_size: u16,
+    extended_rcode: u8,
+    version: u8,
+    dnssec_ok: bool,
+    options: Vec<EDNSOption>,
+}
+
+impl Edns {
+    pub(crate) fn from_record(record: &ResourceRecord, rcode: RCode) -> Option<Self> {
+        match record.get_rdata() {
+            RecordData::OPT(opt) => Some(Self {
+                udp_payload_size: record.get_rclass().code(),
+                extended_rcode: extended_rcode(rcode),
+                version: ((record.get_ttl().as_secs() & VERSION_MASK) >> VERSION_SHIFT) as u8,
+                dnssec_ok: (record.get_ttl().as_secs() & DNSSEC_OK_FLAG) != 0,
+                options: opt.options().to_vec(),
+            }),
+            _ => None,
+        }
+    }
+
+    /// The largest UDP payload the sender can reassemble. Sizes below `MINIMUM_EDNS_BUFFER_SIZE`
+    /// should be treated as that size.
+    #[inline]
+    pub fn udp_payload_size(&self) -> u16 { self.udp_payload_size }
+
+    #[inline]
+    pub fn extended_rcode(&self) -> u8 { self.extended_rcode }
+
+    #[inline]
+    pub fn version(&self) -> u8 { self.version }
+
+    #[inline]
+    pub fn dnssec_ok(&self) -> bool { self.dnssec_ok }
+
+    #[inline]
+    pub fn options(&self) -> &[EDNSOption] { &self.options }
+
+    #[inline]
+    pub fn option(&self, code: EDNSOptionCode) -> Option<&EDNSOption> {
+        self.options.iter().find(|option| option.code() == code)
+    }
+}
+
+/// Edits the message's OPT record in place. Returned by `Message::edns_mut()`, which makes sure
+/// the message has exactly one OPT record first. The extended RCODE is set through the message's
+/// `rcode`.
+#[derive(Debug)]
+pub struct EdnsMut<'a> {
+    record: &'a mut ResourceRecord,
+}
+
+impl<'a> EdnsMut<'a> {
+    #[inline]
+    pub(crate) fn new(record: &'a mut ResourceRecord) -> Self {
+        debug_assert!(matches!(record.get_rdata(), RecordData::OPT(_)));
+        Self { record }
+    }
+
+    #[inline]
+    pub fn udp_payload_size(&self) -> u16 { self.record.get_rclass().code() }
+
+    /// Sizes below `MINIMUM_EDNS_BUFFER_SIZE` are raised to it.
+    #[inline]
+    pub fn set_udp_payload_size(&mut self, payload_size: u16) {
+        self.record.set_rclass(RClass::from_code(payload_size.max(MINIMUM_EDNS_BUFFER_SIZE)));
+    }
+
+    #[inline]
+    pub fn version(&self) -> u8 {
+        ((self.record.get_ttl().as_secs() & VERSION_MASK) >> VERSION_SHIFT) as u8
+    }
+
+    #[inline]
+    pub fn set_version(&mut self, version: u8) {
+        let ttl = (self.record.get_ttl().as_secs() & !VERSION_MASK) | (u32::from(version) << VERSION_SHIFT);
+        self.record.set_ttl(Time::from_secs(ttl));
+    }
+
+    #[inline]
+    pub fn dnssec_ok(&self) -> bool {
+        (self.record.get_ttl().as_secs() & DNSSEC_OK_FLAG) != 0
+    }
+
+    #[inline]
+    pub fn set_dnssec_ok(&mut self, dnssec_ok: bool) {
+        let ttl = match dnssec_ok {
+            true => self.record.get_ttl().as_secs() | DNSSEC_OK_FLAG,
+            false => self.record.get_ttl().as_secs() & !DNSSEC_OK_FLAG,
+        };
+        self.record.set_ttl(Time::from_secs(ttl));
+    }
+
+    #[inline]
+    pub fn options(&self) -> &[EDNSOption] {
+        match self.record.get_rdata() {
+            RecordData::OPT(opt) => opt.options(),
+            _ => &[],
+        }
+    }
+
+    #[inline]
+    pub fn options_mut(&mut self) -> &mut Vec<EDNSOption> {
+        match self.record.get_rdata_mut() {
+            RecordData::OPT(opt) => opt.options_mut(),
+            _ => unreachable!("EdnsMut is only created for OPT records"),
+        }
+    }
+
+    #[inline]
+    pub fn option(&self, code: EDNSOptionCode) -> Option<&EDNSOption> {
+        self.options().iter().find(|option| option.code() == code)
+    }
+
+    #[inline]
+    pub(crate) fn into_opt(self) -> &'a mut OPT {
+        match self.record.get_rdata_mut() {
+            RecordData::OPT(opt) => opt,
+            _ => unreachable!("EdnsMut is only created for OPT records"),
+        }
+    }
+
+    /// Replaces any options with the same code.
+    pub fn set_option(&mut self, option: EDNSOption) {
+        let options = self.options_mut();
+        options.retain(|existing| existing.code() != option.code());
+        options.push(option);
+    }
+}
+
+/// The UDP payload size advertised by the message's OPT record, if it has one.
+pub fn udp_payload_size(message: &Message) -> Option<u16> {
+    message.additional.iter()
+        .find(|record| matches!(record.get_rdata(), RecordData::OPT(_)))
+        .map(|record| record.get_rclass().code())
+}
+
+/// Advertises the UDP payload size in the message's OPT record, adding an OPT record if the message
+/// does not already have one. Sizes below `MINIMUM_EDNS_BUFFER_SIZE` are raised to it.
+pub fn set_udp_payload_size(message: &mut Message, payload_size: u16) {
+    let payload_size = payload_size.max(MINIMUM_EDNS_BUFFER_SIZE);
+    opt_rdata_mut(message);
+    if let Some(record) = message.additional.iter_mut().find(|record| matches!(record.get_rdata(), RecordData::OPT(_))) {
+        record.set_rclass(RClass::from_code(payload_size));
+    }
+}
+
+/// Lowers the UDP payload size advertised by the message to at most `max_payload_size`. Messages
+/// without an OPT record are left alone since they are already limited to 512 bytes.
+pub fn limit_udp_payload_size(message: &mut Message, max_payload_size: u16) {
+    match udp_payload_size(message) {
+        Some(payload_size) if payload_size > max_payload_size => set_udp_payload_size(message, max_payload_size),
+        _ => (),
+    }
+}
+
+/// Whether the message's OPT record has the DNSSEC OK (DO) bit set. Messages without an OPT record
+/// never do.
+pub fn dnssec_ok(message: &Message) -> bool {
+    message.additional.iter()
+        .find(|record| matches!(record.get_rdata(), RecordData::OPT(_)))
+        .is_some_and(|record| (record.get_ttl().as_secs() & DNSSEC_OK_FLAG) != 0)
+}
+
+/// Sets or clears the DNSSEC OK (DO) bit, which asks the server to include DNSSEC records in its
+/// response. An OPT record is added if the message does not already have one.
+pub fn set_dnssec_ok(message: &mut Message, dnssec_ok: bool) {
+    opt_rdata_mut(message);
+    if let Some(record) = message.additional.iter_mut().find(|record| matches!(record.get_rdata(), RecordData::OPT(_))) {
+        let flags = match dnssec_ok {
+            true => record.get_ttl().as_secs() | DNSSEC_OK_FLAG,
+            false => record.get_ttl().as_secs() & !DNSSEC_OK_FLAG,
+        };
+        record.set_ttl(Time::from_secs(flags));
+    }
+}
+
+#[cfg(test)]
+mod edns_tests {
+    use crate::{query::message::Message, resource_record::{edns_option_code::EDNSOptionCode, rclass::RClass, rcode::RCode, resource_record::{RecordData, ResourceRecord}, time::Time, types::opt::{EDNSOption, OPT}}, serde::wire::circular_test::{circular_serde, example_query, wire_length}, types::c_domain_name::CDomainName};
+
+    use super::{dnssec_ok, limit_udp_payload_size, set_dnssec_ok, set_udp_payload_size, udp_payload_size, DEFAULT_EDNS_BUFFER_SIZE, MINIMUM_EDNS_BUFFER_SIZE};
+
+    fn serializes(message: &Message) -> bool {
+        wire_length(message, &mut None).is_some()
+    }
+
+    fn opt_record() -> ResourceRecord {
+        ResourceRecord::new(CDomainName::new_root(), RClass::from_code(4096), Time::from_secs(0), RecordData::OPT(OPT::new(vec![])))
+    }
+
+    #[test]
+    fn set_adds_single_opt() {
+        let mut message = example_query();
+        assert_eq!(udp_payload_size(&message), None);
+
+        set_udp_payload_size(&mut message, 4096);
+        assert_eq!(udp_payload_size(&message), Some(4096));
+        set_udp_payload_size(&mut message, DEFAULT_EDNS_BUFFER_SIZE);
+        assert_eq!(udp_payload_size(&message), Some(DEFAULT_EDNS_BUFFER_SIZE));
+        assert_eq!(message.additional.len(), 1);
+
+        set_udp_payload_size(&mut message, 100);
+        assert_eq!(udp_payload_size(&message), Some(MINIMUM_EDNS_BUFFER_SIZE));
+    }
+
+    #[test]
+    fn limit_only_lowers() {
+        let mut message = example_query();
+        limit_udp_payload_size(&mut message, DEFAULT_EDNS_BUFFER_SIZE);
+        assert_eq!(udp_payload_size(&message), None);
+
+        set_udp_payload_size(&mut message, 4096);
+        limit_udp_payload_size(&mut message, DEFAULT_EDNS_BUFFER_SIZE);
+        assert_eq!(udp_payload_size(&message), Some(DEFAULT_EDNS_BUFFER_SIZE));
+
+        limit_udp_payload_size(&mut message, 4096);
+        assert_eq!(udp_payload_size(&message), Some(DEFAULT_EDNS_BUFFER_SIZE));
+    }
+
+    #[test]
+    fn dnssec_ok_keeps_payload_size() {
+        let mut message = example_query();
+        assert!(!dnssec_ok(&message));
+
+        set_udp_payload_size(&mut message, DEFAULT_EDNS_BUFFER_SIZE);
+        set_dnssec_ok(&mut message, true);
+        assert!(dnssec_ok(&message));
+        assert_eq!(udp_payload_size(&message), Some(DEFAULT_EDNS_BUFFER_SIZE));
+        assert_eq!(message.additional.len(), 1);
+
+        set_dnssec_ok(&mut message, false);
+        assert!(!dnssec_ok(&message));
+    }
+
+    #[test]
+    fn edns_view() {
+        let mut message = example_query();
+        assert_eq!(message.edns(), None);
+
+        let mut edns = message.edns_mut();
+        edns.set_udp_payload_size(4096);
+        edns.set_dnssec_ok(true);
+        edns.set_option(EDNSOption::new(EDNSOptionCode::NSID, vec![]));
+        edns.set_option(EDNSOption::new(EDNSOptionCode::NSID, vec![1]));
+
+        let edns = circular_serde(&message).edns().unwrap();
+        assert_eq!(edns.udp_payload_size(), 4096);
+        assert_eq!(edns.version(), 0);
+        assert_eq!(edns.extended_rcode(), 0);
+        assert!(edns.dnssec_ok());
+        assert_eq!(edns.options(), &[EDNSOption::new(EDNSOptionCode::NSID, vec![1])]);
+        assert_eq!(udp_payload_size(&message), Some(4096));
+        assert!(dnssec_ok(&message));
+
+        assert_eq!(message.remove_edns(), Some(edns));
+        assert_eq!(message.edns(), None);
+    }
+
+    #[test]
+    fn version_keeps_flags() {
+        let mut message = example_query();
+        message.edns_mut().set_dnssec_ok(true);
+        message.edns_mut().set_version(1);
+        let edns = message.edns().unwrap();
+        assert_eq!(edns.version(), 1);
+        assert!(edns.dnssec_ok());
+    }
+
+    #[test]
+    fn extended_rcode_uses_opt() {
+        let mut message = example_query();
+        message.rcode = RCode::BadVers;
+        assert!(!serializes(&message));
+
+        message.edns_mut();
+        let parsed = circular_serde(&message);
+        assert_eq!(parsed.rcode, RCode::BadVers);
+        assert_eq!(parsed.edns().unwrap().extended_rcode(), 1);
+
+        // The OPT record follows the message's rcode, not the other way around.
+        message.rcode = RCode::NXDomain;
+        let parsed = circular_serde(&message);
+        assert_eq!(parsed.rcode, RCode::NXDomain);
+        assert_eq!(parsed.edns().unwrap().extended_rcode(), 0);
+    }
+
+    #[test]
+    fn single_opt_in_additional() {
+        let mut message = example_query();
+        message.additional.push(opt_record());
+        message.additional.push(opt_record());
+        assert!(!serializes(&message));
+        message.edns_mut();
+        assert_eq!(message.additional.len(), 1);
+        assert!(serializes(&message));
+
+        let mut message = example_query();
+        message.answer.push(opt_record());
+        assert!(!serializes(&message));
+        message.edns_mut();
+        assert!(message.answer.is_empty());
+        assert_eq!(message.edns().unwrap().udp_payload_size(), 4096);
+        assert!(serializes(&message));
+    }
+}
@@ -1,9 +1,14 @@
+use std::fmt;
+
 use tinyvec::TinyVec;
+use tokio::io::AsyncWrite;
 use ux::{u3, u1, u4};
 
-use crate::{resource_record::{resource_record::ResourceRecord, rcode::RCode, opcode::OpCode}, serde::wire::{to_wire::ToWire, from_wire::FromWire, write_wire::WriteWireError, read_wire::ReadWireError}};
+use std::borrow::Cow;
+
+use crate::{resource_record::{resource_record::{RecordData, ResourceRecord}, rcode::RCode, opcode::OpCode, rtype::RType}, serde::{presentation::to_presentation::{PresentationWriter, ToPresentation}, wire::{to_wire::ToWire, from_wire::FromWire, write_wire::{WriteWire, WriteWireError}, read_wire::ReadWireError, stream_write::{write_with_two_octet_length, StreamWriteError, MAX_STREAM_MESSAGE_SIZE}}}, types::c_domain_name::CompressionMap};
 
-use super::{qr::QR, question::Question};
+use super::{edns::{extended_rcode, new_opt_record, ttl_extended_rcode, with_extended_rcode, Edns, EdnsMut, HEADER_RCODE_BITS}, qr::QR, question::{Question, QuestionKey}};
 
 /// https://datatracker.ietf.org/doc/html/rfc1035#section-4
 #[derive(Clone, PartialEq, Hash, Debug)]
@@ -87,6 +92,54 @@ impl Message {
     pub fn additional(&self) -> &[ResourceRecord] {
         &self.additional
     }
+
+    /// The EDNS fields from the message's OPT record, or `None` if the message does not use EDNS.
+    pub fn edns(&self) -> Option<Edns> {
+        self.additional.iter().find_map(|record| Edns::from_record(record, self.rcode))
+    }
+
+    /// Edits the message's OPT record, adding one if the message does not use EDNS yet. Any OPT
+    /// records beyond the first are removed and an OPT record that is not in the additional
+    /// section is moved there, so the message can be serialized afterwards.
+    pub fn edns_mut(&mut self) -> EdnsMut<'_> {
+        let mut misplaced = None;
+        for section in [&mut self.answer, &mut self.authority] {
+            while let Some(index) = section.iter().position(is_opt) {
+                let record = section.remove(index);
+                misplaced.get_or_insert(record);
+            }
+        }
+        let mut seen_opt = misplaced.is_some() && !self.additional.iter().any(is_opt);
+        self.additional.retain(|record| !is_opt(record) || !std::mem::replace(&mut seen_opt, true));
+
+        let index = match self.additional.iter().position(is_opt) {
+            Some(index) => index,
+            None => {
+                // TSIG and SIG(0) records sign everything before them, so they must stay last.
+                let index = self.additional.iter()
+                    .position(|record| matches!(record.get_rtype(), RType::TSIG | RType::SIG))
+                    .unwrap_or(self.additional.len());
+                self.additional.insert(index, misplaced.unwrap_or_else(new_opt_record));
+                index
+            },
+        };
+        EdnsMut::new(&mut self.additional[index])
+    }
+
+    /// Removes the message's OPT records. Upper RCODE bits cannot be sent without one, so they
+    /// should be cleared as well.
+    pub fn remove_edns(&mut self) -> Option<Edns> {
+        let edns = self.edns();
+        self.answer.retain(|record| !is_opt(record));
+        self.authority.retain(|record| !is_opt(record));
+        self.additional.retain(|record| !is_opt(record));
+        edns
+    }
+}
+
+#[inline]
+fn is_opt(record: &ResourceRecord) -> bool {
+    matches!(record.get_rdata(), RecordData::OPT(_))
 }
 
 impl From<Question> for Message {
@@ -133,7 +186,79 @@ fn u1_to_bool(integer: u1) -> bool {
     }
 }
 
+/// The ID, flags, and four section counts.
+const HEADER_LENGTH: usize = 12;
+
 impl Message {
+    /// The normalized question section, used to match up queries that ask for the same thing.
+    #[inline]
+    pub fn question_key(&self) -> QuestionKey {
+        QuestionKey::new(&self.question)
+    }
+
+    /// Computes the number of octets `to_wire_format()` would write for this message without
+    /// serializing it. If `compression` is true, question and owner names are compressed the same
+    /// way they would be when serializing with a `CompressionMap`. Names inside RDATA are counted
+    /// at their uncompressed length, so the result is never smaller than the actual size.
+    pub fn estimated_wire_size(&self, compression: bool) -> usize {
+        let compression = if compression { Some(CompressionMap::new()) } else { None };
+        let mut offset = HEADER_LENGTH;
+        for question in &self.question {
+            offset += question.wire_length(offset, &compression);
+        }
+        for record in self.answer.iter().chain(&self.authority).chain(&self.additional) {
+            offset += record.wire_length(offset, &compression);
+        }
+        offset
+    }
+
+    /// Removes records until the message fits within `max_size` octets. Records in the
+    /// additional section (other than the OPT record) are removed first since they do not need
+    /// to be included. If the answer and authority sections still do not fit, they are cleared
+    /// and the truncation flag is set so that the client retries over TCP.
+    ///
+    /// https://datatracker.ietf.org/doc/html/rfc2181#section-9
+    pub fn truncate_to_fit(&mut self, max_size: usize) {
+        if self.estimated_wire_size(true) <= max_size {
+            return;
+        }
+
+        while let Some(index) = self.additional.iter().rposition(|record| record.get_rtype() != RType::OPT) {
+            self.additional.remove(index);
+            if self.estimated_wire_size(true) <= max_size {
+                return;
+            }
+        }
+
+        self.answer.clear();
+        self.authority.clear();
+        self.truncation = true;
+    }
+
+    /// Writes the message in presentation format without building intermediate `String`s. The
+    /// question section is written as comments followed by the answer, authority, and additional
+    /// records, one per line. Records that have no presentation format (such as OPT) are written
+    /// as comments.
+    pub fn write_presentation(&self, out: &mut impl fmt::Write) -> fmt::Result {
+        let mut writer = PresentationWriter::new(out);
+        for question in &self.question {
+            writer.inner().write_char(';')?;
+            question.qname().write_presentation_tokens(&mut writer)?;
+            question.qclass().write_presentation_tokens(&mut writer)?;
+            question.qtype().write_presentation_tokens(&mut writer)?;
+            writer.end_line()?;
+        }
+        for record in self.answer.iter().chain(&self.authority).chain(&self.additional) {
+            if record.get_rdata().presentation_allowed() {
+                record.write_presentation_tokens(&mut writer)?;
+            } else {
+                write!(writer.inner(), "; {} {} record", record.get_name(), record.get_rtype())?;
+            }
+            writer.end_line()?;
+        }
+        Ok(())
+    }
+
     #[inline]
     pub fn to_wire_format_with_two_octet_length<'a, 'b>(&self, wire: &'b mut crate::serde::wire::write_wire::WriteWire<'a>, compression: &mut Option<crate::types::c_domain_name::CompressionMap>) -> Result<(), crate::serde::wire::write_wire::WriteWireError> where 'a: 'b {
         // Push two bytes onto the wire. These will be replaced with the u16 that indicates the wire
@@ -151,6 +276,36 @@ impl Message {
         }
         wire.write_bytes_at(&(wire_length as u16).to_be_bytes(), two_octet_length_offset)
     }
+
+    /// Serializes the message into a buffer that is sized for it, rather than a fixed size buffer.
+    /// Messages can be as large as a two octet length allows.
+    pub fn to_wire_vec(&self, compression: &mut Option<CompressionMap>) -> Result<Vec<u8>, WriteWireError> {
+        // The estimate is never smaller than the serialized message. Messages that are larger than
+        // the cap do not fit behind a two octet length and fail to serialize.
+        let capacity = self.estimated_wire_size(compression.is_some()).min(MAX_STREAM_MESSAGE_SIZE);
+        let mut raw_message = vec![0_u8; capacity];
+        let mut wire = WriteWire::from_bytes(&mut raw_message);
+        self.to_wire_format(&mut wire, compression)?;
+        let wire_length = wire.current_len();
+        raw_message.truncate(wire_length);
+        Ok(raw_message)
+    }
+
+    /// Serializes the message and writes it to the stream, preceded by its two octet length. This
+    /// is the streaming counterpart to `to_wire_format_with_two_octet_length()`. The length and
+    /// the message are written with a single vectored write. Returns the number of bytes written,
+    /// including the length.
+    #[inline]
+    pub async fn write_with_two_octet_length<W: AsyncWrite + Unpin + ?Sized>(&self, stream: &mut W, compression: &mut Option<CompressionMap>) -> Result<usize, StreamWriteError> {
+        let raw_message = self.to_wire_vec(compression)?;
+        write_with_two_octet_length(stream, &raw_message).await
+    }
+}
+
+impl fmt::Display for Message {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.write_presentation(f)
+    }
 }
 
 impl ToWire for Message {
@@ -168,11 +323,23 @@ impl ToWire for Message {
         let rd = bool_to_u1(self.recursion_desired);
         (qr, opcode, aa, tc, rd).to_wire_format(wire, compression)?;
 
+        // https://datatracker.ietf.org/doc/html/rfc6891#section-6.1.1
+        if self.answer.iter().chain(&self.authority).any(is_opt) {
+            return Err(WriteWireError::FormatError(String::from("An OPT record can only be included in the additional section")));
+        }
+        let opt_count = self.additional.iter().filter(|record| is_opt(record)).count();
+        if opt_count > 1 {
+            return Err(WriteWireError::FormatError(format!("A message can include at most one OPT record but it has {opt_count}")));
+        }
+
         let ra = bool_to_u1(self.recursion_available);
         let z = self.z;
-        let rcode = match self.rcode.code() {
-            rcode @ 0..=255 => u4::new(rcode as u8),
-            rcode @ 256.. => return Err(WriteWireError::OutOfBoundsError(format!("The Message RCode must be within the range 0 to 255 but it was {rcode}"))),
+        // The upper bits of an extended RCode are carried in the OPT record.
+        let rcode = match (self.rcode.code(), opt_count) {
+            (rcode @ 0..=0xF, _) => u4::new(rcode as u8),
+            (rcode @ 0x10..=0xFFF, 1) => u4::new((rcode & 0xF) as u8),
+            (rcode @ 0x10..=0xFFF, _) => return Err(WriteWireError::ValueError(format!("The Message RCode {rcode} can only be sent in a message with an OPT record"))),
+            (rcode @ 0x1000.., _) => return Err(WriteWireError::OutOfBoundsError(format!("The Message RCode must be within the range 0 to 4095 but it was {rcode}"))),
         };
         (ra, z, rcode).to_wire_format(wire, compression)?;
 
@@ -184,7 +351,16 @@ impl ToWire for Message {
         self.question.iter().try_for_each(|question| question.to_wire_format(wire, compression))?;
         self.answer.to_wire_format(wire, compression)?;
         self.authority.to_wire_format(wire, compression)?;
-        self.additional.to_wire_format(wire, compression)
+        let extended_rcode = extended_rcode(self.rcode);
+        self.additional.iter().try_for_each(|record| match record.get_rdata() {
+            RecordData::OPT(_) if ttl_extended_rcode(record.get_ttl()) != extended_rcode => {
+                let mut record = Cow::Borrowed(record);
+                let ttl = with_extended_rcode(record.get_ttl(), extended_rcode);
+                record.to_mut().set_ttl(ttl);
+                record.to_wire_format(wire, compression)
+            },
+            _ => record.to_wire_format(wire, compression),
+        })
     }
 
     #[inline]
@@ -230,7 +406,6 @@ impl FromWire for Message {
         let (ra, z, rcode) = <(u1, u3, u4)>::from_wire_format(wire)?;
 
         let ra = u1_to_bool(ra);
-        let rcode = RCode::from_code(rcode.into());
 
         let mut qd_count = u16::from_wire_format(wire)?;
         let mut an_count = u16::from_wire_format(wire)?;
@@ -259,6 +434,12 @@ impl FromWire for Message {
             ar_count -= 1;
         }
 
+        // The upper bits of an extended RCode are carried in the OPT record.
+        let rcode = match additional.iter().find(|record| is_opt(record)) {
+            Some(opt) => RCode::from_code((u16::from(ttl_extended_rcode(opt.get_ttl())) << HEADER_RCODE_BITS) | u16::from(rcode)),
+            None => RCode::from_code(rcode.into()),
+        };
+
         Ok(Self {
             id,
 
@@ -280,3 +461,136 @@ impl FromWire for Message {
         })
     }
 }
+
+#[cfg(test)]
+mod estimated_wire_size_tests {
+    use std::net::Ipv4Addr;
+
+    use crate::{query::question::Question, resource_record::{rclass::RClass, resource_record::{RecordData, ResourceRecord}, rtype::RType, time::Time, types::{a::A, ns::NS}}, serde::wire::{to_wire::ToWire, write_wire::WriteWire}, types::c_domain_name::{CDomainName, CompressionMap}};
+
+    use super::Message;
+
+    fn response() -> Message {
+        let name = CDomainName::from_utf8("www.example.org.").unwrap();
+        let mut message = Message::from(Question::new(name.clone(), RType::A, RClass::Internet));
+        for octet in 1..=4 {
+            message.answer.push(ResourceRecord::new(name.clone(), RClass::Internet, Time::new(300), RecordData::A(A::new(Ipv4Addr::new(192, 0, 2, octet)))));
+        }
+        message.authority.push(ResourceRecord::new(CDomainName::from_utf8("example.org.").unwrap(), RClass::Internet, Time::new(300), RecordData::NS(NS::new(CDomainName::from_utf8("ns1.example.org.").unwrap()))));
+        message.additional.push(ResourceRecord::new(CDomainName::from_utf8("ns1.example.org.").unwrap(), RClass::Internet, Time::new(300), RecordData::A(A::new(Ipv4Addr::new(192, 0, 2, 53)))));
+        message
+    }
+
+    fn serialized_length(message: &Message, compression: bool) -> usize {
+        let mut buffer = vec![0_u8; u16::MAX as usize];
+        let mut wire = WriteWire::from_bytes(&mut buffer);
+        let mut compression = if compression { Some(CompressionMap::new()) } else { None };
+        message.to_wire_format(&mut wire, &mut compression).unwrap();
+        wire.current_len()
+    }
+
+    #[test]
+    fn matches_serialized_length() {
+        let message = response();
+        assert_eq!(message.estimated_wire_size(false), serialized_length(&message, false));
+        assert_eq!(message.estimated_wire_size(true), serialized_length(&message, true));
+        assert_eq!(message.estimated_wire_size(false), message.serial_length() as usize);
+    }
+
+    #[test]
+    fn truncate_additional_first() {
+        let mut message = response();
+        let without_additional = message.estimated_wire_size(true) - message.additional[0].wire_length(0, &None);
+        message.truncate_to_fit(without_additional);
+        assert!(message.additional.is_empty());
+        assert_eq!(message.answer.len(), 4);
+        assert!(!message.truncation);
+
+        message.truncate_to_fit(64);
+        assert!(message.answer.is_empty());
+        assert!(message.authority.is_empty());
+        assert!(message.truncation);
+    }
+}
+
+#[cfg(test)]
+mod write_presentation_tests {
+    use std::net::Ipv4Addr;
+
+    use crate::{query::question::Question, resource_record::{rclass::RClass, resource_record::{RecordData, ResourceRecord}, rtype::RType, time::Time, types::{a::A, mx::MX, opt::OPT}}, serde::presentation::to_presentation::ToPresentation, types::c_domain_name::CDomainName};
+
+    use super::Message;
+
+    #[test]
+    fn writer_matches_tokens() {
+        let record = ResourceRecord::new(CDomainName::from_utf8("example.org.").unwrap(), RClass::Internet, Time::new(300), RecordData::MX(MX::new(10, CDomainName::from_utf8("mail.example.org.").unwrap())));
+        let mut tokens = Vec::new();
+        record.to_presentation_format(&mut tokens);
+        let mut written = String::new();
+        record.write_presentation(&mut written).unwrap();
+        assert_eq!(written, tokens.join("\t"));
+        assert_eq!(written, "example.org.\t300\tIN\tMX\t10\tmail.example.org.");
+    }
+
+    #[test]
+    fn message_records_one_per_line() {
+        let name = CDomainName::from_utf8("www.example.org.").unwrap();
+        let mut message = Message::from(Question::new(name.clone(), RType::A, RClass::Internet));
+        message.answer.push(ResourceRecord::new(name.clone(), RClass::Internet, Time::new(60), RecordData::A(A::new(Ipv4Addr::new(192, 0, 2, 1)))));
+        message.additional.push(ResourceRecord::new(CDomainName::new_root(), RClass::Unknown(1232), Time::new(0), RecordData::OPT(OPT::new(vec![]))));
+
+        let mut written = String::new();
+        message.write_presentation(&mut written).unwrap();
+        assert_eq!(written, ";www.example.org.\tIN\tA\nwww.example.org.\t60\tIN\tA\t192.0.2.1\n; . OPT record\n");
+    }
+
+    #[test]
+    fn forbidden_records_use_generic_format() {
+        let record = ResourceRecord::new(CDomainName::new_root(), RClass::Unknown(1232), Time::new(0), RecordData::OPT(OPT::new(vec![])));
+        let mut tokens = Vec::new();
+        record.to_presentation_format(&mut tokens);
+        assert_eq!(&tokens[tokens.len() - 2..], &["\\#", "0"]);
+        assert!(record.to_string().ends_with("OPT\t\\#\t0"));
+    }
+}
+
+#[cfg(test)]
+mod malformed_wire_tests {
+    use std::net::Ipv4Addr;
+
+    use crate::{query::question::Question, resource_record::{rclass::RClass, resource_record::{RecordData, ResourceRecord}, rtype::RType, time::Time, types::a::A}, serde::wire::{from_wire::FromWire, read_wire::ReadWire, to_wire::ToWire, write_wire::WriteWire}, types::c_domain_name::{CDomainName, CompressionMap}};
+
+    use super::Message;
+
+    fn serialized_response() -> Vec<u8> {
+        let name = CDomainName::from_utf8("www.example.org.").unwrap();
+        let mut message = Message::from(Question::new(name.clone(), RType::A, RClass::Internet));
+        message.answer.push(ResourceRecord::new(name, RClass::Internet, Time::new(300), RecordData::A(A::new(Ipv4Addr::new(192, 0, 2, 1)))));
+
+        let mut buffer = vec![0_u8; u16::MAX as usize];
+        let mut wire = WriteWire::from_bytes(&mut buffer);
+        message.to_wire_format(&mut wire, &mut Some(CompressionMap::new())).unwrap();
+        let length = wire.current_len();
+        buffer.truncate(length);
+        buffer
+    }
+
+    #[test]
+    fn truncated_messages_are_errors() {
+        let response = serialized_response();
+        assert!(Message::from_wire_format(&mut ReadWire::from_bytes(&response)).is_ok());
+        for length in 0..response.len() {
+            assert!(Message::from_wire_format(&mut ReadWire::from_bytes(&response[..length])).is_err(), "truncated to {length} bytes");
+        }
+    }
+
+    #[test]
+    fn oversized_rdlength_is_error() {
+        let mut response = serialized_response();
+        // The RDLENGTH of the only answer comes right before its 4 byte address.
+        let rd_length_index = response.len() - 6;
+        assert_eq!(&response[rd_length_index..(rd_length_index + 2)], &[0, 4]);
+        response[rd_length_index..(rd_length_index + 2)].copy_from_slice(&u16::MAX.to_be_bytes());
+        assert!(Message::from_wire_format(&mut ReadWire::from_bytes(&response)).is_err());
+    }
+}
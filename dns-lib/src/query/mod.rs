@@ -1,3 +1,9 @@
 pub mod message;
 pub mod question;
 pub mod qr;
+pub mod padding;
+pub mod nsid;
+pub mod chaos;
+pub mod edns;
+pub mod dig;
+
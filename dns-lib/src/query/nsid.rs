@@ -0,0 +1,142 @@
+use std::fmt::Display;
+
+use crate::resource_record::{edns_option_code::EDNSOptionCode, resource_record::RecordData, types::opt::EDNSOption};
+
+use super::{message::Message, padding::opt_rdata_mut};
+
+/// The identifier a name server returns in the NSID option. The contents are chosen by the
+/// operator and have no defined structure. They are often the host name of the particular
+/// instance of an anycast service that answered the query.
+///
+/// https://datatracker.ietf.org/doc/html/rfc5001#section-2.1
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub struct Nsid {
+    identifier: Vec<u8>,
+}
+
+impl Nsid {
+    #[inline]
+    pub fn new(identifier: Vec<u8>) -> Self {
+        Self { identifier }
+    }
+
+    #[inline]
+    pub fn from_utf8(identifier: &str) -> Self {
+        Self { identifier: identifier.as_bytes().to_vec() }
+    }
+
+    #[inline]
+    pub fn as_bytes(&self) -> &[u8] { &self.identifier }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool { self.identifier.is_empty() }
+
+    /// The identifier as a string, if every octet is printable ASCII.
+    pub fn as_printable(&self) -> Option<&str> {
+        if self.identifier.iter().all(|octet| octet.is_ascii_graphic() || (*octet == b' ')) {
+            std::str::from_utf8(&self.identifier).ok()
+        } else {
+            None
+        }
+    }
+}
+
+impl Display for Nsid {
+    /// Printable identifiers are written as text. Anything else is written in hexadecimal since
+    /// the identifier does not need to be text.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.as_printable() {
+            Some(identifier) => write!(f, "{identifier}"),
+            None => {
+                for octet in &self.identifier {
+                    write!(f, "{octet:02x}")?;
+                }
+                Ok(())
+            },
+        }
+    }
+}
+
+/// Adds an empty NSID option to the query (creating the OPT record if needed), which asks the
+/// name server to identify itself in the response.
+///
+/// https://datatracker.ietf.org/doc/html/rfc5001#section-2.1
+pub fn request_nsid(query: &mut Message) {
+    let opt = opt_rdata_mut(query);
+    if opt.option(EDNSOptionCode::NSID).is_none() {
+        opt.options_mut().push(EDNSOption::new(EDNSOptionCode::NSID, vec![]));
+    }
+}
+
+/// Whether the message contains an NSID option.
+pub fn has_nsid(message: &Message) -> bool {
+    message.additional.iter().any(|record| match record.get_rdata() {
+        RecordData::OPT(opt) => opt.option(EDNSOptionCode::NSID).is_some(),
+        _ => false,
+    })
+}
+
+/// The identifier the name server included in the response. Servers that do not support NSID, or
+/// that choose not to identify themselves, do not return one.
+pub fn response_nsid(response: &Message) -> Option<Nsid> {
+    response.additional.iter()
+        .find_map(|record| match record.get_rdata() {
+            RecordData::OPT(opt) => opt.option(EDNSOptionCode::NSID),
+            _ => None,
+        })
+        .filter(|option| !option.data().is_empty())
+        .map(|option| Nsid::new(option.data().to_vec()))
+}
+
+/// Adds the server's identifier to a response, but only if the query it answers asked for it.
+/// Servers must not include the NSID option in responses to queries that did not request it.
+///
+/// https://datatracker.ietf.org/doc/html/rfc5001#section-2.2
+pub fn answer_nsid(query: &Message, response: &mut Message, nsid: &Nsid) {
+    if !has_nsid(query) {
+        return;
+    }
+    let opt = opt_rdata_mut(response);
+    opt.options_mut().retain(|option| option.code() != EDNSOptionCode::NSID);
+    opt.options_mut().push(EDNSOption::new(EDNSOptionCode::NSID, nsid.as_bytes().to_vec()));
+}
+
+#[cfg(test)]
+mod nsid_tests {
+    use crate::serde::wire::circular_test::{circular_serde, example_query};
+
+    use super::{answer_nsid, has_nsid, request_nsid, response_nsid, Nsid};
+
+    #[test]
+    fn request_is_idempotent() {
+        let mut message = example_query();
+        assert!(!has_nsid(&message));
+        request_nsid(&mut message);
+        request_nsid(&mut message);
+        assert!(has_nsid(&message));
+        assert_eq!(message.additional.len(), 1);
+        // An empty request is not an identifier.
+        assert_eq!(response_nsid(&message), None);
+    }
+
+    #[test]
+    fn answered_only_if_requested() {
+        let nsid = Nsid::from_utf8("ns1.lax.example");
+
+        let mut response = example_query();
+        answer_nsid(&example_query(), &mut response, &nsid);
+        assert!(response.additional.is_empty());
+
+        let mut requesting_query = example_query();
+        request_nsid(&mut requesting_query);
+        answer_nsid(&requesting_query, &mut response, &nsid);
+        assert_eq!(response_nsid(&circular_serde(&response)), Some(nsid));
+    }
+
+    #[test]
+    fn display() {
+        assert_eq!(Nsid::from_utf8("ns1.lax.example").to_string(), "ns1.lax.example");
+        assert_eq!(Nsid::new(vec![0x00, 0xab, 0x10]).to_string(), "00ab10");
+        assert_eq!(Nsid::new(vec![]).to_string(), "");
+    }
+}
@@ -0,0 +1,126 @@
+use crate::{resource_record::{edns_option_code::EDNSOptionCode, resource_record::RecordData, types::opt::{EDNSOption, OPT}}, serde::wire::write_wire::WriteWireError};
+
+use super::message::Message;
+
+/// The block length that clients should pad queries to.
+///
+/// https://datatracker.ietf.org/doc/html/rfc8467#section-4.1
+pub const QUERY_BLOCK_LENGTH: u16 = 128;
+/// The block length that servers should pad responses to.
+///
+/// https://datatracker.ietf.org/doc/html/rfc8467#section-4.1
+pub const RESPONSE_BLOCK_LENGTH: u16 = 468;
+
+/// Padding should only be applied to messages sent over encrypted transports. Padding a message
+/// sent in the clear wastes bandwidth without hiding anything.
+///
+/// https://datatracker.ietf.org/doc/html/rfc7830#section-6
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum PaddingPolicy {
+    None,
+    BlockLength(u16),
+}
+
+impl PaddingPolicy {
+    /// The Block-Length Padding strategy recommended for queries.
+    pub const RECOMMENDED_QUERY: Self = Self::BlockLength(QUERY_BLOCK_LENGTH);
+    /// The Block-Length Padding strategy recommended for responses.
+    pub const RECOMMENDED_RESPONSE: Self = Self::BlockLength(RESPONSE_BLOCK_LENGTH);
+
+    #[inline]
+    pub fn apply(&self, message: &mut Message) -> Result<(), WriteWireError> {
+        match self {
+            Self::None => Ok(()),
+            Self::BlockLength(block_length) => pad_to_block_length(message, *block_length),
+        }
+    }
+}
+
+/// The message's OPT record data. An OPT record is added to the additional section if the message
+/// does not already have one.
+pub(crate) fn opt_rdata_mut(message: &mut Message) -> &mut OPT {
+    message.edns_mut().into_opt()
+}
+
+/// Adds a padding option to the message's OPT record (creating the OPT record if needed) so that
+/// the length of the message on the wire, excluding any two octet length prefix, is a multiple of
+/// the block length. Any existing padding option is replaced.
+///
+/// https://datatracker.ietf.org/doc/html/rfc7830#section-3
+pub fn pad_to_block_length(message: &mut Message, block_length: u16) -> Result<(), WriteWireError> {
+    if block_length == 0 {
+        return Ok(());
+    }
+
+    // Start with an empty padding option so that the option header is included in the length.
+    let opt = opt_rdata_mut(message);
+    opt.options_mut().retain(|option| option.code() != EDNSOptionCode::Padding);
+    opt.options_mut().push(EDNSOption::new_padding(0));
+
+    // The compressed length is needed, so `serial_length()` cannot be used.
+    let unpadded_length = message.estimated_wire_size(true);
+
+    let block_length = block_length as usize;
+    let padding_length = (block_length - (unpadded_length % block_length)) % block_length;
+    if (unpadded_length + padding_length) > (u16::MAX as usize) {
+        return Err(WriteWireError::OverflowError(format!("Padding a {unpadded_length} byte message to a multiple of {block_length} would exceed {} bytes", u16::MAX)));
+    }
+
+    let opt = opt_rdata_mut(message);
+    if let Some(padding) = opt.options_mut().iter_mut().find(|option| option.code() == EDNSOptionCode::Padding) {
+        padding.data_mut().resize(padding_length, 0);
+    }
+    Ok(())
+}
+
+/// Pads a response, but only if the query it answers was padded. Servers must not pad responses
+/// to clients that did not include the padding option.
+///
+/// https://datatracker.ietf.org/doc/html/rfc8467#section-4
+pub fn pad_response(query: &Message, response: &mut Message, policy: PaddingPolicy) -> Result<(), WriteWireError> {
+    let query_padded = query.additional.iter().any(|record| match record.get_rdata() {
+        RecordData::OPT(opt) => opt.option(EDNSOptionCode::Padding).is_some(),
+        _ => false,
+    });
+    if query_padded {
+        policy.apply(response)
+    } else {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod padding_tests {
+    use crate::{query::message::Message, serde::wire::circular_test::{example_query, wire_length as compressed_wire_length}, types::c_domain_name::CompressionMap};
+
+    use super::{pad_response, pad_to_block_length, PaddingPolicy, QUERY_BLOCK_LENGTH, RESPONSE_BLOCK_LENGTH};
+
+    fn wire_length(message: &Message) -> usize {
+        compressed_wire_length(message, &mut Some(CompressionMap::new())).unwrap()
+    }
+
+    #[test]
+    fn query_padded_to_block() {
+        let mut message = example_query();
+        pad_to_block_length(&mut message, QUERY_BLOCK_LENGTH).unwrap();
+        assert_eq!(wire_length(&message) % (QUERY_BLOCK_LENGTH as usize), 0);
+        assert_eq!(message.additional.len(), 1);
+
+        // Re-padding replaces the existing option instead of adding a second one.
+        pad_to_block_length(&mut message, QUERY_BLOCK_LENGTH).unwrap();
+        assert_eq!(wire_length(&message), QUERY_BLOCK_LENGTH as usize);
+    }
+
+    #[test]
+    fn response_only_padded_if_query_padded() {
+        let unpadded_query = example_query();
+        let mut response = example_query();
+        pad_response(&unpadded_query, &mut response, PaddingPolicy::RECOMMENDED_RESPONSE).unwrap();
+        assert!(response.additional.is_empty());
+
+        let mut padded_query = example_query();
+        PaddingPolicy::RECOMMENDED_QUERY.apply(&mut padded_query).unwrap();
+        pad_response(&padded_query, &mut response, PaddingPolicy::RECOMMENDED_RESPONSE).unwrap();
+        assert_eq!(wire_length(&response) % (RESPONSE_BLOCK_LENGTH as usize), 0);
+    }
+}
@@ -1,8 +1,9 @@
 use std::fmt::Display;
 
 use dns_macros::{ToWire, FromWire};
+use tinyvec::TinyVec;
 
-use crate::{resource_record::{rtype::RType, rclass::RClass}, types::c_domain_name::CDomainName};
+use crate::{resource_record::{rtype::RType, rclass::RClass}, types::c_domain_name::{CDomainName, CmpDomainName, CompressionMap}, serde::wire::to_wire::ToWire};
 
 /// https://datatracker.ietf.org/doc/html/rfc1035#section-4.1.2
 #[derive(Clone, PartialEq, Eq, Hash, Debug, ToWire, FromWire)]
@@ -38,6 +39,35 @@ impl Question {
     #[inline]
     pub const fn qclass(&self) -> RClass { self.qclass }
 
+    /// The number of octets this question would take up if it were written at `offset`.
+    #[inline]
+    pub fn wire_length(&self, offset: usize, compression: &Option<CompressionMap>) -> usize {
+        self.qname.wire_length(offset, compression)
+            + (self.qtype.serial_length() as usize)
+            + (self.qclass.serial_length() as usize)
+    }
+
+    /// Gets the question in its normalized form. The name is lowercase and the type and class use
+    /// their named variants when they have one, so that two questions asking for the same thing
+    /// are always equal.
+    #[inline]
+    pub fn normalized(&self) -> Self {
+        Question {
+            qname: self.qname.as_lowercase(),
+            qtype: RType::from_code(self.qtype.code()),
+            qclass: RClass::from_code(self.qclass.code()),
+        }
+    }
+
+    /// Checks if the two questions ask for the same thing. Unlike `==`, the names are compared
+    /// case-insensitively.
+    #[inline]
+    pub fn matches(&self, other: &Self) -> bool {
+        (self.qtype.code() == other.qtype.code())
+        && (self.qclass.code() == other.qclass.code())
+        && self.qname.matches(&other.qname)
+    }
+
     pub fn with_new_qname(&self, qname: CDomainName) -> Self {
         Question {
             qname,
@@ -84,3 +114,83 @@ impl Display for Question {
         write!(f, "Question: {{qname: '{}', qtype: {}, qclass: {}}}", self.qname, self.qtype, self.qclass)
     }
 }
+
+/// Identifies a set of questions, such as the question section of a query. Questions are
+/// normalized when the key is created so logically identical queries always produce the same key,
+/// regardless of the case of the names.
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub struct QuestionKey {
+    questions: TinyVec<[Question; 1]>,
+}
+
+impl QuestionKey {
+    #[inline]
+    pub fn new(questions: &[Question]) -> Self {
+        Self { questions: questions.iter().map(Question::normalized).collect() }
+    }
+
+    #[inline]
+    pub fn questions(&self) -> &[Question] {
+        &self.questions
+    }
+}
+
+impl From<&Question> for QuestionKey {
+    #[inline]
+    fn from(question: &Question) -> Self {
+        Self::new(std::slice::from_ref(question))
+    }
+}
+
+impl From<&[Question]> for QuestionKey {
+    #[inline]
+    fn from(questions: &[Question]) -> Self {
+        Self::new(questions)
+    }
+}
+
+impl Display for QuestionKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut questions = self.questions.iter();
+        if let Some(question) = questions.next() {
+            write!(f, "{question}")?;
+        }
+        for question in questions {
+            write!(f, ", {question}")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod question_key_tests {
+    use crate::{resource_record::{rclass::RClass, rtype::RType}, types::c_domain_name::CDomainName};
+
+    use super::{Question, QuestionKey};
+
+    #[test]
+    fn case_insensitive_names() {
+        let lower = Question::new(CDomainName::from_utf8("www.example.org.").unwrap(), RType::A, RClass::Internet);
+        let mixed = Question::new(CDomainName::from_utf8("WwW.ExAmPlE.oRg.").unwrap(), RType::A, RClass::Internet);
+        assert_ne!(lower, mixed);
+        assert!(lower.matches(&mixed));
+        assert_eq!(QuestionKey::from(&lower), QuestionKey::from(&mixed));
+    }
+
+    #[test]
+    fn canonical_type_and_class() {
+        let named = Question::new(CDomainName::from_utf8("example.org.").unwrap(), RType::AAAA, RClass::Internet);
+        let unknown = Question::new(CDomainName::from_utf8("example.org.").unwrap(), RType::Unknown(RType::AAAA.code()), RClass::Unknown(RClass::Internet.code()));
+        assert!(named.matches(&unknown));
+        assert_eq!(QuestionKey::from(&named), QuestionKey::from(&unknown));
+    }
+
+    #[test]
+    fn different_questions() {
+        let a = Question::new(CDomainName::from_utf8("example.org.").unwrap(), RType::A, RClass::Internet);
+        assert!(!a.matches(&a.with_new_qtype(RType::AAAA)));
+        assert!(!a.matches(&a.with_new_qname(CDomainName::from_utf8("example.com.").unwrap())));
+        assert_ne!(QuestionKey::from(&a), QuestionKey::from(&a.with_new_qclass(RClass::Chaos)));
+        assert_ne!(QuestionKey::new(&[a.clone()]), QuestionKey::new(&[a.clone(), a]));
+    }
+}
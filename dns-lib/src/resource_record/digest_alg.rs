@@ -13,3 +13,29 @@ enum_encoding!(
     code_presentation,
     mnemonic_display
 );
+
+impl DigestAlgorithm {
+    /// Whether the digest is no longer considered secure.
+    ///
+    /// https://datatracker.ietf.org/doc/html/rfc8624#section-3.3
+    #[inline]
+    pub const fn is_weak(&self) -> bool {
+        matches!(self, Self::Sha1 | Self::Gostr341194)
+    }
+
+    /// How strong the digest is compared to the others, from 0 for the weakest. When a zone has DS
+    /// records with several digest types, only the strongest one should be used so that an
+    /// attacker cannot downgrade the validation by removing the stronger ones.
+    ///
+    /// https://datatracker.ietf.org/doc/html/rfc4509#section-3
+    #[inline]
+    pub const fn strength(&self) -> u8 {
+        match self {
+            Self::Sha1 => 1,
+            Self::Gostr341194 => 2,
+            Self::Sha256 => 3,
+            Self::Sha384 => 4,
+            _ => 0,
+        }
+    }
+}
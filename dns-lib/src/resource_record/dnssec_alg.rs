@@ -47,3 +47,46 @@ enum_encoding!(
     mnemonic_presentation,
     mnemonic_display
 );
+
+impl DnsSecAlgorithm {
+    /// Whether the algorithm is no longer considered secure. Signatures made with these should
+    /// not be trusted, even if they verify.
+    ///
+    /// https://datatracker.ietf.org/doc/html/rfc8624#section-3.1
+    /// https://datatracker.ietf.org/doc/html/rfc9905
+    #[inline]
+    pub const fn is_weak(&self) -> bool {
+        match self {
+            Self::RsaMd5
+            | Self::Dsa
+            | Self::RsaSha1
+            | Self::DsaNsec3Sha1
+            | Self::RsaSha1Nsec3Sha1
+            | Self::EccGhost => true,
+            _ => false,
+        }
+    }
+
+    /// Whether the algorithm is used to sign zone data. The others are reserved, used for other
+    /// purposes (like DELETE in CDS and CDNSKEY records), or unassigned.
+    #[inline]
+    pub const fn is_zone_signing(&self) -> bool {
+        match self {
+            Self::RsaMd5
+            | Self::Dsa
+            | Self::RsaSha1
+            | Self::DsaNsec3Sha1
+            | Self::RsaSha1Nsec3Sha1
+            | Self::RsaSha256
+            | Self::RsaSha512
+            | Self::EccGhost
+            | Self::EcdsaP256Sha256
+            | Self::EcdsaP384Sha384
+            | Self::Ed25519
+            | Self::Ed448
+            | Self::PrivateDns
+            | Self::PrivateOid => true,
+            _ => false,
+        }
+    }
+}
@@ -0,0 +1,25 @@
+use crate::gen_enum::enum_encoding;
+
+enum_encoding!(
+    (doc "https://www.iana.org/assignments/dns-parameters/dns-parameters.xhtml#dns-parameters-11"),
+    EDNSOptionCode,
+    u16,
+    (
+        (LLQ,              "LLQ",              1),
+        (UL,               "UL",               2),
+        (NSID,             "NSID",             3),
+        (DAU,              "DAU",              5),
+        (DHU,              "DHU",              6),
+        (N3U,              "N3U",              7),
+        (ClientSubnet,     "edns-client-subnet", 8),
+        (Expire,           "EDNS EXPIRE",      9),
+        (Cookie,           "COOKIE",           10),
+        (TcpKeepalive,     "edns-tcp-keepalive", 11),
+        (Padding,          "Padding",          12),
+        (Chain,            "CHAIN",            13),
+        (KeyTag,           "edns-key-tag",     14),
+        (ExtendedDNSError, "Extended DNS Error", 15),
+    ),
+    code_presentation,
+    mnemonic_display
+);
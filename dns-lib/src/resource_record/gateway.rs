@@ -0,0 +1,244 @@
+use std::{fmt::Display, net::{IpAddr, Ipv4Addr, Ipv6Addr}};
+
+use crate::{serde::{presentation::{errors::TokenizedRecordError, from_presentation::FromPresentation, to_presentation::{PresentationWriter, ToPresentation}}, wire::{from_wire::FromWire, read_wire::{ReadWire, ReadWireError}, to_wire::ToWire, write_wire::{WriteWire, WriteWireError}}}, types::{c_domain_name::CompressionMap, domain_name::DomainName}};
+
+/// A gateway (or relay) field whose form is selected by a separate type field in the same RDATA.
+/// The type field is not part of this value. It is derived from the variant using
+/// `gateway_type()` and is passed in when the gateway is read.
+///
+/// (IPSECKEY) https://datatracker.ietf.org/doc/html/rfc4025#section-2.3
+/// (AMTRELAY) https://datatracker.ietf.org/doc/html/rfc8777#section-4.2.3
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub enum Gateway {
+    /// No gateway is present. In presentation format, this is written as ".".
+    None,
+    Ipv4(Ipv4Addr),
+    Ipv6(Ipv6Addr),
+    /// The domain name is never compressed.
+    DomainName(DomainName),
+}
+
+impl Gateway {
+    pub const NONE_TYPE: u8 = 0;
+    pub const IPV4_TYPE: u8 = 1;
+    pub const IPV6_TYPE: u8 = 2;
+    pub const DOMAIN_NAME_TYPE: u8 = 3;
+
+    /// The value of the type field that selects this form of gateway.
+    #[inline]
+    pub const fn gateway_type(&self) -> u8 {
+        match self {
+            Self::None          => Self::NONE_TYPE,
+            Self::Ipv4(_)       => Self::IPV4_TYPE,
+            Self::Ipv6(_)       => Self::IPV6_TYPE,
+            Self::DomainName(_) => Self::DOMAIN_NAME_TYPE,
+        }
+    }
+
+    #[inline]
+    pub const fn is_none(&self) -> bool {
+        matches!(self, Self::None)
+    }
+
+    /// The address of the gateway, if it was given as an address instead of a domain name.
+    #[inline]
+    pub const fn ip_addr(&self) -> Option<IpAddr> {
+        match self {
+            Self::Ipv4(address) => Some(IpAddr::V4(*address)),
+            Self::Ipv6(address) => Some(IpAddr::V6(*address)),
+            Self::None | Self::DomainName(_) => None,
+        }
+    }
+
+    #[inline]
+    pub const fn domain_name(&self) -> Option<&DomainName> {
+        match self {
+            Self::DomainName(domain_name) => Some(domain_name),
+            Self::None | Self::Ipv4(_) | Self::Ipv6(_) => None,
+        }
+    }
+
+    /// Reads a gateway of the form selected by `gateway_type`. Unknown types are an error because
+    /// their length cannot be determined.
+    pub fn from_wire_format_with_type<'a, 'b>(gateway_type: u8, wire: &'b mut ReadWire<'a>) -> Result<Self, ReadWireError> where 'a: 'b {
+        match gateway_type {
+            Self::NONE_TYPE => Ok(Self::None),
+            Self::IPV4_TYPE => Ok(Self::Ipv4(Ipv4Addr::from_wire_format(wire)?)),
+            Self::IPV6_TYPE => Ok(Self::Ipv6(Ipv6Addr::from_wire_format(wire)?)),
+            Self::DOMAIN_NAME_TYPE => Ok(Self::DomainName(DomainName::from_wire_format(wire)?)),
+            _ => Err(ReadWireError::VersionError(
+                format!("the gateway type {gateway_type} is unrecognized")
+            )),
+        }
+    }
+
+    /// Parses the presentation format of a gateway of the form selected by `gateway_type`. The
+    /// token must agree with the type. For example, an IPv4 address is rejected if the type is
+    /// for a domain name.
+    pub fn from_token_format_with_type<'a, 'b>(gateway_type: u8, token: &'a str) -> Result<Self, TokenizedRecordError<'b>> where 'a: 'b {
+        match gateway_type {
+            Self::NONE_TYPE => {
+                // "If no gateway is to be indicated, then the gateway type field MUST be zero and
+                //  the gateway field MUST be "."" (RFC 4025 section 2.3, RFC 8777 section 4.2.3)
+                let (root_domain, _) = DomainName::from_token_format(&[token])?;
+                if !root_domain.is_root() {
+                    return Err(TokenizedRecordError::ValueError(
+                        format!("The gateway type was 0 but the gateway was not \".\". Instead, it was '{token}'")
+                    ));
+                }
+                Ok(Self::None)
+            },
+            Self::IPV4_TYPE => Ok(Self::Ipv4(Ipv4Addr::from_token_format(&[token])?.0)),
+            Self::IPV6_TYPE => Ok(Self::Ipv6(Ipv6Addr::from_token_format(&[token])?.0)),
+            Self::DOMAIN_NAME_TYPE => Ok(Self::DomainName(DomainName::from_token_format(&[token])?.0)),
+            _ => Err(TokenizedRecordError::ValueError(
+                format!("The gateway type {gateway_type} is unrecognized")
+            )),
+        }
+    }
+}
+
+impl Display for Gateway {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::None => write!(f, "."),
+            Self::Ipv4(address) => write!(f, "{address}"),
+            Self::Ipv6(address) => write!(f, "{address}"),
+            Self::DomainName(domain_name) => write!(f, "{domain_name}"),
+        }
+    }
+}
+
+impl From<IpAddr> for Gateway {
+    #[inline]
+    fn from(value: IpAddr) -> Self {
+        match value {
+            IpAddr::V4(address) => Self::Ipv4(address),
+            IpAddr::V6(address) => Self::Ipv6(address),
+        }
+    }
+}
+
+impl From<Ipv4Addr> for Gateway {
+    #[inline]
+    fn from(value: Ipv4Addr) -> Self {
+        Self::Ipv4(value)
+    }
+}
+
+impl From<Ipv6Addr> for Gateway {
+    #[inline]
+    fn from(value: Ipv6Addr) -> Self {
+        Self::Ipv6(value)
+    }
+}
+
+impl From<DomainName> for Gateway {
+    #[inline]
+    fn from(value: DomainName) -> Self {
+        Self::DomainName(value)
+    }
+}
+
+impl ToWire for Gateway {
+    #[inline]
+    fn to_wire_format<'a, 'b>(&self, wire: &'b mut WriteWire<'a>, compression: &mut Option<CompressionMap>) -> Result<(), WriteWireError> where 'a: 'b {
+        match self {
+            Self::None => Ok(()),
+            Self::Ipv4(address) => address.to_wire_format(wire, compression),
+            Self::Ipv6(address) => address.to_wire_format(wire, compression),
+            Self::DomainName(domain_name) => domain_name.to_wire_format(wire, compression),
+        }
+    }
+
+    #[inline]
+    fn serial_length(&self) -> u16 {
+        match self {
+            Self::None => 0,
+            Self::Ipv4(address) => address.serial_length(),
+            Self::Ipv6(address) => address.serial_length(),
+            Self::DomainName(domain_name) => domain_name.serial_length(),
+        }
+    }
+}
+
+impl ToPresentation for Gateway {
+    #[inline]
+    fn to_presentation_format(&self, out_buffer: &mut Vec<String>) {
+        match self {
+            Self::None => out_buffer.push(".".to_string()),
+            Self::Ipv4(address) => address.to_presentation_format(out_buffer),
+            Self::Ipv6(address) => address.to_presentation_format(out_buffer),
+            Self::DomainName(domain_name) => domain_name.to_presentation_format(out_buffer),
+        }
+    }
+
+    #[inline]
+    fn write_presentation_tokens<W: std::fmt::Write + ?Sized>(&self, out: &mut PresentationWriter<'_, W>) -> std::fmt::Result {
+        match self {
+            Self::None => out.write_token("."),
+            Self::Ipv4(address) => address.write_presentation_tokens(out),
+            Self::Ipv6(address) => address.write_presentation_tokens(out),
+            Self::DomainName(domain_name) => domain_name.write_presentation_tokens(out),
+        }
+    }
+}
+
+#[cfg(test)]
+mod gateway_tests {
+    use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+    use crate::{serde::wire::{read_wire::ReadWire, to_wire::ToWire, write_wire::WriteWire}, types::domain_name::DomainName};
+
+    use super::Gateway;
+
+    fn circular(gateway: Gateway) {
+        let mut buffer = [0_u8; 512];
+        let mut write_wire = WriteWire::from_bytes(&mut buffer);
+        gateway.to_wire_format(&mut write_wire, &mut None).unwrap();
+        assert_eq!(write_wire.current().len(), gateway.serial_length() as usize);
+
+        let mut read_wire = ReadWire::from_bytes(write_wire.current());
+        let read_gateway = Gateway::from_wire_format_with_type(gateway.gateway_type(), &mut read_wire).unwrap();
+        assert_eq!(read_gateway, gateway);
+        assert!(read_wire.is_end_reached());
+    }
+
+    #[test]
+    fn circular_serde() {
+        circular(Gateway::None);
+        circular(Gateway::Ipv4(Ipv4Addr::new(192, 0, 2, 38)));
+        circular(Gateway::Ipv6(Ipv6Addr::new(0x2001, 0xdb8, 0, 8, 0x2fc, 0x2ff, 0xfe00, 0x1234)));
+        circular(Gateway::DomainName(DomainName::from_utf8("gateway.example.com.").unwrap()));
+    }
+
+    #[test]
+    fn unknown_wire_type() {
+        let mut read_wire = ReadWire::from_bytes(&[1, 2, 3, 4]);
+        assert!(Gateway::from_wire_format_with_type(4, &mut read_wire).is_err());
+    }
+
+    #[test]
+    fn token_must_match_type() {
+        assert_eq!(Gateway::from_token_format_with_type(0, ".").unwrap(), Gateway::None);
+        assert!(Gateway::from_token_format_with_type(0, "example.com.").is_err());
+        assert!(Gateway::from_token_format_with_type(1, ".").is_err());
+        assert!(Gateway::from_token_format_with_type(1, "2001:db8::1").is_err());
+        assert!(Gateway::from_token_format_with_type(2, "192.0.2.1").is_err());
+        assert!(Gateway::from_token_format_with_type(4, ".").is_err());
+    }
+
+    #[test]
+    fn std_net_conversions() {
+        let ipv4 = IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1));
+        let ipv6 = IpAddr::V6(Ipv6Addr::LOCALHOST);
+        assert_eq!(Gateway::from(ipv4).ip_addr(), Some(ipv4));
+        assert_eq!(Gateway::from(ipv6).ip_addr(), Some(ipv6));
+        assert_eq!(Gateway::from(ipv4).gateway_type(), Gateway::IPV4_TYPE);
+        assert_eq!(Gateway::None.ip_addr(), None);
+
+        let domain_name = DomainName::from_utf8("gateway.example.com.").unwrap();
+        assert_eq!(Gateway::from(domain_name.clone()).ip_addr(), None);
+        assert_eq!(Gateway::from(domain_name.clone()).domain_name(), Some(&domain_name));
+    }
+}
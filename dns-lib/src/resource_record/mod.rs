@@ -7,10 +7,14 @@ pub mod rcode;
 pub mod dnssec_alg;
 pub mod ipsec_alg;
 pub mod digest_alg;
+pub mod edns_option_code;
+pub mod svc_param_key;
+pub mod svc_param;
 pub mod opcode;
 pub mod key_protocol;
 pub mod protocol;
 pub(crate) mod port_from_service;
 pub mod ports;
 pub mod address_family;
+pub mod gateway;
 pub mod time;
@@ -1,8 +1,8 @@
 use std::{error::Error, fmt::Display, hash::Hash, ops::Deref};
 
-use crate::{serde::{presentation::{errors::TokenizedRecordError, from_presentation::FromPresentation, from_tokenized_rdata::FromTokenizedRData, to_presentation::ToPresentation}, wire::{from_wire::FromWire, read_wire::{ReadWireError, SliceWireVisibility}, to_wire::ToWire}}, types::c_domain_name::CDomainName};
+use crate::{serde::{presentation::{errors::TokenizedRecordError, from_presentation::FromPresentation, from_tokenized_rdata::FromTokenizedRData, to_presentation::{PresentationWriter, ToPresentation}}, wire::{from_wire::FromWire, read_wire::{ReadWireError, SliceWireVisibility}, to_wire::ToWire, write_wire::WriteWire}}, types::c_domain_name::{CDomainName, CompressionMap}};
 
-use super::{rclass::RClass, rtype::RType, time::Time, types::{a::A, a6::A6, aaaa::AAAA, afsdb::AFSDB, amtrelay::AMTRELAY, any::ANY, apl::APL, axfr::AXFR, caa::CAA, cdnskey::CDNSKEY, cds::CDS, cert::CERT, cname::CNAME, csync::CSYNC, dname::DNAME, dnskey::DNSKEY, ds::DS, eui48::EUI48, eui64::EUI64, hinfo::HINFO, maila::MAILA, mailb::MAILB, mb::MB, md::MD, mf::MF, mg::MG, minfo::MINFO, mr::MR, mx::MX, naptr::NAPTR, ns::NS, nsec::NSEC, null::NULL, ptr::PTR, rrsig::RRSIG, soa::SOA, srv::SRV, tlsa::TLSA, tsig::TSIG, txt::TXT, wks::WKS}};
+use super::{rclass::RClass, rtype::RType, time::Time, types::{a::A, a6::A6, aaaa::AAAA, afsdb::AFSDB, amtrelay::AMTRELAY, any::ANY, apl::APL, axfr::AXFR, caa::CAA, cdnskey::CDNSKEY, cds::CDS, cert::CERT, cname::CNAME, csync::CSYNC, dname::DNAME, dnskey::DNSKEY, ds::DS, eui48::EUI48, eui64::EUI64, hinfo::HINFO, https::HTTPS, ipseckey::IPSECKEY, isdn::ISDN, loc::LOC, maila::MAILA, mailb::MAILB, mb::MB, md::MD, mf::MF, mg::MG, minfo::MINFO, mr::MR, mx::MX, naptr::NAPTR, ns::NS, nsap::NSAP, nsap_ptr::NSAP_PTR, nsec::NSEC, null::NULL, opt::OPT, ptr::PTR, rp::RP, rrsig::RRSIG, rt::RT, soa::SOA, srv::SRV, svcb::SVCB, tlsa::TLSA, tsig::TSIG, txt::TXT, wks::WKS, x25::X25}};
 
 
 #[derive(Debug)]
@@ -49,11 +49,21 @@ impl<RDataT: RData> ResourceRecord<RDataT> {
         self.name
     }
 
+    #[inline]
+    pub fn set_name(&mut self, new_name: CDomainName) {
+        self.name = new_name;
+    }
+
     #[inline]
     pub const fn get_rclass(&self) -> RClass {
         self.rclass
     }
 
+    #[inline]
+    pub const fn set_rclass(&mut self, new_rclass: RClass) {
+        self.rclass = new_rclass;
+    }
+
     #[inline]
     pub const fn get_ttl(&self) -> &Time {
         &self.ttl
@@ -78,11 +88,29 @@ impl<RDataT: RData> ResourceRecord<RDataT> {
     pub const fn get_rdata(&self) -> &RDataT {
         &self.rdata
     }
+
+    #[inline]
+    pub fn get_rdata_mut(&mut self) -> &mut RDataT {
+        &mut self.rdata
+    }
     
     #[inline]
     pub fn into_rdata(self) -> RDataT {
         self.rdata
     }
+
+    /// The number of octets this record would take up if it were written at `offset`. The owner
+    /// name is compressed the same way `to_wire_format()` would compress it but the RDATA is
+    /// counted at its uncompressed length.
+    #[inline]
+    pub fn wire_length(&self, offset: usize, compression: &Option<CompressionMap>) -> usize {
+        self.name.wire_length(offset, compression)
+            + (self.rdata.get_rtype().serial_length() as usize)
+            + (self.rclass.serial_length() as usize)
+            + (self.ttl.serial_length() as usize)
+            + (0_u16.serial_length() as usize)
+            + (self.rdata.serial_length() as usize)
+    }
 }
 
 impl<RDataT: RData> Deref for ResourceRecord<RDataT> {
@@ -153,6 +181,7 @@ impl<RDataT: RData> ToWire for ResourceRecord<RDataT> {
 macro_rules! gen_record_data {
     ($(($record:ident, $presentation_rule:ident)),+$(,)?) => {
         /// https://datatracker.ietf.org/doc/html/rfc1035#section-4.1.3
+        #[allow(non_camel_case_types)]
         #[derive(Clone, PartialEq, Eq, Hash, Debug)]
         pub enum RecordData {
             $($record($record),)+
@@ -167,6 +196,17 @@ macro_rules! gen_record_data {
             }
         }
 
+        impl RecordData {
+            /// Whether this type of record can be written in presentation format. Calling
+            /// `to_presentation_format()` on a record that cannot will panic.
+            #[inline]
+            pub const fn presentation_allowed(&self) -> bool {
+                match self {
+                    $(Self::$record(_) => gen_presentation_allowed!($presentation_rule),)+
+                }
+            }
+        }
+
         impl ToWire for RecordData {
             fn to_wire_format<'a, 'b>(&self, wire: &'b mut crate::serde::wire::write_wire::WriteWire<'a>, compression: &mut Option<crate::types::c_domain_name::CompressionMap>) -> Result<(), crate::serde::wire::write_wire::WriteWireError> where 'a: 'b {
                 match self {
@@ -285,15 +325,30 @@ macro_rules! gen_record_data {
                     $(RecordData::$record(rdata) => gen_to_presentation!($record, rtype, rdata, out_buffer, $presentation_rule),)+
                 }
             }
+
+            fn write_presentation_tokens<W: std::fmt::Write + ?Sized>(&self, out: &mut PresentationWriter<'_, W>) -> std::fmt::Result {
+                let rtype = self.get_rtype();
+                self.name.write_presentation_tokens(out)?;
+                self.ttl.write_presentation_tokens(out)?;
+                self.rclass.write_presentation_tokens(out)?;
+                rtype.write_presentation_tokens(out)?;
+                match &self.rdata {
+                    $(RecordData::$record(rdata) => gen_write_presentation!($record, rtype, rdata, out, $presentation_rule),)+
+                }
+            }
+
+            fn presentation_comment(&self) -> Option<String> {
+                match &self.rdata {
+                    $(RecordData::$record(rdata) => gen_presentation_comment!(rdata, $presentation_rule),)+
+                }
+            }
         }
 
         $(resource_record_to_presentation!($record, $presentation_rule);)+
 
         impl Display for ResourceRecord<RecordData> {
             fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-                let mut buffer = Vec::new();
-                self.to_presentation_format(&mut buffer);
-                write!(f, "{}", buffer.join("\t"))
+                self.write_presentation(f)
             }
         }
 
@@ -339,13 +394,40 @@ macro_rules! gen_record_data {
     };
 }
 
+/// The token that marks the RDATA of a record as being written in the generic format.
+///
+/// https://datatracker.ietf.org/doc/html/rfc3597#section-5
+const GENERIC_RDATA_TOKEN: &str = "\\#";
+
+/// The RDATA length and hex encoded RDATA of a record, as used by the generic format. Returns
+/// `None` if the RDATA could not be serialized.
+fn generic_rdata(rdata: &impl ToWire) -> Option<(u16, String)> {
+    let mut buffer = vec![0; rdata.serial_length() as usize];
+    let mut wire = WriteWire::from_bytes(&mut buffer);
+    rdata.to_wire_format(&mut wire, &mut None).ok()?;
+    let rdata_wire = wire.current();
+    let mut rdata_hex = String::with_capacity(rdata_wire.len() * 2);
+    for byte in rdata_wire {
+        rdata_hex.push_str(&format!("{byte:02X}"));
+    }
+    Some((rdata_wire.len() as u16, rdata_hex))
+}
+
 macro_rules! gen_to_presentation {
     ($record:ident, $rtype_var:expr, $rdata_var:expr, $out_buffer_var:expr, presentation_forbidden) => {
         {
-            // clears the warning generated because the `rdata` field is unused.
-            let _ = $rdata_var;
-
-            panic!("Cannot convert {} to presentation", $rtype_var);
+            // clears the warning generated because the `rtype` variable is unused.
+            let _ = $rtype_var;
+
+            // Records without a presentation format are written using the generic format from
+            // RFC 3597 instead.
+            if let Some((rd_length, rdata_hex)) = generic_rdata($rdata_var) {
+                $out_buffer_var.push(GENERIC_RDATA_TOKEN.to_string());
+                $out_buffer_var.push(rd_length.to_string());
+                if !rdata_hex.is_empty() {
+                    $out_buffer_var.push(rdata_hex);
+                }
+            }
         }
     };
     ($record:ident, $rtype_var:expr, $rdata_var:expr, $out_buffer_var:expr, presentation_allowed) => {
@@ -353,6 +435,51 @@ macro_rules! gen_to_presentation {
     };
 }
 
+macro_rules! gen_presentation_allowed {
+    (presentation_forbidden) => { false };
+    (presentation_allowed) => { true };
+}
+
+macro_rules! gen_write_presentation {
+    ($record:ident, $rtype_var:expr, $rdata_var:expr, $out_var:expr, presentation_forbidden) => {
+        {
+            // clears the warning generated because the `rtype` variable is unused.
+            let _ = $rtype_var;
+
+            // Records without a presentation format are written using the generic format from
+            // RFC 3597 instead.
+            match generic_rdata($rdata_var) {
+                Some((rd_length, rdata_hex)) => {
+                    $out_var.write_token(GENERIC_RDATA_TOKEN)?;
+                    $out_var.write_display(&rd_length)?;
+                    if rdata_hex.is_empty() {
+                        Ok(())
+                    } else {
+                        $out_var.write_token(&rdata_hex)
+                    }
+                },
+                None => Err(std::fmt::Error),
+            }
+        }
+    };
+    ($record:ident, $rtype_var:expr, $rdata_var:expr, $out_var:expr, presentation_allowed) => {
+        $rdata_var.write_presentation_tokens($out_var)
+    };
+}
+
+macro_rules! gen_presentation_comment {
+    ($rdata_var:expr, presentation_forbidden) => {
+        {
+            // Records in the generic format have nothing to explain.
+            let _ = $rdata_var;
+            None
+        }
+    };
+    ($rdata_var:expr, presentation_allowed) => {
+        $rdata_var.presentation_comment()
+    };
+}
+
 macro_rules! resource_record_to_presentation {
     ($record:ident, presentation_forbidden) => {
         // No presentation format
@@ -367,6 +494,20 @@ macro_rules! resource_record_to_presentation {
                 rtype.to_presentation_format(out_buffer);
                 self.rdata.to_presentation_format(out_buffer);
             }
+
+            fn write_presentation_tokens<W: std::fmt::Write + ?Sized>(&self, out: &mut PresentationWriter<'_, W>) -> std::fmt::Result {
+                let rtype = self.get_rtype();
+                self.name.write_presentation_tokens(out)?;
+                self.ttl.write_presentation_tokens(out)?;
+                self.rclass.write_presentation_tokens(out)?;
+                rtype.write_presentation_tokens(out)?;
+                self.rdata.write_presentation_tokens(out)
+            }
+
+            #[inline]
+            fn presentation_comment(&self) -> Option<String> {
+                self.rdata.presentation_comment()
+            }
         }
     };
 }
@@ -378,9 +519,7 @@ macro_rules! resource_record_gen_display {
     ($record:ident, presentation_allowed) => {
         impl Display for ResourceRecord<$record> {
             fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-                let mut buffer = Vec::new();
-                self.rdata.to_presentation_format(&mut buffer);
-                write!(f, "{}", buffer.join("\t"))
+                self.rdata.write_presentation(f)
             }
         }
     };
@@ -431,15 +570,15 @@ gen_record_data!(
     // GPOS(RRHeader, GPOS),
     (HINFO, presentation_allowed),
     // HIP(RRHeader, HIP),
-    // HTTPS(RRHeader, HTTPS),
-    // IPSECKEY(RRHeader, IPSECKEY),
-    // ISDN(RRHeader, ISDN),
+    (HTTPS, presentation_allowed),
+    (IPSECKEY, presentation_allowed),
+    (ISDN, presentation_allowed),
     // IXFR(RRHeader, IXFR),
     // KEY(RRHeader, KEY),
     // KX(RRHeader, KX),
     // L32(RRHeader, L32),
     // L64(RRHeader, L64),
-    // LOC(RRHeader, LOC),
+    (LOC, presentation_allowed),
     // LP(RRHeader, LP),
     (MAILA, presentation_forbidden),
     (MAILB, presentation_forbidden),
@@ -455,21 +594,21 @@ gen_record_data!(
     // NIMLOC(RRHeader, NIMLOC),
     // NINFO(RRHeader, NINFO),
     (NS, presentation_allowed),
-    // NSAP_PTR(RRHeader, NSAP_PTR),
-    // NSAP(RRHeader, NSAP),
+    (NSAP, presentation_allowed),
+    (NSAP_PTR, presentation_allowed),
     (NSEC, presentation_allowed),
     // NSEC3(RRHeader, NSEC3),
     // NSEC3PARAM(RRHeader, NSEC3PARAM),
     (NULL, presentation_forbidden),
     // NXT(RRHeader, NXT),
     // OPENPGPKEY(RRHeader, OPENPGPKEY),
-    // OPT(RRHeader, OPT),
+    (OPT, presentation_forbidden),
     (PTR, presentation_allowed),
     // PX(RRHeader, PX),
     // RKEY(RRHeader, RKEY),
-    // RP(RRHeader, RP),
+    (RP, presentation_allowed),
     (RRSIG, presentation_allowed),
-    // RT(RRHeader, RT),
+    (RT, presentation_allowed),
     // SIG(RRHeader, SIG),
     // SINK(RRHeader, SINK),
     // SMIMEA(RRHeader, SMIMEA),
@@ -477,7 +616,7 @@ gen_record_data!(
     // SPF(RRHeader, SPF),
     (SRV, presentation_allowed),
     // SSHFP(RRHeader, SSHFP),
-    // SVCB(RRHeader, SVCB),
+    (SVCB, presentation_allowed),
     // TA(RRHeader, TA),
     // TALINK(RRHeader, TALINK),
     // TKEY(RRHeader, TKEY),
@@ -489,6 +628,6 @@ gen_record_data!(
     // UNSPEC(RRHeader, UNSPEC),
     // URI(RRHeader, URI),
     (WKS, presentation_allowed),
-    // X25(RRHeader, X25),
+    (X25, presentation_allowed),
     // ZONEMD(RRHeader, ZONEMD),
 );
@@ -99,6 +99,8 @@ enum_encoding!(
         (EUI48,  "EUI48",  108),
         (EUI64,  "EUI64",  109),
 
+        (NXNAME, "NXNAME", 128),
+
         (TKEY,     "TKEY",     249),
         (TSIG,     "TSIG",     250),
         (IXFR,     "IXFR",     251),
@@ -119,3 +121,23 @@ enum_encoding!(
     mnemonic_presentation,
     mnemonic_display
 );
+
+impl RType {
+    /// The reason that records of this type should no longer be published, if it is obsolete or
+    /// was only ever experimental. These types are still read, stored, and written without loss so
+    /// that old zones can be migrated, but readers report them as warnings.
+    pub const fn deprecation(&self) -> Option<&'static str> {
+        match self {
+            Self::MD | Self::MF => Some("obsoleted by MX (RFC 973)"),
+            Self::MB | Self::MG | Self::MR | Self::MINFO => Some("experimental mailbox type that was never deployed (RFC 1035)"),
+            Self::MAILA => Some("obsoleted by MX (RFC 973)"),
+            Self::MAILB => Some("experimental mailbox query type that was never deployed (RFC 1035)"),
+            Self::WKS => Some("should not be relied on (RFC 1123 section 2.2)"),
+            Self::X25 | Self::ISDN | Self::RT => Some("experimental type that was never deployed (RFC 1183)"),
+            Self::NSAP => Some("maps OSI network addresses, which are no longer in use (RFC 1706)"),
+            Self::NSAP_PTR => Some("deprecated by RFC 1706 in favour of PTR records under NSAP.INT"),
+            Self::A6 => Some("historic (RFC 6563)"),
+            _ => None,
+        }
+    }
+}
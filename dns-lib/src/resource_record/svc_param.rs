@@ -0,0 +1,491 @@
+use std::{error::Error, fmt::{Display, Write}, net::{Ipv4Addr, Ipv6Addr}, str::FromStr};
+
+use crate::{resource_record::svc_param_key::SvcParamKey, serde::{presentation::{errors::{TokenError, TokenizedRecordError}, from_presentation::FromPresentation, parse_chars::{char_token::EscapableChar, escaped_to_escapable::EscapedToEscapableIter, non_escaped_to_escaped::NonEscapedIntoEscapedIter}, to_presentation::{PresentationWriter, ToPresentation}}, wire::{from_wire::FromWire, read_wire::{ReadWire, ReadWireError}, to_wire::ToWire, write_wire::{WriteWire, WriteWireError}}}, types::{ascii::{constants::{ASCII_BACKSLASH, ASCII_CLOSE_PARENTHESIS, ASCII_COMMA, ASCII_DOUBLE_QUOTES, ASCII_OPEN_PARENTHESIS, ASCII_SEMICOLON, ASCII_SPACE}, AsciiChar, AsciiString}, base64::Base64, base_conversions::BaseConversions, c_domain_name::CompressionMap, character_string::{CharacterString, CharacterStringError}}};
+
+/// The ways that the parameters of an SVCB or HTTPS record can break the rules of RFC 9460,
+/// regardless of how they were read.
+///
+/// https://datatracker.ietf.org/doc/html/rfc9460#section-2.2
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub enum SvcParamError {
+    /// A key is given more than once.
+    DuplicateKey(SvcParamKey),
+    /// A key comes after a key with a higher number.
+    KeyOutOfOrder(SvcParamKey),
+    MandatoryListsItself,
+    /// The mandatory keys are not in increasing order or list a key more than once.
+    MandatoryKeysOutOfOrder,
+    MissingMandatoryKey(SvcParamKey),
+    /// The value is longer than its 16-bit length field can say.
+    ValueTooLong(SvcParamKey),
+}
+impl Error for SvcParamError {}
+impl Display for SvcParamError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::DuplicateKey(key) => write!(f, "the service parameter '{key}' is given more than once"),
+            Self::KeyOutOfOrder(key) => write!(f, "the service parameter '{key}' is out of order"),
+            Self::MandatoryListsItself => write!(f, "the service parameter '{}' cannot list itself", SvcParamKey::Mandatory),
+            Self::MandatoryKeysOutOfOrder => write!(f, "the keys of the service parameter '{}' are not in strictly increasing order", SvcParamKey::Mandatory),
+            Self::MissingMandatoryKey(key) => write!(f, "the mandatory service parameter '{key}' is missing"),
+            Self::ValueTooLong(key) => write!(f, "the value of the service parameter '{key}' is longer than {} bytes", u16::MAX),
+        }
+    }
+}
+impl From<SvcParamError> for ReadWireError {
+    fn from(value: SvcParamError) -> Self {
+        Self::FormatError(value.to_string())
+    }
+}
+impl From<SvcParamError> for WriteWireError {
+    fn from(value: SvcParamError) -> Self {
+        Self::OverflowError(value.to_string())
+    }
+}
+impl<'a> From<SvcParamError> for TokenizedRecordError<'a> {
+    fn from(value: SvcParamError) -> Self {
+        Self::ValueError(value.to_string())
+    }
+}
+
+/// A service parameter of an SVCB or HTTPS record. On the wire, it is the key, the length of the
+/// value, and the value. In presentation format, it is written as `key=value`, or just `key` if
+/// the value is empty.
+///
+/// https://datatracker.ietf.org/doc/html/rfc9460#section-2.1
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub enum SvcParam {
+    /// The keys that a client must understand to use the record.
+    Mandatory(Vec<SvcParamKey>),
+    /// The TLS ALPN protocol identifiers supported by the endpoint.
+    Alpn(Vec<CharacterString>),
+    /// The endpoint does not support the default protocol of the scheme.
+    NoDefaultAlpn,
+    Port(u16),
+    Ipv4Hint(Vec<Ipv4Addr>),
+    /// An ECHConfigList. In presentation format, this is written in base64.
+    Ech(Base64),
+    Ipv6Hint(Vec<Ipv6Addr>),
+    /// Any key whose value is not interpreted. The value is kept as the octets that make it up.
+    Opaque(SvcParamKey, Vec<u8>),
+}
+
+impl SvcParam {
+    #[inline]
+    pub const fn key(&self) -> SvcParamKey {
+        match self {
+            Self::Mandatory(_)    => SvcParamKey::Mandatory,
+            Self::Alpn(_)         => SvcParamKey::Alpn,
+            Self::NoDefaultAlpn   => SvcParamKey::NoDefaultAlpn,
+            Self::Port(_)         => SvcParamKey::Port,
+            Self::Ipv4Hint(_)     => SvcParamKey::Ipv4Hint,
+            Self::Ech(_)          => SvcParamKey::Ech,
+            Self::Ipv6Hint(_)     => SvcParamKey::Ipv6Hint,
+            Self::Opaque(key, _)  => *key,
+        }
+    }
+
+    #[inline]
+    fn value_length(&self) -> Result<u16, SvcParamError> {
+        match self {
+            Self::Mandatory(keys)     => Ok(keys.serial_length()),
+            Self::Alpn(alpn_ids)      => Ok(alpn_ids.serial_length()),
+            Self::NoDefaultAlpn       => Ok(0),
+            Self::Port(port)          => Ok(port.serial_length()),
+            Self::Ipv4Hint(addresses) => Ok(addresses.serial_length()),
+            Self::Ech(ech)            => Ok(ech.serial_length()),
+            Self::Ipv6Hint(addresses) => Ok(addresses.serial_length()),
+            Self::Opaque(key, value)  => u16::try_from(value.len()).map_err(|_| SvcParamError::ValueTooLong(*key)),
+        }
+    }
+
+    /// Checks that the parameters are in strictly increasing order of their keys, that the
+    /// mandatory keys are listed in the same way and are all present, and that every value fits
+    /// within its length field.
+    ///
+    /// https://datatracker.ietf.org/doc/html/rfc9460#section-2.2
+    /// https://datatracker.ietf.org/doc/html/rfc9460#section-8
+    pub fn validate(params: &[Self]) -> Result<(), SvcParamError> {
+        for param in params {
+            param.value_length()?;
+        }
+        for pair in params.windows(2) {
+            match pair[0].key().code().cmp(&pair[1].key().code()) {
+                std::cmp::Ordering::Less => (),
+                std::cmp::Ordering::Equal => return Err(SvcParamError::DuplicateKey(pair[1].key())),
+                std::cmp::Ordering::Greater => return Err(SvcParamError::KeyOutOfOrder(pair[1].key())),
+            }
+        }
+        // Mandatory has the lowest key, so if it is present, it is first.
+        if let Some(Self::Mandatory(mandatory_keys)) = params.first() {
+            if mandatory_keys.windows(2).any(|pair| pair[0].code() >= pair[1].code()) {
+                return Err(SvcParamError::MandatoryKeysOutOfOrder);
+            }
+            for mandatory_key in mandatory_keys {
+                if *mandatory_key == SvcParamKey::Mandatory {
+                    return Err(SvcParamError::MandatoryListsItself);
+                }
+                if !params.iter().any(|param| param.key() == *mandatory_key) {
+                    return Err(SvcParamError::MissingMandatoryKey(*mandatory_key));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Reads the parameter from its key and its value, as they are written in presentation
+    /// format. The value is `None` if the key was written without an `=`.
+    ///
+    /// https://datatracker.ietf.org/doc/html/rfc9460#appendix-A
+    pub fn from_key_value<'a, 'b>(key: &'a str, value: Option<&'a str>) -> Result<Self, TokenError<'b>> where 'a: 'b {
+        let key = SvcParamKey::from_str(key)?;
+        let value = match (key, value) {
+            (SvcParamKey::NoDefaultAlpn, None | Some("")) => return Ok(Self::NoDefaultAlpn),
+            (SvcParamKey::NoDefaultAlpn, Some(_)) => return Err(TokenError::ValueError(format!("the service parameter '{key}' cannot have a value"))),
+            (SvcParamKey::Mandatory | SvcParamKey::Alpn | SvcParamKey::Port | SvcParamKey::Ipv4Hint | SvcParamKey::Ech | SvcParamKey::Ipv6Hint, None) => return Err(TokenError::ValueError(format!("the service parameter '{key}' requires a value"))),
+            (_, None) => return Ok(Self::Opaque(key, Vec::new())),
+            (_, Some(value)) => value,
+        };
+
+        match key {
+            SvcParamKey::Mandatory => {
+                let mut keys = value.split(',')
+                    .map(SvcParamKey::from_str)
+                    .collect::<Result<Vec<_>, _>>()?;
+                // On the wire, the keys must be in increasing order.
+                keys.sort_by_key(SvcParamKey::code);
+                if keys.windows(2).any(|pair| pair[0] == pair[1]) {
+                    return Err(TokenError::ValueError(format!("the service parameter '{key}' lists a key more than once")));
+                }
+                Ok(Self::Mandatory(keys))
+            },
+            SvcParamKey::Alpn => {
+                let alpn_ids = split_value_list(&unescape(value)?)?
+                    .into_iter()
+                    .map(|alpn_id| CharacterString::new(AsciiString::from(&alpn_id)))
+                    .collect::<Result<Vec<_>, _>>()?;
+                Ok(Self::Alpn(alpn_ids))
+            },
+            SvcParamKey::Port => Ok(Self::Port(u16::from_str(value)?)),
+            SvcParamKey::Ipv4Hint => Ok(Self::Ipv4Hint(
+                value.split(',').map(Ipv4Addr::from_str).collect::<Result<Vec<_>, _>>()?
+            )),
+            SvcParamKey::Ech => Ok(Self::Ech(Base64::from_utf8(value)?)),
+            SvcParamKey::Ipv6Hint => Ok(Self::Ipv6Hint(
+                value.split(',').map(Ipv6Addr::from_str).collect::<Result<Vec<_>, _>>()?
+            )),
+            _ => Ok(Self::Opaque(key, unescape(value)?)),
+        }
+    }
+
+    /// Reads the parameters that make up the rest of an SVCB or HTTPS record. Parameters may be
+    /// given in any order but are returned in the order that they are written on the wire. A
+    /// quoted value is its own token, so a token that ends in `=` takes the next token as its
+    /// value, unless that token is a parameter itself.
+    ///
+    /// https://datatracker.ietf.org/doc/html/rfc9460#section-2.2
+    pub(crate) fn from_tokens<'a, 'b>(mut tokens: &[&'a str]) -> Result<Vec<Self>, TokenizedRecordError<'b>> where 'a: 'b {
+        let mut params = Vec::with_capacity(tokens.len());
+        while let [token, remaining_tokens @ ..] = tokens {
+            tokens = remaining_tokens;
+            let param = match token.split_once('=') {
+                None => Self::from_key_value(token, None)?,
+                Some((key, "")) => match tokens {
+                    [value, remaining_tokens @ ..] if !is_param_token(value) => {
+                        tokens = remaining_tokens;
+                        Self::from_key_value(key, Some(value))?
+                    },
+                    _ => Self::from_key_value(key, Some(""))?,
+                },
+                Some((key, value)) => Self::from_key_value(key, Some(value))?,
+            };
+            params.push(param);
+        }
+
+        params.sort_by_key(|param| param.key().code());
+        Self::validate(&params)?;
+        Ok(params)
+    }
+}
+
+/// Whether the token starts a new parameter, rather than being the value of the one before it.
+#[inline]
+fn is_param_token(token: &str) -> bool {
+    let key = token.split_once('=').map_or(token, |(key, _)| key);
+    SvcParamKey::from_str(key).is_ok()
+}
+
+/// Replaces the `\X` and `\DDD` escape sequences in the value with the octets they represent.
+#[inline]
+fn unescape<'b>(value: &str) -> Result<Vec<AsciiChar>, TokenError<'b>> {
+    let escaped = AsciiString::from_utf8(value)?;
+    EscapedToEscapableIter::new(escaped.iter().copied())
+        .map(|character| character.map(EscapableChar::into_unescaped_character))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|error| TokenError::from(CharacterStringError::from(error)))
+}
+
+/// Splits an unescaped value into the items of a comma-separated list. Within an item, a comma
+/// is written as `\,` and a backslash as `\\`. Items cannot be empty.
+///
+/// https://datatracker.ietf.org/doc/html/rfc9460#appendix-A.1
+fn split_value_list<'b>(value: &[AsciiChar]) -> Result<Vec<Vec<AsciiChar>>, TokenError<'b>> {
+    let mut items = vec![Vec::new()];
+    let mut characters = value.iter().copied();
+    while let Some(character) = characters.next() {
+        match character {
+            ASCII_BACKSLASH => match characters.next() {
+                Some(escaped) => items.last_mut().unwrap().push(escaped),
+                None => return Err(TokenError::ValueError("a value list cannot end in an escape character".to_string())),
+            },
+            ASCII_COMMA => items.push(Vec::new()),
+            _ => items.last_mut().unwrap().push(character),
+        }
+    }
+    if items.iter().any(Vec::is_empty) {
+        return Err(TokenError::ValueError("a value list cannot contain an empty item".to_string()));
+    }
+    Ok(items)
+}
+
+/// Writes the octets with the escapes needed to read them back in from a single token.
+#[inline]
+fn write_escaped<W: Write + ?Sized>(out: &mut W, value: impl Iterator<Item = AsciiChar>) -> std::fmt::Result {
+    NonEscapedIntoEscapedIter::new(value)
+        .map(|character| match character {
+            EscapableChar::Ascii(character @ (ASCII_SPACE | ASCII_SEMICOLON | ASCII_OPEN_PARENTHESIS | ASCII_CLOSE_PARENTHESIS | ASCII_DOUBLE_QUOTES)) => EscapableChar::EscapedAscii(character),
+            _ => character,
+        })
+        .try_for_each(|character| write!(out, "{character}"))
+}
+
+/// Writes the items separated by commas.
+#[inline]
+fn write_list<W: Write + ?Sized, T>(out: &mut W, items: &[T], mut write_item: impl FnMut(&mut W, &T) -> std::fmt::Result) -> std::fmt::Result {
+    for (index, item) in items.iter().enumerate() {
+        if index > 0 {
+            out.write_char(',')?;
+        }
+        write_item(out, item)?;
+    }
+    Ok(())
+}
+
+impl ToWire for SvcParam {
+    #[inline]
+    fn to_wire_format<'a, 'b>(&self, wire: &'b mut WriteWire<'a>, compression: &mut Option<CompressionMap>) -> Result<(), WriteWireError> where 'a: 'b {
+        self.key().to_wire_format(wire, compression)?;
+        self.value_length()?.to_wire_format(wire, compression)?;
+        match self {
+            Self::Mandatory(keys)     => keys.to_wire_format(wire, compression),
+            Self::Alpn(alpn_ids)      => alpn_ids.to_wire_format(wire, compression),
+            Self::NoDefaultAlpn       => Ok(()),
+            Self::Port(port)          => port.to_wire_format(wire, compression),
+            Self::Ipv4Hint(addresses) => addresses.to_wire_format(wire, compression),
+            Self::Ech(ech)            => ech.to_wire_format(wire, compression),
+            Self::Ipv6Hint(addresses) => addresses.to_wire_format(wire, compression),
+            Self::Opaque(_, value)    => wire.write_bytes(value),
+        }
+    }
+
+    #[inline]
+    fn serial_length(&self) -> u16 {
+        // A value that is too long cannot be written, which `to_wire_format()` reports.
+        self.key().serial_length()
+            .saturating_add(2)  //< self.value_length().serial_length()
+            .saturating_add(self.value_length().unwrap_or(u16::MAX))
+    }
+}
+
+impl FromWire for SvcParam {
+    #[inline]
+    fn from_wire_format<'a, 'b>(wire: &'b mut ReadWire<'a>) -> Result<Self, ReadWireError> where Self: Sized, 'a: 'b {
+        let key = SvcParamKey::from_wire_format(wire)?;
+        let value_length = u16::from_wire_format(wire)?;
+        let mut value = wire.take_as_read_wire_or_err(value_length as usize, || format!("there are not enough bytes remaining in the wire to read the value of the service parameter '{key}'"))?;
+
+        let param = match key {
+            SvcParamKey::Mandatory     => Self::Mandatory(Vec::from_wire_format(&mut value)?),
+            SvcParamKey::Alpn          => Self::Alpn(Vec::from_wire_format(&mut value)?),
+            SvcParamKey::NoDefaultAlpn => Self::NoDefaultAlpn,
+            SvcParamKey::Port          => Self::Port(u16::from_wire_format(&mut value)?),
+            SvcParamKey::Ipv4Hint      => Self::Ipv4Hint(Vec::from_wire_format(&mut value)?),
+            SvcParamKey::Ech           => Self::Ech(Base64::from_bytes(value.take_all())),
+            SvcParamKey::Ipv6Hint      => Self::Ipv6Hint(Vec::from_wire_format(&mut value)?),
+            _                          => Self::Opaque(key, value.take_all().to_vec()),
+        };
+
+        if !value.is_end_reached() {
+            return Err(ReadWireError::FormatError(
+                format!("the value of the service parameter '{key}' has {} bytes left over", value.current_len())
+            ));
+        }
+        match &param {
+            Self::Mandatory(keys) if keys.is_empty() => Err(ReadWireError::FormatError(format!("the service parameter '{key}' must have a value"))),
+            Self::Alpn(alpn_ids) if alpn_ids.is_empty() => Err(ReadWireError::FormatError(format!("the service parameter '{key}' must have a value"))),
+            Self::Ipv4Hint(addresses) if addresses.is_empty() => Err(ReadWireError::FormatError(format!("the service parameter '{key}' must have a value"))),
+            Self::Ipv6Hint(addresses) if addresses.is_empty() => Err(ReadWireError::FormatError(format!("the service parameter '{key}' must have a value"))),
+            _ => Ok(param),
+        }
+    }
+}
+
+impl FromPresentation for SvcParam {
+    #[inline]
+    fn from_token_format<'a, 'b, 'c, 'd>(tokens: &'c [&'a str]) -> Result<(Self, &'d [&'a str]), TokenError<'b>> where Self: Sized, 'a: 'b, 'c: 'd, 'c: 'd {
+        match *tokens {
+            [] => Err(TokenError::OutOfTokens),
+            [token, ..] => match token.split_once('=') {
+                Some((key, value)) => Ok((Self::from_key_value(key, Some(value))?, &tokens[1..])),
+                None => Ok((Self::from_key_value(token, None)?, &tokens[1..])),
+            },
+        }
+    }
+}
+
+impl ToPresentation for SvcParam {
+    #[inline]
+    fn to_presentation_format(&self, out_buffer: &mut Vec<String>) {
+        let mut token = String::new();
+        // Writing to a String cannot fail.
+        let _ = self.write_presentation(&mut token);
+        out_buffer.push(token)
+    }
+
+    fn write_presentation_tokens<W: Write + ?Sized>(&self, out: &mut PresentationWriter<'_, W>) -> std::fmt::Result {
+        let out = out.start_token()?;
+        self.key().write_mnemonic(out)?;
+        match self {
+            Self::NoDefaultAlpn => return Ok(()),
+            Self::Opaque(_, value) if value.is_empty() => return Ok(()),
+            _ => out.write_char('=')?,
+        }
+        match self {
+            Self::Mandatory(keys) => write_list(out, keys, |out, key| key.write_mnemonic(out)),
+            Self::Alpn(alpn_ids) => write_list(out, alpn_ids, |out, alpn_id| {
+                // Commas and backslashes are escaped once to keep them within the item, and then
+                // once more along with the rest of the value.
+                let item = alpn_id.iter().flat_map(|character| match *character {
+                    ASCII_BACKSLASH | ASCII_COMMA => vec![ASCII_BACKSLASH, *character],
+                    _ => vec![*character],
+                });
+                write_escaped(out, item)
+            }),
+            Self::NoDefaultAlpn => Ok(()),
+            Self::Port(port) => write!(out, "{port}"),
+            Self::Ipv4Hint(addresses) => write_list(out, addresses, |out, address| write!(out, "{address}")),
+            Self::Ech(ech) => write!(out, "{ech}"),
+            Self::Ipv6Hint(addresses) => write_list(out, addresses, |out, address| write!(out, "{address}")),
+            Self::Opaque(_, value) => write_escaped(out, value.iter().copied()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod circular_serde_sanity_test {
+    use std::net::{Ipv4Addr, Ipv6Addr};
+
+    use crate::{resource_record::svc_param_key::SvcParamKey, serde::wire::circular_test::gen_test_circular_serde_sanity_test, types::{base64::Base64, character_string::CharacterString}};
+    use super::SvcParam;
+
+    gen_test_circular_serde_sanity_test!(
+        mandatory_circular_serde_sanity_test,
+        SvcParam::Mandatory(vec![SvcParamKey::Alpn, SvcParamKey::Ipv4Hint])
+    );
+    gen_test_circular_serde_sanity_test!(
+        alpn_circular_serde_sanity_test,
+        SvcParam::Alpn(vec![CharacterString::from_utf8("h2").unwrap(), CharacterString::from_utf8("h3").unwrap()])
+    );
+    gen_test_circular_serde_sanity_test!(
+        no_default_alpn_circular_serde_sanity_test,
+        SvcParam::NoDefaultAlpn
+    );
+    gen_test_circular_serde_sanity_test!(
+        port_circular_serde_sanity_test,
+        SvcParam::Port(8443)
+    );
+    gen_test_circular_serde_sanity_test!(
+        ipv4hint_circular_serde_sanity_test,
+        SvcParam::Ipv4Hint(vec![Ipv4Addr::new(192, 0, 2, 1), Ipv4Addr::new(192, 0, 2, 2)])
+    );
+    gen_test_circular_serde_sanity_test!(
+        ech_circular_serde_sanity_test,
+        SvcParam::Ech(Base64::from_utf8("AEP+DQA/BAAgACCW2/dfOBZAtQU55/py/BlhdRdaauPAkrERAUwppoeSEgAEAAEAAQAQY2QxLnRlc3QuZGVmby5pZQAA").unwrap())
+    );
+    gen_test_circular_serde_sanity_test!(
+        ipv6hint_circular_serde_sanity_test,
+        SvcParam::Ipv6Hint(vec![Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 1)])
+    );
+    gen_test_circular_serde_sanity_test!(
+        opaque_circular_serde_sanity_test,
+        SvcParam::Opaque(SvcParamKey::Unknown(667), b"hello\xd2qoo".to_vec())
+    );
+    gen_test_circular_serde_sanity_test!(
+        empty_opaque_circular_serde_sanity_test,
+        SvcParam::Opaque(SvcParamKey::Ohttp, Vec::new())
+    );
+}
+
+#[cfg(test)]
+mod tokenizer_tests {
+    use std::net::{Ipv4Addr, Ipv6Addr};
+
+    use crate::{resource_record::svc_param_key::SvcParamKey, serde::presentation::{from_presentation::FromPresentation, to_presentation::ToPresentation}, types::character_string::CharacterString};
+    use super::SvcParam;
+
+    fn parse(token: &str) -> Option<SvcParam> {
+        SvcParam::from_token_format(&[token]).ok().map(|(param, _)| param)
+    }
+
+    fn present(param: &SvcParam) -> String {
+        let mut token = String::new();
+        param.write_presentation(&mut token).unwrap();
+        token
+    }
+
+    #[test]
+    fn parameters_are_read_and_written() {
+        for (token, expected) in [
+            ("mandatory=ipv4hint,alpn", SvcParam::Mandatory(vec![SvcParamKey::Alpn, SvcParamKey::Ipv4Hint])),
+            ("alpn=h2,h3-19", SvcParam::Alpn(vec![CharacterString::from_utf8("h2").unwrap(), CharacterString::from_utf8("h3-19").unwrap()])),
+            ("no-default-alpn", SvcParam::NoDefaultAlpn),
+            ("port=53", SvcParam::Port(53)),
+            ("ipv4hint=192.0.2.1,192.0.2.2", SvcParam::Ipv4Hint(vec![Ipv4Addr::new(192, 0, 2, 1), Ipv4Addr::new(192, 0, 2, 2)])),
+            ("ipv6hint=2001:db8::1,2001:db8::53:1", SvcParam::Ipv6Hint(vec![Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 1), Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0x53, 1)])),
+            ("key667=hello", SvcParam::Opaque(SvcParamKey::Unknown(667), b"hello".to_vec())),
+            ("key667", SvcParam::Opaque(SvcParamKey::Unknown(667), Vec::new())),
+        ] {
+            assert_eq!(parse(token), Some(expected.clone()), "{token}");
+            let written = present(&expected);
+            assert_eq!(parse(&written), Some(expected), "{written}");
+        }
+    }
+
+    #[test]
+    fn values_are_unescaped() {
+        // https://datatracker.ietf.org/doc/html/rfc9460#appendix-D.2 (Figure 7 and Figure 11)
+        assert_eq!(parse(r"key667=hello\210qoo"), Some(SvcParam::Opaque(SvcParamKey::Unknown(667), b"hello\xd2qoo".to_vec())));
+        let alpn = SvcParam::Alpn(vec![CharacterString::from_utf8(r"f\oo,bar").unwrap(), CharacterString::from_utf8("h2").unwrap()]);
+        assert_eq!(parse(r"alpn=f\\\\oo\\,bar,h2"), Some(alpn.clone()));
+        assert_eq!(parse(r"alpn=f\\\092oo\092,bar,h2"), Some(alpn.clone()));
+        assert_eq!(present(&alpn), r"alpn=f\\\\oo\\,bar,h2");
+    }
+
+    #[test]
+    fn bad_parameters_are_rejected() {
+        for token in [
+            "port",
+            "port=",
+            "port=65536",
+            "alpn",
+            "alpn=h2,,h3",
+            "no-default-alpn=h2",
+            "ipv4hint=2001:db8::1",
+            "ipv6hint=192.0.2.1",
+            "mandatory=alpn,alpn",
+            "unknown=value",
+        ] {
+            assert_eq!(parse(token), None, "{token}");
+        }
+    }
+}
@@ -0,0 +1,37 @@
+use std::{error::Error, fmt::Display};
+
+use crate::gen_enum::enum_encoding;
+
+#[derive(Debug)]
+pub enum SvcParamKeyError<'a> {
+    UnknownMnemonic(&'a str),
+}
+impl<'a> Error for SvcParamKeyError<'a> {}
+impl<'a> Display for SvcParamKeyError<'a> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::UnknownMnemonic(mnemonic) => write!(f, "unknown service parameter key mnemonic '{mnemonic}'"),
+        }
+    }
+}
+
+enum_encoding!(
+    (doc "https://www.iana.org/assignments/dns-svcb/dns-svcb.xhtml#dns-svcparamkeys"),
+    SvcParamKey,
+    u16,
+    SvcParamKeyError,
+    (
+        (Mandatory,     "mandatory",       0),
+        (Alpn,          "alpn",            1),
+        (NoDefaultAlpn, "no-default-alpn", 2),
+        (Port,          "port",            3),
+        (Ipv4Hint,      "ipv4hint",        4),
+        (Ech,           "ech",             5),
+        (Ipv6Hint,      "ipv6hint",        6),
+        (DohPath,       "dohpath",         7),
+        (Ohttp,         "ohttp",           8),
+    ),
+    (wildcard_or_mnemonic_from_str, "key"),
+    mnemonic_presentation,
+    mnemonic_display
+);
@@ -402,49 +402,49 @@ fn days_to_seconds(days: TimeInt) -> Option<TimeInt> {
 }
 
 #[inline]
-fn month_to_days(month: TimeInt, year: TimeInt) -> TimeInt {
-    match (month, year % 4) {
+fn is_leap_year(year: TimeInt) -> bool {
+    (year % 4 == 0) && ((year % 100 != 0) || (year % 400 == 0))
+}
+
+#[inline]
+fn month_to_days(month: TimeInt, year: TimeInt) -> Option<TimeInt> {
+    match (month, is_leap_year(year)) {
         // Months with 31 days. Leap year does not matter.
-        (1, _) | (3, _) | (5, _) | (7, _) | (8, _) | (10, _) | (12, _) => 31,
+        (1, _) | (3, _) | (5, _) | (7, _) | (8, _) | (10, _) | (12, _) => Some(31),
         // Months with 30 days. Leap year does not matter.
-        (4, _) | (6, _) | (9, _) | (11, _) => 30,
+        (4, _) | (6, _) | (9, _) | (11, _) => Some(30),
         // February with 29. Leap year only.
-        (2, 0) => 29,
+        (2, true) => Some(29),
         // February with 28. Non-leap year only.
-        (2, _) => 28,
-        _ => panic!("Invalid month in the year. Acceptable inputs are 1-12"),
+        (2, false) => Some(28),
+        // Invalid month in the year. Acceptable inputs are 1-12.
+        _ => None,
     }
 }
 
 #[inline]
 fn months_to_seconds(months: TimeInt, year: TimeInt) -> Option<TimeInt> {
-    let days = (1..=months).into_iter().map(|month| month_to_days(month, year)).sum();
+    let mut days: TimeInt = 0;
+    for month in 1..=months {
+        days = days.checked_add(month_to_days(month, year)?)?;
+    }
     days_to_seconds(days)
 }
 
-// #[inline]
-// fn year_to_seconds(year: TimeInt) -> Option<TimeInt> {
-//     match year % 4 {
-//         // Leap year
-//         0 => days_to_seconds(year.checked_mul(366)?),
-//         // Non-leap year
-//         _ => days_to_seconds(year.checked_mul(365)?),
-//     }
-// }
-
 #[inline]
-fn years_since_1970_to_seconds(years: TimeInt) -> Option<TimeInt> {
-    let leap_years = years / 4;
-    let non_leap_years = years - leap_years;
-    days_to_seconds(leap_years.checked_mul(366)?)?
-        .checked_add(non_leap_years.checked_mul(365)?)
+fn years_since_1970_to_seconds(year: TimeInt) -> Option<TimeInt> {
+    let mut days: TimeInt = 0;
+    for year in 1970..year {
+        days = days.checked_add(if is_leap_year(year) { 366 } else { 365 })?;
+    }
+    days_to_seconds(days)
 }
 
 #[inline]
 fn seconds_since_1970(year: TimeInt, month: TimeInt, day: TimeInt, hour: TimeInt, minute: TimeInt, second: TimeInt) -> Option<TimeInt> {
     let mut total_second = 0_u32;
     if year > 1970 {
-        total_second = total_second.checked_add(years_since_1970_to_seconds(1970 - year - 1)?)?;
+        total_second = total_second.checked_add(years_since_1970_to_seconds(year)?)?;
     }
     if month > 1 {
         total_second = total_second.checked_add(months_to_seconds(month - 1, year)?)?;
@@ -452,35 +452,33 @@ fn seconds_since_1970(year: TimeInt, month: TimeInt, day: TimeInt, hour: TimeInt
     if day > 1 {
         total_second = total_second.checked_add(days_to_seconds(day - 1)?)?;
     }
-    if hour > 0 {
-        total_second = total_second.checked_add(hours_to_seconds(hour - 1)?)?;
-    }
-    if minute > 1 {
-        total_second = total_second.checked_add(minutes_to_seconds(minute - 1)?)?;
-    }
+    total_second = total_second.checked_add(hours_to_seconds(hour)?)?;
+    total_second = total_second.checked_add(minutes_to_seconds(minute)?)?;
     total_second.checked_add(second)
 }
 
 #[inline]
 fn datetime_parse<'a, 'b>(token: &'a str) -> Result<TimeInt, DateTimeError> where 'a: 'b {
-    if token.len() < DATE_TIME_DIGITS {
-        todo!("Error: cannot parse");
+    // Checking that every character is an ASCII digit also guarantees that the slices below fall on
+    // character boundaries.
+    if (token.len() != DATE_TIME_DIGITS) || !token.bytes().all(|character| character.is_ascii_digit()) {
+        return Err(DateTimeError::IncorrectNumberOfDigits(token.chars().count() as TimeInt));
     }
 
     let year = match TimeInt::from_str_radix(&token[0..4], 10)? {
-        year @ 0 => return Err(DateTimeError::YearTooLarge(year)),
+        year @ 0 => return Err(DateTimeError::YearTooSmall(year)),
         year @ 1..=9999 => year,
-        year @ 1000.. => return Err(DateTimeError::YearTooSmall(year)),
+        year @ 10000.. => return Err(DateTimeError::YearTooLarge(year)),
     };
     let month = match TimeInt::from_str_radix(&token[4..6], 10)? {
-        month @ 0 => return Err(DateTimeError::MonthTooLarge(month)),
+        month @ 0 => return Err(DateTimeError::MonthTooSmall(month)),
         month @ 1..=12 => month,
-        month @ 13.. => return Err(DateTimeError::MonthTooSmall(month)),
+        month @ 13.. => return Err(DateTimeError::MonthTooLarge(month)),
     };
     let day = match TimeInt::from_str_radix(&token[6..8], 10)? {
-        day @ 0 => return Err(DateTimeError::DayTooLarge(day)),
-        day @ 1..=31 => day,
-        day @ 32.. => return Err(DateTimeError::DayTooSmall(day)),
+        day @ 0 => return Err(DateTimeError::DayTooSmall(day)),
+        day if Some(day) <= month_to_days(month, year) => day,
+        day => return Err(DateTimeError::DayTooLarge(day)),
     };
     let hour = match TimeInt::from_str_radix(&token[8..10], 10)? {
         hour @ 0..=23 => hour,
@@ -546,4 +544,12 @@ mod tokenizer_tests {
     gen_fail_token_test!(test_fail_date_time_month_overflow, Time, &["00011301000000"]);
     gen_fail_token_test!(test_fail_date_time_digit_overflow, Time, &["100010101000000"]);
     gen_fail_token_test!(test_fail_date_time_digit_underflow, Time, &["0010101000000"]);
+    gen_ok_token_test!(test_ok_date_time_epoch, Time, Time { ttl: 0 }, &["19700101000000"]);
+    gen_ok_token_test!(test_ok_date_time_recent, Time, Time { ttl: 1729080000 }, &["20241016120000"]);
+    gen_ok_token_test!(test_ok_date_time_leap_day, Time, Time { ttl: 951782400 }, &["20000229000000"]);
+    gen_fail_token_test!(test_fail_date_time_not_leap_day, Time, &["19000229000000"]);
+    gen_fail_token_test!(test_fail_date_time_day_of_month_overflow, Time, &["20240431000000"]);
+    gen_fail_token_test!(test_fail_date_time_zero_year, Time, &["00000101000000"]);
+    gen_fail_token_test!(test_fail_date_time_non_ascii, Time, &["202410161200é"]);
+    gen_fail_token_test!(test_fail_date_time_sign, Time, &["+0241016120000"]);
 }
@@ -2,7 +2,7 @@ use std::net::Ipv6Addr;
 
 use dns_macros::RData;
 
-use crate::{types::domain_name::DomainName, serde::{wire::{to_wire::ToWire, write_wire::WriteWire, from_wire::FromWire, read_wire::{ReadWireError, ReadWire}}, presentation::{from_tokenized_rdata::FromTokenizedRData, from_presentation::FromPresentation, to_presentation::ToPresentation}}};
+use crate::{types::domain_name::DomainName, serde::{wire::{to_wire::ToWire, write_wire::WriteWire, from_wire::FromWire, read_wire::{ReadWireError, ReadWire}}, presentation::{from_tokenized_rdata::FromTokenizedRData, from_presentation::FromPresentation, to_presentation::{PresentationWriter, ToPresentation}}}};
 
 
 const IPV6_ADDRESS_LENGTH: usize = 128 / 8;
@@ -186,6 +186,19 @@ impl ToPresentation for A6 {
             _ => panic!("A6 record is in an illegal state. It has both ipv6_address and domain_name set to None")
         }
     }
+
+    fn write_presentation_tokens<W: std::fmt::Write + ?Sized>(&self, out: &mut PresentationWriter<'_, W>) -> std::fmt::Result {
+        self.prefix_length.write_presentation_tokens(out)?;
+        match (&self.ipv6_address, &self.domain_name) {
+            (None, Some(domain_name)) => domain_name.write_presentation_tokens(out),
+            (Some(ipv6_address), None) => ipv6_address.write_presentation_tokens(out),
+            (Some(ipv6_address), Some(domain_name)) => {
+                ipv6_address.write_presentation_tokens(out)?;
+                domain_name.write_presentation_tokens(out)
+            },
+            (None, None) => panic!("A6 record is in an illegal state. It has both ipv6_address and domain_name set to None"),
+        }
+    }
 }
 
 #[cfg(test)]
@@ -1,9 +1,7 @@
-use std::net::{Ipv4Addr, Ipv6Addr};
-
 use dns_macros::RData;
 use ux::{u1, u7};
 
-use crate::{serde::{presentation::{from_presentation::FromPresentation, from_tokenized_rdata::FromTokenizedRData, to_presentation::ToPresentation}, wire::{from_wire::FromWire, to_wire::ToWire}}, types::domain_name::DomainName};
+use crate::{resource_record::gateway::Gateway, serde::{presentation::{from_presentation::FromPresentation, from_tokenized_rdata::FromTokenizedRData, to_presentation::{PresentationWriter, ToPresentation}}, wire::{from_wire::FromWire, to_wire::ToWire}}};
 
 /// (Original) https://datatracker.ietf.org/doc/html/rfc8777#name-amtrelay-rdata-format
 ///
@@ -12,12 +10,12 @@ use crate::{serde::{presentation::{from_presentation::FromPresentation, from_tok
 pub struct AMTRELAY {
     precedence: u8,
     discovery_optional: u1,
-    relay: RelayType,
+    relay: Gateway,
 }
 
 impl AMTRELAY {
     #[inline]
-    pub fn new(precedence: u8, discovery_optional: bool, relay: RelayType) -> Self {
+    pub fn new(precedence: u8, discovery_optional: bool, relay: Gateway) -> Self {
         Self {
             precedence,
             discovery_optional: u1::from(discovery_optional),
@@ -32,82 +30,37 @@ impl AMTRELAY {
     pub fn discovery_optional(&self) -> bool{ bool::from(self.discovery_optional) }
 
     #[inline]
-    pub fn relay_type(&self) -> &RelayType { &self.relay }
+    pub fn relay_type(&self) -> u7 { u7::new(self.relay.gateway_type()) }
 
     #[inline]
-    pub fn into_relay_type(self) -> RelayType { self.relay }
-}
-
-#[derive(Clone, PartialEq, Eq, Hash, Debug)]
-pub enum RelayType {
-    Unknown(u7, Vec<u8>),
-
-    Empty,
-    Ipv4(Ipv4Addr),
-    Ipv6(Ipv6Addr),
-    DomainName(DomainName),
-}
+    pub fn relay(&self) -> &Gateway { &self.relay }
 
-impl RelayType {
     #[inline]
-    pub const fn relay_type(&self) -> u7 {
-        match self {
-            Self::Unknown(x, _) => *x,
-
-            Self::Empty         => u7::new(0),
-            Self::Ipv4(_)       => u7::new(1),
-            Self::Ipv6(_)       => u7::new(2),
-            Self::DomainName(_) => u7::new(3),
-        }
-    }
+    pub fn into_relay(self) -> Gateway { self.relay }
 }
 
 impl ToWire for AMTRELAY {
     #[inline]
     fn to_wire_format<'a, 'b>(&self, wire: &'b mut crate::serde::wire::write_wire::WriteWire<'a>, compression: &mut Option<crate::types::c_domain_name::CompressionMap>) -> Result<(), crate::serde::wire::write_wire::WriteWireError> where 'a: 'b {
         self.precedence.to_wire_format(wire, compression)?;
-        (self.discovery_optional, self.relay.relay_type()).to_wire_format(wire, compression)?;
-        match &self.relay {
-            RelayType::Empty => Ok(()),
-            RelayType::Ipv4(address) => address.to_wire_format(wire, compression),
-            RelayType::Ipv6(address) => address.to_wire_format(wire, compression),
-            RelayType::DomainName(dn) => dn.to_wire_format(wire, compression),
-            RelayType::Unknown(_, data) => {
-                wire.write_bytes(&data)?;
-                Ok(())
-            },
-        }
+        (self.discovery_optional, self.relay_type()).to_wire_format(wire, compression)?;
+        self.relay.to_wire_format(wire, compression)
     }
 
     #[inline]
     fn serial_length(&self) -> u16 {
         self.precedence.serial_length()
-        + (self.discovery_optional, self.relay.relay_type()).serial_length()
-        + match &self.relay {
-            RelayType::Empty => 0,
-            RelayType::Ipv4(address) => address.serial_length(),
-            RelayType::Ipv6(address) => address.serial_length(),
-            RelayType::DomainName(dn) => dn.serial_length(),
-            RelayType::Unknown(_, data) => data.len() as u16,
-        }
+        + (self.discovery_optional, self.relay_type()).serial_length()
+        + self.relay.serial_length()
     }
 }
 
-const U7_MAX: u8 = 2_u8.pow(7) - 1;
-
 impl FromWire for AMTRELAY {
     #[inline]
     fn from_wire_format<'a, 'b>(wire: &'b mut crate::serde::wire::read_wire::ReadWire<'a>) -> Result<Self, crate::serde::wire::read_wire::ReadWireError> where Self: Sized, 'a: 'b {
         let precedence = u8::from_wire_format(wire)?;
         let (discovery_optional, relay_type) = <(u1, u7)>::from_wire_format(wire)?;
-        let relay = match u8::from(relay_type) {
-            0 => RelayType::Empty,
-            1 => RelayType::Ipv4(Ipv4Addr::from_wire_format(wire)?),
-            2 => RelayType::Ipv6(Ipv6Addr::from_wire_format(wire)?),
-            3 => RelayType::DomainName(DomainName::from_wire_format(wire)?),
-            4..=U7_MAX => RelayType::Unknown(relay_type, wire.take_all().to_vec()),
-            _ => unreachable!("All numbers, 0-127, are represented by the u7 type. No value outside that range should be possible"),
-        };
+        let relay = Gateway::from_wire_format_with_type(u8::from(relay_type), wire)?;
 
         Ok(Self { precedence, discovery_optional, relay })
     }
@@ -121,26 +74,7 @@ impl FromTokenizedRData for AMTRELAY {
                 let (precedence, _) = u8::from_token_format(&[precedence])?;
                 let (discovery_optional, _) = u1::from_token_format(&[discovery_optional])?;
                 let (relay_type, _) = u7::from_token_format(&[relay_type])?;
-                let relay = match u8::from(relay_type) {
-                    0 => {
-                        let (root_domain, _) = DomainName::from_token_format(&[relay])?;
-                        // According to RFC 8777,
-                        // "If the relay type field is 0, the relay field MUST be ".""
-                        // In other words, the relay type is equivalent to the root domain encoding.
-                        if !root_domain.is_root() {
-                            return Err(crate::serde::presentation::errors::TokenizedRecordError::ValueError(
-                                format!("The relay type field was 0 but the relay field was not \".\". Instead, it was '{relay}'")
-                            ));
-                        }
-                        RelayType::Empty
-                    },
-                    1 => RelayType::Ipv4(Ipv4Addr::from_token_format(&[relay])?.0),
-                    2 => RelayType::Ipv6(Ipv6Addr::from_token_format(&[relay])?.0),
-                    3 => RelayType::DomainName(DomainName::from_token_format(&[relay])?.0),
-                    _ => return Err(crate::serde::presentation::errors::TokenizedRecordError::ValueError(
-                        format!("The relay type {relay_type} is unrecognized"))
-                    ),
-                };
+                let relay = Gateway::from_token_format_with_type(u8::from(relay_type), relay)?;
 
                 Ok(Self {precedence, discovery_optional, relay })
             },
@@ -155,14 +89,15 @@ impl ToPresentation for AMTRELAY {
     fn to_presentation_format(&self, out_buffer: &mut Vec<String>) {
         self.precedence.to_presentation_format(out_buffer);
         self.discovery_optional.to_presentation_format(out_buffer);
-        self.relay.relay_type().to_presentation_format(out_buffer);
-        match &self.relay {
-            RelayType::Unknown(_, _) => panic!("There is no process for writing an unknown record to presentation format"),
-            RelayType::Empty => out_buffer.push(".".to_string()),
-            RelayType::Ipv4(address) => address.to_presentation_format(out_buffer),
-            RelayType::Ipv6(address) => address.to_presentation_format(out_buffer),
-            RelayType::DomainName(domain_name) => domain_name.to_presentation_format(out_buffer),
-        }
+        self.relay_type().to_presentation_format(out_buffer);
+        self.relay.to_presentation_format(out_buffer);
+    }
+
+    fn write_presentation_tokens<W: std::fmt::Write + ?Sized>(&self, out: &mut PresentationWriter<'_, W>) -> std::fmt::Result {
+        self.precedence.write_presentation_tokens(out)?;
+        self.discovery_optional.write_presentation_tokens(out)?;
+        self.relay_type().write_presentation_tokens(out)?;
+        self.relay.write_presentation_tokens(out)
     }
 }
 
@@ -170,31 +105,34 @@ impl ToPresentation for AMTRELAY {
 mod circular_serde_sanity_test {
     use std::{net::{Ipv4Addr, Ipv6Addr}, str::FromStr};
 
-    use ux::{u1, u7};
+    use ux::u1;
 
-    use crate::{serde::wire::circular_test::gen_test_circular_serde_sanity_test, types::domain_name::DomainName};
-    use super::{AMTRELAY, RelayType};
+    use crate::{resource_record::gateway::Gateway, serde::wire::{circular_test::gen_test_circular_serde_sanity_test, read_wire::ReadWire, from_wire::FromWire}, types::domain_name::DomainName};
+    use super::AMTRELAY;
 
     gen_test_circular_serde_sanity_test!(
         record_circular_serde_sanity_test_domain,
-        AMTRELAY { precedence: 1, discovery_optional: u1::new(0), relay: RelayType::DomainName(DomainName::from_utf8("www.example.org.").unwrap()) }
+        AMTRELAY { precedence: 1, discovery_optional: u1::new(0), relay: Gateway::DomainName(DomainName::from_utf8("www.example.org.").unwrap()) }
     );
     gen_test_circular_serde_sanity_test!(
         record_circular_serde_sanity_test_ipv4,
-        AMTRELAY { precedence: 2, discovery_optional: u1::new(1), relay: RelayType::Ipv4(Ipv4Addr::from_str("192.168.86.1").unwrap()) }
+        AMTRELAY { precedence: 2, discovery_optional: u1::new(1), relay: Gateway::Ipv4(Ipv4Addr::from_str("192.168.86.1").unwrap()) }
     );
     gen_test_circular_serde_sanity_test!(
         record_circular_serde_sanity_test_ipv6,
-        AMTRELAY { precedence: 3, discovery_optional: u1::new(0), relay: RelayType::Ipv6(Ipv6Addr::from_str("a:9:8:7:6:5:4:3").unwrap()) }
+        AMTRELAY { precedence: 3, discovery_optional: u1::new(0), relay: Gateway::Ipv6(Ipv6Addr::from_str("a:9:8:7:6:5:4:3").unwrap()) }
     );
     gen_test_circular_serde_sanity_test!(
         record_circular_serde_sanity_test_empty,
-        AMTRELAY { precedence: 4, discovery_optional: u1::new(0), relay: RelayType::Empty }
-    );
-    gen_test_circular_serde_sanity_test!(
-        record_circular_serde_sanity_test_unknown,
-        AMTRELAY { precedence: 5, discovery_optional: u1::new(0), relay: RelayType::Unknown(u7::new(10), vec![0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10]) }
+        AMTRELAY { precedence: 4, discovery_optional: u1::new(0), relay: Gateway::None }
     );
+
+    #[test]
+    fn unknown_relay_type_is_rejected() {
+        // precedence 5, discovery optional 0, relay type 10, followed by an opaque relay.
+        let wire = [5, 10, 0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10];
+        assert!(AMTRELAY::from_wire_format(&mut ReadWire::from_bytes(&wire)).is_err());
+    }
 }
 
 #[cfg(test)]
@@ -203,7 +141,7 @@ mod tokenizer_tests {
 
     use ux::u1;
 
-    use crate::{serde::presentation::test_from_tokenized_rdata::{gen_ok_record_test, gen_fail_record_test}, types::domain_name::DomainName, resource_record::types::amtrelay::RelayType};
+    use crate::{serde::presentation::test_from_tokenized_rdata::{gen_ok_record_test, gen_fail_record_test}, types::domain_name::DomainName, resource_record::gateway::Gateway};
     use super::AMTRELAY;
 
     const GOOD_PRECEDENCE: &str = "1";
@@ -231,10 +169,10 @@ mod tokenizer_tests {
     const GOOD_IPV6: &str = "a:9:8:7:6:5:4:3";
     const BAD_IPV6: &str = "a:9:8:7:6:5:4:3:2:1";
 
-    gen_ok_record_test!(test_ok_empty, AMTRELAY, AMTRELAY { precedence: 1, discovery_optional: u1::new(1), relay: RelayType::Empty }, [GOOD_PRECEDENCE, GOOD_DISCOVERY, RELAY_TYPE_EMPTY, GOOD_EMPTY]);
-    gen_ok_record_test!(test_ok_ipv4, AMTRELAY, AMTRELAY { precedence: 1, discovery_optional: u1::new(1), relay: RelayType::Ipv4(Ipv4Addr::new(192, 168, 86, 1)) }, [GOOD_PRECEDENCE, GOOD_DISCOVERY, RELAY_TYPE_IPV4, GOOD_IPV4]);
-    gen_ok_record_test!(test_ok_ipv6, AMTRELAY, AMTRELAY { precedence: 1, discovery_optional: u1::new(1), relay: RelayType::Ipv6(Ipv6Addr::new(10, 9, 8, 7, 6, 5, 4, 3)) }, [GOOD_PRECEDENCE, GOOD_DISCOVERY, RELAY_TYPE_IPV6, GOOD_IPV6]);
-    gen_ok_record_test!(test_ok_domain, AMTRELAY, AMTRELAY { precedence: 1, discovery_optional: u1::new(1), relay: RelayType::DomainName(DomainName::from_utf8(GOOD_DOMAIN).unwrap()) }, [GOOD_PRECEDENCE, GOOD_DISCOVERY, RELAY_TYPE_DOMAIN, GOOD_DOMAIN]);
+    gen_ok_record_test!(test_ok_empty, AMTRELAY, AMTRELAY { precedence: 1, discovery_optional: u1::new(1), relay: Gateway::None }, [GOOD_PRECEDENCE, GOOD_DISCOVERY, RELAY_TYPE_EMPTY, GOOD_EMPTY]);
+    gen_ok_record_test!(test_ok_ipv4, AMTRELAY, AMTRELAY { precedence: 1, discovery_optional: u1::new(1), relay: Gateway::Ipv4(Ipv4Addr::new(192, 168, 86, 1)) }, [GOOD_PRECEDENCE, GOOD_DISCOVERY, RELAY_TYPE_IPV4, GOOD_IPV4]);
+    gen_ok_record_test!(test_ok_ipv6, AMTRELAY, AMTRELAY { precedence: 1, discovery_optional: u1::new(1), relay: Gateway::Ipv6(Ipv6Addr::new(10, 9, 8, 7, 6, 5, 4, 3)) }, [GOOD_PRECEDENCE, GOOD_DISCOVERY, RELAY_TYPE_IPV6, GOOD_IPV6]);
+    gen_ok_record_test!(test_ok_domain, AMTRELAY, AMTRELAY { precedence: 1, discovery_optional: u1::new(1), relay: Gateway::DomainName(DomainName::from_utf8(GOOD_DOMAIN).unwrap()) }, [GOOD_PRECEDENCE, GOOD_DISCOVERY, RELAY_TYPE_DOMAIN, GOOD_DOMAIN]);
 
     // Bad value tests
     gen_fail_record_test!(test_fail_bad_precedence, AMTRELAY, [BAD_PRECEDENCE, GOOD_DISCOVERY, RELAY_TYPE_EMPTY, GOOD_EMPTY]);
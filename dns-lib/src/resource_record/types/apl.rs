@@ -1,15 +1,16 @@
-use std::{fmt::Display, net::{Ipv4Addr, Ipv6Addr}};
+use std::{fmt::Display, net::{IpAddr, Ipv4Addr, Ipv6Addr}};
 
-use dns_macros::{RData, ToWire, FromWire, ToPresentation};
+use dns_macros::{RData, ToWire, FromWire, FromTokenizedRData, ToPresentation};
 use lazy_static::lazy_static;
 use regex::Regex;
 use ux::{u1, u7};
 
-use crate::{resource_record::address_family::AddressFamily, serde::{wire::{to_wire::ToWire, from_wire::FromWire, write_wire::WriteWire, read_wire::{ReadWireError, ReadWire}}, presentation::{from_tokenized_rdata::FromTokenizedRData, from_presentation::FromPresentation, errors::TokenizedRecordError, to_presentation::ToPresentation}}};
+use crate::{resource_record::address_family::AddressFamily, serde::{wire::{to_wire::ToWire, from_wire::FromWire, write_wire::WriteWire, read_wire::{ReadWireError, ReadWire}}, presentation::{from_presentation::FromPresentation, errors::{TokenError, TokenizedRecordError}, to_presentation::{PresentationWriter, ToPresentation}}}};
 
 /// (Original) https://datatracker.ietf.org/doc/html/rfc3123
-#[derive(Clone, PartialEq, Eq, Hash, Debug, ToWire, FromWire, ToPresentation, RData)]
+#[derive(Clone, PartialEq, Eq, Hash, Debug, ToWire, FromWire, ToPresentation, FromTokenizedRData, RData)]
 pub struct APL {
+    #[rdata(repeated)]
     apitems: Vec<APItem>
 }
 
@@ -23,17 +24,6 @@ impl APL {
     pub fn apitems(&self) -> &[APItem] { &self.apitems }
 }
 
-impl FromTokenizedRData for APL {
-    #[inline]
-    fn from_tokenized_rdata<'a, 'b>(rdata: &Vec<&'a str>) -> Result<Self, crate::serde::presentation::errors::TokenizedRecordError<'b>> where Self: Sized, 'a: 'b {
-        let mut apitems = Vec::with_capacity(rdata.len());
-        for token in rdata {
-            apitems.push(APItem::from_token_format(token)?);
-        }
-        Ok(Self { apitems })
-    }
-}
-
 #[derive(Clone, PartialEq, Eq, Hash, Debug)]
 pub struct APItem {
     address_family: AddressFamily,
@@ -77,6 +67,13 @@ impl APItem {
         Self { address_family, prefix, negation_flag, afd_length, afd_part }
     }
 
+    /// Creates an item for the address prefix. The address family is taken from the address.
+    #[inline]
+    pub fn from_ip_prefix(address: IpAddr, prefix: u8, negation_flag: bool) -> Self {
+        let afd_part = AFDPart::from(address);
+        Self::new(afd_part.address_family(), prefix, negation_flag, afd_part)
+    }
+
     #[inline]
     pub fn address_family(&self) -> &AddressFamily { &self.address_family }
 
@@ -88,6 +85,10 @@ impl APItem {
 
     #[inline]
     pub fn afd_part(&self) -> &AFDPart { &self.afd_part }
+
+    /// The address part of the prefix. Octets that were omitted from the wire format are zero.
+    #[inline]
+    pub fn ip_addr(&self) -> IpAddr { IpAddr::from(&self.afd_part) }
 }
 
 #[derive(Clone, PartialEq, Eq, Hash, Debug)]
@@ -96,6 +97,36 @@ pub enum AFDPart {
     Ipv6(Ipv6Addr),
 }
 
+impl AFDPart {
+    #[inline]
+    pub const fn address_family(&self) -> AddressFamily {
+        match self {
+            Self::Ipv4(_) => AddressFamily::Ipv4,
+            Self::Ipv6(_) => AddressFamily::Ipv6,
+        }
+    }
+}
+
+impl From<IpAddr> for AFDPart {
+    #[inline]
+    fn from(value: IpAddr) -> Self {
+        match value {
+            IpAddr::V4(address) => Self::Ipv4(address),
+            IpAddr::V6(address) => Self::Ipv6(address),
+        }
+    }
+}
+
+impl From<&AFDPart> for IpAddr {
+    #[inline]
+    fn from(value: &AFDPart) -> Self {
+        match value {
+            AFDPart::Ipv4(address) => IpAddr::V4(*address),
+            AFDPart::Ipv6(address) => IpAddr::V6(*address),
+        }
+    }
+}
+
 impl Display for AFDPart {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
@@ -325,6 +356,20 @@ impl APItem {
     }
 }
 
+impl FromPresentation for APItem {
+    #[inline]
+    fn from_token_format<'a, 'b, 'c, 'd>(tokens: &'c [&'a str]) -> Result<(Self, &'d [&'a str]), TokenError<'b>> where Self: Sized, 'a: 'b, 'c: 'd, 'c: 'd {
+        match tokens {
+            &[] => Err(TokenError::OutOfTokens),
+            &[token, ..] => match APItem::from_token_format(token) {
+                Ok(apitem) => Ok((apitem, &tokens[1..])),
+                Err(TokenizedRecordError::TokenError(error)) => Err(error),
+                Err(error) => Err(TokenError::ValueError(error.to_string())),
+            },
+        }
+    }
+}
+
 impl ToPresentation for APItem {
     #[inline]
     fn to_presentation_format(&self, out_buffer: &mut Vec<String>) {
@@ -333,11 +378,19 @@ impl ToPresentation for APItem {
             false => out_buffer.push(format!("{0}:{1}/{2}", self.address_family, self.afd_part, self.prefix)),
         }
     }
+
+    #[inline]
+    fn write_presentation_tokens<W: std::fmt::Write + ?Sized>(&self, out: &mut PresentationWriter<'_, W>) -> std::fmt::Result {
+        match self.negation_flag {
+            true => write!(out.start_token()?, "!{0}:{1}/{2}", self.address_family, self.afd_part, self.prefix),
+            false => write!(out.start_token()?, "{0}:{1}/{2}", self.address_family, self.afd_part, self.prefix),
+        }
+    }
 }
 
 #[cfg(test)]
 mod apitem_circular_serde_sanity_test {
-    use std::net::{Ipv4Addr, Ipv6Addr};
+    use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
 
     use ux::u7;
 
@@ -438,6 +491,19 @@ mod apitem_circular_serde_sanity_test {
         negation_flag_record_circular_serde_sanity_test,
         APItem { address_family: AddressFamily::Ipv4, prefix: 0, negation_flag: true, afd_length: u7::new(0), afd_part: AFDPart::Ipv4(Ipv4Addr::new(0, 0, 0, 0)) }
     );
+
+    #[test]
+    fn from_ip_prefix() {
+        let ipv4 = IpAddr::V4(Ipv4Addr::new(192, 168, 0, 0));
+        let item = APItem::from_ip_prefix(ipv4, 16, false);
+        assert_eq!(item, APItem { address_family: AddressFamily::Ipv4, prefix: 16, negation_flag: false, afd_length: u7::new(2), afd_part: AFDPart::Ipv4(Ipv4Addr::new(192, 168, 0, 0)) });
+        assert_eq!(item.ip_addr(), ipv4);
+
+        let ipv6 = IpAddr::V6(Ipv6Addr::new(10, 9, 0, 0, 0, 0, 0, 0));
+        let item = APItem::from_ip_prefix(ipv6, 32, true);
+        assert_eq!(item, APItem { address_family: AddressFamily::Ipv6, prefix: 32, negation_flag: true, afd_length: u7::new(4), afd_part: AFDPart::Ipv6(Ipv6Addr::new(10, 9, 0, 0, 0, 0, 0, 0)) });
+        assert_eq!(item.ip_addr(), ipv6);
+    }
 }
 
 #[cfg(test)]
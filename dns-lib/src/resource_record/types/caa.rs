@@ -2,7 +2,7 @@ use std::{error::Error, fmt::Display};
 
 use dns_macros::RData;
 
-use crate::{serde::{presentation::{errors::TokenizedRecordError, from_presentation::FromPresentation, from_tokenized_rdata::FromTokenizedRData, to_presentation::ToPresentation}, wire::{from_wire::FromWire, read_wire::ReadWireError, to_wire::ToWire}}, types::ascii::{AsciiChar, AsciiString}};
+use crate::{serde::{presentation::{errors::TokenizedRecordError, from_presentation::FromPresentation, from_tokenized_rdata::FromTokenizedRData, to_presentation::{PresentationWriter, ToPresentation}}, wire::{from_wire::FromWire, read_wire::ReadWireError, to_wire::ToWire}}, types::ascii::{AsciiChar, AsciiString}};
 
 #[derive(Debug)]
 pub enum CAAError {
@@ -155,6 +155,27 @@ impl ToPresentation for CAA {
         //       escaped with an escape character.
         AsciiString::from(&self.value).to_presentation_format(out_buffer);
     }
+
+    fn write_presentation_tokens<W: std::fmt::Write + ?Sized>(&self, out: &mut PresentationWriter<'_, W>) -> std::fmt::Result {
+        self.flags.write_presentation_tokens(out)?;
+        self.tag.write_presentation_tokens(out)?;
+        let out = out.start_token()?;
+        self.value.iter().try_for_each(|character| out.write_char(*character as char))
+    }
+
+    /// The flags that are set. Only the Issuer Critical flag has been defined.
+    ///
+    /// https://datatracker.ietf.org/doc/html/rfc8659#section-4.1
+    fn presentation_comment(&self) -> Option<String> {
+        let reserved_flags = self.flags & 0b01111111;
+        let comment = match (self.issuer_critical_flag(), reserved_flags) {
+            (false, 0) => "flags: none".to_string(),
+            (true, 0) => "flags: issuer critical".to_string(),
+            (false, reserved_flags) => format!("flags: reserved {reserved_flags:#04x}"),
+            (true, reserved_flags) => format!("flags: issuer critical, reserved {reserved_flags:#04x}"),
+        };
+        Some(comment)
+    }
 }
 
 #[cfg(test)]
@@ -355,3 +376,22 @@ mod tokenizer_tests {
     gen_fail_record_test!(test_fail_ic_flag_fail_tag_non_alphanumeric_ok_value_non_alphanumeric, CAA, [STR_ISSUER_CRITICAL_FLAG, &STR_FAIL_TAG_NON_ALPHANUMERIC, &STR_OK_VALUE_NON_ALPHANUMERIC]);
     gen_fail_record_test!(test_fail_unknown_flags_fail_tag_non_alphanumeric_ok_value_non_alphanumeric, CAA, [STR_UNKNOWN_FLAG, &STR_FAIL_TAG_NON_ALPHANUMERIC, &STR_OK_VALUE_NON_ALPHANUMERIC]);
 }
+
+#[cfg(test)]
+mod presentation_comment_tests {
+    use crate::{serde::presentation::to_presentation::ToPresentation, types::ascii::AsciiString};
+
+    use super::CAA;
+
+    fn flags_comment(flags: u8) -> String {
+        CAA::new(flags, AsciiString::from_utf8("issue").unwrap(), b"ca.example.net".to_vec()).unwrap().presentation_comment().unwrap()
+    }
+
+    #[test]
+    fn flags_are_decoded() {
+        assert_eq!(flags_comment(0), "flags: none");
+        assert_eq!(flags_comment(128), "flags: issuer critical");
+        assert_eq!(flags_comment(64), "flags: reserved 0x40");
+        assert_eq!(flags_comment(129), "flags: issuer critical, reserved 0x01");
+    }
+}
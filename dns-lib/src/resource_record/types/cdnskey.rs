@@ -2,13 +2,14 @@ use std::{fmt::Debug, ops::{Deref, DerefMut}};
 
 use dns_macros::{FromWire, RData, ToPresentation, ToWire};
 
-use crate::serde::presentation::from_tokenized_rdata::FromTokenizedRData;
+use crate::serde::presentation::{from_tokenized_rdata::FromTokenizedRData, to_presentation::ToPresentation};
 
 use super::dnskey::DNSKEY;
 
 
 /// (Original) https://datatracker.ietf.org/doc/html/rfc7344#section-3.2
 #[derive(Clone, PartialEq, Eq, Hash, ToWire, FromWire, ToPresentation, RData)]
+#[presentation(comment = comment)]
 pub struct CDNSKEY {
     key: DNSKEY
 }
@@ -24,6 +25,14 @@ impl Debug for CDNSKEY {
     }
 }
 
+impl CDNSKEY {
+    /// Explains the record the same way as the DNSKEY record.
+    #[inline]
+    fn comment(&self) -> Option<String> {
+        self.key.presentation_comment()
+    }
+}
+
 impl Deref for CDNSKEY {
     type Target = DNSKEY;
 
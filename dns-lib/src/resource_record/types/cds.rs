@@ -2,13 +2,14 @@ use std::{fmt::Debug, ops::{Deref, DerefMut}};
 
 use dns_macros::{FromWire, RData, ToPresentation, ToWire};
 
-use crate::serde::presentation::from_tokenized_rdata::FromTokenizedRData;
+use crate::serde::presentation::{from_tokenized_rdata::FromTokenizedRData, to_presentation::ToPresentation};
 
 use super::ds::DS;
 
 
 /// (Original) https://datatracker.ietf.org/doc/html/rfc4034#section-5
 #[derive(Clone, PartialEq, Eq, Hash, ToWire, FromWire, ToPresentation, RData)]
+#[presentation(comment = comment)]
 pub struct CDS {
     ds: DS
 }
@@ -24,6 +25,14 @@ impl Debug for CDS {
     }
 }
 
+impl CDS {
+    /// Explains the record the same way as the DS record.
+    #[inline]
+    fn comment(&self) -> Option<String> {
+        self.ds.presentation_comment()
+    }
+}
+
 impl Deref for CDS {
     type Target = DS;
 
@@ -1,6 +1,6 @@
 use dns_macros::{FromTokenizedRData, FromWire, RData, ToPresentation, ToWire};
 
-use crate::{resource_record::dnssec_alg::DnsSecAlgorithm, types::base64::Base64};
+use crate::{dnssec::key_tag, resource_record::dnssec_alg::DnsSecAlgorithm, types::base64::Base64};
 
 const DNS_ZONE_KEY_FLAG_MASK: u16       = 0b0000_0001_0000_0000;
 const SECURE_ENTRY_POINT_FLAG_MASK: u16 = 0b0000_0000_0000_0001;
@@ -10,6 +10,7 @@ const SECURE_ENTRY_POINT_FLAG_MASK: u16 = 0b0000_0000_0000_0001;
 /// (Update) https://datatracker.ietf.org/doc/html/rfc6840
 /// (Update) https://datatracker.ietf.org/doc/html/rfc6944
 #[derive(Clone, PartialEq, Eq, Hash, Debug, ToWire, FromWire, ToPresentation, FromTokenizedRData, RData)]
+#[presentation(comment = comment)]
 pub struct DNSKEY {
     ///                     1 1 1 1 1 1
     /// 0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5
@@ -74,6 +75,15 @@ impl DNSKEY {
         self.key
     }
 
+    /// The role of the key, its algorithm, and its key tag, the way `dig` writes them.
+    fn comment(&self) -> Option<String> {
+        let role = match (self.dns_zone_key(), self.secure_entry_point()) {
+            (true, true) => "KSK; ",
+            (true, false) => "ZSK; ",
+            (false, _) => "",
+        };
+        Some(format!("{role}alg = {}; key id = {}", self.algorithm, key_tag(self)))
+    }
 }
 
 #[cfg(test)]
@@ -92,3 +102,24 @@ mod circular_serde_sanity_test {
         }
     );
 }
+
+#[cfg(test)]
+mod presentation_comment_tests {
+    use crate::{resource_record::dnssec_alg::DnsSecAlgorithm, serde::presentation::to_presentation::ToPresentation, types::base64::Base64};
+
+    use super::DNSKEY;
+
+    #[test]
+    fn keys_are_explained_by_role_and_key_tag() {
+        // https://datatracker.ietf.org/doc/html/rfc4034#section-2.3
+        let key = Base64::from_utf8("AQPSKmynfzW4kyBv015MUG2DeIQ3Cbl+BBZH4b/0PY1kxkmvHjcZc8nokfzj31GajIQKY+5CptLr3buXA10hWqTkF7H6RfoRqXQeogmMHfpftf6zMv1LyBUgia7za6ZEzOJBOztyvhjL742iU/TpPSEDhm2SNKLijfUppn1UaNvv4w==").unwrap();
+        let dnskey = DNSKEY::new(256, DnsSecAlgorithm::RsaSha1, key.clone());
+        assert_eq!(dnskey.presentation_comment().unwrap(), "ZSK; alg = RSASHA1; key id = 2642");
+
+        let dnskey = DNSKEY::new(257, DnsSecAlgorithm::RsaSha1, key.clone());
+        assert!(dnskey.presentation_comment().unwrap().starts_with("KSK; alg = RSASHA1; key id = "));
+
+        let dnskey = DNSKEY::new(0, DnsSecAlgorithm::RsaSha1, key);
+        assert!(dnskey.presentation_comment().unwrap().starts_with("alg = RSASHA1; key id = "));
+    }
+}
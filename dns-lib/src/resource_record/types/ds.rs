@@ -5,6 +5,7 @@ use crate::{resource_record::{digest_alg::DigestAlgorithm, dnssec_alg::DnsSecAlg
 
 /// (Original) https://datatracker.ietf.org/doc/html/rfc4034#section-5
 #[derive(Clone, PartialEq, Eq, Hash, Debug, ToWire, FromWire, FromTokenizedRData, ToPresentation, RData)]
+#[presentation(comment = comment)]
 pub struct DS {
     key_tag: u16,
     algorithm: DnsSecAlgorithm,
@@ -42,6 +43,11 @@ impl DS {
     pub fn into_digest(self) -> Base16 {
         self.digest
     }
+
+    /// The key tag and the names of the algorithms.
+    fn comment(&self) -> Option<String> {
+        Some(format!("key id = {}; alg = {}; digest = {}", self.key_tag, self.algorithm, self.digest_type))
+    }
 }
 
 #[cfg(test)]
@@ -1,6 +1,6 @@
 use dns_macros::{ToWire, FromWire, FromTokenizedRData, RData, ToPresentation};
 
-use crate::types::character_string::CharacterString;
+use crate::types::character_string::{CharacterString, CharacterStringError};
 
 /// (Original) https://datatracker.ietf.org/doc/html/rfc1035#section-3.3.2
 #[derive(Clone, PartialEq, Eq, Hash, Debug, ToWire, FromWire, ToPresentation, FromTokenizedRData, RData)]
@@ -15,6 +15,12 @@ impl HINFO {
         Self { cpu, os }
     }
 
+    /// Creates the record from unescaped strings, such as "Intel Xeon" and "Linux".
+    #[inline]
+    pub fn from_utf8(cpu: &str, os: &str) -> Result<Self, CharacterStringError> {
+        Ok(Self { cpu: CharacterString::from_utf8(cpu)?, os: CharacterString::from_utf8(os)? })
+    }
+
     #[inline]
     pub fn cpu(&self) -> &CharacterString {
         &self.cpu
@@ -24,6 +30,11 @@ impl HINFO {
     pub fn os(&self) -> &CharacterString {
         &self.os
     }
+
+    #[inline]
+    pub fn into_cpu_os(self) -> (CharacterString, CharacterString) {
+        (self.cpu, self.os)
+    }
 }
 
 #[cfg(test)]
@@ -53,3 +64,33 @@ mod tokenizer_tests {
     gen_fail_record_test!(test_fail_one_token, HINFO, [GOOD_CPU]);
     gen_fail_record_test!(test_fail_no_tokens, HINFO, []);
 }
+
+#[cfg(test)]
+mod presentation_tests {
+    use crate::{resource_record::{rclass::RClass, resource_record::{RecordData, ResourceRecord}, time::Time}, serde::presentation::zone_file_reader::{ZoneFileReader, ZoneToken}, types::c_domain_name::CDomainName};
+    use super::HINFO;
+
+    fn zone_round_trip(hinfo: HINFO) -> String {
+        let record = ResourceRecord::new(CDomainName::from_utf8("host.example.com.").unwrap(), RClass::Internet, Time::new(300), RecordData::HINFO(hinfo));
+        let zone = format!("{record}\n");
+        let read_records = ZoneFileReader::new(&zone)
+            .map(|token| match token.unwrap() {
+                ZoneToken::ResourceRecord(record) => record,
+                ZoneToken::Include { .. } => panic!("unexpected $INCLUDE"),
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(read_records, vec![record]);
+        zone
+    }
+
+    #[test]
+    fn quoted_strings_with_spaces() {
+        let zone = zone_round_trip(HINFO::from_utf8("Intel Xeon", "Linux 6.1").unwrap());
+        assert!(zone.contains("HINFO\tIntel\\ Xeon\tLinux\\ 6.1"));
+    }
+
+    #[test]
+    fn special_characters() {
+        zone_round_trip(HINFO::from_utf8("x86;64 \"(amd)\"", "").unwrap());
+    }
+}
@@ -0,0 +1,107 @@
+use dns_macros::{RData, ToPresentation, ToWire};
+
+use crate::{resource_record::{svc_param::{SvcParam, SvcParamError}, svc_param_key::SvcParamKey}, serde::{presentation::{errors::TokenizedRecordError, from_presentation::FromPresentation, from_tokenized_rdata::FromTokenizedRData}, wire::{from_wire::FromWire, read_wire::{ReadWire, ReadWireError}}}, types::domain_name::DomainName};
+
+/// An SVCB record for the "https" and "http" schemes. It has the same format as an SVCB record.
+///
+/// (Original) https://datatracker.ietf.org/doc/html/rfc9460#section-9
+#[derive(Clone, PartialEq, Eq, Hash, Debug, ToWire, ToPresentation, RData)]
+pub struct HTTPS {
+    priority: u16,
+    target_name: DomainName,
+    params: Vec<SvcParam>,
+}
+
+impl HTTPS {
+    /// The parameters must be in increasing order of their keys, with each key given at most
+    /// once, and must include every mandatory key.
+    #[inline]
+    pub fn new(priority: u16, target_name: DomainName, params: Vec<SvcParam>) -> Result<Self, SvcParamError> {
+        SvcParam::validate(&params)?;
+        Ok(Self { priority, target_name, params })
+    }
+
+    #[inline]
+    pub fn priority(&self) -> u16 { self.priority }
+
+    /// In AliasMode (a priority of 0), the record is an alias for the target name and has no
+    /// parameters.
+    #[inline]
+    pub fn is_alias_mode(&self) -> bool { self.priority == 0 }
+
+    #[inline]
+    pub fn target_name(&self) -> &DomainName { &self.target_name }
+
+    #[inline]
+    pub fn params(&self) -> &[SvcParam] { &self.params }
+
+    #[inline]
+    pub fn param(&self, key: SvcParamKey) -> Option<&SvcParam> {
+        self.params.iter().find(|param| param.key() == key)
+    }
+}
+
+impl FromWire for HTTPS {
+    #[inline]
+    fn from_wire_format<'a, 'b>(wire: &'b mut ReadWire<'a>) -> Result<Self, ReadWireError> where Self: Sized, 'a: 'b {
+        let priority = u16::from_wire_format(wire)?;
+        let target_name = DomainName::from_wire_format(wire)?;
+        let params = Vec::from_wire_format(wire)?;
+        SvcParam::validate(&params)?;
+        Ok(Self { priority, target_name, params })
+    }
+}
+
+impl FromTokenizedRData for HTTPS {
+    fn from_tokenized_rdata<'a, 'b>(rdata: &Vec<&'a str>) -> Result<Self, TokenizedRecordError<'b>> where Self: Sized, 'a: 'b {
+        match rdata.as_slice() {
+            &[] | &[_] => Err(TokenizedRecordError::TooFewRDataTokensError{expected: 2, received: rdata.len()}),
+            &[priority, target_name, ..] => {
+                let (priority, _) = u16::from_token_format(&[priority])?;
+                let (target_name, _) = DomainName::from_token_format(&[target_name])?;
+                let params = SvcParam::from_tokens(&rdata[2..])?;
+                Ok(Self { priority, target_name, params })
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod circular_serde_sanity_test {
+    use std::net::Ipv6Addr;
+
+    use crate::{resource_record::{svc_param::SvcParam, svc_param_key::SvcParamKey}, serde::wire::circular_test::gen_test_circular_serde_sanity_test, types::{base64::Base64, character_string::CharacterString, domain_name::DomainName}};
+    use super::HTTPS;
+
+    gen_test_circular_serde_sanity_test!(
+        alias_mode_record_circular_serde_sanity_test,
+        HTTPS { priority: 0, target_name: DomainName::from_utf8("svc.example.net.").unwrap(), params: vec![] }
+    );
+    gen_test_circular_serde_sanity_test!(
+        service_mode_record_circular_serde_sanity_test,
+        HTTPS {
+            priority: 1,
+            target_name: DomainName::from_utf8(".").unwrap(),
+            params: vec![
+                SvcParam::Alpn(vec![CharacterString::from_utf8("h3").unwrap(), CharacterString::from_utf8("h2").unwrap()]),
+                SvcParam::Port(8443),
+                SvcParam::Ech(Base64::from_utf8("AQID").unwrap()),
+                SvcParam::Ipv6Hint(vec![Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 1)]),
+                SvcParam::Opaque(SvcParamKey::DohPath, b"/dns-query{?dns}".to_vec()),
+            ],
+        }
+    );
+}
+
+#[cfg(test)]
+mod tokenizer_tests {
+    use crate::{resource_record::svc_param::SvcParam, serde::presentation::test_from_tokenized_rdata::{gen_fail_record_test, gen_ok_record_test}, types::{character_string::CharacterString, domain_name::DomainName}};
+    use super::HTTPS;
+
+    // https://datatracker.ietf.org/doc/html/rfc9460#section-9.1
+    gen_ok_record_test!(test_ok_alias_mode, HTTPS, HTTPS { priority: 0, target_name: DomainName::from_utf8("svc.example.net.").unwrap(), params: vec![] }, ["0", "svc.example.net."]);
+    gen_ok_record_test!(test_ok_service_mode, HTTPS, HTTPS { priority: 1, target_name: DomainName::from_utf8(".").unwrap(), params: vec![SvcParam::Alpn(vec![CharacterString::from_utf8("h2").unwrap()]), SvcParam::Port(8443)] }, ["1", ".", "port=8443", "alpn=h2"]);
+    gen_fail_record_test!(test_fail_no_tokens, HTTPS, [""; 0]);
+    gen_fail_record_test!(test_fail_bad_priority, HTTPS, ["65536", "."]);
+    gen_fail_record_test!(test_fail_duplicate_key, HTTPS, ["1", ".", "port=443", "port=8443"]);
+}
@@ -0,0 +1,183 @@
+use dns_macros::RData;
+
+use crate::{resource_record::{gateway::Gateway, ipsec_alg::IpSecAlgorithm}, serde::{presentation::{errors::TokenizedRecordError, from_presentation::FromPresentation, from_tokenized_rdata::FromTokenizedRData, to_presentation::{PresentationWriter, ToPresentation}}, wire::{from_wire::FromWire, read_wire::{ReadWire, ReadWireError}, to_wire::ToWire, write_wire::{WriteWire, WriteWireError}}}, types::{base64::Base64, base_conversions::BaseConversions, c_domain_name::CompressionMap}};
+
+/// (Original) https://datatracker.ietf.org/doc/html/rfc4025#section-2
+#[derive(Clone, PartialEq, Eq, Hash, Debug, RData)]
+pub struct IPSECKEY {
+    precedence: u8,
+    algorithm: IpSecAlgorithm,
+    gateway: Gateway,
+    /// Empty if no key is present, in which case the algorithm should be 0.
+    public_key: Base64,
+}
+
+impl IPSECKEY {
+    #[inline]
+    pub fn new(precedence: u8, algorithm: IpSecAlgorithm, gateway: Gateway, public_key: Base64) -> Self {
+        Self { precedence, algorithm, gateway, public_key }
+    }
+
+    #[inline]
+    pub fn precedence(&self) -> u8 { self.precedence }
+
+    #[inline]
+    pub fn gateway_type(&self) -> u8 { self.gateway.gateway_type() }
+
+    #[inline]
+    pub fn algorithm(&self) -> IpSecAlgorithm { self.algorithm }
+
+    #[inline]
+    pub fn gateway(&self) -> &Gateway { &self.gateway }
+
+    #[inline]
+    pub fn public_key(&self) -> Option<&Base64> {
+        if self.public_key.is_empty() {
+            None
+        } else {
+            Some(&self.public_key)
+        }
+    }
+}
+
+impl ToWire for IPSECKEY {
+    #[inline]
+    fn to_wire_format<'a, 'b>(&self, wire: &'b mut WriteWire<'a>, compression: &mut Option<CompressionMap>) -> Result<(), WriteWireError> where 'a: 'b {
+        self.precedence.to_wire_format(wire, compression)?;
+        self.gateway_type().to_wire_format(wire, compression)?;
+        self.algorithm.to_wire_format(wire, compression)?;
+        self.gateway.to_wire_format(wire, compression)?;
+        self.public_key.to_wire_format(wire, compression)
+    }
+
+    #[inline]
+    fn serial_length(&self) -> u16 {
+        self.precedence.serial_length()
+        + self.gateway_type().serial_length()
+        + self.algorithm.serial_length()
+        + self.gateway.serial_length()
+        + self.public_key.serial_length()
+    }
+}
+
+impl FromWire for IPSECKEY {
+    #[inline]
+    fn from_wire_format<'a, 'b>(wire: &'b mut ReadWire<'a>) -> Result<Self, ReadWireError> where Self: Sized, 'a: 'b {
+        let precedence = u8::from_wire_format(wire)?;
+        let gateway_type = u8::from_wire_format(wire)?;
+        let algorithm = IpSecAlgorithm::from_wire_format(wire)?;
+        let gateway = Gateway::from_wire_format_with_type(gateway_type, wire)?;
+        let public_key = Base64::from_wire_format(wire)?;
+
+        Ok(Self { precedence, algorithm, gateway, public_key })
+    }
+}
+
+impl FromTokenizedRData for IPSECKEY {
+    #[inline]
+    fn from_tokenized_rdata<'a, 'b>(rdata: &Vec<&'a str>) -> Result<Self, TokenizedRecordError<'b>> where Self: Sized, 'a: 'b {
+        let (precedence, gateway_type, algorithm, gateway, public_key) = match rdata.as_slice() {
+            &[precedence, gateway_type, algorithm, gateway] => (precedence, gateway_type, algorithm, gateway, None),
+            &[precedence, gateway_type, algorithm, gateway, public_key] => (precedence, gateway_type, algorithm, gateway, Some(public_key)),
+            &[_, _, _, _, _, ..] => return Err(TokenizedRecordError::TooManyRDataTokensError{expected: 5, received: rdata.len()}),
+            _ => return Err(TokenizedRecordError::TooFewRDataTokensError{expected: 4, received: rdata.len()}),
+        };
+
+        let (precedence, _) = u8::from_token_format(&[precedence])?;
+        let (gateway_type, _) = u8::from_token_format(&[gateway_type])?;
+        let (algorithm, _) = IpSecAlgorithm::from_token_format(&[algorithm])?;
+        let gateway = Gateway::from_token_format_with_type(gateway_type, gateway)?;
+        let public_key = match public_key {
+            Some(public_key) => Base64::from_token_format(&[public_key])?.0,
+            None => Base64::from_bytes(&[]),
+        };
+
+        Ok(Self { precedence, algorithm, gateway, public_key })
+    }
+}
+
+impl ToPresentation for IPSECKEY {
+    #[inline]
+    fn to_presentation_format(&self, out_buffer: &mut Vec<String>) {
+        self.precedence.to_presentation_format(out_buffer);
+        self.gateway_type().to_presentation_format(out_buffer);
+        self.algorithm.to_presentation_format(out_buffer);
+        self.gateway.to_presentation_format(out_buffer);
+        if !self.public_key.is_empty() {
+            self.public_key.to_presentation_format(out_buffer);
+        }
+    }
+
+    fn write_presentation_tokens<W: std::fmt::Write + ?Sized>(&self, out: &mut PresentationWriter<'_, W>) -> std::fmt::Result {
+        self.precedence.write_presentation_tokens(out)?;
+        self.gateway_type().write_presentation_tokens(out)?;
+        self.algorithm.write_presentation_tokens(out)?;
+        self.gateway.write_presentation_tokens(out)?;
+        if !self.public_key.is_empty() {
+            self.public_key.write_presentation_tokens(out)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod circular_serde_sanity_test {
+    use std::net::{Ipv4Addr, Ipv6Addr};
+
+    use crate::{resource_record::{gateway::Gateway, ipsec_alg::IpSecAlgorithm}, serde::wire::circular_test::gen_test_circular_serde_sanity_test, types::{base64::Base64, base_conversions::BaseConversions, domain_name::DomainName}};
+    use super::IPSECKEY;
+
+    const KEY: &str = "AQNRU3mG7TVTO2BkR47usntb102uFJtugbo6BSGvgqt4AQ==";
+
+    gen_test_circular_serde_sanity_test!(
+        record_circular_serde_sanity_test_none,
+        IPSECKEY { precedence: 10, algorithm: IpSecAlgorithm::Rsa, gateway: Gateway::None, public_key: Base64::from_utf8(KEY).unwrap() }
+    );
+    gen_test_circular_serde_sanity_test!(
+        record_circular_serde_sanity_test_ipv4,
+        IPSECKEY { precedence: 10, algorithm: IpSecAlgorithm::Rsa, gateway: Gateway::Ipv4(Ipv4Addr::new(192, 0, 2, 38)), public_key: Base64::from_utf8(KEY).unwrap() }
+    );
+    gen_test_circular_serde_sanity_test!(
+        record_circular_serde_sanity_test_ipv6,
+        IPSECKEY { precedence: 10, algorithm: IpSecAlgorithm::Rsa, gateway: Gateway::Ipv6(Ipv6Addr::new(0x2001, 0xdb8, 0, 8, 0x2fc, 0x2ff, 0xfe00, 0x1234)), public_key: Base64::from_utf8(KEY).unwrap() }
+    );
+    gen_test_circular_serde_sanity_test!(
+        record_circular_serde_sanity_test_domain,
+        IPSECKEY { precedence: 10, algorithm: IpSecAlgorithm::Rsa, gateway: Gateway::DomainName(DomainName::from_utf8("mygateway.example.com.").unwrap()), public_key: Base64::from_utf8(KEY).unwrap() }
+    );
+    gen_test_circular_serde_sanity_test!(
+        record_circular_serde_sanity_test_no_key,
+        IPSECKEY { precedence: 10, algorithm: IpSecAlgorithm::Unknown(0), gateway: Gateway::Ipv4(Ipv4Addr::new(192, 0, 2, 3)), public_key: Base64::from_bytes(&[]) }
+    );
+}
+
+#[cfg(test)]
+mod tokenizer_tests {
+    use std::net::{Ipv4Addr, Ipv6Addr};
+
+    use crate::{resource_record::{gateway::Gateway, ipsec_alg::IpSecAlgorithm}, serde::presentation::test_from_tokenized_rdata::{gen_fail_record_test, gen_ok_record_test}, types::{base64::Base64, base_conversions::BaseConversions, domain_name::DomainName}};
+    use super::IPSECKEY;
+
+    // Examples from RFC 4025 section 3.1
+    const PRECEDENCE: &str = "10";
+    const ALGORITHM_RSA: &str = "2";
+    const ALGORITHM_NONE: &str = "0";
+    const KEY: &str = "AQNRU3mG7TVTO2BkR47usntb102uFJtugbo6BSGvgqt4AQ==";
+
+    gen_ok_record_test!(test_ok_none, IPSECKEY, IPSECKEY { precedence: 10, algorithm: IpSecAlgorithm::Rsa, gateway: Gateway::None, public_key: Base64::from_utf8(KEY).unwrap() }, [PRECEDENCE, "0", ALGORITHM_RSA, ".", KEY]);
+    gen_ok_record_test!(test_ok_ipv4, IPSECKEY, IPSECKEY { precedence: 10, algorithm: IpSecAlgorithm::Rsa, gateway: Gateway::Ipv4(Ipv4Addr::new(192, 0, 2, 38)), public_key: Base64::from_utf8(KEY).unwrap() }, [PRECEDENCE, "1", ALGORITHM_RSA, "192.0.2.38", KEY]);
+    gen_ok_record_test!(test_ok_ipv6, IPSECKEY, IPSECKEY { precedence: 10, algorithm: IpSecAlgorithm::Rsa, gateway: Gateway::Ipv6(Ipv6Addr::new(0x2001, 0xdb8, 0, 8, 0x2fc, 0x2ff, 0xfe00, 0x1234)), public_key: Base64::from_utf8(KEY).unwrap() }, [PRECEDENCE, "2", ALGORITHM_RSA, "2001:db8:0:8:2fc:2ff:fe00:1234", KEY]);
+    gen_ok_record_test!(test_ok_domain, IPSECKEY, IPSECKEY { precedence: 10, algorithm: IpSecAlgorithm::Rsa, gateway: Gateway::DomainName(DomainName::from_utf8("mygateway.example.com.").unwrap()), public_key: Base64::from_utf8(KEY).unwrap() }, [PRECEDENCE, "3", ALGORITHM_RSA, "mygateway.example.com.", KEY]);
+    gen_ok_record_test!(test_ok_no_key, IPSECKEY, IPSECKEY { precedence: 10, algorithm: IpSecAlgorithm::Unknown(0), gateway: Gateway::Ipv4(Ipv4Addr::new(192, 0, 2, 3)), public_key: Base64::from_bytes(&[]) }, [PRECEDENCE, "1", ALGORITHM_NONE, "192.0.2.3"]);
+
+    // Gateway type does not match gateway value tests
+    gen_fail_record_test!(test_fail_none_type_but_domain, IPSECKEY, [PRECEDENCE, "0", ALGORITHM_RSA, "mygateway.example.com.", KEY]);
+    gen_fail_record_test!(test_fail_ipv4_type_but_ipv6, IPSECKEY, [PRECEDENCE, "1", ALGORITHM_RSA, "2001:db8::1", KEY]);
+    gen_fail_record_test!(test_fail_ipv6_type_but_ipv4, IPSECKEY, [PRECEDENCE, "2", ALGORITHM_RSA, "192.0.2.38", KEY]);
+    gen_fail_record_test!(test_fail_unknown_type, IPSECKEY, [PRECEDENCE, "4", ALGORITHM_RSA, ".", KEY]);
+
+    // Incorrect number of tokens tests
+    gen_fail_record_test!(test_fail_six_tokens, IPSECKEY, [PRECEDENCE, "0", ALGORITHM_RSA, ".", KEY, KEY]);
+    gen_fail_record_test!(test_fail_three_tokens, IPSECKEY, [PRECEDENCE, "0", ALGORITHM_RSA]);
+    gen_fail_record_test!(test_fail_no_tokens, IPSECKEY, []);
+}
@@ -0,0 +1,57 @@
+use dns_macros::{ToWire, FromWire, FromTokenizedRData, RData, ToPresentation};
+
+use crate::types::character_string::CharacterString;
+
+/// (Original) https://datatracker.ietf.org/doc/html/rfc1183#section-3.2
+#[derive(Clone, PartialEq, Eq, Hash, Debug, ToWire, FromWire, ToPresentation, FromTokenizedRData, RData)]
+pub struct ISDN {
+    isdn_address: CharacterString,
+    #[rdata(optional)]
+    subaddress: Option<CharacterString>,
+}
+
+impl ISDN {
+    #[inline]
+    pub fn new(isdn_address: CharacterString, subaddress: Option<CharacterString>) -> Self {
+        Self { isdn_address, subaddress }
+    }
+
+    #[inline]
+    pub fn isdn_address(&self) -> &CharacterString {
+        &self.isdn_address
+    }
+
+    #[inline]
+    pub fn subaddress(&self) -> Option<&CharacterString> {
+        self.subaddress.as_ref()
+    }
+}
+
+#[cfg(test)]
+mod circular_serde_sanity_test {
+    use crate::{serde::wire::circular_test::gen_test_circular_serde_sanity_test, types::character_string::CharacterString};
+    use super::ISDN;
+
+    gen_test_circular_serde_sanity_test!(
+        record_circular_serde_sanity_test,
+        ISDN { isdn_address: CharacterString::from_utf8("150862028003217").unwrap(), subaddress: Some(CharacterString::from_utf8("004").unwrap()) }
+    );
+    gen_test_circular_serde_sanity_test!(
+        no_subaddress_record_circular_serde_sanity_test,
+        ISDN { isdn_address: CharacterString::from_utf8("150862028003217").unwrap(), subaddress: None }
+    );
+}
+
+#[cfg(test)]
+mod tokenizer_tests {
+    use crate::{serde::presentation::test_from_tokenized_rdata::{gen_ok_record_test, gen_fail_record_test}, types::character_string::CharacterString};
+    use super::ISDN;
+
+    const GOOD_ADDRESS: &str = "150862028003217";
+    const GOOD_SUBADDRESS: &str = "004";
+
+    gen_ok_record_test!(test_ok, ISDN, ISDN { isdn_address: CharacterString::from_utf8(GOOD_ADDRESS).unwrap(), subaddress: Some(CharacterString::from_utf8(GOOD_SUBADDRESS).unwrap()) }, [GOOD_ADDRESS, GOOD_SUBADDRESS]);
+    gen_ok_record_test!(test_ok_no_subaddress, ISDN, ISDN { isdn_address: CharacterString::from_utf8(GOOD_ADDRESS).unwrap(), subaddress: None }, [GOOD_ADDRESS]);
+    gen_fail_record_test!(test_fail_three_tokens, ISDN, [GOOD_ADDRESS, GOOD_SUBADDRESS, GOOD_SUBADDRESS]);
+    gen_fail_record_test!(test_fail_no_tokens, ISDN, []);
+}
@@ -0,0 +1,490 @@
+use std::{error::Error, fmt::Display};
+
+use dns_macros::RData;
+
+use crate::serde::{presentation::{errors::TokenizedRecordError, from_tokenized_rdata::FromTokenizedRData, to_presentation::{PresentationWriter, ToPresentation}}, wire::{from_wire::FromWire, read_wire::{ReadWire, ReadWireError}, to_wire::ToWire, write_wire::{WriteWire, WriteWireError}}};
+
+/// The latitude and longitude of the equator and prime meridian, in thousandths of a second of arc.
+const EQUATOR: u32 = 1 << 31;
+const PRIME_MERIDIAN: u32 = 1 << 31;
+/// The altitude is stored in centimeters above a base of 100,000m below the WGS 84 reference
+/// spheroid.
+const ALTITUDE_BASE_CM: i64 = 100_000_00;
+
+const MILLISECONDS_PER_DEGREE: f64 = 3_600_000.0;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LocError {
+    LatitudeOutOfRange(f64),
+    LongitudeOutOfRange(f64),
+    AltitudeOutOfRange(f64),
+    SizeOutOfRange(f64),
+}
+impl Error for LocError {}
+impl Display for LocError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::LatitudeOutOfRange(degrees) => write!(f, "the latitude {degrees} is not between -90 and 90 degrees"),
+            Self::LongitudeOutOfRange(degrees) => write!(f, "the longitude {degrees} is not between -180 and 180 degrees"),
+            Self::AltitudeOutOfRange(meters) => write!(f, "the altitude {meters}m is not between -100000m and 42849672.95m"),
+            Self::SizeOutOfRange(meters) => write!(f, "the size {meters}m is not between 0m and 90000000m"),
+        }
+    }
+}
+
+/// A size or precision, encoded as a base (the high nibble) and a power of ten (the low nibble)
+/// in centimeters. Only one significant digit is kept.
+///
+/// https://datatracker.ietf.org/doc/html/rfc1876#section-2
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct LocSize {
+    encoded: u8,
+}
+
+impl LocSize {
+    pub const MAX_CENTIMETERS: u64 = 9_000_000_000;
+
+    /// Rounds the number of centimeters down to one significant digit. Values larger than
+    /// `MAX_CENTIMETERS` are clamped.
+    #[inline]
+    pub const fn from_centimeters(centimeters: u64) -> Self {
+        let mut base = if centimeters > Self::MAX_CENTIMETERS { Self::MAX_CENTIMETERS } else { centimeters };
+        let mut exponent = 0;
+        while base >= 10 {
+            base /= 10;
+            exponent += 1;
+        }
+        Self { encoded: ((base as u8) << 4) | exponent }
+    }
+
+    /// Rounds the number of meters down to one significant digit.
+    #[inline]
+    pub fn from_meters(meters: f64) -> Result<Self, LocError> {
+        let centimeters = (meters * 100.0).round();
+        if !(0.0..=(Self::MAX_CENTIMETERS as f64)).contains(&centimeters) {
+            return Err(LocError::SizeOutOfRange(meters));
+        }
+        Ok(Self::from_centimeters(centimeters as u64))
+    }
+
+    #[inline]
+    pub const fn centimeters(&self) -> u64 {
+        // Neither nibble is supposed to be larger than 9 but that is not enforced on the wire.
+        let base = (self.encoded >> 4) as u64;
+        let exponent = (self.encoded & 0x0F) as u32;
+        base * 10_u64.pow(exponent)
+    }
+
+    #[inline]
+    pub fn meters(&self) -> f64 {
+        self.centimeters() as f64 / 100.0
+    }
+}
+
+impl Display for LocSize {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let centimeters = self.centimeters();
+        write!(f, "{}.{:02}m", centimeters / 100, centimeters % 100)
+    }
+}
+
+/// (Original) https://datatracker.ietf.org/doc/html/rfc1876
+#[derive(Clone, PartialEq, Eq, Hash, Debug, RData)]
+pub struct LOC {
+    /// The diameter of a sphere enclosing the described entity.
+    size: LocSize,
+    horizontal_precision: LocSize,
+    vertical_precision: LocSize,
+    /// Thousandths of a second of arc. 2^31 is the equator and larger numbers are north.
+    latitude: u32,
+    /// Thousandths of a second of arc. 2^31 is the prime meridian and larger numbers are east.
+    longitude: u32,
+    /// Centimeters above a base of 100,000m below the WGS 84 reference spheroid.
+    altitude: u32,
+}
+
+impl LOC {
+    /// The only version of the LOC record.
+    pub const VERSION: u8 = 0;
+
+    /// The size used when none is given in the presentation format (1m).
+    pub const DEFAULT_SIZE: LocSize = LocSize::from_centimeters(100);
+    /// The horizontal precision used when none is given in the presentation format (10,000m).
+    pub const DEFAULT_HORIZONTAL_PRECISION: LocSize = LocSize::from_centimeters(10_000_00);
+    /// The vertical precision used when none is given in the presentation format (10m).
+    pub const DEFAULT_VERTICAL_PRECISION: LocSize = LocSize::from_centimeters(10_00);
+
+    /// Creates a record from decimal degrees (positive for north and east) and meters above the
+    /// WGS 84 reference spheroid. The default size and precisions are used.
+    pub fn from_degrees(latitude: f64, longitude: f64, altitude: f64) -> Result<Self, LocError> {
+        if !(-90.0..=90.0).contains(&latitude) {
+            return Err(LocError::LatitudeOutOfRange(latitude));
+        }
+        if !(-180.0..=180.0).contains(&longitude) {
+            return Err(LocError::LongitudeOutOfRange(longitude));
+        }
+        let altitude_cm = (altitude * 100.0).round() + (ALTITUDE_BASE_CM as f64);
+        if !(0.0..=(u32::MAX as f64)).contains(&altitude_cm) {
+            return Err(LocError::AltitudeOutOfRange(altitude));
+        }
+
+        Ok(Self {
+            size: Self::DEFAULT_SIZE,
+            horizontal_precision: Self::DEFAULT_HORIZONTAL_PRECISION,
+            vertical_precision: Self::DEFAULT_VERTICAL_PRECISION,
+            latitude: (EQUATOR as i64 + (latitude * MILLISECONDS_PER_DEGREE).round() as i64) as u32,
+            longitude: (PRIME_MERIDIAN as i64 + (longitude * MILLISECONDS_PER_DEGREE).round() as i64) as u32,
+            altitude: altitude_cm as u32,
+        })
+    }
+
+    #[inline]
+    pub fn with_precision(mut self, size: LocSize, horizontal_precision: LocSize, vertical_precision: LocSize) -> Self {
+        self.size = size;
+        self.horizontal_precision = horizontal_precision;
+        self.vertical_precision = vertical_precision;
+        self
+    }
+
+    #[inline]
+    pub const fn version(&self) -> u8 { Self::VERSION }
+
+    #[inline]
+    pub const fn size(&self) -> LocSize { self.size }
+
+    #[inline]
+    pub const fn horizontal_precision(&self) -> LocSize { self.horizontal_precision }
+
+    #[inline]
+    pub const fn vertical_precision(&self) -> LocSize { self.vertical_precision }
+
+    /// The latitude in decimal degrees. Positive numbers are north of the equator.
+    #[inline]
+    pub fn latitude(&self) -> f64 {
+        (self.latitude as i64 - EQUATOR as i64) as f64 / MILLISECONDS_PER_DEGREE
+    }
+
+    /// The longitude in decimal degrees. Positive numbers are east of the prime meridian.
+    #[inline]
+    pub fn longitude(&self) -> f64 {
+        (self.longitude as i64 - PRIME_MERIDIAN as i64) as f64 / MILLISECONDS_PER_DEGREE
+    }
+
+    /// The altitude in meters above the WGS 84 reference spheroid.
+    #[inline]
+    pub fn altitude(&self) -> f64 {
+        self.altitude_cm() as f64 / 100.0
+    }
+
+    #[inline]
+    fn altitude_cm(&self) -> i64 {
+        self.altitude as i64 - ALTITUDE_BASE_CM
+    }
+
+    fn presentation_tokens(&self) -> Vec<String> {
+        let mut tokens = Vec::with_capacity(12);
+        let (latitude, north) = match self.latitude.checked_sub(EQUATOR) {
+            Some(latitude) => (latitude, true),
+            None => (EQUATOR - self.latitude, false),
+        };
+        push_dms(&mut tokens, latitude, if north { "N" } else { "S" });
+        let (longitude, east) = match self.longitude.checked_sub(PRIME_MERIDIAN) {
+            Some(longitude) => (longitude, true),
+            None => (PRIME_MERIDIAN - self.longitude, false),
+        };
+        push_dms(&mut tokens, longitude, if east { "E" } else { "W" });
+
+        let altitude = self.altitude_cm();
+        let sign = if altitude < 0 { "-" } else { "" };
+        tokens.push(format!("{sign}{}.{:02}m", altitude.abs() / 100, altitude.abs() % 100));
+        tokens.push(self.size.to_string());
+        tokens.push(self.horizontal_precision.to_string());
+        tokens.push(self.vertical_precision.to_string());
+        tokens
+    }
+}
+
+fn push_dms(tokens: &mut Vec<String>, milliseconds: u32, hemisphere: &str) {
+    tokens.push((milliseconds / 3_600_000).to_string());
+    tokens.push(((milliseconds / 60_000) % 60).to_string());
+    tokens.push(format!("{}.{:03}", (milliseconds / 1000) % 60, milliseconds % 1000));
+    tokens.push(hemisphere.to_string());
+}
+
+impl ToWire for LOC {
+    #[inline]
+    fn to_wire_format<'a, 'b>(&self, wire: &'b mut WriteWire<'a>, compression: &mut Option<crate::types::c_domain_name::CompressionMap>) -> Result<(), WriteWireError> where 'a: 'b {
+        Self::VERSION.to_wire_format(wire, compression)?;
+        self.size.encoded.to_wire_format(wire, compression)?;
+        self.horizontal_precision.encoded.to_wire_format(wire, compression)?;
+        self.vertical_precision.encoded.to_wire_format(wire, compression)?;
+        self.latitude.to_wire_format(wire, compression)?;
+        self.longitude.to_wire_format(wire, compression)?;
+        self.altitude.to_wire_format(wire, compression)
+    }
+
+    #[inline]
+    fn serial_length(&self) -> u16 {
+        1 + 1 + 1 + 1 + 4 + 4 + 4
+    }
+}
+
+impl FromWire for LOC {
+    #[inline]
+    fn from_wire_format<'a, 'b>(wire: &'b mut ReadWire<'a>) -> Result<Self, ReadWireError> where Self: Sized, 'a: 'b {
+        let version = u8::from_wire_format(wire)?;
+        if version != Self::VERSION {
+            return Err(ReadWireError::VersionError(
+                format!("only LOC version {} is supported. Found version {version}", Self::VERSION)
+            ));
+        }
+        let size = LocSize { encoded: u8::from_wire_format(wire)? };
+        let horizontal_precision = LocSize { encoded: u8::from_wire_format(wire)? };
+        let vertical_precision = LocSize { encoded: u8::from_wire_format(wire)? };
+        let latitude = u32::from_wire_format(wire)?;
+        let longitude = u32::from_wire_format(wire)?;
+        let altitude = u32::from_wire_format(wire)?;
+
+        Ok(Self { size, horizontal_precision, vertical_precision, latitude, longitude, altitude })
+    }
+}
+
+/// Parses the degrees, optional minutes, optional seconds, and hemisphere of a latitude or
+/// longitude. Returns the offset from the equator or prime meridian in thousandths of a second.
+fn dms_from_tokens<'a, 'b, 'c>(tokens: &mut &'c [&'a str], positive: &str, negative: &str, max_degrees: u32) -> Result<i64, TokenizedRecordError<'b>> where 'a: 'b {
+    let mut values = Vec::with_capacity(3);
+    let sign = loop {
+        let (token, rest) = match tokens.split_first() {
+            Some(split) => split,
+            None => return Err(TokenizedRecordError::ValueError(
+                format!("expected the hemisphere '{positive}' or '{negative}'")
+            )),
+        };
+        *tokens = rest;
+        if token.eq_ignore_ascii_case(positive) {
+            break 1;
+        } else if token.eq_ignore_ascii_case(negative) {
+            break -1;
+        } else if values.len() == 3 {
+            return Err(TokenizedRecordError::ValueError(
+                format!("expected the hemisphere '{positive}' or '{negative}' but found '{token}'")
+            ));
+        }
+        values.push(*token);
+    };
+
+    let milliseconds = match values.as_slice() {
+        &[degrees] => parse_integer(degrees, max_degrees)? * 3_600_000,
+        &[degrees, minutes] => (parse_integer(degrees, max_degrees)? * 3_600_000)
+            + (parse_integer(minutes, 59)? * 60_000),
+        &[degrees, minutes, seconds] => (parse_integer(degrees, max_degrees)? * 3_600_000)
+            + (parse_integer(minutes, 59)? * 60_000)
+            + parse_decimal(seconds, 3, 59_999)?,
+        _ => return Err(TokenizedRecordError::ValueError(
+            format!("expected degrees before the hemisphere '{positive}' or '{negative}'")
+        )),
+    };
+    if milliseconds > (max_degrees as i64 * 3_600_000) {
+        return Err(TokenizedRecordError::OutOfBoundsError(
+            format!("the coordinate cannot be more than {max_degrees} degrees")
+        ));
+    }
+    Ok(sign * milliseconds)
+}
+
+fn parse_integer<'b>(token: &str, max: u32) -> Result<i64, TokenizedRecordError<'b>> {
+    match token.parse::<u32>() {
+        Ok(value) if value <= max => Ok(value as i64),
+        _ => Err(TokenizedRecordError::ValueError(
+            format!("expected an integer between 0 and {max} but found '{token}'")
+        )),
+    }
+}
+
+/// Parses an unsigned decimal with up to `places` digits after the decimal point. The result is
+/// scaled by `10^places`.
+fn parse_decimal<'b>(token: &str, places: u32, max: i64) -> Result<i64, TokenizedRecordError<'b>> {
+    let error = || TokenizedRecordError::ValueError(
+        format!("expected a number with at most {places} decimal places but found '{token}'")
+    );
+    let (whole, fraction) = token.split_once('.').unwrap_or((token, ""));
+    if whole.is_empty()
+    || (fraction.len() > places as usize)
+    || !whole.bytes().all(|byte| byte.is_ascii_digit())
+    || !fraction.bytes().all(|byte| byte.is_ascii_digit()) {
+        return Err(error());
+    }
+    let whole = whole.parse::<i64>().map_err(|_| error())?;
+    let fraction = format!("{fraction:0<width$}", width = places as usize);
+    let fraction = if fraction.is_empty() { 0 } else { fraction.parse::<i64>().map_err(|_| error())? };
+    match whole.checked_mul(10_i64.pow(places)).and_then(|whole| whole.checked_add(fraction)) {
+        Some(value) if value <= max => Ok(value),
+        _ => Err(TokenizedRecordError::OutOfBoundsError(
+            format!("the value '{token}' is too large")
+        )),
+    }
+}
+
+/// Parses a distance in meters with an optional "m" suffix. The result is in centimeters.
+fn parse_meters<'b>(token: &str, max_cm: i64) -> Result<i64, TokenizedRecordError<'b>> {
+    let token = token.strip_suffix(['m', 'M']).unwrap_or(token);
+    match token.strip_prefix('-') {
+        Some(token) => Ok(-parse_decimal(token, 2, max_cm)?),
+        None => parse_decimal(token, 2, max_cm),
+    }
+}
+
+impl FromTokenizedRData for LOC {
+    #[inline]
+    fn from_tokenized_rdata<'a, 'b>(rdata: &Vec<&'a str>) -> Result<Self, TokenizedRecordError<'b>> where Self: Sized, 'a: 'b {
+        let mut tokens = rdata.as_slice();
+        let latitude = dms_from_tokens(&mut tokens, "N", "S", 90)?;
+        let longitude = dms_from_tokens(&mut tokens, "E", "W", 180)?;
+
+        let (altitude, mut tokens) = match tokens.split_first() {
+            Some((altitude, tokens)) => (parse_meters(altitude, u32::MAX as i64 - ALTITUDE_BASE_CM)?, tokens),
+            None => return Err(TokenizedRecordError::TooFewRDataTokensError{expected: 7, received: rdata.len()}),
+        };
+        if altitude < -ALTITUDE_BASE_CM {
+            return Err(TokenizedRecordError::OutOfBoundsError(
+                format!("the altitude cannot be less than -{}m", ALTITUDE_BASE_CM / 100)
+            ));
+        }
+
+        let mut sizes = [Self::DEFAULT_SIZE, Self::DEFAULT_HORIZONTAL_PRECISION, Self::DEFAULT_VERTICAL_PRECISION];
+        for size in sizes.iter_mut() {
+            match tokens.split_first() {
+                Some((token, rest)) => {
+                    if token.starts_with('-') {
+                        return Err(TokenizedRecordError::OutOfBoundsError(
+                            format!("the size or precision '{token}' cannot be negative")
+                        ));
+                    }
+                    *size = LocSize::from_centimeters(parse_meters(token, LocSize::MAX_CENTIMETERS as i64)? as u64);
+                    tokens = rest;
+                },
+                None => break,
+            }
+        }
+        if !tokens.is_empty() {
+            return Err(TokenizedRecordError::TooManyRDataTokensError{expected: rdata.len() - tokens.len(), received: rdata.len()});
+        }
+
+        let [size, horizontal_precision, vertical_precision] = sizes;
+        Ok(Self {
+            size,
+            horizontal_precision,
+            vertical_precision,
+            latitude: (EQUATOR as i64 + latitude) as u32,
+            longitude: (PRIME_MERIDIAN as i64 + longitude) as u32,
+            altitude: (altitude + ALTITUDE_BASE_CM) as u32,
+        })
+    }
+}
+
+impl ToPresentation for LOC {
+    #[inline]
+    fn to_presentation_format(&self, out_buffer: &mut Vec<String>) {
+        out_buffer.extend(self.presentation_tokens());
+    }
+
+    fn write_presentation_tokens<W: std::fmt::Write + ?Sized>(&self, out: &mut PresentationWriter<'_, W>) -> std::fmt::Result {
+        for token in self.presentation_tokens() {
+            out.write_token(&token)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod circular_serde_sanity_test {
+    use crate::serde::wire::circular_test::gen_test_circular_serde_sanity_test;
+    use super::{LocSize, LOC};
+
+    gen_test_circular_serde_sanity_test!(
+        record_circular_serde_sanity_test,
+        LOC::from_degrees(42.365, -71.105, -24.0).unwrap()
+    );
+    gen_test_circular_serde_sanity_test!(
+        record_circular_serde_sanity_test_precision,
+        LOC::from_degrees(-33.8568, 151.2153, 5.0).unwrap().with_precision(LocSize::from_centimeters(3000), LocSize::from_centimeters(500), LocSize::from_centimeters(200))
+    );
+}
+
+#[cfg(test)]
+mod tokenizer_tests {
+    use crate::serde::presentation::test_from_tokenized_rdata::{gen_fail_record_test, gen_ok_record_test};
+    use super::{LocSize, LOC};
+
+    // Examples from RFC 1876 section 4
+    gen_ok_record_test!(
+        test_ok_cambridge,
+        LOC,
+        LOC { size: LocSize::from_centimeters(3000), horizontal_precision: LOC::DEFAULT_HORIZONTAL_PRECISION, vertical_precision: LOC::DEFAULT_VERTICAL_PRECISION, latitude: (1 << 31) + 152514000, longitude: (1 << 31) - 255978000, altitude: 100_000_00 - 2400 },
+        ["42", "21", "54", "N", "71", "06", "18", "W", "-24m", "30m"]
+    );
+    gen_ok_record_test!(
+        test_ok_cambridge_all_fields,
+        LOC,
+        LOC { size: LocSize::from_centimeters(3000), horizontal_precision: LOC::DEFAULT_HORIZONTAL_PRECISION, vertical_precision: LOC::DEFAULT_VERTICAL_PRECISION, latitude: (1 << 31) + 152514000, longitude: (1 << 31) - 255978000, altitude: 100_000_00 - 2400 },
+        ["42", "21", "54.000", "N", "71", "6", "18.000", "W", "-24.00m", "30.00m", "10000.00m", "10.00m"]
+    );
+    gen_ok_record_test!(
+        test_ok_degrees_only,
+        LOC,
+        LOC { size: LOC::DEFAULT_SIZE, horizontal_precision: LOC::DEFAULT_HORIZONTAL_PRECISION, vertical_precision: LOC::DEFAULT_VERTICAL_PRECISION, latitude: (1 << 31) - 15 * 3_600_000, longitude: (1 << 31) + 20 * 3_600_000, altitude: 100_000_00 + 123 },
+        ["15", "S", "20", "E", "1.23"]
+    );
+
+    gen_fail_record_test!(test_fail_no_hemisphere, LOC, ["42", "21", "54", "71", "06", "18", "W", "-24m"]);
+    gen_fail_record_test!(test_fail_latitude_too_large, LOC, ["91", "N", "71", "W", "0m"]);
+    gen_fail_record_test!(test_fail_longitude_too_large, LOC, ["42", "N", "180", "1", "W", "0m"]);
+    gen_fail_record_test!(test_fail_bad_minutes, LOC, ["42", "60", "N", "71", "W", "0m"]);
+    gen_fail_record_test!(test_fail_too_precise_seconds, LOC, ["42", "21", "54.0001", "N", "71", "W", "0m"]);
+    gen_fail_record_test!(test_fail_altitude_too_low, LOC, ["42", "N", "71", "W", "-100000.01m"]);
+    gen_fail_record_test!(test_fail_no_altitude, LOC, ["42", "N", "71", "W"]);
+    gen_fail_record_test!(test_fail_negative_size, LOC, ["42", "N", "71", "W", "0m", "-1m"]);
+    gen_fail_record_test!(test_fail_too_many_tokens, LOC, ["42", "N", "71", "W", "0m", "1m", "1m", "1m", "1m"]);
+    gen_fail_record_test!(test_fail_no_tokens, LOC, []);
+}
+
+#[cfg(test)]
+mod conversion_tests {
+    use super::{LocError, LocSize, LOC};
+
+    #[test]
+    fn degrees_round_trip() {
+        let loc = LOC::from_degrees(42.365, -71.105, -24.0).unwrap();
+        assert!((loc.latitude() - 42.365).abs() < 1e-9);
+        assert!((loc.longitude() + 71.105).abs() < 1e-9);
+        assert_eq!(loc.altitude(), -24.0);
+    }
+
+    #[test]
+    fn degrees_out_of_range() {
+        assert_eq!(LOC::from_degrees(90.5, 0.0, 0.0), Err(LocError::LatitudeOutOfRange(90.5)));
+        assert_eq!(LOC::from_degrees(0.0, -180.5, 0.0), Err(LocError::LongitudeOutOfRange(-180.5)));
+        assert_eq!(LOC::from_degrees(0.0, 0.0, -100_001.0), Err(LocError::AltitudeOutOfRange(-100_001.0)));
+    }
+
+    #[test]
+    fn size_encoding() {
+        assert_eq!(LocSize::from_centimeters(0).centimeters(), 0);
+        assert_eq!(LocSize::from_centimeters(100).centimeters(), 100);
+        assert_eq!(LocSize::from_centimeters(3000), LocSize { encoded: 0x33 });
+        assert_eq!(LocSize::from_centimeters(1_000_000), LocSize { encoded: 0x16 });
+        // Only one significant digit is kept.
+        assert_eq!(LocSize::from_centimeters(1234).centimeters(), 1000);
+        assert_eq!(LocSize::from_centimeters(u64::MAX).centimeters(), LocSize::MAX_CENTIMETERS);
+        assert_eq!(LocSize::from_meters(30.0).unwrap().meters(), 30.0);
+        assert!(LocSize::from_meters(-1.0).is_err());
+    }
+
+    #[test]
+    fn presentation() {
+        let loc = LOC::from_degrees(42.365, -71.105, -24.0).unwrap().with_precision(LocSize::from_centimeters(3000), LOC::DEFAULT_HORIZONTAL_PRECISION, LOC::DEFAULT_VERTICAL_PRECISION);
+        let mut tokens = Vec::new();
+        crate::serde::presentation::to_presentation::ToPresentation::to_presentation_format(&loc, &mut tokens);
+        assert_eq!(tokens, ["42", "21", "54.000", "N", "71", "6", "18.000", "W", "-24.00m", "30.00m", "10000.00m", "10.00m"]);
+    }
+}
@@ -1,6 +1,6 @@
 use dns_macros::{ToWire, FromWire, FromTokenizedRData, RData, ToPresentation};
 
-use crate::types::c_domain_name::CDomainName;
+use crate::types::c_domain_name::{CDomainName, CDomainNameError};
 
 /// (Original) https://datatracker.ietf.org/doc/html/rfc1035#section-3.3.7
 #[derive(Clone, PartialEq, Eq, Hash, Debug, ToWire, FromWire, ToPresentation, FromTokenizedRData, RData)]
@@ -15,6 +15,15 @@ impl MINFO {
         Self { responsible_mailbox, error_mailbox }
     }
 
+    /// Creates the record from email addresses. See `CDomainName::from_mailbox`.
+    #[inline]
+    pub fn from_mailboxes(responsible_mailbox: &str, error_mailbox: &str) -> Result<Self, CDomainNameError> {
+        Ok(Self {
+            responsible_mailbox: CDomainName::from_mailbox(responsible_mailbox)?,
+            error_mailbox: CDomainName::from_mailbox(error_mailbox)?,
+        })
+    }
+
     #[inline]
     pub fn responsible_mailbox(&self) -> &CDomainName {
         &self.responsible_mailbox
@@ -24,6 +33,19 @@ impl MINFO {
     pub fn error_mailbox(&self) -> &CDomainName {
         &self.error_mailbox
     }
+
+    /// The responsible mailbox as an email address. `None` if it is the root domain.
+    #[inline]
+    pub fn responsible_mailbox_address(&self) -> Option<String> {
+        self.responsible_mailbox.to_mailbox()
+    }
+
+    /// The error mailbox as an email address. `None` if it is the root domain, in which case
+    /// errors go to the responsible mailbox.
+    #[inline]
+    pub fn error_mailbox_address(&self) -> Option<String> {
+        self.error_mailbox.to_mailbox()
+    }
 }
 
 #[cfg(test)]
@@ -57,4 +79,13 @@ mod tokenizer_tests {
     gen_fail_record_test!(test_fail_bad_rmailbox, MINFO, [BAD_DOMAIN, GOOD_DOMAIN]);
     gen_fail_record_test!(test_fail_bad_emailbox, MINFO, [GOOD_DOMAIN, BAD_DOMAIN]);
     gen_fail_record_test!(test_fail_bad_mailboxes, MINFO, [BAD_DOMAIN, BAD_DOMAIN]);
+
+    gen_ok_record_test!(test_ok_escaped_mailboxes, MINFO, MINFO::from_mailboxes("list.owner@example.com", "list.errors@example.com").unwrap(), ["list\\.owner.example.com.", "list\\.errors.example.com."]);
+
+    #[test]
+    fn mailbox_addresses() {
+        let minfo = MINFO::from_mailboxes("list.owner@example.com", "errors@example.com").unwrap();
+        assert_eq!(minfo.responsible_mailbox_address().as_deref(), Some("list.owner@example.com"));
+        assert_eq!(minfo.error_mailbox_address().as_deref(), Some("errors@example.com"));
+    }
 }
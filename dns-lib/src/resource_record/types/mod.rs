@@ -27,15 +27,15 @@ pub mod eui64;
 // pub mod GPOS;
 pub mod hinfo;
 // pub mod HIP;
-// pub mod HTTPS;
-// pub mod IPSECKEY;
-// pub mod ISDN;
+pub mod https;
+pub mod ipseckey;
+pub mod isdn;
 // pub mod IXFR;
 // pub mod KEY;
 // pub mod KX;
 // pub mod L32;
 // pub mod L64;
-// pub mod LOC;
+pub mod loc;
 // pub mod LP;
 pub mod maila;
 pub mod mailb;
@@ -51,21 +51,21 @@ pub mod naptr;
 // pub mod NIMLOC;
 // pub mod NINFO;
 pub mod ns;
-// pub mod NSAP_PTR;
-// pub mod NSAP;
+pub mod nsap;
+pub mod nsap_ptr;
 pub mod nsec;
 // pub mod NSEC3;
 // pub mod NSEC3PARAM;
 pub mod null;
 // pub mod NXT;
 // pub mod OPENPGPKEY;
-// pub mod OPT;
+pub mod opt;
 pub mod ptr;
 // pub mod PX;
 // pub mod RKEY;
-// pub mod RP;
+pub mod rp;
 pub mod rrsig;
-// pub mod RT;
+pub mod rt;
 // pub mod SIG;
 // pub mod SINK;
 // pub mod SMIMEA;
@@ -73,7 +73,7 @@ pub mod soa;
 // pub mod SPF;
 pub mod srv;
 // pub mod SSHFP;
-// pub mod SVCB;
+pub mod svcb;
 // pub mod TA;
 // pub mod TALINK;
 // pub mod TKEY;
@@ -85,7 +85,7 @@ pub mod txt;
 // pub mod UNSPEC;
 // pub mod URI;
 pub mod wks;
-// pub mod X25;
+pub mod x25;
 // pub mod ZONEMD;
 
 // pub mod Unknown;
@@ -0,0 +1,96 @@
+use dns_macros::{ToWire, FromWire, RData};
+
+use crate::serde::presentation::{errors::TokenizedRecordError, from_tokenized_rdata::FromTokenizedRData, to_presentation::{PresentationWriter, ToPresentation}};
+
+/// (Original) https://datatracker.ietf.org/doc/html/rfc1706#section-5
+#[derive(Clone, PartialEq, Eq, Hash, Debug, ToWire, FromWire, RData)]
+pub struct NSAP {
+    address: Vec<u8>,
+}
+
+impl NSAP {
+    #[inline]
+    pub fn new(address: Vec<u8>) -> Self {
+        Self { address }
+    }
+
+    #[inline]
+    pub fn address(&self) -> &[u8] {
+        &self.address
+    }
+
+    /// The address as it is written in a zone file: `0x` followed by the hex digits.
+    fn to_hex(&self) -> String {
+        let mut hex = String::with_capacity(2 + (self.address.len() * 2));
+        hex.push_str("0x");
+        for byte in &self.address {
+            hex.push_str(&format!("{byte:02X}"));
+        }
+        hex
+    }
+}
+
+impl FromTokenizedRData for NSAP {
+    /// The address is `0x` followed by an even number of hex digits. Periods may be placed between
+    /// the digits to make the address easier to read and are ignored.
+    fn from_tokenized_rdata<'a, 'b>(rdata: &Vec<&'a str>) -> Result<Self, TokenizedRecordError<'b>> where Self: Sized, 'a: 'b {
+        let token = match rdata.as_slice() {
+            &[token] => token,
+            &[] => return Err(TokenizedRecordError::TooFewRDataTokensError { expected: 1, received: 0 }),
+            _ => return Err(TokenizedRecordError::TooManyRDataTokensError { expected: 1, received: rdata.len() }),
+        };
+        let Some(hex) = token.strip_prefix("0x").or_else(|| token.strip_prefix("0X")) else {
+            return Err(TokenizedRecordError::ValueError(format!("The NSAP address '{token}' must start with '0x'")));
+        };
+        let digits = hex.chars().filter(|character| *character != '.').collect::<Vec<_>>();
+        if digits.is_empty() || (digits.len() % 2 != 0) {
+            return Err(TokenizedRecordError::ValueError(format!("The NSAP address '{token}' must have a non-zero, even number of hex digits")));
+        }
+        let address = digits.chunks_exact(2)
+            .map(|pair| match (pair[0].to_digit(16), pair[1].to_digit(16)) {
+                (Some(high), Some(low)) => Ok(((high << 4) | low) as u8),
+                _ => Err(TokenizedRecordError::ValueError(format!("The NSAP address '{token}' contains characters that are not hex digits"))),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Self { address })
+    }
+}
+
+impl ToPresentation for NSAP {
+    fn to_presentation_format(&self, out_buffer: &mut Vec<String>) {
+        out_buffer.push(self.to_hex());
+    }
+
+    fn write_presentation_tokens<W: std::fmt::Write + ?Sized>(&self, out: &mut PresentationWriter<'_, W>) -> std::fmt::Result {
+        out.write_token(&self.to_hex())
+    }
+}
+
+#[cfg(test)]
+mod circular_serde_sanity_test {
+    use crate::serde::wire::circular_test::gen_test_circular_serde_sanity_test;
+    use super::NSAP;
+
+    gen_test_circular_serde_sanity_test!(
+        record_circular_serde_sanity_test,
+        NSAP { address: vec![0x47, 0x00, 0x05, 0x80, 0x00, 0x5a, 0x00, 0x00, 0x00, 0x00, 0x01, 0xe1, 0x33, 0xff, 0xff, 0xff, 0x00, 0x01, 0x61, 0x00] }
+    );
+}
+
+#[cfg(test)]
+mod tokenizer_tests {
+    use crate::serde::presentation::test_from_tokenized_rdata::{gen_ok_record_test, gen_fail_record_test};
+    use super::NSAP;
+
+    const GOOD_ADDRESS: &str = "0x47.0005.80.005a00.0000.0001.e133.ffffff000161.00";
+    const NO_PREFIX: &str = "47000580005a0000000001e133ffffff00016100";
+    const ODD_DIGITS: &str = "0x470";
+    const BAD_DIGITS: &str = "0x47zz";
+
+    gen_ok_record_test!(test_ok, NSAP, NSAP { address: vec![0x47, 0x00, 0x05, 0x80, 0x00, 0x5a, 0x00, 0x00, 0x00, 0x00, 0x01, 0xe1, 0x33, 0xff, 0xff, 0xff, 0x00, 0x01, 0x61, 0x00] }, [GOOD_ADDRESS]);
+    gen_fail_record_test!(test_fail_no_prefix, NSAP, [NO_PREFIX]);
+    gen_fail_record_test!(test_fail_odd_digits, NSAP, [ODD_DIGITS]);
+    gen_fail_record_test!(test_fail_bad_digits, NSAP, [BAD_DIGITS]);
+    gen_fail_record_test!(test_fail_two_tokens, NSAP, [GOOD_ADDRESS, GOOD_ADDRESS]);
+    gen_fail_record_test!(test_fail_no_tokens, NSAP, []);
+}
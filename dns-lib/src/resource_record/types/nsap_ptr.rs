@@ -0,0 +1,34 @@
+use dns_macros::{ToWire, FromWire, FromTokenizedRData, RData, ToPresentation};
+
+use crate::types::domain_name::DomainName;
+
+/// (Original)   https://datatracker.ietf.org/doc/html/rfc1348
+/// (Deprecated) https://datatracker.ietf.org/doc/html/rfc1706#section-6
+#[allow(non_camel_case_types)]
+#[derive(Clone, PartialEq, Eq, Hash, Debug, ToWire, FromWire, ToPresentation, FromTokenizedRData, RData)]
+pub struct NSAP_PTR {
+    owner: DomainName,
+}
+
+impl NSAP_PTR {
+    #[inline]
+    pub fn new(owner: DomainName) -> Self {
+        Self { owner }
+    }
+
+    #[inline]
+    pub fn owner(&self) -> &DomainName {
+        &self.owner
+    }
+}
+
+#[cfg(test)]
+mod circular_serde_sanity_test {
+    use crate::{serde::wire::circular_test::gen_test_circular_serde_sanity_test, types::domain_name::DomainName};
+    use super::NSAP_PTR;
+
+    gen_test_circular_serde_sanity_test!(
+        record_circular_serde_sanity_test,
+        NSAP_PTR { owner: DomainName::from_utf8("host.school.de.").unwrap() }
+    );
+}
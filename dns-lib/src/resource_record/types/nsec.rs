@@ -13,6 +13,24 @@ pub struct NSEC {
     type_bit_map: RTypeBitmap,
 }
 
+impl NSEC {
+    #[inline]
+    pub fn new(next_domain_name: DomainName, type_bit_map: RTypeBitmap) -> Self {
+        Self { next_domain_name, type_bit_map }
+    }
+
+    #[inline]
+    pub fn next_domain_name(&self) -> &DomainName {
+        &self.next_domain_name
+    }
+
+    /// The types of the RRsets that exist at the owner name.
+    #[inline]
+    pub fn type_bit_map(&self) -> &RTypeBitmap {
+        &self.type_bit_map
+    }
+}
+
 impl FromTokenizedRData for NSEC {
     fn from_tokenized_rdata<'a, 'b>(rdata: &Vec<&'a str>) -> Result<Self, crate::serde::presentation::errors::TokenizedRecordError<'b>> where Self: Sized, 'a: 'b {
         match rdata.as_slice() {
@@ -0,0 +1,117 @@
+use dns_macros::{ToWire, FromWire, RData};
+
+use crate::{resource_record::edns_option_code::EDNSOptionCode, serde::wire::{from_wire::FromWire, read_wire::ReadWireError, to_wire::ToWire}};
+
+/// A single `{attribute, value}` pair carried in the rdata of an OPT pseudo-record.
+///
+/// https://datatracker.ietf.org/doc/html/rfc6891#section-6.1.2
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub struct EDNSOption {
+    code: EDNSOptionCode,
+    data: Vec<u8>,
+}
+
+impl EDNSOption {
+    #[inline]
+    pub fn new(code: EDNSOptionCode, data: Vec<u8>) -> Self {
+        Self { code, data }
+    }
+
+    /// https://datatracker.ietf.org/doc/html/rfc7830#section-3
+    #[inline]
+    pub fn new_padding(length: u16) -> Self {
+        Self { code: EDNSOptionCode::Padding, data: vec![0; length as usize] }
+    }
+
+    #[inline]
+    pub fn code(&self) -> EDNSOptionCode {
+        self.code
+    }
+
+    #[inline]
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    #[inline]
+    pub fn data_mut(&mut self) -> &mut Vec<u8> {
+        &mut self.data
+    }
+}
+
+impl ToWire for EDNSOption {
+    #[inline]
+    fn to_wire_format<'a, 'b>(&self, wire: &'b mut crate::serde::wire::write_wire::WriteWire<'a>, compression: &mut Option<crate::types::c_domain_name::CompressionMap>) -> Result<(), crate::serde::wire::write_wire::WriteWireError> where 'a: 'b {
+        self.code.to_wire_format(wire, compression)?;
+        (self.data.len() as u16).to_wire_format(wire, compression)?;
+        wire.write_bytes(&self.data)
+    }
+
+    #[inline]
+    fn serial_length(&self) -> u16 {
+        self.code.serial_length() + 0_u16.serial_length() + (self.data.len() as u16)
+    }
+}
+
+impl FromWire for EDNSOption {
+    #[inline]
+    fn from_wire_format<'a, 'b>(wire: &'b mut crate::serde::wire::read_wire::ReadWire<'a>) -> Result<Self, ReadWireError> where Self: Sized, 'a: 'b {
+        let code = EDNSOptionCode::from_wire_format(wire)?;
+        let length = u16::from_wire_format(wire)?;
+        let data = wire.take_or_err(length as usize, || format!("EDNS option {code} claims to have {length} bytes of data but the rdata is too short"))?
+            .to_vec();
+        Ok(Self { code, data })
+    }
+}
+
+/// The OPT pseudo-record. Only the rdata is represented here. The fixed part of the record
+/// (requestor's payload size, extended rcode, version, and flags) is carried in the class and ttl
+/// fields of the resource record.
+///
+/// (Original) https://datatracker.ietf.org/doc/html/rfc6891#section-6.1
+#[derive(Clone, PartialEq, Eq, Hash, Debug, ToWire, FromWire, RData)]
+pub struct OPT {
+    options: Vec<EDNSOption>,
+}
+
+impl OPT {
+    #[inline]
+    pub fn new(options: Vec<EDNSOption>) -> Self {
+        Self { options }
+    }
+
+    #[inline]
+    pub fn options(&self) -> &[EDNSOption] {
+        &self.options
+    }
+
+    #[inline]
+    pub fn options_mut(&mut self) -> &mut Vec<EDNSOption> {
+        &mut self.options
+    }
+
+    #[inline]
+    pub fn option(&self, code: EDNSOptionCode) -> Option<&EDNSOption> {
+        self.options.iter().find(|option| option.code == code)
+    }
+}
+
+#[cfg(test)]
+mod circular_serde_sanity_test {
+    use crate::{resource_record::edns_option_code::EDNSOptionCode, serde::wire::circular_test::gen_test_circular_serde_sanity_test};
+    use super::{EDNSOption, OPT};
+
+    gen_test_circular_serde_sanity_test!(
+        record_no_options_circular_serde_sanity_test,
+        OPT { options: vec![] }
+    );
+    gen_test_circular_serde_sanity_test!(
+        record_two_options_circular_serde_sanity_test,
+        OPT {
+            options: vec![
+                EDNSOption::new(EDNSOptionCode::NSID, vec![]),
+                EDNSOption::new_padding(17),
+            ]
+        }
+    );
+}
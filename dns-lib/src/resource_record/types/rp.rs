@@ -0,0 +1,123 @@
+use dns_macros::{ToWire, FromWire, FromTokenizedRData, RData, ToPresentation};
+
+use crate::types::domain_name::DomainName;
+
+/// (Original) https://datatracker.ietf.org/doc/html/rfc1183#section-2.2
+#[derive(Clone, PartialEq, Eq, Hash, Debug, ToWire, FromWire, ToPresentation, FromTokenizedRData, RData)]
+pub struct RP {
+    /// The mailbox of the responsible person. The root domain indicates that there is no mailbox.
+    mailbox: DomainName,
+    /// A domain name with TXT records that describe the responsible person. The root domain
+    /// indicates that there are no TXT records.
+    txt_domain: DomainName,
+}
+
+impl RP {
+    #[inline]
+    pub fn new(mailbox: DomainName, txt_domain: DomainName) -> Self {
+        Self { mailbox, txt_domain }
+    }
+
+    /// Creates the record from an email address and an optional domain with TXT records.
+    #[inline]
+    pub fn from_mailbox(mailbox: Option<&str>, txt_domain: Option<DomainName>) -> Result<Self, crate::types::domain_name::DomainNameError> {
+        let mailbox = match mailbox {
+            Some(mailbox) => DomainName::from_mailbox(mailbox)?,
+            None => DomainName::new_root(),
+        };
+        Ok(Self { mailbox, txt_domain: txt_domain.unwrap_or_else(DomainName::new_root) })
+    }
+
+    #[inline]
+    pub fn mailbox(&self) -> &DomainName {
+        &self.mailbox
+    }
+
+    /// The mailbox as an email address. `None` if no mailbox is given.
+    #[inline]
+    pub fn mailbox_address(&self) -> Option<String> {
+        self.mailbox.to_mailbox()
+    }
+
+    /// The domain with TXT records. `None` if no domain is given.
+    #[inline]
+    pub fn txt_domain(&self) -> Option<&DomainName> {
+        match self.txt_domain.is_root() {
+            true => None,
+            false => Some(&self.txt_domain),
+        }
+    }
+}
+
+#[cfg(test)]
+mod circular_serde_sanity_test {
+    use crate::{serde::wire::circular_test::gen_test_circular_serde_sanity_test, types::domain_name::DomainName};
+    use super::RP;
+
+    gen_test_circular_serde_sanity_test!(
+        record_circular_serde_sanity_test,
+        RP {
+            mailbox: DomainName::from_utf8("louie.trantor.umd.edu.").unwrap(),
+            txt_domain: DomainName::from_utf8("lam1.people.umd.edu.").unwrap(),
+        }
+    );
+    gen_test_circular_serde_sanity_test!(
+        record_circular_serde_sanity_test_root,
+        RP {
+            mailbox: DomainName::new_root(),
+            txt_domain: DomainName::new_root(),
+        }
+    );
+}
+
+#[cfg(test)]
+mod tokenizer_tests {
+    use crate::{serde::presentation::test_from_tokenized_rdata::{gen_ok_record_test, gen_fail_record_test}, types::domain_name::DomainName};
+    use super::RP;
+
+    // Examples from RFC 1183 section 2.2
+    const GOOD_MAILBOX: &str = "louie.trantor.umd.edu.";
+    const GOOD_TXT_DOMAIN: &str = "lam1.people.umd.edu.";
+    const ROOT: &str = ".";
+    const BAD_DOMAIN: &str = "..www.example.com.";
+
+    gen_ok_record_test!(test_ok, RP, RP { mailbox: DomainName::from_utf8(GOOD_MAILBOX).unwrap(), txt_domain: DomainName::from_utf8(GOOD_TXT_DOMAIN).unwrap() }, [GOOD_MAILBOX, GOOD_TXT_DOMAIN]);
+    gen_ok_record_test!(test_ok_no_txt_domain, RP, RP { mailbox: DomainName::from_utf8(GOOD_MAILBOX).unwrap(), txt_domain: DomainName::new_root() }, [GOOD_MAILBOX, ROOT]);
+    gen_ok_record_test!(test_ok_escaped_mailbox, RP, RP { mailbox: DomainName::from_mailbox("john.doe@example.com").unwrap(), txt_domain: DomainName::new_root() }, ["john\\.doe.example.com.", ROOT]);
+
+    gen_fail_record_test!(test_fail_three_tokens, RP, [GOOD_MAILBOX, GOOD_TXT_DOMAIN, GOOD_TXT_DOMAIN]);
+    gen_fail_record_test!(test_fail_one_token, RP, [GOOD_MAILBOX]);
+    gen_fail_record_test!(test_fail_no_tokens, RP, []);
+    gen_fail_record_test!(test_fail_bad_mailbox, RP, [BAD_DOMAIN, GOOD_TXT_DOMAIN]);
+    gen_fail_record_test!(test_fail_bad_txt_domain, RP, [GOOD_MAILBOX, BAD_DOMAIN]);
+}
+
+#[cfg(test)]
+mod presentation_tests {
+    use crate::{resource_record::{rclass::RClass, resource_record::{RecordData, ResourceRecord}, time::Time}, serde::presentation::zone_file_reader::{ZoneFileReader, ZoneToken}, types::{c_domain_name::CDomainName, domain_name::DomainName}};
+    use super::RP;
+
+    #[test]
+    fn mailbox_survives_zone_round_trip() {
+        let rp = RP::from_mailbox(Some("john.doe@example.com"), Some(DomainName::from_utf8("john.people.example.com.").unwrap())).unwrap();
+        assert_eq!(rp.mailbox_address().as_deref(), Some("john.doe@example.com"));
+        let record = ResourceRecord::new(CDomainName::from_utf8("example.com.").unwrap(), RClass::Internet, Time::new(300), RecordData::RP(rp));
+
+        let zone = format!("{record}\n");
+        assert!(zone.contains("john\\.doe.example.com."));
+        let read_records = ZoneFileReader::new(&zone)
+            .map(|token| match token.unwrap() {
+                ZoneToken::ResourceRecord(record) => record,
+                ZoneToken::Include { .. } => panic!("unexpected $INCLUDE"),
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(read_records, vec![record]);
+    }
+
+    #[test]
+    fn no_mailbox() {
+        let rp = RP::from_mailbox(None, None).unwrap();
+        assert_eq!(rp.mailbox_address(), None);
+        assert_eq!(rp.txt_domain(), None);
+    }
+}
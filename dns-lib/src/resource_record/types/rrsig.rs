@@ -1,6 +1,6 @@
 use dns_macros::{FromTokenizedRData, FromWire, RData, ToPresentation, ToWire};
 
-use crate::{resource_record::{dnssec_alg::DnsSecAlgorithm, rtype::RType, time::Time}, types::{base64::Base64, domain_name::DomainName}};
+use crate::{resource_record::{dnssec_alg::DnsSecAlgorithm, rtype::RType, time::Time}, serde::presentation::to_presentation::UtcTimestamp, types::{base64::Base64, base_conversions::BaseConversions, domain_name::DomainName}};
 
 
 /// (Original) https://datatracker.ietf.org/doc/html/rfc4034#section-3
@@ -8,6 +8,7 @@ use crate::{resource_record::{dnssec_alg::DnsSecAlgorithm, rtype::RType, time::T
 /// (Update) https://datatracker.ietf.org/doc/html/rfc6840
 /// (Update) https://datatracker.ietf.org/doc/html/rfc6944
 #[derive(Clone, PartialEq, Eq, Hash, Debug, ToWire, FromWire, ToPresentation, FromTokenizedRData, RData)]
+#[presentation(comment = comment)]
 pub struct RRSIG {
     type_covered: RType,
     algorithm: DnsSecAlgorithm,
@@ -20,6 +21,76 @@ pub struct RRSIG {
     signature: Base64,
 }
 
+impl RRSIG {
+    #[inline]
+    pub fn new(type_covered: RType, algorithm: DnsSecAlgorithm, labels: u8, original_ttl: Time, signature_expiration: u32, signature_inception: u32, key_tag: u16, signers_name: DomainName, signature: Base64) -> Self {
+        Self { type_covered, algorithm, labels, original_ttl, signature_expiration, signature_inception, key_tag, signers_name, signature }
+    }
+
+    #[inline]
+    pub fn type_covered(&self) -> RType { self.type_covered }
+
+    #[inline]
+    pub fn algorithm(&self) -> DnsSecAlgorithm { self.algorithm }
+
+    #[inline]
+    pub fn labels(&self) -> u8 { self.labels }
+
+    #[inline]
+    pub fn original_ttl(&self) -> &Time { &self.original_ttl }
+
+    /// Seconds since the epoch, in serial number arithmetic.
+    #[inline]
+    pub fn signature_expiration(&self) -> u32 { self.signature_expiration }
+
+    /// Seconds since the epoch, in serial number arithmetic.
+    #[inline]
+    pub fn signature_inception(&self) -> u32 { self.signature_inception }
+
+    #[inline]
+    pub fn key_tag(&self) -> u16 { self.key_tag }
+
+    #[inline]
+    pub fn signers_name(&self) -> &DomainName { &self.signers_name }
+
+    #[inline]
+    pub fn signature(&self) -> &Base64 { &self.signature }
+
+    /// A copy of the record with the signer's name in lowercase and an empty signature. Written
+    /// out, this is the RRSIG RDATA that the signature itself covers.
+    ///
+    /// https://datatracker.ietf.org/doc/html/rfc4034#section-3.1.8.1
+    #[inline]
+    pub fn without_signature(&self) -> Self {
+        Self {
+            signers_name: self.signers_name.as_lowercase(),
+            signature: Base64::from_bytes(&[]),
+            ..self.clone()
+        }
+    }
+
+    /// Whether the signature is valid at this time (seconds since the epoch). The comparison uses
+    /// serial number arithmetic so that it keeps working after the 32 bit counter wraps.
+    ///
+    /// https://datatracker.ietf.org/doc/html/rfc4034#section-3.1.5
+    #[inline]
+    pub fn is_current(&self, now: u32) -> bool {
+        (now.wrapping_sub(self.signature_inception) as i32) >= 0
+        && (self.signature_expiration.wrapping_sub(now) as i32) >= 0
+    }
+
+    /// The validity period as dates, and the algorithm. The timestamps are read as the first time
+    /// since the epoch that they could refer to.
+    fn comment(&self) -> Option<String> {
+        Some(format!(
+            "alg = {}; inception = {}; expiration = {}",
+            self.algorithm,
+            UtcTimestamp(u64::from(self.signature_inception)),
+            UtcTimestamp(u64::from(self.signature_expiration)),
+        ))
+    }
+}
+
 
 #[cfg(test)]
 mod circular_serde_sanity_test {
@@ -42,3 +113,34 @@ mod circular_serde_sanity_test {
         }
     );
 }
+
+#[cfg(test)]
+mod presentation_comment_tests {
+    use crate::{resource_record::{dnssec_alg::DnsSecAlgorithm, rtype::RType, time::Time}, serde::presentation::to_presentation::{ToPresentation, UtcTimestamp}, types::{base64::Base64, domain_name::DomainName}};
+
+    use super::RRSIG;
+
+    #[test]
+    fn timestamps_are_explained_as_dates() {
+        // https://datatracker.ietf.org/doc/html/rfc4034#section-3.3
+        let rrsig = RRSIG::new(
+            RType::A,
+            DnsSecAlgorithm::RsaSha1,
+            3,
+            Time::from_secs(86400),
+            1048354263,
+            1045762263,
+            2642,
+            DomainName::from_utf8("example.com.").unwrap(),
+            Base64::from_utf8("oJB1W6WNGv+ldvQ3WDG0MQkg5IEhjRip8WTrPYGv07h108dUKGMeDPKijVCHX3DDKdfb+v6oB9wfuh3DTJXUAfI/M0zmO/zz8bW0Rznl8O3tGNazPwQKkRN20XPXV6nwwfoXmJQbsLNrLfkGJ5D6fwFm8nN+6pBzeDQfsS3Ap3o=").unwrap(),
+        );
+        assert_eq!(rrsig.presentation_comment().unwrap(), "alg = RSASHA1; inception = 2003-02-20 17:31:03 UTC; expiration = 2003-03-22 17:31:03 UTC");
+    }
+
+    #[test]
+    fn utc_timestamps() {
+        assert_eq!(UtcTimestamp(0).to_string(), "1970-01-01 00:00:00 UTC");
+        assert_eq!(UtcTimestamp(951782400).to_string(), "2000-02-29 00:00:00 UTC");
+        assert_eq!(UtcTimestamp(u64::from(u32::MAX)).to_string(), "2106-02-07 06:28:15 UTC");
+    }
+}
@@ -0,0 +1,62 @@
+use dns_macros::{ToWire, FromWire, FromTokenizedRData, RData, ToPresentation};
+
+use crate::types::domain_name::DomainName;
+
+/// (Original) https://datatracker.ietf.org/doc/html/rfc1183#section-3.3
+#[derive(Clone, PartialEq, Eq, Hash, Debug, ToWire, FromWire, ToPresentation, FromTokenizedRData, RData)]
+pub struct RT {
+    preference: u16,
+    intermediate_host: DomainName,
+}
+
+impl RT {
+    #[inline]
+    pub fn new(preference: u16, intermediate_host: DomainName) -> Self {
+        Self { preference, intermediate_host }
+    }
+
+    #[inline]
+    pub fn preference(&self) -> u16 {
+        self.preference
+    }
+
+    #[inline]
+    pub fn intermediate_host(&self) -> &DomainName {
+        &self.intermediate_host
+    }
+
+    #[inline]
+    pub fn into_intermediate_host(self) -> DomainName {
+        self.intermediate_host
+    }
+}
+
+#[cfg(test)]
+mod circular_serde_sanity_test {
+    use crate::{serde::wire::circular_test::gen_test_circular_serde_sanity_test, types::domain_name::DomainName};
+    use super::RT;
+
+    gen_test_circular_serde_sanity_test!(
+        record_circular_serde_sanity_test,
+        RT { preference: 2, intermediate_host: DomainName::from_utf8("Relay.Prime.COM.").unwrap() }
+    );
+}
+
+#[cfg(test)]
+mod tokenizer_tests {
+    use crate::{serde::presentation::test_from_tokenized_rdata::{gen_ok_record_test, gen_fail_record_test}, types::domain_name::DomainName};
+    use super::RT;
+
+    const GOOD_PREFERENCE: &str = "2";
+    const BAD_PREFERENCE: &str = "-1";
+
+    const GOOD_DOMAIN: &str = "Relay.Prime.COM.";
+    const BAD_DOMAIN: &str = "..Relay.Prime.COM.";
+
+    gen_ok_record_test!(test_ok, RT, RT { preference: 2, intermediate_host: DomainName::from_utf8(GOOD_DOMAIN).unwrap() }, [GOOD_PREFERENCE, GOOD_DOMAIN]);
+    gen_fail_record_test!(test_fail_bad_preference, RT, [BAD_PREFERENCE, GOOD_DOMAIN]);
+    gen_fail_record_test!(test_fail_bad_domain, RT, [GOOD_PREFERENCE, BAD_DOMAIN]);
+    gen_fail_record_test!(test_fail_three_tokens, RT, [GOOD_PREFERENCE, GOOD_DOMAIN, GOOD_DOMAIN]);
+    gen_fail_record_test!(test_fail_one_token, RT, [GOOD_PREFERENCE]);
+    gen_fail_record_test!(test_fail_no_tokens, RT, []);
+}
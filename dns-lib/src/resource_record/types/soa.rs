@@ -30,6 +30,12 @@ impl SOA {
         &self.rname
     }
 
+    /// The responsible mailbox as an email address. See `CDomainName::to_mailbox`.
+    #[inline]
+    pub fn responsible_mailbox_address(&self) -> Option<String> {
+        self.rname.to_mailbox()
+    }
+
     #[inline]
     pub fn serial(&self) -> &u32 {
         &self.serial
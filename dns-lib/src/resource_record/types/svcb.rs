@@ -0,0 +1,266 @@
+use dns_macros::{RData, ToPresentation, ToWire};
+
+use crate::{resource_record::{svc_param::{SvcParam, SvcParamError}, svc_param_key::SvcParamKey}, serde::{presentation::{errors::TokenizedRecordError, from_presentation::FromPresentation, from_tokenized_rdata::FromTokenizedRData}, wire::{from_wire::FromWire, read_wire::{ReadWire, ReadWireError}}}, types::domain_name::DomainName};
+
+/// (Original) https://datatracker.ietf.org/doc/html/rfc9460#section-2
+#[derive(Clone, PartialEq, Eq, Hash, Debug, ToWire, ToPresentation, RData)]
+pub struct SVCB {
+    priority: u16,
+    target_name: DomainName,
+    params: Vec<SvcParam>,
+}
+
+impl SVCB {
+    /// The parameters must be in increasing order of their keys, with each key given at most
+    /// once, and must include every mandatory key.
+    #[inline]
+    pub fn new(priority: u16, target_name: DomainName, params: Vec<SvcParam>) -> Result<Self, SvcParamError> {
+        SvcParam::validate(&params)?;
+        Ok(Self { priority, target_name, params })
+    }
+
+    #[inline]
+    pub fn priority(&self) -> u16 { self.priority }
+
+    /// In AliasMode (a priority of 0), the record is an alias for the target name and has no
+    /// parameters.
+    #[inline]
+    pub fn is_alias_mode(&self) -> bool { self.priority == 0 }
+
+    #[inline]
+    pub fn target_name(&self) -> &DomainName { &self.target_name }
+
+    #[inline]
+    pub fn params(&self) -> &[SvcParam] { &self.params }
+
+    #[inline]
+    pub fn param(&self, key: SvcParamKey) -> Option<&SvcParam> {
+        self.params.iter().find(|param| param.key() == key)
+    }
+}
+
+impl FromWire for SVCB {
+    #[inline]
+    fn from_wire_format<'a, 'b>(wire: &'b mut ReadWire<'a>) -> Result<Self, ReadWireError> where Self: Sized, 'a: 'b {
+        let priority = u16::from_wire_format(wire)?;
+        let target_name = DomainName::from_wire_format(wire)?;
+        let params = Vec::from_wire_format(wire)?;
+        SvcParam::validate(&params)?;
+        Ok(Self { priority, target_name, params })
+    }
+}
+
+impl FromTokenizedRData for SVCB {
+    fn from_tokenized_rdata<'a, 'b>(rdata: &Vec<&'a str>) -> Result<Self, TokenizedRecordError<'b>> where Self: Sized, 'a: 'b {
+        match rdata.as_slice() {
+            &[] | &[_] => Err(TokenizedRecordError::TooFewRDataTokensError{expected: 2, received: rdata.len()}),
+            &[priority, target_name, ..] => {
+                let (priority, _) = u16::from_token_format(&[priority])?;
+                let (target_name, _) = DomainName::from_token_format(&[target_name])?;
+                let params = SvcParam::from_tokens(&rdata[2..])?;
+                Ok(Self { priority, target_name, params })
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod circular_serde_sanity_test {
+    use std::net::Ipv4Addr;
+
+    use crate::{resource_record::{svc_param::SvcParam, svc_param_key::SvcParamKey}, serde::wire::circular_test::gen_test_circular_serde_sanity_test, types::{character_string::CharacterString, domain_name::DomainName}};
+    use super::SVCB;
+
+    gen_test_circular_serde_sanity_test!(
+        alias_mode_record_circular_serde_sanity_test,
+        SVCB { priority: 0, target_name: DomainName::from_utf8("foo.example.com.").unwrap(), params: vec![] }
+    );
+    gen_test_circular_serde_sanity_test!(
+        service_mode_record_circular_serde_sanity_test,
+        SVCB {
+            priority: 16,
+            target_name: DomainName::from_utf8("foo.example.org.").unwrap(),
+            params: vec![
+                SvcParam::Mandatory(vec![SvcParamKey::Alpn, SvcParamKey::Ipv4Hint]),
+                SvcParam::Alpn(vec![CharacterString::from_utf8("h2").unwrap(), CharacterString::from_utf8("h3-19").unwrap()]),
+                SvcParam::Ipv4Hint(vec![Ipv4Addr::new(192, 0, 2, 1)]),
+            ],
+        }
+    );
+}
+
+/// The test vectors from https://datatracker.ietf.org/doc/html/rfc9460#appendix-D
+#[cfg(test)]
+mod rfc_9460_tests {
+    use std::net::{Ipv4Addr, Ipv6Addr};
+
+    use crate::{resource_record::{svc_param::{SvcParam, SvcParamError}, svc_param_key::SvcParamKey}, serde::{presentation::{from_tokenized_rdata::FromTokenizedRData, test_from_tokenized_rdata::{gen_fail_record_test, gen_ok_record_test}, to_presentation::ToPresentation}, wire::{from_wire::FromWire, read_wire::ReadWire, to_wire::ToWire, write_wire::WriteWire}}, types::{character_string::CharacterString, domain_name::DomainName}};
+    use super::SVCB;
+
+    const EXAMPLE_COM: [u8; 13] = [0x07, 0x65, 0x78, 0x61, 0x6d, 0x70, 0x6c, 0x65, 0x03, 0x63, 0x6f, 0x6d, 0x00];
+    const FOO_EXAMPLE_COM: [u8; 17] = [0x03, 0x66, 0x6f, 0x6f, 0x07, 0x65, 0x78, 0x61, 0x6d, 0x70, 0x6c, 0x65, 0x03, 0x63, 0x6f, 0x6d, 0x00];
+    const FOO_EXAMPLE_ORG: [u8; 17] = [0x03, 0x66, 0x6f, 0x6f, 0x07, 0x65, 0x78, 0x61, 0x6d, 0x70, 0x6c, 0x65, 0x03, 0x6f, 0x72, 0x67, 0x00];
+
+    /// Checks that the presentation format and the wire format both produce the record, and that
+    /// the record is written back out as the same wire format.
+    fn check(presentation: &[&str], wire: &[u8]) -> SVCB {
+        let record = SVCB::from_tokenized_rdata(&presentation.to_vec()).unwrap();
+        assert_eq!(SVCB::from_wire_format(&mut ReadWire::from_bytes(wire)).unwrap(), record);
+
+        let mut buffer = [0_u8; 128];
+        let mut write_wire = WriteWire::from_bytes(&mut buffer);
+        record.to_wire_format(&mut write_wire, &mut None).unwrap();
+        assert_eq!(write_wire.current(), wire);
+        assert_eq!(record.serial_length() as usize, wire.len());
+
+        // And reading the record's own presentation format gives back the same record.
+        let mut tokens = Vec::new();
+        record.to_presentation_format(&mut tokens);
+        let tokens = tokens.iter().map(String::as_str).collect::<Vec<_>>();
+        assert_eq!(SVCB::from_tokenized_rdata(&tokens).unwrap(), record);
+        record
+    }
+
+    #[test]
+    fn alias_form() {
+        let wire = [&[0x00, 0x00][..], &FOO_EXAMPLE_COM].concat();
+        let record = check(&["0", "foo.example.com."], &wire);
+        assert!(record.is_alias_mode());
+    }
+
+    #[test]
+    fn use_the_ownername() {
+        let record = check(&["1", "."], &[0x00, 0x01, 0x00]);
+        assert!(!record.is_alias_mode());
+        assert!(record.params().is_empty());
+    }
+
+    #[test]
+    fn map_port() {
+        let wire = [&[0x00, 0x10][..], &FOO_EXAMPLE_COM, &[0x00, 0x03, 0x00, 0x02, 0x00, 0x35]].concat();
+        let record = check(&["16", "foo.example.com.", "port=53"], &wire);
+        assert_eq!(record.param(SvcParamKey::Port), Some(&SvcParam::Port(53)));
+    }
+
+    #[test]
+    fn unregistered_key_value() {
+        let wire = [&[0x00, 0x01][..], &FOO_EXAMPLE_COM, &[0x02, 0x9b, 0x00, 0x05, 0x68, 0x65, 0x6c, 0x6c, 0x6f]].concat();
+        check(&["1", "foo.example.com.", "key667=hello"], &wire);
+    }
+
+    #[test]
+    fn unregistered_key_escaped_value() {
+        let wire = [&[0x00, 0x01][..], &FOO_EXAMPLE_COM, &[0x02, 0x9b, 0x00, 0x09, 0x68, 0x65, 0x6c, 0x6c, 0x6f, 0xd2, 0x71, 0x6f, 0x6f]].concat();
+        // The quoted value is its own token.
+        check(&["1", "foo.example.com.", "key667=", r"hello\210qoo"], &wire);
+    }
+
+    #[test]
+    fn two_ipv6_hints() {
+        let wire = [
+            &[0x00, 0x01][..], &FOO_EXAMPLE_COM,
+            &[0x00, 0x06, 0x00, 0x20],
+            &[0x20, 0x01, 0x0d, 0xb8, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x01],
+            &[0x20, 0x01, 0x0d, 0xb8, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x53, 0x00, 0x01],
+        ].concat();
+        check(&["1", "foo.example.com.", "ipv6hint=", "2001:db8::1,2001:db8::53:1"], &wire);
+    }
+
+    #[test]
+    fn ipv6_hint_with_embedded_ipv4() {
+        let wire = [
+            &[0x00, 0x01][..], &EXAMPLE_COM,
+            &[0x00, 0x06, 0x00, 0x10],
+            &[0x20, 0x01, 0x0d, 0xb8, 0x01, 0x22, 0x03, 0x44, 0x00, 0x00, 0x00, 0x00, 0xc0, 0x00, 0x02, 0x21],
+        ].concat();
+        let record = check(&["1", "example.com.", "ipv6hint=", "2001:db8:122:344::192.0.2.33"], &wire);
+        assert_eq!(record.param(SvcParamKey::Ipv6Hint), Some(&SvcParam::Ipv6Hint(vec![Ipv6Addr::new(0x2001, 0xdb8, 0x122, 0x344, 0, 0, 0xc000, 0x221)])));
+    }
+
+    #[test]
+    fn unsorted_params_and_mandatory_key() {
+        let wire = [
+            &[0x00, 0x10][..], &FOO_EXAMPLE_ORG,
+            &[0x00, 0x00, 0x00, 0x04, 0x00, 0x01, 0x00, 0x04],
+            &[0x00, 0x01, 0x00, 0x09, 0x02, 0x68, 0x32, 0x05, 0x68, 0x33, 0x2d, 0x31, 0x39],
+            &[0x00, 0x04, 0x00, 0x04, 0xc0, 0x00, 0x02, 0x01],
+        ].concat();
+        let record = check(&["16", "foo.example.org.", "alpn=h2,h3-19", "mandatory=ipv4hint,alpn", "ipv4hint=192.0.2.1"], &wire);
+        assert_eq!(record.target_name(), &DomainName::from_utf8("foo.example.org.").unwrap());
+        assert_eq!(record.param(SvcParamKey::Ipv4Hint), Some(&SvcParam::Ipv4Hint(vec![Ipv4Addr::new(192, 0, 2, 1)])));
+    }
+
+    #[test]
+    fn alpn_with_escaped_values() {
+        let wire = [
+            &[0x00, 0x10][..], &FOO_EXAMPLE_ORG,
+            &[0x00, 0x01, 0x00, 0x0c, 0x08, 0x66, 0x5c, 0x6f, 0x6f, 0x2c, 0x62, 0x61, 0x72, 0x02, 0x68, 0x32],
+        ].concat();
+        check(&["16", "foo.example.org.", "alpn=", r"f\\\\oo\\,bar,h2"], &wire);
+        let record = check(&["16", "foo.example.org.", r"alpn=f\\\092oo\092,bar,h2"], &wire);
+        assert_eq!(record.param(SvcParamKey::Alpn), Some(&SvcParam::Alpn(vec![CharacterString::from_utf8(r"f\oo,bar").unwrap(), CharacterString::from_utf8("h2").unwrap()])));
+    }
+
+    // https://datatracker.ietf.org/doc/html/rfc9460#appendix-D.3
+    gen_fail_record_test!(test_fail_multiple_instances_of_the_same_key, SVCB, ["1", "foo.example.com.", "key123=abc", "key123=def"]);
+    gen_fail_record_test!(test_fail_missing_value_for_port, SVCB, ["1", "foo.example.com.", "port"]);
+    gen_fail_record_test!(test_fail_missing_value_for_alpn, SVCB, ["1", "foo.example.com.", "alpn"]);
+    gen_fail_record_test!(test_fail_no_default_alpn_with_value, SVCB, ["1", "foo.example.com.", "no-default-alpn=abc"]);
+    gen_fail_record_test!(test_fail_missing_mandatory_key, SVCB, ["1", "foo.example.com.", "mandatory=key123"]);
+    gen_fail_record_test!(test_fail_mandatory_lists_itself, SVCB, ["1", "foo.example.com.", "mandatory=mandatory"]);
+    gen_fail_record_test!(test_fail_mandatory_lists_a_key_twice, SVCB, ["1", "foo.example.com.", "mandatory=key123,key123", "key123=abc"]);
+    gen_fail_record_test!(test_fail_no_target_name, SVCB, ["1"]);
+    gen_ok_record_test!(test_ok_mandatory_key_is_present, SVCB, SVCB { priority: 1, target_name: DomainName::from_utf8("foo.example.com.").unwrap(), params: vec![SvcParam::Mandatory(vec![SvcParamKey::Unknown(123)]), SvcParam::Opaque(SvcParamKey::Unknown(123), b"abc".to_vec())] }, ["1", "foo.example.com.", "mandatory=key123", "key123=abc"]);
+
+    gen_ok_record_test!(test_ok_empty_value_does_not_take_the_next_parameter, SVCB, SVCB { priority: 1, target_name: DomainName::from_utf8("foo.example.com.").unwrap(), params: vec![SvcParam::Port(53), SvcParam::Opaque(SvcParamKey::Unknown(667), Vec::new())] }, ["1", "foo.example.com.", "key667=", "port=53"]);
+
+    #[test]
+    fn wire_params_must_follow_rfc_9460() {
+        // https://datatracker.ietf.org/doc/html/rfc9460#section-2.2
+        for params in [
+            // The same key twice.
+            &[0x00, 0x03, 0x00, 0x02, 0x00, 0x35, 0x00, 0x03, 0x00, 0x02, 0x00, 0x35][..],
+            // The keys in decreasing order.
+            &[0x00, 0x03, 0x00, 0x02, 0x00, 0x35, 0x00, 0x02, 0x00, 0x00],
+            // A mandatory key that is not present.
+            &[0x00, 0x00, 0x00, 0x02, 0x00, 0x03],
+            // Mandatory keys in decreasing order.
+            &[0x00, 0x00, 0x00, 0x04, 0x00, 0x03, 0x00, 0x02, 0x00, 0x02, 0x00, 0x00, 0x00, 0x03, 0x00, 0x02, 0x00, 0x35],
+        ] {
+            let wire = [&[0x00, 0x01][..], &FOO_EXAMPLE_COM, params].concat();
+            assert!(SVCB::from_wire_format(&mut ReadWire::from_bytes(&wire)).is_err(), "{params:x?}");
+        }
+    }
+
+    #[test]
+    fn new_checks_params() {
+        let target_name = DomainName::from_utf8("foo.example.com.").unwrap();
+        assert_eq!(
+            SVCB::new(1, target_name.clone(), vec![SvcParam::Port(53), SvcParam::NoDefaultAlpn]),
+            Err(SvcParamError::KeyOutOfOrder(SvcParamKey::NoDefaultAlpn)),
+        );
+        assert_eq!(
+            SVCB::new(1, target_name.clone(), vec![SvcParam::Port(53), SvcParam::Port(853)]),
+            Err(SvcParamError::DuplicateKey(SvcParamKey::Port)),
+        );
+        assert_eq!(
+            SVCB::new(1, target_name.clone(), vec![SvcParam::Mandatory(vec![SvcParamKey::Alpn]), SvcParam::Port(53)]),
+            Err(SvcParamError::MissingMandatoryKey(SvcParamKey::Alpn)),
+        );
+        assert_eq!(
+            SVCB::new(1, target_name.clone(), vec![SvcParam::Opaque(SvcParamKey::Unknown(667), vec![0; u16::MAX as usize + 1])]),
+            Err(SvcParamError::ValueTooLong(SvcParamKey::Unknown(667))),
+        );
+        assert!(SVCB::new(1, target_name, vec![SvcParam::Mandatory(vec![SvcParamKey::Port]), SvcParam::Port(53)]).is_ok());
+    }
+
+    #[test]
+    fn wire_values_must_fill_their_length() {
+        // A port that is three octets long.
+        let wire = [&[0x00, 0x01][..], &FOO_EXAMPLE_COM, &[0x00, 0x03, 0x00, 0x03, 0x00, 0x35, 0x00]].concat();
+        assert!(SVCB::from_wire_format(&mut ReadWire::from_bytes(&wire)).is_err());
+        // A length that runs past the end of the record.
+        let wire = [&[0x00, 0x01][..], &FOO_EXAMPLE_COM, &[0x00, 0x03, 0x00, 0x04, 0x00, 0x35]].concat();
+        assert!(SVCB::from_wire_format(&mut ReadWire::from_bytes(&wire)).is_err());
+    }
+}
@@ -15,6 +15,50 @@ pub struct TSIG {
     other_data: Vec<u8>,
 }
 
+impl TSIG {
+    #[inline]
+    pub fn new(algorithm_name: DomainName, time_signed: u48, fudge: u16, mac: Vec<u8>, original_id: u16, error: RCode, other_data: Vec<u8>) -> Self {
+        Self { algorithm_name, time_signed, fudge, mac, original_id, error, other_data }
+    }
+
+    #[inline]
+    pub const fn algorithm_name(&self) -> &DomainName {
+        &self.algorithm_name
+    }
+
+    /// Seconds since the Unix epoch.
+    #[inline]
+    pub const fn time_signed(&self) -> u48 {
+        self.time_signed
+    }
+
+    /// The number of seconds that `time_signed` may differ from the receiver's clock.
+    #[inline]
+    pub const fn fudge(&self) -> u16 {
+        self.fudge
+    }
+
+    #[inline]
+    pub fn mac(&self) -> &[u8] {
+        &self.mac
+    }
+
+    #[inline]
+    pub const fn original_id(&self) -> u16 {
+        self.original_id
+    }
+
+    #[inline]
+    pub const fn error(&self) -> RCode {
+        self.error
+    }
+
+    #[inline]
+    pub fn other_data(&self) -> &[u8] {
+        &self.other_data
+    }
+}
+
 impl ToWire for TSIG {
     fn to_wire_format<'a, 'b>(&self, wire: &'b mut crate::serde::wire::write_wire::WriteWire<'a>, compression: &mut Option<crate::types::c_domain_name::CompressionMap>) -> Result<(), crate::serde::wire::write_wire::WriteWireError> where 'a: 'b {
         self.algorithm_name.to_wire_format(wire, compression)?;
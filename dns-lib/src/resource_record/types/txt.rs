@@ -1,10 +1,13 @@
-use dns_macros::{ToWire, FromWire, RData};
+use std::str::FromStr;
 
-use crate::{types::character_string::CharacterString, serde::presentation::{from_tokenized_rdata::FromTokenizedRData, from_presentation::FromPresentation, to_presentation::ToPresentation}};
+use dns_macros::{ToWire, FromWire, FromTokenizedRData, RData, ToPresentation};
+
+use crate::types::{ascii::{constants::{ASCII_EQUALS, ASCII_GRAVE_ACCENT, ASCII_HORIZONTAL_TAB, ASCII_SPACE}, AsciiChar, AsciiString}, character_string::{CharacterString, CharacterStringError}};
 
 /// (Original) https://datatracker.ietf.org/doc/html/rfc1035#section-3.3.14
-#[derive(Clone, PartialEq, Eq, Hash, Debug, ToWire, FromWire, RData)]
+#[derive(Clone, PartialEq, Eq, Hash, Debug, ToWire, FromWire, ToPresentation, FromTokenizedRData, RData)]
 pub struct TXT {
+    #[rdata(repeated, nonempty)]
     strings: Vec<CharacterString>,
 }
 
@@ -18,30 +21,84 @@ impl TXT {
     pub fn strings(&self) -> &[CharacterString] {
         &self.strings
     }
-}
 
-impl FromTokenizedRData for TXT {
+    /// Splits the octets into as many character-strings as are needed to hold them. An empty
+    /// slice results in a single empty character-string.
+    pub fn from_octets(octets: &[AsciiChar]) -> Self {
+        if octets.is_empty() {
+            return Self { strings: vec![CharacterString::new_empty()] };
+        }
+        let strings = octets.chunks(CharacterString::MAX_OCTETS)
+            .map(|chunk| CharacterString::new(AsciiString::from(chunk)).expect("chunks are at most MAX_OCTETS long"))
+            .collect();
+        Self { strings }
+    }
+
+    /// Joins all of the character-strings into a single string. This is how long values, such as
+    /// DKIM keys, are interpreted once they have been split across multiple character-strings.
+    pub fn concatenated(&self) -> AsciiString {
+        let octets = self.strings.iter()
+            .flat_map(|string| string.iter().copied())
+            .collect::<Vec<_>>();
+        AsciiString::from(&octets)
+    }
+
+    /// Iterates over the attributes stored in the character-strings using the `key=value` format
+    /// described by RFC 1464. Character-strings that do not contain an unquoted `=` are skipped.
+    ///
+    /// https://datatracker.ietf.org/doc/html/rfc1464
     #[inline]
-    fn from_tokenized_rdata<'a, 'b>(rdata: &Vec<&'a str>) -> Result<Self, crate::serde::presentation::errors::TokenizedRecordError<'b>> where Self: Sized, 'a: 'b {
-        match rdata.as_slice() {
-            &[_, ..] => {
-                let mut strings = Vec::with_capacity(rdata.len());
-                for string_token in rdata {
-                    strings.push(CharacterString::from_token_format(&[string_token])?.0);
-                }
-                Ok(Self { strings })
+    pub fn attributes(&self) -> impl Iterator<Item = (AsciiString, AsciiString)> + '_ {
+        self.strings.iter().filter_map(parse_attribute)
+    }
+
+    /// Finds the value of the first attribute with the key. Keys are compared case-insensitively.
+    pub fn attribute(&self, key: &str) -> Option<AsciiString> {
+        let key = AsciiString::from_utf8(key).ok()?.as_lowercase();
+        self.attributes()
+            .find(|(attribute_key, _)| attribute_key.as_lowercase() == key)
+            .map(|(_, value)| value)
+    }
+}
+
+/// Splits a character-string into an RFC 1464 attribute name and value. A grave accent quotes the
+/// character that follows it in the name. Unquoted leading and trailing whitespace in the name is
+/// ignored. The value is everything after the first unquoted `=`.
+fn parse_attribute(string: &CharacterString) -> Option<(AsciiString, AsciiString)> {
+    let mut key = AsciiString::new_empty();
+    // The length of the key, up to and including the last quoted or non-whitespace character.
+    let mut key_length = 0;
+    let mut characters = string.iter().copied().enumerate();
+    while let Some((index, character)) = characters.next() {
+        match character {
+            ASCII_GRAVE_ACCENT => {
+                key.push(characters.next()?.1);
+                key_length = key.len();
+            },
+            ASCII_EQUALS => {
+                let value = AsciiString::from(&string.iter().copied().skip(index + 1).collect::<Vec<_>>());
+                let key = key.from_range(0, key_length);
+                return Some((key, value));
+            },
+            ASCII_SPACE | ASCII_HORIZONTAL_TAB if key.is_empty() => (),
+            ASCII_SPACE | ASCII_HORIZONTAL_TAB => key.push(character),
+            _ => {
+                key.push(character);
+                key_length = key.len();
             },
-            _ => Err(crate::serde::presentation::errors::TokenizedRecordError::TooFewRDataTokensError{expected: 1, received: rdata.len()}),
         }
     }
+    None
 }
 
-impl ToPresentation for TXT {
+impl FromStr for TXT {
+    type Err = CharacterStringError;
+
+    /// Creates a TXT record containing the string, split into as many character-strings as are
+    /// needed to hold it.
     #[inline]
-    fn to_presentation_format(&self, out_buffer: &mut Vec<String>) {
-        for string in &self.strings {
-            string.to_presentation_format(out_buffer);
-        }
+    fn from_str(string: &str) -> Result<Self, Self::Err> {
+        Ok(Self::from_octets(AsciiString::from_utf8(string)?.as_slice()))
     }
 }
 
@@ -129,3 +186,45 @@ mod tokenizer_tests {
     );
     gen_fail_record_test!(test_fail_no_tokens, TXT, []);
 }
+
+#[cfg(test)]
+mod ergonomics_tests {
+    use std::str::FromStr;
+
+    use crate::types::{ascii::AsciiString, character_string::CharacterString};
+    use super::TXT;
+
+    #[test]
+    fn long_string_is_chunked() {
+        let long_string = "a".repeat(600);
+        let txt = TXT::from_str(&long_string).unwrap();
+        assert_eq!(txt.strings().iter().map(|string| string.len()).collect::<Vec<_>>(), vec![255, 255, 90]);
+        assert_eq!(txt.concatenated(), AsciiString::from_utf8(&long_string).unwrap());
+    }
+
+    #[test]
+    fn empty_string() {
+        let txt = TXT::from_str("").unwrap();
+        assert_eq!(txt.strings(), &[CharacterString::new_empty()]);
+        assert!(txt.concatenated().is_empty());
+    }
+
+    #[test]
+    fn attributes() {
+        let txt = TXT::new(vec![
+            CharacterString::from_utf8("color=blue").unwrap(),
+            CharacterString::from_utf8("no attribute here").unwrap(),
+            CharacterString::from_utf8(" Spaced Key =value=with=equals").unwrap(),
+            CharacterString::from_utf8("quoted`=key= value").unwrap(),
+            CharacterString::from_utf8("`  padded=x").unwrap(),
+            CharacterString::from_utf8("empty=").unwrap(),
+        ]);
+        assert_eq!(txt.attributes().count(), 5);
+        assert_eq!(txt.attribute("COLOR"), Some(AsciiString::from_utf8("blue").unwrap()));
+        assert_eq!(txt.attribute("spaced key"), Some(AsciiString::from_utf8("value=with=equals").unwrap()));
+        assert_eq!(txt.attribute("quoted=key"), Some(AsciiString::from_utf8(" value").unwrap()));
+        assert_eq!(txt.attribute("  padded"), Some(AsciiString::from_utf8("x").unwrap()));
+        assert_eq!(txt.attribute("empty"), Some(AsciiString::new_empty()));
+        assert_eq!(txt.attribute("missing"), None);
+    }
+}
@@ -4,7 +4,7 @@ use dns_macros::{ToWire, FromWire, RData};
 use lazy_static::lazy_static;
 use regex::Regex;
 
-use crate::{serde::presentation::{from_tokenized_rdata::FromTokenizedRData, to_presentation::ToPresentation, errors::{TokenizedRecordError, TokenError}, from_presentation::FromPresentation}, resource_record::{protocol::Protocol, port_from_service::port_from_service}};
+use crate::{serde::presentation::{from_tokenized_rdata::FromTokenizedRData, to_presentation::{PresentationWriter, ToPresentation}, errors::{TokenizedRecordError, TokenError}, from_presentation::FromPresentation}, resource_record::{protocol::Protocol, port_from_service::port_from_service}};
 
 #[derive(Clone, PartialEq, Eq, Hash, Debug, ToWire, FromWire, RData)]
 pub struct WKS {
@@ -167,6 +167,21 @@ impl ToPresentation for WKS {
             }
         }
     }
+
+    fn write_presentation_tokens<W: std::fmt::Write + ?Sized>(&self, out: &mut PresentationWriter<'_, W>) -> std::fmt::Result {
+        self.address.write_presentation_tokens(out)?;
+        self.protocol.write_presentation_tokens(out)?;
+        for (index, byte) in self.bit_map.iter().enumerate() {
+            let index = index as u16;
+            // Written in the same order as `to_presentation_format()`.
+            for bit_offset in (0..8).rev() {
+                if *byte & (0b10000000 >> bit_offset) != 0 {
+                    ((index * 8) + bit_offset).write_presentation_tokens(out)?;
+                }
+            }
+        }
+        Ok(())
+    }
 }
 
 #[cfg(test)]
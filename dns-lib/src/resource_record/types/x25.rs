@@ -0,0 +1,47 @@
+use dns_macros::{ToWire, FromWire, FromTokenizedRData, RData, ToPresentation};
+
+use crate::types::character_string::CharacterString;
+
+/// (Original) https://datatracker.ietf.org/doc/html/rfc1183#section-3.1
+#[derive(Clone, PartialEq, Eq, Hash, Debug, ToWire, FromWire, ToPresentation, FromTokenizedRData, RData)]
+pub struct X25 {
+    psdn_address: CharacterString,
+}
+
+impl X25 {
+    #[inline]
+    pub fn new(psdn_address: CharacterString) -> Self {
+        Self { psdn_address }
+    }
+
+    /// The X.121 address of the host on the public switched data network. RFC 1183 requires it to
+    /// be between 4 and 15 decimal digits, but this is not enforced so that records which break
+    /// the rule can still be migrated.
+    #[inline]
+    pub fn psdn_address(&self) -> &CharacterString {
+        &self.psdn_address
+    }
+}
+
+#[cfg(test)]
+mod circular_serde_sanity_test {
+    use crate::{serde::wire::circular_test::gen_test_circular_serde_sanity_test, types::character_string::CharacterString};
+    use super::X25;
+
+    gen_test_circular_serde_sanity_test!(
+        record_circular_serde_sanity_test,
+        X25 { psdn_address: CharacterString::from_utf8("311061700956").unwrap() }
+    );
+}
+
+#[cfg(test)]
+mod tokenizer_tests {
+    use crate::{serde::presentation::test_from_tokenized_rdata::{gen_ok_record_test, gen_fail_record_test}, types::character_string::CharacterString};
+    use super::X25;
+
+    const GOOD_ADDRESS: &str = "311061700956";
+
+    gen_ok_record_test!(test_ok, X25, X25 { psdn_address: CharacterString::from_utf8(GOOD_ADDRESS).unwrap() }, [GOOD_ADDRESS]);
+    gen_fail_record_test!(test_fail_two_tokens, X25, [GOOD_ADDRESS, GOOD_ADDRESS]);
+    gen_fail_record_test!(test_fail_no_tokens, X25, []);
+}
@@ -0,0 +1,163 @@
+//! Checks the wire and presentation parsers against fixed messages and records that were not
+//! produced by this library, most of them taken from the examples in the RFCs. The circular tests
+//! only show that this library agrees with itself. These show that it agrees with everyone else.
+//!
+//! The vectors are in `conformance_vectors.txt`, which also describes their format.
+
+use ux::u4;
+
+use crate::{query::{message::Message, qr::QR, question::Question}, resource_record::{opcode::OpCode, rclass::RClass, rcode::RCode, resource_record::{RecordData, ResourceRecord}, rtype::RType}, serde::{presentation::zone_file_reader::{ZoneFileReader, ZoneToken}, wire::{from_wire::FromWire, read_wire::ReadWire, to_wire::ToWire, write_wire::WriteWire}}, types::c_domain_name::{CDomainName, CompressionMap}};
+
+const VECTORS: &str = include_str!("conformance_vectors.txt");
+
+#[derive(Debug, Default)]
+struct Vector<'a> {
+    name: &'a str,
+    fields: Vec<(&'a str, &'a str)>,
+}
+
+impl<'a> Vector<'a> {
+    fn all(&self, key: &str) -> impl Iterator<Item = &'a str> + '_ {
+        let key = key.to_string();
+        self.fields.iter().filter(move |(field, _)| *field == key).map(|(_, value)| *value)
+    }
+
+    fn get(&self, key: &str) -> Option<&'a str> {
+        self.all(key).next()
+    }
+
+    /// The hex encoded bytes of every line with the key, concatenated.
+    fn bytes(&self, key: &str) -> Vec<u8> {
+        let hex = self.all(key).flat_map(|value| value.chars()).filter(|character| !character.is_whitespace()).collect::<String>();
+        assert_eq!(hex.len() % 2, 0, "[{}] '{key}' has an odd number of hex digits", self.name);
+        (0..hex.len()).step_by(2)
+            .map(|index| u8::from_str_radix(&hex[index..(index + 2)], 16).unwrap_or_else(|_| panic!("[{}] '{key}' is not hex", self.name)))
+            .collect()
+    }
+}
+
+fn parse_vectors(vectors: &str) -> Vec<Vector<'_>> {
+    let mut parsed: Vec<Vector> = Vec::new();
+    for line in vectors.lines().map(str::trim) {
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        if let Some(name) = line.strip_prefix('[').and_then(|line| line.strip_suffix(']')) {
+            parsed.push(Vector { name, fields: Vec::new() });
+            continue;
+        }
+        let (key, value) = line.split_once('=').unwrap_or_else(|| panic!("'{line}' is not a key-value pair"));
+        parsed.last_mut()
+            .unwrap_or_else(|| panic!("'{line}' is not in a vector"))
+            .fields.push((key.trim(), value.trim()));
+    }
+    parsed
+}
+
+fn parse_record(vector: &Vector, presentation: &str) -> ResourceRecord {
+    let (tokens, diagnostics) = ZoneFileReader::new(presentation).read_all();
+    assert!(diagnostics.is_empty(), "[{}] could not parse '{presentation}': {diagnostics:?}", vector.name);
+    match tokens.as_slice() {
+        [ZoneToken::ResourceRecord(record)] => record.clone(),
+        _ => panic!("[{}] expected '{presentation}' to be exactly one record", vector.name),
+    }
+}
+
+fn parse_question(vector: &Vector, presentation: &str) -> Question {
+    let [qname, qclass, qtype] = presentation.split_whitespace().collect::<Vec<_>>()[..] else {
+        panic!("[{}] the question '{presentation}' is not '<name> <class> <type>'", vector.name);
+    };
+    Question::new(
+        CDomainName::from_utf8(qname).unwrap(),
+        RType::from_str(qtype).unwrap(),
+        RClass::from_str(qclass).unwrap(),
+    )
+}
+
+fn check_header(vector: &Vector, message: &Message, header: &str) {
+    for field in header.split_whitespace() {
+        let (key, value) = field.split_once('=').unwrap_or_else(|| panic!("[{}] '{field}' is not a header field", vector.name));
+        let value = value.parse::<u16>().unwrap_or_else(|_| panic!("[{}] '{field}' is not a number", vector.name));
+        let matches = match key {
+            "id" => message.id == value,
+            "qr" => message.qr == if value == 0 { QR::Query } else { QR::Response },
+            "opcode" => message.opcode == OpCode::from_code(u4::new(value as u8)),
+            "aa" => message.authoritative_answer == (value != 0),
+            "tc" => message.truncation == (value != 0),
+            "rd" => message.recursion_desired == (value != 0),
+            "ra" => message.recursion_available == (value != 0),
+            "rcode" => message.rcode == RCode::from_code(value),
+            _ => panic!("[{}] unknown header field '{key}'", vector.name),
+        };
+        assert!(matches, "[{}] header field '{field}' does not match the message: {message:?}", vector.name);
+    }
+}
+
+fn check_section(vector: &Vector, key: &str, actual: &[ResourceRecord]) {
+    let expected = vector.all(key).map(|presentation| parse_record(vector, presentation)).collect::<Vec<_>>();
+    assert_eq!(actual.len(), expected.len(), "[{}] wrong number of {key} records: {actual:?}", vector.name);
+    for (actual, expected) in actual.iter().zip(expected.iter()) {
+        assert_eq!(actual, expected, "[{}] {key} record does not match", vector.name);
+        assert_eq!(actual.get_ttl(), expected.get_ttl(), "[{}] {key} record TTL does not match", vector.name);
+    }
+}
+
+fn check_message(vector: &Vector) {
+    let wire = vector.bytes("wire");
+    let result = Message::from_wire_format(&mut ReadWire::from_bytes(&wire));
+    if vector.get("error") == Some("yes") {
+        assert!(result.is_err(), "[{}] expected the message to be rejected but got {result:?}", vector.name);
+        return;
+    }
+    let message = result.unwrap_or_else(|error| panic!("[{}] could not parse the message: {error}", vector.name));
+
+    if let Some(header) = vector.get("header") {
+        check_header(vector, &message, header);
+    }
+    let questions = vector.all("question").map(|question| parse_question(vector, question)).collect::<Vec<_>>();
+    assert_eq!(message.question.as_slice(), questions.as_slice(), "[{}] the questions do not match", vector.name);
+    check_section(vector, "answer", &message.answer);
+    check_section(vector, "authority", &message.authority);
+    check_section(vector, "additional", &message.additional);
+
+    // Whether or not the names get compressed the same way, the message must survive being written
+    // back out.
+    let mut buffer = [0_u8; u16::MAX as usize];
+    let mut write_wire = WriteWire::from_bytes(&mut buffer);
+    message.to_wire_format(&mut write_wire, &mut Some(CompressionMap::new())).unwrap();
+    if vector.get("reencode") == Some("exact") {
+        assert_eq!(write_wire.current(), wire.as_slice(), "[{}] re-encoding the message changed it", vector.name);
+    }
+    let reparsed = Message::from_wire_format(&mut ReadWire::from_bytes(write_wire.current()))
+        .unwrap_or_else(|error| panic!("[{}] could not parse the re-encoded message: {error}", vector.name));
+    assert_eq!(reparsed, message, "[{}] the re-encoded message does not parse back into the same message", vector.name);
+}
+
+fn check_record(vector: &Vector, presentation: &str) {
+    let record = parse_record(vector, presentation);
+    let rdata = vector.bytes("rdata");
+
+    let mut buffer = [0_u8; u16::MAX as usize];
+    let mut write_wire = WriteWire::from_bytes(&mut buffer);
+    record.to_wire_format(&mut write_wire, &mut None).unwrap();
+    let record_wire = write_wire.current();
+    // The owner name, type, class, TTL, and RDATA length come before the RDATA.
+    let rdata_start = record_wire.len() - rdata.len();
+    assert_eq!(&record_wire[rdata_start..], rdata.as_slice(), "[{}] the presentation format does not serialize to the expected RDATA", vector.name);
+    assert_eq!(u16::from_be_bytes([record_wire[rdata_start - 2], record_wire[rdata_start - 1]]) as usize, rdata.len(), "[{}] the RDATA length is wrong", vector.name);
+
+    let parsed = ResourceRecord::<RecordData>::from_wire_format(&mut ReadWire::from_bytes(record_wire)).unwrap();
+    assert_eq!(parsed, record, "[{}] the expected RDATA does not parse into the record", vector.name);
+}
+
+#[test]
+fn conformance_vectors() {
+    let vectors = parse_vectors(VECTORS);
+    assert!(!vectors.is_empty());
+    for vector in &vectors {
+        match vector.get("record") {
+            Some(presentation) => check_record(vector, presentation),
+            None => check_message(vector),
+        }
+    }
+}
@@ -2,3 +2,6 @@ pub mod wire;
 pub mod presentation;
 
 mod const_byte_counts;
+
+#[cfg(test)]
+mod conformance_tests;
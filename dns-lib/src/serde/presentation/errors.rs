@@ -2,7 +2,7 @@ use std::{error::Error, fmt::Display, num::{ParseIntError, TryFromIntError}, net
 
 use mac_address::MacParseError;
 
-use crate::{resource_record::{dnssec_alg::DnsSecAlgorithmError, ports::PortError, protocol::ProtocolError, rclass::RClassError, rtype::{RType, RTypeError}, time::{DateTimeError, TimeError}, types::cert::CertificateTypeError}, types::{ascii::AsciiError, base16::Base16Error, base32::Base32Error, base64::Base64Error, c_domain_name::CDomainNameError, character_string::CharacterStringError, domain_name::DomainNameError, extended_base32::ExtendedBase32Error}};
+use crate::{resource_record::{dnssec_alg::DnsSecAlgorithmError, ports::PortError, protocol::ProtocolError, rclass::RClassError, rtype::{RType, RTypeError}, svc_param_key::SvcParamKeyError, time::{DateTimeError, TimeError}, types::cert::CertificateTypeError}, types::{ascii::AsciiError, base16::Base16Error, base32::Base32Error, base64::Base64Error, c_domain_name::CDomainNameError, character_string::CharacterStringError, domain_name::DomainNameError, extended_base32::ExtendedBase32Error}};
 
 use super::tokenizer::errors::TokenizerError;
 
@@ -53,6 +53,7 @@ pub enum TokenError<'a> {
     MacParseError(MacParseError),
     RClassError(RClassError<'a>),
     RTypeError(RTypeError<'a>),
+    SvcParamKeyError(SvcParamKeyError<'a>),
     DnsSecAlgorithmError(DnsSecAlgorithmError<'a>),
     AsciiError(AsciiError),
     CharacterStringError(CharacterStringError),
@@ -66,6 +67,7 @@ pub enum TokenError<'a> {
     ProtocolError(ProtocolError<'a>),
     PortError(PortError),
     CertificateTypeError(CertificateTypeError<'a>),
+    ValueError(String),
 }
 impl<'a> Error for TokenError<'a> {}
 impl<'a> Display for TokenError<'a> {
@@ -79,6 +81,7 @@ impl<'a> Display for TokenError<'a> {
             Self::MacParseError(error) => write!(f, "{error}"),
             Self::RClassError(error) => write!(f, "{error}"),
             Self::RTypeError(error) => write!(f, "{error}"),
+            Self::SvcParamKeyError(error) => write!(f, "{error}"),
             Self::DnsSecAlgorithmError(error) => write!(f, "{error}"),
             Self::AsciiError(error) => write!(f, "{error}"),
             Self::CharacterStringError(error) => write!(f, "{error}"),
@@ -92,6 +95,7 @@ impl<'a> Display for TokenError<'a> {
             Self::ProtocolError(error) => write!(f, "{error}"),
             Self::PortError(error) => write!(f, "{error}"),
             Self::CertificateTypeError(error) => write!(f, "{error}"),
+            Self::ValueError(error) => write!(f, "Value Error: {error}"),
         }
     }
 }
@@ -120,6 +124,11 @@ impl<'a> From<RTypeError<'a>> for TokenError<'a> {
         Self::RTypeError(value)
     }
 }
+impl<'a> From<SvcParamKeyError<'a>> for TokenError<'a> {
+    fn from(value: SvcParamKeyError<'a>) -> Self {
+        Self::SvcParamKeyError(value)
+    }
+}
 impl<'a> From<DnsSecAlgorithmError<'a>> for TokenError<'a> {
     fn from(value: DnsSecAlgorithmError<'a>) -> Self {
         Self::DnsSecAlgorithmError(value)
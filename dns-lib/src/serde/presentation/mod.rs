@@ -1,5 +1,6 @@
 pub mod tokenizer;
 pub mod zone_file_reader;
+pub mod zone_file_writer;
 pub(crate) mod parse_chars;
 
 pub mod from_tokenized_rdata;
@@ -6,7 +6,7 @@ use crate::types::ascii::{AsciiChar, constants::ASCII_ZERO};
 pub enum EscapableChar {
     Ascii(AsciiChar),
     EscapedAscii(AsciiChar),
-    EscapedOctal(AsciiChar)
+    EscapedDecimal(AsciiChar)
 }
 
 impl EscapableChar {
@@ -14,7 +14,7 @@ impl EscapableChar {
         match self {
             EscapableChar::Ascii(character) => character,
             EscapableChar::EscapedAscii(character) => character,
-            EscapableChar::EscapedOctal(character) => character,
+            EscapableChar::EscapedDecimal(character) => character,
         }
     }
 }
@@ -25,8 +25,8 @@ impl Display for EscapableChar {
         match self {
             Self::Ascii(character) => write!(f, "{}", *character as char),
             Self::EscapedAscii(escaped_character) => write!(f, "\\{}", *escaped_character as char),
-            Self::EscapedOctal(escaped_octal) => {
-                let (char1, char2, char3) = ascii_to_octal(*escaped_octal);
+            Self::EscapedDecimal(escaped_decimal) => {
+                let (char1, char2, char3) = ascii_to_decimal(*escaped_decimal);
                 write!(f, "\\{}{}{}", char1 as char, char2 as char, char3 as char)
             },
         }
@@ -39,46 +39,53 @@ impl Debug for EscapableChar {
         match self {
             Self::Ascii(character) => write!(f, "EscapableChar::Ascii({character} '{self}')"),
             Self::EscapedAscii(character) => write!(f, "EscapableChar::EscapedAscii({character} '{self}')"),
-            Self::EscapedOctal(character) => write!(f, "EscapableChar::EscapedOctal({character} '{self}')"),
+            Self::EscapedDecimal(character) => write!(f, "EscapableChar::EscapedDecimal({character} '{self}')"),
         }
     }
 }
 
 #[inline]
-pub const fn ascii_to_octal(character: AsciiChar) -> (AsciiChar, AsciiChar, AsciiChar) {
+pub const fn ascii_to_decimal(character: AsciiChar) -> (AsciiChar, AsciiChar, AsciiChar) {
     (
-        ((character >> 6) & 0b00000011) + ASCII_ZERO,
-        ((character >> 3) & 0b00000111) + ASCII_ZERO,
-        ((character >> 0) & 0b00000111) + ASCII_ZERO
+        (character / 100) + ASCII_ZERO,
+        ((character / 10) % 10) + ASCII_ZERO,
+        (character % 10) + ASCII_ZERO
     )
 }
 
+/// Converts the three digits of a `\DDD` escape sequence into the octet they represent. If the
+/// value is larger than 255, `None` is returned.
 #[inline]
-pub const fn octal_to_ascii(char1: AsciiChar, char2: AsciiChar, char3: AsciiChar) -> AsciiChar {
-    (((char1 - ASCII_ZERO) << 6) & 0b11000000 )
-  + (((char2 - ASCII_ZERO) << 3) & 0b00111000 )
-  + (((char3 - ASCII_ZERO) << 0) & 0b00000111 )
+pub const fn decimal_to_ascii(char1: AsciiChar, char2: AsciiChar, char3: AsciiChar) -> Option<AsciiChar> {
+    let value = ((char1 - ASCII_ZERO) as u16 * 100)
+        + ((char2 - ASCII_ZERO) as u16 * 10)
+        + ((char3 - ASCII_ZERO) as u16);
+    if value > (AsciiChar::MAX as u16) {
+        None
+    } else {
+        Some(value as AsciiChar)
+    }
 }
 
 #[cfg(test)]
 mod non_escaped_to_escaped_tests {
-    use crate::serde::presentation::parse_chars::char_token::{ascii_to_octal, octal_to_ascii};
+    use crate::serde::presentation::parse_chars::char_token::{ascii_to_decimal, decimal_to_ascii};
 
     use super::EscapableChar;
 
-    fn test_escapable_octal_to_display_mapping(character: EscapableChar, display: &str, digits: (char, char, char)) {
+    fn test_escapable_decimal_to_display_mapping(character: EscapableChar, display: &str, digits: (char, char, char)) {
         // Check for incorrect input.
         assert_eq!(display, format!("\\{}{}{}", digits.0, digits.1, digits.2).as_str(), "The expected display ({display}) did not match the provided digits {digits:?}");
 
         let byte_digits = (digits.0 as u8, digits.1 as u8, digits.2 as u8);
 
-        // Test octal ascii -> octal
-        let octal_characters = ascii_to_octal(character.into_unescaped_character());
-        assert_eq!(octal_characters, byte_digits, "The conversion ascii_to_octal() did not result in the same octal code as the input");
+        // Test decimal ascii -> decimal
+        let decimal_characters = ascii_to_decimal(character.into_unescaped_character());
+        assert_eq!(decimal_characters, byte_digits, "The conversion ascii_to_decimal() did not result in the same decimal code as the input");
 
-        // Test octal octal -> ascii
-        let ascii_character = octal_to_ascii(byte_digits.0, byte_digits.1, byte_digits.2);
-        assert_eq!(ascii_character, character.into_unescaped_character(), "The conversion octal_to_ascii() did not result in the same ascii code as the input");
+        // Test decimal decimal -> ascii
+        let ascii_character = decimal_to_ascii(byte_digits.0, byte_digits.1, byte_digits.2);
+        assert_eq!(ascii_character, Some(character.into_unescaped_character()), "The conversion decimal_to_ascii() did not result in the same ascii code as the input");
 
         // Test escapable character -> display
         let character_as_string = character.to_string();
@@ -86,262 +93,268 @@ mod non_escaped_to_escaped_tests {
     }
 
     #[test]
-    fn test_all_escapable_octal_to_octal() {
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(000), r"\000", ('0','0','0'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(001), r"\001", ('0','0','1'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(002), r"\002", ('0','0','2'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(003), r"\003", ('0','0','3'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(004), r"\004", ('0','0','4'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(005), r"\005", ('0','0','5'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(006), r"\006", ('0','0','6'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(007), r"\007", ('0','0','7'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(008), r"\010", ('0','1','0'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(009), r"\011", ('0','1','1'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(010), r"\012", ('0','1','2'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(011), r"\013", ('0','1','3'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(012), r"\014", ('0','1','4'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(013), r"\015", ('0','1','5'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(014), r"\016", ('0','1','6'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(015), r"\017", ('0','1','7'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(016), r"\020", ('0','2','0'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(017), r"\021", ('0','2','1'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(018), r"\022", ('0','2','2'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(019), r"\023", ('0','2','3'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(020), r"\024", ('0','2','4'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(021), r"\025", ('0','2','5'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(022), r"\026", ('0','2','6'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(023), r"\027", ('0','2','7'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(024), r"\030", ('0','3','0'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(025), r"\031", ('0','3','1'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(026), r"\032", ('0','3','2'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(027), r"\033", ('0','3','3'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(028), r"\034", ('0','3','4'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(029), r"\035", ('0','3','5'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(030), r"\036", ('0','3','6'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(031), r"\037", ('0','3','7'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(032), r"\040", ('0','4','0'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(033), r"\041", ('0','4','1'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(034), r"\042", ('0','4','2'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(035), r"\043", ('0','4','3'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(036), r"\044", ('0','4','4'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(037), r"\045", ('0','4','5'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(038), r"\046", ('0','4','6'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(039), r"\047", ('0','4','7'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(040), r"\050", ('0','5','0'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(041), r"\051", ('0','5','1'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(042), r"\052", ('0','5','2'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(043), r"\053", ('0','5','3'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(044), r"\054", ('0','5','4'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(045), r"\055", ('0','5','5'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(046), r"\056", ('0','5','6'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(047), r"\057", ('0','5','7'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(048), r"\060", ('0','6','0'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(049), r"\061", ('0','6','1'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(050), r"\062", ('0','6','2'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(051), r"\063", ('0','6','3'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(052), r"\064", ('0','6','4'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(053), r"\065", ('0','6','5'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(054), r"\066", ('0','6','6'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(055), r"\067", ('0','6','7'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(056), r"\070", ('0','7','0'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(057), r"\071", ('0','7','1'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(058), r"\072", ('0','7','2'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(059), r"\073", ('0','7','3'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(060), r"\074", ('0','7','4'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(061), r"\075", ('0','7','5'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(062), r"\076", ('0','7','6'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(063), r"\077", ('0','7','7'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(064), r"\100", ('1','0','0'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(065), r"\101", ('1','0','1'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(066), r"\102", ('1','0','2'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(067), r"\103", ('1','0','3'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(068), r"\104", ('1','0','4'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(069), r"\105", ('1','0','5'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(070), r"\106", ('1','0','6'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(071), r"\107", ('1','0','7'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(072), r"\110", ('1','1','0'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(073), r"\111", ('1','1','1'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(074), r"\112", ('1','1','2'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(075), r"\113", ('1','1','3'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(076), r"\114", ('1','1','4'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(077), r"\115", ('1','1','5'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(078), r"\116", ('1','1','6'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(079), r"\117", ('1','1','7'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(080), r"\120", ('1','2','0'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(081), r"\121", ('1','2','1'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(082), r"\122", ('1','2','2'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(083), r"\123", ('1','2','3'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(084), r"\124", ('1','2','4'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(085), r"\125", ('1','2','5'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(086), r"\126", ('1','2','6'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(087), r"\127", ('1','2','7'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(088), r"\130", ('1','3','0'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(089), r"\131", ('1','3','1'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(090), r"\132", ('1','3','2'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(091), r"\133", ('1','3','3'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(092), r"\134", ('1','3','4'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(093), r"\135", ('1','3','5'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(094), r"\136", ('1','3','6'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(095), r"\137", ('1','3','7'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(096), r"\140", ('1','4','0'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(097), r"\141", ('1','4','1'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(098), r"\142", ('1','4','2'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(099), r"\143", ('1','4','3'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(100), r"\144", ('1','4','4'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(101), r"\145", ('1','4','5'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(102), r"\146", ('1','4','6'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(103), r"\147", ('1','4','7'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(104), r"\150", ('1','5','0'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(105), r"\151", ('1','5','1'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(106), r"\152", ('1','5','2'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(107), r"\153", ('1','5','3'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(108), r"\154", ('1','5','4'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(109), r"\155", ('1','5','5'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(110), r"\156", ('1','5','6'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(111), r"\157", ('1','5','7'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(112), r"\160", ('1','6','0'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(113), r"\161", ('1','6','1'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(114), r"\162", ('1','6','2'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(115), r"\163", ('1','6','3'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(116), r"\164", ('1','6','4'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(117), r"\165", ('1','6','5'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(118), r"\166", ('1','6','6'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(119), r"\167", ('1','6','7'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(120), r"\170", ('1','7','0'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(121), r"\171", ('1','7','1'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(122), r"\172", ('1','7','2'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(123), r"\173", ('1','7','3'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(124), r"\174", ('1','7','4'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(125), r"\175", ('1','7','5'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(126), r"\176", ('1','7','6'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(127), r"\177", ('1','7','7'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(128), r"\200", ('2','0','0'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(129), r"\201", ('2','0','1'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(130), r"\202", ('2','0','2'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(131), r"\203", ('2','0','3'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(132), r"\204", ('2','0','4'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(133), r"\205", ('2','0','5'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(134), r"\206", ('2','0','6'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(135), r"\207", ('2','0','7'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(136), r"\210", ('2','1','0'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(137), r"\211", ('2','1','1'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(138), r"\212", ('2','1','2'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(139), r"\213", ('2','1','3'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(140), r"\214", ('2','1','4'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(141), r"\215", ('2','1','5'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(142), r"\216", ('2','1','6'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(143), r"\217", ('2','1','7'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(144), r"\220", ('2','2','0'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(145), r"\221", ('2','2','1'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(146), r"\222", ('2','2','2'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(147), r"\223", ('2','2','3'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(148), r"\224", ('2','2','4'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(149), r"\225", ('2','2','5'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(150), r"\226", ('2','2','6'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(151), r"\227", ('2','2','7'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(152), r"\230", ('2','3','0'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(153), r"\231", ('2','3','1'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(154), r"\232", ('2','3','2'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(155), r"\233", ('2','3','3'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(156), r"\234", ('2','3','4'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(157), r"\235", ('2','3','5'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(158), r"\236", ('2','3','6'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(159), r"\237", ('2','3','7'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(160), r"\240", ('2','4','0'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(161), r"\241", ('2','4','1'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(162), r"\242", ('2','4','2'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(163), r"\243", ('2','4','3'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(164), r"\244", ('2','4','4'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(165), r"\245", ('2','4','5'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(166), r"\246", ('2','4','6'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(167), r"\247", ('2','4','7'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(168), r"\250", ('2','5','0'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(169), r"\251", ('2','5','1'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(170), r"\252", ('2','5','2'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(171), r"\253", ('2','5','3'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(172), r"\254", ('2','5','4'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(173), r"\255", ('2','5','5'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(174), r"\256", ('2','5','6'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(175), r"\257", ('2','5','7'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(176), r"\260", ('2','6','0'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(177), r"\261", ('2','6','1'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(178), r"\262", ('2','6','2'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(179), r"\263", ('2','6','3'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(180), r"\264", ('2','6','4'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(181), r"\265", ('2','6','5'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(182), r"\266", ('2','6','6'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(183), r"\267", ('2','6','7'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(184), r"\270", ('2','7','0'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(185), r"\271", ('2','7','1'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(186), r"\272", ('2','7','2'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(187), r"\273", ('2','7','3'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(188), r"\274", ('2','7','4'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(189), r"\275", ('2','7','5'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(190), r"\276", ('2','7','6'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(191), r"\277", ('2','7','7'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(192), r"\300", ('3','0','0'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(193), r"\301", ('3','0','1'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(194), r"\302", ('3','0','2'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(195), r"\303", ('3','0','3'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(196), r"\304", ('3','0','4'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(197), r"\305", ('3','0','5'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(198), r"\306", ('3','0','6'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(199), r"\307", ('3','0','7'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(200), r"\310", ('3','1','0'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(201), r"\311", ('3','1','1'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(202), r"\312", ('3','1','2'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(203), r"\313", ('3','1','3'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(204), r"\314", ('3','1','4'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(205), r"\315", ('3','1','5'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(206), r"\316", ('3','1','6'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(207), r"\317", ('3','1','7'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(208), r"\320", ('3','2','0'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(209), r"\321", ('3','2','1'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(210), r"\322", ('3','2','2'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(211), r"\323", ('3','2','3'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(212), r"\324", ('3','2','4'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(213), r"\325", ('3','2','5'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(214), r"\326", ('3','2','6'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(215), r"\327", ('3','2','7'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(216), r"\330", ('3','3','0'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(217), r"\331", ('3','3','1'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(218), r"\332", ('3','3','2'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(219), r"\333", ('3','3','3'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(220), r"\334", ('3','3','4'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(221), r"\335", ('3','3','5'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(222), r"\336", ('3','3','6'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(223), r"\337", ('3','3','7'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(224), r"\340", ('3','4','0'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(225), r"\341", ('3','4','1'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(226), r"\342", ('3','4','2'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(227), r"\343", ('3','4','3'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(228), r"\344", ('3','4','4'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(229), r"\345", ('3','4','5'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(230), r"\346", ('3','4','6'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(231), r"\347", ('3','4','7'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(232), r"\350", ('3','5','0'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(233), r"\351", ('3','5','1'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(234), r"\352", ('3','5','2'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(235), r"\353", ('3','5','3'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(236), r"\354", ('3','5','4'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(237), r"\355", ('3','5','5'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(238), r"\356", ('3','5','6'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(239), r"\357", ('3','5','7'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(240), r"\360", ('3','6','0'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(241), r"\361", ('3','6','1'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(242), r"\362", ('3','6','2'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(243), r"\363", ('3','6','3'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(244), r"\364", ('3','6','4'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(245), r"\365", ('3','6','5'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(246), r"\366", ('3','6','6'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(247), r"\367", ('3','6','7'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(248), r"\370", ('3','7','0'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(249), r"\371", ('3','7','1'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(250), r"\372", ('3','7','2'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(251), r"\373", ('3','7','3'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(252), r"\374", ('3','7','4'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(253), r"\375", ('3','7','5'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(254), r"\376", ('3','7','6'));
-        test_escapable_octal_to_display_mapping(EscapableChar::EscapedOctal(255), r"\377", ('3','7','7'));
+    fn test_all_escapable_decimal_to_decimal() {
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(000), r"\000", ('0','0','0'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(001), r"\001", ('0','0','1'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(002), r"\002", ('0','0','2'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(003), r"\003", ('0','0','3'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(004), r"\004", ('0','0','4'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(005), r"\005", ('0','0','5'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(006), r"\006", ('0','0','6'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(007), r"\007", ('0','0','7'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(008), r"\008", ('0','0','8'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(009), r"\009", ('0','0','9'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(010), r"\010", ('0','1','0'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(011), r"\011", ('0','1','1'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(012), r"\012", ('0','1','2'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(013), r"\013", ('0','1','3'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(014), r"\014", ('0','1','4'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(015), r"\015", ('0','1','5'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(016), r"\016", ('0','1','6'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(017), r"\017", ('0','1','7'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(018), r"\018", ('0','1','8'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(019), r"\019", ('0','1','9'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(020), r"\020", ('0','2','0'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(021), r"\021", ('0','2','1'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(022), r"\022", ('0','2','2'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(023), r"\023", ('0','2','3'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(024), r"\024", ('0','2','4'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(025), r"\025", ('0','2','5'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(026), r"\026", ('0','2','6'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(027), r"\027", ('0','2','7'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(028), r"\028", ('0','2','8'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(029), r"\029", ('0','2','9'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(030), r"\030", ('0','3','0'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(031), r"\031", ('0','3','1'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(032), r"\032", ('0','3','2'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(033), r"\033", ('0','3','3'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(034), r"\034", ('0','3','4'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(035), r"\035", ('0','3','5'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(036), r"\036", ('0','3','6'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(037), r"\037", ('0','3','7'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(038), r"\038", ('0','3','8'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(039), r"\039", ('0','3','9'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(040), r"\040", ('0','4','0'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(041), r"\041", ('0','4','1'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(042), r"\042", ('0','4','2'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(043), r"\043", ('0','4','3'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(044), r"\044", ('0','4','4'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(045), r"\045", ('0','4','5'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(046), r"\046", ('0','4','6'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(047), r"\047", ('0','4','7'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(048), r"\048", ('0','4','8'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(049), r"\049", ('0','4','9'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(050), r"\050", ('0','5','0'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(051), r"\051", ('0','5','1'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(052), r"\052", ('0','5','2'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(053), r"\053", ('0','5','3'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(054), r"\054", ('0','5','4'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(055), r"\055", ('0','5','5'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(056), r"\056", ('0','5','6'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(057), r"\057", ('0','5','7'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(058), r"\058", ('0','5','8'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(059), r"\059", ('0','5','9'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(060), r"\060", ('0','6','0'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(061), r"\061", ('0','6','1'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(062), r"\062", ('0','6','2'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(063), r"\063", ('0','6','3'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(064), r"\064", ('0','6','4'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(065), r"\065", ('0','6','5'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(066), r"\066", ('0','6','6'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(067), r"\067", ('0','6','7'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(068), r"\068", ('0','6','8'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(069), r"\069", ('0','6','9'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(070), r"\070", ('0','7','0'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(071), r"\071", ('0','7','1'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(072), r"\072", ('0','7','2'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(073), r"\073", ('0','7','3'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(074), r"\074", ('0','7','4'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(075), r"\075", ('0','7','5'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(076), r"\076", ('0','7','6'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(077), r"\077", ('0','7','7'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(078), r"\078", ('0','7','8'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(079), r"\079", ('0','7','9'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(080), r"\080", ('0','8','0'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(081), r"\081", ('0','8','1'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(082), r"\082", ('0','8','2'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(083), r"\083", ('0','8','3'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(084), r"\084", ('0','8','4'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(085), r"\085", ('0','8','5'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(086), r"\086", ('0','8','6'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(087), r"\087", ('0','8','7'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(088), r"\088", ('0','8','8'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(089), r"\089", ('0','8','9'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(090), r"\090", ('0','9','0'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(091), r"\091", ('0','9','1'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(092), r"\092", ('0','9','2'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(093), r"\093", ('0','9','3'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(094), r"\094", ('0','9','4'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(095), r"\095", ('0','9','5'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(096), r"\096", ('0','9','6'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(097), r"\097", ('0','9','7'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(098), r"\098", ('0','9','8'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(099), r"\099", ('0','9','9'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(100), r"\100", ('1','0','0'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(101), r"\101", ('1','0','1'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(102), r"\102", ('1','0','2'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(103), r"\103", ('1','0','3'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(104), r"\104", ('1','0','4'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(105), r"\105", ('1','0','5'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(106), r"\106", ('1','0','6'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(107), r"\107", ('1','0','7'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(108), r"\108", ('1','0','8'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(109), r"\109", ('1','0','9'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(110), r"\110", ('1','1','0'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(111), r"\111", ('1','1','1'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(112), r"\112", ('1','1','2'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(113), r"\113", ('1','1','3'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(114), r"\114", ('1','1','4'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(115), r"\115", ('1','1','5'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(116), r"\116", ('1','1','6'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(117), r"\117", ('1','1','7'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(118), r"\118", ('1','1','8'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(119), r"\119", ('1','1','9'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(120), r"\120", ('1','2','0'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(121), r"\121", ('1','2','1'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(122), r"\122", ('1','2','2'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(123), r"\123", ('1','2','3'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(124), r"\124", ('1','2','4'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(125), r"\125", ('1','2','5'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(126), r"\126", ('1','2','6'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(127), r"\127", ('1','2','7'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(128), r"\128", ('1','2','8'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(129), r"\129", ('1','2','9'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(130), r"\130", ('1','3','0'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(131), r"\131", ('1','3','1'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(132), r"\132", ('1','3','2'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(133), r"\133", ('1','3','3'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(134), r"\134", ('1','3','4'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(135), r"\135", ('1','3','5'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(136), r"\136", ('1','3','6'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(137), r"\137", ('1','3','7'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(138), r"\138", ('1','3','8'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(139), r"\139", ('1','3','9'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(140), r"\140", ('1','4','0'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(141), r"\141", ('1','4','1'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(142), r"\142", ('1','4','2'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(143), r"\143", ('1','4','3'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(144), r"\144", ('1','4','4'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(145), r"\145", ('1','4','5'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(146), r"\146", ('1','4','6'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(147), r"\147", ('1','4','7'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(148), r"\148", ('1','4','8'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(149), r"\149", ('1','4','9'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(150), r"\150", ('1','5','0'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(151), r"\151", ('1','5','1'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(152), r"\152", ('1','5','2'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(153), r"\153", ('1','5','3'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(154), r"\154", ('1','5','4'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(155), r"\155", ('1','5','5'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(156), r"\156", ('1','5','6'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(157), r"\157", ('1','5','7'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(158), r"\158", ('1','5','8'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(159), r"\159", ('1','5','9'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(160), r"\160", ('1','6','0'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(161), r"\161", ('1','6','1'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(162), r"\162", ('1','6','2'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(163), r"\163", ('1','6','3'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(164), r"\164", ('1','6','4'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(165), r"\165", ('1','6','5'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(166), r"\166", ('1','6','6'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(167), r"\167", ('1','6','7'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(168), r"\168", ('1','6','8'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(169), r"\169", ('1','6','9'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(170), r"\170", ('1','7','0'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(171), r"\171", ('1','7','1'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(172), r"\172", ('1','7','2'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(173), r"\173", ('1','7','3'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(174), r"\174", ('1','7','4'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(175), r"\175", ('1','7','5'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(176), r"\176", ('1','7','6'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(177), r"\177", ('1','7','7'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(178), r"\178", ('1','7','8'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(179), r"\179", ('1','7','9'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(180), r"\180", ('1','8','0'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(181), r"\181", ('1','8','1'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(182), r"\182", ('1','8','2'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(183), r"\183", ('1','8','3'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(184), r"\184", ('1','8','4'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(185), r"\185", ('1','8','5'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(186), r"\186", ('1','8','6'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(187), r"\187", ('1','8','7'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(188), r"\188", ('1','8','8'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(189), r"\189", ('1','8','9'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(190), r"\190", ('1','9','0'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(191), r"\191", ('1','9','1'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(192), r"\192", ('1','9','2'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(193), r"\193", ('1','9','3'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(194), r"\194", ('1','9','4'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(195), r"\195", ('1','9','5'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(196), r"\196", ('1','9','6'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(197), r"\197", ('1','9','7'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(198), r"\198", ('1','9','8'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(199), r"\199", ('1','9','9'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(200), r"\200", ('2','0','0'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(201), r"\201", ('2','0','1'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(202), r"\202", ('2','0','2'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(203), r"\203", ('2','0','3'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(204), r"\204", ('2','0','4'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(205), r"\205", ('2','0','5'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(206), r"\206", ('2','0','6'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(207), r"\207", ('2','0','7'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(208), r"\208", ('2','0','8'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(209), r"\209", ('2','0','9'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(210), r"\210", ('2','1','0'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(211), r"\211", ('2','1','1'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(212), r"\212", ('2','1','2'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(213), r"\213", ('2','1','3'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(214), r"\214", ('2','1','4'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(215), r"\215", ('2','1','5'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(216), r"\216", ('2','1','6'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(217), r"\217", ('2','1','7'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(218), r"\218", ('2','1','8'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(219), r"\219", ('2','1','9'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(220), r"\220", ('2','2','0'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(221), r"\221", ('2','2','1'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(222), r"\222", ('2','2','2'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(223), r"\223", ('2','2','3'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(224), r"\224", ('2','2','4'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(225), r"\225", ('2','2','5'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(226), r"\226", ('2','2','6'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(227), r"\227", ('2','2','7'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(228), r"\228", ('2','2','8'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(229), r"\229", ('2','2','9'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(230), r"\230", ('2','3','0'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(231), r"\231", ('2','3','1'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(232), r"\232", ('2','3','2'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(233), r"\233", ('2','3','3'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(234), r"\234", ('2','3','4'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(235), r"\235", ('2','3','5'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(236), r"\236", ('2','3','6'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(237), r"\237", ('2','3','7'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(238), r"\238", ('2','3','8'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(239), r"\239", ('2','3','9'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(240), r"\240", ('2','4','0'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(241), r"\241", ('2','4','1'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(242), r"\242", ('2','4','2'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(243), r"\243", ('2','4','3'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(244), r"\244", ('2','4','4'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(245), r"\245", ('2','4','5'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(246), r"\246", ('2','4','6'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(247), r"\247", ('2','4','7'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(248), r"\248", ('2','4','8'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(249), r"\249", ('2','4','9'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(250), r"\250", ('2','5','0'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(251), r"\251", ('2','5','1'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(252), r"\252", ('2','5','2'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(253), r"\253", ('2','5','3'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(254), r"\254", ('2','5','4'));
+        test_escapable_decimal_to_display_mapping(EscapableChar::EscapedDecimal(255), r"\255", ('2','5','5'));
+    }
+
+    #[test]
+    fn test_decimal_out_of_range() {
+        assert_eq!(decimal_to_ascii(b'2', b'5', b'6'), None);
+        assert_eq!(decimal_to_ascii(b'9', b'9', b'9'), None);
     }
 }
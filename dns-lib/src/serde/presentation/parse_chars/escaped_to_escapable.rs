@@ -2,7 +2,7 @@ use std::{error::Error, fmt::Display};
 
 use crate::types::ascii::{AsciiChar, constants::{ASCII_BACKSLASH, ASCII_ZERO, ASCII_NINE}};
 
-use super::char_token::{EscapableChar, octal_to_ascii};
+use super::char_token::{EscapableChar, decimal_to_ascii};
 
 #[derive(Clone, PartialEq, Eq, Hash, Debug)]
 pub enum ParseError {
@@ -19,6 +19,8 @@ pub enum ParseError {
 
     OneEscapedCharacterButThreeEscapedDigitsExpected{ char1: AsciiChar },
     TwoEscapedCharactersButThreeEscapedDigitsExpected{ char1: AsciiChar, char2: AsciiChar },
+
+    EscapedDecimalOutOfRange{ char1: AsciiChar, char2: AsciiChar, char3: AsciiChar },
 }
 impl Error for ParseError {}
 impl Display for ParseError {
@@ -37,13 +39,15 @@ impl Display for ParseError {
 
             Self::OneEscapedCharacterButThreeEscapedDigitsExpected{ char1 } => write!(f, "There was only one escaped character but three escaped digits were expected ('\\{char1}')"),
             Self::TwoEscapedCharactersButThreeEscapedDigitsExpected{ char1, char2 } => write!(f, "There were only two escaped characters but three escaped digits were expected ('\\{char1}{char2}')"),
+
+            Self::EscapedDecimalOutOfRange{ char1, char2, char3 } => write!(f, "The escaped decimal is larger than 255 ('\\{char1}{char2}{char3}')"),
         }
     }
 }
 
 /// Given a sequence of characters, finds escape sequences and turns them into raw ascii. Characters
-/// that were previously escaped will output as [`EscapableChar::EscapedAscii`] while octal
-/// sequences that were previously escaped will output as [`EscapableChar::EscapedOctal`]. All other
+/// that were previously escaped will output as [`EscapableChar::EscapedAscii`] while decimal
+/// sequences (`\DDD`) that were previously escaped will output as [`EscapableChar::EscapedDecimal`]. All other
 /// characters are output as raw ascii, represented by [`EscapableChar::Ascii`].
 pub struct EscapedToEscapableIter<T> where T: Iterator<Item = AsciiChar> {
     chars: T
@@ -81,7 +85,7 @@ impl<T> EscapedToEscapableIter<T> where T: Iterator<Item = AsciiChar> {
     fn next_after_escape(&mut self) -> Option<Result<EscapableChar, ParseError>> {
         match self.chars.next() {
             Some(digit @ ASCII_ZERO..=ASCII_NINE) => Self::next_after_escaped_digit(self, digit),
-            Some(character) => Some(Ok(EscapableChar::Ascii(character))),
+            Some(character) => Some(Ok(EscapableChar::EscapedAscii(character))),
             None => Some(Err(ParseError::TrailingEscapeCharacter)),
         }
     }
@@ -89,7 +93,10 @@ impl<T> EscapedToEscapableIter<T> where T: Iterator<Item = AsciiChar> {
     #[inline]
     fn next_after_escaped_digit(&mut self, first_char: AsciiChar) -> Option<Result<EscapableChar, ParseError>> {
         match (first_char, self.chars.next(), self.chars.next()) {
-            (ASCII_ZERO..=ASCII_NINE, Some(second_char @ ASCII_ZERO..=ASCII_NINE), Some(third_char @ ASCII_ZERO..=ASCII_NINE)) => Some(Ok(EscapableChar::EscapedOctal(octal_to_ascii(first_char, second_char, third_char)))),
+            (ASCII_ZERO..=ASCII_NINE, Some(second_char @ ASCII_ZERO..=ASCII_NINE), Some(third_char @ ASCII_ZERO..=ASCII_NINE)) => match decimal_to_ascii(first_char, second_char, third_char) {
+                Some(character) => Some(Ok(EscapableChar::EscapedDecimal(character))),
+                None => Some(Err(ParseError::EscapedDecimalOutOfRange{ char1: first_char, char2: second_char, char3: third_char })),
+            },
 
             (char1, Some(char2 @ ASCII_ZERO..=ASCII_NINE), Some(char3 @ ASCII_ZERO..=ASCII_NINE)) => Some(Err(ParseError::FirstEscapedCharacterNotADigit{ char1, char2, char3 })),
             (char1 @ ASCII_ZERO..=ASCII_NINE, Some(char2), Some(char3 @ ASCII_ZERO..=ASCII_NINE)) => Some(Err(ParseError::SecondEscapedCharacterNotADigit{ char1, char2, char3 })),
@@ -151,7 +158,10 @@ impl<T> EscapedCharsEnumerateIter<T> where T: Iterator<Item = (usize, AsciiChar)
     #[inline]
     fn next_after_escaped_digit(&mut self, first_char: AsciiChar) -> Option<Result<(usize, EscapableChar), ParseError>> {
         match (first_char, self.chars.next(), self.chars.next()) {
-            (ASCII_ZERO..=ASCII_NINE, Some((_, second_char @ ASCII_ZERO..=ASCII_NINE)), Some((third_index, third_char @ ASCII_ZERO..=ASCII_NINE))) => Some(Ok((third_index, EscapableChar::EscapedOctal(octal_to_ascii(first_char, second_char, third_char))))),
+            (ASCII_ZERO..=ASCII_NINE, Some((_, second_char @ ASCII_ZERO..=ASCII_NINE)), Some((third_index, third_char @ ASCII_ZERO..=ASCII_NINE))) => match decimal_to_ascii(first_char, second_char, third_char) {
+                Some(character) => Some(Ok((third_index, EscapableChar::EscapedDecimal(character)))),
+                None => Some(Err(ParseError::EscapedDecimalOutOfRange{ char1: first_char, char2: second_char, char3: third_char })),
+            },
 
             (char1, Some((_, char2 @ ASCII_ZERO..=ASCII_NINE)), Some((_, char3 @ ASCII_ZERO..=ASCII_NINE))) => Some(Err(ParseError::FirstEscapedCharacterNotADigit{ char1, char2, char3 })),
             (char1 @ ASCII_ZERO..=ASCII_NINE, Some((_, char2)), Some((_, char3 @ ASCII_ZERO..=ASCII_NINE))) => Some(Err(ParseError::SecondEscapedCharacterNotADigit{ char1, char2, char3 })),
@@ -3,7 +3,7 @@ use crate::types::ascii::{AsciiChar, constants::ASCII_BACKSLASH, is_ascii_contro
 use super::char_token::EscapableChar;
 
 /// Converts from an internal sequence of raw ascii characters, with no escape sequences, and
-/// converts it into printable ascii, with escaped backslashes and octal sequences for non-printable
+/// converts it into printable ascii, with escaped backslashes and decimal sequences for non-printable
 /// characters.
 pub struct NonEscapedIntoEscapedIter<T> where T: Iterator<Item = AsciiChar> {
     chars: T
@@ -31,8 +31,8 @@ impl<T> Iterator for NonEscapedIntoEscapedIter<T> where T: Iterator<Item = Ascii
         match self.chars.next() {
             None => None,
             Some(ASCII_BACKSLASH) => Some(EscapableChar::EscapedAscii(ASCII_BACKSLASH)),
-            Some(character) if is_ascii_control(&character) => Some(EscapableChar::EscapedOctal(character)),
-            Some(character @ 128..) => Some(EscapableChar::EscapedOctal(character)),
+            Some(character) if is_ascii_control(&character) => Some(EscapableChar::EscapedDecimal(character)),
+            Some(character @ 128..) => Some(EscapableChar::EscapedDecimal(character)),
             Some(character) => Some(EscapableChar::Ascii(character)),
         }
     }
@@ -57,38 +57,38 @@ mod non_escaped_to_escaped_tests {
 
     #[test]
     fn characters_map_correctly() {
-        test_character_escape_mapping(000, EscapableChar::EscapedOctal(000));
-        test_character_escape_mapping(001, EscapableChar::EscapedOctal(001));
-        test_character_escape_mapping(002, EscapableChar::EscapedOctal(002));
-        test_character_escape_mapping(003, EscapableChar::EscapedOctal(003));
-        test_character_escape_mapping(004, EscapableChar::EscapedOctal(004));
-        test_character_escape_mapping(005, EscapableChar::EscapedOctal(005));
-        test_character_escape_mapping(006, EscapableChar::EscapedOctal(006));
-        test_character_escape_mapping(007, EscapableChar::EscapedOctal(007));
-        test_character_escape_mapping(008, EscapableChar::EscapedOctal(008));
-        test_character_escape_mapping(009, EscapableChar::EscapedOctal(009));
-        test_character_escape_mapping(010, EscapableChar::EscapedOctal(010));
-        test_character_escape_mapping(011, EscapableChar::EscapedOctal(011));
-        test_character_escape_mapping(012, EscapableChar::EscapedOctal(012));
-        test_character_escape_mapping(013, EscapableChar::EscapedOctal(013));
-        test_character_escape_mapping(014, EscapableChar::EscapedOctal(014));
-        test_character_escape_mapping(015, EscapableChar::EscapedOctal(015));
-        test_character_escape_mapping(016, EscapableChar::EscapedOctal(016));
-        test_character_escape_mapping(017, EscapableChar::EscapedOctal(017));
-        test_character_escape_mapping(018, EscapableChar::EscapedOctal(018));
-        test_character_escape_mapping(019, EscapableChar::EscapedOctal(019));
-        test_character_escape_mapping(020, EscapableChar::EscapedOctal(020));
-        test_character_escape_mapping(021, EscapableChar::EscapedOctal(021));
-        test_character_escape_mapping(022, EscapableChar::EscapedOctal(022));
-        test_character_escape_mapping(023, EscapableChar::EscapedOctal(023));
-        test_character_escape_mapping(024, EscapableChar::EscapedOctal(024));
-        test_character_escape_mapping(025, EscapableChar::EscapedOctal(025));
-        test_character_escape_mapping(026, EscapableChar::EscapedOctal(026));
-        test_character_escape_mapping(027, EscapableChar::EscapedOctal(027));
-        test_character_escape_mapping(028, EscapableChar::EscapedOctal(028));
-        test_character_escape_mapping(029, EscapableChar::EscapedOctal(029));
-        test_character_escape_mapping(030, EscapableChar::EscapedOctal(030));
-        test_character_escape_mapping(031, EscapableChar::EscapedOctal(031));
+        test_character_escape_mapping(000, EscapableChar::EscapedDecimal(000));
+        test_character_escape_mapping(001, EscapableChar::EscapedDecimal(001));
+        test_character_escape_mapping(002, EscapableChar::EscapedDecimal(002));
+        test_character_escape_mapping(003, EscapableChar::EscapedDecimal(003));
+        test_character_escape_mapping(004, EscapableChar::EscapedDecimal(004));
+        test_character_escape_mapping(005, EscapableChar::EscapedDecimal(005));
+        test_character_escape_mapping(006, EscapableChar::EscapedDecimal(006));
+        test_character_escape_mapping(007, EscapableChar::EscapedDecimal(007));
+        test_character_escape_mapping(008, EscapableChar::EscapedDecimal(008));
+        test_character_escape_mapping(009, EscapableChar::EscapedDecimal(009));
+        test_character_escape_mapping(010, EscapableChar::EscapedDecimal(010));
+        test_character_escape_mapping(011, EscapableChar::EscapedDecimal(011));
+        test_character_escape_mapping(012, EscapableChar::EscapedDecimal(012));
+        test_character_escape_mapping(013, EscapableChar::EscapedDecimal(013));
+        test_character_escape_mapping(014, EscapableChar::EscapedDecimal(014));
+        test_character_escape_mapping(015, EscapableChar::EscapedDecimal(015));
+        test_character_escape_mapping(016, EscapableChar::EscapedDecimal(016));
+        test_character_escape_mapping(017, EscapableChar::EscapedDecimal(017));
+        test_character_escape_mapping(018, EscapableChar::EscapedDecimal(018));
+        test_character_escape_mapping(019, EscapableChar::EscapedDecimal(019));
+        test_character_escape_mapping(020, EscapableChar::EscapedDecimal(020));
+        test_character_escape_mapping(021, EscapableChar::EscapedDecimal(021));
+        test_character_escape_mapping(022, EscapableChar::EscapedDecimal(022));
+        test_character_escape_mapping(023, EscapableChar::EscapedDecimal(023));
+        test_character_escape_mapping(024, EscapableChar::EscapedDecimal(024));
+        test_character_escape_mapping(025, EscapableChar::EscapedDecimal(025));
+        test_character_escape_mapping(026, EscapableChar::EscapedDecimal(026));
+        test_character_escape_mapping(027, EscapableChar::EscapedDecimal(027));
+        test_character_escape_mapping(028, EscapableChar::EscapedDecimal(028));
+        test_character_escape_mapping(029, EscapableChar::EscapedDecimal(029));
+        test_character_escape_mapping(030, EscapableChar::EscapedDecimal(030));
+        test_character_escape_mapping(031, EscapableChar::EscapedDecimal(031));
         test_character_escape_mapping(032, EscapableChar::Ascii(032));
         test_character_escape_mapping(033, EscapableChar::Ascii(033));
         test_character_escape_mapping(034, EscapableChar::Ascii(034));
@@ -184,134 +184,134 @@ mod non_escaped_to_escaped_tests {
         test_character_escape_mapping(124, EscapableChar::Ascii(124));
         test_character_escape_mapping(125, EscapableChar::Ascii(125));
         test_character_escape_mapping(126, EscapableChar::Ascii(126));
-        test_character_escape_mapping(127, EscapableChar::EscapedOctal(127));
-        test_character_escape_mapping(128, EscapableChar::EscapedOctal(128));
-        test_character_escape_mapping(129, EscapableChar::EscapedOctal(129));
-        test_character_escape_mapping(130, EscapableChar::EscapedOctal(130));
-        test_character_escape_mapping(131, EscapableChar::EscapedOctal(131));
-        test_character_escape_mapping(132, EscapableChar::EscapedOctal(132));
-        test_character_escape_mapping(133, EscapableChar::EscapedOctal(133));
-        test_character_escape_mapping(134, EscapableChar::EscapedOctal(134));
-        test_character_escape_mapping(135, EscapableChar::EscapedOctal(135));
-        test_character_escape_mapping(136, EscapableChar::EscapedOctal(136));
-        test_character_escape_mapping(137, EscapableChar::EscapedOctal(137));
-        test_character_escape_mapping(138, EscapableChar::EscapedOctal(138));
-        test_character_escape_mapping(139, EscapableChar::EscapedOctal(139));
-        test_character_escape_mapping(140, EscapableChar::EscapedOctal(140));
-        test_character_escape_mapping(141, EscapableChar::EscapedOctal(141));
-        test_character_escape_mapping(142, EscapableChar::EscapedOctal(142));
-        test_character_escape_mapping(143, EscapableChar::EscapedOctal(143));
-        test_character_escape_mapping(144, EscapableChar::EscapedOctal(144));
-        test_character_escape_mapping(145, EscapableChar::EscapedOctal(145));
-        test_character_escape_mapping(146, EscapableChar::EscapedOctal(146));
-        test_character_escape_mapping(147, EscapableChar::EscapedOctal(147));
-        test_character_escape_mapping(148, EscapableChar::EscapedOctal(148));
-        test_character_escape_mapping(149, EscapableChar::EscapedOctal(149));
-        test_character_escape_mapping(150, EscapableChar::EscapedOctal(150));
-        test_character_escape_mapping(151, EscapableChar::EscapedOctal(151));
-        test_character_escape_mapping(152, EscapableChar::EscapedOctal(152));
-        test_character_escape_mapping(153, EscapableChar::EscapedOctal(153));
-        test_character_escape_mapping(154, EscapableChar::EscapedOctal(154));
-        test_character_escape_mapping(155, EscapableChar::EscapedOctal(155));
-        test_character_escape_mapping(156, EscapableChar::EscapedOctal(156));
-        test_character_escape_mapping(157, EscapableChar::EscapedOctal(157));
-        test_character_escape_mapping(158, EscapableChar::EscapedOctal(158));
-        test_character_escape_mapping(159, EscapableChar::EscapedOctal(159));
-        test_character_escape_mapping(160, EscapableChar::EscapedOctal(160));
-        test_character_escape_mapping(161, EscapableChar::EscapedOctal(161));
-        test_character_escape_mapping(162, EscapableChar::EscapedOctal(162));
-        test_character_escape_mapping(163, EscapableChar::EscapedOctal(163));
-        test_character_escape_mapping(164, EscapableChar::EscapedOctal(164));
-        test_character_escape_mapping(165, EscapableChar::EscapedOctal(165));
-        test_character_escape_mapping(166, EscapableChar::EscapedOctal(166));
-        test_character_escape_mapping(167, EscapableChar::EscapedOctal(167));
-        test_character_escape_mapping(168, EscapableChar::EscapedOctal(168));
-        test_character_escape_mapping(169, EscapableChar::EscapedOctal(169));
-        test_character_escape_mapping(170, EscapableChar::EscapedOctal(170));
-        test_character_escape_mapping(171, EscapableChar::EscapedOctal(171));
-        test_character_escape_mapping(172, EscapableChar::EscapedOctal(172));
-        test_character_escape_mapping(173, EscapableChar::EscapedOctal(173));
-        test_character_escape_mapping(174, EscapableChar::EscapedOctal(174));
-        test_character_escape_mapping(175, EscapableChar::EscapedOctal(175));
-        test_character_escape_mapping(176, EscapableChar::EscapedOctal(176));
-        test_character_escape_mapping(177, EscapableChar::EscapedOctal(177));
-        test_character_escape_mapping(178, EscapableChar::EscapedOctal(178));
-        test_character_escape_mapping(179, EscapableChar::EscapedOctal(179));
-        test_character_escape_mapping(180, EscapableChar::EscapedOctal(180));
-        test_character_escape_mapping(181, EscapableChar::EscapedOctal(181));
-        test_character_escape_mapping(182, EscapableChar::EscapedOctal(182));
-        test_character_escape_mapping(183, EscapableChar::EscapedOctal(183));
-        test_character_escape_mapping(184, EscapableChar::EscapedOctal(184));
-        test_character_escape_mapping(185, EscapableChar::EscapedOctal(185));
-        test_character_escape_mapping(186, EscapableChar::EscapedOctal(186));
-        test_character_escape_mapping(187, EscapableChar::EscapedOctal(187));
-        test_character_escape_mapping(188, EscapableChar::EscapedOctal(188));
-        test_character_escape_mapping(189, EscapableChar::EscapedOctal(189));
-        test_character_escape_mapping(190, EscapableChar::EscapedOctal(190));
-        test_character_escape_mapping(191, EscapableChar::EscapedOctal(191));
-        test_character_escape_mapping(192, EscapableChar::EscapedOctal(192));
-        test_character_escape_mapping(193, EscapableChar::EscapedOctal(193));
-        test_character_escape_mapping(194, EscapableChar::EscapedOctal(194));
-        test_character_escape_mapping(195, EscapableChar::EscapedOctal(195));
-        test_character_escape_mapping(196, EscapableChar::EscapedOctal(196));
-        test_character_escape_mapping(197, EscapableChar::EscapedOctal(197));
-        test_character_escape_mapping(198, EscapableChar::EscapedOctal(198));
-        test_character_escape_mapping(199, EscapableChar::EscapedOctal(199));
-        test_character_escape_mapping(200, EscapableChar::EscapedOctal(200));
-        test_character_escape_mapping(201, EscapableChar::EscapedOctal(201));
-        test_character_escape_mapping(202, EscapableChar::EscapedOctal(202));
-        test_character_escape_mapping(203, EscapableChar::EscapedOctal(203));
-        test_character_escape_mapping(204, EscapableChar::EscapedOctal(204));
-        test_character_escape_mapping(205, EscapableChar::EscapedOctal(205));
-        test_character_escape_mapping(206, EscapableChar::EscapedOctal(206));
-        test_character_escape_mapping(207, EscapableChar::EscapedOctal(207));
-        test_character_escape_mapping(208, EscapableChar::EscapedOctal(208));
-        test_character_escape_mapping(209, EscapableChar::EscapedOctal(209));
-        test_character_escape_mapping(210, EscapableChar::EscapedOctal(210));
-        test_character_escape_mapping(211, EscapableChar::EscapedOctal(211));
-        test_character_escape_mapping(212, EscapableChar::EscapedOctal(212));
-        test_character_escape_mapping(213, EscapableChar::EscapedOctal(213));
-        test_character_escape_mapping(214, EscapableChar::EscapedOctal(214));
-        test_character_escape_mapping(215, EscapableChar::EscapedOctal(215));
-        test_character_escape_mapping(216, EscapableChar::EscapedOctal(216));
-        test_character_escape_mapping(217, EscapableChar::EscapedOctal(217));
-        test_character_escape_mapping(218, EscapableChar::EscapedOctal(218));
-        test_character_escape_mapping(219, EscapableChar::EscapedOctal(219));
-        test_character_escape_mapping(220, EscapableChar::EscapedOctal(220));
-        test_character_escape_mapping(221, EscapableChar::EscapedOctal(221));
-        test_character_escape_mapping(222, EscapableChar::EscapedOctal(222));
-        test_character_escape_mapping(223, EscapableChar::EscapedOctal(223));
-        test_character_escape_mapping(224, EscapableChar::EscapedOctal(224));
-        test_character_escape_mapping(225, EscapableChar::EscapedOctal(225));
-        test_character_escape_mapping(226, EscapableChar::EscapedOctal(226));
-        test_character_escape_mapping(227, EscapableChar::EscapedOctal(227));
-        test_character_escape_mapping(228, EscapableChar::EscapedOctal(228));
-        test_character_escape_mapping(229, EscapableChar::EscapedOctal(229));
-        test_character_escape_mapping(230, EscapableChar::EscapedOctal(230));
-        test_character_escape_mapping(231, EscapableChar::EscapedOctal(231));
-        test_character_escape_mapping(232, EscapableChar::EscapedOctal(232));
-        test_character_escape_mapping(233, EscapableChar::EscapedOctal(233));
-        test_character_escape_mapping(234, EscapableChar::EscapedOctal(234));
-        test_character_escape_mapping(235, EscapableChar::EscapedOctal(235));
-        test_character_escape_mapping(236, EscapableChar::EscapedOctal(236));
-        test_character_escape_mapping(237, EscapableChar::EscapedOctal(237));
-        test_character_escape_mapping(238, EscapableChar::EscapedOctal(238));
-        test_character_escape_mapping(239, EscapableChar::EscapedOctal(239));
-        test_character_escape_mapping(240, EscapableChar::EscapedOctal(240));
-        test_character_escape_mapping(241, EscapableChar::EscapedOctal(241));
-        test_character_escape_mapping(242, EscapableChar::EscapedOctal(242));
-        test_character_escape_mapping(243, EscapableChar::EscapedOctal(243));
-        test_character_escape_mapping(244, EscapableChar::EscapedOctal(244));
-        test_character_escape_mapping(245, EscapableChar::EscapedOctal(245));
-        test_character_escape_mapping(246, EscapableChar::EscapedOctal(246));
-        test_character_escape_mapping(247, EscapableChar::EscapedOctal(247));
-        test_character_escape_mapping(248, EscapableChar::EscapedOctal(248));
-        test_character_escape_mapping(249, EscapableChar::EscapedOctal(249));
-        test_character_escape_mapping(250, EscapableChar::EscapedOctal(250));
-        test_character_escape_mapping(251, EscapableChar::EscapedOctal(251));
-        test_character_escape_mapping(252, EscapableChar::EscapedOctal(252));
-        test_character_escape_mapping(253, EscapableChar::EscapedOctal(253));
-        test_character_escape_mapping(254, EscapableChar::EscapedOctal(254));
-        test_character_escape_mapping(255, EscapableChar::EscapedOctal(255));
+        test_character_escape_mapping(127, EscapableChar::EscapedDecimal(127));
+        test_character_escape_mapping(128, EscapableChar::EscapedDecimal(128));
+        test_character_escape_mapping(129, EscapableChar::EscapedDecimal(129));
+        test_character_escape_mapping(130, EscapableChar::EscapedDecimal(130));
+        test_character_escape_mapping(131, EscapableChar::EscapedDecimal(131));
+        test_character_escape_mapping(132, EscapableChar::EscapedDecimal(132));
+        test_character_escape_mapping(133, EscapableChar::EscapedDecimal(133));
+        test_character_escape_mapping(134, EscapableChar::EscapedDecimal(134));
+        test_character_escape_mapping(135, EscapableChar::EscapedDecimal(135));
+        test_character_escape_mapping(136, EscapableChar::EscapedDecimal(136));
+        test_character_escape_mapping(137, EscapableChar::EscapedDecimal(137));
+        test_character_escape_mapping(138, EscapableChar::EscapedDecimal(138));
+        test_character_escape_mapping(139, EscapableChar::EscapedDecimal(139));
+        test_character_escape_mapping(140, EscapableChar::EscapedDecimal(140));
+        test_character_escape_mapping(141, EscapableChar::EscapedDecimal(141));
+        test_character_escape_mapping(142, EscapableChar::EscapedDecimal(142));
+        test_character_escape_mapping(143, EscapableChar::EscapedDecimal(143));
+        test_character_escape_mapping(144, EscapableChar::EscapedDecimal(144));
+        test_character_escape_mapping(145, EscapableChar::EscapedDecimal(145));
+        test_character_escape_mapping(146, EscapableChar::EscapedDecimal(146));
+        test_character_escape_mapping(147, EscapableChar::EscapedDecimal(147));
+        test_character_escape_mapping(148, EscapableChar::EscapedDecimal(148));
+        test_character_escape_mapping(149, EscapableChar::EscapedDecimal(149));
+        test_character_escape_mapping(150, EscapableChar::EscapedDecimal(150));
+        test_character_escape_mapping(151, EscapableChar::EscapedDecimal(151));
+        test_character_escape_mapping(152, EscapableChar::EscapedDecimal(152));
+        test_character_escape_mapping(153, EscapableChar::EscapedDecimal(153));
+        test_character_escape_mapping(154, EscapableChar::EscapedDecimal(154));
+        test_character_escape_mapping(155, EscapableChar::EscapedDecimal(155));
+        test_character_escape_mapping(156, EscapableChar::EscapedDecimal(156));
+        test_character_escape_mapping(157, EscapableChar::EscapedDecimal(157));
+        test_character_escape_mapping(158, EscapableChar::EscapedDecimal(158));
+        test_character_escape_mapping(159, EscapableChar::EscapedDecimal(159));
+        test_character_escape_mapping(160, EscapableChar::EscapedDecimal(160));
+        test_character_escape_mapping(161, EscapableChar::EscapedDecimal(161));
+        test_character_escape_mapping(162, EscapableChar::EscapedDecimal(162));
+        test_character_escape_mapping(163, EscapableChar::EscapedDecimal(163));
+        test_character_escape_mapping(164, EscapableChar::EscapedDecimal(164));
+        test_character_escape_mapping(165, EscapableChar::EscapedDecimal(165));
+        test_character_escape_mapping(166, EscapableChar::EscapedDecimal(166));
+        test_character_escape_mapping(167, EscapableChar::EscapedDecimal(167));
+        test_character_escape_mapping(168, EscapableChar::EscapedDecimal(168));
+        test_character_escape_mapping(169, EscapableChar::EscapedDecimal(169));
+        test_character_escape_mapping(170, EscapableChar::EscapedDecimal(170));
+        test_character_escape_mapping(171, EscapableChar::EscapedDecimal(171));
+        test_character_escape_mapping(172, EscapableChar::EscapedDecimal(172));
+        test_character_escape_mapping(173, EscapableChar::EscapedDecimal(173));
+        test_character_escape_mapping(174, EscapableChar::EscapedDecimal(174));
+        test_character_escape_mapping(175, EscapableChar::EscapedDecimal(175));
+        test_character_escape_mapping(176, EscapableChar::EscapedDecimal(176));
+        test_character_escape_mapping(177, EscapableChar::EscapedDecimal(177));
+        test_character_escape_mapping(178, EscapableChar::EscapedDecimal(178));
+        test_character_escape_mapping(179, EscapableChar::EscapedDecimal(179));
+        test_character_escape_mapping(180, EscapableChar::EscapedDecimal(180));
+        test_character_escape_mapping(181, EscapableChar::EscapedDecimal(181));
+        test_character_escape_mapping(182, EscapableChar::EscapedDecimal(182));
+        test_character_escape_mapping(183, EscapableChar::EscapedDecimal(183));
+        test_character_escape_mapping(184, EscapableChar::EscapedDecimal(184));
+        test_character_escape_mapping(185, EscapableChar::EscapedDecimal(185));
+        test_character_escape_mapping(186, EscapableChar::EscapedDecimal(186));
+        test_character_escape_mapping(187, EscapableChar::EscapedDecimal(187));
+        test_character_escape_mapping(188, EscapableChar::EscapedDecimal(188));
+        test_character_escape_mapping(189, EscapableChar::EscapedDecimal(189));
+        test_character_escape_mapping(190, EscapableChar::EscapedDecimal(190));
+        test_character_escape_mapping(191, EscapableChar::EscapedDecimal(191));
+        test_character_escape_mapping(192, EscapableChar::EscapedDecimal(192));
+        test_character_escape_mapping(193, EscapableChar::EscapedDecimal(193));
+        test_character_escape_mapping(194, EscapableChar::EscapedDecimal(194));
+        test_character_escape_mapping(195, EscapableChar::EscapedDecimal(195));
+        test_character_escape_mapping(196, EscapableChar::EscapedDecimal(196));
+        test_character_escape_mapping(197, EscapableChar::EscapedDecimal(197));
+        test_character_escape_mapping(198, EscapableChar::EscapedDecimal(198));
+        test_character_escape_mapping(199, EscapableChar::EscapedDecimal(199));
+        test_character_escape_mapping(200, EscapableChar::EscapedDecimal(200));
+        test_character_escape_mapping(201, EscapableChar::EscapedDecimal(201));
+        test_character_escape_mapping(202, EscapableChar::EscapedDecimal(202));
+        test_character_escape_mapping(203, EscapableChar::EscapedDecimal(203));
+        test_character_escape_mapping(204, EscapableChar::EscapedDecimal(204));
+        test_character_escape_mapping(205, EscapableChar::EscapedDecimal(205));
+        test_character_escape_mapping(206, EscapableChar::EscapedDecimal(206));
+        test_character_escape_mapping(207, EscapableChar::EscapedDecimal(207));
+        test_character_escape_mapping(208, EscapableChar::EscapedDecimal(208));
+        test_character_escape_mapping(209, EscapableChar::EscapedDecimal(209));
+        test_character_escape_mapping(210, EscapableChar::EscapedDecimal(210));
+        test_character_escape_mapping(211, EscapableChar::EscapedDecimal(211));
+        test_character_escape_mapping(212, EscapableChar::EscapedDecimal(212));
+        test_character_escape_mapping(213, EscapableChar::EscapedDecimal(213));
+        test_character_escape_mapping(214, EscapableChar::EscapedDecimal(214));
+        test_character_escape_mapping(215, EscapableChar::EscapedDecimal(215));
+        test_character_escape_mapping(216, EscapableChar::EscapedDecimal(216));
+        test_character_escape_mapping(217, EscapableChar::EscapedDecimal(217));
+        test_character_escape_mapping(218, EscapableChar::EscapedDecimal(218));
+        test_character_escape_mapping(219, EscapableChar::EscapedDecimal(219));
+        test_character_escape_mapping(220, EscapableChar::EscapedDecimal(220));
+        test_character_escape_mapping(221, EscapableChar::EscapedDecimal(221));
+        test_character_escape_mapping(222, EscapableChar::EscapedDecimal(222));
+        test_character_escape_mapping(223, EscapableChar::EscapedDecimal(223));
+        test_character_escape_mapping(224, EscapableChar::EscapedDecimal(224));
+        test_character_escape_mapping(225, EscapableChar::EscapedDecimal(225));
+        test_character_escape_mapping(226, EscapableChar::EscapedDecimal(226));
+        test_character_escape_mapping(227, EscapableChar::EscapedDecimal(227));
+        test_character_escape_mapping(228, EscapableChar::EscapedDecimal(228));
+        test_character_escape_mapping(229, EscapableChar::EscapedDecimal(229));
+        test_character_escape_mapping(230, EscapableChar::EscapedDecimal(230));
+        test_character_escape_mapping(231, EscapableChar::EscapedDecimal(231));
+        test_character_escape_mapping(232, EscapableChar::EscapedDecimal(232));
+        test_character_escape_mapping(233, EscapableChar::EscapedDecimal(233));
+        test_character_escape_mapping(234, EscapableChar::EscapedDecimal(234));
+        test_character_escape_mapping(235, EscapableChar::EscapedDecimal(235));
+        test_character_escape_mapping(236, EscapableChar::EscapedDecimal(236));
+        test_character_escape_mapping(237, EscapableChar::EscapedDecimal(237));
+        test_character_escape_mapping(238, EscapableChar::EscapedDecimal(238));
+        test_character_escape_mapping(239, EscapableChar::EscapedDecimal(239));
+        test_character_escape_mapping(240, EscapableChar::EscapedDecimal(240));
+        test_character_escape_mapping(241, EscapableChar::EscapedDecimal(241));
+        test_character_escape_mapping(242, EscapableChar::EscapedDecimal(242));
+        test_character_escape_mapping(243, EscapableChar::EscapedDecimal(243));
+        test_character_escape_mapping(244, EscapableChar::EscapedDecimal(244));
+        test_character_escape_mapping(245, EscapableChar::EscapedDecimal(245));
+        test_character_escape_mapping(246, EscapableChar::EscapedDecimal(246));
+        test_character_escape_mapping(247, EscapableChar::EscapedDecimal(247));
+        test_character_escape_mapping(248, EscapableChar::EscapedDecimal(248));
+        test_character_escape_mapping(249, EscapableChar::EscapedDecimal(249));
+        test_character_escape_mapping(250, EscapableChar::EscapedDecimal(250));
+        test_character_escape_mapping(251, EscapableChar::EscapedDecimal(251));
+        test_character_escape_mapping(252, EscapableChar::EscapedDecimal(252));
+        test_character_escape_mapping(253, EscapableChar::EscapedDecimal(253));
+        test_character_escape_mapping(254, EscapableChar::EscapedDecimal(254));
+        test_character_escape_mapping(255, EscapableChar::EscapedDecimal(255));
     }
 }
@@ -1,10 +1,143 @@
-use std::net::{Ipv4Addr, Ipv6Addr};
+use std::{fmt::{self, Display, Write}, net::{Ipv4Addr, Ipv6Addr}};
 
 use mac_address::MacAddress;
 
 /// https://datatracker.ietf.org/doc/html/rfc1035#section-5
 pub trait ToPresentation {
     fn to_presentation_format(&self, out_buffer: &mut Vec<String>);
+
+    /// Writes the presentation format tokens directly to the writer instead of collecting them
+    /// into `String`s. The default implementation falls back on `to_presentation_format()` so
+    /// types only need to override it to avoid the intermediate allocations.
+    #[inline]
+    fn write_presentation_tokens<W: Write + ?Sized>(&self, out: &mut PresentationWriter<'_, W>) -> fmt::Result {
+        let mut buffer = Vec::new();
+        self.to_presentation_format(&mut buffer);
+        buffer.iter().try_for_each(|token| out.write_token(token))
+    }
+
+    /// Writes the presentation format to `out`, with tokens separated by tabs.
+    #[inline]
+    fn write_presentation(&self, out: &mut impl Write) -> fmt::Result {
+        self.write_presentation_tokens(&mut PresentationWriter::new(out))
+    }
+
+    /// Explains the fields that are hard to read in the presentation format, such as the key tag
+    /// of a DNSKEY or the dates of an RRSIG. Most types have nothing to explain.
+    #[inline]
+    fn presentation_comment(&self) -> Option<String> {
+        None
+    }
+
+    /// Writes the presentation format to `out`, with tokens separated by tabs, and then anything
+    /// else the options ask for.
+    fn write_presentation_with_options(&self, out: &mut impl Write, options: &PresentationOptions) -> fmt::Result {
+        self.write_presentation(out)?;
+        match self.presentation_comment() {
+            Some(comment) if options.annotate => write!(out, "\t; {comment}"),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Controls what is written alongside the presentation format.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Default)]
+pub struct PresentationOptions {
+    /// Follow each record with a comment explaining it (see
+    /// `ToPresentation::presentation_comment()`), like `dig` and `named-checkzone` do for DNSSEC
+    /// records. The comments are ignored when the output is read back in.
+    pub annotate: bool,
+}
+
+impl PresentationOptions {
+    /// Options that annotate records with comments.
+    #[inline]
+    pub const fn annotated() -> Self {
+        Self { annotate: true }
+    }
+}
+
+/// A number of seconds since the Unix epoch, displayed as a UTC date and time such as
+/// "2026-10-17 09:30:00 UTC".
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct UtcTimestamp(pub u64);
+
+impl Display for UtcTimestamp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        const SECONDS_PER_DAY: u64 = 86_400;
+        let days = self.0 / SECONDS_PER_DAY;
+        let seconds = self.0 % SECONDS_PER_DAY;
+
+        // Converts the days since the epoch into a date in the proleptic Gregorian calendar,
+        // counting in 400 year eras that start on March 1st so that leap days come last.
+        // https://howardhinnant.github.io/date_algorithms.html#civil_from_days
+        let days = days + 719_468;
+        let era = days / 146_097;
+        let day_of_era = days % 146_097;
+        let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+        let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+        let shifted_month = (5 * day_of_year + 2) / 153;
+        let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+        let month = if shifted_month < 10 { shifted_month + 3 } else { shifted_month - 9 };
+        let year = year_of_era + era * 400 + u64::from(month <= 2);
+
+        write!(f, "{year:04}-{month:02}-{day:02} {:02}:{:02}:{:02} UTC", seconds / 3600, (seconds / 60) % 60, seconds % 60)
+    }
+}
+
+/// Wraps a `fmt::Write` target and inserts the separator between presentation tokens.
+pub struct PresentationWriter<'a, W: Write + ?Sized> {
+    out: &'a mut W,
+    separator: char,
+    first_token: bool,
+}
+
+impl<'a, W: Write + ?Sized> PresentationWriter<'a, W> {
+    /// Creates a writer that separates tokens with tabs, the same as the `Display`
+    /// implementation for resource records.
+    #[inline]
+    pub fn new(out: &'a mut W) -> Self {
+        Self::with_separator(out, '\t')
+    }
+
+    #[inline]
+    pub fn with_separator(out: &'a mut W, separator: char) -> Self {
+        Self { out, separator, first_token: true }
+    }
+
+    /// Writes the separator (unless this is the first token) and returns the underlying writer so
+    /// that a single token can be written in multiple parts.
+    #[inline]
+    pub fn start_token(&mut self) -> Result<&mut W, fmt::Error> {
+        if !self.first_token {
+            self.out.write_char(self.separator)?;
+        }
+        self.first_token = false;
+        Ok(self.out)
+    }
+
+    #[inline]
+    pub fn write_token(&mut self, token: &str) -> fmt::Result {
+        self.start_token()?.write_str(token)
+    }
+
+    #[inline]
+    pub fn write_display(&mut self, token: &impl Display) -> fmt::Result {
+        write!(self.start_token()?, "{token}")
+    }
+
+    /// Ends the current line. The next token will not be preceded by a separator.
+    #[inline]
+    pub fn end_line(&mut self) -> fmt::Result {
+        self.first_token = true;
+        self.out.write_char('\n')
+    }
+
+    /// Gets the underlying writer, for writing text that is not a token (such as comments).
+    #[inline]
+    pub fn inner(&mut self) -> &mut W {
+        self.out
+    }
 }
 
 // #################### BUILT-IN PRIMITIVE TYPES ####################
@@ -16,6 +149,11 @@ macro_rules! std_to_token_impl {
             fn to_presentation_format(&self, out_buffer: &mut Vec<String>) {
                 out_buffer.push(self.to_string())
             }
+
+            #[inline]
+            fn write_presentation_tokens<W: Write + ?Sized>(&self, out: &mut PresentationWriter<'_, W>) -> fmt::Result {
+                out.write_display(self)
+            }
         }
     }
 }
@@ -43,6 +181,11 @@ macro_rules! ux_to_token_impl {
             fn to_presentation_format(&self, out_buffer: &mut Vec<String>) {
                 out_buffer.push(self.to_string())
             }
+
+            #[inline]
+            fn write_presentation_tokens<W: Write + ?Sized>(&self, out: &mut PresentationWriter<'_, W>) -> fmt::Result {
+                out.write_display(self)
+            }
         }
     }
 }
@@ -300,3 +443,37 @@ ux_to_token_impl!(i127);
 std_to_token_impl!(Ipv4Addr);
 std_to_token_impl!(Ipv6Addr);
 std_to_token_impl!(MacAddress);
+
+// #################### COLLECTIONS ####################
+
+/// `None` is written as no tokens at all, so it can only be used for trailing fields.
+impl<T: ToPresentation> ToPresentation for Option<T> {
+    #[inline]
+    fn to_presentation_format(&self, out_buffer: &mut Vec<String>) {
+        if let Some(item) = self {
+            item.to_presentation_format(out_buffer);
+        }
+    }
+
+    #[inline]
+    fn write_presentation_tokens<W: Write + ?Sized>(&self, out: &mut PresentationWriter<'_, W>) -> fmt::Result {
+        match self {
+            Some(item) => item.write_presentation_tokens(out),
+            None => Ok(()),
+        }
+    }
+}
+
+impl<T: ToPresentation> ToPresentation for Vec<T> {
+    #[inline]
+    fn to_presentation_format(&self, out_buffer: &mut Vec<String>) {
+        for item in self {
+            item.to_presentation_format(out_buffer);
+        }
+    }
+
+    #[inline]
+    fn write_presentation_tokens<W: Write + ?Sized>(&self, out: &mut PresentationWriter<'_, W>) -> fmt::Result {
+        self.iter().try_for_each(|item| item.write_presentation_tokens(out))
+    }
+}
@@ -1,5 +1,6 @@
 mod regex;
 pub mod errors;
+pub mod span;
 
 mod raw_literals;
 mod raw_entries;
@@ -11,9 +11,9 @@ use super::{raw_literals::{RawLiteral, RawLiteralIter}, errors::TokenizerError};
 /// - [RawItem::Separator] is used to represent a non-empty sequence of tab and/or space characters.
 #[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
 pub enum RawItem<'a> {
-    /// A sequence of ascii characters, including escaped characters, and octal sequences.
+    /// A sequence of ascii characters, including escaped characters, and decimal sequences.
     Text(&'a str),
-    /// A sequence of ascii characters, including escaped characters, and octal sequences. The
+    /// A sequence of ascii characters, including escaped characters, and decimal sequences. The
     /// sequence does not have special meaning (e.g. '@' does not get read as the origin)
     QuotedText(&'a str),
     /// Either a single newline character or a return carriage followed by a newline character.
@@ -58,11 +58,22 @@ impl<'a> Into<&'a str> for &RawItem<'a> {
 /// tokens in a single entry could span multiple lines.
 #[derive(Clone, PartialEq, Eq, Hash, Debug)]
 pub struct RawEntry<'a> {
-    raw_items: Vec<RawItem<'a>>
+    raw_items: Vec<RawItem<'a>>,
+    comments: Vec<&'a str>,
+    /// The byte offsets of the start and end of the items in the feed.
+    span: (usize, usize),
 }
 
 impl<'a> RawEntry<'a> {
     pub fn as_slice(&self) -> &[RawItem<'a>] { self.raw_items.as_slice() }
+
+    /// The text of the comments on the lines that make up this entry, without the leading `;` or
+    /// surrounding whitespace.
+    pub fn comments(&self) -> &[&'a str] { self.comments.as_slice() }
+
+    /// The byte offsets of the start and end of the entry in the feed, not including comments or
+    /// the trailing newline.
+    pub fn span(&self) -> (usize, usize) { self.span }
 }
 
 impl<'a> Display for RawEntry<'a> {
@@ -79,13 +90,42 @@ impl<'a> Display for RawEntry<'a> {
 /// basic entries. The output is an iterator of [RawLiteralEntry], where each [RawLiteralEntry]
 /// represents something like a raw resource record.
 pub struct RawEntryIter<'a> {
-    raw_literal_iter: RawLiteralIter<'a>
+    raw_literal_iter: RawLiteralIter<'a>,
+    /// The byte offsets of the start and end of the text that caused the last error.
+    error_span: (usize, usize),
 }
 
 impl<'a> RawEntryIter<'a> {
     #[inline]
     pub fn new(feed: &'a str) -> Self {
-        Self { raw_literal_iter: RawLiteralIter::new(feed) }
+        Self { raw_literal_iter: RawLiteralIter::new(feed), error_span: (0, 0) }
+    }
+
+    /// The byte offsets of the start and end of the entry that the last error occurred in.
+    #[inline]
+    pub fn error_span(&self) -> (usize, usize) {
+        self.error_span
+    }
+
+    /// Skips the rest of an erroneous entry, so that the next entry starts on the next line that
+    /// is not within a set of parenthesis. `depth` is the number of parenthesis that are open.
+    fn skip_entry(&mut self, mut depth: usize) {
+        loop {
+            match (self.raw_literal_iter.next(), depth) {
+                (Some(Ok(RawLiteral::Text("("))), _) => depth += 1,
+                (Some(Ok(RawLiteral::Text(")"))), _) => depth = depth.saturating_sub(1),
+                (Some(Ok(RawLiteral::NewLine(_))), 0) => return,
+                (None, _) => return,
+                _ => (),
+            }
+        }
+    }
+
+    #[inline]
+    fn error(&mut self, error: TokenizerError<'a>, start: Option<usize>, error_start: usize, depth: usize) -> Option<Result<RawEntry<'a>, TokenizerError<'a>>> {
+        self.error_span = (start.unwrap_or(error_start), self.raw_literal_iter.offset());
+        self.skip_entry(depth);
+        Some(Err(error))
     }
 }
 
@@ -95,41 +135,55 @@ impl<'a> Iterator for RawEntryIter<'a> {
     #[inline]
     fn next(&mut self) -> Option<Self::Item> {
         let mut entry_items = Vec::new();
+        let mut comments = Vec::new();
         let mut ignore_new_line = false;
+        let mut start = None;
+        let mut end = self.raw_literal_iter.offset();
         loop {
-            match (self.raw_literal_iter.next(), ignore_new_line) {
+            let literal_start = self.raw_literal_iter.offset();
+            let literal = self.raw_literal_iter.next();
+            if let Some(Ok(RawLiteral::Text(_) | RawLiteral::QuotedText(_))) = literal {
+                start.get_or_insert(literal_start);
+                end = self.raw_literal_iter.offset();
+            }
+            match (literal, ignore_new_line) {
                 // Open parenthesis is used to indicate that newlines should be ignored
-                (Some(Ok(RawLiteral::Text("("))), true) => return Some(Err(TokenizerError::NestedOpenParenthesis)),
+                (Some(Ok(RawLiteral::Text("("))), true) => return self.error(TokenizerError::NestedOpenParenthesis, start, literal_start, 2),
                 (Some(Ok(RawLiteral::Text("("))), false) => ignore_new_line = true,
 
                 // Closing parenthesis is used to end the indication that newlines should be ignored
                 (Some(Ok(RawLiteral::Text(")"))), true) => ignore_new_line = false,
-                (Some(Ok(RawLiteral::Text(")"))), false) => return Some(Err(TokenizerError::UnopenedClosingParenthesis)),
+                (Some(Ok(RawLiteral::Text(")"))), false) => return self.error(TokenizerError::UnopenedClosingParenthesis, start, literal_start, 0),
 
                 // Any text literals that are not a part of a comment should be included as part
                 // of the entry
                 (Some(Ok(RawLiteral::Text(token_str))), _) => entry_items.push(RawItem::Text(token_str)),
                 (Some(Ok(RawLiteral::QuotedText(token_str))), _) => entry_items.push(RawItem::QuotedText(token_str)),
 
-                // Comments have no meaning
-                (Some(Ok(RawLiteral::Comment(_))), _) => (),
+                // Comments have no meaning but are kept so that they can be written back out
+                (Some(Ok(RawLiteral::Comment(comment))), _) => comments.push(comment.strip_prefix(';').unwrap_or(comment).trim()),
 
                 // Separators are removed at this step. We should only care about the text
                 // literals from this point onwards. The only time they matter is if they are
                 // the first token.
-                (Some(Ok(RawLiteral::Separator(token_str))), _) if entry_items.is_empty() => entry_items.push(RawItem::Separator(token_str)),
+                (Some(Ok(RawLiteral::Separator(token_str))), _) if entry_items.is_empty() => {
+                    start.get_or_insert(literal_start);
+                    entry_items.push(RawItem::Separator(token_str));
+                },
                 (Some(Ok(RawLiteral::Separator(_))), _) => (),
 
                 (Some(Ok(RawLiteral::NewLine(_))), true) => (),
                 (Some(Ok(RawLiteral::NewLine(_))), false) => break,
 
-                (None, true) => return Some(Err(TokenizerError::NoClosingParenthesis)),
-                (None, false) => return None,
+                (None, true) => return self.error(TokenizerError::NoClosingParenthesis, start, literal_start, 1),
+                (None, false) if entry_items.is_empty() && comments.is_empty() => return None,
+                (None, false) => break,
 
-                (Some(Err(error)), _) => return Some(Err(error)),
+                (Some(Err(error)), _) => return self.error(error, start, literal_start, ignore_new_line as usize),
             }
         }
 
-        return Some(Ok(RawEntry { raw_items: entry_items }));
+        let start = start.unwrap_or(end);
+        return Some(Ok(RawEntry { raw_items: entry_items, comments, span: (start, end) }));
     }
 }
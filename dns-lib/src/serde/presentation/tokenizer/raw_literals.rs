@@ -4,13 +4,13 @@ use super::{errors::TokenizerError, regex::{REGEX_CHARACTER_STR_UNQUOTED, REGEX_
 
 #[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
 pub enum RawLiteral<'a> {
-    /// A non-empty contiguous set of ascii characters, including escaped characters, and octal
+    /// A non-empty contiguous set of ascii characters, including escaped characters, and decimal
     /// sequences. Certain characters cannot be included unless escaped, such as spaces & tabs
     /// (indicate token separators), semicolons (indicate comments), newline & carriage (indicate
     /// line separators), double-quotes (indicate quoted strings), open & close parenthesis
     /// (indicate multi-line records).
     Text(&'a str),
-    /// A contiguous set of ascii characters, including escaped characters, and octal sequences, all
+    /// A contiguous set of ascii characters, including escaped characters, and decimal sequences, all
     /// enclosed by a pair of double-quotes. Inside the quotations, all characters are legal except
     /// for non-escaped double-quotations and backslashes. This sequence CAN be empty.
     QuotedText(&'a str),
@@ -65,13 +65,27 @@ impl<'a> Into<&'a str> for &RawLiteral<'a> {
 /// explained in the descriptions of the tokens: [RawLiteral::Text], [RawLiteral::QuotedText],
 /// [RawLiteral::Separator], [RawLiteral::NewLine], & [RawLiteral::Comment].
 pub struct RawLiteralIter<'a> {
-    feed: &'a str
+    feed: &'a str,
+    /// The number of bytes of the original feed that have been consumed.
+    offset: usize,
 }
 
 impl<'a> RawLiteralIter<'a> {
     #[inline]
     pub fn new(feed: &'a str) -> Self {
-        RawLiteralIter { feed }
+        RawLiteralIter { feed, offset: 0 }
+    }
+
+    /// The byte offset into the original feed of the next literal.
+    #[inline]
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    #[inline]
+    fn consume(&mut self, length: usize) {
+        self.feed = &self.feed[length..];
+        self.offset += length;
     }
 }
 
@@ -88,38 +102,44 @@ impl<'a> Iterator for RawLiteralIter<'a> {
         // Case 1: The next token is a string literal
         if let Some(next_literal) = REGEX_CHARACTER_STR_UNQUOTED.find(self.feed) {
             let result = RawLiteral::Text(&self.feed[..next_literal.end()]);
-            self.feed = &self.feed[next_literal.end()..];
+            self.consume(next_literal.end());
             return Some(Ok(result));
         }
         if let Some(next_literal) = REGEX_CHARACTER_STR_QUOTED.find(self.feed) {
             // Exclude quotation marks from actual token string
             let result = RawLiteral::QuotedText(&self.feed[1..(next_literal.end()-1)]);
-            self.feed = &self.feed[next_literal.end()..];
+            self.consume(next_literal.end());
             return Some(Ok(result));
         }
 
         // Case 2: The next token is a separator
         if let Some(next_separator) = REGEX_SEPARATOR.find(self.feed) {
             let result = RawLiteral::Separator(&self.feed[..(next_separator.end())]);
-            self.feed = &self.feed[next_separator.end()..];
+            self.consume(next_separator.end());
             return Some(Ok(result));
         }
 
         // Case 3: The next token is a new line
         if let Some(next_new_line) = REGEX_NEW_LINE.find(self.feed) {
             let result = RawLiteral::NewLine(&self.feed[..next_new_line.end()]);
-            self.feed = &self.feed[next_new_line.end()..];
+            self.consume(next_new_line.end());
             return Some(Ok(result));
         }
 
         // Case 4: The next token is a comment
         if let Some(next_comment) = REGEX_COMMENT.find(self.feed) {
             let result = RawLiteral::Comment(&self.feed[..next_comment.end()]);
-            self.feed = &self.feed[next_comment.end()..];
+            self.consume(next_comment.end());
             return Some(Ok(result));
         }
 
-        return Some(Err(TokenizerError::UnknownToken(self.feed)));
+        // The rest of the line cannot be tokenized. Skip it so that the next call can carry on
+        // from the next line.
+        let unknown_length = self.feed.find(['\r', '\n']).unwrap_or(self.feed.len()).max(1);
+        let unknown_length = (unknown_length..=self.feed.len()).find(|length| self.feed.is_char_boundary(*length)).unwrap_or(self.feed.len());
+        let unknown = &self.feed[..unknown_length];
+        self.consume(unknown_length);
+        return Some(Err(TokenizerError::UnknownToken(unknown)));
     }
 }
 
@@ -15,7 +15,8 @@ lazy_static! {
 }
 
 const RCLASS_STR: &str = r"\A((IN)|(CS)|(CH)|(HS)|(NONE)|(ANY)|(CLASS[[:digit:]]+))\z";
-const RTYPE_STR: &str = r"\A(([A-Z]+)|(TYPE[[:digit:]]+))\z";
+// Mnemonics start with a letter but may contain digits and hyphens, such as X25 and NSAP-PTR.
+const RTYPE_STR: &str = r"\A(([A-Z][A-Z0-9\-]*)|(TYPE[[:digit:]]+))\z";
 const TTL_STR: &str = r"\A([[:digit:]]+)\z";
 
 lazy_static! {
@@ -0,0 +1,92 @@
+use std::fmt::Display;
+
+/// The location of some text within the feed given to the tokenizer. The line and column are
+/// 1-based and the column counts characters, not bytes.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct Span {
+    /// The byte offset of the start of the text.
+    pub start: usize,
+    /// The byte offset just past the end of the text.
+    pub end: usize,
+    pub line: usize,
+    pub column: usize,
+}
+
+impl Span {
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.end - self.start
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.start == self.end
+    }
+}
+
+impl Display for Span {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "line {}, column {}", self.line, self.column)
+    }
+}
+
+/// Converts byte offsets into the feed to spans with line and column numbers.
+pub(crate) struct LineIndex<'a> {
+    feed: &'a str,
+    /// The byte offset of the start of each line.
+    line_starts: Vec<usize>,
+}
+
+impl<'a> LineIndex<'a> {
+    pub fn new(feed: &'a str) -> Self {
+        let line_starts = std::iter::once(0)
+            .chain(feed.match_indices('\n').map(|(index, _)| index + 1))
+            .collect();
+        Self { feed, line_starts }
+    }
+
+    /// Gets the span of the bytes from `start` to `end`. Both offsets are clamped to the feed.
+    pub fn span(&self, start: usize, end: usize) -> Span {
+        let end = end.min(self.feed.len());
+        let start = start.min(end);
+        let line = self.line_starts.partition_point(|line_start| *line_start <= start);
+        let line_start = self.line_starts[line - 1];
+        let column = self.feed.get(line_start..start)
+            .map_or(start - line_start, |line_prefix| line_prefix.chars().count())
+            + 1;
+        Span { start, end, line, column }
+    }
+
+    /// Gets the span of `text` if it is a slice of the feed.
+    pub fn span_of(&self, text: &str) -> Option<Span> {
+        let feed_start = self.feed.as_ptr() as usize;
+        let text_start = text.as_ptr() as usize;
+        let start = text_start.checked_sub(feed_start)?;
+        let end = start.checked_add(text.len())?;
+        if end <= self.feed.len() {
+            Some(self.span(start, end))
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod span_tests {
+    use super::{LineIndex, Span};
+
+    #[test]
+    fn lines_and_columns() {
+        let feed = "a 1\nbé c\n\nd";
+        let index = LineIndex::new(feed);
+        assert_eq!(index.span(0, 1), Span { start: 0, end: 1, line: 1, column: 1 });
+        assert_eq!(index.span(2, 3), Span { start: 2, end: 3, line: 1, column: 3 });
+        assert_eq!(index.span(4, 7), Span { start: 4, end: 7, line: 2, column: 1 });
+        // 'é' takes two bytes but is a single column.
+        assert_eq!(index.span(8, 9), Span { start: 8, end: 9, line: 2, column: 4 });
+        assert_eq!(index.span(11, 12), Span { start: 11, end: 12, line: 4, column: 1 });
+
+        assert_eq!(index.span_of(&feed[8..9]), Some(index.span(8, 9)));
+        assert_eq!(index.span_of("elsewhere"), None);
+    }
+}
@@ -91,13 +91,28 @@ impl<'a> Display for StringLiteral<'a> {
 /// determine what types of values that entry contains. However, it does not validate for
 /// correctness of most of those values. They are still stored as raw strings at this point.
 pub struct EntryIter<'a> {
-    token_iter: RawEntryIter<'a>
+    token_iter: RawEntryIter<'a>,
+    comments: Vec<&'a str>,
+    span: (usize, usize),
 }
 
 impl<'a> EntryIter<'a> {
     #[inline]
     pub fn new(feed: &'a str) -> Self {
-        EntryIter { token_iter: RawEntryIter::new(feed) }
+        EntryIter { token_iter: RawEntryIter::new(feed), comments: Vec::new(), span: (0, 0) }
+    }
+
+    /// The byte offsets of the start and end of the last entry or error returned.
+    #[inline]
+    pub fn span(&self) -> (usize, usize) {
+        self.span
+    }
+
+    /// Takes the comments from every entry read since the last call, including blank entries that
+    /// only contain a comment.
+    #[inline]
+    pub fn take_comments(&mut self) -> Vec<&'a str> {
+        std::mem::take(&mut self.comments)
     }
 }
 
@@ -109,13 +124,19 @@ impl<'a> Iterator for EntryIter<'a> {
         loop {
             let entry_tokens = match self.token_iter.next() {
                 Some(Ok(entry_tokens)) => entry_tokens,
-                Some(Err(error)) => return Some(Err(error)),
+                Some(Err(error)) => {
+                    self.span = self.token_iter.error_span();
+                    return Some(Err(error));
+                },
                 None => return None,
             };
+            self.comments.extend_from_slice(entry_tokens.comments());
+            self.span = entry_tokens.span();
 
             match entry_tokens.as_slice() {
                 // <blank>[<comment>]
                 &[] => continue,    //< Skip entries that are empty
+                &[RawItem::Separator(_)] => continue,
 
                 // $ORIGIN <domain-name> [<comment>]
                 &[RawItem::Text("$ORIGIN"), RawItem::Text("@")] => return Some(Ok(
@@ -2,7 +2,7 @@ use std::fmt::Display;
 
 use crate::serde::presentation::tokenizer::token_entries::Entry;
 
-use super::{token_entries::{EntryIter, StringLiteral}, errors::TokenizerError};
+use super::{errors::TokenizerError, span::{LineIndex, Span}, token_entries::{EntryIter, StringLiteral}};
 
 const DEFAULT_DOMAIN_NAME: Option<&str> = None;
 const DEFAULT_TTL: Option<&str> = Some("86400");
@@ -67,6 +67,9 @@ pub struct Tokenizer<'a> {
     /// to be updated by the user.
     pub origin: Option<&'a str>,
     entry_iter: EntryIter<'a>,
+    /// The comments read while reading the last token.
+    comments: Vec<&'a str>,
+    line_index: LineIndex<'a>,
 }
 
 impl<'a> Tokenizer<'a> {
@@ -81,9 +84,36 @@ impl<'a> Tokenizer<'a> {
             ttl_directive: None,
             origin: None,
             entry_iter: EntryIter::new(feed),
+            comments: Vec::new(),
+            line_index: LineIndex::new(feed),
         }
     }
 
+    /// The span of the entry that the last token or error came from. If an entry spans multiple
+    /// lines, the span covers all of them.
+    #[inline]
+    pub fn span(&self) -> Span {
+        let (start, end) = self.entry_iter.span();
+        self.line_index.span(start, end)
+    }
+
+    /// The span of any text returned by the tokenizer, such as the RDATA of a record. Returns
+    /// `None` if the text is not part of the feed, which is the case for default values and the
+    /// origin if it was set using `origin` instead of a `$ORIGIN` directive.
+    #[inline]
+    pub fn span_of(&self, text: &str) -> Option<Span> {
+        self.line_index.span_of(text)
+    }
+
+    /// The comments that were read along with the last token returned by the iterator. This
+    /// includes comment lines (and comments on directives) that came before the token and any
+    /// comments on the lines of the token itself. Once the iterator is exhausted, these are the
+    /// comments at the end of the feed.
+    #[inline]
+    pub fn comments(&self) -> &[&'a str] {
+        &self.comments
+    }
+
     #[inline]
     fn default_ttl(&self) -> Option<&'a str> {
         match (self.ttl_directive, self.last_ttl) {
@@ -113,6 +143,15 @@ impl<'a> Iterator for Tokenizer<'a> {
 
     #[inline]
     fn next(&mut self) -> Option<Self::Item> {
+        let token = self.next_token();
+        self.comments = self.entry_iter.take_comments();
+        token
+    }
+}
+
+impl<'a> Tokenizer<'a> {
+    #[inline]
+    fn next_token(&mut self) -> Option<Result<Token<'a>, TokenizerError<'a>>> {
         loop {
             match self.entry_iter.next() {
                 None => return None,
@@ -1,8 +1,8 @@
-use std::path::Path;
+use std::{error::Error, fmt::Display, path::Path};
 
-use crate::{resource_record::resource_record::ResourceRecord, types::c_domain_name::CDomainName};
+use crate::{resource_record::{resource_record::ResourceRecord, rtype::RType}, types::c_domain_name::CDomainName};
 
-use super::{tokenizer::tokenizer::{Tokenizer, Token}, errors::TokenizedRecordError, from_presentation::FromPresentation};
+use super::{tokenizer::{span::Span, tokenizer::{Tokenizer, Token}}, errors::TokenizedRecordError, from_presentation::FromPresentation};
 
 #[derive(Clone, PartialEq, Hash, Debug)]
 pub enum ZoneToken<'a> {
@@ -10,20 +10,109 @@ pub enum ZoneToken<'a> {
     Include{ file_path: &'a Path, domain_name: Option<CDomainName> }
 }
 
+/// An error found while reading a zone file, along with where in the file it was found.
+#[derive(Debug)]
+pub struct ZoneDiagnostic<'a> {
+    pub span: Span,
+    pub error: TokenizedRecordError<'a>,
+}
+impl<'a> Error for ZoneDiagnostic<'a> {}
+impl<'a> Display for ZoneDiagnostic<'a> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.span, self.error)
+    }
+}
+
+/// A record that was read without any errors but that should probably not be published, such as
+/// one of a type that is obsolete. The record is still returned by the reader.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ZoneWarning {
+    pub span: Span,
+    pub name: CDomainName,
+    pub rtype: RType,
+    pub reason: &'static str,
+}
+impl Display for ZoneWarning {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {} record for '{}': {}", self.span, self.rtype, self.name, self.reason)
+    }
+}
+
+/// Reads resource records out of a zone file. If an entry is invalid, the error is returned and
+/// the reader carries on from the next entry, so the rest of the file can still be read.
 pub struct ZoneFileReader<'a> {
-    tokenizer: Tokenizer<'a>
+    tokenizer: Tokenizer<'a>,
+    warnings: Vec<ZoneWarning>,
 }
 
 impl<'a> ZoneFileReader<'a> {
     #[inline]
     pub fn new(feed: &'a str) -> Self {
-        Self { tokenizer: Tokenizer::new(feed) }
+        Self { tokenizer: Tokenizer::new(feed), warnings: Vec::new() }
     }
 
     #[inline]
     pub fn set_origin(&mut self, origin: &'a str) {
         self.tokenizer.origin = Some(origin);
     }
+
+    /// The comments that were read along with the last token. Comment lines are attributed to the
+    /// token that follows them. Once the reader is exhausted, these are the comments at the end of
+    /// the file.
+    #[inline]
+    pub fn comments(&self) -> &[&'a str] {
+        self.tokenizer.comments()
+    }
+
+    /// The span of the entry that the last token or error came from.
+    #[inline]
+    pub fn span(&self) -> Span {
+        self.tokenizer.span()
+    }
+
+    /// The span of any text in the file, such as one of the tokens that caused an error.
+    #[inline]
+    pub fn span_of(&self, text: &str) -> Option<Span> {
+        self.tokenizer.span_of(text)
+    }
+
+    /// The warnings for the records that have been read so far. Records of deprecated types, such
+    /// as MD or WKS, are read and returned like any other but are also reported here.
+    #[inline]
+    pub fn warnings(&self) -> &[ZoneWarning] {
+        &self.warnings
+    }
+
+    /// Converts the reader into an iterator whose errors include the span of the entry they were
+    /// found in.
+    #[inline]
+    pub fn diagnosed(mut self) -> impl Iterator<Item = Result<ZoneToken<'a>, ZoneDiagnostic<'a>>> {
+        std::iter::from_fn(move || match self.next()? {
+            Ok(token) => Some(Ok(token)),
+            Err(error) => Some(Err(ZoneDiagnostic { span: self.span(), error })),
+        })
+    }
+
+    /// Reads every entry in the file. Invalid entries are skipped and reported as diagnostics.
+    #[inline]
+    pub fn read_all(self) -> (Vec<ZoneToken<'a>>, Vec<ZoneDiagnostic<'a>>) {
+        let (tokens, diagnostics, _) = self.read_all_with_warnings();
+        (tokens, diagnostics)
+    }
+
+    /// Reads every entry in the file, like `read_all()`, and also returns the warnings for the
+    /// records that were read.
+    pub fn read_all_with_warnings(mut self) -> (Vec<ZoneToken<'a>>, Vec<ZoneDiagnostic<'a>>, Vec<ZoneWarning>) {
+        let mut tokens = Vec::new();
+        let mut diagnostics = Vec::new();
+        while let Some(token) = self.next() {
+            match token {
+                Ok(token) => tokens.push(token),
+                Err(error) => diagnostics.push(ZoneDiagnostic { span: self.span(), error }),
+            }
+        }
+        (tokens, diagnostics, self.warnings)
+    }
 }
 
 impl<'a> Iterator for ZoneFileReader<'a> {
@@ -38,7 +127,17 @@ impl<'a> Iterator for ZoneFileReader<'a> {
 
         match next_token {
             Token::ResourceRecord(record) => match ResourceRecord::from_tokenized_record(&record) {
-                Ok(record) => Some(Ok(ZoneToken::ResourceRecord(record))),
+                Ok(record) => {
+                    if let Some(reason) = record.get_rtype().deprecation() {
+                        self.warnings.push(ZoneWarning {
+                            span: self.span(),
+                            name: record.get_name().clone(),
+                            rtype: record.get_rtype(),
+                            reason,
+                        });
+                    }
+                    Some(Ok(ZoneToken::ResourceRecord(record)))
+                },
                 Err(error) => Some(Err(error)),
             },
             Token::Include { file_name, domain_name } => {
@@ -59,3 +158,76 @@ impl<'a> Iterator for ZoneFileReader<'a> {
 
     }
 }
+
+#[cfg(test)]
+mod zone_file_reader_tests {
+    use crate::{resource_record::rtype::RType, serde::presentation::{errors::TokenizedRecordError, tokenizer::errors::TokenizerError}};
+
+    use super::{ZoneFileReader, ZoneToken};
+
+    #[test]
+    fn recovers_after_bad_entries() {
+        let zone = "\
+$ORIGIN example.org.
+a.example.org. 300 IN A 192.0.2.1
+b.example.org. 300 IN A not-an-address
+c.example.org. 300 IN A ( 192.0.2.3
+  ( )
+d.example.org. 300 IN A 192.0.2.4
+) extra
+e.example.org. 300 IN A 192.0.2.5";
+        let (tokens, diagnostics) = ZoneFileReader::new(zone).read_all();
+
+        let names = tokens.iter()
+            .map(|token| match token {
+                ZoneToken::ResourceRecord(record) => record.get_name().to_string(),
+                ZoneToken::Include { .. } => panic!("unexpected $INCLUDE"),
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(names, vec!["a.example.org.", "e.example.org."]);
+
+        assert_eq!(diagnostics.len(), 2);
+        assert_eq!((diagnostics[0].span.line, diagnostics[0].span.column), (3, 1));
+        assert_eq!(&zone[diagnostics[0].span.start..diagnostics[0].span.end], "b.example.org. 300 IN A not-an-address");
+        // Everything up until the outer parenthesis is closed belongs to the bad entry, including
+        // the record on line 6.
+        assert!(matches!(diagnostics[1].error, TokenizedRecordError::TokenizerError(TokenizerError::NestedOpenParenthesis)));
+        assert_eq!((diagnostics[1].span.line, diagnostics[1].span.column), (4, 1));
+    }
+
+    #[test]
+    fn deprecated_types_round_trip_with_warnings() {
+        let zone = "\
+a.example.org.\t300\tIN\tMD\tmail.example.org.
+b.example.org.\t300\tIN\tX25\t311061700956
+c.example.org.\t300\tIN\tISDN\t150862028003217\t004
+d.example.org.\t300\tIN\tRT\t2\trelay.example.org.
+e.example.org.\t300\tIN\tNSAP\t0x47000580005A0000000001E133FFFFFF00016100
+f.example.org.\t300\tIN\tNSAP-PTR\thost.example.org.
+g.example.org.\t300\tIN\tA\t192.0.2.1
+";
+        let (tokens, diagnostics, warnings) = ZoneFileReader::new(zone).read_all_with_warnings();
+        assert!(diagnostics.is_empty(), "{diagnostics:?}");
+        let written = tokens.iter()
+            .map(|token| match token {
+                ZoneToken::ResourceRecord(record) => format!("{record}\n"),
+                ZoneToken::Include { .. } => panic!("unexpected $INCLUDE"),
+            })
+            .collect::<String>();
+        assert_eq!(written, zone);
+
+        let warned = warnings.iter().map(|warning| (warning.span.line, warning.rtype)).collect::<Vec<_>>();
+        assert_eq!(warned, vec![(1, RType::MD), (2, RType::X25), (3, RType::ISDN), (4, RType::RT), (5, RType::NSAP), (6, RType::NSAP_PTR)]);
+    }
+
+    #[test]
+    fn unknown_tokens_skip_line() {
+        let zone = "a.example.org. 300 IN TXT \"unterminated\nb.example.org. 300 IN A 192.0.2.2\n";
+        let mut reader = ZoneFileReader::new(zone);
+        assert!(reader.next().unwrap().is_err());
+        assert_eq!(reader.span().line, 1);
+        assert!(matches!(reader.next(), Some(Ok(ZoneToken::ResourceRecord(_)))));
+        assert_eq!(reader.span().line, 2);
+        assert!(reader.next().is_none());
+    }
+}
@@ -0,0 +1,303 @@
+use std::fmt::{self, Display, Write};
+
+use crate::{resource_record::{resource_record::ResourceRecord, time::Time}, types::{c_domain_name::{CDomainName, CmpDomainName}, label::Label}};
+
+use super::to_presentation::{PresentationOptions, PresentationWriter, ToPresentation};
+
+/// A record along with the comments that go with it, such as the comments read in alongside it by
+/// the `ZoneFileReader`.
+#[derive(Clone, PartialEq, Hash, Debug)]
+pub struct CommentedRecord {
+    pub record: ResourceRecord,
+    pub comments: Vec<String>,
+}
+
+impl CommentedRecord {
+    #[inline]
+    pub fn new(record: ResourceRecord, comments: Vec<String>) -> Self {
+        Self { record, comments }
+    }
+}
+
+impl From<ResourceRecord> for CommentedRecord {
+    #[inline]
+    fn from(record: ResourceRecord) -> Self {
+        Self { record, comments: Vec::new() }
+    }
+}
+
+/// A record that has been split up into the columns of a zone file.
+struct ZoneLine {
+    record: CommentedRecord,
+    /// The owner name, TTL, class, and type.
+    columns: [String; 4],
+    rdata: String,
+}
+
+impl ZoneLine {
+    fn new(record: CommentedRecord) -> Self {
+        let mut tokens = Vec::new();
+        record.record.to_presentation_format(&mut tokens);
+        let mut tokens = tokens.into_iter();
+        let columns = [(); 4].map(|_| tokens.next().unwrap_or_default());
+        let rdata = tokens.collect::<Vec<_>>().join(" ");
+        Self { record, columns, rdata }
+    }
+
+    /// Orders records by owner name in canonical order (RFC 4034 section 6.1), then by class,
+    /// type, and RDATA so that the same zone is always written the same way.
+    fn sort_key(&self) -> (Vec<Vec<u8>>, u16, u16, String) {
+        let record = &self.record.record;
+        (
+            record.get_name().case_insensitive_labels()
+                .rev()
+                .map(|label| label.octets().to_ascii_lowercase())
+                .collect(),
+            record.get_rclass().code(),
+            record.get_rtype().code(),
+            self.rdata.clone(),
+        )
+    }
+}
+
+/// Writes resource records as a zone file (RFC 1035 section 5) that can be read back in by the
+/// `ZoneFileReader`. Owner names are always written fully qualified so that the output does not
+/// depend on the `$ORIGIN` directive, which is only written for the benefit of human readers and
+/// other tools.
+pub struct ZoneFileWriter<'a, W: Write + ?Sized> {
+    writer: PresentationWriter<'a, W>,
+    options: PresentationOptions,
+}
+
+impl<'a, W: Write + ?Sized> ZoneFileWriter<'a, W> {
+    /// Creates the writer and writes the `$ORIGIN` and `$TTL` directives.
+    pub fn new(out: &'a mut W, origin: &CDomainName, default_ttl: Time) -> Result<Self, fmt::Error> {
+        let mut writer = PresentationWriter::new(out);
+        writer.write_token("$ORIGIN")?;
+        origin.write_presentation_tokens(&mut writer)?;
+        writer.end_line()?;
+        writer.write_token("$TTL")?;
+        default_ttl.write_presentation_tokens(&mut writer)?;
+        writer.end_line()?;
+        Ok(Self { writer, options: PresentationOptions::default() })
+    }
+
+    /// Sets the options that records are written with from here on.
+    #[inline]
+    pub fn with_options(mut self, options: PresentationOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// The comment that explains the record, if the options ask for one.
+    #[inline]
+    fn annotation(&self, record: &ResourceRecord) -> Option<String> {
+        self.options.annotate.then(|| record.presentation_comment()).flatten()
+    }
+
+    /// Writes a line containing only a comment.
+    #[inline]
+    pub fn write_comment(&mut self, comment: impl Display) -> fmt::Result {
+        write!(self.writer.inner(), "; {comment}")?;
+        self.writer.end_line()
+    }
+
+    /// Writes an entire zone in a stable layout that is easy to diff. Records are sorted, records
+    /// that belong to the same RRset are written next to each other, each owner name is separated
+    /// from the next by a blank line, and the columns are aligned with spaces. Comments are
+    /// written on the lines directly before the record they belong to.
+    pub fn write_zone(&mut self, records: impl IntoIterator<Item = impl Into<CommentedRecord>>) -> fmt::Result {
+        let mut lines = Vec::new();
+        let mut forbidden = Vec::new();
+        for record in records {
+            let record = record.into();
+            if record.record.get_rdata().presentation_allowed() {
+                lines.push(ZoneLine::new(record));
+            } else {
+                forbidden.push(record);
+            }
+        }
+        lines.sort_by_cached_key(ZoneLine::sort_key);
+
+        let mut widths = [0; 4];
+        for line in &lines {
+            for (width, column) in widths.iter_mut().zip(&line.columns) {
+                *width = (*width).max(column.len());
+            }
+        }
+
+        let mut previous_owner: Option<&CDomainName> = None;
+        for line in &lines {
+            let owner = line.record.record.get_name();
+            if previous_owner.is_some_and(|previous_owner| !previous_owner.matches(owner)) {
+                self.writer.end_line()?;
+            }
+            previous_owner = Some(owner);
+
+            for comment in &line.record.comments {
+                self.write_comment(comment)?;
+            }
+            let annotation = self.annotation(&line.record.record);
+            let out = self.writer.inner();
+            for (width, column) in widths.iter().zip(&line.columns) {
+                write!(out, "{column:width$} ")?;
+            }
+            out.write_str(&line.rdata)?;
+            if let Some(annotation) = annotation {
+                write!(out, " ; {annotation}")?;
+            }
+            self.writer.end_line()?;
+        }
+
+        for record in forbidden {
+            for comment in &record.comments {
+                self.write_comment(comment)?;
+            }
+            self.write_record(&record.record)?;
+        }
+        Ok(())
+    }
+
+    /// Writes the record on its own line. Records that do not have a presentation format (such as
+    /// OPT) cannot be loaded from a zone file so they are written as comments instead.
+    #[inline]
+    pub fn write_record(&mut self, record: &ResourceRecord) -> fmt::Result {
+        self.write_record_with_comment(record, None::<&str>)
+    }
+
+    /// Writes the record on its own line, followed by a comment. If the options ask for records
+    /// to be annotated, the annotation comes first.
+    pub fn write_record_with_comment(&mut self, record: &ResourceRecord, comment: Option<impl Display>) -> fmt::Result {
+        if record.get_rdata().presentation_allowed() {
+            let annotation = self.annotation(record);
+            record.write_presentation_tokens(&mut self.writer)?;
+            match (annotation, comment) {
+                (Some(annotation), Some(comment)) => write!(self.writer.inner(), "\t; {annotation}; {comment}")?,
+                (Some(annotation), None) => write!(self.writer.inner(), "\t; {annotation}")?,
+                (None, Some(comment)) => write!(self.writer.inner(), "\t; {comment}")?,
+                (None, None) => (),
+            }
+        } else {
+            write!(self.writer.inner(), "; {} {} record has no presentation format", record.get_name(), record.get_rtype())?;
+        }
+        self.writer.end_line()
+    }
+}
+
+#[cfg(test)]
+mod zone_file_writer_tests {
+    use std::net::Ipv4Addr;
+
+    use crate::{resource_record::{dnssec_alg::DnsSecAlgorithm, rclass::RClass, resource_record::{RecordData, ResourceRecord}, time::Time, types::{a::A, dnskey::DNSKEY}}, serde::presentation::{to_presentation::PresentationOptions, zone_file_reader::{ZoneFileReader, ZoneToken}}, types::{base64::Base64, c_domain_name::CDomainName}};
+
+    use super::{CommentedRecord, ZoneFileWriter};
+
+    fn read_commented_records(zone: &str) -> Vec<CommentedRecord> {
+        let mut reader = ZoneFileReader::new(zone);
+        let mut records = Vec::new();
+        while let Some(token) = reader.next() {
+            match token.unwrap() {
+                ZoneToken::ResourceRecord(record) => records.push(CommentedRecord::new(
+                    record,
+                    reader.comments().iter().map(|comment| comment.to_string()).collect(),
+                )),
+                ZoneToken::Include { .. } => panic!("unexpected $INCLUDE"),
+            }
+        }
+        records
+    }
+
+    fn write_zone(records: Vec<CommentedRecord>) -> String {
+        let mut zone = String::new();
+        let mut writer = ZoneFileWriter::new(&mut zone, &CDomainName::from_utf8("example.org.").unwrap(), Time::new(3600)).unwrap();
+        writer.write_zone(records).unwrap();
+        zone
+    }
+
+    #[test]
+    fn round_trip() {
+        let origin = CDomainName::from_utf8("example.org.").unwrap();
+        let records = vec![
+            ResourceRecord::new(CDomainName::from_utf8("example.org.").unwrap(), RClass::Internet, Time::new(300), RecordData::A(A::new(Ipv4Addr::new(192, 0, 2, 1)))),
+            ResourceRecord::new(CDomainName::from_utf8("www.example.org.").unwrap(), RClass::Internet, Time::new(60), RecordData::A(A::new(Ipv4Addr::new(192, 0, 2, 2)))),
+        ];
+
+        let mut zone = String::new();
+        let mut writer = ZoneFileWriter::new(&mut zone, &origin, Time::new(3600)).unwrap();
+        writer.write_comment("test zone").unwrap();
+        writer.write_record(&records[0]).unwrap();
+        writer.write_record_with_comment(&records[1], Some("expires in 60s")).unwrap();
+
+        assert!(zone.starts_with("$ORIGIN\texample.org.\n$TTL\t3600\n; test zone\n"));
+        assert!(zone.contains("\t; expires in 60s\n"));
+
+        let read_records = ZoneFileReader::new(&zone)
+            .map(|token| match token.unwrap() {
+                ZoneToken::ResourceRecord(record) => record,
+                ZoneToken::Include { .. } => panic!("unexpected $INCLUDE"),
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(read_records, records);
+    }
+
+    #[test]
+    fn stable_layout() {
+        let zone = "\
+$ORIGIN example.org.
+$TTL 3600
+; web server
+www.example.org.   60 IN A    192.0.2.2 ; primary
+@                 300 IN MX   10 mail.example.org.
+mail.example.org. 300 IN A    192.0.2.3
+www.example.org.   60 IN A    192.0.2.1
+@                 300 IN A    192.0.2.4
+; the end
+";
+        let records = read_commented_records(zone);
+        assert_eq!(records.len(), 5);
+        assert_eq!(records[0].comments, vec!["web server", "primary"]);
+
+        let written = write_zone(records.clone());
+        assert_eq!(written, "\
+$ORIGIN\texample.org.
+$TTL\t3600
+example.org.      300 IN A  192.0.2.4
+example.org.      300 IN MX 10 mail.example.org.
+
+mail.example.org. 300 IN A  192.0.2.3
+
+www.example.org.  60  IN A  192.0.2.1
+; web server
+; primary
+www.example.org.  60  IN A  192.0.2.2
+");
+
+        // Writing the zone that was just written does not change it.
+        let rewritten_records = read_commented_records(&written);
+        assert_eq!(rewritten_records.len(), records.len());
+        assert_eq!(write_zone(rewritten_records), written);
+    }
+
+    #[test]
+    fn annotations_are_read_back_as_comments() {
+        let key = Base64::from_utf8("AQPSKmynfzW4kyBv015MUG2DeIQ3Cbl+BBZH4b/0PY1kxkmvHjcZc8nokfzj31GajIQKY+5CptLr3buXA10hWqTkF7H6RfoRqXQeogmMHfpftf6zMv1LyBUgia7za6ZEzOJBOztyvhjL742iU/TpPSEDhm2SNKLijfUppn1UaNvv4w==").unwrap();
+        let records = vec![
+            ResourceRecord::new(CDomainName::from_utf8("example.org.").unwrap(), RClass::Internet, Time::new(300), RecordData::A(A::new(Ipv4Addr::new(192, 0, 2, 1)))),
+            ResourceRecord::new(CDomainName::from_utf8("example.org.").unwrap(), RClass::Internet, Time::new(300), RecordData::DNSKEY(DNSKEY::new(256, DnsSecAlgorithm::RsaSha1, key))),
+        ];
+
+        let mut zone = String::new();
+        let mut writer = ZoneFileWriter::new(&mut zone, &CDomainName::from_utf8("example.org.").unwrap(), Time::new(3600)).unwrap()
+            .with_options(PresentationOptions::annotated());
+        writer.write_zone(records.clone()).unwrap();
+        writer.write_record_with_comment(&records[1], Some("rolled 2026-10-01")).unwrap();
+
+        assert!(zone.contains("192.0.2.1\n"));
+        assert!(zone.contains("aNvv4w== ; ZSK; alg = RSASHA1; key id = 2642\n"));
+        assert!(zone.ends_with("aNvv4w==\t; ZSK; alg = RSASHA1; key id = 2642; rolled 2026-10-01\n"));
+
+        let read_records = read_commented_records(&zone);
+        assert_eq!(read_records.iter().map(|record| record.record.clone()).collect::<Vec<_>>(), vec![records[0].clone(), records[1].clone(), records[1].clone()]);
+        assert_eq!(read_records[1].comments, vec!["ZSK; alg = RSASHA1; key id = 2642"]);
+    }
+}
@@ -1,9 +1,33 @@
 use std::fmt::Debug;
 
-use crate::{serde::wire::write_wire::WriteWire, types::c_domain_name::CompressionMap};
+use crate::{query::{message::Message, question::Question}, resource_record::{rclass::RClass, rtype::RType}, serde::wire::{read_wire::ReadWire, write_wire::WriteWire}, types::c_domain_name::{CDomainName, CompressionMap}};
 
 use super::{to_wire::ToWire, from_wire::FromWire};
 
+/// A query for `www.example.org. IN A`, for tests that need a message to work on.
+pub(crate) fn example_query() -> Message {
+    Message::from(Question::new(CDomainName::from_utf8("www.example.org.").unwrap(), RType::A, RClass::Internet))
+}
+
+/// Serializes the input without compression and reads it back. Unlike
+/// `circular_serde_sanity_test`, the output is returned instead of being compared to the input,
+/// so that tests can check what the wire format keeps.
+pub(crate) fn circular_serde<T>(input: &T) -> T where T: ToWire + FromWire {
+    let wire = &mut [0_u8; u16::MAX as usize];
+    let mut wire = WriteWire::from_bytes(wire);
+    input.to_wire_format(&mut wire, &mut None).unwrap();
+    let mut wire = ReadWire::from_bytes(wire.current());
+    T::from_wire_format(&mut wire).unwrap()
+}
+
+/// The length of the input on the wire, or `None` if it cannot be serialized.
+pub(crate) fn wire_length<T>(input: &T, compression_map: &mut Option<CompressionMap>) -> Option<usize> where T: ToWire {
+    let wire = &mut [0_u8; u16::MAX as usize];
+    let mut wire = WriteWire::from_bytes(wire);
+    input.to_wire_format(&mut wire, compression_map).ok()?;
+    Some(wire.current_len())
+}
+
 pub(crate) fn circular_serde_sanity_test<T>(input: T) where T: Debug + ToWire + FromWire + PartialEq {
     // PART 1: No Compression Map
 
@@ -2,6 +2,7 @@ pub mod bytes;
 
 pub mod read_wire;
 pub mod write_wire;
+pub mod stream_write;
 
 pub mod to_wire;
 pub mod from_wire;
@@ -301,19 +301,21 @@ impl<'a> ReadWire<'a> {
         let current_len = self.current_len();
         let start = match range.start_bound() {
             Bound::Included(&start) => start,
-            Bound::Excluded(&start) => start.checked_add(1).expect("out of range"),
+            Bound::Excluded(&start) => start.checked_add(1).ok_or_else(|| ReadWireError::OverflowError(format!("range start out of range: {start}")))?,
             Bound::Unbounded => 0,
         };
         let end = match range.end_bound() {
-            Bound::Included(&end) => end.checked_add(1).expect("out of range"),
+            Bound::Included(&end) => end.checked_add(1).ok_or_else(|| ReadWireError::OverflowError(format!("range end out of range: {end}")))?,
             Bound::Excluded(&end) => end,
             Bound::Unbounded => current_len,
         };
+        // The range is usually derived from a length read off of the wire, so a malformed message
+        // must not be able to trigger a panic here.
         if end < start {
-            panic!("range start must not be greater than end: {:?} <= {:?}", start, end);
+            return Err(ReadWireError::OutOfBoundsError(format!("range start must not be greater than end: {start} <= {end}")));
         }
         if current_len < end {
-            panic!("range end out of bounds: {:?} <= {:?}", end, current_len);
+            return Err(ReadWireError::OutOfBoundsError(format!("range end out of bounds: {end} <= {current_len}")));
         }
 
         match visibility {
@@ -0,0 +1,119 @@
+use std::{error::Error, fmt::Display, io::{self, IoSlice}};
+
+use tokio::io::{AsyncWrite, AsyncWriteExt};
+
+use super::write_wire::WriteWireError;
+
+/// The largest message that can follow a two octet length.
+pub const MAX_STREAM_MESSAGE_SIZE: usize = u16::MAX as usize;
+
+#[derive(Debug)]
+pub enum StreamWriteError {
+    Serialization(WriteWireError),
+    Io(io::Error),
+}
+impl Error for StreamWriteError {}
+impl Display for StreamWriteError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Serialization(error) => write!(f, "{error}"),
+            Self::Io(error) => write!(f, "Stream Write IO Error: {error}"),
+        }
+    }
+}
+impl From<WriteWireError> for StreamWriteError {
+    fn from(error: WriteWireError) -> Self {
+        Self::Serialization(error)
+    }
+}
+impl From<io::Error> for StreamWriteError {
+    fn from(error: io::Error) -> Self {
+        Self::Io(error)
+    }
+}
+
+/// Writes the bytes to the stream, preceded by their length as two octets. The length and the
+/// bytes are handed to the stream together as a vectored write, so they are not copied into
+/// another buffer first and usually go out as a single segment. Returns the number of bytes
+/// written, including the length.
+///
+/// https://datatracker.ietf.org/doc/html/rfc1035#section-4.2.2
+pub async fn write_with_two_octet_length<W: AsyncWrite + Unpin + ?Sized>(stream: &mut W, bytes: &[u8]) -> Result<usize, StreamWriteError> {
+    if bytes.len() > MAX_STREAM_MESSAGE_SIZE {
+        return Err(StreamWriteError::Serialization(WriteWireError::OverflowError(format!("Tried to write {} bytes but the length octet can be at most {}", bytes.len(), u16::MAX))));
+    }
+    let length = (bytes.len() as u16).to_be_bytes();
+    let total_length = length.len() + bytes.len();
+
+    // Streams may accept only part of a vectored write. Whatever is left of the length and the
+    // bytes is written on the next pass.
+    let mut written = 0;
+    while written < total_length {
+        let count = if written < length.len() {
+            stream.write_vectored(&[IoSlice::new(&length[written..]), IoSlice::new(bytes)]).await?
+        } else {
+            stream.write(&bytes[(written - length.len())..]).await?
+        };
+        if count == 0 {
+            return Err(StreamWriteError::Io(io::Error::from(io::ErrorKind::WriteZero)));
+        }
+        written += count;
+    }
+    Ok(total_length)
+}
+
+#[cfg(test)]
+mod stream_write_tests {
+    use std::{io, pin::Pin, task::{Context, Poll}};
+
+    use tokio::io::AsyncWrite;
+
+    use super::{write_with_two_octet_length, StreamWriteError, MAX_STREAM_MESSAGE_SIZE};
+
+    /// A stream that accepts at most a few bytes per write, to make sure that partial vectored
+    /// writes are picked up where they left off.
+    struct Trickle {
+        written: Vec<u8>,
+        max_write: usize,
+    }
+
+    impl AsyncWrite for Trickle {
+        fn poll_write(mut self: Pin<&mut Self>, _cx: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>> {
+            let count = buf.len().min(self.max_write);
+            self.written.extend_from_slice(&buf[..count]);
+            Poll::Ready(Ok(count))
+        }
+
+        fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn poll_shutdown(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+            Poll::Ready(Ok(()))
+        }
+    }
+
+    #[tokio::test]
+    async fn partial_writes_are_resumed() {
+        // Larger than the 8192 byte buffers that were used before.
+        let bytes: Vec<u8> = (0..20_000_u32).map(|index| index as u8).collect();
+        for max_write in [1, 3, 4096, usize::MAX] {
+            let mut stream = Trickle { written: Vec::new(), max_write };
+            assert_eq!(write_with_two_octet_length(&mut stream, &bytes).await.unwrap(), bytes.len() + 2);
+            assert_eq!(&stream.written[..2], &(bytes.len() as u16).to_be_bytes());
+            assert_eq!(&stream.written[2..], &bytes[..]);
+        }
+    }
+
+    #[tokio::test]
+    async fn messages_that_do_not_fit_the_length_are_not_written() {
+        let mut stream = Trickle { written: Vec::new(), max_write: usize::MAX };
+        let result = write_with_two_octet_length(&mut stream, &vec![0; MAX_STREAM_MESSAGE_SIZE + 1]).await;
+        assert!(matches!(result, Err(StreamWriteError::Serialization(_))));
+        assert!(stream.written.is_empty());
+
+        let mut stream = Trickle { written: Vec::new(), max_write: 0 };
+        let result = write_with_two_octet_length(&mut stream, &[1, 2, 3]).await;
+        assert!(matches!(result, Err(StreamWriteError::Io(error)) if error.kind() == io::ErrorKind::WriteZero));
+    }
+}
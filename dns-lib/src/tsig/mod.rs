@@ -0,0 +1,748 @@
+#[cfg(feature = "ring")]
+pub mod ring_mac;
+
+use std::{error::Error, fmt::Display, time::{SystemTime, UNIX_EPOCH}};
+
+use ux::u48;
+
+use crate::{query::{message::Message, question::Question}, resource_record::{rclass::RClass, rcode::RCode, resource_record::{RecordData, ResourceRecord}, rtype::RType, time::Time, types::tsig::TSIG}, serde::wire::{from_wire::FromWire, read_wire::{ReadWire, ReadWireError}, to_wire::ToWire, write_wire::{WriteWire, WriteWireError}}, types::{base64::{Base64, Base64Error}, base_conversions::BaseConversions, c_domain_name::{CDomainName, CmpDomainName, CompressionMap}, domain_name::DomainName}};
+
+/// The default number of seconds that the time a message was signed may differ from the
+/// receiver's clock.
+///
+/// https://datatracker.ietf.org/doc/html/rfc8945#section-10
+pub const DEFAULT_FUDGE: u16 = 300;
+
+/// A client must accept up to this many messages without a TSIG record in a row in a multi-message
+/// response, such as a zone transfer.
+///
+/// https://datatracker.ietf.org/doc/html/rfc8945#section-5.3.1
+const MAX_UNSIGNED_MESSAGES: usize = 99;
+
+/// The largest message that can be signed or verified.
+const MAX_MESSAGE_SIZE: usize = u16::MAX as usize;
+
+/// The offsets of the ID and ARCOUNT fields in the message header.
+const ID_OFFSET: usize = 0;
+const ARCOUNT_OFFSET: usize = 10;
+const HEADER_LENGTH: usize = 12;
+
+/// The HMAC algorithms that TSIG keys can use. MD5 and SHA-224 are not supported.
+///
+/// https://datatracker.ietf.org/doc/html/rfc8945#section-6
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TsigAlgorithm {
+    HmacSha1,
+    HmacSha256,
+    HmacSha384,
+    HmacSha512,
+}
+
+impl TsigAlgorithm {
+    /// The name that identifies the algorithm in TSIG records.
+    #[inline]
+    pub const fn name(&self) -> &'static str {
+        match self {
+            Self::HmacSha1   => "hmac-sha1.",
+            Self::HmacSha256 => "hmac-sha256.",
+            Self::HmacSha384 => "hmac-sha384.",
+            Self::HmacSha512 => "hmac-sha512.",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        let name = name.strip_suffix('.').unwrap_or(name);
+        [Self::HmacSha1, Self::HmacSha256, Self::HmacSha384, Self::HmacSha512].into_iter()
+            .find(|algorithm| algorithm.name().trim_end_matches('.').eq_ignore_ascii_case(name))
+    }
+
+    /// The length of the untruncated MAC.
+    #[inline]
+    pub const fn output_length(&self) -> usize {
+        match self {
+            Self::HmacSha1   => 20,
+            Self::HmacSha256 => 32,
+            Self::HmacSha384 => 48,
+            Self::HmacSha512 => 64,
+        }
+    }
+
+    /// The shortest that a truncated MAC may be: half of the output length, but never less than
+    /// 10 octets.
+    ///
+    /// https://datatracker.ietf.org/doc/html/rfc8945#section-5.2.2.1
+    #[inline]
+    pub const fn min_mac_length(&self) -> usize {
+        let half = self.output_length() / 2;
+        if half > 10 { half } else { 10 }
+    }
+}
+
+impl Display for TsigAlgorithm {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+/// Calculates MACs for TSIG. This keeps the algorithms (and whichever library implements them)
+/// out of the rest of the protocol. Calculators are shared between the tasks that send messages,
+/// so they must be `Send` and `Sync`.
+pub trait MacCalculator: Send + Sync {
+    fn supports(&self, algorithm: TsigAlgorithm) -> bool;
+
+    /// The full length MAC of the data, or `None` if the algorithm is not supported.
+    fn mac(&self, algorithm: TsigAlgorithm, secret: &[u8], data: &[u8]) -> Option<Vec<u8>>;
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum TsigError {
+    /// The message should have been signed but does not have a TSIG record.
+    Unsigned,
+    /// More messages than allowed in a row were not signed.
+    TooManyUnsigned,
+    /// The TSIG record is not the last record, or there is more than one.
+    Misplaced,
+    /// The MAC is longer than the algorithm's output.
+    MacTooLong,
+    /// The key name or algorithm is not the one that is expected.
+    BadKey,
+    /// The MAC is not valid.
+    BadSig,
+    /// The time the message was signed differs from the current time by more than the fudge.
+    BadTime { time_signed: u64, now: u64 },
+    /// The MAC is truncated to less than the algorithm or the key allows.
+    BadTrunc,
+    /// The MAC calculator does not support the key's algorithm.
+    UnsupportedAlgorithm(TsigAlgorithm),
+    /// The other side of the exchange rejected the signature on the message it was sent.
+    Rejected(RCode),
+    Encode(WriteWireError),
+    Decode(ReadWireError),
+}
+impl Error for TsigError {}
+impl Display for TsigError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Unsigned => write!(f, "The message is not signed"),
+            Self::TooManyUnsigned => write!(f, "More than {MAX_UNSIGNED_MESSAGES} messages in a row are not signed"),
+            Self::Misplaced => write!(f, "The TSIG record must be the only one and the last record in the message"),
+            Self::MacTooLong => write!(f, "The MAC is longer than the algorithm's output"),
+            Self::BadKey => write!(f, "The message is signed with an unexpected key"),
+            Self::BadSig => write!(f, "The MAC is not valid"),
+            Self::BadTime { time_signed, now } => write!(f, "The message was signed at {time_signed} but it is now {now}, which is outside the fudge"),
+            Self::BadTrunc => write!(f, "The MAC is truncated to less than is allowed"),
+            Self::UnsupportedAlgorithm(algorithm) => write!(f, "The algorithm {algorithm} is not supported"),
+            Self::Rejected(error) => write!(f, "The signature was rejected with {error}"),
+            Self::Encode(error) => write!(f, "Could not write the signed data: {error}"),
+            Self::Decode(error) => write!(f, "Could not read the signed message: {error}"),
+        }
+    }
+}
+
+impl TsigError {
+    /// The error that a server reports in its response when a request fails verification. Errors
+    /// that are not specific to TSIG are reported as FORMERR in the header instead.
+    ///
+    /// https://datatracker.ietf.org/doc/html/rfc8945#section-5.2
+    #[inline]
+    pub const fn rcode(&self) -> RCode {
+        match self {
+            Self::BadKey | Self::UnsupportedAlgorithm(_) => RCode::BadKey,
+            Self::BadSig => RCode::BadSig,
+            Self::BadTime { .. } => RCode::BadTime,
+            Self::BadTrunc => RCode::BadTrunc,
+            Self::Rejected(error) => *error,
+            Self::Unsigned
+          | Self::TooManyUnsigned
+          | Self::Misplaced
+          | Self::MacTooLong
+          | Self::Decode(_) => RCode::FormErr,
+            Self::Encode(_) => RCode::ServFail,
+        }
+    }
+}
+
+impl From<WriteWireError> for TsigError {
+    #[inline]
+    fn from(error: WriteWireError) -> Self {
+        Self::Encode(error)
+    }
+}
+
+impl From<ReadWireError> for TsigError {
+    #[inline]
+    fn from(error: ReadWireError) -> Self {
+        Self::Decode(error)
+    }
+}
+
+/// A shared secret that both sides of an exchange use to sign and verify messages. The name
+/// identifies the key and must be the same on both sides.
+#[derive(Clone, PartialEq, Eq, Hash)]
+pub struct TsigKey {
+    name: CDomainName,
+    algorithm: TsigAlgorithm,
+    secret: Vec<u8>,
+    mac_length: usize,
+}
+
+impl TsigKey {
+    #[inline]
+    pub fn new(name: CDomainName, algorithm: TsigAlgorithm, secret: Vec<u8>) -> Self {
+        Self { name, algorithm, secret, mac_length: algorithm.output_length() }
+    }
+
+    /// Reads the secret from base64, the way keys are written in configuration files.
+    #[inline]
+    pub fn from_base64(name: CDomainName, algorithm: TsigAlgorithm, secret: &str) -> Result<Self, Base64Error> {
+        Ok(Self::new(name, algorithm, Base64::from_utf8(secret)?.to_bytes().to_vec()))
+    }
+
+    /// Truncates the MACs of the messages signed with this key to `mac_length` octets, and
+    /// rejects messages whose MAC is truncated to less than this. The length is limited to the
+    /// range that RFC 8945 allows for the algorithm.
+    #[inline]
+    pub fn with_mac_length(mut self, mac_length: usize) -> Self {
+        self.mac_length = mac_length.clamp(self.algorithm.min_mac_length(), self.algorithm.output_length());
+        self
+    }
+
+    #[inline]
+    pub fn name(&self) -> &CDomainName { &self.name }
+
+    #[inline]
+    pub fn algorithm(&self) -> TsigAlgorithm { self.algorithm }
+
+    #[inline]
+    pub fn mac_length(&self) -> usize { self.mac_length }
+
+    /// The most octets that signing adds to a message.
+    pub fn max_signature_length(&self) -> usize {
+        let algorithm_name = DomainName::from_utf8(self.algorithm.name()).map_or(0, |name| usize::from(name.serial_length()));
+        // Owner name, type, class, TTL, and RDLENGTH.
+        let record_header = usize::from(self.name.serial_length()) + 10;
+        // Time signed, fudge, MAC size, original ID, error, and other length, plus six octets of
+        // other data for a BADTIME response.
+        let rdata = algorithm_name + 6 + 2 + 2 + self.mac_length + 2 + 2 + 2 + 6;
+        record_header + rdata
+    }
+}
+
+impl std::fmt::Debug for TsigKey {
+    // The secret is left out so that it does not end up in logs.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TsigKey")
+            .field("name", &self.name)
+            .field("algorithm", &self.algorithm)
+            .field("mac_length", &self.mac_length)
+            .finish_non_exhaustive()
+    }
+}
+
+/// The seconds since the Unix epoch, which is what TSIG timestamps count.
+#[inline]
+pub fn unix_time() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |since| since.as_secs())
+}
+
+/// Signs and verifies the messages of one exchange: a request and its response, or a request and
+/// every message of a multi-message response like a zone transfer. Each message's MAC covers the
+/// MAC of the message before it, so the same exchange must be used for every message, in order.
+///
+/// The first message signed or verified is the request. The client signs it and verifies the
+/// responses; the server verifies it and signs the responses.
+///
+/// https://datatracker.ietf.org/doc/html/rfc8945#section-5.3
+pub struct TsigExchange<'a> {
+    key: &'a TsigKey,
+    calculator: &'a dyn MacCalculator,
+    fudge: u16,
+    /// The number of messages that have been signed or verified.
+    messages: usize,
+    /// The MAC of the last message that was signed or verified.
+    previous_mac: Vec<u8>,
+    /// The messages received without a TSIG record since the last one that had one. The next MAC
+    /// covers all of them.
+    unsigned: Vec<u8>,
+    unsigned_messages: usize,
+}
+
+impl<'a> TsigExchange<'a> {
+    pub fn new(key: &'a TsigKey, calculator: &'a dyn MacCalculator) -> Result<Self, TsigError> {
+        if !calculator.supports(key.algorithm) {
+            return Err(TsigError::UnsupportedAlgorithm(key.algorithm));
+        }
+        Ok(Self { key, calculator, fudge: DEFAULT_FUDGE, messages: 0, previous_mac: Vec::new(), unsigned: Vec::new(), unsigned_messages: 0 })
+    }
+
+    #[inline]
+    pub fn with_fudge(mut self, fudge: u16) -> Self {
+        self.fudge = fudge;
+        self
+    }
+
+    #[inline]
+    pub fn key(&self) -> &TsigKey { self.key }
+
+    /// The MAC of the last message that was signed or verified.
+    #[inline]
+    pub fn previous_mac(&self) -> &[u8] { &self.previous_mac }
+
+    /// Adds a TSIG record to the end of the message, replacing any that it already has. The
+    /// message must not be changed after it has been signed, except for its ID, and it must be
+    /// written out with a `CompressionMap`, the way the network layer sends messages.
+    #[inline]
+    pub fn sign(&mut self, message: &mut Message) -> Result<(), TsigError> {
+        self.sign_at(message, unix_time())
+    }
+
+    /// Signs the message as if the time were `now`, in seconds since the Unix epoch.
+    pub fn sign_at(&mut self, message: &mut Message, now: u64) -> Result<(), TsigError> {
+        if message.additional.last().is_some_and(is_tsig) {
+            message.additional.pop();
+        }
+        let unsigned = encode(message)?;
+        let (record, mac) = self.signature(&unsigned, message.id, now)?;
+        message.additional.push(record);
+        self.finish_message(mac);
+        Ok(())
+    }
+
+    /// Signs a message that has already been written out, adding the TSIG record to the end of
+    /// it. Unlike `sign()`, the message may be written out any way the sender likes, such as
+    /// without compressing its names.
+    #[inline]
+    pub fn sign_wire(&mut self, wire: &mut Vec<u8>) -> Result<(), TsigError> {
+        self.sign_wire_at(wire, unix_time())
+    }
+
+    /// Signs the written out message as if the time were `now`, in seconds since the Unix epoch.
+    pub fn sign_wire_at(&mut self, wire: &mut Vec<u8>, now: u64) -> Result<(), TsigError> {
+        let header = ReadWire::from_bytes(wire).take(HEADER_LENGTH)?;
+        let id = u16::from_be_bytes([header[ID_OFFSET], header[ID_OFFSET + 1]]);
+        let additional_count = u16::from_be_bytes([header[ARCOUNT_OFFSET], header[ARCOUNT_OFFSET + 1]]) + 1;
+        let (record, mac) = self.signature(wire, id, now)?;
+        let mut buffer = vec![0_u8; MAX_MESSAGE_SIZE.saturating_sub(wire.len())];
+        let mut record_wire = WriteWire::from_bytes(&mut buffer);
+        record.to_wire_format(&mut record_wire, &mut None)?;
+        let length = record_wire.current_len();
+        wire.extend_from_slice(&buffer[..length]);
+        wire[ARCOUNT_OFFSET..ARCOUNT_OFFSET + 2].copy_from_slice(&additional_count.to_be_bytes());
+        self.finish_message(mac);
+        Ok(())
+    }
+
+    /// Checks the TSIG record of a message as it was received. Messages after the first response
+    /// may be unsigned, in which case they are covered by the MAC of the next message that is
+    /// signed; use `finish()` to check that the last message was signed.
+    #[inline]
+    pub fn verify(&mut self, wire: &[u8]) -> Result<(), TsigError> {
+        self.verify_at(wire, unix_time())
+    }
+
+    /// Verifies the message as if the time were `now`, in seconds since the Unix epoch.
+    pub fn verify_at(&mut self, wire: &[u8], now: u64) -> Result<(), TsigError> {
+        let (tsig_offset, record) = find_tsig(wire)?;
+        let Some(record) = record else {
+            // Only the messages after the first response may be left unsigned.
+            if self.messages < 2 {
+                return Err(TsigError::Unsigned);
+            }
+            if self.unsigned_messages >= MAX_UNSIGNED_MESSAGES {
+                return Err(TsigError::TooManyUnsigned);
+            }
+            self.unsigned.extend_from_slice(wire);
+            self.unsigned_messages += 1;
+            return Ok(());
+        };
+        let RecordData::TSIG(tsig) = record.get_rdata() else {
+            return Err(TsigError::Misplaced);
+        };
+        if tsig.error() != RCode::NoError {
+            return Err(TsigError::Rejected(tsig.error()));
+        }
+        if !record.get_name().matches(&self.key.name) || (TsigAlgorithm::from_name(&tsig.algorithm_name().to_string()) != Some(self.key.algorithm)) {
+            return Err(TsigError::BadKey);
+        }
+        let mac_length = tsig.mac().len();
+        if mac_length > self.key.algorithm.output_length() {
+            return Err(TsigError::MacTooLong);
+        }
+        if mac_length < self.key.mac_length {
+            return Err(TsigError::BadTrunc);
+        }
+
+        // The MAC covers the message as it was before the TSIG record was added, with the ID it
+        // was signed with.
+        let mut unsigned = wire[..tsig_offset].to_vec();
+        unsigned[ID_OFFSET..ID_OFFSET + 2].copy_from_slice(&tsig.original_id().to_be_bytes());
+        let additional_count = u16::from_be_bytes([unsigned[ARCOUNT_OFFSET], unsigned[ARCOUNT_OFFSET + 1]]) - 1;
+        unsigned[ARCOUNT_OFFSET..ARCOUNT_OFFSET + 2].copy_from_slice(&additional_count.to_be_bytes());
+        let data = self.signed_data(&unsigned, tsig)?;
+        let expected = self.calculate(&data)?;
+        if !constant_time_eq(&expected[..mac_length], tsig.mac()) {
+            return Err(TsigError::BadSig);
+        }
+
+        // The time is only checked once the MAC is known to be valid, since it is part of what the
+        // MAC covers.
+        // https://datatracker.ietf.org/doc/html/rfc8945#section-5.2.3
+        let time_signed = u64::from(tsig.time_signed());
+        if now.abs_diff(time_signed) > u64::from(tsig.fudge()) {
+            return Err(TsigError::BadTime { time_signed, now });
+        }
+        self.finish_message(tsig.mac().to_vec());
+        Ok(())
+    }
+
+    /// Sends a message of a multi-message response without signing it. The MAC of the next
+    /// message that is signed covers it. At most 99 messages in a row may be left unsigned and the
+    /// last message must be signed.
+    ///
+    /// https://datatracker.ietf.org/doc/html/rfc8945#section-5.3.1
+    pub fn skip(&mut self, message: &Message) -> Result<(), TsigError> {
+        if self.messages < 2 {
+            return Err(TsigError::Unsigned);
+        }
+        if self.unsigned_messages >= MAX_UNSIGNED_MESSAGES {
+            return Err(TsigError::TooManyUnsigned);
+        }
+        self.unsigned.extend_from_slice(&encode(message)?);
+        self.unsigned_messages += 1;
+        Ok(())
+    }
+
+    /// Checks that the last message verified was signed. Messages received after the last signed
+    /// one cannot be trusted.
+    #[inline]
+    pub fn finish(&self) -> Result<(), TsigError> {
+        match self.unsigned_messages {
+            0 => Ok(()),
+            _ => Err(TsigError::Unsigned),
+        }
+    }
+
+    /// The TSIG record for a message with this ID that was written out as `unsigned`, and its
+    /// MAC.
+    fn signature(&self, unsigned: &[u8], id: u16, now: u64) -> Result<(ResourceRecord, Vec<u8>), TsigError> {
+        let tsig = TSIG::new(algorithm_name(self.key.algorithm), u48::new(now), self.fudge, Vec::new(), id, RCode::NoError, Vec::new());
+        let data = self.signed_data(unsigned, &tsig)?;
+        let mut mac = self.calculate(&data)?;
+        mac.truncate(self.key.mac_length);
+        let tsig = TSIG::new(tsig.algorithm_name().clone(), tsig.time_signed(), tsig.fudge(), mac.clone(), tsig.original_id(), tsig.error(), Vec::new());
+        Ok((ResourceRecord::new(self.key.name.clone(), RClass::QClassAny, Time::from_secs(0), RecordData::TSIG(tsig)), mac))
+    }
+
+    #[inline]
+    fn finish_message(&mut self, mac: Vec<u8>) {
+        self.previous_mac = mac;
+        self.unsigned.clear();
+        self.unsigned_messages = 0;
+        self.messages += 1;
+    }
+
+    #[inline]
+    fn calculate(&self, data: &[u8]) -> Result<Vec<u8>, TsigError> {
+        self.calculator.mac(self.key.algorithm, &self.key.secret, data)
+            .ok_or(TsigError::UnsupportedAlgorithm(self.key.algorithm))
+    }
+
+    /// The data that the MAC is calculated over. A response starts with the MAC of the message
+    /// before it. The request and the first response include all of the TSIG variables, but later
+    /// messages in the same response only include the timers.
+    ///
+    /// https://datatracker.ietf.org/doc/html/rfc8945#section-4.3
+    fn signed_data(&self, message: &[u8], tsig: &TSIG) -> Result<Vec<u8>, TsigError> {
+        let mut buffer = vec![0_u8; self.previous_mac.len() + self.unsigned.len() + message.len() + MAX_VARIABLES_LENGTH];
+        let mut wire = WriteWire::from_bytes(&mut buffer);
+        if self.messages > 0 {
+            (self.previous_mac.len() as u16).to_wire_format(&mut wire, &mut None)?;
+            wire.write_bytes(&self.previous_mac)?;
+        }
+        wire.write_bytes(&self.unsigned)?;
+        wire.write_bytes(message)?;
+        if self.messages < 2 {
+            DomainName::from(&self.key.name).as_lowercase().to_wire_format(&mut wire, &mut None)?;
+            RClass::QClassAny.to_wire_format(&mut wire, &mut None)?;
+            0_u32.to_wire_format(&mut wire, &mut None)?;
+            tsig.algorithm_name().as_lowercase().to_wire_format(&mut wire, &mut None)?;
+        }
+        tsig.time_signed().to_wire_format(&mut wire, &mut None)?;
+        tsig.fudge().to_wire_format(&mut wire, &mut None)?;
+        if self.messages < 2 {
+            tsig.error().to_wire_format(&mut wire, &mut None)?;
+            (tsig.other_data().len() as u16).to_wire_format(&mut wire, &mut None)?;
+            wire.write_bytes(tsig.other_data())?;
+        }
+        let length = wire.current_len();
+        buffer.truncate(length);
+        Ok(buffer)
+    }
+}
+
+/// The name of the key that a received message was signed with, or `None` if it is not signed. A
+/// server uses this to pick the key to verify a request with before it starts an exchange.
+pub fn signing_key_name(wire: &[u8]) -> Result<Option<CDomainName>, TsigError> {
+    let (_, record) = find_tsig(wire)?;
+    Ok(record.map(|record| record.get_name().clone()))
+}
+
+/// Room for the key name, class, TTL, algorithm name, timers, error, and other data in the data
+/// that the MAC is calculated over.
+const MAX_VARIABLES_LENGTH: usize = 255 + 2 + 4 + 255 + 6 + 2 + 2 + 2 + u16::MAX as usize;
+
+/// Writes the message out the way the network layer does.
+fn encode(message: &Message) -> Result<Vec<u8>, TsigError> {
+    let mut buffer = vec![0_u8; MAX_MESSAGE_SIZE];
+    let mut wire = WriteWire::from_bytes(&mut buffer);
+    message.to_wire_format(&mut wire, &mut Some(CompressionMap::new()))?;
+    let length = wire.current_len();
+    buffer.truncate(length);
+    Ok(buffer)
+}
+
+#[inline]
+fn algorithm_name(algorithm: TsigAlgorithm) -> DomainName {
+    DomainName::from_utf8(algorithm.name()).expect("TSIG algorithm names are valid domain names")
+}
+
+#[inline]
+fn is_tsig(record: &ResourceRecord) -> bool {
+    record.get_rtype() == RType::TSIG
+}
+
+/// Finds where the message's TSIG record starts and reads it. The record must be the last one in
+/// the message and there must not be any others.
+fn find_tsig(wire: &[u8]) -> Result<(usize, Option<ResourceRecord>), TsigError> {
+    let mut read_wire = ReadWire::from_bytes(wire);
+    let header = read_wire.take(HEADER_LENGTH)?;
+    let count = |offset: usize| usize::from(u16::from_be_bytes([header[offset], header[offset + 1]]));
+    let (questions, records) = (count(4), count(6) + count(8) + count(ARCOUNT_OFFSET));
+    for _ in 0..questions {
+        Question::from_wire_format(&mut read_wire)?;
+    }
+    let mut last = None;
+    for index in 0..records {
+        let offset = read_wire.current_offset();
+        let record = ResourceRecord::from_wire_format(&mut read_wire)?;
+        if is_tsig(&record) {
+            if index + 1 != records {
+                return Err(TsigError::Misplaced);
+            }
+            last = Some((offset, record));
+        }
+    }
+    // The ARCOUNT is decremented when the TSIG record is taken off, so it must be in the
+    // additional section.
+    match last {
+        Some(_) if count(ARCOUNT_OFFSET) == 0 => Err(TsigError::Misplaced),
+        Some((offset, record)) => Ok((offset, Some(record))),
+        None => Ok((wire.len(), None)),
+    }
+}
+
+/// Compares every octet so that the time taken does not reveal how much of a forged MAC was
+/// correct.
+fn constant_time_eq(left: &[u8], right: &[u8]) -> bool {
+    (left.len() == right.len())
+    && (left.iter().zip(right).fold(0, |difference, (left, right)| difference | (left ^ right)) == 0)
+}
+
+#[cfg(test)]
+mod tsig_tests {
+    use std::net::Ipv4Addr;
+
+    use crate::{query::{message::Message, qr::QR, question::Question}, resource_record::{rclass::RClass, rcode::RCode, resource_record::{RecordData, ResourceRecord}, rtype::RType, types::tsig::TSIG}, serde::wire::{from_wire::FromWire, read_wire::ReadWire}, types::c_domain_name::CDomainName};
+
+    use super::{encode, signing_key_name, MacCalculator, TsigAlgorithm, TsigError, TsigExchange, TsigKey};
+
+    const NOW: u64 = 1_700_000_000;
+
+    /// Not a real MAC, but it depends on every octet of the key and the data, which is all that
+    /// these tests need.
+    struct XorCalculator;
+
+    impl MacCalculator for XorCalculator {
+        fn supports(&self, algorithm: TsigAlgorithm) -> bool {
+            algorithm != TsigAlgorithm::HmacSha512
+        }
+
+        fn mac(&self, algorithm: TsigAlgorithm, secret: &[u8], data: &[u8]) -> Option<Vec<u8>> {
+            let mut mac = vec![0_u8; algorithm.output_length()];
+            for (index, octet) in secret.iter().chain(data).enumerate() {
+                let position = index % mac.len();
+                mac[position] = mac[position].rotate_left(3) ^ octet ^ (index as u8);
+            }
+            Some(mac)
+        }
+    }
+
+    fn key(secret: &[u8]) -> TsigKey {
+        TsigKey::new(CDomainName::from_utf8("transfer.example.").unwrap(), TsigAlgorithm::HmacSha256, secret.to_vec())
+    }
+
+    fn query() -> Message {
+        let mut query = Message::from(Question::new(CDomainName::from_utf8("example.com.").unwrap(), RType::AXFR, RClass::Internet));
+        query.id = 1234;
+        query
+    }
+
+    fn response_to(query: &Message) -> Message {
+        let mut response = query.clone();
+        response.additional.clear();
+        response.qr = QR::Response;
+        response
+    }
+
+    fn wire(message: &Message) -> Vec<u8> {
+        encode(message).unwrap()
+    }
+
+    #[test]
+    fn signed_requests_and_responses_verify() {
+        let key = key(b"secret");
+        let mut client = TsigExchange::new(&key, &XorCalculator).unwrap();
+        let mut server = TsigExchange::new(&key, &XorCalculator).unwrap();
+
+        let mut query = query();
+        client.sign_at(&mut query, NOW).unwrap();
+        assert_eq!(query.additional.last().unwrap().get_rtype(), RType::TSIG);
+        // The network layer may give the query a new ID after it has been signed.
+        query.id = 4321;
+        server.verify_at(&wire(&query), NOW + 10).unwrap();
+
+        // Each message of a zone transfer is chained to the one before it.
+        for _ in 0..3 {
+            let mut response = response_to(&query);
+            server.sign_at(&mut response, NOW + 20).unwrap();
+            client.verify_at(&wire(&response), NOW + 20).unwrap();
+        }
+        client.finish().unwrap();
+    }
+
+    #[test]
+    fn messages_written_out_differently_verify() {
+        let key = key(b"secret");
+        let mut client = TsigExchange::new(&key, &XorCalculator).unwrap();
+        let mut server = TsigExchange::new(&key, &XorCalculator).unwrap();
+
+        let mut query = query();
+        client.sign_at(&mut query, NOW).unwrap();
+        server.verify_at(&wire(&query), NOW).unwrap();
+
+        // The server compresses the owner names of its answers to point at the question, which
+        // this library does not do. The answers are written out by hand to get the pointers.
+        let mut compressed = wire(&response_to(&query));
+        compressed[7] = 2;
+        for address in [Ipv4Addr::new(192, 0, 2, 1), Ipv4Addr::new(192, 0, 2, 2)] {
+            compressed.extend_from_slice(&[0xC0, 0x0C, 0, 1, 0, 1, 0, 0, 1, 44, 0, 4]);
+            compressed.extend_from_slice(&address.octets());
+        }
+        let response = Message::from_wire_format(&mut ReadWire::from_bytes(&compressed)).unwrap();
+        assert_eq!(response.answer.len(), 2);
+        assert_ne!(wire(&response), compressed);
+
+        server.sign_wire_at(&mut compressed, NOW).unwrap();
+        client.verify_at(&compressed, NOW).unwrap();
+    }
+
+    #[test]
+    fn signing_key_name_is_found() {
+        let key = key(b"secret");
+        let mut query = query();
+        assert_eq!(signing_key_name(&wire(&query)), Ok(None));
+        TsigExchange::new(&key, &XorCalculator).unwrap().sign_at(&mut query, NOW).unwrap();
+        assert_eq!(signing_key_name(&wire(&query)), Ok(Some(key.name().clone())));
+    }
+
+    #[test]
+    fn wrong_keys_and_changed_messages_are_rejected() {
+        let key = key(b"secret");
+        let mut query = query();
+        TsigExchange::new(&key, &XorCalculator).unwrap().sign_at(&mut query, NOW).unwrap();
+
+        let other_secret = self::key(b"different");
+        assert_eq!(TsigExchange::new(&other_secret, &XorCalculator).unwrap().verify_at(&wire(&query), NOW), Err(TsigError::BadSig));
+
+        let other_name = TsigKey::new(CDomainName::from_utf8("other.example.").unwrap(), TsigAlgorithm::HmacSha256, b"secret".to_vec());
+        assert_eq!(TsigExchange::new(&other_name, &XorCalculator).unwrap().verify_at(&wire(&query), NOW), Err(TsigError::BadKey));
+
+        let mut changed = query.clone();
+        changed.recursion_desired = true;
+        assert_eq!(TsigExchange::new(&key, &XorCalculator).unwrap().verify_at(&wire(&changed), NOW), Err(TsigError::BadSig));
+
+        let unsigned = self::query();
+        assert_eq!(TsigExchange::new(&key, &XorCalculator).unwrap().verify_at(&wire(&unsigned), NOW), Err(TsigError::Unsigned));
+
+        let unsupported = TsigKey::new(CDomainName::from_utf8("transfer.example.").unwrap(), TsigAlgorithm::HmacSha512, b"secret".to_vec());
+        assert!(matches!(TsigExchange::new(&unsupported, &XorCalculator), Err(TsigError::UnsupportedAlgorithm(TsigAlgorithm::HmacSha512))));
+    }
+
+    #[test]
+    fn messages_outside_the_fudge_are_rejected() {
+        let key = key(b"secret");
+        let mut query = query();
+        TsigExchange::new(&key, &XorCalculator).unwrap().with_fudge(60).sign_at(&mut query, NOW).unwrap();
+        let mut server = TsigExchange::new(&key, &XorCalculator).unwrap();
+        assert_eq!(server.verify_at(&wire(&query), NOW + 61), Err(TsigError::BadTime { time_signed: NOW, now: NOW + 61 }));
+        assert_eq!(server.verify_at(&wire(&query), NOW - 60), Ok(()));
+    }
+
+    #[test]
+    fn truncated_macs_are_only_accepted_down_to_the_key_length() {
+        let truncated = key(b"secret").with_mac_length(16);
+        let mut query = query();
+        TsigExchange::new(&truncated, &XorCalculator).unwrap().sign_at(&mut query, NOW).unwrap();
+        TsigExchange::new(&truncated, &XorCalculator).unwrap().verify_at(&wire(&query), NOW).unwrap();
+
+        let full = key(b"secret");
+        assert_eq!(TsigExchange::new(&full, &XorCalculator).unwrap().verify_at(&wire(&query), NOW), Err(TsigError::BadTrunc));
+
+        // Never less than half of the output length.
+        assert_eq!(key(b"secret").with_mac_length(4).mac_length(), 16);
+    }
+
+    #[test]
+    fn some_later_messages_may_be_unsigned() {
+        let key = key(b"secret");
+        let mut client = TsigExchange::new(&key, &XorCalculator).unwrap();
+        let mut server = TsigExchange::new(&key, &XorCalculator).unwrap();
+        let mut query = query();
+        client.sign_at(&mut query, NOW).unwrap();
+        server.verify_at(&wire(&query), NOW).unwrap();
+
+        let mut first = response_to(&query);
+        server.sign_at(&mut first, NOW).unwrap();
+        client.verify_at(&wire(&first), NOW).unwrap();
+
+        // The server's MAC for the last message covers the unsigned one before it.
+        let unsigned = response_to(&query);
+        client.verify_at(&wire(&unsigned), NOW).unwrap();
+        assert_eq!(client.finish(), Err(TsigError::Unsigned));
+
+        server.skip(&unsigned).unwrap();
+        let mut last = response_to(&query);
+        server.sign_at(&mut last, NOW).unwrap();
+        client.verify_at(&wire(&last), NOW).unwrap();
+        client.finish().unwrap();
+    }
+
+    #[test]
+    fn rejections_from_the_other_side_are_reported() {
+        let key = key(b"secret");
+        let mut client = TsigExchange::new(&key, &XorCalculator).unwrap();
+        let mut query = query();
+        client.sign_at(&mut query, NOW).unwrap();
+
+        // A server that does not know the key answers with an unsigned TSIG record.
+        let mut response = response_to(&query);
+        response.rcode = RCode::NotAuth;
+        let mut server = TsigExchange::new(&key, &XorCalculator).unwrap();
+        server.sign_at(&mut response, NOW).unwrap();
+        let record = response.additional.pop().unwrap();
+        let RecordData::TSIG(tsig) = record.get_rdata() else { unreachable!() };
+        let rejection = TSIG::new(tsig.algorithm_name().clone(), tsig.time_signed(), tsig.fudge(), Vec::new(), tsig.original_id(), RCode::BadKey, Vec::new());
+        response.additional.push(ResourceRecord::new(record.get_name().clone(), record.get_rclass(), *record.get_ttl(), RecordData::TSIG(rejection)));
+        assert_eq!(client.verify_at(&wire(&response), NOW), Err(TsigError::Rejected(RCode::BadKey)));
+    }
+}
@@ -0,0 +1,46 @@
+use ring::hmac;
+
+use super::{MacCalculator, TsigAlgorithm};
+
+/// Calculates TSIG MACs with `ring`. Supports every `TsigAlgorithm`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct RingMac;
+
+impl MacCalculator for RingMac {
+    #[inline]
+    fn supports(&self, _algorithm: TsigAlgorithm) -> bool {
+        true
+    }
+
+    fn mac(&self, algorithm: TsigAlgorithm, secret: &[u8], data: &[u8]) -> Option<Vec<u8>> {
+        let algorithm = match algorithm {
+            TsigAlgorithm::HmacSha1   => hmac::HMAC_SHA1_FOR_LEGACY_USE_ONLY,
+            TsigAlgorithm::HmacSha256 => hmac::HMAC_SHA256,
+            TsigAlgorithm::HmacSha384 => hmac::HMAC_SHA384,
+            TsigAlgorithm::HmacSha512 => hmac::HMAC_SHA512,
+        };
+        Some(hmac::sign(&hmac::Key::new(algorithm, secret), data).as_ref().to_vec())
+    }
+}
+
+#[cfg(test)]
+mod ring_mac_tests {
+    use crate::{query::{message::Message, question::Question}, resource_record::{rclass::RClass, rtype::RType}, tsig::{encode, MacCalculator, TsigAlgorithm, TsigExchange, TsigKey}, types::{base16::Base16, base_conversions::BaseConversions, c_domain_name::CDomainName}};
+
+    use super::RingMac;
+
+    /// https://datatracker.ietf.org/doc/html/rfc4231#section-4.2
+    #[test]
+    fn hmac_sha256_test_vector() {
+        let mac = RingMac.mac(TsigAlgorithm::HmacSha256, &[0x0b; 20], b"Hi There").unwrap();
+        assert_eq!(mac, Base16::from_utf8("B0344C61D8DB38535CA8AFCEAF0BF12B881DC200C9833DA726E9376C2E32CFF7").unwrap().to_bytes());
+    }
+
+    #[test]
+    fn signed_query_verifies() {
+        let key = TsigKey::from_base64(CDomainName::from_utf8("transfer.example.").unwrap(), TsigAlgorithm::HmacSha256, "c2VjcmV0IGtleSBmb3IgdGVzdGluZw==").unwrap();
+        let mut query = Message::from(Question::new(CDomainName::from_utf8("example.com.").unwrap(), RType::SOA, RClass::Internet));
+        TsigExchange::new(&key, &RingMac).unwrap().sign(&mut query).unwrap();
+        TsigExchange::new(&key, &RingMac).unwrap().verify(&encode(&query).unwrap()).unwrap();
+    }
+}
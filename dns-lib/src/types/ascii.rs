@@ -2,7 +2,7 @@ use std::{fmt::{Display, Debug}, slice::{Iter, IterMut}, iter::Rev, error::Error
 
 use tinyvec::{tiny_vec, TinyVec};
 
-use crate::serde::{presentation::{errors::TokenError, from_presentation::FromPresentation, parse_chars::non_escaped_to_escaped::NonEscapedIntoEscapedIter, to_presentation::ToPresentation}, wire::{from_wire::FromWire, to_wire::ToWire}};
+use crate::serde::{presentation::{errors::TokenError, from_presentation::FromPresentation, parse_chars::non_escaped_to_escaped::NonEscapedIntoEscapedIter, to_presentation::{PresentationWriter, ToPresentation}}, wire::{from_wire::FromWire, to_wire::ToWire}};
 
 #[derive(Clone, PartialEq, Eq, Hash, Debug)]
 pub enum AsciiError {
@@ -529,6 +529,12 @@ impl ToPresentation for AsciiString {
                 .collect::<String>()
         )
     }
+
+    #[inline]
+    fn write_presentation_tokens<W: std::fmt::Write + ?Sized>(&self, out: &mut PresentationWriter<'_, W>) -> std::fmt::Result {
+        let out = out.start_token()?;
+        self.string.iter().try_for_each(|character| out.write_char(*character as char))
+    }
 }
 
 #[cfg(test)]
@@ -2,7 +2,7 @@ use std::{collections::HashMap, error::Error, fmt::{Debug, Display}, iter::Fused
 
 use tinyvec::{tiny_vec, ArrayVec, TinyVec};
 
-use crate::{serde::{presentation::{errors::TokenError, from_presentation::FromPresentation, parse_chars::{char_token::EscapableChar, escaped_to_escapable::{EscapedCharsEnumerateIter, ParseError}}, to_presentation::ToPresentation}, wire::{from_wire::FromWire, to_wire::ToWire}}, types::ascii::{constants::ASCII_PERIOD, AsciiError, AsciiString}};
+use crate::{serde::{presentation::{errors::TokenError, from_presentation::FromPresentation, parse_chars::{char_token::EscapableChar, escaped_to_escapable::{EscapedCharsEnumerateIter, ParseError}}, to_presentation::{PresentationWriter, ToPresentation}}, wire::{from_wire::FromWire, to_wire::ToWire}}, types::ascii::{constants::ASCII_PERIOD, AsciiError, AsciiString}};
 
 use super::{ascii::AsciiChar, domain_name::DomainName, label::{CaseInsensitiveRefLabel, CaseSensitiveOwnedLabel, CaseSensitiveRefLabel, Label, LabelOwned, LabelRef}};
 
@@ -21,6 +21,7 @@ pub enum CDomainNameError {
     ForwardPointers,
     InvalidPointer,
     BadRData,
+    BadMailbox(String),
     AsciiError(AsciiError),
     ParseError(ParseError)
 }
@@ -41,6 +42,7 @@ impl Display for CDomainNameError {
             Self::ForwardPointers =>   write!(f, "Forward Pointer: domain name pointers can only point backwards. Cannot point forward in the buffer"),
             Self::InvalidPointer =>    write!(f, "Invalid Pointer: domain name pointer cannot use the first two bits. These are reserved"),
             Self::BadRData =>          write!(f, "Bad RData."),
+            Self::BadMailbox(mailbox) => write!(f, "Bad Mailbox: '{mailbox}' cannot be converted between an email address and a domain name"),
             Self::AsciiError(error) => write!(f, "{error}"),
             Self::ParseError(error) => write!(f, "{error}"),
         }
@@ -56,6 +58,9 @@ pub trait CmpDomainName<T>: Sized {
     /// determines if two sets of labels are identical, ignoring capitalization
     fn matches(&self, other: &T) -> bool;
 
+    /// determines if two sets of labels are identical, including capitalization
+    fn matches_case(&self, other: &T) -> bool;
+
     /// is_parent_domain_of checks if child is indeed a child of the parent. If child and parent are
     /// the same domain true is returned as well.
     fn is_parent_domain_of(&self, child: &T) -> bool;
@@ -369,6 +374,26 @@ impl CDomainName {
         }
     }
 
+    /// Sets each letter in the name, in order, to upper case if `uppercase()` returns `true` and to
+    /// lower case otherwise. All other octets are left as they are. Given random bits, this hides a
+    /// nonce in the name that a response has to echo back to be accepted.
+    ///
+    /// https://datatracker.ietf.org/doc/html/draft-vixie-dnsext-dns0x20-00
+    pub fn set_case_with(&mut self, mut uppercase: impl FnMut() -> bool) {
+        let mut index = 0;
+        for length_octet in &self.length_octets {
+            let label_end = index + (*length_octet as usize);
+            for octet in self.octets[(index + 1)..=label_end].iter_mut().filter(|octet| octet.is_ascii_alphabetic()) {
+                if uppercase() {
+                    octet.make_ascii_uppercase();
+                } else {
+                    octet.make_ascii_lowercase();
+                }
+            }
+            index = label_end + 1;
+        }
+    }
+
     #[inline]
     pub fn case_sensitive_labels<'a>(&'a self) -> impl 'a + DoubleEndedIterator<Item = CaseSensitiveRefLabel<'a>> + ExactSizeIterator<Item = CaseSensitiveRefLabel<'a>> {
         CDomainCaseSensitiveLabelIter::new(self)
@@ -383,6 +408,58 @@ impl CDomainName {
     pub fn search_domains<'a>(&'a self) -> impl 'a + DoubleEndedIterator<Item = Self> + ExactSizeIterator<Item = Self> {
         CDomainSearchNameIter::new(self)
     }
+
+    /// Converts an email address into the domain name used to represent a mailbox in records like
+    /// SOA, MINFO, and RP. The local part becomes the first label, even if it contains periods,
+    /// and the domain becomes the rest of the name.
+    ///
+    /// The local part may be internationalized (RFC 6531). It is stored as its UTF-8 octets. The
+    /// domain must already be ASCII, using A-labels for any internationalized labels.
+    ///
+    /// https://datatracker.ietf.org/doc/html/rfc1035#section-8
+    pub fn from_mailbox(mailbox: &str) -> Result<Self, CDomainNameError> {
+        let bad_mailbox = || CDomainNameError::BadMailbox(mailbox.to_string());
+        let (local_part, domain) = mailbox.rsplit_once('@').ok_or_else(bad_mailbox)?;
+        let local_part = local_part.strip_prefix('"')
+            .and_then(|local_part| local_part.strip_suffix('"'))
+            .unwrap_or(local_part);
+        if local_part.is_empty() || domain.is_empty() || !domain.is_ascii() {
+            return Err(bad_mailbox());
+        }
+        if local_part.len() > CaseSensitiveOwnedLabel::MAX_OCTETS as usize {
+            return Err(CDomainNameError::LongLabel);
+        }
+
+        let domain = match domain.ends_with('.') {
+            true => Self::from_utf8(domain)?,
+            false => Self::from_utf8(&format!("{domain}."))?,
+        };
+        let mut labels = Vec::with_capacity(domain.label_count() + 1);
+        labels.push(CaseSensitiveOwnedLabel { octets: local_part.as_bytes().into() });
+        labels.extend(domain.case_sensitive_labels().map(|label| label.as_case_sensitive_owned()));
+        Self::from_owned_labels(labels)
+    }
+
+    /// Converts a domain name representing a mailbox back into an email address. Returns `None` if
+    /// the name does not have at least a local part and one domain label, or if the local part is
+    /// not valid UTF-8. Local parts that cannot be written unquoted are quoted.
+    pub fn to_mailbox(&self) -> Option<String> {
+        let mut labels = self.case_sensitive_labels().filter(|label| !label.is_root());
+        let local_part = std::str::from_utf8(labels.next()?.into_octets()).ok()?;
+        let domain = labels.map(|label| label.to_string()).collect::<Vec<_>>();
+        if domain.is_empty() {
+            return None;
+        }
+
+        let needs_quotes = local_part.starts_with('.')
+            || local_part.ends_with('.')
+            || local_part.contains("..")
+            || local_part.chars().any(|character| character.is_ascii() && !(character.is_ascii_alphanumeric() || "!#$%&'*+-/=?^_`{|}~.".contains(character)));
+        match needs_quotes {
+            true => Some(format!("\"{}\"@{}", local_part.replace('\\', "\\\\").replace('"', "\\\""), domain.join("."))),
+            false => Some(format!("{local_part}@{}", domain.join("."))),
+        }
+    }
 }
 
 struct CDomainCaseSensitiveLabelIter<'a> {
@@ -571,6 +648,12 @@ impl CmpDomainName<CDomainName> for CDomainName {
             .all(|(self_label, other_label)| self_label.as_case_insensitive() == other_label.as_case_insensitive())
     }
 
+    #[inline]
+    fn matches_case(&self, other: &CDomainName) -> bool {
+        // The octets include the length octets, so equal octets means equal labels.
+        self.octets == other.octets
+    }
+
     #[inline]
     fn is_parent_domain_of(&self, child: &CDomainName) -> bool {
         if self.serial_length() > child.serial_length() {
@@ -592,45 +675,75 @@ impl CmpDomainName<DomainName> for CDomainName {
         self.matches(&other.domain_name)
     }
 
+    #[inline]
+    fn matches_case(&self, other: &DomainName) -> bool {
+        self.matches_case(&other.domain_name)
+    }
+
     #[inline]
     fn is_parent_domain_of(&self, child: &DomainName) -> bool {
         self.is_parent_domain_of(&child.domain_name)
     }
 }
 
-impl ToWire for CDomainName {
-    #[inline]
-    fn to_wire_format<'a, 'b>(&self, wire: &'b mut crate::serde::wire::write_wire::WriteWire<'a>, compression: &mut Option<crate::types::c_domain_name::CompressionMap>) -> Result<(), crate::serde::wire::write_wire::WriteWireError> where 'a: 'b {
-        if let Some(compression_map) = compression {
-            let mut length_byte_index = 0_usize;
-            while length_byte_index < self.octets.len() {
-                if let Some(pointer) = compression_map.find_sequence(&self.octets[length_byte_index..]) {
-                    // The pointer cannot make use of the first two bits. These are reserved for
-                    // use indicating that this label is a pointer. If they are needed for the
-                    // pointer itself, the pointer would be corrupted.
-                    //
-                    // To solve this issue, we will just not use a pointer if using one would
-                    // lead to a corrupted pointer. Easy as that.
-                    if (pointer & 0b1100_0000_0000_0000) != 0b0000_0000_0000_0000 {
-                        break;
-                    }
-                    wire.write_bytes(&self.octets[..length_byte_index])?;
-                    return pointer.to_wire_format(wire, compression);
-                } else {
-                    // Don't insert malformed pointers. Otherwise, it might overwrite an
-                    // existing well-formed pointer. If we reach an index that would form a
-                    // malformed pointer, then none of the pointers after this one will be well
-                    // formed.
-                    let pointer = wire.current_len() as u16;
-                    if ((pointer & 0b1100_0000_0000_0000) != 0b0000_0000_0000_0000) || (&self.octets[length_byte_index..] != &[0]) {
-                        break;
-                    }
-                    length_byte_index += (self.octets[length_byte_index] as usize) + 1;
+impl CDomainName {
+    /// Searches the compression map for a pointer that can replace the end of this name if it
+    /// were written at `offset`. If one is found, the number of octets written before the pointer
+    /// and the pointer itself are returned.
+    fn find_compression_pointer(&self, offset: usize, compression_map: &CompressionMap) -> Option<(usize, u16)> {
+        let mut length_byte_index = 0_usize;
+        while length_byte_index < self.octets.len() {
+            if let Some(pointer) = compression_map.find_sequence(&self.octets[length_byte_index..]) {
+                // The pointer cannot make use of the first two bits. These are reserved for
+                // use indicating that this label is a pointer. If they are needed for the
+                // pointer itself, the pointer would be corrupted.
+                //
+                // To solve this issue, we will just not use a pointer if using one would
+                // lead to a corrupted pointer. Easy as that.
+                if (pointer & 0b1100_0000_0000_0000) != 0b0000_0000_0000_0000 {
+                    return None;
+                }
+                return Some((length_byte_index, pointer));
+            } else {
+                // Don't insert malformed pointers. Otherwise, it might overwrite an
+                // existing well-formed pointer. If we reach an index that would form a
+                // malformed pointer, then none of the pointers after this one will be well
+                // formed.
+                let pointer = offset as u16;
+                if ((pointer & 0b1100_0000_0000_0000) != 0b0000_0000_0000_0000) || (&self.octets[length_byte_index..] != &[0]) {
+                    return None;
                 }
+                length_byte_index += (self.octets[length_byte_index] as usize) + 1;
             }
         }
+        None
+    }
 
-        wire.write_bytes(&self.octets)
+    /// The number of octets `to_wire_format()` would write for this name if it were written at
+    /// `offset` with the given compression map.
+    #[inline]
+    pub fn wire_length(&self, offset: usize, compression: &Option<CompressionMap>) -> usize {
+        let pointer = compression.as_ref()
+            .and_then(|compression_map| self.find_compression_pointer(offset, compression_map));
+        match pointer {
+            Some((prefix_length, _)) => prefix_length + 2,
+            None => self.octets.len(),
+        }
+    }
+}
+
+impl ToWire for CDomainName {
+    #[inline]
+    fn to_wire_format<'a, 'b>(&self, wire: &'b mut crate::serde::wire::write_wire::WriteWire<'a>, compression: &mut Option<crate::types::c_domain_name::CompressionMap>) -> Result<(), crate::serde::wire::write_wire::WriteWireError> where 'a: 'b {
+        let pointer = compression.as_ref()
+            .and_then(|compression_map| self.find_compression_pointer(wire.current_len(), compression_map));
+        match pointer {
+            Some((prefix_length, pointer)) => {
+                wire.write_bytes(&self.octets[..prefix_length])?;
+                pointer.to_wire_format(wire, compression)
+            },
+            None => wire.write_bytes(&self.octets),
+        }
     }
 
     #[inline]
@@ -717,6 +830,11 @@ impl ToPresentation for CDomainName {
     fn to_presentation_format(&self, out_buffer: &mut Vec<String>) {
         out_buffer.push(self.to_string())
     }
+
+    #[inline]
+    fn write_presentation_tokens<W: std::fmt::Write + ?Sized>(&self, out: &mut PresentationWriter<'_, W>) -> std::fmt::Result {
+        out.write_display(self)
+    }
 }
 
 #[derive(Clone, PartialEq, Eq, Debug)]
@@ -745,7 +863,7 @@ impl CompressionMap {
 mod circular_serde_sanity_test {
     use tinyvec::TinyVec;
 
-    use crate::{serde::wire::{circular_test::gen_test_circular_serde_sanity_test, from_wire::FromWire, read_wire::ReadWire, to_wire::ToWire, write_wire::WriteWire}, types::{ascii::AsciiString, c_domain_name::CDomainName, label::{CaseSensitiveOwnedLabel, Label}}};
+    use crate::{serde::wire::{circular_test::gen_test_circular_serde_sanity_test, from_wire::FromWire, read_wire::ReadWire, to_wire::ToWire, write_wire::WriteWire}, types::{ascii::AsciiString, c_domain_name::{CDomainName, CmpDomainName}, label::{CaseSensitiveOwnedLabel, Label}}};
 
 
     gen_test_circular_serde_sanity_test!(
@@ -802,4 +920,132 @@ mod circular_serde_sanity_test {
             assert_eq!(expected_search_names, actual_search_names);
         }
     }
+
+    #[test]
+    fn set_case_only_changes_letters() {
+        let lower = CDomainName::from_utf8("www.a-1.example.").unwrap();
+        let mut mixed = lower.clone();
+        let mut uppercase = false;
+        mixed.set_case_with(|| { uppercase = !uppercase; uppercase });
+        assert_eq!(mixed.to_string(), "WwW.a-1.ExAmPlE.");
+        assert_eq!(mixed.label_count(), lower.label_count());
+        assert!(mixed.matches(&lower));
+        assert!(!mixed.matches_case(&lower));
+        assert!(mixed.matches_case(&CDomainName::from_utf8("WwW.a-1.ExAmPlE.").unwrap()));
+
+        mixed.set_case_with(|| false);
+        assert!(mixed.matches_case(&lower));
+    }
+}
+
+#[cfg(test)]
+mod presentation_escape_tests {
+    use proptest::prelude::*;
+
+    use crate::{serde::{presentation::{from_presentation::FromPresentation, to_presentation::ToPresentation}, wire::{from_wire::FromWire, read_wire::ReadWire}}, types::label::Label};
+
+    use super::CDomainName;
+
+    /// Builds the wire format of a domain name out of arbitrary label octets.
+    fn labels_to_wire(labels: &[Vec<u8>]) -> Vec<u8> {
+        let mut wire = Vec::new();
+        for label in labels {
+            wire.push(label.len() as u8);
+            wire.extend_from_slice(label);
+        }
+        wire.push(0);
+        wire
+    }
+
+    fn case_sensitive_labels(domain_name: &CDomainName) -> Vec<Vec<u8>> {
+        domain_name.case_sensitive_labels().map(|label| label.octets().to_vec()).collect()
+    }
+
+    #[test]
+    fn special_characters_are_escaped() {
+        let wire = labels_to_wire(&[b"a.b c".to_vec(), br#"\;"()@$"#.to_vec(), vec![0, 9, 127, 200, 255]]);
+        let domain_name = CDomainName::from_wire_format(&mut ReadWire::from_bytes(&wire)).unwrap();
+        assert_eq!(domain_name.to_string(), r#"a\.b\ c.\\\;\"\(\)\@\$.\000\009\127\200\255."#);
+    }
+
+    #[test]
+    fn escapes_are_parsed() {
+        let domain_name = CDomainName::from_token_format(&[r"a\.b\065\255.c."]).unwrap().0;
+        assert_eq!(case_sensitive_labels(&domain_name), vec![b"a.bA\xff".to_vec(), b"c".to_vec(), vec![]]);
+    }
+
+    #[test]
+    fn out_of_range_decimal_escape() {
+        assert!(CDomainName::from_token_format(&[r"a\256.c."]).is_err());
+    }
+
+    proptest! {
+        #[test]
+        fn binary_labels_round_trip(labels in prop::collection::vec(prop::collection::vec(any::<u8>(), 1..=63), 0..=3)) {
+            let wire = labels_to_wire(&labels);
+            let domain_name = CDomainName::from_wire_format(&mut ReadWire::from_bytes(&wire)).unwrap();
+
+            let mut presentation = String::new();
+            domain_name.write_presentation(&mut presentation).unwrap();
+            prop_assert!(presentation.is_ascii());
+
+            let tokens = [presentation.as_str()];
+            let (parsed, remaining) = CDomainName::from_token_format(&tokens).unwrap();
+            prop_assert!(remaining.is_empty());
+            prop_assert_eq!(case_sensitive_labels(&parsed), case_sensitive_labels(&domain_name));
+        }
+    }
+}
+
+#[cfg(test)]
+mod mailbox_tests {
+    use crate::types::label::Label;
+
+    use super::CDomainName;
+
+    #[test]
+    fn simple_mailbox() {
+        let domain_name = CDomainName::from_mailbox("hostmaster@example.com").unwrap();
+        assert_eq!(domain_name, CDomainName::from_utf8("hostmaster.example.com.").unwrap());
+        assert_eq!(domain_name.to_mailbox().as_deref(), Some("hostmaster@example.com"));
+    }
+
+    #[test]
+    fn local_part_with_period() {
+        let domain_name = CDomainName::from_mailbox("john.doe@example.com.").unwrap();
+        assert_eq!(domain_name, CDomainName::from_utf8("john\\.doe.example.com.").unwrap());
+        assert_eq!(domain_name.label_count(), 4);
+        assert_eq!(domain_name.to_string(), "john\\.doe.example.com.");
+        assert_eq!(domain_name.to_mailbox().as_deref(), Some("john.doe@example.com"));
+    }
+
+    #[test]
+    fn internationalized_local_part() {
+        let domain_name = CDomainName::from_mailbox("δοκιμή@example.com").unwrap();
+        let first_label = domain_name.case_sensitive_labels().next().unwrap();
+        assert_eq!(first_label.octets(), "δοκιμή".as_bytes());
+        assert_eq!(domain_name.to_mailbox().as_deref(), Some("δοκιμή@example.com"));
+    }
+
+    #[test]
+    fn quoted_local_part() {
+        let domain_name = CDomainName::from_mailbox("\"john doe\"@example.com").unwrap();
+        assert_eq!(domain_name.to_mailbox().as_deref(), Some("\"john doe\"@example.com"));
+    }
+
+    #[test]
+    fn bad_mailboxes() {
+        assert!(CDomainName::from_mailbox("example.com").is_err());
+        assert!(CDomainName::from_mailbox("@example.com").is_err());
+        assert!(CDomainName::from_mailbox("hostmaster@").is_err());
+        assert!(CDomainName::from_mailbox("hostmaster@bücher.example").is_err());
+        assert!(CDomainName::from_mailbox(&format!("{}@example.com", "a".repeat(64))).is_err());
+    }
+
+    #[test]
+    fn not_a_mailbox() {
+        assert_eq!(CDomainName::new_root().to_mailbox(), None);
+        assert_eq!(CDomainName::from_utf8("com.").unwrap().to_mailbox(), None);
+        assert_eq!(CDomainName::from_utf8("\\255.example.com.").unwrap().to_mailbox(), None);
+    }
 }
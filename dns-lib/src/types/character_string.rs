@@ -1,14 +1,14 @@
 use std::{error::Error, fmt::Display, iter::Rev, slice::{Iter, IterMut}};
 
-use lazy_static::lazy_static;
 
-use crate::{serde::{presentation::{errors::TokenError, from_presentation::FromPresentation, to_presentation::ToPresentation}, wire::{from_wire::FromWire, to_wire::ToWire}}, types::ascii::{constants::{ASCII_BACKSLASH, ASCII_CLOSE_PARENTHESIS, ASCII_OPEN_PARENTHESIS, ASCII_SEMICOLON, ASCII_SPACE}, AsciiChar, AsciiError, AsciiString}};
+use crate::{serde::{presentation::{errors::TokenError, from_presentation::FromPresentation, parse_chars::{char_token::EscapableChar, escaped_to_escapable::{EscapedToEscapableIter, ParseError}, non_escaped_to_escaped::NonEscapedIntoEscapedIter}, to_presentation::{PresentationWriter, ToPresentation}}, wire::{from_wire::FromWire, to_wire::ToWire}}, types::ascii::{constants::{ASCII_CLOSE_PARENTHESIS, ASCII_DOUBLE_QUOTES, ASCII_OPEN_PARENTHESIS, ASCII_SEMICOLON, ASCII_SPACE}, AsciiChar, AsciiError, AsciiString}};
 
-use super::ascii::constants::ASCII_HORIZONTAL_TAB;
+use super::ascii::constants::ASCII_AT_SIGN;
 
 #[derive(Clone, PartialEq, Eq, Hash, Debug)]
 pub enum CharacterStringError {
     AsciiError(AsciiError),
+    ParseError(ParseError),
     ExceededMaxString,
 }
 
@@ -17,6 +17,7 @@ impl Display for CharacterStringError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             Self::AsciiError(error) => write!(f, "{error}"),
+            Self::ParseError(error) => write!(f, "{error}"),
             Self::ExceededMaxString => write!(f, "String Exceeded 255 Bytes in Txt"),
         }
     }
@@ -26,6 +27,11 @@ impl From<AsciiError> for CharacterStringError {
         Self::AsciiError(value)
     }
 }
+impl From<ParseError> for CharacterStringError {
+    fn from(value: ParseError) -> Self {
+        Self::ParseError(value)
+    }
+}
 
 /// Implemented as a wrapper around AsciiString, but follows the rules of a DNS
 /// character string so this is preferred when those rules need to be followed.
@@ -67,6 +73,17 @@ impl CharacterString {
         )
     }
 
+    /// Creates a character string from its presentation format, replacing any `\X` and `\DDD`
+    /// escape sequences with the characters they represent.
+    #[inline]
+    pub fn from_escaped_utf8(string: &str) -> Result<Self, CharacterStringError> {
+        let escaped = AsciiString::from_utf8(string)?;
+        let unescaped = EscapedToEscapableIter::new(escaped.iter().copied())
+            .map(|character| character.map(EscapableChar::into_unescaped_character))
+            .collect::<Result<Vec<_>, _>>()?;
+        Self::new(AsciiString::from(&unescaped))
+    }
+
     #[inline]
     pub fn len(&self) -> usize {
         self.ascii.len()
@@ -284,29 +301,43 @@ impl FromPresentation for CharacterString {
     fn from_token_format<'a, 'b, 'c, 'd>(tokens: &'c [&'a str]) -> Result<(Self, &'d [&'a str]), TokenError<'b>> where Self: Sized, 'a: 'b, 'c: 'd, 'c: 'd {
         match tokens {
             &[] => Err(TokenError::OutOfTokens),
-            &[token, ..] => Ok((Self::from_utf8(token)?, &tokens[1..])),
+            &[token, ..] => Ok((Self::from_escaped_utf8(token)?, &tokens[1..])),
         }
     }
 }
 
+impl CharacterString {
+    /// Iterates over the characters of the string as they should be written in presentation
+    /// format. Non-printable octets are written as `\DDD`. Backslashes and characters that would
+    /// otherwise end the token are written as `\X`.
+    #[inline]
+    fn iter_escaped<'a>(&'a self) -> impl Iterator<Item = EscapableChar> + 'a {
+        NonEscapedIntoEscapedIter::new(self.ascii.iter().copied())
+            .map(|character| match character {
+                EscapableChar::Ascii(character @ (ASCII_SPACE | ASCII_SEMICOLON | ASCII_OPEN_PARENTHESIS | ASCII_CLOSE_PARENTHESIS | ASCII_DOUBLE_QUOTES)) => EscapableChar::EscapedAscii(character),
+                _ => character,
+            })
+    }
+}
+
 impl ToPresentation for CharacterString {
     fn to_presentation_format(&self, out_buffer: &mut Vec<String>) {
-        lazy_static!{
-            static ref AT_SIGN: AsciiString = AsciiString::from_utf8(r"@").unwrap();
-        }
+        let mut token = String::new();
+        // Writing to a String cannot fail.
+        let _ = self.write_presentation(&mut token);
+        out_buffer.push(token)
+    }
 
-        if self.ascii == *AT_SIGN {
-            out_buffer.push(r"\@".to_string());
-            return;
+    fn write_presentation_tokens<W: std::fmt::Write + ?Sized>(&self, out: &mut PresentationWriter<'_, W>) -> std::fmt::Result {
+        let out = out.start_token()?;
+        // An empty token would be skipped over when the record is read back in.
+        if self.ascii.is_empty() {
+            return out.write_str("\"\"");
         }
-
-        let out_string = self.ascii.iter()
-            .flat_map(|character| match *character {
-                ASCII_SPACE | ASCII_HORIZONTAL_TAB | ASCII_SEMICOLON | ASCII_OPEN_PARENTHESIS | ASCII_CLOSE_PARENTHESIS => vec![ASCII_BACKSLASH as char, *character as char],
-                _ => vec![*character as char]
-            }).collect::<String>();
-
-        out_buffer.push(out_string)
+        if self.ascii.len() == 1 && self.ascii.iter().next() == Some(&ASCII_AT_SIGN) {
+            return out.write_str(r"\@");
+        }
+        self.iter_escaped().try_for_each(|character| write!(out, "{character}"))
     }
 }
 
@@ -328,3 +359,45 @@ mod circular_serde_sanity_test {
         CharacterString::from_utf8("").unwrap()
     );
 }
+
+#[cfg(test)]
+mod presentation_tests {
+    use crate::serde::presentation::{from_presentation::FromPresentation, to_presentation::ToPresentation};
+    use super::CharacterString;
+
+    #[test]
+    fn escapes_round_trip() {
+        let string = CharacterString::from_utf8("hello world; (a)\t@").unwrap();
+        let mut presentation = String::new();
+        string.write_presentation(&mut presentation).unwrap();
+        assert_eq!(presentation, r"hello\ world\;\ \(a\)\009@");
+        let (parsed, _) = CharacterString::from_token_format(&[&presentation]).unwrap();
+        assert_eq!(parsed, string);
+    }
+}
+
+#[cfg(test)]
+mod presentation_escape_tests {
+    use proptest::prelude::*;
+
+    use crate::{serde::presentation::{from_presentation::FromPresentation, to_presentation::ToPresentation}, types::ascii::AsciiString};
+
+    use super::CharacterString;
+
+    proptest! {
+        #[test]
+        fn binary_strings_round_trip(octets in prop::collection::vec(any::<u8>(), 0..=255)) {
+            let string = CharacterString::new(AsciiString::from(&octets)).unwrap();
+
+            let mut presentation = String::new();
+            string.write_presentation(&mut presentation).unwrap();
+            prop_assert!(presentation.is_ascii());
+
+            // The tokenizer strips the quotes off of quoted strings.
+            let token = presentation.strip_prefix('"').and_then(|token| token.strip_suffix('"')).unwrap_or(&presentation);
+            let tokens = [token];
+            let (parsed, _) = CharacterString::from_token_format(&tokens).unwrap();
+            prop_assert_eq!(parsed, string);
+        }
+    }
+}
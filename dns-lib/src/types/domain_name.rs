@@ -124,6 +124,18 @@ impl DomainName {
     pub fn search_domains<'a>(&'a self) -> impl 'a + ExactSizeIterator<Item = Self> {
         self.domain_name.search_domains().map(|domain_name| DomainName { domain_name })
     }
+
+    /// See `CDomainName::from_mailbox`.
+    #[inline]
+    pub fn from_mailbox(mailbox: &str) -> Result<Self, DomainNameError> {
+        Ok(Self { domain_name: CDomainName::from_mailbox(mailbox)? })
+    }
+
+    /// See `CDomainName::to_mailbox`.
+    #[inline]
+    pub fn to_mailbox(&self) -> Option<String> {
+        self.domain_name.to_mailbox()
+    }
 }
 
 impl Debug for DomainName {
@@ -156,6 +168,11 @@ impl CmpDomainName<CDomainName> for DomainName {
         self.domain_name.matches(other)
     }
 
+    #[inline]
+    fn matches_case(&self, other: &CDomainName) -> bool {
+        self.domain_name.matches_case(other)
+    }
+
     #[inline]
     fn is_parent_domain_of(&self, child: &CDomainName) -> bool {
         self.domain_name.is_parent_domain_of(child)
@@ -168,6 +185,11 @@ impl CmpDomainName<DomainName> for DomainName {
         self.domain_name.matches(&other.domain_name)
     }
 
+    #[inline]
+    fn matches_case(&self, other: &DomainName) -> bool {
+        self.domain_name.matches_case(&other.domain_name)
+    }
+
     #[inline]
     fn is_parent_domain_of(&self, child: &DomainName) -> bool {
         self.domain_name.matches(&child.domain_name)
@@ -4,7 +4,7 @@ use tinyvec::{tiny_vec, TinyVec};
 
 use crate::{serde::presentation::parse_chars::{char_token::EscapableChar, non_escaped_to_escaped}, types::ascii::AsciiChar};
 
-use super::ascii::constants::ASCII_PERIOD;
+use super::ascii::constants::{ASCII_AT_SIGN, ASCII_CLOSE_PARENTHESIS, ASCII_DOLLAR_SIGN, ASCII_DOUBLE_QUOTES, ASCII_OPEN_PARENTHESIS, ASCII_PERIOD, ASCII_SEMICOLON, ASCII_SPACE};
 
 
 pub trait Label: Hash + PartialEq + Eq {
@@ -57,11 +57,14 @@ pub trait Label: Hash + PartialEq + Eq {
         CaseSensitiveOwnedLabel { octets }
     }
 
+    /// Iterates over the characters of the label as they should be written in presentation
+    /// format. Non-printable octets are written as `\DDD`. Periods and characters with special
+    /// meaning in zone files are written as `\X`.
     #[inline]
     fn iter_escaped<'a>(&'a self) -> impl Iterator<Item = EscapableChar> + 'a {
         non_escaped_to_escaped::NonEscapedIntoEscapedIter::from(self.octets().iter().map(|character| *character))
             .map(|character| match character {
-                EscapableChar::Ascii(ASCII_PERIOD) => EscapableChar::EscapedAscii(ASCII_PERIOD),
+                EscapableChar::Ascii(character @ (ASCII_PERIOD | ASCII_SPACE | ASCII_SEMICOLON | ASCII_OPEN_PARENTHESIS | ASCII_CLOSE_PARENTHESIS | ASCII_DOUBLE_QUOTES | ASCII_AT_SIGN | ASCII_DOLLAR_SIGN)) => EscapableChar::EscapedAscii(character),
                 EscapableChar::Ascii(character) => EscapableChar::Ascii(character),
                 _ => character,
             })
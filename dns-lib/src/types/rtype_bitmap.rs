@@ -1,6 +1,6 @@
 use tinyvec::ArrayVec;
 
-use crate::{resource_record::rtype::RType, serde::{presentation::{from_presentation::FromPresentation, to_presentation::ToPresentation}, wire::{from_wire::FromWire, read_wire::ReadWireError, to_wire::ToWire}}};
+use crate::{resource_record::rtype::RType, serde::{presentation::{from_presentation::FromPresentation, to_presentation::{PresentationWriter, ToPresentation}}, wire::{from_wire::FromWire, read_wire::ReadWireError, to_wire::ToWire}}};
 
 #[derive(Clone, PartialEq, Eq, Hash, Debug)]
 struct WindowBlock {
@@ -167,6 +167,10 @@ impl ToPresentation for RTypeBitmap {
             rtype.to_presentation_format(out_buffer);
         }
     }
+
+    fn write_presentation_tokens<W: std::fmt::Write + ?Sized>(&self, out: &mut PresentationWriter<'_, W>) -> std::fmt::Result {
+        self.to_rtypes().try_for_each(|rtype| rtype.write_presentation_tokens(out))
+    }
 }
 
 impl FromPresentation for RTypeBitmap {
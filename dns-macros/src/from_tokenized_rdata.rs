@@ -1,77 +1,161 @@
 use proc_macro;
-use syn::{DeriveInput, Data, DataStruct};
+use syn::{DeriveInput, Data, DataStruct, Field, GenericArgument, PathArguments, Type};
 use quote::quote;
 
 pub fn impl_from_tokenized_rdata_macro(ast: &DeriveInput) -> proc_macro::TokenStream {
     match &ast.data {
-        Data::Struct(data) => impl_from_tokenized_rdata_struct_macro(data, ast),
+        Data::Struct(data) => impl_from_tokenized_rdata_struct_macro(data, ast).unwrap_or_else(|error| error.to_compile_error().into()),
         Data::Enum(_) => panic!("Enum not implemented"),
         Data::Union(_) => panic!("Union not implemented"),
     }
 }
 
-fn impl_from_tokenized_rdata_struct_macro(data: &DataStruct, ast: &DeriveInput) -> proc_macro::TokenStream {
+/// How many tokens a field is read from.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum FieldKind {
+    /// Exactly one token.
+    Required,
+    /// `#[rdata(optional)]`: an `Option<T>` read from one token if there are any left.
+    Optional,
+    /// `#[rdata(repeated)]`: a `Vec<T>` with one item per remaining token. With
+    /// `#[rdata(repeated, nonempty)]`, at least one token is required.
+    Repeated { nonempty: bool },
+}
+
+fn field_kind(field: &Field) -> syn::Result<FieldKind> {
+    let mut optional = false;
+    let mut repeated = false;
+    let mut nonempty = false;
+    for attribute in field.attrs.iter().filter(|attribute| attribute.path().is_ident("rdata")) {
+        attribute.parse_nested_meta(|meta| {
+            if meta.path.is_ident("optional") {
+                optional = true;
+                Ok(())
+            } else if meta.path.is_ident("repeated") {
+                repeated = true;
+                Ok(())
+            } else if meta.path.is_ident("nonempty") {
+                nonempty = true;
+                Ok(())
+            } else {
+                Err(meta.error("unsupported rdata attribute; expected `optional`, `repeated`, or `nonempty`"))
+            }
+        })?;
+    }
+    match (optional, repeated, nonempty) {
+        (false, false, false) => Ok(FieldKind::Required),
+        (true, false, false) => Ok(FieldKind::Optional),
+        (false, true, _) => Ok(FieldKind::Repeated { nonempty }),
+        (true, true, _) => Err(syn::Error::new_spanned(field, "a field cannot be both `optional` and `repeated`")),
+        (_, false, true) => Err(syn::Error::new_spanned(field, "`nonempty` can only be used with `repeated`")),
+    }
+}
+
+/// The `T` in `Option<T>` or `Vec<T>`.
+fn inner_type<'a>(field: &'a Field, wrapper: &str) -> syn::Result<&'a Type> {
+    if let Type::Path(type_path) = &field.ty {
+        if let Some(segment) = type_path.path.segments.last() {
+            if segment.ident == wrapper {
+                if let PathArguments::AngleBracketed(arguments) = &segment.arguments {
+                    if let Some(GenericArgument::Type(inner_type)) = arguments.args.first() {
+                        return Ok(inner_type);
+                    }
+                }
+            }
+        }
+    }
+    Err(syn::Error::new_spanned(&field.ty, format!("expected a field of type `{wrapper}<T>`")))
+}
+
+fn impl_from_tokenized_rdata_struct_macro(data: &DataStruct, ast: &DeriveInput) -> syn::Result<proc_macro::TokenStream> {
     let name = &ast.ident;
 
     let mut from_token_calls = quote!{};
-    let mut pattern_match = quote!{};
-    let mut ignored_pattern_match = quote!{};
     let mut struct_declaration_builder = quote!{};
-    let field_count = data.fields.len();
-    for field in data.fields.iter() {
+    let mut min_tokens: usize = 0;
+    let mut max_tokens: Option<usize> = Some(0);
+    let mut previous_kind = FieldKind::Required;
+    for (index, field) in data.fields.iter().enumerate() {
         let field_name = &field.ident;
         let field_type = &field.ty;
+        let kind = field_kind(field)?;
 
-        pattern_match.extend(quote! {
-            #field_name,
-        });
-
-        ignored_pattern_match.extend(quote! {
-            _,
-        });
+        // Tokens are assigned to fields in order, so every field that needs a token must come
+        // before any field that may not get one.
+        match (previous_kind, kind) {
+            (FieldKind::Repeated { .. }, _) => return Err(syn::Error::new_spanned(field, "a `repeated` field must be the last field")),
+            (FieldKind::Optional, FieldKind::Required) => return Err(syn::Error::new_spanned(field, "a required field cannot follow an `optional` field")),
+            _ => (),
+        }
+        previous_kind = kind;
 
-        from_token_calls.extend(quote! {
-            let (#field_name, _) = <#field_type as crate::serde::presentation::from_presentation::FromPresentation>::from_token_format(&[#field_name])?;
-        });
+        match kind {
+            FieldKind::Required => {
+                min_tokens += 1;
+                max_tokens = max_tokens.map(|max_tokens| max_tokens + 1);
+                from_token_calls.extend(quote! {
+                    let #field_name = <#field_type as crate::serde::presentation::from_presentation::FromPresentation>::from_token_format(&[rdata[#index]])?.0;
+                });
+            },
+            FieldKind::Optional => {
+                let inner_type = inner_type(field, "Option")?;
+                max_tokens = max_tokens.map(|max_tokens| max_tokens + 1);
+                from_token_calls.extend(quote! {
+                    let #field_name = match rdata.get(#index) {
+                        Some(token) => Some(<#inner_type as crate::serde::presentation::from_presentation::FromPresentation>::from_token_format(&[*token])?.0),
+                        None => None,
+                    };
+                });
+            },
+            FieldKind::Repeated { nonempty } => {
+                let inner_type = inner_type(field, "Vec")?;
+                if nonempty {
+                    min_tokens += 1;
+                }
+                max_tokens = None;
+                from_token_calls.extend(quote! {
+                    let mut #field_name = Vec::with_capacity(rdata.len().saturating_sub(#index));
+                    for token in rdata.iter().skip(#index) {
+                        #field_name.push(<#inner_type as crate::serde::presentation::from_presentation::FromPresentation>::from_token_format(&[*token])?.0);
+                    }
+                });
+            },
+        }
 
         struct_declaration_builder.extend(quote!(
             #field_name: #field_name,
         ))
     }
 
-    let gen;
-    if struct_declaration_builder.is_empty() {
-        // Case 1: Struct has no fields.
-        gen = quote! {
-            impl crate::serde::presentation::from_tokenized_rdata::FromTokenizedRData for #name {
-                #[inline]
-                fn from_tokenized_rdata<'a, 'b>(rdata: &Vec<&'a str>) -> Result<Self, crate::serde::presentation::errors::TokenizedRecordError<'b>> where Self: Sized, 'a: 'b {
-                    match rdata.as_slice() {
-                        &[] => Ok(Self {}),
-                        &[..] => Err(crate::serde::presentation::errors::TokenizedRecordError::TooManyRDataTokensError(0, rdata.len())),
-                    }
-                }
+    let too_few_check = match min_tokens {
+        0 => quote!{},
+        _ => quote! {
+            if rdata.len() < #min_tokens {
+                return Err(crate::serde::presentation::errors::TokenizedRecordError::TooFewRDataTokensError{expected: #min_tokens, received: rdata.len()});
             }
-        };
-    } else {
-        // Case 2: Struct has 1+ fields.
-        gen = quote! {
-            impl crate::serde::presentation::from_tokenized_rdata::FromTokenizedRData for #name {
-                #[inline]
-                fn from_tokenized_rdata<'a, 'b>(rdata: &Vec<&'a str>) -> Result<Self, crate::serde::presentation::errors::TokenizedRecordError<'b>> where Self: Sized, 'a: 'b {
-                    match rdata.as_slice() {
-                        &[#pattern_match] => {
-                            #from_token_calls
-                            Ok(Self {
-                                #struct_declaration_builder
-                            })
-                        },
-                        &[#ignored_pattern_match ..] => Err(crate::serde::presentation::errors::TokenizedRecordError::TooManyRDataTokensError{expected: #field_count, received: rdata.len()}),
-                        &[..] => Err(crate::serde::presentation::errors::TokenizedRecordError::TooFewRDataTokensError{expected: #field_count, received: rdata.len()}),
-                    }
-                }
+        },
+    };
+    let too_many_check = match max_tokens {
+        Some(max_tokens) => quote! {
+            if rdata.len() > #max_tokens {
+                return Err(crate::serde::presentation::errors::TokenizedRecordError::TooManyRDataTokensError{expected: #max_tokens, received: rdata.len()});
             }
-        };
-    }
-    gen.into()
+        },
+        None => quote!{},
+    };
+
+    let gen = quote! {
+        impl crate::serde::presentation::from_tokenized_rdata::FromTokenizedRData for #name {
+            #[inline]
+            fn from_tokenized_rdata<'a, 'b>(rdata: &Vec<&'a str>) -> Result<Self, crate::serde::presentation::errors::TokenizedRecordError<'b>> where Self: Sized, 'a: 'b {
+                #too_few_check
+                #too_many_check
+                #from_token_calls
+                Ok(Self {
+                    #struct_declaration_builder
+                })
+            }
+        }
+    };
+    Ok(gen.into())
 }
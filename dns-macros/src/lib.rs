@@ -35,7 +35,11 @@ pub fn derive_from_wire(input: TokenStream) -> TokenStream {
     impl_from_wire_macro(&ast)
 }
 
-#[proc_macro_derive(FromTokenizedRData)]
+/// Each field is read from one token, in order. The trailing fields can be marked with
+/// `#[rdata(optional)]` (an `Option<T>` that is `None` if there are no tokens left) and the last
+/// field with `#[rdata(repeated)]` (a `Vec<T>` with one item per remaining token). Add `nonempty`
+/// to a repeated field to require at least one item.
+#[proc_macro_derive(FromTokenizedRData, attributes(rdata))]
 pub fn derive_from_tokenized_rdata(input: TokenStream) -> TokenStream {
     // Construct a representation of Rust code as a syntax tree
     // that we can manipulate
@@ -45,7 +49,9 @@ pub fn derive_from_tokenized_rdata(input: TokenStream) -> TokenStream {
     impl_from_tokenized_rdata_macro(&ast)
 }
 
-#[proc_macro_derive(ToPresentation)]
+/// Each field is written in order. Mark the struct with `#[presentation(comment = method)]` to
+/// implement `presentation_comment()` with the method `fn method(&self) -> Option<String>`.
+#[proc_macro_derive(ToPresentation, attributes(presentation))]
 pub fn derive_to_presentation(input: TokenStream) -> TokenStream {
     // Construct a representation of Rust code as a syntax tree
     // that we can manipulate
@@ -1,35 +1,72 @@
 use proc_macro;
-use syn::{DeriveInput, Data, DataStruct};
+use syn::{DeriveInput, Data, DataStruct, Ident};
 use quote::quote;
 
 pub fn impl_to_presentation_macro(ast: &DeriveInput) -> proc_macro::TokenStream {
     match &ast.data {
-        Data::Struct(data) => impl_to_presentation_struct_macro(data, ast),
+        Data::Struct(data) => impl_to_presentation_struct_macro(data, ast).unwrap_or_else(|error| error.to_compile_error().into()),
         Data::Enum(_) => panic!("Enum not implemented"),
         Data::Union(_) => panic!("Union not implemented"),
     }
 }
 
-fn impl_to_presentation_struct_macro(data: &DataStruct, ast: &DeriveInput) -> proc_macro::TokenStream {
+/// The method named by `#[presentation(comment = method)]`, which returns the type's
+/// `presentation_comment()`.
+fn comment_method(ast: &DeriveInput) -> syn::Result<Option<Ident>> {
+    let mut method = None;
+    for attribute in ast.attrs.iter().filter(|attribute| attribute.path().is_ident("presentation")) {
+        attribute.parse_nested_meta(|meta| {
+            if meta.path.is_ident("comment") {
+                method = Some(meta.value()?.parse()?);
+                Ok(())
+            } else {
+                Err(meta.error("unsupported presentation attribute; expected `comment`"))
+            }
+        })?;
+    }
+    Ok(method)
+}
+
+fn impl_to_presentation_struct_macro(data: &DataStruct, ast: &DeriveInput) -> syn::Result<proc_macro::TokenStream> {
     let name = &ast.ident;
 
+    let comment_fn = match comment_method(ast)? {
+        Some(method) => quote! {
+            #[inline]
+            fn presentation_comment(&self) -> Option<String> {
+                Self::#method(self)
+            }
+        },
+        None => quote!{},
+    };
+
     let mut to_token_calls = quote!{};
-    let struct_declaration_builder = quote!{};
+    let mut write_token_calls = quote!{};
     for field in data.fields.iter() {
         let field_name = &field.ident;
 
         to_token_calls.extend(quote! {
-            (self.#field_name as crate::serde::presentation::to_presentation::ToPresentation).to_presentation_format(out_buffer);
+            crate::serde::presentation::to_presentation::ToPresentation::to_presentation_format(&self.#field_name, out_buffer);
+        });
+        write_token_calls.extend(quote! {
+            crate::serde::presentation::to_presentation::ToPresentation::write_presentation_tokens(&self.#field_name, out)?;
         });
     }
 
     let gen;
-    if struct_declaration_builder.is_empty() {
+    if to_token_calls.is_empty() {
         // Case 1: Struct has no fields.
         gen = quote! {
             impl crate::serde::presentation::to_presentation::ToPresentation for #name {
                 #[inline]
-                fn to_presentation_format(&self, out_buffer: &mut Vec<String>) {}
+                fn to_presentation_format(&self, _out_buffer: &mut Vec<String>) {}
+
+                #[inline]
+                fn write_presentation_tokens<W: std::fmt::Write + ?Sized>(&self, _out: &mut crate::serde::presentation::to_presentation::PresentationWriter<'_, W>) -> std::fmt::Result {
+                    Ok(())
+                }
+
+                #comment_fn
             }
         };
     } else {
@@ -40,8 +77,16 @@ fn impl_to_presentation_struct_macro(data: &DataStruct, ast: &DeriveInput) -> pr
                 fn to_presentation_format(&self, out_buffer: &mut Vec<String>) {
                     #to_token_calls
                 }
+
+                #[inline]
+                fn write_presentation_tokens<W: std::fmt::Write + ?Sized>(&self, out: &mut crate::serde::presentation::to_presentation::PresentationWriter<'_, W>) -> std::fmt::Result {
+                    #write_token_calls
+                    Ok(())
+                }
+
+                #comment_fn
             }
         };
     }
-    gen.into()
+    Ok(gen.into())
 }
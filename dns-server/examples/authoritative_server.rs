@@ -0,0 +1,99 @@
+//! Runs a minimal authoritative name server for one zone, loaded from a zone file. Before it starts
+//! listening, the zone's SOA record is looked up through the same code path that answers queries
+//! from the network, which catches zones that load but are not served as expected.
+//!
+//! ```text
+//! cargo run --example authoritative_server -- [--listen <address:port>] <origin> <zone-file>
+//! dig @127.0.0.1 -p 5353 example.com. SOA
+//! ```
+
+use std::{env, net::SocketAddr, process::ExitCode, sync::Arc};
+
+use dns_lib::prelude::*;
+use dns_server::{answer::answer_query, server::{AuthoritativeServer, ServerConfig}, zone_store::{Zone, ZoneStore}};
+
+const USAGE: &str = "usage: authoritative_server [--listen <address:port>] <origin> <zone-file>";
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    // An unprivileged port, so that the example can be run without root.
+    let mut listen = SocketAddr::from(([127, 0, 0, 1], 5353));
+    let mut positional = Vec::new();
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--listen" => match args.next().map(|address| address.parse()) {
+                Some(Ok(address)) => listen = address,
+                Some(Err(error)) => {
+                    eprintln!("invalid listen address: {error}");
+                    return ExitCode::FAILURE;
+                },
+                None => {
+                    eprintln!("missing address after '--listen'\n\n{USAGE}");
+                    return ExitCode::FAILURE;
+                },
+            },
+            _ => positional.push(arg),
+        }
+    }
+    let [origin, zone_path] = positional.as_slice() else {
+        eprintln!("{USAGE}");
+        return ExitCode::FAILURE;
+    };
+    let origin = match CDomainName::from_utf8(origin) {
+        Ok(origin) if origin.is_fully_qualified() => origin,
+        Ok(origin) => {
+            eprintln!("the origin '{origin}' must be fully qualified");
+            return ExitCode::FAILURE;
+        },
+        Err(error) => {
+            eprintln!("{error}");
+            return ExitCode::FAILURE;
+        },
+    };
+    let zone_file = match tokio::fs::read_to_string(zone_path).await {
+        Ok(zone_file) => zone_file,
+        Err(error) => {
+            eprintln!("failed to read '{zone_path}': {error}");
+            return ExitCode::FAILURE;
+        },
+    };
+    let zone = match Zone::from_zone_file(origin.clone(), RClass::Internet, &zone_file) {
+        Ok(zone) => zone,
+        Err(error) => {
+            eprintln!("failed to load '{zone_path}': {error}");
+            return ExitCode::FAILURE;
+        },
+    };
+    println!("loaded '{}' with {} records", zone.origin(), zone.len());
+
+    // The store is shared with the server, so zones can still be added or replaced once it is
+    // running.
+    let zones = Arc::new(ZoneStore::new());
+    zones.insert(zone);
+
+    let response = answer_query(&zones, &Message::from(Question::new(origin, RType::SOA, RClass::Internet)));
+    match (response.rcode, response.answer.first()) {
+        (RCode::NoError, Some(soa)) if response.authoritative_answer => println!("{soa}"),
+        (rcode, _) => {
+            eprintln!("the zone's SOA record was answered with {rcode}");
+            return ExitCode::FAILURE;
+        },
+    }
+
+    let server = match AuthoritativeServer::bind(listen, zones, ServerConfig::default()).await {
+        Ok(server) => server,
+        Err(error) => {
+            eprintln!("failed to listen on '{listen}': {error}");
+            return ExitCode::FAILURE;
+        },
+    };
+    println!("listening on '{}'", server.local_addr());
+    match tokio::signal::ctrl_c().await {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(error) => {
+            eprintln!("{error}");
+            ExitCode::FAILURE
+        },
+    }
+}
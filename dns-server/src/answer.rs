@@ -0,0 +1,292 @@
+use dns_lib::{query::{edns::{set_udp_payload_size, DEFAULT_EDNS_BUFFER_SIZE}, message::Message, qr::QR, question::Question}, resource_record::{opcode::OpCode, rcode::RCode, resource_record::{RecordData, ResourceRecord}, rtype::RType, time::Time}, types::c_domain_name::{CDomainName, CmpDomainName}};
+
+use crate::zone_store::{Zone, ZoneStore};
+
+/// The most CNAME records followed within a zone before the chain is assumed to be a loop.
+const MAX_CNAME_CHAIN: usize = 8;
+
+/// Answers the query from the zones, the way an authoritative name server does (RFC 1034 section
+/// 4.3.2). Names in one of the zones are answered with their records, a referral to the name
+/// servers of a delegated child zone, or NXDOMAIN or NODATA with the zone's SOA. CNAME records
+/// are followed for as long as they stay in the zone and wildcards are expanded (RFC 4592).
+///
+/// Queries for names outside every zone are refused. Zone transfers are not answered here since
+/// they are only allowed over TCP.
+pub fn answer_query(zones: &ZoneStore, query: &Message) -> Message {
+    let question = match standard_question(query) {
+        Ok(question) => question,
+        Err(rcode) => return empty_response(query, rcode),
+    };
+    let Some(zone) = zones.find(question.qname(), question.qclass()) else {
+        return empty_response(query, RCode::Refused);
+    };
+
+    let mut response = empty_response(query, RCode::NoError);
+    response.authoritative_answer = true;
+    let mut qname = question.qname().clone();
+    for _ in 0..MAX_CNAME_CHAIN {
+        match answer_name(&zone, &qname, question.qtype(), &mut response) {
+            Some(target) if zone.origin().is_parent_domain_of(&target) => qname = target,
+            // The rest of the chain is for the resolver to follow.
+            Some(_) | None => break,
+        }
+    }
+    response
+}
+
+/// Adds the answer for a single name to the response. If the name is an alias, the CNAME record
+/// is added and its target is returned so that the caller can continue with it.
+fn answer_name(zone: &Zone, qname: &CDomainName, qtype: RType, response: &mut Message) -> Option<CDomainName> {
+    if let Some(cut) = find_zone_cut(zone, qname, qtype) {
+        add_referral(zone, &cut, response);
+        return None;
+    }
+
+    let owner = if zone.contains_name(qname) {
+        qname.clone()
+    } else {
+        match wildcard(zone, qname) {
+            Some(wildcard) => wildcard,
+            None => {
+                response.rcode = RCode::NXDomain;
+                response.authority.push(negative_soa(zone));
+                return None;
+            },
+        }
+    };
+
+    let records = if qtype == RType::ANY {
+        zone.rrsets_at(&owner).flatten().collect::<Vec<_>>()
+    } else {
+        zone.rrset(&owner, qtype).iter().collect()
+    };
+    if !records.is_empty() {
+        response.answer.extend(records.into_iter().map(|record| synthesize(record, qname)));
+        return None;
+    }
+
+    if let Some(cname) = zone.rrset(&owner, RType::CNAME).first() {
+        let target = match cname.get_rdata() {
+            RecordData::CNAME(rdata) => rdata.primary_name().clone(),
+            _ => return None,
+        };
+        response.answer.push(synthesize(cname, qname));
+        return Some(target);
+    }
+
+    // NODATA: the name exists but does not have any records of this type.
+    response.authority.push(negative_soa(zone));
+    None
+}
+
+/// The highest delegation between the zone's apex and the name, including the name itself. A DS
+/// query for the delegated name is answered by the parent, which holds the DS records.
+fn find_zone_cut(zone: &Zone, qname: &CDomainName, qtype: RType) -> Option<CDomainName> {
+    let apex_labels = zone.origin().label_count();
+    qname.search_domains()
+        .rev()
+        .filter(|name| name.label_count() > apex_labels)
+        .filter(|name| !((qtype == RType::DS) && name.matches(qname)))
+        .find(|name| !zone.rrset(name, RType::NS).is_empty())
+}
+
+/// A referral is not authoritative. The NS records go in the authority section along with any
+/// glue addresses the zone has for them.
+fn add_referral(zone: &Zone, cut: &CDomainName, response: &mut Message) {
+    if response.answer.is_empty() {
+        response.authoritative_answer = false;
+    }
+    let name_servers = zone.rrset(cut, RType::NS);
+    response.authority.extend(name_servers.iter().cloned());
+    for name_server in name_servers {
+        let RecordData::NS(rdata) = name_server.get_rdata() else { continue };
+        let name_server = rdata.name_server_domain_name();
+        if !zone.origin().is_parent_domain_of(name_server) {
+            continue;
+        }
+        response.additional.extend(zone.rrset(name_server, RType::A).iter().cloned());
+        response.additional.extend(zone.rrset(name_server, RType::AAAA).iter().cloned());
+    }
+}
+
+/// The wildcard that covers a name that does not exist, if there is one. Only the closest
+/// encloser's wildcard can match (RFC 4592 section 3.3.1).
+fn wildcard(zone: &Zone, qname: &CDomainName) -> Option<CDomainName> {
+    let closest_encloser = qname.search_domains()
+        .skip(1)
+        .find(|name| zone.contains_name(name))?;
+    let wildcard = CDomainName::from_utf8(&format!("*.{closest_encloser}")).ok()?;
+    zone.contains_name(&wildcard).then_some(wildcard)
+}
+
+/// A copy of the record owned by the qname. This only changes the owner of records that came from
+/// a wildcard.
+#[inline]
+fn synthesize(record: &ResourceRecord, qname: &CDomainName) -> ResourceRecord {
+    ResourceRecord::new(qname.clone(), record.get_rclass(), record.get_ttl().clone(), record.get_rdata().clone())
+}
+
+/// The zone's SOA record with the TTL that negative answers are cached for, which is the lower of
+/// the SOA's TTL and its MINIMUM field.
+///
+/// https://datatracker.ietf.org/doc/html/rfc2308#section-3
+fn negative_soa(zone: &Zone) -> ResourceRecord {
+    let mut soa = zone.soa().clone();
+    if let RecordData::SOA(rdata) = soa.get_rdata() {
+        let ttl = soa.get_ttl().as_secs().min(*rdata.minimum());
+        soa.set_ttl(Time::from_secs(ttl));
+    }
+    soa
+}
+
+/// The question of a standard query, or the rcode to answer with if the message is not one.
+/// Standard queries have exactly one question, which is not for a zone transfer.
+pub(crate) fn standard_question(query: &Message) -> Result<&Question, RCode> {
+    if query.qr != QR::Query {
+        return Err(RCode::FormErr);
+    }
+    if query.opcode != OpCode::Query {
+        return Err(RCode::NotImp);
+    }
+    let [question] = query.question.as_slice() else {
+        return Err(RCode::FormErr);
+    };
+    if matches!(question.qtype(), RType::AXFR | RType::IXFR) {
+        return Err(RCode::FormErr);
+    }
+    Ok(question)
+}
+
+/// A response to the query with its ID, opcode, RD bit, and question, the rcode, and nothing
+/// else. If the query has EDNS, so does the response.
+pub(crate) fn empty_response(query: &Message, rcode: RCode) -> Message {
+    let mut response = query.clone();
+    response.qr = QR::Response;
+    response.authoritative_answer = false;
+    response.truncation = false;
+    response.recursion_available = false;
+    response.rcode = rcode;
+    response.answer.clear();
+    response.authority.clear();
+    response.additional.clear();
+    if query.edns().is_some() {
+        set_udp_payload_size(&mut response, DEFAULT_EDNS_BUFFER_SIZE);
+    }
+    response
+}
+
+#[cfg(test)]
+mod answer_tests {
+    use dns_lib::{query::{message::Message, qr::QR, question::Question}, resource_record::{rclass::RClass, rcode::RCode, rtype::RType}, types::c_domain_name::CDomainName};
+
+    use crate::zone_store::{Zone, ZoneStore};
+
+    use super::answer_query;
+
+    const ZONE: &str = "\
+@                              3600 IN SOA   ns1.example.com. hostmaster.example.com. 1 7200 3600 1209600 300
+@                              3600 IN NS    ns1.example.com.
+ns1.example.com.               3600 IN A     192.0.2.1
+www.example.com.               3600 IN A     192.0.2.2
+alias.example.com.             3600 IN CNAME www.example.com.
+outside.example.com.           3600 IN CNAME www.example.org.
+a.b.example.com.               3600 IN TXT   \"below an empty non-terminal\"
+*.wild.example.com.            3600 IN A     192.0.2.3
+child.example.com.             3600 IN NS    ns.child.example.com.
+ns.child.example.com.          3600 IN A     192.0.2.4
+";
+
+    fn zones() -> ZoneStore {
+        let zones = ZoneStore::new();
+        zones.insert(Zone::from_zone_file(CDomainName::from_utf8("example.com.").unwrap(), RClass::Internet, ZONE).unwrap());
+        zones
+    }
+
+    fn query(qname: &str, qtype: RType) -> Message {
+        let mut query = Message::from(Question::new(CDomainName::from_utf8(qname).unwrap(), qtype, RClass::Internet));
+        query.id = 7;
+        query
+    }
+
+    fn names_and_types(records: &[dns_lib::resource_record::resource_record::ResourceRecord]) -> Vec<(String, RType)> {
+        records.iter()
+            .map(|record| (record.get_name().to_string(), record.get_rtype()))
+            .collect()
+    }
+
+    #[test]
+    fn answers_are_authoritative() {
+        let response = answer_query(&zones(), &query("www.example.com.", RType::A));
+        assert_eq!(response.id, 7);
+        assert_eq!(response.qr, QR::Response);
+        assert_eq!(response.rcode, RCode::NoError);
+        assert!(response.authoritative_answer);
+        assert_eq!(names_and_types(&response.answer), vec![("www.example.com.".to_string(), RType::A)]);
+    }
+
+    #[test]
+    fn cnames_are_followed_within_the_zone() {
+        let response = answer_query(&zones(), &query("alias.example.com.", RType::A));
+        assert_eq!(names_and_types(&response.answer), vec![
+            ("alias.example.com.".to_string(), RType::CNAME),
+            ("www.example.com.".to_string(), RType::A),
+        ]);
+
+        let response = answer_query(&zones(), &query("outside.example.com.", RType::A));
+        assert_eq!(names_and_types(&response.answer), vec![("outside.example.com.".to_string(), RType::CNAME)]);
+        assert_eq!(response.rcode, RCode::NoError);
+    }
+
+    #[test]
+    fn missing_names_are_nxdomain_with_soa() {
+        let response = answer_query(&zones(), &query("missing.example.com.", RType::A));
+        assert_eq!(response.rcode, RCode::NXDomain);
+        assert!(response.authoritative_answer);
+        assert!(response.answer.is_empty());
+        assert_eq!(names_and_types(&response.authority), vec![("example.com.".to_string(), RType::SOA)]);
+        assert_eq!(response.authority[0].get_ttl().as_secs(), 300);
+    }
+
+    #[test]
+    fn missing_types_are_nodata_with_soa() {
+        for qname in ["www.example.com.", "b.example.com."] {
+            let response = answer_query(&zones(), &query(qname, RType::AAAA));
+            assert_eq!(response.rcode, RCode::NoError, "{qname}");
+            assert!(response.answer.is_empty(), "{qname}");
+            assert_eq!(names_and_types(&response.authority), vec![("example.com.".to_string(), RType::SOA)], "{qname}");
+        }
+    }
+
+    #[test]
+    fn wildcards_are_expanded() {
+        let response = answer_query(&zones(), &query("anything.wild.example.com.", RType::A));
+        assert_eq!(response.rcode, RCode::NoError);
+        assert_eq!(names_and_types(&response.answer), vec![("anything.wild.example.com.".to_string(), RType::A)]);
+
+        // Only the closest encloser's wildcard applies.
+        let response = answer_query(&zones(), &query("x.b.example.com.", RType::A));
+        assert_eq!(response.rcode, RCode::NXDomain);
+    }
+
+    #[test]
+    fn delegations_are_referrals_with_glue() {
+        let response = answer_query(&zones(), &query("www.child.example.com.", RType::A));
+        assert_eq!(response.rcode, RCode::NoError);
+        assert!(!response.authoritative_answer);
+        assert!(response.answer.is_empty());
+        assert_eq!(names_and_types(&response.authority), vec![("child.example.com.".to_string(), RType::NS)]);
+        assert_eq!(names_and_types(&response.additional), vec![("ns.child.example.com.".to_string(), RType::A)]);
+
+        // The parent side of the delegation answers for DS records.
+        let response = answer_query(&zones(), &query("child.example.com.", RType::DS));
+        assert!(response.authoritative_answer);
+        assert_eq!(names_and_types(&response.authority), vec![("example.com.".to_string(), RType::SOA)]);
+    }
+
+    #[test]
+    fn names_outside_the_zones_are_refused() {
+        let response = answer_query(&zones(), &query("www.example.org.", RType::A));
+        assert_eq!(response.rcode, RCode::Refused);
+        assert!(!response.authoritative_answer);
+    }
+}
@@ -0,0 +1,499 @@
+use std::{error::Error, fmt::Display, iter::{self, Peekable}, sync::Arc, time::Duration};
+
+use dns_lib::{query::{message::Message, qr::QR}, resource_record::{rcode::RCode, resource_record::ResourceRecord, rtype::RType}, serde::wire::{to_wire::ToWire, write_wire::{WriteWire, WriteWireError}}, tsig::TsigExchange, types::c_domain_name::{CmpDomainName, CompressionMap}};
+use tokio::{io::{AsyncWrite, AsyncWriteExt}, time::Instant};
+
+use crate::zone_store::{Zone, ZoneStore};
+
+/// The largest message sent in a transfer by default. Smaller messages than the 65535 octets
+/// allowed by RFC 5936 mean less is encoded ahead of what the receiver has read.
+const DEFAULT_MAX_MESSAGE_SIZE: u16 = 16 * 1024;
+
+/// At most 99 messages in a row may be left unsigned, so at least every 100th message of a
+/// transfer must be signed.
+///
+/// https://datatracker.ietf.org/doc/html/rfc8945#section-5.3.1
+const MAX_SIGNED_MESSAGE_INTERVAL: usize = 100;
+
+#[derive(Debug)]
+pub enum AxfrError {
+    /// The query is not a well formed AXFR query.
+    NotAxfr,
+    /// None of the zones have the question's name as their apex.
+    NotAuthoritative,
+    /// The record cannot fit in a message of the configured size, even on its own.
+    RecordTooLarge { record_length: usize, max_message_size: u16 },
+    Sign(String),
+    Encode(WriteWireError),
+    Io(std::io::Error),
+}
+impl Error for AxfrError {}
+impl Display for AxfrError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::NotAxfr => write!(f, "Query is not an AXFR query"),
+            Self::NotAuthoritative => write!(f, "Not authoritative for the zone requested for transfer"),
+            Self::RecordTooLarge { record_length, max_message_size } => write!(f, "Record of {record_length} bytes does not fit in a transfer message of at most {max_message_size} bytes"),
+            Self::Sign(error) => write!(f, "Failed to sign transfer message: {error}"),
+            Self::Encode(error) => write!(f, "{error}"),
+            Self::Io(error) => write!(f, "{error}"),
+        }
+    }
+}
+impl From<WriteWireError> for AxfrError {
+    fn from(error: WriteWireError) -> Self {
+        Self::Encode(error)
+    }
+}
+impl From<std::io::Error> for AxfrError {
+    fn from(error: std::io::Error) -> Self {
+        Self::Io(error)
+    }
+}
+
+impl AxfrError {
+    /// The RCODE to answer the query with if the transfer could not be started.
+    #[inline]
+    pub fn rcode(&self) -> RCode {
+        match self {
+            Self::NotAxfr => RCode::FormErr,
+            Self::NotAuthoritative => RCode::NotAuth,
+            Self::RecordTooLarge { .. }
+          | Self::Sign(_)
+          | Self::Encode(_)
+          | Self::Io(_) => RCode::ServFail,
+        }
+    }
+}
+
+/// Signs each message of a transfer just before it is written. The messages are given to the
+/// signer in order so that a signer that chains signatures, like TSIG (RFC 8945 section 5.3.1),
+/// can carry its state from one to the next. Signed messages are written out with a
+/// `CompressionMap`.
+pub trait EnvelopeSigner: Send {
+    /// The most octets that signing adds to a message. Messages are packed with this much room to
+    /// spare so that they still fit once they are signed.
+    fn max_signature_length(&self) -> usize;
+
+    /// Signs the message, or leaves it unsigned if the signer allows it. `last` is set for the
+    /// last message of the transfer.
+    fn sign(&mut self, message: &mut Message, last: bool) -> Result<(), AxfrError>;
+}
+
+/// Signs a transfer with TSIG. The first and last messages are signed, as is every
+/// `signed_message_interval`th message in between. The messages in between are left unsigned and
+/// are covered by the MAC of the next message that is signed.
+///
+/// https://datatracker.ietf.org/doc/html/rfc8945#section-5.3.1
+pub struct TsigEnvelopeSigner<'a> {
+    exchange: TsigExchange<'a>,
+    signed_message_interval: usize,
+    messages: usize,
+}
+
+impl<'a> TsigEnvelopeSigner<'a> {
+    /// The exchange must have already verified the AXFR query. The interval is limited to what
+    /// RFC 8945 allows, so an interval of 1 signs every message.
+    #[inline]
+    pub fn new(exchange: TsigExchange<'a>, signed_message_interval: usize) -> Self {
+        Self { exchange, signed_message_interval: signed_message_interval.clamp(1, MAX_SIGNED_MESSAGE_INTERVAL), messages: 0 }
+    }
+}
+
+impl<'a> EnvelopeSigner for TsigEnvelopeSigner<'a> {
+    #[inline]
+    fn max_signature_length(&self) -> usize {
+        self.exchange.key().max_signature_length()
+    }
+
+    fn sign(&mut self, message: &mut Message, last: bool) -> Result<(), AxfrError> {
+        let signed = last || (self.messages % self.signed_message_interval == 0);
+        self.messages += 1;
+        let result = match signed {
+            true => self.exchange.sign(message),
+            false => self.exchange.skip(message),
+        };
+        result.map_err(|error| AxfrError::Sign(error.to_string()))
+    }
+}
+
+/// Limits how quickly a single transfer is sent so that one secondary cannot use up the server's
+/// bandwidth. The first `burst_bytes` are sent without delay.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct TransferRateLimit {
+    pub bytes_per_second: u32,
+    pub burst_bytes: u32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct AxfrConfig {
+    /// The largest message sent in the transfer, including the signature.
+    pub max_message_size: u16,
+    pub rate_limit: Option<TransferRateLimit>,
+    /// How often the messages of a signed transfer are signed. See `TsigEnvelopeSigner`.
+    pub signed_message_interval: usize,
+}
+
+impl Default for AxfrConfig {
+    #[inline]
+    fn default() -> Self {
+        Self { max_message_size: DEFAULT_MAX_MESSAGE_SIZE, rate_limit: None, signed_message_interval: MAX_SIGNED_MESSAGE_INTERVAL }
+    }
+}
+
+/// The number of messages, records, and bytes (including the length prefixes) sent in a transfer.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct AxfrStats {
+    pub messages: usize,
+    pub records: usize,
+    pub bytes: u64,
+}
+
+/// Finds the zone that an AXFR query asks for. The question must name the apex of the zone
+/// exactly (RFC 5936 section 2.2.1).
+pub fn find_transfer_zone(zones: &ZoneStore, query: &Message) -> Result<Arc<Zone>, AxfrError> {
+    let [question] = query.question.as_slice() else {
+        return Err(AxfrError::NotAxfr);
+    };
+    if (query.qr != QR::Query) || (question.qtype() != RType::AXFR) {
+        return Err(AxfrError::NotAxfr);
+    }
+    zones.get(question.qname(), question.qclass()).ok_or(AxfrError::NotAuthoritative)
+}
+
+/// The messages of a transfer of the zone, built one at a time as they are needed. The zone's SOA
+/// comes first and last and every other record is in canonical order between them (RFC 5936
+/// section 2.2). Only the first message repeats the question.
+pub struct AxfrMessages<'a> {
+    query: &'a Message,
+    records: Peekable<Box<dyn Iterator<Item = &'a ResourceRecord> + Send + 'a>>,
+    max_payload_size: usize,
+    max_message_size: u16,
+    first: bool,
+}
+
+impl<'a> AxfrMessages<'a> {
+    /// `reserved` octets are left free at the end of each message for a signature.
+    pub fn new(zone: &'a Zone, query: &'a Message, max_message_size: u16, reserved: usize) -> Self {
+        let records: Box<dyn Iterator<Item = &'a ResourceRecord> + Send + 'a> = Box::new(
+            iter::once(zone.soa())
+                .chain(zone.records())
+                .chain(iter::once(zone.soa()))
+        );
+        Self {
+            query,
+            records: records.peekable(),
+            max_payload_size: usize::from(max_message_size).saturating_sub(reserved),
+            max_message_size,
+            first: true,
+        }
+    }
+
+    fn empty_response(&self) -> Message {
+        let mut response = self.query.clone();
+        response.qr = QR::Response;
+        response.authoritative_answer = true;
+        response.truncation = false;
+        response.recursion_available = false;
+        response.rcode = RCode::NoError;
+        if !self.first {
+            response.question.clear();
+        }
+        response.answer.clear();
+        response.authority.clear();
+        response.additional.clear();
+        response
+    }
+}
+
+impl<'a> Iterator for AxfrMessages<'a> {
+    type Item = Result<Message, AxfrError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.records.peek()?;
+        let mut message = self.empty_response();
+        let mut message_size = message.estimated_wire_size(false);
+        while let Some(record) = self.records.peek() {
+            let record_length = usize::from(record.serial_length());
+            if message_size + record_length > self.max_payload_size {
+                if message.answer.is_empty() {
+                    return Some(Err(AxfrError::RecordTooLarge { record_length, max_message_size: self.max_message_size }));
+                }
+                break;
+            }
+            message_size += record_length;
+            message.answer.push((*record).clone());
+            self.records.next();
+        }
+        self.first = false;
+        Some(Ok(message))
+    }
+}
+
+/// Spaces out the writes of a transfer so that it stays under its rate limit.
+struct Pacer {
+    limit: TransferRateLimit,
+    start: Instant,
+    sent_bytes: u64,
+}
+
+impl Pacer {
+    #[inline]
+    fn new(limit: TransferRateLimit) -> Self {
+        Self { limit, start: Instant::now(), sent_bytes: 0 }
+    }
+
+    async fn wait_to_send(&mut self, bytes: usize) {
+        let limited_bytes = self.sent_bytes.saturating_sub(u64::from(self.limit.burst_bytes));
+        self.sent_bytes += bytes as u64;
+        if (limited_bytes == 0) || (self.limit.bytes_per_second == 0) {
+            return;
+        }
+        let due = self.start + Duration::from_secs_f64(limited_bytes as f64 / f64::from(self.limit.bytes_per_second));
+        tokio::time::sleep_until(due).await;
+    }
+}
+
+/// Writes the transfer of the zone to a TCP stream, each message preceded by its two octet
+/// length.
+///
+/// Only one message is encoded at a time and the next one is not built until the stream has
+/// accepted the last, so a secondary that reads slowly holds the transfer back instead of the
+/// whole zone piling up in memory. If a signer is given, each message is handed to it before it is
+/// written.
+pub async fn send_axfr<W>(writer: &mut W, zone: &Zone, query: &Message, mut signer: Option<&mut dyn EnvelopeSigner>, config: &AxfrConfig) -> Result<AxfrStats, AxfrError>
+where
+    W: AsyncWrite + Unpin,
+{
+    if !query.question.first().is_some_and(|question| question.qname().matches(zone.origin())) {
+        return Err(AxfrError::NotAuthoritative);
+    }
+
+    let reserved = signer.as_ref().map_or(0, |signer| signer.max_signature_length());
+    let mut pacer = config.rate_limit.map(Pacer::new);
+    let mut buffer = vec![0_u8; 2 + usize::from(u16::MAX)];
+    let mut stats = AxfrStats::default();
+    let mut messages = AxfrMessages::new(zone, query, config.max_message_size, reserved).peekable();
+    while let Some(message) = messages.next() {
+        let mut message = message?;
+        // Signatures cover the messages as they are written with compression.
+        let mut compression = None;
+        if let Some(signer) = signer.as_mut() {
+            signer.sign(&mut message, messages.peek().is_none())?;
+            compression = Some(CompressionMap::new());
+        }
+
+        let mut wire = WriteWire::from_bytes(&mut buffer[2..]);
+        message.to_wire_format(&mut wire, &mut compression)?;
+        let message_length = wire.current_len();
+        buffer[..2].copy_from_slice(&(message_length as u16).to_be_bytes());
+        let wire_length = 2 + message_length;
+
+        if let Some(pacer) = pacer.as_mut() {
+            pacer.wait_to_send(wire_length).await;
+        }
+        writer.write_all(&buffer[..wire_length]).await?;
+
+        stats.messages += 1;
+        stats.records += message.answer.len();
+        stats.bytes += wire_length as u64;
+    }
+    writer.flush().await?;
+    Ok(stats)
+}
+
+#[cfg(test)]
+mod axfr_tests {
+    use dns_lib::{query::{message::Message, qr::QR, question::Question}, resource_record::{rclass::RClass, rtype::RType}, serde::wire::{from_wire::FromWire, read_wire::ReadWire}, tsig::{MacCalculator, TsigAlgorithm, TsigExchange, TsigKey}, types::c_domain_name::{CDomainName, CompressionMap}};
+
+    use crate::zone_store::{Zone, ZoneStore};
+
+    use super::{find_transfer_zone, send_axfr, AxfrConfig, AxfrError, AxfrMessages, EnvelopeSigner, TsigEnvelopeSigner};
+
+    const ZONE: &str = "\
+@                3600 IN SOA ns1.example.com. hostmaster.example.com. 1 7200 3600 1209600 300
+@                3600 IN NS  ns1.example.com.
+zz.example.com.  3600 IN A   192.0.2.3
+ns1.example.com. 3600 IN A   192.0.2.1
+a.example.com.   3600 IN TXT \"first\"
+@                3600 IN A   192.0.2.2
+";
+
+    fn zone() -> Zone {
+        Zone::from_zone_file(CDomainName::from_utf8("example.com.").unwrap(), RClass::Internet, ZONE).unwrap()
+    }
+
+    /// A zone with enough records that its transfer takes many small messages.
+    fn large_zone() -> Zone {
+        let hosts = (1..=20).map(|host| format!("host{host}.example.com. 3600 IN A 192.0.2.{host}\n")).collect::<String>();
+        Zone::from_zone_file(CDomainName::from_utf8("example.com.").unwrap(), RClass::Internet, &format!("{ZONE}{hosts}")).unwrap()
+    }
+
+    fn axfr_query() -> Message {
+        let mut query = Message::from(Question::new(CDomainName::from_utf8("example.com.").unwrap(), RType::AXFR, RClass::Internet));
+        query.id = 42;
+        query
+    }
+
+    fn read_frames(mut bytes: &[u8]) -> Vec<&[u8]> {
+        let mut frames = Vec::new();
+        while !bytes.is_empty() {
+            let length = usize::from(u16::from_be_bytes([bytes[0], bytes[1]]));
+            frames.push(&bytes[2..(2 + length)]);
+            bytes = &bytes[(2 + length)..];
+        }
+        frames
+    }
+
+    fn read_messages(bytes: &[u8]) -> Vec<Message> {
+        read_frames(bytes).into_iter()
+            .map(|frame| Message::from_wire_format(&mut ReadWire::from_bytes(frame)).unwrap())
+            .collect()
+    }
+
+    struct CountingSigner { signed: usize, last: usize }
+
+    impl EnvelopeSigner for CountingSigner {
+        fn max_signature_length(&self) -> usize { 100 }
+
+        fn sign(&mut self, _message: &mut Message, last: bool) -> Result<(), AxfrError> {
+            self.signed += 1;
+            if last {
+                self.last = self.signed;
+            }
+            Ok(())
+        }
+    }
+
+    /// Not a real MAC, but it depends on every octet of the key and the data.
+    struct XorCalculator;
+
+    impl MacCalculator for XorCalculator {
+        fn supports(&self, _algorithm: TsigAlgorithm) -> bool { true }
+
+        fn mac(&self, algorithm: TsigAlgorithm, secret: &[u8], data: &[u8]) -> Option<Vec<u8>> {
+            let mut mac = vec![0_u8; algorithm.output_length()];
+            for (index, octet) in secret.iter().chain(data).enumerate() {
+                let position = index % mac.len();
+                mac[position] = mac[position].rotate_left(3) ^ octet ^ (index as u8);
+            }
+            Some(mac)
+        }
+    }
+
+    #[test]
+    fn records_are_soa_bracketed_in_canonical_order() {
+        let zone = zone();
+        let query = axfr_query();
+        let messages = AxfrMessages::new(&zone, &query, u16::MAX, 0).collect::<Result<Vec<_>, _>>().unwrap();
+        assert_eq!(messages.len(), 1);
+        let names_and_types = messages[0].answer.iter()
+            .map(|record| (record.get_name().to_string(), record.get_rtype()))
+            .collect::<Vec<_>>();
+        assert_eq!(names_and_types, vec![
+            ("example.com.".to_string(), RType::SOA),
+            ("example.com.".to_string(), RType::A),
+            ("example.com.".to_string(), RType::NS),
+            ("a.example.com.".to_string(), RType::TXT),
+            ("ns1.example.com.".to_string(), RType::A),
+            ("zz.example.com.".to_string(), RType::A),
+            ("example.com.".to_string(), RType::SOA),
+        ]);
+    }
+
+    #[tokio::test]
+    async fn small_messages_split_the_transfer() {
+        let zone = zone();
+        let query = axfr_query();
+        let mut signer = CountingSigner { signed: 0, last: 0 };
+        let config = AxfrConfig { max_message_size: 256, ..Default::default() };
+        let mut stream = Vec::new();
+        let stats = send_axfr(&mut stream, &zone, &query, Some(&mut signer), &config).await.unwrap();
+
+        let messages = read_messages(&stream);
+        assert!(messages.len() > 1);
+        assert_eq!(stats.messages, messages.len());
+        assert_eq!(signer.signed, messages.len());
+        assert_eq!(signer.last, messages.len());
+        assert_eq!(stats.records, zone.len() + 1);
+        assert_eq!(stats.bytes, stream.len() as u64);
+        for (index, message) in messages.iter().enumerate() {
+            assert_eq!(message.id, 42);
+            assert_eq!(message.qr, QR::Response);
+            assert!(message.authoritative_answer);
+            assert_eq!(message.question.len(), if index == 0 { 1 } else { 0 });
+            assert!(message.estimated_wire_size(false) + 100 <= 256);
+        }
+    }
+
+    #[tokio::test]
+    async fn signed_transfer_verifies() {
+        let zone = large_zone();
+        let key = TsigKey::new(CDomainName::from_utf8("transfer.example.").unwrap(), TsigAlgorithm::HmacSha256, b"secret".to_vec());
+        let mut secondary = TsigExchange::new(&key, &XorCalculator).unwrap();
+        let mut primary = TsigExchange::new(&key, &XorCalculator).unwrap();
+
+        let mut query = axfr_query();
+        secondary.sign(&mut query).unwrap();
+        let query_wire = query.to_wire_vec(&mut Some(CompressionMap::new())).unwrap();
+        primary.verify(&query_wire).unwrap();
+
+        let mut signer = TsigEnvelopeSigner::new(primary, 3);
+        let config = AxfrConfig { max_message_size: 256, ..Default::default() };
+        let mut stream = Vec::new();
+        let stats = send_axfr(&mut stream, &zone, &query, Some(&mut signer), &config).await.unwrap();
+
+        let frames = read_frames(&stream);
+        assert_eq!(stats.messages, frames.len());
+        // Enough messages that some of them are left unsigned.
+        assert!(frames.len() > 4);
+        for (index, frame) in frames.iter().enumerate() {
+            let message = Message::from_wire_format(&mut ReadWire::from_bytes(frame)).unwrap();
+            let signed = message.additional.last().is_some_and(|record| record.get_rtype() == RType::TSIG);
+            assert_eq!(signed, (index % 3 == 0) || (index + 1 == frames.len()), "message {index}");
+            assert!(frame.len() <= 256);
+            secondary.verify(frame).unwrap();
+        }
+        secondary.finish().unwrap();
+    }
+
+    #[tokio::test]
+    async fn tampered_signed_transfer_is_rejected() {
+        let zone = large_zone();
+        let key = TsigKey::new(CDomainName::from_utf8("transfer.example.").unwrap(), TsigAlgorithm::HmacSha256, b"secret".to_vec());
+        let mut secondary = TsigExchange::new(&key, &XorCalculator).unwrap();
+        let mut primary = TsigExchange::new(&key, &XorCalculator).unwrap();
+
+        let mut query = axfr_query();
+        secondary.sign(&mut query).unwrap();
+        primary.verify(&query.to_wire_vec(&mut Some(CompressionMap::new())).unwrap()).unwrap();
+
+        let mut signer = TsigEnvelopeSigner::new(primary, 3);
+        let config = AxfrConfig { max_message_size: 256, ..Default::default() };
+        let mut stream = Vec::new();
+        send_axfr(&mut stream, &zone, &query, Some(&mut signer), &config).await.unwrap();
+
+        // Change the last octet of the second message, which is unsigned. The MAC of the fourth
+        // message covers it.
+        let frames = read_frames(&stream);
+        let mut tampered = frames[1].to_vec();
+        *tampered.last_mut().unwrap() ^= 1;
+        secondary.verify(frames[0]).unwrap();
+        secondary.verify(&tampered).unwrap();
+        secondary.verify(frames[2]).unwrap();
+        assert!(secondary.verify(frames[3]).is_err());
+    }
+
+    #[test]
+    fn only_the_apex_can_be_transferred() {
+        let zones = ZoneStore::new();
+        zones.insert(zone());
+        assert!(find_transfer_zone(&zones, &axfr_query()).is_ok());
+
+        let below_apex = Message::from(Question::new(CDomainName::from_utf8("ns1.example.com.").unwrap(), RType::AXFR, RClass::Internet));
+        assert!(matches!(find_transfer_zone(&zones, &below_apex), Err(AxfrError::NotAuthoritative)));
+
+        let not_axfr = Message::from(Question::new(CDomainName::from_utf8("example.com.").unwrap(), RType::SOA, RClass::Internet));
+        assert!(matches!(find_transfer_zone(&zones, &not_axfr), Err(AxfrError::NotAxfr)));
+    }
+}
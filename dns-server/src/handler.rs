@@ -0,0 +1,634 @@
+use std::{collections::HashMap, error::Error, fmt::{Debug, Display}, net::{IpAddr, Ipv4Addr, Ipv6Addr}, str::FromStr, sync::{atomic::{AtomicU64, Ordering}, Arc, Mutex}, time::Instant};
+
+use async_trait::async_trait;
+use dns_lib::{interface::{cache::main_cache::SharedAsyncMainCache, client::{AsyncClient, Context, QNameMinimization, Response}, server::{QueryHandler, Request}}, query::{message::Message, qr::QR, question::Question}, resource_record::{opcode::OpCode, rcode::RCode, resource_record::ResourceRecord, rtype::RType}, types::c_domain_name::CDomainName};
+use log::debug;
+
+use crate::{answer::{answer_query, empty_response, standard_question}, zone_store::ZoneStore};
+
+/// Once the rate limiter tracks this many clients and responses, the ones that are no longer over
+/// the limit are forgotten.
+const MAX_RATE_LIMIT_BUCKETS: usize = 1 << 16;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum AddressRangeError {
+    BadAddress(String),
+    BadPrefixLength(String),
+}
+impl Error for AddressRangeError {}
+impl Display for AddressRangeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::BadAddress(address) => write!(f, "'{address}' is not an IPv4 or IPv6 address"),
+            Self::BadPrefixLength(prefix_length) => write!(f, "'{prefix_length}' is not a valid prefix length for the address"),
+        }
+    }
+}
+
+/// A block of addresses, written as `address/prefix-length`. An address without a prefix length
+/// is a block of one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct AddressRange {
+    network: IpAddr,
+    prefix_length: u8,
+}
+
+impl AddressRange {
+    /// The block of addresses that share the first `prefix_length` bits with the address. Returns
+    /// `None` if the prefix length is longer than the address.
+    pub fn new(address: IpAddr, prefix_length: u8) -> Option<Self> {
+        let network = match address {
+            IpAddr::V4(address) if prefix_length <= 32 => IpAddr::V4(Ipv4Addr::from(u32::from(address) & u32::MAX.checked_shl(32 - u32::from(prefix_length)).unwrap_or(0))),
+            IpAddr::V6(address) if prefix_length <= 128 => IpAddr::V6(Ipv6Addr::from(u128::from(address) & u128::MAX.checked_shl(128 - u32::from(prefix_length)).unwrap_or(0))),
+            _ => return None,
+        };
+        Some(Self { network, prefix_length })
+    }
+
+    #[inline]
+    pub fn network(&self) -> IpAddr { self.network }
+
+    #[inline]
+    pub fn prefix_length(&self) -> u8 { self.prefix_length }
+
+    /// Whether the address is in the block. IPv4 addresses mapped into IPv6, as they are reported
+    /// by dual stack sockets, are treated as the IPv4 address.
+    #[inline]
+    pub fn contains(&self, address: &IpAddr) -> bool {
+        Self::new(address.to_canonical(), self.prefix_length).is_some_and(|range| range.network == self.network)
+    }
+}
+
+impl FromStr for AddressRange {
+    type Err = AddressRangeError;
+
+    fn from_str(string: &str) -> Result<Self, Self::Err> {
+        let (address, prefix_length) = match string.split_once('/') {
+            Some((address, prefix_length)) => (address, Some(prefix_length)),
+            None => (string, None),
+        };
+        let address = IpAddr::from_str(address).map_err(|_| AddressRangeError::BadAddress(address.to_string()))?;
+        let prefix_length = match (prefix_length, address) {
+            (Some(prefix_length), _) => u8::from_str(prefix_length).map_err(|_| AddressRangeError::BadPrefixLength(prefix_length.to_string()))?,
+            (None, IpAddr::V4(_)) => 32,
+            (None, IpAddr::V6(_)) => 128,
+        };
+        Self::new(address, prefix_length).ok_or_else(|| AddressRangeError::BadPrefixLength(prefix_length.to_string()))
+    }
+}
+
+impl Display for AddressRange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}/{}", self.network, self.prefix_length)
+    }
+}
+
+/// Answers queries authoritatively from the zones in the store. Queries for names outside of every
+/// zone are refused.
+#[derive(Debug, Clone)]
+pub struct ZoneHandler {
+    zones: Arc<ZoneStore>,
+}
+
+impl ZoneHandler {
+    #[inline]
+    pub fn new(zones: Arc<ZoneStore>) -> Self {
+        Self { zones }
+    }
+
+    #[inline]
+    pub fn zones(&self) -> &Arc<ZoneStore> { &self.zones }
+}
+
+#[async_trait]
+impl QueryHandler for ZoneHandler {
+    async fn handle(&self, request: &Request) -> Option<Message> {
+        Some(answer_query(&self.zones, &request.message))
+    }
+}
+
+/// Resolves queries with a client, such as a `DNSAsyncClient`, and answers with what it found.
+/// This turns the client into a recursive or forwarding server.
+pub struct ClientHandler<C: ?Sized> {
+    client: Arc<C>,
+    minimization: QNameMinimization,
+}
+
+impl<C: ?Sized> ClientHandler<C> {
+    /// A handler that resolves queries without QNAME minimization.
+    #[inline]
+    pub fn new(client: Arc<C>) -> Self {
+        Self::with_minimization(client, QNameMinimization::None)
+    }
+
+    #[inline]
+    pub fn with_minimization(client: Arc<C>, minimization: QNameMinimization) -> Self {
+        Self { client, minimization }
+    }
+}
+
+impl<C: ?Sized> Debug for ClientHandler<C> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ClientHandler")
+            .field("minimization", &self.minimization)
+            .finish_non_exhaustive()
+    }
+}
+
+#[async_trait]
+impl<C> QueryHandler for ClientHandler<C> where C: AsyncClient + ?Sized + 'static {
+    async fn handle(&self, request: &Request) -> Option<Message> {
+        let question = match standard_question(&request.message) {
+            Ok(question) => question,
+            Err(rcode) => return Some(empty_response(&request.message, rcode)),
+        };
+        let response = match self.client.clone().query(Context::new(question.clone(), self.minimization)).await {
+            Response::Answer(answer) => {
+                let mut response = empty_response(&request.message, RCode::NoError);
+                response.answer = answer.answer;
+                response.authority = answer.name_servers.into_iter().map(ResourceRecord::from).collect();
+                if let Some(soa) = answer.negative.and_then(|negative| negative.soa) {
+                    response.authority.push(soa.into());
+                }
+                // The client's own OPT and signature records belong to the messages it exchanged
+                // with its upstreams, not to this response.
+                response.additional.extend(answer.additional.into_iter().filter(|record| !matches!(record.get_rtype(), RType::OPT | RType::TSIG | RType::SIG)));
+                response
+            },
+            Response::Error(error) => {
+                let mut response = empty_response(&request.message, error.rcode);
+                if let Some(soa) = error.negative.and_then(|negative| negative.soa) {
+                    response.authority.push(soa.into());
+                }
+                response
+            },
+        };
+        Some(Message { recursion_available: true, ..response })
+    }
+}
+
+/// Asks the primary handler first, and the secondary one if the primary refuses the query. With a
+/// `ZoneHandler` as the primary and a `ClientHandler` as the secondary, names in the zones are
+/// answered authoritatively and everything else is resolved.
+#[derive(Debug, Clone)]
+pub struct Fallback<P, S> {
+    primary: P,
+    secondary: S,
+}
+
+impl<P, S> Fallback<P, S> {
+    #[inline]
+    pub fn new(primary: P, secondary: S) -> Self {
+        Self { primary, secondary }
+    }
+}
+
+#[async_trait]
+impl<P, S> QueryHandler for Fallback<P, S> where P: QueryHandler, S: QueryHandler {
+    async fn handle(&self, request: &Request) -> Option<Message> {
+        match self.primary.handle(request).await {
+            Some(response) if response.rcode == RCode::Refused => self.secondary.handle(request).await,
+            response => response,
+        }
+    }
+}
+
+/// Refuses queries from peers outside of the allowed address ranges and passes the rest on to the
+/// inner handler.
+#[derive(Debug, Clone)]
+pub struct Acl<H> {
+    allowed: Vec<AddressRange>,
+    inner: H,
+}
+
+impl<H> Acl<H> {
+    #[inline]
+    pub fn new(allowed: impl IntoIterator<Item = AddressRange>, inner: H) -> Self {
+        Self { allowed: allowed.into_iter().collect(), inner }
+    }
+
+    #[inline]
+    pub fn allowed(&self) -> &[AddressRange] { &self.allowed }
+}
+
+#[async_trait]
+impl<H> QueryHandler for Acl<H> where H: QueryHandler {
+    async fn handle(&self, request: &Request) -> Option<Message> {
+        let peer = request.info.peer.ip();
+        if self.allowed.iter().any(|range| range.contains(&peer)) {
+            self.inner.handle(request).await
+        } else {
+            Some(empty_response(&request.message, RCode::Refused))
+        }
+    }
+}
+
+/// Limits how often the same response is sent to the same network over UDP, so that the server
+/// cannot be used to flood an address that queries are spoofed from.
+///
+/// https://kb.isc.org/docs/aa-00994
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct RateLimitConfig {
+    /// How many identical responses (same name and rcode) each network may receive per second.
+    /// Up to a second's worth can be sent in a burst.
+    pub responses_per_second: u32,
+    /// Every `slip`th response over the limit is sent truncated, with no records, instead of being
+    /// dropped. A legitimate client whose address is also being spoofed can then retry over TCP,
+    /// which is never limited. With 0, every response over the limit is dropped.
+    pub slip: u8,
+    /// Clients in the same IPv4 block of this length share a limit.
+    pub ipv4_prefix_length: u8,
+    /// Clients in the same IPv6 block of this length share a limit.
+    pub ipv6_prefix_length: u8,
+}
+
+impl Default for RateLimitConfig {
+    fn default() -> Self {
+        Self { responses_per_second: 10, slip: 2, ipv4_prefix_length: 24, ipv6_prefix_length: 56 }
+    }
+}
+
+/// The responses that were held back by a rate limit.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct RateLimitStats {
+    pub dropped: u64,
+    /// Sent truncated instead of being dropped.
+    pub slipped: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct RateLimitKey {
+    network: AddressRange,
+    qname: CDomainName,
+    rcode: RCode,
+}
+
+#[derive(Debug)]
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+    over_limit: u64,
+}
+
+/// Passes every request to the inner handler and applies the rate limit to its UDP responses.
+#[derive(Debug)]
+pub struct RateLimit<H> {
+    config: RateLimitConfig,
+    inner: H,
+    buckets: Mutex<HashMap<RateLimitKey, Bucket>>,
+    dropped: AtomicU64,
+    slipped: AtomicU64,
+}
+
+impl<H> RateLimit<H> {
+    #[inline]
+    pub fn new(config: RateLimitConfig, inner: H) -> Self {
+        Self { config, inner, buckets: Mutex::new(HashMap::new()), dropped: AtomicU64::new(0), slipped: AtomicU64::new(0) }
+    }
+
+    #[inline]
+    pub fn config(&self) -> &RateLimitConfig { &self.config }
+
+    #[inline]
+    pub fn stats(&self) -> RateLimitStats {
+        RateLimitStats { dropped: self.dropped.load(Ordering::Relaxed), slipped: self.slipped.load(Ordering::Relaxed) }
+    }
+
+    /// Whether the response may be sent. If not, returns the number of responses for the key that
+    /// have been over the limit so far, including this one.
+    fn take_token(&self, key: RateLimitKey) -> Result<(), u64> {
+        let capacity = f64::from(self.config.responses_per_second);
+        let now = Instant::now();
+        let mut buckets = self.buckets.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        if buckets.len() >= MAX_RATE_LIMIT_BUCKETS {
+            // Buckets that have refilled since they were last used are the same as new ones.
+            buckets.retain(|_, bucket| now.duration_since(bucket.updated).as_secs_f64() * capacity + bucket.tokens < capacity);
+        }
+        let bucket = buckets.entry(key).or_insert(Bucket { tokens: capacity, updated: now, over_limit: 0 });
+        bucket.tokens = (bucket.tokens + (now.duration_since(bucket.updated).as_secs_f64() * capacity)).min(capacity);
+        bucket.updated = now;
+        let result = if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            Ok(())
+        } else {
+            bucket.over_limit += 1;
+            Err(bucket.over_limit)
+        };
+        drop(buckets);
+        result
+    }
+}
+
+#[async_trait]
+impl<H> QueryHandler for RateLimit<H> where H: QueryHandler {
+    async fn handle(&self, request: &Request) -> Option<Message> {
+        let response = self.inner.handle(request).await?;
+        if request.info.transport.is_connection_oriented() || (self.config.responses_per_second == 0) {
+            return Some(response);
+        }
+        let peer = request.info.peer.ip().to_canonical();
+        let prefix_length = match peer {
+            IpAddr::V4(_) => self.config.ipv4_prefix_length,
+            IpAddr::V6(_) => self.config.ipv6_prefix_length,
+        };
+        let Some(network) = AddressRange::new(peer, prefix_length) else {
+            return Some(response);
+        };
+        let qname = response.question.first().map_or_else(CDomainName::new_root, |question| question.qname().as_lowercase());
+        match self.take_token(RateLimitKey { network, qname, rcode: response.rcode }) {
+            Ok(()) => Some(response),
+            Err(over_limit) if (self.config.slip != 0) && (over_limit % u64::from(self.config.slip) == 0) => {
+                self.slipped.fetch_add(1, Ordering::Relaxed);
+                Some(Message { truncation: true, ..empty_response(&request.message, response.rcode) })
+            },
+            Err(_) => {
+                self.dropped.fetch_add(1, Ordering::Relaxed);
+                None
+            },
+        }
+    }
+}
+
+/// Which requests cause a `CacheFlush` to remove a zone from the cache.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct CacheFlushConfig {
+    /// Flush the zone named by a NOTIFY (RFC 1996), which a primary sends when the zone changes.
+    pub on_notify: bool,
+    /// Flush the zone of an UPDATE (RFC 2136) once the inner handler answers it with NOERROR,
+    /// such as after forwarding it to the primary.
+    pub on_update: bool,
+}
+
+impl Default for CacheFlushConfig {
+    fn default() -> Self {
+        Self { on_notify: true, on_update: true }
+    }
+}
+
+/// The zones removed from the cache by a `CacheFlush`.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct CacheFlushStats {
+    pub notifies: u64,
+    pub updates: u64,
+    /// The records removed across all of the flushes, as counted by the cache.
+    pub records: u64,
+}
+
+/// Removes a zone from the resolver's cache as soon as the server hears that it changed, so that
+/// clients are not answered from stale records until they expire. Every request is passed on to
+/// the inner handler. A NOTIFY that the inner handler does not implement is still acknowledged.
+///
+/// Anyone who can send a NOTIFY can flush the cache, so this should be wrapped in an `Acl` that
+/// only allows the primaries of the zones.
+pub struct CacheFlush<H> {
+    config: CacheFlushConfig,
+    cache: SharedAsyncMainCache,
+    inner: H,
+    notifies: AtomicU64,
+    updates: AtomicU64,
+    records: AtomicU64,
+}
+
+impl<H> CacheFlush<H> {
+    #[inline]
+    pub fn new(config: CacheFlushConfig, cache: SharedAsyncMainCache, inner: H) -> Self {
+        Self { config, cache, inner, notifies: AtomicU64::new(0), updates: AtomicU64::new(0), records: AtomicU64::new(0) }
+    }
+
+    #[inline]
+    pub fn config(&self) -> &CacheFlushConfig { &self.config }
+
+    #[inline]
+    pub fn stats(&self) -> CacheFlushStats {
+        CacheFlushStats {
+            notifies: self.notifies.load(Ordering::Relaxed),
+            updates: self.updates.load(Ordering::Relaxed),
+            records: self.records.load(Ordering::Relaxed),
+        }
+    }
+
+    /// The zone that a NOTIFY or UPDATE request is about. Both put it in the only question (the
+    /// zone section of an UPDATE).
+    #[inline]
+    fn zone(request: &Request) -> Option<&Question> {
+        match request.message.question.as_slice() {
+            [zone] if request.message.qr == QR::Query => Some(zone),
+            _ => None,
+        }
+    }
+
+    async fn flush(&self, zone: &Question) {
+        let removed = self.cache.remove_subtree(zone.qname(), zone.qclass()).await.unwrap_or(0);
+        debug!("Flushed {removed} records at or below '{}' from the cache", zone.qname());
+        self.records.fetch_add(removed as u64, Ordering::Relaxed);
+    }
+}
+
+impl<H: Debug> Debug for CacheFlush<H> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CacheFlush")
+            .field("config", &self.config)
+            .field("inner", &self.inner)
+            .finish_non_exhaustive()
+    }
+}
+
+#[async_trait]
+impl<H> QueryHandler for CacheFlush<H> where H: QueryHandler {
+    async fn handle(&self, request: &Request) -> Option<Message> {
+        match (request.message.opcode, Self::zone(request)) {
+            (OpCode::Notify, Some(zone)) if self.config.on_notify => {
+                // The zone is flushed before answering so that the primary's next query for it,
+                // or a client's, is not answered from the cache.
+                self.flush(zone).await;
+                self.notifies.fetch_add(1, Ordering::Relaxed);
+                match self.inner.handle(request).await? {
+                    response if response.rcode == RCode::NotImp => Some(empty_response(&request.message, RCode::NoError)),
+                    response => Some(response),
+                }
+            },
+            (OpCode::Update, Some(zone)) if self.config.on_update => {
+                let response = self.inner.handle(request).await?;
+                if response.rcode == RCode::NoError {
+                    self.flush(zone).await;
+                    self.updates.fetch_add(1, Ordering::Relaxed);
+                }
+                Some(response)
+            },
+            _ => self.inner.handle(request).await,
+        }
+    }
+}
+
+#[cfg(test)]
+mod handler_tests {
+    use std::{net::{IpAddr, Ipv4Addr, SocketAddr}, str::FromStr, sync::{Arc, Mutex}};
+
+    use async_trait::async_trait;
+    use dns_lib::{interface::{cache::{main_cache::AsyncMainCache, CacheQuery, CacheRecord, CacheResponse}, client::{Answer, AsyncClient, Context, ErrorResponse, Response}, server::{QueryHandler, Request, RequestInfo, RequestTransport}}, query::{message::Message, question::Question}, resource_record::{opcode::OpCode, rclass::RClass, rcode::RCode, resource_record::{RecordData, ResourceRecord}, rtype::RType, time::Time, types::a::A}, types::c_domain_name::CDomainName};
+
+    use crate::{answer::empty_response, zone_store::{Zone, ZoneStore}};
+
+    use super::{Acl, AddressRange, CacheFlush, CacheFlushConfig, CacheFlushStats, ClientHandler, Fallback, RateLimit, RateLimitConfig, RateLimitStats, ZoneHandler};
+
+    const ZONE: &str = "\
+@                3600 IN SOA ns1.example.com. hostmaster.example.com. 1 7200 3600 1209600 300
+@                3600 IN NS  ns1.example.com.
+ns1.example.com. 3600 IN A   192.0.2.1
+";
+
+    /// Answers every query with one A record, or NXDOMAIN for names under "invalid.".
+    struct StubClient;
+
+    #[async_trait]
+    impl AsyncClient for StubClient {
+        async fn query(self: Arc<Self>, context: Context) -> Response {
+            if context.qname().to_string().ends_with("invalid.") {
+                return Response::Error(ErrorResponse::new(RCode::NXDomain));
+            }
+            let record = ResourceRecord::new(context.qname().clone(), RClass::Internet, Time::from_secs(60), RecordData::A(A::new(Ipv4Addr::new(198, 51, 100, 1))));
+            Response::Answer(Answer { answer: vec![record], name_servers: Vec::new(), additional: Vec::new(), authoritative: false, negative: None, trace: None })
+        }
+    }
+
+    /// Records the subtrees that are removed from it, and says that each one held two records.
+    #[derive(Default)]
+    struct StubCache {
+        removed: Mutex<Vec<CDomainName>>,
+    }
+
+    #[async_trait]
+    impl AsyncMainCache for StubCache {
+        async fn get(&self, _query: &CacheQuery) -> CacheResponse { CacheResponse::Records(Vec::new()) }
+        async fn insert_record(&self, _record: CacheRecord) {}
+        async fn clean(&self) {}
+        async fn remove_subtree(&self, apex: &CDomainName, _qclass: RClass) -> Option<usize> {
+            self.removed.lock().unwrap().push(apex.clone());
+            Some(2)
+        }
+    }
+
+    /// Accepts every UPDATE for "example.com." and refuses the rest.
+    #[derive(Debug)]
+    struct StubUpdates;
+
+    #[async_trait]
+    impl QueryHandler for StubUpdates {
+        async fn handle(&self, request: &Request) -> Option<Message> {
+            match request.message.question.first() {
+                Some(zone) if zone.qname() == &CDomainName::from_utf8("example.com.").unwrap() => Some(empty_response(&request.message, RCode::NoError)),
+                _ => Some(empty_response(&request.message, RCode::Refused)),
+            }
+        }
+    }
+
+    fn zone_handler() -> ZoneHandler {
+        let zones = ZoneStore::new();
+        zones.insert(Zone::from_zone_file(CDomainName::from_utf8("example.com.").unwrap(), RClass::Internet, ZONE).unwrap());
+        ZoneHandler::new(Arc::new(zones))
+    }
+
+    fn request(qname: &str, peer: IpAddr, transport: RequestTransport) -> Request {
+        Request {
+            message: Message::from(Question::new(CDomainName::from_utf8(qname).unwrap(), RType::A, RClass::Internet)),
+            info: RequestInfo { peer: SocketAddr::new(peer, 5300), local: SocketAddr::new(Ipv4Addr::LOCALHOST.into(), 53), transport },
+        }
+    }
+
+    fn zone_request(opcode: OpCode, zone: &str) -> Request {
+        let mut request = request(zone, IpAddr::V4(Ipv4Addr::new(192, 0, 2, 53)), RequestTransport::Udp);
+        request.message = Message::from(Question::new(CDomainName::from_utf8(zone).unwrap(), RType::SOA, RClass::Internet));
+        request.message.opcode = opcode;
+        request
+    }
+
+    #[test]
+    fn address_ranges() {
+        let range = AddressRange::from_str("192.0.2.77/24").unwrap();
+        assert_eq!(range.to_string(), "192.0.2.0/24");
+        assert!(range.contains(&"192.0.2.1".parse().unwrap()));
+        assert!(range.contains(&"::ffff:192.0.2.1".parse().unwrap()));
+        assert!(!range.contains(&"192.0.3.1".parse().unwrap()));
+        assert!(AddressRange::from_str("2001:db8::/32").unwrap().contains(&"2001:db8:1::1".parse().unwrap()));
+        assert!(AddressRange::from_str("0.0.0.0/0").unwrap().contains(&"203.0.113.1".parse().unwrap()));
+        assert_eq!(AddressRange::from_str("192.0.2.1").unwrap().prefix_length(), 32);
+        assert!(AddressRange::from_str("192.0.2.1/33").is_err());
+        assert!(AddressRange::from_str("example.com/24").is_err());
+    }
+
+    #[tokio::test]
+    async fn zones_fall_back_to_the_client() {
+        let handler = Fallback::new(zone_handler(), ClientHandler::new(Arc::new(StubClient)));
+        let peer = IpAddr::V4(Ipv4Addr::new(192, 0, 2, 100));
+
+        let response = handler.handle(&request("ns1.example.com.", peer, RequestTransport::Udp)).await.unwrap();
+        assert!(response.authoritative_answer);
+        assert_eq!(response.answer[0].get_rdata(), &RecordData::A(A::new(Ipv4Addr::new(192, 0, 2, 1))));
+
+        let response = handler.handle(&request("www.example.org.", peer, RequestTransport::Udp)).await.unwrap();
+        assert!(!response.authoritative_answer);
+        assert!(response.recursion_available);
+        assert_eq!(response.answer[0].get_rdata(), &RecordData::A(A::new(Ipv4Addr::new(198, 51, 100, 1))));
+
+        let response = handler.handle(&request("www.invalid.", peer, RequestTransport::Udp)).await.unwrap();
+        assert_eq!(response.rcode, RCode::NXDomain);
+    }
+
+    #[tokio::test]
+    async fn peers_outside_the_acl_are_refused() {
+        let handler = Acl::new([AddressRange::from_str("192.0.2.0/24").unwrap()], zone_handler());
+        let response = handler.handle(&request("ns1.example.com.", IpAddr::V4(Ipv4Addr::new(192, 0, 2, 100)), RequestTransport::Udp)).await.unwrap();
+        assert_eq!(response.rcode, RCode::NoError);
+        let response = handler.handle(&request("ns1.example.com.", IpAddr::V4(Ipv4Addr::new(203, 0, 113, 1)), RequestTransport::Udp)).await.unwrap();
+        assert_eq!(response.rcode, RCode::Refused);
+        assert!(response.answer.is_empty());
+    }
+
+    #[tokio::test]
+    async fn udp_responses_over_the_limit_are_dropped_or_slipped() {
+        let config = RateLimitConfig { responses_per_second: 2, slip: 2, ..RateLimitConfig::default() };
+        let handler = RateLimit::new(config, zone_handler());
+        let peer = IpAddr::V4(Ipv4Addr::new(192, 0, 2, 100));
+
+        let mut responses = Vec::new();
+        for _ in 0..6 {
+            responses.push(handler.handle(&request("ns1.example.com.", peer, RequestTransport::Udp)).await);
+        }
+        assert!(responses[..2].iter().all(|response| response.as_ref().is_some_and(|response| !response.truncation && !response.answer.is_empty())));
+        // Every second response over the limit is truncated and the rest are dropped.
+        assert!(responses[2].is_none());
+        assert!(responses[3].as_ref().is_some_and(|response| response.truncation && response.answer.is_empty()));
+        assert!(responses[4].is_none());
+        assert!(responses[5].as_ref().is_some_and(|response| response.truncation));
+        assert_eq!(handler.stats(), RateLimitStats { dropped: 2, slipped: 2 });
+
+        // Another network, another name, or TCP each get their own limit.
+        assert!(handler.handle(&request("ns1.example.com.", IpAddr::V4(Ipv4Addr::new(192, 0, 3, 100)), RequestTransport::Udp)).await.is_some_and(|response| !response.truncation));
+        assert!(handler.handle(&request("example.com.", peer, RequestTransport::Udp)).await.is_some_and(|response| !response.truncation));
+        assert!(handler.handle(&request("ns1.example.com.", peer, RequestTransport::Tcp)).await.is_some_and(|response| !response.truncation));
+    }
+
+    #[tokio::test]
+    async fn notify_and_update_flush_the_zone() {
+        let cache = Arc::new(StubCache::default());
+        let handler = CacheFlush::new(CacheFlushConfig::default(), cache.clone(), StubUpdates);
+
+        // The stub refuses the NOTIFY, which is passed on as is.
+        let response = handler.handle(&zone_request(OpCode::Notify, "example.org.")).await.unwrap();
+        assert_eq!(response.rcode, RCode::Refused);
+        let response = handler.handle(&zone_request(OpCode::Update, "example.com.")).await.unwrap();
+        assert_eq!(response.rcode, RCode::NoError);
+        // Failed updates and standard queries do not flush anything.
+        handler.handle(&zone_request(OpCode::Update, "example.org.")).await.unwrap();
+        handler.handle(&zone_request(OpCode::Query, "example.com.")).await.unwrap();
+        assert_eq!(*cache.removed.lock().unwrap(), [CDomainName::from_utf8("example.org.").unwrap(), CDomainName::from_utf8("example.com.").unwrap()]);
+        assert_eq!(handler.stats(), CacheFlushStats { notifies: 1, updates: 1, records: 4 });
+
+        // A NOTIFY that the inner handler does not implement is acknowledged anyway.
+        let handler = CacheFlush::new(CacheFlushConfig { on_notify: true, on_update: false }, cache.clone(), zone_handler());
+        let response = handler.handle(&zone_request(OpCode::Notify, "example.com.")).await.unwrap();
+        assert_eq!(response.rcode, RCode::NoError);
+        assert_eq!(response.opcode, OpCode::Notify);
+        assert_eq!(handler.stats().notifies, 1);
+    }
+}
@@ -0,0 +1,6 @@
+pub mod answer;
+pub mod axfr;
+pub mod handler;
+pub mod server;
+pub mod tsig;
+pub mod zone_store;
@@ -0,0 +1,145 @@
+use std::{env, net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr}, process::ExitCode, sync::Arc};
+
+use dns_lib::{resource_record::rclass::RClass, types::c_domain_name::CDomainName};
+use dns_server::{server::{AuthoritativeServer, ServerConfig, DNS_PORT}, zone_store::{Zone, ZoneStore}};
+use network::listener::{bind_all, InboundSocket, ListenSpec, PrivilegeDrop};
+
+const USAGE: &str = "\
+usage: dns-server [--listen <address:port>]... [--user <user> [--group <group>]] <origin> <zone-file> [<origin> <zone-file>...]
+
+Answers queries for the zones authoritatively over UDP and TCP until interrupted. Listens on
+port 53 of every IPv4 and IPv6 address by default. Sockets passed in by systemd socket activation
+are used for the addresses they are bound to. With '--user', the server switches to that user
+(and group) once its sockets are bound. Owner names in the zone files must be fully qualified or
+'@'.";
+
+fn main() -> ExitCode {
+    let mut listen = Vec::new();
+    let mut user = None;
+    let mut group = None;
+    let mut zone_args = Vec::new();
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--listen" => match args.next().map(|address| address.parse()) {
+                Some(Ok(address)) => listen.push(address),
+                Some(Err(error)) => {
+                    eprintln!("invalid listen address: {error}");
+                    return ExitCode::FAILURE;
+                },
+                None => {
+                    eprintln!("missing address after '--listen'\n\n{USAGE}");
+                    return ExitCode::FAILURE;
+                },
+            },
+            "--user" | "--group" => match args.next() {
+                Some(name) if arg == "--user" => user = Some(name),
+                Some(name) => group = Some(name),
+                None => {
+                    eprintln!("missing name after '{arg}'\n\n{USAGE}");
+                    return ExitCode::FAILURE;
+                },
+            },
+            _ => zone_args.push(arg),
+        }
+    }
+    if zone_args.is_empty() || (zone_args.len() % 2 != 0) {
+        eprintln!("{USAGE}");
+        return ExitCode::FAILURE;
+    }
+    let privilege_drop = match (user, group) {
+        (Some(user), group) => Some(PrivilegeDrop { user, group }),
+        (None, Some(_)) => {
+            eprintln!("'--group' requires '--user'\n\n{USAGE}");
+            return ExitCode::FAILURE;
+        },
+        (None, None) => None,
+    };
+    if listen.is_empty() {
+        listen.push(SocketAddr::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED), DNS_PORT));
+        listen.push(SocketAddr::new(IpAddr::V6(Ipv6Addr::UNSPECIFIED), DNS_PORT));
+    }
+
+    let zones = ZoneStore::new();
+    for zone_arg in zone_args.chunks_exact(2) {
+        let [origin, zone_path] = zone_arg else { unreachable!() };
+        let origin = match CDomainName::from_utf8(origin) {
+            Ok(origin) if origin.is_fully_qualified() => origin,
+            Ok(origin) => {
+                eprintln!("the origin '{origin}' must be fully qualified");
+                return ExitCode::FAILURE;
+            },
+            Err(error) => {
+                eprintln!("{error}");
+                return ExitCode::FAILURE;
+            },
+        };
+        let zone = match std::fs::read_to_string(zone_path) {
+            Ok(zone_file) => Zone::from_zone_file(origin, RClass::Internet, &zone_file).map_err(|error| error.to_string()),
+            Err(error) => Err(error.to_string()),
+        };
+        match zone {
+            Ok(zone) => {
+                println!("loaded '{}' with {} records from '{zone_path}'", zone.origin(), zone.len());
+                zones.insert(zone);
+            },
+            Err(error) => {
+                eprintln!("failed to load '{zone_path}': {error}");
+                return ExitCode::FAILURE;
+            },
+        }
+    }
+
+    // The sockets are bound, and privileges dropped, before the runtime starts any threads.
+    let specs = listen.iter()
+        .flat_map(|address| [ListenSpec::udp(*address), ListenSpec::tcp(*address)])
+        .collect::<Vec<_>>();
+    let sockets = match bind_all(&specs, privilege_drop.as_ref()) {
+        Ok(sockets) => sockets,
+        Err(error) => {
+            eprintln!("failed to listen: {error}");
+            return ExitCode::FAILURE;
+        },
+    };
+    let runtime = match tokio::runtime::Runtime::new() {
+        Ok(runtime) => runtime,
+        Err(error) => {
+            eprintln!("{error}");
+            return ExitCode::FAILURE;
+        },
+    };
+    runtime.block_on(serve(sockets, Arc::new(zones)))
+}
+
+/// Serves the zones on each pair of UDP and TCP sockets until interrupted.
+async fn serve(sockets: Vec<(ListenSpec, InboundSocket)>, zones: Arc<ZoneStore>) -> ExitCode {
+    let mut servers = Vec::with_capacity(sockets.len() / 2);
+    let mut sockets = sockets.into_iter();
+    while let (Some((spec, udp_socket)), Some((_, tcp_listener))) = (sockets.next(), sockets.next()) {
+        let sockets = udp_socket.into_tokio_udp().and_then(|udp_socket| Ok((udp_socket, tcp_listener.into_tokio_tcp()?)));
+        let server = match sockets {
+            Ok((udp_socket, tcp_listener)) => AuthoritativeServer::from_sockets(udp_socket, tcp_listener, zones.clone(), ServerConfig::default()),
+            Err(error) => {
+                eprintln!("failed to listen on '{}': {error}", spec.address);
+                return ExitCode::FAILURE;
+            },
+        };
+        match server {
+            Ok(server) => {
+                println!("listening on '{}'", server.local_addr());
+                servers.push(server);
+            },
+            Err(error) => {
+                eprintln!("failed to listen on '{}': {error}", spec.address);
+                return ExitCode::FAILURE;
+            },
+        }
+    }
+    match tokio::signal::ctrl_c().await {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(error) => {
+            eprintln!("{error}");
+            ExitCode::FAILURE
+        },
+    }
+}
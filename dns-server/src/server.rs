@@ -0,0 +1,577 @@
+use std::{io, net::SocketAddr, sync::Arc, time::Duration};
+
+use dns_lib::{interface::server::{QueryHandler, Request, RequestInfo, RequestTransport, SharedQueryHandler}, query::{edns::{udp_payload_size, DEFAULT_EDNS_BUFFER_SIZE, MINIMUM_EDNS_BUFFER_SIZE}, message::Message, qr::QR}, resource_record::{rcode::RCode, rtype::RType}, serde::wire::{from_wire::FromWire, read_wire::ReadWire, to_wire::ToWire, write_wire::WriteWire}};
+use log::{debug, warn};
+use network::{listener::{recv_with_destination, send_from, PacketDestination}, stream_limits::{ConnectionPermit, StreamGuard, StreamLimitStats, StreamLimits}};
+use tokio::{io::AsyncWriteExt, net::{TcpListener, TcpStream, UdpSocket}, sync::Semaphore, task::JoinHandle, time::timeout};
+
+use crate::{answer::empty_response, axfr::{find_transfer_zone, send_axfr, AxfrConfig, EnvelopeSigner, TsigEnvelopeSigner}, handler::ZoneHandler, tsig::TsigKeys, zone_store::ZoneStore};
+
+/// The standard port for DNS over UDP and TCP.
+pub const DNS_PORT: u16 = 53;
+
+/// The largest message that can be received over UDP or sent over TCP.
+const MAX_MESSAGE_SIZE: usize = u16::MAX as usize;
+
+/// How long the TCP listener waits before accepting again after it fails to accept a connection.
+/// The wait doubles with each failure in a row, up to the maximum.
+const INITIAL_ACCEPT_BACKOFF: Duration = Duration::from_millis(10);
+const MAX_ACCEPT_BACKOFF: Duration = Duration::from_secs(1);
+
+/// The number of UDP queries that are answered at once by default.
+pub const DEFAULT_MAX_UDP_QUERIES: usize = 1024;
+
+#[derive(Debug, Clone)]
+pub struct ServerConfig {
+    /// Zone transfers (AXFR over TCP) are refused unless this is set.
+    pub transfers: Option<AxfrConfig>,
+    /// The keys that transfer requests may be signed with. Transfers requested with one of these
+    /// keys are signed with it. Unsigned requests get unsigned transfers.
+    pub transfer_keys: TsigKeys,
+    /// The per-address connection cap and the timeouts for TCP connections. A response that the
+    /// client does not read within the idle timeout closes the connection. Queries on a
+    /// connection are answered one at a time, so the in-flight limit does not apply.
+    ///
+    /// https://datatracker.ietf.org/doc/html/rfc7766#section-6.2.3
+    pub stream_limits: StreamLimits,
+    /// The number of UDP queries that are answered at once. Queries that arrive while this many
+    /// are being answered are dropped, so that a flood of queries that take a while to answer
+    /// cannot use up the server's memory.
+    pub max_udp_queries: usize,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self {
+            transfers: None,
+            transfer_keys: TsigKeys::default(),
+            stream_limits: StreamLimits::default(),
+            max_udp_queries: DEFAULT_MAX_UDP_QUERIES,
+        }
+    }
+}
+
+/// An authoritative name server for the zones in a `ZoneStore`, listening on UDP and TCP at the
+/// same address. Zones can be added to and replaced in the store while the server is running.
+///
+/// The server can also answer with any other `QueryHandler`, such as a resolver or a chain of
+/// handlers that the zones are only part of (see `bind_handler()`).
+///
+/// The server stops when it is dropped.
+pub struct AuthoritativeServer {
+    local_addr: SocketAddr,
+    stream_guard: Arc<StreamGuard>,
+    tasks: Vec<JoinHandle<()>>,
+}
+
+/// The zones that can be transferred, and how.
+#[derive(Debug, Clone)]
+struct Transfers {
+    zones: Arc<ZoneStore>,
+    config: AxfrConfig,
+    keys: Arc<TsigKeys>,
+}
+
+impl Transfers {
+    #[inline]
+    fn new(zones: &Arc<ZoneStore>, config: ServerConfig) -> Option<Self> {
+        let axfr_config = config.transfers?;
+        Some(Self { zones: zones.clone(), config: axfr_config, keys: Arc::new(config.transfer_keys) })
+    }
+}
+
+/// The limits from the `ServerConfig` that apply to every query, not just transfers.
+#[derive(Debug, Clone, Copy)]
+struct ServerLimits {
+    stream_limits: StreamLimits,
+    max_udp_queries: usize,
+}
+
+impl From<&ServerConfig> for ServerLimits {
+    #[inline]
+    fn from(config: &ServerConfig) -> Self {
+        Self { stream_limits: config.stream_limits, max_udp_queries: config.max_udp_queries }
+    }
+}
+
+impl AuthoritativeServer {
+    /// Binds UDP and TCP to the address. Use port 0 to bind to any free port. The TCP listener
+    /// uses whichever port the UDP socket was given.
+    pub async fn bind(address: SocketAddr, zones: Arc<ZoneStore>, config: ServerConfig) -> io::Result<Self> {
+        debug!("Serving {} zones on '{address}'", zones.len());
+        let limits = ServerLimits::from(&config);
+        let transfers = Transfers::new(&zones, config);
+        Self::bind_with_transfers(address, Arc::new(ZoneHandler::new(zones)), transfers, limits).await
+    }
+
+    /// Binds UDP and TCP to the address, like `bind()`, and answers every query with the handler.
+    /// Zone transfers are refused.
+    pub async fn bind_handler(address: SocketAddr, handler: SharedQueryHandler) -> io::Result<Self> {
+        Self::bind_with_transfers(address, handler, None, ServerLimits::from(&ServerConfig::default())).await
+    }
+
+    /// Serves the zones on sockets that are already bound, such as the ones from
+    /// `network::listener::bind_all()`. Both sockets should be bound to the same address. Must be
+    /// called from within a tokio runtime.
+    pub fn from_sockets(udp_socket: UdpSocket, tcp_listener: TcpListener, zones: Arc<ZoneStore>, config: ServerConfig) -> io::Result<Self> {
+        debug!("Serving {} zones on '{}'", zones.len(), udp_socket.local_addr()?);
+        let limits = ServerLimits::from(&config);
+        let transfers = Transfers::new(&zones, config);
+        Self::serve(udp_socket, tcp_listener, Arc::new(ZoneHandler::new(zones)), transfers, limits)
+    }
+
+    async fn bind_with_transfers(address: SocketAddr, handler: SharedQueryHandler, transfers: Option<Transfers>, limits: ServerLimits) -> io::Result<Self> {
+        let udp_socket = UdpSocket::bind(address).await?;
+        let tcp_listener = TcpListener::bind(udp_socket.local_addr()?).await?;
+        Self::serve(udp_socket, tcp_listener, handler, transfers, limits)
+    }
+
+    fn serve(udp_socket: UdpSocket, tcp_listener: TcpListener, handler: SharedQueryHandler, transfers: Option<Transfers>, limits: ServerLimits) -> io::Result<Self> {
+        let local_addr = udp_socket.local_addr()?;
+        let stream_guard = StreamGuard::new(limits.stream_limits);
+        let udp_queries = Arc::new(Semaphore::new(limits.max_udp_queries));
+        let tasks = vec![
+            tokio::spawn(serve_udp(Arc::new(udp_socket), local_addr, udp_queries, handler.clone())),
+            tokio::spawn(serve_tcp(tcp_listener, stream_guard.clone(), handler, transfers)),
+        ];
+        Ok(Self { local_addr, stream_guard, tasks })
+    }
+
+    #[inline]
+    pub fn local_addr(&self) -> SocketAddr { self.local_addr }
+
+    /// The number of TCP connections accepted, and how often each of the limits on them was
+    /// enforced.
+    #[inline]
+    pub fn stream_stats(&self) -> StreamLimitStats { self.stream_guard.stats() }
+}
+
+impl Drop for AuthoritativeServer {
+    fn drop(&mut self) {
+        for task in &self.tasks {
+            task.abort();
+        }
+    }
+}
+
+/// The largest response that the requestor can receive over UDP. Without EDNS, this is 512
+/// octets. With EDNS, it is what the requestor advertised, but no more than the server's own
+/// buffer size.
+///
+/// https://datatracker.ietf.org/doc/html/rfc6891#section-6.2.5
+#[inline]
+fn max_udp_response_size(query: &Message) -> usize {
+    let payload_size = udp_payload_size(query).unwrap_or(MINIMUM_EDNS_BUFFER_SIZE);
+    usize::from(payload_size.clamp(MINIMUM_EDNS_BUFFER_SIZE, DEFAULT_EDNS_BUFFER_SIZE))
+}
+
+#[inline]
+fn parse(bytes: &[u8]) -> Option<Message> {
+    Message::from_wire_format(&mut ReadWire::from_bytes(bytes)).ok()
+}
+
+/// Sockets bound to the ANY address report which of the host's addresses each query was sent to,
+/// so that the response can be sent back from it.
+///
+/// Messages that are responses are dropped. Answering them would let anyone who can spoof a
+/// source address bounce traffic off of the server.
+async fn serve_udp(socket: Arc<UdpSocket>, local_addr: SocketAddr, queries: Arc<Semaphore>, handler: SharedQueryHandler) {
+    let mut buffer = vec![0_u8; MAX_MESSAGE_SIZE];
+    loop {
+        let (length, peer, destination) = match recv_with_destination(&socket, &mut buffer).await {
+            Ok(received) => received,
+            // Errors such as ICMP port unreachable from a previous send are not fatal.
+            Err(_) => continue,
+        };
+        let Some(query) = parse(&buffer[..length]) else {
+            debug!("Dropped malformed query from '{peer}'");
+            continue;
+        };
+        if query.qr == QR::Response {
+            debug!("Dropped response from '{peer}'");
+            continue;
+        }
+        // Handlers may take a while to answer, such as when they resolve the query, so each query
+        // is answered on its own task. The number of those tasks is limited.
+        let Ok(permit) = queries.clone().try_acquire_owned() else {
+            debug!("Dropped query from '{peer}' because too many UDP queries are being answered");
+            continue;
+        };
+        let local = destination.map_or(local_addr, |destination| SocketAddr::new(destination.address, local_addr.port()));
+        let request = Request { message: query, info: RequestInfo { peer, local, transport: RequestTransport::Udp } };
+        let socket = socket.clone();
+        let handler = handler.clone();
+        tokio::spawn(async move {
+            respond_udp(socket, request, destination, handler).await;
+            drop(permit);
+        });
+    }
+}
+
+async fn respond_udp(socket: Arc<UdpSocket>, request: Request, destination: Option<PacketDestination>, handler: SharedQueryHandler) {
+    let Some(mut response) = handler.handle(&request).await else {
+        return;
+    };
+    let max_size = max_udp_response_size(&request.message);
+    response.truncate_to_fit(max_size);
+
+    let mut buffer = vec![0_u8; max_size];
+    let mut wire = WriteWire::from_bytes(&mut buffer);
+    if let Err(error) = response.to_wire_format(&mut wire, &mut None) {
+        warn!("Failed to encode response to '{}': {error}", request.info.peer);
+        return;
+    }
+    let length = wire.current_len();
+    let _ = send_from(&socket, &buffer[..length], &request.info.peer, destination.as_ref()).await;
+}
+
+async fn serve_tcp(listener: TcpListener, stream_guard: Arc<StreamGuard>, handler: SharedQueryHandler, transfers: Option<Transfers>) {
+    let mut connections: Vec<AbortOnDrop> = Vec::new();
+    let mut backoff = INITIAL_ACCEPT_BACKOFF;
+    loop {
+        let (stream, peer) = match listener.accept().await {
+            Ok(accepted) => accepted,
+            // Errors such as running out of file descriptors go away once other connections
+            // close, so they must not stop the listener. Retrying straight away would only spin.
+            Err(error) => {
+                warn!("Failed to accept a TCP connection: {error}");
+                tokio::time::sleep(backoff).await;
+                backoff = (backoff * 2).min(MAX_ACCEPT_BACKOFF);
+                continue;
+            },
+        };
+        backoff = INITIAL_ACCEPT_BACKOFF;
+        // Dropping the stream closes the connection.
+        let permit = match stream_guard.admit(peer.ip()) {
+            Ok(permit) => permit,
+            Err(error) => {
+                debug!("Refused connection from '{peer}': {error}");
+                continue;
+            },
+        };
+        connections.retain(|connection| !connection.is_finished());
+        let write_timeout = stream_guard.limits().idle_timeout;
+        connections.push(AbortOnDrop(tokio::spawn(serve_connection(stream, peer, permit, write_timeout, handler.clone(), transfers.clone()))));
+    }
+}
+
+/// Connections are closed along with the listener's task.
+struct AbortOnDrop(JoinHandle<()>);
+
+impl AbortOnDrop {
+    #[inline]
+    fn is_finished(&self) -> bool { self.0.is_finished() }
+}
+
+impl Drop for AbortOnDrop {
+    fn drop(&mut self) {
+        self.0.abort();
+    }
+}
+
+/// The connection is closed when it has been idle for too long, when a client takes too long to
+/// send a query that it has started sending, or when it does not read a response within the write
+/// timeout.
+async fn serve_connection(mut stream: TcpStream, peer: SocketAddr, permit: ConnectionPermit, write_timeout: Duration, handler: SharedQueryHandler, transfers: Option<Transfers>) {
+    let Ok(local_addr) = stream.local_addr() else {
+        return;
+    };
+    let mut buffer = vec![0_u8; MAX_MESSAGE_SIZE + 2];
+    loop {
+        let raw_query = match permit.read_message(&mut stream).await {
+            Ok(raw_query) => raw_query,
+            Err(error) => {
+                debug!("Closing connection from '{peer}': {error}");
+                return;
+            },
+        };
+        let Some(query) = parse(&raw_query) else {
+            debug!("Closing connection from '{peer}' after a malformed query");
+            return;
+        };
+
+        let is_transfer = query.question.first().is_some_and(|question| question.qtype() == RType::AXFR);
+        let response = match (is_transfer, &transfers) {
+            (true, Some(transfers)) => match (find_transfer_zone(&transfers.zones, &query), transfers.keys.verify_request(&raw_query)) {
+                (Ok(zone), Ok(exchange)) => {
+                    let mut tsig_signer = exchange.map(|exchange| TsigEnvelopeSigner::new(exchange, transfers.config.signed_message_interval));
+                    let signer = tsig_signer.as_mut().map(|signer| signer as &mut dyn EnvelopeSigner);
+                    match send_axfr(&mut stream, &zone, &query, signer, &transfers.config).await {
+                        Ok(stats) => {
+                            debug!("Transferred '{}' to '{peer}' in {} messages", zone.origin(), stats.messages);
+                            continue;
+                        },
+                        // Part of the transfer may already have been written, so the stream
+                        // cannot be used for anything else.
+                        Err(error) => {
+                            warn!("Transfer of '{}' to '{peer}' failed: {error}", zone.origin());
+                            return;
+                        },
+                    }
+                },
+                (Err(error), _) => empty_response(&query, error.rcode()),
+                // https://datatracker.ietf.org/doc/html/rfc8945#section-5.2
+                (Ok(zone), Err(error)) => {
+                    debug!("Refused transfer of '{}' to '{peer}': {error}", zone.origin());
+                    empty_response(&query, RCode::NotAuth)
+                },
+            },
+            (true, None) => empty_response(&query, RCode::Refused),
+            (false, _) => {
+                let request = Request { message: query, info: RequestInfo { peer, local: local_addr, transport: RequestTransport::Tcp } };
+                match handler.handle(&request).await {
+                    Some(response) => response,
+                    None => continue,
+                }
+            },
+        };
+
+        let mut wire = WriteWire::from_bytes(&mut buffer);
+        if let Err(error) = response.to_wire_format_with_two_octet_length(&mut wire, &mut None) {
+            warn!("Failed to encode response to '{peer}': {error}");
+            return;
+        }
+        let length = wire.current_len();
+        match timeout(write_timeout, stream.write_all(&buffer[..length])).await {
+            Ok(Ok(())) => (),
+            Ok(Err(_)) => return,
+            Err(_) => {
+                debug!("Closing connection from '{peer}' after it stopped reading responses");
+                return;
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod server_tests {
+    use std::{net::{Ipv4Addr, SocketAddr}, sync::Arc, time::Duration};
+
+    use dns_lib::{query::{message::Message, qr::QR, question::Question}, resource_record::{rclass::RClass, rcode::RCode, rtype::RType}, serde::wire::{from_wire::FromWire, read_wire::ReadWire, to_wire::ToWire, write_wire::WriteWire}, tsig::{MacCalculator, TsigAlgorithm, TsigExchange, TsigKey}, types::c_domain_name::{CDomainName, CompressionMap}};
+    use network::{listener::{bind_inbound, ListenSpec}, stream_limits::StreamLimits};
+    use tokio::{io::{AsyncReadExt, AsyncWriteExt}, net::{TcpStream, UdpSocket}, time::timeout};
+
+    use crate::{axfr::AxfrConfig, handler::{Acl, AddressRange, ZoneHandler}, tsig::TsigKeys, zone_store::{Zone, ZoneStore}};
+
+    use super::{AuthoritativeServer, ServerConfig};
+
+    const ZONE: &str = "\
+@                3600 IN SOA ns1.example.com. hostmaster.example.com. 1 7200 3600 1209600 300
+@                3600 IN NS  ns1.example.com.
+ns1.example.com. 3600 IN A   192.0.2.1
+www.example.com. 3600 IN A   192.0.2.2
+";
+
+    async fn server(config: ServerConfig) -> AuthoritativeServer {
+        let zones = ZoneStore::new();
+        zones.insert(Zone::from_zone_file(CDomainName::from_utf8("example.com.").unwrap(), RClass::Internet, ZONE).unwrap());
+        AuthoritativeServer::bind(SocketAddr::new(Ipv4Addr::LOCALHOST.into(), 0), Arc::new(zones), config).await.unwrap()
+    }
+
+    fn query(qname: &str, qtype: RType) -> Message {
+        Message::from(Question::new(CDomainName::from_utf8(qname).unwrap(), qtype, RClass::Internet))
+    }
+
+    async fn query_tcp(stream: &mut TcpStream, query: &Message) -> Message {
+        let response = query_tcp_wire(stream, query).await;
+        Message::from_wire_format(&mut ReadWire::from_bytes(&response)).unwrap()
+    }
+
+    async fn query_tcp_wire(stream: &mut TcpStream, query: &Message) -> Vec<u8> {
+        let mut buffer = vec![0_u8; u16::MAX as usize + 2];
+        let mut wire = WriteWire::from_bytes(&mut buffer);
+        query.to_wire_format_with_two_octet_length(&mut wire, &mut Some(CompressionMap::new())).unwrap();
+        let length = wire.current_len();
+        stream.write_all(&buffer[..length]).await.unwrap();
+
+        let length = usize::from(stream.read_u16().await.unwrap());
+        stream.read_exact(&mut buffer[..length]).await.unwrap();
+        buffer.truncate(length);
+        buffer
+    }
+
+    /// Not a real MAC, but it depends on every octet of the key and the data.
+    struct XorCalculator;
+
+    impl MacCalculator for XorCalculator {
+        fn supports(&self, _algorithm: TsigAlgorithm) -> bool { true }
+
+        fn mac(&self, algorithm: TsigAlgorithm, secret: &[u8], data: &[u8]) -> Option<Vec<u8>> {
+            let mut mac = vec![0_u8; algorithm.output_length()];
+            for (index, octet) in secret.iter().chain(data).enumerate() {
+                let position = index % mac.len();
+                mac[position] = mac[position].rotate_left(3) ^ octet ^ (index as u8);
+            }
+            Some(mac)
+        }
+    }
+
+    fn transfer_key(name: &str) -> TsigKey {
+        TsigKey::new(CDomainName::from_utf8(name).unwrap(), TsigAlgorithm::HmacSha256, b"secret".to_vec())
+    }
+
+    #[tokio::test]
+    async fn answers_over_udp() {
+        let server = server(ServerConfig::default()).await;
+        let socket = UdpSocket::bind(SocketAddr::new(Ipv4Addr::LOCALHOST.into(), 0)).await.unwrap();
+
+        let mut buffer = vec![0_u8; 512];
+        let mut wire = WriteWire::from_bytes(&mut buffer);
+        query("www.example.com.", RType::A).to_wire_format(&mut wire, &mut None).unwrap();
+        let length = wire.current_len();
+        socket.send_to(&buffer[..length], server.local_addr()).await.unwrap();
+
+        let length = socket.recv(&mut buffer).await.unwrap();
+        let response = Message::from_wire_format(&mut ReadWire::from_bytes(&buffer[..length])).unwrap();
+        assert_eq!(response.rcode, RCode::NoError);
+        assert!(response.authoritative_answer);
+        assert_eq!(response.answer.len(), 1);
+    }
+
+    async fn send_udp(socket: &UdpSocket, message: &Message, server_address: SocketAddr) {
+        let mut buffer = vec![0_u8; 512];
+        let mut wire = WriteWire::from_bytes(&mut buffer);
+        message.to_wire_format(&mut wire, &mut None).unwrap();
+        let length = wire.current_len();
+        socket.send_to(&buffer[..length], server_address).await.unwrap();
+    }
+
+    /// Waits a short time for a response to arrive on the socket.
+    async fn recv_udp(socket: &UdpSocket) -> Option<Message> {
+        let mut buffer = vec![0_u8; 512];
+        let length = timeout(Duration::from_millis(200), socket.recv(&mut buffer)).await.ok()?.unwrap();
+        Some(Message::from_wire_format(&mut ReadWire::from_bytes(&buffer[..length])).unwrap())
+    }
+
+    #[tokio::test]
+    async fn udp_responses_are_not_answered() {
+        let server = server(ServerConfig::default()).await;
+        let socket = UdpSocket::bind(SocketAddr::new(Ipv4Addr::LOCALHOST.into(), 0)).await.unwrap();
+
+        let mut response = query("www.example.com.", RType::A);
+        response.qr = QR::Response;
+        send_udp(&socket, &response, server.local_addr()).await;
+        assert!(recv_udp(&socket).await.is_none());
+
+        send_udp(&socket, &query("www.example.com.", RType::A), server.local_addr()).await;
+        assert_eq!(recv_udp(&socket).await.unwrap().answer.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn udp_queries_over_the_limit_are_dropped() {
+        let server = server(ServerConfig { max_udp_queries: 0, ..Default::default() }).await;
+        let socket = UdpSocket::bind(SocketAddr::new(Ipv4Addr::LOCALHOST.into(), 0)).await.unwrap();
+        send_udp(&socket, &query("www.example.com.", RType::A), server.local_addr()).await;
+        assert!(recv_udp(&socket).await.is_none());
+
+        // TCP has its own limits.
+        let mut stream = TcpStream::connect(server.local_addr()).await.unwrap();
+        let response = query_tcp(&mut stream, &query("www.example.com.", RType::A)).await;
+        assert_eq!(response.answer.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn answers_on_wildcard_sockets_from_the_listener() {
+        let zones = ZoneStore::new();
+        zones.insert(Zone::from_zone_file(CDomainName::from_utf8("example.com.").unwrap(), RClass::Internet, ZONE).unwrap());
+        let udp_socket = bind_inbound(&ListenSpec::udp(SocketAddr::new(Ipv4Addr::UNSPECIFIED.into(), 0))).unwrap();
+        let port = udp_socket.local_addr().unwrap().port();
+        let tcp_listener = bind_inbound(&ListenSpec::tcp(SocketAddr::new(Ipv4Addr::UNSPECIFIED.into(), port))).unwrap();
+        let _server = AuthoritativeServer::from_sockets(udp_socket.into_tokio_udp().unwrap(), tcp_listener.into_tokio_tcp().unwrap(), Arc::new(zones), ServerConfig::default()).unwrap();
+        let server_address = SocketAddr::new(Ipv4Addr::LOCALHOST.into(), port);
+
+        let socket = UdpSocket::bind(SocketAddr::new(Ipv4Addr::LOCALHOST.into(), 0)).await.unwrap();
+        let mut buffer = vec![0_u8; 512];
+        let mut wire = WriteWire::from_bytes(&mut buffer);
+        query("www.example.com.", RType::A).to_wire_format(&mut wire, &mut None).unwrap();
+        let length = wire.current_len();
+        socket.send_to(&buffer[..length], server_address).await.unwrap();
+        // The response comes from the address that the query was sent to.
+        let (length, responder) = socket.recv_from(&mut buffer).await.unwrap();
+        assert_eq!(responder, server_address);
+        let response = Message::from_wire_format(&mut ReadWire::from_bytes(&buffer[..length])).unwrap();
+        assert_eq!(response.answer.len(), 1);
+
+        let mut stream = TcpStream::connect(server_address).await.unwrap();
+        let response = query_tcp(&mut stream, &query("www.example.com.", RType::A)).await;
+        assert_eq!(response.answer.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn answers_over_tcp_and_only_transfers_when_allowed() {
+        let server = server(ServerConfig::default()).await;
+        let mut stream = TcpStream::connect(server.local_addr()).await.unwrap();
+        let response = query_tcp(&mut stream, &query("www.example.com.", RType::A)).await;
+        assert_eq!(response.answer.len(), 1);
+        let response = query_tcp(&mut stream, &query("example.com.", RType::AXFR)).await;
+        assert_eq!(response.rcode, RCode::Refused);
+        assert!(response.answer.is_empty());
+
+        let server = self::server(ServerConfig { transfers: Some(AxfrConfig::default()), ..Default::default() }).await;
+        let mut stream = TcpStream::connect(server.local_addr()).await.unwrap();
+        let response = query_tcp(&mut stream, &query("example.com.", RType::AXFR)).await;
+        assert_eq!(response.rcode, RCode::NoError);
+        // The SOA, both of the other records, and the SOA again.
+        assert_eq!(response.answer.len(), 5);
+    }
+
+    #[tokio::test]
+    async fn tcp_connections_are_limited() {
+        let stream_limits = StreamLimits { max_connections_per_ip: 1, idle_timeout: Duration::from_millis(50), ..Default::default() };
+        let server = server(ServerConfig { stream_limits, ..Default::default() }).await;
+        let mut first = TcpStream::connect(server.local_addr()).await.unwrap();
+        let response = query_tcp(&mut first, &query("www.example.com.", RType::A)).await;
+        assert_eq!(response.answer.len(), 1);
+
+        // The second connection from the same address is closed straight away.
+        let mut second = TcpStream::connect(server.local_addr()).await.unwrap();
+        assert_eq!(timeout(Duration::from_secs(1), second.read(&mut [0_u8; 2])).await.unwrap().unwrap(), 0);
+        assert_eq!(server.stream_stats().rejected_per_ip, 1);
+
+        // The first is closed once it has been idle for too long, which frees up its slot.
+        assert_eq!(timeout(Duration::from_secs(1), first.read(&mut [0_u8; 2])).await.unwrap().unwrap(), 0);
+        assert_eq!(server.stream_stats().idle_terminations, 1);
+        let mut third = TcpStream::connect(server.local_addr()).await.unwrap();
+        let response = query_tcp(&mut third, &query("www.example.com.", RType::A)).await;
+        assert_eq!(response.answer.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn signed_transfer_requests_get_signed_transfers() {
+        let mut transfer_keys = TsigKeys::new(Arc::new(XorCalculator));
+        transfer_keys.insert(transfer_key("transfer.example.")).unwrap();
+        let server = server(ServerConfig { transfers: Some(AxfrConfig::default()), transfer_keys, ..Default::default() }).await;
+        let mut stream = TcpStream::connect(server.local_addr()).await.unwrap();
+
+        let key = transfer_key("transfer.example.");
+        let mut exchange = TsigExchange::new(&key, &XorCalculator).unwrap();
+        let mut request = query("example.com.", RType::AXFR);
+        exchange.sign(&mut request).unwrap();
+        let response = query_tcp_wire(&mut stream, &request).await;
+        exchange.verify(&response).unwrap();
+        exchange.finish().unwrap();
+
+        // The server does not have this key.
+        let key = transfer_key("unknown.example.");
+        let mut request = query("example.com.", RType::AXFR);
+        TsigExchange::new(&key, &XorCalculator).unwrap().sign(&mut request).unwrap();
+        let response = query_tcp(&mut stream, &request).await;
+        assert_eq!(response.rcode, RCode::NotAuth);
+        assert!(response.answer.is_empty());
+    }
+
+    #[tokio::test]
+    async fn answers_with_the_handler() {
+        let zones = ZoneStore::new();
+        zones.insert(Zone::from_zone_file(CDomainName::from_utf8("example.com.").unwrap(), RClass::Internet, ZONE).unwrap());
+        let handler = Acl::new(["192.0.2.0/24".parse::<AddressRange>().unwrap()], ZoneHandler::new(Arc::new(zones)));
+        let server = AuthoritativeServer::bind_handler(SocketAddr::new(Ipv4Addr::LOCALHOST.into(), 0), Arc::new(handler)).await.unwrap();
+
+        // The loopback address is not in the ACL.
+        let mut stream = TcpStream::connect(server.local_addr()).await.unwrap();
+        let response = query_tcp(&mut stream, &query("www.example.com.", RType::A)).await;
+        assert_eq!(response.rcode, RCode::Refused);
+        let response = query_tcp(&mut stream, &query("example.com.", RType::AXFR)).await;
+        assert_eq!(response.rcode, RCode::Refused);
+    }
+}
@@ -0,0 +1,69 @@
+use std::{fmt::Debug, sync::Arc};
+
+use dns_lib::{tsig::{signing_key_name, MacCalculator, TsigAlgorithm, TsigError, TsigExchange, TsigKey}, types::c_domain_name::CmpDomainName};
+
+/// The TSIG keys that the server accepts on requests, by key name. A signed request is verified
+/// with the key it names and the response is signed with the same key. Requests signed with a key
+/// that the server does not have are rejected.
+#[derive(Clone, Default)]
+pub struct TsigKeys {
+    keys: Vec<TsigKey>,
+    calculator: Option<Arc<dyn MacCalculator>>,
+}
+
+impl TsigKeys {
+    #[inline]
+    pub fn new(calculator: Arc<dyn MacCalculator>) -> Self {
+        Self { keys: Vec::new(), calculator: Some(calculator) }
+    }
+
+    /// Accepts requests signed with the key, replacing any key with the same name. The key is
+    /// rejected if the calculator does not support its algorithm.
+    pub fn insert(&mut self, key: TsigKey) -> Result<Option<TsigKey>, TsigError> {
+        if !self.supports(key.algorithm()) {
+            return Err(TsigError::UnsupportedAlgorithm(key.algorithm()));
+        }
+        match self.keys.iter_mut().find(|existing| existing.name().matches(key.name())) {
+            Some(existing) => Ok(Some(std::mem::replace(existing, key))),
+            None => {
+                self.keys.push(key);
+                Ok(None)
+            },
+        }
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+
+    #[inline]
+    fn supports(&self, algorithm: TsigAlgorithm) -> bool {
+        self.calculator.as_ref().is_some_and(|calculator| calculator.supports(algorithm))
+    }
+
+    /// Verifies a request as it was received. If it is signed, the exchange that the response must
+    /// be signed with is returned. Unsigned requests return `None`.
+    pub fn verify_request(&self, wire: &[u8]) -> Result<Option<TsigExchange<'_>>, TsigError> {
+        let Some(key_name) = signing_key_name(wire)? else {
+            return Ok(None);
+        };
+        let Some(key) = self.keys.iter().find(|key| key.name().matches(&key_name)) else {
+            return Err(TsigError::BadKey);
+        };
+        let Some(calculator) = self.calculator.as_deref() else {
+            return Err(TsigError::BadKey);
+        };
+        let mut exchange = TsigExchange::new(key, calculator)?;
+        exchange.verify(wire)?;
+        Ok(Some(exchange))
+    }
+}
+
+impl Debug for TsigKeys {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TsigKeys")
+            .field("keys", &self.keys)
+            .finish_non_exhaustive()
+    }
+}
@@ -0,0 +1,210 @@
+use std::{collections::{BTreeMap, HashMap}, error::Error, fmt::Display, sync::{Arc, RwLock}};
+
+use dns_lib::{resource_record::{rclass::RClass, resource_record::ResourceRecord, rtype::RType}, serde::presentation::zone_file_reader::{ZoneFileReader, ZoneToken}, types::{c_domain_name::{CDomainName, CmpDomainName}, label::Label}};
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum ZoneError {
+    MissingSoa(CDomainName),
+    MultipleSoa(CDomainName),
+    OutOfZone { origin: CDomainName, name: CDomainName },
+    WrongClass { expected: RClass, actual: RClass },
+    Parse(String),
+    UnsupportedInclude,
+}
+impl Error for ZoneError {}
+impl Display for ZoneError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::MissingSoa(origin) => write!(f, "Zone '{origin}' does not have an SOA record at its apex"),
+            Self::MultipleSoa(origin) => write!(f, "Zone '{origin}' has more than one SOA record at its apex"),
+            Self::OutOfZone { origin, name } => write!(f, "Record for '{name}' is not in zone '{origin}'"),
+            Self::WrongClass { expected, actual } => write!(f, "Record has class {actual} but the zone has class {expected}"),
+            Self::Parse(error) => write!(f, "{error}"),
+            Self::UnsupportedInclude => write!(f, "$INCLUDE is not supported when loading a zone"),
+        }
+    }
+}
+
+/// The owner name in canonical order (RFC 4034 section 6.1): the labels are compared from the
+/// root down, as lowercase octets.
+type CanonicalName = Vec<Vec<u8>>;
+
+#[inline]
+fn canonical_name(name: &CDomainName) -> CanonicalName {
+    name.case_insensitive_labels()
+        .rev()
+        .map(|label| label.octets().to_ascii_lowercase())
+        .collect()
+}
+
+/// The records of one zone, kept in canonical order so that they can be transferred or signed
+/// without sorting them again. A zone is immutable once it is created. To change it, build a new
+/// one and replace it in the `ZoneStore`, so that transfers already in progress keep working from
+/// a consistent copy.
+#[derive(Debug)]
+pub struct Zone {
+    origin: CDomainName,
+    rclass: RClass,
+    soa: ResourceRecord,
+    // Every record other than the apex SOA, grouped into RRsets.
+    rrsets: BTreeMap<(CanonicalName, u16), Vec<ResourceRecord>>,
+    record_count: usize,
+}
+
+impl Zone {
+    pub fn new(origin: CDomainName, rclass: RClass, records: impl IntoIterator<Item = ResourceRecord>) -> Result<Self, ZoneError> {
+        let mut soa = None;
+        let mut rrsets: BTreeMap<(CanonicalName, u16), Vec<ResourceRecord>> = BTreeMap::new();
+        let mut record_count = 0;
+        for record in records {
+            if !origin.is_parent_domain_of(record.get_name()) {
+                return Err(ZoneError::OutOfZone { origin, name: record.get_name().clone() });
+            }
+            if record.get_rclass() != rclass {
+                return Err(ZoneError::WrongClass { expected: rclass, actual: record.get_rclass() });
+            }
+            if (record.get_rtype() == RType::SOA) && record.get_name().matches(&origin) {
+                if soa.replace(record).is_some() {
+                    return Err(ZoneError::MultipleSoa(origin));
+                }
+                continue;
+            }
+            let rrset = rrsets.entry((canonical_name(record.get_name()), record.get_rtype().code())).or_default();
+            if !rrset.contains(&record) {
+                rrset.push(record);
+                record_count += 1;
+            }
+        }
+        let Some(soa) = soa else {
+            return Err(ZoneError::MissingSoa(origin));
+        };
+        Ok(Self { origin, rclass, soa, rrsets, record_count })
+    }
+
+    /// Reads the zone out of zone file text. `@` stands for `origin` unless the file sets its own
+    /// with `$ORIGIN`.
+    pub fn from_zone_file(origin: CDomainName, rclass: RClass, zone_file: &str) -> Result<Self, ZoneError> {
+        let origin_text = origin.to_string();
+        let mut reader = ZoneFileReader::new(zone_file);
+        reader.set_origin(&origin_text);
+        let (tokens, diagnostics) = reader.read_all();
+        if let Some(diagnostic) = diagnostics.first() {
+            return Err(ZoneError::Parse(diagnostic.to_string()));
+        }
+        let records = tokens.into_iter()
+            .map(|token| match token {
+                ZoneToken::ResourceRecord(record) => Ok(record),
+                ZoneToken::Include { .. } => Err(ZoneError::UnsupportedInclude),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Self::new(origin, rclass, records)
+    }
+
+    #[inline]
+    pub fn origin(&self) -> &CDomainName { &self.origin }
+
+    #[inline]
+    pub fn rclass(&self) -> RClass { self.rclass }
+
+    #[inline]
+    pub fn soa(&self) -> &ResourceRecord { &self.soa }
+
+    /// The number of records in the zone, including the SOA.
+    #[inline]
+    pub fn len(&self) -> usize { self.record_count + 1 }
+
+    /// Every record other than the apex SOA, in canonical order. Records with the same owner are
+    /// ordered by type.
+    #[inline]
+    pub fn records(&self) -> impl Iterator<Item = &ResourceRecord> {
+        self.rrsets.values().flatten()
+    }
+
+    /// The records with this owner name and type.
+    pub fn rrset(&self, name: &CDomainName, rtype: RType) -> &[ResourceRecord] {
+        if (rtype == RType::SOA) && name.matches(&self.origin) {
+            return std::slice::from_ref(&self.soa);
+        }
+        self.rrsets.get(&(canonical_name(name), rtype.code()))
+            .map(|rrset| rrset.as_slice())
+            .unwrap_or_default()
+    }
+
+    /// Every RRset owned by this name, ordered by type.
+    pub fn rrsets_at<'a>(&'a self, name: &CDomainName) -> impl Iterator<Item = &'a [ResourceRecord]> {
+        let name_key = canonical_name(name);
+        let soa = name.matches(&self.origin).then_some(std::slice::from_ref(&self.soa));
+        soa.into_iter().chain(
+            self.rrsets.range((name_key.clone(), 0)..=(name_key, u16::MAX))
+                .map(|(_, rrset)| rrset.as_slice())
+        )
+    }
+
+    /// Whether the name exists in the zone, either because it owns records or because a name
+    /// below it does (an empty non-terminal).
+    pub fn contains_name(&self, name: &CDomainName) -> bool {
+        if name.matches(&self.origin) {
+            return true;
+        }
+        // Canonical order puts every name below this one directly after it.
+        let name_key = canonical_name(name);
+        self.rrsets.range((name_key.clone(), 0)..)
+            .next()
+            .is_some_and(|((owner, _), _)| owner.starts_with(&name_key))
+    }
+}
+
+/// The zones a server is authoritative for.
+#[derive(Debug, Default)]
+pub struct ZoneStore {
+    zones: RwLock<HashMap<(CDomainName, RClass), Arc<Zone>>>,
+}
+
+impl ZoneStore {
+    #[inline]
+    pub fn new() -> Self {
+        Self { zones: RwLock::new(HashMap::new()) }
+    }
+
+    /// Adds the zone, replacing (and returning) any zone with the same origin and class.
+    pub fn insert(&self, zone: Zone) -> Option<Arc<Zone>> {
+        let key = (zone.origin.as_lowercase(), zone.rclass);
+        let mut w_zones = self.zones.write().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let previous = w_zones.insert(key, Arc::new(zone));
+        drop(w_zones);
+        previous
+    }
+
+    pub fn remove(&self, origin: &CDomainName, rclass: RClass) -> Option<Arc<Zone>> {
+        let mut w_zones = self.zones.write().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let zone = w_zones.remove(&(origin.as_lowercase(), rclass));
+        drop(w_zones);
+        zone
+    }
+
+    /// The zone whose apex is exactly this name.
+    pub fn get(&self, origin: &CDomainName, rclass: RClass) -> Option<Arc<Zone>> {
+        let r_zones = self.zones.read().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let zone = r_zones.get(&(origin.as_lowercase(), rclass)).cloned();
+        drop(r_zones);
+        zone
+    }
+
+    /// The closest zone that contains this name.
+    pub fn find(&self, name: &CDomainName, rclass: RClass) -> Option<Arc<Zone>> {
+        let name = name.as_lowercase();
+        let r_zones = self.zones.read().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let zone = name.search_domains()
+            .find_map(|domain| r_zones.get(&(domain, rclass)).cloned());
+        drop(r_zones);
+        zone
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        let r_zones = self.zones.read().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let len = r_zones.len();
+        drop(r_zones);
+        len
+    }
+}
@@ -0,0 +1,55 @@
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use dns_lib::{query::{message::Message, question::Question}, resource_record::{rclass::RClass, rcode::RCode, resource_record::RecordData, rtype::RType, time::Time, types::a::A}, types::c_domain_name::CDomainName};
+use dns_test_support::echo_server::{EchoMode, EchoServer};
+use futures::future::join_all;
+use network::{async_query::QueryOpt, mixed_tcp_udp::MixedSocket};
+use tokio::runtime::Runtime;
+
+const LOCALHOST: SocketAddr = SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 0);
+
+fn question() -> Question {
+    Question::new(CDomainName::from_utf8("www.example.org.").unwrap(), RType::A, RClass::Internet)
+}
+
+fn static_mode() -> EchoMode {
+    EchoMode::Static {
+        rcode: RCode::NoError,
+        records: vec![(Time::from_secs(60), RecordData::A(A::new(Ipv4Addr::new(192, 0, 2, 1))))],
+    }
+}
+
+fn mixed_socket_udp_benchmark(c: &mut Criterion) {
+    let runtime = Runtime::new().unwrap();
+    let server = runtime.block_on(EchoServer::bind(LOCALHOST, static_mode(), 4)).unwrap();
+    let socket = MixedSocket::new(server.local_addr());
+    let question = question();
+
+    c.bench_function("MixedSocket UDP Sequential Queries", |b|
+        b.to_async(&runtime).iter(|| async {
+            let mut query = Message::from(&question);
+            black_box(MixedSocket::query(&socket, &mut query, QueryOpt::UdpTcp).await.unwrap());
+        })
+    );
+
+    let mut benchmark_group = c.benchmark_group("MixedSocket UDP Concurrent Queries");
+    for concurrency in [8_usize, 64, 256] {
+        benchmark_group.bench_with_input(BenchmarkId::from_parameter(concurrency), &concurrency, |b, concurrency|
+            b.to_async(&runtime).iter(|| join_all((0..*concurrency).map(|_| {
+                let socket = socket.clone();
+                let question = question.clone();
+                async move {
+                    let mut query = Message::from(question);
+                    black_box(MixedSocket::query(&socket, &mut query, QueryOpt::UdpTcp).await.unwrap());
+                }
+            })))
+        );
+    }
+    benchmark_group.finish();
+
+    runtime.block_on(socket.disable());
+}
+
+criterion_group!(benches, mixed_socket_udp_benchmark);
+criterion_main!(benches);
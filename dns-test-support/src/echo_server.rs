@@ -0,0 +1,257 @@
+use std::{io, net::SocketAddr, sync::{atomic::{AtomicU64, Ordering}, Arc}};
+
+use dns_lib::{resource_record::{rcode::RCode, resource_record::{RData, RecordData}, time::Time}, serde::wire::{to_wire::ToWire, write_wire::{WriteWire, WriteWireError}}};
+use tokio::{net::UdpSocket, task::JoinHandle};
+
+const HEADER_LENGTH: usize = 12;
+const MAX_UDP_PACKET: usize = 65535;
+/// A compression pointer to the question's qname, which always starts right after the header.
+const QNAME_POINTER: [u8; 2] = [0xC0, HEADER_LENGTH as u8];
+
+/// How the server responds to each query.
+#[derive(Debug, Clone, PartialEq)]
+pub enum EchoMode {
+    /// The query is sent back with the QR bit set. Nothing else is changed.
+    Echo,
+    /// Every query is answered with the same records, owned by the query's qname and in the
+    /// query's class.
+    Static {
+        rcode: RCode,
+        records: Vec<(Time, RecordData)>,
+    },
+}
+
+/// The answer section for `EchoMode::Static`, serialized once so that each response is a few
+/// copies. The class of each record is filled in from the question.
+#[derive(Debug)]
+struct AnswerTemplate {
+    rcode: u8,
+    record_count: u16,
+    answer: Vec<u8>,
+    class_offsets: Vec<usize>,
+}
+
+impl AnswerTemplate {
+    fn new(rcode: RCode, records: &[(Time, RecordData)]) -> Result<Self, WriteWireError> {
+        let mut buffer = vec![0_u8; MAX_UDP_PACKET];
+        let mut wire = WriteWire::from_bytes(&mut buffer);
+        let mut class_offsets = Vec::with_capacity(records.len());
+        for (ttl, rdata) in records {
+            wire.write_bytes(&QNAME_POINTER)?;
+            rdata.get_rtype().to_wire_format(&mut wire, &mut None)?;
+            class_offsets.push(wire.current_len());
+            0_u16.to_wire_format(&mut wire, &mut None)?;
+            ttl.to_wire_format(&mut wire, &mut None)?;
+            rdata.serial_length().to_wire_format(&mut wire, &mut None)?;
+            rdata.to_wire_format(&mut wire, &mut None)?;
+        }
+        Ok(Self {
+            // Only the low four bits of the rcode fit in the header.
+            rcode: (rcode.code() & 0x0F) as u8,
+            record_count: records.len() as u16,
+            answer: wire.current().to_vec(),
+            class_offsets,
+        })
+    }
+
+    /// Writes the response to the query into `response`. `question_end` is the offset of the
+    /// first octet after the question.
+    fn respond(&self, query: &[u8], question_end: usize, response: &mut Vec<u8>) {
+        response.clear();
+        response.extend_from_slice(&query[..question_end]);
+        // QR and AA are set. The opcode and RD are copied from the query.
+        response[2] = 0x80 | (query[2] & 0x79) | 0x04;
+        response[3] = 0x80 | self.rcode;
+        response[6..8].copy_from_slice(&self.record_count.to_be_bytes());
+        response[8..12].fill(0);
+
+        let answer_start = response.len();
+        response.extend_from_slice(&self.answer);
+        let qclass = [query[question_end - 2], query[question_end - 1]];
+        for offset in &self.class_offsets {
+            response[answer_start + offset..answer_start + offset + 2].copy_from_slice(&qclass);
+        }
+    }
+}
+
+/// Finds the end of the question in a query that has exactly one question. Anything else, including
+/// a compressed qname, is malformed for the purposes of this server.
+fn question_end(query: &[u8]) -> Option<usize> {
+    if (query.len() < HEADER_LENGTH) || (query[2] & 0x80 != 0) || (query[4..6] != [0, 1]) {
+        return None;
+    }
+    let mut offset = HEADER_LENGTH;
+    loop {
+        let label_length = *query.get(offset)? as usize;
+        if label_length & 0xC0 != 0 {
+            return None;
+        }
+        offset += 1 + label_length;
+        if label_length == 0 {
+            break;
+        }
+    }
+    // The qtype and qclass follow the qname.
+    let end = offset + 4;
+    (end <= query.len()).then_some(end)
+}
+
+#[derive(Debug, Default)]
+struct Counters {
+    served: AtomicU64,
+    malformed: AtomicU64,
+}
+
+/// A UDP server that answers single-question queries without fully parsing them. It does as
+/// little work per packet as possible so that the client (or listener) being measured, not the
+/// server, is the bottleneck.
+///
+/// The server stops when it is dropped.
+#[derive(Debug)]
+pub struct EchoServer {
+    local_addr: SocketAddr,
+    counters: Arc<Counters>,
+    workers: Vec<JoinHandle<()>>,
+}
+
+impl EchoServer {
+    /// Binds to the address and starts `workers` tasks that share the socket. Use port 0 to bind
+    /// to any free port and `local_addr()` to find out which one was chosen.
+    pub async fn bind(address: SocketAddr, mode: EchoMode, workers: usize) -> io::Result<Self> {
+        let socket = Arc::new(UdpSocket::bind(address).await?);
+        let local_addr = socket.local_addr()?;
+        let template = match &mode {
+            EchoMode::Echo => None,
+            EchoMode::Static { rcode, records } => Some(Arc::new(
+                AnswerTemplate::new(*rcode, records)
+                    .map_err(|error| io::Error::new(io::ErrorKind::InvalidInput, error.to_string()))?
+            )),
+        };
+        let counters = Arc::new(Counters::default());
+        let workers = (0..workers.max(1))
+            .map(|_| tokio::spawn(serve(socket.clone(), template.clone(), counters.clone())))
+            .collect();
+        Ok(Self { local_addr, counters, workers })
+    }
+
+    #[inline]
+    pub fn local_addr(&self) -> SocketAddr { self.local_addr }
+
+    /// The number of queries that have been answered.
+    #[inline]
+    pub fn served(&self) -> u64 { self.counters.served.load(Ordering::Relaxed) }
+
+    /// The number of packets that were dropped because they were not single-question queries.
+    #[inline]
+    pub fn malformed(&self) -> u64 { self.counters.malformed.load(Ordering::Relaxed) }
+}
+
+impl Drop for EchoServer {
+    fn drop(&mut self) {
+        for worker in &self.workers {
+            worker.abort();
+        }
+    }
+}
+
+async fn serve(socket: Arc<UdpSocket>, template: Option<Arc<AnswerTemplate>>, counters: Arc<Counters>) {
+    let mut query = vec![0_u8; MAX_UDP_PACKET];
+    let mut response = Vec::with_capacity(MAX_UDP_PACKET);
+    loop {
+        let (length, peer) = match socket.recv_from(&mut query).await {
+            Ok(received) => received,
+            // Errors such as ICMP port unreachable from a previous send are not fatal.
+            Err(_) => continue,
+        };
+        let query = &query[..length];
+        let question_end = match question_end(query) {
+            Some(question_end) => question_end,
+            None => {
+                counters.malformed.fetch_add(1, Ordering::Relaxed);
+                continue;
+            },
+        };
+        match &template {
+            Some(template) => template.respond(query, question_end, &mut response),
+            None => {
+                response.clear();
+                response.extend_from_slice(query);
+                response[2] |= 0x80;
+            },
+        }
+        if socket.send_to(&response, peer).await.is_ok() {
+            counters.served.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+}
+
+#[cfg(test)]
+mod echo_server_tests {
+    use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+
+    use dns_lib::{query::{message::Message, qr::QR, question::Question}, resource_record::{rclass::RClass, rcode::RCode, resource_record::RecordData, rtype::RType, time::Time, types::a::A}, serde::wire::{from_wire::FromWire, read_wire::ReadWire, to_wire::ToWire, write_wire::WriteWire}, types::c_domain_name::CDomainName};
+    use tokio::net::UdpSocket;
+
+    use super::{question_end, EchoMode, EchoServer};
+
+    const LOCALHOST: SocketAddr = SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 0);
+
+    fn query() -> Message {
+        let mut message = Message::from(Question::new(CDomainName::from_utf8("www.example.org.").unwrap(), RType::A, RClass::Internet));
+        message.id = 0x1234;
+        message.recursion_desired = true;
+        message
+    }
+
+    async fn exchange(server: &EchoServer, message: &Message) -> Message {
+        let mut buffer = vec![0_u8; 512];
+        let mut wire = WriteWire::from_bytes(&mut buffer);
+        message.to_wire_format(&mut wire, &mut None).unwrap();
+
+        let client = UdpSocket::bind(LOCALHOST).await.unwrap();
+        client.send_to(wire.current(), server.local_addr()).await.unwrap();
+        let mut response = vec![0_u8; 512];
+        let length = client.recv(&mut response).await.unwrap();
+        Message::from_wire_format(&mut ReadWire::from_bytes(&response[..length])).unwrap()
+    }
+
+    #[test]
+    fn rejects_malformed() {
+        assert_eq!(question_end(&[0; 11]), None);
+        // No questions.
+        assert_eq!(question_end(&[0; 12]), None);
+        // The qname runs past the end of the packet.
+        assert_eq!(question_end(&[0, 0, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 3, b'w', b'w']), None);
+        // A compressed qname.
+        assert_eq!(question_end(&[0, 0, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0xC0, 12, 0, 1, 0, 1]), None);
+        assert_eq!(question_end(&[0, 0, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0, 0, 1, 0, 1]), Some(17));
+    }
+
+    #[tokio::test]
+    async fn echo() {
+        let server = EchoServer::bind(LOCALHOST, EchoMode::Echo, 1).await.unwrap();
+        let query = query();
+        let response = exchange(&server, &query).await;
+        assert_eq!(response.qr, QR::Response);
+        assert_eq!(response.id, query.id);
+        assert_eq!(response.question, query.question);
+        assert_eq!(server.served(), 1);
+    }
+
+    #[tokio::test]
+    async fn static_answer() {
+        let rdata = RecordData::A(A::new(Ipv4Addr::new(192, 0, 2, 1)));
+        let mode = EchoMode::Static { rcode: RCode::NoError, records: vec![(Time::from_secs(60), rdata.clone())] };
+        let server = EchoServer::bind(LOCALHOST, mode, 2).await.unwrap();
+        let query = query();
+        let response = exchange(&server, &query).await;
+        assert_eq!(response.qr, QR::Response);
+        assert_eq!(response.id, query.id);
+        assert!(response.recursion_desired);
+        assert_eq!(response.rcode, RCode::NoError);
+        assert_eq!(response.answer.len(), 1);
+        assert_eq!(response.answer[0].get_name(), query.question[0].qname());
+        assert_eq!(response.answer[0].get_rclass(), RClass::Internet);
+        assert_eq!(response.answer[0].get_rdata(), &rdata);
+    }
+}
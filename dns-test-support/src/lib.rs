@@ -0,0 +1,4 @@
+pub mod echo_server;
+pub mod mock_server;
+pub mod query_script;
+pub mod self_test;
@@ -0,0 +1,152 @@
+use std::{io, net::SocketAddr, sync::{Arc, Mutex}};
+
+use dns_lib::{query::{message::Message, qr::QR, question::Question}, resource_record::rcode::RCode, serde::wire::{from_wire::FromWire, read_wire::ReadWire, to_wire::ToWire, write_wire::WriteWire}};
+use tokio::{io::{AsyncReadExt, AsyncWriteExt}, net::{TcpListener, TcpStream, UdpSocket}, task::JoinHandle};
+
+const MAX_MESSAGE: usize = 65535;
+
+/// The transport that a query reached the mock server over.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MockTransport {
+    Udp,
+    Tcp,
+}
+
+/// Decides how the mock server answers each query. Returning `None` drops the query without
+/// answering it.
+pub type MockHandler = Arc<dyn Fn(&Message, MockTransport) -> Option<Message> + Send + Sync>;
+
+/// A name server that answers queries over UDP and TCP on the same port using a handler written
+/// by the test. Unlike the `EchoServer`, every query is fully parsed so the handler can answer
+/// based on its contents. Every query that is received is recorded.
+///
+/// The server stops when it is dropped.
+pub struct MockServer {
+    local_addr: SocketAddr,
+    queries: Arc<Mutex<Vec<(MockTransport, Question)>>>,
+    tasks: Vec<JoinHandle<()>>,
+}
+
+impl MockServer {
+    /// Binds UDP and TCP to the address. Use port 0 to bind to any free port. The TCP listener
+    /// uses whichever port the UDP socket was given.
+    pub async fn bind(address: SocketAddr, handler: MockHandler) -> io::Result<Self> {
+        let udp_socket = Arc::new(UdpSocket::bind(address).await?);
+        let local_addr = udp_socket.local_addr()?;
+        let tcp_listener = TcpListener::bind(local_addr).await?;
+        let queries = Arc::new(Mutex::new(Vec::new()));
+        let tasks = vec![
+            tokio::spawn(serve_udp(udp_socket, handler.clone(), queries.clone())),
+            tokio::spawn(serve_tcp(tcp_listener, handler, queries.clone())),
+        ];
+        Ok(Self { local_addr, queries, tasks })
+    }
+
+    #[inline]
+    pub fn local_addr(&self) -> SocketAddr { self.local_addr }
+
+    /// The questions that have been received so far, in the order they arrived.
+    #[inline]
+    pub fn queries(&self) -> Vec<(MockTransport, Question)> {
+        self.queries.lock().unwrap().clone()
+    }
+}
+
+impl Drop for MockServer {
+    fn drop(&mut self) {
+        for task in &self.tasks {
+            task.abort();
+        }
+    }
+}
+
+/// Starts a response to the query with the ID, opcode, RD bit, and question copied from it. The
+/// response is authoritative and has no records.
+pub fn response_to(query: &Message, rcode: RCode) -> Message {
+    let mut response = query.clone();
+    response.qr = QR::Response;
+    response.authoritative_answer = true;
+    response.truncation = false;
+    response.recursion_available = false;
+    response.rcode = rcode;
+    response.answer.clear();
+    response.authority.clear();
+    response.additional.clear();
+    response
+}
+
+fn record_query(queries: &Mutex<Vec<(MockTransport, Question)>>, transport: MockTransport, query: &Message) {
+    let mut queries = queries.lock().unwrap();
+    queries.extend(query.question.iter().map(|question| (transport, question.clone())));
+}
+
+fn parse(bytes: &[u8]) -> Option<Message> {
+    Message::from_wire_format(&mut ReadWire::from_bytes(bytes)).ok()
+}
+
+fn serialize(message: &Message, buffer: &mut [u8]) -> Option<usize> {
+    let mut wire = WriteWire::from_bytes(buffer);
+    message.to_wire_format(&mut wire, &mut None).ok()?;
+    Some(wire.current_len())
+}
+
+async fn serve_udp(socket: Arc<UdpSocket>, handler: MockHandler, queries: Arc<Mutex<Vec<(MockTransport, Question)>>>) {
+    let mut buffer = vec![0_u8; MAX_MESSAGE];
+    loop {
+        let (length, peer) = match socket.recv_from(&mut buffer).await {
+            Ok(received) => received,
+            // Errors such as ICMP port unreachable from a previous send are not fatal.
+            Err(_) => continue,
+        };
+        let Some(query) = parse(&buffer[..length]) else { continue };
+        record_query(&queries, MockTransport::Udp, &query);
+        let Some(response) = handler(&query, MockTransport::Udp) else { continue };
+        if let Some(length) = serialize(&response, &mut buffer) {
+            let _ = socket.send_to(&buffer[..length], peer).await;
+        }
+    }
+}
+
+async fn serve_tcp(listener: TcpListener, handler: MockHandler, queries: Arc<Mutex<Vec<(MockTransport, Question)>>>) {
+    let mut connections: Vec<AbortOnDrop> = Vec::new();
+    while let Ok((stream, _)) = listener.accept().await {
+        connections.retain(|connection| !connection.is_finished());
+        connections.push(AbortOnDrop(tokio::spawn(serve_connection(stream, handler.clone(), queries.clone()))));
+    }
+}
+
+/// Connections are closed along with the listener's task.
+struct AbortOnDrop(JoinHandle<()>);
+
+impl AbortOnDrop {
+    #[inline]
+    fn is_finished(&self) -> bool { self.0.is_finished() }
+}
+
+impl Drop for AbortOnDrop {
+    fn drop(&mut self) {
+        self.0.abort();
+    }
+}
+
+async fn serve_connection(mut stream: TcpStream, handler: MockHandler, queries: Arc<Mutex<Vec<(MockTransport, Question)>>>) {
+    let mut buffer = vec![0_u8; MAX_MESSAGE + 2];
+    loop {
+        let mut length = [0_u8; 2];
+        if stream.read_exact(&mut length).await.is_err() {
+            return;
+        }
+        let length = u16::from_be_bytes(length) as usize;
+        if stream.read_exact(&mut buffer[..length]).await.is_err() {
+            return;
+        }
+        let Some(query) = parse(&buffer[..length]) else { return };
+        record_query(&queries, MockTransport::Tcp, &query);
+        let Some(response) = handler(&query, MockTransport::Tcp) else { continue };
+        let Some(length) = serialize(&response, &mut buffer[2..]) else { return };
+        buffer[..2].copy_from_slice(&(length as u16).to_be_bytes());
+        if stream.write_all(&buffer[..length + 2]).await.is_err() {
+            return;
+        }
+    }
+}
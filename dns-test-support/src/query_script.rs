@@ -0,0 +1,575 @@
+use std::{fmt::Display, io, net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr}, sync::Arc, time::{Duration, Instant}};
+
+use dns_client::DNSAsyncClient;
+use dns_lib::{interface::client::{AsyncClient, Context, QNameMinimization, Response}, query::{message::Message, qr::QR, question::Question}, resource_record::{rclass::RClass, rcode::RCode, resource_record::{RecordData, ResourceRecord}, rtype::RType}, serde::{presentation::zone_file_reader::{ZoneFileReader, ZoneToken}, wire::{from_wire::FromWire, read_wire::ReadWire, to_wire::ToWire, write_wire::WriteWire}}, types::c_domain_name::CDomainName};
+use tokio::{io::{AsyncReadExt, AsyncWriteExt}, net::{TcpStream, UdpSocket}, time::timeout};
+
+const MAX_MESSAGE: usize = 65535;
+/// How long each query is given before its step is failed.
+const STEP_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// A header flag that a step can expect to be set or clear.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ScriptFlag {
+    /// Authoritative answer.
+    AA,
+    /// Truncation.
+    TC,
+    /// Recursion desired.
+    RD,
+    /// Recursion available.
+    RA,
+}
+
+impl ScriptFlag {
+    fn from_text(text: &str) -> Option<Self> {
+        match text.to_ascii_lowercase().as_str() {
+            "aa" => Some(Self::AA),
+            "tc" => Some(Self::TC),
+            "rd" => Some(Self::RD),
+            "ra" => Some(Self::RA),
+            _ => None,
+        }
+    }
+}
+
+impl Display for ScriptFlag {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::AA => write!(f, "aa"),
+            Self::TC => write!(f, "tc"),
+            Self::RD => write!(f, "rd"),
+            Self::RA => write!(f, "ra"),
+        }
+    }
+}
+
+/// A section of the response.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ScriptSection {
+    Answer,
+    Authority,
+    Additional,
+}
+
+impl ScriptSection {
+    fn from_text(text: &str) -> Option<Self> {
+        match text.to_ascii_lowercase().as_str() {
+            "answer" => Some(Self::Answer),
+            "authority" => Some(Self::Authority),
+            "additional" => Some(Self::Additional),
+            _ => None,
+        }
+    }
+}
+
+impl Display for ScriptSection {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Answer => write!(f, "answer"),
+            Self::Authority => write!(f, "authority"),
+            Self::Additional => write!(f, "additional"),
+        }
+    }
+}
+
+/// Something that must be true of a step's response for the step to pass.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expectation {
+    RCode(RCode),
+    Flag { flag: ScriptFlag, set: bool },
+    /// A record of the type is (or is not) in the section. If `rdata` is set, only a record with
+    /// that data counts. The data is kept along with the text it was read from.
+    Record { section: ScriptSection, rtype: RType, rdata: Option<(RecordData, String)>, present: bool },
+    Count { section: ScriptSection, count: usize },
+}
+
+/// A query and what its response must look like.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScriptStep {
+    /// The line of the script that the query is on.
+    pub line: usize,
+    pub question: Question,
+    /// Only used when the script is run against a server. A client always recurses.
+    pub recursion_desired: bool,
+    pub expectations: Vec<Expectation>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ScriptError {
+    pub line: usize,
+    pub reason: String,
+}
+
+impl std::error::Error for ScriptError {}
+impl Display for ScriptError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "line {}: {}", self.line, self.reason)
+    }
+}
+
+/// A sequence of queries and the outcomes expected for each, for regression testing zones and
+/// resolvers. Scripts are line based. Blank lines and lines starting with `#` are skipped.
+///
+/// ```text
+/// # Each query starts a step. The class defaults to IN. `+rd` asks a server to recurse.
+/// query www.example.com. A
+/// expect rcode NOERROR
+/// expect flag aa
+/// expect not flag tc
+/// # Any A record in the answer section, then one with specific data.
+/// expect answer A
+/// expect answer A 192.0.2.1
+/// expect not answer AAAA
+/// expect count additional 0
+///
+/// query missing.example.com. A IN +rd
+/// expect rcode NXDOMAIN
+/// expect authority SOA
+/// ```
+///
+/// Names must be fully qualified. Record data is written the way it is in a zone file.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct QueryScript {
+    pub steps: Vec<ScriptStep>,
+}
+
+impl QueryScript {
+    pub fn from_text(text: &str) -> Result<Self, ScriptError> {
+        let mut steps: Vec<ScriptStep> = Vec::new();
+        for (index, line) in text.lines().enumerate() {
+            let line_number = index + 1;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let error = |reason: String| ScriptError { line: line_number, reason };
+            let (keyword, arguments) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+            match keyword.to_ascii_lowercase().as_str() {
+                "query" => steps.push(parse_query(line_number, arguments).map_err(error)?),
+                "expect" => {
+                    let Some(step) = steps.last_mut() else {
+                        return Err(error(String::from("'expect' must follow a 'query'")));
+                    };
+                    let expectation = parse_expectation(&step.question, arguments).map_err(error)?;
+                    step.expectations.push(expectation);
+                },
+                _ => return Err(error(format!("unknown keyword '{keyword}'"))),
+            }
+        }
+        Ok(Self { steps })
+    }
+
+    /// Runs every step in order, whatever the outcome of the ones before it.
+    pub async fn run(&self, target: &ScriptTarget) -> ScriptReport {
+        let mut report = ScriptReport::default();
+        for (index, step) in self.steps.iter().enumerate() {
+            let started = Instant::now();
+            // The index is used as the ID so that responses to earlier steps are not mistaken for
+            // this one's.
+            let failures = match timeout(STEP_TIMEOUT, target.observe(step, index as u16)).await {
+                Ok(Ok(observation)) => step.expectations.iter()
+                    .filter_map(|expectation| observation.check(expectation).err())
+                    .collect(),
+                Ok(Err(error)) => vec![format!("no response: {error}")],
+                Err(_) => vec![format!("no response within {}s", STEP_TIMEOUT.as_secs())],
+            };
+            report.results.push(StepResult { line: step.line, question: step.question.clone(), failures, elapsed: started.elapsed() });
+        }
+        report
+    }
+}
+
+fn parse_query(line: usize, arguments: &str) -> Result<ScriptStep, String> {
+    let mut arguments = arguments.split_whitespace();
+    let qname = arguments.next().ok_or_else(|| String::from("missing the name to query"))?;
+    let qname = parse_name(qname)?;
+    let qtype = arguments.next().ok_or_else(|| String::from("missing the type to query"))?;
+    let qtype = parse_rtype(qtype)?;
+    let mut qclass = RClass::Internet;
+    let mut recursion_desired = false;
+    for argument in arguments {
+        match argument {
+            "+rd" => recursion_desired = true,
+            _ => qclass = RClass::from_str(&argument.to_ascii_uppercase()).map_err(|_| format!("unknown class '{argument}'"))?,
+        }
+    }
+    Ok(ScriptStep { line, question: Question::new(qname, qtype, qclass), recursion_desired, expectations: Vec::new() })
+}
+
+fn parse_expectation(question: &Question, arguments: &str) -> Result<Expectation, String> {
+    let (present, arguments) = match arguments.split_once(char::is_whitespace) {
+        Some((not, rest)) if not.eq_ignore_ascii_case("not") => (false, rest.trim()),
+        _ => (true, arguments),
+    };
+    let (kind, rest) = arguments.split_once(char::is_whitespace).unwrap_or((arguments, ""));
+    let rest = rest.trim();
+    if let Some(section) = ScriptSection::from_text(kind) {
+        let (rtype, rdata) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+        if rtype.is_empty() {
+            return Err(format!("missing the type of record expected in the {section} section"));
+        }
+        let rtype = parse_rtype(rtype)?;
+        let rdata = match rdata.trim() {
+            "" => None,
+            rdata => Some((parse_rdata(question, rtype, rdata)?, rdata.to_string())),
+        };
+        return Ok(Expectation::Record { section, rtype, rdata, present });
+    }
+    match (kind.to_ascii_lowercase().as_str(), present) {
+        ("rcode", true) => parse_rcode(rest).map(Expectation::RCode),
+        ("flag", set) => match ScriptFlag::from_text(rest) {
+            Some(flag) => Ok(Expectation::Flag { flag, set }),
+            None => Err(format!("unknown flag '{rest}'")),
+        },
+        ("count", true) => {
+            let (section, count) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+            let section = ScriptSection::from_text(section).ok_or_else(|| format!("unknown section '{section}'"))?;
+            let count = count.trim().parse().map_err(|_| format!("invalid count '{}'", count.trim()))?;
+            Ok(Expectation::Count { section, count })
+        },
+        ("rcode" | "count", false) => Err(format!("'not' cannot be used with '{kind}'")),
+        _ => Err(format!("unknown expectation '{kind}'")),
+    }
+}
+
+fn parse_name(text: &str) -> Result<CDomainName, String> {
+    let name = CDomainName::from_utf8(text).map_err(|error| format!("invalid name '{text}': {error}"))?;
+    if !name.is_fully_qualified() {
+        return Err(format!("the name '{text}' must be fully qualified"));
+    }
+    Ok(name)
+}
+
+#[inline]
+fn parse_rtype(text: &str) -> Result<RType, String> {
+    RType::from_str(&text.to_ascii_uppercase()).map_err(|_| format!("unknown type '{text}'"))
+}
+
+/// Response codes are matched without regard to case, so `NOERROR` and `NoError` are the same.
+fn parse_rcode(text: &str) -> Result<RCode, String> {
+    (0..=u16::from(u8::MAX))
+        .map(RCode::from_code)
+        .filter(|rcode| !matches!(rcode, RCode::Unknown(_)))
+        .find(|rcode| rcode.to_string().eq_ignore_ascii_case(text))
+        .ok_or_else(|| format!("unknown rcode '{text}'"))
+}
+
+/// Reads the record data as if it were on a line of a zone file, so that it is compared by value
+/// rather than by how it is written.
+fn parse_rdata(question: &Question, rtype: RType, text: &str) -> Result<RecordData, String> {
+    let line = format!("{} 0 {} {rtype} {text}\n", question.qname(), question.qclass());
+    let (tokens, diagnostics) = ZoneFileReader::new(&line).read_all();
+    if let Some(diagnostic) = diagnostics.first() {
+        return Err(format!("invalid {rtype} record data '{text}': {diagnostic}"));
+    }
+    match tokens.into_iter().next() {
+        Some(ZoneToken::ResourceRecord(record)) => Ok(record.get_rdata().clone()),
+        _ => Err(format!("invalid {rtype} record data '{text}'")),
+    }
+}
+
+/// What a script is run against.
+#[derive(Clone)]
+pub enum ScriptTarget {
+    /// Queries are resolved by the client. Only the AA flag is known, since the client does not
+    /// return the header of the message that it was answered with.
+    Client(Arc<DNSAsyncClient>),
+    /// Queries are sent to the name server at this address over UDP, and again over TCP if the
+    /// response is truncated. TC is only reported if the TCP response is truncated too.
+    Server(SocketAddr),
+}
+
+impl ScriptTarget {
+    async fn observe(&self, step: &ScriptStep, id: u16) -> io::Result<Observation> {
+        match self {
+            Self::Client(client) => {
+                let context = Context::new(step.question.clone(), QNameMinimization::None).with_timeout(STEP_TIMEOUT);
+                Ok(Observation::from_response(client.clone().query(context).await))
+            },
+            Self::Server(address) => {
+                let mut query = Message::from(&step.question);
+                query.id = id;
+                query.recursion_desired = step.recursion_desired;
+                let response = match exchange_udp(*address, &query).await? {
+                    response if response.truncation => exchange_tcp(*address, &query).await?,
+                    response => response,
+                };
+                Ok(Observation::from_message(response))
+            },
+        }
+    }
+}
+
+fn encode(query: &Message, buffer: &mut [u8], two_octet_length: bool) -> io::Result<usize> {
+    let mut wire = WriteWire::from_bytes(buffer);
+    let result = match two_octet_length {
+        true => query.to_wire_format_with_two_octet_length(&mut wire, &mut None),
+        false => query.to_wire_format(&mut wire, &mut None),
+    };
+    result.map_err(|error| io::Error::new(io::ErrorKind::InvalidInput, error.to_string()))?;
+    Ok(wire.current_len())
+}
+
+/// Whether the message answers the query. Anything else that arrives is ignored.
+fn is_response_to(message: &Message, query: &Message) -> bool {
+    (message.qr == QR::Response) && (message.id == query.id) && (message.question == query.question)
+}
+
+async fn exchange_udp(address: SocketAddr, query: &Message) -> io::Result<Message> {
+    let local_address = match address {
+        SocketAddr::V4(_) => SocketAddr::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED), 0),
+        SocketAddr::V6(_) => SocketAddr::new(IpAddr::V6(Ipv6Addr::UNSPECIFIED), 0),
+    };
+    let socket = UdpSocket::bind(local_address).await?;
+    socket.connect(address).await?;
+    let mut buffer = vec![0_u8; MAX_MESSAGE];
+    let length = encode(query, &mut buffer, false)?;
+    socket.send(&buffer[..length]).await?;
+    loop {
+        let length = socket.recv(&mut buffer).await?;
+        match Message::from_wire_format(&mut ReadWire::from_bytes(&buffer[..length])) {
+            Ok(response) if is_response_to(&response, query) => return Ok(response),
+            _ => continue,
+        }
+    }
+}
+
+async fn exchange_tcp(address: SocketAddr, query: &Message) -> io::Result<Message> {
+    let mut stream = TcpStream::connect(address).await?;
+    let mut buffer = vec![0_u8; MAX_MESSAGE + 2];
+    let length = encode(query, &mut buffer, true)?;
+    stream.write_all(&buffer[..length]).await?;
+    loop {
+        let length = usize::from(stream.read_u16().await?);
+        stream.read_exact(&mut buffer[..length]).await?;
+        match Message::from_wire_format(&mut ReadWire::from_bytes(&buffer[..length])) {
+            Ok(response) if is_response_to(&response, query) => return Ok(response),
+            _ => continue,
+        }
+    }
+}
+
+/// The parts of a response that expectations are checked against.
+#[derive(Debug)]
+struct Observation {
+    rcode: RCode,
+    answer: Vec<ResourceRecord>,
+    authority: Vec<ResourceRecord>,
+    additional: Vec<ResourceRecord>,
+    authoritative: bool,
+    /// The TC, RD, and RA flags. Only known if the response came straight from a name server.
+    header: Option<(bool, bool, bool)>,
+}
+
+impl Observation {
+    fn from_message(message: Message) -> Self {
+        Self {
+            rcode: message.rcode,
+            authoritative: message.authoritative_answer,
+            header: Some((message.truncation, message.recursion_desired, message.recursion_available)),
+            answer: message.answer,
+            authority: message.authority,
+            additional: message.additional,
+        }
+    }
+
+    /// The client's answer, with the SOA record of a negative answer in the authority section
+    /// where a name server would have put it.
+    fn from_response(response: Response) -> Self {
+        let rcode = response.rcode();
+        match response {
+            Response::Answer(answer) => {
+                let mut authority = answer.name_servers.into_iter().map(ResourceRecord::from).collect::<Vec<_>>();
+                authority.extend(answer.negative.and_then(|negative| negative.soa).map(ResourceRecord::from));
+                Self { rcode, answer: answer.answer, authority, additional: answer.additional, authoritative: answer.authoritative, header: None }
+            },
+            Response::Error(error) => {
+                let authority = error.negative.and_then(|negative| negative.soa).map(ResourceRecord::from).into_iter().collect();
+                Self { rcode, answer: Vec::new(), authority, additional: Vec::new(), authoritative: false, header: None }
+            },
+        }
+    }
+
+    #[inline]
+    fn section(&self, section: ScriptSection) -> &[ResourceRecord] {
+        match section {
+            ScriptSection::Answer => &self.answer,
+            ScriptSection::Authority => &self.authority,
+            ScriptSection::Additional => &self.additional,
+        }
+    }
+
+    fn flag(&self, flag: ScriptFlag) -> Option<bool> {
+        match flag {
+            ScriptFlag::AA => Some(self.authoritative),
+            ScriptFlag::TC => self.header.map(|(truncation, _, _)| truncation),
+            ScriptFlag::RD => self.header.map(|(_, recursion_desired, _)| recursion_desired),
+            ScriptFlag::RA => self.header.map(|(_, _, recursion_available)| recursion_available),
+        }
+    }
+
+    /// Explains why the expectation was not met.
+    fn check(&self, expectation: &Expectation) -> Result<(), String> {
+        match expectation {
+            Expectation::RCode(rcode) if self.rcode == *rcode => Ok(()),
+            Expectation::RCode(rcode) => Err(format!("expected rcode {rcode} but got {}", self.rcode)),
+            Expectation::Flag { flag, set } => match self.flag(*flag) {
+                Some(actual) if actual == *set => Ok(()),
+                Some(_) if *set => Err(format!("expected the {flag} flag to be set")),
+                Some(_) => Err(format!("expected the {flag} flag to be clear")),
+                None => Err(format!("the {flag} flag is not known for this target")),
+            },
+            Expectation::Record { section, rtype, rdata, present } => {
+                let found = self.section(*section).iter()
+                    .filter(|record| record.get_rtype() == *rtype)
+                    .any(|record| rdata.as_ref().is_none_or(|(rdata, _)| record.get_rdata() == rdata));
+                match (found, present, rdata) {
+                    (true, true, _) | (false, false, _) => Ok(()),
+                    (false, true, None) => Err(format!("expected a {rtype} record in the {section} section")),
+                    (false, true, Some((_, rdata))) => Err(format!("expected a {rtype} record with '{rdata}' in the {section} section")),
+                    (true, false, None) => Err(format!("expected no {rtype} records in the {section} section")),
+                    (true, false, Some((_, rdata))) => Err(format!("expected no {rtype} record with '{rdata}' in the {section} section")),
+                }
+            },
+            Expectation::Count { section, count } => match self.section(*section).len() {
+                actual if actual == *count => Ok(()),
+                actual => Err(format!("expected {count} records in the {section} section but got {actual}")),
+            },
+        }
+    }
+}
+
+/// The outcome of a single step. It passed if there are no failures.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct StepResult {
+    pub line: usize,
+    pub question: Question,
+    pub failures: Vec<String>,
+    pub elapsed: Duration,
+}
+
+impl StepResult {
+    #[inline]
+    pub fn passed(&self) -> bool { self.failures.is_empty() }
+}
+
+impl Display for StepResult {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let outcome = if self.passed() { "PASS" } else { "FAIL" };
+        write!(f, "{outcome} line {} {} ({}ms)", self.line, self.question, self.elapsed.as_millis())?;
+        for failure in &self.failures {
+            write!(f, "\n    {failure}")?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Default)]
+pub struct ScriptReport {
+    pub results: Vec<StepResult>,
+}
+
+impl ScriptReport {
+    /// True if every step passed.
+    #[inline]
+    pub fn passed(&self) -> bool {
+        self.results.iter().all(StepResult::passed)
+    }
+
+    #[inline]
+    pub fn failures(&self) -> impl Iterator<Item = &StepResult> {
+        self.results.iter().filter(|result| !result.passed())
+    }
+}
+
+impl Display for ScriptReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for result in &self.results {
+            writeln!(f, "{result}")?;
+        }
+        let passed = self.results.iter().filter(|result| result.passed()).count();
+        write!(f, "{passed}/{} steps passed", self.results.len())
+    }
+}
+
+#[cfg(test)]
+mod query_script_tests {
+    use std::{net::{IpAddr, Ipv4Addr, SocketAddr}, sync::Arc};
+
+    use dns_lib::{query::message::Message, resource_record::{rclass::RClass, rcode::RCode, resource_record::{RecordData, ResourceRecord}, time::Time, types::a::A}, types::c_domain_name::{CDomainName, CmpDomainName}};
+
+    use crate::mock_server::{response_to, MockServer, MockTransport};
+
+    use super::{QueryScript, ScriptTarget};
+
+    const SCRIPT: &str = "\
+# A name that exists.
+query www.example.com. A
+expect rcode NOERROR
+expect flag aa
+expect not flag tc
+expect answer A 192.0.2.1
+expect not answer A 192.0.2.2
+expect count answer 1
+
+query missing.example.com. a in
+expect rcode nxdomain
+expect count answer 0
+";
+
+    async fn server() -> MockServer {
+        MockServer::bind(SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 0), Arc::new(|query: &Message, _: MockTransport| {
+            let qname = query.question.first()?.qname().clone();
+            if !qname.matches(&CDomainName::from_utf8("www.example.com.").unwrap()) {
+                return Some(response_to(query, RCode::NXDomain));
+            }
+            let mut response = response_to(query, RCode::NoError);
+            response.answer.push(ResourceRecord::new(qname, RClass::Internet, Time::from_secs(300), RecordData::A(A::new(Ipv4Addr::new(192, 0, 2, 1)))));
+            Some(response)
+        })).await.unwrap()
+    }
+
+    #[test]
+    fn scripts_are_parsed_into_steps() {
+        let script = QueryScript::from_text(SCRIPT).unwrap();
+        assert_eq!(script.steps.len(), 2);
+        assert_eq!(script.steps[0].line, 2);
+        assert_eq!(script.steps[0].expectations.len(), 6);
+        assert_eq!(script.steps[1].line, 10);
+        assert_eq!(script.steps[1].expectations.len(), 2);
+    }
+
+    #[test]
+    fn errors_report_their_line() {
+        let error = QueryScript::from_text("expect rcode NOERROR\n").unwrap_err();
+        assert_eq!(error.line, 1);
+        let error = QueryScript::from_text("query www.example.com. A\n\nexpect rcode NOPE\n").unwrap_err();
+        assert_eq!(error.line, 3);
+        let error = QueryScript::from_text("query www.example.com A\n").unwrap_err();
+        assert_eq!(error.line, 1);
+        let error = QueryScript::from_text("query www.example.com. A\nexpect answer A not-an-address\n").unwrap_err();
+        assert_eq!(error.line, 2);
+    }
+
+    #[tokio::test]
+    async fn steps_pass_when_expectations_are_met() {
+        let server = server().await;
+        let report = QueryScript::from_text(SCRIPT).unwrap().run(&ScriptTarget::Server(server.local_addr())).await;
+        assert!(report.passed(), "{report}");
+    }
+
+    #[tokio::test]
+    async fn every_unmet_expectation_is_reported() {
+        let server = server().await;
+        let script = QueryScript::from_text("\
+query www.example.com. A
+expect rcode REFUSED
+expect not flag aa
+expect answer AAAA
+").unwrap();
+        let report = script.run(&ScriptTarget::Server(server.local_addr())).await;
+        assert!(!report.passed());
+        assert_eq!(report.failures().next().unwrap().failures.len(), 3, "{report}");
+    }
+}
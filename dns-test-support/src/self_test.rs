@@ -0,0 +1,345 @@
+use std::{fmt::Display, io, net::{IpAddr, Ipv4Addr, SocketAddr}, sync::Arc, time::{Duration, Instant}};
+
+use dns_cache::asynchronous::async_main_cache::AsyncMainTreeCache;
+use dns_client::{ClientConfig, DNSAsyncClient};
+use dns_lib::{interface::{cache::{main_cache::AsyncMainCache, CacheMeta, CacheRecord, MetaAuth}, client::{Answer, AsyncClient, Context, ErrorResponse, QNameMinimization, Response}}, query::{message::Message, question::Question}, resource_record::{rclass::RClass, rcode::RCode, resource_record::{RecordData, ResourceRecord}, rtype::RType, time::Time, types::{a::A, cname::CNAME, ns::NS, soa::SOA}}, types::c_domain_name::{CDomainName, CmpDomainName}};
+
+use crate::mock_server::{response_to, MockHandler, MockServer, MockTransport};
+
+/// The zone served by the mock name server. Each check queries a different name in it.
+const ZONE: &str = "selftest.";
+const NAME_SERVER: &str = "ns.selftest.";
+const TRUNCATED: &str = "tc.selftest.";
+const MIXED_CASE: &str = "case.selftest.";
+const MINIMIZED: &str = "a.b.c.d.min.selftest.";
+const NXDOMAIN: &str = "nx.selftest.";
+const LOOP_START: &str = "loop1.selftest.";
+const LOOP_END: &str = "loop2.selftest.";
+const NO_EDNS: &str = "noedns.selftest.";
+
+const TRUNCATED_ADDRESS: Ipv4Addr = Ipv4Addr::new(192, 0, 2, 1);
+const MIXED_CASE_ADDRESS: Ipv4Addr = Ipv4Addr::new(192, 0, 2, 2);
+const MINIMIZED_ADDRESS: Ipv4Addr = Ipv4Addr::new(192, 0, 2, 3);
+const NO_EDNS_ADDRESS: Ipv4Addr = Ipv4Addr::new(192, 0, 2, 4);
+
+const TTL: Time = Time::from_secs(300);
+const NEGATIVE_TTL: u32 = 60;
+/// The number of labels that may be minimized in the QNAME minimization check.
+const MINIMIZATION_LIMIT: usize = 2;
+/// A CNAME loop is given up on long before this many queries.
+const MAX_LOOP_QUERIES: usize = 10;
+/// How long each check is given before it is failed.
+const CHECK_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// The behaviours that the self-test checks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SelfTestCheck {
+    /// A truncated UDP response is retried over TCP.
+    Truncation,
+    /// A response that echoes the question with different capitalization is accepted, which mixed
+    /// case (0x20) queries rely on.
+    QNameCase,
+    /// Minimized queries stay within the context's limit and only ever ask for ancestors of the
+    /// qname.
+    QNameMinimization,
+    /// The SOA record from an NXDOMAIN response is cached and reported along with the negative
+    /// TTL it allows.
+    NegativeCaching,
+    /// A CNAME chain that loops back on itself fails instead of being followed forever.
+    CNameLoop,
+    /// A name server that answers queries with EDNS with FORMERR is queried again without it.
+    EdnsFallback,
+}
+
+impl SelfTestCheck {
+    pub const ALL: [Self; 6] = [
+        Self::Truncation,
+        Self::QNameCase,
+        Self::QNameMinimization,
+        Self::NegativeCaching,
+        Self::CNameLoop,
+        Self::EdnsFallback,
+    ];
+
+    #[inline]
+    pub const fn name(&self) -> &'static str {
+        match self {
+            Self::Truncation        => "truncation",
+            Self::QNameCase         => "qname-case",
+            Self::QNameMinimization => "qname-minimization",
+            Self::NegativeCaching   => "negative-caching",
+            Self::CNameLoop         => "cname-loop",
+            Self::EdnsFallback      => "edns-fallback",
+        }
+    }
+}
+
+impl Display for SelfTestCheck {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+/// The outcome of a single check. `detail` explains why it failed, or what was observed if it
+/// passed.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct CheckResult {
+    pub check: SelfTestCheck,
+    pub passed: bool,
+    pub detail: String,
+    pub elapsed: Duration,
+}
+
+impl Display for CheckResult {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let outcome = if self.passed { "PASS" } else { "FAIL" };
+        write!(f, "{outcome} {} ({}ms): {}", self.check, self.elapsed.as_millis(), self.detail)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Default)]
+pub struct SelfTestReport {
+    pub results: Vec<CheckResult>,
+}
+
+impl SelfTestReport {
+    /// True if every check passed.
+    #[inline]
+    pub fn passed(&self) -> bool {
+        self.results.iter().all(|result| result.passed)
+    }
+
+    #[inline]
+    pub fn failures(&self) -> impl Iterator<Item = &CheckResult> {
+        self.results.iter().filter(|result| !result.passed)
+    }
+}
+
+impl Display for SelfTestReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for result in &self.results {
+            writeln!(f, "{result}")?;
+        }
+        let passed = self.results.iter().filter(|result| result.passed).count();
+        write!(f, "{passed}/{} checks passed", self.results.len())
+    }
+}
+
+/// Runs every check against the resolver's own pipeline. Each check gets a fresh client and cache
+/// and a mock name server on the loopback address that is authoritative for `selftest.`, so the
+/// checks do not affect each other and never touch the network.
+///
+/// An error is only returned if a mock name server could not be started.
+pub async fn run_self_test() -> io::Result<SelfTestReport> {
+    let mut report = SelfTestReport::default();
+    for check in SelfTestCheck::ALL {
+        report.results.push(run_check(check).await?);
+    }
+    Ok(report)
+}
+
+/// Runs a single check. See `run_self_test()`.
+pub async fn run_check(check: SelfTestCheck) -> io::Result<CheckResult> {
+    let server = MockServer::bind(SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 0), zone_handler()).await?;
+    let client = Arc::new(client_for(&server).await);
+    let started = Instant::now();
+    let outcome = tokio::time::timeout(CHECK_TIMEOUT, async {
+        match check {
+            SelfTestCheck::Truncation => check_truncation(&client, &server).await,
+            SelfTestCheck::QNameCase => check_qname_case(&client).await,
+            SelfTestCheck::QNameMinimization => check_qname_minimization(&client, &server).await,
+            SelfTestCheck::NegativeCaching => check_negative_caching(&client).await,
+            SelfTestCheck::CNameLoop => check_cname_loop(&client, &server).await,
+            SelfTestCheck::EdnsFallback => check_edns_fallback(&client, &server).await,
+        }
+    }).await;
+    let elapsed = started.elapsed();
+    client.close().await;
+    let (passed, detail) = match outcome {
+        Ok(Ok(detail)) => (true, detail),
+        Ok(Err(detail)) => (false, detail),
+        Err(_) => (false, format!("did not finish within {}s", CHECK_TIMEOUT.as_secs())),
+    };
+    Ok(CheckResult { check, passed, detail, elapsed })
+}
+
+#[inline]
+fn name(name: &str) -> CDomainName {
+    CDomainName::from_utf8(name).expect("self-test names are valid domain names")
+}
+
+/// A client whose cache delegates `selftest.` to the mock server and whose queries are sent to the
+/// mock server's port.
+async fn client_for(server: &MockServer) -> DNSAsyncClient {
+    let cache = AsyncMainTreeCache::new();
+    let meta = CacheMeta { auth: MetaAuth::NotAuthoritative, insertion_time: Instant::now(), original_ttl: None };
+    let delegation = [
+        ResourceRecord::new(name(ZONE), RClass::Internet, TTL, RecordData::NS(NS::new(name(NAME_SERVER)))),
+        ResourceRecord::new(name(NAME_SERVER), RClass::Internet, TTL, RecordData::A(A::new(Ipv4Addr::LOCALHOST))),
+    ];
+    cache.insert_records(delegation.into_iter().map(|record| CacheRecord { meta: meta.clone(), record }).collect()).await;
+
+    let config = ClientConfig {
+        upstream_port: server.local_addr().port(),
+        ..ClientConfig::default()
+    };
+    DNSAsyncClient::with_config(Arc::new(cache), config).await
+}
+
+async fn query(client: &Arc<DNSAsyncClient>, qname: &str, minimization: QNameMinimization) -> Response {
+    let question = Question::new(name(qname), RType::A, RClass::Internet);
+    client.clone().query(Context::new(question, minimization).with_timeout(CHECK_TIMEOUT)).await
+}
+
+fn expect_address(response: &Response, address: Ipv4Addr) -> Result<(), String> {
+    match response {
+        Response::Answer(Answer { answer, .. }) if answer.iter().any(|record| record.get_rdata() == &RecordData::A(A::new(address))) => Ok(()),
+        Response::Answer(Answer { answer, .. }) => Err(format!("expected the address {address} but got {} records", answer.len())),
+        Response::Error(error) => Err(format!("expected the address {address} but got {error}")),
+    }
+}
+
+async fn check_truncation(client: &Arc<DNSAsyncClient>, server: &MockServer) -> Result<String, String> {
+    let response = query(client, TRUNCATED, QNameMinimization::None).await;
+    expect_address(&response, TRUNCATED_ADDRESS)?;
+    let tcp_queries = server.queries().into_iter()
+        .filter(|(transport, question)| (*transport == MockTransport::Tcp) && question.qname().matches(&name(TRUNCATED)))
+        .count();
+    match tcp_queries {
+        0 => Err(String::from("answered without retrying over TCP")),
+        _ => Ok(String::from("truncated UDP response was retried over TCP")),
+    }
+}
+
+async fn check_qname_case(client: &Arc<DNSAsyncClient>) -> Result<String, String> {
+    let response = query(client, MIXED_CASE, QNameMinimization::None).await;
+    expect_address(&response, MIXED_CASE_ADDRESS)?;
+    match client.validation_stats().question_mismatch {
+        0 => Ok(String::from("response with a recapitalized question was accepted")),
+        rejected => Err(format!("{rejected} responses were rejected for their question")),
+    }
+}
+
+async fn check_qname_minimization(client: &Arc<DNSAsyncClient>, server: &MockServer) -> Result<String, String> {
+    let minimization = QNameMinimization::PrimaryQuery { primary_minimization_limit: MINIMIZATION_LIMIT };
+    let response = query(client, MINIMIZED, minimization).await;
+    expect_address(&response, MINIMIZED_ADDRESS)?;
+    let qname = name(MINIMIZED);
+    let queries = server.queries();
+    if let Some((_, question)) = queries.iter().find(|(_, question)| !qname.is_child_domain_of(question.qname())) {
+        return Err(format!("queried '{}', which is not an ancestor of the qname", question.qname()));
+    }
+    let minimized = queries.iter().filter(|(_, question)| !question.qname().matches(&qname)).count();
+    match minimized {
+        0 => Err(String::from("the full qname was sent to the zone's name server without minimizing it")),
+        minimized if minimized > MINIMIZATION_LIMIT => Err(format!("sent {minimized} minimized queries but the limit is {MINIMIZATION_LIMIT}")),
+        minimized => Ok(format!("sent {minimized} minimized queries with a limit of {MINIMIZATION_LIMIT}")),
+    }
+}
+
+async fn check_negative_caching(client: &Arc<DNSAsyncClient>) -> Result<String, String> {
+    match query(client, NXDOMAIN, QNameMinimization::None).await {
+        Response::Error(ErrorResponse { rcode: RCode::NXDomain, negative: Some(negative) }) => {
+            match (negative.zone(), negative.negative_ttl()) {
+                (Some(zone), Some(negative_ttl)) if zone.matches(&name(ZONE)) && (negative_ttl.as_secs() == NEGATIVE_TTL) => Ok(format!("NXDOMAIN from {negative}")),
+                _ => Err(format!("expected the SOA of '{ZONE}' with a negative TTL of {NEGATIVE_TTL}s but got {negative}")),
+            }
+        },
+        Response::Error(error) => Err(format!("expected NXDOMAIN with the zone's SOA but got {error}")),
+        Response::Answer(_) => Err(String::from("expected NXDOMAIN but got an answer")),
+    }
+}
+
+async fn check_cname_loop(client: &Arc<DNSAsyncClient>, server: &MockServer) -> Result<String, String> {
+    let response = query(client, LOOP_START, QNameMinimization::None).await;
+    let queries = server.queries().len();
+    if queries > MAX_LOOP_QUERIES {
+        return Err(format!("sent {queries} queries following the loop"));
+    }
+    match response {
+        Response::Error(error) => Ok(format!("gave up with {error} after {queries} queries")),
+        Response::Answer(_) => Err(String::from("answered a query whose CNAME chain loops")),
+    }
+}
+
+async fn check_edns_fallback(client: &Arc<DNSAsyncClient>, server: &MockServer) -> Result<String, String> {
+    let response = query(client, NO_EDNS, QNameMinimization::None).await;
+    expect_address(&response, NO_EDNS_ADDRESS)?;
+    if client.edns_supported(&server.local_addr().ip()).await {
+        return Err(String::from("the name server is still sent queries with EDNS"));
+    }
+    let queries = server.queries().into_iter()
+        .filter(|(_, question)| question.qname().matches(&name(NO_EDNS)))
+        .count();
+    Ok(format!("answered after {queries} queries and stopped sending EDNS to the name server"))
+}
+
+/// Answers for `selftest.` the way an authoritative server would, except for the names that each
+/// check needs to behave differently.
+fn zone_handler() -> MockHandler {
+    Arc::new(|query: &Message, transport: MockTransport| {
+        let question = query.question.first()?.clone();
+        let qname = question.qname();
+        let record = |owner: &CDomainName, rdata: RecordData| ResourceRecord::new(owner.clone(), RClass::Internet, TTL, rdata);
+        let soa = || record(&name(ZONE), RecordData::SOA(SOA::new(name(NAME_SERVER), name("hostmaster.selftest."), 1, TTL, TTL, TTL, NEGATIVE_TTL)));
+
+        if qname.matches(&name(TRUNCATED)) && (transport == MockTransport::Udp) {
+            let mut response = response_to(query, RCode::NoError);
+            response.truncation = true;
+            return Some(response);
+        }
+        // This name server does not implement EDNS.
+        // https://datatracker.ietf.org/doc/html/rfc6891#section-7
+        if qname.matches(&name(NO_EDNS)) && query.edns().is_some() {
+            return Some(response_to(query, RCode::FormErr));
+        }
+        if qname.matches(&name(MIXED_CASE)) {
+            let mut response = response_to(query, RCode::NoError);
+            let upper_qname = name(&qname.to_string().to_uppercase());
+            response.question[0] = question.with_new_qname_qtype(upper_qname.clone(), question.qtype());
+            response.answer.push(record(&upper_qname, RecordData::A(A::new(MIXED_CASE_ADDRESS))));
+            return Some(response);
+        }
+
+        let mut response = response_to(query, RCode::NoError);
+        let answer = match () {
+            _ if !qname.is_child_domain_of(&name(ZONE)) => {
+                response.rcode = RCode::Refused;
+                response.authoritative_answer = false;
+                return Some(response);
+            },
+            _ if qname.matches(&name(TRUNCATED)) => RecordData::A(A::new(TRUNCATED_ADDRESS)),
+            _ if qname.matches(&name(MINIMIZED)) => RecordData::A(A::new(MINIMIZED_ADDRESS)),
+            _ if qname.matches(&name(NO_EDNS)) => RecordData::A(A::new(NO_EDNS_ADDRESS)),
+            _ if qname.matches(&name(NAME_SERVER)) => RecordData::A(A::new(Ipv4Addr::LOCALHOST)),
+            _ if qname.matches(&name(LOOP_START)) => RecordData::CNAME(CNAME::new(name(LOOP_END))),
+            _ if qname.matches(&name(LOOP_END)) => RecordData::CNAME(CNAME::new(name(LOOP_START))),
+            // The ancestors of the minimized name are empty non-terminals.
+            _ if name(MINIMIZED).is_child_domain_of(qname) => {
+                response.authority.push(soa());
+                return Some(response);
+            },
+            _ => {
+                response.rcode = RCode::NXDomain;
+                response.authority.push(soa());
+                return Some(response);
+            },
+        };
+        match answer {
+            RecordData::A(_) if question.qtype() != RType::A => response.authority.push(soa()),
+            answer => response.answer.push(record(qname, answer)),
+        }
+        Some(response)
+    })
+}
+
+#[cfg(test)]
+mod self_test_tests {
+    use super::run_self_test;
+
+    #[tokio::test]
+    async fn resolver_passes_self_test() {
+        let report = run_self_test().await.unwrap();
+        assert!(report.passed(), "{report}");
+    }
+}
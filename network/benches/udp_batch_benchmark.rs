@@ -0,0 +1,85 @@
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use network::udp_batch::{BatchUdpSocket, RecvBatch, DEFAULT_SLOT_SIZE, MAX_BATCH_SIZE};
+use tokio::{net::UdpSocket, runtime::Runtime};
+
+const LOCALHOST: SocketAddr = SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 0);
+/// About the size of a typical query.
+const DATAGRAM: [u8; 40] = [0xAB; 40];
+const DATAGRAMS_PER_ITERATION: usize = 256;
+
+struct Sockets {
+    sender: BatchUdpSocket,
+    receiver: BatchUdpSocket,
+    receiver_address: SocketAddr,
+}
+
+impl Sockets {
+    async fn bind() -> Self {
+        let sender = BatchUdpSocket::new(UdpSocket::bind(LOCALHOST).await.unwrap());
+        let receiver = BatchUdpSocket::new(UdpSocket::bind(LOCALHOST).await.unwrap());
+        let receiver_address = receiver.get_ref().local_addr().unwrap();
+        Self { sender, receiver, receiver_address }
+    }
+
+    /// Sends and receives every datagram one system call at a time. Returns the number of system
+    /// calls made.
+    async fn round_trip_individually(&self) -> usize {
+        let mut buffer = [0_u8; DEFAULT_SLOT_SIZE];
+        let mut system_calls = 0;
+        for chunk in (0..DATAGRAMS_PER_ITERATION).collect::<Vec<_>>().chunks(MAX_BATCH_SIZE) {
+            for _ in chunk {
+                self.sender.get_ref().send_to(&DATAGRAM, self.receiver_address).await.unwrap();
+                system_calls += 1;
+            }
+            for _ in chunk {
+                self.receiver.get_ref().recv_from(&mut buffer).await.unwrap();
+                system_calls += 1;
+            }
+        }
+        system_calls
+    }
+
+    /// Sends and receives every datagram using `sendmmsg` and `recvmmsg`. Returns the number of
+    /// system calls made.
+    async fn round_trip_batched(&self, batch: &mut RecvBatch) -> usize {
+        let datagrams = vec![(DATAGRAM.as_slice(), self.receiver_address); MAX_BATCH_SIZE];
+        let mut system_calls = 0;
+        for chunk in (0..DATAGRAMS_PER_ITERATION).collect::<Vec<_>>().chunks(MAX_BATCH_SIZE) {
+            self.sender.send_batch(&datagrams[..chunk.len()]).await.unwrap();
+            system_calls += 1;
+            let mut received = 0;
+            while received < chunk.len() {
+                self.receiver.recv_batch(batch).await.unwrap();
+                received += batch.iter().count();
+                system_calls += 1;
+            }
+        }
+        system_calls
+    }
+}
+
+fn udp_batch_benchmark(c: &mut Criterion) {
+    let runtime = Runtime::new().unwrap();
+    let sockets = runtime.block_on(Sockets::bind());
+    let mut batch = RecvBatch::new(MAX_BATCH_SIZE, DEFAULT_SLOT_SIZE);
+
+    // Criterion only reports time, so report the system call reduction up front.
+    let individual_calls = runtime.block_on(sockets.round_trip_individually());
+    let batched_calls = runtime.block_on(sockets.round_trip_batched(&mut batch));
+    println!("{DATAGRAMS_PER_ITERATION} datagrams: {individual_calls} system calls individually, {batched_calls} system calls batched");
+
+    let mut benchmark_group = c.benchmark_group("UDP Round Trip");
+    benchmark_group.throughput(Throughput::Elements(DATAGRAMS_PER_ITERATION as u64));
+    benchmark_group.bench_function(BenchmarkId::from_parameter("send_to + recv_from"), |b|
+        b.to_async(&runtime).iter(|| sockets.round_trip_individually())
+    );
+    benchmark_group.bench_function(BenchmarkId::from_parameter("sendmmsg + recvmmsg"), |b|
+        b.iter(|| runtime.block_on(sockets.round_trip_batched(&mut batch)))
+    );
+    benchmark_group.finish();
+}
+
+criterion_group!(benches, udp_batch_benchmark);
+criterion_main!(benches);
@@ -1,7 +1,7 @@
 use std::fmt::Display;
 
 use async_lib::once_watch;
-use dns_lib::query::message::Message;
+use dns_lib::query::{message::Message, padding::PaddingPolicy};
 use futures::{future::BoxFuture, FutureExt};
 use pin_project::pin_project;
 use tokio::sync::{RwLock, RwLockReadGuard, RwLockWriteGuard};
@@ -9,16 +9,57 @@ use tokio::sync::{RwLock, RwLockReadGuard, RwLockWriteGuard};
 use crate::errors;
 
 
+/// The transport used to send a query. The encrypted transports are only available if the
+/// matching cargo feature is enabled.
 #[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
 pub enum QueryOpt {
     UdpTcp,
     Tcp,
+    #[cfg(feature = "quic")]
     Quic,
+    #[cfg(feature = "tls")]
     Tls,
+    /// DNS over TLS without verifying the server's certificate. Only meant for lab environments.
+    #[cfg(feature = "tls")]
+    TlsInsecure,
+    #[cfg(feature = "quic")]
     QuicTls,
+    #[cfg(feature = "https")]
     Https,
 }
 
+impl QueryOpt {
+    /// Whether queries sent with this option are protected from on-path observers.
+    #[inline]
+    pub const fn is_encrypted(&self) -> bool {
+        match self {
+            Self::UdpTcp => false,
+            Self::Tcp => false,
+            #[cfg(feature = "quic")]
+            Self::Quic => true,
+            #[cfg(feature = "tls")]
+            Self::Tls => true,
+            #[cfg(feature = "tls")]
+            Self::TlsInsecure => true,
+            #[cfg(feature = "quic")]
+            Self::QuicTls => true,
+            #[cfg(feature = "https")]
+            Self::Https => true,
+        }
+    }
+
+    /// Queries sent over encrypted transports are padded to hide their size. Queries sent in the
+    /// clear are not padded.
+    #[inline]
+    pub const fn padding_policy(&self) -> PaddingPolicy {
+        if self.is_encrypted() {
+            PaddingPolicy::RECOMMENDED_QUERY
+        } else {
+            PaddingPolicy::None
+        }
+    }
+}
+
 #[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
 pub(crate) enum QSendType {
     Initial,
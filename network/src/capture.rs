@@ -0,0 +1,282 @@
+use std::{collections::VecDeque, fmt::{Display, Write}, net::SocketAddr, sync::{atomic::{AtomicBool, Ordering}, Mutex}, time::{Duration, Instant, SystemTime, UNIX_EPOCH}};
+
+use lazy_static::lazy_static;
+
+lazy_static! {
+    /// The malformed packets captured by every socket. Parsing happens in several places (and for
+    /// sockets that are not owned by a manager), so the captures for all of them are kept in one
+    /// place.
+    static ref MALFORMED_CAPTURE: Mutex<MalformedCapture> = Mutex::new(MalformedCapture::new(CaptureConfig::default()));
+}
+
+/// Set while malformed packets are being captured so that the receive path can skip the lock when
+/// capturing is not in use, which should be almost always.
+static CAPTURE_ACTIVE: AtomicBool = AtomicBool::new(false);
+
+/// Bytes shown on each line of a hexdump.
+const HEXDUMP_WIDTH: usize = 16;
+
+/// Limits on how much is kept when capturing malformed packets. Packets are only captured while
+/// capturing is enabled, and the limits keep a flood of garbage from using up memory.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct CaptureConfig {
+    /// The number of packets kept. Once full, the oldest packet is dropped to make room for each
+    /// new one.
+    pub max_packets: usize,
+    /// Packets longer than this are truncated. The full length is still recorded.
+    pub max_bytes: usize,
+    /// The number of packets captured in any one second. Packets beyond this are counted but not
+    /// kept.
+    pub per_second: u32,
+}
+
+impl Default for CaptureConfig {
+    #[inline]
+    fn default() -> Self {
+        Self { max_packets: 64, max_bytes: 512, per_second: 10 }
+    }
+}
+
+/// A packet that could not be parsed.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct MalformedPacket {
+    pub time: SystemTime,
+    /// For example, "UDP" or "TCP".
+    pub protocol: &'static str,
+    pub peer: Option<SocketAddr>,
+    /// Why the packet could not be parsed.
+    pub error: String,
+    /// The length of the packet as it was received.
+    pub length: usize,
+    /// The start of the packet, up to `CaptureConfig::max_bytes`.
+    pub bytes: Vec<u8>,
+}
+
+impl MalformedPacket {
+    #[inline]
+    pub fn is_truncated(&self) -> bool {
+        self.bytes.len() < self.length
+    }
+
+    #[inline]
+    pub fn hexdump(&self) -> String {
+        hexdump(&self.bytes)
+    }
+}
+
+impl Display for MalformedPacket {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let time = self.time.duration_since(UNIX_EPOCH).unwrap_or_default();
+        write!(f, "{}.{:03} {} ", time.as_secs(), time.subsec_millis(), self.protocol)?;
+        match self.peer {
+            Some(peer) => write!(f, "from {peer}")?,
+            None => write!(f, "from unknown peer")?,
+        }
+        write!(f, " ({} bytes", self.length)?;
+        if self.is_truncated() {
+            write!(f, ", first {} shown", self.bytes.len())?;
+        }
+        writeln!(f, "): {}", self.error)?;
+        write!(f, "{}", self.hexdump())
+    }
+}
+
+/// Counts of the malformed packets seen while capturing was enabled.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct CaptureStats {
+    /// The packets that were kept, including those that have since been pushed out.
+    pub captured: u64,
+    /// The packets that were not kept because too many had already been captured that second.
+    pub rate_limited: u64,
+}
+
+#[derive(Debug)]
+struct MalformedCapture {
+    config: CaptureConfig,
+    packets: VecDeque<MalformedPacket>,
+    window_start: Option<Instant>,
+    window_count: u32,
+    stats: CaptureStats,
+}
+
+impl MalformedCapture {
+    #[inline]
+    fn new(config: CaptureConfig) -> Self {
+        Self {
+            config,
+            packets: VecDeque::new(),
+            window_start: None,
+            window_count: 0,
+            stats: CaptureStats::default(),
+        }
+    }
+
+    /// Whether another packet can be captured at this time.
+    fn admit(&mut self, now: Instant) -> bool {
+        match self.window_start {
+            Some(window_start) if now.saturating_duration_since(window_start) < Duration::from_secs(1) => (),
+            _ => {
+                self.window_start = Some(now);
+                self.window_count = 0;
+            },
+        }
+        if self.window_count >= self.config.per_second {
+            self.stats.rate_limited += 1;
+            return false;
+        }
+        self.window_count += 1;
+        true
+    }
+
+    fn capture(&mut self, now: Instant, protocol: &'static str, peer: Option<SocketAddr>, bytes: &[u8], error: &impl Display) {
+        if (self.config.max_packets == 0) || !self.admit(now) {
+            return;
+        }
+        while self.packets.len() >= self.config.max_packets {
+            self.packets.pop_front();
+        }
+        self.packets.push_back(MalformedPacket {
+            time: SystemTime::now(),
+            protocol,
+            peer,
+            error: error.to_string(),
+            length: bytes.len(),
+            bytes: bytes[..bytes.len().min(self.config.max_bytes)].to_vec(),
+        });
+        self.stats.captured += 1;
+    }
+}
+
+/// Starts keeping a copy of the packets that cannot be parsed, within the limits of `config`.
+/// Packets that were already captured are kept unless the new limits are smaller.
+///
+/// This is meant for diagnosing misbehaving peers. The captured bytes may contain anything that
+/// the peer sent.
+pub fn enable_malformed_capture(config: CaptureConfig) {
+    let mut w_capture = MALFORMED_CAPTURE.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    w_capture.config = config;
+    while w_capture.packets.len() > config.max_packets {
+        w_capture.packets.pop_front();
+    }
+    CAPTURE_ACTIVE.store(true, Ordering::Release);
+    drop(w_capture);
+}
+
+/// Stops capturing malformed packets. The packets that were already captured are kept until they
+/// are drained.
+#[inline]
+pub fn disable_malformed_capture() {
+    CAPTURE_ACTIVE.store(false, Ordering::Release);
+}
+
+#[inline]
+pub fn is_malformed_capture_enabled() -> bool {
+    CAPTURE_ACTIVE.load(Ordering::Acquire)
+}
+
+/// The packets currently captured, oldest first.
+pub fn malformed_packets() -> Vec<MalformedPacket> {
+    let r_capture = MALFORMED_CAPTURE.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    let packets = r_capture.packets.iter().cloned().collect();
+    drop(r_capture);
+    packets
+}
+
+/// Removes and returns the packets currently captured, oldest first.
+pub fn drain_malformed_packets() -> Vec<MalformedPacket> {
+    let mut w_capture = MALFORMED_CAPTURE.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    let packets = w_capture.packets.drain(..).collect();
+    drop(w_capture);
+    packets
+}
+
+pub fn malformed_capture_stats() -> CaptureStats {
+    let r_capture = MALFORMED_CAPTURE.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    let stats = r_capture.stats;
+    drop(r_capture);
+    stats
+}
+
+/// Records a packet that could not be parsed, if capturing is enabled.
+#[inline]
+pub(crate) fn capture_malformed(protocol: &'static str, peer: Option<SocketAddr>, bytes: &[u8], error: &impl Display) {
+    if !CAPTURE_ACTIVE.load(Ordering::Acquire) {
+        return;
+    }
+    let mut w_capture = MALFORMED_CAPTURE.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    w_capture.capture(Instant::now(), protocol, peer, bytes, error);
+    drop(w_capture);
+}
+
+/// Formats bytes the way `hexdump -C` does: the offset, then up to 16 bytes in hex, then the same
+/// bytes as ASCII with anything unprintable shown as a '.'.
+pub fn hexdump(bytes: &[u8]) -> String {
+    let mut dump = String::with_capacity((bytes.len() / HEXDUMP_WIDTH + 1) * 78);
+    for (line, chunk) in bytes.chunks(HEXDUMP_WIDTH).enumerate() {
+        let _ = write!(dump, "{:08x} ", line * HEXDUMP_WIDTH);
+        for index in 0..HEXDUMP_WIDTH {
+            if index == (HEXDUMP_WIDTH / 2) {
+                dump.push(' ');
+            }
+            match chunk.get(index) {
+                Some(byte) => { let _ = write!(dump, " {byte:02x}"); },
+                None => dump.push_str("   "),
+            }
+        }
+        dump.push_str("  |");
+        dump.extend(chunk.iter().map(|byte| if byte.is_ascii_graphic() || (*byte == b' ') { *byte as char } else { '.' }));
+        dump.push_str("|\n");
+    }
+    dump
+}
+
+#[cfg(test)]
+mod capture_tests {
+    use std::{net::{IpAddr, Ipv4Addr, SocketAddr}, time::{Duration, Instant}};
+
+    use super::{hexdump, CaptureConfig, CaptureStats, MalformedCapture};
+
+    #[test]
+    fn hexdump_format() {
+        let dump = hexdump(b"\x12\x34\x01\x00\x00\x01\x00\x00\x00\x00\x00\x00\x07example\x03com\x00");
+        assert_eq!(
+            dump,
+            "00000000  12 34 01 00 00 01 00 00  00 00 00 00 07 65 78 61  |.4...........exa|\n\
+             00000010  6d 70 6c 65 03 63 6f 6d  00                       |mple.com.|\n"
+        );
+        assert_eq!(hexdump(&[]), "");
+    }
+
+    #[test]
+    fn truncates_and_bounds_packets() {
+        let peer = SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 53);
+        let mut capture = MalformedCapture::new(CaptureConfig { max_packets: 2, max_bytes: 4, per_second: 100 });
+        let now = Instant::now();
+        for id in 0..3_u8 {
+            capture.capture(now, "UDP", Some(peer), &[id, 1, 2, 3, 4, 5], &"bad packet");
+        }
+
+        assert_eq!(capture.packets.len(), 2);
+        assert_eq!(capture.packets[0].bytes, vec![1, 1, 2, 3]);
+        assert_eq!(capture.packets[1].bytes, vec![2, 1, 2, 3]);
+        assert_eq!(capture.packets[1].length, 6);
+        assert!(capture.packets[1].is_truncated());
+        assert_eq!(capture.packets[1].error, "bad packet");
+        assert_eq!(capture.stats, CaptureStats { captured: 3, rate_limited: 0 });
+    }
+
+    #[test]
+    fn rate_limits_per_second() {
+        let mut capture = MalformedCapture::new(CaptureConfig { max_packets: 10, max_bytes: 16, per_second: 2 });
+        let now = Instant::now();
+        for _ in 0..5 {
+            capture.capture(now, "TCP", None, &[0; 8], &"bad packet");
+        }
+        assert_eq!(capture.packets.len(), 2);
+        assert_eq!(capture.stats, CaptureStats { captured: 2, rate_limited: 3 });
+
+        capture.capture(now + Duration::from_secs(1), "TCP", None, &[0; 8], &"bad packet");
+        assert_eq!(capture.packets.len(), 3);
+        assert_eq!(capture.stats, CaptureStats { captured: 3, rate_limited: 3 });
+    }
+}
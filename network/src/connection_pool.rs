@@ -0,0 +1,281 @@
+use std::{collections::{HashMap, HashSet}, net::{IpAddr, SocketAddr}, sync::{Mutex, Weak}};
+
+use crate::mixed_tcp_udp::MixedSocket;
+#[cfg(feature = "tls")]
+use crate::dot::DotClient;
+
+const DEFAULT_MAX_TCP_CONNECTIONS: usize = 256;
+const DEFAULT_MAX_TLS_CONNECTIONS: usize = 128;
+const DEFAULT_MAX_QUIC_CONNECTIONS: usize = 128;
+const DEFAULT_MAX_CONNECTIONS_PER_PEER: usize = 8;
+
+/// The stream transports whose connections are kept open between queries.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PooledTransport {
+    Tcp,
+    Tls,
+    Quic,
+}
+
+/// The most connections of one transport that may be open at once.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct PoolLimits {
+    /// Across every upstream server.
+    pub max_connections: usize,
+    /// To any one IP address, across its ports and server names.
+    pub max_per_peer: usize,
+}
+
+impl PoolLimits {
+    /// Connections are never closed to make room for others.
+    pub const UNLIMITED: Self = Self { max_connections: usize::MAX, max_per_peer: usize::MAX };
+}
+
+/// How many connections of each stream transport may be open at once. Once a new connection would
+/// go over a limit, the connection that was least recently used is closed to make room for it, so
+/// a busy resolver cannot run out of file descriptors.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ConnectionPoolConfig {
+    pub tcp: PoolLimits,
+    pub tls: PoolLimits,
+    pub quic: PoolLimits,
+}
+
+impl ConnectionPoolConfig {
+    #[inline]
+    pub fn limits(&self, transport: PooledTransport) -> PoolLimits {
+        match transport {
+            PooledTransport::Tcp => self.tcp,
+            PooledTransport::Tls => self.tls,
+            PooledTransport::Quic => self.quic,
+        }
+    }
+}
+
+impl Default for ConnectionPoolConfig {
+    fn default() -> Self {
+        Self {
+            tcp: PoolLimits { max_connections: DEFAULT_MAX_TCP_CONNECTIONS, max_per_peer: DEFAULT_MAX_CONNECTIONS_PER_PEER },
+            tls: PoolLimits { max_connections: DEFAULT_MAX_TLS_CONNECTIONS, max_per_peer: DEFAULT_MAX_CONNECTIONS_PER_PEER },
+            quic: PoolLimits { max_connections: DEFAULT_MAX_QUIC_CONNECTIONS, max_per_peer: DEFAULT_MAX_CONNECTIONS_PER_PEER },
+        }
+    }
+}
+
+/// The connections of one transport.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct TransportPoolStats {
+    /// Connections that are open or being set up.
+    pub open: usize,
+    /// The number of IP addresses with an open connection.
+    pub peers: usize,
+    /// Connections that were closed to stay within the limits.
+    pub evicted: u64,
+}
+
+/// A point-in-time view of the connections in a `ConnectionPool`.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ConnectionPoolStats {
+    pub tcp: TransportPoolStats,
+    pub tls: TransportPoolStats,
+    pub quic: TransportPoolStats,
+}
+
+impl ConnectionPoolStats {
+    #[inline]
+    fn transport_mut(&mut self, transport: PooledTransport) -> &mut TransportPoolStats {
+        match transport {
+            PooledTransport::Tcp => &mut self.tcp,
+            PooledTransport::Tls => &mut self.tls,
+            PooledTransport::Quic => &mut self.quic,
+        }
+    }
+}
+
+/// A connection that the pool can check on and close. The pool does not keep the socket or
+/// client alive.
+#[derive(Clone)]
+pub(crate) enum PooledConnection {
+    Tcp(Weak<MixedSocket>),
+    #[cfg(feature = "quic")]
+    Quic(Weak<MixedSocket>),
+    #[cfg(feature = "tls")]
+    Tls(Weak<DotClient>),
+}
+
+impl PooledConnection {
+    #[inline]
+    fn transport(&self) -> PooledTransport {
+        match self {
+            Self::Tcp(_) => PooledTransport::Tcp,
+            #[cfg(feature = "quic")]
+            Self::Quic(_) => PooledTransport::Quic,
+            #[cfg(feature = "tls")]
+            Self::Tls(_) => PooledTransport::Tls,
+        }
+    }
+
+    /// Whether the connection is open or being set up.
+    #[inline]
+    fn is_open(&self) -> bool {
+        match self {
+            Self::Tcp(socket) => socket.upgrade().is_some_and(|socket| socket.tcp_connected()),
+            #[cfg(feature = "quic")]
+            Self::Quic(socket) => socket.upgrade().is_some_and(|socket| socket.quic_connected()),
+            #[cfg(feature = "tls")]
+            Self::Tls(client) => client.upgrade().is_some_and(|client| client.is_connected()),
+        }
+    }
+
+    /// Closes the connection in the background. Queries that are still using it fail.
+    #[inline]
+    fn close(&self) {
+        match self {
+            Self::Tcp(socket) => if let Some(socket) = socket.upgrade() {
+                tokio::task::spawn(socket.shutdown_tcp());
+            },
+            #[cfg(feature = "quic")]
+            Self::Quic(socket) => if let Some(socket) = socket.upgrade() {
+                tokio::task::spawn(socket.shutdown_quic());
+            },
+            #[cfg(feature = "tls")]
+            Self::Tls(client) => if let Some(client) = client.upgrade() {
+                tokio::task::spawn(async move { client.close().await });
+            },
+        }
+    }
+}
+
+/// Sockets and clients are identified by their upstream and, for the encrypted transports, the
+/// server name.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct PoolKey {
+    transport: PooledTransport,
+    address: SocketAddr,
+    server_name: Option<String>,
+}
+
+struct PoolEntry {
+    connection: PooledConnection,
+    /// The value of the pool's clock when the connection was last used.
+    last_used: u64,
+}
+
+#[derive(Default)]
+struct PoolState {
+    clock: u64,
+    entries: HashMap<PoolKey, PoolEntry>,
+    evicted: ConnectionPoolStats,
+}
+
+impl PoolState {
+    #[inline]
+    fn over_limits(&self, key: &PoolKey, limits: PoolLimits) -> bool {
+        let (open, peer_open) = self.entries.keys()
+            .filter(|other| other.transport == key.transport)
+            .fold((0, 0), |(open, peer_open), other| {
+                (open + 1, peer_open + usize::from(other.address.ip() == key.address.ip()))
+            });
+        (open > limits.max_connections) || (peer_open > limits.max_per_peer)
+    }
+
+    /// The least recently used connection, other than `key`, that is closed to bring the
+    /// transport back within its limits. Connections to the same peer go first if the peer is
+    /// over its limit.
+    fn least_recently_used(&self, key: &PoolKey, limits: PoolLimits) -> Option<PoolKey> {
+        let peer_open = self.entries.keys()
+            .filter(|other| (other.transport == key.transport) && (other.address.ip() == key.address.ip()))
+            .count();
+        let same_peer = peer_open > limits.max_per_peer;
+        self.entries.iter()
+            .filter(|(other, _)| (other.transport == key.transport) && (*other != key))
+            .filter(|(other, _)| !same_peer || (other.address.ip() == key.address.ip()))
+            .min_by_key(|(_, entry)| entry.last_used)
+            .map(|(other, _)| other.clone())
+    }
+}
+
+/// Tracks the stream connections made by the sockets and clients of a `SocketManager`, in the
+/// order they were last used. Every query over a stream transport is recorded before it is sent.
+/// If its connection would take the transport over one of its limits, the least recently used
+/// connections are closed.
+///
+/// Connections that close by themselves are only noticed when the limits are checked, or when the
+/// pool is pruned.
+#[derive(Default)]
+pub(crate) struct ConnectionPool {
+    config: Mutex<ConnectionPoolConfig>,
+    state: Mutex<PoolState>,
+}
+
+impl ConnectionPool {
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[inline]
+    pub fn config(&self) -> ConnectionPoolConfig {
+        *self.config.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Sets the limits. Connections over the new limits are closed as the next queries are sent.
+    #[inline]
+    pub fn set_config(&self, config: ConnectionPoolConfig) {
+        *self.config.lock().unwrap_or_else(|poisoned| poisoned.into_inner()) = config;
+    }
+
+    /// Records that a query is about to be sent over the connection, which may open it. Closes the
+    /// connections that it would take over the limits.
+    pub fn touch(&self, address: SocketAddr, server_name: Option<&str>, connection: PooledConnection) {
+        let transport = connection.transport();
+        let limits = self.config().limits(transport);
+        let key = PoolKey { transport, address, server_name: server_name.map(str::to_string) };
+
+        let mut state = self.state.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        state.clock += 1;
+        let last_used = state.clock;
+        state.entries.insert(key.clone(), PoolEntry { connection, last_used });
+
+        let mut evicted = Vec::new();
+        if state.over_limits(&key, limits) {
+            // Connections that have already closed make room without closing anything.
+            state.entries.retain(|other, entry| (other == &key) || entry.connection.is_open());
+            while state.over_limits(&key, limits) {
+                let Some(lru_key) = state.least_recently_used(&key, limits) else { break };
+                if let Some(entry) = state.entries.remove(&lru_key) {
+                    evicted.push(entry.connection);
+                }
+            }
+            state.evicted.transport_mut(transport).evicted += evicted.len() as u64;
+        }
+        drop(state);
+
+        for connection in evicted {
+            connection.close();
+        }
+    }
+
+    /// Forgets the connections that have closed, and those whose socket or client was dropped.
+    pub fn prune(&self) {
+        let mut state = self.state.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        state.entries.retain(|_, entry| entry.connection.is_open());
+        drop(state);
+    }
+
+    pub fn stats(&self) -> ConnectionPoolStats {
+        let state = self.state.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let mut stats = state.evicted;
+        let mut peers = HashSet::<(PooledTransport, IpAddr)>::new();
+        for (key, entry) in state.entries.iter() {
+            if entry.connection.is_open() {
+                stats.transport_mut(key.transport).open += 1;
+                if peers.insert((key.transport, key.address.ip())) {
+                    stats.transport_mut(key.transport).peers += 1;
+                }
+            }
+        }
+        drop(state);
+        stats
+    }
+}
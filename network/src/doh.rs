@@ -0,0 +1,388 @@
+use std::{io, net::SocketAddr, sync::{atomic::{AtomicBool, AtomicU64, Ordering}, Arc}};
+
+use bytes::Bytes;
+use dns_lib::{query::message::Message, serde::wire::{from_wire::FromWire, read_wire::ReadWire, to_wire::ToWire, write_wire::WriteWire}, types::c_domain_name::CompressionMap};
+use http::{header, Method, Request, StatusCode};
+
+use crate::{async_query::QueryOpt, mixed_tcp_udp::ConnectionState, socket::https::HttpsSocket};
+#[cfg(feature = "http3")]
+use bytes::{Buf, BytesMut};
+#[cfg(feature = "http3")]
+use tokio::sync::Mutex;
+#[cfg(feature = "http3")]
+use crate::{quic_pool::QuicConnectionPool, tls_config::H3_ALPN};
+
+pub(crate) const MAX_MESSAGE_SIZE: usize = 4096;
+/// https://datatracker.ietf.org/doc/html/rfc8484#section-6
+const DNS_MESSAGE_MEDIA_TYPE: &str = "application/dns-message";
+pub const DEFAULT_DOH_PATH: &str = "/dns-query";
+
+#[cfg(feature = "http3")]
+type H3SendRequest = h3::client::SendRequest<h3_quinn::OpenStreams, Bytes>;
+
+/// The HTTP version used to carry a DNS over HTTPS query.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DohProtocol {
+    Http2,
+    Http3,
+}
+
+/// Which HTTP versions a `DohClient` is allowed to use.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DohVersionPolicy {
+    /// Only use HTTP/2 over TLS over TCP.
+    Http2Only,
+    /// Only use HTTP/3 over QUIC.
+    Http3Only,
+    /// Try HTTP/3 first. If the server does not negotiate `h3` or the query fails, the query is
+    /// retried over HTTP/2.
+    PreferHttp3,
+}
+
+impl Default for DohVersionPolicy {
+    /// Prefers HTTP/3 if the `http3` feature is enabled. Otherwise, only HTTP/2 is used.
+    fn default() -> Self {
+        if cfg!(feature = "http3") {
+            Self::PreferHttp3
+        } else {
+            Self::Http2Only
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct DohProtocolCounters {
+    connections: AtomicU64,
+    queries: AtomicU64,
+    failures: AtomicU64,
+}
+
+impl DohProtocolCounters {
+    #[inline]
+    fn snapshot(&self) -> DohProtocolStats {
+        DohProtocolStats {
+            connections: self.connections.load(Ordering::Relaxed),
+            queries: self.queries.load(Ordering::Relaxed),
+            failures: self.failures.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// A point-in-time copy of the counters for a single HTTP version.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct DohProtocolStats {
+    /// The number of connections that were established.
+    pub connections: u64,
+    /// The number of queries that were answered.
+    pub queries: u64,
+    /// The number of queries that failed.
+    pub failures: u64,
+}
+
+/// A point-in-time copy of the counters for a `DohClient`.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct DohStats {
+    pub http2: DohProtocolStats,
+    pub http3: DohProtocolStats,
+    /// The number of queries that were retried over HTTP/2 after HTTP/3 failed.
+    pub fallbacks: u64,
+}
+
+impl DohStats {
+    #[inline]
+    pub fn protocol(&self, protocol: DohProtocol) -> &DohProtocolStats {
+        match protocol {
+            DohProtocol::Http2 => &self.http2,
+            DohProtocol::Http3 => &self.http3,
+        }
+    }
+}
+
+/// A DNS over HTTPS client for a single upstream. Connections are kept open and queries are
+/// multiplexed over them as concurrent streams. The HTTP/2 connection is managed by an
+/// `HttpsSocket`. HTTP/3 connections are taken from the shared QUIC connection pool, which uses the
+/// same endpoint as DNS over QUIC.
+pub struct DohClient {
+    upstream_socket: SocketAddr,
+    server_name: String,
+    path: String,
+    policy: DohVersionPolicy,
+    // Set while the client is disabled, so that neither HTTP version opens a new connection.
+    disabled: AtomicBool,
+
+    #[cfg(feature = "http3")]
+    http3: Mutex<Option<H3SendRequest>>,
+    http2: Arc<HttpsSocket>,
+
+    http3_counters: DohProtocolCounters,
+    http2_counters: DohProtocolCounters,
+    fallbacks: AtomicU64,
+}
+
+impl DohClient {
+    #[inline]
+    pub fn new(upstream_socket: SocketAddr, server_name: String, path: String, policy: DohVersionPolicy) -> Arc<Self> {
+        Arc::new(Self {
+            upstream_socket,
+            http2: HttpsSocket::new(upstream_socket, server_name.clone()),
+            server_name,
+            path,
+            policy,
+            disabled: AtomicBool::new(false),
+            #[cfg(feature = "http3")]
+            http3: Mutex::new(None),
+            http3_counters: DohProtocolCounters::default(),
+            http2_counters: DohProtocolCounters::default(),
+            fallbacks: AtomicU64::new(0),
+        })
+    }
+
+    #[inline]
+    pub fn upstream_socket(&self) -> &SocketAddr { &self.upstream_socket }
+
+    #[inline]
+    pub fn server_name(&self) -> &str { &self.server_name }
+
+    #[inline]
+    pub fn path(&self) -> &str { &self.path }
+
+    #[inline]
+    pub fn policy(&self) -> DohVersionPolicy { self.policy }
+
+    #[inline]
+    pub fn stats(&self) -> DohStats {
+        DohStats {
+            http2: DohProtocolStats { connections: self.http2.connections(), ..self.http2_counters.snapshot() },
+            http3: self.http3_counters.snapshot(),
+            fallbacks: self.fallbacks.load(Ordering::Relaxed),
+        }
+    }
+
+    /// The state of the HTTP/2 connection.
+    #[inline]
+    pub async fn http2_state(&self) -> ConnectionState {
+        self.http2.state().await
+    }
+
+    /// Closes the open connections. Later queries open new ones.
+    pub async fn shutdown(&self) {
+        #[cfg(feature = "http3")]
+        {
+            let mut w_http3 = self.http3.lock().await;
+            *w_http3 = None;
+            drop(w_http3);
+        }
+        let _ = self.http2.clone().shutdown_https().await;
+    }
+
+    /// Closes the open connections and fails later queries until the client is enabled again.
+    pub async fn disable(&self) {
+        self.disabled.store(true, Ordering::SeqCst);
+        let _ = self.http2.clone().disable_https().await;
+        self.shutdown().await;
+    }
+
+    #[inline]
+    pub async fn enable(&self) {
+        let _ = self.http2.clone().enable_https().await;
+        self.disabled.store(false, Ordering::SeqCst);
+    }
+
+    /// Sends the query and waits for the response. The HTTP versions that are tried depend on the
+    /// client's `DohVersionPolicy`.
+    pub async fn query(self: Arc<Self>, query: Message) -> io::Result<Message> {
+        if self.disabled.load(Ordering::SeqCst) {
+            return Err(io::Error::from(io::ErrorKind::ConnectionAborted));
+        }
+        let request_body = serialize_query(query.clone())?;
+        let response_body = match self.policy {
+            DohVersionPolicy::Http2Only => self.query_http2(request_body).await?,
+            DohVersionPolicy::Http3Only => self.query_http3(request_body).await?,
+            DohVersionPolicy::PreferHttp3 => match self.clone().query_http3(request_body.clone()).await {
+                Ok(response_body) => response_body,
+                Err(error) => {
+                    println!("DoH over HTTP/3 to {} failed, falling back to HTTP/2: {error}", self.upstream_socket);
+                    self.fallbacks.fetch_add(1, Ordering::Relaxed);
+                    self.query_http2(request_body).await?
+                },
+            },
+        };
+
+        let mut wire = ReadWire::from_bytes(&response_body);
+        let mut response = match Message::from_wire_format(&mut wire) {
+            Ok(response) => response,
+            Err(error) => return Err(io::Error::new(io::ErrorKind::InvalidData, error)),
+        };
+        // The query was sent with ID 0 so that it is cacheable by HTTP caches.
+        response.id = query.id;
+        Ok(response)
+    }
+
+    async fn query_http3(self: Arc<Self>, request_body: Bytes) -> io::Result<Bytes> {
+        let result = self.clone().query_http3_inner(request_body).await;
+        match &result {
+            Ok(_) => self.http3_counters.queries.fetch_add(1, Ordering::Relaxed),
+            Err(_) => self.http3_counters.failures.fetch_add(1, Ordering::Relaxed),
+        };
+        result
+    }
+
+    #[cfg(not(feature = "http3"))]
+    async fn query_http3_inner(self: Arc<Self>, _request_body: Bytes) -> io::Result<Bytes> {
+        Err(io::Error::new(io::ErrorKind::Unsupported, "HTTP/3 requires the 'http3' feature"))
+    }
+
+    #[cfg(feature = "http3")]
+    async fn query_http3_inner(self: Arc<Self>, request_body: Bytes) -> io::Result<Bytes> {
+        let mut send_request = self.clone().http3_send_request().await?;
+        let mut request_stream = match send_request.send_request(self.request()?).await {
+            Ok(request_stream) => request_stream,
+            Err(error) => {
+                // The connection is no longer usable. A new one will be established for the next
+                // query.
+                let mut w_http3 = self.http3.lock().await;
+                *w_http3 = None;
+                drop(w_http3);
+                return Err(io::Error::new(io::ErrorKind::ConnectionAborted, error));
+            },
+        };
+        request_stream.send_data(request_body).await.map_err(|error| io::Error::new(io::ErrorKind::BrokenPipe, error))?;
+        request_stream.finish().await.map_err(|error| io::Error::new(io::ErrorKind::BrokenPipe, error))?;
+
+        let response = request_stream.recv_response().await.map_err(|error| io::Error::new(io::ErrorKind::ConnectionAborted, error))?;
+        check_response(response.status(), response.headers())?;
+
+        let mut response_body = BytesMut::new();
+        while let Some(mut chunk) = request_stream.recv_data().await.map_err(|error| io::Error::new(io::ErrorKind::ConnectionAborted, error))? {
+            if response_body.len() + chunk.remaining() > MAX_MESSAGE_SIZE {
+                return Err(io::Error::new(io::ErrorKind::InvalidData, "DoH response exceeded the maximum message size"));
+            }
+            response_body.extend_from_slice(&chunk.copy_to_bytes(chunk.remaining()));
+        }
+        Ok(response_body.freeze())
+    }
+
+    #[cfg(feature = "http3")]
+    async fn http3_send_request(self: Arc<Self>) -> io::Result<H3SendRequest> {
+        let mut w_http3 = self.http3.lock().await;
+        if let Some(send_request) = w_http3.as_ref() {
+            let send_request = send_request.clone();
+            drop(w_http3);
+            return Ok(send_request);
+        }
+
+        let (quic_connection, alpn) = QuicConnectionPool::shared().get_or_connect(self.upstream_socket, &self.server_name, &[H3_ALPN]).await?;
+        if alpn != H3_ALPN {
+            return Err(io::Error::new(io::ErrorKind::Unsupported, "server did not negotiate HTTP/3"));
+        }
+        let (mut driver, send_request) = match h3::client::new(h3_quinn::Connection::new(quic_connection)).await {
+            Ok(h3_connection) => h3_connection,
+            Err(error) => return Err(io::Error::new(io::ErrorKind::ConnectionAborted, error)),
+        };
+        self.http3_counters.connections.fetch_add(1, Ordering::Relaxed);
+
+        let upstream_socket = self.upstream_socket;
+        tokio::spawn(async move {
+            let error = std::future::poll_fn(|cx| driver.poll_close(cx)).await;
+            println!("HTTP/3 connection to {upstream_socket} closed: {error}");
+        });
+
+        *w_http3 = Some(send_request.clone());
+        drop(w_http3);
+        Ok(send_request)
+    }
+
+    async fn query_http2(self: Arc<Self>, request_body: Bytes) -> io::Result<Bytes> {
+        let result = self.clone().query_http2_inner(request_body).await;
+        match &result {
+            Ok(_) => self.http2_counters.queries.fetch_add(1, Ordering::Relaxed),
+            Err(_) => self.http2_counters.failures.fetch_add(1, Ordering::Relaxed),
+        };
+        result
+    }
+
+    async fn query_http2_inner(self: Arc<Self>, request_body: Bytes) -> io::Result<Bytes> {
+        self.http2.clone().query(self.request()?, request_body).await
+    }
+
+    fn request(&self) -> io::Result<Request<()>> {
+        Request::builder()
+            .method(Method::POST)
+            .uri(format!("https://{}{}", self.server_name, self.path))
+            .header(header::CONTENT_TYPE, DNS_MESSAGE_MEDIA_TYPE)
+            .header(header::ACCEPT, DNS_MESSAGE_MEDIA_TYPE)
+            .body(())
+            .map_err(|error| io::Error::new(io::ErrorKind::InvalidInput, error))
+    }
+}
+
+/// The path to send POST requests to, given the URI template that a DoH server is advertised with
+/// (e.g. `/dns-query{?dns}`). The template's variables are only used by GET requests, so they are
+/// removed.
+///
+/// https://datatracker.ietf.org/doc/html/rfc8484#section-4.1
+pub fn post_path(uri_template: &str) -> &str {
+    match uri_template.find('{') {
+        Some(index) => &uri_template[..index],
+        None => uri_template,
+    }
+}
+
+/// Serializes the query for use as a request body. The message ID is set to 0 to maximize HTTP
+/// cache friendliness, as recommended by RFC 8484 section 4.1.
+fn serialize_query(mut query: Message) -> io::Result<Bytes> {
+    query.id = 0;
+    if let Err(error) = QueryOpt::Https.padding_policy().apply(&mut query) {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, error));
+    }
+
+    let raw_message = &mut [0_u8; MAX_MESSAGE_SIZE];
+    let mut raw_message = WriteWire::from_bytes(raw_message);
+    if let Err(wire_error) = query.to_wire_format(&mut raw_message, &mut Some(CompressionMap::new())) {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, wire_error));
+    };
+    Ok(Bytes::copy_from_slice(raw_message.current()))
+}
+
+pub(crate) fn check_response(status: StatusCode, headers: &http::HeaderMap) -> io::Result<()> {
+    if !status.is_success() {
+        return Err(io::Error::new(io::ErrorKind::Other, format!("DoH server responded with status {status}")));
+    }
+    match headers.get(header::CONTENT_TYPE) {
+        Some(content_type) if content_type.as_bytes().eq_ignore_ascii_case(DNS_MESSAGE_MEDIA_TYPE.as_bytes()) => Ok(()),
+        _ => Err(io::Error::new(io::ErrorKind::InvalidData, "DoH response did not have the 'application/dns-message' content type")),
+    }
+}
+
+#[cfg(test)]
+mod doh_tests {
+    use dns_lib::{query::{message::Message, question::Question, padding::QUERY_BLOCK_LENGTH}, resource_record::{rclass::RClass, rtype::RType}, types::c_domain_name::CDomainName};
+    use http::{header, HeaderMap, HeaderValue, StatusCode};
+
+    use super::{check_response, post_path, serialize_query, DEFAULT_DOH_PATH, DNS_MESSAGE_MEDIA_TYPE};
+
+    #[test]
+    fn query_id_zeroed_and_padded() {
+        let mut query = Message::from(Question::new(CDomainName::from_utf8("www.example.org.").unwrap(), RType::A, RClass::Internet));
+        query.id = 0x1234;
+        let body = serialize_query(query).unwrap();
+        assert_eq!(&body[..2], &[0, 0]);
+        assert_eq!(body.len() % (QUERY_BLOCK_LENGTH as usize), 0);
+    }
+
+    #[test]
+    fn response_checks() {
+        let mut headers = HeaderMap::new();
+        assert!(check_response(StatusCode::OK, &headers).is_err());
+        headers.insert(header::CONTENT_TYPE, HeaderValue::from_static(DNS_MESSAGE_MEDIA_TYPE));
+        assert!(check_response(StatusCode::OK, &headers).is_ok());
+        assert!(check_response(StatusCode::BAD_REQUEST, &headers).is_err());
+    }
+
+    #[test]
+    fn post_path_drops_template_variables() {
+        assert_eq!(post_path("/dns-query{?dns}"), DEFAULT_DOH_PATH);
+        assert_eq!(post_path(DEFAULT_DOH_PATH), DEFAULT_DOH_PATH);
+        assert_eq!(post_path("/resolve{?dns,ct}"), "/resolve");
+    }
+}
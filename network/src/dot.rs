@@ -0,0 +1,216 @@
+use std::{io, net::SocketAddr, sync::{atomic::{AtomicU64, Ordering}, Arc}};
+
+use dns_lib::{query::message::Message, types::c_domain_name::CompressionMap, serde::wire::stream_write::write_with_two_octet_length};
+use rustls::pki_types::ServerName;
+use tokio::{io::AsyncWriteExt, net::TcpStream, sync::Mutex};
+use tokio_rustls::{client::TlsStream, TlsConnector};
+
+use crate::{async_query::QueryOpt, connection_pool::{ConnectionPool, PooledConnection}, errors::{IoError, QueryError, TcpInitError, TcpSendError, TlsSocketError}, receive::read_stream_message, tls_config::{self, TlsVerification, DOT_ALPN}, tls_diagnostics::{self, TlsConnectionInfo}};
+
+/// The port that opportunistic upstreams are queried on when TLS is not available.
+const CLEARTEXT_PORT: u16 = 53;
+
+/// A DNS over TLS client for a single upstream. The connection is kept open between queries and
+/// the queries are sent over it one at a time.
+///
+/// The server's certificate is verified as specified by the `TlsVerification`, so upstreams in a
+/// private deployment can be verified against the deployment's own roots, and upstreams known only
+/// by their address can be verified against a pin set.
+pub struct DotClient {
+    upstream_socket: SocketAddr,
+    server_name: String,
+    verification: TlsVerification,
+    connection: Mutex<Option<TlsStream<TcpStream>>>,
+    /// The pool that the connection is counted in, if the client was created by a
+    /// `SocketManager`.
+    pool: Option<Arc<ConnectionPool>>,
+    reuse_races: AtomicU64,
+    cleartext_fallbacks: AtomicU64,
+}
+
+impl DotClient {
+    #[inline]
+    pub fn new(upstream_socket: SocketAddr, server_name: String, verification: TlsVerification) -> Arc<Self> {
+        Self::build(upstream_socket, server_name, verification, None)
+    }
+
+    /// Creates a client whose connection is counted in the pool, and may be closed by it.
+    #[inline]
+    pub(crate) fn with_pool(upstream_socket: SocketAddr, server_name: String, verification: TlsVerification, pool: Arc<ConnectionPool>) -> Arc<Self> {
+        Self::build(upstream_socket, server_name, verification, Some(pool))
+    }
+
+    #[inline]
+    fn build(upstream_socket: SocketAddr, server_name: String, verification: TlsVerification, pool: Option<Arc<ConnectionPool>>) -> Arc<Self> {
+        if verification.is_opportunistic() {
+            println!("WARNING: DNS over TLS to {upstream_socket} ('{server_name}') will not verify the server's certificate and will fall back to unencrypted TCP");
+        } else if !verification.is_authenticated() {
+            println!("WARNING: DNS over TLS to {upstream_socket} ('{server_name}') will not verify the server's certificate");
+        }
+        Arc::new(Self {
+            upstream_socket,
+            server_name,
+            verification,
+            connection: Mutex::new(None),
+            pool,
+            reuse_races: AtomicU64::new(0),
+            cleartext_fallbacks: AtomicU64::new(0),
+        })
+    }
+
+    #[inline]
+    pub fn upstream_socket(&self) -> &SocketAddr { &self.upstream_socket }
+
+    #[inline]
+    pub fn server_name(&self) -> &str { &self.server_name }
+
+    #[inline]
+    pub fn verification(&self) -> &TlsVerification { &self.verification }
+
+    /// `QueryOpt::TlsInsecure` if the server is not authenticated. Otherwise, `QueryOpt::Tls`.
+    #[inline]
+    pub fn transport(&self) -> QueryOpt {
+        if !self.verification.is_authenticated() {
+            QueryOpt::TlsInsecure
+        } else {
+            QueryOpt::Tls
+        }
+    }
+
+    /// Sends the query and waits for the response, connecting first if there is no open
+    /// connection. If the query fails, the connection is closed and a new one is established for
+    /// the next query.
+    ///
+    /// Opportunistic upstreams are sent the query over unencrypted TCP if a TLS connection cannot
+    /// be established. TLS is tried again for the next query.
+    pub async fn query(self: Arc<Self>, query: &mut Message) -> Result<Message, QueryError> {
+        self.transport().padding_policy().apply(query).map_err(TcpSendError::from).map_err(TlsSocketError::from)?;
+        let raw_message = query.to_wire_vec(&mut Some(CompressionMap::new())).map_err(TcpSendError::from).map_err(TlsSocketError::from)?;
+
+        if let Some(pool) = &self.pool {
+            pool.touch(self.upstream_socket, Some(&self.server_name), PooledConnection::Tls(Arc::downgrade(&self)));
+        }
+        let mut w_connection = self.connection.lock().await;
+        let mut reused = w_connection.is_some();
+        let result = loop {
+            let tls_stream = match w_connection.as_mut() {
+                Some(tls_stream) => tls_stream,
+                None => match self.connect().await {
+                    Ok(tls_stream) => w_connection.insert(tls_stream),
+                    Err(error) if self.verification.is_opportunistic() => {
+                        drop(w_connection);
+                        return Ok(self.query_cleartext(&raw_message, error).await?);
+                    },
+                    Err(error) => return Err(error.into()),
+                },
+            };
+            match send(tls_stream, &raw_message).await {
+                // The server may have closed the connection while it was idle. The query was not
+                // sent, so it is retried once on a new connection.
+                Err(_) if reused => {
+                    *w_connection = None;
+                    reused = false;
+                    self.reuse_races.fetch_add(1, Ordering::Relaxed);
+                    continue;
+                },
+                Err(error) => break Err(TlsSocketError::from(error)),
+                Ok(()) => break read_stream_message::<{ u16::MAX as usize }>(tls_stream, Some(self.upstream_socket)).await.map_err(TlsSocketError::from),
+            }
+        };
+        if result.is_err() {
+            *w_connection = None;
+        }
+        drop(w_connection);
+        Ok(result?)
+    }
+
+    /// Whether a connection is open. If a query is using the connection, it is assumed to be open.
+    #[inline]
+    pub(crate) fn is_connected(&self) -> bool {
+        self.connection.try_lock().map_or(true, |connection| connection.is_some())
+    }
+
+    /// Closes the connection once the query using it, if any, is done. The next query opens a new
+    /// one.
+    pub async fn close(&self) {
+        let mut w_connection = self.connection.lock().await;
+        *w_connection = None;
+        drop(w_connection);
+    }
+
+    /// The number of queries that were retried because their connection was closed before they
+    /// were sent.
+    #[inline]
+    pub fn reuse_races(&self) -> u64 {
+        self.reuse_races.load(Ordering::Relaxed)
+    }
+
+    /// The number of queries that opportunistic upstreams were sent without encryption.
+    #[inline]
+    pub fn cleartext_fallbacks(&self) -> u64 {
+        self.cleartext_fallbacks.load(Ordering::Relaxed)
+    }
+
+    /// Sends the query over TCP, without encryption, to the DNS port at the upstream's address.
+    async fn query_cleartext(&self, raw_message: &[u8], tls_error: TlsSocketError) -> Result<Message, TlsSocketError> {
+        let cleartext_socket = SocketAddr::new(self.upstream_socket.ip(), CLEARTEXT_PORT);
+        // Only the first fallback is reported. The count keeps track of the rest.
+        if self.cleartext_fallbacks.fetch_add(1, Ordering::Relaxed) == 0 {
+            println!("WARNING: DNS over TLS to {} failed ({tls_error}). Queries are sent to {cleartext_socket} without encryption whenever TLS cannot be established", self.upstream_socket);
+        }
+        let mut tcp_stream = TcpStream::connect(cleartext_socket).await.map_err(TcpInitError::from)?;
+        write_with_two_octet_length(&mut tcp_stream, raw_message).await.map_err(TcpSendError::from)?;
+        Ok(read_stream_message::<{ u16::MAX as usize }>(&mut tcp_stream, Some(cleartext_socket)).await?)
+    }
+
+    async fn connect(&self) -> Result<TlsStream<TcpStream>, TlsSocketError> {
+        let tls_config = tls_config::client_config_with_verification(&[DOT_ALPN], &self.verification)
+            .map_err(|error| TlsSocketError::Handshake(IoError::from(io::Error::new(io::ErrorKind::Other, error))))?;
+        let Ok(server_name) = ServerName::try_from(self.server_name.clone()) else {
+            return Err(TlsSocketError::InvalidServerName(self.server_name.clone()));
+        };
+        let tcp_stream = TcpStream::connect(self.upstream_socket).await.map_err(TcpInitError::from)?;
+        let tls_stream = TlsConnector::from(tls_config).connect(server_name, tcp_stream).await.map_err(handshake_error)?;
+        tls_diagnostics::record(TlsConnectionInfo::from_tls(self.upstream_socket, &self.server_name, self.transport(), tls_stream.get_ref().1));
+        Ok(tls_stream)
+    }
+}
+
+#[inline]
+async fn send(tls_stream: &mut TlsStream<TcpStream>, bytes: &[u8]) -> Result<(), TcpSendError> {
+    write_with_two_octet_length(tls_stream, bytes).await?;
+    tls_stream.flush().await?;
+    Ok(())
+}
+
+/// Separates certificate verification failures from other handshake failures so that they can be
+/// reported as such.
+fn handshake_error(error: io::Error) -> TlsSocketError {
+    match error.get_ref().and_then(|inner| inner.downcast_ref::<rustls::Error>()) {
+        Some(tls_error @ rustls::Error::InvalidCertificate(_)) => TlsSocketError::Verification(tls_error.to_string()),
+        _ => TlsSocketError::Handshake(IoError::from(error)),
+    }
+}
+
+#[cfg(test)]
+mod dot_tests {
+    use std::io;
+
+    use rustls::CertificateError;
+
+    use crate::errors::{IoError, TlsSocketError};
+
+    use super::handshake_error;
+
+    #[test]
+    fn verification_failures_are_distinguished() {
+        let error = io::Error::new(io::ErrorKind::InvalidData, rustls::Error::InvalidCertificate(CertificateError::UnknownIssuer));
+        assert!(matches!(handshake_error(error), TlsSocketError::Verification(_)));
+
+        let error = io::Error::new(io::ErrorKind::InvalidData, rustls::Error::HandshakeNotComplete);
+        assert_eq!(handshake_error(error), TlsSocketError::Handshake(IoError::Message(io::ErrorKind::InvalidData)));
+
+        let error = io::Error::from(io::ErrorKind::ConnectionReset);
+        assert_eq!(handshake_error(error), TlsSocketError::Handshake(IoError::Message(io::ErrorKind::ConnectionReset)));
+    }
+}
@@ -1,8 +1,10 @@
-use std::{error::Error, fmt::Display, io};
+use std::{error::Error, fmt::Display, io, net::IpAddr};
 
-use dns_lib::serde::wire::{read_wire::ReadWireError, write_wire::WriteWireError};
+use dns_lib::serde::wire::{read_wire::ReadWireError, stream_write::StreamWriteError, write_wire::WriteWireError};
 use tokio::task::JoinError;
 
+use crate::{async_query::QueryOpt, listener::ListenProtocol};
+
 
 #[derive(Debug, Clone, Hash, PartialEq, Eq)]
 pub enum QueryError {
@@ -10,7 +12,17 @@ pub enum QueryError {
     TcpSend(TcpSendError),
     UdpSocket(UdpSocketError),
     UdpSend(UdpSendError),
+    TlsSocket(TlsSocketError),
+    /// A DNS over HTTPS query failed to connect, was rejected by the server, or could not be read.
+    HttpsSocket(IoError),
+    /// A DNS over QUIC query failed to connect, could not be sent, or its response could not be
+    /// read.
+    QuicSocket(IoError),
     Timeout,
+    /// Every caller waiting on the query cancelled it.
+    Cancelled,
+    InvalidResponse(ResponseRejection),
+    UnsupportedTransport(QueryOpt),
 }
 impl Display for QueryError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
@@ -19,11 +31,22 @@ impl Display for QueryError {
             Self::TcpSend(tcp_error) => write!(f, "{tcp_error}"),
             Self::UdpSocket(udp_error) => write!(f, "{udp_error}"),
             Self::UdpSend(udp_error) => write!(f, "{udp_error}"),
+            Self::TlsSocket(tls_error) => write!(f, "{tls_error}"),
+            Self::HttpsSocket(io_error) => write!(f, "{io_error} during DNS over HTTPS query"),
+            Self::QuicSocket(io_error) => write!(f, "{io_error} during DNS over QUIC query"),
             Self::Timeout => write!(f, "timeout during query"),
+            Self::Cancelled => write!(f, "query cancelled"),
+            Self::InvalidResponse(rejection) => write!(f, "{rejection}"),
+            Self::UnsupportedTransport(transport) => write!(f, "queries over {transport:?} are not supported yet"),
         }
     }
 }
 impl Error for QueryError {}
+impl From<ResponseRejection> for QueryError {
+    fn from(rejection: ResponseRejection) -> Self {
+        Self::InvalidResponse(rejection)
+    }
+}
 impl From<TcpSocketError> for QueryError {
     fn from(error: TcpSocketError) -> Self {
         Self::TcpSocket(error)
@@ -44,6 +67,11 @@ impl From<UdpSendError> for QueryError {
         Self::UdpSend(error)
     }
 }
+impl From<TlsSocketError> for QueryError {
+    fn from(error: TlsSocketError) -> Self {
+        Self::TlsSocket(error)
+    }
+}
 impl From<SocketSendError> for QueryError {
     fn from(error: SocketSendError) -> Self {
         match error {
@@ -57,9 +85,43 @@ impl From<SocketError> for QueryError {
         match error {
             SocketError::Tcp(tcp_error) => Self::from(tcp_error),
             SocketError::Udp(udp_error) => Self::from(udp_error),
+            SocketError::Tls(tls_error) => Self::from(tls_error),
+        }
+    }
+}
+
+/// The reasons a response can be rejected for not matching the query that was sent.
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq)]
+pub enum ResponseRejection {
+    /// The QR bit was not set.
+    NotAResponse,
+    /// The opcode is not the one that was used in the query.
+    OpcodeMismatch,
+    /// The question section does not echo the question that was asked.
+    QuestionMismatch,
+    /// The AA, RA, or RD flags could not have been set by the server being queried.
+    IncoherentFlags,
+    /// The response has more records than the client accepts in a single response.
+    TooManyRecords,
+    /// The response is larger than the client accepts.
+    TooLarge,
+    /// The query was signed with TSIG but the response's signature is missing or not valid.
+    Unauthenticated,
+}
+impl Display for ResponseRejection {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self {
+            Self::NotAResponse => write!(f, "the response does not have the QR bit set"),
+            Self::OpcodeMismatch => write!(f, "the response opcode does not match the query"),
+            Self::QuestionMismatch => write!(f, "the response question does not match the query"),
+            Self::IncoherentFlags => write!(f, "the response flags are not coherent with the server's role"),
+            Self::TooManyRecords => write!(f, "the response has more records than allowed"),
+            Self::TooLarge => write!(f, "the response is larger than allowed"),
+            Self::Unauthenticated => write!(f, "the response is not signed with the query's TSIG key"),
         }
     }
 }
+impl Error for ResponseRejection {}
 
 #[derive(Debug, Clone, Hash, PartialEq, Eq)]
 pub enum SocketSendError {
@@ -110,6 +172,14 @@ impl From<WriteWireError> for TcpSendError {
         Self::Serialization(error)
     }
 }
+impl From<StreamWriteError> for TcpSendError {
+    fn from(error: StreamWriteError) -> Self {
+        match error {
+            StreamWriteError::Serialization(error) => Self::Serialization(error),
+            StreamWriteError::Io(error) => Self::from(error),
+        }
+    }
+}
 impl From<IoError> for TcpSendError {
     fn from(error: IoError) -> Self {
         Self::Io(error)
@@ -228,12 +298,14 @@ impl From<io::Error> for UdpReceiveError {
 pub enum SocketError {
     Udp(UdpSocketError),
     Tcp(TcpSocketError),
+    Tls(TlsSocketError),
 }
 impl Display for SocketError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match &self {
             Self::Udp(udp_error) => write!(f, "{udp_error}"),
             Self::Tcp(tcp_error) => write!(f, "{tcp_error}"),
+            Self::Tls(tls_error) => write!(f, "{tls_error}"),
         }
     }
 }
@@ -248,6 +320,11 @@ impl From<TcpSocketError> for SocketError {
         Self::Tcp(error)
     }
 }
+impl From<TlsSocketError> for SocketError {
+    fn from(error: TlsSocketError) -> Self {
+        Self::Tls(error)
+    }
+}
 
 #[derive(Debug, Clone, Hash, PartialEq, Eq)]
 pub enum TcpSocketError {
@@ -293,6 +370,47 @@ impl From<UdpInitError> for UdpSocketError {
     }
 }
 
+#[derive(Debug, Clone, Hash, PartialEq, Eq)]
+pub enum TlsSocketError {
+    /// The server's name cannot be used to verify its certificate.
+    InvalidServerName(String),
+    /// The server's certificate was rejected. The reason is included.
+    Verification(String),
+    /// The TLS handshake failed for a reason other than the certificate.
+    Handshake(IoError),
+    Init(TcpInitError),
+    Send(TcpSendError),
+    Receive(StreamReceiveError),
+}
+impl Display for TlsSocketError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self {
+            Self::InvalidServerName(server_name) => write!(f, "'{server_name}' is not a valid TLS server name"),
+            Self::Verification(reason) => write!(f, "TLS certificate verification failed: {reason}"),
+            Self::Handshake(io_error) => write!(f, "{io_error} during TLS handshake"),
+            Self::Init(init_error) => write!(f, "{init_error}"),
+            Self::Send(send_error) => write!(f, "{send_error}"),
+            Self::Receive(receive_error) => write!(f, "{receive_error}"),
+        }
+    }
+}
+impl Error for TlsSocketError {}
+impl From<TcpInitError> for TlsSocketError {
+    fn from(error: TcpInitError) -> Self {
+        Self::Init(error)
+    }
+}
+impl From<TcpSendError> for TlsSocketError {
+    fn from(error: TcpSendError) -> Self {
+        Self::Send(error)
+    }
+}
+impl From<StreamReceiveError> for TlsSocketError {
+    fn from(error: StreamReceiveError) -> Self {
+        Self::Receive(error)
+    }
+}
+
 #[derive(Debug, Clone, Hash, PartialEq, Eq)]
 pub enum SocketInitError {
     Udp(UdpInitError),
@@ -419,3 +537,72 @@ impl From<io::Error> for IoError {
         }
     }
 }
+
+#[derive(Debug, Clone, Hash, PartialEq, Eq)]
+pub enum ListenerError {
+    InvalidEnvironment(&'static str),
+    UnsupportedSocketType(i32),
+    WrongProtocol {
+        expected: ListenProtocol,
+        actual: ListenProtocol,
+    },
+    UnknownUser(String),
+    UnknownGroup(String),
+    PrivilegeDrop(IoError),
+    PrivilegesRetained,
+    Io(IoError),
+}
+impl Display for ListenerError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self {
+            Self::InvalidEnvironment(variable) => write!(f, "environment variable {variable} is invalid for socket activation"),
+            Self::UnsupportedSocketType(fd) => write!(f, "socket activated file descriptor {fd} is not a UDP or TCP socket"),
+            Self::WrongProtocol { expected, actual } => write!(f, "expected a {expected:?} listener but found a {actual:?} listener"),
+            Self::UnknownUser(user) => write!(f, "unknown user '{user}'"),
+            Self::UnknownGroup(group) => write!(f, "unknown group '{group}'"),
+            Self::PrivilegeDrop(io_error) => write!(f, "{io_error} while dropping privileges"),
+            Self::PrivilegesRetained => write!(f, "privileges could be regained after they were dropped"),
+            Self::Io(io_error) => write!(f, "{io_error} while setting up listener"),
+        }
+    }
+}
+impl Error for ListenerError {}
+impl From<IoError> for ListenerError {
+    fn from(error: IoError) -> Self {
+        Self::Io(error)
+    }
+}
+impl From<io::Error> for ListenerError {
+    fn from(error: io::Error) -> Self {
+        Self::Io(IoError::from(error))
+    }
+}
+
+#[derive(Debug, Clone, Hash, PartialEq, Eq)]
+pub enum StreamLimitError {
+    TooManyConnections(IpAddr),
+    ReadTimeout,
+    IdleTimeout,
+    Io(IoError),
+}
+impl Display for StreamLimitError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self {
+            Self::TooManyConnections(peer) => write!(f, "too many connections from {peer}"),
+            Self::ReadTimeout => write!(f, "timeout while reading message on inbound stream"),
+            Self::IdleTimeout => write!(f, "inbound stream closed after being idle"),
+            Self::Io(io_error) => write!(f, "{io_error} while reading inbound stream"),
+        }
+    }
+}
+impl Error for StreamLimitError {}
+impl From<IoError> for StreamLimitError {
+    fn from(error: IoError) -> Self {
+        Self::Io(error)
+    }
+}
+impl From<io::Error> for StreamLimitError {
+    fn from(error: io::Error) -> Self {
+        Self::Io(IoError::from(error))
+    }
+}
@@ -0,0 +1,245 @@
+use std::{collections::HashMap, net::SocketAddr, sync::{atomic::{AtomicBool, Ordering}, RwLock}, time::Duration};
+
+use lazy_static::lazy_static;
+use rand::Rng;
+use tinyvec::TinyVec;
+
+lazy_static! {
+    /// The fault profiles that are currently active, keyed by the upstream address they apply to.
+    static ref FAULT_PROFILES: RwLock<HashMap<SocketAddr, FaultProfile>> = RwLock::new(HashMap::new());
+}
+
+/// Set whenever at least one profile is active so that the send path can skip the lock when
+/// fault injection is not in use, which should be almost always.
+static FAULTS_ACTIVE: AtomicBool = AtomicBool::new(false);
+
+/// The extra delay added to a datagram before it is sent.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Latency {
+    #[default]
+    None,
+    Fixed(Duration),
+    /// A delay chosen uniformly between `min` and `max` (inclusive) for each datagram.
+    Uniform { min: Duration, max: Duration },
+}
+
+impl Latency {
+    fn sample(&self, rng: &mut impl Rng) -> Duration {
+        match self {
+            Self::None => Duration::ZERO,
+            Self::Fixed(delay) => *delay,
+            Self::Uniform { min, max } if min >= max => *min,
+            Self::Uniform { min, max } => rng.gen_range(*min..=*max),
+        }
+    }
+}
+
+/// Describes how the datagrams sent to a peer should be degraded. Every rate is a probability
+/// between `0.0` and `1.0` that is applied to each datagram independently.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct FaultProfile {
+    /// The probability that a datagram is silently dropped.
+    pub drop_rate: f64,
+    /// The delay added to every datagram that is not dropped.
+    pub latency: Latency,
+    /// The probability that a datagram is held back by an additional `reorder_delay` so that
+    /// datagrams sent after it may overtake it.
+    pub reorder_rate: f64,
+    pub reorder_delay: Duration,
+    /// The probability that a datagram is sent twice. Each copy gets its own latency.
+    pub duplicate_rate: f64,
+}
+
+impl FaultProfile {
+    /// A profile that drops datagrams with the given probability and does nothing else.
+    #[inline]
+    pub fn with_drop_rate(drop_rate: f64) -> Self {
+        Self { drop_rate, ..Default::default() }
+    }
+
+    /// Decides what happens to a single datagram. The returned delays are those after which a
+    /// copy of the datagram should be sent. No delays means that the datagram was dropped.
+    pub(crate) fn plan(&self, rng: &mut impl Rng) -> TinyVec<[Duration; 2]> {
+        let mut deliveries = TinyVec::new();
+        if rng.gen_bool(self.drop_rate.clamp(0.0, 1.0)) {
+            return deliveries;
+        }
+        let copies = if rng.gen_bool(self.duplicate_rate.clamp(0.0, 1.0)) { 2 } else { 1 };
+        for _ in 0..copies {
+            let mut delay = self.latency.sample(rng);
+            if rng.gen_bool(self.reorder_rate.clamp(0.0, 1.0)) {
+                delay = delay.saturating_add(self.reorder_delay);
+            }
+            deliveries.push(delay);
+        }
+        deliveries
+    }
+}
+
+/// Starts degrading the UDP datagrams sent to `peer` according to `profile`, replacing any profile
+/// that was already set for it. This takes effect for the next datagram, including those sent by
+/// sockets that are already open.
+///
+/// This is meant for exercising the retransmission and timeout logic under controlled conditions
+/// and should not be used outside of testing.
+pub fn set_fault_profile(peer: SocketAddr, profile: FaultProfile) {
+    let mut w_profiles = FAULT_PROFILES.write().unwrap_or_else(|poisoned| poisoned.into_inner());
+    w_profiles.insert(peer, profile);
+    FAULTS_ACTIVE.store(true, Ordering::Release);
+    drop(w_profiles);
+}
+
+/// Stops degrading the datagrams sent to `peer`. Returns the profile that was removed, if any.
+pub fn clear_fault_profile(peer: &SocketAddr) -> Option<FaultProfile> {
+    let mut w_profiles = FAULT_PROFILES.write().unwrap_or_else(|poisoned| poisoned.into_inner());
+    let profile = w_profiles.remove(peer);
+    FAULTS_ACTIVE.store(!w_profiles.is_empty(), Ordering::Release);
+    drop(w_profiles);
+    profile
+}
+
+/// Stops degrading the datagrams sent to every peer.
+pub fn clear_all_fault_profiles() {
+    let mut w_profiles = FAULT_PROFILES.write().unwrap_or_else(|poisoned| poisoned.into_inner());
+    w_profiles.clear();
+    FAULTS_ACTIVE.store(false, Ordering::Release);
+    drop(w_profiles);
+}
+
+pub fn fault_profile(peer: &SocketAddr) -> Option<FaultProfile> {
+    if !FAULTS_ACTIVE.load(Ordering::Acquire) {
+        return None;
+    }
+    let r_profiles = FAULT_PROFILES.read().unwrap_or_else(|poisoned| poisoned.into_inner());
+    let profile = r_profiles.get(peer).copied();
+    drop(r_profiles);
+    profile
+}
+
+/// Decides what happens to the next datagram sent to `peer`. Returns `None` if no faults are being
+/// injected for the peer, in which case the datagram should be sent normally.
+#[inline]
+pub(crate) fn plan_datagram(peer: &SocketAddr) -> Option<TinyVec<[Duration; 2]>> {
+    fault_profile(peer).map(|profile| profile.plan(&mut rand::thread_rng()))
+}
+
+#[cfg(test)]
+mod fault_injection_tests {
+    use std::{net::{IpAddr, Ipv4Addr, SocketAddr}, time::Duration};
+
+    use dns_lib::{query::{message::Message, question::Question}, resource_record::{rclass::RClass, rtype::RType}, types::c_domain_name::CDomainName};
+    use tokio::{net::UdpSocket, select};
+
+    use crate::{async_query::QueryOpt, mixed_tcp_udp::MixedSocket};
+
+    use super::{clear_fault_profile, fault_profile, set_fault_profile, FaultProfile, Latency};
+
+    const LISTEN_ADDR: SocketAddr = SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 0);
+
+    fn query() -> Message {
+        Message::from(Question::new(CDomainName::from_utf8("example.org.").unwrap(), RType::A, RClass::Internet))
+    }
+
+    #[test]
+    fn plan_drops_everything() {
+        let profile = FaultProfile::with_drop_rate(1.0);
+        let mut rng = rand::thread_rng();
+        for _ in 0..100 {
+            assert!(profile.plan(&mut rng).is_empty());
+        }
+    }
+
+    #[test]
+    fn plan_without_faults_sends_once_immediately() {
+        let profile = FaultProfile::default();
+        let mut rng = rand::thread_rng();
+        for _ in 0..100 {
+            assert_eq!(profile.plan(&mut rng).as_slice(), &[Duration::ZERO]);
+        }
+    }
+
+    #[test]
+    fn plan_duplicates_with_latency() {
+        let profile = FaultProfile {
+            latency: Latency::Uniform { min: Duration::from_millis(10), max: Duration::from_millis(20) },
+            duplicate_rate: 1.0,
+            ..Default::default()
+        };
+        let mut rng = rand::thread_rng();
+        for _ in 0..100 {
+            let plan = profile.plan(&mut rng);
+            assert_eq!(plan.len(), 2);
+            assert!(plan.iter().all(|delay| (Duration::from_millis(10)..=Duration::from_millis(20)).contains(delay)));
+        }
+    }
+
+    #[test]
+    fn plan_reorders() {
+        let profile = FaultProfile {
+            latency: Latency::Fixed(Duration::from_millis(5)),
+            reorder_rate: 1.0,
+            reorder_delay: Duration::from_millis(50),
+            ..Default::default()
+        };
+        assert_eq!(profile.plan(&mut rand::thread_rng()).as_slice(), &[Duration::from_millis(55)]);
+    }
+
+    #[tokio::test]
+    async fn dropped_datagrams_are_not_sent() {
+        let listener = UdpSocket::bind(LISTEN_ADDR).await.unwrap();
+        let peer = listener.local_addr().unwrap();
+        set_fault_profile(peer, FaultProfile::with_drop_rate(1.0));
+
+        let mixed_socket = MixedSocket::new(peer);
+        let query_task = tokio::spawn({
+            let mixed_socket = mixed_socket.clone();
+            async move { mixed_socket.query(&mut query(), QueryOpt::UdpTcp).await }
+        });
+
+        let mut buffer = [0_u8; 512];
+        select! {
+            _ = listener.recv(&mut buffer) => panic!("Received a datagram that should have been dropped."),
+            () = tokio::time::sleep(Duration::from_millis(250)) => (),
+        }
+
+        // Once cleared, the retransmission makes it through.
+        assert_eq!(clear_fault_profile(&peer), Some(FaultProfile::with_drop_rate(1.0)));
+        assert_eq!(fault_profile(&peer), None);
+        select! {
+            bytes_read = listener.recv(&mut buffer) => assert!(bytes_read.is_ok()),
+            () = tokio::time::sleep(Duration::from_secs(2)) => panic!("Did not receive the retransmission in time."),
+        }
+
+        query_task.abort();
+        mixed_socket.disable().await;
+    }
+
+    #[tokio::test]
+    async fn duplicated_datagrams_are_sent_twice() {
+        let listener = UdpSocket::bind(LISTEN_ADDR).await.unwrap();
+        let peer = listener.local_addr().unwrap();
+        set_fault_profile(peer, FaultProfile { duplicate_rate: 1.0, ..Default::default() });
+
+        let mixed_socket = MixedSocket::new(peer);
+        let query_task = tokio::spawn({
+            let mixed_socket = mixed_socket.clone();
+            async move { mixed_socket.query(&mut query(), QueryOpt::UdpTcp).await }
+        });
+
+        let mut first = [0_u8; 512];
+        let mut second = [0_u8; 512];
+        let first_length = select! {
+            bytes_read = listener.recv(&mut first) => bytes_read.unwrap(),
+            () = tokio::time::sleep(Duration::from_secs(1)) => panic!("Did not receive the first copy in time."),
+        };
+        let second_length = select! {
+            bytes_read = listener.recv(&mut second) => bytes_read.unwrap(),
+            () = tokio::time::sleep(Duration::from_millis(50)) => panic!("Did not receive the duplicate in time."),
+        };
+        assert_eq!(first[..first_length], second[..second_length]);
+
+        clear_fault_profile(&peer);
+        query_task.abort();
+        mixed_socket.disable().await;
+    }
+}
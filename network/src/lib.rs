@@ -2,9 +2,31 @@ pub(crate) mod rolling_average;
 pub(crate) mod receive;
 pub mod async_query;
 pub(crate) mod socket;
+#[cfg(feature = "tls")]
+pub mod tls_config;
 
 pub mod errors;
 pub mod socket_manager;
+pub mod peer_stats;
+pub mod retry_budget;
+pub mod connection_pool;
+pub mod listener;
+pub mod stream_limits;
 
 pub mod mixed_tcp_udp;
+#[cfg(feature = "quic")]
 pub mod quic;
+#[cfg(feature = "quic")]
+pub mod quic_pool;
+#[cfg(feature = "https")]
+pub mod doh;
+#[cfg(feature = "tls")]
+pub mod dot;
+#[cfg(feature = "tls")]
+pub mod tls_diagnostics;
+pub mod capture;
+pub mod fault_injection;
+pub mod traffic_class;
+pub mod udp_size;
+#[cfg(all(feature = "batch-udp", target_os = "linux"))]
+pub mod udp_batch;
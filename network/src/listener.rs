@@ -0,0 +1,458 @@
+use std::{env, ffi::CString, io, mem, net::{self, IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr}, os::fd::{AsFd, AsRawFd, FromRawFd, RawFd}, ptr};
+
+use libc::{c_int, c_void, in6_pktinfo, in_pktinfo, iovec, msghdr, socklen_t};
+use socket2::{Domain, Protocol, SockAddr, Socket, Type};
+use tokio::{io::Interest, net::{TcpListener, UdpSocket}};
+
+use crate::errors::{IoError, ListenerError};
+
+/// The first file descriptor passed by systemd during socket activation. Descriptors 0, 1, and 2
+/// are stdin, stdout, and stderr.
+const SD_LISTEN_FDS_START: RawFd = 3;
+const DEFAULT_TCP_BACKLOG: i32 = 1024;
+
+/// The transport protocol an inbound listener accepts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ListenProtocol {
+    Udp,
+    Tcp,
+}
+
+/// Describes a single socket that a server wants to listen on. Multiple services (DNS over UDP
+/// and TCP on 53, DoT on 853, DoH on 443) are described with one spec each.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ListenSpec {
+    pub address: SocketAddr,
+    pub protocol: ListenProtocol,
+}
+
+impl ListenSpec {
+    #[inline]
+    pub fn udp(address: SocketAddr) -> Self {
+        Self { address, protocol: ListenProtocol::Udp }
+    }
+
+    #[inline]
+    pub fn tcp(address: SocketAddr) -> Self {
+        Self { address, protocol: ListenProtocol::Tcp }
+    }
+
+    /// Whether the spec binds the ANY address (`0.0.0.0` or `::`) and so receives packets sent to
+    /// every address of the host.
+    #[inline]
+    pub fn is_wildcard(&self) -> bool {
+        self.address.ip().is_unspecified()
+    }
+}
+
+/// A bound inbound socket. These are bound as `std` sockets so that they can be created before
+/// the runtime exists (and before privileges are dropped) and then converted into their tokio
+/// equivalents when the server is ready to use them.
+#[derive(Debug)]
+pub enum InboundSocket {
+    Udp(net::UdpSocket),
+    Tcp(net::TcpListener),
+}
+
+impl InboundSocket {
+    #[inline]
+    pub fn protocol(&self) -> ListenProtocol {
+        match self {
+            Self::Udp(_) => ListenProtocol::Udp,
+            Self::Tcp(_) => ListenProtocol::Tcp,
+        }
+    }
+
+    #[inline]
+    pub fn local_addr(&self) -> Result<SocketAddr, ListenerError> {
+        match self {
+            Self::Udp(socket) => Ok(socket.local_addr()?),
+            Self::Tcp(listener) => Ok(listener.local_addr()?),
+        }
+    }
+
+    /// Asks the kernel to report the destination address of each datagram received on a UDP
+    /// socket so that `recv_with_destination()` can report it. This is done by `bind_inbound()`
+    /// for wildcard UDP sockets but must be done by hand for sockets from systemd.
+    #[inline]
+    pub fn enable_packet_info(&self) -> Result<(), ListenerError> {
+        match self {
+            Self::Udp(socket) => Ok(enable_packet_info(socket, &socket.local_addr()?)?),
+            Self::Tcp(_) => Err(ListenerError::WrongProtocol { expected: ListenProtocol::Udp, actual: ListenProtocol::Tcp }),
+        }
+    }
+
+    /// Converts the socket into a tokio UDP socket. Must be called from within a tokio runtime.
+    #[inline]
+    pub fn into_tokio_udp(self) -> Result<UdpSocket, ListenerError> {
+        match self {
+            Self::Udp(socket) => Ok(UdpSocket::from_std(socket)?),
+            Self::Tcp(_) => Err(ListenerError::WrongProtocol { expected: ListenProtocol::Udp, actual: ListenProtocol::Tcp }),
+        }
+    }
+
+    /// Converts the socket into a tokio TCP listener. Must be called from within a tokio runtime.
+    #[inline]
+    pub fn into_tokio_tcp(self) -> Result<TcpListener, ListenerError> {
+        match self {
+            Self::Tcp(listener) => Ok(TcpListener::from_std(listener)?),
+            Self::Udp(_) => Err(ListenerError::WrongProtocol { expected: ListenProtocol::Tcp, actual: ListenProtocol::Udp }),
+        }
+    }
+}
+
+/// Binds a non-blocking inbound socket for the spec. IPv6 sockets are bound as IPv6-only so that
+/// a server can bind both `0.0.0.0:53` and `[::]:53` side by side. Wildcard UDP sockets report the
+/// destination address of each datagram so that responses can be sent from it.
+pub fn bind_inbound(spec: &ListenSpec) -> Result<InboundSocket, ListenerError> {
+    let domain = Domain::for_address(spec.address);
+    let socket = match spec.protocol {
+        ListenProtocol::Udp => Socket::new(domain, Type::DGRAM, Some(Protocol::UDP))?,
+        ListenProtocol::Tcp => Socket::new(domain, Type::STREAM, Some(Protocol::TCP))?,
+    };
+    if spec.address.is_ipv6() {
+        socket.set_only_v6(true)?;
+    }
+    socket.set_reuse_address(true)?;
+    socket.set_nonblocking(true)?;
+    if spec.is_wildcard() && (spec.protocol == ListenProtocol::Udp) {
+        enable_packet_info(&socket, &spec.address)?;
+    }
+    socket.bind(&spec.address.into())?;
+    match spec.protocol {
+        ListenProtocol::Udp => Ok(InboundSocket::Udp(socket.into())),
+        ListenProtocol::Tcp => {
+            socket.listen(DEFAULT_TCP_BACKLOG)?;
+            Ok(InboundSocket::Tcp(socket.into()))
+        },
+    }
+}
+
+/// The local address that a datagram was sent to and the interface it arrived on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct PacketDestination {
+    pub address: IpAddr,
+    pub interface: u32,
+}
+
+/// Enables `IP_PKTINFO` or `IPV6_RECVPKTINFO`, depending on the socket's address family.
+fn enable_packet_info(socket: &impl AsFd, local_address: &SocketAddr) -> io::Result<()> {
+    let (level, name) = match local_address {
+        SocketAddr::V4(_) => (libc::IPPROTO_IP, libc::IP_PKTINFO),
+        SocketAddr::V6(_) => (libc::IPPROTO_IPV6, libc::IPV6_RECVPKTINFO),
+    };
+    let enable: c_int = 1;
+    // Safety: The option value is a valid `int` that outlives the call.
+    let result = unsafe { libc::setsockopt(
+        socket.as_fd().as_raw_fd(),
+        level,
+        name,
+        ptr::addr_of!(enable).cast::<c_void>(),
+        mem::size_of::<c_int>() as socklen_t,
+    ) };
+    if result != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+/// Receives a single datagram along with its source and the local address it was sent to. The
+/// destination is only reported if packet info was enabled on the socket, which `bind_inbound()`
+/// does for wildcard UDP sockets.
+pub async fn recv_with_destination(udp_socket: &UdpSocket, buffer: &mut [u8]) -> io::Result<(usize, SocketAddr, Option<PacketDestination>)> {
+    let fd = udp_socket.as_raw_fd();
+    udp_socket.async_io(Interest::READABLE, || {
+        let mut iovec = iovec { iov_base: buffer.as_mut_ptr().cast(), iov_len: buffer.len() };
+        // Safety: `sockaddr_storage` is plain old data for which all zeroes is valid.
+        let mut source: libc::sockaddr_storage = unsafe { mem::zeroed() };
+        // Aligned for `cmsghdr` and large enough for either an IPv4 or IPv6 packet info.
+        let mut control = [0_u64; 8];
+        // Safety: `msghdr` is plain old data for which all zeroes is valid.
+        let mut header: msghdr = unsafe { mem::zeroed() };
+        header.msg_name = ptr::addr_of_mut!(source).cast();
+        header.msg_namelen = mem::size_of_val(&source) as socklen_t;
+        header.msg_iov = &mut iovec;
+        header.msg_iovlen = 1;
+        header.msg_control = control.as_mut_ptr().cast();
+        header.msg_controllen = mem::size_of_val(&control) as _;
+
+        // Safety: The header points to memory that outlives the call.
+        let received = unsafe { libc::recvmsg(fd, &mut header, libc::MSG_DONTWAIT) };
+        if received < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        // Safety: The kernel wrote a socket address of `msg_namelen` bytes into the storage.
+        let source = unsafe { SockAddr::new(source, header.msg_namelen) };
+        let Some(source) = source.as_socket() else {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "datagram was not from an IP address"));
+        };
+        Ok((received as usize, source, received_destination(&header)))
+    }).await
+}
+
+fn received_destination(header: &msghdr) -> Option<PacketDestination> {
+    // Safety: The control buffer was filled in by the kernel and `msg_controllen` was updated to
+    // the length it used, so the `CMSG_*` macros stay within it.
+    unsafe {
+        let mut control_message = libc::CMSG_FIRSTHDR(header);
+        while !control_message.is_null() {
+            match ((*control_message).cmsg_level, (*control_message).cmsg_type) {
+                (libc::IPPROTO_IP, libc::IP_PKTINFO) => {
+                    let packet_info = ptr::read_unaligned(libc::CMSG_DATA(control_message).cast::<in_pktinfo>());
+                    return Some(PacketDestination {
+                        address: IpAddr::V4(Ipv4Addr::from(u32::from_be(packet_info.ipi_addr.s_addr))),
+                        interface: packet_info.ipi_ifindex as u32,
+                    });
+                },
+                (libc::IPPROTO_IPV6, libc::IPV6_PKTINFO) => {
+                    let packet_info = ptr::read_unaligned(libc::CMSG_DATA(control_message).cast::<in6_pktinfo>());
+                    return Some(PacketDestination {
+                        address: IpAddr::V6(Ipv6Addr::from(packet_info.ipi6_addr.s6_addr)),
+                        interface: packet_info.ipi6_ifindex,
+                    });
+                },
+                _ => (),
+            }
+            control_message = libc::CMSG_NXTHDR(header, control_message);
+        }
+    }
+    None
+}
+
+/// Sends a datagram to the target. If a source is given, the datagram is sent from that address
+/// (and, for IPv6, out of that interface) so that a response to a datagram received on a wildcard
+/// socket comes from the address the query was sent to.
+pub async fn send_from(udp_socket: &UdpSocket, buffer: &[u8], target: &SocketAddr, source: Option<&PacketDestination>) -> io::Result<usize> {
+    let Some(source) = source else {
+        return udp_socket.send_to(buffer, target).await;
+    };
+    let fd = udp_socket.as_raw_fd();
+    let target = SockAddr::from(*target);
+    udp_socket.async_io(Interest::WRITABLE, || {
+        let mut iovec = iovec { iov_base: buffer.as_ptr().cast_mut().cast(), iov_len: buffer.len() };
+        let mut control = [0_u64; 8];
+        // Safety: `msghdr` is plain old data for which all zeroes is valid.
+        let mut header: msghdr = unsafe { mem::zeroed() };
+        header.msg_name = target.as_ptr().cast_mut().cast();
+        header.msg_namelen = target.len();
+        header.msg_iov = &mut iovec;
+        header.msg_iovlen = 1;
+        header.msg_control = control.as_mut_ptr().cast();
+        // Safety: The control buffer is aligned for `cmsghdr` and large enough for one packet info
+        // of either family, which is all that is written to it.
+        unsafe {
+            match source.address {
+                IpAddr::V4(address) => {
+                    header.msg_controllen = libc::CMSG_SPACE(mem::size_of::<in_pktinfo>() as u32) as _;
+                    let control_message = libc::CMSG_FIRSTHDR(&header);
+                    (*control_message).cmsg_level = libc::IPPROTO_IP;
+                    (*control_message).cmsg_type = libc::IP_PKTINFO;
+                    (*control_message).cmsg_len = libc::CMSG_LEN(mem::size_of::<in_pktinfo>() as u32) as _;
+                    // The interface is left for routing to pick. Only the source address is fixed.
+                    let packet_info = in_pktinfo {
+                        ipi_ifindex: 0,
+                        ipi_spec_dst: libc::in_addr { s_addr: u32::from(address).to_be() },
+                        ipi_addr: libc::in_addr { s_addr: 0 },
+                    };
+                    ptr::write_unaligned(libc::CMSG_DATA(control_message).cast::<in_pktinfo>(), packet_info);
+                },
+                IpAddr::V6(address) => {
+                    header.msg_controllen = libc::CMSG_SPACE(mem::size_of::<in6_pktinfo>() as u32) as _;
+                    let control_message = libc::CMSG_FIRSTHDR(&header);
+                    (*control_message).cmsg_level = libc::IPPROTO_IPV6;
+                    (*control_message).cmsg_type = libc::IPV6_PKTINFO;
+                    (*control_message).cmsg_len = libc::CMSG_LEN(mem::size_of::<in6_pktinfo>() as u32) as _;
+                    // Link-local sources are only meaningful on the interface they arrived on.
+                    let packet_info = in6_pktinfo {
+                        ipi6_addr: libc::in6_addr { s6_addr: address.octets() },
+                        ipi6_ifindex: source.interface,
+                    };
+                    ptr::write_unaligned(libc::CMSG_DATA(control_message).cast::<in6_pktinfo>(), packet_info);
+                },
+            }
+        }
+
+        // Safety: The header points to memory that outlives the call.
+        let sent = unsafe { libc::sendmsg(fd, &header, libc::MSG_DONTWAIT) };
+        if sent < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(sent as usize)
+    }).await
+}
+
+/// Takes ownership of the sockets passed in by systemd socket activation (`LISTEN_PID` and
+/// `LISTEN_FDS`). If the process was not socket activated, an empty list is returned.
+///
+/// The environment variables are cleared so that child processes do not try to claim the same
+/// descriptors.
+pub fn take_systemd_sockets() -> Result<Vec<InboundSocket>, ListenerError> {
+    let listen_pid = match env::var("LISTEN_PID") {
+        Ok(listen_pid) => listen_pid,
+        Err(_) => return Ok(vec![]),
+    };
+    let listen_fds = env::var("LISTEN_FDS").unwrap_or_default();
+    env::remove_var("LISTEN_PID");
+    env::remove_var("LISTEN_FDS");
+    env::remove_var("LISTEN_FDNAMES");
+
+    match listen_pid.trim().parse::<u32>() {
+        Ok(pid) if pid == std::process::id() => (),
+        // The descriptors were meant for some other process.
+        Ok(_) => return Ok(vec![]),
+        Err(_) => return Err(ListenerError::InvalidEnvironment("LISTEN_PID")),
+    }
+    let fd_count = match listen_fds.trim().parse::<RawFd>() {
+        Ok(fd_count) if fd_count >= 0 => fd_count,
+        _ => return Err(ListenerError::InvalidEnvironment("LISTEN_FDS")),
+    };
+
+    let mut sockets = Vec::with_capacity(fd_count as usize);
+    for fd in SD_LISTEN_FDS_START..(SD_LISTEN_FDS_START + fd_count) {
+        // Safety: systemd guarantees that these descriptors are open and owned by this process
+        // once `LISTEN_PID` has been verified. The environment was cleared above so they cannot
+        // be claimed twice.
+        let socket = unsafe { Socket::from_raw_fd(fd) };
+        socket.set_nonblocking(true)?;
+        match socket.r#type()? {
+            Type::DGRAM => sockets.push(InboundSocket::Udp(socket.into())),
+            Type::STREAM => sockets.push(InboundSocket::Tcp(socket.into())),
+            _ => return Err(ListenerError::UnsupportedSocketType(fd)),
+        }
+    }
+    Ok(sockets)
+}
+
+/// The user and group that a server switches to once its privileged sockets are bound.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct PrivilegeDrop {
+    pub user: String,
+    pub group: Option<String>,
+}
+
+impl PrivilegeDrop {
+    /// Switches the process to the configured user and group. The group is changed first since
+    /// the process will no longer be allowed to after it stops being root. Supplementary groups
+    /// are cleared.
+    pub fn apply(&self) -> Result<(), ListenerError> {
+        let user_name = CString::new(self.user.as_str()).map_err(|_| ListenerError::UnknownUser(self.user.clone()))?;
+        // Safety: `getpwnam` returns either null or a pointer to a static buffer which is read
+        // before any other call that could overwrite it.
+        let passwd = unsafe { libc::getpwnam(user_name.as_ptr()) };
+        if passwd.is_null() {
+            return Err(ListenerError::UnknownUser(self.user.clone()));
+        }
+        let (uid, mut gid) = unsafe { ((*passwd).pw_uid, (*passwd).pw_gid) };
+
+        if let Some(group) = &self.group {
+            let group_name = CString::new(group.as_str()).map_err(|_| ListenerError::UnknownGroup(group.clone()))?;
+            // Safety: Same as `getpwnam`.
+            let group_entry = unsafe { libc::getgrnam(group_name.as_ptr()) };
+            if group_entry.is_null() {
+                return Err(ListenerError::UnknownGroup(group.clone()));
+            }
+            gid = unsafe { (*group_entry).gr_gid };
+        }
+
+        // Safety: These are plain system calls with no pointer arguments other than the empty
+        // group list.
+        unsafe {
+            if libc::setgroups(0, std::ptr::null()) != 0 {
+                return Err(ListenerError::PrivilegeDrop(IoError::from(std::io::Error::last_os_error())));
+            }
+            if libc::setgid(gid) != 0 {
+                return Err(ListenerError::PrivilegeDrop(IoError::from(std::io::Error::last_os_error())));
+            }
+            if libc::setuid(uid) != 0 {
+                return Err(ListenerError::PrivilegeDrop(IoError::from(std::io::Error::last_os_error())));
+            }
+            // Make sure root cannot be regained.
+            if uid != 0 && libc::setuid(0) == 0 {
+                return Err(ListenerError::PrivilegesRetained);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Binds every requested socket, preferring sockets handed over by systemd. Any spec not covered
+/// by socket activation is bound directly, which requires the process to still have the
+/// privileges for ports below 1024. Privileges are dropped only after all sockets are bound.
+pub fn bind_all(specs: &[ListenSpec], privilege_drop: Option<&PrivilegeDrop>) -> Result<Vec<(ListenSpec, InboundSocket)>, ListenerError> {
+    let mut activated = take_systemd_sockets()?;
+    let mut bound = Vec::with_capacity(specs.len());
+    for spec in specs {
+        let activated_index = activated.iter()
+            .position(|socket| (socket.protocol() == spec.protocol) && socket.local_addr().is_ok_and(|address| address == spec.address));
+        match activated_index {
+            Some(index) => bound.push((*spec, activated.swap_remove(index))),
+            None => bound.push((*spec, bind_inbound(spec)?)),
+        }
+    }
+    if let Some(privilege_drop) = privilege_drop {
+        privilege_drop.apply()?;
+    }
+    Ok(bound)
+}
+
+#[cfg(test)]
+mod listener_tests {
+    use std::{net::{IpAddr, Ipv4Addr, SocketAddr}, time::Duration};
+
+    use tokio::{io::{AsyncReadExt, AsyncWriteExt}, net::{TcpStream, UdpSocket}, time::timeout};
+
+    use super::{bind_inbound, recv_with_destination, send_from, ListenProtocol, ListenSpec};
+
+    const LOCALHOST: SocketAddr = SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 0);
+    const WILDCARD: SocketAddr = SocketAddr::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED), 0);
+
+    #[tokio::test]
+    async fn bind_udp_and_convert() {
+        let socket = bind_inbound(&ListenSpec::udp(LOCALHOST)).unwrap();
+        assert_eq!(socket.protocol(), ListenProtocol::Udp);
+        assert!(socket.into_tokio_udp().is_ok());
+    }
+
+    #[tokio::test]
+    async fn bind_tcp_and_convert() {
+        let socket = bind_inbound(&ListenSpec::tcp(LOCALHOST)).unwrap();
+        assert_eq!(socket.protocol(), ListenProtocol::Tcp);
+        let server_address = socket.local_addr().unwrap();
+        let listener = socket.into_tokio_tcp().unwrap();
+        assert_eq!(listener.local_addr().unwrap(), server_address);
+
+        let mut client = TcpStream::connect(server_address).await.unwrap();
+        let (mut accepted, peer) = timeout(Duration::from_secs(1), listener.accept()).await.unwrap().unwrap();
+        assert_eq!(peer, client.local_addr().unwrap());
+        client.write_all(b"query").await.unwrap();
+        let mut buffer = [0_u8; 5];
+        timeout(Duration::from_secs(1), accepted.read_exact(&mut buffer)).await.unwrap().unwrap();
+        assert_eq!(&buffer, b"query");
+
+        let socket = bind_inbound(&ListenSpec::tcp(LOCALHOST)).unwrap();
+        assert!(socket.into_tokio_udp().is_err());
+    }
+
+    #[tokio::test]
+    async fn wildcard_reports_destination() {
+        let spec = ListenSpec::udp(WILDCARD);
+        assert!(spec.is_wildcard());
+        let server = bind_inbound(&spec).unwrap().into_tokio_udp().unwrap();
+        let server_port = server.local_addr().unwrap().port();
+
+        let client = UdpSocket::bind(LOCALHOST).await.unwrap();
+        client.send_to(b"query", SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), server_port)).await.unwrap();
+
+        let mut buffer = [0_u8; 64];
+        let (length, source, destination) = timeout(Duration::from_secs(1), recv_with_destination(&server, &mut buffer)).await.unwrap().unwrap();
+        assert_eq!(&buffer[..length], b"query");
+        assert_eq!(source, client.local_addr().unwrap());
+        let destination = destination.unwrap();
+        assert_eq!(destination.address, IpAddr::V4(Ipv4Addr::LOCALHOST));
+
+        send_from(&server, b"response", &source, Some(&destination)).await.unwrap();
+        let (length, responder) = timeout(Duration::from_secs(1), client.recv_from(&mut buffer)).await.unwrap().unwrap();
+        assert_eq!(&buffer[..length], b"response");
+        assert_eq!(responder, SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), server_port));
+    }
+}
@@ -1,19 +1,30 @@
-use std::{cmp::{max, min}, collections::HashMap, future::Future, net::SocketAddr, num::NonZeroU8, pin::Pin, sync::{atomic::{AtomicBool, Ordering}, Arc}, task::Poll, time::Duration};
+use std::{cmp::{max, min}, collections::HashMap, future::Future, net::SocketAddr, num::NonZeroU8, pin::Pin, sync::{atomic::{AtomicBool, AtomicU16, AtomicU64, AtomicU8, AtomicUsize, Ordering}, Arc}, task::Poll, time::Duration};
 
-use async_lib::{awake_token::{AwakeToken, AwokenToken, SameAwakeToken}, once_watch::{self, OnceWatchSend, OnceWatchSubscribe}};
+use async_lib::{awake_token::{AwakeToken, AwokenToken, SameAwakeToken}, once_watch::{self, OnceWatchSend, OnceWatchSubscribe}, poll_budget::{PollBudget, PollLoopSnapshot, PollLoopStats}};
 use async_trait::async_trait;
-use atomic::Atomic;
-use dns_lib::{query::{message::Message, question::Question}, serde::wire::{to_wire::ToWire, write_wire::WriteWire}, types::c_domain_name::CompressionMap};
+use dns_lib::{query::{edns::udp_payload_size, message::Message, question::{Question, QuestionKey}}, resource_record::{resource_record::{RecordData, ResourceRecord}, rtype::RType, types::{cname::CNAME, ns::NS, ptr::PTR, soa::SOA}}, serde::wire::{stream_write::write_with_two_octet_length, to_wire::ToWire, write_wire::WriteWire}, types::c_domain_name::{CDomainName, CmpDomainName, CompressionMap}};
 use futures::{future::BoxFuture, FutureExt};
 use pin_project::{pin_project, pinned_drop};
 use tinyvec::TinyVec;
-use tokio::{io::{self, AsyncWriteExt}, join, net::{self, tcp::{OwnedReadHalf, OwnedWriteHalf}, TcpStream}, pin, select, sync::{Mutex, RwLock, RwLockWriteGuard}, task::{self, JoinHandle}, time::{Instant, Sleep}};
+use tokio::{io, join, net::{self, tcp::{OwnedReadHalf, OwnedWriteHalf}, TcpStream}, pin, select, sync::{Mutex, RwLock, RwLockWriteGuard}, task::{self, JoinHandle}, time::{Instant, Sleep}};
 
-use crate::{async_query::{QInitQuery, QInitQueryProj, QSend, QSendProj, QSendType, QueryOpt}, errors, receive::{read_stream_message, read_udp_message}, rolling_average::{fetch_update, RollingAverage}, socket::{tcp::{QTcpSocket, QTcpSocketProj, TcpSocket, TcpState}, udp::{QUdpSocket, QUdpSocketProj, UdpSocket, UdpState}, udp_tcp::{QUdpTcpSocket, QUdpTcpSocketProj}, FutureSocket, PollSocket}};
+use crate::{async_query::{QInitQuery, QInitQueryProj, QSend, QSendProj, QSendType, QueryOpt}, connection_pool::{ConnectionPool, PooledConnection}, errors, fault_injection, receive::{read_stream_message_with_wire, read_udp_message_with_traffic_class, UdpReceiver}, peer_stats::PeerStats, rolling_average::{fetch_update, RollingAverage}, socket::{tcp::{QTcpSocket, QTcpSocketProj, TcpSocket, TcpState}, udp::{QUdpSocket, QUdpSocketProj, UdpSocket, UdpState}, udp_tcp::{QUdpTcpSocket, QUdpTcpSocketProj}, FutureSocket, ListenerTracker, PollSocket}, traffic_class::{Ecn, TrafficClass}, udp_size::UdpSizeConfig};
+#[cfg(feature = "quic")]
+use crate::quic::{QuicPathStats, QuicSocket};
+#[cfg(feature = "https")]
+use crate::doh::{DohClient, DohVersionPolicy};
 
 const MAX_MESSAGE_SIZE: u16 = 8192;
+/// The length that precedes each message sent over TCP.
+const TWO_OCTET_LENGTH: usize = 2;
+/// Queries larger than this are sent over TCP since they may not make it through over UDP.
+///
+/// https://datatracker.ietf.org/doc/html/rfc1035#section-4.2.1
+const MAX_UDP_QUERY_SIZE: usize = 512;
 
 const MILLISECONDS_IN_1_SECOND: f64 = 1000.0;
+/// Stored in place of a TOS byte before any response has reported one.
+const NO_RECEIVED_TOS: u16 = u16::MAX;
 
 pub(crate) const TCP_INIT_TIMEOUT: Duration = Duration::from_secs(5);
 pub(crate) const TCP_LISTEN_TIMEOUT: Duration = Duration::from_secs(120);
@@ -93,6 +104,36 @@ pub(crate) const MAX_UDP_TIMEOUT: Duration = Duration::from_secs(10);
 /// The minimum allowable UDP timeout.
 pub(crate) const MIN_UDP_TIMEOUT: Duration = Duration::from_millis(50);
 
+/// The initial QUIC timeout, used when setting up a socket, before anything is known about the
+/// average response time. It covers the handshake of the first query on a new connection.
+#[cfg(feature = "quic")]
+pub(crate) const INIT_QUIC_TIMEOUT: Duration = Duration::from_secs(1);
+/// The percentage of the average QUIC response time that the timeout should be set to. Currently,
+/// this represents 200%. If the average response time were 20 ms, then the timeout would be 40 ms.
+#[cfg(feature = "quic")]
+pub(crate) const QUIC_TIMEOUT_DURATION_ABOVE_QUIC_RESPONSE_TIME: f64 = 2.00;
+/// The maximum percentage of the average QUIC response time that the timeout should be set to.
+/// Currently, this represents 400%. If the average response time were 20 ms, then the timeout
+/// would be 80 ms.
+#[cfg(feature = "quic")]
+pub(crate) const QUIC_TIMEOUT_MAX_DURATION_ABOVE_QUIC_RESPONSE_TIME: f64 = 4.00;
+/// The step size to use if INCREASE_QUIC_TIMEOUT_DROPPED_AVERAGE_THRESHOLD is exceeded.
+#[cfg(feature = "quic")]
+pub(crate) const QUIC_TIMEOUT_STEP_WHEN_DROPPED_THRESHOLD_EXCEEDED: Duration = Duration::from_millis(50);
+/// When 20% or more of queries are timing out, then it is time to start slowing down the socket.
+#[cfg(feature = "quic")]
+pub(crate) const INCREASE_QUIC_TIMEOUT_DROPPED_AVERAGE_THRESHOLD: f64 = 0.20;
+/// When 1% or more of queries are timing out, then we might want to try speeding up the socket
+/// again, to reflect the average response time.
+#[cfg(feature = "quic")]
+pub(crate) const DECREASE_QUIC_TIMEOUT_DROPPED_AVERAGE_THRESHOLD: f64 = 0.01;
+/// The maximum allowable QUIC timeout.
+#[cfg(feature = "quic")]
+pub(crate) const MAX_QUIC_TIMEOUT: Duration = Duration::from_secs(10);
+/// The minimum allowable QUIC timeout.
+#[cfg(feature = "quic")]
+pub(crate) const MIN_QUIC_TIMEOUT: Duration = Duration::from_millis(50);
+
 // Using the safe checked version of new is not stable. As long as we always use non-zero constants,
 // there should not be any problems with this.
 pub(crate) const ROLLING_AVERAGE_TCP_MAX_DROPPED: NonZeroU8        = unsafe { NonZeroU8::new_unchecked(11) };
@@ -100,16 +141,57 @@ pub(crate) const ROLLING_AVERAGE_TCP_MAX_RESPONSE_TIMES: NonZeroU8 = unsafe { No
 pub(crate) const ROLLING_AVERAGE_UDP_MAX_DROPPED: NonZeroU8        = unsafe { NonZeroU8::new_unchecked(11) };
 pub(crate) const ROLLING_AVERAGE_UDP_MAX_RESPONSE_TIMES: NonZeroU8 = unsafe { NonZeroU8::new_unchecked(13) };
 pub(crate) const ROLLING_AVERAGE_UDP_MAX_TRUNCATED: NonZeroU8      = unsafe { NonZeroU8::new_unchecked(50) };
+#[cfg(feature = "quic")]
+pub(crate) const ROLLING_AVERAGE_QUIC_MAX_DROPPED: NonZeroU8        = unsafe { NonZeroU8::new_unchecked(11) };
+#[cfg(feature = "quic")]
+pub(crate) const ROLLING_AVERAGE_QUIC_MAX_RESPONSE_TIMES: NonZeroU8 = unsafe { NonZeroU8::new_unchecked(13) };
+
+static TCP_QUERY_RUNNER_POLL_LOOP: PollLoopStats = PollLoopStats::new("TcpQueryRunner");
+static TCP_QUERY_POLL_LOOP: PollLoopStats = PollLoopStats::new("TcpQuery");
+static UDP_QUERY_RUNNER_POLL_LOOP: PollLoopStats = PollLoopStats::new("UdpQueryRunner");
+static UDP_QUERY_POLL_LOOP: PollLoopStats = PollLoopStats::new("UdpQuery");
+
+/// How hard the poll loops of the TCP and UDP query state machines have been working, across every
+/// socket in the process.
+pub fn poll_loop_stats() -> [PollLoopSnapshot; 4] {
+    [
+        TCP_QUERY_RUNNER_POLL_LOOP.snapshot(),
+        TCP_QUERY_POLL_LOOP.snapshot(),
+        UDP_QUERY_RUNNER_POLL_LOOP.snapshot(),
+        UDP_QUERY_POLL_LOOP.snapshot(),
+    ]
+}
 
 fn bound<T>(value: T, lower_bound: T, upper_bound: T) -> T where T: Ord {
     debug_assert!(lower_bound <= upper_bound);
     value.clamp(lower_bound, upper_bound)
 }
 
+/// Sends a copy of the datagram after each of the delays chosen by the fault injector. Delayed
+/// copies are sent from their own tasks so that the query carries on as if they were in flight.
+fn send_with_faults(udp_socket: Arc<net::UdpSocket>, datagram: &[u8], deliveries: TinyVec<[Duration; 2]>) {
+    let datagram: Arc<[u8]> = Arc::from(datagram);
+    for delay in deliveries {
+        let udp_socket = udp_socket.clone();
+        let datagram = datagram.clone();
+        task::spawn(async move {
+            if !delay.is_zero() {
+                tokio::time::sleep(delay).await;
+            }
+            let _ = udp_socket.send(&datagram).await;
+        });
+    }
+}
+
 #[pin_project(project = MixedQueryProj)]
 pub enum MixedQuery<'a, 'b, 'c, 'd> {
     Tcp(#[pin] TcpQuery<'a, 'b, 'c, 'd>),
     Udp(#[pin] UdpQuery<'a, 'b, 'c, 'd>),
+    #[cfg(feature = "quic")]
+    Quic(#[pin] QuicQuery<'a, 'b, 'c, 'd>),
+    #[cfg(feature = "https")]
+    Https(#[pin] HttpsQuery<'a, 'b>),
+    Unsupported(QueryOpt),
 }
 
 impl<'a, 'b, 'c, 'd> Future for MixedQuery<'a, 'b, 'c, 'd> {
@@ -119,8 +201,389 @@ impl<'a, 'b, 'c, 'd> Future for MixedQuery<'a, 'b, 'c, 'd> {
         match self.project() {
             MixedQueryProj::Tcp(tcp_query) => tcp_query.poll(cx),
             MixedQueryProj::Udp(udp_query) => udp_query.poll(cx),
+            #[cfg(feature = "quic")]
+            MixedQueryProj::Quic(quic_query) => quic_query.poll(cx),
+            #[cfg(feature = "https")]
+            MixedQueryProj::Https(https_query) => https_query.poll(cx),
+            MixedQueryProj::Unsupported(transport) => Poll::Ready(Err(errors::QueryError::UnsupportedTransport(*transport))),
+        }
+    }
+}
+
+impl<'a, 'b, 'c, 'd> MixedQuery<'a, 'b, 'c, 'd> {
+    /// Stops waiting on the query once the token is awoken and returns `QueryError::Cancelled`.
+    /// The query itself is stopped, and removed from the socket, once every caller waiting on it
+    /// has been cancelled.
+    pub fn with_cancellation(mut self, cancellation: Option<&AwakeToken>) -> Self {
+        let cancellation = cancellation.map(AwakeToken::awoken);
+        match &mut self {
+            Self::Tcp(tcp_query) => tcp_query.cancellation = cancellation,
+            Self::Udp(udp_query) => udp_query.cancellation = cancellation,
+            #[cfg(feature = "quic")]
+            Self::Quic(quic_query) => quic_query.cancellation = cancellation,
+            #[cfg(feature = "https")]
+            Self::Https(https_query) => https_query.cancellation = cancellation,
+            Self::Unsupported(_) => (),
+        }
+        self
+    }
+
+    /// How the query was carried to the server. This is `None` until the query has been handed to
+    /// a runner (its own or an identical one that was already in flight) and for unsupported
+    /// transports. The details are only final once the query has completed.
+    pub fn details(&self) -> Option<QueryDetails> {
+        let (query, progress) = match self {
+            Self::Tcp(tcp_query) => (&*tcp_query.query, tcp_query.progress.as_ref()?),
+            Self::Udp(udp_query) => (&*udp_query.query, udp_query.progress.as_ref()?),
+            #[cfg(feature = "quic")]
+            Self::Quic(quic_query) => (&*quic_query.query, quic_query.progress.as_ref()?),
+            #[cfg(feature = "https")]
+            Self::Https(https_query) => (&*https_query.query, https_query.progress.as_ref()?),
+            Self::Unsupported(_) => return None,
+        };
+        let transport = match progress.fell_back_to_tcp.load(Ordering::Acquire) {
+            true => MixedTransport::Tcp,
+            false => progress.transport,
+        };
+        Some(QueryDetails {
+            transport,
+            retransmissions: progress.sends.load(Ordering::Acquire).saturating_sub(1),
+            retransmission_timeout: progress.retransmission_timeout,
+            timeout: progress.timeout,
+            query_size: progress.query_size.load(Ordering::Acquire),
+            udp_payload_size: udp_payload_size(query),
+        })
+    }
+
+    /// The response to a signed UDP or TCP query, exactly as it was received, so that its
+    /// signature can be checked over the bytes the server signed. This is `None` for queries that
+    /// are not signed and until the query has completed. The bytes can only be taken once.
+    pub fn take_response_wire(&self) -> Option<Vec<u8>> {
+        match self {
+            Self::Tcp(tcp_query) => tcp_query.progress.as_ref()?.take_wire(),
+            Self::Udp(udp_query) => udp_query.progress.as_ref()?.take_wire(),
+            _ => None,
+        }
+    }
+}
+
+/// The transport that carried a query sent through a `MixedSocket`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MixedTransport {
+    Udp,
+    Tcp,
+    /// DNS over QUIC, for sockets created with a QUIC server name.
+    #[cfg(feature = "quic")]
+    Quic,
+    /// DNS over HTTPS, for sockets created with a DoH server name and path.
+    #[cfg(feature = "https")]
+    Https,
+}
+
+/// How a query sent through a `MixedSocket` was carried. Queries that join an identical query
+/// that is already in flight report the details of that query.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct QueryDetails {
+    /// The transport of the last transmission. UDP queries switch to TCP once all of their
+    /// retransmissions have been dropped.
+    pub transport: MixedTransport,
+    /// The number of times the query was sent again after the first transmission, including the
+    /// switch to TCP.
+    pub retransmissions: u8,
+    /// The adaptive UDP retransmission timeout in effect when the query was started. TCP queries
+    /// are not retransmitted.
+    pub retransmission_timeout: Option<Duration>,
+    /// The adaptive timeout in effect when the query was started. For UDP queries, this is how
+    /// long the last retransmission is given before switching to TCP.
+    pub timeout: Duration,
+    /// The length of the last transmission in octets, excluding any two octet length prefix.
+    pub query_size: usize,
+    /// The UDP payload size advertised in the query's OPT record, if it has one.
+    pub udp_payload_size: Option<u16>,
+}
+
+/// What the runner of an in-flight query has done so far. It is shared with every caller waiting
+/// on the query so that each of them can report how it was carried.
+#[derive(Debug)]
+struct QueryProgress {
+    transport: MixedTransport,
+    retransmission_timeout: Option<Duration>,
+    timeout: Duration,
+    sends: AtomicU8,
+    query_size: AtomicUsize,
+    fell_back_to_tcp: AtomicBool,
+    /// The latest time that any of the callers waiting on the query still want a response by.
+    /// `None` if at least one of them is willing to wait for as long as the query takes.
+    deadline: std::sync::Mutex<Option<Instant>>,
+    /// The number of callers waiting on the query that have not cancelled it. Callers that cannot
+    /// be cancelled stay for as long as the query runs.
+    followers: AtomicUsize,
+    /// Awoken once every caller waiting on the query has cancelled it, so that the runner stops.
+    abandoned: AwakeToken,
+    /// The casing the question names were sent with, if they were randomized.
+    case_nonce: Option<CaseNonce>,
+    /// For signed queries, the response exactly as it was received. Its signature is checked over
+    /// these bytes since the response may not be written out the same way again.
+    response_wire: Option<std::sync::Mutex<Option<Vec<u8>>>>,
+}
+
+impl QueryProgress {
+    #[inline]
+    fn tcp(tcp_timeout: Duration, deadline: Option<Instant>, signed: bool) -> Arc<Self> {
+        Arc::new(Self {
+            transport: MixedTransport::Tcp,
+            retransmission_timeout: None,
+            timeout: tcp_timeout,
+            sends: AtomicU8::new(0),
+            query_size: AtomicUsize::new(0),
+            fell_back_to_tcp: AtomicBool::new(false),
+            deadline: std::sync::Mutex::new(deadline),
+            followers: AtomicUsize::new(1),
+            abandoned: AwakeToken::new(),
+            case_nonce: None,
+            response_wire: signed.then(|| std::sync::Mutex::new(None)),
+        })
+    }
+
+    #[cfg(feature = "quic")]
+    #[inline]
+    fn quic(quic_timeout: Duration, deadline: Option<Instant>) -> Arc<Self> {
+        Arc::new(Self {
+            transport: MixedTransport::Quic,
+            retransmission_timeout: None,
+            timeout: quic_timeout,
+            sends: AtomicU8::new(0),
+            query_size: AtomicUsize::new(0),
+            fell_back_to_tcp: AtomicBool::new(false),
+            deadline: std::sync::Mutex::new(deadline),
+            followers: AtomicUsize::new(1),
+            abandoned: AwakeToken::new(),
+            case_nonce: None,
+            response_wire: None,
+        })
+    }
+
+    /// DNS over HTTPS queries are not given a timeout of their own. They are only bounded by the
+    /// deadline, which is reported as their timeout.
+    #[cfg(feature = "https")]
+    #[inline]
+    fn https(deadline: Option<Instant>) -> Arc<Self> {
+        Arc::new(Self {
+            transport: MixedTransport::Https,
+            retransmission_timeout: None,
+            timeout: deadline.map_or(Duration::ZERO, |deadline| deadline.saturating_duration_since(Instant::now())),
+            sends: AtomicU8::new(0),
+            query_size: AtomicUsize::new(0),
+            fell_back_to_tcp: AtomicBool::new(false),
+            deadline: std::sync::Mutex::new(deadline),
+            followers: AtomicUsize::new(1),
+            abandoned: AwakeToken::new(),
+            case_nonce: None,
+            response_wire: None,
+        })
+    }
+
+    #[inline]
+    fn udp(udp_retransmission_timeout: Duration, udp_timeout: Duration, deadline: Option<Instant>, case_nonce: Option<CaseNonce>, signed: bool) -> Arc<Self> {
+        Arc::new(Self {
+            transport: MixedTransport::Udp,
+            retransmission_timeout: Some(udp_retransmission_timeout),
+            timeout: udp_timeout,
+            sends: AtomicU8::new(0),
+            query_size: AtomicUsize::new(0),
+            fell_back_to_tcp: AtomicBool::new(false),
+            deadline: std::sync::Mutex::new(deadline),
+            followers: AtomicUsize::new(1),
+            abandoned: AwakeToken::new(),
+            case_nonce,
+            response_wire: signed.then(|| std::sync::Mutex::new(None)),
+        })
+    }
+
+    /// Whether the response may be handed to the callers. If the question names were sent with
+    /// random casing, the response must echo it. It is then given back the casing that was asked.
+    #[inline]
+    fn accept(&self, response: &mut Message) -> bool {
+        self.case_nonce.as_ref().is_none_or(|case_nonce| case_nonce.verify(response))
+    }
+
+    /// Keeps a copy of the response as it was received, if the query was signed.
+    #[inline]
+    fn keep_wire(&self, wire: &[u8]) {
+        if let Some(response_wire) = &self.response_wire {
+            let mut w_response_wire = response_wire.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+            *w_response_wire = Some(wire.to_vec());
+            drop(w_response_wire);
+        }
+    }
+
+    #[inline]
+    fn take_wire(&self) -> Option<Vec<u8>> {
+        let response_wire = self.response_wire.as_ref()?;
+        let mut w_response_wire = response_wire.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let wire = w_response_wire.take();
+        drop(w_response_wire);
+        wire
+    }
+
+    /// The runner keeps going for as long as the most patient caller is interested.
+    #[inline]
+    fn extend_deadline(&self, deadline: Option<Instant>) {
+        let mut w_deadline = self.deadline.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        *w_deadline = match (*w_deadline, deadline) {
+            (Some(current), Some(deadline)) => Some(max(current, deadline)),
+            (None, _) | (_, None) => None,
+        };
+        drop(w_deadline);
+    }
+
+    /// Called when another caller starts waiting on the query. Returns `false` if every caller
+    /// has already cancelled the query, in which case the runner is stopping and the caller must
+    /// start a query of its own.
+    #[inline]
+    fn follow(&self, deadline: Option<Instant>) -> bool {
+        let followed = self.followers.fetch_update(Ordering::AcqRel, Ordering::Acquire, |followers| {
+            (followers > 0).then(|| followers + 1)
+        });
+        if followed.is_err() {
+            return false;
+        }
+        self.extend_deadline(deadline);
+        true
+    }
+
+    /// Called when a caller cancels the query. The last caller to leave stops the runner.
+    #[inline]
+    fn abandon(&self) {
+        if self.followers.fetch_sub(1, Ordering::AcqRel) == 1 {
+            self.abandoned.awake();
+        }
+    }
+
+    #[inline]
+    fn deadline(&self) -> Option<Instant> {
+        let r_deadline = self.deadline.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let deadline = *r_deadline;
+        drop(r_deadline);
+        deadline
+    }
+
+    /// When a timeout of this length, starting now, should fire. Timeouts are cut short at the
+    /// deadline so that the runner does not retransmit a query that no one is waiting for.
+    #[inline]
+    fn timeout_at(&self, timeout: Duration) -> Instant {
+        let now = Instant::now();
+        let timeout_at = now.checked_add(timeout).unwrap_or(now);
+        match self.deadline() {
+            Some(deadline) => min(timeout_at, deadline),
+            None => timeout_at,
+        }
+    }
+
+    #[inline]
+    fn deadline_passed(&self) -> bool {
+        self.deadline().is_some_and(|deadline| deadline <= Instant::now())
+    }
+
+    #[inline]
+    fn record_send(&self, transport: MixedTransport, query_size: usize) {
+        let _ = self.sends.fetch_update(Ordering::AcqRel, Ordering::Acquire, |sends| Some(sends.saturating_add(1)));
+        self.query_size.store(query_size, Ordering::Release);
+        if (self.transport == MixedTransport::Udp) && (transport == MixedTransport::Tcp) {
+            self.fell_back_to_tcp.store(true, Ordering::Release);
+        }
+    }
+}
+
+/// Whether the query has a TSIG or SIG(0) record. A signed query is sent with the casing it was
+/// signed with, and never shares a response with an identical query since a signed response
+/// answers one request.
+#[inline]
+fn is_signed(query: &Message) -> bool {
+    query.additional.iter().any(|record| matches!(record.get_rtype(), RType::TSIG | RType::SIG))
+}
+
+/// The question names of a query, as they were asked and as they were sent after their letters
+/// were given random casing. Servers copy the question from the query into the response, so an
+/// off-path attacker spoofing a response also has to guess the casing.
+///
+/// https://datatracker.ietf.org/doc/html/draft-vixie-dnsext-dns0x20-00
+#[derive(Debug)]
+struct CaseNonce {
+    asked: TinyVec<[Question; 1]>,
+    sent: TinyVec<[Question; 1]>,
+}
+
+impl CaseNonce {
+    /// Gives each letter of the query's question names a random case.
+    fn randomize(query: &mut Message) -> Self {
+        let asked = query.question.clone();
+        for question in query.question.iter_mut() {
+            let mut qname = question.qname().clone();
+            qname.set_case_with(rand::random::<bool>);
+            *question = question.with_new_qname(qname);
+        }
+        Self { asked, sent: query.question.clone() }
+    }
+
+    /// Checks that the response's question echoes the casing that was sent. If it does, the
+    /// question and every name in the response that is one of the question names are given back
+    /// the casing that was asked, so that the randomization is not visible past the socket.
+    fn verify(&self, response: &mut Message) -> bool {
+        let echoed = (response.question.len() == self.sent.len())
+            && response.question.iter()
+                .zip(self.sent.iter())
+                .all(|(received, sent)| received.matches(sent) && received.qname().matches_case(sent.qname()));
+        if !echoed {
+            return false;
+        }
+        let records = response.answer.iter_mut()
+            .chain(response.authority.iter_mut())
+            .chain(response.additional.iter_mut());
+        for record in records {
+            self.restore_case(record);
+        }
+        response.question = self.asked.clone();
+        true
+    }
+
+    /// Gives the record's owner name and the names in its data the asked casing if they are one
+    /// of the sent question names.
+    fn restore_case(&self, record: &mut ResourceRecord) {
+        if let Some(name) = self.asked_name(record.get_name()) {
+            record.set_name(name);
+        }
+        let rdata = match record.get_rdata() {
+            RecordData::CNAME(cname) => self.asked_name(cname.primary_name()).map(|name| RecordData::CNAME(CNAME::new(name))),
+            RecordData::NS(ns) => self.asked_name(ns.name_server_domain_name()).map(|name| RecordData::NS(NS::new(name))),
+            RecordData::PTR(ptr) => self.asked_name(ptr.ptr_domain_name()).map(|name| RecordData::PTR(PTR::new(name))),
+            RecordData::SOA(soa) => {
+                let mname = self.asked_name(soa.main_domain_name());
+                let rname = self.asked_name(soa.responsible_mailbox_domain_name());
+                (mname.is_some() || rname.is_some()).then(|| RecordData::SOA(SOA::new(
+                    mname.unwrap_or_else(|| soa.main_domain_name().clone()),
+                    rname.unwrap_or_else(|| soa.responsible_mailbox_domain_name().clone()),
+                    *soa.serial(),
+                    *soa.refresh(),
+                    *soa.retry(),
+                    *soa.expire(),
+                    *soa.minimum(),
+                )))
+            },
+            _ => None,
+        };
+        if let Some(rdata) = rdata {
+            *record.get_rdata_mut() = rdata;
         }
     }
+
+    /// The asked casing of the name, if it has the casing of one of the sent question names.
+    fn asked_name(&self, name: &CDomainName) -> Option<CDomainName> {
+        self.sent.iter()
+            .zip(self.asked.iter())
+            .find(|(sent, _)| name.matches_case(sent.qname()))
+            .map(|(_, asked)| asked.qname().clone())
+    }
 }
 
 enum TcpResponseTime {
@@ -149,6 +612,19 @@ enum LoopPoll {
     Pending,
 }
 
+/// Whether the error means that the connection the query acquired was closed before the query was
+/// written to it. Connections can be closed by another task (for example, because they were idle)
+/// at any point after they are acquired, so a query that loses this race is retried on a new
+/// connection.
+#[inline]
+fn is_tcp_reuse_race(error: &errors::QueryError) -> bool {
+    match error {
+        errors::QueryError::TcpSocket(errors::TcpSocketError::Shutdown) => true,
+        errors::QueryError::TcpSend(errors::TcpSendError::Io(_)) => true,
+        _ => false,
+    }
+}
+
 #[pin_project(PinnedDrop)]
 struct TcpQueryRunner<'a, 'b, 'c, 'd, 'e, 'f, 'g, 'h>
 where
@@ -158,8 +634,15 @@ where
     query: &'b mut Message,
     tcp_timeout: &'h Duration,
     tcp_start_time: Instant,
+    progress: Arc<QueryProgress>,
+    /// Set once the query has been retried because the connection it acquired was closed before
+    /// the query could be written to it.
+    reuse_race_retried: bool,
     #[pin]
     timeout: Sleep,
+    /// Ready once every caller waiting on the query has cancelled it.
+    #[pin]
+    abandoned: AwokenToken,
     #[pin]
     result_receiver: once_watch::Receiver<Result<Message, errors::QueryError>>,
     #[pin]
@@ -171,13 +654,16 @@ where
     'g: 'f
 {
     #[inline]
-    pub fn new(socket: &'a Arc<MixedSocket>, query: &'b mut Message, result_receiver: once_watch::Receiver<Result<Message, errors::QueryError>>, tcp_timeout: &'h Duration) -> Self {
+    pub fn new(socket: &'a Arc<MixedSocket>, query: &'b mut Message, result_receiver: once_watch::Receiver<Result<Message, errors::QueryError>>, tcp_timeout: &'h Duration, progress: Arc<QueryProgress>) -> Self {
         Self {
             socket,
             query,
             tcp_timeout,
             tcp_start_time: Instant::now(),
-            timeout: tokio::time::sleep(*tcp_timeout),
+            timeout: tokio::time::sleep_until(progress.timeout_at(*tcp_timeout)),
+            abandoned: progress.abandoned.awoken(),
+            progress,
+            reuse_race_retried: false,
             result_receiver,
             inner: InnerTQ::Fresh,
         }
@@ -232,6 +718,22 @@ impl<'a, 'b, 'c, 'd, 'e, 'f, 'g, 'h> Future for TcpQueryRunner<'a, 'b, 'c, 'd, '
         let mut this = self.as_mut().project();
         match this.inner.as_mut().project() {
             InnerTQProj::Fresh
+          | InnerTQProj::Running { tq_socket: _, send_query: _ } if this.abandoned.as_mut().poll(cx).is_ready() => {
+                // Every caller has cancelled the query. Like a passed deadline, this says nothing
+                // about the connection.
+                let _ = this.result_receiver.get_sender().send(Err(errors::QueryError::Cancelled));
+
+                this.inner.set_cleanup(TcpResponseTime::None, this.socket);
+            },
+            InnerTQProj::Fresh
+          | InnerTQProj::Running { tq_socket: _, send_query: _ } if this.progress.deadline_passed() => {
+                // Every caller has given up on the query. It did not time out so it says nothing
+                // about the connection.
+                let _ = this.result_receiver.get_sender().send(Err(errors::QueryError::Timeout));
+
+                this.inner.set_cleanup(TcpResponseTime::None, this.socket);
+            },
+            InnerTQProj::Fresh
           | InnerTQProj::Running { tq_socket: _, send_query: _ } => {
                 if let Poll::Ready(()) = this.timeout.as_mut().poll(cx) {
                     let _ = this.result_receiver.get_sender().send(Err(errors::QueryError::Timeout));
@@ -247,7 +749,12 @@ impl<'a, 'b, 'c, 'd, 'e, 'f, 'g, 'h> Future for TcpQueryRunner<'a, 'b, 'c, 'd, '
             },
         }
 
+        let mut budget = PollBudget::new(&TCP_QUERY_RUNNER_POLL_LOOP);
         loop {
+            if budget.exhausted(cx) {
+                return Poll::Pending;
+            }
+
             let mut this = self.as_mut().project();
             match this.inner.as_mut().project() {
                 InnerTQProj::Fresh => {
@@ -295,6 +802,15 @@ impl<'a, 'b, 'c, 'd, 'e, 'f, 'g, 'h> Future for TcpQueryRunner<'a, 'b, 'c, 'd, '
                             let tcp_socket = tcp_socket.clone();
 
                             if let PollSocket::Error(error) = tq_socket.poll(this.socket, cx) {
+                                if !*this.reuse_race_retried && is_tcp_reuse_race(&errors::QueryError::from(error.clone())) {
+                                    *this.reuse_race_retried = true;
+                                    this.socket.tcp_reuse_races.fetch_add(1, Ordering::Relaxed);
+                                    this.inner.set_running(QSendType::Retransmit);
+
+                                    // Next loop will acquire a new connection and send the query
+                                    // on it.
+                                    continue;
+                                }
                                 let _ = this.result_receiver.get_sender().send(Err(errors::QueryError::from(error)));
 
                                 this.inner.set_cleanup(TcpResponseTime::None, this.socket);
@@ -304,20 +820,22 @@ impl<'a, 'b, 'c, 'd, 'e, 'f, 'g, 'h> Future for TcpQueryRunner<'a, 'b, 'c, 'd, '
                                 continue;
                             }
 
-                            let mut raw_message = [0_u8; MAX_MESSAGE_SIZE as usize];
-                            let mut write_wire = WriteWire::from_bytes(&mut raw_message);
-                            if let Err(wire_error) = this.query.to_wire_format_with_two_octet_length(&mut write_wire, &mut Some(CompressionMap::new())) {
-                                let _ = this.result_receiver.get_sender().send(Err(errors::QueryError::from(errors::TcpSendError::from(wire_error))));
+                            let raw_message = match this.query.to_wire_vec(&mut Some(CompressionMap::new())) {
+                                Ok(raw_message) => raw_message,
+                                Err(wire_error) => {
+                                    let _ = this.result_receiver.get_sender().send(Err(errors::QueryError::from(errors::TcpSendError::from(wire_error))));
 
-                                this.inner.set_cleanup(TcpResponseTime::None, this.socket);
+                                    this.inner.set_cleanup(TcpResponseTime::None, this.socket);
 
-                                // Next loop will poll for the in-flight map lock to clean up the
-                                // query ID before returning the response.
-                                continue;
+                                    // Next loop will poll for the in-flight map lock to clean up
+                                    // the query ID before returning the response.
+                                    continue;
+                                },
                             };
-                            let wire_length = write_wire.current_len();
+                            let wire_length = TWO_OCTET_LENGTH + raw_message.len();
 
                             println!("Sending on TCP socket {} {{ drop rate {:.2}%, truncation rate {:.2}%, response time {:.2} ms, timeout {} ms }} :: {:?}", this.socket.upstream_socket, this.socket.average_dropped_tcp_packets() * 100.0, this.socket.average_truncated_udp_packets() * 100.0, this.socket.average_tcp_response_time(), this.tcp_timeout.as_millis(), this.query);
+                            this.progress.record_send(MixedTransport::Tcp, wire_length);
 
                             let send_query_future = async move {
                                 let socket = socket;
@@ -326,7 +844,7 @@ impl<'a, 'b, 'c, 'd, 'e, 'f, 'g, 'h> Future for TcpQueryRunner<'a, 'b, 'c, 'd, '
 
                                 socket.recent_messages_sent.store(true, Ordering::Release);
                                 let mut w_tcp_stream = tcp_socket.lock().await;
-                                let bytes_written = w_tcp_stream.write(&raw_message[..wire_length]).await?;
+                                let bytes_written = write_with_two_octet_length(&mut *w_tcp_stream, &raw_message).await?;
                                 drop(w_tcp_stream);
                                 // Verify that the correct number of bytes were written.
                                 if bytes_written != wire_length {
@@ -346,7 +864,17 @@ impl<'a, 'b, 'c, 'd, 'e, 'f, 'g, 'h> Future for TcpQueryRunner<'a, 'b, 'c, 'd, '
                             // We don't poll the receiver until the QSendQuery state is Complete.
                             match (send_query_future.as_mut().poll(cx), tq_socket.poll(this.socket, cx)) {
                                 (_, PollSocket::Error(error)) => {
-                                    let _ = this.result_receiver.get_sender().send(Err(errors::QueryError::from(error)));
+                                    let error = errors::QueryError::from(error);
+                                    if !*this.reuse_race_retried && is_tcp_reuse_race(&error) {
+                                        *this.reuse_race_retried = true;
+                                        this.socket.tcp_reuse_races.fetch_add(1, Ordering::Relaxed);
+                                        this.inner.set_running(QSendType::Retransmit);
+
+                                        // Next loop will acquire a new connection and send the
+                                        // query on it.
+                                        continue;
+                                    }
+                                    let _ = this.result_receiver.get_sender().send(Err(error));
 
                                     this.inner.set_cleanup(TcpResponseTime::None, this.socket);
 
@@ -355,7 +883,17 @@ impl<'a, 'b, 'c, 'd, 'e, 'f, 'g, 'h> Future for TcpQueryRunner<'a, 'b, 'c, 'd, '
                                     continue;
                                 },
                                 (Poll::Ready(Err(error)), _) => {
-                                    let _ = this.result_receiver.get_sender().send(Err(errors::QueryError::from(error)));
+                                    let error = errors::QueryError::from(error);
+                                    if !*this.reuse_race_retried && is_tcp_reuse_race(&error) {
+                                        *this.reuse_race_retried = true;
+                                        this.socket.tcp_reuse_races.fetch_add(1, Ordering::Relaxed);
+                                        this.inner.set_running(QSendType::Retransmit);
+
+                                        // Next loop will acquire a new connection and send the
+                                        // query on it.
+                                        continue;
+                                    }
+                                    let _ = this.result_receiver.get_sender().send(Err(error));
 
                                     this.inner.set_cleanup(TcpResponseTime::None, this.socket);
 
@@ -472,9 +1010,10 @@ impl<'a, 'b, 'c, 'd, 'e, 'f, 'g, 'h> Future for TcpQueryRunner<'a, 'b, 'c, 'd, '
                                 },
                                 TcpResponseTime::None => (),
                             }
+                            this.socket.peer.save_tcp_timeout(w_active_queries.tcp_timeout);
 
                             w_active_queries.in_flight.remove(&this.query.id);
-                            w_active_queries.tcp_only.remove(&this.query.question);
+                            remove_joinable(&mut w_active_queries.tcp_only, &this.query.question_key(), this.query.id);
                             drop(w_active_queries);
 
                             this.inner.set_complete();
@@ -502,7 +1041,7 @@ impl<'a, 'b, 'c, 'd, 'e, 'f, 'g, 'h> PinnedDrop for TcpQueryRunner<'a, 'b, 'c, '
         async fn cleanup(socket: Arc<MixedSocket>, query: Message) {
             let mut w_active_queries = socket.active_queries.write().await;
             let _ = w_active_queries.in_flight.remove(&query.id);
-            let _ = w_active_queries.tcp_only.remove(&query.question);
+            remove_joinable(&mut w_active_queries.tcp_only, &query.question_key(), query.id);
             drop(w_active_queries);
         }
 
@@ -521,33 +1060,69 @@ impl<'a, 'b, 'c, 'd, 'e, 'f, 'g, 'h> PinnedDrop for TcpQueryRunner<'a, 'b, 'c, '
     }
 }
 
-#[pin_project]
+#[pin_project(PinnedDrop)]
 struct TcpQuery<'a, 'b, 'c, 'd>
 where
     'a: 'd
 {
     socket: &'a Arc<MixedSocket>,
     query: &'b mut Message,
+    deadline: Option<Instant>,
+    progress: Option<Arc<QueryProgress>>,
+    /// Ready once the caller cancels the query.
+    #[pin]
+    cancellation: Option<AwokenToken>,
     #[pin]
     inner: QInitQuery<'c, 'd, ActiveQueries>,
 }
 
 impl<'a, 'b, 'c, 'd> TcpQuery<'a, 'b, 'c, 'd> {
     #[inline]
-    pub fn new(socket: &'a Arc<MixedSocket>, query: &'b mut Message) -> Self {
+    pub fn new(socket: &'a Arc<MixedSocket>, query: &'b mut Message, deadline: Option<Instant>) -> Self {
         Self {
             socket,
             query,
+            deadline,
+            progress: None,
+            cancellation: None,
             inner: QInitQuery::Fresh,
         }
     }
 }
 
+#[pinned_drop]
+impl<'a, 'b, 'c, 'd> PinnedDrop for TcpQuery<'a, 'b, 'c, 'd> {
+    fn drop(self: Pin<&mut Self>) {
+        // Callers are usually dropped rather than polled once they are cancelled. They leave the
+        // query they were following all the same.
+        let this = self.project();
+        let cancelled = this.cancellation.as_pin_mut().is_some_and(|cancellation| cancellation.try_awoken());
+        if let (true, QInitQuery::Following(_), Some(progress)) = (cancelled, &*this.inner, this.progress.as_ref()) {
+            progress.abandon();
+        }
+    }
+}
+
 impl<'a, 'b, 'c, 'd> Future for TcpQuery<'a, 'b, 'c, 'd> {
     type Output = Result<Message, errors::QueryError>;
 
     fn poll(mut self: std::pin::Pin<&mut Self>, cx: &mut std::task::Context<'_>) -> std::task::Poll<Self::Output> {
+        let mut this = self.as_mut().project();
+        if let Some(Poll::Ready(())) = this.cancellation.as_mut().as_pin_mut().map(|cancellation| cancellation.poll(cx)) {
+            if let (QInitQueryProj::Following(_), Some(progress)) = (this.inner.as_mut().project(), this.progress.as_ref()) {
+                progress.abandon();
+            }
+            this.inner.set_complete();
+
+            return Poll::Ready(Err(errors::QueryError::Cancelled));
+        }
+
+        let mut budget = PollBudget::new(&TCP_QUERY_POLL_LOOP);
         loop {
+            if budget.exhausted(cx) {
+                return Poll::Pending;
+            }
+
             let mut this = self.as_mut().project();
             match this.inner.as_mut().project() {
                 QInitQueryProj::Fresh => {
@@ -559,10 +1134,10 @@ impl<'a, 'b, 'c, 'd> Future for TcpQuery<'a, 'b, 'c, 'd> {
                 QInitQueryProj::ReadActiveQuery(r_active_queries) => {
                     match r_active_queries.as_mut().poll(cx) {
                         Poll::Ready(r_active_queries) => {
-                            match r_active_queries.tcp_only.get(&this.query.question) {
-                                Some((query_id, result_sender)) => {
-                                    this.query.id = *query_id;
-                                    let result_receiver = result_sender.subscribe();
+                            match r_active_queries.join(&[&r_active_queries.tcp_only], this.query, *this.deadline) {
+                                Some((query_id, result_receiver, progress)) => {
+                                    this.query.id = query_id;
+                                    *this.progress = Some(progress);
                                     drop(r_active_queries);
 
                                     this.inner.set_following(result_receiver);
@@ -589,10 +1164,10 @@ impl<'a, 'b, 'c, 'd> Future for TcpQuery<'a, 'b, 'c, 'd> {
                 QInitQueryProj::WriteActiveQuery(w_active_queries) => {
                     match w_active_queries.as_mut().poll(cx) {
                         Poll::Ready(mut w_active_queries) => {
-                            match w_active_queries.tcp_only.get(&this.query.question) {
-                                Some((query_id, result_sender)) => {
-                                    this.query.id = *query_id;
-                                    let result_receiver = result_sender.subscribe();
+                            match w_active_queries.join(&[&w_active_queries.tcp_only], this.query, *this.deadline) {
+                                Some((query_id, result_receiver, progress)) => {
+                                    this.query.id = query_id;
+                                    *this.progress = Some(progress);
                                     drop(w_active_queries);
 
                                     this.inner.set_following(result_receiver);
@@ -615,18 +1190,23 @@ impl<'a, 'b, 'c, 'd> Future for TcpQuery<'a, 'b, 'c, 'd> {
                                         // keys? May want to verify that the list isn't full.
                                     }
 
+                                    let progress = QueryProgress::tcp(w_active_queries.tcp_timeout, *this.deadline, is_signed(this.query));
                                     let join_handle = tokio::spawn({
                                         let tcp_timeout = w_active_queries.tcp_timeout;
                                         let result_receiver = result_sender.subscribe();
                                         let socket = this.socket.clone();
                                         let mut query = this.query.clone();
+                                        let progress = progress.clone();
                                         async move {
-                                            TcpQueryRunner::new(&socket, &mut query, result_receiver, &tcp_timeout).await;
+                                            TcpQueryRunner::new(&socket, &mut query, result_receiver, &tcp_timeout, progress).await;
                                         }
                                     });
 
-                                    w_active_queries.in_flight.insert(this.query.id, (result_sender.clone(), join_handle));
-                                    w_active_queries.tcp_only.insert(this.query.question.clone(), (this.query.id, result_sender));
+                                    w_active_queries.in_flight.insert(this.query.id, (result_sender.clone(), join_handle, progress.clone()));
+                                    *this.progress = Some(progress);
+                                    if !is_signed(this.query) {
+                                        w_active_queries.tcp_only.insert(this.query.question_key(), (this.query.id, result_sender));
+                                    }
                                     drop(w_active_queries);
 
                                     this.inner.set_following(result_receiver);
@@ -684,6 +1264,16 @@ impl TcpSocket for MixedSocket {
         &self.tcp
     }
 
+    #[inline]
+    fn listeners(&self) -> &ListenerTracker {
+        &self.listeners
+    }
+
+    #[inline]
+    fn traffic_class(&self) -> Option<TrafficClass> {
+        self.traffic_class
+    }
+
     #[inline]
     async fn listen(self: Arc<Self>, mut tcp_reader: OwnedReadHalf, kill_tcp: AwakeToken) {
         pin!(let kill_tcp_awoken = kill_tcp.awoken(););
@@ -698,14 +1288,20 @@ impl TcpSocket for MixedSocket {
                     println!("TCP Socket {} Timed Out. Shutting down TCP Listener.", self.upstream_socket);
                     break;
                 },
-                response = read_stream_message::<{ MAX_MESSAGE_SIZE as usize }>(&mut tcp_reader) => {
+                response = read_stream_message_with_wire::<{ MAX_MESSAGE_SIZE as usize }>(&mut tcp_reader, Some(self.upstream_socket)) => {
                     match response {
-                        Ok(response) => {
+                        Ok((mut response, wire)) => {
                             self.recent_messages_received.store(true, Ordering::Release);
                             let response_id = response.id;
                             let r_active_queries = self.active_queries.read().await;
-                            if let Some((sender, _)) = r_active_queries.in_flight.get(&response_id) {
-                                let _ = sender.send(Ok(response));
+                            if let Some((sender, _, progress)) = r_active_queries.in_flight.get(&response_id) {
+                                if progress.accept(&mut response) {
+                                    progress.keep_wire(&wire);
+                                    let _ = sender.send(Ok(response));
+                                } else {
+                                    self.case_mismatches.fetch_add(1, Ordering::Relaxed);
+                                    println!("TCP Socket {} discarded a response to query {response_id} that did not echo the casing of the question. It may have been spoofed.", self.upstream_socket);
+                                }
                             };
                             drop(r_active_queries);
                             // Cleanup is handled by the management processes. This
@@ -753,92 +1349,82 @@ impl MixedSocket {
     }
 }
 
+#[cfg(feature = "quic")]
+enum QuicResponseTime {
+    Dropped,
+    Responded(Duration),
+    /// `None` is used for cases where the message was never sent (e.g. the connection could not be
+    /// established) or the connection was closed before a response could be received.
+    None,
+}
+
+/// Sends one query over the socket's QUIC connection. The `QuicSocket` opens a new bidirectional
+/// stream for each query, so the runner only has to enforce the timeout and keep the rolling
+/// averages up to date.
+#[cfg(feature = "quic")]
 #[pin_project(PinnedDrop)]
-struct UdpQueryRunner<'a, 'b, 'c, 'd, 'f, 'g, 'h, 'i>
+struct QuicQueryRunner<'a, 'b, 'f, 'g>
 where
-    'a: 'd + 'h
+    'a: 'g
 {
     socket: &'a Arc<MixedSocket>,
     query: &'b mut Message,
-    udp_retransmission_timeout: &'i Duration,
-    udp_timeout: &'i Duration,
-    tcp_start_time: Instant,
-    udp_start_time: Instant,
+    quic_timeout: Duration,
+    quic_start_time: Instant,
+    progress: Arc<QueryProgress>,
     #[pin]
     timeout: Sleep,
+    /// Ready once every caller waiting on the query has cancelled it.
+    #[pin]
+    abandoned: AwokenToken,
     #[pin]
     result_receiver: once_watch::Receiver<Result<Message, errors::QueryError>>,
     #[pin]
-    inner: InnerUQ<'c, 'd, 'f, 'g, 'h>,
+    inner: InnerQQ<'f, 'g>,
 }
 
-impl<'a, 'b, 'c, 'd, 'f, 'g, 'h, 'i> UdpQueryRunner<'a, 'b, 'c, 'd, 'f, 'g, 'h, 'i>
-where
-    'h: 'g
-{
+#[cfg(feature = "quic")]
+impl<'a, 'b, 'f, 'g> QuicQueryRunner<'a, 'b, 'f, 'g> {
     #[inline]
-    pub fn new(socket: &'a Arc<MixedSocket>, query: &'b mut Message, result_receiver: once_watch::Receiver<Result<Message, errors::QueryError>>, udp_retransmission_timeout: &'i Duration, udp_timeout: &'i Duration) -> Self {
+    pub fn new(socket: &'a Arc<MixedSocket>, query: &'b mut Message, result_receiver: once_watch::Receiver<Result<Message, errors::QueryError>>, quic_timeout: Duration, progress: Arc<QueryProgress>) -> Self {
         Self {
             socket,
             query,
-            udp_retransmission_timeout,
-            udp_timeout,
-            timeout: tokio::time::sleep(*udp_retransmission_timeout),
+            quic_timeout,
+            quic_start_time: Instant::now(),
+            timeout: tokio::time::sleep_until(progress.timeout_at(quic_timeout)),
+            abandoned: progress.abandoned.awoken(),
+            progress,
             result_receiver,
-            tcp_start_time: Instant::now(),
-            udp_start_time: Instant::now(),
-            inner: InnerUQ::Fresh { udp_retransmissions: UDP_RETRANSMISSIONS },
-        }
-    }
-
-    #[inline]
-    fn reset_timeout(self: std::pin::Pin<&mut Self>, next_timeout: Duration) {
-        let now = Instant::now();
-        match now.checked_add(next_timeout) {
-            Some(new_deadline) => self.project().timeout.reset(new_deadline),
-            None => self.project().timeout.reset(now),
+            inner: InnerQQ::Fresh,
         }
     }
 }
 
-#[pin_project(project = InnerUQProj)]
-enum InnerUQ<'c, 'd, 'f, 'g, 'h>
+#[cfg(feature = "quic")]
+#[pin_project(project = InnerQQProj)]
+enum InnerQQ<'f, 'g>
 where
-    'h: 'g
+    'g: 'f
 {
-    Fresh { udp_retransmissions: u8 },
-    Running {
-        #[pin]
-        socket: QUdpTcpSocket<'c, 'd>,
-        #[pin]
-        send_query: QSend<'f, QSendType, errors::SocketSendError>,
-    },
-    Cleanup(BoxFuture<'g, RwLockWriteGuard<'h, ActiveQueries>>, UdpResponseTime),
+    Fresh,
+    Running(BoxFuture<'static, io::Result<Message>>),
+    Cleanup(BoxFuture<'f, RwLockWriteGuard<'g, ActiveQueries>>, QuicResponseTime),
     Complete,
 }
 
-impl<'a, 'c, 'd, 'f, 'g, 'h> InnerUQ<'c, 'd, 'f, 'g, 'h>
+#[cfg(feature = "quic")]
+impl<'a, 'f, 'g> InnerQQ<'f, 'g>
 where
-    'a: 'd + 'h
+    'a: 'g
 {
     #[inline]
-    pub fn set_running_udp(mut self: std::pin::Pin<&mut Self>, udp_retransmissions: u8, query_type: QSendType) {
-        self.set(Self::Running {
-            socket: QUdpTcpSocket::Udp { uq_socket: QUdpSocket::Fresh, retransmits: udp_retransmissions },
-            send_query: QSend::Fresh(query_type),
-        });
-    }
-
-    #[inline]
-    fn set_running_tcp(mut self: std::pin::Pin<&mut Self>, query_type: QSendType) {
-        self.set(InnerUQ::Running {
-            socket: QUdpTcpSocket::Tcp { tq_socket: QTcpSocket::Fresh },
-            send_query: QSend::Fresh(query_type),
-        });
+    pub fn set_running(mut self: std::pin::Pin<&mut Self>, send_query: BoxFuture<'static, io::Result<Message>>) {
+        self.set(Self::Running(send_query));
     }
 
     #[inline]
-    pub fn set_cleanup(mut self: std::pin::Pin<&mut Self>, execution_time: UdpResponseTime, socket: &'a Arc<MixedSocket>) {
+    pub fn set_cleanup(mut self: std::pin::Pin<&mut Self>, execution_time: QuicResponseTime, socket: &'a Arc<MixedSocket>) {
         let w_active_queries = socket.active_queries.write().boxed();
 
         self.set(Self::Cleanup(w_active_queries, execution_time));
@@ -850,55 +1436,589 @@ where
     }
 }
 
-impl<'a, 'b, 'c, 'd, 'f, 'g, 'h, 'i> Future for UdpQueryRunner<'a, 'b, 'c, 'd, 'f, 'g, 'h, 'i> {
+#[cfg(feature = "quic")]
+impl<'a, 'b, 'f, 'g> Future for QuicQueryRunner<'a, 'b, 'f, 'g> {
     type Output = ();
 
     fn poll(mut self: std::pin::Pin<&mut Self>, cx: &mut std::task::Context<'_>) -> std::task::Poll<Self::Output> {
         let mut this = self.as_mut().project();
         match this.inner.as_mut().project() {
-            InnerUQProj::Fresh { udp_retransmissions: 0 } => {
-                if let Poll::Ready(()) = this.timeout.as_mut().poll(cx) {
-                    let new_timeout = **this.udp_timeout;
-                    // If we run out of UDP retransmissions before the query has even begun,
-                    // then it is time to transmit via TCP.
-                    // Setting the socket state to QTcpSocket::Fresh will cause the socket to be
-                    // initialized (if needed) and then a message sent over that socket.
-                    this.inner.set_running_tcp(QSendType::Initial);
-                    self.as_mut().reset_timeout(new_timeout);
+            InnerQQProj::Fresh
+          | InnerQQProj::Running(_) if this.abandoned.as_mut().poll(cx).is_ready() => {
+                // Every caller has cancelled the query. Dropping the running query resets its
+                // stream.
+                let _ = this.result_receiver.get_sender().send(Err(errors::QueryError::Cancelled));
 
-                    // TODO
-                }
+                this.inner.set_cleanup(QuicResponseTime::None, this.socket);
             },
-            InnerUQProj::Fresh { udp_retransmissions: udp_retransmissions @ 1.. } => {
+            InnerQQProj::Fresh
+          | InnerQQProj::Running(_) if this.progress.deadline_passed() => {
+                // Every caller has given up on the query. It did not time out so it says nothing
+                // about the connection.
+                let _ = this.result_receiver.get_sender().send(Err(errors::QueryError::Timeout));
+
+                this.inner.set_cleanup(QuicResponseTime::None, this.socket);
+            },
+            InnerQQProj::Fresh
+          | InnerQQProj::Running(_) => {
                 if let Poll::Ready(()) = this.timeout.as_mut().poll(cx) {
-                    let new_timeout = **this.udp_retransmission_timeout;
-                    // If we time out before the first query has begin, burn a retransmission.
-                    *udp_retransmissions = udp_retransmissions.saturating_sub(1);
-                    self.as_mut().reset_timeout(new_timeout);
+                    let _ = this.result_receiver.get_sender().send(Err(errors::QueryError::Timeout));
 
-                    // TODO
+                    // Dropping the running query resets its stream.
+                    this.inner.set_cleanup(QuicResponseTime::Dropped, this.socket);
                 }
             },
-            InnerUQProj::Running { mut socket, mut send_query } => {
-                match (send_query.as_mut().project(), socket.as_mut().project()) {
-                    (QSendProj::Fresh(_), QUdpTcpSocketProj::Udp { uq_socket: _, retransmits: 0 })
-                  | (QSendProj::SendQuery(_, _), QUdpTcpSocketProj::Udp { uq_socket: _, retransmits: 0 }) => {
-                        if let Poll::Ready(()) = this.timeout.as_mut().poll(cx) {
-                            let new_timeout = **this.udp_timeout;
-                            // Setting the socket state to QTcpSocket::Fresh will cause the socket
-                            // to be initialized (if needed) and then a message sent over that
-                            // socket.
-                            this.inner.set_running_tcp(QSendType::Retransmit);
-                            self.as_mut().reset_timeout(new_timeout);
+            InnerQQProj::Cleanup(_, _)
+          | InnerQQProj::Complete => {
+                // Not allowed to timeout. This is a cleanup state.
+            },
+        }
 
-                            // TODO
-                        }
-                    },
-                    (QSendProj::Complete(_), QUdpTcpSocketProj::Udp { uq_socket: _, retransmits: 0 }) => {
-                        if let Poll::Ready(()) = this.timeout.as_mut().poll(cx) {
-                            let new_timeout = **this.udp_timeout;
-                            // Setting the socket state to QTcpSocket::Fresh will cause the socket
-                            // to be initialized (if needed) and then a message sent over that
+        loop {
+            let mut this = self.as_mut().project();
+            match this.inner.as_mut().project() {
+                InnerQQProj::Fresh => {
+                    let Some(quic_socket) = this.socket.quic.clone() else {
+                        let _ = this.result_receiver.get_sender().send(Err(errors::QueryError::UnsupportedTransport(QueryOpt::Quic)));
+
+                        this.inner.set_cleanup(QuicResponseTime::None, this.socket);
+
+                        // Next loop will poll for the in-flight map lock to clean up the query ID
+                        // before returning the response.
+                        continue;
+                    };
+
+                    println!("Sending on QUIC socket {} {{ drop rate {:.2}%, response time {:.2} ms, timeout {} ms }} :: {:?}", this.socket.upstream_socket, this.socket.average_dropped_quic_packets() * 100.0, this.socket.average_quic_response_time(), this.quic_timeout.as_millis(), this.query);
+                    this.progress.record_send(MixedTransport::Quic, this.query.estimated_wire_size(true));
+                    this.socket.recent_messages_sent.store(true, Ordering::Release);
+
+                    this.inner.set_running(quic_socket.query(this.query.clone()).boxed());
+
+                    // Next loop will poll the query. This connects to the server if there is no
+                    // connection yet, opens a stream, and reads the response from it.
+                    continue;
+                },
+                InnerQQProj::Running(send_query) => {
+                    match send_query.as_mut().poll(cx) {
+                        Poll::Ready(Ok(mut response)) => {
+                            let execution_time = this.quic_start_time.elapsed();
+                            this.socket.recent_messages_received.store(true, Ordering::Release);
+                            response.id = this.query.id;
+                            let _ = this.result_receiver.get_sender().send(Ok(response));
+
+                            this.inner.set_cleanup(QuicResponseTime::Responded(execution_time), this.socket);
+
+                            // Next loop will poll for the in-flight map lock to clean up the query
+                            // ID and update the timeout.
+                            continue;
+                        },
+                        Poll::Ready(Err(error)) => {
+                            let execution_time = match error.kind() {
+                                io::ErrorKind::TimedOut => QuicResponseTime::Dropped,
+                                _ => QuicResponseTime::None,
+                            };
+                            let _ = this.result_receiver.get_sender().send(Err(errors::QueryError::QuicSocket(errors::IoError::from(error))));
+
+                            this.inner.set_cleanup(execution_time, this.socket);
+
+                            // Next loop will poll for the in-flight map lock to clean up the query
+                            // ID before returning the response.
+                            continue;
+                        },
+                        Poll::Pending => {
+                            // Will wake up if the query makes progress or the timeout occurs.
+                            return Poll::Pending;
+                        },
+                    }
+                },
+                InnerQQProj::Cleanup(w_active_queries, execution_time) => {
+                    this.result_receiver.close();
+
+                    match w_active_queries.as_mut().poll(cx) {
+                        Poll::Ready(mut w_active_queries) => {
+                            match execution_time {
+                                QuicResponseTime::Dropped => {
+                                    let average_quic_dropped_packets = this.socket.add_dropped_packet_to_quic_average();
+                                    let average_quic_response_time = this.socket.average_quic_response_time();
+                                    if average_quic_dropped_packets.current_average() >= INCREASE_QUIC_TIMEOUT_DROPPED_AVERAGE_THRESHOLD {
+                                        let quic_timeout = w_active_queries.quic_timeout.saturating_add(QUIC_TIMEOUT_STEP_WHEN_DROPPED_THRESHOLD_EXCEEDED);
+                                        let quic_timeout = match average_quic_response_time.is_finite() {
+                                            true => min(quic_timeout, Duration::from_secs_f64(average_quic_response_time * QUIC_TIMEOUT_MAX_DURATION_ABOVE_QUIC_RESPONSE_TIME / MILLISECONDS_IN_1_SECOND)),
+                                            false => quic_timeout,
+                                        };
+                                        w_active_queries.quic_timeout = bound(quic_timeout, MIN_QUIC_TIMEOUT, MAX_QUIC_TIMEOUT);
+                                    }
+                                },
+                                QuicResponseTime::Responded(response_time) => {
+                                    let (average_quic_response_time, average_quic_dropped_packets) = this.socket.add_response_time_to_quic_average(*response_time);
+                                    if average_quic_dropped_packets.current_average() <= DECREASE_QUIC_TIMEOUT_DROPPED_AVERAGE_THRESHOLD {
+                                        w_active_queries.quic_timeout = bound(
+                                            Duration::from_secs_f64(average_quic_response_time.current_average() * QUIC_TIMEOUT_DURATION_ABOVE_QUIC_RESPONSE_TIME / MILLISECONDS_IN_1_SECOND),
+                                            MIN_QUIC_TIMEOUT,
+                                            MAX_QUIC_TIMEOUT,
+                                        );
+                                    }
+                                },
+                                QuicResponseTime::None => (),
+                            }
+                            this.socket.peer.save_quic_timeout(w_active_queries.quic_timeout);
+
+                            w_active_queries.in_flight.remove(&this.query.id);
+                            remove_joinable(&mut w_active_queries.quic, &this.query.question_key(), this.query.id);
+                            drop(w_active_queries);
+
+                            this.inner.set_complete();
+
+                            return Poll::Ready(());
+                        },
+                        Poll::Pending => {
+                            return Poll::Pending;
+                        },
+                    }
+                },
+                InnerQQProj::Complete => {
+                    panic!("QUIC query polled after completion");
+                },
+            }
+        }
+    }
+}
+
+#[cfg(feature = "quic")]
+#[pinned_drop]
+impl<'a, 'b, 'f, 'g> PinnedDrop for QuicQueryRunner<'a, 'b, 'f, 'g> {
+    fn drop(mut self: Pin<&mut Self>) {
+        async fn cleanup(socket: Arc<MixedSocket>, query: Message) {
+            let mut w_active_queries = socket.active_queries.write().await;
+            let _ = w_active_queries.in_flight.remove(&query.id);
+            remove_joinable(&mut w_active_queries.quic, &query.question_key(), query.id);
+            drop(w_active_queries);
+        }
+
+        match self.as_mut().project().inner.as_mut().project() {
+            InnerQQProj::Fresh
+          | InnerQQProj::Running(_)
+          | InnerQQProj::Cleanup(_, _) => {
+                let socket = self.socket.clone();
+                let query = self.query.clone();
+                tokio::spawn(cleanup(socket, query));
+            },
+            InnerQQProj::Complete => {
+                // Nothing to do for active queries.
+            }
+        }
+    }
+}
+
+/// A query sent over DNS over QUIC. Identical queries that are already in flight on the socket are
+/// joined instead of being sent again.
+#[cfg(feature = "quic")]
+#[pin_project(PinnedDrop)]
+pub struct QuicQuery<'a, 'b, 'c, 'd>
+where
+    'a: 'd
+{
+    socket: &'a Arc<MixedSocket>,
+    query: &'b mut Message,
+    deadline: Option<Instant>,
+    progress: Option<Arc<QueryProgress>>,
+    /// Ready once the caller cancels the query.
+    #[pin]
+    cancellation: Option<AwokenToken>,
+    #[pin]
+    inner: QInitQuery<'c, 'd, ActiveQueries>,
+}
+
+#[cfg(feature = "quic")]
+impl<'a, 'b, 'c, 'd> QuicQuery<'a, 'b, 'c, 'd> {
+    #[inline]
+    pub fn new(socket: &'a Arc<MixedSocket>, query: &'b mut Message, deadline: Option<Instant>) -> Self {
+        Self {
+            socket,
+            query,
+            deadline,
+            progress: None,
+            cancellation: None,
+            inner: QInitQuery::Fresh,
+        }
+    }
+}
+
+#[cfg(feature = "quic")]
+#[pinned_drop]
+impl<'a, 'b, 'c, 'd> PinnedDrop for QuicQuery<'a, 'b, 'c, 'd> {
+    fn drop(self: Pin<&mut Self>) {
+        // Callers are usually dropped rather than polled once they are cancelled. They leave the
+        // query they were following all the same.
+        let this = self.project();
+        let cancelled = this.cancellation.as_pin_mut().is_some_and(|cancellation| cancellation.try_awoken());
+        if let (true, QInitQuery::Following(_), Some(progress)) = (cancelled, &*this.inner, this.progress.as_ref()) {
+            progress.abandon();
+        }
+    }
+}
+
+#[cfg(feature = "quic")]
+impl<'a, 'b, 'c, 'd> Future for QuicQuery<'a, 'b, 'c, 'd> {
+    type Output = Result<Message, errors::QueryError>;
+
+    fn poll(mut self: std::pin::Pin<&mut Self>, cx: &mut std::task::Context<'_>) -> std::task::Poll<Self::Output> {
+        let mut this = self.as_mut().project();
+        if let Some(Poll::Ready(())) = this.cancellation.as_mut().as_pin_mut().map(|cancellation| cancellation.poll(cx)) {
+            if let (QInitQueryProj::Following(_), Some(progress)) = (this.inner.as_mut().project(), this.progress.as_ref()) {
+                progress.abandon();
+            }
+            this.inner.set_complete();
+
+            return Poll::Ready(Err(errors::QueryError::Cancelled));
+        }
+
+        loop {
+            let mut this = self.as_mut().project();
+            match this.inner.as_mut().project() {
+                QInitQueryProj::Fresh => {
+                    this.inner.set_read_active_query(&this.socket.active_queries);
+
+                    continue;
+                },
+                QInitQueryProj::ReadActiveQuery(r_active_queries) => {
+                    match r_active_queries.as_mut().poll(cx) {
+                        Poll::Ready(r_active_queries) => {
+                            match r_active_queries.join(&[&r_active_queries.quic], this.query, *this.deadline) {
+                                Some((query_id, result_receiver, progress)) => {
+                                    this.query.id = query_id;
+                                    *this.progress = Some(progress);
+                                    drop(r_active_queries);
+
+                                    this.inner.set_following(result_receiver);
+                                    continue;
+                                },
+                                None => {
+                                    drop(r_active_queries);
+                                    this.inner.set_write_active_query(&this.socket.active_queries);
+                                    continue;
+                                },
+                            }
+                        },
+                        Poll::Pending => return Poll::Pending,
+                    }
+                },
+                QInitQueryProj::WriteActiveQuery(w_active_queries) => {
+                    match w_active_queries.as_mut().poll(cx) {
+                        Poll::Ready(mut w_active_queries) => {
+                            match w_active_queries.join(&[&w_active_queries.quic], this.query, *this.deadline) {
+                                Some((query_id, result_receiver, progress)) => {
+                                    this.query.id = query_id;
+                                    *this.progress = Some(progress);
+                                    drop(w_active_queries);
+
+                                    this.inner.set_following(result_receiver);
+                                    continue;
+                                },
+                                None => {
+                                    let (result_sender, result_receiver) = once_watch::channel();
+
+                                    // The ID is only used to find the query in the in-flight map.
+                                    // It is sent as 0, as required by RFC 9250.
+                                    this.query.id = rand::random();
+
+                                    // verify that ID is unique.
+                                    while w_active_queries.in_flight.contains_key(&this.query.id) {
+                                        this.query.id = rand::random();
+                                    }
+
+                                    let progress = QueryProgress::quic(w_active_queries.quic_timeout, *this.deadline);
+                                    let join_handle = tokio::spawn({
+                                        let quic_timeout = w_active_queries.quic_timeout;
+                                        let result_receiver = result_sender.subscribe();
+                                        let socket = this.socket.clone();
+                                        let mut query = this.query.clone();
+                                        let progress = progress.clone();
+                                        async move {
+                                            QuicQueryRunner::new(&socket, &mut query, result_receiver, quic_timeout, progress).await;
+                                        }
+                                    });
+
+                                    w_active_queries.in_flight.insert(this.query.id, (result_sender.clone(), join_handle, progress.clone()));
+                                    *this.progress = Some(progress);
+                                    if !is_signed(this.query) {
+                                        w_active_queries.quic.insert(this.query.question_key(), (this.query.id, result_sender));
+                                    }
+                                    drop(w_active_queries);
+
+                                    this.inner.set_following(result_receiver);
+                                    continue;
+                                },
+                            }
+                        },
+                        Poll::Pending => return Poll::Pending,
+                    }
+                },
+                QInitQueryProj::Following(mut result_receiver) => {
+                    match result_receiver.as_mut().poll(cx) {
+                        Poll::Ready(Ok(response)) => {
+                            this.inner.set_complete();
+                            return Poll::Ready(response);
+                        },
+                        Poll::Ready(Err(once_watch::RecvError::Closed)) => {
+                            let error = errors::QueryError::QuicSocket(errors::IoError::from(io::Error::from(io::ErrorKind::Interrupted)));
+
+                            this.inner.set_complete();
+                            return Poll::Ready(Err(error));
+                        },
+                        Poll::Pending => return Poll::Pending,
+                    }
+                },
+                QInitQueryProj::Complete => panic!("QuicQuery cannot be polled after completion"),
+            }
+        }
+    }
+}
+
+/// A query sent over DNS over HTTPS. Each query is sent as its own request on the socket's HTTP
+/// connection, so identical queries are not joined. Dropping the query resets its stream.
+#[cfg(feature = "https")]
+#[pin_project]
+pub struct HttpsQuery<'a, 'b> {
+    socket: &'a Arc<MixedSocket>,
+    query: &'b mut Message,
+    deadline: Option<Instant>,
+    progress: Option<Arc<QueryProgress>>,
+    /// Ready once the caller cancels the query.
+    #[pin]
+    cancellation: Option<AwokenToken>,
+    #[pin]
+    timeout: Option<Sleep>,
+    response: Option<BoxFuture<'static, io::Result<Message>>>,
+}
+
+#[cfg(feature = "https")]
+impl<'a, 'b> HttpsQuery<'a, 'b> {
+    #[inline]
+    pub fn new(socket: &'a Arc<MixedSocket>, query: &'b mut Message, deadline: Option<Instant>) -> Self {
+        Self {
+            socket,
+            query,
+            deadline,
+            progress: None,
+            cancellation: None,
+            timeout: deadline.map(tokio::time::sleep_until),
+            response: None,
+        }
+    }
+}
+
+#[cfg(feature = "https")]
+impl<'a, 'b> Future for HttpsQuery<'a, 'b> {
+    type Output = Result<Message, errors::QueryError>;
+
+    fn poll(self: std::pin::Pin<&mut Self>, cx: &mut std::task::Context<'_>) -> std::task::Poll<Self::Output> {
+        let mut this = self.project();
+        if let Some(Poll::Ready(())) = this.cancellation.as_mut().as_pin_mut().map(|cancellation| cancellation.poll(cx)) {
+            *this.response = None;
+            return Poll::Ready(Err(errors::QueryError::Cancelled));
+        }
+        if let Some(Poll::Ready(())) = this.timeout.as_mut().as_pin_mut().map(|timeout| timeout.poll(cx)) {
+            *this.response = None;
+            return Poll::Ready(Err(errors::QueryError::Timeout));
+        }
+
+        let response = match this.response {
+            Some(response) => response,
+            None => {
+                let Some(doh_client) = this.socket.https.clone() else {
+                    return Poll::Ready(Err(errors::QueryError::UnsupportedTransport(QueryOpt::Https)));
+                };
+                println!("Sending on HTTPS socket {} :: {:?}", this.socket.upstream_socket, this.query);
+                let progress = QueryProgress::https(*this.deadline);
+                progress.record_send(MixedTransport::Https, this.query.estimated_wire_size(true));
+                *this.progress = Some(progress);
+                this.socket.recent_messages_sent.store(true, Ordering::SeqCst);
+                this.response.insert(doh_client.query(this.query.clone()).boxed())
+            },
+        };
+        match response.as_mut().poll(cx) {
+            Poll::Ready(Ok(response)) => {
+                this.socket.recent_messages_received.store(true, Ordering::SeqCst);
+                *this.response = None;
+                Poll::Ready(Ok(response))
+            },
+            Poll::Ready(Err(error)) => {
+                *this.response = None;
+                Poll::Ready(Err(errors::QueryError::HttpsSocket(errors::IoError::from(error))))
+            },
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+#[pin_project(PinnedDrop)]
+struct UdpQueryRunner<'a, 'b, 'c, 'd, 'f, 'g, 'h, 'i>
+where
+    'a: 'd + 'h
+{
+    socket: &'a Arc<MixedSocket>,
+    query: &'b mut Message,
+    udp_retransmission_timeout: &'i Duration,
+    udp_timeout: &'i Duration,
+    tcp_start_time: Instant,
+    udp_start_time: Instant,
+    progress: Arc<QueryProgress>,
+    #[pin]
+    timeout: Sleep,
+    /// Ready once every caller waiting on the query has cancelled it.
+    #[pin]
+    abandoned: AwokenToken,
+    #[pin]
+    result_receiver: once_watch::Receiver<Result<Message, errors::QueryError>>,
+    #[pin]
+    inner: InnerUQ<'c, 'd, 'f, 'g, 'h>,
+}
+
+impl<'a, 'b, 'c, 'd, 'f, 'g, 'h, 'i> UdpQueryRunner<'a, 'b, 'c, 'd, 'f, 'g, 'h, 'i>
+where
+    'h: 'g
+{
+    #[inline]
+    pub fn new(socket: &'a Arc<MixedSocket>, query: &'b mut Message, result_receiver: once_watch::Receiver<Result<Message, errors::QueryError>>, udp_retransmission_timeout: &'i Duration, udp_timeout: &'i Duration, progress: Arc<QueryProgress>) -> Self {
+        Self {
+            socket,
+            query,
+            udp_retransmission_timeout,
+            udp_timeout,
+            timeout: tokio::time::sleep_until(progress.timeout_at(*udp_retransmission_timeout)),
+            abandoned: progress.abandoned.awoken(),
+            progress,
+            result_receiver,
+            tcp_start_time: Instant::now(),
+            udp_start_time: Instant::now(),
+            inner: InnerUQ::Fresh { udp_retransmissions: UDP_RETRANSMISSIONS },
+        }
+    }
+
+    #[inline]
+    fn reset_timeout(self: std::pin::Pin<&mut Self>, next_timeout: Duration) {
+        let this = self.project();
+        let new_deadline = this.progress.timeout_at(next_timeout);
+        this.timeout.reset(new_deadline);
+    }
+}
+
+#[pin_project(project = InnerUQProj)]
+enum InnerUQ<'c, 'd, 'f, 'g, 'h>
+where
+    'h: 'g
+{
+    Fresh { udp_retransmissions: u8 },
+    Running {
+        #[pin]
+        socket: QUdpTcpSocket<'c, 'd>,
+        #[pin]
+        send_query: QSend<'f, QSendType, errors::SocketSendError>,
+    },
+    Cleanup(BoxFuture<'g, RwLockWriteGuard<'h, ActiveQueries>>, UdpResponseTime),
+    Complete,
+}
+
+impl<'a, 'c, 'd, 'f, 'g, 'h> InnerUQ<'c, 'd, 'f, 'g, 'h>
+where
+    'a: 'd + 'h
+{
+    #[inline]
+    pub fn set_running_udp(mut self: std::pin::Pin<&mut Self>, udp_retransmissions: u8, query_type: QSendType) {
+        self.set(Self::Running {
+            socket: QUdpTcpSocket::Udp { uq_socket: QUdpSocket::Fresh, retransmits: udp_retransmissions },
+            send_query: QSend::Fresh(query_type),
+        });
+    }
+
+    #[inline]
+    fn set_running_tcp(mut self: std::pin::Pin<&mut Self>, query_type: QSendType) {
+        self.set(InnerUQ::Running {
+            socket: QUdpTcpSocket::Tcp { tq_socket: QTcpSocket::Fresh },
+            send_query: QSend::Fresh(query_type),
+        });
+    }
+
+    #[inline]
+    pub fn set_cleanup(mut self: std::pin::Pin<&mut Self>, execution_time: UdpResponseTime, socket: &'a Arc<MixedSocket>) {
+        let w_active_queries = socket.active_queries.write().boxed();
+
+        self.set(Self::Cleanup(w_active_queries, execution_time));
+    }
+
+    #[inline]
+    pub fn set_complete(mut self: std::pin::Pin<&mut Self>) {
+        self.set(Self::Complete);
+    }
+}
+
+impl<'a, 'b, 'c, 'd, 'f, 'g, 'h, 'i> Future for UdpQueryRunner<'a, 'b, 'c, 'd, 'f, 'g, 'h, 'i> {
+    type Output = ();
+
+    fn poll(mut self: std::pin::Pin<&mut Self>, cx: &mut std::task::Context<'_>) -> std::task::Poll<Self::Output> {
+        let mut this = self.as_mut().project();
+        match this.inner.as_mut().project() {
+            InnerUQProj::Fresh { udp_retransmissions: _ }
+          | InnerUQProj::Running { socket: _, send_query: _ } if this.abandoned.as_mut().poll(cx).is_ready() => {
+                // Every caller has cancelled the query. Stop without retransmitting it or
+                // switching to TCP.
+                let _ = this.result_receiver.get_sender().send(Err(errors::QueryError::Cancelled));
+
+                this.inner.set_cleanup(UdpResponseTime::None, this.socket);
+            },
+            InnerUQProj::Fresh { udp_retransmissions: _ }
+          | InnerUQProj::Running { socket: _, send_query: _ } if this.progress.deadline_passed() => {
+                // Every caller has given up on the query. Rather than retransmitting it or
+                // switching to TCP, stop here. It did not time out so it says nothing about the
+                // connection.
+                let _ = this.result_receiver.get_sender().send(Err(errors::QueryError::Timeout));
+
+                this.inner.set_cleanup(UdpResponseTime::None, this.socket);
+            },
+            InnerUQProj::Fresh { udp_retransmissions: 0 } => {
+                if let Poll::Ready(()) = this.timeout.as_mut().poll(cx) {
+                    let new_timeout = **this.udp_timeout;
+                    // If we run out of UDP retransmissions before the query has even begun,
+                    // then it is time to transmit via TCP.
+                    // Setting the socket state to QTcpSocket::Fresh will cause the socket to be
+                    // initialized (if needed) and then a message sent over that socket.
+                    this.inner.set_running_tcp(QSendType::Initial);
+                    self.as_mut().reset_timeout(new_timeout);
+
+                    // TODO
+                }
+            },
+            InnerUQProj::Fresh { udp_retransmissions: udp_retransmissions @ 1.. } => {
+                if let Poll::Ready(()) = this.timeout.as_mut().poll(cx) {
+                    let new_timeout = **this.udp_retransmission_timeout;
+                    // If we time out before the first query has begin, burn a retransmission.
+                    *udp_retransmissions = udp_retransmissions.saturating_sub(1);
+                    self.as_mut().reset_timeout(new_timeout);
+
+                    // TODO
+                }
+            },
+            InnerUQProj::Running { mut socket, mut send_query } => {
+                match (send_query.as_mut().project(), socket.as_mut().project()) {
+                    (QSendProj::Fresh(_), QUdpTcpSocketProj::Udp { uq_socket: _, retransmits: 0 })
+                  | (QSendProj::SendQuery(_, _), QUdpTcpSocketProj::Udp { uq_socket: _, retransmits: 0 }) => {
+                        if let Poll::Ready(()) = this.timeout.as_mut().poll(cx) {
+                            let new_timeout = **this.udp_timeout;
+                            // Setting the socket state to QTcpSocket::Fresh will cause the socket
+                            // to be initialized (if needed) and then a message sent over that
+                            // socket.
+                            this.inner.set_running_tcp(QSendType::Retransmit);
+                            self.as_mut().reset_timeout(new_timeout);
+
+                            // TODO
+                        }
+                    },
+                    (QSendProj::Complete(_), QUdpTcpSocketProj::Udp { uq_socket: _, retransmits: 0 }) => {
+                        if let Poll::Ready(()) = this.timeout.as_mut().poll(cx) {
+                            let new_timeout = **this.udp_timeout;
+                            // Setting the socket state to QTcpSocket::Fresh will cause the socket
+                            // to be initialized (if needed) and then a message sent over that
                             // socket.
                             this.socket.add_dropped_packet_to_udp_average();
                             this.inner.set_running_tcp(QSendType::Retransmit);
@@ -949,7 +2069,12 @@ impl<'a, 'b, 'c, 'd, 'f, 'g, 'h, 'i> Future for UdpQueryRunner<'a, 'b, 'c, 'd, '
             },
         }
 
+        let mut budget = PollBudget::new(&UDP_QUERY_RUNNER_POLL_LOOP);
         loop {
+            if budget.exhausted(cx) {
+                return Poll::Pending;
+            }
+
             let mut this = self.as_mut().project();
             match this.inner.as_mut().project() {
                 InnerUQProj::Fresh { udp_retransmissions } => {
@@ -1020,6 +2145,7 @@ impl<'a, 'b, 'c, 'd, 'f, 'g, 'h, 'i> Future for UdpQueryRunner<'a, 'b, 'c, 'd, '
                                 let udp_socket = udp_socket.clone();
 
                                 println!("Sending on UDP socket {} {{ drop rate {:.2}%, truncation rate {:.2}%, response time {:.2} ms, timeout {} ms }} :: {:?}", this.socket.upstream_socket, this.socket.average_dropped_udp_packets() * 100.0, this.socket.average_truncated_udp_packets() * 100.0, this.socket.average_udp_response_time(), this.udp_retransmission_timeout.as_millis(), this.query);
+                                this.progress.record_send(MixedTransport::Udp, wire_length);
 
                                 let send_query_future = async move {
                                     let socket = socket;
@@ -1027,6 +2153,10 @@ impl<'a, 'b, 'c, 'd, 'f, 'g, 'h, 'i> Future for UdpQueryRunner<'a, 'b, 'c, 'd, '
                                     let wire_length = wire_length;
 
                                     socket.recent_messages_sent.store(true, Ordering::Release);
+                                    if let Some(deliveries) = fault_injection::plan_datagram(&socket.upstream_socket) {
+                                        send_with_faults(udp_socket, &raw_message[..wire_length], deliveries);
+                                        return Ok(());
+                                    }
                                     let bytes_written = match udp_socket.send(&raw_message[..wire_length]).await {
                                         Ok(bytes_written) => bytes_written,
                                         Err(error) => {
@@ -1094,23 +2224,25 @@ impl<'a, 'b, 'c, 'd, 'f, 'g, 'h, 'i> Future for UdpQueryRunner<'a, 'b, 'c, 'd, '
                             };
 
                             if let QTcpSocketProj::Acquired { tcp_socket, kill_tcp: _ } = tq_socket.as_mut().project() {
-                                let mut raw_message = [0_u8; MAX_MESSAGE_SIZE as usize];
-                                let mut write_wire = WriteWire::from_bytes(&mut raw_message);
-                                if let Err(wire_error) = this.query.to_wire_format_with_two_octet_length(&mut write_wire, &mut Some(CompressionMap::new())) {
-                                    let _ = this.result_receiver.get_sender().send(Err(errors::QueryError::from(errors::TcpSendError::from(wire_error))));
+                                let raw_message = match this.query.to_wire_vec(&mut Some(CompressionMap::new())) {
+                                    Ok(raw_message) => raw_message,
+                                    Err(wire_error) => {
+                                        let _ = this.result_receiver.get_sender().send(Err(errors::QueryError::from(errors::TcpSendError::from(wire_error))));
 
-                                    this.inner.set_cleanup(UdpResponseTime::None, &this.socket);
+                                        this.inner.set_cleanup(UdpResponseTime::None, &this.socket);
 
-                                    // Next loop will poll for the in-flight map lock to clean up the
-                                    // query ID before returning the response.
-                                    continue;
+                                        // Next loop will poll for the in-flight map lock to clean
+                                        // up the query ID before returning the response.
+                                        continue;
+                                    },
                                 };
-                                let wire_length = write_wire.current_len();
+                                let wire_length = TWO_OCTET_LENGTH + raw_message.len();
 
                                 let socket = this.socket.clone();
                                 let tcp_socket = tcp_socket.clone();
 
                                 println!("Sending on TCP socket {} {{ drop rate {:.2}%, truncation rate {:.2}%, response time {:.2} ms, timeout {} ms }} :: {:?}", this.socket.upstream_socket, this.socket.average_dropped_tcp_packets() * 100.0, this.socket.average_truncated_udp_packets() * 100.0, this.socket.average_tcp_response_time(), this.udp_timeout.as_millis(), this.query);
+                                this.progress.record_send(MixedTransport::Tcp, wire_length);
 
                                 let send_query_future = async move {
                                     let socket = socket;
@@ -1119,7 +2251,7 @@ impl<'a, 'b, 'c, 'd, 'f, 'g, 'h, 'i> Future for UdpQueryRunner<'a, 'b, 'c, 'd, '
 
                                     socket.recent_messages_sent.store(true, Ordering::Release);
                                     let mut w_tcp_stream = tcp_socket.lock().await;
-                                    let bytes_written = match w_tcp_stream.write(&raw_message[..wire_length]).await {
+                                    let bytes_written = match write_with_two_octet_length(&mut *w_tcp_stream, &raw_message).await {
                                         Ok(bytes_written) => bytes_written,
                                         Err(error) => {
                                             return Err(errors::SocketSendError::from(errors::TcpSendError::from(error)));
@@ -1259,6 +2391,7 @@ impl<'a, 'b, 'c, 'd, 'f, 'g, 'h, 'i> Future for UdpQueryRunner<'a, 'b, 'c, 'd, '
                         Poll::Ready(mut w_active_queries) => {
                             match execution_time {
                                 UdpResponseTime::Dropped => {
+                                    this.socket.peer.udp_size.record_missing_response();
                                     let average_udp_dropped_packets = this.socket.add_dropped_packet_to_udp_average();
                                     let average_udp_response_time = this.socket.average_udp_response_time();
                                     if average_udp_response_time.is_finite() {
@@ -1300,6 +2433,7 @@ impl<'a, 'b, 'c, 'd, 'f, 'g, 'h, 'i> Future for UdpQueryRunner<'a, 'b, 'c, 'd, '
                                     }
                                 },
                                 UdpResponseTime::UdpDroppedTcpResponded(response_time) => {
+                                    this.socket.peer.udp_size.record_missing_response();
                                     let average_udp_dropped_packets = this.socket.add_dropped_packet_to_udp_average();
                                     let (average_tcp_response_time, average_tcp_dropped_packets) = this.socket.add_response_time_to_tcp_average(*response_time);
                                     if average_udp_dropped_packets.current_average() >= INCREASE_UDP_TIMEOUT_DROPPED_AVERAGE_THRESHOLD {
@@ -1318,6 +2452,7 @@ impl<'a, 'b, 'c, 'd, 'f, 'g, 'h, 'i> Future for UdpQueryRunner<'a, 'b, 'c, 'd, '
                                     }
                                 },
                                 UdpResponseTime::Responded { execution_time: response_time, truncated } => {
+                                    this.socket.peer.udp_size.record_response();
                                     let (average_udp_response_time, average_udp_dropped_packets) = this.socket.add_response_time_to_udp_average(*response_time);
                                     if average_udp_dropped_packets.current_average() <= DECREASE_UDP_TIMEOUT_DROPPED_AVERAGE_THRESHOLD {
                                         w_active_queries.udp_timeout = bound(
@@ -1345,9 +2480,10 @@ impl<'a, 'b, 'c, 'd, 'f, 'g, 'h, 'i> Future for UdpQueryRunner<'a, 'b, 'c, 'd, '
                                 },
                                 UdpResponseTime::None => (),
                             }
+                            this.socket.peer.save_udp_timeouts(w_active_queries.udp_retransmit_timeout, w_active_queries.udp_timeout);
 
                             w_active_queries.in_flight.remove(&this.query.id);
-                            w_active_queries.tcp_or_udp.remove(&this.query.question);
+                            remove_joinable(&mut w_active_queries.tcp_or_udp, &this.query.question_key(), this.query.id);
                             drop(w_active_queries);
 
                             this.inner.set_complete();
@@ -1374,7 +2510,7 @@ impl<'a, 'b, 'c, 'd, 'f, 'g, 'h, 'i> PinnedDrop for UdpQueryRunner<'a, 'b, 'c, '
         async fn cleanup(socket: Arc<MixedSocket>, query: Message) {
             let mut w_active_queries = socket.active_queries.write().await;
             let _ = w_active_queries.in_flight.remove(&query.id);
-            let _ = w_active_queries.tcp_or_udp.remove(&query.question);
+            remove_joinable(&mut w_active_queries.tcp_or_udp, &query.question_key(), query.id);
             drop(w_active_queries);
         }
 
@@ -1393,33 +2529,69 @@ impl<'a, 'b, 'c, 'd, 'f, 'g, 'h, 'i> PinnedDrop for UdpQueryRunner<'a, 'b, 'c, '
     }
 }
 
-#[pin_project]
+#[pin_project(PinnedDrop)]
 struct UdpQuery<'a, 'b, 'c, 'd>
 where
     'a: 'd
 {
     socket: &'a Arc<MixedSocket>,
     query: &'b mut Message,
+    deadline: Option<Instant>,
+    progress: Option<Arc<QueryProgress>>,
+    /// Ready once the caller cancels the query.
+    #[pin]
+    cancellation: Option<AwokenToken>,
     #[pin]
     inner: QInitQuery<'c, 'd, ActiveQueries>,
 }
 
 impl<'a, 'b, 'c, 'd> UdpQuery<'a, 'b, 'c, 'd> {
     #[inline]
-    pub fn new(socket: &'a Arc<MixedSocket>, query: &'b mut Message) -> Self {
+    pub fn new(socket: &'a Arc<MixedSocket>, query: &'b mut Message, deadline: Option<Instant>) -> Self {
         Self {
             socket,
             query,
+            deadline,
+            progress: None,
+            cancellation: None,
             inner: QInitQuery::Fresh,
         }
     }
 }
 
+#[pinned_drop]
+impl<'a, 'b, 'c, 'd> PinnedDrop for UdpQuery<'a, 'b, 'c, 'd> {
+    fn drop(self: Pin<&mut Self>) {
+        // Callers are usually dropped rather than polled once they are cancelled. They leave the
+        // query they were following all the same.
+        let this = self.project();
+        let cancelled = this.cancellation.as_pin_mut().is_some_and(|cancellation| cancellation.try_awoken());
+        if let (true, QInitQuery::Following(_), Some(progress)) = (cancelled, &*this.inner, this.progress.as_ref()) {
+            progress.abandon();
+        }
+    }
+}
+
 impl<'a, 'b, 'c, 'd> Future for UdpQuery<'a, 'b, 'c, 'd> {
     type Output = Result<Message, errors::QueryError>;
 
     fn poll(mut self: std::pin::Pin<&mut Self>, cx: &mut std::task::Context<'_>) -> std::task::Poll<Self::Output> {
+        let mut this = self.as_mut().project();
+        if let Some(Poll::Ready(())) = this.cancellation.as_mut().as_pin_mut().map(|cancellation| cancellation.poll(cx)) {
+            if let (QInitQueryProj::Following(_), Some(progress)) = (this.inner.as_mut().project(), this.progress.as_ref()) {
+                progress.abandon();
+            }
+            this.inner.set_complete();
+
+            return Poll::Ready(Err(errors::QueryError::Cancelled));
+        }
+
+        let mut budget = PollBudget::new(&UDP_QUERY_POLL_LOOP);
         loop {
+            if budget.exhausted(cx) {
+                return Poll::Pending;
+            }
+
             let mut this = self.as_mut().project();
             match this.inner.as_mut().project() {
                 QInitQueryProj::Fresh => {
@@ -1431,14 +2603,10 @@ impl<'a, 'b, 'c, 'd> Future for UdpQuery<'a, 'b, 'c, 'd> {
                 QInitQueryProj::ReadActiveQuery(r_active_queries) => {
                     match r_active_queries.as_mut().poll(cx) {
                         Poll::Ready(r_active_queries) => {
-                            match (
-                                r_active_queries.tcp_or_udp.get(&this.query.question),
-                                r_active_queries.tcp_only.get(&this.query.question)
-                            ) {
-                                (Some((query_id, result_sender)), _)
-                              | (_, Some((query_id, result_sender))) => {
-                                    this.query.id = *query_id;
-                                    let result_receiver = result_sender.subscribe();
+                            match r_active_queries.join(&[&r_active_queries.tcp_or_udp, &r_active_queries.tcp_only], this.query, *this.deadline) {
+                                Some((query_id, result_receiver, progress)) => {
+                                    this.query.id = query_id;
+                                    *this.progress = Some(progress);
                                     drop(r_active_queries);
 
                                     this.inner.set_following(result_receiver);
@@ -1447,7 +2615,7 @@ impl<'a, 'b, 'c, 'd> Future for UdpQuery<'a, 'b, 'c, 'd> {
                                     // TODO
                                     continue;
                                 },
-                                (None, None) => {
+                                None => {
                                     drop(r_active_queries);
                                     this.inner.set_write_active_query(&this.socket.active_queries);
 
@@ -1465,14 +2633,10 @@ impl<'a, 'b, 'c, 'd> Future for UdpQuery<'a, 'b, 'c, 'd> {
                 QInitQueryProj::WriteActiveQuery(w_active_queries) => {
                     match w_active_queries.as_mut().poll(cx) {
                         Poll::Ready(mut w_active_queries) => {
-                            match (
-                                w_active_queries.tcp_or_udp.get(&this.query.question),
-                                w_active_queries.tcp_only.get(&this.query.question)
-                            ) {
-                                (Some((query_id, result_sender)), _)
-                              | (_, Some((query_id, result_sender))) => {
-                                    this.query.id = *query_id;
-                                    let result_receiver = result_sender.subscribe();
+                            match w_active_queries.join(&[&w_active_queries.tcp_or_udp, &w_active_queries.tcp_only], this.query, *this.deadline) {
+                                Some((query_id, result_receiver, progress)) => {
+                                    this.query.id = query_id;
+                                    *this.progress = Some(progress);
                                     drop(w_active_queries);
 
                                     this.inner.set_following(result_receiver);
@@ -1481,7 +2645,7 @@ impl<'a, 'b, 'c, 'd> Future for UdpQuery<'a, 'b, 'c, 'd> {
                                     // TODO
                                     continue;
                                 },
-                                (None, None) => {
+                                None => {
                                     let (result_sender, result_receiver) = once_watch::channel();
 
                                     // This is the initial query ID. However, it could change if it
@@ -1495,19 +2659,29 @@ impl<'a, 'b, 'c, 'd> Future for UdpQuery<'a, 'b, 'c, 'd> {
                                         // keys? May want to verify that the list isn't full.
                                     }
 
+                                    // Only the runner's copy of the query is randomized. The caller's
+                                    // query, and the key it is followed by, keep the asked casing.
+                                    // Signed queries are never randomized.
+                                    let mut runner_query = this.query.clone();
+                                    let case_nonce = (this.socket.case_randomization && !is_signed(&runner_query)).then(|| CaseNonce::randomize(&mut runner_query));
+                                    let progress = QueryProgress::udp(w_active_queries.udp_retransmit_timeout, w_active_queries.udp_timeout, *this.deadline, case_nonce, is_signed(this.query));
                                     let join_handle = tokio::spawn({
                                         let udp_retransmit_timeout = w_active_queries.udp_retransmit_timeout;
                                         let udp_timeout = w_active_queries.udp_timeout;
                                         let result_receiver = result_sender.subscribe();
                                         let socket = this.socket.clone();
-                                        let mut query = this.query.clone();
+                                        let mut query = runner_query;
+                                        let progress = progress.clone();
                                         async move {
-                                            UdpQueryRunner::new(&socket, &mut query, result_receiver, &udp_retransmit_timeout, &udp_timeout).await;
+                                            UdpQueryRunner::new(&socket, &mut query, result_receiver, &udp_retransmit_timeout, &udp_timeout, progress).await;
                                         }
                                     });
 
-                                    w_active_queries.in_flight.insert(this.query.id, (result_sender.clone(), join_handle));
-                                    w_active_queries.tcp_or_udp.insert(this.query.question.clone(), (this.query.id, result_sender));
+                                    w_active_queries.in_flight.insert(this.query.id, (result_sender.clone(), join_handle, progress.clone()));
+                                    *this.progress = Some(progress);
+                                    if !is_signed(this.query) {
+                                        w_active_queries.tcp_or_udp.insert(this.query.question_key(), (this.query.id, result_sender));
+                                    }
                                     drop(w_active_queries);
 
                                     this.inner.set_following(result_receiver);
@@ -1571,9 +2745,20 @@ impl super::socket::udp::UdpSocket for MixedSocket {
         &self.udp
     }
 
+    #[inline]
+    fn listeners(&self) -> &ListenerTracker {
+        &self.listeners
+    }
+
+    #[inline]
+    fn traffic_class(&self) -> Option<TrafficClass> {
+        self.traffic_class
+    }
+
     #[inline]
     async fn listen(self: Arc<Self>, udp_reader: Arc<net::UdpSocket>, kill_udp: AwakeToken) {
         pin!(let kill_udp_awoken = kill_udp.awoken(););
+        let mut udp_receiver = UdpReceiver::new();
         loop {
             select! {
                 biased;
@@ -1585,15 +2770,23 @@ impl super::socket::udp::UdpSocket for MixedSocket {
                     println!("UDP Socket {} Timed Out. Shutting down UDP Listener.", self.upstream_socket);
                     break;
                 },
-                response = read_udp_message::<{ MAX_MESSAGE_SIZE as usize }>(&udp_reader) => {
+                response = self.read_udp_response(&udp_reader, &mut udp_receiver) => {
                     match response {
-                        Ok(response) => {
+                        Ok((mut response, wire)) => {
                             // Note: if truncation flag is set, that will be dealt with by the caller.
                             self.recent_messages_received.store(true, Ordering::Release);
                             let response_id = response.id;
                             let r_active_queries = self.active_queries.read().await;
-                            if let Some((sender, _)) = r_active_queries.in_flight.get(&response_id) {
-                                let _ = sender.send(Ok(response));
+                            if let Some((sender, _, progress)) = r_active_queries.in_flight.get(&response_id) {
+                                // A spoofed response is dropped so that the real one can still
+                                // be received.
+                                if progress.accept(&mut response) {
+                                    progress.keep_wire(&wire);
+                                    let _ = sender.send(Ok(response));
+                                } else {
+                                    self.case_mismatches.fetch_add(1, Ordering::Relaxed);
+                                    println!("UDP Socket {} discarded a response to query {response_id} that did not echo the casing of the question. It may have been spoofed.", self.upstream_socket);
+                                }
                             };
                             drop(r_active_queries);
                             // Cleanup is handled by the management processes. This
@@ -1613,6 +2806,19 @@ impl super::socket::udp::UdpSocket for MixedSocket {
 }
 
 impl MixedSocket {
+    #[inline]
+    async fn read_udp_response(&self, udp_reader: &net::UdpSocket, udp_receiver: &mut UdpReceiver<{ MAX_MESSAGE_SIZE as usize }>) -> Result<(Message, Vec<u8>), errors::UdpReceiveError> {
+        if self.traffic_class.is_none() {
+            return udp_receiver.read_udp_message(udp_reader).await;
+        }
+        let (response, wire, traffic_class) = read_udp_message_with_traffic_class::<{ MAX_MESSAGE_SIZE as usize }>(udp_reader).await?;
+        if let Some(traffic_class) = traffic_class {
+            self.last_received_tos.store(traffic_class.tos() as u16, Ordering::Release);
+            self.received_ecn[traffic_class.ecn().code() as usize].fetch_add(1, Ordering::AcqRel);
+        }
+        Ok((response, wire))
+    }
+
     #[inline]
     async fn listen_udp_cleanup(self: Arc<Self>,  kill_udp: AwakeToken) {
         println!("Cleaning up UDP socket {}", self.upstream_socket);
@@ -1644,24 +2850,62 @@ struct ActiveQueries {
     udp_retransmit_timeout: Duration,
     udp_timeout: Duration,
     tcp_timeout: Duration,
+    #[cfg(feature = "quic")]
+    quic_timeout: Duration,
+
+    in_flight: HashMap<u16, (once_watch::Sender<Result<Message, errors::QueryError>>, JoinHandle<()>, Arc<QueryProgress>)>,
+    tcp_only: JoinableQueries,
+    tcp_or_udp: JoinableQueries,
+    #[cfg(feature = "quic")]
+    quic: JoinableQueries,
+}
+
+/// The queries in flight that identical queries can join, by question.
+type JoinableQueries = HashMap<QuestionKey, (u16, once_watch::Sender<Result<Message, errors::QueryError>>)>;
 
-    in_flight: HashMap<u16, (once_watch::Sender<Result<Message, errors::QueryError>>, JoinHandle<()>)>,
-    tcp_only: HashMap<TinyVec<[Question; 1]>, (u16, once_watch::Sender<Result<Message, errors::QueryError>>)>,
-    tcp_or_udp: HashMap<TinyVec<[Question; 1]>, (u16, once_watch::Sender<Result<Message, errors::QueryError>>)>,
+/// Removes the query so that it can no longer be joined, unless a new query for the same question
+/// has already taken its place.
+#[inline]
+fn remove_joinable(joinable: &mut JoinableQueries, question_key: &QuestionKey, query_id: u16) {
+    if joinable.get(question_key).is_some_and(|(joinable_id, _)| *joinable_id == query_id) {
+        joinable.remove(question_key);
+    }
 }
 
 impl ActiveQueries {
+    /// Starts from the timeouts last learned for the peer.
     #[inline]
-    pub fn new() -> Self {
+    pub fn new(peer: &PeerStats) -> Self {
         Self {
-            udp_retransmit_timeout: INIT_UDP_RETRANSMISSION_TIMEOUT,
-            udp_timeout: INIT_UDP_TIMEOUT,
-            tcp_timeout: INIT_TCP_TIMEOUT,
+            udp_retransmit_timeout: peer.udp_retransmission_timeout(),
+            udp_timeout: peer.udp_timeout(),
+            tcp_timeout: peer.tcp_timeout(),
+            #[cfg(feature = "quic")]
+            quic_timeout: peer.quic_timeout(),
 
             in_flight: HashMap::new(),
             tcp_only: HashMap::new(),
             tcp_or_udp: HashMap::new(),
+            #[cfg(feature = "quic")]
+            quic: HashMap::new(),
+        }
+    }
+
+    /// Joins the first of the queries for the question that is still running, extending its
+    /// deadline. Queries that every caller has cancelled are skipped since their runners are
+    /// stopping. Signed queries never join another query.
+    #[inline]
+    fn join(&self, joinable: &[&JoinableQueries], query: &Message, deadline: Option<Instant>) -> Option<(u16, once_watch::Receiver<Result<Message, errors::QueryError>>, Arc<QueryProgress>)> {
+        if is_signed(query) {
+            return None;
         }
+        let question_key = query.question_key();
+        joinable.iter()
+            .filter_map(|joinable| joinable.get(&question_key))
+            .find_map(|(query_id, result_sender)| {
+                let (_, _, progress) = self.in_flight.get(query_id)?;
+                progress.follow(deadline).then(|| (*query_id, result_sender.subscribe(), progress.clone()))
+            })
     }
 }
 
@@ -1669,68 +2913,299 @@ pub struct MixedSocket {
     upstream_socket: SocketAddr,
     tcp: RwLock<TcpState>,
     udp: RwLock<UdpState>,
+    // DNS over QUIC is only available if the socket was created with the server name to verify.
+    #[cfg(feature = "quic")]
+    quic: Option<Arc<QuicSocket>>,
+    // DNS over HTTPS is only available if the socket was created with the server name and path.
+    #[cfg(feature = "https")]
+    https: Option<Arc<DohClient>>,
     active_queries: RwLock<ActiveQueries>,
+    // The UDP and TCP listener tasks, so that closing the socket can wait for them to exit.
+    listeners: ListenerTracker,
+    // The pool that the TCP and QUIC connections are counted in, if the socket was created by a
+    // `SocketManager`.
+    pool: Option<Arc<ConnectionPool>>,
 
-    // Rolling averages
-    average_tcp_response_time: Atomic<RollingAverage>,
-    average_tcp_dropped_packets: Atomic<RollingAverage>,
-    average_udp_response_time: Atomic<RollingAverage>,
-    average_udp_dropped_packets: Atomic<RollingAverage>,
-    average_udp_truncated_packets: Atomic<RollingAverage>,
+    // The rolling averages, timeouts, and EDNS payload size learned for the upstream. These
+    // outlive the socket if it was created by a `SocketManager`.
+    peer: Arc<PeerStats>,
 
     // Counters used to determine when the socket should be closed.
     recent_messages_sent: AtomicBool,
     recent_messages_received: AtomicBool,
+
+    // The traffic class that outgoing packets are marked with and the markings seen on the UDP
+    // responses. Responses are only inspected if a traffic class is set.
+    traffic_class: Option<TrafficClass>,
+    last_received_tos: AtomicU16,
+    received_ecn: [AtomicU64; 4],
+
+    // The number of TCP queries retried because their connection was closed before they were sent.
+    tcp_reuse_races: AtomicU64,
+
+    // Whether the question names of UDP queries are sent with random casing, and the number of
+    // responses discarded for not echoing it.
+    case_randomization: bool,
+    case_mismatches: AtomicU64,
+}
+
+/// The state of one transport of a `MixedSocket`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ConnectionState {
+    /// A connection is open and its listener is running.
+    Managed,
+    /// A TCP connection is being set up.
+    Establishing,
+    /// No connection is open. One will be opened by the next query.
+    None,
+    /// The transport has been disabled.
+    Blocked,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum EncryptedTransition {
+    Shutdown,
+    Enable,
+    Disable,
+}
+
+/// A point-in-time view of a `MixedSocket`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct MixedSocketStats {
+    pub tcp: ConnectionState,
+    pub udp: ConnectionState,
+    /// Queries waiting on a response from the upstream.
+    pub in_flight_queries: usize,
+    /// Query runner tasks that have been spawned and have not yet finished.
+    pub running_query_tasks: usize,
+    /// UDP and TCP listener tasks that have been spawned and have not yet exited.
+    pub running_listeners: usize,
+    /// Queries that were retried on a new TCP connection because the connection they acquired
+    /// was closed before they could be sent.
+    pub tcp_reuse_races: u64,
+    /// Responses that were discarded because their question did not echo the random casing of
+    /// the query's question.
+    pub case_mismatches: u64,
+}
+
+/// Options that apply to every connection a `MixedSocket` makes to its upstream server.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SocketOptions {
+    /// The DSCP and ECN bits that outgoing packets are marked with.
+    pub traffic_class: Option<TrafficClass>,
+    pub udp_size: UdpSizeConfig,
+    /// Sends the question names of UDP queries with each letter in a random case, and discards
+    /// responses that do not echo it back (0x20 encoding). Some servers do not preserve the
+    /// casing, so queries to them time out while this is on.
+    pub case_randomization: bool,
 }
 
 impl MixedSocket {
     #[inline]
     pub fn new(upstream_socket: SocketAddr) -> Arc<Self> {
+        Self::with_options(upstream_socket, SocketOptions::default())
+    }
+
+    #[inline]
+    pub fn with_options(upstream_socket: SocketAddr, options: SocketOptions) -> Arc<Self> {
+        Self::with_peer_stats(upstream_socket, options, Arc::new(PeerStats::new(options.udp_size)), None)
+    }
+
+    /// Creates a socket that starts from, and keeps updating, what was already learned about the
+    /// upstream. The UDP size config in `options` is ignored in favour of the one the stats were
+    /// created with. If there is a pool, the socket's TCP connection is counted in it and may be
+    /// closed by it.
+    #[inline]
+    pub(crate) fn with_peer_stats(upstream_socket: SocketAddr, options: SocketOptions, peer: Arc<PeerStats>, pool: Option<Arc<ConnectionPool>>) -> Arc<Self> {
+        Self::build(upstream_socket, options, peer, pool, #[cfg(feature = "quic")] None, #[cfg(feature = "https")] None)
+    }
+
+    /// Creates a socket that can also send queries over DNS over QUIC (`QueryOpt::Quic`). The
+    /// server's certificate is verified against `server_name`.
+    #[cfg(feature = "quic")]
+    #[inline]
+    pub fn with_quic(upstream_socket: SocketAddr, server_name: String, options: SocketOptions) -> Arc<Self> {
+        Self::with_quic_peer_stats(upstream_socket, server_name, options, Arc::new(PeerStats::new(options.udp_size)), None)
+    }
+
+    #[cfg(feature = "quic")]
+    #[inline]
+    pub(crate) fn with_quic_peer_stats(upstream_socket: SocketAddr, server_name: String, options: SocketOptions, peer: Arc<PeerStats>, pool: Option<Arc<ConnectionPool>>) -> Arc<Self> {
+        Self::build(upstream_socket, options, peer, pool, Some(QuicSocket::new(upstream_socket, server_name)), #[cfg(feature = "https")] None)
+    }
+
+    /// Creates a socket that can also send queries over DNS over HTTPS (`QueryOpt::Https`), as
+    /// POST requests to `path`. The server's certificate is verified against `server_name`.
+    #[cfg(feature = "https")]
+    #[inline]
+    pub fn with_https(upstream_socket: SocketAddr, server_name: String, path: String, options: SocketOptions) -> Arc<Self> {
+        Self::with_https_peer_stats(upstream_socket, server_name, path, options, Arc::new(PeerStats::new(options.udp_size)), None)
+    }
+
+    #[cfg(feature = "https")]
+    #[inline]
+    pub(crate) fn with_https_peer_stats(upstream_socket: SocketAddr, server_name: String, path: String, options: SocketOptions, peer: Arc<PeerStats>, pool: Option<Arc<ConnectionPool>>) -> Arc<Self> {
+        let https = DohClient::new(upstream_socket, server_name, path, DohVersionPolicy::default());
+        Self::build(upstream_socket, options, peer, pool, #[cfg(feature = "quic")] None, Some(https))
+    }
+
+    #[inline]
+    fn build(upstream_socket: SocketAddr, options: SocketOptions, peer: Arc<PeerStats>, pool: Option<Arc<ConnectionPool>>, #[cfg(feature = "quic")] quic: Option<Arc<QuicSocket>>, #[cfg(feature = "https")] https: Option<Arc<DohClient>>) -> Arc<Self> {
         Arc::new(MixedSocket {
             upstream_socket,
             tcp: RwLock::new(TcpState::None),
             udp: RwLock::new(UdpState::None),
-            active_queries: RwLock::new(ActiveQueries::new()),
+            #[cfg(feature = "quic")]
+            quic,
+            #[cfg(feature = "https")]
+            https,
+            active_queries: RwLock::new(ActiveQueries::new(&peer)),
+            listeners: ListenerTracker::default(),
+            pool,
 
-            average_tcp_response_time: Atomic::new(RollingAverage::new()),
-            average_tcp_dropped_packets: Atomic::new(RollingAverage::new()),
-            average_udp_response_time: Atomic::new(RollingAverage::new()),
-            average_udp_dropped_packets: Atomic::new(RollingAverage::new()),
-            average_udp_truncated_packets: Atomic::new(RollingAverage::new()),
+            peer,
 
             recent_messages_sent: AtomicBool::new(false),
             recent_messages_received: AtomicBool::new(false),
+
+            traffic_class: options.traffic_class,
+            last_received_tos: AtomicU16::new(NO_RECEIVED_TOS),
+            received_ecn: [AtomicU64::new(0), AtomicU64::new(0), AtomicU64::new(0), AtomicU64::new(0)],
+
+            tcp_reuse_races: AtomicU64::new(0),
+
+            case_randomization: options.case_randomization,
+            case_mismatches: AtomicU64::new(0),
         })
     }
 
+    #[inline]
+    pub fn options(&self) -> SocketOptions {
+        SocketOptions { traffic_class: self.traffic_class, udp_size: *self.peer.udp_size.config(), case_randomization: self.case_randomization }
+    }
+
+    /// The largest EDNS UDP payload size that queries to this upstream advertise. It starts at the
+    /// configured initial size and is reduced if responses go missing.
+    #[inline]
+    pub fn udp_buffer_size(&self) -> u16 {
+        self.peer.udp_size.buffer_size()
+    }
+
+    #[inline]
+    pub fn traffic_class(&self) -> Option<TrafficClass> {
+        self.traffic_class
+    }
+
+    /// The name that the DNS over QUIC server's certificate is verified against, or `None` if the
+    /// socket cannot send queries over QUIC.
+    #[cfg(feature = "quic")]
+    #[inline]
+    pub fn quic_server_name(&self) -> Option<&str> {
+        self.quic.as_ref().map(|quic| quic.server_name())
+    }
+
+    /// The client that sends the socket's DNS over HTTPS queries, or `None` if the socket cannot
+    /// send queries over HTTPS.
+    #[cfg(feature = "https")]
+    #[inline]
+    pub fn doh_client(&self) -> Option<&Arc<DohClient>> {
+        self.https.as_ref()
+    }
+
+    /// The traffic class of the most recent UDP response, if the socket was created with a
+    /// traffic class and the kernel reported one.
+    #[inline]
+    pub fn last_received_traffic_class(&self) -> Option<TrafficClass> {
+        match self.last_received_tos.load(Ordering::Acquire) {
+            NO_RECEIVED_TOS => None,
+            tos => Some(TrafficClass::from_tos(tos as u8)),
+        }
+    }
+
+    /// The number of UDP responses received with the ECN codepoint. Only counted if the socket was
+    /// created with a traffic class.
+    #[inline]
+    pub fn received_ecn_count(&self, ecn: Ecn) -> u64 {
+        self.received_ecn[ecn.code() as usize].load(Ordering::Acquire)
+    }
+
     #[inline]
     pub fn socket_address(&self) -> &SocketAddr {
         &self.upstream_socket
     }
 
+    /// What has been learned about the upstream, shared with any socket to it that replaces this
+    /// one.
+    #[inline]
+    pub fn peer_stats(&self) -> &Arc<PeerStats> {
+        &self.peer
+    }
+
     #[inline]
     pub fn average_tcp_response_time(&self) -> f64 {
-        self.average_tcp_response_time.load(Ordering::Acquire).current_average()
+        self.peer.average_tcp_response_time()
     }
 
     #[inline]
     pub fn average_dropped_tcp_packets(&self) -> f64 {
-        self.average_tcp_dropped_packets.load(Ordering::Acquire).current_average()
+        self.peer.average_dropped_tcp_packets()
     }
 
     #[inline]
     pub fn average_udp_response_time(&self) -> f64 {
-        self.average_udp_response_time.load(Ordering::Acquire).current_average()
+        self.peer.average_udp_response_time()
     }
 
     #[inline]
     pub fn average_dropped_udp_packets(&self) -> f64 {
-        self.average_udp_dropped_packets.load(Ordering::Acquire).current_average()
+        self.peer.average_dropped_udp_packets()
     }
 
     #[inline]
     pub fn average_truncated_udp_packets(&self) -> f64 {
-        self.average_udp_truncated_packets.load(Ordering::Acquire).current_average()
+        self.peer.average_truncated_udp_packets()
+    }
+
+    #[cfg(feature = "quic")]
+    #[inline]
+    pub fn average_quic_response_time(&self) -> f64 {
+        self.peer.average_quic_response_time()
+    }
+
+    #[cfg(feature = "quic")]
+    #[inline]
+    pub fn average_dropped_quic_packets(&self) -> f64 {
+        self.peer.average_dropped_quic_packets()
+    }
+
+    /// What the QUIC connection has measured about the path to the upstream, or `None` if the
+    /// socket cannot send queries over QUIC or has not connected yet.
+    #[cfg(feature = "quic")]
+    #[inline]
+    pub fn quic_path_stats(&self) -> Option<QuicPathStats> {
+        self.quic.as_ref().and_then(|quic| quic.path_stats())
+    }
+
+    /// The drop rate and response time (in milliseconds) that upstreams are compared by when
+    /// choosing which one to query. These are the UDP averages, unless the socket sends its
+    /// queries over QUIC. Then they are the QUIC averages, made worse by the connection's own
+    /// packet loss and round trip time if those are higher. The connection sees every packet, so
+    /// it notices a lossy or slow path before enough queries have been dropped to move the
+    /// averages.
+    pub fn selection_stats(&self) -> (f64, f64) {
+        #[cfg(feature = "quic")]
+        if self.quic.is_some() {
+            let mut dropped = self.average_dropped_quic_packets();
+            let mut response_time = self.average_quic_response_time();
+            if let Some(path_stats) = self.quic_path_stats() {
+                let loss_rate = path_stats.loss_rate();
+                let rtt = path_stats.rtt.as_secs_f64() * MILLISECONDS_IN_1_SECOND;
+                dropped = if dropped.is_finite() { dropped.max(loss_rate) } else { loss_rate };
+                response_time = if response_time.is_finite() { response_time.max(rtt) } else { rtt };
+            }
+            return (dropped, response_time);
+        }
+        (self.average_dropped_udp_packets(), self.average_udp_response_time())
     }
 
     #[inline]
@@ -1739,7 +3214,7 @@ impl MixedSocket {
         // for synchronization nor do we care about the order of atomic operations. We only care
         // that the operation is atomic.
         fetch_update(
-            &self.average_tcp_dropped_packets,
+            &self.peer.average_tcp_dropped_packets,
             Ordering::Relaxed,
             Ordering::Relaxed,
             |average| average.put_next(1, ROLLING_AVERAGE_TCP_MAX_DROPPED)
@@ -1753,13 +3228,13 @@ impl MixedSocket {
         // that the operation is atomic.
         (
             fetch_update(
-                &self.average_tcp_response_time,
+                &self.peer.average_tcp_response_time,
                 Ordering::Relaxed,
                 Ordering::Relaxed,
                 |average| average.put_next(u32::try_from(response_time.as_millis()).unwrap_or(u32::MAX), ROLLING_AVERAGE_TCP_MAX_RESPONSE_TIMES)
             ),
             fetch_update(
-                &self.average_tcp_dropped_packets,
+                &self.peer.average_tcp_dropped_packets,
                 Ordering::Relaxed,
                 Ordering::Relaxed,
                 |average| average.put_next(0, ROLLING_AVERAGE_TCP_MAX_DROPPED)
@@ -1767,13 +3242,49 @@ impl MixedSocket {
         )
     }
 
+    #[cfg(feature = "quic")]
+    #[inline]
+    fn add_dropped_packet_to_quic_average(&self) -> RollingAverage {
+        // We can use relaxed memory orderings with the rolling average because it is not being used
+        // for synchronization nor do we care about the order of atomic operations. We only care
+        // that the operation is atomic.
+        fetch_update(
+            &self.peer.average_quic_dropped_packets,
+            Ordering::Relaxed,
+            Ordering::Relaxed,
+            |average| average.put_next(1, ROLLING_AVERAGE_QUIC_MAX_DROPPED)
+        )
+    }
+
+    #[cfg(feature = "quic")]
+    #[inline]
+    fn add_response_time_to_quic_average(&self, response_time: Duration) -> (RollingAverage, RollingAverage) {
+        // We can use relaxed memory orderings with the rolling average because it is not being used
+        // for synchronization nor do we care about the order of atomic operations. We only care
+        // that the operation is atomic.
+        (
+            fetch_update(
+                &self.peer.average_quic_response_time,
+                Ordering::Relaxed,
+                Ordering::Relaxed,
+                |average| average.put_next(u32::try_from(response_time.as_millis()).unwrap_or(u32::MAX), ROLLING_AVERAGE_QUIC_MAX_RESPONSE_TIMES)
+            ),
+            fetch_update(
+                &self.peer.average_quic_dropped_packets,
+                Ordering::Relaxed,
+                Ordering::Relaxed,
+                |average| average.put_next(0, ROLLING_AVERAGE_QUIC_MAX_DROPPED)
+            )
+        )
+    }
+
     #[inline]
     fn add_dropped_packet_to_udp_average(&self) -> RollingAverage {
         // We can use relaxed memory orderings with the rolling average because it is not being used
         // for synchronization nor do we care about the order of atomic operations. We only care
         // that the operation is atomic.
         fetch_update(
-            &self.average_udp_dropped_packets,
+            &self.peer.average_udp_dropped_packets,
             Ordering::Relaxed,
             Ordering::Relaxed,
             |average| average.put_next(1, ROLLING_AVERAGE_UDP_MAX_DROPPED)
@@ -1787,13 +3298,13 @@ impl MixedSocket {
         // that the operation is atomic.
         (
             fetch_update(
-                &self.average_udp_response_time,
+                &self.peer.average_udp_response_time,
                 Ordering::Relaxed,
                 Ordering::Relaxed,
                 |average| average.put_next(u32::try_from(response_time.as_millis()).unwrap_or(u32::MAX), ROLLING_AVERAGE_UDP_MAX_RESPONSE_TIMES)
             ),
             fetch_update(
-                &self.average_udp_dropped_packets,
+                &self.peer.average_udp_dropped_packets,
                 Ordering::Relaxed,
                 Ordering::Relaxed,
                 |average| average.put_next(0, ROLLING_AVERAGE_UDP_MAX_DROPPED)
@@ -1807,7 +3318,7 @@ impl MixedSocket {
         // for synchronization nor do we care about the order of atomic operations. We only care
         // that the operation is atomic.
         fetch_update(
-            &self.average_udp_truncated_packets,
+            &self.peer.average_udp_truncated_packets,
             Ordering::Relaxed,
             Ordering::Relaxed,
             |average| average.put_next(truncated.into(), ROLLING_AVERAGE_UDP_MAX_TRUNCATED)
@@ -1856,6 +3367,39 @@ impl MixedSocket {
         self.recent_messages_received.swap(false, Ordering::AcqRel)
     }
 
+    /// The state of both transports and the number of queries in flight.
+    pub async fn stats(&self) -> MixedSocketStats {
+        let r_tcp = self.tcp.read().await;
+        let tcp = match &*r_tcp {
+            TcpState::Managed { socket: _, kill: _ } => ConnectionState::Managed,
+            TcpState::Establishing { sender: _, kill: _ } => ConnectionState::Establishing,
+            TcpState::None => ConnectionState::None,
+            TcpState::Blocked => ConnectionState::Blocked,
+        };
+        drop(r_tcp);
+
+        let r_udp = self.udp.read().await;
+        let udp = match &*r_udp {
+            UdpState::Managed(_, _) => ConnectionState::Managed,
+            UdpState::None => ConnectionState::None,
+            UdpState::Blocked => ConnectionState::Blocked,
+        };
+        drop(r_udp);
+
+        let r_active_queries = self.active_queries.read().await;
+        let in_flight_queries = r_active_queries.in_flight.len();
+        let running_query_tasks = r_active_queries.in_flight.values()
+            .filter(|(_, join_handle, _)| !join_handle.is_finished())
+            .count();
+        drop(r_active_queries);
+
+        let running_listeners = self.listeners.running();
+        let tcp_reuse_races = self.tcp_reuse_races.load(Ordering::Relaxed);
+        let case_mismatches = self.case_mismatches.load(Ordering::Relaxed);
+
+        MixedSocketStats { tcp, udp, in_flight_queries, running_query_tasks, running_listeners, tcp_reuse_races, case_mismatches }
+    }
+
     #[inline]
     pub async fn start(self: Arc<Self>) -> Result<(), errors::SocketInitError> {
         match join!(
@@ -1873,7 +3417,9 @@ impl MixedSocket {
     pub async fn shutdown(self: Arc<Self>) {
         join!(
             <Self as UdpSocket>::shutdown(self.clone()),
-            <Self as TcpSocket>::shutdown(self),
+            <Self as TcpSocket>::shutdown(self.clone()),
+            self.transition_quic(EncryptedTransition::Shutdown),
+            self.transition_https(EncryptedTransition::Shutdown),
         );
     }
 
@@ -1881,7 +3427,9 @@ impl MixedSocket {
     pub async fn enable(self: Arc<Self>) {
         join!(
             <Self as UdpSocket>::enable(self.clone()),
-            <Self as TcpSocket>::enable(self),
+            <Self as TcpSocket>::enable(self.clone()),
+            self.transition_quic(EncryptedTransition::Enable),
+            self.transition_https(EncryptedTransition::Enable),
         );
     }
 
@@ -1889,15 +3437,118 @@ impl MixedSocket {
     pub async fn disable(self: Arc<Self>) {
         join!(
             <Self as UdpSocket>::disable(self.clone()),
-            <Self as TcpSocket>::disable(self),
+            <Self as TcpSocket>::disable(self.clone()),
+            self.transition_quic(EncryptedTransition::Disable),
+            self.transition_https(EncryptedTransition::Disable),
         );
     }
 
+    /// Fails every query that is still in flight and aborts its runner task. Each query's caller
+    /// gets a shutdown error right away instead of waiting on a response that can no longer
+    /// arrive. This should be done after the socket is disabled, so that no new queries start.
+    /// Returns the number of queries that were abandoned.
+    pub async fn abort_queries(&self) -> usize {
+        let mut w_active_queries = self.active_queries.write().await;
+        let in_flight = std::mem::take(&mut w_active_queries.in_flight);
+        // The same channels are also in `in_flight`, which closes them below.
+        w_active_queries.tcp_only.clear();
+        w_active_queries.tcp_or_udp.clear();
+        #[cfg(feature = "quic")]
+        w_active_queries.quic.clear();
+        drop(w_active_queries);
+
+        let abandoned = in_flight.len();
+        for (_, (result_sender, join_handle, _)) in in_flight {
+            // Dropping the senders does not close the channel, so the callers following it would
+            // wait forever. Closing it fails them with a shutdown error.
+            result_sender.close();
+            join_handle.abort();
+        }
+        abandoned
+    }
+
+    /// Whether a TCP connection is open or being set up. If another task holds the lock on the
+    /// TCP state, the connection is assumed to be in use.
+    pub(crate) fn tcp_connected(&self) -> bool {
+        match self.tcp.try_read() {
+            Ok(r_tcp) => matches!(&*r_tcp, TcpState::Managed { socket: _, kill: _ } | TcpState::Establishing { sender: _, kill: _ }),
+            Err(_) => true,
+        }
+    }
+
+    /// Whether a QUIC connection is open or being set up.
+    #[cfg(feature = "quic")]
+    #[inline]
+    pub(crate) fn quic_connected(&self) -> bool {
+        self.quic.as_ref().is_some_and(|quic| quic.is_connected())
+    }
+
+    /// Closes the TCP connection, if there is one. Unlike `shutdown()`, the UDP socket and QUIC
+    /// connection are left open.
+    #[inline]
+    pub(crate) async fn shutdown_tcp(self: Arc<Self>) {
+        <Self as TcpSocket>::shutdown(self).await;
+    }
+
+    /// Closes the QUIC connection, if there is one. Unlike `shutdown()`, the UDP socket and TCP
+    /// connection are left open.
+    #[cfg(feature = "quic")]
+    #[inline]
+    pub(crate) async fn shutdown_quic(self: Arc<Self>) {
+        self.transition_quic(EncryptedTransition::Shutdown).await;
+    }
+
+    /// Waits until the UDP and TCP listener tasks have exited. Listeners exit once their
+    /// connection is killed, such as by `shutdown()` or `disable()`.
+    #[inline]
+    pub async fn wait_for_listeners(&self) {
+        self.listeners.wait_for_exit().await;
+    }
+
+    /// Applies the transition to the QUIC connection, if the socket has one.
+    #[cfg_attr(not(feature = "quic"), allow(unused_variables))]
+    #[inline]
+    async fn transition_quic(&self, transition: EncryptedTransition) {
+        #[cfg(feature = "quic")]
+        if let Some(quic) = &self.quic {
+            let _ = match transition {
+                EncryptedTransition::Shutdown => quic.clone().shutdown_quic().await,
+                EncryptedTransition::Enable => quic.clone().enable_quic().await,
+                EncryptedTransition::Disable => quic.clone().disable_quic().await,
+            };
+        }
+    }
+
+    /// Applies the transition to the DNS over HTTPS client, if the socket has one.
+    #[cfg_attr(not(feature = "https"), allow(unused_variables))]
+    #[inline]
+    async fn transition_https(&self, transition: EncryptedTransition) {
+        #[cfg(feature = "https")]
+        if let Some(https) = &self.https {
+            match transition {
+                EncryptedTransition::Shutdown => https.shutdown().await,
+                EncryptedTransition::Enable => https.enable().await,
+                EncryptedTransition::Disable => https.disable().await,
+            }
+        }
+    }
+
+    #[inline]
     pub fn query<'a, 'b, 'c, 'd>(self: &'a Arc<Self>, query: &'b mut Message, options: QueryOpt) -> MixedQuery<'a, 'b, 'c, 'd> {
+        self.query_with_deadline(query, options, None)
+    }
+
+    /// Sends the query the same way as `query()`, but no retransmission or timeout is allowed to
+    /// run past the deadline. Once it passes, the query is abandoned (unless an identical query
+    /// with a later deadline is waiting on it) and `QueryError::Timeout` is returned.
+    pub fn query_with_deadline<'a, 'b, 'c, 'd>(self: &'a Arc<Self>, query: &'b mut Message, options: QueryOpt, deadline: Option<Instant>) -> MixedQuery<'a, 'b, 'c, 'd> {
         // If the UDP socket is unreliable, send most data via TCP. Some queries should still use
         // UDP to determine if the network conditions are improving. However, if the TCP connection
         // is also unstable, then we should not rely on it.
         let query_task = match options {
+            QueryOpt::UdpTcp if query.estimated_wire_size(true) > MAX_UDP_QUERY_SIZE => {
+                MixedQuery::Tcp(TcpQuery::new(&self, query, deadline))
+            },
             QueryOpt::UdpTcp => {
                 let average_dropped_udp_packets = self.average_dropped_udp_packets();
                 let average_truncated_udp_packets = self.average_truncated_udp_packets();
@@ -1907,34 +3558,69 @@ impl MixedSocket {
                 && (average_dropped_tcp_packets.is_nan() || (average_dropped_tcp_packets <= 0.25))
                 && (rand::random::<f32>() >= 0.20)
                 {
-                    MixedQuery::Tcp(TcpQuery::new(&self, query))
+                    MixedQuery::Tcp(TcpQuery::new(&self, query, deadline))
+                // Large responses on this path keep going missing, even at the reduced payload
+                // size. Rather than relying on fragments, use TCP.
+                } else if self.peer.udp_size.prefers_tcp() && (rand::random::<f32>() >= 0.20) {
+                    MixedQuery::Tcp(TcpQuery::new(&self, query, deadline))
                 } else {
-                    MixedQuery::Udp(UdpQuery::new(&self, query))
+                    self.peer.udp_size.limit(query);
+                    MixedQuery::Udp(UdpQuery::new(&self, query, deadline))
                 }
             },
             QueryOpt::Tcp => {
-                MixedQuery::Tcp(TcpQuery::new(&self, query))
+                MixedQuery::Tcp(TcpQuery::new(&self, query, deadline))
             },
-            QueryOpt::Quic => todo!(),
-            QueryOpt::Tls => todo!(),
-            QueryOpt::QuicTls => todo!(),
-            QueryOpt::Https => todo!(),
+            #[cfg(feature = "quic")]
+            QueryOpt::Quic if self.quic.is_some() => {
+                MixedQuery::Quic(QuicQuery::new(&self, query, deadline))
+            },
+            // The other encrypted transports are handled by their own sockets.
+            #[cfg(feature = "quic")]
+            QueryOpt::Quic | QueryOpt::QuicTls => MixedQuery::Unsupported(options),
+            #[cfg(feature = "tls")]
+            QueryOpt::Tls | QueryOpt::TlsInsecure => MixedQuery::Unsupported(options),
+            #[cfg(feature = "https")]
+            QueryOpt::Https if self.https.is_some() => {
+                MixedQuery::Https(HttpsQuery::new(&self, query, deadline))
+            },
+            #[cfg(feature = "https")]
+            QueryOpt::Https => MixedQuery::Unsupported(options),
         };
 
+        if let Some(pool) = &self.pool {
+            match &query_task {
+                MixedQuery::Tcp(_) => pool.touch(self.upstream_socket, None, PooledConnection::Tcp(Arc::downgrade(self))),
+                #[cfg(feature = "quic")]
+                MixedQuery::Quic(_) => pool.touch(self.upstream_socket, self.quic_server_name(), PooledConnection::Quic(Arc::downgrade(self))),
+                _ => (),
+            }
+        }
+
         return query_task;
     }
+
+    /// Sends the query the same way as `query_with_deadline()`, stopping early if it is cancelled,
+    /// and also returns how it was carried.
+    pub async fn query_with_details(self: &Arc<Self>, query: &mut Message, options: QueryOpt, deadline: Option<Instant>, cancellation: Option<&AwakeToken>) -> (Result<Message, errors::QueryError>, Option<QueryDetails>) {
+        let query_task = self.query_with_deadline(query, options, deadline).with_cancellation(cancellation);
+        pin!(query_task);
+        let result = query_task.as_mut().await;
+        (result, query_task.details())
+    }
 }
 
 #[cfg(test)]
 mod mixed_udp_tcp_tests {
-    use std::{net::{IpAddr, Ipv4Addr, SocketAddr}, time::Duration};
+    use std::{collections::HashMap, net::{IpAddr, Ipv4Addr, SocketAddr}, sync::Arc, time::Duration};
 
-    use dns_lib::{query::{message::Message, qr::QR, question::Question}, resource_record::{opcode::OpCode, rclass::RClass, rcode::RCode, resource_record::ResourceRecord, rtype::RType, time::Time, types::a::A}, serde::wire::{from_wire::FromWire, read_wire::ReadWire, to_wire::ToWire}, types::c_domain_name::CDomainName};
+    use async_lib::awake_token::AwakeToken;
+    use dns_lib::{query::{message::Message, qr::QR, question::Question}, resource_record::{opcode::OpCode, rclass::RClass, rcode::RCode, resource_record::{RecordData, ResourceRecord}, rtype::RType, time::Time, types::{a::A, cname::CNAME, soa::SOA}}, serde::wire::{from_wire::FromWire, read_wire::ReadWire, to_wire::ToWire}, tsig::{MacCalculator, TsigAlgorithm, TsigExchange, TsigKey}, types::c_domain_name::{CDomainName, CmpDomainName, CompressionMap}};
     use tinyvec::TinyVec;
-    use tokio::{io::AsyncReadExt, select};
+    use tokio::{io::AsyncReadExt, pin, select};
     use ux::u3;
 
-    use crate::mixed_tcp_udp::{MixedSocket, QueryOpt};
+    use crate::{errors, mixed_tcp_udp::{is_tcp_reuse_race, poll_loop_stats, ConnectionState, MixedSocket, MixedTransport, QueryOpt, SocketOptions, INIT_UDP_RETRANSMISSION_TIMEOUT, INIT_UDP_TIMEOUT, UDP_RETRANSMISSIONS}};
 
     const LISTEN_ADDR: SocketAddr = SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 65000);
     const SEND_ADDR: SocketAddr = SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 65000);
@@ -2089,9 +3775,411 @@ mod mixed_udp_tcp_tests {
 
         // Test: Client connection failed
         let query_task_response = query_task.await;
-        assert!(query_task_response.is_err());   //< io error
+        assert!(matches!(query_task_response, Ok(Err(_))));   //< io error
+
+        // Cleanup
+        mixed_socket.disable().await;
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn query_details_count_retransmissions() {
+        const DETAILS_ADDR: SocketAddr = SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 65001);
+
+        // Setup
+        let listen_udp_socket = tokio::net::UdpSocket::bind(DETAILS_ADDR).await.unwrap();
+        let question = Question::new(CDomainName::from_utf8("example.org.").unwrap(), RType::A, RClass::Internet);
+        let mut query = Message::from(question);
+        let mixed_socket = MixedSocket::new(DETAILS_ADDR);
+
+        // Test: Every UDP transmission is dropped and nothing is listening for TCP.
+        let query_task = tokio::spawn({
+            let mixed_socket = mixed_socket.clone();
+            async move { mixed_socket.query_with_details(&mut query, QueryOpt::UdpTcp, None, None).await }
+        });
+        let mut buffer = [0_u8; 512];
+        let mut transmissions = Vec::new();
+        for _ in 0..=UDP_RETRANSMISSIONS {
+            let bytes_read = select! {
+                bytes_read = listen_udp_socket.recv(&mut buffer) => bytes_read.unwrap(),
+                () = tokio::time::sleep(Duration::from_secs(2)) => panic!("Did not receive the query in time."),
+            };
+            transmissions.push(bytes_read);
+        }
+
+        let (result, details) = select! {
+            result = query_task => result.unwrap(),
+            () = tokio::time::sleep(Duration::from_secs(5)) => panic!("The query did not fail in time."),
+        };
+        assert!(result.is_err());
+        let details = details.unwrap();
+        assert_eq!(details.transport, MixedTransport::Udp);
+        assert_eq!(details.retransmissions, UDP_RETRANSMISSIONS);
+        assert_eq!(details.retransmission_timeout, Some(INIT_UDP_RETRANSMISSION_TIMEOUT));
+        assert_eq!(details.timeout, INIT_UDP_TIMEOUT);
+        assert_eq!(details.query_size, transmissions[0]);
+        assert_eq!(details.udp_payload_size, None);
+        let [_, _, udp_query_runner, udp_query] = poll_loop_stats();
+        assert!((udp_query_runner.polls > 0) && (udp_query_runner.iterations >= udp_query_runner.polls));
+        assert!(udp_query.polls > 0);
+
+        // Cleanup
+        mixed_socket.disable().await;
+    }
+
+    #[cfg(feature = "quic")]
+    #[tokio::test(flavor = "multi_thread")]
+    async fn quic_query_requires_server_name() {
+        const QUIC_ADDR: SocketAddr = SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 65003);
+
+        let question = Question::new(CDomainName::from_utf8("example.org.").unwrap(), RType::A, RClass::Internet);
+
+        // Test: A socket created without a server name cannot send queries over QUIC.
+        let mut query = Message::from(question.clone());
+        let mixed_socket = MixedSocket::new(QUIC_ADDR);
+        let (result, details) = mixed_socket.query_with_details(&mut query, QueryOpt::Quic, None, None).await;
+        assert!(matches!(result, Err(errors::QueryError::UnsupportedTransport(QueryOpt::Quic))));
+        assert!(details.is_none());
+
+        // Test: Nothing is listening, so the query gives up at the deadline.
+        let mut query = Message::from(question);
+        let quic_socket = MixedSocket::with_quic(QUIC_ADDR, "dns.example".to_string(), Default::default());
+        let deadline = tokio::time::Instant::now() + Duration::from_millis(300);
+        let (result, details) = select! {
+            result = quic_socket.query_with_details(&mut query, QueryOpt::Quic, Some(deadline), None) => result,
+            () = tokio::time::sleep(Duration::from_secs(5)) => panic!("The query did not give up in time."),
+        };
+        assert!(result.is_err());
+        assert_eq!(details.unwrap().transport, MixedTransport::Quic);
+
+        // Test: The connection was never made and the query gave up at its deadline rather than
+        // timing out, so there is nothing to compare the socket by yet.
+        assert!(quic_socket.quic_path_stats().is_none());
+        let (dropped, response_time) = quic_socket.selection_stats();
+        assert!(dropped.is_nan() && response_time.is_nan());
+        assert!(mixed_socket.quic_path_stats().is_none());
+
+        // Cleanup
+        quic_socket.disable().await;
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn query_deadline_stops_retransmissions() {
+        const DEADLINE_ADDR: SocketAddr = SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 65002);
+
+        // Setup
+        let listen_udp_socket = tokio::net::UdpSocket::bind(DEADLINE_ADDR).await.unwrap();
+        let question = Question::new(CDomainName::from_utf8("example.org.").unwrap(), RType::A, RClass::Internet);
+        let mut query = Message::from(question);
+        let mixed_socket = MixedSocket::new(DEADLINE_ADDR);
+
+        // Test: The deadline is shorter than the first retransmission timeout, so the query is
+        // only sent once and gives up at the deadline instead of retransmitting.
+        let started = tokio::time::Instant::now();
+        let deadline = started + (INIT_UDP_RETRANSMISSION_TIMEOUT / 5);
+        let query_task = tokio::spawn({
+            let mixed_socket = mixed_socket.clone();
+            async move { mixed_socket.query_with_details(&mut query, QueryOpt::UdpTcp, Some(deadline), None).await }
+        });
+        let mut buffer = [0_u8; 512];
+        select! {
+            bytes_read = listen_udp_socket.recv(&mut buffer) => { bytes_read.unwrap(); },
+            () = tokio::time::sleep(Duration::from_secs(2)) => panic!("Did not receive the query in time."),
+        };
+
+        let (result, details) = select! {
+            result = query_task => result.unwrap(),
+            () = tokio::time::sleep(Duration::from_secs(2)) => panic!("The query did not fail in time."),
+        };
+        assert!(matches!(result, Err(errors::QueryError::Timeout)));
+        assert!(started.elapsed() < INIT_UDP_RETRANSMISSION_TIMEOUT);
+        assert_eq!(details.unwrap().retransmissions, 0);
+        let retransmission = select! {
+            bytes_read = listen_udp_socket.recv(&mut buffer) => Some(bytes_read.unwrap()),
+            () = tokio::time::sleep(INIT_UDP_RETRANSMISSION_TIMEOUT + INIT_UDP_TIMEOUT) => None,
+        };
+        assert_eq!(retransmission, None);
+
+        // Cleanup
+        mixed_socket.disable().await;
+    }
+
+    #[tokio::test]
+    async fn responses_must_echo_the_random_case() {
+        let listen_udp_socket = tokio::net::UdpSocket::bind((Ipv4Addr::LOCALHOST, 0)).await.unwrap();
+        let mixed_socket = MixedSocket::with_options(listen_udp_socket.local_addr().unwrap(), SocketOptions { case_randomization: true, ..SocketOptions::default() });
+        let qname = CDomainName::from_utf8("www.example.org.").unwrap();
+        let query_task = tokio::spawn({
+            let mixed_socket = mixed_socket.clone();
+            let mut query = Message::from(Question::new(qname.clone(), RType::A, RClass::Internet));
+            async move { mixed_socket.query(&mut query, QueryOpt::UdpTcp).await }
+        });
+
+        let mut buffer = [0_u8; 512];
+        let (bytes_read, client) = listen_udp_socket.recv_from(&mut buffer).await.unwrap();
+        let query = Message::from_wire_format(&mut ReadWire::from_bytes(&buffer[..bytes_read])).unwrap();
+        let sent_qname = query.question[0].qname().clone();
+        assert!(sent_qname.matches(&qname));
+
+        let mut response = query.clone();
+        response.qr = QR::Response;
+        response.answer = vec![ResourceRecord::new(sent_qname.clone(), RClass::Internet, Time::from_secs(3600), A::new(Ipv4Addr::LOCALHOST)).into()];
+        // A spoofed response gets the ID right but every letter's case wrong.
+        let mut spoofed_qname = sent_qname.clone();
+        let mut sent_lowercase = sent_qname.to_string().into_bytes().into_iter()
+            .filter(u8::is_ascii_alphabetic)
+            .map(|letter| letter.is_ascii_lowercase());
+        spoofed_qname.set_case_with(|| sent_lowercase.next().unwrap());
+        let mut spoofed_response = response.clone();
+        spoofed_response.question = TinyVec::from([query.question[0].with_new_qname(spoofed_qname)]);
+        for message in [spoofed_response, response] {
+            let wire = message.to_wire_vec(&mut Some(CompressionMap::new())).unwrap();
+            listen_udp_socket.send_to(&wire, client).await.unwrap();
+        }
+
+        // The caller gets the real response, with the casing that it asked with.
+        let response = query_task.await.unwrap().unwrap();
+        assert!(response.question[0].qname().matches_case(&qname));
+        assert!(response.answer[0].get_name().matches_case(&qname));
+        assert_eq!(mixed_socket.stats().await.case_mismatches, 1);
+
+        // Cleanup
+        mixed_socket.disable().await;
+    }
+
+    #[tokio::test]
+    async fn every_name_in_the_response_gets_the_asked_case() {
+        let listen_udp_socket = tokio::net::UdpSocket::bind((Ipv4Addr::LOCALHOST, 0)).await.unwrap();
+        let mixed_socket = MixedSocket::with_options(listen_udp_socket.local_addr().unwrap(), SocketOptions { case_randomization: true, ..SocketOptions::default() });
+        let qname = CDomainName::from_utf8("example.org.").unwrap();
+        let query_task = tokio::spawn({
+            let mixed_socket = mixed_socket.clone();
+            let mut query = Message::from(Question::new(qname.clone(), RType::NS, RClass::Internet));
+            async move { mixed_socket.query(&mut query, QueryOpt::UdpTcp).await }
+        });
+
+        let mut buffer = [0_u8; 512];
+        let (bytes_read, client) = listen_udp_socket.recv_from(&mut buffer).await.unwrap();
+        let query = Message::from_wire_format(&mut ReadWire::from_bytes(&buffer[..bytes_read])).unwrap();
+        let sent_qname = query.question[0].qname().clone();
+
+        let mut response = query.clone();
+        response.qr = QR::Response;
+        let alias = CDomainName::from_utf8("alias.example.net.").unwrap();
+        response.answer = vec![ResourceRecord::new(alias.clone(), RClass::Internet, Time::from_secs(3600), CNAME::new(sent_qname.clone())).into()];
+        response.authority = vec![ResourceRecord::new(sent_qname.clone(), RClass::Internet, Time::from_secs(3600), SOA::new(sent_qname.clone(), CDomainName::from_utf8("hostmaster.example.org.").unwrap(), 1, Time::from_secs(7200), Time::from_secs(3600), Time::from_secs(1209600), 300)).into()];
+        response.additional = vec![ResourceRecord::new(sent_qname.clone(), RClass::Internet, Time::from_secs(3600), A::new(Ipv4Addr::LOCALHOST)).into()];
+        let wire = response.to_wire_vec(&mut Some(CompressionMap::new())).unwrap();
+        listen_udp_socket.send_to(&wire, client).await.unwrap();
+
+        let response = query_task.await.unwrap().unwrap();
+        let RecordData::CNAME(cname) = response.answer[0].get_rdata() else { panic!("expected a CNAME record") };
+        assert!(cname.primary_name().matches_case(&qname));
+        assert!(response.answer[0].get_name().matches_case(&alias));
+        assert!(response.authority[0].get_name().matches_case(&qname));
+        let RecordData::SOA(soa) = response.authority[0].get_rdata() else { panic!("expected an SOA record") };
+        assert!(soa.main_domain_name().matches_case(&qname));
+        assert!(response.additional[0].get_name().matches_case(&qname));
+
+        // Cleanup
+        mixed_socket.disable().await;
+    }
+
+    /// Not a real MAC, but it depends on every octet of the key and the data.
+    struct XorCalculator;
+
+    impl MacCalculator for XorCalculator {
+        fn supports(&self, _algorithm: TsigAlgorithm) -> bool { true }
+
+        fn mac(&self, algorithm: TsigAlgorithm, secret: &[u8], data: &[u8]) -> Option<Vec<u8>> {
+            let mut mac = vec![0_u8; algorithm.output_length()];
+            for (index, octet) in secret.iter().chain(data).enumerate() {
+                let position = index % mac.len();
+                mac[position] = mac[position].rotate_left(3) ^ octet ^ (index as u8);
+            }
+            Some(mac)
+        }
+    }
+
+    #[tokio::test]
+    async fn signed_queries_keep_their_case() {
+        let listen_udp_socket = tokio::net::UdpSocket::bind((Ipv4Addr::LOCALHOST, 0)).await.unwrap();
+        let mixed_socket = MixedSocket::with_options(listen_udp_socket.local_addr().unwrap(), SocketOptions { case_randomization: true, ..SocketOptions::default() });
+        let key = TsigKey::new(CDomainName::from_utf8("key.example.").unwrap(), TsigAlgorithm::HmacSha256, b"secret".to_vec());
+        let qname = CDomainName::from_utf8("www.example.org.").unwrap();
+        let query_task = tokio::spawn({
+            let mixed_socket = mixed_socket.clone();
+            let mut query = Message::from(Question::new(qname.clone(), RType::A, RClass::Internet));
+            TsigExchange::new(&key, &XorCalculator).unwrap().sign(&mut query).unwrap();
+            async move { mixed_socket.query(&mut query, QueryOpt::UdpTcp).await }
+        });
+
+        // The name server can verify the query because it was sent with the casing it was signed
+        // with.
+        let mut buffer = [0_u8; 512];
+        let (bytes_read, client) = listen_udp_socket.recv_from(&mut buffer).await.unwrap();
+        TsigExchange::new(&key, &XorCalculator).unwrap().verify(&buffer[..bytes_read]).unwrap();
+        let query = Message::from_wire_format(&mut ReadWire::from_bytes(&buffer[..bytes_read])).unwrap();
+        assert!(query.question[0].qname().matches_case(&qname));
+
+        let mut response = query.clone();
+        response.qr = QR::Response;
+        response.additional.clear();
+        let wire = response.to_wire_vec(&mut Some(CompressionMap::new())).unwrap();
+        listen_udp_socket.send_to(&wire, client).await.unwrap();
+        query_task.await.unwrap().unwrap();
+
+        // Cleanup
+        mixed_socket.disable().await;
+    }
+
+    #[tokio::test]
+    async fn identical_signed_queries_are_not_joined() {
+        let listen_udp_socket = tokio::net::UdpSocket::bind((Ipv4Addr::LOCALHOST, 0)).await.unwrap();
+        let mixed_socket = MixedSocket::new(listen_udp_socket.local_addr().unwrap());
+        let key = TsigKey::new(CDomainName::from_utf8("key.example.").unwrap(), TsigAlgorithm::HmacSha256, b"secret".to_vec());
+        let question = Question::new(CDomainName::from_utf8("www.example.org.").unwrap(), RType::A, RClass::Internet);
+        let query_tasks = [(), ()].map(|()| {
+            let mixed_socket = mixed_socket.clone();
+            let mut query = Message::from(question.clone());
+            TsigExchange::new(&key, &XorCalculator).unwrap().sign(&mut query).unwrap();
+            tokio::spawn(async move {
+                let query_task = mixed_socket.query(&mut query, QueryOpt::UdpTcp);
+                pin!(query_task);
+                let response = query_task.as_mut().await;
+                (response, query_task.take_response_wire())
+            })
+        });
+
+        // Each signed query is sent on its own, even though they ask the same question, and each
+        // gets back the bytes of its own response. The responses are not compressed, unlike the
+        // messages the socket writes.
+        let mut response_wires = HashMap::new();
+        let mut buffer = [0_u8; 512];
+        for _ in 0..2 {
+            let (bytes_read, client) = select! {
+                received = listen_udp_socket.recv_from(&mut buffer) => received.unwrap(),
+                () = tokio::time::sleep(Duration::from_secs(2)) => panic!("Did not receive both queries in time."),
+            };
+            let mut response = Message::from_wire_format(&mut ReadWire::from_bytes(&buffer[..bytes_read])).unwrap();
+            response.qr = QR::Response;
+            response.additional.clear();
+            let wire = response.to_wire_vec(&mut None).unwrap();
+            listen_udp_socket.send_to(&wire, client).await.unwrap();
+            response_wires.insert(response.id, wire);
+        }
+        for query_task in query_tasks {
+            let (response, wire) = query_task.await.unwrap();
+            assert_eq!(wire.as_ref(), response_wires.get(&response.unwrap().id));
+        }
+
+        // Cleanup
+        mixed_socket.disable().await;
+    }
+
+    #[cfg(feature = "https")]
+    #[tokio::test]
+    async fn https_queries_are_sent_through_the_doh_client() {
+        let question = Question::new(CDomainName::from_utf8("example.org.").unwrap(), RType::A, RClass::Internet);
+
+        // Without a server name and path, the socket cannot send DoH queries.
+        let socket = MixedSocket::new(SEND_ADDR);
+        let mut query = Message::from(question.clone());
+        assert!(matches!(socket.query(&mut query, QueryOpt::Https).await, Err(errors::QueryError::UnsupportedTransport(QueryOpt::Https))));
+
+        // Nothing listens on the discard port, so the connection is refused.
+        let upstream = SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 9);
+        let socket = MixedSocket::with_https(upstream, "dns.example".to_string(), "/dns-query".to_string(), SocketOptions::default());
+        let mut query = Message::from(question.clone());
+        let (result, details) = socket.query_with_details(&mut query, QueryOpt::Https, None, None).await;
+        assert!(matches!(result, Err(errors::QueryError::HttpsSocket(_))));
+        assert_eq!(details.unwrap().transport, MixedTransport::Https);
+
+        // Disabling the socket blocks its HTTP/2 connection.
+        socket.clone().disable().await;
+        assert_eq!(socket.doh_client().unwrap().http2_state().await, ConnectionState::Blocked);
+        let mut query = Message::from(question.clone());
+        assert!(matches!(socket.query(&mut query, QueryOpt::Https).await, Err(errors::QueryError::HttpsSocket(_))));
+        socket.clone().enable().await;
+        assert_eq!(socket.doh_client().unwrap().http2_state().await, ConnectionState::None);
+
+        let cancellation = AwakeToken::new();
+        cancellation.awake();
+        let mut query = Message::from(question);
+        let (result, _) = socket.query_with_details(&mut query, QueryOpt::Https, None, Some(&cancellation)).await;
+        assert!(matches!(result, Err(errors::QueryError::Cancelled)));
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn cancelled_queries_are_stopped_and_removed() {
+        // Setup
+        let listen_udp_socket = tokio::net::UdpSocket::bind((Ipv4Addr::LOCALHOST, 0)).await.unwrap();
+        let mixed_socket = MixedSocket::new(listen_udp_socket.local_addr().unwrap());
+        let question = Question::new(CDomainName::from_utf8("example.org.").unwrap(), RType::A, RClass::Internet);
+        let first_cancellation = AwakeToken::new();
+        let first_query = tokio::spawn({
+            let mixed_socket = mixed_socket.clone();
+            let first_cancellation = first_cancellation.clone();
+            let mut query = Message::from(question.clone());
+            async move { mixed_socket.query(&mut query, QueryOpt::UdpTcp).with_cancellation(Some(&first_cancellation)).await }
+        });
+        let mut buffer = [0_u8; 512];
+        select! {
+            bytes_read = listen_udp_socket.recv(&mut buffer) => { bytes_read.unwrap(); },
+            () = tokio::time::sleep(Duration::from_secs(2)) => panic!("Did not receive the query in time."),
+        };
+
+        // A second caller joins the query that is already in flight.
+        let second_cancellation = AwakeToken::new();
+        let mut second_query = Message::from(question.clone());
+        let mut second_query = Box::pin(mixed_socket.query(&mut second_query, QueryOpt::UdpTcp).with_cancellation(Some(&second_cancellation)));
+        assert!(tokio::time::timeout(Duration::from_millis(50), second_query.as_mut()).await.is_err());
+        let r_active_queries = mixed_socket.active_queries.read().await;
+        assert_eq!(r_active_queries.in_flight.len(), 1);
+        let progress = r_active_queries.in_flight.values()
+            .map(|(_, _, progress)| Arc::downgrade(progress))
+            .next()
+            .unwrap();
+        drop(r_active_queries);
+
+        // Test: The query keeps running while another caller is still waiting on it.
+        first_cancellation.awake();
+        assert!(matches!(first_query.await.unwrap(), Err(errors::QueryError::Cancelled)));
+        assert_eq!(mixed_socket.stats().await.running_query_tasks, 1);
+
+        // Test: Once the last caller is cancelled and dropped, the runner stops well before its
+        // retransmission timeout and takes the query out of the socket.
+        second_cancellation.awake();
+        drop(second_query);
+        let runner_exited = tokio::time::timeout(INIT_UDP_RETRANSMISSION_TIMEOUT / 2, async {
+            while progress.strong_count() > 0 {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        }).await;
+        assert!(runner_exited.is_ok());
+        let r_active_queries = mixed_socket.active_queries.read().await;
+        assert!(r_active_queries.in_flight.is_empty());
+        assert!(r_active_queries.tcp_only.is_empty());
+        assert!(r_active_queries.tcp_or_udp.is_empty());
+        drop(r_active_queries);
+        let stats = mixed_socket.stats().await;
+        assert_eq!((stats.in_flight_queries, stats.running_query_tasks), (0, 0));
+
+        // Test: The query was not retransmitted after it was cancelled.
+        let retransmission = select! {
+            bytes_read = listen_udp_socket.recv(&mut buffer) => Some(bytes_read.unwrap()),
+            () = tokio::time::sleep(INIT_UDP_RETRANSMISSION_TIMEOUT) => None,
+        };
+        assert_eq!(retransmission, None);
 
         // Cleanup
         mixed_socket.disable().await;
     }
+
+    #[test]
+    fn tcp_reuse_races() {
+        assert!(is_tcp_reuse_race(&errors::QueryError::TcpSocket(errors::TcpSocketError::Shutdown)));
+        assert!(is_tcp_reuse_race(&errors::QueryError::TcpSend(errors::TcpSendError::from(std::io::Error::from(std::io::ErrorKind::BrokenPipe)))));
+        assert!(!is_tcp_reuse_race(&errors::QueryError::TcpSocket(errors::TcpSocketError::Disabled)));
+        assert!(!is_tcp_reuse_race(&errors::QueryError::Timeout));
+    }
 }
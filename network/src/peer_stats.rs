@@ -0,0 +1,233 @@
+use std::{collections::HashMap, net::SocketAddr, sync::{atomic::{AtomicU64, Ordering}, Arc}, time::Duration};
+
+use atomic::Atomic;
+
+use crate::{mixed_tcp_udp::{INIT_TCP_TIMEOUT, INIT_UDP_RETRANSMISSION_TIMEOUT, INIT_UDP_TIMEOUT}, rolling_average::RollingAverage, udp_size::{PathUdpSize, UdpSizeConfig}};
+#[cfg(feature = "quic")]
+use crate::mixed_tcp_udp::INIT_QUIC_TIMEOUT;
+
+/// The number of peers the registry keeps once their sockets have been closed. When there are more
+/// than this, the peers without an open socket are forgotten.
+const MAX_REMEMBERED_PEERS: usize = 4096;
+
+/// What has been learned about one upstream server: how long it takes to respond, how often its
+/// responses go missing or are truncated, the timeouts adapted to those, and the EDNS payload size
+/// that makes it across the path.
+///
+/// This is owned by the `SocketManager` rather than the socket, so when an idle socket is closed
+/// and later reopened, the new socket picks up where the old one left off instead of starting
+/// over from the initial timeouts.
+#[derive(Debug)]
+pub struct PeerStats {
+    pub(crate) average_tcp_response_time: Atomic<RollingAverage>,
+    pub(crate) average_tcp_dropped_packets: Atomic<RollingAverage>,
+    pub(crate) average_udp_response_time: Atomic<RollingAverage>,
+    pub(crate) average_udp_dropped_packets: Atomic<RollingAverage>,
+    pub(crate) average_udp_truncated_packets: Atomic<RollingAverage>,
+    #[cfg(feature = "quic")]
+    pub(crate) average_quic_response_time: Atomic<RollingAverage>,
+    #[cfg(feature = "quic")]
+    pub(crate) average_quic_dropped_packets: Atomic<RollingAverage>,
+
+    // The timeouts are adjusted while the socket's in-flight map is locked, so the socket keeps its
+    // own copy and stores them here each time they change. Stored as nanoseconds.
+    tcp_timeout: AtomicU64,
+    udp_retransmission_timeout: AtomicU64,
+    udp_timeout: AtomicU64,
+    #[cfg(feature = "quic")]
+    quic_timeout: AtomicU64,
+
+    pub(crate) udp_size: PathUdpSize,
+}
+
+impl PeerStats {
+    #[inline]
+    pub fn new(udp_size: UdpSizeConfig) -> Self {
+        Self {
+            average_tcp_response_time: Atomic::new(RollingAverage::new()),
+            average_tcp_dropped_packets: Atomic::new(RollingAverage::new()),
+            average_udp_response_time: Atomic::new(RollingAverage::new()),
+            average_udp_dropped_packets: Atomic::new(RollingAverage::new()),
+            average_udp_truncated_packets: Atomic::new(RollingAverage::new()),
+            #[cfg(feature = "quic")]
+            average_quic_response_time: Atomic::new(RollingAverage::new()),
+            #[cfg(feature = "quic")]
+            average_quic_dropped_packets: Atomic::new(RollingAverage::new()),
+
+            tcp_timeout: AtomicU64::new(INIT_TCP_TIMEOUT.as_nanos() as u64),
+            udp_retransmission_timeout: AtomicU64::new(INIT_UDP_RETRANSMISSION_TIMEOUT.as_nanos() as u64),
+            udp_timeout: AtomicU64::new(INIT_UDP_TIMEOUT.as_nanos() as u64),
+            #[cfg(feature = "quic")]
+            quic_timeout: AtomicU64::new(INIT_QUIC_TIMEOUT.as_nanos() as u64),
+
+            udp_size: PathUdpSize::new(udp_size),
+        }
+    }
+
+    #[inline]
+    pub fn tcp_timeout(&self) -> Duration {
+        Duration::from_nanos(self.tcp_timeout.load(Ordering::Acquire))
+    }
+
+    #[inline]
+    pub fn udp_retransmission_timeout(&self) -> Duration {
+        Duration::from_nanos(self.udp_retransmission_timeout.load(Ordering::Acquire))
+    }
+
+    #[inline]
+    pub fn udp_timeout(&self) -> Duration {
+        Duration::from_nanos(self.udp_timeout.load(Ordering::Acquire))
+    }
+
+    #[cfg(feature = "quic")]
+    #[inline]
+    pub fn quic_timeout(&self) -> Duration {
+        Duration::from_nanos(self.quic_timeout.load(Ordering::Acquire))
+    }
+
+    #[inline]
+    pub(crate) fn save_tcp_timeout(&self, tcp_timeout: Duration) {
+        self.tcp_timeout.store(tcp_timeout.as_nanos() as u64, Ordering::Release);
+    }
+
+    #[inline]
+    pub(crate) fn save_udp_timeouts(&self, udp_retransmission_timeout: Duration, udp_timeout: Duration) {
+        self.udp_retransmission_timeout.store(udp_retransmission_timeout.as_nanos() as u64, Ordering::Release);
+        self.udp_timeout.store(udp_timeout.as_nanos() as u64, Ordering::Release);
+    }
+
+    #[cfg(feature = "quic")]
+    #[inline]
+    pub(crate) fn save_quic_timeout(&self, quic_timeout: Duration) {
+        self.quic_timeout.store(quic_timeout.as_nanos() as u64, Ordering::Release);
+    }
+
+    #[inline]
+    pub fn average_tcp_response_time(&self) -> f64 {
+        self.average_tcp_response_time.load(Ordering::Acquire).current_average()
+    }
+
+    #[inline]
+    pub fn average_dropped_tcp_packets(&self) -> f64 {
+        self.average_tcp_dropped_packets.load(Ordering::Acquire).current_average()
+    }
+
+    #[inline]
+    pub fn average_udp_response_time(&self) -> f64 {
+        self.average_udp_response_time.load(Ordering::Acquire).current_average()
+    }
+
+    #[inline]
+    pub fn average_dropped_udp_packets(&self) -> f64 {
+        self.average_udp_dropped_packets.load(Ordering::Acquire).current_average()
+    }
+
+    #[inline]
+    pub fn average_truncated_udp_packets(&self) -> f64 {
+        self.average_udp_truncated_packets.load(Ordering::Acquire).current_average()
+    }
+
+    #[cfg(feature = "quic")]
+    #[inline]
+    pub fn average_quic_response_time(&self) -> f64 {
+        self.average_quic_response_time.load(Ordering::Acquire).current_average()
+    }
+
+    #[cfg(feature = "quic")]
+    #[inline]
+    pub fn average_dropped_quic_packets(&self) -> f64 {
+        self.average_quic_dropped_packets.load(Ordering::Acquire).current_average()
+    }
+
+    /// The largest EDNS UDP payload size that queries to this peer advertise.
+    #[inline]
+    pub fn udp_buffer_size(&self) -> u16 {
+        self.udp_size.buffer_size()
+    }
+}
+
+/// The `PeerStats` for every upstream address a `SocketManager` has had a socket for.
+#[derive(Debug, Default)]
+pub(crate) struct PeerStatsRegistry {
+    peers: HashMap<SocketAddr, Arc<PeerStats>>,
+}
+
+impl PeerStatsRegistry {
+    #[inline]
+    pub fn new() -> Self {
+        Self { peers: HashMap::new() }
+    }
+
+    #[inline]
+    pub fn get(&self, address: &SocketAddr) -> Option<Arc<PeerStats>> {
+        self.peers.get(address).cloned()
+    }
+
+    /// Gets the stats for the address, creating them if nothing is known about it yet.
+    pub fn get_or_create(&mut self, address: &SocketAddr, udp_size: UdpSizeConfig) -> Arc<PeerStats> {
+        if let Some(peer) = self.peers.get(address) {
+            return peer.clone();
+        }
+        if self.peers.len() >= MAX_REMEMBERED_PEERS {
+            self.forget_unused();
+        }
+        let peer = Arc::new(PeerStats::new(udp_size));
+        self.peers.insert(*address, peer.clone());
+        peer
+    }
+
+    /// Forgets the peers that no socket is using.
+    #[inline]
+    pub fn forget_unused(&mut self) {
+        self.peers.retain(|_, peer| Arc::strong_count(peer) > 1);
+    }
+
+    #[inline]
+    pub fn clear(&mut self) {
+        self.peers.clear();
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.peers.len()
+    }
+}
+
+#[cfg(test)]
+mod peer_stats_tests {
+    use std::{net::{IpAddr, Ipv4Addr, SocketAddr}, sync::Arc, time::Duration};
+
+    use crate::{mixed_tcp_udp::INIT_TCP_TIMEOUT, udp_size::UdpSizeConfig};
+
+    use super::PeerStatsRegistry;
+
+    const PEER_A: SocketAddr = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1)), 53);
+    const PEER_B: SocketAddr = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(192, 0, 2, 2)), 53);
+
+    #[test]
+    fn same_address_shares_stats() {
+        let mut registry = PeerStatsRegistry::new();
+        let first = registry.get_or_create(&PEER_A, UdpSizeConfig::default());
+        assert_eq!(first.tcp_timeout(), INIT_TCP_TIMEOUT);
+        first.save_tcp_timeout(Duration::from_millis(250));
+
+        let second = registry.get_or_create(&PEER_A, UdpSizeConfig::default());
+        assert!(Arc::ptr_eq(&first, &second));
+        assert_eq!(second.tcp_timeout(), Duration::from_millis(250));
+
+        let other = registry.get_or_create(&PEER_B, UdpSizeConfig::default());
+        assert_eq!(other.tcp_timeout(), INIT_TCP_TIMEOUT);
+    }
+
+    #[test]
+    fn forget_unused_keeps_peers_in_use() {
+        let mut registry = PeerStatsRegistry::new();
+        let in_use = registry.get_or_create(&PEER_A, UdpSizeConfig::default());
+        drop(registry.get_or_create(&PEER_B, UdpSizeConfig::default()));
+
+        registry.forget_unused();
+        assert_eq!(registry.len(), 1);
+        assert!(Arc::ptr_eq(&registry.get(&PEER_A).unwrap(), &in_use));
+        assert!(registry.get(&PEER_B).is_none());
+    }
+}
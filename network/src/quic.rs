@@ -1,15 +1,15 @@
-use std::{collections::HashSet, io::ErrorKind, net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV4, SocketAddrV6}, sync::{atomic::{AtomicBool, Ordering}, Arc}};
+use std::{error::Error, fmt::Display, io::ErrorKind, net::SocketAddr, sync::{atomic::{AtomicBool, AtomicU64, Ordering}, Arc, Mutex}, time::Duration};
 
 use async_lib::awake_token::AwakeToken;
 use dns_lib::{query::message::Message, serde::wire::{from_wire::FromWire, read_wire::ReadWire, to_wire::ToWire, write_wire::WriteWire}, types::c_domain_name::CompressionMap};
-use quinn::{ConnectError, Connection, ConnectionError, Endpoint, ReadExactError, RecvStream, VarInt};
+use quinn::{Connection, ConnectionError, PathStats, ReadExactError, RecvStream, VarInt, WriteError};
 use tokio::{io, pin, select, sync::{broadcast, RwLock, RwLockReadGuard}};
 
+use crate::{async_query::QueryOpt, quic_pool, tls_config::DOQ_ALPN};
+
 
 const MAX_MESSAGE_SIZE: usize = 4096;
 
-const LOCAL_V4_SOCKET: SocketAddr = SocketAddr::V4(SocketAddrV4::new(Ipv4Addr::new(0, 0, 0, 0), 0));
-const LOCAL_V6_SOCKET: SocketAddr = SocketAddr::V6(SocketAddrV6::new(Ipv6Addr::new(0, 0, 0, 0, 0, 0, 0, 0), 0, 0, 0));
 
 
 enum QuicState {
@@ -22,16 +22,59 @@ enum QuicState {
 /// The shared mutable state for the QUIC socket. This struct is stored behind a lock.
 struct SharedQuic { state: QuicState }
 
+/// What the QUIC connection has measured about the path to the upstream. Unlike the rolling
+/// averages kept for each transport, which only see whole queries, these come from every packet
+/// the connection has sent, including acknowledgements and retransmissions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct QuicPathStats {
+    /// The connection's smoothed estimate of the round trip time.
+    pub rtt: Duration,
+    /// The number of bytes the connection is allowed to have in flight.
+    pub congestion_window: u64,
+    /// The number of times the congestion window was reduced because of loss.
+    pub congestion_events: u64,
+    pub sent_packets: u64,
+    pub lost_packets: u64,
+}
+
+impl QuicPathStats {
+    /// The fraction of the packets sent on the connection that were lost. `NaN` if nothing has
+    /// been sent yet.
+    #[inline]
+    pub fn loss_rate(&self) -> f64 {
+        if self.sent_packets == 0 {
+            return f64::NAN;
+        }
+        self.lost_packets as f64 / self.sent_packets as f64
+    }
+}
+
+impl From<PathStats> for QuicPathStats {
+    #[inline]
+    fn from(path: PathStats) -> Self {
+        Self {
+            rtt: path.rtt,
+            congestion_window: path.cwnd,
+            congestion_events: path.congestion_events,
+            sent_packets: path.sent_packets,
+            lost_packets: path.lost_packets,
+        }
+    }
+}
+
 pub struct QuicSocket {
     quic_shared: RwLock<SharedQuic>,
 
     upstream_socket: SocketAddr,
     server_name: String,
-    in_flight: RwLock<HashSet<u16>>,
 
     // Counters used to determine when the socket should be closed.
     recent_messages_sent: AtomicBool,
     recent_messages_received: AtomicBool,
+
+    reuse_races: AtomicU64,
+    // The path statistics of the last connection, as of the last query sent on it.
+    last_path_stats: Mutex<Option<QuicPathStats>>,
 }
 
 impl QuicSocket {
@@ -42,13 +85,20 @@ impl QuicSocket {
 
             upstream_socket,
             server_name,
-            in_flight: RwLock::new(HashSet::new()),
 
             recent_messages_sent: AtomicBool::new(false),
             recent_messages_received: AtomicBool::new(false),
+
+            reuse_races: AtomicU64::new(0),
+            last_path_stats: Mutex::new(None),
         })
     }
 
+    #[inline]
+    pub fn server_name(&self) -> &str {
+        &self.server_name
+    }
+
     #[inline]
     pub fn recent_messages_sent_or_received(&self) -> bool {
         self.recent_messages_sent.load(Ordering::SeqCst)
@@ -99,6 +149,19 @@ impl QuicSocket {
         }
     }
 
+    /// Whether a connection is open or being set up. If another task holds the lock on the
+    /// connection state, the connection is assumed to be in use.
+    pub(crate) fn is_connected(&self) -> bool {
+        match self.quic_shared.try_read() {
+            Ok(r_quic) => match &r_quic.state {
+                QuicState::Connected(quic_connection, _) => quic_connection.close_reason().is_none(),
+                QuicState::Establishing(_) => true,
+                QuicState::None | QuicState::Blocked => false,
+            },
+            Err(_) => true,
+        }
+    }
+
     #[inline]
     pub async fn shutdown_quic(self: Arc<Self>) -> io::Result<()> {
         let r_quic = self.quic_shared.read().await;
@@ -140,7 +203,13 @@ impl QuicSocket {
 
                 Ok(())
             },
-            QuicState::Establishing(_) => todo!("Recursively call self once connection is setup"),
+            QuicState::Establishing(_) => {
+                // The task setting up the connection closes it once it sees that the socket has
+                // been disabled.
+                w_quic.state = QuicState::Blocked;
+                drop(w_quic);
+                Ok(())
+            },
             QuicState::None => {
                 w_quic.state = QuicState::Blocked;
                 drop(w_quic);
@@ -222,67 +291,34 @@ impl QuicSocket {
         drop(w_quic);
         println!("Initializing QUIC connection to {}", self.upstream_socket);
 
+        // The connection is set up by its own task so that the state always leaves Establishing,
+        // even if the query that started it is cancelled (e.g. because it timed out).
+        match tokio::spawn(self.establish_quic(quic_connection_sender)).await {
+            Ok(result) => result,
+            Err(join_error) => Err(io::Error::new(io::ErrorKind::Interrupted, join_error)),
+        }
+    }
+
+    #[inline]
+    async fn establish_quic(self: Arc<Self>, quic_connection_sender: broadcast::Sender<(Connection, AwakeToken)>) -> io::Result<(Connection, AwakeToken)> {
         // Since state has been set to Establishing, this process is now fully
         // in charge of establishing the QUIC connection. Next time the write
         // lock is obtained, it won't need to check the state.
 
-        let local_socket = match self.upstream_socket.ip() {
-            IpAddr::V4(_) => LOCAL_V4_SOCKET,
-            IpAddr::V6(_) => LOCAL_V6_SOCKET,
-        };
-
-        let quic_endpoint = match Endpoint::client(local_socket) {
-            Ok(quic_endpoint) => quic_endpoint,
-            Err(error) => {
-                eprintln!("Failed to establish QUIC connection to {}", self.upstream_socket);
-
-                // Before returning, we must ensure that the "Establishing" status gets cleared
-                // since we failed to establish the connection.
-                let mut w_quic = self.quic_shared.write().await;
-                w_quic.state = QuicState::None;
-                drop(w_quic);
-
-                // Notify all of the waiters by dropping the sender. This
-                // causes the receivers to receiver an error.
-
-                // It might be worth adding another state that blocks future QUIC connections.
-                drop(quic_connection_sender);
-                return Err(error);
-            },
-        };
-
-        let quic_connecting = match quic_endpoint.connect(self.upstream_socket, &self.server_name) {
-            Ok(quic_connecting) => quic_connecting,
-            Err(error) => {
-                eprintln!("Failed to establish QUIC connection to {}", self.upstream_socket);
-
-                // Before returning, we must ensure that the "Establishing" status gets cleared
-                // since we failed to establish the connection.
-                let mut w_quic = self.quic_shared.write().await;
-                w_quic.state = QuicState::None;
-                drop(w_quic);
-
-                // Notify all of the waiters by dropping the sender. This
-                // causes the receivers to receiver an error.
-
-                // It might be worth adding another state that blocks future QUIC connections.
-                drop(quic_connection_sender);
-                match error {
-                    ConnectError::UnsupportedVersion => return Err(io::Error::new(io::ErrorKind::Unsupported, error)),
-                    error => return Err(io::Error::new(io::ErrorKind::Other, error)),
-                }
-            },
-        };
-
-        let quic_connection = match quic_connecting.await {
+        // The endpoint (and its UDP socket) is shared with DNS over HTTP/3 but DoQ gets its own
+        // connection since the connection is closed when the socket shuts down.
+        let quic_connection = match quic_pool::connect(self.upstream_socket, &self.server_name, &[DOQ_ALPN]).await {
             Ok(quic_connection) => quic_connection,
             Err(error) => {
                 eprintln!("Failed to establish QUIC connection to {}", self.upstream_socket);
 
                 // Before returning, we must ensure that the "Establishing" status gets cleared
                 // since we failed to establish the connection.
+                // If the socket was disabled in the meantime, it stays disabled.
                 let mut w_quic = self.quic_shared.write().await;
-                w_quic.state = QuicState::None;
+                if let QuicState::Establishing(_) = &w_quic.state {
+                    w_quic.state = QuicState::None;
+                }
                 drop(w_quic);
 
                 // Notify all of the waiters by dropping the sender. This
@@ -290,18 +326,20 @@ impl QuicSocket {
 
                 // It might be worth adding another state that blocks future QUIC connections.
                 drop(quic_connection_sender);
-                match error {
-                    ConnectionError::VersionMismatch => return Err(io::Error::new(io::ErrorKind::Unsupported, error)),
-                    ConnectionError::ConnectionClosed(_) | ConnectionError::ApplicationClosed(_) => return Err(io::Error::new(io::ErrorKind::ConnectionAborted, error)),
-                    ConnectionError::Reset => return Err(io::Error::new(io::ErrorKind::ConnectionReset, error)),
-                    ConnectionError::TimedOut => return Err(io::Error::new(io::ErrorKind::TimedOut, error)),
-                    error => return Err(io::Error::new(io::ErrorKind::Other, error)),
-                }
+                return Err(error);
             },
         };
 
         let quic_kill = AwakeToken::new();
         let mut w_quic = self.quic_shared.write().await;
+        if let QuicState::Blocked = &w_quic.state {
+            drop(w_quic);
+            println!("Shutting down QUIC connection {} since it was disabled while connecting", self.upstream_socket);
+            quic_connection.close(VarInt::default(), &[]);
+            // Dropping the sender notifies the waiters that no connection will be made.
+            drop(quic_connection_sender);
+            return Err(io::Error::from(io::ErrorKind::ConnectionAborted));
+        }
         w_quic.state = QuicState::Connected(quic_connection.clone(), quic_kill.clone());
         drop(w_quic);
 
@@ -310,13 +348,6 @@ impl QuicSocket {
         return Ok((quic_connection, quic_kill));
     }
 
-    #[inline]
-    async fn cleanup_query(self: Arc<Self>, query_id: u16) {
-        let mut w_in_flight = self.in_flight.write().await;
-        w_in_flight.remove(&query_id);
-        drop(w_in_flight);
-    }
-
     #[inline]
     async fn query_quic_rsocket<'a>(self: Arc<Self>, r_quic: RwLockReadGuard<'a, SharedQuic>, query: Message) -> io::Result<Message> {
         match &r_quic.state {
@@ -351,39 +382,28 @@ impl QuicSocket {
         pin!(
             let quic_kill_awoken = quic_kill.awoken();
         );
-        // Step 1: Register the query as an in-flight message.
-
-        // This is the initial query ID. However, it could change if it is already in use.
-        query.id = rand::random();
+        // Step 1: Clear the message ID.
+        // Each query gets its own stream, so the ID is not needed to match the response to the
+        // query. RFC 9250 section 4.2.1 requires that it is 0. The caller's ID is restored on the
+        // response.
+        let query_id = query.id;
+        query.id = 0;
 
-        select! {
-            mut w_in_flight = self.in_flight.write() => {
-                // verify that ID is unique.
-                while w_in_flight.contains(&query.id) {
-                    query.id = rand::random();
-                    // FIXME: should this fail after some number of non-unique keys? May want to verify that the list isn't full.
-                }
-                w_in_flight.insert(query.id);
-                drop(w_in_flight);
-            },
-            _ = &mut quic_kill_awoken => return Err(io::Error::new(io::ErrorKind::Interrupted, "The connection was canceled locally")),
+        // Step 2: Serialize Data
+        // Queries over QUIC are encrypted so they are padded to hide their length.
+        if let Err(error) = QueryOpt::Quic.padding_policy().apply(&mut query) {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, error));
         }
 
-        // IMPORTANT: This task is responsible for cleaning up the entry in `in_flight` for all
-        //            return points after this,
-
-        // Step 2: Serialize Data
         let raw_message = &mut [0_u8; MAX_MESSAGE_SIZE];
         let mut raw_message = WriteWire::from_bytes(raw_message);
         // Push two bytes onto the wire. These will be replaced with the u16 that indicates
         // the wire length.
         if let Err(error) = raw_message.write_bytes(&[0, 0]) {
-            self.cleanup_query(query.id).await;
             return Err(io::Error::new(io::ErrorKind::InvalidData, error));
         };
 
         if let Err(wire_error) = query.to_wire_format(&mut raw_message, &mut Some(CompressionMap::new())) {
-            self.cleanup_query(query.id).await;
             return Err(io::Error::new(io::ErrorKind::InvalidData, wire_error));
         };
 
@@ -392,30 +412,26 @@ impl QuicSocket {
         let message_wire_length: u16 = (wire_length - 2) as u16;
         let bytes = message_wire_length.to_be_bytes();
         if let Err(error) = raw_message.write_bytes_at(&bytes, 0) {
-            self.cleanup_query(query.id).await;
             return Err(io::Error::new(io::ErrorKind::InvalidData, error));
         };
 
         // Step 3: Bounds check against the configurations.
         //  TODO: No configuration options have been defined yet.
 
-        // Now that the message is registered, set up a stream to send the message over.
-        let query_id = query.id;
+        // Each query is sent on its own bidirectional stream (RFC 9250 section 4.2).
         let (mut send_stream, mut receive_stream) = match select! {
             connection_result = quic_connection.open_bi() => connection_result,
-            _ = &mut quic_kill_awoken => {
-                self.clone().cleanup_query(query.id).await;
-                return Err(io::Error::new(io::ErrorKind::Interrupted, format!("QUIC connection to {} was canceled locally", self.upstream_socket)))
-            },
+            _ = &mut quic_kill_awoken => return Err(closed_before_send(self.upstream_socket)),
         } {
             Ok(streams) => streams,
             Err(error) => {
                 eprintln!("Failed to open a bidirectional QUIC stream to {}", self.upstream_socket);
-                self.cleanup_query(query.id).await;
                 match error {
                     ConnectionError::VersionMismatch => return Err(io::Error::new(io::ErrorKind::Unsupported, error)),
-                    ConnectionError::ConnectionClosed(_) | ConnectionError::ApplicationClosed(_) => return Err(io::Error::new(io::ErrorKind::ConnectionAborted, error)),
-                    ConnectionError::Reset => return Err(io::Error::new(io::ErrorKind::ConnectionReset, error)),
+                    ConnectionError::ConnectionClosed(_)
+                  | ConnectionError::ApplicationClosed(_)
+                  | ConnectionError::Reset
+                  | ConnectionError::LocallyClosed => return Err(closed_before_send(self.upstream_socket)),
                     ConnectionError::TimedOut => return Err(io::Error::new(io::ErrorKind::TimedOut, error)),
                     error => return Err(io::Error::new(io::ErrorKind::Other, error)),
                 }
@@ -427,36 +443,37 @@ impl QuicSocket {
         println!("Sending on QUIC connection {} :: {:?}", self.upstream_socket, query);
         let bytes_written = match select! {
             send_result = send_stream.write(raw_message.current()) => send_result,
-            _ = &mut quic_kill_awoken => {
-                self.clone().cleanup_query(query.id).await;
-                return Err(io::Error::new(io::ErrorKind::Interrupted, format!("QUIC connection to {} was canceled locally", self.upstream_socket)))
-            },
+            _ = &mut quic_kill_awoken => return Err(closed_before_send(self.upstream_socket)),
         } {
             Ok(bytes_written) => bytes_written,
+            Err(WriteError::ConnectionLost(_)) => {
+                eprintln!("Failed to send message on QUIC connection to {}", self.upstream_socket);
+                return Err(closed_before_send(self.upstream_socket));
+            },
             Err(error) => {
                 eprintln!("Failed to send message on QUIC connection to {}", self.upstream_socket);
-                self.cleanup_query(query.id).await;
                 return Err(io::Error::new(io::ErrorKind::Other, error));
             },
         };
         // Verify that the correct number of bytes were written.
         if bytes_written != wire_length {
-            self.cleanup_query(query_id).await;
             return Err(io::Error::new(
                 io::ErrorKind::InvalidData,
-                format!("Incorrect number of bytes sent to TCP stream; expected {wire_length} bytes but sent {bytes_written} bytes"),
+                format!("Incorrect number of bytes sent to QUIC stream; expected {wire_length} bytes but sent {bytes_written} bytes"),
             ));
         }
+        // The client must indicate with a STREAM FIN that nothing else will be sent on the stream.
+        if let Err(error) = send_stream.finish() {
+            return Err(io::Error::new(io::ErrorKind::Other, error));
+        }
 
         let response = match select! {
             response = read_quic_message(&mut receive_stream) => response,
-            _ = &mut quic_kill_awoken => {
-                self.clone().cleanup_query(query.id).await;
-                return Err(io::Error::new(io::ErrorKind::Interrupted, format!("QUIC connection to {} was canceled locally", self.upstream_socket)))
-            },
+            _ = &mut quic_kill_awoken => return Err(io::Error::new(io::ErrorKind::Interrupted, format!("QUIC connection to {} was canceled locally", self.upstream_socket))),
         } {
-            Ok(message) => {
+            Ok(mut message) => {
                 self.recent_messages_received.store(true, Ordering::SeqCst);
+                message.id = query_id;
                 Ok(message)
             },
             Err(error) => {
@@ -464,15 +481,69 @@ impl QuicSocket {
                 Err(error)
             },
         };
-        self.cleanup_query(query_id).await;
+        *self.last_path_stats.lock().unwrap_or_else(|poisoned| poisoned.into_inner()) = Some(QuicPathStats::from(quic_connection.stats().path));
         return response;
     }
 
+    /// Sends the query and waits for the response. If the connection is closed after it is
+    /// acquired but before the query is sent on it, the query is retried once on a new connection.
     pub async fn query(self: Arc<Self>, query: Message) -> io::Result<Message> {
+        match self.clone().query_once(query.clone()).await {
+            Err(error) if is_closed_before_send(&error) => {
+                self.reuse_races.fetch_add(1, Ordering::Relaxed);
+                self.query_once(query).await
+            },
+            result => result,
+        }
+    }
+
+    #[inline]
+    async fn query_once(self: Arc<Self>, query: Message) -> io::Result<Message> {
         let self_lock = self.clone();
         let r_quic = self_lock.quic_shared.read().await;
         self.query_quic_rsocket(r_quic, query).await
     }
+
+    /// The number of queries that were retried because their connection was closed before they
+    /// were sent.
+    #[inline]
+    pub fn reuse_races(&self) -> u64 {
+        self.reuse_races.load(Ordering::Relaxed)
+    }
+
+    /// What the QUIC connection has measured about the path to the upstream. If the connection is
+    /// open (and not being replaced), the statistics are current. Otherwise, they are the ones from
+    /// the last query sent on the previous connection. `None` if no QUIC connection has been made.
+    pub fn path_stats(&self) -> Option<QuicPathStats> {
+        if let Ok(r_quic) = self.quic_shared.try_read() {
+            if let QuicState::Connected(quic_connection, _) = &r_quic.state {
+                return Some(QuicPathStats::from(quic_connection.stats().path));
+            }
+        }
+        *self.last_path_stats.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+/// The connection was closed after the query acquired it but before the query was sent on it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct ClosedBeforeSend(SocketAddr);
+
+impl Display for ClosedBeforeSend {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "QUIC connection to {} was closed before the query was sent", self.0)
+    }
+}
+
+impl Error for ClosedBeforeSend {}
+
+#[inline]
+fn closed_before_send(upstream_socket: SocketAddr) -> io::Error {
+    io::Error::new(io::ErrorKind::Interrupted, ClosedBeforeSend(upstream_socket))
+}
+
+#[inline]
+fn is_closed_before_send(error: &io::Error) -> bool {
+    error.get_ref().is_some_and(|inner| inner.is::<ClosedBeforeSend>())
 }
 
 impl Drop for QuicSocket {
@@ -0,0 +1,128 @@
+use std::{collections::HashMap, io, net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV4, SocketAddrV6}, sync::Arc};
+
+use lazy_static::lazy_static;
+use quinn::{crypto::rustls::QuicClientConfig, ClientConfig, ConnectError, Connection, ConnectionError, Endpoint};
+use tokio::sync::Mutex;
+
+use crate::{async_query::QueryOpt, tls_config::{self, DOQ_ALPN}, tls_diagnostics::{self, TlsConnectionInfo}};
+#[cfg(feature = "https")]
+use crate::tls_config::H3_ALPN;
+
+const LOCAL_V4_SOCKET: SocketAddr = SocketAddr::V4(SocketAddrV4::new(Ipv4Addr::new(0, 0, 0, 0), 0));
+const LOCAL_V6_SOCKET: SocketAddr = SocketAddr::V6(SocketAddrV6::new(Ipv6Addr::new(0, 0, 0, 0, 0, 0, 0, 0), 0, 0, 0));
+
+lazy_static! {
+    /// All outbound QUIC connections (DoQ and DNS over HTTP/3) share one endpoint per address
+    /// family, and therefore one UDP socket.
+    static ref SHARED_ENDPOINTS: Mutex<(Option<Endpoint>, Option<Endpoint>)> = Mutex::new((None, None));
+    static ref SHARED_CONNECTIONS: QuicConnectionPool = QuicConnectionPool::new();
+}
+
+/// Gets the endpoint used to connect to the upstream address, creating it if needed.
+pub(crate) async fn shared_endpoint(upstream_socket: &SocketAddr) -> io::Result<Endpoint> {
+    let mut w_endpoints = SHARED_ENDPOINTS.lock().await;
+    let (endpoint, local_socket) = match upstream_socket.ip() {
+        IpAddr::V4(_) => (&mut w_endpoints.0, LOCAL_V4_SOCKET),
+        IpAddr::V6(_) => (&mut w_endpoints.1, LOCAL_V6_SOCKET),
+    };
+    let endpoint = match endpoint {
+        Some(endpoint) => endpoint.clone(),
+        None => endpoint.insert(Endpoint::client(local_socket)?).clone(),
+    };
+    drop(w_endpoints);
+    Ok(endpoint)
+}
+
+pub(crate) fn quic_client_config(alpn_protocols: &[&[u8]]) -> io::Result<ClientConfig> {
+    let tls_config = tls_config::client_config(alpn_protocols)
+        .map_err(|error| io::Error::new(io::ErrorKind::Other, error))?;
+    let quic_tls_config = QuicClientConfig::try_from(tls_config)
+        .map_err(|error| io::Error::new(io::ErrorKind::Unsupported, error))?;
+    Ok(ClientConfig::new(Arc::new(quic_tls_config)))
+}
+
+/// Connects to the upstream over the shared endpoint. The ALPN identifiers are offered in order
+/// of preference.
+pub(crate) async fn connect(upstream_socket: SocketAddr, server_name: &str, alpn_protocols: &[&[u8]]) -> io::Result<Connection> {
+    let endpoint = shared_endpoint(&upstream_socket).await?;
+    let quic_connecting = match endpoint.connect_with(quic_client_config(alpn_protocols)?, upstream_socket, server_name) {
+        Ok(quic_connecting) => quic_connecting,
+        Err(ConnectError::UnsupportedVersion) => return Err(io::Error::new(io::ErrorKind::Unsupported, ConnectError::UnsupportedVersion)),
+        Err(error) => return Err(io::Error::new(io::ErrorKind::Other, error)),
+    };
+    match quic_connecting.await {
+        Ok(quic_connection) => {
+            let transport = match negotiated_alpn(&quic_connection).as_deref() {
+                Some(DOQ_ALPN) => Some(QueryOpt::Quic),
+                #[cfg(feature = "https")]
+                Some(H3_ALPN) => Some(QueryOpt::Https),
+                _ => None,
+            };
+            if let Some(transport) = transport {
+                tls_diagnostics::record(TlsConnectionInfo::from_quic(upstream_socket, server_name, transport, &quic_connection));
+            }
+            Ok(quic_connection)
+        },
+        Err(error) => match error {
+            ConnectionError::VersionMismatch => Err(io::Error::new(io::ErrorKind::Unsupported, error)),
+            ConnectionError::ConnectionClosed(_) | ConnectionError::ApplicationClosed(_) => Err(io::Error::new(io::ErrorKind::ConnectionAborted, error)),
+            ConnectionError::Reset => Err(io::Error::new(io::ErrorKind::ConnectionReset, error)),
+            ConnectionError::TimedOut => Err(io::Error::new(io::ErrorKind::TimedOut, error)),
+            error => Err(io::Error::new(io::ErrorKind::Other, error)),
+        },
+    }
+}
+
+/// The negotiated ALPN identifier of an established connection.
+pub(crate) fn negotiated_alpn(connection: &Connection) -> Option<Vec<u8>> {
+    connection.handshake_data()
+        .and_then(|handshake_data| handshake_data.downcast::<quinn::crypto::rustls::HandshakeData>().ok())
+        .and_then(|handshake_data| handshake_data.protocol)
+}
+
+/// Keeps open QUIC connections so that different users of the same upstream can share them. A
+/// connection can only be shared by users that speak the protocol negotiated for it, so
+/// connections are keyed by the negotiated ALPN identifier as well as the upstream.
+pub struct QuicConnectionPool {
+    connections: Mutex<HashMap<(SocketAddr, String, Vec<u8>), Connection>>,
+}
+
+impl QuicConnectionPool {
+    #[inline]
+    pub fn new() -> Self {
+        Self { connections: Mutex::new(HashMap::new()) }
+    }
+
+    /// The pool shared by every socket in the process.
+    #[inline]
+    pub fn shared() -> &'static Self {
+        &SHARED_CONNECTIONS
+    }
+
+    /// Gets an open connection to the upstream that negotiated one of the ALPN identifiers,
+    /// connecting if there is none. The negotiated identifier is returned with the connection.
+    pub async fn get_or_connect(&self, upstream_socket: SocketAddr, server_name: &str, alpn_protocols: &[&[u8]]) -> io::Result<(Connection, Vec<u8>)> {
+        let mut w_connections = self.connections.lock().await;
+        w_connections.retain(|_, connection| connection.close_reason().is_none());
+        for alpn in alpn_protocols {
+            if let Some(connection) = w_connections.get(&(upstream_socket, server_name.to_string(), alpn.to_vec())) {
+                let connection = connection.clone();
+                drop(w_connections);
+                return Ok((connection, alpn.to_vec()));
+            }
+        }
+        drop(w_connections);
+
+        let connection = connect(upstream_socket, server_name, alpn_protocols).await?;
+        let alpn = match negotiated_alpn(&connection) {
+            Some(alpn) => alpn,
+            None => return Err(io::Error::new(io::ErrorKind::Unsupported, "server did not negotiate an application protocol")),
+        };
+        let mut w_connections = self.connections.lock().await;
+        let connection = w_connections.entry((upstream_socket, server_name.to_string(), alpn.clone()))
+            .or_insert(connection)
+            .clone();
+        drop(w_connections);
+        Ok((connection, alpn))
+    }
+}
@@ -1,12 +1,25 @@
-use dns_lib::{query::message::Message, serde::wire::{from_wire::FromWire, read_wire::ReadWire}};
+#[cfg(all(feature = "batch-udp", target_os = "linux"))]
+use std::collections::VecDeque;
+use std::net::SocketAddr;
+
+use dns_lib::{query::message::Message, serde::wire::{from_wire::FromWire, read_wire::{ReadWire, ReadWireError}}};
 use tokio::{io::AsyncReadExt, net::UdpSocket};
 
-use crate::errors;
+use crate::{capture::capture_malformed, errors, traffic_class::{recv_with_traffic_class, TrafficClass}};
+#[cfg(all(feature = "batch-udp", target_os = "linux"))]
+use crate::udp_batch::{recv_batch, RecvBatch};
+
+/// The most datagrams that a `UdpReceiver` takes from the socket with a single system call.
+#[cfg(all(feature = "batch-udp", target_os = "linux"))]
+const UDP_RECEIVE_BATCH_SIZE: usize = 8;
 
 
+/// Returns the message along with the bytes it was read from. With the `batch-udp` feature,
+/// messages are read through `UdpReceiver` instead.
+#[cfg(not(all(feature = "batch-udp", target_os = "linux")))]
 #[inline]
-pub async fn read_udp_message<const BUFFER_SIZE: usize>(udp_socket: &UdpSocket) -> Result<Message, errors::UdpReceiveError> {
-    debug_assert!(u16::MAX as usize <= BUFFER_SIZE);
+pub async fn read_udp_message<const BUFFER_SIZE: usize>(udp_socket: &UdpSocket) -> Result<(Message, Vec<u8>), errors::UdpReceiveError> {
+    debug_assert!(BUFFER_SIZE <= u16::MAX as usize);
 
     // Step 1: Setup buffer. Make sure it is within the configured size.
     let mut buffer = [0; BUFFER_SIZE];
@@ -16,15 +29,93 @@ pub async fn read_udp_message<const BUFFER_SIZE: usize>(udp_socket: &UdpSocket)
     let received_byte_count = udp_socket.recv(&mut buffer).await?;
 
     // Step 3: Deserialize the Message received on UDP socket.
-    let mut wire = ReadWire::from_bytes(&buffer[..received_byte_count]);
-    let message = Message::from_wire_format(&mut wire)?;
+    let message = parse_message(&buffer[..received_byte_count], "UDP", udp_socket.peer_addr().ok())?;
+
+    return Ok((message, buffer[..received_byte_count].to_vec()));
+}
+
+/// Reads the messages that arrive on a UDP socket one at a time, like `read_udp_message`. With the
+/// `batch-udp` feature, every datagram that is waiting (up to a batch) is received with a single
+/// `recvmmsg` call and the rest are kept until they are asked for.
+pub struct UdpReceiver<const BUFFER_SIZE: usize> {
+    #[cfg(all(feature = "batch-udp", target_os = "linux"))]
+    batch: RecvBatch,
+    #[cfg(all(feature = "batch-udp", target_os = "linux"))]
+    received: VecDeque<Result<(Message, Vec<u8>), errors::UdpReceiveError>>,
+}
+
+impl<const BUFFER_SIZE: usize> UdpReceiver<BUFFER_SIZE> {
+    #[inline]
+    pub fn new() -> Self {
+        debug_assert!(BUFFER_SIZE <= u16::MAX as usize);
+
+        Self {
+            #[cfg(all(feature = "batch-udp", target_os = "linux"))]
+            batch: RecvBatch::new(UDP_RECEIVE_BATCH_SIZE, BUFFER_SIZE),
+            #[cfg(all(feature = "batch-udp", target_os = "linux"))]
+            received: VecDeque::with_capacity(UDP_RECEIVE_BATCH_SIZE),
+        }
+    }
+
+    /// Returns the next message from the socket along with the bytes it was read from. A datagram
+    /// that cannot be parsed is returned as an error without discarding the ones received with it.
+    #[cfg(all(feature = "batch-udp", target_os = "linux"))]
+    pub async fn read_udp_message(&mut self, udp_socket: &UdpSocket) -> Result<(Message, Vec<u8>), errors::UdpReceiveError> {
+        loop {
+            if let Some(message) = self.received.pop_front() {
+                return message;
+            }
+            recv_batch(udp_socket, &mut self.batch).await?;
+            let peer = udp_socket.peer_addr().ok();
+            self.received.extend(self.batch.iter().map(|(datagram, _)| parse_message(datagram, "UDP", peer).map(|message| (message, datagram.to_vec())).map_err(errors::UdpReceiveError::from)));
+        }
+    }
+
+    /// Returns the next message from the socket along with the bytes it was read from.
+    #[cfg(not(all(feature = "batch-udp", target_os = "linux")))]
+    #[inline]
+    pub async fn read_udp_message(&mut self, udp_socket: &UdpSocket) -> Result<(Message, Vec<u8>), errors::UdpReceiveError> {
+        read_udp_message::<BUFFER_SIZE>(udp_socket).await
+    }
+}
+
+/// Same as `read_udp_message` but also reports the traffic class the datagram was marked with,
+/// if the kernel was asked to report it.
+#[inline]
+pub async fn read_udp_message_with_traffic_class<const BUFFER_SIZE: usize>(udp_socket: &UdpSocket) -> Result<(Message, Vec<u8>, Option<TrafficClass>), errors::UdpReceiveError> {
+    debug_assert!(BUFFER_SIZE <= u16::MAX as usize);
+
+    let mut buffer = [0; BUFFER_SIZE];
+    let (received_byte_count, traffic_class) = recv_with_traffic_class(udp_socket, &mut buffer).await?;
+
+    let message = parse_message(&buffer[..received_byte_count], "UDP", udp_socket.peer_addr().ok())?;
+
+    return Ok((message, buffer[..received_byte_count].to_vec(), traffic_class));
+}
 
-    return Ok(message);
+#[cfg(feature = "tls")]
+#[inline]
+pub async fn read_stream_message<const BUFFER_SIZE: usize>(tcp_stream: &mut (impl AsyncReadExt + Unpin), peer: Option<SocketAddr>) -> Result<Message, errors::StreamReceiveError> {
+    let mut tcp_buffer = [0; BUFFER_SIZE];
+    let message_size = read_stream_bytes(tcp_stream, &mut tcp_buffer).await?;
+    parse_stream_message(&tcp_buffer[..message_size], peer)
+}
+
+/// Same as `read_stream_message` but also returns the bytes the message was read from, without
+/// the two octet length prefix.
+#[inline]
+pub async fn read_stream_message_with_wire<const BUFFER_SIZE: usize>(tcp_stream: &mut (impl AsyncReadExt + Unpin), peer: Option<SocketAddr>) -> Result<(Message, Vec<u8>), errors::StreamReceiveError> {
+    let mut tcp_buffer = [0; BUFFER_SIZE];
+    let message_size = read_stream_bytes(tcp_stream, &mut tcp_buffer).await?;
+    let message = parse_stream_message(&tcp_buffer[..message_size], peer)?;
+    Ok((message, tcp_buffer[..message_size].to_vec()))
 }
 
+/// Reads the next length prefixed message from the stream into the buffer. Returns the length of
+/// the message.
 #[inline]
-pub async fn read_stream_message<const BUFFER_SIZE: usize>(tcp_stream: &mut (impl AsyncReadExt + Unpin)) -> Result<Message, errors::StreamReceiveError> {
-    debug_assert!(u16::MAX as usize <= BUFFER_SIZE);
+async fn read_stream_bytes<const BUFFER_SIZE: usize>(tcp_stream: &mut (impl AsyncReadExt + Unpin), tcp_buffer: &mut [u8; BUFFER_SIZE]) -> Result<usize, errors::StreamReceiveError> {
+    debug_assert!(BUFFER_SIZE <= u16::MAX as usize);
 
     // Step 1: Deserialize the u16 representing the size of the rest of the data. This is the first
     //         2 bytes of data.
@@ -58,7 +149,6 @@ pub async fn read_stream_message<const BUFFER_SIZE: usize>(tcp_stream: &mut (imp
 
     // Step 2: Read the rest of the packet.
     // Note: It MUST be the size of the previous u16 (expected_message_size).
-    let mut tcp_buffer = [0; BUFFER_SIZE];
     // TODO: bound tcp_buffer based on configuration
     match tcp_stream.read_exact(&mut tcp_buffer[..expected_message_size as usize]).await {
         Ok(bytes_read) => {
@@ -78,9 +168,13 @@ pub async fn read_stream_message<const BUFFER_SIZE: usize>(tcp_stream: &mut (imp
         },
     }
 
+    Ok(expected_message_size as usize)
+}
+
+#[inline]
+fn parse_stream_message(bytes: &[u8], peer: Option<SocketAddr>) -> Result<Message, errors::StreamReceiveError> {
     // Step 3: Deserialize the Message from the buffer.
-    let mut wire = ReadWire::from_bytes(&mut tcp_buffer[..expected_message_size as usize]);
-    match Message::from_wire_format(&mut wire) {
+    match parse_message(bytes, "TCP", peer) {
         Ok(message) => Ok(message),
         Err(read_wire_error) => Err(errors::StreamReceiveError::Deserialization {
             stream_protocol: "TCP",
@@ -88,3 +182,48 @@ pub async fn read_stream_message<const BUFFER_SIZE: usize>(tcp_stream: &mut (imp
         }),
     }
 }
+
+/// Deserializes a message, keeping a copy of the bytes if they cannot be parsed and malformed
+/// packets are being captured.
+#[inline]
+fn parse_message(bytes: &[u8], protocol: &'static str, peer: Option<SocketAddr>) -> Result<Message, ReadWireError> {
+    let mut wire = ReadWire::from_bytes(bytes);
+    Message::from_wire_format(&mut wire).inspect_err(|error| capture_malformed(protocol, peer, bytes, error))
+}
+
+#[cfg(test)]
+mod receive_tests {
+    use std::net::Ipv4Addr;
+
+    use dns_lib::{query::{message::Message, question::Question}, resource_record::{rclass::RClass, rtype::RType}, types::c_domain_name::{CDomainName, CompressionMap}};
+    use tokio::net::UdpSocket;
+
+    use crate::errors;
+
+    use super::UdpReceiver;
+
+    #[tokio::test]
+    async fn udp_receiver_returns_every_message_in_order() {
+        let receiver = UdpSocket::bind((Ipv4Addr::LOCALHOST, 0)).await.unwrap();
+        let sender = UdpSocket::bind((Ipv4Addr::LOCALHOST, 0)).await.unwrap();
+        receiver.connect(sender.local_addr().unwrap()).await.unwrap();
+
+        let mut messages = (0..3).map(|_| Message::from(Question::new(CDomainName::from_utf8("www.example.org.").unwrap(), RType::A, RClass::Internet))).collect::<Vec<_>>();
+        for (id, message) in messages.iter_mut().enumerate() {
+            message.id = id as u16;
+        }
+        let wires = messages.iter().map(|message| message.to_wire_vec(&mut Some(CompressionMap::new())).unwrap()).collect::<Vec<_>>();
+        sender.send_to(&wires[0], receiver.local_addr().unwrap()).await.unwrap();
+        // A malformed datagram in the middle must not cost the messages around it.
+        sender.send_to(&[0xFF; 3], receiver.local_addr().unwrap()).await.unwrap();
+        sender.send_to(&wires[1], receiver.local_addr().unwrap()).await.unwrap();
+        sender.send_to(&wires[2], receiver.local_addr().unwrap()).await.unwrap();
+
+        let mut udp_receiver = UdpReceiver::<512>::new();
+        // The bytes each message was read from are kept with it.
+        assert_eq!(udp_receiver.read_udp_message(&receiver).await.map(|(message, wire)| (message.id, wire)).unwrap(), (0, wires[0].clone()));
+        assert!(matches!(udp_receiver.read_udp_message(&receiver).await, Err(errors::UdpReceiveError::Deserialization(_))));
+        assert_eq!(udp_receiver.read_udp_message(&receiver).await.map(|(message, wire)| (message.id, wire)).unwrap(), (1, wires[1].clone()));
+        assert_eq!(udp_receiver.read_udp_message(&receiver).await.map(|(message, wire)| (message.id, wire)).unwrap(), (2, wires[2].clone()));
+    }
+}
@@ -0,0 +1,217 @@
+use std::{collections::HashMap, net::SocketAddr, sync::Mutex, time::Duration};
+
+use tokio::time::Instant;
+
+/// The number of servers whose failures are remembered. When there are more than this, the ones
+/// that are not down are forgotten.
+const MAX_REMEMBERED_SERVERS: usize = 4096;
+
+/// How many failures in a row mark a server as down, and how long to wait before trying it again.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct RetryBudgetConfig {
+    /// The number of queries in a row that must fail before the server is marked as down. With 0,
+    /// servers are never marked as down.
+    pub max_failures: u32,
+    /// How long a server is skipped after it is first marked as down. This doubles each time a
+    /// probe of the server fails.
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+}
+
+impl Default for RetryBudgetConfig {
+    fn default() -> Self {
+        Self { max_failures: 3, initial_backoff: Duration::from_secs(1), max_backoff: Duration::from_secs(300) }
+    }
+}
+
+/// Whether queries are being sent to a server.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ServerStatus {
+    Up,
+    /// The server is skipped until it is probed again, `retry_in` from now.
+    Down { retry_in: Duration },
+    /// The server is down, but its backoff has elapsed, so the next query to it is sent as a
+    /// probe.
+    Probing,
+}
+
+#[derive(Debug)]
+struct ServerBudget {
+    consecutive_failures: u32,
+    backoff: Duration,
+    /// When the server may next be probed. `None` while the server is up.
+    retry_at: Option<Instant>,
+}
+
+impl ServerBudget {
+    #[inline]
+    fn status(&self, now: Instant) -> ServerStatus {
+        match self.retry_at {
+            None => ServerStatus::Up,
+            Some(retry_at) if retry_at <= now => ServerStatus::Probing,
+            Some(retry_at) => ServerStatus::Down { retry_in: retry_at - now },
+        }
+    }
+}
+
+/// The consecutive failures of each upstream server. Once a server fails `max_failures` queries in
+/// a row, it is marked as down and skipped, so queries stop waiting on it to time out. After the
+/// backoff, a single query is let through as a probe. If it succeeds, the server is up again.
+/// Otherwise, the backoff doubles.
+///
+/// Only servers that have failed are remembered, so this stays small while every server is up.
+#[derive(Debug, Default)]
+pub(crate) struct RetryBudgets {
+    config: Mutex<RetryBudgetConfig>,
+    servers: Mutex<HashMap<SocketAddr, ServerBudget>>,
+}
+
+impl RetryBudgets {
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[inline]
+    pub fn config(&self) -> RetryBudgetConfig {
+        *self.config.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Sets the budget for the servers. Servers that are already down keep their current backoff
+    /// until they are next probed.
+    #[inline]
+    pub fn set_config(&self, config: RetryBudgetConfig) {
+        let mut w_config = self.config.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        *w_config = config;
+        drop(w_config);
+        if config.max_failures == 0 {
+            self.servers.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).clear();
+        }
+    }
+
+    pub fn status(&self, address: &SocketAddr) -> ServerStatus {
+        let servers = self.servers.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let status = servers.get(address).map_or(ServerStatus::Up, |server| server.status(Instant::now()));
+        drop(servers);
+        status
+    }
+
+    /// Whether a query may be sent to the server now. If the server is due to be probed, the query
+    /// is the probe and the next probe is pushed back by the backoff, so that only one query is let
+    /// through even if the probe never reports back.
+    pub fn try_acquire(&self, address: &SocketAddr) -> bool {
+        let now = Instant::now();
+        let mut servers = self.servers.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let acquired = match servers.get_mut(address) {
+            Some(server) => match server.status(now) {
+                ServerStatus::Up => true,
+                ServerStatus::Down { .. } => false,
+                ServerStatus::Probing => {
+                    server.retry_at = Some(now + server.backoff);
+                    true
+                },
+            },
+            None => true,
+        };
+        drop(servers);
+        acquired
+    }
+
+    /// Forgets the server's failures. Returns `true` if the server was down.
+    pub fn record_success(&self, address: &SocketAddr) -> bool {
+        let mut servers = self.servers.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let was_down = servers.remove(address).is_some_and(|server| server.retry_at.is_some());
+        drop(servers);
+        was_down
+    }
+
+    /// Counts a failed query to the server. Returns the backoff if this marked the server as down,
+    /// or if it was a failed probe that backed off further.
+    pub fn record_failure(&self, address: &SocketAddr) -> Option<Duration> {
+        let config = self.config();
+        if config.max_failures == 0 {
+            return None;
+        }
+        let now = Instant::now();
+        let mut servers = self.servers.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        if (servers.len() >= MAX_REMEMBERED_SERVERS) && !servers.contains_key(address) {
+            servers.retain(|_, server| server.retry_at.is_some());
+        }
+        let server = servers.entry(*address).or_insert(ServerBudget { consecutive_failures: 0, backoff: Duration::ZERO, retry_at: None });
+        server.consecutive_failures = server.consecutive_failures.saturating_add(1);
+        let backoff = if server.retry_at.is_some() {
+            // A failed probe. Queries that were already in flight when the server was marked as
+            // down also end up here, but they only push the next probe back.
+            server.backoff = server.backoff.saturating_mul(2).min(config.max_backoff);
+            Some(server.backoff)
+        } else if server.consecutive_failures >= config.max_failures {
+            server.backoff = config.initial_backoff.min(config.max_backoff);
+            Some(server.backoff)
+        } else {
+            None
+        };
+        if let Some(backoff) = backoff {
+            server.retry_at = Some(now + backoff);
+        }
+        drop(servers);
+        backoff
+    }
+
+    /// The number of servers that are currently marked as down, including those due for a probe.
+    pub fn down(&self) -> usize {
+        let servers = self.servers.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let down = servers.values().filter(|server| server.retry_at.is_some()).count();
+        drop(servers);
+        down
+    }
+}
+
+#[cfg(test)]
+mod retry_budget_tests {
+    use std::{net::{IpAddr, Ipv4Addr, SocketAddr}, time::Duration};
+
+    use super::{RetryBudgetConfig, RetryBudgets, ServerStatus};
+
+    const SERVER: SocketAddr = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1)), 53);
+
+    #[tokio::test(start_paused = true)]
+    async fn servers_back_off_after_consecutive_failures() {
+        let budgets = RetryBudgets::new();
+        budgets.set_config(RetryBudgetConfig { max_failures: 2, initial_backoff: Duration::from_secs(1), max_backoff: Duration::from_secs(3) });
+
+        assert_eq!(budgets.record_failure(&SERVER), None);
+        assert!(!budgets.record_success(&SERVER));
+        assert_eq!(budgets.record_failure(&SERVER), None);
+        assert_eq!(budgets.record_failure(&SERVER), Some(Duration::from_secs(1)));
+        assert_eq!(budgets.status(&SERVER), ServerStatus::Down { retry_in: Duration::from_secs(1) });
+        assert!(!budgets.try_acquire(&SERVER));
+        assert_eq!(budgets.down(), 1);
+
+        // Only one probe is let through, and each failed probe doubles the backoff up to the max.
+        tokio::time::advance(Duration::from_secs(1)).await;
+        assert_eq!(budgets.status(&SERVER), ServerStatus::Probing);
+        assert!(budgets.try_acquire(&SERVER));
+        assert!(!budgets.try_acquire(&SERVER));
+        assert_eq!(budgets.record_failure(&SERVER), Some(Duration::from_secs(2)));
+        tokio::time::advance(Duration::from_secs(2)).await;
+        assert!(budgets.try_acquire(&SERVER));
+        assert_eq!(budgets.record_failure(&SERVER), Some(Duration::from_secs(3)));
+
+        // A successful probe brings the server back up.
+        tokio::time::advance(Duration::from_secs(3)).await;
+        assert!(budgets.try_acquire(&SERVER));
+        assert!(budgets.record_success(&SERVER));
+        assert_eq!(budgets.status(&SERVER), ServerStatus::Up);
+        assert_eq!(budgets.down(), 0);
+    }
+
+    #[test]
+    fn disabled_budgets_never_mark_servers_down() {
+        let budgets = RetryBudgets::new();
+        budgets.set_config(RetryBudgetConfig { max_failures: 0, ..RetryBudgetConfig::default() });
+        for _ in 0..10 {
+            assert_eq!(budgets.record_failure(&SERVER), None);
+        }
+        assert!(budgets.try_acquire(&SERVER));
+    }
+}
@@ -0,0 +1,447 @@
+use std::{io, net::SocketAddr, sync::{atomic::{AtomicU64, Ordering}, Arc, Weak}};
+
+use async_lib::awake_token::AwakeToken;
+use bytes::{Bytes, BytesMut};
+use http::Request;
+use rustls::pki_types::ServerName;
+use tokio::{net::TcpStream, pin, select, sync::{broadcast, RwLock, RwLockReadGuard}};
+use tokio_rustls::{client::TlsStream, TlsConnector};
+
+use crate::{async_query::QueryOpt, doh::{check_response, MAX_MESSAGE_SIZE}, mixed_tcp_udp::{ConnectionState, TCP_INIT_TIMEOUT}, tls_config::{self, H2_ALPN}, tls_diagnostics::{self, TlsConnectionInfo}};
+
+
+type H2SendRequest = h2::client::SendRequest<Bytes>;
+type H2Connection = h2::client::Connection<TlsStream<TcpStream>, Bytes>;
+
+enum HttpsState {
+    Managed(H2SendRequest, AwakeToken),
+    Establishing(broadcast::Sender<(H2SendRequest, AwakeToken)>),
+    None,
+    Blocked,
+}
+
+/// The shared mutable state for the HTTPS socket. This struct is stored behind a lock.
+struct SharedHttps { state: HttpsState }
+
+/// An HTTP/2 connection to a DNS over HTTPS server. The connection is opened by the first request
+/// and reused by later ones, each of which is sent on its own stream. It is closed when the socket
+/// is shut down, disabled, or dropped.
+///
+/// https://datatracker.ietf.org/doc/html/rfc8484#section-5.2
+pub struct HttpsSocket {
+    https_shared: RwLock<SharedHttps>,
+
+    upstream_socket: SocketAddr,
+    server_name: String,
+
+    // The number of connections that were established.
+    connections: AtomicU64,
+}
+
+impl HttpsSocket {
+    #[inline]
+    pub fn new(upstream_socket: SocketAddr, server_name: String) -> Arc<Self> {
+        Arc::new(Self {
+            https_shared: RwLock::new(SharedHttps { state: HttpsState::None }),
+
+            upstream_socket,
+            server_name,
+
+            connections: AtomicU64::new(0),
+        })
+    }
+
+    /// The number of connections that were established.
+    #[inline]
+    pub fn connections(&self) -> u64 {
+        self.connections.load(Ordering::Relaxed)
+    }
+
+    #[inline]
+    pub async fn state(&self) -> ConnectionState {
+        let r_https = self.https_shared.read().await;
+        let state = match &r_https.state {
+            HttpsState::Managed(_, _) => ConnectionState::Managed,
+            HttpsState::Establishing(_) => ConnectionState::Establishing,
+            HttpsState::None => ConnectionState::None,
+            HttpsState::Blocked => ConnectionState::Blocked,
+        };
+        drop(r_https);
+        state
+    }
+
+    #[inline]
+    pub async fn shutdown_https(self: Arc<Self>) -> io::Result<()> {
+        let r_https = self.https_shared.read().await;
+        if let HttpsState::Managed(_, https_kill) = &r_https.state {
+            let https_kill = https_kill.clone();
+            drop(r_https);
+
+            println!("Shutting down HTTPS connection {}", self.upstream_socket);
+            https_kill.awake();
+
+            // Note: this task is not responsible for actual cleanup. The connection's task clears
+            // the state once it sees the kill token.
+        }
+        Ok(())
+    }
+
+    #[inline]
+    pub async fn disable_https(self: Arc<Self>) -> io::Result<()> {
+        println!("Disabling HTTPS connection {}", self.upstream_socket);
+
+        let mut w_https = self.https_shared.write().await;
+        match &w_https.state {
+            HttpsState::Managed(_, https_kill) => {
+                // Since we are removing the reference the https_kill by setting state to Blocked,
+                // we need to kill it now since the connection's task won't be able to.
+                let https_kill = https_kill.clone();
+                w_https.state = HttpsState::Blocked;
+                drop(w_https);
+
+                println!("Shutting down HTTPS connection {}", self.upstream_socket);
+                https_kill.awake();
+
+                Ok(())
+            },
+            HttpsState::Establishing(_) => {
+                // The task setting up the connection closes it once it sees that the socket has
+                // been disabled.
+                w_https.state = HttpsState::Blocked;
+                drop(w_https);
+                Ok(())
+            },
+            HttpsState::None => {
+                w_https.state = HttpsState::Blocked;
+                drop(w_https);
+                Ok(())
+            },
+            HttpsState::Blocked => { //< Already disabled
+                drop(w_https);
+                Ok(())
+            },
+        }
+    }
+
+    #[inline]
+    pub async fn enable_https(self: Arc<Self>) -> io::Result<()> {
+        println!("Enabling HTTPS connection {}", self.upstream_socket);
+
+        let mut w_https = self.https_shared.write().await;
+        match &w_https.state {
+            HttpsState::Managed(_, _) => (), //< Already enabled
+            HttpsState::Establishing(_) => (), //< Already enabled
+            HttpsState::None => (),            //< Already enabled
+            HttpsState::Blocked => w_https.state = HttpsState::None,
+        }
+        drop(w_https);
+        return Ok(());
+    }
+
+    #[inline]
+    async fn init_https(self: Arc<Self>) -> io::Result<(H2SendRequest, AwakeToken)> {
+        // Initially, verify if the connection has already been established.
+        let r_https = self.https_shared.read().await;
+        match &r_https.state {
+            HttpsState::Managed(send_request, https_kill) => return Ok((send_request.clone(), https_kill.clone())),
+            HttpsState::Establishing(sender) => {
+                let mut receiver = sender.subscribe();
+                drop(r_https);
+                match receiver.recv().await {
+                    Ok((send_request, https_kill)) => return Ok((send_request, https_kill)),
+                    Err(_) => {
+                        eprintln!("Failed to establish HTTPS connection to {}", self.upstream_socket);
+                        return Err(io::Error::from(io::ErrorKind::Interrupted));
+                    },
+                }
+            },
+            HttpsState::None => (),
+            HttpsState::Blocked => {
+                drop(r_https);
+                return Err(io::Error::from(io::ErrorKind::ConnectionAborted));
+            },
+        }
+        drop(r_https);
+
+        // Setup for once the write lock is obtained.
+        let (send_request_sender, _) = broadcast::channel(1);
+
+        // Need to re-verify state with new lock. State could have changed in between.
+        let mut w_https = self.https_shared.write().await;
+        match &w_https.state {
+            HttpsState::Managed(send_request, https_kill) => return Ok((send_request.clone(), https_kill.clone())),
+            HttpsState::Establishing(sender) => {
+                let mut receiver = sender.subscribe();
+                drop(w_https);
+                match receiver.recv().await {
+                    Ok((send_request, https_kill)) => return Ok((send_request, https_kill)),
+                    Err(_) => {
+                        eprintln!("Failed to establish HTTPS connection to {}", self.upstream_socket);
+                        return Err(io::Error::from(io::ErrorKind::Interrupted));
+                    },
+                }
+            },
+            HttpsState::None => (),
+            HttpsState::Blocked => {
+                drop(w_https);
+                return Err(io::Error::from(io::ErrorKind::ConnectionAborted));
+            },
+        }
+
+        w_https.state = HttpsState::Establishing(send_request_sender.clone());
+        drop(w_https);
+        println!("Initializing HTTPS connection to {}", self.upstream_socket);
+
+        // The connection is set up by its own task so that the state always leaves Establishing,
+        // even if the query that started it is cancelled (e.g. because it timed out).
+        match tokio::spawn(self.establish_https(send_request_sender)).await {
+            Ok(result) => result,
+            Err(join_error) => Err(io::Error::new(io::ErrorKind::Interrupted, join_error)),
+        }
+    }
+
+    #[inline]
+    async fn establish_https(self: Arc<Self>, send_request_sender: broadcast::Sender<(H2SendRequest, AwakeToken)>) -> io::Result<(H2SendRequest, AwakeToken)> {
+        // Since state has been set to Establishing, this process is now fully
+        // in charge of establishing the HTTPS connection. Next time the write
+        // lock is obtained, it won't need to check the state.
+        let connection = match tokio::time::timeout(TCP_INIT_TIMEOUT, self.connect()).await {
+            Ok(connection) => connection,
+            Err(_) => Err(io::Error::from(io::ErrorKind::TimedOut)),
+        };
+        let (send_request, h2_connection) = match connection {
+            Ok(connection) => connection,
+            Err(error) => {
+                eprintln!("Failed to establish HTTPS connection to {}", self.upstream_socket);
+
+                // Before returning, we must ensure that the "Establishing" status gets cleared
+                // since we failed to establish the connection.
+                // If the socket was disabled in the meantime, it stays disabled.
+                let mut w_https = self.https_shared.write().await;
+                if let HttpsState::Establishing(_) = &w_https.state {
+                    w_https.state = HttpsState::None;
+                }
+                drop(w_https);
+
+                // Notify all of the waiters by dropping the sender. This
+                // causes the receivers to receiver an error.
+                drop(send_request_sender);
+                return Err(error);
+            },
+        };
+        self.connections.fetch_add(1, Ordering::Relaxed);
+
+        let https_kill = AwakeToken::new();
+        let mut w_https = self.https_shared.write().await;
+        if let HttpsState::Blocked = &w_https.state {
+            drop(w_https);
+            println!("Shutting down HTTPS connection {} since it was disabled while connecting", self.upstream_socket);
+            // Dropping the connection closes it.
+            drop(h2_connection);
+            drop(send_request_sender);
+            return Err(io::Error::from(io::ErrorKind::ConnectionAborted));
+        }
+        w_https.state = HttpsState::Managed(send_request.clone(), https_kill.clone());
+        drop(w_https);
+
+        // The task only holds a weak reference so that an idle connection does not keep the
+        // socket alive. Dropping the socket kills the connection.
+        tokio::spawn(Self::listen_https(Arc::downgrade(&self), self.upstream_socket, h2_connection, https_kill.clone()));
+
+        let _ = send_request_sender.send((send_request.clone(), https_kill.clone()));
+
+        return Ok((send_request, https_kill));
+    }
+
+    /// Opens the TCP connection, negotiates HTTP/2 over TLS, and performs the HTTP/2 handshake.
+    #[inline]
+    async fn connect(&self) -> io::Result<(H2SendRequest, H2Connection)> {
+        let tls_config = tls_config::client_config(&[H2_ALPN])
+            .map_err(|error| io::Error::new(io::ErrorKind::Other, error))?;
+        let server_name = ServerName::try_from(self.server_name.clone())
+            .map_err(|error| io::Error::new(io::ErrorKind::InvalidInput, error))?;
+        let tcp_stream = TcpStream::connect(self.upstream_socket).await?;
+        let tls_stream = TlsConnector::from(tls_config).connect(server_name, tcp_stream).await?;
+        if tls_stream.get_ref().1.alpn_protocol() != Some(H2_ALPN) {
+            return Err(io::Error::new(io::ErrorKind::Unsupported, "server did not negotiate HTTP/2"));
+        }
+        tls_diagnostics::record(TlsConnectionInfo::from_tls(self.upstream_socket, &self.server_name, QueryOpt::Https, tls_stream.get_ref().1));
+        h2::client::handshake(tls_stream).await
+            .map_err(|error| io::Error::new(io::ErrorKind::ConnectionAborted, error))
+    }
+
+    /// Drives the HTTP/2 connection until it is closed by the server or killed, then clears the
+    /// state if it still refers to this connection.
+    #[inline]
+    async fn listen_https(socket: Weak<Self>, upstream_socket: SocketAddr, h2_connection: H2Connection, https_kill: AwakeToken) {
+        select! {
+            result = h2_connection => if let Err(error) = result {
+                println!("HTTPS connection to {upstream_socket} closed: {error}");
+            },
+            () = https_kill.awoken() => println!("HTTPS connection to {upstream_socket} killed"),
+        }
+
+        println!("Cleaning up HTTPS connection {upstream_socket}");
+        let Some(socket) = socket.upgrade() else {
+            return;
+        };
+        let mut w_https = socket.https_shared.write().await;
+        match &w_https.state {
+            HttpsState::Managed(_, managed_https_kill) => {
+                // If the managed connection is the one that we are cleaning up...
+                if &https_kill == managed_https_kill {
+                    // We are responsible for cleanup.
+                    w_https.state = HttpsState::None;
+                    drop(w_https);
+
+                    https_kill.awake();
+
+                // If the managed connection isn't the one that we are cleaning up...
+                } else {
+                    // This is not our connection to clean up.
+                    drop(w_https);
+                }
+            },
+            HttpsState::Establishing(_) => drop(w_https), //< Not our connection to clean up
+            HttpsState::None => drop(w_https),            //< Not our connection to clean up
+            HttpsState::Blocked => drop(w_https),         //< Not our connection to clean up
+        }
+    }
+
+    #[inline]
+    async fn query_https_rsocket<'a>(self: Arc<Self>, r_https: RwLockReadGuard<'a, SharedHttps>, request: Request<()>, request_body: Bytes) -> io::Result<Bytes> {
+        match &r_https.state {
+            HttpsState::Managed(send_request, https_kill) => {
+                let send_request = send_request.clone();
+                let https_kill = https_kill.clone();
+                drop(r_https);
+                return self.query_https(send_request, https_kill, request, request_body).await;
+            },
+            HttpsState::Establishing(send_request_sender) => {
+                let mut send_request_receiver = send_request_sender.subscribe();
+                drop(r_https);
+                match send_request_receiver.recv().await {
+                    Ok((send_request, https_kill)) => return self.query_https(send_request, https_kill, request, request_body).await,
+                    Err(_) => Err(io::Error::from(io::ErrorKind::Interrupted)),
+                }
+            },
+            HttpsState::None => {
+                drop(r_https);
+                let (send_request, https_kill) = self.clone().init_https().await?;
+                return self.query_https(send_request, https_kill, request, request_body).await;
+            },
+            HttpsState::Blocked => {
+                drop(r_https);
+                return Err(io::Error::from(io::ErrorKind::ConnectionAborted));
+            },
+        }
+    }
+
+    #[inline]
+    async fn query_https(self: Arc<Self>, send_request: H2SendRequest, https_kill: AwakeToken, request: Request<()>, request_body: Bytes) -> io::Result<Bytes> {
+        pin!(
+            let https_kill_awoken = https_kill.awoken();
+        );
+
+        // Step 1: Wait for the connection to allow another stream.
+        let mut send_request = match select! {
+            send_request = send_request.ready() => send_request,
+            _ = &mut https_kill_awoken => return Err(io::Error::new(io::ErrorKind::Interrupted, format!("HTTPS connection to {} was canceled locally", self.upstream_socket))),
+        } {
+            Ok(send_request) => send_request,
+            Err(error) => {
+                // The connection is no longer usable. Killing it clears the state so that a new
+                // one is established for the next query.
+                eprintln!("Failed to open a stream on HTTPS connection to {}", self.upstream_socket);
+                https_kill.awake();
+                return Err(io::Error::new(io::ErrorKind::ConnectionAborted, error));
+            },
+        };
+
+        // Step 2: Send the request. Each query is sent on its own stream (RFC 8484 section 5.2).
+        let (response_future, mut send_stream) = send_request.send_request(request, false)
+            .map_err(|error| io::Error::new(io::ErrorKind::ConnectionAborted, error))?;
+        send_stream.send_data(request_body, true).map_err(|error| io::Error::new(io::ErrorKind::BrokenPipe, error))?;
+
+        // Step 3: Read the response.
+        let read_response = async {
+            let response = response_future.await.map_err(|error| io::Error::new(io::ErrorKind::ConnectionAborted, error))?;
+            check_response(response.status(), response.headers())?;
+
+            let mut body = response.into_body();
+            let mut response_body = BytesMut::new();
+            while let Some(chunk) = body.data().await {
+                let chunk = chunk.map_err(|error| io::Error::new(io::ErrorKind::ConnectionAborted, error))?;
+                let _ = body.flow_control().release_capacity(chunk.len());
+                if response_body.len() + chunk.len() > MAX_MESSAGE_SIZE {
+                    return Err(io::Error::new(io::ErrorKind::InvalidData, "DoH response exceeded the maximum message size"));
+                }
+                response_body.extend_from_slice(&chunk);
+            }
+            Ok(response_body.freeze())
+        };
+        select! {
+            response_body = read_response => response_body,
+            _ = &mut https_kill_awoken => Err(io::Error::new(io::ErrorKind::Interrupted, format!("HTTPS connection to {} was canceled locally", self.upstream_socket))),
+        }
+    }
+
+    /// Sends the request with the body over the connection, opening a new one if there is not one
+    /// yet, and returns the body of the response.
+    #[inline]
+    pub async fn query(self: Arc<Self>, request: Request<()>, request_body: Bytes) -> io::Result<Bytes> {
+        let self_lock = self.clone();
+        let r_https = self_lock.https_shared.read().await;
+        self.query_https_rsocket(r_https, request, request_body).await
+    }
+}
+
+impl Drop for HttpsSocket {
+    fn drop(&mut self) {
+        println!("Dropping socket {}", self.upstream_socket);
+        // The connection's task only has a weak reference to the socket, so it must be told to
+        // close the connection.
+        if let HttpsState::Managed(_, https_kill) = &self.https_shared.get_mut().state {
+            https_kill.awake();
+        }
+    }
+}
+
+#[cfg(test)]
+mod https_tests {
+    use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+
+    use bytes::Bytes;
+    use http::Request;
+
+    use crate::mixed_tcp_udp::ConnectionState;
+
+    use super::HttpsSocket;
+
+    #[tokio::test]
+    async fn disabled_sockets_do_not_connect() {
+        let address = SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 9);
+        let socket = HttpsSocket::new(address, "dns.example".to_string());
+        assert_eq!(socket.state().await, ConnectionState::None);
+
+        socket.clone().disable_https().await.unwrap();
+        assert_eq!(socket.state().await, ConnectionState::Blocked);
+        let error = socket.clone().query(Request::new(()), Bytes::new()).await.unwrap_err();
+        assert_eq!(error.kind(), std::io::ErrorKind::ConnectionAborted);
+        assert_eq!(socket.connections(), 0);
+
+        socket.clone().enable_https().await.unwrap();
+        assert_eq!(socket.state().await, ConnectionState::None);
+    }
+
+    #[tokio::test]
+    async fn failed_connections_leave_establishing() {
+        // Nothing listens on the discard port, so the connection is refused.
+        let address = SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 9);
+        let socket = HttpsSocket::new(address, "dns.example".to_string());
+
+        assert!(socket.clone().query(Request::new(()), Bytes::new()).await.is_err());
+        assert_eq!(socket.state().await, ConnectionState::None);
+        assert_eq!(socket.connections(), 0);
+    }
+}
@@ -1,5 +1,9 @@
-use std::{pin::Pin, sync::Arc};
+use std::{future::Future, pin::{pin, Pin}, sync::{atomic::{AtomicUsize, Ordering}, Arc}};
 
+use tokio::{sync::Notify, task::JoinHandle};
+
+#[cfg(feature = "https")]
+pub mod https;
 pub mod tcp;
 pub mod udp;
 pub mod udp_tcp;
@@ -18,3 +22,58 @@ pub(crate) trait FutureSocket<'d, S, E> {
     /// is killed.
     fn poll<'a>(self: &mut Pin<&mut Self>, socket: &'a Arc<S>, cx: &mut std::task::Context<'_>) -> PollSocket<E> where 'a: 'd;
 }
+
+/// Counts the listener tasks of a socket so that closing the socket can wait for them to exit.
+#[derive(Debug, Default)]
+pub(crate) struct ListenerTracker {
+    inner: Arc<ListenerCount>,
+}
+
+#[derive(Debug, Default)]
+struct ListenerCount {
+    running: AtomicUsize,
+    exited: Notify,
+}
+
+/// Counts one listener as running until it is dropped along with the listener's task, whether the
+/// task returns, panics, or is aborted.
+struct ListenerGuard(Arc<ListenerCount>);
+
+impl Drop for ListenerGuard {
+    fn drop(&mut self) {
+        if self.0.running.fetch_sub(1, Ordering::AcqRel) == 1 {
+            self.0.exited.notify_waiters();
+        }
+    }
+}
+
+impl ListenerTracker {
+    /// Spawns the listener as its own task. It is counted as running from now, not from when the
+    /// task is first polled, so that a listener that has not started yet is still waited on.
+    pub fn spawn(&self, listener: impl Future<Output = ()> + Send + 'static) -> JoinHandle<()> {
+        self.inner.running.fetch_add(1, Ordering::AcqRel);
+        let guard = ListenerGuard(self.inner.clone());
+        tokio::spawn(async move {
+            listener.await;
+            drop(guard);
+        })
+    }
+
+    #[inline]
+    pub fn running(&self) -> usize {
+        self.inner.running.load(Ordering::Acquire)
+    }
+
+    /// Waits until every listener has exited.
+    pub async fn wait_for_exit(&self) {
+        loop {
+            // Registered before the count is checked so that an exit in between is not missed.
+            let mut exited = pin!(self.inner.exited.notified());
+            exited.as_mut().enable();
+            if self.running() == 0 {
+                return;
+            }
+            exited.await;
+        }
+    }
+}
@@ -6,9 +6,9 @@ use futures::{future::BoxFuture, FutureExt};
 use pin_project::{pin_project, pinned_drop};
 use tokio::{net::{tcp::{OwnedReadHalf, OwnedWriteHalf}, TcpStream}, sync::{Mutex, RwLock, RwLockReadGuard, RwLockWriteGuard}, task::JoinHandle, time::Sleep};
 
-use crate::{errors, mixed_tcp_udp::TCP_INIT_TIMEOUT};
+use crate::{errors, mixed_tcp_udp::TCP_INIT_TIMEOUT, traffic_class::{connect_tcp, TrafficClass}};
 
-use super::{FutureSocket, PollSocket};
+use super::{FutureSocket, ListenerTracker, PollSocket};
 
 
 pub(crate) enum TcpState {
@@ -28,6 +28,11 @@ pub(crate) enum TcpState {
 pub(crate) trait TcpSocket where Self: 'static + Sized + Send + Sync {
     fn peer(&self) -> &SocketAddr;
     fn state(&self) -> &RwLock<TcpState>;
+    /// The listener tasks started for the socket.
+    fn listeners(&self) -> &ListenerTracker;
+    /// The traffic class that new TCP connections are marked with.
+    #[inline]
+    fn traffic_class(&self) -> Option<TrafficClass> { None }
 
     /// Start the TCP listener and drive the TCP state to Managed.
     #[inline]
@@ -498,7 +503,7 @@ where
                                 TcpState::None => {
                                     let tcp_socket_sender = this.tcp_socket_sender.clone();
                                     let kill_init_tcp = this.kill_tcp.get_awake_token();
-                                    let init_connection = TcpStream::connect(this.socket.peer()).boxed();
+                                    let init_connection = connect_tcp(*this.socket.peer(), this.socket.traffic_class()).boxed();
 
                                     *tcp_state = TcpState::Establishing {
                                         sender: tcp_socket_sender,
@@ -537,7 +542,7 @@ where
                             let (tcp_reader, tcp_writer) = socket.into_split();
                             let tcp_socket = Arc::new(Mutex::new(tcp_writer));
                             let w_tcp_state = this.socket.state().write().boxed();
-                            tokio::spawn(this.socket.clone().listen(tcp_reader, this.kill_tcp.get_awake_token()));
+                            this.socket.listeners().spawn(this.socket.clone().listen(tcp_reader, this.kill_tcp.get_awake_token()));
 
                             *this.inner = InnerInitTcp::WriteManaged { w_tcp_state, tcp_socket };
 
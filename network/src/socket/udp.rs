@@ -6,9 +6,9 @@ use futures::{future::BoxFuture, FutureExt};
 use pin_project::pin_project;
 use tokio::{net, sync::{RwLock, RwLockReadGuard, RwLockWriteGuard}};
 
-use crate::errors;
+use crate::{errors, traffic_class::{connect_udp, TrafficClass}};
 
-use super::{FutureSocket, PollSocket};
+use super::{FutureSocket, ListenerTracker, PollSocket};
 
 
 pub(crate) enum UdpState {
@@ -21,6 +21,11 @@ pub(crate) enum UdpState {
 pub(crate) trait UdpSocket where Self: 'static + Sized + Send + Sync {
     fn peer(&self) -> &SocketAddr;
     fn state(&self) -> &RwLock<UdpState>;
+    /// The listener tasks started for the socket.
+    fn listeners(&self) -> &ListenerTracker;
+    /// The traffic class that new UDP sockets are marked with.
+    #[inline]
+    fn traffic_class(&self) -> Option<TrafficClass> { None }
 
     /// Start the UDP listener and drive the UDP state to Managed.
     #[inline]
@@ -47,8 +52,7 @@ pub(crate) trait UdpSocket where Self: 'static + Sized + Send + Sync {
         }
         drop(r_state);
 
-        let udp_socket = Arc::new(net::UdpSocket::bind("0.0.0.0:0").await?);
-        udp_socket.connect(self.peer()).await?;
+        let udp_socket = Arc::new(connect_udp(self.peer(), self.traffic_class()).await?);
         let udp_reader = udp_socket.clone();
         let udp_writer = udp_socket;
         let kill_udp = AwakeToken::new();
@@ -66,7 +70,8 @@ pub(crate) trait UdpSocket where Self: 'static + Sized + Send + Sync {
                 *w_state = UdpState::Managed(udp_writer.clone(), kill_udp.clone());
                 drop(w_state);
 
-                tokio::spawn(self.listen(udp_reader, kill_udp.clone()));
+                let listener = self.clone().listen(udp_reader, kill_udp.clone());
+                self.listeners().spawn(listener);
 
                 return Ok((udp_writer, kill_udp));
             },
@@ -168,9 +173,9 @@ where
     #[inline]
     fn set_init_udp<S: UdpSocket>(mut self: std::pin::Pin<&mut Self>, socket: &'a Arc<S>) {
         let upstream_socket = socket.peer();
+        let traffic_class = socket.traffic_class();
         let init_udp = async move {
-            let udp_socket = Arc::new(net::UdpSocket::bind("0.0.0.0:0").await?);
-            udp_socket.connect(upstream_socket).await?;
+            let udp_socket = Arc::new(connect_udp(upstream_socket, traffic_class).await?);
             return Ok((udp_socket, AwakeToken::new()));
         }.boxed();
 
@@ -237,7 +242,7 @@ impl<'c, 'd, S: UdpSocket> FutureSocket<'d, S, errors::UdpSocketError> for QUdpS
             QUdpSocketProj::InitUdp(init_udp) => {
                 match init_udp.as_mut().poll(cx) {
                     Poll::Ready(Ok((udp_socket, kill_udp_token))) => {
-                        tokio::spawn(socket.clone().listen(udp_socket.clone(), kill_udp_token.clone()));
+                        socket.listeners().spawn(socket.clone().listen(udp_socket.clone(), kill_udp_token.clone()));
                         self.as_mut().set_get_write_udp_state(socket, udp_socket, kill_udp_token);
 
                         // Next loop should poll `kill_udp`
@@ -1,32 +1,92 @@
 use std::{collections::HashMap, net::SocketAddr, sync::Arc, time::Duration};
+#[cfg(feature = "tls")]
+use std::time::SystemTime;
 
-use futures::StreamExt;
+use dns_lib::query::message::Message;
+use futures::future::join_all;
 use tokio::{select, sync::{watch, RwLock}, task::JoinHandle};
 
-use crate::mixed_tcp_udp::MixedSocket;
+use crate::{async_query::QueryOpt, connection_pool::{ConnectionPool, ConnectionPoolConfig, ConnectionPoolStats}, errors::QueryError, mixed_tcp_udp::{ConnectionState, MixedSocket, SocketOptions}, peer_stats::{PeerStats, PeerStatsRegistry}, retry_budget::{RetryBudgetConfig, RetryBudgets, ServerStatus}, traffic_class::TrafficClass, udp_size::UdpSizeConfig};
+#[cfg(feature = "tls")]
+use crate::{dot::DotClient, tls_config::TlsVerification, tls_diagnostics::{self, TlsConnectionInfo}};
 
 
 const DEFAULT_KEEP_ALIVE: Duration = Duration::from_secs(30);
+/// How often `SocketManager::close()` checks whether the queries in flight have finished.
+const DRAIN_POLL_INTERVAL: Duration = Duration::from_millis(10);
 
 
 struct InternalSocketManager {
     sockets: HashMap<SocketAddr, (Arc<MixedSocket>, u8)>,
     garbage_collection: Option<JoinHandle<()>>,
     keep_alive: watch::Sender<Duration>,
+    options: SocketOptions,
+    peers: PeerStatsRegistry,
+    connection_pool: Arc<ConnectionPool>,
+    /// How the certificate of each DNS over TLS upstream is verified. Upstreams that are not
+    /// listed are verified against the platform's trust store.
+    #[cfg(feature = "tls")]
+    tls_verification: HashMap<SocketAddr, TlsVerification>,
+    /// Keyed by the upstream address and the server name.
+    #[cfg(feature = "tls")]
+    dot_clients: HashMap<(SocketAddr, String), Arc<DotClient>>,
 }
 
 impl InternalSocketManager {
     #[inline]
-    pub fn with_keep_alive(keep_alive: Duration) -> (Self, watch::Receiver<Duration>) {
+    pub fn with_keep_alive(keep_alive: Duration, connection_pool: Arc<ConnectionPool>) -> (Self, watch::Receiver<Duration>) {
         let (keep_alive_sender, keep_alive_receiver) = watch::channel(keep_alive);
         let manager = Self {
             sockets: HashMap::new(),
             garbage_collection: None,
             keep_alive: keep_alive_sender,
+            options: SocketOptions::default(),
+            peers: PeerStatsRegistry::new(),
+            connection_pool,
+            #[cfg(feature = "tls")]
+            tls_verification: HashMap::new(),
+            #[cfg(feature = "tls")]
+            dot_clients: HashMap::new(),
         };
         (manager, keep_alive_receiver)
     }
 
+    /// Creates a socket to the address that starts from what was learned by any earlier socket to
+    /// it.
+    #[inline]
+    fn insert_socket(&mut self, address: &SocketAddr) -> Arc<MixedSocket> {
+        let peer = self.peers.get_or_create(address, self.options.udp_size);
+        let socket = MixedSocket::with_peer_stats(*address, self.options, peer, Some(self.connection_pool.clone()));
+        self.sockets.insert(*address, (socket.clone(), 0));
+        socket
+    }
+
+    /// Creates a socket to the address that can also send queries over DNS over QUIC. It replaces
+    /// any socket to the address that was created for a different server name.
+    #[cfg(feature = "quic")]
+    #[inline]
+    fn insert_quic_socket(&mut self, address: &SocketAddr, server_name: &str) -> Arc<MixedSocket> {
+        let peer = self.peers.get_or_create(address, self.options.udp_size);
+        let socket = MixedSocket::with_quic_peer_stats(*address, server_name.to_string(), self.options, peer, Some(self.connection_pool.clone()));
+        if let Some((replaced_socket, _)) = self.sockets.insert(*address, (socket.clone(), 0)) {
+            tokio::task::spawn(replaced_socket.shutdown());
+        }
+        socket
+    }
+
+    /// Creates a socket to the address that can also send queries over DNS over HTTPS. It replaces
+    /// any socket to the address that was created for a different server name or path.
+    #[cfg(feature = "https")]
+    #[inline]
+    fn insert_https_socket(&mut self, address: &SocketAddr, server_name: &str, path: &str) -> Arc<MixedSocket> {
+        let peer = self.peers.get_or_create(address, self.options.udp_size);
+        let socket = MixedSocket::with_https_peer_stats(*address, server_name.to_string(), path.to_string(), self.options, peer, Some(self.connection_pool.clone()));
+        if let Some((replaced_socket, _)) = self.sockets.insert(*address, (socket.clone(), 0)) {
+            tokio::task::spawn(replaced_socket.shutdown());
+        }
+        socket
+    }
+
     #[inline]
     fn start_garbage_collection(internal_socket_manager: Arc<RwLock<Self>>, mut keep_alive_receiver: watch::Receiver<Duration>) -> JoinHandle<()> {
         tokio::task::spawn(async move {
@@ -108,24 +168,105 @@ impl InternalSocketManager {
             // TODO: If access is ever given to get the number of active queries, that could be used
             // to determine if the socket should be closed too.
         });
+        w_socket_manager.connection_pool.prune();
         drop(w_socket_manager);
     }
+}
 
+/// The number of sockets whose transport is in each state.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ConnectionStateCounts {
+    pub managed: usize,
+    pub establishing: usize,
+    pub none: usize,
+    pub blocked: usize,
+}
+
+impl ConnectionStateCounts {
     #[inline]
-    async fn drop_all_sockets(internal_socket_manager: &Arc<RwLock<Self>>) {
-        let mut w_socket_manager = internal_socket_manager.write().await;
-        futures::stream::iter(w_socket_manager.sockets.drain())
-            .for_each_concurrent(None, |(address, (socket, _))| async move {
-                println!("GC: Removing {address} from socket manager");
-                let _ = socket.disable().await;
-            }).await;
-        drop(w_socket_manager);
+    fn add(&mut self, state: ConnectionState) {
+        match state {
+            ConnectionState::Managed => self.managed += 1,
+            ConnectionState::Establishing => self.establishing += 1,
+            ConnectionState::None => self.none += 1,
+            ConnectionState::Blocked => self.blocked += 1,
+        }
+    }
+
+    /// The number of open connections, including those still being set up.
+    #[inline]
+    pub fn open(&self) -> usize {
+        self.managed + self.establishing
     }
 }
 
+/// How long each stage of `SocketManager::close()` may take.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct CloseTimeouts {
+    /// How long the queries in flight are given to finish before they are abandoned.
+    pub drain: Duration,
+    /// How long the listeners are given to exit once their connections are killed.
+    pub listeners: Duration,
+}
+
+impl Default for CloseTimeouts {
+    fn default() -> Self {
+        Self { drain: Duration::from_secs(2), listeners: Duration::from_secs(1) }
+    }
+}
+
+/// What was left unfinished when a `SocketManager` was closed.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct CloseReport {
+    /// The number of sockets that were closed.
+    pub sockets: usize,
+    /// Queries that did not finish draining in time and were failed with a shutdown error.
+    pub abandoned_queries: usize,
+    /// Listener tasks that had not exited by the time the listeners' timeout passed.
+    pub running_listeners: usize,
+}
+
+/// A point-in-time summary of every socket owned by a `SocketManager`.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SocketManagerStats {
+    /// The number of upstream addresses with a socket.
+    pub sockets: usize,
+    pub tcp: ConnectionStateCounts,
+    pub udp: ConnectionStateCounts,
+    /// Queries waiting on a response from an upstream.
+    pub in_flight_queries: usize,
+    /// Query runner tasks that have been spawned and have not yet finished.
+    pub running_query_tasks: usize,
+    /// The number of encrypted upstreams with a recorded connection. Always 0 if the `tls`
+    /// feature is disabled.
+    pub tls_connections: usize,
+    /// The number of upstream addresses whose timeouts and averages are remembered, including
+    /// those whose sockets have been closed.
+    pub remembered_peers: usize,
+    /// The number of sockets that can send queries over DNS over HTTPS. Always 0 if the `https`
+    /// feature is disabled.
+    pub doh_clients: usize,
+    /// The number of DNS over TLS upstreams with a client. Always 0 if the `tls` feature is
+    /// disabled.
+    pub dot_clients: usize,
+    /// The number of upstream addresses that are skipped for failing too many queries in a row.
+    pub down_servers: usize,
+    /// The open TCP, TLS, and QUIC connections and how many were closed to stay within the
+    /// limits.
+    pub connection_pool: ConnectionPoolStats,
+    /// Responses that were discarded because they did not echo the random casing of the question.
+    pub case_mismatches: u64,
+}
+
+/// Owns the sockets used to reach each upstream address. A clone refers to the same sockets, so
+/// the manager can be shared between multiple clients by cloning it.
 #[derive(Clone)]
 pub struct SocketManager {
-    internal: Arc<RwLock<InternalSocketManager>>
+    internal: Arc<RwLock<InternalSocketManager>>,
+    /// Checked before every query, so it is kept outside of the lock.
+    retry_budgets: Arc<RetryBudgets>,
+    /// Shared with every socket and DNS over TLS client created by the manager.
+    connection_pool: Arc<ConnectionPool>,
 }
 
 impl SocketManager {
@@ -134,8 +275,9 @@ impl SocketManager {
 
     #[inline]
     pub async fn with_keep_alive(keep_alive: Duration) -> Self {
-        let (socket_manager, keep_alive_receiver) = InternalSocketManager::with_keep_alive(keep_alive);
-        let socket_manager = Self { internal: Arc::new(RwLock::new(socket_manager)) };
+        let connection_pool = Arc::new(ConnectionPool::new());
+        let (socket_manager, keep_alive_receiver) = InternalSocketManager::with_keep_alive(keep_alive, connection_pool.clone());
+        let socket_manager = Self { internal: Arc::new(RwLock::new(socket_manager)), retry_budgets: Arc::new(RetryBudgets::new()), connection_pool };
 
         let join_handle = InternalSocketManager::start_garbage_collection(socket_manager.internal.clone(), keep_alive_receiver);
         let mut w_isocket_manager = socket_manager.internal.write().await;
@@ -159,6 +301,60 @@ impl SocketManager {
         drop(w_socket_manager);
     }
 
+    /// Sets the DSCP and ECN bits that packets to upstream servers are marked with. Only sockets
+    /// created after this call are affected, so this should be set before the manager is used.
+    #[inline]
+    pub async fn set_traffic_class(&self, traffic_class: Option<TrafficClass>) {
+        let mut w_socket_manager = self.internal.write().await;
+        w_socket_manager.options.traffic_class = traffic_class;
+        drop(w_socket_manager);
+    }
+
+    #[inline]
+    pub async fn traffic_class(&self) -> Option<TrafficClass> {
+        let r_socket_manager = self.internal.read().await;
+        let traffic_class = r_socket_manager.options.traffic_class;
+        drop(r_socket_manager);
+        traffic_class
+    }
+
+    /// Sets whether the question names of UDP queries are sent with random casing, which the
+    /// responses have to echo to be accepted. Like the traffic class, this only affects sockets
+    /// created after this call.
+    #[inline]
+    pub async fn set_case_randomization(&self, case_randomization: bool) {
+        let mut w_socket_manager = self.internal.write().await;
+        w_socket_manager.options.case_randomization = case_randomization;
+        drop(w_socket_manager);
+    }
+
+    #[inline]
+    pub async fn case_randomization(&self) -> bool {
+        let r_socket_manager = self.internal.read().await;
+        let case_randomization = r_socket_manager.options.case_randomization;
+        drop(r_socket_manager);
+        case_randomization
+    }
+
+    /// Sets the EDNS payload sizes and the thresholds used to adapt them for each upstream. Like
+    /// the traffic class, this only affects sockets created after this call. Everything learned
+    /// about the upstreams so far is forgotten, since it was learned under the old thresholds.
+    #[inline]
+    pub async fn set_udp_size_config(&self, udp_size: UdpSizeConfig) {
+        let mut w_socket_manager = self.internal.write().await;
+        w_socket_manager.options.udp_size = udp_size;
+        w_socket_manager.peers.clear();
+        drop(w_socket_manager);
+    }
+
+    #[inline]
+    pub async fn udp_size_config(&self) -> UdpSizeConfig {
+        let r_socket_manager = self.internal.read().await;
+        let udp_size = r_socket_manager.options.udp_size;
+        drop(r_socket_manager);
+        udp_size
+    }
+
     /// # Cancel Safety
     ///
     /// This function is cancel safe.
@@ -174,11 +370,32 @@ impl SocketManager {
         let mut w_socket_manager = self.internal.write().await;
         match w_socket_manager.sockets.get(address) {
             Some((socket, _)) => return socket.clone(),
-            None => {
-                let socket = MixedSocket::new(address.clone());
-                w_socket_manager.sockets.insert(address.clone(), (socket.clone(), 0));
-                return socket;
-            },
+            None => return w_socket_manager.insert_socket(address),
+        }
+    }
+
+    /// Gets the socket to the address, which can send queries over DNS over QUIC
+    /// (`QueryOpt::Quic`) as well as UDP and TCP. The server's certificate is verified against
+    /// `server_name`. Like the other sockets, its QUIC connection is reused by later queries until
+    /// the socket is closed for being idle.
+    ///
+    /// # Cancel Safety
+    ///
+    /// This function is cancel safe.
+    #[cfg(feature = "quic")]
+    pub async fn get_quic(&self, address: &SocketAddr, server_name: &str) -> Arc<MixedSocket> {
+        let r_socket_manager = self.internal.read().await;
+        if let Some((socket, _)) = r_socket_manager.sockets.get(address) {
+            if socket.quic_server_name() == Some(server_name) {
+                return socket.clone();
+            }
+        }
+        drop(r_socket_manager);
+
+        let mut w_socket_manager = self.internal.write().await;
+        match w_socket_manager.sockets.get(address) {
+            Some((socket, _)) if socket.quic_server_name() == Some(server_name) => return socket.clone(),
+            _ => return w_socket_manager.insert_quic_socket(address, server_name),
         }
     }
 
@@ -202,11 +419,7 @@ impl SocketManager {
         let sockets = addresses
             .map(|address| match w_socket_manager.sockets.get(address) {
                 Some((socket, _)) => socket.clone(),
-                None => {
-                    let socket = MixedSocket::new(address.clone());
-                    w_socket_manager.sockets.insert(address.clone(), (socket.clone(), 0));
-                    socket
-                },
+                None => w_socket_manager.insert_socket(address),
             })
             .collect::<Vec<_>>();
         drop(w_socket_manager);
@@ -237,9 +450,271 @@ impl SocketManager {
         drop(r_socket_manager);
     }
 
+    /// What has been learned about the upstream address, if there has ever been a socket to it.
+    /// This is kept after the socket is closed so that the next socket to the address starts
+    /// with the same timeouts and averages.
+    #[inline]
+    pub async fn peer_stats(&self, address: &SocketAddr) -> Option<Arc<PeerStats>> {
+        let r_socket_manager = self.internal.read().await;
+        let peer = r_socket_manager.peers.get(address);
+        drop(r_socket_manager);
+        peer
+    }
+
+    /// Sets how many queries in a row an upstream may fail before it is marked as down, and how
+    /// long it is then skipped for. See `server_status()`.
+    #[inline]
+    pub fn set_retry_budget(&self, config: RetryBudgetConfig) {
+        self.retry_budgets.set_config(config);
+    }
+
+    #[inline]
+    pub fn retry_budget(&self) -> RetryBudgetConfig {
+        self.retry_budgets.config()
+    }
+
+    /// Whether queries are being sent to the upstream. An upstream is marked as down once it
+    /// fails too many queries in a row, and is then skipped until it is due to be probed. The
+    /// backoff between probes doubles each time a probe fails.
+    #[inline]
+    pub fn server_status(&self, address: &SocketAddr) -> ServerStatus {
+        self.retry_budgets.status(address)
+    }
+
+    /// Whether a query may be sent to the upstream now. Returns `false` if the upstream is down.
+    /// If it is due to be probed, this query is the probe and `true` is returned to this caller
+    /// only, until the probe's result is recorded or the backoff elapses again.
+    #[inline]
+    pub fn try_acquire_query(&self, address: &SocketAddr) -> bool {
+        self.retry_budgets.try_acquire(address)
+    }
+
+    /// Records that a query to the upstream got a response. Returns `true` if this brought the
+    /// upstream back up.
+    #[inline]
+    pub fn record_query_success(&self, address: &SocketAddr) -> bool {
+        self.retry_budgets.record_success(address)
+    }
+
+    /// Records that a query to the upstream failed. Returns the time until the upstream is next
+    /// probed if this marked it as down, or if it was a failed probe.
+    #[inline]
+    pub fn record_query_failure(&self, address: &SocketAddr) -> Option<Duration> {
+        self.retry_budgets.record_failure(address)
+    }
+
+    /// Sets how many TCP, TLS, and QUIC connections may be open at once, overall and to each IP
+    /// address. Once a query would open a connection over a limit, the least recently used
+    /// connection is closed, along with any queries still waiting on it.
+    #[inline]
+    pub fn set_connection_pool(&self, config: ConnectionPoolConfig) {
+        self.connection_pool.set_config(config);
+    }
+
+    #[inline]
+    pub fn connection_pool(&self) -> ConnectionPoolConfig {
+        self.connection_pool.config()
+    }
+
+    /// Gets the socket to the address, which can send queries over DNS over HTTPS
+    /// (`QueryOpt::Https`) as POST requests to `path`, as well as over UDP and TCP. The server's
+    /// certificate is verified against `server_name` using the platform's trust store. Like the
+    /// other sockets, its connections are reused by later queries until the socket is closed for
+    /// being idle.
+    ///
+    /// # Cancel Safety
+    ///
+    /// This function is cancel safe.
+    #[cfg(feature = "https")]
+    pub async fn get_https(&self, address: &SocketAddr, server_name: &str, path: &str) -> Arc<MixedSocket> {
+        let matches = |socket: &MixedSocket| socket.doh_client().is_some_and(|doh_client| (doh_client.server_name() == server_name) && (doh_client.path() == path));
+        let r_socket_manager = self.internal.read().await;
+        if let Some((socket, _)) = r_socket_manager.sockets.get(address) {
+            if matches(socket) {
+                return socket.clone();
+            }
+        }
+        drop(r_socket_manager);
+
+        let mut w_socket_manager = self.internal.write().await;
+        match w_socket_manager.sockets.get(address) {
+            Some((socket, _)) if matches(socket) => return socket.clone(),
+            _ => return w_socket_manager.insert_https_socket(address, server_name, path),
+        }
+    }
+
+    /// Sets how the certificates of the DNS over TLS upstream at the address are verified. Any
+    /// client already created for the address is replaced when it is next requested, so later
+    /// queries use the new policy.
+    #[cfg(feature = "tls")]
+    pub async fn set_tls_verification(&self, address: &SocketAddr, verification: TlsVerification) {
+        let mut w_socket_manager = self.internal.write().await;
+        w_socket_manager.dot_clients.retain(|(dot_address, _), _| dot_address != address);
+        w_socket_manager.tls_verification.insert(*address, verification);
+        drop(w_socket_manager);
+    }
+
+    /// How the certificates of the DNS over TLS upstream at the address are verified.
+    #[cfg(feature = "tls")]
+    pub async fn tls_verification(&self, address: &SocketAddr) -> TlsVerification {
+        let r_socket_manager = self.internal.read().await;
+        let verification = r_socket_manager.tls_verification.get(address).cloned().unwrap_or_default();
+        drop(r_socket_manager);
+        verification
+    }
+
+    /// The DNS over TLS client for the upstream, creating one if there is not one yet. The
+    /// server's certificate is verified as set by `set_tls_verification()`. Without a server name,
+    /// the certificate must be issued for the address, unless the upstream is pinned or
+    /// opportunistic. Clients are kept until the sockets are dropped so that later queries reuse
+    /// their connections.
+    ///
+    /// # Cancel Safety
+    ///
+    /// This function is cancel safe.
+    #[cfg(feature = "tls")]
+    pub async fn dot_client(&self, address: &SocketAddr, server_name: Option<&str>) -> Arc<DotClient> {
+        let server_name = server_name.map_or_else(|| address.ip().to_string(), str::to_string);
+        let key = (*address, server_name);
+        let r_socket_manager = self.internal.read().await;
+        if let Some(dot_client) = r_socket_manager.dot_clients.get(&key) {
+            return dot_client.clone();
+        }
+        drop(r_socket_manager);
+
+        let mut w_socket_manager = self.internal.write().await;
+        let verification = w_socket_manager.tls_verification.get(address).cloned().unwrap_or_default();
+        let dot_client = w_socket_manager.dot_clients.entry(key)
+            .or_insert_with_key(|(address, server_name)| DotClient::with_pool(*address, server_name.clone(), verification, self.connection_pool.clone()))
+            .clone();
+        drop(w_socket_manager);
+        dot_client
+    }
+
+    /// Sends the message to the server at the address and returns its response as it was
+    /// received. Nothing is done to either message beyond what the transport requires, so a
+    /// truncated response is returned rather than retried over TCP. The managed socket for the
+    /// address is used, so the message shares its connections and adaptive timeouts with every
+    /// other query to the server.
+    ///
+    /// Queries can only be sent over DNS over QUIC or DNS over HTTPS once `get_quic()` or
+    /// `get_https()` has created the socket with the server name to verify. DNS over TLS queries
+    /// use the client from `dot_client()` without a server name.
+    ///
+    /// # Cancel Safety
+    ///
+    /// This function is cancel safe.
+    pub async fn send_message(&self, address: &SocketAddr, transport: QueryOpt, mut message: Message) -> Result<Message, QueryError> {
+        #[cfg(feature = "tls")]
+        if matches!(transport, QueryOpt::Tls | QueryOpt::TlsInsecure) {
+            return self.dot_client(address, None).await.query(&mut message).await;
+        }
+        let socket = self.get(address).await;
+        socket.query(&mut message, transport).await
+    }
+
+    /// Closes every socket with the default `CloseTimeouts`. See `close()`.
     #[inline]
     pub async fn drop_all_sockets(&self) {
-        InternalSocketManager::drop_all_sockets(&self.internal).await;
+        self.close(CloseTimeouts::default()).await;
+    }
+
+    /// Closes every socket in a fixed order, so that no query or listener is left waiting on a
+    /// connection that is gone:
+    ///
+    /// 1. The sockets are removed from the manager so that no new queries are sent on them. The
+    ///    DoT clients are dropped, which closes their connections once the queries using them
+    ///    finish.
+    /// 2. The queries in flight are given until `timeouts.drain` to finish.
+    /// 3. The sockets are disabled, which kills their connections, and any queries that are still
+    ///    in flight are failed with a shutdown error.
+    /// 4. The listeners are given until `timeouts.listeners` to exit.
+    ///
+    /// The manager can still be used afterwards. Later queries open new sockets.
+    pub async fn close(&self, timeouts: CloseTimeouts) -> CloseReport {
+        let mut w_socket_manager = self.internal.write().await;
+        let sockets = w_socket_manager.sockets.drain()
+            .map(|(_, (socket, _))| socket)
+            .collect::<Vec<_>>();
+        #[cfg(feature = "tls")]
+        w_socket_manager.dot_clients.clear();
+        drop(w_socket_manager);
+
+        let drain = async {
+            for socket in &sockets {
+                while socket.stats().await.in_flight_queries > 0 {
+                    tokio::time::sleep(DRAIN_POLL_INTERVAL).await;
+                }
+            }
+        };
+        let _ = tokio::time::timeout(timeouts.drain, drain).await;
+
+        join_all(sockets.iter().map(|socket| socket.clone().disable())).await;
+        let abandoned_queries = join_all(sockets.iter().map(|socket| socket.abort_queries())).await.into_iter().sum();
+
+        let _ = tokio::time::timeout(timeouts.listeners, join_all(sockets.iter().map(|socket| socket.wait_for_listeners()))).await;
+        let running_listeners = join_all(sockets.iter().map(|socket| socket.stats())).await.into_iter()
+            .map(|stats| stats.running_listeners)
+            .sum();
+
+        CloseReport { sockets: sockets.len(), abandoned_queries, running_listeners }
+    }
+
+    /// Counts the managed sockets by the state of each transport, along with the queries and
+    /// query runner tasks that are still running on them. Useful for dashboards and capacity
+    /// planning.
+    pub async fn stats(&self) -> SocketManagerStats {
+        let r_socket_manager = self.internal.read().await;
+        let sockets = r_socket_manager.sockets.values()
+            .map(|(socket, _)| socket.clone())
+            .collect::<Vec<_>>();
+        let remembered_peers = r_socket_manager.peers.len();
+        #[cfg(feature = "https")]
+        let doh_clients = sockets.iter().filter(|socket| socket.doh_client().is_some()).count();
+        #[cfg(not(feature = "https"))]
+        let doh_clients = 0;
+        #[cfg(feature = "tls")]
+        let dot_clients = r_socket_manager.dot_clients.len();
+        #[cfg(not(feature = "tls"))]
+        let dot_clients = 0;
+        drop(r_socket_manager);
+
+        let down_servers = self.retry_budgets.down();
+        let connection_pool = self.connection_pool.stats();
+        let mut stats = SocketManagerStats { sockets: sockets.len(), remembered_peers, doh_clients, dot_clients, down_servers, connection_pool, ..Default::default() };
+        #[cfg(feature = "tls")]
+        {
+            stats.tls_connections = tls_diagnostics::connections().len();
+        }
+        for socket in sockets {
+            let socket_stats = socket.stats().await;
+            stats.tcp.add(socket_stats.tcp);
+            stats.udp.add(socket_stats.udp);
+            stats.in_flight_queries += socket_stats.in_flight_queries;
+            stats.running_query_tasks += socket_stats.running_query_tasks;
+            stats.case_mismatches += socket_stats.case_mismatches;
+        }
+        stats
+    }
+
+    /// The negotiated parameters and certificates of the most recent connection to each encrypted
+    /// (DoQ and DoH) upstream. Encrypted connections are shared by every socket manager in the
+    /// process, so every manager reports the same connections.
+    #[cfg(feature = "tls")]
+    #[inline]
+    pub fn tls_connections(&self) -> Vec<TlsConnectionInfo> {
+        tls_diagnostics::connections()
+    }
+
+    /// The encrypted upstreams whose certificate chains expire within `window` from now, or
+    /// already have.
+    #[cfg(feature = "tls")]
+    #[inline]
+    pub fn expiring_tls_connections(&self, window: Duration) -> Vec<TlsConnectionInfo> {
+        let now = SystemTime::now();
+        let mut connections = tls_diagnostics::connections();
+        connections.retain(|connection| connection.expires_within(now, window));
+        connections
     }
 }
 
@@ -261,3 +736,286 @@ impl Drop for SocketManager {
         });
     }
 }
+
+#[cfg(test)]
+mod socket_manager_tests {
+    use std::{net::{IpAddr, Ipv4Addr, SocketAddr}, sync::Arc, time::Duration};
+
+    use dns_lib::{query::{message::Message, question::Question}, resource_record::{rclass::RClass, rtype::RType}, types::c_domain_name::CDomainName};
+    use tokio::{io::{AsyncReadExt, AsyncWriteExt}, net::{TcpListener, UdpSocket}, task::JoinHandle};
+
+    use crate::{async_query::QueryOpt, connection_pool::{ConnectionPoolConfig, PoolLimits, TransportPoolStats}, mixed_tcp_udp::ConnectionState, retry_budget::{RetryBudgetConfig, ServerStatus}, traffic_class::{Ecn, TrafficClass}};
+
+    use super::{CloseReport, CloseTimeouts, ConnectionStateCounts, SocketManager};
+
+    #[tokio::test]
+    async fn clones_share_sockets() {
+        let address = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1)), 53);
+        let socket_manager = SocketManager::new().await;
+        let shared_socket_manager = socket_manager.clone();
+
+        let socket = socket_manager.get(&address).await;
+        let shared_socket = shared_socket_manager.try_get(&address).await.unwrap();
+        assert!(Arc::ptr_eq(&socket, &shared_socket));
+    }
+
+    #[tokio::test]
+    async fn stats_count_idle_sockets() {
+        let socket_manager = SocketManager::new().await;
+        assert_eq!(socket_manager.stats().await.sockets, 0);
+
+        let addresses = [
+            SocketAddr::new(IpAddr::V4(Ipv4Addr::new(192, 0, 2, 3)), 53),
+            SocketAddr::new(IpAddr::V4(Ipv4Addr::new(192, 0, 2, 4)), 53),
+        ];
+        socket_manager.get_all(addresses.iter()).await;
+        let stats = socket_manager.stats().await;
+        assert_eq!(stats.sockets, 2);
+        assert_eq!(stats.tcp, ConnectionStateCounts { none: 2, ..Default::default() });
+        assert_eq!(stats.udp, ConnectionStateCounts { none: 2, ..Default::default() });
+        assert_eq!(stats.tcp.open(), 0);
+        assert_eq!(stats.in_flight_queries, 0);
+        assert_eq!(stats.running_query_tasks, 0);
+    }
+
+    #[tokio::test]
+    async fn new_sockets_use_traffic_class() {
+        let address = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(192, 0, 2, 2)), 53);
+        let traffic_class = TrafficClass::new(TrafficClass::DSCP_CS2, Ecn::Ect0);
+        let socket_manager = SocketManager::new().await;
+        socket_manager.set_traffic_class(traffic_class).await;
+        assert_eq!(socket_manager.traffic_class().await, traffic_class);
+
+        let socket = socket_manager.get(&address).await;
+        assert_eq!(socket.traffic_class(), traffic_class);
+    }
+
+    #[tokio::test]
+    async fn recreated_sockets_keep_peer_stats() {
+        let address = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(192, 0, 2, 5)), 53);
+        let socket_manager = SocketManager::new().await;
+
+        let socket = socket_manager.get(&address).await;
+        socket.peer_stats().save_tcp_timeout(Duration::from_millis(250));
+        socket_manager.drop_all_sockets().await;
+        assert!(socket_manager.try_get(&address).await.is_none());
+
+        let recreated_socket = socket_manager.get(&address).await;
+        assert!(!Arc::ptr_eq(&socket, &recreated_socket));
+        assert!(Arc::ptr_eq(socket.peer_stats(), recreated_socket.peer_stats()));
+        assert_eq!(recreated_socket.peer_stats().tcp_timeout(), Duration::from_millis(250));
+        assert_eq!(socket_manager.stats().await.remembered_peers, 1);
+    }
+
+    #[tokio::test]
+    async fn close_leaves_no_tasks_behind() {
+        // A server that accepts queries over UDP and TCP but never answers them.
+        let udp_server = UdpSocket::bind((Ipv4Addr::LOCALHOST, 0)).await.unwrap();
+        let address = udp_server.local_addr().unwrap();
+        let tcp_server = TcpListener::bind(address).await.unwrap();
+        let accept = tokio::spawn(async move {
+            let mut connections = Vec::new();
+            while let Ok((connection, _)) = tcp_server.accept().await {
+                connections.push(connection);
+            }
+        });
+        let socket_manager = SocketManager::new().await;
+        let metrics = tokio::runtime::Handle::current().metrics();
+        let alive_tasks = metrics.num_alive_tasks();
+
+        let socket = socket_manager.get(&address).await;
+        let queries = [("udp.example.", QueryOpt::UdpTcp), ("tcp.example.", QueryOpt::Tcp)].map(|(qname, transport)| {
+            let socket = socket.clone();
+            let mut query = Message::from(Question::new(CDomainName::from_utf8(qname).unwrap(), RType::A, RClass::Internet));
+            tokio::spawn(async move { socket.query(&mut query, transport).await })
+        });
+        tokio::time::timeout(Duration::from_secs(1), async {
+            while socket.stats().await.in_flight_queries < 2 {
+                tokio::time::sleep(Duration::from_millis(1)).await;
+            }
+        }).await.unwrap();
+        assert!(socket.stats().await.running_listeners > 0);
+
+        let report = socket_manager.close(CloseTimeouts { drain: Duration::from_millis(50), listeners: Duration::from_secs(1) }).await;
+        assert_eq!(report, CloseReport { sockets: 1, abandoned_queries: 2, running_listeners: 0 });
+        for query in queries {
+            assert!(query.await.unwrap().is_err());
+        }
+        drop(socket);
+
+        // Aborted tasks are only counted as gone once the runtime gets to them.
+        tokio::time::timeout(Duration::from_secs(1), async {
+            while metrics.num_alive_tasks() > alive_tasks {
+                tokio::time::sleep(Duration::from_millis(1)).await;
+            }
+        }).await.expect("tasks were left running after the sockets were closed");
+        accept.abort();
+        drop(udp_server);
+    }
+
+    /// Answers every query sent to the listener over TCP by sending it back as a response.
+    async fn tcp_echo_server(ip: Ipv4Addr) -> (SocketAddr, JoinHandle<()>) {
+        let listener = TcpListener::bind((ip, 0)).await.unwrap();
+        let address = listener.local_addr().unwrap();
+        let accept = tokio::spawn(async move {
+            while let Ok((mut connection, _)) = listener.accept().await {
+                tokio::spawn(async move {
+                    while let Ok(length) = connection.read_u16().await {
+                        let mut message = vec![0; length as usize];
+                        if connection.read_exact(&mut message).await.is_err() {
+                            break;
+                        }
+                        // Sets the QR bit.
+                        message[2] |= 0b1000_0000;
+                        if connection.write_u16(length).await.is_err() || connection.write_all(&message).await.is_err() {
+                            break;
+                        }
+                    }
+                });
+            }
+        });
+        (address, accept)
+    }
+
+    #[tokio::test]
+    async fn least_recently_used_connections_are_closed() {
+        let socket_manager = SocketManager::new().await;
+        socket_manager.set_connection_pool(ConnectionPoolConfig { tcp: PoolLimits { max_connections: 3, max_per_peer: 2 }, ..ConnectionPoolConfig::default() });
+        let peer_1 = Ipv4Addr::new(127, 0, 0, 1);
+        let peer_2 = Ipv4Addr::new(127, 0, 0, 2);
+        let mut servers = Vec::new();
+        for ip in [peer_1, peer_1, peer_2, peer_1, peer_2] {
+            servers.push(tcp_echo_server(ip).await);
+        }
+
+        let mut sockets = Vec::new();
+        for (address, _) in &servers {
+            let socket = socket_manager.get(address).await;
+            let mut query = Message::from(Question::new(CDomainName::from_utf8("example.").unwrap(), RType::A, RClass::Internet));
+            assert!(socket.query(&mut query, QueryOpt::Tcp).await.is_ok());
+            sockets.push(socket);
+        }
+
+        // The fourth connection took the first peer over its limit, so the least recently used
+        // connection to that peer was closed. The fifth took the pool over its limit, so the least
+        // recently used connection to either peer was closed.
+        tokio::time::timeout(Duration::from_secs(1), async {
+            while (sockets[0].stats().await.tcp != ConnectionState::None) || (sockets[1].stats().await.tcp != ConnectionState::None) {
+                tokio::time::sleep(Duration::from_millis(1)).await;
+            }
+        }).await.expect("the least recently used connections were not closed");
+        for socket in &sockets[2..] {
+            assert_eq!(socket.stats().await.tcp, ConnectionState::Managed);
+        }
+        assert_eq!(socket_manager.stats().await.connection_pool.tcp, TransportPoolStats { open: 3, peers: 2, evicted: 2 });
+
+        for (_, accept) in servers {
+            accept.abort();
+        }
+    }
+
+    #[tokio::test]
+    async fn clones_share_retry_budgets() {
+        let address = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(192, 0, 2, 9)), 53);
+        let socket_manager = SocketManager::new().await;
+        let shared_socket_manager = socket_manager.clone();
+        socket_manager.set_retry_budget(RetryBudgetConfig { max_failures: 2, ..RetryBudgetConfig::default() });
+        assert_eq!(shared_socket_manager.retry_budget().max_failures, 2);
+
+        assert_eq!(socket_manager.record_query_failure(&address), None);
+        assert!(shared_socket_manager.record_query_failure(&address).is_some());
+        assert!(matches!(shared_socket_manager.server_status(&address), ServerStatus::Down { .. }));
+        assert!(!socket_manager.try_acquire_query(&address));
+        assert_eq!(socket_manager.stats().await.down_servers, 1);
+
+        assert!(socket_manager.record_query_success(&address));
+        assert_eq!(shared_socket_manager.server_status(&address), ServerStatus::Up);
+        assert_eq!(socket_manager.stats().await.down_servers, 0);
+    }
+
+    #[cfg(feature = "https")]
+    #[tokio::test]
+    async fn https_sockets_are_reused() {
+        let address = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(192, 0, 2, 6)), 443);
+        let socket_manager = SocketManager::new().await;
+
+        let socket = socket_manager.get_https(&address, "dns.example", "/dns-query").await;
+        let doh_client = socket.doh_client().unwrap();
+        assert_eq!((doh_client.server_name(), doh_client.path()), ("dns.example", "/dns-query"));
+        let reused_socket = socket_manager.get_https(&address, "dns.example", "/dns-query").await;
+        assert!(Arc::ptr_eq(&socket, &reused_socket));
+        assert!(Arc::ptr_eq(&socket, &socket_manager.get(&address).await));
+
+        let other_socket = socket_manager.get_https(&address, "dns.example", "/other-query").await;
+        assert!(!Arc::ptr_eq(&socket, &other_socket));
+        let stats = socket_manager.stats().await;
+        assert_eq!((stats.sockets, stats.doh_clients), (1, 1));
+
+        socket_manager.drop_all_sockets().await;
+        assert_eq!(socket_manager.stats().await.doh_clients, 0);
+    }
+
+    #[cfg(feature = "https")]
+    #[tokio::test]
+    async fn idle_https_sockets_are_evicted() {
+        let address = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(192, 0, 2, 9)), 443);
+        let socket_manager = SocketManager::with_keep_alive(Duration::from_millis(20)).await;
+
+        let socket = socket_manager.get_https(&address, "dns.example", "/dns-query").await;
+        let doh_client = Arc::downgrade(socket.doh_client().unwrap());
+        drop(socket);
+        assert_eq!(socket_manager.stats().await.doh_clients, 1);
+
+        // The garbage collection removes the idle socket, which drops its client and with it the
+        // client's HTTP/2 connection.
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(socket_manager.stats().await.doh_clients, 0);
+        assert_eq!(doh_client.strong_count(), 0);
+    }
+
+    #[cfg(feature = "tls")]
+    #[tokio::test]
+    async fn dot_clients_use_the_upstream_verification() {
+        use crate::tls_config::TlsVerification;
+
+        let address = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(192, 0, 2, 8)), 853);
+        let socket_manager = SocketManager::new().await;
+        assert_eq!(socket_manager.tls_verification(&address).await, TlsVerification::Platform);
+
+        // Without a server name, the certificate is verified against the address.
+        let dot_client = socket_manager.dot_client(&address, None).await;
+        assert_eq!(dot_client.server_name(), "192.0.2.8");
+        assert_eq!(dot_client.verification(), &TlsVerification::Platform);
+        assert!(Arc::ptr_eq(&dot_client, &socket_manager.dot_client(&address, None).await));
+
+        // Changing the policy replaces the client.
+        socket_manager.set_tls_verification(&address, TlsVerification::Opportunistic).await;
+        let opportunistic_dot_client = socket_manager.dot_client(&address, None).await;
+        assert!(!Arc::ptr_eq(&dot_client, &opportunistic_dot_client));
+        assert_eq!(opportunistic_dot_client.verification(), &TlsVerification::Opportunistic);
+        let named_dot_client = socket_manager.dot_client(&address, Some("dns.example")).await;
+        assert_eq!(named_dot_client.server_name(), "dns.example");
+        assert_eq!(socket_manager.stats().await.dot_clients, 2);
+
+        socket_manager.drop_all_sockets().await;
+        assert_eq!(socket_manager.stats().await.dot_clients, 0);
+    }
+
+    #[cfg(feature = "quic")]
+    #[tokio::test]
+    async fn quic_sockets_are_reused() {
+        let address = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(192, 0, 2, 7)), 853);
+        let socket_manager = SocketManager::new().await;
+
+        let socket = socket_manager.get_quic(&address, "dns.example").await;
+        assert_eq!(socket.quic_server_name(), Some("dns.example"));
+        let reused_socket = socket_manager.get_quic(&address, "dns.example").await;
+        assert!(Arc::ptr_eq(&socket, &reused_socket));
+        assert!(Arc::ptr_eq(&socket, &socket_manager.get(&address).await));
+
+        let other_socket = socket_manager.get_quic(&address, "other.example").await;
+        assert!(!Arc::ptr_eq(&socket, &other_socket));
+        assert_eq!(other_socket.quic_server_name(), Some("other.example"));
+        assert_eq!(socket_manager.stats().await.sockets, 1);
+    }
+}
@@ -0,0 +1,292 @@
+use std::{collections::{hash_map::Entry, HashMap}, net::IpAddr, sync::{atomic::{AtomicU64, AtomicUsize, Ordering}, Arc, Mutex}, time::Duration};
+
+use tokio::{io::{AsyncRead, AsyncReadExt}, time::timeout};
+
+use crate::errors::StreamLimitError;
+
+const DEFAULT_MAX_CONNECTIONS_PER_IP: usize = 16;
+const DEFAULT_MAX_IN_FLIGHT_PER_CONNECTION: usize = 32;
+const DEFAULT_READ_HEADER_TIMEOUT: Duration = Duration::from_secs(2);
+const DEFAULT_IDLE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Limits applied to inbound stream (TCP and TLS) connections so that a small number of clients
+/// cannot exhaust the listener.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct StreamLimits {
+    /// The maximum number of concurrent connections that may be open from a single IP address.
+    pub max_connections_per_ip: usize,
+    /// The maximum number of queries that may be outstanding on a single connection. Once
+    /// reached, the connection stops reading until a response has been written.
+    pub max_in_flight_per_connection: usize,
+    /// How long a client has to send the complete two octet length prefix and the message it
+    /// describes once it has started sending it.
+    pub read_header_timeout: Duration,
+    /// How long a connection may sit without any query in flight before it is closed.
+    pub idle_timeout: Duration,
+}
+
+impl Default for StreamLimits {
+    fn default() -> Self {
+        Self {
+            max_connections_per_ip: DEFAULT_MAX_CONNECTIONS_PER_IP,
+            max_in_flight_per_connection: DEFAULT_MAX_IN_FLIGHT_PER_CONNECTION,
+            read_header_timeout: DEFAULT_READ_HEADER_TIMEOUT,
+            idle_timeout: DEFAULT_IDLE_TIMEOUT,
+        }
+    }
+}
+
+/// Counters for the number of times each limit was enforced.
+#[derive(Debug, Default)]
+pub struct StreamLimitCounters {
+    pub accepted: AtomicU64,
+    pub rejected_per_ip: AtomicU64,
+    pub header_timeouts: AtomicU64,
+    pub idle_terminations: AtomicU64,
+    pub in_flight_throttled: AtomicU64,
+}
+
+/// A point-in-time copy of the `StreamLimitCounters`.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct StreamLimitStats {
+    pub accepted: u64,
+    pub rejected_per_ip: u64,
+    pub header_timeouts: u64,
+    pub idle_terminations: u64,
+    pub in_flight_throttled: u64,
+}
+
+impl StreamLimitCounters {
+    #[inline]
+    pub fn snapshot(&self) -> StreamLimitStats {
+        StreamLimitStats {
+            accepted: self.accepted.load(Ordering::Relaxed),
+            rejected_per_ip: self.rejected_per_ip.load(Ordering::Relaxed),
+            header_timeouts: self.header_timeouts.load(Ordering::Relaxed),
+            idle_terminations: self.idle_terminations.load(Ordering::Relaxed),
+            in_flight_throttled: self.in_flight_throttled.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// Shared by every connection accepted on a stream listener. Tracks the number of connections
+/// per IP address and the counters for each limit.
+#[derive(Debug)]
+pub struct StreamGuard {
+    limits: StreamLimits,
+    connections: Mutex<HashMap<IpAddr, usize>>,
+    counters: StreamLimitCounters,
+}
+
+impl StreamGuard {
+    #[inline]
+    pub fn new(limits: StreamLimits) -> Arc<Self> {
+        Arc::new(Self {
+            limits,
+            connections: Mutex::new(HashMap::new()),
+            counters: StreamLimitCounters::default(),
+        })
+    }
+
+    #[inline]
+    pub fn limits(&self) -> &StreamLimits { &self.limits }
+
+    #[inline]
+    pub fn stats(&self) -> StreamLimitStats { self.counters.snapshot() }
+
+    /// Called when a new connection is accepted. If the peer already has the maximum number of
+    /// connections open, an error is returned and the caller should close the connection
+    /// immediately. Otherwise, the returned permit must be held for the lifetime of the
+    /// connection.
+    pub fn admit(self: &Arc<Self>, peer: IpAddr) -> Result<ConnectionPermit, StreamLimitError> {
+        let mut w_connections = self.connections.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let count = w_connections.entry(peer).or_insert(0);
+        if *count >= self.limits.max_connections_per_ip {
+            drop(w_connections);
+            self.counters.rejected_per_ip.fetch_add(1, Ordering::Relaxed);
+            return Err(StreamLimitError::TooManyConnections(peer));
+        }
+        *count += 1;
+        drop(w_connections);
+        self.counters.accepted.fetch_add(1, Ordering::Relaxed);
+        Ok(ConnectionPermit {
+            guard: self.clone(),
+            peer,
+            in_flight: Arc::new(AtomicUsize::new(0)),
+        })
+    }
+
+    /// The number of connections currently open from the peer.
+    pub fn connection_count(&self, peer: &IpAddr) -> usize {
+        let r_connections = self.connections.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let count = r_connections.get(peer).copied().unwrap_or(0);
+        drop(r_connections);
+        count
+    }
+
+    fn release(&self, peer: IpAddr) {
+        let mut w_connections = self.connections.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        if let Entry::Occupied(mut entry) = w_connections.entry(peer) {
+            *entry.get_mut() -= 1;
+            if *entry.get() == 0 {
+                entry.remove();
+            }
+        }
+        drop(w_connections);
+    }
+}
+
+/// Held by a single accepted connection. Dropping it frees up the peer's connection slot.
+#[derive(Debug)]
+pub struct ConnectionPermit {
+    guard: Arc<StreamGuard>,
+    peer: IpAddr,
+    in_flight: Arc<AtomicUsize>,
+}
+
+impl ConnectionPermit {
+    #[inline]
+    pub fn peer(&self) -> IpAddr { self.peer }
+
+    #[inline]
+    pub fn in_flight(&self) -> usize { self.in_flight.load(Ordering::Acquire) }
+
+    /// Reserves a slot for a query that was just read from the connection. If the connection is
+    /// already at the in-flight limit, `None` is returned and the caller should stop reading
+    /// from the connection until an outstanding query completes.
+    pub fn start_query(&self) -> Option<InFlightQuery> {
+        let limit = self.guard.limits.max_in_flight_per_connection;
+        let reserved = self.in_flight.fetch_update(Ordering::AcqRel, Ordering::Acquire, |in_flight| {
+            if in_flight >= limit {
+                None
+            } else {
+                Some(in_flight + 1)
+            }
+        });
+        match reserved {
+            Ok(_) => Some(InFlightQuery { in_flight: self.in_flight.clone() }),
+            Err(_) => {
+                self.guard.counters.in_flight_throttled.fetch_add(1, Ordering::Relaxed);
+                None
+            },
+        }
+    }
+
+    /// Reads a single length-prefixed DNS message from the stream, enforcing the idle timeout
+    /// while waiting for the first octet and the header timeout for the rest of the message.
+    pub async fn read_message<R: AsyncRead + Unpin>(&self, stream: &mut R) -> Result<Vec<u8>, StreamLimitError> {
+        let limits = &self.guard.limits;
+        let mut length_bytes = [0_u8; 2];
+
+        // While there are queries in flight, the connection is not idle. The caller is expected
+        // to not call this while at the in-flight limit.
+        let first_octet = if self.in_flight() == 0 {
+            match timeout(limits.idle_timeout, stream.read_exact(&mut length_bytes[..1])).await {
+                Ok(result) => result,
+                Err(_) => {
+                    self.guard.counters.idle_terminations.fetch_add(1, Ordering::Relaxed);
+                    return Err(StreamLimitError::IdleTimeout);
+                },
+            }
+        } else {
+            stream.read_exact(&mut length_bytes[..1]).await
+        };
+        first_octet?;
+
+        let read_rest = async {
+            stream.read_exact(&mut length_bytes[1..]).await?;
+            let message_length = u16::from_be_bytes(length_bytes) as usize;
+            let mut message = vec![0; message_length];
+            stream.read_exact(&mut message).await?;
+            Ok::<_, std::io::Error>(message)
+        };
+        match timeout(limits.read_header_timeout, read_rest).await {
+            Ok(message) => Ok(message?),
+            Err(_) => {
+                self.guard.counters.header_timeouts.fetch_add(1, Ordering::Relaxed);
+                Err(StreamLimitError::ReadTimeout)
+            },
+        }
+    }
+}
+
+impl Drop for ConnectionPermit {
+    fn drop(&mut self) {
+        self.guard.release(self.peer);
+    }
+}
+
+/// Held while a query read from a connection is being answered.
+#[derive(Debug)]
+pub struct InFlightQuery {
+    in_flight: Arc<AtomicUsize>,
+}
+
+impl Drop for InFlightQuery {
+    fn drop(&mut self) {
+        self.in_flight.fetch_sub(1, Ordering::AcqRel);
+    }
+}
+
+#[cfg(test)]
+mod stream_limits_tests {
+    use std::{net::{IpAddr, Ipv4Addr}, time::Duration};
+
+    use crate::errors::StreamLimitError;
+
+    use super::{StreamGuard, StreamLimits};
+
+    const PEER: IpAddr = IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1));
+
+    fn limits() -> StreamLimits {
+        StreamLimits {
+            max_connections_per_ip: 2,
+            max_in_flight_per_connection: 1,
+            read_header_timeout: Duration::from_millis(50),
+            idle_timeout: Duration::from_millis(50),
+        }
+    }
+
+    #[test]
+    fn per_ip_connection_cap() {
+        let guard = StreamGuard::new(limits());
+        let permit_1 = guard.admit(PEER).unwrap();
+        let _permit_2 = guard.admit(PEER).unwrap();
+        assert_eq!(guard.admit(PEER).unwrap_err(), StreamLimitError::TooManyConnections(PEER));
+        drop(permit_1);
+        assert!(guard.admit(PEER).is_ok());
+        assert_eq!(guard.stats().rejected_per_ip, 1);
+        assert_eq!(guard.stats().accepted, 3);
+    }
+
+    #[test]
+    fn in_flight_cap() {
+        let guard = StreamGuard::new(limits());
+        let permit = guard.admit(PEER).unwrap();
+        let query = permit.start_query().unwrap();
+        assert!(permit.start_query().is_none());
+        drop(query);
+        assert!(permit.start_query().is_some());
+        assert_eq!(guard.stats().in_flight_throttled, 1);
+    }
+
+    #[tokio::test]
+    async fn slow_header_times_out() {
+        let guard = StreamGuard::new(limits());
+        let permit = guard.admit(PEER).unwrap();
+        let (mut client, mut server) = tokio::io::duplex(64);
+        // Only send the first byte of the length, then stall.
+        tokio::io::AsyncWriteExt::write_all(&mut client, &[0]).await.unwrap();
+        assert_eq!(permit.read_message(&mut server).await.unwrap_err(), StreamLimitError::ReadTimeout);
+        assert_eq!(guard.stats().header_timeouts, 1);
+    }
+
+    #[tokio::test]
+    async fn idle_connection_terminated() {
+        let guard = StreamGuard::new(limits());
+        let permit = guard.admit(PEER).unwrap();
+        let (_client, mut server) = tokio::io::duplex(64);
+        assert_eq!(permit.read_message(&mut server).await.unwrap_err(), StreamLimitError::IdleTimeout);
+        assert_eq!(guard.stats().idle_terminations, 1);
+    }
+}
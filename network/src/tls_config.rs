@@ -0,0 +1,263 @@
+use std::{fmt::Debug, hash::{Hash, Hasher}, io, mem, path::Path, sync::Arc};
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use rustls::{client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier}, crypto::{ring, verify_tls12_signature, verify_tls13_signature, CryptoProvider}, pki_types::{pem::PemObject, CertificateDer, ServerName, UnixTime}, CertificateError, ClientConfig, DigitallySignedStruct, RootCertStore, SignatureScheme};
+use rustls_platform_verifier::BuilderVerifierExt;
+
+use crate::tls_diagnostics::CertificateSummary;
+
+// Application-Layer Protocol Negotiation identifiers used by the encrypted transports.
+/// https://datatracker.ietf.org/doc/html/rfc9461#section-4.1
+pub const DOT_ALPN: &[u8] = b"dot";
+/// https://datatracker.ietf.org/doc/html/rfc9250#section-4.1.1
+#[cfg(feature = "quic")]
+pub const DOQ_ALPN: &[u8] = b"doq";
+#[cfg(feature = "https")]
+pub const H2_ALPN: &[u8] = b"h2";
+#[cfg(all(feature = "quic", feature = "https"))]
+pub const H3_ALPN: &[u8] = b"h3";
+
+/// How the certificate presented by an upstream is verified.
+#[derive(Debug, Clone, Default)]
+pub enum TlsVerification {
+    /// Verify the certificate against the platform's trust store.
+    #[default]
+    Platform,
+    /// Verify the certificate against these roots only. This is meant for private deployments
+    /// whose servers use certificates issued by an internal certificate authority.
+    CustomRoots(Arc<RootCertStore>),
+    /// Accept the certificate if the SHA-256 digest of its SubjectPublicKeyInfo is one of these.
+    /// Neither the name nor the chain is verified, so this can authenticate upstreams that are
+    /// only known by their address.
+    ///
+    /// https://datatracker.ietf.org/doc/html/rfc7858#section-4.2
+    SpkiPins(Vec<[u8; 32]>),
+    /// Accept any certificate, like `Insecure`, but fall back to an unencrypted connection if the
+    /// encrypted one cannot be established. This protects against passive observers only.
+    ///
+    /// https://datatracker.ietf.org/doc/html/rfc8310#section-5
+    Opportunistic,
+    /// Accept any certificate. Anyone on the path to the upstream can impersonate it, so this
+    /// must only be used in lab environments.
+    Insecure,
+}
+
+impl TlsVerification {
+    /// Loads the roots from a file of PEM encoded certificates.
+    pub fn custom_roots_from_pem_file(path: impl AsRef<Path>) -> io::Result<Self> {
+        let mut roots = RootCertStore::empty();
+        for certificate in CertificateDer::pem_file_iter(path).map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))? {
+            let certificate = certificate.map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))?;
+            roots.add(certificate).map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))?;
+        }
+        if roots.is_empty() {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "no certificates found"));
+        }
+        Ok(Self::CustomRoots(Arc::new(roots)))
+    }
+
+    /// Reads the pins from their base64 form, as they are written in pin sets.
+    pub fn spki_pins_from_base64<'a>(pins: impl IntoIterator<Item = &'a str>) -> io::Result<Self> {
+        let pins = pins.into_iter()
+            .map(|pin| {
+                let digest = STANDARD.decode(pin).map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))?;
+                <[u8; 32]>::try_from(digest).map_err(|digest| io::Error::new(io::ErrorKind::InvalidData, format!("a SHA-256 pin is 32 bytes long but '{pin}' is {}", digest.len())))
+            })
+            .collect::<io::Result<Vec<_>>>()?;
+        if pins.is_empty() {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "no pins found"));
+        }
+        Ok(Self::SpkiPins(pins))
+    }
+
+    #[inline]
+    pub fn is_insecure(&self) -> bool {
+        matches!(self, Self::Insecure)
+    }
+
+    /// Whether the server is authenticated at all. Insecure and opportunistic upstreams can be
+    /// impersonated.
+    #[inline]
+    pub fn is_authenticated(&self) -> bool {
+        !matches!(self, Self::Insecure | Self::Opportunistic)
+    }
+
+    #[inline]
+    pub fn is_opportunistic(&self) -> bool {
+        matches!(self, Self::Opportunistic)
+    }
+}
+
+impl PartialEq for TlsVerification {
+    /// Custom roots are only equal if they are the same store.
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Self::Platform, Self::Platform) => true,
+            (Self::CustomRoots(roots), Self::CustomRoots(other_roots)) => Arc::ptr_eq(roots, other_roots),
+            (Self::SpkiPins(pins), Self::SpkiPins(other_pins)) => pins == other_pins,
+            (Self::Opportunistic, Self::Opportunistic) => true,
+            (Self::Insecure, Self::Insecure) => true,
+            _ => false,
+        }
+    }
+}
+
+impl Eq for TlsVerification {}
+
+impl Hash for TlsVerification {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        mem::discriminant(self).hash(state);
+        match self {
+            Self::CustomRoots(roots) => Arc::as_ptr(roots).hash(state),
+            Self::SpkiPins(pins) => pins.hash(state),
+            _ => (),
+        }
+    }
+}
+
+/// Accepts every certificate but still checks that the handshake was signed by the key in the
+/// certificate that was presented.
+#[derive(Debug)]
+struct NoCertificateVerification(Arc<CryptoProvider>);
+
+impl ServerCertVerifier for NoCertificateVerification {
+    #[inline]
+    fn verify_server_cert(&self, _end_entity: &CertificateDer<'_>, _intermediates: &[CertificateDer<'_>], _server_name: &ServerName<'_>, _ocsp_response: &[u8], _now: UnixTime) -> Result<ServerCertVerified, rustls::Error> {
+        Ok(ServerCertVerified::assertion())
+    }
+
+    #[inline]
+    fn verify_tls12_signature(&self, message: &[u8], certificate: &CertificateDer<'_>, dss: &DigitallySignedStruct) -> Result<HandshakeSignatureValid, rustls::Error> {
+        verify_tls12_signature(message, certificate, dss, &self.0.signature_verification_algorithms)
+    }
+
+    #[inline]
+    fn verify_tls13_signature(&self, message: &[u8], certificate: &CertificateDer<'_>, dss: &DigitallySignedStruct) -> Result<HandshakeSignatureValid, rustls::Error> {
+        verify_tls13_signature(message, certificate, dss, &self.0.signature_verification_algorithms)
+    }
+
+    #[inline]
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.0.signature_verification_algorithms.supported_schemes()
+    }
+}
+
+/// Accepts the certificate if its public key is pinned. Only the server's own certificate is
+/// matched. The chain is not verified, so a pinned intermediate would not prove anything.
+#[derive(Debug)]
+struct SpkiPinVerification {
+    pins: Vec<[u8; 32]>,
+    provider: Arc<CryptoProvider>,
+}
+
+impl ServerCertVerifier for SpkiPinVerification {
+    #[inline]
+    fn verify_server_cert(&self, end_entity: &CertificateDer<'_>, _intermediates: &[CertificateDer<'_>], _server_name: &ServerName<'_>, _ocsp_response: &[u8], _now: UnixTime) -> Result<ServerCertVerified, rustls::Error> {
+        match CertificateSummary::from_der(end_entity) {
+            Some(certificate) if self.pins.contains(&certificate.spki_sha256) => Ok(ServerCertVerified::assertion()),
+            Some(_) => Err(rustls::Error::InvalidCertificate(CertificateError::ApplicationVerificationFailure)),
+            None => Err(rustls::Error::InvalidCertificate(CertificateError::BadEncoding)),
+        }
+    }
+
+    #[inline]
+    fn verify_tls12_signature(&self, message: &[u8], certificate: &CertificateDer<'_>, dss: &DigitallySignedStruct) -> Result<HandshakeSignatureValid, rustls::Error> {
+        verify_tls12_signature(message, certificate, dss, &self.provider.signature_verification_algorithms)
+    }
+
+    #[inline]
+    fn verify_tls13_signature(&self, message: &[u8], certificate: &CertificateDer<'_>, dss: &DigitallySignedStruct) -> Result<HandshakeSignatureValid, rustls::Error> {
+        verify_tls13_signature(message, certificate, dss, &self.provider.signature_verification_algorithms)
+    }
+
+    #[inline]
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.provider.signature_verification_algorithms.supported_schemes()
+    }
+}
+
+/// Builds a TLS client configuration that verifies servers using the platform's trust store and
+/// offers the ALPN identifiers in order of preference.
+#[cfg(any(feature = "quic", feature = "https"))]
+#[inline]
+pub(crate) fn client_config(alpn_protocols: &[&[u8]]) -> Result<Arc<ClientConfig>, rustls::Error> {
+    client_config_with_verification(alpn_protocols, &TlsVerification::Platform)
+}
+
+/// Builds a TLS client configuration that verifies servers as specified and offers the ALPN
+/// identifiers in order of preference.
+pub(crate) fn client_config_with_verification(alpn_protocols: &[&[u8]], verification: &TlsVerification) -> Result<Arc<ClientConfig>, rustls::Error> {
+    let provider = Arc::new(ring::default_provider());
+    let builder = ClientConfig::builder_with_provider(provider.clone())
+        .with_safe_default_protocol_versions()?;
+    let mut config = match verification {
+        TlsVerification::Platform => builder.with_platform_verifier()?.with_no_client_auth(),
+        TlsVerification::CustomRoots(roots) => builder.with_root_certificates(roots.clone()).with_no_client_auth(),
+        TlsVerification::SpkiPins(pins) => builder.dangerous()
+            .with_custom_certificate_verifier(Arc::new(SpkiPinVerification { pins: pins.clone(), provider }))
+            .with_no_client_auth(),
+        // Warned about when the upstream is configured.
+        TlsVerification::Opportunistic => builder.dangerous()
+            .with_custom_certificate_verifier(Arc::new(NoCertificateVerification(provider)))
+            .with_no_client_auth(),
+        TlsVerification::Insecure => {
+            println!("WARNING: TLS certificate verification is DISABLED. Upstreams using this configuration can be impersonated by anyone on the network path.");
+            builder.dangerous()
+                .with_custom_certificate_verifier(Arc::new(NoCertificateVerification(provider)))
+                .with_no_client_auth()
+        },
+    };
+    config.alpn_protocols = alpn_protocols.iter().map(|alpn| alpn.to_vec()).collect();
+    Ok(Arc::new(config))
+}
+
+#[cfg(test)]
+mod tls_config_tests {
+    use std::sync::Arc;
+
+    use base64::{engine::general_purpose::STANDARD, Engine};
+    use rustls::{client::danger::ServerCertVerifier, crypto::ring, pki_types::{CertificateDer, ServerName, UnixTime}, CertificateError};
+
+    use super::{SpkiPinVerification, TlsVerification};
+
+    /// A self-signed certificate for "dns.example".
+    const CERTIFICATE: &str = "MIIBmDCCAT+gAwIBAgIUR2S2aDjXtVikWb9YOqL7LlkKNyUwCgYIKoZIzj0EAwIwFjEUMBIGA1UEAwwLZG5zLmV4YW1wbGUwHhcNMjYxMDE2MTk1MjQ2WhcNMzYxMDEzMTk1MjQ2WjAWMRQwEgYDVQQDDAtkbnMuZXhhbXBsZTBZMBMGByqGSM49AgEGCCqGSM49AwEHA0IABKzBOvOi5ypHRqiYzaz0t5LwmSq74z4JbDTFDhO4KDW/TtfWesyD4iqm7ntUF1H0t3rOKMeEwtEzZl2V8vr/tw2jazBpMB0GA1UdDgQWBBTUts+2ZfSR+aNw8Fc4Js+9E1CU7TAfBgNVHSMEGDAWgBTUts+2ZfSR+aNw8Fc4Js+9E1CU7TAPBgNVHRMBAf8EBTADAQH/MBYGA1UdEQQPMA2CC2Rucy5leGFtcGxlMAoGCCqGSM49BAMCA0cAMEQCIFpewU9H3QvIlqvf8ObbGqQU+J5dCfyz8nLA1CY+g/PGAiBrGyoLLGXwTQKYo1qEYHMpxJx2KIsmCrEQOG4H7HE5Ig==";
+    const PIN: &str = "z8vMwVVVHwoqXJrg8HWKiCAWkI6VsSLe8Ugp2g2Qbug=";
+    const OTHER_PIN: &str = "AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA=";
+
+    fn verify(pins: &[&str]) -> Result<(), rustls::Error> {
+        let TlsVerification::SpkiPins(pins) = TlsVerification::spki_pins_from_base64(pins.iter().copied()).unwrap() else {
+            panic!("the pins were not read as SPKI pins");
+        };
+        let verifier = SpkiPinVerification { pins, provider: Arc::new(ring::default_provider()) };
+        let certificate = CertificateDer::from(STANDARD.decode(CERTIFICATE).unwrap());
+        // The pin is all that is checked, so any name is accepted.
+        let server_name = ServerName::try_from("192.0.2.1").unwrap();
+        verifier.verify_server_cert(&certificate, &[], &server_name, &[], UnixTime::now()).map(|_| ())
+    }
+
+    #[test]
+    fn certificates_are_accepted_if_their_key_is_pinned() {
+        assert!(verify(&[PIN]).is_ok());
+        assert!(verify(&[OTHER_PIN, PIN]).is_ok());
+        assert_eq!(verify(&[OTHER_PIN]), Err(rustls::Error::InvalidCertificate(CertificateError::ApplicationVerificationFailure)));
+    }
+
+    #[test]
+    fn malformed_pins_are_rejected() {
+        assert!(TlsVerification::spki_pins_from_base64([]).is_err());
+        assert!(TlsVerification::spki_pins_from_base64(["not base64!"]).is_err());
+        // A SHA-1 digest.
+        assert!(TlsVerification::spki_pins_from_base64(["2jmj7l5rSw0yVb/vlWAYkK/YBwk="]).is_err());
+    }
+
+    #[test]
+    fn only_opportunistic_and_insecure_upstreams_are_unauthenticated() {
+        assert!(TlsVerification::Platform.is_authenticated());
+        assert!(TlsVerification::spki_pins_from_base64([PIN]).unwrap().is_authenticated());
+        assert!(!TlsVerification::Opportunistic.is_authenticated());
+        assert!(TlsVerification::Opportunistic.is_opportunistic());
+        assert!(!TlsVerification::Opportunistic.is_insecure());
+        assert!(!TlsVerification::Insecure.is_authenticated());
+    }
+}
@@ -0,0 +1,212 @@
+use std::{collections::HashMap, fmt::Display, net::SocketAddr, sync::RwLock, time::{Duration, SystemTime, UNIX_EPOCH}};
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use lazy_static::lazy_static;
+#[cfg(feature = "quic")]
+use quinn::Connection;
+use ring::digest::{digest, SHA256};
+use rustls::ClientConnection;
+use rustls::pki_types::CertificateDer;
+
+use crate::async_query::QueryOpt;
+
+lazy_static! {
+    /// The most recent connection established to each encrypted upstream. Connections are owned by
+    /// the transports that use them (and by the shared QUIC pool), so the diagnostics for all of
+    /// them are kept in one place.
+    static ref TLS_CONNECTIONS: RwLock<HashMap<(SocketAddr, String, QueryOpt), TlsConnectionInfo>> = RwLock::new(HashMap::new());
+}
+
+/// A summary of one certificate presented by an upstream.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct CertificateSummary {
+    pub subject: String,
+    pub issuer: String,
+    pub not_before: SystemTime,
+    pub not_after: SystemTime,
+    /// The SHA-256 digest of the DER encoded SubjectPublicKeyInfo.
+    pub spki_sha256: [u8; 32],
+}
+
+impl CertificateSummary {
+    /// Parses a DER encoded X.509 certificate. Returns `None` if it cannot be parsed.
+    pub fn from_der(der: &[u8]) -> Option<Self> {
+        let (_, certificate) = x509_parser::parse_x509_certificate(der).ok()?;
+        let validity = certificate.validity();
+        let spki_sha256 = digest(&SHA256, certificate.public_key().raw).as_ref().try_into().ok()?;
+        Some(Self {
+            subject: certificate.subject().to_string(),
+            issuer: certificate.issuer().to_string(),
+            not_before: timestamp_to_system_time(validity.not_before.timestamp()),
+            not_after: timestamp_to_system_time(validity.not_after.timestamp()),
+            spki_sha256,
+        })
+    }
+
+    /// The SPKI digest in the base64 form used by pin sets.
+    ///
+    /// https://datatracker.ietf.org/doc/html/rfc7858#appendix-A
+    #[inline]
+    pub fn spki_pin(&self) -> String {
+        STANDARD.encode(self.spki_sha256)
+    }
+
+    /// Whether the certificate is within its validity window at this time.
+    #[inline]
+    pub fn is_valid_at(&self, time: SystemTime) -> bool {
+        (self.not_before <= time) && (time <= self.not_after)
+    }
+
+    /// The time left until the certificate expires, or `None` if it already has.
+    #[inline]
+    pub fn expires_in(&self, now: SystemTime) -> Option<Duration> {
+        self.not_after.duration_since(now).ok()
+    }
+}
+
+impl Display for CertificateSummary {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let not_before = self.not_before.duration_since(UNIX_EPOCH).map_or(0, |duration| duration.as_secs());
+        let not_after = self.not_after.duration_since(UNIX_EPOCH).map_or(0, |duration| duration.as_secs());
+        write!(f, "subject '{}', issuer '{}', valid {not_before}..{not_after}, pin-sha256 {}", self.subject, self.issuer, self.spki_pin())
+    }
+}
+
+#[inline]
+fn timestamp_to_system_time(timestamp: i64) -> SystemTime {
+    match u64::try_from(timestamp) {
+        Ok(seconds) => UNIX_EPOCH + Duration::from_secs(seconds),
+        Err(_) => UNIX_EPOCH - Duration::from_secs(timestamp.unsigned_abs()),
+    }
+}
+
+/// What was negotiated with an encrypted upstream and the certificates it presented.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct TlsConnectionInfo {
+    pub upstream_socket: SocketAddr,
+    pub server_name: String,
+    pub transport: QueryOpt,
+    /// For example, "TLSv1_3".
+    pub protocol_version: Option<String>,
+    /// The negotiated cipher suite. QUIC connections do not report it.
+    pub cipher_suite: Option<String>,
+    pub alpn: Option<Vec<u8>>,
+    /// The certificates presented by the upstream, starting with its own. Certificates that could
+    /// not be parsed are left out.
+    pub certificate_chain: Vec<CertificateSummary>,
+    pub established: SystemTime,
+}
+
+impl TlsConnectionInfo {
+    pub(crate) fn from_tls(upstream_socket: SocketAddr, server_name: &str, transport: QueryOpt, connection: &ClientConnection) -> Self {
+        Self {
+            upstream_socket,
+            server_name: server_name.to_string(),
+            transport,
+            protocol_version: connection.protocol_version().map(|version| format!("{version:?}")),
+            cipher_suite: connection.negotiated_cipher_suite().map(|suite| format!("{:?}", suite.suite())),
+            alpn: connection.alpn_protocol().map(|alpn| alpn.to_vec()),
+            certificate_chain: summarize_chain(connection.peer_certificates().unwrap_or_default()),
+            established: SystemTime::now(),
+        }
+    }
+
+    #[cfg(feature = "quic")]
+    pub(crate) fn from_quic(upstream_socket: SocketAddr, server_name: &str, transport: QueryOpt, connection: &Connection) -> Self {
+        let alpn = connection.handshake_data()
+            .and_then(|handshake_data| handshake_data.downcast::<quinn::crypto::rustls::HandshakeData>().ok())
+            .and_then(|handshake_data| handshake_data.protocol);
+        let certificates = connection.peer_identity()
+            .and_then(|identity| identity.downcast::<Vec<CertificateDer<'static>>>().ok())
+            .map(|certificates| *certificates)
+            .unwrap_or_default();
+        Self {
+            upstream_socket,
+            server_name: server_name.to_string(),
+            transport,
+            // QUIC always uses TLS 1.3.
+            // https://datatracker.ietf.org/doc/html/rfc9001#section-4.2
+            protocol_version: Some("TLSv1_3".to_string()),
+            cipher_suite: None,
+            alpn,
+            certificate_chain: summarize_chain(&certificates),
+            established: SystemTime::now(),
+        }
+    }
+
+    /// The earliest time at which a certificate in the chain expires.
+    #[inline]
+    pub fn not_after(&self) -> Option<SystemTime> {
+        self.certificate_chain.iter().map(|certificate| certificate.not_after).min()
+    }
+
+    /// Whether any certificate in the chain expires within `window` of `now` (or already has).
+    #[inline]
+    pub fn expires_within(&self, now: SystemTime, window: Duration) -> bool {
+        match self.not_after() {
+            Some(not_after) => not_after.duration_since(now).map_or(true, |remaining| remaining <= window),
+            None => false,
+        }
+    }
+
+    /// The SPKI pin of the upstream's own certificate.
+    #[inline]
+    pub fn spki_pin(&self) -> Option<String> {
+        self.certificate_chain.first().map(CertificateSummary::spki_pin)
+    }
+}
+
+#[inline]
+fn summarize_chain(certificates: &[CertificateDer<'_>]) -> Vec<CertificateSummary> {
+    certificates.iter()
+        .filter_map(|certificate| CertificateSummary::from_der(certificate.as_ref()))
+        .collect()
+}
+
+/// Records a newly established connection, replacing what was known about the previous one.
+pub(crate) fn record(info: TlsConnectionInfo) {
+    let mut w_connections = TLS_CONNECTIONS.write().unwrap_or_else(|poisoned| poisoned.into_inner());
+    w_connections.insert((info.upstream_socket, info.server_name.clone(), info.transport), info);
+    drop(w_connections);
+}
+
+/// The most recent connection to each encrypted upstream.
+pub(crate) fn connections() -> Vec<TlsConnectionInfo> {
+    let r_connections = TLS_CONNECTIONS.read().unwrap_or_else(|poisoned| poisoned.into_inner());
+    let connections = r_connections.values().cloned().collect();
+    drop(r_connections);
+    connections
+}
+
+#[cfg(test)]
+mod tls_diagnostics_tests {
+    use std::time::{Duration, UNIX_EPOCH};
+
+    use base64::{engine::general_purpose::STANDARD, Engine};
+
+    use super::CertificateSummary;
+
+    /// A self-signed P-256 certificate for "dns.example".
+    const CERTIFICATE: &str = "MIIBmDCCAT+gAwIBAgIUR2S2aDjXtVikWb9YOqL7LlkKNyUwCgYIKoZIzj0EAwIwFjEUMBIGA1UEAwwLZG5zLmV4YW1wbGUwHhcNMjYxMDE2MTk1MjQ2WhcNMzYxMDEzMTk1MjQ2WjAWMRQwEgYDVQQDDAtkbnMuZXhhbXBsZTBZMBMGByqGSM49AgEGCCqGSM49AwEHA0IABKzBOvOi5ypHRqiYzaz0t5LwmSq74z4JbDTFDhO4KDW/TtfWesyD4iqm7ntUF1H0t3rOKMeEwtEzZl2V8vr/tw2jazBpMB0GA1UdDgQWBBTUts+2ZfSR+aNw8Fc4Js+9E1CU7TAfBgNVHSMEGDAWgBTUts+2ZfSR+aNw8Fc4Js+9E1CU7TAPBgNVHRMBAf8EBTADAQH/MBYGA1UdEQQPMA2CC2Rucy5leGFtcGxlMAoGCCqGSM49BAMCA0cAMEQCIFpewU9H3QvIlqvf8ObbGqQU+J5dCfyz8nLA1CY+g/PGAiBrGyoLLGXwTQKYo1qEYHMpxJx2KIsmCrEQOG4H7HE5Ig==";
+
+    #[test]
+    fn summarizes_certificate() {
+        let der = STANDARD.decode(CERTIFICATE).unwrap();
+        let summary = CertificateSummary::from_der(&der).unwrap();
+        assert_eq!(summary.subject, "CN=dns.example");
+        assert_eq!(summary.issuer, "CN=dns.example");
+        assert_eq!(summary.not_before, UNIX_EPOCH + Duration::from_secs(1792180366));
+        assert_eq!(summary.not_after, UNIX_EPOCH + Duration::from_secs(2107540366));
+        assert_eq!(summary.spki_pin(), "z8vMwVVVHwoqXJrg8HWKiCAWkI6VsSLe8Ugp2g2Qbug=");
+
+        assert!(summary.is_valid_at(UNIX_EPOCH + Duration::from_secs(1800000000)));
+        assert!(!summary.is_valid_at(UNIX_EPOCH + Duration::from_secs(1700000000)));
+        assert_eq!(summary.expires_in(UNIX_EPOCH + Duration::from_secs(2107540000)), Some(Duration::from_secs(366)));
+        assert_eq!(summary.expires_in(UNIX_EPOCH + Duration::from_secs(2107550000)), None);
+    }
+
+    #[test]
+    fn rejects_garbage() {
+        assert_eq!(CertificateSummary::from_der(&[0x30, 0x03, 0x02, 0x01]), None);
+    }
+}
@@ -0,0 +1,249 @@
+use std::{fmt::Display, io, mem::{self, size_of}, net::SocketAddr, os::fd::{AsFd, AsRawFd}, ptr};
+
+use libc::{c_int, c_void, iovec, msghdr, socklen_t};
+use socket2::SockRef;
+use tokio::{io::Interest, net::{self, TcpStream}};
+
+/// The Explicit Congestion Notification codepoint carried in the two low bits of the IPv4 TOS
+/// field or IPv6 traffic class (RFC 3168).
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Ecn {
+    /// The transport is not ECN capable.
+    #[default]
+    NotEct,
+    /// ECN capable transport, codepoint 1.
+    Ect1,
+    /// ECN capable transport, codepoint 0.
+    Ect0,
+    /// Congestion was experienced by a router along the path.
+    Ce,
+}
+
+impl Ecn {
+    #[inline]
+    pub const fn code(&self) -> u8 {
+        match self {
+            Self::NotEct => 0b00,
+            Self::Ect1   => 0b01,
+            Self::Ect0   => 0b10,
+            Self::Ce     => 0b11,
+        }
+    }
+
+    #[inline]
+    pub const fn from_code(code: u8) -> Self {
+        match code & 0b11 {
+            0b00 => Self::NotEct,
+            0b01 => Self::Ect1,
+            0b10 => Self::Ect0,
+            _    => Self::Ce,
+        }
+    }
+}
+
+impl Display for Ecn {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::NotEct => write!(f, "Not-ECT"),
+            Self::Ect1   => write!(f, "ECT(1)"),
+            Self::Ect0   => write!(f, "ECT(0)"),
+            Self::Ce     => write!(f, "CE"),
+        }
+    }
+}
+
+/// The DSCP and ECN bits to mark outgoing packets with. On IPv4 these are written to the TOS
+/// field and on IPv6 to the traffic class.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct TrafficClass {
+    dscp: u8,
+    ecn: Ecn,
+}
+
+impl TrafficClass {
+    /// Class selector 0, the default for best effort traffic.
+    pub const DSCP_DEFAULT: u8 = 0;
+    /// Class selector 2, recommended for operations and management traffic such as DNS
+    /// (RFC 4594).
+    pub const DSCP_CS2: u8 = 16;
+    /// Assured forwarding class 2 with low drop precedence, recommended for low latency data.
+    pub const DSCP_AF21: u8 = 18;
+    /// Expedited forwarding.
+    pub const DSCP_EF: u8 = 46;
+
+    /// Returns `None` if the DSCP does not fit in six bits.
+    #[inline]
+    pub const fn new(dscp: u8, ecn: Ecn) -> Option<Self> {
+        if dscp > 0b0011_1111 {
+            None
+        } else {
+            Some(Self { dscp, ecn })
+        }
+    }
+
+    #[inline]
+    pub const fn from_tos(tos: u8) -> Self {
+        Self { dscp: tos >> 2, ecn: Ecn::from_code(tos) }
+    }
+
+    #[inline]
+    pub const fn tos(&self) -> u8 {
+        (self.dscp << 2) | self.ecn.code()
+    }
+
+    #[inline]
+    pub const fn dscp(&self) -> u8 {
+        self.dscp
+    }
+
+    #[inline]
+    pub const fn ecn(&self) -> Ecn {
+        self.ecn
+    }
+
+    /// Marks every packet sent on the socket with this traffic class and asks the kernel to report
+    /// the traffic class of received packets.
+    pub fn apply(&self, socket: &impl AsFd, local_address: &SocketAddr) -> io::Result<()> {
+        let socket_ref = SockRef::from(socket);
+        match local_address {
+            SocketAddr::V4(_) => {
+                socket_ref.set_tos(self.tos() as u32)?;
+                socket_ref.set_recv_tos(true)?;
+            },
+            SocketAddr::V6(_) => {
+                let traffic_class = self.tos() as c_int;
+                // Safety: The option value is a valid `int` that outlives the call.
+                let result = unsafe { libc::setsockopt(
+                    socket.as_fd().as_raw_fd(),
+                    libc::IPPROTO_IPV6,
+                    libc::IPV6_TCLASS,
+                    ptr::addr_of!(traffic_class).cast::<c_void>(),
+                    size_of::<c_int>() as socklen_t,
+                ) };
+                if result != 0 {
+                    return Err(io::Error::last_os_error());
+                }
+                socket_ref.set_recv_tclass_v6(true)?;
+            },
+        }
+        Ok(())
+    }
+}
+
+impl Display for TrafficClass {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "DSCP {} {}", self.dscp, self.ecn)
+    }
+}
+
+/// Binds a UDP socket connected to the peer, marked with the traffic class if there is one.
+pub(crate) async fn connect_udp(peer: &SocketAddr, traffic_class: Option<TrafficClass>) -> io::Result<net::UdpSocket> {
+    let udp_socket = net::UdpSocket::bind("0.0.0.0:0").await?;
+    if let Some(traffic_class) = traffic_class {
+        traffic_class.apply(&udp_socket, &udp_socket.local_addr()?)?;
+    }
+    udp_socket.connect(peer).await?;
+    Ok(udp_socket)
+}
+
+/// Opens a TCP connection to the peer. The traffic class is set before connecting so that the
+/// handshake is marked too.
+pub(crate) async fn connect_tcp(peer: SocketAddr, traffic_class: Option<TrafficClass>) -> io::Result<TcpStream> {
+    let Some(traffic_class) = traffic_class else {
+        return TcpStream::connect(peer).await;
+    };
+    let tcp_socket = match peer {
+        SocketAddr::V4(_) => net::TcpSocket::new_v4()?,
+        SocketAddr::V6(_) => net::TcpSocket::new_v6()?,
+    };
+    traffic_class.apply(&tcp_socket, &peer)?;
+    tcp_socket.connect(peer).await
+}
+
+/// Receives a single datagram from a connected socket along with the traffic class it was marked
+/// with. The traffic class is only reported if the socket was set up with `TrafficClass::apply`
+/// and the kernel supports it.
+pub(crate) async fn recv_with_traffic_class(udp_socket: &net::UdpSocket, buffer: &mut [u8]) -> io::Result<(usize, Option<TrafficClass>)> {
+    let fd = udp_socket.as_raw_fd();
+    udp_socket.async_io(Interest::READABLE, || {
+        let mut iovec = iovec { iov_base: buffer.as_mut_ptr().cast(), iov_len: buffer.len() };
+        // Aligned for `cmsghdr` and large enough for either an IPv4 or IPv6 traffic class.
+        let mut control = [0_u64; 4];
+        // Safety: `msghdr` is plain old data for which all zeroes is valid.
+        let mut header: msghdr = unsafe { mem::zeroed() };
+        header.msg_iov = &mut iovec;
+        header.msg_iovlen = 1;
+        header.msg_control = control.as_mut_ptr().cast();
+        header.msg_controllen = mem::size_of_val(&control) as _;
+
+        // Safety: The header points to memory that outlives the call.
+        let received = unsafe { libc::recvmsg(fd, &mut header, libc::MSG_DONTWAIT) };
+        if received < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok((received as usize, received_traffic_class(&header)))
+    }).await
+}
+
+fn received_traffic_class(header: &msghdr) -> Option<TrafficClass> {
+    // Safety: The control buffer was filled in by the kernel and `msg_controllen` was updated to
+    // the length it used, so the `CMSG_*` macros stay within it.
+    unsafe {
+        let mut control_message = libc::CMSG_FIRSTHDR(header);
+        while !control_message.is_null() {
+            match ((*control_message).cmsg_level, (*control_message).cmsg_type) {
+                (libc::IPPROTO_IP, libc::IP_TOS) => {
+                    return Some(TrafficClass::from_tos(ptr::read_unaligned(libc::CMSG_DATA(control_message))));
+                },
+                (libc::IPPROTO_IPV6, libc::IPV6_TCLASS) => {
+                    let traffic_class = ptr::read_unaligned(libc::CMSG_DATA(control_message).cast::<c_int>());
+                    return Some(TrafficClass::from_tos(traffic_class as u8));
+                },
+                _ => (),
+            }
+            control_message = libc::CMSG_NXTHDR(header, control_message);
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod traffic_class_tests {
+    use std::{net::{IpAddr, Ipv4Addr, SocketAddr}, time::Duration};
+
+    use socket2::SockRef;
+    use tokio::{net::UdpSocket, time::timeout};
+
+    use super::{connect_udp, recv_with_traffic_class, Ecn, TrafficClass};
+
+    const LOCALHOST: SocketAddr = SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 0);
+
+    #[test]
+    fn tos_round_trip() {
+        for tos in 0..=u8::MAX {
+            assert_eq!(TrafficClass::from_tos(tos).tos(), tos);
+        }
+        let traffic_class = TrafficClass::new(TrafficClass::DSCP_EF, Ecn::Ect0).unwrap();
+        assert_eq!(traffic_class.tos(), 0xB8 | 0b10);
+        assert_eq!(traffic_class.dscp(), TrafficClass::DSCP_EF);
+        assert_eq!(traffic_class.ecn(), Ecn::Ect0);
+        assert_eq!(TrafficClass::new(64, Ecn::NotEct), None);
+    }
+
+    #[tokio::test]
+    async fn outgoing_datagrams_are_marked() {
+        let receiver = UdpSocket::bind(LOCALHOST).await.unwrap();
+        let receiver_address = receiver.local_addr().unwrap();
+        SockRef::from(&receiver).set_recv_tos(true).unwrap();
+
+        let traffic_class = TrafficClass::new(TrafficClass::DSCP_CS2, Ecn::Ect0).unwrap();
+        let sender = connect_udp(&receiver_address, Some(traffic_class)).await.unwrap();
+        assert_eq!(SockRef::from(&sender).tos().unwrap(), traffic_class.tos() as u32);
+        sender.send(b"marked").await.unwrap();
+
+        let mut buffer = [0_u8; 64];
+        let (length, received_class) = timeout(Duration::from_secs(1), recv_with_traffic_class(&receiver, &mut buffer)).await.unwrap().unwrap();
+        assert_eq!(&buffer[..length], b"marked");
+        assert_eq!(received_class, Some(traffic_class));
+    }
+}
@@ -0,0 +1,377 @@
+use std::{cmp::{max, min}, io, mem::{self, size_of}, net::SocketAddr, os::fd::{AsRawFd, RawFd}, ptr};
+
+use libc::{c_int, c_uint, c_void, iovec, mmsghdr, msghdr, sockaddr_storage, socklen_t};
+use socket2::SockAddr;
+use tokio::{io::Interest, net::UdpSocket};
+
+/// The most datagrams that are sent or received with a single system call. The message headers
+/// for a batch are built on the stack, so this also bounds the stack usage of each call.
+pub const MAX_BATCH_SIZE: usize = 64;
+pub const DEFAULT_BATCH_SIZE: usize = 32;
+/// Large enough for any DNS message sent over UDP.
+pub const DEFAULT_SLOT_SIZE: usize = 4096;
+/// The slot size needed to hold a fully coalesced GRO buffer.
+pub const GRO_SLOT_SIZE: usize = u16::MAX as usize;
+
+// These are defined in `linux/udp.h` but are not exported by libc for every target.
+const UDP_SEGMENT: c_int = 103;
+const UDP_GRO: c_int = 104;
+/// The kernel refuses to split a single send into more segments than this.
+const UDP_MAX_SEGMENTS: usize = 64;
+/// The largest payload that fits in a single UDP datagram over IPv6, which is the smaller limit.
+const MAX_GSO_PAYLOAD: usize = 65_527 - 40;
+
+/// The number of `u64` words needed to hold a single `int` control message. Using `u64` keeps the
+/// buffer aligned for `cmsghdr`.
+const CONTROL_WORDS: usize = 4;
+
+/// Reusable buffers for receiving a batch of datagrams with a single `recvmmsg` call.
+pub struct RecvBatch {
+    slot_size: usize,
+    buffers: Vec<u8>,
+    addresses: Vec<sockaddr_storage>,
+    controls: Vec<u64>,
+    received: Vec<ReceivedSlot>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct ReceivedSlot {
+    length: usize,
+    peer: Option<SocketAddr>,
+    /// Set if GRO coalesced multiple datagrams into this slot. Each datagram is this long except
+    /// for the last, which may be shorter.
+    segment_size: Option<usize>,
+}
+
+impl RecvBatch {
+    /// Creates buffers for up to `batch_size` datagrams (at most `MAX_BATCH_SIZE`) of up to
+    /// `slot_size` bytes each. Datagrams longer than `slot_size` are truncated. If GRO is enabled
+    /// on the socket, the slot size should be `GRO_SLOT_SIZE`.
+    pub fn new(batch_size: usize, slot_size: usize) -> Self {
+        let batch_size = max(1, min(batch_size, MAX_BATCH_SIZE));
+        let slot_size = max(1, slot_size);
+        Self {
+            slot_size,
+            buffers: vec![0; batch_size * slot_size],
+            // Safety: `sockaddr_storage` is plain old data for which all zeroes is valid.
+            addresses: vec![unsafe { mem::zeroed() }; batch_size],
+            controls: vec![0; batch_size * CONTROL_WORDS],
+            received: Vec::with_capacity(batch_size),
+        }
+    }
+
+    #[inline]
+    pub fn capacity(&self) -> usize {
+        self.addresses.len()
+    }
+
+    /// The number of buffers filled by the last receive. With GRO, a buffer may contain more than
+    /// one datagram.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.received.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.received.is_empty()
+    }
+
+    /// Iterates over each datagram received by the last receive along with the address that sent
+    /// it. Buffers coalesced by GRO are split back into their original datagrams.
+    pub fn iter(&self) -> impl Iterator<Item = (&[u8], SocketAddr)> + '_ {
+        self.received.iter()
+            .zip(self.buffers.chunks_exact(self.slot_size))
+            .filter_map(|(slot, buffer)| slot.peer.map(|peer| (slot, &buffer[..slot.length], peer)))
+            .flat_map(|(slot, data, peer)| data.chunks(slot.segment_size.unwrap_or(max(1, slot.length))).map(move |datagram| (datagram, peer)))
+    }
+}
+
+/// A UDP socket that can send and receive many datagrams per system call using `sendmmsg`,
+/// `recvmmsg`, and (where the kernel supports it) UDP generic segmentation offload.
+#[derive(Debug)]
+pub struct BatchUdpSocket {
+    socket: UdpSocket,
+    gso: bool,
+}
+
+impl BatchUdpSocket {
+    pub fn new(socket: UdpSocket) -> Self {
+        let gso = probe_gso(socket.as_raw_fd());
+        Self { socket, gso }
+    }
+
+    #[inline]
+    pub fn get_ref(&self) -> &UdpSocket {
+        &self.socket
+    }
+
+    #[inline]
+    pub fn into_inner(self) -> UdpSocket {
+        self.socket
+    }
+
+    /// Whether `send_segments` can hand the whole payload to the kernel at once. If not, it falls
+    /// back to `sendmmsg`.
+    #[inline]
+    pub fn supports_gso(&self) -> bool {
+        self.gso
+    }
+
+    /// Allows the kernel to coalesce datagrams from the same peer into a single receive buffer.
+    /// `RecvBatch` splits them back apart, but its slots must be large enough to hold the
+    /// coalesced buffers (see `GRO_SLOT_SIZE`).
+    pub fn enable_gro(&self) -> io::Result<()> {
+        let enable: c_int = 1;
+        // Safety: The option value is a valid `int` that outlives the call.
+        let result = unsafe { libc::setsockopt(
+            self.socket.as_raw_fd(),
+            libc::SOL_UDP,
+            UDP_GRO,
+            ptr::addr_of!(enable).cast::<c_void>(),
+            size_of::<c_int>() as socklen_t,
+        ) };
+        if result == 0 { Ok(()) } else { Err(io::Error::last_os_error()) }
+    }
+
+    /// Waits until at least one datagram is available and then receives as many as fit in the
+    /// batch. Returns the number of buffers that were filled.
+    #[inline]
+    pub async fn recv_batch(&self, batch: &mut RecvBatch) -> io::Result<usize> {
+        recv_batch(&self.socket, batch).await
+    }
+
+    /// Sends every datagram to its paired address, using as few system calls as possible. Returns
+    /// the number of datagrams sent, which is all of them unless an error occurs.
+    pub async fn send_batch(&self, datagrams: &[(&[u8], SocketAddr)]) -> io::Result<usize> {
+        let fd = self.socket.as_raw_fd();
+        let mut sent = 0;
+        while sent < datagrams.len() {
+            sent += self.socket.async_io(Interest::WRITABLE, || try_send_batch(fd, &datagrams[sent..])).await?;
+        }
+        Ok(sent)
+    }
+
+    /// Sends `payload` to `peer` as a series of datagrams that are each `segment_size` bytes long
+    /// (the last may be shorter). With GSO, the kernel does the splitting so the whole series goes
+    /// out in a single system call.
+    pub async fn send_segments(&self, payload: &[u8], segment_size: u16, peer: SocketAddr) -> io::Result<()> {
+        let segment_size = max(1, segment_size as usize);
+        if !self.gso || (payload.len() <= segment_size) {
+            let datagrams = payload.chunks(segment_size).map(|datagram| (datagram, peer)).collect::<Vec<_>>();
+            self.send_batch(&datagrams).await?;
+            return Ok(());
+        }
+
+        let fd = self.socket.as_raw_fd();
+        let max_segments = max(1, min(UDP_MAX_SEGMENTS, MAX_GSO_PAYLOAD / segment_size));
+        for chunk in payload.chunks(segment_size * max_segments) {
+            self.socket.async_io(Interest::WRITABLE, || try_send_segmented(fd, chunk, segment_size as u16, peer)).await?;
+        }
+        Ok(())
+    }
+}
+
+/// Same as `BatchUdpSocket::recv_batch` but for a socket that is shared with other tasks, such as
+/// the connected sockets that the UDP listeners read responses from.
+pub async fn recv_batch(socket: &UdpSocket, batch: &mut RecvBatch) -> io::Result<usize> {
+    let fd = socket.as_raw_fd();
+    socket.async_io(Interest::READABLE, || try_recv_batch(fd, batch)).await
+}
+
+fn probe_gso(fd: RawFd) -> bool {
+    let mut value: c_int = 0;
+    let mut length = size_of::<c_int>() as socklen_t;
+    // Safety: The value and length point to valid memory of the size given.
+    let result = unsafe { libc::getsockopt(fd, libc::SOL_UDP, UDP_SEGMENT, ptr::addr_of_mut!(value).cast::<c_void>(), &mut length) };
+    result == 0
+}
+
+fn try_recv_batch(fd: RawFd, batch: &mut RecvBatch) -> io::Result<usize> {
+    let slots = batch.capacity();
+    // Safety: These are plain old data for which all zeroes is valid. Null pointers are replaced
+    // before the kernel sees them.
+    let mut iovecs: [iovec; MAX_BATCH_SIZE] = unsafe { mem::zeroed() };
+    let mut headers: [mmsghdr; MAX_BATCH_SIZE] = unsafe { mem::zeroed() };
+
+    let buffers = batch.buffers.chunks_exact_mut(batch.slot_size);
+    let controls = batch.controls.chunks_exact_mut(CONTROL_WORDS);
+    for (index, ((buffer, address), control)) in buffers.zip(batch.addresses.iter_mut()).zip(controls).enumerate() {
+        iovecs[index] = iovec { iov_base: buffer.as_mut_ptr().cast(), iov_len: buffer.len() };
+        let header = &mut headers[index].msg_hdr;
+        header.msg_name = ptr::from_mut(address).cast();
+        header.msg_namelen = size_of::<sockaddr_storage>() as socklen_t;
+        header.msg_iov = ptr::addr_of_mut!(iovecs[index]);
+        header.msg_iovlen = 1;
+        header.msg_control = control.as_mut_ptr().cast();
+        header.msg_controllen = mem::size_of_val(control) as _;
+    }
+
+    // Safety: Every header points to buffers owned by `batch` (or the iovecs on this stack frame)
+    // that stay alive and unaliased for the duration of the call.
+    let received = unsafe { libc::recvmmsg(fd, headers.as_mut_ptr(), slots as c_uint, libc::MSG_DONTWAIT, ptr::null_mut()) };
+    if received < 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    batch.received.clear();
+    for (header, address) in headers.iter().zip(batch.addresses.iter()).take(received as usize) {
+        // Safety: The kernel filled in `msg_namelen` bytes of the address storage.
+        let peer = unsafe { SockAddr::new(*address, header.msg_hdr.msg_namelen) }.as_socket();
+        let length = min(header.msg_len as usize, batch.slot_size);
+        batch.received.push(ReceivedSlot { length, peer, segment_size: gro_segment_size(&header.msg_hdr) });
+    }
+    Ok(batch.received.len())
+}
+
+fn gro_segment_size(header: &msghdr) -> Option<usize> {
+    // Safety: The control buffer was filled in by the kernel and `msg_controllen` was updated to
+    // the length it used, so the `CMSG_*` macros stay within it.
+    unsafe {
+        let mut control_message = libc::CMSG_FIRSTHDR(header);
+        while !control_message.is_null() {
+            if ((*control_message).cmsg_level == libc::SOL_UDP) && ((*control_message).cmsg_type == UDP_GRO) {
+                let segment_size = ptr::read_unaligned(libc::CMSG_DATA(control_message).cast::<c_int>());
+                return usize::try_from(segment_size).ok().filter(|segment_size| *segment_size > 0);
+            }
+            control_message = libc::CMSG_NXTHDR(header, control_message);
+        }
+    }
+    None
+}
+
+fn try_send_batch(fd: RawFd, datagrams: &[(&[u8], SocketAddr)]) -> io::Result<usize> {
+    let count = min(datagrams.len(), MAX_BATCH_SIZE);
+    // Safety: See `try_recv_batch`.
+    let mut iovecs: [iovec; MAX_BATCH_SIZE] = unsafe { mem::zeroed() };
+    let mut headers: [mmsghdr; MAX_BATCH_SIZE] = unsafe { mem::zeroed() };
+    let mut addresses: [sockaddr_storage; MAX_BATCH_SIZE] = unsafe { mem::zeroed() };
+
+    for (index, (datagram, peer)) in datagrams.iter().take(count).enumerate() {
+        let peer = SockAddr::from(*peer);
+        let peer_length = peer.len();
+        addresses[index] = peer.as_storage();
+        // The kernel does not write through `iov_base` when sending.
+        iovecs[index] = iovec { iov_base: datagram.as_ptr().cast_mut().cast(), iov_len: datagram.len() };
+        let header = &mut headers[index].msg_hdr;
+        header.msg_name = ptr::addr_of_mut!(addresses[index]).cast();
+        header.msg_namelen = peer_length;
+        header.msg_iov = ptr::addr_of_mut!(iovecs[index]);
+        header.msg_iovlen = 1;
+    }
+
+    // Safety: Every header points to memory that outlives the call.
+    let sent = unsafe { libc::sendmmsg(fd, headers.as_mut_ptr(), count as c_uint, libc::MSG_DONTWAIT) };
+    if sent < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(sent as usize)
+}
+
+fn try_send_segmented(fd: RawFd, payload: &[u8], segment_size: u16, peer: SocketAddr) -> io::Result<()> {
+    let peer = SockAddr::from(peer);
+    let mut iovec = iovec { iov_base: payload.as_ptr().cast_mut().cast(), iov_len: payload.len() };
+    let mut control = [0_u64; CONTROL_WORDS];
+
+    // Safety: See `try_recv_batch`.
+    let mut header: msghdr = unsafe { mem::zeroed() };
+    header.msg_name = peer.as_ptr().cast_mut().cast();
+    header.msg_namelen = peer.len();
+    header.msg_iov = &mut iovec;
+    header.msg_iovlen = 1;
+    header.msg_control = control.as_mut_ptr().cast();
+    // Safety: `CMSG_SPACE` is a pure calculation. The control buffer is large enough for a
+    // `u16` control message and is aligned for `cmsghdr`.
+    unsafe {
+        header.msg_controllen = libc::CMSG_SPACE(size_of::<u16>() as c_uint) as _;
+        let control_message = libc::CMSG_FIRSTHDR(&header);
+        (*control_message).cmsg_level = libc::SOL_UDP;
+        (*control_message).cmsg_type = UDP_SEGMENT;
+        (*control_message).cmsg_len = libc::CMSG_LEN(size_of::<u16>() as c_uint) as _;
+        ptr::write_unaligned(libc::CMSG_DATA(control_message).cast::<u16>(), segment_size);
+    }
+
+    // Safety: The header points to memory that outlives the call.
+    let sent = unsafe { libc::sendmsg(fd, &header, libc::MSG_DONTWAIT) };
+    if sent < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod udp_batch_tests {
+    use std::{net::{IpAddr, Ipv4Addr, SocketAddr}, time::Duration};
+
+    use tokio::{net::UdpSocket, time::timeout};
+
+    use super::{BatchUdpSocket, RecvBatch, DEFAULT_BATCH_SIZE, DEFAULT_SLOT_SIZE, GRO_SLOT_SIZE};
+
+    const LOCALHOST: SocketAddr = SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 0);
+
+    async fn recv_all(socket: &BatchUdpSocket, batch: &mut RecvBatch, expected: usize) -> Vec<(Vec<u8>, SocketAddr)> {
+        let mut datagrams = Vec::new();
+        while datagrams.len() < expected {
+            timeout(Duration::from_secs(1), socket.recv_batch(batch)).await
+                .expect("Did not receive all of the datagrams in time.")
+                .unwrap();
+            datagrams.extend(batch.iter().map(|(datagram, peer)| (datagram.to_vec(), peer)));
+        }
+        datagrams
+    }
+
+    #[tokio::test]
+    async fn recv_batch_receives_every_datagram() {
+        let receiver = BatchUdpSocket::new(UdpSocket::bind(LOCALHOST).await.unwrap());
+        let sender = UdpSocket::bind(LOCALHOST).await.unwrap();
+        let receiver_address = receiver.get_ref().local_addr().unwrap();
+
+        for index in 0..10_u8 {
+            sender.send_to(&[index; 12], receiver_address).await.unwrap();
+        }
+
+        let mut batch = RecvBatch::new(DEFAULT_BATCH_SIZE, DEFAULT_SLOT_SIZE);
+        let datagrams = recv_all(&receiver, &mut batch, 10).await;
+        assert_eq!(datagrams.len(), 10);
+        for (index, (datagram, peer)) in datagrams.into_iter().enumerate() {
+            assert_eq!(datagram, vec![index as u8; 12]);
+            assert_eq!(peer, sender.local_addr().unwrap());
+        }
+    }
+
+    #[tokio::test]
+    async fn send_batch_sends_every_datagram() {
+        let sender = BatchUdpSocket::new(UdpSocket::bind(LOCALHOST).await.unwrap());
+        let receiver = UdpSocket::bind(LOCALHOST).await.unwrap();
+        let receiver_address = receiver.local_addr().unwrap();
+
+        let payloads = (0..100_u8).map(|index| vec![index; (index as usize) + 1]).collect::<Vec<_>>();
+        let datagrams = payloads.iter().map(|payload| (payload.as_slice(), receiver_address)).collect::<Vec<_>>();
+        assert_eq!(sender.send_batch(&datagrams).await.unwrap(), 100);
+
+        let mut buffer = [0_u8; 512];
+        for payload in &payloads {
+            let (length, peer) = timeout(Duration::from_secs(1), receiver.recv_from(&mut buffer)).await.unwrap().unwrap();
+            assert_eq!(&buffer[..length], payload.as_slice());
+            assert_eq!(peer, sender.get_ref().local_addr().unwrap());
+        }
+    }
+
+    #[tokio::test]
+    async fn send_segments_round_trip() {
+        let sender = BatchUdpSocket::new(UdpSocket::bind(LOCALHOST).await.unwrap());
+        let receiver = BatchUdpSocket::new(UdpSocket::bind(LOCALHOST).await.unwrap());
+        let receiver_address = receiver.get_ref().local_addr().unwrap();
+        // If the kernel does not support GRO, the datagrams simply arrive one per slot.
+        let _ = receiver.enable_gro();
+
+        let payload = (0..1000_u16).map(|index| index as u8).collect::<Vec<_>>();
+        sender.send_segments(&payload, 300, receiver_address).await.unwrap();
+
+        let mut batch = RecvBatch::new(DEFAULT_BATCH_SIZE, GRO_SLOT_SIZE);
+        let datagrams = recv_all(&receiver, &mut batch, 4).await;
+        let lengths = datagrams.iter().map(|(datagram, _)| datagram.len()).collect::<Vec<_>>();
+        assert_eq!(lengths, vec![300, 300, 300, 100]);
+        assert_eq!(datagrams.into_iter().flat_map(|(datagram, _)| datagram).collect::<Vec<_>>(), payload);
+    }
+}
@@ -0,0 +1,149 @@
+use std::sync::atomic::{AtomicBool, AtomicU16, AtomicU8, Ordering};
+
+use dns_lib::query::{edns::{limit_udp_payload_size, DEFAULT_EDNS_BUFFER_SIZE, MINIMUM_EDNS_BUFFER_SIZE}, message::Message};
+
+const DEFAULT_MISSING_RESPONSES_BEFORE_REDUCING: u8 = 2;
+const DEFAULT_MISSING_RESPONSES_BEFORE_TCP: u8 = 3;
+
+/// Controls the EDNS UDP payload size advertised to an upstream server. Each path starts at
+/// `initial_buffer_size` and is only lowered if responses go missing, which is what happens when
+/// a large response is fragmented and the fragments are dropped along the way.
+///
+/// https://www.dnsflagday.net/2020/
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct UdpSizeConfig {
+    /// The largest UDP payload size advertised on a path that has not had any problems.
+    pub initial_buffer_size: u16,
+    /// The payload size that a path is reduced to once responses go missing.
+    pub reduced_buffer_size: u16,
+    /// The number of consecutive UDP queries without a response before the path's payload size is
+    /// reduced.
+    pub missing_responses_before_reducing: u8,
+    /// The number of consecutive UDP queries without a response, after the payload size has been
+    /// reduced, before most queries to the path are sent over TCP instead. Some queries are still
+    /// sent over UDP so that the path can recover.
+    pub missing_responses_before_tcp: u8,
+}
+
+impl Default for UdpSizeConfig {
+    fn default() -> Self {
+        Self {
+            initial_buffer_size: DEFAULT_EDNS_BUFFER_SIZE,
+            reduced_buffer_size: MINIMUM_EDNS_BUFFER_SIZE,
+            missing_responses_before_reducing: DEFAULT_MISSING_RESPONSES_BEFORE_REDUCING,
+            missing_responses_before_tcp: DEFAULT_MISSING_RESPONSES_BEFORE_TCP,
+        }
+    }
+}
+
+/// What has been learned about the payload sizes that make it across the path to one upstream
+/// server. Sockets created by a `SocketManager` share this with the sockets that replace them.
+#[derive(Debug)]
+pub(crate) struct PathUdpSize {
+    config: UdpSizeConfig,
+    buffer_size: AtomicU16,
+    missing_responses: AtomicU8,
+    prefer_tcp: AtomicBool,
+}
+
+impl PathUdpSize {
+    #[inline]
+    pub fn new(config: UdpSizeConfig) -> Self {
+        Self {
+            config,
+            buffer_size: AtomicU16::new(config.initial_buffer_size),
+            missing_responses: AtomicU8::new(0),
+            prefer_tcp: AtomicBool::new(false),
+        }
+    }
+
+    #[inline]
+    pub fn config(&self) -> &UdpSizeConfig {
+        &self.config
+    }
+
+    #[inline]
+    pub fn buffer_size(&self) -> u16 {
+        self.buffer_size.load(Ordering::Acquire)
+    }
+
+    /// Whether UDP responses keep going missing even with the reduced payload size.
+    #[inline]
+    pub fn prefers_tcp(&self) -> bool {
+        self.prefer_tcp.load(Ordering::Acquire)
+    }
+
+    /// Lowers the payload size advertised by the query to what is known to work on this path.
+    #[inline]
+    pub fn limit(&self, query: &mut Message) {
+        limit_udp_payload_size(query, self.buffer_size());
+    }
+
+    #[inline]
+    pub fn record_response(&self) {
+        self.missing_responses.store(0, Ordering::Release);
+        self.prefer_tcp.store(false, Ordering::Release);
+    }
+
+    pub fn record_missing_response(&self) {
+        let missing_responses = self.missing_responses.fetch_add(1, Ordering::AcqRel).saturating_add(1);
+        let buffer_size = self.buffer_size();
+        if buffer_size > self.config.reduced_buffer_size {
+            if missing_responses >= self.config.missing_responses_before_reducing {
+                self.buffer_size.store(self.config.reduced_buffer_size, Ordering::Release);
+                self.missing_responses.store(0, Ordering::Release);
+            }
+        } else if missing_responses >= self.config.missing_responses_before_tcp {
+            self.prefer_tcp.store(true, Ordering::Release);
+        }
+    }
+}
+
+#[cfg(test)]
+mod udp_size_tests {
+    use dns_lib::{query::{edns::{set_udp_payload_size, udp_payload_size, DEFAULT_EDNS_BUFFER_SIZE, MINIMUM_EDNS_BUFFER_SIZE}, message::Message, question::Question}, resource_record::{rclass::RClass, rtype::RType}, types::c_domain_name::CDomainName};
+
+    use super::{PathUdpSize, UdpSizeConfig};
+
+    #[test]
+    fn missing_responses_reduce_then_prefer_tcp() {
+        let path = PathUdpSize::new(UdpSizeConfig::default());
+        assert_eq!(path.buffer_size(), DEFAULT_EDNS_BUFFER_SIZE);
+
+        path.record_missing_response();
+        assert_eq!(path.buffer_size(), DEFAULT_EDNS_BUFFER_SIZE);
+        path.record_missing_response();
+        assert_eq!(path.buffer_size(), MINIMUM_EDNS_BUFFER_SIZE);
+        assert!(!path.prefers_tcp());
+
+        // The reduced size is remembered even once responses arrive again.
+        path.record_response();
+        assert_eq!(path.buffer_size(), MINIMUM_EDNS_BUFFER_SIZE);
+
+        for _ in 0..3 {
+            path.record_missing_response();
+        }
+        assert!(path.prefers_tcp());
+        path.record_response();
+        assert!(!path.prefers_tcp());
+    }
+
+    #[test]
+    fn responses_reset_missing_count() {
+        let path = PathUdpSize::new(UdpSizeConfig::default());
+        for _ in 0..10 {
+            path.record_missing_response();
+            path.record_response();
+        }
+        assert_eq!(path.buffer_size(), DEFAULT_EDNS_BUFFER_SIZE);
+    }
+
+    #[test]
+    fn limit_lowers_advertised_size() {
+        let path = PathUdpSize::new(UdpSizeConfig::default());
+        let mut query = Message::from(Question::new(CDomainName::from_utf8("example.org.").unwrap(), RType::A, RClass::Internet));
+        set_udp_payload_size(&mut query, 4096);
+        path.limit(&mut query);
+        assert_eq!(udp_payload_size(&query), Some(DEFAULT_EDNS_BUFFER_SIZE));
+    }
+}